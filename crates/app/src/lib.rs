@@ -52,6 +52,15 @@ pub mod monitoring;
 /// Application plugin system
 pub mod plugin;
 
+/// Ordered startup of subsystems with readiness gates
+pub mod startup;
+
+/// Supervised background tasks
+pub mod supervisor;
+
+/// Periodic tasks of the host and plugins
+pub mod schedule;
+
 /// Re-exports
 #[doc = "Common types for convenience"]
 pub mod prelude {
@@ -0,0 +1,300 @@
+//! Host API versioning for plugins
+//!
+//! Every plugin declares the host API version it was built against
+//! (`api_version` in its metadata file, e.g. `"1.0"`). Before a plugin is
+//! registered, [`negotiate`] compares that version with [`HOST_API_VERSION`]:
+//!
+//! - the same major version and a minor version no newer than the host's is
+//!   compatible; plugins built against an older minor version are wrapped in
+//!   the [`CompatShim`]s covering the changes since,
+//! - anything else is rejected, since the plugin may rely on behaviour or
+//!   trait layouts the host does not provide.
+//!
+//! # History
+//!
+//! - `1.0`: initial plugin API.
+//! - `1.1`: the manager persists plugin state through
+//!   [`Plugin::get_state`] and [`Plugin::set_state`]. Plugins built for 1.0
+//!   did not implement them meaningfully, so their shim keeps the state on
+//!   the host side.
+//! - `1.2`: the manager hands plugins a [`HostContext`](super::HostContext)
+//!   through [`Plugin::attach`], to schedule periodic tasks with. Plugins
+//!   built for 1.1 keep the default, which ignores it, so their shim leaves
+//!   them as they are.
+
+use std::any::Any;
+use std::fmt;
+use std::path::PathBuf;
+use std::str::FromStr;
+use std::sync::Arc;
+
+use futures::future::BoxFuture;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use tokio::sync::RwLock;
+
+use super::{HostContext, Plugin, PluginMetadata, PluginState, PluginStatus};
+use crate::error::Result;
+
+/// Version of the plugin API implemented by this host
+pub const HOST_API_VERSION: ApiVersion = ApiVersion::new(1, 2);
+
+/// Version assumed for plugins that do not declare one, which predate the
+/// handshake
+pub const LEGACY_API_VERSION: ApiVersion = ApiVersion::new(1, 0);
+
+/// A plugin API version
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct ApiVersion {
+    /// Incremented for incompatible changes
+    pub major: u32,
+    /// Incremented for backwards-compatible additions
+    pub minor: u32,
+}
+
+impl ApiVersion {
+    /// Create a version
+    #[must_use]
+    pub const fn new(major: u32, minor: u32) -> Self {
+        Self { major, minor }
+    }
+}
+
+impl fmt::Display for ApiVersion {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}.{}", self.major, self.minor)
+    }
+}
+
+impl FromStr for ApiVersion {
+    type Err = String;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        let (major, minor) = s.split_once('.').unwrap_or((s, "0"));
+        let parse = |part: &str| {
+            part.trim()
+                .parse::<u32>()
+                .map_err(|_| format!("Invalid plugin API version '{s}': expected MAJOR.MINOR"))
+        };
+        Ok(Self::new(parse(major)?, parse(minor)?))
+    }
+}
+
+impl Serialize for ApiVersion {
+    fn serialize<S: Serializer>(&self, serializer: S) -> std::result::Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+impl<'de> Deserialize<'de> for ApiVersion {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> std::result::Result<Self, D::Error> {
+        String::deserialize(deserializer)?
+            .parse()
+            .map_err(serde::de::Error::custom)
+    }
+}
+
+/// Adapts plugins built against an older minor API version
+#[derive(Debug, Clone, Copy)]
+pub struct CompatShim {
+    /// Plugins built against this version or older need the shim
+    pub applies_to: ApiVersion,
+    /// What the shim adapts
+    pub description: &'static str,
+    /// Wraps the plugin
+    pub wrap: fn(Box<dyn Plugin>) -> Box<dyn Plugin>,
+}
+
+/// Shims for every minor version of the current major version, oldest first
+pub const COMPAT_SHIMS: &[CompatShim] = &[
+    CompatShim {
+        applies_to: ApiVersion::new(1, 0),
+        description: "host-side plugin state (added in 1.1)",
+        wrap: HostStateShim::wrap,
+    },
+    CompatShim {
+        applies_to: ApiVersion::new(1, 1),
+        description: "no host context (added in 1.2)",
+        wrap: unchanged,
+    },
+];
+
+/// Shim for 1.1 plugins: the default [`Plugin::attach`] already ignores the
+/// host context
+fn unchanged(plugin: Box<dyn Plugin>) -> Box<dyn Plugin> {
+    plugin
+}
+
+/// Outcome of negotiating a plugin's API version
+#[derive(Debug, Clone)]
+pub enum Negotiation {
+    /// The plugin targets the host's version
+    Native,
+    /// The plugin targets an older version and runs behind these shims
+    Shimmed(Vec<CompatShim>),
+    /// The plugin cannot be loaded
+    Rejected(String),
+}
+
+/// Decide whether a plugin built against `required` can run on this host
+#[must_use]
+pub fn negotiate(required: ApiVersion) -> Negotiation {
+    if required.major != HOST_API_VERSION.major {
+        return Negotiation::Rejected(format!(
+            "major version {} is not supported; this host implements {}.x",
+            required.major, HOST_API_VERSION.major
+        ));
+    }
+    if required > HOST_API_VERSION {
+        return Negotiation::Rejected("the plugin needs a newer host".to_string());
+    }
+    if required == HOST_API_VERSION {
+        return Negotiation::Native;
+    }
+
+    let shims: Vec<CompatShim> = COMPAT_SHIMS
+        .iter()
+        .filter(|shim| shim.applies_to.major == required.major && required <= shim.applies_to)
+        .copied()
+        .collect();
+    // Every minor version between the plugin's and the host's needs a shim
+    let covered = (required.minor..HOST_API_VERSION.minor)
+        .all(|minor| shims.iter().any(|shim| shim.applies_to.minor == minor));
+    if covered {
+        Negotiation::Shimmed(shims)
+    } else {
+        Negotiation::Rejected("no compatibility shim is available".to_string())
+    }
+}
+
+/// Result of checking one discovered plugin
+#[derive(Debug, Clone, Serialize)]
+pub struct CompatibilityReport {
+    /// Plugin name
+    pub plugin: String,
+    /// Metadata file the plugin was discovered from, if any
+    pub source: Option<PathBuf>,
+    /// API version the plugin was built against
+    pub required: ApiVersion,
+    /// API version of this host
+    pub available: ApiVersion,
+    /// Descriptions of the shims applied
+    pub shims: Vec<String>,
+    /// Why the plugin was rejected, if it was
+    pub rejected: Option<String>,
+}
+
+impl CompatibilityReport {
+    /// Whether the plugin was accepted
+    #[must_use]
+    pub fn is_compatible(&self) -> bool {
+        self.rejected.is_none()
+    }
+}
+
+impl fmt::Display for CompatibilityReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "plugin '{}' requires host API {}, available {}",
+            self.plugin, self.required, self.available
+        )?;
+        if let Some(source) = &self.source {
+            write!(f, " ({})", source.display())?;
+        }
+        match &self.rejected {
+            Some(reason) => write!(f, ": rejected, {reason}"),
+            None if self.shims.is_empty() => write!(f, ": compatible"),
+            None => write!(f, ": compatible with shims: {}", self.shims.join(", ")),
+        }
+    }
+}
+
+/// Shim for 1.0 plugins: keeps plugin state on the host side
+#[derive(Debug, Clone)]
+struct HostStateShim {
+    /// The wrapped plugin
+    inner: Arc<dyn Plugin>,
+    /// State kept on behalf of the plugin
+    state: Arc<RwLock<Option<PluginState>>>,
+}
+
+impl HostStateShim {
+    /// Wrap a 1.0 plugin
+    fn wrap(plugin: Box<dyn Plugin>) -> Box<dyn Plugin> {
+        Box::new(Self {
+            inner: Arc::from(plugin),
+            state: Arc::new(RwLock::new(None)),
+        })
+    }
+}
+
+impl Plugin for HostStateShim {
+    fn metadata(&self) -> &PluginMetadata {
+        self.inner.metadata()
+    }
+
+    fn initialize(&self) -> BoxFuture<'_, Result<()>> {
+        self.inner.initialize()
+    }
+
+    fn shutdown(&self) -> BoxFuture<'_, Result<()>> {
+        self.inner.shutdown()
+    }
+
+    fn get_state(&self) -> BoxFuture<'_, Result<Option<PluginState>>> {
+        Box::pin(async move { Ok(self.state.read().await.clone()) })
+    }
+
+    fn set_state(&self, state: PluginState) -> BoxFuture<'_, Result<()>> {
+        Box::pin(async move {
+            *self.state.write().await = Some(state);
+            Ok(())
+        })
+    }
+
+    fn attach(&self, host: HostContext) {
+        self.inner.attach(host);
+    }
+
+    fn get_status(&self) -> BoxFuture<'_, PluginStatus> {
+        self.inner.get_status()
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self.inner.as_any()
+    }
+
+    fn clone_box(&self) -> Box<dyn Plugin> {
+        Box::new(self.clone())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_negotiate() {
+        assert!(matches!(negotiate(HOST_API_VERSION), Negotiation::Native));
+        match negotiate(LEGACY_API_VERSION) {
+            Negotiation::Shimmed(shims) => assert_eq!(shims.len(), 2),
+            other => panic!("expected shims, got {other:?}"),
+        }
+        match negotiate(ApiVersion::new(1, 1)) {
+            Negotiation::Shimmed(shims) => assert_eq!(shims.len(), 1),
+            other => panic!("expected shims, got {other:?}"),
+        }
+        assert!(matches!(negotiate(ApiVersion::new(1, 9)), Negotiation::Rejected(_)));
+        assert!(matches!(negotiate(ApiVersion::new(2, 0)), Negotiation::Rejected(_)));
+        assert!(matches!(negotiate(ApiVersion::new(0, 3)), Negotiation::Rejected(_)));
+    }
+
+    #[test]
+    fn test_api_version_parsing() {
+        assert_eq!("1.0".parse::<ApiVersion>().unwrap(), ApiVersion::new(1, 0));
+        assert_eq!("2".parse::<ApiVersion>().unwrap(), ApiVersion::new(2, 0));
+        assert!("one.two".parse::<ApiVersion>().is_err());
+        let json = serde_json::to_string(&HOST_API_VERSION).unwrap();
+        assert_eq!(serde_json::from_str::<ApiVersion>(&json).unwrap(), HOST_API_VERSION);
+    }
+}
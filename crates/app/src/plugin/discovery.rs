@@ -8,7 +8,20 @@ use uuid::Uuid;
 use serde::{Deserialize, Serialize};
 use futures::future::BoxFuture;
 use std::any::Any;
+use log::warn;
 use crate::plugin::PluginState;
+use super::api::{self, ApiVersion, CompatibilityReport, Negotiation, HOST_API_VERSION, LEGACY_API_VERSION};
+
+/// A discovered plugin and the host API version it was built against
+#[derive(Debug)]
+pub struct DiscoveredPlugin {
+    /// The plugin
+    pub plugin: Box<dyn Plugin>,
+    /// Host API version the plugin was built against
+    pub api_version: ApiVersion,
+    /// Metadata file the plugin was discovered from, if any
+    pub source: Option<PathBuf>,
+}
 
 /// Plugin discovery strategy trait
 #[async_trait]
@@ -22,6 +35,22 @@ pub trait PluginDiscovery: Send + Sync {
     /// - Plugin validation fails
     async fn discover_plugins(&self, directory: &Path) -> Result<Vec<Box<dyn Plugin>>>;
     
+    /// Discover plugins along with the host API version each was built against
+    ///
+    /// The default assumes the plugins were built against this host, which
+    /// holds for discoveries that construct plugins in-process.
+    /// 
+    /// # Errors
+    /// Returns an error if discovery fails
+    async fn discover_with_api_versions(&self, directory: &Path) -> Result<Vec<DiscoveredPlugin>> {
+        Ok(self
+            .discover_plugins(directory)
+            .await?
+            .into_iter()
+            .map(|plugin| DiscoveredPlugin { plugin, api_version: HOST_API_VERSION, source: None })
+            .collect())
+    }
+    
     /// Load plugin metadata from a file
     /// 
     /// # Errors
@@ -78,6 +107,25 @@ impl FileSystemDiscovery {
         self.validation_rules.push(Box::new(rule));
     }
     
+    /// Read the `api_version` a metadata file declares
+    ///
+    /// Files without one predate the version handshake and are treated as
+    /// [`LEGACY_API_VERSION`].
+    fn load_api_version(path: &Path) -> Result<ApiVersion> {
+        #[derive(Deserialize)]
+        struct ApiDeclaration {
+            api_version: Option<ApiVersion>,
+        }
+        
+        let content = fs::read_to_string(path)?;
+        let declaration = if path.extension().and_then(|ext| ext.to_str()) == Some("json") {
+            serde_json::from_str::<ApiDeclaration>(&content)?
+        } else {
+            toml::from_str::<ApiDeclaration>(&content)?
+        };
+        Ok(declaration.api_version.unwrap_or(LEGACY_API_VERSION))
+    }
+    
     /// Check if file extension is supported
     fn is_supported_extension(&self, path: &Path) -> bool {
         path.extension()
@@ -89,7 +137,16 @@ impl FileSystemDiscovery {
 #[async_trait]
 impl PluginDiscovery for FileSystemDiscovery {
     async fn discover_plugins(&self, directory: &Path) -> Result<Vec<Box<dyn Plugin>>> {
-        let mut plugins: Vec<Box<dyn Plugin>> = Vec::new();
+        Ok(self
+            .discover_with_api_versions(directory)
+            .await?
+            .into_iter()
+            .map(|discovered| discovered.plugin)
+            .collect())
+    }
+    
+    async fn discover_with_api_versions(&self, directory: &Path) -> Result<Vec<DiscoveredPlugin>> {
+        let mut plugins = Vec::new();
         
         for entry in fs::read_dir(directory)? {
             let entry = entry?;
@@ -98,11 +155,12 @@ impl PluginDiscovery for FileSystemDiscovery {
             if path.is_file() && self.is_supported_extension(&path) {
                 let metadata = self.load_metadata(&path)?;
                 self.validate_plugin(&metadata)?;
+                let api_version = Self::load_api_version(&path)?;
                 
                 // Here we would actually load the plugin based on the metadata
                 // For now, we'll just create a placeholder plugin
                 let plugin: Box<dyn Plugin> = Box::new(PlaceholderPlugin { metadata });
-                plugins.push(plugin);
+                plugins.push(DiscoveredPlugin { plugin, api_version, source: Some(path) });
             }
         }
         
@@ -325,20 +383,47 @@ impl PluginLoader {
     }
     
     /// Load plugins from all registered directories
+    ///
+    /// Each plugin's host API version is negotiated before it is registered:
+    /// plugins built against an older compatible version are wrapped in
+    /// compatibility shims, and incompatible plugins are skipped. Returns a
+    /// report for every discovered plugin, including the rejected ones.
     /// 
     /// # Errors
     /// Returns an error if:
     /// - Any plugin directory cannot be read
     /// - Plugin discovery fails
     /// - Plugin registration fails
-    pub async fn load_all(&self) -> Result<()> {
+    pub async fn load_all(&self) -> Result<Vec<CompatibilityReport>> {
+        let mut reports = Vec::new();
         for directory in &self.directories {
-            let plugins = self.discovery.discover_plugins(directory).await?;
-            for plugin in plugins {
+            for discovered in self.discovery.discover_with_api_versions(directory).await? {
+                let mut report = CompatibilityReport {
+                    plugin: discovered.plugin.metadata().name.clone(),
+                    source: discovered.source,
+                    required: discovered.api_version,
+                    available: HOST_API_VERSION,
+                    shims: Vec::new(),
+                    rejected: None,
+                };
+                let plugin = match api::negotiate(discovered.api_version) {
+                    Negotiation::Native => discovered.plugin,
+                    Negotiation::Shimmed(shims) => {
+                        report.shims = shims.iter().map(|shim| shim.description.to_string()).collect();
+                        shims.iter().fold(discovered.plugin, |plugin, shim| (shim.wrap)(plugin))
+                    }
+                    Negotiation::Rejected(reason) => {
+                        report.rejected = Some(reason);
+                        warn!("Skipping incompatible {report}");
+                        reports.push(report);
+                        continue;
+                    }
+                };
                 self.manager.register_plugin(plugin).await?;
+                reports.push(report);
             }
         }
-        Ok(())
+        Ok(reports)
     }
 }
 
@@ -556,4 +641,44 @@ mod tests {
         // Load plugins
         loader.load_all().await.unwrap();
     }
-} 
\ No newline at end of file
+    
+    #[tokio::test]
+    async fn test_api_version_handshake() {
+        let temp_dir = TempDir::new().unwrap();
+        for (name, api_version) in [("native", Some("1.2")), ("legacy", None), ("future", Some("2.0"))] {
+            let mut value = serde_json::to_value(PluginMetadata {
+                id: Uuid::new_v4(),
+                name: name.to_string(),
+                version: "1.0.0".to_string(),
+                description: "Test plugin".to_string(),
+                author: "Test Author".to_string(),
+                dependencies: vec![],
+                capabilities: vec![],
+            }).unwrap();
+            if let Some(api_version) = api_version {
+                value["api_version"] = serde_json::json!(api_version);
+            }
+            fs::write(temp_dir.path().join(format!("{name}.json")), value.to_string()).unwrap();
+        }
+        
+        let manager = PluginManager::new();
+        let mut loader = PluginLoader::new(manager.clone(), Box::new(FileSystemDiscovery::default()));
+        loader.add_directory(temp_dir.path());
+        let mut reports = loader.load_all().await.unwrap();
+        reports.sort_by(|a, b| a.plugin.cmp(&b.plugin));
+        
+        let future = &reports[0];
+        assert_eq!(future.plugin, "future");
+        assert!(!future.is_compatible());
+        assert!(future.to_string().contains("requires host API 2.0, available 1.2"), "{future}");
+        let legacy = &reports[1];
+        assert!(legacy.is_compatible());
+        assert_eq!(legacy.required, ApiVersion::new(1, 0));
+        assert_eq!(legacy.shims.len(), 2);
+        let native = &reports[2];
+        assert!(native.is_compatible() && native.shims.is_empty());
+        
+        // Only the compatible plugins were registered
+        assert_eq!(manager.plugins.read().await.len(), 2);
+    }
+}
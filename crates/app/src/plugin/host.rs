@@ -0,0 +1,209 @@
+//! Services the host offers plugins
+//!
+//! When a [`PluginManager`](super::PluginManager) with a scheduler loads a
+//! plugin, it hands the plugin a [`HostContext`] through
+//! [`Plugin::attach`](super::Plugin::attach), before initializing it. The
+//! tasks a plugin schedules through the context are named
+//! `<plugin>.<name>`, owned by `plugin:<plugin>`, run under the host's
+//! supervisor next to the host's own tasks and are unscheduled when the
+//! plugin is unloaded.
+//!
+//! A manager with a key-value store also gives each plugin the namespace
+//! `plugin:<plugin>` of the store, for small pieces of state that should
+//! outlive the plugin, such as a cursor or the last item it has seen.
+
+use std::future::Future;
+use std::sync::Arc;
+
+use squirrel_core::kv::{KvStore, Namespace};
+
+use crate::schedule::{ScheduleError, TaskOptions, TaskScheduler, TaskStatus};
+use crate::supervisor::TaskResult;
+
+/// What a plugin can ask of the host
+#[derive(Debug, Clone)]
+pub struct HostContext {
+    /// Name of the plugin
+    plugin: String,
+    /// Schedules the plugin's tasks
+    scheduler: Arc<TaskScheduler>,
+    /// Keeps the plugin's key-value state, if the host has a store
+    store: Option<KvStore>,
+}
+
+impl HostContext {
+    /// Create the context of the plugin `plugin`
+    #[must_use]
+    pub fn new(plugin: impl Into<String>, scheduler: Arc<TaskScheduler>) -> Self {
+        Self {
+            plugin: plugin.into(),
+            scheduler,
+            store: None,
+        }
+    }
+
+    /// Give the plugin a namespace of `store`
+    #[must_use]
+    pub fn with_store(mut self, store: KvStore) -> Self {
+        self.store = Some(store);
+        self
+    }
+
+    /// Owner of the tasks the plugin `plugin` schedules
+    #[must_use]
+    pub fn owner_of(plugin: &str) -> String {
+        format!("plugin:{plugin}")
+    }
+
+    /// Name of the plugin
+    #[must_use]
+    pub fn plugin(&self) -> &str {
+        &self.plugin
+    }
+
+    /// Schedule `run` as the plugin's task `name`, returning the name it is
+    /// scheduled under, `<plugin>.<name>`
+    ///
+    /// A task scheduled under the name before is replaced.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the name is not valid or taken by another plugin
+    pub fn schedule<F, Fut>(&self, name: &str, options: TaskOptions, run: F) -> Result<String, ScheduleError>
+    where
+        F: Fn() -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = TaskResult> + Send + 'static,
+    {
+        let task = format!("{}.{name}", self.plugin);
+        self.scheduler
+            .register(&Self::owner_of(&self.plugin), &task, options, run)?;
+        Ok(task)
+    }
+
+    /// Unschedule the plugin's task `name`, returning whether there was one
+    #[must_use]
+    pub fn unschedule(&self, name: &str) -> bool {
+        let task = format!("{}.{name}", self.plugin);
+        let owned = self
+            .scheduler
+            .status(&task)
+            .is_some_and(|status| status.owner == Self::owner_of(&self.plugin));
+        owned && self.scheduler.unregister(&task)
+    }
+
+    /// State of the plugin's tasks
+    #[must_use]
+    pub fn scheduled(&self) -> Vec<TaskStatus> {
+        self.scheduler.tasks_of(&Self::owner_of(&self.plugin))
+    }
+
+    /// The plugin's namespace of the host's key-value store, `plugin:<plugin>`
+    ///
+    /// Returns `None` if the host has no store or the plugin's name is not
+    /// a valid namespace.
+    #[must_use]
+    pub fn store(&self) -> Option<Namespace> {
+        self.store
+            .as_ref()
+            .and_then(|store| store.namespace(&Self::owner_of(&self.plugin)).ok())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::any::Any;
+    use std::time::Duration;
+
+    use futures::future::BoxFuture;
+    use uuid::Uuid;
+
+    use crate::error::Result;
+    use crate::plugin::{Plugin, PluginManager, PluginMetadata, PluginState};
+    use crate::supervisor::Supervisor;
+
+    /// Schedules a task when it is attached
+    #[derive(Debug, Clone)]
+    struct TickerPlugin {
+        /// Metadata of the plugin
+        metadata: PluginMetadata,
+    }
+
+    impl Plugin for TickerPlugin {
+        fn metadata(&self) -> &PluginMetadata {
+            &self.metadata
+        }
+
+        fn initialize(&self) -> BoxFuture<'_, Result<()>> {
+            Box::pin(async { Ok(()) })
+        }
+
+        fn shutdown(&self) -> BoxFuture<'_, Result<()>> {
+            Box::pin(async { Ok(()) })
+        }
+
+        fn get_state(&self) -> BoxFuture<'_, Result<Option<PluginState>>> {
+            Box::pin(async { Ok(None) })
+        }
+
+        fn set_state(&self, _state: PluginState) -> BoxFuture<'_, Result<()>> {
+            Box::pin(async { Ok(()) })
+        }
+
+        fn attach(&self, host: HostContext) {
+            let options = TaskOptions::new(Duration::from_mins(1)).with_jitter(Duration::from_secs(5));
+            host.schedule("tick", options, || async { Ok(()) }).unwrap();
+            if let Some(state) = host.store() {
+                let attached = state.get::<u32>("attached").unwrap().unwrap_or(0);
+                state.set("attached", &(attached + 1), None).unwrap();
+            }
+        }
+
+        fn as_any(&self) -> &dyn Any {
+            self
+        }
+
+        fn clone_box(&self) -> Box<dyn Plugin> {
+            Box::new(self.clone())
+        }
+    }
+
+    #[tokio::test]
+    async fn test_plugin_tasks_live_as_long_as_the_plugin() {
+        let scheduler = Arc::new(TaskScheduler::new(Arc::new(Supervisor::new())));
+        let manager = PluginManager::new();
+        manager.set_scheduler(Some(scheduler.clone()));
+        let store = KvStore::in_memory();
+        manager.set_store(Some(store.clone()));
+        let metadata = PluginMetadata {
+            id: Uuid::new_v4(),
+            name: "ticker".to_string(),
+            version: "1.0.0".to_string(),
+            description: "Ticks".to_string(),
+            author: "Test Author".to_string(),
+            dependencies: vec![],
+            capabilities: vec![],
+        };
+        let id = metadata.id;
+        manager.register_plugin(Box::new(TickerPlugin { metadata })).await.unwrap();
+
+        manager.load_plugin(id).await.unwrap();
+        let tasks = scheduler.tasks();
+        assert_eq!(tasks.len(), 1);
+        assert_eq!((tasks[0].name.as_str(), tasks[0].owner.as_str()), ("ticker.tick", "plugin:ticker"));
+        assert_eq!(tasks[0].jitter_ms, 5000);
+        let host = HostContext::new("ticker", scheduler.clone());
+        assert_eq!(host.scheduled().len(), 1);
+        assert!(!HostContext::new("other", scheduler.clone()).unschedule("tick"));
+
+        manager.unload_plugin(id).await.unwrap();
+        assert!(scheduler.tasks().is_empty());
+        assert!(!host.unschedule("tick"));
+
+        // The plugin's state outlives it
+        manager.load_plugin(id).await.unwrap();
+        let state = store.namespace("plugin:ticker").unwrap();
+        assert_eq!(state.get::<u32>("attached").unwrap(), Some(2));
+    }
+}
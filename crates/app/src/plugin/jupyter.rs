@@ -0,0 +1,1136 @@
+//! Jupyter kernel bridge for the plugin system
+//!
+//! This module lets Squirrel act as a Jupyter-compatible execution backend.
+//! Registered commands and [`ToolPlugin`] tools are exposed to notebooks as
+//! magics (`%squirrel`, `%tool` and `%%tool`), messages are encoded with the
+//! Jupyter wire protocol (multipart frames signed with HMAC-SHA256), and a
+//! kernel spec can be installed so that Jupyter knows how to launch Squirrel.
+//!
+//! The bridge consumes decoded [`JupyterMessage`]s and returns the replies to
+//! publish on each channel. [`JupyterKernel`] drives it over the ZMQ sockets
+//! of a kernel connection file; the installed spec launches
+//! `squirrel jupyter kernel --connection-file <file>`, which runs one.
+
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU32, Ordering};
+use std::sync::Arc;
+
+use chrono::Utc;
+use clap::{Arg, Command as ClapCommand};
+use hmac::{Hmac, Mac};
+use log::{debug, warn};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use sha2::Sha256;
+use squirrel_commands::{Command, CommandError, CommandRegistry, CommandResult};
+use uuid::Uuid;
+use zeromq::{PubSocket, RepSocket, RouterSocket, Socket, SocketRecv, SocketSend, ZmqError, ZmqMessage};
+
+use super::ToolPlugin;
+use crate::error::{CoreError, Result};
+
+/// Delimiter separating routing identities from the signed message frames
+pub const DELIMITER: &[u8] = b"<IDS|MSG>";
+
+/// Jupyter messaging protocol version implemented by the bridge
+pub const PROTOCOL_VERSION: &str = "5.3";
+
+/// HMAC-SHA256 type used for message signatures
+type HmacSha256 = Hmac<Sha256>;
+
+/// Header of a Jupyter message
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct JupyterHeader {
+    /// Unique message identifier
+    pub msg_id: String,
+    /// Session identifier of the sender
+    pub session: String,
+    /// User name of the sender
+    pub username: String,
+    /// ISO 8601 timestamp of message creation
+    pub date: String,
+    /// Message type, e.g. `execute_request`
+    pub msg_type: String,
+    /// Messaging protocol version
+    pub version: String,
+}
+
+impl JupyterHeader {
+    /// Create a new header for the given message type and session
+    #[must_use]
+    pub fn new(msg_type: impl Into<String>, session: impl Into<String>) -> Self {
+        Self {
+            msg_id: Uuid::new_v4().to_string(),
+            session: session.into(),
+            username: "squirrel".to_string(),
+            date: Utc::now().to_rfc3339(),
+            msg_type: msg_type.into(),
+            version: PROTOCOL_VERSION.to_string(),
+        }
+    }
+}
+
+/// A decoded Jupyter message
+#[derive(Debug, Clone)]
+pub struct JupyterMessage {
+    /// ZMQ routing identities preceding the delimiter
+    pub identities: Vec<Vec<u8>>,
+    /// Message header
+    pub header: JupyterHeader,
+    /// Header of the message this one responds to
+    pub parent_header: Option<JupyterHeader>,
+    /// Free-form metadata
+    pub metadata: Value,
+    /// Message content
+    pub content: Value,
+}
+
+impl JupyterMessage {
+    /// Create a new message without a parent
+    #[must_use]
+    pub fn new(msg_type: impl Into<String>, session: impl Into<String>, content: Value) -> Self {
+        Self {
+            identities: Vec::new(),
+            header: JupyterHeader::new(msg_type, session),
+            parent_header: None,
+            metadata: json!({}),
+            content,
+        }
+    }
+
+    /// Create a message responding to this one
+    ///
+    /// The reply keeps the routing identities and session of the request and
+    /// records the request header as its parent.
+    #[must_use]
+    pub fn reply(&self, msg_type: impl Into<String>, content: Value) -> Self {
+        Self {
+            identities: self.identities.clone(),
+            header: JupyterHeader::new(msg_type, self.header.session.clone()),
+            parent_header: Some(self.header.clone()),
+            metadata: json!({}),
+            content,
+        }
+    }
+
+    /// Get the message type
+    #[must_use]
+    pub fn msg_type(&self) -> &str {
+        &self.header.msg_type
+    }
+}
+
+/// Signs and verifies messages on the wire
+#[derive(Debug, Clone)]
+pub struct MessageSigner {
+    /// HMAC key; an empty key disables signing
+    key: Vec<u8>,
+}
+
+impl MessageSigner {
+    /// Create a signer from the key in the kernel connection file
+    #[must_use]
+    pub fn new(key: impl AsRef<[u8]>) -> Self {
+        Self {
+            key: key.as_ref().to_vec(),
+        }
+    }
+
+    /// Compute the hex-encoded signature of the given frames
+    ///
+    /// # Errors
+    /// Returns an error if the HMAC cannot be initialized with the key
+    pub fn sign(&self, frames: &[&[u8]]) -> Result<String> {
+        if self.key.is_empty() {
+            return Ok(String::new());
+        }
+        let mut mac = self.mac()?;
+        for frame in frames {
+            mac.update(frame);
+        }
+        Ok(hex::encode(mac.finalize().into_bytes()))
+    }
+
+    /// Encode a message into wire frames
+    ///
+    /// # Errors
+    /// Returns an error if the message parts cannot be serialized
+    pub fn encode(&self, message: &JupyterMessage) -> Result<Vec<Vec<u8>>> {
+        let header = serde_json::to_vec(&message.header)?;
+        let parent = match &message.parent_header {
+            Some(parent) => serde_json::to_vec(parent)?,
+            None => b"{}".to_vec(),
+        };
+        let metadata = serde_json::to_vec(&message.metadata)?;
+        let content = serde_json::to_vec(&message.content)?;
+        let signature = self.sign(&[&header, &parent, &metadata, &content])?;
+
+        let mut frames = message.identities.clone();
+        frames.push(DELIMITER.to_vec());
+        frames.push(signature.into_bytes());
+        frames.extend([header, parent, metadata, content]);
+        Ok(frames)
+    }
+
+    /// Decode and verify a message from wire frames
+    ///
+    /// # Errors
+    /// Returns an error if the delimiter is missing, frames are missing,
+    /// the signature does not match, or the JSON parts are malformed
+    pub fn decode(&self, frames: &[Vec<u8>]) -> Result<JupyterMessage> {
+        let delimiter = frames
+            .iter()
+            .position(|frame| frame.as_slice() == DELIMITER)
+            .ok_or_else(|| CoreError::Plugin("Missing Jupyter message delimiter".to_string()))?;
+        let identities = frames.get(..delimiter).unwrap_or_default().to_vec();
+        let parts = frames.get(delimiter + 1..).unwrap_or_default();
+        let [signature, header, parent, metadata, content, ..] = parts else {
+            return Err(CoreError::Plugin(format!(
+                "Incomplete Jupyter message: expected 5 frames after delimiter, got {}",
+                parts.len()
+            )));
+        };
+
+        if !self.key.is_empty() {
+            let expected = hex::decode(signature)
+                .map_err(|e| CoreError::Plugin(format!("Malformed message signature: {e}")))?;
+            let mut mac = self.mac()?;
+            for frame in [header, parent, metadata, content] {
+                mac.update(frame);
+            }
+            mac.verify_slice(&expected)
+                .map_err(|_| CoreError::Plugin("Invalid Jupyter message signature".to_string()))?;
+        }
+
+        let parent_value: Value = serde_json::from_slice(parent)?;
+        let parent_header = match &parent_value {
+            Value::Object(map) if map.is_empty() => None,
+            _ => Some(serde_json::from_value(parent_value)?),
+        };
+
+        Ok(JupyterMessage {
+            identities,
+            header: serde_json::from_slice(header)?,
+            parent_header,
+            metadata: serde_json::from_slice(metadata)?,
+            content: serde_json::from_slice(content)?,
+        })
+    }
+
+    /// Create a keyed HMAC instance
+    fn mac(&self) -> Result<HmacSha256> {
+        HmacSha256::new_from_slice(&self.key)
+            .map_err(|e| CoreError::Plugin(format!("Invalid signing key: {e}")))
+    }
+}
+
+/// Kernel connection information written by Jupyter
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ConnectionInfo {
+    /// IP address to bind to
+    pub ip: String,
+    /// Transport, usually `tcp`
+    pub transport: String,
+    /// Shell channel port
+    pub shell_port: u16,
+    /// `IOPub` channel port
+    pub iopub_port: u16,
+    /// Stdin channel port
+    pub stdin_port: u16,
+    /// Control channel port
+    pub control_port: u16,
+    /// Heartbeat port
+    pub hb_port: u16,
+    /// Signing key
+    pub key: String,
+    /// Signature scheme, only `hmac-sha256` is supported
+    pub signature_scheme: String,
+    /// Name of the kernel that was launched
+    #[serde(default)]
+    pub kernel_name: Option<String>,
+}
+
+impl ConnectionInfo {
+    /// Load connection information from a connection file
+    ///
+    /// # Errors
+    /// Returns an error if the file cannot be read, parsed, or uses an
+    /// unsupported signature scheme
+    pub fn from_file(path: &Path) -> Result<Self> {
+        let raw = std::fs::read_to_string(path)?;
+        let info: Self = serde_json::from_str(&raw)?;
+        if !info.key.is_empty() && info.signature_scheme != "hmac-sha256" {
+            return Err(CoreError::Plugin(format!(
+                "Unsupported signature scheme: {}",
+                info.signature_scheme
+            )));
+        }
+        Ok(info)
+    }
+
+    /// Build the endpoint address for a port
+    #[must_use]
+    pub fn endpoint(&self, port: u16) -> String {
+        format!("{}://{}:{}", self.transport, self.ip, port)
+    }
+
+    /// Create a signer for this connection
+    #[must_use]
+    pub fn signer(&self) -> MessageSigner {
+        MessageSigner::new(self.key.as_bytes())
+    }
+}
+
+/// Channel on which a reply must be sent
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Channel {
+    /// Request/reply channel for execution
+    Shell,
+    /// Broadcast channel for outputs and status
+    IoPub,
+    /// Request/reply channel for shutdown and interrupts
+    Control,
+}
+
+/// A notebook magic understood by the bridge
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Magic {
+    /// `%squirrel <command> [args...]` runs a registered command
+    Command {
+        /// Command name
+        name: String,
+        /// Command arguments
+        args: Vec<String>,
+    },
+    /// `%tool <tool> [json]` or a `%%tool <tool>` cell runs a plugin tool
+    Tool {
+        /// Tool name
+        name: String,
+        /// Tool arguments
+        args: Value,
+    },
+}
+
+impl Magic {
+    /// Parse a notebook cell into a magic
+    ///
+    /// # Errors
+    /// Returns an error if the cell is not a supported magic or the tool
+    /// arguments are not valid JSON
+    pub fn parse(code: &str) -> Result<Self> {
+        let code = code.trim();
+        let (first_line, body) = code.split_once('\n').unwrap_or((code, ""));
+        let mut words = first_line.split_whitespace();
+        let magic = words.next().unwrap_or_default();
+
+        match magic {
+            "%squirrel" => {
+                let name = words
+                    .next()
+                    .ok_or_else(|| CoreError::Command("%squirrel requires a command name".to_string()))?;
+                Ok(Self::Command {
+                    name: name.to_string(),
+                    args: words.map(ToString::to_string).collect(),
+                })
+            }
+            "%tool" | "%%tool" => {
+                let name = words
+                    .next()
+                    .ok_or_else(|| CoreError::Command(format!("{magic} requires a tool name")))?;
+                let raw_args = if magic == "%tool" {
+                    first_line
+                        .splitn(3, char::is_whitespace)
+                        .nth(2)
+                        .unwrap_or_default()
+                } else {
+                    body
+                };
+                let args = if raw_args.trim().is_empty() {
+                    Value::Null
+                } else {
+                    serde_json::from_str(raw_args)?
+                };
+                Ok(Self::Tool {
+                    name: name.to_string(),
+                    args,
+                })
+            }
+            _ => Err(CoreError::Command(
+                "Only %squirrel, %tool and %%tool magics are supported".to_string(),
+            )),
+        }
+    }
+}
+
+/// Executes notebook requests against the command registry and tool plugins
+#[derive(Debug)]
+pub struct JupyterBridge {
+    /// Commands exposed through `%squirrel`
+    registry: Arc<CommandRegistry>,
+    /// Tool plugins keyed by tool name
+    tools: HashMap<String, Arc<dyn ToolPlugin>>,
+    /// Execution counter shown in notebook cells
+    execution_count: AtomicU32,
+}
+
+impl JupyterBridge {
+    /// Create a bridge exposing the given command registry
+    #[must_use]
+    pub fn new(registry: Arc<CommandRegistry>) -> Self {
+        Self {
+            registry,
+            tools: HashMap::new(),
+            execution_count: AtomicU32::new(0),
+        }
+    }
+
+    /// Expose every tool provided by a tool plugin
+    #[must_use]
+    pub fn with_tool_plugin(mut self, plugin: &Arc<dyn ToolPlugin>) -> Self {
+        for tool in plugin.list_tools() {
+            self.tools.insert(tool, Arc::clone(plugin));
+        }
+        self
+    }
+
+    /// Names of the tools available to `%tool`
+    #[must_use]
+    pub fn tool_names(&self) -> Vec<String> {
+        let mut names: Vec<String> = self.tools.keys().cloned().collect();
+        names.sort();
+        names
+    }
+
+    /// Handle a request and return the messages to send in response
+    ///
+    /// Unknown message types are ignored, as required by the protocol.
+    ///
+    /// # Errors
+    /// Returns an error if the command registry cannot be queried
+    pub async fn handle(&self, request: &JupyterMessage) -> Result<Vec<(Channel, JupyterMessage)>> {
+        let reply = match request.msg_type() {
+            "kernel_info_request" => (Channel::Shell, request.reply("kernel_info_reply", Self::kernel_info())),
+            "execute_request" => return Ok(self.handle_execute(request).await),
+            "complete_request" => (Channel::Shell, self.handle_complete(request)?),
+            // The kernel has no comms, history or introspection, so these
+            // get empty but valid replies rather than leaving the client waiting
+            "comm_info_request" => (
+                Channel::Shell,
+                request.reply("comm_info_reply", json!({ "status": "ok", "comms": {} })),
+            ),
+            "is_complete_request" => (
+                Channel::Shell,
+                request.reply("is_complete_reply", json!({ "status": "unknown" })),
+            ),
+            "inspect_request" => (
+                Channel::Shell,
+                request.reply(
+                    "inspect_reply",
+                    json!({ "status": "ok", "found": false, "data": {}, "metadata": {} }),
+                ),
+            ),
+            "history_request" => (
+                Channel::Shell,
+                request.reply("history_reply", json!({ "status": "ok", "history": [] })),
+            ),
+            "shutdown_request" => {
+                let restart = request.content.get("restart").cloned().unwrap_or(Value::Bool(false));
+                (
+                    Channel::Control,
+                    request.reply("shutdown_reply", json!({ "status": "ok", "restart": restart })),
+                )
+            }
+            other => {
+                debug!("Ignoring unsupported Jupyter message type: {other}");
+                return Ok(Vec::new());
+            }
+        };
+
+        Ok(vec![
+            (Channel::IoPub, Self::status(request, "busy")),
+            reply,
+            (Channel::IoPub, Self::status(request, "idle")),
+        ])
+    }
+
+    /// Content of a `kernel_info_reply`
+    fn kernel_info() -> Value {
+        json!({
+            "status": "ok",
+            "protocol_version": PROTOCOL_VERSION,
+            "implementation": "squirrel",
+            "implementation_version": crate::VERSION,
+            "language_info": {
+                "name": "squirrel",
+                "version": crate::VERSION,
+                "mimetype": "text/plain",
+                "file_extension": ".sq",
+            },
+            "banner": "Squirrel kernel - use %squirrel <command> or %tool <tool> <json>",
+        })
+    }
+
+    /// Build a kernel status message
+    fn status(request: &JupyterMessage, state: &str) -> JupyterMessage {
+        request.reply("status", json!({ "execution_state": state }))
+    }
+
+    /// Run an `execute_request`
+    async fn handle_execute(&self, request: &JupyterMessage) -> Vec<(Channel, JupyterMessage)> {
+        let code = request
+            .content
+            .get("code")
+            .and_then(Value::as_str)
+            .unwrap_or_default()
+            .to_string();
+        let silent = request
+            .content
+            .get("silent")
+            .and_then(Value::as_bool)
+            .unwrap_or(false);
+        let count = self.execution_count.fetch_add(1, Ordering::SeqCst) + 1;
+
+        let mut replies = vec![(Channel::IoPub, Self::status(request, "busy"))];
+        if !silent {
+            replies.push((
+                Channel::IoPub,
+                request.reply("execute_input", json!({ "code": code, "execution_count": count })),
+            ));
+        }
+
+        let outcome = match Magic::parse(&code) {
+            Ok(magic) => self.run_magic(magic).await,
+            Err(e) => Err(e),
+        };
+
+        let reply_content = match outcome {
+            Ok(output) => {
+                if !silent {
+                    replies.push((
+                        Channel::IoPub,
+                        request.reply(
+                            "execute_result",
+                            json!({
+                                "execution_count": count,
+                                "data": { "text/plain": output },
+                                "metadata": {},
+                            }),
+                        ),
+                    ));
+                }
+                json!({
+                    "status": "ok",
+                    "execution_count": count,
+                    "payload": [],
+                    "user_expressions": {},
+                })
+            }
+            Err(e) => {
+                let error = json!({
+                    "ename": "SquirrelError",
+                    "evalue": e.to_string(),
+                    "traceback": [e.to_string()],
+                });
+                replies.push((Channel::IoPub, request.reply("error", error.clone())));
+                let mut content = error;
+                content["status"] = json!("error");
+                content["execution_count"] = json!(count);
+                content
+            }
+        };
+
+        replies.push((Channel::Shell, request.reply("execute_reply", reply_content)));
+        replies.push((Channel::IoPub, Self::status(request, "idle")));
+        replies
+    }
+
+    /// Execute a parsed magic and render its output as text
+    async fn run_magic(&self, magic: Magic) -> Result<String> {
+        match magic {
+            Magic::Command { name, args } => self
+                .registry
+                .execute(&name, &args)
+                .map_err(|e| CoreError::Command(e.to_string())),
+            Magic::Tool { name, args } => {
+                let plugin = self
+                    .tools
+                    .get(&name)
+                    .ok_or_else(|| CoreError::Plugin(format!("Unknown tool: {name}")))?;
+                let value = plugin.execute_tool(&name, args).await?;
+                Ok(match value {
+                    Value::String(text) => text,
+                    other => serde_json::to_string_pretty(&other)?,
+                })
+            }
+        }
+    }
+
+    /// Complete command and tool names after a magic
+    fn handle_complete(&self, request: &JupyterMessage) -> Result<JupyterMessage> {
+        let code = request
+            .content
+            .get("code")
+            .and_then(Value::as_str)
+            .unwrap_or_default();
+        let cursor = request
+            .content
+            .get("cursor_pos")
+            .and_then(Value::as_u64)
+            .map_or(code.len(), |pos| usize::try_from(pos).unwrap_or(usize::MAX))
+            .min(code.len());
+        let before = code.get(..cursor).unwrap_or(code);
+        let (candidates, prefix) = match before.split_once(char::is_whitespace) {
+            Some((magic, rest)) => {
+                let prefix = if rest.ends_with(char::is_whitespace) {
+                    ""
+                } else {
+                    rest.split_whitespace().last().unwrap_or_default()
+                };
+                let candidates = match magic {
+                    "%squirrel" => self
+                        .registry
+                        .list_commands()
+                        .map_err(|e| CoreError::Command(e.to_string()))?,
+                    "%tool" | "%%tool" => self.tool_names(),
+                    _ => Vec::new(),
+                };
+                (candidates, prefix)
+            }
+            None => (
+                vec!["%squirrel".to_string(), "%tool".to_string(), "%%tool".to_string()],
+                before,
+            ),
+        };
+        let mut matches: Vec<String> = candidates
+            .into_iter()
+            .filter(|candidate| candidate.starts_with(prefix))
+            .collect();
+        matches.sort();
+
+        Ok(request.reply(
+            "complete_reply",
+            json!({
+                "status": "ok",
+                "matches": matches,
+                "cursor_start": cursor - prefix.len(),
+                "cursor_end": cursor,
+                "metadata": {},
+            }),
+        ))
+    }
+}
+
+/// A [`JupyterBridge`] serving a notebook over the kernel's ZMQ sockets
+///
+/// Shell and control are ROUTER sockets whose replies go back to the peer
+/// that asked, `IOPub` is a PUB socket and the heartbeat a REP socket
+/// echoing every ping. Stdin is bound to hold its port, but the bridge never
+/// asks for input.
+pub struct JupyterKernel {
+    /// Bridge executing the requests
+    bridge: JupyterBridge,
+    /// Signs replies and verifies requests
+    signer: MessageSigner,
+    /// Shell channel
+    shell: RouterSocket,
+    /// Control channel
+    control: RouterSocket,
+    /// `IOPub` channel
+    iopub: PubSocket,
+    /// Stdin channel, bound but unused
+    _stdin: RouterSocket,
+    /// Heartbeat channel
+    heartbeat: RepSocket,
+}
+
+impl JupyterKernel {
+    /// Bind the sockets named by `info` for `bridge`
+    ///
+    /// # Errors
+    /// Returns an error if a socket cannot be bound
+    pub async fn bind(bridge: JupyterBridge, info: &ConnectionInfo) -> Result<Self> {
+        let mut shell = RouterSocket::new();
+        let mut control = RouterSocket::new();
+        let mut iopub = PubSocket::new();
+        let mut stdin = RouterSocket::new();
+        let mut heartbeat = RepSocket::new();
+        shell.bind(&info.endpoint(info.shell_port)).await.map_err(socket_error)?;
+        control.bind(&info.endpoint(info.control_port)).await.map_err(socket_error)?;
+        iopub.bind(&info.endpoint(info.iopub_port)).await.map_err(socket_error)?;
+        stdin.bind(&info.endpoint(info.stdin_port)).await.map_err(socket_error)?;
+        heartbeat.bind(&info.endpoint(info.hb_port)).await.map_err(socket_error)?;
+        Ok(Self {
+            bridge,
+            signer: info.signer(),
+            shell,
+            control,
+            iopub,
+            _stdin: stdin,
+            heartbeat,
+        })
+    }
+
+    /// Serve requests until a shutdown request arrives
+    ///
+    /// Messages that fail to decode or verify are dropped.
+    ///
+    /// # Errors
+    /// Returns an error if a socket fails
+    pub async fn serve(self) -> Result<()> {
+        let Self {
+            bridge,
+            signer,
+            mut shell,
+            mut control,
+            mut iopub,
+            _stdin,
+            mut heartbeat,
+        } = self;
+        let heartbeat = tokio::spawn(async move {
+            while let Ok(ping) = heartbeat.recv().await {
+                if heartbeat.send(ping).await.is_err() {
+                    break;
+                }
+            }
+        });
+
+        let served = async {
+            loop {
+                let (channel, received) = tokio::select! {
+                    received = shell.recv() => (Channel::Shell, received),
+                    received = control.recv() => (Channel::Control, received),
+                };
+                let frames: Vec<Vec<u8>> = received
+                    .map_err(socket_error)?
+                    .into_vec()
+                    .into_iter()
+                    .map(|frame| frame.to_vec())
+                    .collect();
+                let request = match signer.decode(&frames) {
+                    Ok(request) => request,
+                    Err(e) => {
+                        warn!("Dropping Jupyter message: {e}");
+                        continue;
+                    }
+                };
+
+                let replies = match bridge.handle(&request).await {
+                    Ok(replies) => replies,
+                    Err(e) => {
+                        warn!("Failed to handle Jupyter {} message: {e}", request.msg_type());
+                        continue;
+                    }
+                };
+                for (reply_channel, mut reply) in replies {
+                    match (reply_channel, channel) {
+                        (Channel::IoPub, _) => {
+                            // Subscribers filter on the message type
+                            reply.identities = vec![reply.msg_type().as_bytes().to_vec()];
+                            iopub.send(wire_message(signer.encode(&reply)?)).await
+                        }
+                        (_, Channel::Control) => control.send(wire_message(signer.encode(&reply)?)).await,
+                        _ => shell.send(wire_message(signer.encode(&reply)?)).await,
+                    }
+                    .map_err(socket_error)?;
+                }
+                if request.msg_type() == "shutdown_request" {
+                    return Ok(());
+                }
+            }
+        };
+        let result = served.await;
+        heartbeat.abort();
+        result
+    }
+}
+
+impl std::fmt::Debug for JupyterKernel {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("JupyterKernel").field("bridge", &self.bridge).finish_non_exhaustive()
+    }
+}
+
+/// Build a ZMQ message from wire frames
+fn wire_message(frames: Vec<Vec<u8>>) -> ZmqMessage {
+    let mut frames = frames.into_iter();
+    let mut message = ZmqMessage::from(frames.next().unwrap_or_default());
+    for frame in frames {
+        message.push_back(frame.into());
+    }
+    message
+}
+
+/// Convert a socket error
+#[allow(clippy::needless_pass_by_value)] // Passed to `map_err`
+fn socket_error(e: ZmqError) -> CoreError {
+    CoreError::Plugin(format!("Jupyter socket error: {e}"))
+}
+
+/// A Jupyter kernel specification (`kernel.json`)
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct KernelSpec {
+    /// Command used to launch the kernel
+    pub argv: Vec<String>,
+    /// Name shown in the notebook UI
+    pub display_name: String,
+    /// Kernel language
+    pub language: String,
+    /// Additional metadata
+    #[serde(default)]
+    pub metadata: Value,
+}
+
+impl KernelSpec {
+    /// Default kernel name used when installing
+    pub const DEFAULT_NAME: &'static str = "squirrel";
+
+    /// Create a kernel spec that launches the given executable
+    #[must_use]
+    pub fn for_executable(executable: &Path) -> Self {
+        Self {
+            argv: vec![
+                executable.display().to_string(),
+                "jupyter".to_string(),
+                "kernel".to_string(),
+                "--connection-file".to_string(),
+                "{connection_file}".to_string(),
+            ],
+            display_name: "Squirrel".to_string(),
+            language: "squirrel".to_string(),
+            metadata: json!({ "squirrel_version": crate::VERSION }),
+        }
+    }
+
+    /// Install the spec as `<kernels_dir>/<name>/kernel.json`
+    ///
+    /// # Errors
+    /// Returns an error if the directory cannot be created or the file
+    /// cannot be written
+    pub fn install(&self, kernels_dir: &Path, name: &str) -> Result<PathBuf> {
+        if name.is_empty() || name.contains(['/', '\\']) || name.starts_with('.') {
+            return Err(CoreError::Config(format!("Invalid kernel name: {name}")));
+        }
+        let dir = kernels_dir.join(name);
+        std::fs::create_dir_all(&dir)?;
+        let path = dir.join("kernel.json");
+        std::fs::write(&path, serde_json::to_string_pretty(self)?)?;
+        Ok(path)
+    }
+}
+
+/// Default directory where user kernel specs are installed
+///
+/// Honors `JUPYTER_DATA_DIR` and falls back to the per-user data directory.
+#[must_use]
+pub fn default_kernels_dir() -> PathBuf {
+    if let Some(dir) = std::env::var_os("JUPYTER_DATA_DIR") {
+        return PathBuf::from(dir).join("kernels");
+    }
+    let home = std::env::var_os("HOME")
+        .or_else(|| std::env::var_os("USERPROFILE"))
+        .map_or_else(|| PathBuf::from("."), PathBuf::from);
+    if cfg!(target_os = "macos") {
+        home.join("Library/Jupyter/kernels")
+    } else if cfg!(windows) {
+        std::env::var_os("APPDATA").map_or(home, PathBuf::from).join("jupyter/kernels")
+    } else {
+        home.join(".local/share/jupyter/kernels")
+    }
+}
+
+/// Command that installs the Squirrel kernel spec for Jupyter
+#[derive(Debug, Clone, Default)]
+pub struct KernelSpecInstallCommand;
+
+impl KernelSpecInstallCommand {
+    /// Create a new kernel spec install command
+    #[must_use]
+    pub const fn new() -> Self {
+        Self
+    }
+}
+
+impl Command for KernelSpecInstallCommand {
+    fn name(&self) -> &'static str {
+        "jupyter-install"
+    }
+
+    fn description(&self) -> &'static str {
+        "Install the Squirrel Jupyter kernel spec"
+    }
+
+    fn execute(&self, args: &[String]) -> CommandResult<String> {
+        let matches = self
+            .parser()
+            .try_get_matches_from(std::iter::once(self.name().to_string()).chain(args.iter().cloned()))
+            .map_err(|e| CommandError::ValidationError(e.to_string()))?;
+
+        let name = matches
+            .get_one::<String>("name")
+            .map_or(KernelSpec::DEFAULT_NAME, String::as_str);
+        let kernels_dir = matches
+            .get_one::<String>("prefix")
+            .map_or_else(default_kernels_dir, |prefix| PathBuf::from(prefix).join("share/jupyter/kernels"));
+        let executable = match matches.get_one::<String>("executable") {
+            Some(path) => PathBuf::from(path),
+            None => std::env::current_exe()
+                .map_err(|e| CommandError::ExecutionError(format!("Cannot locate executable: {e}")))?,
+        };
+
+        let path = KernelSpec::for_executable(&executable)
+            .install(&kernels_dir, name)
+            .map_err(|e| CommandError::ExecutionError(e.to_string()))?;
+        Ok(format!("Installed kernel spec '{name}' in {}", path.display()))
+    }
+
+    fn parser(&self) -> ClapCommand {
+        ClapCommand::new("jupyter-install")
+            .about("Install the Squirrel Jupyter kernel spec")
+            .arg(Arg::new("name").long("name").help("Kernel name").value_name("NAME"))
+            .arg(
+                Arg::new("prefix")
+                    .long("prefix")
+                    .help("Install under PREFIX/share/jupyter/kernels")
+                    .value_name("PREFIX"),
+            )
+            .arg(
+                Arg::new("executable")
+                    .long("executable")
+                    .help("Executable Jupyter should launch")
+                    .value_name("PATH"),
+            )
+    }
+
+    fn clone_box(&self) -> Box<dyn Command> {
+        Box::new(self.clone())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::plugin::{Plugin, PluginMetadata, PluginState};
+    use futures::future::BoxFuture;
+    use std::any::Any;
+
+    #[derive(Debug, Clone)]
+    struct EchoToolPlugin {
+        metadata: PluginMetadata,
+    }
+
+    impl Plugin for EchoToolPlugin {
+        fn metadata(&self) -> &PluginMetadata {
+            &self.metadata
+        }
+
+        fn initialize(&self) -> BoxFuture<'_, Result<()>> {
+            Box::pin(async { Ok(()) })
+        }
+
+        fn shutdown(&self) -> BoxFuture<'_, Result<()>> {
+            Box::pin(async { Ok(()) })
+        }
+
+        fn get_state(&self) -> BoxFuture<'_, Result<Option<PluginState>>> {
+            Box::pin(async { Ok(None) })
+        }
+
+        fn set_state(&self, _state: PluginState) -> BoxFuture<'_, Result<()>> {
+            Box::pin(async { Ok(()) })
+        }
+
+        fn as_any(&self) -> &dyn Any {
+            self
+        }
+
+        fn clone_box(&self) -> Box<dyn Plugin> {
+            Box::new(self.clone())
+        }
+    }
+
+    #[async_trait::async_trait]
+    impl ToolPlugin for EchoToolPlugin {
+        async fn execute_tool(&self, _tool: &str, args: Value) -> Result<Value> {
+            Ok(json!({ "echo": args }))
+        }
+
+        fn get_tool_config(&self, _tool: &str) -> Option<Value> {
+            None
+        }
+
+        fn list_tools(&self) -> Vec<String> {
+            vec!["echo".to_string()]
+        }
+    }
+
+    fn bridge() -> JupyterBridge {
+        let plugin = EchoToolPlugin {
+            metadata: PluginMetadata {
+                id: Uuid::new_v4(),
+                name: "echo".to_string(),
+                version: "0.1.0".to_string(),
+                description: "Echo tool".to_string(),
+                author: "Test".to_string(),
+                dependencies: vec![],
+                capabilities: vec!["tool".to_string()],
+            },
+        };
+        let plugin: Arc<dyn ToolPlugin> = Arc::new(plugin);
+        JupyterBridge::new(Arc::new(CommandRegistry::new())).with_tool_plugin(&plugin)
+    }
+
+    #[test]
+    fn test_signed_round_trip() {
+        let signer = MessageSigner::new("secret");
+        let mut message = JupyterMessage::new("execute_request", "s1", json!({ "code": "%tool echo" }));
+        message.identities.push(b"client".to_vec());
+
+        let frames = signer.encode(&message).unwrap();
+        let decoded = signer.decode(&frames).unwrap();
+        assert_eq!(decoded.header, message.header);
+        assert_eq!(decoded.identities, message.identities);
+        assert!(decoded.parent_header.is_none());
+
+        let mut tampered = frames;
+        let last = tampered.len() - 1;
+        tampered[last] = br#"{"code":"other"}"#.to_vec();
+        assert!(signer.decode(&tampered).is_err());
+    }
+
+    #[test]
+    fn test_parse_magics() {
+        assert_eq!(
+            Magic::parse("%squirrel help version").unwrap(),
+            Magic::Command { name: "help".to_string(), args: vec!["version".to_string()] }
+        );
+        assert_eq!(
+            Magic::parse("%tool echo {\"a\": 1}").unwrap(),
+            Magic::Tool { name: "echo".to_string(), args: json!({ "a": 1 }) }
+        );
+        assert_eq!(
+            Magic::parse("%%tool echo\n[1, 2]").unwrap(),
+            Magic::Tool { name: "echo".to_string(), args: json!([1, 2]) }
+        );
+        assert!(Magic::parse("print(1)").is_err());
+    }
+
+    #[tokio::test]
+    async fn test_execute_tool_magic() {
+        let bridge = bridge();
+        let request = JupyterMessage::new("execute_request", "s1", json!({ "code": "%tool echo 42" }));
+        let replies = bridge.handle(&request).await.unwrap();
+
+        let (_, reply) = replies
+            .iter()
+            .find(|(channel, msg)| *channel == Channel::Shell && msg.msg_type() == "execute_reply")
+            .unwrap();
+        assert_eq!(reply.content["status"], "ok");
+        assert_eq!(reply.content["execution_count"], 1);
+        assert_eq!(reply.parent_header.as_ref().unwrap().msg_id, request.header.msg_id);
+
+        let (_, result) = replies
+            .iter()
+            .find(|(_, msg)| msg.msg_type() == "execute_result")
+            .unwrap();
+        assert!(result.content["data"]["text/plain"].as_str().unwrap().contains("42"));
+        assert_eq!(replies.last().unwrap().1.content["execution_state"], "idle");
+    }
+
+    #[tokio::test]
+    async fn test_answers_requests_without_content() {
+        let bridge = bridge();
+        for (request_type, reply_type, status) in [
+            ("comm_info_request", "comm_info_reply", "ok"),
+            ("is_complete_request", "is_complete_reply", "unknown"),
+            ("inspect_request", "inspect_reply", "ok"),
+            ("history_request", "history_reply", "ok"),
+        ] {
+            let request = JupyterMessage::new(request_type, "s1", json!({ "code": "%tool echo 42" }));
+            let replies = bridge.handle(&request).await.unwrap();
+            assert_eq!(replies.len(), 3, "{request_type}");
+
+            let (channel, reply) = &replies[1];
+            assert_eq!(*channel, Channel::Shell);
+            assert_eq!(reply.msg_type(), reply_type);
+            assert_eq!(reply.content["status"], status);
+            assert_eq!(reply.parent_header.as_ref().unwrap().msg_id, request.header.msg_id);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_execute_unknown_command_reports_error() {
+        let bridge = bridge();
+        let request = JupyterMessage::new("execute_request", "s1", json!({ "code": "%squirrel missing" }));
+        let replies = bridge.handle(&request).await.unwrap();
+        assert!(replies.iter().any(|(_, msg)| msg.msg_type() == "error"));
+        let (_, reply) = replies
+            .iter()
+            .find(|(_, msg)| msg.msg_type() == "execute_reply")
+            .unwrap();
+        assert_eq!(reply.content["status"], "error");
+    }
+
+    fn free_port() -> u16 {
+        std::net::TcpListener::bind("127.0.0.1:0").unwrap().local_addr().unwrap().port()
+    }
+
+    fn frames(message: ZmqMessage) -> Vec<Vec<u8>> {
+        message.into_vec().into_iter().map(|frame| frame.to_vec()).collect()
+    }
+
+    #[tokio::test]
+    async fn test_kernel_serves_requests_over_zmq() {
+        use zeromq::{DealerSocket, ReqSocket};
+
+        let info = ConnectionInfo {
+            ip: "127.0.0.1".to_string(),
+            transport: "tcp".to_string(),
+            shell_port: free_port(),
+            iopub_port: free_port(),
+            stdin_port: free_port(),
+            control_port: free_port(),
+            hb_port: free_port(),
+            key: "secret".to_string(),
+            signature_scheme: "hmac-sha256".to_string(),
+            kernel_name: None,
+        };
+        let kernel = JupyterKernel::bind(bridge(), &info).await.unwrap();
+        let kernel = tokio::spawn(kernel.serve());
+        let signer = info.signer();
+
+        let mut heartbeat = ReqSocket::new();
+        heartbeat.connect(&info.endpoint(info.hb_port)).await.unwrap();
+        heartbeat.send("ping".into()).await.unwrap();
+        assert_eq!(frames(heartbeat.recv().await.unwrap()), vec![b"ping".to_vec()]);
+
+        let mut shell = DealerSocket::new();
+        shell.connect(&info.endpoint(info.shell_port)).await.unwrap();
+        let request = JupyterMessage::new("execute_request", "s1", json!({ "code": "%tool echo 42" }));
+        shell.send(wire_message(signer.encode(&request).unwrap())).await.unwrap();
+        let reply = signer.decode(&frames(shell.recv().await.unwrap())).unwrap();
+        assert_eq!(reply.msg_type(), "execute_reply");
+        assert_eq!(reply.content["status"], "ok");
+        assert_eq!(reply.parent_header.unwrap().msg_id, request.header.msg_id);
+
+        let mut control = DealerSocket::new();
+        control.connect(&info.endpoint(info.control_port)).await.unwrap();
+        let request = JupyterMessage::new("shutdown_request", "s1", json!({ "restart": false }));
+        control.send(wire_message(signer.encode(&request).unwrap())).await.unwrap();
+        let reply = signer.decode(&frames(control.recv().await.unwrap())).unwrap();
+        assert_eq!(reply.msg_type(), "shutdown_reply");
+        kernel.await.unwrap().unwrap();
+    }
+
+    #[test]
+    fn test_install_kernel_spec() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = KernelSpec::for_executable(Path::new("/usr/bin/squirrel"))
+            .install(dir.path(), "squirrel")
+            .unwrap();
+        let spec: KernelSpec = serde_json::from_str(&std::fs::read_to_string(path).unwrap()).unwrap();
+        assert_eq!(spec.argv[0], "/usr/bin/squirrel");
+        assert!(spec.argv.contains(&"{connection_file}".to_string()));
+        assert!(KernelSpec::for_executable(Path::new("x")).install(dir.path(), "../evil").is_err());
+    }
+}
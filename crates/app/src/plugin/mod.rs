@@ -1,4 +1,7 @@
 use crate::error::Result;
+use crate::schedule::TaskScheduler;
+use squirrel_core::kv::KvStore;
+use arc_swap::ArcSwapOption;
 use async_trait::async_trait;
 use futures::future::BoxFuture;
 use log::{debug, error, info, warn};
@@ -19,9 +22,17 @@ mod discovery;
 mod state;
 /// Plugin security and sandboxing functionality
 mod security;
+/// Jupyter kernel bridge exposing commands and tools as notebook magics
+pub mod jupyter;
+/// Host API versioning and compatibility shims
+pub mod api;
+/// Services the host offers plugins, such as scheduled tasks
+pub mod host;
 
 pub use types::{CommandPlugin, UiPlugin, ToolPlugin, McpPlugin};
-pub use discovery::{PluginDiscovery, FileSystemDiscovery, PluginLoader};
+pub use discovery::{PluginDiscovery, FileSystemDiscovery, PluginLoader, DiscoveredPlugin};
+pub use api::{ApiVersion, CompatibilityReport, HOST_API_VERSION};
+pub use host::HostContext;
 pub use state::{PluginStateStorage, FileSystemStateStorage, MemoryStateStorage, PluginStateManager};
 pub use security::{
     PermissionLevel, ResourceLimits, SecurityContext, ResourceUsage, 
@@ -131,6 +142,11 @@ pub trait Plugin: Send + Sync + Any + std::fmt::Debug {
     /// Set plugin state
     fn set_state(&self, state: PluginState) -> BoxFuture<'_, Result<()>>;
 
+    /// Receive the services of the host, before the plugin is initialized
+    ///
+    /// Called on every load by a manager with a scheduler (added in API 1.2).
+    fn attach(&self, _host: HostContext) {}
+
     /// Get plugin status (convenience method for tests)
     fn get_status(&self) -> BoxFuture<'_, PluginStatus> {
         Box::pin(async { PluginStatus::Active })
@@ -212,8 +228,12 @@ pub struct PluginManager {
     pub name_to_id: Arc<RwLock<HashMap<String, Uuid>>>,
     /// Plugin storage (using concrete enum type)
     pub storage: Arc<RwLock<Option<PluginStorageEnum>>>,
-    /// Security validator for plugins
-    pub security_validator: Arc<RwLock<Option<Arc<SecurityValidator>>>>,
+    /// Security validator for plugins, swapped atomically so readers never lock
+    pub security_validator: Arc<ArcSwapOption<SecurityValidator>>,
+    /// Scheduler of the tasks plugins schedule through their host context
+    pub scheduler: Arc<ArcSwapOption<TaskScheduler>>,
+    /// Key-value store plugins keep small state in through their host context
+    pub kv_store: Arc<ArcSwapOption<KvStore>>,
 }
 
 /// Enum of possible plugin storage implementations
@@ -396,7 +416,9 @@ impl PluginManager {
             statuses: Arc::new(RwLock::new(HashMap::new())),
             name_to_id: Arc::new(RwLock::new(HashMap::new())),
             storage: Arc::new(RwLock::new(Some(PluginStorageEnum::Memory(MemoryStorage::new())))),
-            security_validator: Arc::new(RwLock::new(None)),
+            security_validator: Arc::new(ArcSwapOption::empty()),
+            scheduler: Arc::new(ArcSwapOption::empty()),
+            kv_store: Arc::new(ArcSwapOption::empty()),
         }
     }
     
@@ -410,7 +432,9 @@ impl PluginManager {
             statuses: Arc::new(RwLock::new(HashMap::new())),
             name_to_id: Arc::new(RwLock::new(HashMap::new())),
             storage: Arc::new(RwLock::new(Some(PluginStorageEnum::File(FileStorage::new(base_dir)?)))),
-            security_validator: Arc::new(RwLock::new(None)),
+            security_validator: Arc::new(ArcSwapOption::empty()),
+            scheduler: Arc::new(ArcSwapOption::empty()),
+            kv_store: Arc::new(ArcSwapOption::empty()),
         })
     }
     
@@ -424,33 +448,63 @@ impl PluginManager {
             statuses: Arc::new(RwLock::new(HashMap::new())),
             name_to_id: Arc::new(RwLock::new(HashMap::new())),
             storage: Arc::new(RwLock::new(Some(storage))),
-            security_validator: Arc::new(RwLock::new(None)),
+            security_validator: Arc::new(ArcSwapOption::empty()),
+            scheduler: Arc::new(ArcSwapOption::empty()),
+            kv_store: Arc::new(ArcSwapOption::empty()),
         }
     }
 
     /// Enable security validation for plugins
+    ///
+    /// Takes effect for plugins registered afterwards. Safe to call from
+    /// async code: the validator is swapped in without locking.
     pub fn with_security(&mut self) -> &mut Self {
-        let security_validator = Arc::new(SecurityValidator::with_basic_sandbox());
-        {
-            let mut validator = self.security_validator.blocking_write();
-            *validator = Some(security_validator);
-        }
+        self.set_security_validator(Some(Arc::new(SecurityValidator::with_basic_sandbox())));
         self
     }
 
     /// Enable security validation with custom sandbox
     pub fn with_custom_sandbox(&mut self, sandbox: Arc<dyn PluginSandbox>) -> &mut Self {
-        let security_validator = Arc::new(SecurityValidator::new(sandbox));
-        {
-            let mut validator = self.security_validator.blocking_write();
-            *validator = Some(security_validator);
-        }
+        self.set_security_validator(Some(Arc::new(SecurityValidator::new(sandbox))));
         self
     }
 
+    /// Replace the security validator, or disable validation with `None`
+    ///
+    /// Clones of this manager share the validator and see the change.
+    pub fn set_security_validator(&self, validator: Option<Arc<SecurityValidator>>) {
+        self.security_validator.store(validator);
+    }
+
     /// Get the security validator
     #[must_use] pub fn security_validator(&self) -> Option<Arc<SecurityValidator>> {
-        self.security_validator.blocking_read().clone()
+        self.security_validator.load_full()
+    }
+
+    /// Offer plugins loaded afterwards scheduled tasks on `scheduler`, or
+    /// none with `None`
+    ///
+    /// Clones of this manager share the scheduler and see the change.
+    pub fn set_scheduler(&self, scheduler: Option<Arc<TaskScheduler>>) {
+        self.scheduler.store(scheduler);
+    }
+
+    /// Get the scheduler of plugin tasks
+    #[must_use] pub fn scheduler(&self) -> Option<Arc<TaskScheduler>> {
+        self.scheduler.load_full()
+    }
+
+    /// Give plugins loaded afterwards a namespace of `store`, or none with
+    /// `None`
+    ///
+    /// Clones of this manager share the store and see the change.
+    pub fn set_store(&self, store: Option<KvStore>) {
+        self.kv_store.store(store.map(Arc::new));
+    }
+
+    /// Get the key-value store of plugin state
+    #[must_use] pub fn store(&self) -> Option<KvStore> {
+        self.kv_store.load_full().map(|store| KvStore::clone(&store))
     }
     
     /// Register a new plugin
@@ -474,7 +528,7 @@ impl PluginManager {
         }
         
         // Create sandbox for the plugin if security is enabled
-        if let Some(security) = &*self.security_validator.read().await {
+        if let Some(security) = self.security_validator() {
             security.sandbox().create_sandbox(id).await?;
         }
         
@@ -581,6 +635,15 @@ impl PluginManager {
             let plugins = self.plugins.read().await;
             let plugin = plugins.get(&id).ok_or(PluginError::NotFound(id))?;
             
+            // Hand the plugin the host's services
+            if let Some(scheduler) = self.scheduler() {
+                let mut host = HostContext::new(plugin.metadata().name.clone(), scheduler);
+                if let Some(store) = self.store() {
+                    host = host.with_store(store);
+                }
+                plugin.attach(host);
+            }
+            
             // Initialize with a timeout
             match tokio::time::timeout(
                 Duration::from_secs(30),
@@ -723,6 +786,11 @@ impl PluginManager {
                 }
             }
             
+            // Stop the tasks the plugin scheduled
+            if let Some(scheduler) = self.scheduler() {
+                scheduler.unregister_owner(&HostContext::owner_of(&plugin_name));
+            }
+            
             // Update plugin status to unloaded
             // Use try_write instead of write for status updates to avoid deadlocks
             if let Ok(mut statuses) = self.statuses.try_write() {
@@ -759,7 +827,7 @@ impl PluginManager {
     /// Returns `Ok(())` if the operation is allowed, `Err` otherwise
     pub async fn validate_operation(&self, id: Uuid, operation: &str) -> Result<()> {
         // Check security validator
-        if let Some(security) = &*self.security_validator.read().await {
+        if let Some(security) = self.security_validator() {
             security.validate_operation(id, operation).await?;
         }
         
@@ -783,7 +851,7 @@ impl PluginManager {
     /// Returns a `SecurityError` if the path access is not allowed, if the plugin does not have
     /// sufficient permissions, or if security validation is not enabled
     pub async fn validate_path_access(&self, id: Uuid, path: &Path, write: bool) -> Result<()> {
-        if let Some(security) = &*self.security_validator.read().await {
+        if let Some(security) = self.security_validator() {
             security.validate_path_access(id, path, write).await?;
         }
         Ok(())
@@ -805,7 +873,7 @@ impl PluginManager {
     /// Returns a `SecurityError` if the capability is not allowed, if the plugin does not have
     /// sufficient permissions, or if security validation is not enabled
     pub async fn validate_capability(&self, id: Uuid, capability: &str) -> Result<()> {
-        if let Some(security) = &*self.security_validator.read().await {
+        if let Some(security) = self.security_validator() {
             security.validate_capability(id, capability).await?;
         }
         Ok(())
@@ -826,7 +894,7 @@ impl PluginManager {
     /// 
     /// Returns an error if resource tracking fails or if the plugin cannot be found
     pub async fn track_resources(&self, id: Uuid) -> Result<Option<ResourceUsage>> {
-        if let Some(security) = &*self.security_validator.read().await {
+        if let Some(security) = self.security_validator() {
             // Use the sandbox method to track resources
             let usage = security.sandbox().track_resources(id).await?;
             return Ok(Some(usage));
@@ -1775,4 +1843,33 @@ pub mod tests {
         let none_plugins = manager.get_plugins_by_capability("none").await;
         assert_eq!(none_plugins.len(), 0);
     }
-} 
\ No newline at end of file
+
+    #[tokio::test]
+    async fn test_with_security_inside_runtime() {
+        // Enabling security used to block on a lock and panic inside the runtime
+        let mut manager = PluginManager::new();
+        assert!(manager.security_validator().is_none());
+        manager.with_security();
+        let validator = manager.security_validator().unwrap();
+
+        // Clones share the validator, and registration goes through its sandbox
+        let clone = manager.clone();
+        assert!(Arc::ptr_eq(&validator, &clone.security_validator().unwrap()));
+        let plugin = TestPlugin {
+            metadata: PluginMetadata {
+                id: Uuid::new_v4(),
+                name: "secured".to_string(),
+                version: "0.1.0".to_string(),
+                description: "Secured plugin".to_string(),
+                author: "Test Author".to_string(),
+                dependencies: vec![],
+                capabilities: vec![],
+            },
+            state: Arc::new(RwLock::new(None)),
+        };
+        clone.register_plugin(Box::new(plugin)).await.unwrap();
+
+        clone.set_security_validator(None);
+        assert!(manager.security_validator().is_none());
+    }
+}
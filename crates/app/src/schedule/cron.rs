@@ -0,0 +1,230 @@
+//! Cron schedules
+//!
+//! A [`CronSchedule`] is written as five fields, in UTC:
+//! `minute hour day-of-month month day-of-week`. Each field is `*`, a value,
+//! a range `a-b`, any of these with a step such as `*/15`, or a
+//! comma-separated list of them. Days of the week run from 0 (Sunday) to 6;
+//! 7 is Sunday too. When both day fields are restricted, a day matching
+//! either one matches, as in cron. `@hourly`, `@daily`, `@weekly` and
+//! `@monthly` are shorthands.
+
+use std::fmt;
+use std::str::FromStr;
+
+use chrono::{DateTime, Datelike, Duration, DurationRound, TimeZone, Timelike, Utc};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+/// Why a schedule could not be parsed
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+#[error("Invalid cron schedule '{schedule}': {reason}")]
+pub struct CronError {
+    /// The schedule as written
+    pub schedule: String,
+    /// What is wrong with it
+    pub reason: String,
+}
+
+/// Values a field matches, as a bit set
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Field {
+    /// Bit `n` is set if the field matches the value `n`
+    bits: u64,
+    /// Whether the field is `*`, matching every value
+    any: bool,
+}
+
+impl Field {
+    /// Whether the field matches `value`
+    fn matches(self, value: u32) -> bool {
+        self.bits & (1 << value) != 0
+    }
+
+    /// Parse a field whose values run from `min` to `max`
+    fn parse(field: &str, min: u32, max: u32) -> Result<Self, String> {
+        let mut bits = 0;
+        for part in field.split(',') {
+            let (range, step) = match part.split_once('/') {
+                Some((range, step)) => {
+                    let step: u32 = step.parse().map_err(|_| format!("Invalid step '{step}'"))?;
+                    if step == 0 {
+                        return Err("Steps start at 1".to_string());
+                    }
+                    (range, step)
+                }
+                None => (part, 1),
+            };
+            let value = |value: &str| -> Result<u32, String> {
+                let parsed: u32 = value.parse().map_err(|_| format!("Invalid value '{value}'"))?;
+                if (min..=max).contains(&parsed) {
+                    Ok(parsed)
+                } else {
+                    Err(format!("{parsed} is not between {min} and {max}"))
+                }
+            };
+            let (start, end) = match range {
+                "*" => (min, max),
+                range => match range.split_once('-') {
+                    Some((start, end)) => (value(start)?, value(end)?),
+                    // A single value with a step runs to the end, as `5/15`
+                    None if step > 1 => (value(range)?, max),
+                    None => {
+                        let value = value(range)?;
+                        (value, value)
+                    }
+                },
+            };
+            if start > end {
+                return Err(format!("Empty range '{range}'"));
+            }
+            for value in (start..=end).step_by(step as usize) {
+                bits |= 1 << value;
+            }
+        }
+        Ok(Self { bits, any: field == "*" })
+    }
+}
+
+/// When a scheduled task runs, as a cron expression in UTC
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CronSchedule {
+    /// The expression as written
+    expression: String,
+    /// Minutes of the hour
+    minutes: Field,
+    /// Hours of the day
+    hours: Field,
+    /// Days of the month
+    days: Field,
+    /// Months of the year
+    months: Field,
+    /// Days of the week, from Sunday
+    weekdays: Field,
+}
+
+impl CronSchedule {
+    /// The cron expression of the schedule
+    #[must_use]
+    pub fn expression(&self) -> &str {
+        &self.expression
+    }
+
+    /// Whether the schedule runs on the day of `time`
+    fn matches_day(&self, time: DateTime<Utc>) -> bool {
+        let day = self.days.matches(time.day());
+        let weekday = self.weekdays.matches(time.weekday().num_days_from_sunday());
+        match (self.days.any, self.weekdays.any) {
+            (false, false) => day || weekday,
+            _ => day && weekday,
+        }
+    }
+
+    /// The first time after `after` the schedule runs, within four years
+    #[must_use]
+    pub fn next_after(&self, after: DateTime<Utc>) -> Option<DateTime<Utc>> {
+        let mut time = after.duration_trunc(Duration::minutes(1)).ok()? + Duration::minutes(1);
+        let limit = after + Duration::days(4 * 366);
+        while time <= limit {
+            if !self.months.matches(time.month()) {
+                let (year, month) = if time.month() == 12 { (time.year() + 1, 1) } else { (time.year(), time.month() + 1) };
+                time = Utc.with_ymd_and_hms(year, month, 1, 0, 0, 0).single()?;
+            } else if !self.matches_day(time) {
+                time = time.duration_trunc(Duration::days(1)).ok()? + Duration::days(1);
+            } else if !self.hours.matches(time.hour()) {
+                time = time.duration_trunc(Duration::hours(1)).ok()? + Duration::hours(1);
+            } else if !self.minutes.matches(time.minute()) {
+                time += Duration::minutes(1);
+            } else {
+                return Some(time);
+            }
+        }
+        None
+    }
+}
+
+impl FromStr for CronSchedule {
+    type Err = CronError;
+
+    fn from_str(schedule: &str) -> Result<Self, CronError> {
+        let error = |reason: String| CronError {
+            schedule: schedule.to_string(),
+            reason,
+        };
+        let expanded = match schedule.trim() {
+            "@hourly" => "0 * * * *",
+            "@daily" | "@midnight" => "0 0 * * *",
+            "@weekly" => "0 0 * * 0",
+            "@monthly" => "0 0 1 * *",
+            expression => expression,
+        };
+        let fields: Vec<&str> = expanded.split_whitespace().collect();
+        let [minutes, hours, days, months, weekdays] = fields[..] else {
+            return Err(error(format!("Expected 5 fields, found {}", fields.len())));
+        };
+        let mut weekdays = Field::parse(weekdays, 0, 7).map_err(error)?;
+        // 7 is Sunday too
+        if weekdays.matches(7) {
+            weekdays.bits |= 1;
+        }
+        Ok(Self {
+            expression: schedule.trim().to_string(),
+            minutes: Field::parse(minutes, 0, 59).map_err(error)?,
+            hours: Field::parse(hours, 0, 23).map_err(error)?,
+            days: Field::parse(days, 1, 31).map_err(error)?,
+            months: Field::parse(months, 1, 12).map_err(error)?,
+            weekdays,
+        })
+    }
+}
+
+impl fmt::Display for CronSchedule {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.expression)
+    }
+}
+
+impl Serialize for CronSchedule {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&self.expression)
+    }
+}
+
+impl<'de> Deserialize<'de> for CronSchedule {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        String::deserialize(deserializer)?
+            .parse()
+            .map_err(serde::de::Error::custom)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn at(text: &str) -> DateTime<Utc> {
+        DateTime::parse_from_rfc3339(text).unwrap().with_timezone(&Utc)
+    }
+
+    #[test]
+    fn test_schedules_find_their_next_run() {
+        let nightly: CronSchedule = "30 3 * * *".parse().unwrap();
+        assert_eq!(nightly.next_after(at("2026-10-17T02:10:45Z")), Some(at("2026-10-17T03:30:00Z")));
+        assert_eq!(nightly.next_after(at("2026-10-17T03:30:00Z")), Some(at("2026-10-18T03:30:00Z")));
+
+        let quarterly: CronSchedule = "*/15 * * * *".parse().unwrap();
+        assert_eq!(quarterly.next_after(at("2026-12-31T23:59:00Z")), Some(at("2027-01-01T00:00:00Z")));
+
+        // Sundays, or the first of the month
+        let either: CronSchedule = "0 4 1 * 7".parse().unwrap();
+        assert_eq!(either.next_after(at("2026-10-17T12:00:00Z")), Some(at("2026-10-18T04:00:00Z")));
+        assert_eq!(either.next_after(at("2026-10-26T12:00:00Z")), Some(at("2026-11-01T04:00:00Z")));
+
+        let leap: CronSchedule = "0 0 29 2 *".parse().unwrap();
+        assert_eq!(leap.next_after(at("2026-10-17T00:00:00Z")), Some(at("2028-02-29T00:00:00Z")));
+        assert_eq!("@weekly".parse::<CronSchedule>().unwrap().expression(), "@weekly");
+
+        assert!("0 3 * *".parse::<CronSchedule>().is_err());
+        assert!("60 3 * * *".parse::<CronSchedule>().is_err());
+        assert!("0 5-2 * * *".parse::<CronSchedule>().is_err());
+        assert!("0 3 31 2 *".parse::<CronSchedule>().unwrap().next_after(at("2026-10-17T00:00:00Z")).is_none());
+    }
+}
@@ -0,0 +1,924 @@
+//! Scheduled tasks
+//!
+//! A [`TaskScheduler`] runs named tasks periodically, at the times of a
+//! [`CronSchedule`] or at a fixed interval, each under the [`Supervisor`] as
+//! `schedule.<name>`. The host schedules its own maintenance through it, and
+//! plugins schedule theirs through their
+//! [`HostContext`](crate::plugin::HostContext). [`TaskOptions`] say how a
+//! task runs:
+//!
+//! - `jitter` delays every run by a random time up to it, so that instances
+//!   started together do not all run at once,
+//! - `max_runtime` cancels runs that take longer; they count as failed,
+//! - `singleton` runs the task only on the instance the scheduler's
+//!   [`Leadership`] elects, when several instances share the work, and
+//!   only while it holds the task's lock from the scheduler's [`TaskLocks`],
+//!   so that runs on demand on other instances never overlap with it,
+//! - `alert_after` is the number of failures in a row after which the task is
+//!   reported as failing.
+//!
+//! Runs of a task never overlap. Tasks can be paused, resumed and run on
+//! demand, and every run is reported to the scheduler's [`TaskObserver`]s,
+//! which record metrics and raise alerts.
+//!
+//! ```ignore
+//! let scheduler = Arc::new(TaskScheduler::new(supervisor).with_leadership(leader));
+//! scheduler.register(HOST_OWNER, "db.maintenance", TaskOptions::new(cron).singleton(), move || {
+//!     let maintenance = maintenance.clone();
+//!     async move { Ok(maintenance.run().await?) }
+//! })?;
+//! ```
+
+pub mod cron;
+
+pub use cron::{CronError, CronSchedule};
+
+use std::any::Any;
+use std::collections::BTreeMap;
+use std::fmt;
+use std::future::Future;
+use std::panic::AssertUnwindSafe;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use futures::future::BoxFuture;
+use futures::FutureExt;
+use rand::Rng;
+use serde::Serialize;
+use tokio::sync::{MutexGuard, Notify};
+use tokio::time::Instant;
+use tracing::{debug, warn};
+
+use crate::supervisor::{lock, panic_message, RestartPolicy, Supervisor, TaskError, TaskResult};
+
+/// Owner of the tasks the host schedules itself
+pub const HOST_OWNER: &str = "host";
+
+/// Prefix of the names of the supervised tasks running scheduled tasks
+pub const SUPERVISOR_PREFIX: &str = "schedule.";
+
+/// Failures in a row after which a task is reported as failing, unless its
+/// options say otherwise
+pub const DEFAULT_ALERT_AFTER: u32 = 3;
+
+/// Shortest interval between runs of a task scheduled [`Schedule::Every`]
+const MIN_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Longest task name
+const MAX_NAME_LEN: usize = 128;
+
+/// Why a task could not be scheduled or controlled
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+pub enum ScheduleError {
+    /// The task name is empty, too long or has other characters than
+    /// letters, digits, `.`, `_` and `-`
+    #[error("Invalid task name '{0}': use letters, digits, '.', '_' and '-'")]
+    InvalidName(String),
+    /// Another owner scheduled a task under the name
+    #[error("Task '{name}' is already scheduled by {owner}")]
+    Taken {
+        /// Name of the task
+        name: String,
+        /// Owner of the scheduled task
+        owner: String,
+    },
+    /// No task is scheduled under the name
+    #[error("No scheduled task '{0}'")]
+    NotFound(String),
+    /// The task is running already
+    #[error("Task '{0}' is already running")]
+    Running(String),
+    /// The singleton task's lock is held by another instance or cannot be
+    /// taken
+    #[error("Task '{name}' cannot run here now: {reason}")]
+    Locked {
+        /// Name of the task
+        name: String,
+        /// Why the lock was not taken
+        reason: String,
+    },
+}
+
+/// When a task runs
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Schedule {
+    /// At the times of a cron expression
+    Cron(CronSchedule),
+    /// Repeatedly, the first time one interval after it was scheduled
+    Every(Duration),
+}
+
+impl Schedule {
+    /// The first run after `after`, if there is one
+    #[must_use]
+    pub fn next_after(&self, after: DateTime<Utc>) -> Option<DateTime<Utc>> {
+        match self {
+            Self::Cron(cron) => cron.next_after(after),
+            Self::Every(interval) => chrono::Duration::from_std((*interval).max(MIN_INTERVAL))
+                .ok()
+                .and_then(|interval| after.checked_add_signed(interval)),
+        }
+    }
+}
+
+impl fmt::Display for Schedule {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Cron(cron) => write!(f, "{cron}"),
+            Self::Every(interval) => write!(f, "every {interval:?}"),
+        }
+    }
+}
+
+impl From<CronSchedule> for Schedule {
+    fn from(cron: CronSchedule) -> Self {
+        Self::Cron(cron)
+    }
+}
+
+impl From<Duration> for Schedule {
+    fn from(interval: Duration) -> Self {
+        Self::Every(interval)
+    }
+}
+
+/// How a task runs
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TaskOptions {
+    /// When it runs
+    pub schedule: Schedule,
+    /// Runs are delayed by a random time up to this
+    pub jitter: Duration,
+    /// Runs taking longer are cancelled and count as failed
+    pub max_runtime: Option<Duration>,
+    /// Whether only the leader of the instances runs the task
+    pub singleton: bool,
+    /// Failures in a row after which the task is reported as failing; 0
+    /// never reports it
+    pub alert_after: u32,
+}
+
+impl TaskOptions {
+    /// Run on `schedule`, on every instance, without jitter or a time limit
+    #[must_use]
+    pub fn new(schedule: impl Into<Schedule>) -> Self {
+        Self {
+            schedule: schedule.into(),
+            jitter: Duration::ZERO,
+            max_runtime: None,
+            singleton: false,
+            alert_after: DEFAULT_ALERT_AFTER,
+        }
+    }
+
+    /// Delay runs by a random time up to `jitter`
+    #[must_use]
+    pub fn with_jitter(mut self, jitter: Duration) -> Self {
+        self.jitter = jitter;
+        self
+    }
+
+    /// Cancel runs taking longer than `max_runtime`
+    #[must_use]
+    pub fn with_max_runtime(mut self, max_runtime: Duration) -> Self {
+        self.max_runtime = Some(max_runtime);
+        self
+    }
+
+    /// Run the task on the leader only
+    #[must_use]
+    pub fn singleton(mut self) -> Self {
+        self.singleton = true;
+        self
+    }
+
+    /// Report the task as failing after `failures` failures in a row
+    #[must_use]
+    pub fn with_alert_after(mut self, failures: u32) -> Self {
+        self.alert_after = failures;
+        self
+    }
+}
+
+/// Decides which instance runs singleton tasks
+pub trait Leadership: Send + Sync {
+    /// Whether this instance runs them
+    fn is_leader(&self) -> bool;
+}
+
+/// Holds the lock of a singleton task's run until it is dropped
+pub type TaskLockGuard = Box<dyn Any + Send>;
+
+/// Keeps runs of a singleton task on different instances from overlapping
+///
+/// Leadership decides where a singleton task runs on schedule, but it can be
+/// run on demand anywhere, and two instances may briefly both believe they
+/// lead.
+#[async_trait]
+pub trait TaskLocks: Send + Sync {
+    /// Take the lock of the task `task`, or return `None` if it is held
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the lock cannot be taken
+    async fn try_lock(&self, task: &str) -> Result<Option<TaskLockGuard>, TaskError>;
+}
+
+/// How a run ended
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RunOutcome {
+    /// The task succeeded
+    Succeeded,
+    /// The task returned an error or panicked
+    Failed,
+    /// The task took longer than its maximum runtime
+    TimedOut,
+}
+
+impl RunOutcome {
+    /// Name of the outcome, as serialized
+    #[must_use]
+    pub const fn name(self) -> &'static str {
+        match self {
+            Self::Succeeded => "succeeded",
+            Self::Failed => "failed",
+            Self::TimedOut => "timed_out",
+        }
+    }
+}
+
+/// One run of a task
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct TaskRun {
+    /// Name of the task
+    pub task: String,
+    /// When the run started
+    pub started_at: DateTime<Utc>,
+    /// How long it took
+    pub elapsed_ms: u64,
+    /// How it ended
+    pub outcome: RunOutcome,
+    /// Why it failed, if it did
+    pub error: Option<String>,
+    /// Whether it was started on demand rather than by the schedule
+    pub manual: bool,
+}
+
+/// State of a scheduled task, as reported by [`TaskScheduler::tasks`]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct TaskStatus {
+    /// Name of the task
+    pub name: String,
+    /// Who scheduled it: [`HOST_OWNER`] or `plugin:<name>`
+    pub owner: String,
+    /// When it runs
+    pub schedule: String,
+    /// Maximum delay of runs, in milliseconds
+    pub jitter_ms: u64,
+    /// Maximum runtime, in milliseconds
+    pub max_runtime_ms: Option<u64>,
+    /// Whether only the leader runs it
+    pub singleton: bool,
+    /// Failures in a row after which it is reported as failing
+    pub alert_after: u32,
+    /// Whether scheduled runs are skipped
+    pub paused: bool,
+    /// Whether it is running now
+    pub running: bool,
+    /// When it runs next
+    pub next_run: Option<DateTime<Utc>>,
+    /// The latest run
+    pub last_run: Option<TaskRun>,
+    /// Runs since it was scheduled
+    pub runs: u64,
+    /// Failed runs since it was scheduled
+    pub failures: u64,
+    /// Failed runs since the last success
+    pub consecutive_failures: u32,
+}
+
+/// Told about the runs of scheduled tasks
+#[async_trait]
+pub trait TaskObserver: Send + Sync {
+    /// A task ran
+    async fn finished(&self, task: &TaskStatus, run: &TaskRun);
+
+    /// A task failed as often in a row as its options allow
+    async fn failing(&self, task: &TaskStatus) {
+        let _ = task;
+    }
+
+    /// A task reported as failing succeeded again
+    async fn recovered(&self, task: &TaskStatus) {
+        let _ = task;
+    }
+}
+
+/// A run of a task
+type TaskFn = Arc<dyn Fn() -> BoxFuture<'static, TaskResult> + Send + Sync>;
+
+/// Progress of a scheduled task
+#[derive(Debug, Default)]
+struct TaskProgress {
+    /// Whether scheduled runs are skipped
+    paused: bool,
+    /// When the task runs next
+    next_run: Option<DateTime<Utc>>,
+    /// The latest run
+    last_run: Option<TaskRun>,
+    /// Runs so far
+    runs: u64,
+    /// Failed runs so far
+    failures: u64,
+    /// Failed runs since the last success
+    consecutive_failures: u32,
+}
+
+/// A task owned by the scheduler
+struct ScheduledTask {
+    /// Name of the task
+    name: String,
+    /// Who scheduled it
+    owner: String,
+    /// How it runs
+    options: TaskOptions,
+    /// Starts a run
+    run: TaskFn,
+    /// Progress, updated by the runs
+    progress: Mutex<TaskProgress>,
+    /// Held by the run in progress, so runs never overlap
+    running: tokio::sync::Mutex<()>,
+    /// Set when the task was unscheduled
+    removed: AtomicBool,
+    /// Wakes the scheduling loop when the task was unscheduled
+    wake: Notify,
+}
+
+impl ScheduledTask {
+    /// Whether the task was unscheduled
+    fn is_removed(&self) -> bool {
+        self.removed.load(Ordering::SeqCst)
+    }
+
+    /// Unschedule the task, ending its scheduling loop
+    fn remove(&self) {
+        self.removed.store(true, Ordering::SeqCst);
+        self.wake.notify_one();
+    }
+
+    /// Current state of the task
+    fn status(&self) -> TaskStatus {
+        let millis = |duration: Duration| u64::try_from(duration.as_millis()).unwrap_or(u64::MAX);
+        let progress = lock(&self.progress);
+        TaskStatus {
+            name: self.name.clone(),
+            owner: self.owner.clone(),
+            schedule: self.options.schedule.to_string(),
+            jitter_ms: millis(self.options.jitter),
+            max_runtime_ms: self.options.max_runtime.map(millis),
+            singleton: self.options.singleton,
+            alert_after: self.options.alert_after,
+            paused: progress.paused,
+            running: self.running.try_lock().is_err(),
+            next_run: progress.next_run,
+            last_run: progress.last_run.clone(),
+            runs: progress.runs,
+            failures: progress.failures,
+            consecutive_failures: progress.consecutive_failures,
+        }
+    }
+}
+
+/// Runs named tasks on their schedules
+pub struct TaskScheduler {
+    /// Runs the scheduling loops
+    supervisor: Arc<Supervisor>,
+    /// Decides whether singleton tasks run here; without it they always do
+    leadership: Option<Arc<dyn Leadership>>,
+    /// Locks held by runs of singleton tasks; without them none are taken
+    locks: Option<Arc<dyn TaskLocks>>,
+    /// Told about every run
+    observers: Vec<Arc<dyn TaskObserver>>,
+    /// Tasks by name
+    tasks: Mutex<BTreeMap<String, Arc<ScheduledTask>>>,
+}
+
+impl fmt::Debug for TaskScheduler {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("TaskScheduler")
+            .field("tasks", &lock(&self.tasks).keys().collect::<Vec<_>>())
+            .field("observers", &self.observers.len())
+            .finish_non_exhaustive()
+    }
+}
+
+impl TaskScheduler {
+    /// Create a scheduler running its tasks under `supervisor`
+    #[must_use]
+    pub fn new(supervisor: Arc<Supervisor>) -> Self {
+        Self {
+            supervisor,
+            leadership: None,
+            locks: None,
+            observers: Vec::new(),
+            tasks: Mutex::new(BTreeMap::new()),
+        }
+    }
+
+    /// Run singleton tasks only while `leadership` says this instance leads
+    #[must_use]
+    pub fn with_leadership(mut self, leadership: Arc<dyn Leadership>) -> Self {
+        self.leadership = Some(leadership);
+        self
+    }
+
+    /// Hold the lock of a singleton task from `locks` while it runs
+    #[must_use]
+    pub fn with_locks(mut self, locks: Arc<dyn TaskLocks>) -> Self {
+        self.locks = Some(locks);
+        self
+    }
+
+    /// Tell `observer` about every run
+    #[must_use]
+    pub fn with_observer(mut self, observer: Arc<dyn TaskObserver>) -> Self {
+        self.observers.push(observer);
+        self
+    }
+
+    /// Schedule `run` as the task `name` of `owner`
+    ///
+    /// A task the same owner scheduled under the name before is replaced, and
+    /// its run in progress cancelled.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the name is not valid or another owner scheduled a
+    /// task under it
+    pub fn register<F, Fut>(
+        self: &Arc<Self>,
+        owner: &str,
+        name: &str,
+        options: TaskOptions,
+        run: F,
+    ) -> Result<(), ScheduleError>
+    where
+        F: Fn() -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = TaskResult> + Send + 'static,
+    {
+        let valid = !name.is_empty()
+            && name.len() <= MAX_NAME_LEN
+            && name.chars().all(|c| c.is_ascii_alphanumeric() || matches!(c, '.' | '_' | '-'));
+        if !valid {
+            return Err(ScheduleError::InvalidName(name.to_string()));
+        }
+        let task = Arc::new(ScheduledTask {
+            name: name.to_string(),
+            owner: owner.to_string(),
+            options,
+            run: Arc::new(move || run().boxed()),
+            progress: Mutex::new(TaskProgress::default()),
+            running: tokio::sync::Mutex::new(()),
+            removed: AtomicBool::new(false),
+            wake: Notify::new(),
+        });
+        {
+            let mut tasks = lock(&self.tasks);
+            if let Some(existing) = tasks.get(name) {
+                if existing.owner != owner {
+                    return Err(ScheduleError::Taken {
+                        name: name.to_string(),
+                        owner: existing.owner.clone(),
+                    });
+                }
+                existing.remove();
+            }
+            tasks.insert(name.to_string(), task.clone());
+        }
+
+        // The loops hold the scheduler weakly, since it owns the supervisor
+        let scheduler = Arc::downgrade(self);
+        self.supervisor.spawn(
+            format!("{SUPERVISOR_PREFIX}{name}"),
+            RestartPolicy::default(),
+            move |shutdown| {
+                let scheduler = scheduler.clone();
+                let task = task.clone();
+                async move {
+                    while !task.is_removed() {
+                        let now = Utc::now();
+                        let Some(next) = task.options.schedule.next_after(now) else {
+                            break;
+                        };
+                        let next = jittered(next, task.options.jitter);
+                        lock(&task.progress).next_run = Some(next);
+                        let wait = (next - now).to_std().unwrap_or_default();
+                        tokio::select! {
+                            stop = shutdown.sleep(wait) => if stop {
+                                break;
+                            },
+                            () = task.wake.notified() => continue,
+                        }
+
+                        let Some(scheduler) = scheduler.upgrade() else {
+                            break;
+                        };
+                        if lock(&task.progress).paused {
+                            debug!(task = %task.name, "Skipping run of paused task");
+                            continue;
+                        }
+                        if task.options.singleton && !scheduler.is_leader() {
+                            debug!(task = %task.name, "Skipping run of singleton task on a follower");
+                            continue;
+                        }
+                        // A run started on demand is not repeated
+                        let Ok(guard) = task.running.try_lock() else {
+                            continue;
+                        };
+                        let held = match scheduler.hold(&task).await {
+                            Ok(held) => held,
+                            Err(reason) => {
+                                debug!(task = %task.name, "Skipping run of singleton task: {}", reason);
+                                continue;
+                            }
+                        };
+                        scheduler.execute(&task, guard, false).await;
+                        drop(held);
+                    }
+                    lock(&task.progress).next_run = None;
+                    Ok(())
+                }
+            },
+        );
+        Ok(())
+    }
+
+    /// Unschedule the task `name`, returning whether there was one
+    ///
+    /// A run in progress finishes.
+    pub fn unregister(&self, name: &str) -> bool {
+        let removed = lock(&self.tasks).remove(name);
+        match removed {
+            Some(task) => {
+                task.remove();
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Unschedule every task of `owner`, returning how many there were
+    pub fn unregister_owner(&self, owner: &str) -> usize {
+        let mut tasks = lock(&self.tasks);
+        let names: Vec<String> = tasks
+            .values()
+            .filter(|task| task.owner == owner)
+            .map(|task| task.name.clone())
+            .collect();
+        for name in &names {
+            if let Some(task) = tasks.remove(name) {
+                task.remove();
+            }
+        }
+        names.len()
+    }
+
+    /// Skip the scheduled runs of the task `name` until it is resumed
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if there is no such task
+    pub fn pause(&self, name: &str) -> Result<TaskStatus, ScheduleError> {
+        self.set_paused(name, true)
+    }
+
+    /// Run the task `name` on its schedule again
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if there is no such task
+    pub fn resume(&self, name: &str) -> Result<TaskStatus, ScheduleError> {
+        self.set_paused(name, false)
+    }
+
+    /// Run the task `name` now, on this instance, and wait for it to finish
+    ///
+    /// Paused and singleton tasks run too, the latter only if their lock is
+    /// free.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if there is no such task, it is running already or
+    /// its lock is held elsewhere
+    pub async fn run_now(&self, name: &str) -> Result<TaskRun, ScheduleError> {
+        let task = self.task(name)?;
+        let guard = task
+            .running
+            .try_lock()
+            .map_err(|_| ScheduleError::Running(name.to_string()))?;
+        let held = self.hold(&task).await.map_err(|reason| ScheduleError::Locked {
+            name: name.to_string(),
+            reason,
+        })?;
+        let run = self.execute(&task, guard, true).await;
+        drop(held);
+        Ok(run)
+    }
+
+    /// State of the task `name`
+    #[must_use]
+    pub fn status(&self, name: &str) -> Option<TaskStatus> {
+        self.task(name).ok().map(|task| task.status())
+    }
+
+    /// State of every task, by name
+    #[must_use]
+    pub fn tasks(&self) -> Vec<TaskStatus> {
+        self.snapshot().iter().map(|task| task.status()).collect()
+    }
+
+    /// State of the tasks of `owner`, by name
+    #[must_use]
+    pub fn tasks_of(&self, owner: &str) -> Vec<TaskStatus> {
+        self.snapshot()
+            .iter()
+            .filter(|task| task.owner == owner)
+            .map(|task| task.status())
+            .collect()
+    }
+
+    /// The tasks, without holding the lock while their state is read
+    fn snapshot(&self) -> Vec<Arc<ScheduledTask>> {
+        lock(&self.tasks).values().cloned().collect()
+    }
+
+    /// The task `name`
+    fn task(&self, name: &str) -> Result<Arc<ScheduledTask>, ScheduleError> {
+        lock(&self.tasks)
+            .get(name)
+            .cloned()
+            .ok_or_else(|| ScheduleError::NotFound(name.to_string()))
+    }
+
+    /// Whether singleton tasks run on this instance
+    fn is_leader(&self) -> bool {
+        self.leadership.as_ref().is_none_or(|leadership| leadership.is_leader())
+    }
+
+    /// Take the lock of `task` if it is a singleton and the scheduler has
+    /// locks, or say why it must not run
+    async fn hold(&self, task: &ScheduledTask) -> Result<Option<TaskLockGuard>, String> {
+        let Some(locks) = self.locks.as_ref().filter(|_| task.options.singleton) else {
+            return Ok(None);
+        };
+        match locks.try_lock(&task.name).await {
+            Ok(Some(held)) => Ok(Some(held)),
+            Ok(None) => Err("its lock is held by another instance".to_string()),
+            Err(e) => Err(format!("its lock cannot be taken: {e}")),
+        }
+    }
+
+    /// Set whether the scheduled runs of the task `name` are skipped
+    fn set_paused(&self, name: &str, paused: bool) -> Result<TaskStatus, ScheduleError> {
+        let task = self.task(name)?;
+        lock(&task.progress).paused = paused;
+        Ok(task.status())
+    }
+
+    /// Run `task`, holding its `guard`, and tell the observers how it went
+    async fn execute(&self, task: &ScheduledTask, guard: MutexGuard<'_, ()>, manual: bool) -> TaskRun {
+        let started_at = Utc::now();
+        let started = Instant::now();
+        let running = AssertUnwindSafe((task.run)()).catch_unwind();
+        let result = match task.options.max_runtime {
+            Some(limit) => tokio::time::timeout(limit, running)
+                .await
+                .map_err(|_| format!("Still running after {limit:?}")),
+            None => Ok(running.await),
+        };
+        drop(guard);
+        let (outcome, error) = match result {
+            Ok(Ok(Ok(()))) => (RunOutcome::Succeeded, None),
+            Ok(Ok(Err(e))) => (RunOutcome::Failed, Some(e.to_string())),
+            Ok(Err(panic)) => (RunOutcome::Failed, Some(format!("panicked: {}", panic_message(&*panic)))),
+            Err(timeout) => (RunOutcome::TimedOut, Some(timeout)),
+        };
+        let run = TaskRun {
+            task: task.name.clone(),
+            started_at,
+            elapsed_ms: u64::try_from(started.elapsed().as_millis()).unwrap_or(u64::MAX),
+            outcome,
+            error,
+            manual,
+        };
+        if let Some(error) = &run.error {
+            warn!(task = %task.name, "Scheduled task failed: {}", error);
+        }
+
+        let alert_after = task.options.alert_after;
+        let (failing, recovered) = {
+            let mut progress = lock(&task.progress);
+            let was_failing = alert_after > 0 && progress.consecutive_failures >= alert_after;
+            progress.runs += 1;
+            if outcome == RunOutcome::Succeeded {
+                progress.consecutive_failures = 0;
+            } else {
+                progress.failures += 1;
+                progress.consecutive_failures += 1;
+            }
+            progress.last_run = Some(run.clone());
+            (
+                alert_after > 0 && progress.consecutive_failures == alert_after,
+                was_failing && progress.consecutive_failures == 0,
+            )
+        };
+        let status = task.status();
+        for observer in &self.observers {
+            observer.finished(&status, &run).await;
+            if failing {
+                observer.failing(&status).await;
+            }
+            if recovered {
+                observer.recovered(&status).await;
+            }
+        }
+        run
+    }
+}
+
+/// `time`, delayed by a random time up to `jitter`
+fn jittered(time: DateTime<Utc>, jitter: Duration) -> DateTime<Utc> {
+    if jitter.is_zero() {
+        return time;
+    }
+    let delay = jitter.mul_f64(rand::thread_rng().gen_range(0.0..1.0));
+    chrono::Duration::from_std(delay).map_or(time, |delay| time + delay)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::sync::atomic::AtomicU32;
+
+    /// Records what the scheduler reports
+    #[derive(Default)]
+    struct Recorder {
+        /// Outcomes of the runs, in order
+        runs: Mutex<Vec<(String, RunOutcome)>>,
+        /// Tasks reported as failing, then recovered
+        alerts: Mutex<Vec<String>>,
+    }
+
+    #[async_trait]
+    impl TaskObserver for Recorder {
+        async fn finished(&self, _task: &TaskStatus, run: &TaskRun) {
+            lock(&self.runs).push((run.task.clone(), run.outcome));
+        }
+
+        async fn failing(&self, task: &TaskStatus) {
+            lock(&self.alerts).push(format!("failing {}", task.name));
+        }
+
+        async fn recovered(&self, task: &TaskStatus) {
+            lock(&self.alerts).push(format!("recovered {}", task.name));
+        }
+    }
+
+    /// Never leads
+    struct Follower;
+
+    impl Leadership for Follower {
+        fn is_leader(&self) -> bool {
+            false
+        }
+    }
+
+    /// Locks that are always held by another instance
+    struct HeldElsewhere;
+
+    #[async_trait]
+    impl TaskLocks for HeldElsewhere {
+        async fn try_lock(&self, _task: &str) -> Result<Option<TaskLockGuard>, TaskError> {
+            Ok(None)
+        }
+    }
+
+    #[tokio::test]
+    async fn test_tasks_run_on_demand_and_report_failures() {
+        let recorder = Arc::new(Recorder::default());
+        let scheduler = Arc::new(
+            TaskScheduler::new(Arc::new(Supervisor::new()))
+                .with_leadership(Arc::new(Follower))
+                .with_observer(recorder.clone()),
+        );
+        let hourly = || TaskOptions::new(Duration::from_hours(1));
+        let attempts = Arc::new(AtomicU32::new(0));
+        let counted = attempts.clone();
+        scheduler
+            .register(HOST_OWNER, "flaky", hourly().with_alert_after(2), move || {
+                let attempt = counted.fetch_add(1, Ordering::SeqCst);
+                async move {
+                    if attempt < 2 {
+                        Err::<(), TaskError>("not yet".into())
+                    } else {
+                        Ok(())
+                    }
+                }
+            })
+            .unwrap();
+        scheduler
+            .register(HOST_OWNER, "slow", hourly().with_max_runtime(Duration::from_millis(10)), || async {
+                tokio::time::sleep(Duration::from_secs(5)).await;
+                Ok(())
+            })
+            .unwrap();
+
+        assert!(matches!(
+            scheduler.register("plugin:other", "flaky", hourly(), || async { Ok(()) }),
+            Err(ScheduleError::Taken { .. })
+        ));
+        assert!(matches!(
+            scheduler.register(HOST_OWNER, "no spaces", hourly(), || async { Ok(()) }),
+            Err(ScheduleError::InvalidName(_))
+        ));
+
+        for _ in 0..3 {
+            scheduler.run_now("flaky").await.unwrap();
+        }
+        let run = scheduler.run_now("slow").await.unwrap();
+        assert_eq!(run.outcome, RunOutcome::TimedOut);
+        assert!(run.manual);
+        assert_eq!(
+            lock(&recorder.runs).iter().map(|(_, outcome)| *outcome).collect::<Vec<_>>(),
+            vec![RunOutcome::Failed, RunOutcome::Failed, RunOutcome::Succeeded, RunOutcome::TimedOut]
+        );
+        assert_eq!(*lock(&recorder.alerts), vec!["failing flaky", "recovered flaky"]);
+
+        let status = scheduler.status("flaky").unwrap();
+        assert_eq!((status.runs, status.failures, status.consecutive_failures), (3, 2, 0));
+        assert!(status.next_run.is_some());
+        assert!(scheduler.pause("flaky").unwrap().paused);
+        assert!(!scheduler.resume("flaky").unwrap().paused);
+        assert_eq!(scheduler.tasks_of(HOST_OWNER).len(), 2);
+
+        assert_eq!(scheduler.unregister_owner(HOST_OWNER), 2);
+        assert!(scheduler.tasks().is_empty());
+        assert!(matches!(scheduler.run_now("flaky").await, Err(ScheduleError::NotFound(_))));
+    }
+
+    #[tokio::test]
+    async fn test_scheduled_runs_skip_paused_and_singleton_tasks() {
+        let supervisor = Arc::new(Supervisor::new());
+        let scheduler = Arc::new(TaskScheduler::new(supervisor.clone()).with_leadership(Arc::new(Follower)));
+        let runs = Arc::new(AtomicU32::new(0));
+        for (name, options) in [
+            ("every", TaskOptions::new(Duration::ZERO)),
+            ("leader-only", TaskOptions::new(Duration::ZERO).singleton()),
+        ] {
+            let runs = runs.clone();
+            scheduler
+                .register(HOST_OWNER, name, options, move || {
+                    let runs = runs.clone();
+                    async move {
+                        runs.fetch_add(if name == "every" { 1 } else { 100 }, Ordering::SeqCst);
+                        Ok(())
+                    }
+                })
+                .unwrap();
+        }
+        scheduler.pause("every").unwrap();
+
+        // Intervals are at least a second
+        tokio::time::sleep(Duration::from_millis(1200)).await;
+        assert_eq!(runs.load(Ordering::SeqCst), 0);
+        scheduler.resume("every").unwrap();
+        tokio::time::sleep(Duration::from_secs(1)).await;
+        assert_eq!(runs.load(Ordering::SeqCst), 1);
+
+        assert!(scheduler.unregister("every"));
+        assert!(!scheduler.unregister("every"));
+        supervisor.shutdown(Duration::from_secs(1)).await;
+    }
+
+    #[tokio::test]
+    async fn test_singleton_tasks_only_run_holding_their_lock() {
+        let scheduler = Arc::new(TaskScheduler::new(Arc::new(Supervisor::new())).with_locks(Arc::new(HeldElsewhere)));
+        let hourly = || TaskOptions::new(Duration::from_hours(1));
+        scheduler.register(HOST_OWNER, "anywhere", hourly(), || async { Ok(()) }).unwrap();
+        scheduler
+            .register(HOST_OWNER, "once", hourly().singleton(), || async { Ok(()) })
+            .unwrap();
+
+        assert_eq!(scheduler.run_now("anywhere").await.unwrap().outcome, RunOutcome::Succeeded);
+        assert!(matches!(
+            scheduler.run_now("once").await,
+            Err(ScheduleError::Locked { name, .. }) if name == "once"
+        ));
+        assert_eq!(scheduler.status("once").unwrap().runs, 0);
+    }
+}
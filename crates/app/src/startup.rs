@@ -0,0 +1,554 @@
+//! Ordered startup of subsystems with readiness gates
+//!
+//! A process declares its subsystems as [`Stage`]s: a name, the stages it
+//! depends on, an init future, a timeout and optionally a readiness probe.
+//! [`Startup::run`] initializes the stages in dependency order. A stage is
+//! done once its init succeeded and its probe passed; the probe is retried
+//! until the stage's timeout runs out, so a stage can wait for a connection
+//! it started to come up.
+//!
+//! Stages are critical unless declared [optional](Stage::optional). A
+//! critical stage that fails or times out aborts the startup. An optional
+//! one is reported as failed and the stages depending on it are skipped.
+//! The [`Readiness`] shared by the stages reports each stage as it runs, and
+//! afterwards tells whether the process is ready: every critical stage
+//! passed, and their probes still pass.
+//!
+//! ```ignore
+//! let pool = Arc::new(OnceCell::new());
+//! let startup = Startup::new()
+//!     .stage(Stage::new("database", {
+//!         let pool = pool.clone();
+//!         move || async move { pool.set(connect().await?).map_err(Into::into) }
+//!     }).with_timeout(Duration::from_secs(30)))
+//!     .stage(Stage::new("app", move || async move { build(pool.get()).await })
+//!         .depends_on("database"));
+//! let readiness = startup.readiness();
+//! startup.run().await?;
+//! ```
+
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::future::Future;
+use std::sync::{Arc, PoisonError, RwLock};
+use std::time::Duration;
+
+use futures::future::BoxFuture;
+use serde::Serialize;
+use thiserror::Error;
+use tokio::time::Instant;
+use tracing::{info, warn};
+
+/// Time a stage gets when it sets no timeout
+pub const DEFAULT_STAGE_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Delay between readiness probes of a stage that is not ready yet
+const PROBE_INTERVAL: Duration = Duration::from_millis(250);
+
+/// Error a stage's init ends with
+pub type StageError = Box<dyn std::error::Error + Send + Sync>;
+
+/// Initializes a stage
+type Init = Box<dyn FnOnce() -> BoxFuture<'static, Result<(), StageError>> + Send>;
+
+/// Checks whether a stage is ready
+type Probe = Arc<dyn Fn() -> BoxFuture<'static, bool> + Send + Sync>;
+
+/// Error of a startup that did not complete
+#[derive(Debug, Error)]
+pub enum StartupError {
+    /// Two stages have the same name
+    #[error("Stage '{0}' is declared twice")]
+    DuplicateStage(String),
+
+    /// A stage depends on one that is not declared
+    #[error("Stage '{stage}' depends on unknown stage '{dependency}'")]
+    UnknownDependency {
+        /// The depending stage
+        stage: String,
+        /// The missing dependency
+        dependency: String,
+    },
+
+    /// The dependencies of these stages form a cycle
+    #[error("Stages depend on each other: {}", .0.join(", "))]
+    Cycle(Vec<String>),
+
+    /// A critical stage failed, timed out or was skipped
+    #[error("Critical stage '{stage}' did not start: {reason}")]
+    StageFailed {
+        /// The stage
+        stage: String,
+        /// Why it did not start
+        reason: String,
+    },
+}
+
+/// Progress of a stage
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum StageStatus {
+    /// Waiting for its dependencies
+    Pending,
+    /// Initializing, or waiting for its probe to pass
+    Starting,
+    /// Initialized and ready
+    Ready,
+    /// Its init failed
+    Failed,
+    /// It was not ready within its timeout
+    TimedOut,
+    /// Not started because a dependency did not start
+    Skipped,
+}
+
+/// State of a stage, as reported by [`Readiness::report`]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct StageReport {
+    /// Name of the stage
+    pub name: String,
+    /// Whether the process is not ready without it
+    pub critical: bool,
+    /// Progress of the stage
+    pub status: StageStatus,
+    /// Time its startup took, once it finished
+    pub elapsed_ms: Option<u64>,
+    /// Why it did not start
+    pub error: Option<String>,
+}
+
+/// A subsystem to initialize
+pub struct Stage {
+    /// Name of the stage
+    name: String,
+    /// Stages initialized before this one
+    depends_on: Vec<String>,
+    /// Whether the startup fails without it
+    critical: bool,
+    /// Time the stage gets to become ready
+    timeout: Duration,
+    /// Initializes the stage
+    init: Init,
+    /// Checks whether the stage is ready
+    probe: Option<Probe>,
+}
+
+impl std::fmt::Debug for Stage {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Stage")
+            .field("name", &self.name)
+            .field("depends_on", &self.depends_on)
+            .field("critical", &self.critical)
+            .field("timeout", &self.timeout)
+            .field("probe", &self.probe.is_some())
+            .finish_non_exhaustive()
+    }
+}
+
+impl Stage {
+    /// A critical stage named `name`, initialized by `init`
+    pub fn new<F, Fut>(name: impl Into<String>, init: F) -> Self
+    where
+        F: FnOnce() -> Fut + Send + 'static,
+        Fut: Future<Output = Result<(), StageError>> + Send + 'static,
+    {
+        Self {
+            name: name.into(),
+            depends_on: Vec::new(),
+            critical: true,
+            timeout: DEFAULT_STAGE_TIMEOUT,
+            init: Box::new(move || Box::pin(init())),
+            probe: None,
+        }
+    }
+
+    /// Initializes the stage after `stage`
+    #[must_use]
+    pub fn depends_on(mut self, stage: impl Into<String>) -> Self {
+        self.depends_on.push(stage.into());
+        self
+    }
+
+    /// Lets the process start and become ready without this stage
+    #[must_use]
+    pub fn optional(mut self) -> Self {
+        self.critical = false;
+        self
+    }
+
+    /// Gives the stage `timeout` to initialize and pass its probe
+    #[must_use]
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    /// Considers the stage ready only while `probe` passes
+    #[must_use]
+    pub fn with_probe<F, Fut>(mut self, probe: F) -> Self
+    where
+        F: Fn() -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = bool> + Send + 'static,
+    {
+        self.probe = Some(Arc::new(move || Box::pin(probe())));
+        self
+    }
+}
+
+/// Readiness of the process, updated as the stages start
+#[derive(Default)]
+pub struct Readiness {
+    /// Reports in startup order
+    stages: RwLock<Vec<StageReport>>,
+    /// Probes of the critical stages
+    probes: RwLock<Vec<Probe>>,
+}
+
+impl std::fmt::Debug for Readiness {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Readiness")
+            .field("stages", &self.report())
+            .finish_non_exhaustive()
+    }
+}
+
+impl Readiness {
+    /// Readiness of a process without stages, which is always ready
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// State of every stage, in startup order
+    #[must_use]
+    pub fn report(&self) -> Vec<StageReport> {
+        self.stages
+            .read()
+            .unwrap_or_else(PoisonError::into_inner)
+            .clone()
+    }
+
+    /// Whether every critical stage started
+    #[must_use]
+    pub fn is_started(&self) -> bool {
+        self.report()
+            .iter()
+            .all(|stage| !stage.critical || stage.status == StageStatus::Ready)
+    }
+
+    /// Whether every critical stage started and its probe still passes
+    pub async fn is_ready(&self) -> bool {
+        if !self.is_started() {
+            return false;
+        }
+        let probes = self
+            .probes
+            .read()
+            .unwrap_or_else(PoisonError::into_inner)
+            .clone();
+        for probe in probes {
+            if !probe().await {
+                return false;
+            }
+        }
+        true
+    }
+
+    /// Updates the report of `name`
+    fn update(&self, name: &str, update: impl FnOnce(&mut StageReport)) {
+        let mut stages = self.stages.write().unwrap_or_else(PoisonError::into_inner);
+        if let Some(stage) = stages.iter_mut().find(|stage| stage.name == name) {
+            update(stage);
+        }
+    }
+}
+
+/// The stages of a process and the order they start in
+#[derive(Debug, Default)]
+pub struct Startup {
+    /// Stages in declaration order
+    stages: Vec<Stage>,
+    /// Readiness reported while and after the stages start
+    readiness: Arc<Readiness>,
+}
+
+impl Startup {
+    /// A startup without stages
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds a stage
+    #[must_use]
+    pub fn stage(mut self, stage: Stage) -> Self {
+        self.readiness
+            .stages
+            .write()
+            .unwrap_or_else(PoisonError::into_inner)
+            .push(StageReport {
+                name: stage.name.clone(),
+                critical: stage.critical,
+                status: StageStatus::Pending,
+                elapsed_ms: None,
+                error: None,
+            });
+        self.stages.push(stage);
+        self
+    }
+
+    /// Readiness of the process, to serve while and after it starts
+    #[must_use]
+    pub fn readiness(&self) -> Arc<Readiness> {
+        self.readiness.clone()
+    }
+
+    /// Initializes the stages in dependency order
+    ///
+    /// Stages without a dependency between them start in the order they were
+    /// declared.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the dependencies are unknown or form a cycle, or
+    /// if a critical stage did not start.
+    pub async fn run(self) -> Result<Arc<Readiness>, StartupError> {
+        let order = order(&self.stages)?;
+        let mut stages: Vec<Option<Stage>> = self.stages.into_iter().map(Some).collect();
+        let readiness = self.readiness;
+        {
+            let mut reports = readiness
+                .stages
+                .write()
+                .unwrap_or_else(PoisonError::into_inner);
+            let declared = std::mem::take(&mut *reports);
+            *reports = order.iter().map(|&index| declared[index].clone()).collect();
+        }
+
+        let mut started = HashSet::new();
+        for index in order {
+            let Some(stage) = stages[index].take() else {
+                continue;
+            };
+            let name = stage.name.clone();
+            let critical = stage.critical;
+            let missing = stage
+                .depends_on
+                .iter()
+                .find(|dependency| !started.contains(*dependency));
+            if let Some(missing) = missing {
+                let reason = format!("dependency '{missing}' did not start");
+                warn!(stage = %name, "Skipping startup stage: {}", reason);
+                readiness.update(&name, |report| {
+                    report.status = StageStatus::Skipped;
+                    report.error = Some(reason.clone());
+                });
+                if critical {
+                    return Err(StartupError::StageFailed {
+                        stage: name,
+                        reason,
+                    });
+                }
+                continue;
+            }
+
+            let probe = stage.probe.clone();
+            let (status, error, elapsed) = start(stage, &readiness).await;
+            readiness.update(&name, |report| {
+                report.status = status;
+                report.error.clone_from(&error);
+                report.elapsed_ms = Some(u64::try_from(elapsed.as_millis()).unwrap_or(u64::MAX));
+            });
+            match (status, error) {
+                (StageStatus::Ready, _) => {
+                    info!(stage = %name, "Startup stage ready after {:?}", elapsed);
+                    if let (true, Some(probe)) = (critical, probe) {
+                        readiness
+                            .probes
+                            .write()
+                            .unwrap_or_else(PoisonError::into_inner)
+                            .push(probe);
+                    }
+                    started.insert(name);
+                }
+                (_, error) => {
+                    let reason = error.unwrap_or_else(|| format!("{status:?}"));
+                    if critical {
+                        return Err(StartupError::StageFailed {
+                            stage: name,
+                            reason,
+                        });
+                    }
+                    warn!(stage = %name, "Optional startup stage did not start: {}", reason);
+                }
+            }
+        }
+        Ok(readiness)
+    }
+}
+
+/// Initializes `stage` and waits for its probe, within its timeout
+async fn start(stage: Stage, readiness: &Readiness) -> (StageStatus, Option<String>, Duration) {
+    let started = Instant::now();
+    readiness.update(&stage.name, |report| report.status = StageStatus::Starting);
+    let deadline = started + stage.timeout;
+    let (init, probe) = (stage.init, stage.probe);
+    let startup = async move {
+        init().await.map_err(|e| e.to_string())?;
+        if let Some(probe) = probe {
+            while !probe().await {
+                tokio::time::sleep(PROBE_INTERVAL).await;
+            }
+        }
+        Ok::<(), String>(())
+    };
+    let (status, error) = match tokio::time::timeout_at(deadline, startup).await {
+        Ok(Ok(())) => (StageStatus::Ready, None),
+        Ok(Err(e)) => (StageStatus::Failed, Some(e)),
+        Err(_) => (
+            StageStatus::TimedOut,
+            Some(format!("not ready within {:?}", stage.timeout)),
+        ),
+    };
+    (status, error, started.elapsed())
+}
+
+/// Indices of `stages` in dependency order, declaration order among peers
+fn order(stages: &[Stage]) -> Result<Vec<usize>, StartupError> {
+    let mut indices = HashMap::new();
+    for (index, stage) in stages.iter().enumerate() {
+        if indices.insert(stage.name.as_str(), index).is_some() {
+            return Err(StartupError::DuplicateStage(stage.name.clone()));
+        }
+    }
+
+    let mut waiting_on = vec![0usize; stages.len()];
+    let mut dependents = vec![Vec::new(); stages.len()];
+    for (index, stage) in stages.iter().enumerate() {
+        for dependency in &stage.depends_on {
+            let Some(&dependency) = indices.get(dependency.as_str()) else {
+                return Err(StartupError::UnknownDependency {
+                    stage: stage.name.clone(),
+                    dependency: dependency.clone(),
+                });
+            };
+            waiting_on[index] += 1;
+            dependents[dependency].push(index);
+        }
+    }
+
+    let mut order = Vec::with_capacity(stages.len());
+    let mut ready: VecDeque<usize> = (0..stages.len())
+        .filter(|&index| waiting_on[index] == 0)
+        .collect();
+    while let Some(index) = ready.pop_front() {
+        order.push(index);
+        for &dependent in &dependents[index] {
+            waiting_on[dependent] -= 1;
+            if waiting_on[dependent] == 0 {
+                ready.push_back(dependent);
+            }
+        }
+    }
+    if order.len() < stages.len() {
+        let cycle = (0..stages.len())
+            .filter(|&index| waiting_on[index] > 0)
+            .map(|index| stages[index].name.clone())
+            .collect();
+        return Err(StartupError::Cycle(cycle));
+    }
+    Ok(order)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::sync::atomic::{AtomicBool, Ordering};
+    use std::sync::Mutex;
+
+    /// A stage recording its name in `log` when it starts
+    fn logged(name: &'static str, log: &Arc<Mutex<Vec<&'static str>>>) -> Stage {
+        let log = log.clone();
+        Stage::new(name, move || async move {
+            log.lock().unwrap().push(name);
+            Ok(())
+        })
+    }
+
+    #[tokio::test]
+    async fn test_stages_start_in_dependency_order() {
+        let log = Arc::new(Mutex::new(Vec::new()));
+        let readiness = Startup::new()
+            .stage(
+                logged("app", &log)
+                    .depends_on("database")
+                    .depends_on("config"),
+            )
+            .stage(logged("database", &log).depends_on("config"))
+            .stage(logged("config", &log))
+            .run()
+            .await
+            .unwrap();
+        assert_eq!(*log.lock().unwrap(), vec!["config", "database", "app"]);
+        assert!(readiness.is_ready().await);
+
+        let cycle = Startup::new()
+            .stage(logged("a", &log).depends_on("b"))
+            .stage(logged("b", &log).depends_on("a"))
+            .run()
+            .await;
+        assert!(matches!(cycle, Err(StartupError::Cycle(stages)) if stages == ["a", "b"]));
+        let unknown = Startup::new()
+            .stage(logged("a", &log).depends_on("c"))
+            .run()
+            .await;
+        assert!(matches!(
+            unknown,
+            Err(StartupError::UnknownDependency { .. })
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_readiness_gates() {
+        let log = Arc::new(Mutex::new(Vec::new()));
+        let up = Arc::new(AtomicBool::new(true));
+        let probed = up.clone();
+        let startup = Startup::new()
+            .stage(logged("database", &log).with_probe(move || {
+                let up = probed.clone();
+                async move { up.load(Ordering::SeqCst) }
+            }))
+            .stage(
+                Stage::new("mcp", || async { Err::<(), StageError>("refused".into()) }).optional(),
+            )
+            .stage(logged("tools", &log).depends_on("mcp").optional());
+        let readiness = startup.readiness();
+        assert!(!readiness.is_started());
+        startup.run().await.unwrap();
+
+        let report = readiness.report();
+        let status = |name: &str| {
+            report
+                .iter()
+                .find(|stage| stage.name == name)
+                .unwrap()
+                .status
+        };
+        assert_eq!(status("database"), StageStatus::Ready);
+        assert_eq!(status("mcp"), StageStatus::Failed);
+        assert_eq!(status("tools"), StageStatus::Skipped);
+        assert!(readiness.is_ready().await);
+        up.store(false, Ordering::SeqCst);
+        assert!(!readiness.is_ready().await);
+
+        let slow = Startup::new()
+            .stage(
+                Stage::new("slow", || async { Ok(()) })
+                    .with_probe(|| async { false })
+                    .with_timeout(Duration::from_millis(20)),
+            )
+            .run()
+            .await;
+        assert!(matches!(slow, Err(StartupError::StageFailed { stage, .. }) if stage == "slow"));
+    }
+}
@@ -0,0 +1,408 @@
+//! Supervised background tasks
+//!
+//! Monitoring loops, schedulers and watchers run under a [`Supervisor`]
+//! instead of a bare `tokio::spawn`, where a failure or panic would end them
+//! without a trace. Each task has a name and a [`RestartPolicy`]: the
+//! supervisor restarts a task that failed after the policy's backoff, and
+//! marks it [`TaskState::Failed`] once the policy gives up. A task that ran
+//! for at least a minute before failing starts over with the shortest delay.
+//! Restarts are reported like retries, under the operation
+//! `task.<name>`.
+//!
+//! [`Supervisor::health`] reports the state of every task, and
+//! [`Supervisor::shutdown`] asks them to stop through their [`Shutdown`]
+//! handle and waits for them to finish.
+//!
+//! ```ignore
+//! let supervisor = Supervisor::new();
+//! supervisor.spawn("agents.monitor", RestartPolicy::default(), move |shutdown| {
+//!     let scheduler = scheduler.clone();
+//!     async move {
+//!         while !shutdown.is_requested() {
+//!             scheduler.reap_expired().await;
+//!             shutdown.sleep(period).await;
+//!         }
+//!         Ok(())
+//!     }
+//! });
+//! // ...
+//! supervisor.shutdown(Duration::from_secs(10)).await;
+//! ```
+
+use std::any::Any;
+use std::collections::BTreeMap;
+use std::future::Future;
+use std::panic::AssertUnwindSafe;
+use std::sync::{Arc, Mutex, MutexGuard, PoisonError};
+use std::time::Duration;
+
+use chrono::{DateTime, Utc};
+use futures::FutureExt;
+use serde::Serialize;
+use squirrel_core::retry::RetryPolicy;
+use tokio::sync::watch;
+use tokio::task::JoinHandle;
+use tokio::time::Instant;
+use tracing::{error, info, warn};
+
+/// Run time after which a failing task restarts with the shortest delay
+const STABLE_RUN: Duration = Duration::from_mins(1);
+
+/// Error a supervised task ends with
+pub type TaskError = Box<dyn std::error::Error + Send + Sync>;
+
+/// Result of one run of a supervised task
+pub type TaskResult = Result<(), TaskError>;
+
+/// When a supervised task is restarted
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RestartPolicy {
+    /// Never; a failed task stays failed
+    Never,
+    /// After it failed or panicked, as often and as late as the retry policy
+    /// allows
+    OnFailure(RetryPolicy),
+    /// Whenever it ended, also without an error
+    Always(RetryPolicy),
+}
+
+impl Default for RestartPolicy {
+    /// Restart after failures, one second apart and doubling up to a minute,
+    /// giving up after ten failures in a row
+    fn default() -> Self {
+        Self::OnFailure(
+            RetryPolicy::exponential(10, Duration::from_secs(1), Duration::from_mins(1))
+                .with_jitter(),
+        )
+    }
+}
+
+/// State of a supervised task
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TaskState {
+    /// The task is running
+    Running,
+    /// The task ended and waits to be restarted
+    Restarting,
+    /// The task ended without an error and is not restarted
+    Completed,
+    /// The task failed and is not restarted
+    Failed,
+    /// The task was stopped by a shutdown
+    Stopped,
+}
+
+/// Health of a supervised task, as reported by [`Supervisor::health`]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct TaskHealth {
+    /// Name of the task
+    pub name: String,
+    /// Current state
+    pub state: TaskState,
+    /// Times the task was restarted
+    pub restarts: u32,
+    /// Error of the last failed run
+    pub last_error: Option<String>,
+    /// When the current or last run started
+    pub started_at: DateTime<Utc>,
+}
+
+/// Handle a supervised task watches to stop when the supervisor shuts down
+#[derive(Debug, Clone)]
+pub struct Shutdown {
+    /// Set to `true` when the supervisor shuts down
+    receiver: watch::Receiver<bool>,
+}
+
+impl Shutdown {
+    /// Whether the task should stop
+    ///
+    /// Also true once the supervisor was dropped.
+    #[must_use]
+    pub fn is_requested(&self) -> bool {
+        *self.receiver.borrow() || self.receiver.has_changed().is_err()
+    }
+
+    /// Waits until the task should stop
+    pub async fn requested(&self) {
+        let mut receiver = self.receiver.clone();
+        // An error means the supervisor was dropped, which stops the task too
+        let _ = receiver.wait_for(|stop| *stop).await;
+    }
+
+    /// Sleeps for `duration`, or until the task should stop
+    ///
+    /// Returns whether the task should stop.
+    pub async fn sleep(&self, duration: Duration) -> bool {
+        tokio::select! {
+            () = tokio::time::sleep(duration) => self.is_requested(),
+            () = self.requested() => true,
+        }
+    }
+}
+
+/// A task owned by the supervisor
+#[derive(Debug)]
+struct SupervisedTask {
+    /// Health, updated by the supervising loop
+    health: Arc<Mutex<TaskHealth>>,
+    /// The supervising loop
+    handle: JoinHandle<()>,
+}
+
+/// Registry owning named background tasks
+///
+/// Dropping the supervisor asks its tasks to stop, without waiting for them.
+#[derive(Debug)]
+pub struct Supervisor {
+    /// Tasks by name
+    tasks: Mutex<BTreeMap<String, SupervisedTask>>,
+    /// Tells the tasks to stop
+    shutdown: watch::Sender<bool>,
+}
+
+impl Default for Supervisor {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Supervisor {
+    /// Creates a supervisor without tasks
+    #[must_use]
+    pub fn new() -> Self {
+        Self {
+            tasks: Mutex::new(BTreeMap::new()),
+            shutdown: watch::channel(false).0,
+        }
+    }
+
+    /// Spawns a task under `name`, restarting it as `policy` says
+    ///
+    /// `task` is called for every run with the [`Shutdown`] handle the run
+    /// should watch. A task spawned under the name of an existing one
+    /// replaces it; the old task is aborted.
+    pub fn spawn<F, Fut>(&self, name: impl Into<String>, policy: RestartPolicy, task: F)
+    where
+        F: Fn(Shutdown) -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = TaskResult> + Send + 'static,
+    {
+        let name = name.into();
+        let health = Arc::new(Mutex::new(TaskHealth {
+            name: name.clone(),
+            state: TaskState::Running,
+            restarts: 0,
+            last_error: None,
+            started_at: Utc::now(),
+        }));
+        let shutdown = Shutdown {
+            receiver: self.shutdown.subscribe(),
+        };
+        let handle = tokio::spawn(supervise(
+            name.clone(),
+            policy,
+            task,
+            shutdown,
+            health.clone(),
+        ));
+        if let Some(replaced) = lock(&self.tasks).insert(name, SupervisedTask { health, handle }) {
+            replaced.handle.abort();
+        }
+    }
+
+    /// Health of every task, by name
+    #[must_use]
+    pub fn health(&self) -> Vec<TaskHealth> {
+        lock(&self.tasks)
+            .values()
+            .map(|task| lock(&task.health).clone())
+            .collect()
+    }
+
+    /// Whether no task has failed for good
+    #[must_use]
+    pub fn is_healthy(&self) -> bool {
+        self.health()
+            .iter()
+            .all(|task| task.state != TaskState::Failed)
+    }
+
+    /// Asks every task to stop and waits up to `grace` for them to finish
+    ///
+    /// Tasks still running after `grace` are aborted; their names are
+    /// returned.
+    pub async fn shutdown(&self, grace: Duration) -> Vec<String> {
+        self.shutdown.send_replace(true);
+        let deadline = Instant::now() + grace;
+        let mut tasks = std::mem::take(&mut *lock(&self.tasks));
+        let mut aborted = Vec::new();
+        for (name, task) in &mut tasks {
+            if tokio::time::timeout_at(deadline, &mut task.handle).await.is_err() {
+                task.handle.abort();
+                lock(&task.health).state = TaskState::Stopped;
+                aborted.push(name.clone());
+            }
+        }
+        if !aborted.is_empty() {
+            warn!("Aborted background tasks still running after {:?}: {:?}", grace, aborted);
+        }
+        lock(&self.tasks).extend(tasks);
+        aborted
+    }
+}
+
+impl Drop for Supervisor {
+    fn drop(&mut self) {
+        self.shutdown.send_replace(true);
+    }
+}
+
+/// Runs `task` until it ends for good, restarting it as `policy` says
+async fn supervise<F, Fut>(
+    name: String,
+    policy: RestartPolicy,
+    task: F,
+    shutdown: Shutdown,
+    health: Arc<Mutex<TaskHealth>>,
+) where
+    F: Fn(Shutdown) -> Fut,
+    Fut: Future<Output = TaskResult>,
+{
+    let operation = format!("task.{name}");
+    let backoff = match policy {
+        RestartPolicy::Never => RetryPolicy::none(),
+        RestartPolicy::OnFailure(backoff) | RestartPolicy::Always(backoff) => backoff,
+    };
+    let mut retry = backoff.start(&operation);
+    loop {
+        let started = Instant::now();
+        {
+            let mut health = lock(&health);
+            health.state = TaskState::Running;
+            health.started_at = Utc::now();
+        }
+        let error = match AssertUnwindSafe(task(shutdown.clone())).catch_unwind().await {
+            Ok(Ok(())) => None,
+            Ok(Err(e)) => Some(e.to_string()),
+            Err(panic) => Some(format!("panicked: {}", panic_message(&*panic))),
+        };
+        if shutdown.is_requested() {
+            lock(&health).state = TaskState::Stopped;
+            return;
+        }
+        if let Some(error) = &error {
+            warn!(task = %name, "Background task failed: {}", error);
+            lock(&health).last_error = Some(error.clone());
+        }
+
+        let restart = match policy {
+            RestartPolicy::Never => false,
+            RestartPolicy::OnFailure(_) => error.is_some(),
+            RestartPolicy::Always(_) => true,
+        };
+        if !restart {
+            let state = if error.is_some() {
+                retry.stopped();
+                TaskState::Failed
+            } else {
+                retry.succeeded();
+                TaskState::Completed
+            };
+            info!(task = %name, "Background task ended: {:?}", state);
+            lock(&health).state = state;
+            return;
+        }
+
+        if started.elapsed() >= STABLE_RUN {
+            retry = backoff.start(&operation);
+        }
+        let Some(delay) = retry.next_delay() else {
+            error!(task = %name, "Background task ended too often; not restarting it");
+            lock(&health).state = TaskState::Failed;
+            return;
+        };
+        {
+            let mut health = lock(&health);
+            health.state = TaskState::Restarting;
+            health.restarts += 1;
+        }
+        if shutdown.sleep(delay).await {
+            lock(&health).state = TaskState::Stopped;
+            return;
+        }
+    }
+}
+
+/// Message of a caught panic
+pub(crate) fn panic_message(panic: &(dyn Any + Send)) -> &str {
+    panic
+        .downcast_ref::<&str>()
+        .copied()
+        .or_else(|| panic.downcast_ref::<String>().map(String::as_str))
+        .unwrap_or("unknown panic")
+}
+
+/// Locks `mutex`, ignoring poisoning
+pub(crate) fn lock<T>(mutex: &Mutex<T>) -> MutexGuard<'_, T> {
+    mutex.lock().unwrap_or_else(PoisonError::into_inner)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::sync::atomic::{AtomicU32, Ordering};
+
+    #[tokio::test]
+    async fn test_failed_tasks_restart_until_the_policy_gives_up() {
+        let supervisor = Supervisor::new();
+        let runs = Arc::new(AtomicU32::new(0));
+        let counted = runs.clone();
+        let policy = RestartPolicy::OnFailure(RetryPolicy::fixed(3, Duration::from_millis(1)));
+        supervisor.spawn("flaky", policy, move |_| {
+            let runs = counted.clone();
+            async move {
+                assert!(runs.fetch_add(1, Ordering::SeqCst) != 0, "first run");
+                Err::<(), TaskError>("still broken".into())
+            }
+        });
+
+        let given_up = async {
+            while supervisor.health()[0].state != TaskState::Failed {
+                tokio::time::sleep(Duration::from_millis(1)).await;
+            }
+        };
+        assert!(
+            tokio::time::timeout(Duration::from_secs(10), given_up).await.is_ok(),
+            "the supervisor gives up on the task"
+        );
+        let health = supervisor.health();
+        assert_eq!(runs.load(Ordering::SeqCst), 3);
+        assert_eq!(health[0].state, TaskState::Failed);
+        assert_eq!(health[0].restarts, 2);
+        assert_eq!(health[0].last_error.as_deref(), Some("still broken"));
+        assert!(!supervisor.is_healthy());
+    }
+
+    #[tokio::test]
+    async fn test_shutdown_stops_and_joins_tasks() {
+        let supervisor = Supervisor::new();
+        supervisor.spawn("loop", RestartPolicy::default(), |shutdown| async move {
+            while !shutdown.sleep(Duration::from_millis(5)).await {}
+            Ok(())
+        });
+        supervisor.spawn("stubborn", RestartPolicy::Never, |_| async {
+            tokio::time::sleep(Duration::from_mins(1)).await;
+            Ok(())
+        });
+        assert!(supervisor.is_healthy());
+
+        let aborted = supervisor.shutdown(Duration::from_millis(50)).await;
+        assert_eq!(aborted, vec!["stubborn".to_string()]);
+        assert!(supervisor
+            .health()
+            .iter()
+            .all(|task| task.state == TaskState::Stopped));
+    }
+}
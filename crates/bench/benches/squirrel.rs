@@ -0,0 +1,164 @@
+//! Criterion benchmarks of Squirrel hot paths
+//!
+//! Run with `cargo bench -p squirrel-bench`, then compare against the stored
+//! baseline with `squirrel bench`.
+
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use criterion::{black_box, criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
+use serde_json::json;
+use squirrel_commands::{Command, CommandRegistry, CommandResult};
+use squirrel_context::ContextState;
+use squirrel_mcp::tool::{Tool, ToolManager};
+use squirrel_monitoring::metrics::{DefaultMetricCollector, Metric, MetricType};
+use squirrel_test_utils::MockToolExecutor;
+use tokio::runtime::Runtime;
+
+/// Command that echoes its arguments
+#[derive(Clone)]
+struct EchoCommand {
+    name: String,
+}
+
+impl Command for EchoCommand {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn description(&self) -> &str {
+        "Echoes its arguments"
+    }
+
+    fn execute(&self, args: &[String]) -> CommandResult<String> {
+        Ok(args.join(" "))
+    }
+
+    fn parser(&self) -> clap::Command {
+        clap::Command::new("echo")
+    }
+
+    fn clone_box(&self) -> Box<dyn Command> {
+        Box::new(self.clone())
+    }
+}
+
+fn command_registry_dispatch(c: &mut Criterion) {
+    let mut group = c.benchmark_group("command_registry");
+    let args = vec!["hello".to_string(), "world".to_string()];
+    for commands in [1, 100] {
+        let registry = CommandRegistry::new();
+        for index in 0..commands {
+            let name = format!("echo-{index}");
+            registry
+                .register(&name, Arc::new(EchoCommand { name: name.clone() }))
+                .unwrap();
+        }
+        group.bench_with_input(BenchmarkId::new("dispatch", commands), &registry, |b, registry| {
+            b.iter(|| registry.execute(black_box("echo-0"), black_box(&args)).unwrap());
+        });
+    }
+    group.finish();
+}
+
+fn tool_execution_overhead(c: &mut Criterion) {
+    let rt = Runtime::new().unwrap();
+    let manager = ToolManager::new();
+    rt.block_on(async {
+        let tool = Tool::builder().id("bench").name("Bench tool").build();
+        let executor = MockToolExecutor::builder("bench")
+            .on("noop", |e| e.returns(json!({"ok": true})))
+            .build();
+        manager.register_tool(tool, executor).await.unwrap();
+    });
+
+    let mut group = c.benchmark_group("tool_manager");
+    group.bench_function("execute_noop", |b| {
+        b.iter(|| {
+            rt.block_on(manager.execute_tool("bench", "noop", json!({"input": 1}), Some("bench".to_string())))
+                .unwrap()
+        });
+    });
+    group.finish();
+}
+
+fn context_snapshot_diff(c: &mut Criterion) {
+    let mut group = c.benchmark_group("context");
+    for keys in [10, 1_000] {
+        let data: HashMap<String, String> = (0..keys).map(|i| (format!("key-{i}"), format!("value-{i}"))).collect();
+        let mut state = ContextState::with_data(data);
+        let before = state.create_snapshot();
+        for i in (0..keys).step_by(10) {
+            state.set(format!("key-{i}"), "changed".to_string());
+        }
+        let after = state.create_snapshot();
+
+        group.throughput(Throughput::Elements(keys));
+        group.bench_with_input(BenchmarkId::new("snapshot", keys), &state, |b, state| {
+            b.iter(|| state.create_snapshot());
+        });
+        group.bench_with_input(BenchmarkId::new("diff", keys), &(before, after), |b, (before, after)| {
+            b.iter(|| before.diff(after));
+        });
+    }
+    group.finish();
+}
+
+fn metric_recording_throughput(c: &mut Criterion) {
+    let rt = Runtime::new().unwrap();
+    let mut collector = DefaultMetricCollector::new();
+    rt.block_on(collector.initialize_with_config(Default::default())).unwrap();
+    let labels = HashMap::from([("tool".to_string(), "bench".to_string())]);
+
+    let mut group = c.benchmark_group("metrics");
+    group.throughput(Throughput::Elements(100));
+    group.bench_function("record_100", |b| {
+        b.iter(|| {
+            rt.block_on(async {
+                for i in 0..100 {
+                    let metric = Metric::new(
+                        "tool_execution_time_ms",
+                        f64::from(i),
+                        MetricType::Gauge,
+                        labels.clone(),
+                    );
+                    collector.record_metric(metric).await.unwrap();
+                }
+            });
+        });
+    });
+
+    // Tool executions record from every runtime worker at once
+    let threads = std::thread::available_parallelism().map_or(4, std::num::NonZeroUsize::get);
+    group.throughput(Throughput::Elements(100 * threads as u64));
+    group.bench_function(BenchmarkId::new("record_100_per_thread", threads), |b| {
+        b.iter(|| {
+            std::thread::scope(|scope| {
+                for _ in 0..threads {
+                    scope.spawn(|| {
+                        for i in 0..100 {
+                            let metric = Metric::new(
+                                "tool_execution_time_ms",
+                                f64::from(i),
+                                MetricType::Gauge,
+                                labels.clone(),
+                            );
+                            collector.record(metric).unwrap();
+                        }
+                    });
+                }
+            });
+            rt.block_on(collector.flush());
+        });
+    });
+    group.finish();
+}
+
+criterion_group!(
+    benches,
+    command_registry_dispatch,
+    tool_execution_overhead,
+    context_snapshot_diff,
+    metric_recording_throughput
+);
+criterion_main!(benches);
@@ -0,0 +1,350 @@
+//! Benchmark results, baselines and comparisons
+//!
+//! Results are the mean time per iteration of each benchmark in
+//! nanoseconds, keyed by `<group>/<benchmark>` as criterion names them. A
+//! baseline is a results file saved from an earlier run.
+
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt;
+use std::path::{Path, PathBuf};
+
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+/// Default allowed slowdown before a benchmark counts as regressed
+pub const DEFAULT_THRESHOLD_PCT: f64 = 10.0;
+
+/// Errors reading or writing benchmark results
+#[derive(Debug, Error)]
+pub enum BaselineError {
+    /// A file could not be read or written
+    #[error("I/O error on {path}: {source}")]
+    Io {
+        /// The file
+        path: PathBuf,
+        /// The underlying error
+        source: std::io::Error,
+    },
+    /// A file is not valid JSON of the expected shape
+    #[error("Invalid benchmark data in {path}: {source}")]
+    Json {
+        /// The file
+        path: PathBuf,
+        /// The underlying error
+        source: serde_json::Error,
+    },
+    /// No criterion results were found
+    #[error("No criterion results found in {0}")]
+    NoResults(PathBuf),
+}
+
+/// Mean time per iteration of each benchmark
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct BenchmarkResults {
+    /// Mean nanoseconds per iteration by benchmark name
+    pub benchmarks: BTreeMap<String, f64>,
+}
+
+/// Part of criterion's `estimates.json` used here
+#[derive(Deserialize)]
+struct Estimates {
+    mean: Estimate,
+}
+
+#[derive(Deserialize)]
+struct Estimate {
+    point_estimate: f64,
+}
+
+impl BenchmarkResults {
+    /// Loads results saved with [`BenchmarkResults::save`]
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the file cannot be read or parsed.
+    pub fn load(path: &Path) -> Result<Self, BaselineError> {
+        let contents = std::fs::read_to_string(path).map_err(|source| BaselineError::Io {
+            path: path.to_path_buf(),
+            source,
+        })?;
+        serde_json::from_str(&contents).map_err(|source| BaselineError::Json {
+            path: path.to_path_buf(),
+            source,
+        })
+    }
+
+    /// Saves the results as pretty JSON, creating parent directories
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the file cannot be written.
+    pub fn save(&self, path: &Path) -> Result<(), BaselineError> {
+        let io_error = |source| BaselineError::Io {
+            path: path.to_path_buf(),
+            source,
+        };
+        if let Some(parent) = path.parent().filter(|parent| !parent.as_os_str().is_empty()) {
+            std::fs::create_dir_all(parent).map_err(io_error)?;
+        }
+        let json = serde_json::to_string_pretty(self).map_err(|source| BaselineError::Json {
+            path: path.to_path_buf(),
+            source,
+        })?;
+        std::fs::write(path, json + "\n").map_err(io_error)
+    }
+
+    /// Reads the latest results from a criterion output directory,
+    /// usually `target/criterion`
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if an estimates file cannot be read or parsed, or if
+    /// the directory holds no results.
+    pub fn from_criterion_dir(dir: &Path) -> Result<Self, BaselineError> {
+        let mut results = Self::default();
+        collect_estimates(dir, dir, &mut results.benchmarks)?;
+        if results.benchmarks.is_empty() {
+            return Err(BaselineError::NoResults(dir.to_path_buf()));
+        }
+        Ok(results)
+    }
+}
+
+/// Adds the `new/estimates.json` results found under `dir`
+fn collect_estimates(root: &Path, dir: &Path, benchmarks: &mut BTreeMap<String, f64>) -> Result<(), BaselineError> {
+    let io_error = |source| BaselineError::Io {
+        path: dir.to_path_buf(),
+        source,
+    };
+    let estimates = dir.join("new").join("estimates.json");
+    if estimates.is_file() {
+        let contents = std::fs::read_to_string(&estimates).map_err(io_error)?;
+        let parsed: Estimates = serde_json::from_str(&contents).map_err(|source| BaselineError::Json {
+            path: estimates.clone(),
+            source,
+        })?;
+        let name = dir
+            .strip_prefix(root)
+            .unwrap_or(dir)
+            .components()
+            .map(|component| component.as_os_str().to_string_lossy())
+            .collect::<Vec<_>>()
+            .join("/");
+        benchmarks.insert(name, parsed.mean.point_estimate);
+        return Ok(());
+    }
+
+    for entry in std::fs::read_dir(dir).map_err(io_error)? {
+        let path = entry.map_err(io_error)?.path();
+        // Criterion keeps its HTML report next to the benchmark directories
+        if path.is_dir() && path.file_name().is_some_and(|name| name != "report") {
+            collect_estimates(root, &path, benchmarks)?;
+        }
+    }
+    Ok(())
+}
+
+/// How a benchmark changed against the baseline
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ChangeKind {
+    /// Slower by more than the threshold
+    Regressed,
+    /// Faster by more than the threshold
+    Improved,
+    /// Within the threshold
+    Unchanged,
+    /// Not in the baseline
+    New,
+    /// In the baseline but not in the current results
+    Missing,
+}
+
+/// Comparison of one benchmark with its baseline
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct BenchmarkChange {
+    /// Benchmark name
+    pub name: String,
+    /// Baseline mean in nanoseconds
+    pub baseline_ns: Option<f64>,
+    /// Current mean in nanoseconds
+    pub current_ns: Option<f64>,
+    /// Change of the mean in percent; positive is slower
+    pub change_pct: Option<f64>,
+    /// Classification of the change
+    pub kind: ChangeKind,
+}
+
+/// Comparison of a run with a baseline
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ComparisonReport {
+    /// Allowed change in percent
+    pub threshold_pct: f64,
+    /// One entry per benchmark, sorted by name
+    pub changes: Vec<BenchmarkChange>,
+}
+
+impl ComparisonReport {
+    /// Benchmarks slower than the threshold allows
+    pub fn regressions(&self) -> impl Iterator<Item = &BenchmarkChange> {
+        self.changes
+            .iter()
+            .filter(|change| change.kind == ChangeKind::Regressed)
+    }
+
+    /// Whether any benchmark regressed
+    #[must_use]
+    pub fn has_regressions(&self) -> bool {
+        self.regressions().next().is_some()
+    }
+}
+
+impl fmt::Display for ComparisonReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let nanos = |value: Option<f64>| value.map_or_else(|| "-".to_string(), format_nanos);
+        writeln!(
+            f,
+            "{:<50} {:>12} {:>12} {:>9}  STATUS",
+            "BENCHMARK", "BASELINE", "CURRENT", "CHANGE"
+        )?;
+        for change in &self.changes {
+            let pct = change
+                .change_pct
+                .map_or_else(|| "-".to_string(), |pct| format!("{pct:+.1}%"));
+            writeln!(
+                f,
+                "{:<50} {:>12} {:>12} {:>9}  {:?}",
+                change.name,
+                nanos(change.baseline_ns),
+                nanos(change.current_ns),
+                pct,
+                change.kind
+            )?;
+        }
+        let regressions = self.regressions().count();
+        write!(
+            f,
+            "{regressions} regression(s) beyond {:.1}% across {} benchmark(s)",
+            self.threshold_pct,
+            self.changes.len()
+        )
+    }
+}
+
+/// Nanoseconds in the largest unit that keeps the value above one
+fn format_nanos(nanos: f64) -> String {
+    if nanos >= 1e9 {
+        format!("{:.2} s", nanos / 1e9)
+    } else if nanos >= 1e6 {
+        format!("{:.2} ms", nanos / 1e6)
+    } else if nanos >= 1e3 {
+        format!("{:.2} µs", nanos / 1e3)
+    } else {
+        format!("{nanos:.1} ns")
+    }
+}
+
+/// Compares `current` with `baseline`; benchmarks more than `threshold_pct`
+/// percent slower are regressions
+#[must_use]
+pub fn compare(baseline: &BenchmarkResults, current: &BenchmarkResults, threshold_pct: f64) -> ComparisonReport {
+    let names: BTreeSet<&String> = baseline
+        .benchmarks
+        .keys()
+        .chain(current.benchmarks.keys())
+        .collect();
+    let changes = names
+        .into_iter()
+        .map(|name| {
+            let baseline_ns = baseline.benchmarks.get(name).copied();
+            let current_ns = current.benchmarks.get(name).copied();
+            let (change_pct, kind) = match (baseline_ns, current_ns) {
+                (Some(old), Some(new)) if old > 0.0 => {
+                    let pct = (new - old) / old * 100.0;
+                    let kind = if pct > threshold_pct {
+                        ChangeKind::Regressed
+                    } else if pct < -threshold_pct {
+                        ChangeKind::Improved
+                    } else {
+                        ChangeKind::Unchanged
+                    };
+                    (Some(pct), kind)
+                }
+                (Some(_), Some(_)) => (None, ChangeKind::Unchanged),
+                (None, _) => (None, ChangeKind::New),
+                (Some(_), None) => (None, ChangeKind::Missing),
+            };
+            BenchmarkChange {
+                name: name.clone(),
+                baseline_ns,
+                current_ns,
+                change_pct,
+                kind,
+            }
+        })
+        .collect();
+    ComparisonReport {
+        threshold_pct,
+        changes,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn results(entries: &[(&str, f64)]) -> BenchmarkResults {
+        BenchmarkResults {
+            benchmarks: entries.iter().map(|(name, ns)| ((*name).to_string(), *ns)).collect(),
+        }
+    }
+
+    #[test]
+    fn test_compare() {
+        let baseline = results(&[("a/fast", 100.0), ("a/slow", 100.0), ("a/same", 100.0), ("a/gone", 5.0)]);
+        let current = results(&[("a/fast", 50.0), ("a/slow", 125.0), ("a/same", 105.0), ("a/new", 1.0)]);
+        let report = compare(&baseline, &current, DEFAULT_THRESHOLD_PCT);
+
+        let kinds: Vec<(&str, ChangeKind)> = report
+            .changes
+            .iter()
+            .map(|change| (change.name.as_str(), change.kind))
+            .collect();
+        assert_eq!(
+            kinds,
+            vec![
+                ("a/fast", ChangeKind::Improved),
+                ("a/gone", ChangeKind::Missing),
+                ("a/new", ChangeKind::New),
+                ("a/same", ChangeKind::Unchanged),
+                ("a/slow", ChangeKind::Regressed),
+            ]
+        );
+        assert!(report.has_regressions());
+        assert!(report.to_string().contains("1 regression(s)"));
+    }
+
+    #[test]
+    fn test_criterion_dir_and_baseline_file() {
+        let dir = tempfile::tempdir().unwrap();
+        let bench = dir.path().join("registry").join("dispatch").join("new");
+        std::fs::create_dir_all(&bench).unwrap();
+        std::fs::create_dir_all(dir.path().join("report")).unwrap();
+        std::fs::write(
+            bench.join("estimates.json"),
+            r#"{"mean": {"point_estimate": 1234.5, "standard_error": 1.0}, "median": {"point_estimate": 1200.0}}"#,
+        )
+        .unwrap();
+
+        let results = BenchmarkResults::from_criterion_dir(dir.path()).unwrap();
+        assert_eq!(results, self::results(&[("registry/dispatch", 1234.5)]));
+
+        let path = dir.path().join("baselines").join("main.json");
+        results.save(&path).unwrap();
+        assert_eq!(BenchmarkResults::load(&path).unwrap(), results);
+        assert!(matches!(
+            BenchmarkResults::from_criterion_dir(&dir.path().join("baselines")),
+            Err(BaselineError::NoResults(_))
+        ));
+    }
+}
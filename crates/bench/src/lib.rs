@@ -0,0 +1,12 @@
+//! Benchmarks and performance baselines for Squirrel
+//!
+//! The criterion benchmarks in `benches/` cover command dispatch, tool
+//! execution overhead, context snapshots and metric recording. This library
+//! reads their results and compares them with a stored baseline so that
+//! `squirrel bench` can report regressions.
+
+/// Benchmark results, baselines and comparisons
+pub mod baseline;
+pub use baseline::{
+    compare, BaselineError, BenchmarkChange, BenchmarkResults, ChangeKind, ComparisonReport,
+};
@@ -0,0 +1,104 @@
+//! BED genomic interval files
+
+use std::collections::BTreeMap;
+use std::io::BufRead;
+
+use serde::Serialize;
+
+use super::{BioError, IssueCollector, LengthStats, LineReader};
+
+/// Summary statistics for a BED file
+#[derive(Debug, Clone, Serialize)]
+pub struct BedStats {
+    /// Number of intervals
+    pub records: u64,
+    /// Interval length distribution
+    pub lengths: LengthStats,
+    /// Number of columns in the first interval
+    pub columns: usize,
+    /// Number of intervals per chromosome
+    pub per_chromosome: BTreeMap<String, u64>,
+}
+
+/// Whether a line carries no interval (header, comment or blank)
+fn is_header(line: &str) -> bool {
+    line.trim().is_empty()
+        || line.starts_with('#')
+        || line.starts_with("track")
+        || line.starts_with("browser")
+}
+
+/// Parse the chromosome, start and end of an interval
+fn parse_interval(line: &str) -> Result<(&str, u64, u64), String> {
+    let mut fields = line.split('\t');
+    let (Some(chrom), Some(start), Some(end)) = (fields.next(), fields.next(), fields.next()) else {
+        return Err("expected at least 3 tab-separated columns".to_string());
+    };
+    let start: u64 = start
+        .parse()
+        .map_err(|_| format!("invalid start coordinate '{}'", start))?;
+    let end: u64 = end.parse().map_err(|_| format!("invalid end coordinate '{}'", end))?;
+    if start > end {
+        return Err(format!("start {} is greater than end {}", start, end));
+    }
+    Ok((chrom, start, end))
+}
+
+/// Compute summary statistics for a BED stream
+pub fn stats<R: BufRead>(reader: R) -> Result<BedStats, BioError> {
+    let mut lines = LineReader::new(reader);
+    let mut lengths = LengthStats::default();
+    let mut per_chromosome = BTreeMap::new();
+    let mut columns = 0;
+
+    while let Some((line_no, line)) = lines.next_line()? {
+        if is_header(line) {
+            continue;
+        }
+        let (chrom, start, end) =
+            parse_interval(line).map_err(|message| BioError::Parse { line: line_no, message })?;
+        if columns == 0 {
+            columns = line.split('\t').count();
+        }
+        lengths.push(end - start);
+        *per_chromosome.entry(chrom.to_string()).or_insert(0) += 1;
+    }
+
+    Ok(BedStats {
+        records: lengths.count,
+        lengths,
+        columns,
+        per_chromosome,
+    })
+}
+
+/// Validate a BED stream, returning the number of records read
+///
+/// Reports missing columns, non-numeric coordinates, inverted intervals and
+/// an inconsistent number of columns between lines.
+pub fn validate<R: BufRead>(reader: R, issues: &mut IssueCollector) -> Result<u64, BioError> {
+    let mut lines = LineReader::new(reader);
+    let mut records = 0;
+    let mut columns = None;
+
+    while let Some((line_no, line)) = lines.next_line()? {
+        if is_header(line) {
+            continue;
+        }
+        records += 1;
+        if let Err(message) = parse_interval(line) {
+            issues.add(line_no, message);
+            continue;
+        }
+        let count = line.split('\t').count();
+        match columns {
+            None => columns = Some(count),
+            Some(expected) if expected != count => {
+                issues.add(line_no, format!("expected {} columns, found {}", expected, count));
+            }
+            Some(_) => {}
+        }
+    }
+
+    Ok(records)
+}
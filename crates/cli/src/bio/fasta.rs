@@ -0,0 +1,189 @@
+//! FASTA sequence files
+
+use std::collections::HashSet;
+use std::io::BufRead;
+
+use serde::Serialize;
+
+use super::{count_bases, gc_fraction, BioError, IssueCollector, LengthStats, LineReader};
+
+/// Number of longest sequences reported individually
+const TOP_CONTIGS: usize = 20;
+
+/// Summary of a single sequence
+#[derive(Debug, Clone, Serialize)]
+pub struct ContigSummary {
+    /// Sequence identifier (first word of the header)
+    pub name: String,
+    /// Sequence length
+    pub length: u64,
+    /// Fraction of G/C bases
+    pub gc_content: f64,
+}
+
+/// Summary statistics for a FASTA file
+#[derive(Debug, Clone, Serialize)]
+pub struct FastaStats {
+    /// Number of sequences
+    pub records: u64,
+    /// Sequence length distribution
+    pub lengths: LengthStats,
+    /// N50 of sequence lengths
+    pub n50: u64,
+    /// Overall fraction of G/C bases
+    pub gc_content: f64,
+    /// Number of ambiguous (non-ACGTU) positions
+    pub ambiguous_bases: u64,
+    /// The longest sequences, longest first
+    pub contigs: Vec<ContigSummary>,
+}
+
+/// Sequence currently being read
+struct Record {
+    name: String,
+    line: u64,
+    length: u64,
+    gc: u64,
+    at: u64,
+}
+
+impl Record {
+    fn new(header: &str, line: u64) -> Self {
+        Self {
+            name: header.split_whitespace().next().unwrap_or_default().to_string(),
+            line,
+            length: 0,
+            gc: 0,
+            at: 0,
+        }
+    }
+}
+
+/// Compute summary statistics for a FASTA stream
+pub fn stats<R: BufRead>(reader: R) -> Result<FastaStats, BioError> {
+    let mut lengths = LengthStats::default();
+    let mut all_lengths = Vec::new();
+    let mut contigs = Vec::new();
+    let (mut gc, mut at) = (0, 0);
+
+    let mut finish = |record: Record| {
+        lengths.push(record.length);
+        all_lengths.push(record.length);
+        gc += record.gc;
+        at += record.at;
+        contigs.push(ContigSummary {
+            name: record.name,
+            length: record.length,
+            gc_content: gc_fraction(record.gc, record.at),
+        });
+        // Keep memory bounded by trimming the candidate list periodically
+        if contigs.len() >= TOP_CONTIGS * 4 {
+            contigs.sort_by_key(|c| std::cmp::Reverse(c.length));
+            contigs.truncate(TOP_CONTIGS);
+        }
+    };
+
+    let mut lines = LineReader::new(reader);
+    let mut current: Option<Record> = None;
+    while let Some((line_no, line)) = lines.next_line()? {
+        if let Some(header) = line.strip_prefix('>') {
+            if let Some(record) = current.take() {
+                finish(record);
+            }
+            current = Some(Record::new(header, line_no));
+        } else if !line.trim().is_empty() {
+            let record = current.as_mut().ok_or_else(|| BioError::Parse {
+                line: line_no,
+                message: "sequence data before first header".to_string(),
+            })?;
+            let sequence = line.trim().as_bytes();
+            let (g, a) = count_bases(sequence);
+            record.length += sequence.len() as u64;
+            record.gc += g;
+            record.at += a;
+        }
+    }
+    if let Some(record) = current.take() {
+        finish(record);
+    }
+
+    contigs.sort_by_key(|c| std::cmp::Reverse(c.length));
+    contigs.truncate(TOP_CONTIGS);
+
+    Ok(FastaStats {
+        records: lengths.count,
+        n50: n50(&mut all_lengths),
+        ambiguous_bases: lengths.total - gc - at,
+        gc_content: gc_fraction(gc, at),
+        lengths,
+        contigs,
+    })
+}
+
+/// Validate a FASTA stream, returning the number of records read
+///
+/// Reports sequence data before the first header, empty headers, duplicate
+/// identifiers, empty records and characters outside the IUPAC alphabet.
+pub fn validate<R: BufRead>(reader: R, issues: &mut IssueCollector) -> Result<u64, BioError> {
+    let mut lines = LineReader::new(reader);
+    let mut seen = HashSet::new();
+    let mut records = 0;
+    let mut current: Option<Record> = None;
+
+    while let Some((line_no, line)) = lines.next_line()? {
+        if let Some(header) = line.strip_prefix('>') {
+            if let Some(record) = current.take() {
+                check_record(&record, issues);
+            }
+            records += 1;
+            let record = Record::new(header, line_no);
+            if record.name.is_empty() {
+                issues.add(line_no, "empty sequence identifier");
+            } else if !seen.insert(record.name.clone()) {
+                issues.add(line_no, format!("duplicate sequence identifier '{}'", record.name));
+            }
+            current = Some(record);
+        } else if !line.trim().is_empty() {
+            let Some(record) = current.as_mut() else {
+                issues.add(line_no, "sequence data before first header");
+                continue;
+            };
+            let sequence = line.trim();
+            if let Some(invalid) = sequence.chars().find(|c| !is_sequence_char(*c)) {
+                issues.add(line_no, format!("invalid sequence character '{}'", invalid));
+            }
+            record.length += sequence.len() as u64;
+        }
+    }
+    if let Some(record) = current.take() {
+        check_record(&record, issues);
+    }
+
+    Ok(records)
+}
+
+/// Report problems that can only be detected once a record is complete
+fn check_record(record: &Record, issues: &mut IssueCollector) {
+    if record.length == 0 {
+        issues.add(record.line, format!("sequence '{}' is empty", record.name));
+    }
+}
+
+/// Whether a character is a valid nucleotide or amino-acid code
+fn is_sequence_char(c: char) -> bool {
+    c.is_ascii_alphabetic() || matches!(c, '*' | '-' | '.')
+}
+
+/// Compute the N50 of a set of lengths
+fn n50(lengths: &mut [u64]) -> u64 {
+    lengths.sort_unstable_by(|a, b| b.cmp(a));
+    let total: u64 = lengths.iter().sum();
+    let mut running = 0;
+    for length in lengths.iter() {
+        running += length;
+        if running * 2 >= total {
+            return *length;
+        }
+    }
+    0
+}
@@ -0,0 +1,171 @@
+//! FASTQ sequencing read files
+
+use std::collections::BTreeMap;
+use std::io::BufRead;
+
+use serde::Serialize;
+
+use super::{count_bases, gc_fraction, BioError, IssueCollector, LengthStats, LineReader};
+
+/// Offset of Phred quality scores in Sanger/Illumina 1.8+ encoding
+const PHRED_OFFSET: u8 = 33;
+
+/// Summary statistics for a FASTQ file
+#[derive(Debug, Clone, Serialize)]
+pub struct FastqStats {
+    /// Number of reads
+    pub records: u64,
+    /// Total number of bases
+    pub bases: u64,
+    /// Read length distribution
+    pub lengths: LengthStats,
+    /// Fraction of G/C bases
+    pub gc_content: f64,
+    /// Mean base quality
+    pub mean_quality: f64,
+    /// Fraction of bases with quality of at least 30
+    pub q30_fraction: f64,
+    /// Number of bases observed at each Phred quality score
+    pub quality_distribution: BTreeMap<u8, u64>,
+}
+
+/// A single four-line FASTQ record
+struct Record {
+    line: u64,
+    header: String,
+    sequence: String,
+    separator: String,
+    quality: String,
+}
+
+/// Read the next record, or `None` at end of input
+///
+/// Blank lines between records are skipped. A truncated record is returned
+/// with the missing lines left empty, and `truncated` is set.
+fn next_record<R: BufRead>(
+    lines: &mut LineReader<R>,
+    truncated: &mut bool,
+) -> Result<Option<Record>, BioError> {
+    let (line, header) = loop {
+        match lines.next_line()? {
+            Some((_, l)) if l.trim().is_empty() => continue,
+            Some((n, l)) => break (n, l.to_string()),
+            None => return Ok(None),
+        }
+    };
+    let mut rest = [String::new(), String::new(), String::new()];
+    for slot in &mut rest {
+        match lines.next_line()? {
+            Some((_, l)) => *slot = l.to_string(),
+            None => {
+                *truncated = true;
+                break;
+            }
+        }
+    }
+    let [sequence, separator, quality] = rest;
+    Ok(Some(Record {
+        line,
+        header,
+        sequence,
+        separator,
+        quality,
+    }))
+}
+
+/// Compute summary statistics for a FASTQ stream
+pub fn stats<R: BufRead>(reader: R) -> Result<FastqStats, BioError> {
+    let mut lines = LineReader::new(reader);
+    let mut lengths = LengthStats::default();
+    let mut quality_distribution = BTreeMap::new();
+    let (mut gc, mut at) = (0, 0);
+    let (mut quality_sum, mut q30) = (0u64, 0u64);
+    let mut truncated = false;
+
+    while let Some(record) = next_record(&mut lines, &mut truncated)? {
+        if !record.header.starts_with('@') {
+            return Err(BioError::Parse {
+                line: record.line,
+                message: "record header does not start with '@'".to_string(),
+            });
+        }
+        if truncated {
+            return Err(BioError::Parse {
+                line: lines.line(),
+                message: "truncated record at end of file".to_string(),
+            });
+        }
+
+        let (g, a) = count_bases(record.sequence.as_bytes());
+        gc += g;
+        at += a;
+        lengths.push(record.sequence.len() as u64);
+
+        for byte in record.quality.bytes() {
+            let score = byte.saturating_sub(PHRED_OFFSET);
+            *quality_distribution.entry(score).or_insert(0) += 1;
+            quality_sum += u64::from(score);
+            if score >= 30 {
+                q30 += 1;
+            }
+        }
+    }
+
+    let scored: u64 = quality_distribution.values().sum();
+    let ratio = |n: u64| if scored == 0 { 0.0 } else { n as f64 / scored as f64 };
+    Ok(FastqStats {
+        records: lengths.count,
+        bases: lengths.total,
+        gc_content: gc_fraction(gc, at),
+        mean_quality: ratio(quality_sum),
+        q30_fraction: ratio(q30),
+        lengths,
+        quality_distribution,
+    })
+}
+
+/// Validate a FASTQ stream, returning the number of records read
+///
+/// Reports malformed headers and separators, truncated records, sequence
+/// and quality strings of different lengths, and quality characters outside
+/// the Phred+33 range.
+pub fn validate<R: BufRead>(reader: R, issues: &mut IssueCollector) -> Result<u64, BioError> {
+    let mut lines = LineReader::new(reader);
+    let mut records = 0;
+    let mut truncated = false;
+
+    while let Some(record) = next_record(&mut lines, &mut truncated)? {
+        records += 1;
+        let line = record.line;
+        if !record.header.starts_with('@') {
+            issues.add(line, "record header does not start with '@'");
+        } else if record.header.len() == 1 {
+            issues.add(line, "empty read identifier");
+        }
+        if truncated {
+            issues.add(lines.line(), "truncated record at end of file");
+            break;
+        }
+        if !record.separator.starts_with('+') {
+            issues.add(line + 2, "separator line does not start with '+'");
+        }
+        if let Some(invalid) = record.sequence.chars().find(|c| !c.is_ascii_alphabetic() && *c != '.') {
+            issues.add(line + 1, format!("invalid sequence character '{}'", invalid));
+        }
+        if record.sequence.len() != record.quality.len() {
+            issues.add(
+                line + 3,
+                format!(
+                    "quality length {} does not match sequence length {}",
+                    record.quality.len(),
+                    record.sequence.len()
+                ),
+            );
+        }
+        if record.quality.bytes().any(|b| !(PHRED_OFFSET..=b'~').contains(&b)) {
+            issues.add(line + 3, "quality string contains characters outside Phred+33 range");
+        }
+    }
+
+    Ok(records)
+}
@@ -0,0 +1,159 @@
+//! GFF3 genome annotation files
+
+use std::collections::{BTreeMap, HashSet};
+use std::io::BufRead;
+
+use serde::Serialize;
+
+use super::{BioError, IssueCollector, LineReader};
+
+/// Summary statistics for a GFF3 file
+#[derive(Debug, Clone, Serialize)]
+pub struct GffStats {
+    /// Number of features
+    pub records: u64,
+    /// Version declared by the `##gff-version` directive
+    pub gff_version: Option<String>,
+    /// Number of features of each type
+    pub feature_types: BTreeMap<String, u64>,
+    /// Number of features per sequence
+    pub per_seqid: BTreeMap<String, u64>,
+    /// Whether the file embeds sequences in a `##FASTA` section
+    pub has_fasta: bool,
+}
+
+/// Lines of interest in a GFF3 stream
+enum GffLine<'a> {
+    /// A `##` directive, without the leading hashes
+    Directive(&'a str),
+    /// A comment or blank line
+    Skip,
+    /// The start of the embedded FASTA section
+    Fasta,
+    /// A feature line split into columns
+    Feature(Vec<&'a str>),
+}
+
+fn classify(line: &str) -> GffLine<'_> {
+    if line.starts_with("##FASTA") || line.starts_with('>') {
+        GffLine::Fasta
+    } else if let Some(directive) = line.strip_prefix("##") {
+        GffLine::Directive(directive)
+    } else if line.starts_with('#') || line.trim().is_empty() {
+        GffLine::Skip
+    } else {
+        GffLine::Feature(line.split('\t').collect())
+    }
+}
+
+/// Compute summary statistics for a GFF3 stream
+pub fn stats<R: BufRead>(reader: R) -> Result<GffStats, BioError> {
+    let mut lines = LineReader::new(reader);
+    let mut stats = GffStats {
+        records: 0,
+        gff_version: None,
+        feature_types: BTreeMap::new(),
+        per_seqid: BTreeMap::new(),
+        has_fasta: false,
+    };
+
+    while let Some((line_no, line)) = lines.next_line()? {
+        match classify(line) {
+            GffLine::Directive(directive) => {
+                if let Some(version) = directive.strip_prefix("gff-version") {
+                    stats.gff_version = Some(version.trim().to_string());
+                }
+            }
+            GffLine::Skip => {}
+            GffLine::Fasta => {
+                stats.has_fasta = true;
+                break;
+            }
+            GffLine::Feature(fields) => {
+                if fields.len() != 9 {
+                    return Err(BioError::Parse {
+                        line: line_no,
+                        message: format!("expected 9 columns, found {}", fields.len()),
+                    });
+                }
+                stats.records += 1;
+                *stats.per_seqid.entry(fields[0].to_string()).or_insert(0) += 1;
+                *stats.feature_types.entry(fields[2].to_string()).or_insert(0) += 1;
+            }
+        }
+    }
+
+    Ok(stats)
+}
+
+/// Validate a GFF3 stream, returning the number of records read
+///
+/// Reports a missing version directive, wrong column counts, invalid
+/// coordinates, strands and phases, malformed attributes and duplicate IDs.
+pub fn validate<R: BufRead>(reader: R, issues: &mut IssueCollector) -> Result<u64, BioError> {
+    let mut lines = LineReader::new(reader);
+    let mut records = 0;
+    let mut seen_version = false;
+    let mut ids = HashSet::new();
+
+    while let Some((line_no, line)) = lines.next_line()? {
+        let fields = match classify(line) {
+            GffLine::Directive(directive) => {
+                if directive.starts_with("gff-version") {
+                    seen_version = true;
+                }
+                continue;
+            }
+            GffLine::Skip => continue,
+            GffLine::Fasta => break,
+            GffLine::Feature(fields) => fields,
+        };
+
+        records += 1;
+        if !seen_version {
+            issues.add(line_no, "feature before ##gff-version directive");
+            seen_version = true;
+        }
+        if fields.len() != 9 {
+            issues.add(line_no, format!("expected 9 columns, found {}", fields.len()));
+            continue;
+        }
+
+        match (fields[3].parse::<u64>(), fields[4].parse::<u64>()) {
+            (Ok(0), Ok(_)) => issues.add(line_no, "coordinates are 1-based; start must be at least 1"),
+            (Ok(start), Ok(end)) if start > end => {
+                issues.add(line_no, format!("start {} is greater than end {}", start, end));
+            }
+            (Ok(_), Ok(_)) => {}
+            _ => issues.add(line_no, "start and end must be positive integers"),
+        }
+
+        if fields[5] != "." && fields[5].parse::<f64>().is_err() {
+            issues.add(line_no, format!("invalid score '{}'", fields[5]));
+        }
+        if !matches!(fields[6], "+" | "-" | "." | "?") {
+            issues.add(line_no, format!("invalid strand '{}'", fields[6]));
+        }
+        match fields[7] {
+            "0" | "1" | "2" => {}
+            "." if fields[2] == "CDS" => issues.add(line_no, "CDS feature is missing a phase"),
+            "." => {}
+            phase => issues.add(line_no, format!("invalid phase '{}'", phase)),
+        }
+
+        for attribute in fields[8].split(';').filter(|a| !a.is_empty() && *a != ".") {
+            match attribute.split_once('=') {
+                Some(("ID", id)) => {
+                    // Multi-line features (e.g. CDS) legitimately repeat their ID
+                    if !ids.insert((id.to_string(), fields[2].to_string())) && fields[2] != "CDS" {
+                        issues.add(line_no, format!("duplicate ID '{}'", id));
+                    }
+                }
+                Some((key, _)) if !key.is_empty() => {}
+                _ => issues.add(line_no, format!("malformed attribute '{}'", attribute)),
+            }
+        }
+    }
+
+    Ok(records)
+}
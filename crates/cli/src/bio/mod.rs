@@ -0,0 +1,389 @@
+//! Streaming inspection of bioinformatics file formats
+//!
+//! This module provides summary statistics and validation for FASTA, FASTQ,
+//! VCF, BED and GFF3 files. Every parser reads its input line by line so that
+//! large (and optionally gzip-compressed) files can be inspected without
+//! loading them into memory.
+
+pub mod bed;
+pub mod fasta;
+pub mod fastq;
+pub mod gff;
+pub mod vcf;
+
+use std::fs::File;
+use std::io::{self, BufRead, BufReader, Read};
+use std::path::Path;
+
+use flate2::read::MultiGzDecoder;
+use serde::Serialize;
+use thiserror::Error;
+
+/// Errors that can occur while inspecting a file
+#[derive(Debug, Error)]
+pub enum BioError {
+    /// The file could not be read
+    #[error("I/O error: {0}")]
+    Io(#[from] io::Error),
+
+    /// The file is too malformed to summarize
+    #[error("Parse error at line {line}: {message}")]
+    Parse {
+        /// One-based line number
+        line: u64,
+        /// Description of the problem
+        message: String,
+    },
+
+    /// The file format could not be determined
+    #[error("Cannot determine file format for '{0}'; pass --format")]
+    UnknownFormat(String),
+}
+
+/// Supported file formats
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, clap::ValueEnum)]
+#[serde(rename_all = "lowercase")]
+pub enum BioFormat {
+    /// Nucleotide or protein sequences
+    Fasta,
+    /// Sequencing reads with base qualities
+    Fastq,
+    /// Variant calls
+    Vcf,
+    /// Genomic intervals
+    Bed,
+    /// Genome annotations (GFF3)
+    Gff,
+}
+
+impl BioFormat {
+    /// Detect the format from a file name, ignoring a trailing `.gz`
+    pub fn detect(path: &Path) -> Option<Self> {
+        let name = path.file_name()?.to_str()?.to_ascii_lowercase();
+        let name = name
+            .strip_suffix(".gz")
+            .or_else(|| name.strip_suffix(".bgz"))
+            .unwrap_or(&name);
+        let extension = name.rsplit_once('.')?.1;
+        match extension {
+            "fa" | "fasta" | "fna" | "ffn" | "faa" | "frn" | "fas" => Some(Self::Fasta),
+            "fq" | "fastq" => Some(Self::Fastq),
+            "vcf" => Some(Self::Vcf),
+            "bed" => Some(Self::Bed),
+            "gff" | "gff3" => Some(Self::Gff),
+            _ => None,
+        }
+    }
+}
+
+/// A single problem found during validation
+#[derive(Debug, Clone, Serialize)]
+pub struct ValidationIssue {
+    /// One-based line number
+    pub line: u64,
+    /// Description of the problem
+    pub message: String,
+}
+
+/// Result of validating a file
+#[derive(Debug, Clone, Serialize)]
+pub struct ValidationReport {
+    /// Format that was validated
+    pub format: BioFormat,
+    /// Whether no issues were found
+    pub valid: bool,
+    /// Number of records read
+    pub records: u64,
+    /// Total number of issues found
+    pub issue_count: u64,
+    /// The first issues found, up to the configured maximum
+    pub issues: Vec<ValidationIssue>,
+}
+
+/// Collects validation issues, keeping only the first `max` of them
+#[derive(Debug)]
+pub struct IssueCollector {
+    /// Maximum number of issues to keep
+    max: usize,
+    /// Total number of issues seen
+    count: u64,
+    /// Issues kept so far
+    issues: Vec<ValidationIssue>,
+}
+
+impl IssueCollector {
+    /// Create a collector keeping at most `max` issues
+    pub fn new(max: usize) -> Self {
+        Self {
+            max,
+            count: 0,
+            issues: Vec::new(),
+        }
+    }
+
+    /// Record an issue
+    pub fn add(&mut self, line: u64, message: impl Into<String>) {
+        self.count += 1;
+        if self.issues.len() < self.max {
+            self.issues.push(ValidationIssue {
+                line,
+                message: message.into(),
+            });
+        }
+    }
+
+    /// Finish collection and build a report
+    pub fn into_report(self, format: BioFormat, records: u64) -> ValidationReport {
+        ValidationReport {
+            format,
+            valid: self.count == 0,
+            records,
+            issue_count: self.count,
+            issues: self.issues,
+        }
+    }
+}
+
+/// Summary statistics for any supported format
+#[derive(Debug, Clone, Serialize)]
+#[serde(tag = "format", rename_all = "lowercase")]
+pub enum BioStats {
+    /// FASTA statistics
+    Fasta(fasta::FastaStats),
+    /// FASTQ statistics
+    Fastq(fastq::FastqStats),
+    /// VCF statistics
+    Vcf(vcf::VcfStats),
+    /// BED statistics
+    Bed(bed::BedStats),
+    /// GFF statistics
+    Gff(gff::GffStats),
+}
+
+/// Reads lines one at a time while tracking the line number
+pub(crate) struct LineReader<R> {
+    /// Underlying reader
+    inner: R,
+    /// Reusable line buffer
+    buf: String,
+    /// Number of the last line read
+    line: u64,
+}
+
+impl<R: BufRead> LineReader<R> {
+    /// Wrap a buffered reader
+    pub(crate) fn new(inner: R) -> Self {
+        Self {
+            inner,
+            buf: String::new(),
+            line: 0,
+        }
+    }
+
+    /// Read the next line and its number, without the line terminator
+    pub(crate) fn next_line(&mut self) -> io::Result<Option<(u64, &str)>> {
+        self.buf.clear();
+        if self.inner.read_line(&mut self.buf)? == 0 {
+            return Ok(None);
+        }
+        self.line += 1;
+        Ok(Some((self.line, self.buf.trim_end_matches(['\n', '\r']))))
+    }
+
+    /// Number of the last line read
+    pub(crate) fn line(&self) -> u64 {
+        self.line
+    }
+}
+
+/// Open a file for streaming, transparently decompressing gzip input
+pub fn open(path: &Path) -> io::Result<Box<dyn BufRead>> {
+    let mut file = File::open(path)?;
+    let mut magic = [0u8; 2];
+    let is_gzip = file.read(&mut magic)? == 2 && magic == [0x1f, 0x8b];
+    drop(file);
+
+    let file = File::open(path)?;
+    if is_gzip {
+        Ok(Box::new(BufReader::new(MultiGzDecoder::new(file))))
+    } else {
+        Ok(Box::new(BufReader::new(file)))
+    }
+}
+
+/// Compute summary statistics for a file in the given format
+pub fn summarize<R: BufRead>(format: BioFormat, reader: R) -> Result<BioStats, BioError> {
+    Ok(match format {
+        BioFormat::Fasta => BioStats::Fasta(fasta::stats(reader)?),
+        BioFormat::Fastq => BioStats::Fastq(fastq::stats(reader)?),
+        BioFormat::Vcf => BioStats::Vcf(vcf::stats(reader)?),
+        BioFormat::Bed => BioStats::Bed(bed::stats(reader)?),
+        BioFormat::Gff => BioStats::Gff(gff::stats(reader)?),
+    })
+}
+
+/// Validate a file in the given format, keeping at most `max_issues` issues
+pub fn validate<R: BufRead>(
+    format: BioFormat,
+    reader: R,
+    max_issues: usize,
+) -> Result<ValidationReport, BioError> {
+    let mut issues = IssueCollector::new(max_issues);
+    let records = match format {
+        BioFormat::Fasta => fasta::validate(reader, &mut issues)?,
+        BioFormat::Fastq => fastq::validate(reader, &mut issues)?,
+        BioFormat::Vcf => vcf::validate(reader, &mut issues)?,
+        BioFormat::Bed => bed::validate(reader, &mut issues)?,
+        BioFormat::Gff => gff::validate(reader, &mut issues)?,
+    };
+    Ok(issues.into_report(format, records))
+}
+
+/// Running minimum, maximum and total of a sequence of lengths
+#[derive(Debug, Clone, Copy, Default, Serialize)]
+pub struct LengthStats {
+    /// Number of values
+    pub count: u64,
+    /// Sum of values
+    pub total: u64,
+    /// Smallest value
+    pub min: u64,
+    /// Largest value
+    pub max: u64,
+    /// Mean value
+    pub mean: f64,
+}
+
+impl LengthStats {
+    /// Add a value
+    pub(crate) fn push(&mut self, value: u64) {
+        if self.count == 0 || value < self.min {
+            self.min = value;
+        }
+        self.max = self.max.max(value);
+        self.count += 1;
+        self.total += value;
+        self.mean = self.total as f64 / self.count as f64;
+    }
+}
+
+/// Fraction of G/C among unambiguous A/C/G/T bases
+pub(crate) fn gc_fraction(gc: u64, at: u64) -> f64 {
+    if gc + at == 0 {
+        0.0
+    } else {
+        gc as f64 / (gc + at) as f64
+    }
+}
+
+/// Count G/C and A/T bases in a sequence
+pub(crate) fn count_bases(sequence: &[u8]) -> (u64, u64) {
+    let mut gc = 0;
+    let mut at = 0;
+    for base in sequence {
+        match base.to_ascii_uppercase() {
+            b'G' | b'C' => gc += 1,
+            b'A' | b'T' | b'U' => at += 1,
+            _ => {}
+        }
+    }
+    (gc, at)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+
+    fn reader(text: &str) -> Cursor<Vec<u8>> {
+        Cursor::new(text.as_bytes().to_vec())
+    }
+
+    #[test]
+    fn test_detect_format() {
+        assert_eq!(BioFormat::detect(Path::new("reads.fq.gz")), Some(BioFormat::Fastq));
+        assert_eq!(BioFormat::detect(Path::new("ref.FASTA")), Some(BioFormat::Fasta));
+        assert_eq!(BioFormat::detect(Path::new("calls.vcf.bgz")), Some(BioFormat::Vcf));
+        assert_eq!(BioFormat::detect(Path::new("genes.gff3")), Some(BioFormat::Gff));
+        assert_eq!(BioFormat::detect(Path::new("notes.txt")), None);
+    }
+
+    #[test]
+    fn test_fasta_stats_and_validation() {
+        let data = ">chr1 first\nACGTACGT\nGGCC\n>chr2\nAT\n";
+        let stats = fasta::stats(reader(data)).unwrap();
+        assert_eq!(stats.records, 2);
+        assert_eq!(stats.lengths.total, 14);
+        assert_eq!(stats.lengths.max, 12);
+        assert_eq!(stats.n50, 12);
+        assert_eq!(stats.contigs[0].name, "chr1");
+
+        let report = validate(BioFormat::Fasta, reader(">a\nAC!T\n>a\n"), 10).unwrap();
+        assert!(!report.valid);
+        assert_eq!(report.issue_count, 3);
+    }
+
+    #[test]
+    fn test_fastq_stats_and_validation() {
+        let data = "@r1\nACGT\n+\nIIII\n@r2\nGG\n+\n!!\n";
+        let stats = fastq::stats(reader(data)).unwrap();
+        assert_eq!(stats.records, 2);
+        assert_eq!(stats.bases, 6);
+        assert_eq!(stats.quality_distribution.get(&40), Some(&4));
+        assert_eq!(stats.quality_distribution.get(&0), Some(&2));
+
+        let report = validate(BioFormat::Fastq, reader("@r1\nACGT\n+\nII\n"), 10).unwrap();
+        assert!(!report.valid);
+        assert!(fastq::stats(reader("r1\nACGT\n+\nIIII\n")).is_err());
+    }
+
+    #[test]
+    fn test_vcf_stats_and_validation() {
+        let data = "##fileformat=VCFv4.2\n\
+                    #CHROM\tPOS\tID\tREF\tALT\tQUAL\tFILTER\tINFO\tFORMAT\tS1\n\
+                    1\t10\t.\tA\tG\t50\tPASS\t.\tGT\t0/1\n\
+                    1\t20\t.\tC\tA,CT\t.\tq10\t.\tGT\t1/1\n\
+                    2\t5\t.\tAT\tA\t.\tPASS\t.\tGT\t0/1\n";
+        let stats = vcf::stats(reader(data)).unwrap();
+        assert_eq!(stats.records, 3);
+        assert_eq!(stats.samples, vec!["S1".to_string()]);
+        assert_eq!(stats.variant_types.snv, 2);
+        assert_eq!(stats.variant_types.insertion, 1);
+        assert_eq!(stats.variant_types.deletion, 1);
+        assert_eq!(stats.transitions, 1);
+        assert_eq!(stats.transversions, 1);
+        assert_eq!(stats.passed, 2);
+
+        let unsorted = "##fileformat=VCFv4.2\n#CHROM\tPOS\tID\tREF\tALT\tQUAL\tFILTER\tINFO\n\
+                        1\t20\t.\tA\tG\t.\tPASS\t.\n1\t10\t.\tA\tG\t.\tPASS\t.\n";
+        let report = validate(BioFormat::Vcf, reader(unsorted), 10).unwrap();
+        assert_eq!(report.issue_count, 1);
+    }
+
+    #[test]
+    fn test_bed_stats_and_validation() {
+        let data = "track name=test\nchr1\t0\t100\nchr1\t200\t250\nchr2\t10\t20\n";
+        let stats = bed::stats(reader(data)).unwrap();
+        assert_eq!(stats.records, 3);
+        assert_eq!(stats.lengths.total, 160);
+        assert_eq!(stats.per_chromosome.get("chr1"), Some(&2));
+
+        let report = validate(BioFormat::Bed, reader("chr1\t50\t10\nchr1\tx\n"), 10).unwrap();
+        assert_eq!(report.issue_count, 2);
+    }
+
+    #[test]
+    fn test_gff_stats_and_validation() {
+        let data = "##gff-version 3\n\
+                    ctg1\tsrc\tgene\t1\t100\t.\t+\t.\tID=g1\n\
+                    ctg1\tsrc\tCDS\t1\t90\t.\t+\t0\tParent=g1\n";
+        let stats = gff::stats(reader(data)).unwrap();
+        assert_eq!(stats.records, 2);
+        assert_eq!(stats.feature_types.get("gene"), Some(&1));
+        assert_eq!(stats.gff_version.as_deref(), Some("3"));
+
+        let bad = "##gff-version 3\nctg1\tsrc\tCDS\t10\t5\t.\t*\t.\tID=c1\n";
+        let report = validate(BioFormat::Gff, reader(bad), 10).unwrap();
+        assert_eq!(report.issue_count, 3);
+    }
+}
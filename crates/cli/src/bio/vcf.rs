@@ -0,0 +1,235 @@
+//! VCF variant call files
+
+use std::collections::{BTreeMap, HashSet};
+use std::io::BufRead;
+
+use serde::Serialize;
+
+use super::{BioError, IssueCollector, LineReader};
+
+/// Number of fixed columns before the FORMAT column
+const FIXED_COLUMNS: usize = 8;
+
+/// Variant counts by type, counted per ALT allele
+#[derive(Debug, Clone, Copy, Default, Serialize)]
+pub struct VariantTypes {
+    /// Single-nucleotide variants
+    pub snv: u64,
+    /// Multi-nucleotide substitutions
+    pub mnv: u64,
+    /// Insertions
+    pub insertion: u64,
+    /// Deletions
+    pub deletion: u64,
+    /// Symbolic, breakend or complex alleles
+    pub other: u64,
+}
+
+/// Summary statistics for a VCF file
+#[derive(Debug, Clone, Serialize)]
+pub struct VcfStats {
+    /// Number of records
+    pub records: u64,
+    /// VCF version from the `##fileformat` header
+    pub file_format: Option<String>,
+    /// Sample names from the header line
+    pub samples: Vec<String>,
+    /// Counts by variant type
+    pub variant_types: VariantTypes,
+    /// Transition SNVs (A<->G, C<->T)
+    pub transitions: u64,
+    /// Transversion SNVs
+    pub transversions: u64,
+    /// Transition/transversion ratio, if any transversions were seen
+    pub ts_tv_ratio: Option<f64>,
+    /// Records with FILTER of PASS or `.`
+    pub passed: u64,
+    /// Records failing at least one filter
+    pub filtered: u64,
+    /// Number of records per chromosome
+    pub per_chromosome: BTreeMap<String, u64>,
+}
+
+/// Kind of change an ALT allele represents
+enum AlleleKind {
+    Snv { transition: bool },
+    Mnv,
+    Insertion,
+    Deletion,
+    Other,
+}
+
+fn classify(reference: &str, alt: &str) -> AlleleKind {
+    let is_bases = |s: &str| !s.is_empty() && s.bytes().all(|b| b"ACGTNacgtn".contains(&b));
+    if !is_bases(reference) || !is_bases(alt) {
+        return AlleleKind::Other;
+    }
+    match (reference.len(), alt.len()) {
+        (1, 1) => {
+            let pair = (reference.as_bytes()[0].to_ascii_uppercase(), alt.as_bytes()[0].to_ascii_uppercase());
+            AlleleKind::Snv {
+                transition: matches!(pair, (b'A', b'G') | (b'G', b'A') | (b'C', b'T') | (b'T', b'C')),
+            }
+        }
+        (r, a) if r == a => AlleleKind::Mnv,
+        (r, a) if r < a => AlleleKind::Insertion,
+        _ => AlleleKind::Deletion,
+    }
+}
+
+/// Compute summary statistics for a VCF stream
+pub fn stats<R: BufRead>(reader: R) -> Result<VcfStats, BioError> {
+    let mut lines = LineReader::new(reader);
+    let mut stats = VcfStats {
+        records: 0,
+        file_format: None,
+        samples: Vec::new(),
+        variant_types: VariantTypes::default(),
+        transitions: 0,
+        transversions: 0,
+        ts_tv_ratio: None,
+        passed: 0,
+        filtered: 0,
+        per_chromosome: BTreeMap::new(),
+    };
+
+    while let Some((line_no, line)) = lines.next_line()? {
+        if let Some(meta) = line.strip_prefix("##") {
+            if let Some(version) = meta.strip_prefix("fileformat=") {
+                stats.file_format = Some(version.to_string());
+            }
+            continue;
+        }
+        if line.starts_with("#CHROM") {
+            stats.samples = line.split('\t').skip(FIXED_COLUMNS + 1).map(String::from).collect();
+            continue;
+        }
+        if line.trim().is_empty() {
+            continue;
+        }
+
+        let fields: Vec<&str> = line.split('\t').collect();
+        if fields.len() < FIXED_COLUMNS {
+            return Err(BioError::Parse {
+                line: line_no,
+                message: format!("expected at least {} columns, found {}", FIXED_COLUMNS, fields.len()),
+            });
+        }
+
+        stats.records += 1;
+        *stats.per_chromosome.entry(fields[0].to_string()).or_insert(0) += 1;
+        if matches!(fields[6], "PASS" | ".") {
+            stats.passed += 1;
+        } else {
+            stats.filtered += 1;
+        }
+
+        let types = &mut stats.variant_types;
+        for alt in fields[4].split(',').filter(|a| *a != ".") {
+            match classify(fields[3], alt) {
+                AlleleKind::Snv { transition } => {
+                    types.snv += 1;
+                    if transition {
+                        stats.transitions += 1;
+                    } else {
+                        stats.transversions += 1;
+                    }
+                }
+                AlleleKind::Mnv => types.mnv += 1,
+                AlleleKind::Insertion => types.insertion += 1,
+                AlleleKind::Deletion => types.deletion += 1,
+                AlleleKind::Other => types.other += 1,
+            }
+        }
+    }
+
+    if stats.transversions > 0 {
+        stats.ts_tv_ratio = Some(stats.transitions as f64 / stats.transversions as f64);
+    }
+    Ok(stats)
+}
+
+/// Validate a VCF stream, returning the number of records read
+///
+/// Reports a missing `##fileformat` line or `#CHROM` header, wrong column
+/// counts, invalid positions and alleles, and records that are not sorted by
+/// position within a chromosome.
+pub fn validate<R: BufRead>(reader: R, issues: &mut IssueCollector) -> Result<u64, BioError> {
+    let mut lines = LineReader::new(reader);
+    let mut records = 0;
+    let mut seen_fileformat = false;
+    let mut columns: Option<usize> = None;
+    let mut last: Option<(String, u64)> = None;
+    let mut finished_chromosomes = HashSet::new();
+
+    while let Some((line_no, line)) = lines.next_line()? {
+        if line.starts_with("##") {
+            if line_no == 1 && line.starts_with("##fileformat=VCF") {
+                seen_fileformat = true;
+            }
+            continue;
+        }
+        if line.starts_with("#CHROM") {
+            if !seen_fileformat {
+                issues.add(line_no, "missing ##fileformat line at start of file");
+            }
+            columns = Some(line.split('\t').count());
+            continue;
+        }
+        if line.trim().is_empty() {
+            continue;
+        }
+
+        records += 1;
+        let Some(expected) = columns else {
+            issues.add(line_no, "record before #CHROM header line");
+            columns = Some(0);
+            continue;
+        };
+
+        let fields: Vec<&str> = line.split('\t').collect();
+        if fields.len() < FIXED_COLUMNS || (expected > 0 && fields.len() != expected) {
+            issues.add(
+                line_no,
+                format!("expected {} columns, found {}", expected.max(FIXED_COLUMNS), fields.len()),
+            );
+            continue;
+        }
+
+        let chrom = fields[0];
+        let Ok(pos) = fields[1].parse::<u64>() else {
+            issues.add(line_no, format!("invalid position '{}'", fields[1]));
+            continue;
+        };
+
+        match &last {
+            Some((last_chrom, last_pos)) if last_chrom == chrom && pos < *last_pos => {
+                issues.add(line_no, format!("position {} on {} is not sorted", pos, chrom));
+            }
+            Some((last_chrom, _)) if last_chrom == chrom => {}
+            Some((last_chrom, _)) => {
+                finished_chromosomes.insert(last_chrom.clone());
+                if finished_chromosomes.contains(chrom) {
+                    issues.add(line_no, format!("records for {} are not contiguous", chrom));
+                }
+            }
+            None => {}
+        }
+        last = Some((chrom.to_string(), pos));
+
+        if fields[3].is_empty() || !fields[3].bytes().all(|b| b"ACGTNacgtn".contains(&b)) {
+            issues.add(line_no, format!("invalid REF allele '{}'", fields[3]));
+        }
+        if fields[4].is_empty() {
+            issues.add(line_no, "empty ALT field");
+        }
+        if fields[5] != "." && fields[5].parse::<f64>().is_err() {
+            issues.add(line_no, format!("invalid QUAL '{}'", fields[5]));
+        }
+    }
+
+    if columns.is_none() && records == 0 {
+        issues.add(lines.line(), "missing #CHROM header line");
+    }
+    Ok(records)
+}
@@ -0,0 +1,644 @@
+//! Admin command
+//!
+//! Lists and ends sessions, turns plugins and degraded mode on and off,
+//! attaches command policies to users and API keys, compacts the web database, rotates the access token signing keys and
+//! shows the runtime configuration, working on the web database like the
+//! server's `/api/admin` endpoints. Every subcommand needs the access token
+//! of a user with the admin role and is written to the admin audit log.
+
+use std::path::Path;
+use std::sync::Arc;
+
+use clap::{Arg, ArgAction, ArgMatches, Command as ClapCommand};
+use serde_json::json;
+use squirrel_commands::policy::{CommandPolicy, PolicyScope, PolicySubject};
+use squirrel_commands::{Command, CommandError};
+use squirrel_web::admin::{self, AdminControls, AdminPolicy, AuditEntry, SqlAdminStore};
+use squirrel_web::api::error::AppError;
+use squirrel_web::auth::models::Role;
+use squirrel_web::auth::{AuthConfig, AuthService};
+use squirrel_web::config::{AdminConfig, Config};
+use squirrel_web::db::SqlitePool;
+use squirrel_web::migrations;
+use uuid::Uuid;
+
+use super::database::database_url;
+
+/// Environment variable holding the admin's access token
+const ADMIN_TOKEN_ENV: &str = "SQUIRREL_ADMIN_TOKEN";
+
+/// Environment variable naming the server's configuration file
+const CONFIG_ENV: &str = "SQUIRREL_CONFIG";
+
+/// Default number of audit log entries shown
+const DEFAULT_AUDIT_LIMIT: usize = 20;
+
+/// Admin command implementation
+#[derive(Debug, Clone, Default)]
+pub struct AdminCommand;
+
+impl AdminCommand {
+    /// Create a new admin command
+    pub fn new() -> Self {
+        Self
+    }
+
+    /// Runs the subcommand against the web database
+    async fn run(matches: &ArgMatches) -> Result<String, CommandError> {
+        let operator = Operator::connect(matches).await?;
+        let controls = operator.controls();
+        let Operator { pool, auth, actor, is_admin } = operator;
+        let json = matches.get_flag("json");
+
+        let output = match matches.subcommand() {
+            Some(("sessions", sub)) => {
+                let user_id = uuid_arg(sub, "user-id")?;
+                let target = user_id.map(|id| id.to_string());
+                let sessions = controls
+                    .audited(&actor, is_admin, "sessions.list", target.as_deref(), async {
+                        auth.list_all_sessions(user_id).await.map_err(auth_error)
+                    })
+                    .await
+                    .map_err(admin_error)?;
+                if json {
+                    return to_json(&sessions);
+                }
+                if sessions.is_empty() {
+                    return Ok("No active sessions".to_string());
+                }
+                sessions
+                    .iter()
+                    .map(|session| {
+                        format!(
+                            "{}  user {}  {}  last used {}",
+                            session.id,
+                            session.user_id,
+                            session.device,
+                            session.last_used_at.to_rfc3339()
+                        )
+                    })
+                    .collect::<Vec<_>>()
+                    .join("\n")
+            }
+            Some(("kick", sub)) => {
+                let id = uuid_arg(sub, "session")?.unwrap_or_default();
+                controls
+                    .audited(&actor, is_admin, "sessions.kick", Some(&id.to_string()), async {
+                        if auth.kick_session(id).await.map_err(auth_error)? {
+                            Ok(())
+                        } else {
+                            Err(AppError::NotFound(format!("No active session {id}")))
+                        }
+                    })
+                    .await
+                    .map_err(admin_error)?;
+                format!("Ended session {id}")
+            }
+            Some(("plugins", _)) => {
+                let flags = controls
+                    .audited(&actor, is_admin, "plugins.list", None, controls.refresh())
+                    .await
+                    .map_err(admin_error)?;
+                if json {
+                    return to_json(&flags.disabled_plugins);
+                }
+                if flags.disabled_plugins.is_empty() {
+                    return Ok("No plugins are disabled".to_string());
+                }
+                flags
+                    .disabled_plugins
+                    .iter()
+                    .map(|plugin| {
+                        format!(
+                            "{}  by {} at {}{}",
+                            plugin.name,
+                            plugin.disabled_by,
+                            plugin.disabled_at.to_rfc3339(),
+                            plugin.reason.as_ref().map(|reason| format!(": {reason}")).unwrap_or_default()
+                        )
+                    })
+                    .collect::<Vec<_>>()
+                    .join("\n")
+            }
+            Some(("disable-plugin", sub)) => {
+                let name = sub.get_one::<String>("name").cloned().unwrap_or_default();
+                let reason = sub.get_one::<String>("reason").cloned();
+                let plugin = controls
+                    .audited(&actor, is_admin, "plugins.disable", Some(&name), controls.disable_plugin(&actor, &name, reason))
+                    .await
+                    .map_err(admin_error)?;
+                if json {
+                    return to_json(&plugin);
+                }
+                format!("Disabled plugin {name}")
+            }
+            Some(("enable-plugin", sub)) => {
+                let name = sub.get_one::<String>("name").cloned().unwrap_or_default();
+                controls
+                    .audited(&actor, is_admin, "plugins.enable", Some(&name), async {
+                        if controls.enable_plugin(&name).await? {
+                            Ok(())
+                        } else {
+                            Err(AppError::NotFound(format!("Plugin {name} is not disabled")))
+                        }
+                    })
+                    .await
+                    .map_err(admin_error)?;
+                format!("Enabled plugin {name}")
+            }
+            Some(("policies", sub)) => {
+                let subject = subject_arg(sub)?;
+                let target = subject.as_ref().map(ToString::to_string);
+                let flags = controls
+                    .audited(&actor, is_admin, "policies.list", target.as_deref(), controls.refresh())
+                    .await
+                    .map_err(admin_error)?;
+                let policies: Vec<&AdminPolicy> = flags
+                    .policies
+                    .iter()
+                    .filter(|policy| subject.as_ref().is_none_or(|subject| &policy.policy.subject == subject))
+                    .collect();
+                if json {
+                    return to_json(&policies);
+                }
+                if policies.is_empty() {
+                    return Ok("No command policies".to_string());
+                }
+                policies.into_iter().map(format_policy).collect::<Vec<_>>().join("\n")
+            }
+            Some((effect @ ("allow" | "deny"), sub)) => {
+                let subject = subject_arg(sub)?
+                    .ok_or_else(|| CommandError::ValidationError("No policy subject".to_string()))?;
+                let target = subject.to_string();
+                let policy = CommandPolicy {
+                    id: String::new(),
+                    subject,
+                    effect: effect.parse().map_err(CommandError::ValidationError)?,
+                    scope: sub
+                        .get_one::<String>("scope")
+                        .map(|scope| scope.parse::<PolicyScope>())
+                        .transpose()
+                        .map_err(CommandError::ValidationError)?
+                        .unwrap_or_default(),
+                    pattern: sub.get_one::<String>("pattern").cloned().unwrap_or_default(),
+                    description: sub.get_one::<String>("description").cloned(),
+                };
+                let policy = controls
+                    .audited(&actor, is_admin, "policies.add", Some(&target), controls.add_policy(&actor, policy))
+                    .await
+                    .map_err(admin_error)?;
+                if json {
+                    return to_json(&policy);
+                }
+                format!("Added policy {}", format_policy(&policy))
+            }
+            Some(("remove-policy", sub)) => {
+                let id = sub.get_one::<String>("policy").cloned().unwrap_or_default();
+                controls
+                    .audited(&actor, is_admin, "policies.remove", Some(&id), async {
+                        if controls.remove_policy(&id).await? {
+                            Ok(())
+                        } else {
+                            Err(AppError::NotFound(format!("No command policy {id}")))
+                        }
+                    })
+                    .await
+                    .map_err(admin_error)?;
+                format!("Removed policy {id}")
+            }
+            Some(("degraded", sub)) => {
+                let flags = match sub.get_one::<String>("state").map(String::as_str) {
+                    None => controls.audited(&actor, is_admin, "degraded.get", None, controls.refresh()).await,
+                    Some(state) => {
+                        let enabled = state == "on";
+                        let action = if enabled { "degraded.enable" } else { "degraded.disable" };
+                        let reason = sub.get_one::<String>("reason").cloned();
+                        controls
+                            .audited(&actor, is_admin, action, None, controls.set_degraded(&actor, enabled, reason))
+                            .await
+                    }
+                }
+                .map_err(admin_error)?;
+                if json {
+                    return to_json(&flags.degraded);
+                }
+                match flags.degraded {
+                    Some(degraded) => format!(
+                        "Degraded mode is on since {} (by {}){}",
+                        degraded.enabled_at.to_rfc3339(),
+                        degraded.enabled_by,
+                        degraded.reason.map(|reason| format!(": {reason}")).unwrap_or_default()
+                    ),
+                    None => "Degraded mode is off".to_string(),
+                }
+            }
+            Some(("gc", _)) => {
+                let report = controls
+                    .audited(&actor, is_admin, "gc.run", None, admin::compact_database(&pool, &auth))
+                    .await
+                    .map_err(admin_error)?;
+                if json {
+                    return to_json(&report);
+                }
+                format!(
+                    "Deleted {} expired session, token and key rows and {} expired idempotency keys",
+                    report.auth_rows, report.idempotency_keys
+                )
+            }
+            Some(("keys", _)) => {
+                let keys = controls
+                    .audited(&actor, is_admin, "keys.list", None, async { Ok(auth.signing_keys()) })
+                    .await
+                    .map_err(admin_error)?;
+                if json {
+                    return to_json(&keys);
+                }
+                if keys.is_empty() {
+                    return Ok("Access tokens are signed with the configured secret".to_string());
+                }
+                keys.iter()
+                    .map(|key| match key.retired_at {
+                        Some(retired_at) => format!("{}  retired {}", key.id, retired_at.to_rfc3339()),
+                        None => format!("{}  current since {}", key.id, key.created_at.to_rfc3339()),
+                    })
+                    .collect::<Vec<_>>()
+                    .join("\n")
+            }
+            Some(("rotate-keys", _)) => {
+                let key = controls
+                    .audited(&actor, is_admin, "keys.rotate", None, async {
+                        auth.rotate_signing_key().await.map_err(auth_error)
+                    })
+                    .await
+                    .map_err(admin_error)?;
+                if json {
+                    return to_json(&key);
+                }
+                format!("New access tokens are signed with key {}", key.id)
+            }
+            Some(("config", sub)) => {
+                let path = sub
+                    .get_one::<String>("config")
+                    .cloned()
+                    .or_else(|| std::env::var(CONFIG_ENV).ok());
+                let runtime = controls
+                    .audited(&actor, is_admin, "runtime.get", path.as_deref(), async {
+                        let config = match &path {
+                            Some(path) => Config::load(Path::new(path))
+                                .map_err(|e| AppError::InvalidRequest(e.to_string()))?,
+                            None => Config::default(),
+                        };
+                        Ok(json!({
+                            "config": config,
+                            "flags": controls.refresh().await?,
+                            "signing_keys": auth.signing_keys(),
+                        }))
+                    })
+                    .await
+                    .map_err(admin_error)?;
+                return to_json(&runtime);
+            }
+            Some(("audit", sub)) => {
+                let limit = sub.get_one::<usize>("limit").copied().unwrap_or(DEFAULT_AUDIT_LIMIT);
+                let entries = controls
+                    .audited(&actor, is_admin, "audit.list", None, controls.audit_log(limit))
+                    .await
+                    .map_err(admin_error)?;
+                if json {
+                    return to_json(&entries);
+                }
+                if entries.is_empty() {
+                    return Ok("No admin actions".to_string());
+                }
+                entries.iter().map(format_audit).collect::<Vec<_>>().join("\n")
+            }
+            _ => return Err(CommandError::ValidationError("Unknown admin subcommand".to_string())),
+        };
+        Ok(output)
+    }
+}
+
+/// Someone working on the web database, identified by their access token
+pub(crate) struct Operator {
+    /// The web database
+    pub pool: SqlitePool,
+    /// Authentication, verifying tokens with the server's signing keys
+    pub auth: AuthService,
+    /// User ID of the operator, as written to the audit log
+    pub actor: String,
+    /// Whether the operator has the admin role
+    pub is_admin: bool,
+}
+
+impl Operator {
+    /// Opens the web database named by `--database` and verifies the
+    /// access token passed with `--token`
+    pub(crate) async fn connect(matches: &ArgMatches) -> Result<Self, CommandError> {
+        let url = database_url(matches)?;
+        let token = matches
+            .get_one::<String>("token")
+            .cloned()
+            .or_else(|| std::env::var(ADMIN_TOKEN_ENV).ok())
+            .ok_or_else(|| {
+                CommandError::AuthenticationError(format!("No access token; pass --token or set {ADMIN_TOKEN_ENV}"))
+            })?;
+        let pool = migrations::connect(&url)
+            .await
+            .map_err(|e| CommandError::ResourceError(format!("Failed to open {url}: {e}")))?;
+
+        // Verify the token like the server, with the keys it signs with
+        let auth = AuthService::new(AuthConfig::default(), pool.clone());
+        auth.reload_signing_keys()
+            .await
+            .map_err(|e| CommandError::ExecutionError(format!("{e}; is the web database migrated?")))?;
+        let claims = auth
+            .verify_token(&token)
+            .await
+            .map_err(|e| CommandError::AuthenticationError(e.to_string()))?;
+        Ok(Self {
+            pool,
+            auth,
+            actor: claims.sub.to_string(),
+            is_admin: claims.role == Role::Admin,
+        })
+    }
+
+    /// Adds the `--database`, `--token` and `--json` options to `command`
+    pub(crate) fn args(command: ClapCommand) -> ClapCommand {
+        command
+            .arg(Arg::new("database")
+                .long("database")
+                .help("Web database URL [default: $DATABASE_URL]")
+                .value_name("URL")
+                .global(true))
+            .arg(Arg::new("token")
+                .long("token")
+                .help("Access token of a user with the admin role [default: $SQUIRREL_ADMIN_TOKEN]")
+                .value_name("TOKEN")
+                .global(true))
+            .arg(Arg::new("json")
+                .long("json")
+                .help("Output in JSON format")
+                .action(ArgAction::SetTrue)
+                .global(true))
+    }
+
+    /// The audit log and admin flags of the web database
+    pub(crate) fn controls(&self) -> AdminControls {
+        AdminControls::new(Arc::new(SqlAdminStore::new(self.pool.clone())), AdminConfig::default())
+    }
+}
+
+/// Parses an optional UUID argument
+pub(crate) fn uuid_arg(matches: &ArgMatches, name: &str) -> Result<Option<Uuid>, CommandError> {
+    matches
+        .get_one::<String>(name)
+        .map(|id| Uuid::parse_str(id).map_err(|e| CommandError::ValidationError(format!("Invalid {name} {id}: {e}"))))
+        .transpose()
+}
+
+/// Maps an authentication error to the API error the server reports
+pub(crate) fn auth_error(error: squirrel_web::auth::AuthError) -> AppError {
+    AppError::Internal(error.to_string())
+}
+
+/// Maps the error of an admin action to a command error
+pub(crate) fn admin_error(error: AppError) -> CommandError {
+    match error {
+        AppError::Forbidden(message) => CommandError::AuthorizationError(message),
+        AppError::NotFound(message) | AppError::InvalidRequest(message) => CommandError::ValidationError(message),
+        other => CommandError::ExecutionError(other.to_string()),
+    }
+}
+
+/// Parses the optional `subject` argument, e.g. `user:<id>`
+fn subject_arg(matches: &ArgMatches) -> Result<Option<PolicySubject>, CommandError> {
+    matches
+        .get_one::<String>("subject")
+        .map(|subject| subject.parse().map_err(CommandError::ValidationError))
+        .transpose()
+}
+
+/// One command policy as a line
+fn format_policy(policy: &AdminPolicy) -> String {
+    format!(
+        "{}  {} {:<5} {:<9} {}{}",
+        policy.policy.id,
+        policy.policy.subject,
+        policy.policy.effect.as_str(),
+        policy.policy.scope.as_str(),
+        policy.policy.pattern,
+        policy.policy.description.as_ref().map(|description| format!(": {description}")).unwrap_or_default()
+    )
+}
+
+/// One audit log entry as a line
+fn format_audit(entry: &AuditEntry) -> String {
+    let mut line = format!(
+        "{}  {:<9} {}  {}",
+        entry.created_at.to_rfc3339(),
+        entry.outcome.as_str(),
+        entry.actor,
+        entry.action
+    );
+    if let Some(target) = &entry.target {
+        line.push_str(&format!(" {target}"));
+    }
+    if let Some(detail) = &entry.detail {
+        line.push_str(&format!(": {detail}"));
+    }
+    line
+}
+
+/// Pretty-printed JSON of a value
+pub(crate) fn to_json(value: &impl serde::Serialize) -> Result<String, CommandError> {
+    serde_json::to_string_pretty(value).map_err(|e| CommandError::ExecutionError(e.to_string()))
+}
+
+impl Command for AdminCommand {
+    fn name(&self) -> &str {
+        "admin"
+    }
+
+    fn description(&self) -> &str {
+        "Manage sessions, plugins, command policies, degraded mode and signing keys of the web server"
+    }
+
+    fn tags(&self) -> Vec<String> {
+        vec!["sessions".to_string(), "degraded".to_string(), "gc".to_string()]
+    }
+
+    fn parser(&self) -> ClapCommand {
+        let name = || Arg::new("name").help("Name of the plugin").required(true);
+        let policy = |effect: &'static str, about: &'static str| ClapCommand::new(effect)
+            .about(about)
+            .arg(Arg::new("subject")
+                .help("User, written user:<id>")
+                .required(true))
+            .arg(Arg::new("pattern")
+                .help("Glob pattern; * matches any run of characters, ? one")
+                .required(true))
+            .arg(Arg::new("scope")
+                .long("scope")
+                .help("What the pattern is matched against [default: command]")
+                .value_parser(["command", "namespace", "tool"])
+                .value_name("SCOPE"))
+            .arg(Arg::new("description")
+                .long("description")
+                .help("Why the policy exists")
+                .value_name("TEXT"));
+        Operator::args(ClapCommand::new("admin")
+            .about("Manage sessions, plugins, command policies, degraded mode and signing keys of the web server")
+            .subcommand_required(true))
+            .subcommand(ClapCommand::new("sessions")
+                .about("List the active sessions of every user")
+                .arg(Arg::new("user-id")
+                    .long("user-id")
+                    .help("Only list the sessions of this user")
+                    .value_name("UUID")))
+            .subcommand(ClapCommand::new("kick")
+                .about("End a session; its access tokens stay valid until they expire")
+                .arg(Arg::new("session")
+                    .help("ID of the session")
+                    .required(true)))
+            .subcommand(ClapCommand::new("plugins")
+                .about("List the disabled plugins"))
+            .subcommand(ClapCommand::new("disable-plugin")
+                .about("Disable a plugin, refusing the commands it provides")
+                .arg(name())
+                .arg(Arg::new("reason")
+                    .long("reason")
+                    .help("Why the plugin is disabled")
+                    .value_name("TEXT")))
+            .subcommand(ClapCommand::new("enable-plugin")
+                .about("Enable a disabled plugin again")
+                .arg(name()))
+            .subcommand(ClapCommand::new("policies")
+                .about("List the command policies")
+                .arg(Arg::new("subject")
+                    .long("subject")
+                    .help("Only list the policies of this user:<id> or api_key:<id>")
+                    .value_name("SUBJECT")))
+            .subcommand(policy("allow", "Allow a user or API key the matching commands, namespaces or tools, denying the others"))
+            .subcommand(policy("deny", "Deny a user or API key the matching commands, namespaces or tools"))
+            .subcommand(ClapCommand::new("remove-policy")
+                .about("Remove a command policy")
+                .arg(Arg::new("policy")
+                    .help("ID of the policy")
+                    .required(true)))
+            .subcommand(ClapCommand::new("degraded")
+                .about("Show degraded mode, or turn it on or off; the server then refuses changes")
+                .arg(Arg::new("state")
+                    .help("Turn degraded mode on or off")
+                    .value_parser(["on", "off"]))
+                .arg(Arg::new("reason")
+                    .long("reason")
+                    .help("Reason shown to refused clients")
+                    .value_name("TEXT")))
+            .subcommand(ClapCommand::new("gc")
+                .about("Delete expired sessions, tokens, signing keys and idempotency keys and compact the database; see `blob gc` for blobs"))
+            .subcommand(ClapCommand::new("keys")
+                .about("List the access token signing keys"))
+            .subcommand(ClapCommand::new("rotate-keys")
+                .about("Sign new access tokens with a new key; tokens already issued stay valid until they expire"))
+            .subcommand(ClapCommand::new("config")
+                .about("Show the server configuration, admin flags and signing keys")
+                .arg(Arg::new("config")
+                    .long("config")
+                    .help("Server configuration file [default: $SQUIRREL_CONFIG]")
+                    .value_name("PATH")))
+            .subcommand(ClapCommand::new("audit")
+                .about("Show the latest admin actions, newest first")
+                .arg(Arg::new("limit")
+                    .long("limit")
+                    .help("Number of actions to show")
+                    .value_name("N")
+                    .value_parser(clap::value_parser!(usize))))
+    }
+
+    fn clone_box(&self) -> Box<dyn Command> {
+        Box::new(self.clone())
+    }
+
+    fn execute(&self, args: &[String]) -> Result<String, CommandError> {
+        let matches = self
+            .parser()
+            .try_get_matches_from(std::iter::once("admin".to_string()).chain(args.iter().cloned()))
+            .map_err(|e| CommandError::ValidationError(e.to_string()))?;
+        let run = Self::run(&matches);
+        match tokio::runtime::Handle::try_current() {
+            // Run from the CLI's async entry point
+            Ok(handle) => tokio::task::block_in_place(|| handle.block_on(run)),
+            Err(_) => tokio::runtime::Runtime::new()
+                .map_err(|e| CommandError::ExecutionError(format!("Failed to create runtime: {}", e)))?
+                .block_on(run),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::commands::MigrateCommand;
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_admin_commands_need_the_admin_role_and_are_audited() {
+        let dir = tempdir().unwrap();
+        let database = format!("sqlite://{}", dir.path().join("web.db").display());
+        let migrate = ["up", "--store", "web", "--database", &database].map(String::from);
+        MigrateCommand::new().execute(&migrate).unwrap();
+        let token = |role: Role| {
+            let runtime = tokio::runtime::Runtime::new().unwrap();
+            runtime.block_on(async {
+                let pool = migrations::connect(&database).await.unwrap();
+                AuthService::new(AuthConfig::default(), pool)
+                    .generate_token(Uuid::new_v4(), role)
+                    .await
+                    .unwrap()
+            })
+        };
+        let (admin, user) = (token(Role::Admin), token(Role::User));
+        let run = |token: &str, args: &[&str]| {
+            let mut args: Vec<String> = args.iter().map(ToString::to_string).collect();
+            args.extend(["--database".to_string(), database.clone(), "--token".to_string(), token.to_string()]);
+            AdminCommand::new().execute(&args)
+        };
+
+        assert!(matches!(run(&user, &["degraded", "on"]), Err(CommandError::AuthorizationError(_))));
+        assert!(matches!(run("forged", &["degraded", "on"]), Err(CommandError::AuthenticationError(_))));
+        assert_eq!(run(&admin, &["degraded"]).unwrap(), "Degraded mode is off");
+        run(&admin, &["degraded", "on", "--reason", "failover"]).unwrap();
+        assert!(run(&admin, &["degraded"]).unwrap().ends_with(": failover"));
+
+        assert_eq!(run(&admin, &["disable-plugin", "galaxy"]).unwrap(), "Disabled plugin galaxy");
+        assert!(run(&admin, &["plugins"]).unwrap().starts_with("galaxy  by "));
+        assert!(run(&admin, &["enable-plugin", "galaxy"]).is_ok());
+        assert!(run(&admin, &["enable-plugin", "galaxy"]).is_err());
+
+        assert!(matches!(run(&user, &["deny", "user:ci", "deploy"]), Err(CommandError::AuthorizationError(_))));
+        assert!(matches!(run(&admin, &["deny", "ci", "deploy"]), Err(CommandError::ValidationError(_))));
+        assert!(matches!(run(&admin, &["deny", "api_key:ci", "deploy"]), Err(CommandError::ValidationError(_))));
+        let added = run(&admin, &["deny", "user:ci", "deploy", "--description", "no releases from CI"]).unwrap();
+        assert!(added.starts_with("Added policy ") && added.ends_with(": no releases from CI"), "{added}");
+        run(&admin, &["allow", "user:ada", "gal*", "--scope", "namespace"]).unwrap();
+        let policies = run(&admin, &["policies", "--subject", "user:ci"]).unwrap();
+        assert_eq!(policies.lines().count(), 1, "{policies}");
+        let id = policies.split_whitespace().next().unwrap().to_string();
+        assert_eq!(run(&admin, &["remove-policy", &id]).unwrap(), format!("Removed policy {id}"));
+        assert!(run(&admin, &["remove-policy", &id]).is_err());
+
+        // The admin's token, signed with the configured secret, outlives the rotation
+        assert!(run(&admin, &["rotate-keys"]).unwrap().starts_with("New access tokens are signed with key "));
+        assert!(run(&admin, &["keys"]).unwrap().contains("current since"));
+        assert_eq!(run(&admin, &["sessions"]).unwrap(), "No active sessions");
+        assert!(run(&admin, &["kick", &Uuid::new_v4().to_string()]).is_err());
+        assert!(run(&admin, &["gc"]).unwrap().starts_with("Deleted 0 expired"));
+
+        let audit = run(&admin, &["audit", "--limit", "50"]).unwrap();
+        let lines: Vec<&str> = audit.lines().collect();
+        assert!(lines[0].contains("succeeded") && lines[0].contains("gc.run"), "{audit}");
+        assert!(lines[1].contains("failed") && lines[1].contains("sessions.kick"), "{audit}");
+        assert!(lines.last().unwrap().contains("denied") && lines.last().unwrap().contains("degraded.enable"), "{audit}");
+    }
+}
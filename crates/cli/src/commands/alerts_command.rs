@@ -0,0 +1,330 @@
+//! Alerts command
+//!
+//! Lists firing alerts and their groups, acknowledges and resolves them, and
+//! manages silences. Works on the alert state file the server persists to, so
+//! changes made here are picked up by a running server.
+
+use std::path::PathBuf;
+
+use clap::{Arg, ArgAction, ArgMatches, Command as ClapCommand};
+use serde::Serialize;
+use squirrel_commands::{Command, CommandError};
+use squirrel_monitoring::alerts::{AlertLifecycle, LabelMatcher, LifecycleConfig, Silence, TrackedAlert};
+use uuid::Uuid;
+
+/// Alerts command implementation
+#[derive(Debug, Clone, Default)]
+pub struct AlertsCommand;
+
+impl AlertsCommand {
+    /// Create a new alerts command
+    pub fn new() -> Self {
+        Self
+    }
+
+    /// Opens the state file given with `--state`, or the default one
+    fn open(matches: &ArgMatches) -> Result<AlertLifecycle, CommandError> {
+        let state_path = matches
+            .get_one::<String>("state")
+            .map(PathBuf::from)
+            .or_else(LifecycleConfig::default_state_path)
+            .ok_or_else(|| CommandError::ValidationError("No alert state file; pass --state".to_string()))?;
+        let config = LifecycleConfig {
+            state_path: Some(state_path),
+            ..LifecycleConfig::default()
+        };
+        AlertLifecycle::open(config).map_err(|e| CommandError::ResourceError(e.to_string()))
+    }
+}
+
+impl Command for AlertsCommand {
+    fn name(&self) -> &str {
+        "alerts"
+    }
+
+    fn description(&self) -> &str {
+        "List, acknowledge and silence alerts"
+    }
+
+    fn parser(&self) -> ClapCommand {
+        let fingerprint = Arg::new("fingerprint")
+            .help("Fingerprint of the alert")
+            .required(true);
+        ClapCommand::new("alerts")
+            .about("List, acknowledge and silence alerts")
+            .subcommand_required(true)
+            .arg(Arg::new("state")
+                .long("state")
+                .help("Alert state file [default: ~/.squirrel/alerts.json]")
+                .value_name("FILE")
+                .global(true))
+            .arg(Arg::new("json")
+                .long("json")
+                .help("Output in JSON format")
+                .action(ArgAction::SetTrue)
+                .global(true))
+            .subcommand(ClapCommand::new("list")
+                .about("List firing alerts"))
+            .subcommand(ClapCommand::new("groups")
+                .about("List firing alerts by group"))
+            .subcommand(ClapCommand::new("ack")
+                .about("Acknowledge an alert")
+                .arg(fingerprint.clone())
+                .arg(Arg::new("by")
+                    .long("by")
+                    .help("Who acknowledges the alert [default: $USER]")
+                    .value_name("NAME")))
+            .subcommand(ClapCommand::new("unack")
+                .about("Withdraw the acknowledgment of an alert")
+                .arg(fingerprint.clone()))
+            .subcommand(ClapCommand::new("resolve")
+                .about("Resolve an alert")
+                .arg(fingerprint))
+            .subcommand(ClapCommand::new("silences")
+                .about("List silences")
+                .arg(Arg::new("all")
+                    .long("all")
+                    .help("Include silences that have ended")
+                    .action(ArgAction::SetTrue)))
+            .subcommand(ClapCommand::new("silence")
+                .about("Silence alerts matching labels")
+                .arg(Arg::new("matchers")
+                    .help("Label matchers as name=value or name!=value")
+                    .value_name("MATCHER")
+                    .required(true)
+                    .num_args(1..))
+                .arg(Arg::new("duration")
+                    .long("duration")
+                    .help("How long the silence lasts, e.g. 30m, 2h or 1d")
+                    .value_name("DURATION")
+                    .default_value("1h"))
+                .arg(Arg::new("comment")
+                    .long("comment")
+                    .help("Why the alerts are silenced")
+                    .value_name("TEXT")
+                    .default_value("")))
+            .subcommand(ClapCommand::new("unsilence")
+                .about("End a silence now")
+                .arg(Arg::new("id")
+                    .help("Silence ID")
+                    .required(true)))
+    }
+
+    fn clone_box(&self) -> Box<dyn Command> {
+        Box::new(self.clone())
+    }
+
+    fn execute(&self, args: &[String]) -> Result<String, CommandError> {
+        let matches = self
+            .parser()
+            .try_get_matches_from(std::iter::once("alerts".to_string()).chain(args.iter().cloned()))
+            .map_err(|e| CommandError::ValidationError(e.to_string()))?;
+        let lifecycle = Self::open(&matches)?;
+        let json = matches.get_flag("json");
+        let storage_error = |e: squirrel_core::error::SquirrelError| CommandError::ExecutionError(e.to_string());
+
+        match matches.subcommand() {
+            Some(("list", _)) => {
+                let alerts = lifecycle.alerts();
+                if json {
+                    return to_json(&alerts);
+                }
+                if alerts.is_empty() {
+                    return Ok("No firing alerts".to_string());
+                }
+                Ok(alerts.iter().map(format_alert).collect::<Vec<_>>().join("\n"))
+            }
+            Some(("groups", _)) => {
+                let groups = lifecycle.groups();
+                if json {
+                    return to_json(&groups);
+                }
+                if groups.is_empty() {
+                    return Ok("No firing alerts".to_string());
+                }
+                let lines: Vec<String> = groups
+                    .iter()
+                    .map(|group| {
+                        let labels: Vec<String> = group.labels.iter().map(|(k, v)| format!("{k}={v}")).collect();
+                        format!(
+                            "{{{}}} {:?} alerts={} acknowledged={} occurrences={}",
+                            labels.join(","),
+                            group.severity,
+                            group.fingerprints.len(),
+                            group.acknowledged,
+                            group.count
+                        )
+                    })
+                    .collect();
+                Ok(lines.join("\n"))
+            }
+            Some(("ack", sub)) => {
+                let fingerprint = required(sub, "fingerprint");
+                let by = sub
+                    .get_one::<String>("by")
+                    .cloned()
+                    .or_else(|| std::env::var("USER").ok())
+                    .unwrap_or_else(|| "cli".to_string());
+                let alert = lifecycle.acknowledge(fingerprint, &by).map_err(storage_error)?;
+                if json {
+                    return to_json(&alert);
+                }
+                Ok(format!("Acknowledged {} as {}", alert.fingerprint, by))
+            }
+            Some(("unack", sub)) => {
+                let alert = lifecycle.unacknowledge(required(sub, "fingerprint")).map_err(storage_error)?;
+                if json {
+                    return to_json(&alert);
+                }
+                Ok(format!("Withdrew acknowledgment of {}", alert.fingerprint))
+            }
+            Some(("resolve", sub)) => {
+                let fingerprint = required(sub, "fingerprint");
+                let alert = lifecycle
+                    .resolve(fingerprint)
+                    .map_err(storage_error)?
+                    .ok_or_else(|| CommandError::ExecutionError(format!("No firing alert with fingerprint {fingerprint}")))?;
+                if json {
+                    return to_json(&alert);
+                }
+                Ok(format!("Resolved {}", alert.fingerprint))
+            }
+            Some(("silences", sub)) => {
+                let silences = lifecycle.silences(sub.get_flag("all"));
+                if json {
+                    return to_json(&silences);
+                }
+                if silences.is_empty() {
+                    return Ok("No silences".to_string());
+                }
+                Ok(silences.iter().map(format_silence).collect::<Vec<_>>().join("\n"))
+            }
+            Some(("silence", sub)) => {
+                let matchers = sub
+                    .get_many::<String>("matchers")
+                    .unwrap_or_default()
+                    .map(|matcher| matcher.parse::<LabelMatcher>())
+                    .collect::<Result<Vec<_>, _>>()
+                    .map_err(|e| CommandError::ValidationError(e.to_string()))?;
+                let duration = parse_duration(required(sub, "duration"))?;
+                let by = std::env::var("USER").unwrap_or_else(|_| "cli".to_string());
+                let silence = Silence::new(matchers, duration, by, required(sub, "comment").to_string());
+                let silence = lifecycle.add_silence(silence).map_err(storage_error)?;
+                if json {
+                    return to_json(&silence);
+                }
+                Ok(format!("Created silence {} until {}", silence.id, silence.ends_at.to_rfc3339()))
+            }
+            Some(("unsilence", sub)) => {
+                let id = required(sub, "id")
+                    .parse::<Uuid>()
+                    .map_err(|e| CommandError::ValidationError(format!("Invalid silence ID: {e}")))?;
+                let silence = lifecycle.expire_silence(id).map_err(storage_error)?;
+                if json {
+                    return to_json(&silence);
+                }
+                Ok(format!("Ended silence {}", silence.id))
+            }
+            _ => Err(CommandError::ValidationError("Unknown alerts subcommand".to_string())),
+        }
+    }
+}
+
+/// The value of the required or defaulted argument `name`
+fn required<'a>(matches: &'a ArgMatches, name: &str) -> &'a str {
+    matches.get_one::<String>(name).map(String::as_str).unwrap_or_default()
+}
+
+/// Parses a duration such as `90s`, `30m`, `2h` or `1d`; bare numbers are seconds
+pub(crate) fn parse_duration(text: &str) -> Result<chrono::Duration, CommandError> {
+    let invalid = || CommandError::ValidationError(format!("Invalid duration: {text}"));
+    let (number, unit) = text.split_at(text.find(|c: char| !c.is_ascii_digit()).unwrap_or(text.len()));
+    let number: i64 = number.parse().map_err(|_| invalid())?;
+    let seconds = match unit {
+        "" | "s" => 1,
+        "m" => 60,
+        "h" => 3600,
+        "d" => 86_400,
+        _ => return Err(invalid()),
+    };
+    number
+        .checked_mul(seconds)
+        .filter(|secs| *secs > 0)
+        .and_then(chrono::Duration::try_seconds)
+        .ok_or_else(invalid)
+}
+
+/// One line describing a firing alert
+fn format_alert(alert: &TrackedAlert) -> String {
+    let acknowledged = alert
+        .acknowledgment
+        .as_ref()
+        .map(|ack| format!(" (acknowledged by {})", ack.by))
+        .unwrap_or_default();
+    format!(
+        "{} {:?} x{} last {} {}{}",
+        alert.fingerprint,
+        alert.severity,
+        alert.count,
+        alert.last_seen.to_rfc3339(),
+        alert.message,
+        acknowledged
+    )
+}
+
+/// One line describing a silence
+fn format_silence(silence: &Silence) -> String {
+    let matchers: Vec<String> = silence.matchers.iter().map(ToString::to_string).collect();
+    format!(
+        "{} {{{}}} until {} by {}{}",
+        silence.id,
+        matchers.join(","),
+        silence.ends_at.to_rfc3339(),
+        silence.created_by,
+        if silence.comment.is_empty() { String::new() } else { format!(": {}", silence.comment) }
+    )
+}
+
+/// Pretty-printed JSON of `value`
+fn to_json<T: Serialize>(value: &T) -> Result<String, CommandError> {
+    serde_json::to_string_pretty(value).map_err(|e| CommandError::ExecutionError(e.to_string()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use squirrel_monitoring::alerts::{Alert, AlertSeverity, AlertType};
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_acknowledge_and_silence_through_state_file() {
+        let dir = tempdir().unwrap();
+        let state = dir.path().join("alerts.json");
+        let server = AlertLifecycle::open(LifecycleConfig {
+            state_path: Some(state.clone()),
+            ..LifecycleConfig::default()
+        })
+        .unwrap();
+        let alert = Alert::new(AlertType::Generic, AlertSeverity::Warning, "disk".to_string(), "disk almost full".to_string());
+        let fingerprint = server.observe(&alert).unwrap().fingerprint;
+
+        let command = AlertsCommand::new();
+        let run = |args: &[&str]| {
+            let mut args: Vec<String> = args.iter().map(ToString::to_string).collect();
+            args.extend(["--state".to_string(), state.display().to_string()]);
+            command.execute(&args)
+        };
+        assert!(run(&["list"]).unwrap().contains(&fingerprint));
+        run(&["ack", &fingerprint, "--by", "alice"]).unwrap();
+        assert_eq!(server.get(&fingerprint).unwrap().acknowledgment.unwrap().by, "alice");
+
+        let output = run(&["silence", "source=disk", "--duration", "2h", "--comment", "maintenance"]).unwrap();
+        assert!(output.starts_with("Created silence"), "{output}");
+        assert!(server.observe(&alert).unwrap().silenced_by.is_some());
+
+        assert!(run(&["silence", "source", "--duration", "2h"]).is_err());
+        assert!(run(&["silence", "source=disk", "--duration", "2w"]).is_err());
+        run(&["resolve", &fingerprint]).unwrap();
+        assert_eq!(run(&["list"]).unwrap(), "No firing alerts");
+    }
+}
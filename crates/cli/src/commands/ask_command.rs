@@ -0,0 +1,372 @@
+//! Ask command
+//!
+//! Asks the assistant which CLI commands answer a request in plain words,
+//! and optionally runs them. Conversations are kept in the MCP persistence
+//! directory, so a later `squirrel ask --conversation ID` can follow up, and
+//! each call to the model is added to the audit log there.
+
+use std::path::PathBuf;
+use std::sync::Arc;
+
+use clap::{Arg, ArgAction, ArgMatches, Command as ClapCommand};
+use serde_json::{json, Value};
+use squirrel_commands::{Command, CommandError, CommandRegistry};
+use squirrel_core::redaction::Redactor;
+use squirrel_mcp::ai::{Answer, Assistant, AssistantConfig, Caller, PlannedStep, ToolOption};
+use squirrel_mcp::context_manager::ContextManager;
+use squirrel_mcp::persistence::{MCPPersistence, PersistenceConfig};
+use uuid::Uuid;
+
+use super::redact_command::{redaction_config, CONFIG_ENV};
+
+/// Ask command implementation
+#[derive(Debug, Clone, Default)]
+pub struct AskCommand;
+
+impl AskCommand {
+    /// Create a new ask command
+    pub fn new() -> Self {
+        Self
+    }
+
+    /// The language model settings, with `--endpoint` and `--model` applied
+    fn config(matches: &ArgMatches) -> AssistantConfig {
+        let mut config = AssistantConfig::default().with_env_overrides();
+        if let Some(endpoint) = matches.get_one::<String>("endpoint") {
+            config.endpoint = Some(endpoint.clone());
+        }
+        if let Some(model) = matches.get_one::<String>("model") {
+            config.model = model.clone();
+        }
+        config
+    }
+
+    /// Plans the request, storing the conversation under `persistence`
+    async fn ask(
+        config: &AssistantConfig,
+        persistence: PersistenceConfig,
+        caller: &Caller,
+        conversation_id: Option<Uuid>,
+        request: &str,
+        catalog: &[ToolOption],
+    ) -> Result<Answer, CommandError> {
+        let client = config.client().map_err(|e| CommandError::ValidationError(e.to_string()))?;
+        let redaction = redaction_config(std::env::var(CONFIG_ENV).ok().as_deref())?;
+        let redactor = Redactor::new(&redaction)
+            .map_err(|e| CommandError::ValidationError(e.to_string()))?;
+        let auditor = config
+            .auditor(persistence.clone(), Arc::new(redactor))
+            .map_err(|e| CommandError::ResourceError(e.to_string()))?;
+        let contexts = Arc::new(ContextManager::with_persistence(persistence.clone()).await);
+
+        // The context manager starts empty; bring back the conversation to continue
+        if let Some(id) = conversation_id {
+            let stored = MCPPersistence::new(persistence)
+                .load_contexts()
+                .map_err(|e| CommandError::ResourceError(e.to_string()))?;
+            if let Some(context) = stored.into_iter().find(|context| context.id == id) {
+                contexts
+                    .create_context(context)
+                    .await
+                    .map_err(|e| CommandError::ResourceError(e.to_string()))?;
+            }
+        }
+
+        let mut assistant = Assistant::new(Arc::new(client), contexts, config);
+        if let Some(auditor) = auditor {
+            assistant = assistant.with_auditor(auditor);
+        }
+        assistant
+            .ask(caller, conversation_id, request, catalog)
+            .await
+            .map_err(|e| CommandError::ExecutionError(e.to_string()))
+    }
+}
+
+impl Command for AskCommand {
+    fn name(&self) -> &str {
+        "ask"
+    }
+
+    fn description(&self) -> &str {
+        "Ask the assistant which commands to run"
+    }
+
+    fn parser(&self) -> ClapCommand {
+        ClapCommand::new("ask")
+            .about("Ask the assistant which commands to run, e.g. 'show my usage this week'")
+            .arg(Arg::new("request")
+                .help("What you want to do, in plain words")
+                .required(true)
+                .num_args(1..))
+            .arg(Arg::new("conversation")
+                .long("conversation")
+                .help("Continue an earlier conversation")
+                .value_name("ID")
+                .value_parser(clap::value_parser!(Uuid)))
+            .arg(Arg::new("run")
+                .long("run")
+                .help("Run the planned commands")
+                .action(ArgAction::SetTrue))
+            .arg(Arg::new("endpoint")
+                .long("endpoint")
+                .help("OpenAI-compatible API, e.g. http://localhost:11434/v1 [default: $SQUIRREL_AI_ENDPOINT]")
+                .value_name("URL"))
+            .arg(Arg::new("model")
+                .long("model")
+                .help("Model to ask [default: $SQUIRREL_AI_MODEL or gpt-4o-mini]")
+                .value_name("NAME"))
+            .arg(Arg::new("role")
+                .long("role")
+                .help("Role to plan as; repeat for several")
+                .value_name("ROLE")
+                .action(ArgAction::Append))
+            .arg(Arg::new("data-dir")
+                .long("data-dir")
+                .help("MCP persistence directory conversations and the audit log are kept in [default: data/mcp]")
+                .value_name("DIR"))
+            .arg(Arg::new("json")
+                .long("json")
+                .help("Output in JSON format")
+                .action(ArgAction::SetTrue))
+    }
+
+    fn clone_box(&self) -> Box<dyn Command> {
+        Box::new(self.clone())
+    }
+
+    fn execute(&self, args: &[String]) -> Result<String, CommandError> {
+        let matches = self
+            .parser()
+            .try_get_matches_from(std::iter::once("ask".to_string()).chain(args.iter().cloned()))
+            .map_err(|e| CommandError::ValidationError(e.to_string()))?;
+        let request = matches
+            .get_many::<String>("request")
+            .unwrap_or_default()
+            .cloned()
+            .collect::<Vec<_>>()
+            .join(" ");
+        let roles = matches.get_many::<String>("role").unwrap_or_default().cloned().collect();
+        let user = std::env::var("USER")
+            .or_else(|_| std::env::var("USERNAME"))
+            .unwrap_or_else(|_| "local".to_string());
+        let caller = Caller::new(user, roles);
+        let mut persistence = PersistenceConfig::default();
+        if let Some(dir) = matches.get_one::<String>("data-dir") {
+            persistence.data_dir = PathBuf::from(dir);
+        }
+
+        let mut registry = CommandRegistry::new();
+        super::register_commands(&mut registry);
+        let catalog = catalog(&registry)?;
+
+        let config = Self::config(&matches);
+        let ask = Self::ask(
+            &config,
+            persistence,
+            &caller,
+            matches.get_one::<Uuid>("conversation").copied(),
+            &request,
+            &catalog,
+        );
+        let answer = match tokio::runtime::Handle::try_current() {
+            // Run from the CLI's async entry point
+            Ok(handle) => tokio::task::block_in_place(|| handle.block_on(ask))?,
+            Err(_) => tokio::runtime::Runtime::new()
+                .map_err(|e| CommandError::ExecutionError(format!("Failed to create runtime: {}", e)))?
+                .block_on(ask)?,
+        };
+
+        let mut outputs = Vec::new();
+        if matches.get_flag("run") {
+            for step in &answer.plan.steps {
+                let output = registry.execute(&step.target, &step_args(step)?)?;
+                outputs.push(json!({ "command": command_line(step)?, "output": output }));
+            }
+        }
+
+        if matches.get_flag("json") {
+            let mut value = serde_json::to_value(&answer).map_err(|e| CommandError::ExecutionError(e.to_string()))?;
+            if matches.get_flag("run") {
+                value["executed"] = Value::Array(outputs);
+            }
+            return serde_json::to_string_pretty(&value).map_err(|e| CommandError::ExecutionError(e.to_string()));
+        }
+        format_answer(&answer, &outputs)
+    }
+}
+
+/// The CLI commands the assistant may propose, with their help as parameters
+fn catalog(registry: &CommandRegistry) -> Result<Vec<ToolOption>, CommandError> {
+    let mut catalog = Vec::new();
+    for name in registry.list_commands()? {
+        if name == "ask" {
+            continue;
+        }
+        let command = registry.get_command(&name)?;
+        let help = command.parser().render_help().to_string();
+        let parameters = json!({
+            "type": "object",
+            "properties": {
+                "args": {
+                    "type": "array",
+                    "items": { "type": "string" },
+                    "description": format!("Arguments after the command name:\n{help}"),
+                }
+            }
+        });
+        catalog.push(ToolOption::command(name, command.description(), parameters));
+    }
+    catalog.sort_by(|a, b| a.target.cmp(&b.target));
+    Ok(catalog)
+}
+
+/// Arguments of a planned command
+fn step_args(step: &PlannedStep) -> Result<Vec<String>, CommandError> {
+    match step.arguments.get("args") {
+        None => Ok(Vec::new()),
+        Some(Value::Array(args)) => args
+            .iter()
+            .map(|arg| match arg {
+                Value::String(text) => Ok(text.clone()),
+                Value::Number(_) | Value::Bool(_) => Ok(arg.to_string()),
+                _ => Err(CommandError::ValidationError(format!("Invalid argument {arg} for {}", step.target))),
+            })
+            .collect(),
+        Some(other) => Err(CommandError::ValidationError(format!(
+            "Arguments of {} must be a list, got {other}",
+            step.target
+        ))),
+    }
+}
+
+/// A planned command as it would be typed
+fn command_line(step: &PlannedStep) -> Result<String, CommandError> {
+    let mut words = vec!["squirrel".to_string(), step.target.clone()];
+    words.extend(step_args(step)?.into_iter().map(|arg| {
+        if arg.is_empty() || arg.contains(char::is_whitespace) {
+            format!("'{arg}'")
+        } else {
+            arg
+        }
+    }));
+    Ok(words.join(" "))
+}
+
+/// The reply, the plan and the output of commands run
+fn format_answer(answer: &Answer, outputs: &[Value]) -> Result<String, CommandError> {
+    let mut lines = vec![answer.reply.clone()];
+    if !answer.plan.steps.is_empty() {
+        lines.push(String::new());
+        for (i, step) in answer.plan.steps.iter().enumerate() {
+            let mut line = format!("{}. {}", i + 1, command_line(step)?);
+            if !step.reason.is_empty() {
+                line.push_str(&format!("  # {}", step.reason));
+            }
+            lines.push(line);
+        }
+    }
+    for step in &answer.plan.rejected {
+        lines.push(format!("Refused {}: {}", step.target, step.reason));
+    }
+    for output in outputs {
+        lines.push(String::new());
+        lines.push(format!("$ {}", output["command"].as_str().unwrap_or_default()));
+        lines.push(output["output"].as_str().unwrap_or_default().to_string());
+    }
+    lines.push(String::new());
+    lines.push(format!("Continue with --conversation {}", answer.conversation_id));
+    Ok(lines.join("\n"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::{BufRead, BufReader, Read, Write};
+    use std::net::TcpListener;
+    use tempfile::tempdir;
+
+    /// Serves one chat completion answering with `content`
+    fn serve_completion(content: &str) -> String {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let endpoint = format!("http://{}/v1", listener.local_addr().unwrap());
+        let body = json!({ "choices": [{ "message": { "role": "assistant", "content": content } }] }).to_string();
+        std::thread::spawn(move || {
+            let (stream, _) = listener.accept().unwrap();
+            let mut reader = BufReader::new(stream);
+            let mut length = 0;
+            loop {
+                let mut line = String::new();
+                reader.read_line(&mut line).unwrap();
+                if let Some(value) = line.to_ascii_lowercase().strip_prefix("content-length:") {
+                    length = value.trim().parse().unwrap();
+                }
+                if line == "\r\n" {
+                    break;
+                }
+            }
+            let mut request = vec![0; length];
+            reader.read_exact(&mut request).unwrap();
+            let response = format!(
+                "HTTP/1.1 200 OK\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{body}",
+                body.len()
+            );
+            reader.get_mut().write_all(response.as_bytes()).unwrap();
+        });
+        endpoint
+    }
+
+    #[test]
+    fn test_ask_plans_and_runs_cli_commands() {
+        let dir = tempdir().unwrap();
+        let endpoint = serve_completion(
+            r#"{"reply": "Here is the version.", "steps": [
+                {"kind": "command", "target": "version", "arguments": {"args": []}, "reason": "shows the version"},
+                {"kind": "command", "target": "format-disk"}
+            ]}"#,
+        );
+
+        let output = AskCommand::new()
+            .execute(&[
+                "what".to_string(),
+                "version is this?".to_string(),
+                "--run".to_string(),
+                "--endpoint".to_string(),
+                endpoint,
+                "--data-dir".to_string(),
+                dir.path().display().to_string(),
+            ])
+            .unwrap();
+        let lines: Vec<&str> = output.lines().collect();
+        assert_eq!(lines[0], "Here is the version.");
+        assert_eq!(lines[2], "1. squirrel version  # shows the version");
+        assert_eq!(lines[3], "Refused format-disk: Unknown command 'format-disk'");
+        assert_eq!(lines[5], "$ squirrel version");
+        assert!(lines.last().unwrap().starts_with("Continue with --conversation "));
+
+        let audit = std::fs::read_to_string(dir.path().join("records").join("ai_audit.jsonl")).unwrap();
+        let record: Value = serde_json::from_str(audit.lines().next().unwrap()).unwrap();
+        assert_eq!(record["tools"], json!(["version"]));
+        assert_eq!(record["prompt"][1]["content"], "what version is this?");
+    }
+
+    #[test]
+    fn test_catalog_and_arguments() {
+        let mut registry = CommandRegistry::new();
+        crate::commands::register_commands(&mut registry);
+        let catalog = catalog(&registry).unwrap();
+        assert!(catalog.iter().any(|option| option.target == "usage"));
+        assert!(catalog.iter().all(|option| option.target != "ask"));
+
+        let step = PlannedStep {
+            kind: squirrel_mcp::ai::TargetKind::Command,
+            target: "usage".to_string(),
+            arguments: json!({ "args": ["--user", "alice smith", 3] }),
+            reason: String::new(),
+        };
+        assert_eq!(step_args(&step).unwrap(), ["--user", "alice smith", "3"]);
+        assert_eq!(command_line(&step).unwrap(), "squirrel usage --user 'alice smith' 3");
+        let step = PlannedStep { arguments: json!({ "args": "--user" }), ..step };
+        assert!(step_args(&step).is_err());
+    }
+}
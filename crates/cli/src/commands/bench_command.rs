@@ -0,0 +1,173 @@
+//! Bench command
+//!
+//! Compares the latest criterion results of the `squirrel-bench` suite with
+//! a stored baseline and fails when a benchmark regressed beyond the
+//! threshold. `--run` runs the suite first and `--save` stores the results as
+//! the new baseline.
+
+use std::path::PathBuf;
+
+use clap::{Arg, ArgAction, Command as ClapCommand};
+use squirrel_bench::baseline::DEFAULT_THRESHOLD_PCT;
+use squirrel_bench::{compare, BenchmarkResults};
+use squirrel_commands::{Command, CommandError};
+
+/// Default location of the stored baseline
+const DEFAULT_BASELINE: &str = "crates/bench/baseline.json";
+
+/// Default criterion output directory
+const DEFAULT_CRITERION_DIR: &str = "target/criterion";
+
+/// Bench command implementation
+#[derive(Debug, Clone, Default)]
+pub struct BenchCommand;
+
+impl BenchCommand {
+    /// Create a new bench command
+    pub fn new() -> Self {
+        Self
+    }
+}
+
+/// Runs the benchmark suite with cargo
+fn run_suite() -> Result<(), CommandError> {
+    let status = std::process::Command::new("cargo")
+        .args(["bench", "-p", "squirrel-bench"])
+        .status()
+        .map_err(|e| CommandError::ExecutionError(format!("Failed to run cargo bench: {e}")))?;
+    if !status.success() {
+        return Err(CommandError::ExecutionError(format!("cargo bench failed with {status}")));
+    }
+    Ok(())
+}
+
+impl Command for BenchCommand {
+    fn name(&self) -> &str {
+        "bench"
+    }
+
+    fn description(&self) -> &str {
+        "Compare benchmark results with the stored baseline"
+    }
+
+    fn parser(&self) -> ClapCommand {
+        ClapCommand::new("bench")
+            .about("Compare benchmark results with the stored baseline")
+            .arg(Arg::new("run")
+                .long("run")
+                .help("Run the benchmark suite before comparing")
+                .action(ArgAction::SetTrue))
+            .arg(Arg::new("save")
+                .long("save")
+                .help("Store the current results as the new baseline")
+                .action(ArgAction::SetTrue))
+            .arg(Arg::new("baseline")
+                .long("baseline")
+                .help("Baseline file")
+                .value_name("FILE")
+                .default_value(DEFAULT_BASELINE))
+            .arg(Arg::new("criterion-dir")
+                .long("criterion-dir")
+                .help("Criterion output directory")
+                .value_name("DIR")
+                .default_value(DEFAULT_CRITERION_DIR))
+            .arg(Arg::new("threshold")
+                .long("threshold")
+                .help("Allowed slowdown in percent")
+                .value_name("PCT")
+                .default_value("10")
+                .value_parser(clap::value_parser!(f64)))
+            .arg(Arg::new("json")
+                .long("json")
+                .help("Output in JSON format")
+                .action(ArgAction::SetTrue))
+    }
+
+    fn clone_box(&self) -> Box<dyn Command> {
+        Box::new(self.clone())
+    }
+
+    fn execute(&self, args: &[String]) -> Result<String, CommandError> {
+        let matches = self
+            .parser()
+            .try_get_matches_from(std::iter::once("bench".to_string()).chain(args.iter().cloned()))
+            .map_err(|e| CommandError::ValidationError(e.to_string()))?;
+        let path = |name: &str| PathBuf::from(matches.get_one::<String>(name).cloned().unwrap_or_default());
+        let baseline_path = path("baseline");
+        let threshold = matches.get_one::<f64>("threshold").copied().unwrap_or(DEFAULT_THRESHOLD_PCT);
+
+        if matches.get_flag("run") {
+            run_suite()?;
+        }
+        let current = BenchmarkResults::from_criterion_dir(&path("criterion-dir"))
+            .map_err(|e| CommandError::ResourceError(e.to_string()))?;
+
+        if matches.get_flag("save") {
+            current
+                .save(&baseline_path)
+                .map_err(|e| CommandError::ResourceError(e.to_string()))?;
+            return Ok(format!(
+                "Saved {} benchmark(s) as baseline {}",
+                current.benchmarks.len(),
+                baseline_path.display()
+            ));
+        }
+
+        let baseline = BenchmarkResults::load(&baseline_path).map_err(|e| {
+            CommandError::ResourceError(format!("{e}; create a baseline with 'squirrel bench --save'"))
+        })?;
+        let report = compare(&baseline, &current, threshold);
+        let output = if matches.get_flag("json") {
+            serde_json::to_string_pretty(&report).map_err(|e| CommandError::ExecutionError(e.to_string()))?
+        } else {
+            report.to_string()
+        };
+        if report.has_regressions() {
+            return Err(CommandError::ExecutionError(format!("Performance regressions detected\n{output}")));
+        }
+        Ok(output)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    fn write_estimate(dir: &std::path::Path, name: &str, nanos: f64) {
+        let bench = dir.join(name).join("new");
+        std::fs::create_dir_all(&bench).unwrap();
+        std::fs::write(
+            bench.join("estimates.json"),
+            format!(r#"{{"mean": {{"point_estimate": {nanos}}}}}"#),
+        )
+        .unwrap();
+    }
+
+    #[test]
+    fn test_save_and_compare_baseline() {
+        let dir = tempdir().unwrap();
+        let criterion = dir.path().join("criterion");
+        let baseline = dir.path().join("baseline.json");
+        write_estimate(&criterion, "metrics/record_100", 1000.0);
+        let args = |extra: &[&str]| {
+            let mut args = vec![
+                "--criterion-dir".to_string(),
+                criterion.display().to_string(),
+                "--baseline".to_string(),
+                baseline.display().to_string(),
+            ];
+            args.extend(extra.iter().map(ToString::to_string));
+            args
+        };
+
+        let command = BenchCommand::new();
+        assert!(command.execute(&args(&["--save"])).unwrap().contains("Saved 1 benchmark(s)"));
+        assert!(command.execute(&args(&[])).unwrap().contains("0 regression(s)"));
+
+        write_estimate(&criterion, "metrics/record_100", 1500.0);
+        let err = command.execute(&args(&[])).unwrap_err();
+        assert!(err.to_string().contains("Performance regressions detected"));
+        assert!(command.execute(&args(&["--threshold", "60"])).is_ok());
+    }
+}
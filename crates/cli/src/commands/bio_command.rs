@@ -0,0 +1,197 @@
+//! Bioinformatics file inspection command for the Squirrel CLI
+//!
+//! This module provides the `bio` command group, which summarizes and
+//! validates FASTA, FASTQ, VCF, BED and GFF3 files. Output is JSON by default
+//! so that results can be consumed by downstream tooling.
+
+use clap::{Args, FromArgMatches, Subcommand};
+use log::debug;
+use serde::Serialize;
+use std::path::{Path, PathBuf};
+
+use squirrel_commands::{Command, CommandError};
+use crate::bio::{self, BioError, BioFormat};
+use crate::formatter::{Formatter, FormatterFactory, OutputFormat};
+
+/// Bio command arguments
+#[derive(Debug, Args)]
+pub struct BioArgs {
+    /// Bio subcommand
+    #[clap(subcommand)]
+    pub subcommand: BioSubcommand,
+
+    /// Output in YAML format instead of JSON
+    #[clap(long, global = true, conflicts_with = "text")]
+    pub yaml: bool,
+
+    /// Output in human-readable text format instead of JSON
+    #[clap(long, global = true)]
+    pub text: bool,
+}
+
+/// Bio subcommands
+#[derive(Debug, Subcommand)]
+pub enum BioSubcommand {
+    /// Print summary statistics for a file
+    #[clap(name = "stats")]
+    Stats {
+        /// Path to the file (may be gzip-compressed)
+        path: PathBuf,
+
+        /// File format (detected from the extension by default)
+        #[clap(long, short = 'f', value_enum)]
+        format: Option<BioFormat>,
+    },
+
+    /// Check a file for format errors
+    #[clap(name = "validate")]
+    Validate {
+        /// Path to the file (may be gzip-compressed)
+        path: PathBuf,
+
+        /// File format (detected from the extension by default)
+        #[clap(long, short = 'f', value_enum)]
+        format: Option<BioFormat>,
+
+        /// Maximum number of issues to report
+        #[clap(long, default_value_t = 100)]
+        max_issues: usize,
+    },
+}
+
+/// Statistics output, annotated with the inspected file
+#[derive(Debug, Serialize)]
+struct StatsOutput {
+    path: String,
+    #[serde(flatten)]
+    stats: bio::BioStats,
+}
+
+/// Validation output, annotated with the inspected file
+#[derive(Debug, Serialize)]
+struct ValidateOutput {
+    path: String,
+    #[serde(flatten)]
+    report: bio::ValidationReport,
+}
+
+/// Command for inspecting bioinformatics files
+#[derive(Debug, Clone)]
+pub struct BioCommand;
+
+impl Default for BioCommand {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Command for BioCommand {
+    fn name(&self) -> &str {
+        "bio"
+    }
+
+    fn description(&self) -> &str {
+        "Inspect and validate bioinformatics files (FASTA, FASTQ, VCF, BED, GFF)"
+    }
+
+    fn execute(&self, args: &[String]) -> Result<String, CommandError> {
+        debug!("Executing bio command with args: {:?}", args);
+
+        let matches = self
+            .parser()
+            .try_get_matches_from(std::iter::once(String::from("bio")).chain(args.iter().cloned()))
+            .map_err(|err| CommandError::ValidationError(err.to_string()))?;
+
+        let bio_args = BioArgs::from_arg_matches(&matches)
+            .map_err(|err| CommandError::ExecutionError(err.to_string()))?;
+
+        let format = if bio_args.yaml {
+            OutputFormat::Yaml
+        } else if bio_args.text {
+            OutputFormat::Text
+        } else {
+            OutputFormat::Json
+        };
+        let formatter = FormatterFactory::create(format);
+
+        match &bio_args.subcommand {
+            BioSubcommand::Stats { path, format } => self.handle_stats(path, *format, &formatter),
+            BioSubcommand::Validate { path, format, max_issues } => {
+                self.handle_validate(path, *format, *max_issues, &formatter)
+            }
+        }
+    }
+
+    fn parser(&self) -> clap::Command {
+        BioArgs::augment_args(clap::Command::new("bio").about("Inspect bioinformatics files"))
+    }
+
+    fn clone_box(&self) -> Box<dyn Command> {
+        Box::new(self.clone())
+    }
+}
+
+impl BioCommand {
+    /// Create a new BioCommand instance
+    pub fn new() -> Self {
+        BioCommand
+    }
+
+    /// Handle the 'stats' subcommand
+    fn handle_stats(
+        &self,
+        path: &Path,
+        format: Option<BioFormat>,
+        formatter: &Formatter,
+    ) -> Result<String, CommandError> {
+        let format = resolve_format(path, format)?;
+        debug!("Summarizing {:?} as {:?}", path, format);
+
+        let reader = bio::open(path).map_err(|e| to_command_error(path, e.into()))?;
+        let stats = bio::summarize(format, reader).map_err(|e| to_command_error(path, e))?;
+
+        formatter
+            .format(StatsOutput {
+                path: path.display().to_string(),
+                stats,
+            })
+            .map_err(|e| CommandError::ExecutionError(e.to_string()))
+    }
+
+    /// Handle the 'validate' subcommand
+    fn handle_validate(
+        &self,
+        path: &Path,
+        format: Option<BioFormat>,
+        max_issues: usize,
+        formatter: &Formatter,
+    ) -> Result<String, CommandError> {
+        let format = resolve_format(path, format)?;
+        debug!("Validating {:?} as {:?}", path, format);
+
+        let reader = bio::open(path).map_err(|e| to_command_error(path, e.into()))?;
+        let report = bio::validate(format, reader, max_issues).map_err(|e| to_command_error(path, e))?;
+
+        formatter
+            .format(ValidateOutput {
+                path: path.display().to_string(),
+                report,
+            })
+            .map_err(|e| CommandError::ExecutionError(e.to_string()))
+    }
+}
+
+/// Use the explicit format if given, otherwise detect it from the file name
+fn resolve_format(path: &Path, format: Option<BioFormat>) -> Result<BioFormat, CommandError> {
+    format
+        .or_else(|| BioFormat::detect(path))
+        .ok_or_else(|| CommandError::ValidationError(BioError::UnknownFormat(path.display().to_string()).to_string()))
+}
+
+/// Convert a parsing error into a command error mentioning the file
+fn to_command_error(path: &Path, error: BioError) -> CommandError {
+    match error {
+        BioError::Io(e) => CommandError::ResourceError(format!("Cannot read {}: {}", path.display(), e)),
+        other => CommandError::ExecutionError(format!("{}: {}", path.display(), other)),
+    }
+}
@@ -0,0 +1,315 @@
+//! Blob command
+//!
+//! Shows how much space the content-addressed blob store uses, deletes
+//! blobs no file refers to any more, and verifies the blobs' checksums,
+//! restoring damaged blobs from a backup of the store. Works on the store
+//! directory the server links uploads from, so it can run while the server
+//! is up.
+
+use std::collections::BTreeSet;
+use std::path::{Path, PathBuf};
+
+use clap::{Arg, ArgAction, ArgMatches, Command as ClapCommand};
+use squirrel_commands::{Command, CommandError};
+use squirrel_core::blob::{BlobStore, GcReport, IntegrityReport, RestoreReport, StorageUsage};
+
+use super::alerts_command::parse_duration;
+
+/// Blob command implementation
+#[derive(Debug, Clone, Default)]
+pub struct BlobCommand;
+
+impl BlobCommand {
+    /// Create a new blob command
+    pub fn new() -> Self {
+        Self
+    }
+
+    /// Opens the store given with `--store`, or the default one
+    fn open(matches: &ArgMatches) -> Result<BlobStore, CommandError> {
+        let root = matches
+            .get_one::<String>("store")
+            .map(PathBuf::from)
+            .or_else(BlobStore::default_path)
+            .ok_or_else(|| CommandError::ValidationError("No blob store directory; pass --store".to_string()))?;
+        if !root.is_dir() {
+            return Err(CommandError::ResourceError(format!("No blob store at {}", root.display())));
+        }
+        BlobStore::open(root).map_err(|e| CommandError::ResourceError(e.to_string()))
+    }
+}
+
+impl Command for BlobCommand {
+    fn name(&self) -> &str {
+        "blob"
+    }
+
+    fn description(&self) -> &str {
+        "Inspect and clean up the blob store"
+    }
+
+    fn parser(&self) -> ClapCommand {
+        ClapCommand::new("blob")
+            .about("Inspect and clean up the blob store")
+            .subcommand_required(true)
+            .arg(Arg::new("store")
+                .long("store")
+                .help("Blob store directory [default: ~/.squirrel/blobs]")
+                .value_name("DIR")
+                .global(true))
+            .arg(Arg::new("json")
+                .long("json")
+                .help("Output in JSON format")
+                .action(ArgAction::SetTrue)
+                .global(true))
+            .subcommand(ClapCommand::new("usage")
+                .about("Show the space used and saved by deduplication"))
+            .subcommand(ClapCommand::new("gc")
+                .about("Delete blobs no file refers to")
+                .arg(Arg::new("dry-run")
+                    .long("dry-run")
+                    .help("Only report what would be deleted")
+                    .action(ArgAction::SetTrue))
+                .arg(Arg::new("min-age")
+                    .long("min-age")
+                    .help("Keep blobs added more recently, e.g. 30m, 2h or 1d; 0 keeps none")
+                    .value_name("DURATION")
+                    .default_value("1h")))
+            .subcommand(ClapCommand::new("verify")
+                .about("Hash stored blobs again and quarantine corrupt ones; fails if any stay damaged")
+                .arg(Arg::new("sample")
+                    .long("sample")
+                    .help("Only hash this many blobs, continuing after the previous sampled check")
+                    .value_name("N")
+                    .value_parser(clap::value_parser!(usize)))
+                .arg(Arg::new("restore-from")
+                    .long("restore-from")
+                    .help("Restore damaged blobs from this backup of the store")
+                    .value_name("DIR")))
+            .subcommand(ClapCommand::new("restore")
+                .about("Restore the blobs files refer to but that are not stored from a backup of the store")
+                .arg(Arg::new("from")
+                    .long("from")
+                    .help("Backup of the store directory, such as a mirror or snapshot")
+                    .value_name("DIR")
+                    .required(true)))
+    }
+
+    fn clone_box(&self) -> Box<dyn Command> {
+        Box::new(self.clone())
+    }
+
+    fn execute(&self, args: &[String]) -> Result<String, CommandError> {
+        let matches = self
+            .parser()
+            .try_get_matches_from(std::iter::once("blob".to_string()).chain(args.iter().cloned()))
+            .map_err(|e| CommandError::ValidationError(e.to_string()))?;
+        let store = Self::open(&matches)?;
+        let json = matches.get_flag("json");
+        let storage_error = |e: squirrel_core::blob::BlobError| CommandError::ExecutionError(e.to_string());
+
+        match matches.subcommand() {
+            Some(("usage", _)) => {
+                let usage = store.usage().map_err(storage_error)?;
+                if json {
+                    return to_json(&usage);
+                }
+                Ok(format_usage(&usage))
+            }
+            Some(("gc", sub)) => {
+                let min_age = match sub.get_one::<String>("min-age").map(String::as_str) {
+                    None | Some("0") => std::time::Duration::ZERO,
+                    Some(text) => parse_duration(text)?.to_std().unwrap_or_default(),
+                };
+                let report = store.gc(min_age, sub.get_flag("dry-run")).map_err(storage_error)?;
+                if json {
+                    return to_json(&report);
+                }
+                Ok(format_report(&report))
+            }
+            Some(("verify", sub)) => {
+                let integrity = store
+                    .verify(sub.get_one::<usize>("sample").copied())
+                    .map_err(storage_error)?;
+                let restore = match sub.get_one::<String>("restore-from") {
+                    Some(backup) if !integrity.is_intact() => {
+                        Some(store.restore(Path::new(backup)).map_err(storage_error)?)
+                    }
+                    _ => None,
+                };
+                let restored: BTreeSet<&String> = restore.iter().flat_map(|r| &r.restored).collect();
+                let damaged = integrity
+                    .corrupt
+                    .iter()
+                    .chain(&integrity.missing)
+                    .filter(|digest| !restored.contains(digest))
+                    .count();
+                let output = if json {
+                    to_json(&serde_json::json!({ "integrity": integrity, "restore": restore }))?
+                } else {
+                    let mut output = format_integrity(&integrity);
+                    if let Some(restore) = &restore {
+                        output = format!("{output}\n{}", format_restore(restore));
+                    }
+                    output
+                };
+                if damaged > 0 {
+                    return Err(CommandError::ExecutionError(output));
+                }
+                Ok(output)
+            }
+            Some(("restore", sub)) => {
+                let backup = sub.get_one::<String>("from").map(PathBuf::from).unwrap_or_default();
+                let report = store.restore(&backup).map_err(storage_error)?;
+                if json {
+                    return to_json(&report);
+                }
+                Ok(format_restore(&report))
+            }
+            _ => Err(CommandError::ValidationError("Unknown blob subcommand".to_string())),
+        }
+    }
+}
+
+/// Summary of the space a store uses
+fn format_usage(usage: &StorageUsage) -> String {
+    format!(
+        "{} blobs, {} bytes stored\n{} files, {} bytes referenced\n{} bytes saved by deduplication\n{} bytes unreferenced",
+        usage.blobs,
+        usage.stored_bytes,
+        usage.references,
+        usage.referenced_bytes,
+        usage.saved_bytes(),
+        usage.unreferenced_bytes
+    )
+}
+
+/// Summary of a garbage collection
+fn format_report(report: &GcReport) -> String {
+    let verb = if report.dry_run { "Would remove" } else { "Removed" };
+    let mut lines = vec![format!(
+        "{} {} blobs, freeing {} bytes",
+        verb,
+        report.removed_blobs.len(),
+        report.freed_bytes
+    )];
+    if !report.pruned_refs.is_empty() {
+        let verb = if report.dry_run { "Would drop" } else { "Dropped" };
+        lines.push(format!("{} {} references to deleted files:", verb, report.pruned_refs.len()));
+        lines.extend(report.pruned_refs.iter().map(|name| format!("  {name}")));
+    }
+    lines.join("\n")
+}
+
+/// Summary of an integrity check
+fn format_integrity(report: &IntegrityReport) -> String {
+    let mut lines = vec![format!(
+        "Checked {} of {} blobs, {} bytes",
+        report.checked_blobs, report.blobs, report.checked_bytes
+    )];
+    if report.is_intact() {
+        lines.push("No corrupt or missing blobs".to_string());
+    }
+    if !report.corrupt.is_empty() {
+        lines.push(format!("{} corrupt blobs, moved to quarantine:", report.corrupt.len()));
+        lines.extend(report.corrupt.iter().map(|digest| format!("  {digest}")));
+    }
+    if !report.missing.is_empty() {
+        lines.push(format!("{} missing blobs:", report.missing.len()));
+        lines.extend(report.missing.iter().map(|digest| format!("  {digest}")));
+    }
+    lines.join("\n")
+}
+
+/// Summary of a restore from a backup
+fn format_restore(report: &RestoreReport) -> String {
+    let mut lines = vec![format!(
+        "Restored {} blobs, relinking {} files",
+        report.restored.len(),
+        report.relinked.len()
+    )];
+    if !report.unrecoverable.is_empty() {
+        lines.push(format!("{} blobs have no intact copy in the backup:", report.unrecoverable.len()));
+        lines.extend(report.unrecoverable.iter().map(|digest| format!("  {digest}")));
+    }
+    lines.join("\n")
+}
+
+/// Pretty-printed JSON of `value`
+fn to_json<T: serde::Serialize>(value: &T) -> Result<String, CommandError> {
+    serde_json::to_string_pretty(value).map_err(|e| CommandError::ExecutionError(e.to_string()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_usage_and_gc() {
+        let dir = tempdir().unwrap();
+        let root = dir.path().join("blobs");
+        let store = BlobStore::open(&root).unwrap();
+        let source = dir.path().join("reads.fa");
+        std::fs::write(&source, "ACGT").unwrap();
+        let digest = store.add(&source).unwrap();
+        let link = dir.path().join("workspace/reads.fa");
+        store.link("workspace://reads.fa", &digest, Some(&link)).unwrap();
+
+        let command = BlobCommand::new();
+        let run = |args: &[&str]| {
+            let mut args: Vec<String> = args.iter().map(ToString::to_string).collect();
+            args.extend(["--store".to_string(), root.display().to_string()]);
+            command.execute(&args)
+        };
+        assert!(run(&["usage"]).unwrap().starts_with("1 blobs, 4 bytes stored"));
+
+        std::fs::remove_file(&link).unwrap();
+        let output = run(&["gc", "--dry-run", "--min-age", "0"]).unwrap();
+        assert!(output.starts_with("Would remove 1 blobs, freeing 4 bytes"), "{output}");
+        assert!(store.contains(&digest));
+
+        assert!(run(&["gc", "--min-age", "2w"]).is_err());
+        run(&["gc", "--min-age", "0"]).unwrap();
+        assert!(!store.contains(&digest));
+    }
+
+    #[test]
+    fn test_verify_and_restore() {
+        let dir = tempdir().unwrap();
+        let root = dir.path().join("blobs");
+        let store = BlobStore::open(&root).unwrap();
+        let backup = BlobStore::open(dir.path().join("backup")).unwrap();
+        for (store, name) in [(&store, "a"), (&backup, "b")] {
+            let source = dir.path().join(name);
+            std::fs::write(&source, "ACGT").unwrap();
+            store.add(&source).unwrap();
+        }
+        let source = dir.path().join("c");
+        std::fs::write(&source, "lost").unwrap();
+        let lost = store.add(&source).unwrap();
+        store.link("workspace://lost.txt", &lost, None).unwrap();
+
+        let command = BlobCommand::new();
+        let run = |args: &[&str]| {
+            let mut args: Vec<String> = args.iter().map(ToString::to_string).collect();
+            args.extend(["--store".to_string(), root.display().to_string()]);
+            command.execute(&args)
+        };
+        let output = run(&["verify"]).unwrap();
+        assert_eq!(output, "Checked 2 of 2 blobs, 8 bytes\nNo corrupt or missing blobs");
+
+        std::fs::remove_file(store.blob_path(&lost).unwrap()).unwrap();
+        let backup_dir = backup.root().display().to_string();
+        let error = run(&["verify", "--sample", "1", "--restore-from", &backup_dir]).unwrap_err().to_string();
+        assert!(error.contains("Checked 1 of 1 blobs, 4 bytes\n1 missing blobs:"), "{error}");
+        assert!(error.contains("Restored 0 blobs, relinking 0 files\n1 blobs have no intact copy"), "{error}");
+
+        std::fs::write(&source, "lost").unwrap();
+        backup.add(&source).unwrap();
+        let output = run(&["restore", "--from", &backup_dir]).unwrap();
+        assert_eq!(output, "Restored 1 blobs, relinking 0 files");
+        assert!(store.contains(&lost));
+        assert!(run(&["restore"]).is_err());
+    }
+}
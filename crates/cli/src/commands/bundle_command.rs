@@ -0,0 +1,287 @@
+//! Bundle command
+//!
+//! Exports a recorded run with its inputs and output as a portable archive,
+//! and imports such archives into a workspace where `squirrel replay` can
+//! reproduce the run.
+
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use clap::{Arg, ArgAction, ArgMatches, Command as ClapCommand};
+use squirrel_commands::bundle::{self, ExportOptions, ImportOptions};
+use squirrel_commands::manifest::RunManifest;
+use squirrel_commands::{Command, CommandError};
+
+/// Bundle command implementation
+#[derive(Debug, Clone, Default)]
+pub struct BundleCommand;
+
+impl BundleCommand {
+    /// Create a new bundle command
+    pub fn new() -> Self {
+        Self
+    }
+
+    /// Writes a bundle of a recorded run
+    fn export(matches: &ArgMatches) -> Result<String, CommandError> {
+        let manifest = RunManifest::load(Path::new(required(matches, "manifest")?))?;
+        let output = match matches.get_one::<String>("result") {
+            Some(path) => Some(fs::read_to_string(path).map_err(|e| {
+                CommandError::ResourceError(format!("Cannot read result {path}: {e}"))
+            })?),
+            None => None,
+        };
+        let options = ExportOptions {
+            name: matches.get_one::<String>("name").cloned(),
+            workspace: workspace(matches)?,
+            output,
+        };
+        let path = required(matches, "out")?;
+        let file = fs::File::create(path)
+            .map_err(|e| CommandError::ResourceError(format!("Cannot create {path}: {e}")))?;
+        let bundle = bundle::export(&manifest, &options, file)?;
+
+        if matches.get_flag("json") {
+            return to_json(&bundle);
+        }
+        Ok(format!(
+            "Exported '{}' ({} {}) with {} input(s) to {path}",
+            bundle.name,
+            bundle.manifest.command,
+            bundle.manifest.args.join(" "),
+            bundle.inputs.len()
+        ))
+    }
+
+    /// Describes a bundle without importing it
+    fn inspect(matches: &ArgMatches) -> Result<String, CommandError> {
+        let path = required(matches, "bundle")?;
+        let file = fs::File::open(path).map_err(|e| CommandError::ResourceError(format!("Cannot open {path}: {e}")))?;
+        let bundle = bundle::inspect(file)?;
+        if matches.get_flag("json") {
+            return to_json(&bundle);
+        }
+
+        let mut lines = vec![
+            format!("Job: {}", bundle.name),
+            format!("Command: {} {}", bundle.manifest.command, bundle.manifest.args.join(" ")),
+            format!(
+                "Exported: {} by Squirrel {} from {}",
+                bundle.exported_at.to_rfc3339(),
+                bundle.squirrel_version,
+                bundle.workspace.display()
+            ),
+            format!("Recorded on: {}", bundle.manifest.os),
+        ];
+        let compatible = bundle::compatible_versions(&bundle.squirrel_version, env!("CARGO_PKG_VERSION"));
+        if !compatible {
+            lines.push(format!("Not compatible with Squirrel {}", env!("CARGO_PKG_VERSION")));
+        }
+        lines.push(format!("Inputs ({}):", bundle.inputs.len()));
+        lines.extend(
+            bundle
+                .inputs
+                .iter()
+                .map(|input| format!("  {} ({} bytes)", input.path.display(), input.size)),
+        );
+        if let Some(outcome) = &bundle.manifest.outcome {
+            lines.push(format!("Outcome: {}", if outcome.success { "succeeded" } else { "failed" }));
+        }
+        Ok(lines.join("\n"))
+    }
+
+    /// Recreates a bundled run in a workspace
+    fn import(matches: &ArgMatches) -> Result<String, CommandError> {
+        let mappings = matches
+            .get_many::<String>("map")
+            .into_iter()
+            .flatten()
+            .map(|mapping| {
+                mapping
+                    .split_once('=')
+                    .map(|(from, to)| (PathBuf::from(from), PathBuf::from(to)))
+                    .ok_or_else(|| CommandError::ValidationError(format!("Invalid mapping '{mapping}'; use FROM=TO")))
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        let options = ImportOptions {
+            workspace: workspace(matches)?,
+            mappings,
+            allow_version_mismatch: matches.get_flag("allow-version-mismatch"),
+            overwrite: matches.get_flag("force"),
+        };
+        let path = required(matches, "bundle")?;
+        let file = fs::File::open(path).map_err(|e| CommandError::ResourceError(format!("Cannot open {path}: {e}")))?;
+        let report = bundle::import(file, &options)?;
+
+        if matches.get_flag("json") {
+            return to_json(&report);
+        }
+        let mut lines: Vec<String> = report.warnings.iter().map(|warning| format!("Warning: {warning}")).collect();
+        lines.push(format!("Imported '{}' into {}", report.name, options.workspace.display()));
+        lines.extend(report.inputs.iter().map(|input| format!("  {}", input.display())));
+        if let Some(output) = &report.output_path {
+            lines.push(format!("Recorded output: {}", output.display()));
+        }
+        lines.push(format!("Replay with: squirrel replay {}", report.manifest_path.display()));
+        Ok(lines.join("\n"))
+    }
+}
+
+impl Command for BundleCommand {
+    fn name(&self) -> &str {
+        "bundle"
+    }
+
+    fn description(&self) -> &str {
+        "Export and import recorded runs as portable bundles"
+    }
+
+    fn parser(&self) -> ClapCommand {
+        let workspace = || {
+            Arg::new("workspace")
+                .long("workspace")
+                .help("Workspace directory input paths are relative to [default: current directory]")
+                .value_name("DIR")
+        };
+        ClapCommand::new("bundle")
+            .about("Export and import recorded runs as portable bundles")
+            .subcommand_required(true)
+            .arg(Arg::new("json")
+                .long("json")
+                .help("Output in JSON format")
+                .action(ArgAction::SetTrue)
+                .global(true))
+            .subcommand(ClapCommand::new("export")
+                .about("Pack a run recorded with --capture-manifest, its inputs and output")
+                .arg(Arg::new("manifest")
+                    .help("Manifest of the run")
+                    .required(true)
+                    .value_name("MANIFEST"))
+                .arg(Arg::new("out")
+                    .long("out")
+                    .short('o')
+                    .help("Bundle file to write")
+                    .required(true)
+                    .value_name("FILE"))
+                .arg(Arg::new("name")
+                    .long("name")
+                    .help("Job name [default: the command name]")
+                    .value_name("NAME"))
+                .arg(Arg::new("result")
+                    .long("result")
+                    .help("File holding the output of the run, checked against the manifest")
+                    .value_name("FILE"))
+                .arg(workspace()))
+            .subcommand(ClapCommand::new("inspect")
+                .about("Describe a bundle without importing it")
+                .arg(Arg::new("bundle")
+                    .help("Bundle file")
+                    .required(true)
+                    .value_name("FILE")))
+            .subcommand(ClapCommand::new("import")
+                .about("Recreate a bundled run in a workspace")
+                .arg(Arg::new("bundle")
+                    .help("Bundle file")
+                    .required(true)
+                    .value_name("FILE"))
+                .arg(workspace())
+                .arg(Arg::new("map")
+                    .long("map")
+                    .help("Place inputs under FROM, outside the exported workspace, under TO")
+                    .value_name("FROM=TO")
+                    .action(ArgAction::Append))
+                .arg(Arg::new("allow-version-mismatch")
+                    .long("allow-version-mismatch")
+                    .help("Import bundles exported by an incompatible Squirrel version")
+                    .action(ArgAction::SetTrue))
+                .arg(Arg::new("force")
+                    .long("force")
+                    .help("Replace existing files with other content")
+                    .action(ArgAction::SetTrue)))
+    }
+
+    fn clone_box(&self) -> Box<dyn Command> {
+        Box::new(self.clone())
+    }
+
+    fn execute(&self, args: &[String]) -> Result<String, CommandError> {
+        let matches = self
+            .parser()
+            .try_get_matches_from(std::iter::once("bundle".to_string()).chain(args.iter().cloned()))
+            .map_err(|e| CommandError::ValidationError(e.to_string()))?;
+        match matches.subcommand() {
+            Some(("export", sub)) => Self::export(sub),
+            Some(("inspect", sub)) => Self::inspect(sub),
+            Some(("import", sub)) => Self::import(sub),
+            _ => Err(CommandError::ValidationError("Unknown bundle subcommand".to_string())),
+        }
+    }
+}
+
+/// A required argument
+fn required<'a>(matches: &'a ArgMatches, name: &str) -> Result<&'a str, CommandError> {
+    matches
+        .get_one::<String>(name)
+        .map(String::as_str)
+        .ok_or_else(|| CommandError::ValidationError(format!("Missing {name}")))
+}
+
+/// The absolute workspace directory, the current one by default
+fn workspace(matches: &ArgMatches) -> Result<PathBuf, CommandError> {
+    let current = std::env::current_dir()
+        .map_err(|e| CommandError::ResourceError(format!("Cannot read the current directory: {e}")))?;
+    Ok(match matches.get_one::<String>("workspace") {
+        Some(dir) => current.join(dir),
+        None => current,
+    })
+}
+
+/// Pretty-printed JSON of `value`
+fn to_json(value: &impl serde::Serialize) -> Result<String, CommandError> {
+    serde_json::to_string_pretty(value).map_err(|e| CommandError::ExecutionError(e.to_string()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use squirrel_commands::manifest::{ManifestOptions, RunOutcome};
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_export_inspect_import() {
+        let dir = tempdir().unwrap();
+        let lab = dir.path().join("lab");
+        fs::create_dir_all(&lab).unwrap();
+        let input = lab.join("samples.csv");
+        fs::write(&input, "id,reads\ns1,100\n").unwrap();
+        let options = ManifestOptions {
+            inputs: vec![input.clone()],
+            ..ManifestOptions::default()
+        };
+        let mut manifest = RunManifest::capture("bio", &[input.display().to_string()], &options).unwrap();
+        manifest.outcome = Some(RunOutcome::from_result(&Ok("2 samples".to_string())));
+        let manifest_path = dir.path().join("run.json");
+        manifest.save(&manifest_path).unwrap();
+        fs::write(dir.path().join("result.txt"), "2 samples\n").unwrap();
+
+        let command = BundleCommand::new();
+        let run = |args: &[&str]| command.execute(&args.iter().map(ToString::to_string).collect::<Vec<_>>());
+        let path = |name: &str| dir.path().join(name).display().to_string();
+        let output = run(&[
+            "export", &path("run.json"), "--out", &path("run.tar.gz"),
+            "--result", &path("result.txt"), "--workspace", &path("lab"), "--name", "counts",
+        ])
+        .unwrap();
+        assert!(output.contains("Exported 'counts'"), "{output}");
+
+        let output = run(&["inspect", &path("run.tar.gz")]).unwrap();
+        assert!(output.contains("  samples.csv (16 bytes)"), "{output}");
+
+        let output = run(&["import", &path("run.tar.gz"), "--workspace", &path("copy"), "--json"]).unwrap();
+        let report: serde_json::Value = serde_json::from_str(&output).unwrap();
+        assert_eq!(report["manifest"]["args"][0], path("copy/samples.csv"));
+        assert_eq!(fs::read_to_string(dir.path().join("copy/samples.csv")).unwrap(), "id,reads\ns1,100\n");
+
+        assert!(run(&["import", &path("run.tar.gz"), "--map", "no-equals-sign"]).is_err());
+    }
+}
@@ -0,0 +1,224 @@
+//! Cache command
+//!
+//! Lists, shows and invalidates the cached results that `--cache` serves
+//! instead of executing a command again.
+
+use std::path::{Path, PathBuf};
+
+use clap::{Arg, ArgAction, ArgMatches, Command as ClapCommand};
+use squirrel_commands::cache::{CachedResult, ResultCache};
+use squirrel_commands::{Command, CommandError};
+
+/// Cache command implementation
+#[derive(Debug, Clone, Default)]
+pub struct CacheCommand;
+
+impl CacheCommand {
+    /// Create a new cache command
+    pub fn new() -> Self {
+        Self
+    }
+
+    /// Opens the cache given with `--dir`, or the default one
+    fn open(matches: &ArgMatches) -> Result<ResultCache, CommandError> {
+        let dir = matches
+            .get_one::<String>("dir")
+            .map(PathBuf::from)
+            .or_else(ResultCache::default_path)
+            .ok_or_else(|| CommandError::ValidationError("No result cache directory; pass --dir".to_string()))?;
+        ResultCache::open(dir)
+    }
+}
+
+impl Command for CacheCommand {
+    fn name(&self) -> &str {
+        "cache"
+    }
+
+    fn description(&self) -> &str {
+        "Inspect and invalidate cached command results"
+    }
+
+    fn parser(&self) -> ClapCommand {
+        ClapCommand::new("cache")
+            .about("Inspect and invalidate cached command results")
+            .subcommand_required(true)
+            .arg(Arg::new("dir")
+                .long("dir")
+                .help("Result cache directory [default: ~/.squirrel/cache/results]")
+                .value_name("DIR")
+                .global(true))
+            .arg(Arg::new("json")
+                .long("json")
+                .help("Output in JSON format")
+                .action(ArgAction::SetTrue)
+                .global(true))
+            .subcommand(ClapCommand::new("list")
+                .about("List cached results")
+                .arg(Arg::new("command")
+                    .long("command")
+                    .help("Only list results of this command")
+                    .value_name("NAME")))
+            .subcommand(ClapCommand::new("show")
+                .about("Show a cached result and what it was computed from")
+                .arg(Arg::new("key").required(true).help("Cache key")))
+            .subcommand(ClapCommand::new("invalidate")
+                .about("Remove cached results")
+                .arg(Arg::new("key")
+                    .help("Cache key of the result to remove"))
+                .arg(Arg::new("input")
+                    .long("input")
+                    .help("Remove the results of runs that read this file")
+                    .value_name("FILE")
+                    .conflicts_with("key"))
+                .arg(Arg::new("command")
+                    .long("command")
+                    .help("Remove the results of this command")
+                    .value_name("NAME")
+                    .conflicts_with_all(["key", "input"]))
+                .arg(Arg::new("all")
+                    .long("all")
+                    .help("Remove all results")
+                    .action(ArgAction::SetTrue)
+                    .conflicts_with_all(["key", "input", "command"])))
+    }
+
+    fn clone_box(&self) -> Box<dyn Command> {
+        Box::new(self.clone())
+    }
+
+    fn execute(&self, args: &[String]) -> Result<String, CommandError> {
+        let matches = self
+            .parser()
+            .try_get_matches_from(std::iter::once("cache".to_string()).chain(args.iter().cloned()))
+            .map_err(|e| CommandError::ValidationError(e.to_string()))?;
+        let cache = Self::open(&matches)?;
+        let json = matches.get_flag("json");
+
+        match matches.subcommand() {
+            Some(("list", sub)) => {
+                let mut entries = cache.entries()?;
+                if let Some(command) = sub.get_one::<String>("command") {
+                    entries.retain(|entry| &entry.command == command);
+                }
+                if json {
+                    return to_json(&entries);
+                }
+                Ok(format_entries(&entries))
+            }
+            Some(("show", sub)) => {
+                let key = sub.get_one::<String>("key").map(String::as_str).unwrap_or_default();
+                let entry = cache
+                    .get(key)?
+                    .ok_or_else(|| CommandError::ValidationError(format!("No cached result {key}")))?;
+                if json {
+                    return to_json(&entry);
+                }
+                Ok(format_entry(&entry))
+            }
+            Some(("invalidate", sub)) => {
+                let removed = if let Some(key) = sub.get_one::<String>("key") {
+                    if cache.remove(key)? { vec![key.clone()] } else { Vec::new() }
+                } else if let Some(input) = sub.get_one::<String>("input") {
+                    cache.invalidate_input(Path::new(input))?
+                } else if let Some(command) = sub.get_one::<String>("command") {
+                    cache.invalidate_command(command)?
+                } else if sub.get_flag("all") {
+                    cache.clear()?
+                } else {
+                    return Err(CommandError::ValidationError(
+                        "Pass a cache key, --input, --command or --all".to_string(),
+                    ));
+                };
+                if json {
+                    return to_json(&removed);
+                }
+                Ok(format!("Removed {} cached results", removed.len()))
+            }
+            _ => Err(CommandError::ValidationError("Unknown cache subcommand".to_string())),
+        }
+    }
+}
+
+/// One line per cached result
+fn format_entries(entries: &[CachedResult]) -> String {
+    if entries.is_empty() {
+        return "No cached results".to_string();
+    }
+    entries
+        .iter()
+        .map(|entry| {
+            format!(
+                "{}  {} {}  ({} hits, stored {})",
+                &entry.key[..entry.key.len().min(12)],
+                entry.command,
+                entry.args.join(" "),
+                entry.hits,
+                entry.created_at.format("%Y-%m-%d %H:%M:%S")
+            )
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+/// A cached result with its inputs and output
+fn format_entry(entry: &CachedResult) -> String {
+    let mut lines = vec![
+        format!("Key:      {}", entry.key),
+        format!("Command:  {} {}", entry.command, entry.args.join(" ")),
+        format!("Stored:   {}", entry.created_at.to_rfc3339()),
+        format!("Hits:     {}", entry.hits),
+    ];
+    for input in &entry.inputs {
+        lines.push(format!("Input:    {} ({})", input.path.display(), input.sha256));
+    }
+    for (tool, version) in &entry.tools {
+        lines.push(format!("Tool:     {} {}", tool, version.as_deref().unwrap_or("(not found)")));
+    }
+    lines.push(String::new());
+    lines.push(entry.output.clone());
+    lines.join("\n")
+}
+
+/// Pretty-printed JSON of `value`
+fn to_json<T: serde::Serialize>(value: &T) -> Result<String, CommandError> {
+    serde_json::to_string_pretty(value).map_err(|e| CommandError::ExecutionError(e.to_string()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use squirrel_commands::manifest::{ManifestOptions, RunManifest};
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_list_show_and_invalidate() {
+        let dir = tempdir().unwrap();
+        let cache_dir = dir.path().join("cache");
+        let cache = ResultCache::open(&cache_dir).unwrap();
+        let input = dir.path().join("reads.fa");
+        std::fs::write(&input, "ACGT").unwrap();
+        let options = ManifestOptions {
+            env_allowlist: Vec::new(),
+            inputs: vec![input.clone()],
+            ..ManifestOptions::default()
+        };
+        let manifest = RunManifest::capture("count", &["--k".to_string(), "3".to_string()], &options).unwrap();
+        let entry = cache.store(&manifest, "2 kmers").unwrap();
+
+        let command = CacheCommand::new();
+        let run = |args: &[&str]| {
+            let mut args: Vec<String> = args.iter().map(ToString::to_string).collect();
+            args.extend(["--dir".to_string(), cache_dir.display().to_string()]);
+            command.execute(&args)
+        };
+        assert!(run(&["list"]).unwrap().contains("count --k 3"));
+        assert_eq!(run(&["list", "--command", "align"]).unwrap(), "No cached results");
+        assert!(run(&["show", &entry.key]).unwrap().ends_with("2 kmers"));
+        assert!(run(&["invalidate"]).is_err());
+
+        let output = run(&["invalidate", "--input", &input.display().to_string()]).unwrap();
+        assert_eq!(output, "Removed 1 cached results");
+        assert!(run(&["show", &entry.key]).is_err());
+    }
+}
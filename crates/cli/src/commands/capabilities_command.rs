@@ -0,0 +1,118 @@
+//! Capabilities command
+//!
+//! Reports what this CLI build can do: its version, which optional
+//! subsystems are compiled in and configured, and which commands are
+//! registered.
+
+use std::collections::BTreeMap;
+use std::sync::{Arc, Weak};
+
+use clap::{Arg, ArgAction, Command as ClapCommand};
+use squirrel_commands::capabilities::{common_subsystems, CapabilityReport, Subsystem};
+use squirrel_commands::{Command, CommandError, CommandRegistry};
+
+/// Capabilities command implementation
+#[derive(Debug, Clone, Default)]
+pub struct CapabilitiesCommand {
+    /// Registry whose commands are reported
+    registry: Weak<CommandRegistry>,
+    /// Versions of the currently loaded plugins
+    plugins: BTreeMap<String, String>,
+}
+
+impl CapabilitiesCommand {
+    /// Create a capabilities command reporting the commands in `registry`
+    ///
+    /// The registry is held weakly because the command is registered in it.
+    pub fn new(registry: &Arc<CommandRegistry>, plugins: BTreeMap<String, String>) -> Self {
+        Self {
+            registry: Arc::downgrade(registry),
+            plugins,
+        }
+    }
+
+    /// Build the capability report of this CLI
+    pub fn report(&self) -> Result<CapabilityReport, CommandError> {
+        let registry = self
+            .registry
+            .upgrade()
+            .ok_or_else(|| CommandError::RegistryError("Command registry is no longer available".to_string()))?;
+        let plugins = self
+            .plugins
+            .iter()
+            .map(|(name, version)| format!("{} {}", name, version))
+            .collect::<Vec<_>>();
+
+        CapabilityReport::new("squirrel-cli", squirrel_core::build_info::version())
+            .with_subsystem(Subsystem::compiled("plugins", !plugins.is_empty()).with_detail(if plugins.is_empty() {
+                "no plugins loaded".to_string()
+            } else {
+                plugins.join(", ")
+            }))
+            .with_subsystems(common_subsystems())
+            .with_registry(&registry)
+    }
+}
+
+impl Command for CapabilitiesCommand {
+    fn name(&self) -> &str {
+        "capabilities"
+    }
+
+    fn description(&self) -> &str {
+        "Show the compiled-in subsystems, registered commands and version"
+    }
+
+    fn parser(&self) -> ClapCommand {
+        ClapCommand::new("capabilities")
+            .about("Show the compiled-in subsystems, registered commands and version")
+            .arg(Arg::new("json")
+                .long("json")
+                .help("Output the report in JSON format")
+                .action(ArgAction::SetTrue))
+    }
+
+    fn clone_box(&self) -> Box<dyn Command> {
+        Box::new(self.clone())
+    }
+
+    fn execute(&self, args: &[String]) -> Result<String, CommandError> {
+        let matches = self
+            .parser()
+            .try_get_matches_from(std::iter::once("capabilities".to_string()).chain(args.iter().cloned()))
+            .map_err(|e| CommandError::ValidationError(e.to_string()))?;
+
+        let report = self.report()?;
+        if matches.get_flag("json") {
+            serde_json::to_string_pretty(&report).map_err(|e| CommandError::ExecutionError(e.to_string()))
+        } else {
+            Ok(report.to_string())
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::commands::VersionCommand;
+
+    #[test]
+    fn test_capabilities_reports_registry_and_plugins() {
+        let registry = Arc::new(CommandRegistry::new());
+        registry.register("version", Arc::new(VersionCommand::new())).unwrap();
+        let plugins = BTreeMap::from([("hello".to_string(), "1.2.0".to_string())]);
+        let command = CapabilitiesCommand::new(&registry, plugins);
+        registry.register("capabilities", Arc::new(command.clone())).unwrap();
+
+        let output = command.execute(&["--json".to_string()]).unwrap();
+        let report: CapabilityReport = serde_json::from_str(&output).unwrap();
+        assert_eq!(report.commands, ["capabilities", "version"]);
+        assert!(report.supports("plugins"));
+        assert_eq!(report.subsystem("plugins").unwrap().detail.as_deref(), Some("hello 1.2.0"));
+        assert!(!report.supports("wasm"));
+
+        drop(registry);
+        assert!(command.execute(&[]).is_err());
+    }
+}
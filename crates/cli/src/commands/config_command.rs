@@ -8,6 +8,9 @@ use log::debug;
 use serde::Serialize;
 
 use squirrel_commands::{Command, CommandError};
+use crate::config::encryption::{self, MasterKey};
+use crate::config::schema::{self, CONFIG_SCHEMA};
+use crate::config::scope::{self, ConfigLocations, ConfigScope, ResolvedValue, ScopedConfig};
 use crate::config::{ConfigManager, ConfigError};
 use crate::formatter::{FormatterFactory, OutputFormat, Formatter};
 
@@ -19,8 +22,37 @@ pub struct ConfigArgs {
     pub subcommand: ConfigSubcommand,
     
     /// Path to configuration file (default: search standard locations)
-    #[clap(long, short = 'c')]
+    #[clap(long, short = 'c', global = true)]
     pub config_file: Option<PathBuf>,
+
+    /// Use the user's global configuration
+    #[clap(long, global = true, conflicts_with_all = ["profile", "project", "config_file"])]
+    pub global: bool,
+
+    /// Use the named profile
+    #[clap(long, global = true, conflicts_with_all = ["project", "config_file"])]
+    pub profile: Option<String>,
+
+    /// Use the project configuration (.squirrel.toml)
+    #[clap(long, global = true, conflicts_with = "config_file")]
+    pub project: bool,
+}
+
+impl ConfigArgs {
+    /// The scope selected by the scoping flags, if any
+    fn scope(&self) -> Option<ConfigScope> {
+        if let Some(path) = &self.config_file {
+            Some(ConfigScope::File(path.clone()))
+        } else if self.global {
+            Some(ConfigScope::Global)
+        } else if let Some(profile) = &self.profile {
+            Some(ConfigScope::Profile(profile.clone()))
+        } else if self.project {
+            Some(ConfigScope::Project)
+        } else {
+            None
+        }
+    }
 }
 
 /// Configuration subcommands
@@ -31,6 +63,10 @@ pub enum ConfigSubcommand {
     Get {
         /// Configuration key
         key: String,
+
+        /// Show secret values instead of masking them
+        #[clap(long)]
+        show_secrets: bool,
     },
     
     /// Set a configuration value
@@ -42,6 +78,13 @@ pub enum ConfigSubcommand {
         /// Configuration value
         value: String,
     },
+
+    /// Remove a configuration value from a scope
+    #[clap(name = "unset")]
+    Unset {
+        /// Configuration key
+        key: String,
+    },
     
     /// List all configuration values
     #[clap(name = "list")]
@@ -49,6 +92,37 @@ pub enum ConfigSubcommand {
         /// Filter by key prefix
         #[clap(long, short = 'f')]
         filter: Option<String>,
+
+        /// Show secret values instead of masking them
+        #[clap(long)]
+        show_secrets: bool,
+    },
+
+    /// Describe the known configuration keys
+    #[clap(name = "keys")]
+    Keys,
+
+    /// Encrypt a value with the master key
+    ///
+    /// Prints the `enc:` value to paste into a configuration file, or with
+    /// --key encrypts that key's stored value in place.
+    #[clap(name = "encrypt")]
+    Encrypt {
+        /// Value to encrypt
+        #[clap(required_unless_present = "key", conflicts_with = "key")]
+        value: Option<String>,
+
+        /// Encrypt the value stored for this key
+        #[clap(long)]
+        key: Option<String>,
+    },
+
+    /// Generate a master key file for encrypted values
+    #[clap(name = "keygen")]
+    Keygen {
+        /// Replace an existing key file; values encrypted with it can no longer be read
+        #[clap(long)]
+        force: bool,
     },
     
     /// Edit configuration in your default editor
@@ -71,19 +145,28 @@ pub enum ConfigSubcommand {
 }
 
 #[derive(Debug, Serialize)]
-struct ConfigValue {
-    key: String,
-    value: String,
+struct ConfigList {
+    values: Vec<ResolvedValue>,
 }
 
 #[derive(Debug, Serialize)]
-struct ConfigList {
-    values: Vec<ConfigValue>,
+struct ConfigKeyInfo {
+    key: &'static str,
+    description: &'static str,
+    values: String,
+    secret: bool,
 }
 
 /// Command for managing configuration
+///
+/// Reads resolve the effective configuration (defaults, global file,
+/// project file, then `SQUIRREL_*` environment variables) unless a scoping
+/// flag selects a single scope. Writes go to the global scope by default.
 #[derive(Debug, Clone)]
-pub struct ConfigCommand;
+pub struct ConfigCommand {
+    /// Configuration directories; discovered at execution time if unset
+    locations: Option<ConfigLocations>,
+}
 
 impl Default for ConfigCommand {
     fn default() -> Self {
@@ -104,55 +187,59 @@ impl Command for ConfigCommand {
         debug!("Executing config command with args: {:?}", args);
         
         // Parse arguments using clap
-        let config_matches = self.parser().get_matches_from(
+        let config_matches = self.parser().try_get_matches_from(
             std::iter::once(String::from("config")).chain(args.iter().cloned())
-        );
+        ).map_err(|err| CommandError::ValidationError(err.to_string()))?;
         
         let config_args = ConfigArgs::from_arg_matches(&config_matches)
             .map_err(|err| CommandError::ExecutionError(err.to_string()))?;
         
-        // Load configuration
-        let mut config_manager = match ConfigManager::load(config_args.config_file.clone()) {
-            Ok(manager) => manager,
-            Err(e) => {
-                return Err(CommandError::ExecutionError(
-                    format!("Failed to load configuration: {}", e)
-                ));
-            }
-        };
-        
         // Determine output format
-        let format = if args.contains(&"--json".to_string()) {
+        let format = if config_matches.get_flag("json") {
             OutputFormat::Json
-        } else if args.contains(&"--yaml".to_string()) {
+        } else if config_matches.get_flag("yaml") {
             OutputFormat::Yaml
-        } else if args.contains(&"--table".to_string()) {
+        } else if config_matches.get_flag("table") {
             OutputFormat::Table
         } else {
             OutputFormat::Text
         };
         
         let formatter = FormatterFactory::create(format);
+        let structured = matches!(format, OutputFormat::Json | OutputFormat::Yaml);
+        let scope = config_args.scope();
         
         // Process subcommand
         match &config_args.subcommand {
-            ConfigSubcommand::Get { key } => {
-                self.handle_get(&config_manager, key, &formatter)
+            ConfigSubcommand::Get { key, show_secrets } => {
+                self.handle_get(scope, key, *show_secrets, structured.then_some(&formatter))
             },
             ConfigSubcommand::Set { key, value } => {
-                self.handle_set(&mut config_manager, key, value)
+                self.handle_set(scope, key, value)
+            },
+            ConfigSubcommand::Unset { key } => {
+                self.handle_unset(scope, key)
+            },
+            ConfigSubcommand::List { filter, show_secrets } => {
+                self.handle_list(scope, filter, *show_secrets, structured.then_some(&formatter))
             },
-            ConfigSubcommand::List { filter } => {
-                self.handle_list(&config_manager, &formatter, filter)
+            ConfigSubcommand::Keys => {
+                self.handle_keys(structured.then_some(&formatter))
+            },
+            ConfigSubcommand::Encrypt { value, key } => {
+                self.handle_encrypt(scope, value.as_deref(), key.as_deref())
+            },
+            ConfigSubcommand::Keygen { force } => {
+                self.handle_keygen(*force)
             },
             ConfigSubcommand::Edit => {
-                self.handle_edit(&config_manager)
+                self.handle_edit(&self.load_manager(&config_args)?)
             },
             ConfigSubcommand::Import { path } => {
-                self.handle_import(&mut config_manager, path)
+                self.handle_import(&mut self.load_manager(&config_args)?, path)
             },
             ConfigSubcommand::Export { path } => {
-                self.handle_export(&config_manager, path)
+                self.handle_export(&self.load_manager(&config_args)?, path)
             },
         }
     }
@@ -165,14 +252,20 @@ impl Command for ConfigCommand {
             .arg(clap::Arg::new("json")
                 .long("json")
                 .help("Output in JSON format")
+                .action(clap::ArgAction::SetTrue)
+                .global(true)
                 .conflicts_with_all(["yaml", "table"]))
             .arg(clap::Arg::new("yaml")
                 .long("yaml")
                 .help("Output in YAML format")
+                .action(clap::ArgAction::SetTrue)
+                .global(true)
                 .conflicts_with_all(["json", "table"]))
             .arg(clap::Arg::new("table")
                 .long("table")
                 .help("Output in table format")
+                .action(clap::ArgAction::SetTrue)
+                .global(true)
                 .conflicts_with_all(["json", "yaml"]));
         
         cmd
@@ -183,103 +276,243 @@ impl Command for ConfigCommand {
     }
 }
 
+/// Converts a configuration error into a command error
+fn config_error(err: impl Into<ConfigError>) -> CommandError {
+    match err.into() {
+        ConfigError::ValidationError(msg) => CommandError::ValidationError(msg),
+        other => CommandError::ExecutionError(other.to_string()),
+    }
+}
+
+/// Serializes `data` with `formatter`
+fn format_with<T: Serialize + std::fmt::Debug>(formatter: &Formatter, data: T) -> Result<String, CommandError> {
+    formatter.format(data)
+        .map_err(|e| CommandError::ExecutionError(e.to_string()))
+}
+
 impl ConfigCommand {
     /// Create a new ConfigCommand instance
     pub fn new() -> Self {
-        ConfigCommand
+        ConfigCommand { locations: None }
+    }
+
+    /// Create a ConfigCommand using fixed configuration directories
+    pub fn with_locations(locations: ConfigLocations) -> Self {
+        ConfigCommand { locations: Some(locations) }
+    }
+
+    fn locations(&self) -> Result<ConfigLocations, CommandError> {
+        match &self.locations {
+            Some(locations) => Ok(locations.clone()),
+            None => ConfigLocations::discover().map_err(config_error),
+        }
+    }
+
+    fn load_scope(&self, scope: ConfigScope) -> Result<ScopedConfig, CommandError> {
+        ScopedConfig::load(scope, &self.locations()?).map_err(config_error)
+    }
+
+    fn master_key(&self) -> Result<MasterKey, CommandError> {
+        MasterKey::load(&self.locations()?.key_file()).map_err(config_error)
+    }
+
+    /// The value to display, decrypted when secrets are shown
+    fn display_value(&self, key: &str, value: &str, show_secrets: bool) -> Result<String, CommandError> {
+        if show_secrets && encryption::is_encrypted(value) {
+            return self.master_key()?.decrypt(value).map_err(config_error);
+        }
+        Ok(schema::display_value(key, value, show_secrets))
+    }
+
+    /// The effective configuration, or a single scope's settings
+    fn resolve(&self, scope: Option<ConfigScope>) -> Result<Vec<ResolvedValue>, CommandError> {
+        if let Some(scope) = scope {
+            let config = self.load_scope(scope)?;
+            let source = config.scope().to_string();
+            let mut values: Vec<ResolvedValue> = config.entries()
+                .into_iter()
+                .map(|(key, value)| ResolvedValue { key, value, source: source.clone() })
+                .collect();
+            values.sort_by(|a, b| a.key.cmp(&b.key));
+            return Ok(values);
+        }
+
+        let layers = [self.load_scope(ConfigScope::Global)?, self.load_scope(ConfigScope::Project)?];
+        Ok(scope::resolve(&layers, std::env::vars()).into_values().collect())
+    }
+
+    /// Loads the legacy configuration manager used by edit, import and export
+    fn load_manager(&self, config_args: &ConfigArgs) -> Result<ConfigManager, CommandError> {
+        ConfigManager::load(config_args.config_file.clone())
+            .map_err(|e| CommandError::ExecutionError(format!("Failed to load configuration: {}", e)))
     }
     
     /// Handle the 'get' subcommand
     fn handle_get(
         &self,
-        config_manager: &ConfigManager,
+        scope: Option<ConfigScope>,
         key: &str,
-        formatter: &Formatter,
+        show_secrets: bool,
+        formatter: Option<&Formatter>,
     ) -> Result<String, CommandError> {
         debug!("Getting configuration value for key: {}", key);
         
-        match config_manager.get(key) {
-            Ok(value) => {
-                let config_value = ConfigValue {
-                    key: key.to_string(),
-                    value: value.clone(),
-                };
-                formatter.format(config_value)
-                    .map_err(|e| CommandError::ExecutionError(e.to_string()))
-            },
-            Err(ConfigError::KeyNotFound(_)) => {
-                Err(CommandError::ExecutionError(
-                    format!("Configuration key '{}' not found", key)
-                ))
-            },
-            Err(e) => {
-                Err(CommandError::ExecutionError(
-                    format!("Error getting configuration: {}", e)
-                ))
-            },
+        let mut value = self.resolve(scope)?
+            .into_iter()
+            .find(|value| value.key == key)
+            .ok_or_else(|| CommandError::ExecutionError(format!("Configuration key '{}' not found", key)))?;
+        value.value = self.display_value(key, &value.value, show_secrets)?;
+
+        match formatter {
+            Some(formatter) => format_with(formatter, value),
+            None => Ok(value.value),
         }
     }
     
     /// Handle the 'set' subcommand
     fn handle_set(
         &self,
-        config_manager: &mut ConfigManager,
+        scope: Option<ConfigScope>,
         key: &str,
         value: &str,
     ) -> Result<String, CommandError> {
-        debug!("Setting configuration value: {} = {}", key, value);
+        debug!("Setting configuration value for key: {}", key);
         
-        match config_manager.set(key, value.to_string()) {
-            Ok(()) => {
-                // Save the configuration
-                if let Err(e) = config_manager.save(None) {
-                    return Err(CommandError::ExecutionError(
-                        format!("Failed to save configuration: {}", e)
-                    ));
-                }
-                
-                Ok(format!("Set {} = {}", key, value))
-            },
-            Err(e) => {
-                Err(CommandError::ExecutionError(
-                    format!("Failed to set configuration value: {}", e)
-                ))
-            },
+        let mut config = self.load_scope(scope.unwrap_or(ConfigScope::Global))?;
+        config.set(key, value).map_err(config_error)?;
+        config.save().map_err(|e| {
+            CommandError::ExecutionError(format!("Failed to save configuration: {}", e))
+        })?;
+        
+        Ok(format!(
+            "Set {} = {} ({})",
+            key,
+            schema::display_value(key, value, false),
+            config.scope()
+        ))
+    }
+
+    /// Handle the 'unset' subcommand
+    fn handle_unset(
+        &self,
+        scope: Option<ConfigScope>,
+        key: &str,
+    ) -> Result<String, CommandError> {
+        debug!("Removing configuration value for key: {}", key);
+
+        let mut config = self.load_scope(scope.unwrap_or(ConfigScope::Global))?;
+        if !config.unset(key) {
+            return Err(CommandError::ExecutionError(
+                format!("Configuration key '{}' is not set in {}", key, config.scope())
+            ));
         }
+        config.save().map_err(|e| {
+            CommandError::ExecutionError(format!("Failed to save configuration: {}", e))
+        })?;
+
+        Ok(format!("Unset {} ({})", key, config.scope()))
     }
     
     /// Handle the 'list' subcommand
     fn handle_list(
         &self,
-        config_manager: &ConfigManager,
-        formatter: &Formatter,
+        scope: Option<ConfigScope>,
         filter: &Option<String>,
+        show_secrets: bool,
+        formatter: Option<&Formatter>,
     ) -> Result<String, CommandError> {
         debug!("Listing configuration values with filter: {:?}", filter);
         
-        // Get all configuration values
-        let all_values = config_manager.list();
-        
-        // Apply filter if specified
-        let filtered_values: Vec<ConfigValue> = if let Some(prefix) = filter {
-            all_values
-                .into_iter()
-                .filter(|(key, _)| key.starts_with(prefix))
-                .map(|(key, value)| ConfigValue { key, value })
-                .collect()
-        } else {
-            all_values
-                .into_iter()
-                .map(|(key, value)| ConfigValue { key, value })
-                .collect()
-        };
-        
-        let config_list = ConfigList {
-            values: filtered_values,
+        let values: Vec<ResolvedValue> = self.resolve(scope)?
+            .into_iter()
+            .filter(|value| filter.as_ref().is_none_or(|prefix| value.key.starts_with(prefix.as_str())))
+            .map(|mut value| {
+                value.value = self.display_value(&value.key, &value.value, show_secrets)?;
+                Ok(value)
+            })
+            .collect::<Result<_, CommandError>>()?;
+
+        match formatter {
+            Some(formatter) => format_with(formatter, ConfigList { values }),
+            None => Ok(values
+                .iter()
+                .map(|value| format!("{} = {} ({})", value.key, value.value, value.source))
+                .collect::<Vec<_>>()
+                .join("\n")),
+        }
+    }
+
+    /// Handle the 'keys' subcommand
+    fn handle_keys(&self, formatter: Option<&Formatter>) -> Result<String, CommandError> {
+        let keys: Vec<ConfigKeyInfo> = CONFIG_SCHEMA
+            .iter()
+            .map(|key| ConfigKeyInfo {
+                key: key.name,
+                description: key.description,
+                values: key.value_type.describe(),
+                secret: key.secret,
+            })
+            .collect();
+
+        match formatter {
+            Some(formatter) => format_with(formatter, keys),
+            None => Ok(keys
+                .iter()
+                .map(|key| format!("{}: {} [{}]", key.key, key.description, key.values))
+                .chain(std::iter::once(
+                    "Other keys are stored as custom settings; keys containing 'token', 'secret' or 'password' are masked".to_string(),
+                ))
+                .collect::<Vec<_>>()
+                .join("\n")),
+        }
+    }
+    
+    /// Handle the 'encrypt' subcommand
+    fn handle_encrypt(
+        &self,
+        scope: Option<ConfigScope>,
+        value: Option<&str>,
+        key: Option<&str>,
+    ) -> Result<String, CommandError> {
+        let master_key = self.master_key()?;
+        let Some(key) = key else {
+            return master_key.encrypt(value.unwrap_or_default()).map_err(config_error);
         };
-        
-        formatter.format(config_list)
-            .map_err(|e| CommandError::ExecutionError(e.to_string()))
+        debug!("Encrypting configuration value for key: {}", key);
+
+        let mut config = self.load_scope(scope.unwrap_or(ConfigScope::Global))?;
+        let current = config.get(key).ok_or_else(|| {
+            CommandError::ExecutionError(format!("Configuration key '{}' is not set in {}", key, config.scope()))
+        })?;
+        if encryption::is_encrypted(&current) {
+            return Err(CommandError::ExecutionError(format!("Configuration key '{}' is already encrypted", key)));
+        }
+        if schema::lookup(key).is_some_and(|known| known.value_type != schema::ValueType::String) {
+            return Err(CommandError::ValidationError(format!("Only string values can be encrypted, not '{}'", key)));
+        }
+        config.set(key, &master_key.encrypt(&current).map_err(config_error)?).map_err(config_error)?;
+        config.save().map_err(|e| {
+            CommandError::ExecutionError(format!("Failed to save configuration: {}", e))
+        })?;
+
+        Ok(format!("Encrypted {} ({})", key, config.scope()))
+    }
+
+    /// Handle the 'keygen' subcommand
+    fn handle_keygen(&self, force: bool) -> Result<String, CommandError> {
+        let path = self.locations()?.key_file();
+        if path.exists() && !force {
+            return Err(CommandError::ExecutionError(format!(
+                "Master key file {:?} already exists; use --force to replace it",
+                path
+            )));
+        }
+        let key = MasterKey::generate().map_err(config_error)?;
+        write_key_file(&path, &key).map_err(|e| {
+            CommandError::ExecutionError(format!("Failed to write master key file: {}", e))
+        })?;
+
+        Ok(format!("Wrote master key to {:?}", path))
     }
     
     /// Handle the 'edit' subcommand
@@ -324,8 +557,98 @@ impl ConfigCommand {
     }
 }
 
+/// Writes a key file readable only by its owner
+fn write_key_file(path: &Path, key: &MasterKey) -> std::io::Result<()> {
+    use std::io::Write;
+
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+    let mut options = std::fs::OpenOptions::new();
+    options.write(true).create(true).truncate(true);
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::OpenOptionsExt;
+        options.mode(0o600);
+    }
+    options.open(path)?.write_all(format!("{}\n", key.encode()).as_bytes())
+}
+
 /// Register configuration-related commands
 pub fn register_config_commands(registry: &mut squirrel_commands::CommandRegistry) {
-    registry.register("config", std::sync::Arc::new(ConfigCommand))
+    registry.register("config", std::sync::Arc::new(ConfigCommand::new()))
         .expect("Failed to register config command");
-} 
\ No newline at end of file
+} 
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    fn run(command: &ConfigCommand, args: &[&str]) -> Result<String, CommandError> {
+        command.execute(&args.iter().map(ToString::to_string).collect::<Vec<_>>())
+    }
+
+    #[test]
+    fn test_config_scopes_and_masking() {
+        let dir = tempdir().unwrap();
+        let command = ConfigCommand::with_locations(ConfigLocations {
+            config_dir: dir.path().join("config"),
+            project_dir: dir.path().join("project"),
+        });
+
+        assert_eq!(run(&command, &["set", "db_password", "hunter2"]).unwrap(), "Set db_password = ******** (global)");
+        run(&command, &["set", "--profile", "dev", "mcp_port", "9100"]).unwrap();
+        assert!(matches!(
+            run(&command, &["set", "--project", "verbose", "maybe"]),
+            Err(CommandError::ValidationError(_))
+        ));
+
+        assert_eq!(run(&command, &["get", "db_password"]).unwrap(), schema::MASK);
+        assert_eq!(run(&command, &["get", "db_password", "--show-secrets"]).unwrap(), "hunter2");
+        assert_eq!(run(&command, &["get", "mcp_port", "--profile", "dev"]).unwrap(), "9100");
+        assert!(run(&command, &["get", "mcp_port", "--global"]).is_err());
+
+        let json: serde_json::Value =
+            serde_json::from_str(&run(&command, &["list", "--global", "--json"]).unwrap()).unwrap();
+        assert_eq!(json["values"][0]["key"], "db_password");
+        assert_eq!(json["values"][0]["value"], schema::MASK);
+        assert_eq!(json["values"][0]["source"], "global");
+
+        run(&command, &["unset", "db_password"]).unwrap();
+        assert!(run(&command, &["unset", "db_password"]).is_err());
+    }
+
+    #[test]
+    fn test_config_encrypt() {
+        let dir = tempdir().unwrap();
+        let locations = ConfigLocations {
+            config_dir: dir.path().join("config"),
+            project_dir: dir.path().join("project"),
+        };
+        let command = ConfigCommand::with_locations(locations.clone());
+
+        run(&command, &["keygen"]).unwrap();
+        assert!(run(&command, &["keygen"]).is_err());
+
+        let encrypted = run(&command, &["encrypt", "s3cret"]).unwrap();
+        assert!(encrypted.starts_with(encryption::ENCRYPTED_PREFIX));
+        run(&command, &["set", "--project", "db_url", &encrypted]).unwrap();
+        run(&command, &["set", "db_password", "hunter2"]).unwrap();
+        assert_eq!(run(&command, &["encrypt", "--key", "db_password"]).unwrap(), "Encrypted db_password (global)");
+        assert!(run(&command, &["encrypt", "--key", "db_password"]).is_err());
+        run(&command, &["set", "--project", "mcp_port", "9100"]).unwrap();
+        assert!(matches!(
+            run(&command, &["encrypt", "--key", "mcp_port", "--project"]),
+            Err(CommandError::ValidationError(_))
+        ));
+
+        // Encrypted values are masked, and decrypted when secrets are shown
+        assert_eq!(run(&command, &["get", "db_url"]).unwrap(), schema::MASK);
+        assert_eq!(run(&command, &["get", "db_url", "--show-secrets"]).unwrap(), "s3cret");
+        assert_eq!(run(&command, &["get", "db_password", "--show-secrets"]).unwrap(), "hunter2");
+
+        // The file only holds the ciphertext
+        let stored = std::fs::read_to_string(locations.path(&ConfigScope::Global).unwrap()).unwrap();
+        assert!(!stored.contains("hunter2"));
+    }
+}
@@ -3,23 +3,122 @@
 //! This module provides a context for command execution, including access
 //! to parsed command-line arguments and other execution state.
 
+use std::sync::Arc;
+use std::time::Duration;
+
 use clap::ArgMatches;
+use squirrel_commands::extensions::{Extensions, RequestId};
+use squirrel_commands::wire::{SchemaRegistry, WireContext};
+use squirrel_commands::CommandError;
+use squirrel_core::deadline::Deadline;
+use squirrel_core::kv::{KvStore, Namespace};
 
 /// Context for command execution
 #[derive(Debug, Clone)]
 pub struct CommandContext {
     /// Parsed command-line arguments
     matches: ArgMatches,
+    /// Typed values attached by middleware, the web layer or plugins
+    extensions: Extensions,
 }
 
 impl CommandContext {
     /// Create a new command context
     pub fn new(matches: ArgMatches) -> Self {
-        Self { matches }
+        Self {
+            matches,
+            extensions: Extensions::new(),
+        }
+    }
+
+    /// Create a command context carrying the given extensions
+    pub fn with_extensions(matches: ArgMatches, extensions: Extensions) -> Self {
+        Self { matches, extensions }
     }
     
     /// Get the parsed command-line arguments
     pub fn matches(&self) -> &ArgMatches {
         &self.matches
     }
-} 
\ No newline at end of file
+
+    /// Get the typed extensions
+    ///
+    /// Cloning the context shares the extension values; see
+    /// [`Extensions`] for the exact semantics.
+    pub fn extensions(&self) -> &Extensions {
+        &self.extensions
+    }
+
+    /// Get mutable access to the typed extensions
+    pub fn extensions_mut(&mut self) -> &mut Extensions {
+        &mut self.extensions
+    }
+
+    /// Get the extension value of type `T`
+    pub fn get<T: Send + Sync + 'static>(&self) -> Option<&T> {
+        self.extensions.get()
+    }
+
+    /// The deadline the command must finish by, if the request set one
+    ///
+    /// A [`Deadline`] extension takes precedence over the deadline of the
+    /// task the command runs on.
+    pub fn deadline(&self) -> Option<Deadline> {
+        self.get::<Deadline>().copied().or_else(Deadline::current)
+    }
+
+    /// Time left until the deadline, if the request set one
+    pub fn remaining(&self) -> Option<Duration> {
+        self.deadline().map(|deadline| deadline.remaining())
+    }
+
+    /// The namespace `namespace` of the key-value store
+    ///
+    /// Commands keep small pieces of durable state there, such as the last
+    /// item they processed. The store is the [`KvStore`] extension; the CLI
+    /// attaches one kept in `~/.squirrel/kv`.
+    pub fn store(&self, namespace: &str) -> Result<Namespace, CommandError> {
+        let store = self
+            .get::<KvStore>()
+            .ok_or_else(|| CommandError::ResourceError("No key-value store is available".to_string()))?;
+        store
+            .namespace(namespace)
+            .map_err(|e| CommandError::ValidationError(e.to_string()))
+    }
+
+    /// Attach a value, replacing any previous value of the same type
+    pub fn insert<T: Send + Sync + 'static>(&mut self, value: T) -> Option<Arc<T>> {
+        self.extensions.insert(value)
+    }
+
+    /// Capture the state that can be sent to a remote agent or server
+    ///
+    /// Arguments travel separately, and only extensions registered in
+    /// `schemas` are included.
+    pub fn to_wire(&self, schemas: &SchemaRegistry) -> Result<WireContext, CommandError> {
+        Ok(WireContext {
+            request_id: self.get::<RequestId>().map(ToString::to_string),
+            extensions: schemas.encode_extensions(&self.extensions)?,
+            deadline_ms: self.deadline().map(|deadline| deadline.remaining_millis()),
+            ..WireContext::default()
+        })
+    }
+
+    /// Create a command context from state received from a peer
+    pub fn from_wire(
+        matches: ArgMatches,
+        context: &WireContext,
+        schemas: &SchemaRegistry,
+    ) -> Result<Self, CommandError> {
+        let mut extensions = schemas.decode_extensions(&context.extensions)?;
+        if let Some(id) = &context.request_id {
+            if !extensions.contains::<RequestId>() {
+                extensions.insert(RequestId(id.clone()));
+            }
+        }
+        if let Some(millis) = context.deadline_ms {
+            extensions.insert(Deadline::from_remaining_millis(millis));
+        }
+        Ok(Self::with_extensions(matches, extensions))
+    }
+}
@@ -0,0 +1,169 @@
+//! Contexts command
+//!
+//! Searches the contexts recorded in the MCP persistence directory by field
+//! values and full text, without a running server.
+
+use std::path::PathBuf;
+
+use clap::{Arg, ArgAction, Command as ClapCommand};
+use squirrel_commands::{Command, CommandError};
+use squirrel_mcp::persistence::{MCPPersistence, PersistenceConfig};
+use squirrel_mcp::{Context, ContextIndex, ContextQuery};
+
+/// Contexts command implementation
+#[derive(Debug, Clone, Default)]
+pub struct ContextsCommand;
+
+impl ContextsCommand {
+    /// Create a new contexts command
+    pub fn new() -> Self {
+        Self
+    }
+}
+
+impl Command for ContextsCommand {
+    fn name(&self) -> &str {
+        "contexts"
+    }
+
+    fn description(&self) -> &str {
+        "Search stored MCP contexts"
+    }
+
+    fn parser(&self) -> ClapCommand {
+        ClapCommand::new("contexts")
+            .about("Search stored MCP contexts")
+            .subcommand_required(true)
+            .subcommand(ClapCommand::new("search")
+                .about("Find contexts, e.g. 'params.dataset = GRCh38 and tool ~ bwa'")
+                .arg(Arg::new("query")
+                    .help("Clauses 'path = value', 'path != value', 'path ~ word' or bare words")
+                    .required(true)
+                    .num_args(1..))
+                .arg(Arg::new("data-dir")
+                    .long("data-dir")
+                    .help("MCP persistence directory [default: data/mcp]")
+                    .value_name("DIR"))
+                .arg(Arg::new("limit")
+                    .long("limit")
+                    .help("Maximum number of contexts to show")
+                    .value_name("N")
+                    .default_value("50")
+                    .value_parser(clap::value_parser!(usize)))
+                .arg(Arg::new("json")
+                    .long("json")
+                    .help("Output in JSON format")
+                    .action(ArgAction::SetTrue)))
+    }
+
+    fn clone_box(&self) -> Box<dyn Command> {
+        Box::new(self.clone())
+    }
+
+    fn execute(&self, args: &[String]) -> Result<String, CommandError> {
+        let matches = self
+            .parser()
+            .try_get_matches_from(std::iter::once("contexts".to_string()).chain(args.iter().cloned()))
+            .map_err(|e| CommandError::ValidationError(e.to_string()))?;
+        let Some(("search", sub)) = matches.subcommand() else {
+            return Err(CommandError::ValidationError("Unknown contexts subcommand".to_string()));
+        };
+
+        let query = sub
+            .get_many::<String>("query")
+            .unwrap_or_default()
+            .cloned()
+            .collect::<Vec<_>>()
+            .join(" ");
+        let query: ContextQuery = query.parse().map_err(|e| CommandError::ValidationError(format!("{e}")))?;
+
+        let mut config = PersistenceConfig::default();
+        if let Some(dir) = sub.get_one::<String>("data-dir") {
+            config.data_dir = PathBuf::from(dir);
+        }
+        let contexts = MCPPersistence::new(config)
+            .load_contexts()
+            .map_err(|e| CommandError::ResourceError(format!("{e}")))?;
+        let ids = ContextIndex::from_contexts(&contexts).search(&query);
+        let mut found: Vec<&Context> = contexts.iter().filter(|context| ids.contains(&context.id)).collect();
+        found.sort_by_key(|context| std::cmp::Reverse(context.updated_at));
+        found.truncate(sub.get_one::<usize>("limit").copied().unwrap_or(50));
+
+        if sub.get_flag("json") {
+            return serde_json::to_string_pretty(&found).map_err(|e| CommandError::ExecutionError(e.to_string()));
+        }
+        if found.is_empty() {
+            return Ok("No matching contexts".to_string());
+        }
+        let lines: Vec<String> = found
+            .iter()
+            .map(|context| format!("{} {} (updated {})", context.id, context.name, context.updated_at.to_rfc3339()))
+            .collect();
+        Ok(lines.join("\n"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::Utc;
+    use squirrel_mcp::persistence::PersistentState;
+    use squirrel_mcp::sync::{StateChange, StateOperation};
+    use tempfile::tempdir;
+    use uuid::Uuid;
+
+    #[test]
+    fn test_search_persisted_contexts() {
+        let dir = tempdir().unwrap();
+        let persistence = MCPPersistence::new(PersistenceConfig {
+            data_dir: dir.path().to_path_buf(),
+            ..PersistenceConfig::default()
+        });
+        let changes = [("align", "GRCh38"), ("call", "hg19")]
+            .iter()
+            .enumerate()
+            .map(|(version, (name, dataset))| {
+                let context = Context {
+                    id: Uuid::new_v4(),
+                    name: (*name).to_string(),
+                    data: serde_json::json!({"params": {"dataset": dataset}}),
+                    metadata: None,
+                    parent_id: None,
+                    created_at: Utc::now(),
+                    updated_at: Utc::now(),
+                    expires_at: None,
+                };
+                StateChange {
+                    id: Uuid::new_v4(),
+                    context_id: context.id,
+                    operation: StateOperation::Create,
+                    data: serde_json::to_value(&context).unwrap(),
+                    timestamp: Utc::now(),
+                    version: version as u64,
+                }
+            })
+            .collect();
+        persistence
+            .save_state(&PersistentState {
+                contexts: vec![],
+                changes,
+                last_version: 2,
+                last_sync: Utc::now(),
+                id: Uuid::new_v4().to_string(),
+            })
+            .unwrap();
+
+        let command = ContextsCommand::new();
+        let search = |query: &[&str]| {
+            let mut args = vec!["search".to_string()];
+            args.extend(query.iter().map(ToString::to_string));
+            args.extend(["--data-dir".to_string(), dir.path().display().to_string()]);
+            command.execute(&args)
+        };
+        let output = search(&["params.dataset", "=", "GRCh38"]).unwrap();
+        assert!(output.contains(" align "), "{output}");
+        assert!(!output.contains(" call "), "{output}");
+        assert_eq!(search(&["T2T"]).unwrap(), "No matching contexts");
+        assert!(search(&["params.dataset", "="]).is_err());
+    }
+}
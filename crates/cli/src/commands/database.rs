@@ -0,0 +1,25 @@
+//! Web database selection shared by the commands that open it
+
+use clap::ArgMatches;
+use squirrel_commands::CommandError;
+
+/// Environment variable naming the web database, as for the server
+pub(crate) const DATABASE_URL_ENV: &str = "DATABASE_URL";
+
+/// The web database named by `--database` or `$DATABASE_URL`, if any
+pub(crate) fn configured_database_url(matches: &ArgMatches) -> Option<String> {
+    matches
+        .get_one::<String>("database")
+        .cloned()
+        .or_else(|| std::env::var(DATABASE_URL_ENV).ok())
+}
+
+/// The web database named by `--database` or `$DATABASE_URL`
+///
+/// # Errors
+/// Returns a validation error if neither names one
+pub(crate) fn database_url(matches: &ArgMatches) -> Result<String, CommandError> {
+    configured_database_url(matches).ok_or_else(|| {
+        CommandError::ValidationError(format!("No web database; pass --database or set {DATABASE_URL_ENV}"))
+    })
+}
@@ -0,0 +1,205 @@
+//! Deploy command
+//!
+//! Generates a Dockerfile, a Compose file and Kubernetes manifests for the
+//! web server from its configuration file, so a deployment runs with the
+//! settings tried locally.
+
+use std::path::{Path, PathBuf};
+
+use clap::{Arg, ArgAction, ArgMatches, Command as ClapCommand};
+use squirrel_commands::{Command, CommandError};
+use squirrel_web::config::Config;
+use squirrel_web::deployment::{self, Deployment, DeploymentOptions, DeploymentProfile};
+
+/// Deploy command implementation
+#[derive(Debug, Clone, Default)]
+pub struct DeployCommand;
+
+impl DeployCommand {
+    /// Create a new deploy command
+    pub fn new() -> Self {
+        Self
+    }
+
+    /// Deployment settings given on the command line
+    fn options(matches: &ArgMatches) -> Result<DeploymentOptions, CommandError> {
+        let defaults = DeploymentOptions::default();
+        let profile = match matches.get_one::<String>("profile") {
+            Some(profile) => profile.parse::<DeploymentProfile>().map_err(CommandError::ValidationError)?,
+            None => defaults.profile,
+        };
+        Ok(DeploymentOptions {
+            profile,
+            name: matches.get_one::<String>("name").cloned().unwrap_or(defaults.name),
+            image: matches.get_one::<String>("image").cloned().unwrap_or(defaults.image),
+            namespace: matches.get_one::<String>("namespace").cloned(),
+            port: matches.get_one::<u16>("port").copied().unwrap_or(defaults.port),
+            replicas: matches.get_one::<u32>("replicas").copied().unwrap_or(defaults.replicas),
+        })
+    }
+
+    /// Writes the files of `deployment` under `output`
+    ///
+    /// Nothing is written if a file exists and `force` is not set.
+    fn write(deployment: &Deployment, output: &Path, force: bool) -> Result<Vec<PathBuf>, CommandError> {
+        let paths: Vec<PathBuf> = deployment.files.iter().map(|file| output.join(&file.path)).collect();
+        if !force {
+            if let Some(existing) = paths.iter().find(|path| path.exists()) {
+                return Err(CommandError::ValidationError(format!(
+                    "{} already exists; pass --force to overwrite",
+                    existing.display()
+                )));
+            }
+        }
+        let io_error = |path: &Path, e: std::io::Error| {
+            CommandError::ResourceError(format!("Failed to write {}: {}", path.display(), e))
+        };
+        for (file, path) in deployment.files.iter().zip(&paths) {
+            if let Some(parent) = path.parent() {
+                std::fs::create_dir_all(parent).map_err(|e| io_error(parent, e))?;
+            }
+            std::fs::write(path, &file.contents).map_err(|e| io_error(path, e))?;
+        }
+        Ok(paths)
+    }
+}
+
+impl Command for DeployCommand {
+    fn name(&self) -> &str {
+        "deploy"
+    }
+
+    fn description(&self) -> &str {
+        "Generate container images and manifests for the web server"
+    }
+
+    fn parser(&self) -> ClapCommand {
+        ClapCommand::new("deploy")
+            .about("Generate container images and manifests for the web server")
+            .subcommand_required(true)
+            .arg(Arg::new("json")
+                .long("json")
+                .help("Output in JSON format")
+                .action(ArgAction::SetTrue)
+                .global(true))
+            .subcommand(ClapCommand::new("generate")
+                .about("Write a Dockerfile, docker-compose.yml and Kubernetes manifests")
+                .arg(Arg::new("config")
+                    .long("config")
+                    .help("Server configuration file, TOML or JSON [default: built-in defaults]")
+                    .value_name("FILE"))
+                .arg(Arg::new("profile")
+                    .long("profile")
+                    .help("Database setup")
+                    .value_parser(["mock-db"])
+                    .default_value("mock-db"))
+                .arg(Arg::new("output")
+                    .long("output")
+                    .short('o')
+                    .help("Directory to write the files to")
+                    .value_name("DIR")
+                    .default_value("deploy"))
+                .arg(Arg::new("image")
+                    .long("image")
+                    .help("Image the manifests run [default: squirrel-web:latest]"))
+                .arg(Arg::new("name")
+                    .long("name")
+                    .help("Application name used for services and labels [default: squirrel]"))
+                .arg(Arg::new("namespace")
+                    .long("namespace")
+                    .help("Kubernetes namespace [default: the current one]"))
+                .arg(Arg::new("port")
+                    .long("port")
+                    .help("Port the server listens on [default: 3000]")
+                    .value_parser(clap::value_parser!(u16)))
+                .arg(Arg::new("replicas")
+                    .long("replicas")
+                    .help("Number of server replicas [default: 1]")
+                    .value_parser(clap::value_parser!(u32).range(1..)))
+                .arg(Arg::new("force")
+                    .long("force")
+                    .help("Overwrite existing files")
+                    .action(ArgAction::SetTrue)))
+    }
+
+    fn clone_box(&self) -> Box<dyn Command> {
+        Box::new(self.clone())
+    }
+
+    fn execute(&self, args: &[String]) -> Result<String, CommandError> {
+        let matches = self
+            .parser()
+            .try_get_matches_from(std::iter::once("deploy".to_string()).chain(args.iter().cloned()))
+            .map_err(|e| CommandError::ValidationError(e.to_string()))?;
+        let json = matches.get_flag("json");
+
+        match matches.subcommand() {
+            Some(("generate", sub)) => {
+                let config = match sub.get_one::<String>("config") {
+                    Some(path) => Config::load(Path::new(path))
+                        .map_err(|e| CommandError::ResourceError(format!("Failed to load {path}: {e}")))?,
+                    None => Config::default(),
+                };
+                let options = Self::options(sub)?;
+                let deployment = deployment::generate(&config, &options)
+                    .map_err(|e| CommandError::ExecutionError(e.to_string()))?;
+                let output = PathBuf::from(sub.get_one::<String>("output").map_or("deploy", String::as_str));
+                let paths = Self::write(&deployment, &output, sub.get_flag("force"))?;
+
+                if json {
+                    let files: Vec<String> = paths.iter().map(|path| path.display().to_string()).collect();
+                    return serde_json::to_string_pretty(&serde_json::json!({
+                        "profile": options.profile,
+                        "files": files,
+                        "warnings": deployment.warnings,
+                    }))
+                    .map_err(|e| CommandError::ExecutionError(e.to_string()));
+                }
+                let mut lines: Vec<String> = paths.iter().map(|path| format!("Wrote {}", path.display())).collect();
+                lines.extend(deployment.warnings.iter().map(|warning| format!("Warning: {warning}")));
+                Ok(lines.join("\n"))
+            }
+            _ => Err(CommandError::ValidationError("Unknown deploy subcommand".to_string())),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_generate() {
+        let dir = tempdir().unwrap();
+        let config = dir.path().join("server.toml");
+        std::fs::write(&config, "[memory_watchdog]\nbudget_mb = 512\n").unwrap();
+        let output = dir.path().join("out");
+
+        let command = DeployCommand::new();
+        let run = |extra: &[&str]| {
+            let mut args: Vec<String> = vec![
+                "generate".to_string(),
+                "--config".to_string(),
+                config.display().to_string(),
+                "--output".to_string(),
+                output.display().to_string(),
+            ];
+            args.extend(extra.iter().map(ToString::to_string));
+            command.execute(&args)
+        };
+
+        let report = run(&[]).unwrap();
+        assert!(report.contains("Wrote"), "{report}");
+        let manifest = std::fs::read_to_string(output.join("k8s/deployment.yaml")).unwrap();
+        assert!(manifest.contains("512Mi"), "{manifest}");
+        assert!(output.join("Dockerfile").is_file());
+
+        // Existing files are kept unless forced
+        assert!(matches!(run(&[]), Err(CommandError::ValidationError(_))));
+        let report = run(&["--replicas", "2", "--force"]).unwrap();
+        assert!(report.contains("Warning:"), "{report}");
+        assert!(run(&["--profile", "postgres", "--force"]).is_err());
+        assert!(run(&["--profile", "helm", "--force"]).is_err());
+    }
+}
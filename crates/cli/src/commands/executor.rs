@@ -3,10 +3,19 @@
 //! This module provides functionality for executing commands in the CLI.
 
 use clap::ArgMatches;
+use std::path::PathBuf;
 use std::sync::Arc;
-use tracing::{debug, error, info};
+use std::time::Duration;
+use tracing::{debug, error, info, warn};
 
-use squirrel_commands::{CommandError, CommandRegistry};
+use squirrel_commands::cache::ResultCache;
+use squirrel_commands::environments::{EnvironmentRegistry, ExecutionEnvironment};
+use squirrel_commands::extensions::{Extensions, RequestId};
+use squirrel_commands::history::CommandHistory;
+use squirrel_commands::manifest::{ManifestOptions, RunManifest, RunOutcome};
+use squirrel_commands::policy::{self, CommandPolicy, PolicySubject, PolicyTarget};
+use squirrel_commands::{Command, CommandError, CommandRegistry};
+use squirrel_core::deadline::Deadline;
 use crate::formatter::Factory as FormatterFactory;
 use crate::commands::context::CommandContext;
 
@@ -15,6 +24,21 @@ use crate::commands::context::CommandContext;
 pub struct ExecutionContext {
     /// Registry of available commands
     registry: Arc<CommandRegistry>,
+    /// Extensions copied into every command context
+    extensions: Extensions,
+    /// Execution environments commands are run in
+    environments: EnvironmentRegistry,
+    /// Where to write a reproducibility manifest, and what it captures
+    manifest_capture: Option<(PathBuf, ManifestOptions)>,
+    /// Cache serving results of identical runs, and what its keys capture
+    result_cache: Option<(ResultCache, ManifestOptions)>,
+    /// History recording each executed command
+    history: Option<Arc<CommandHistory>>,
+    /// Command policies, and the subjects whose policies apply
+    policies: Vec<CommandPolicy>,
+    subjects: Vec<PolicySubject>,
+    /// Maximum run time of each command
+    timeout: Option<Duration>,
 }
 
 impl ExecutionContext {
@@ -22,9 +46,66 @@ impl ExecutionContext {
     pub fn new(registry: Arc<CommandRegistry>) -> Self {
         Self {
             registry,
+            extensions: Extensions::new(),
+            environments: EnvironmentRegistry::default(),
+            manifest_capture: None,
+            result_cache: None,
+            history: None,
+            policies: Vec::new(),
+            subjects: Vec::new(),
+            timeout: None,
         }
     }
 
+    /// Attach extensions that every executed command will see
+    pub fn with_extensions(mut self, extensions: Extensions) -> Self {
+        self.extensions = extensions;
+        self
+    }
+
+    /// Run commands in the given execution environments
+    pub fn with_environments(mut self, environments: EnvironmentRegistry) -> Self {
+        self.environments = environments;
+        self
+    }
+
+    /// Write a reproducibility manifest of each executed command to `path`
+    pub fn with_manifest_capture(mut self, path: PathBuf, options: ManifestOptions) -> Self {
+        self.manifest_capture = Some((path, options));
+        self
+    }
+
+    /// Serve the cached result of an identical earlier run instead of executing
+    ///
+    /// Runs are identical when their command, arguments, tool and plugin
+    /// versions and the hashes of the input files in `options` match.
+    pub fn with_result_cache(mut self, cache: ResultCache, options: ManifestOptions) -> Self {
+        self.result_cache = Some((cache, options));
+        self
+    }
+
+    /// Record each executed command, and whether it succeeded, in `history`
+    pub fn with_history(mut self, history: Arc<CommandHistory>) -> Self {
+        self.history = Some(history);
+        self
+    }
+
+    /// Refuse commands the policies attached to any of `subjects` deny
+    pub fn with_policies(mut self, policies: Vec<CommandPolicy>, subjects: Vec<PolicySubject>) -> Self {
+        self.policies = policies;
+        self.subjects = subjects;
+        self
+    }
+
+    /// Stop each command that has not finished `timeout` after it started
+    ///
+    /// A [`Deadline`] extension or the environment's timeout stop it
+    /// earlier if they leave less time.
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = Some(timeout);
+        self
+    }
+
     /// Execute a command with the given arguments
     ///
     /// # Arguments
@@ -34,8 +115,31 @@ impl ExecutionContext {
     pub async fn execute_command(&self, command_name: &str, matches: ArgMatches) -> Result<(), CommandError> {
         debug!("Executing command: {}", command_name);
         
-        // Create command context
-        let context = CommandContext::new(matches);
+        // Create command context, tagging it with a request ID unless one was attached
+        let mut context = CommandContext::with_extensions(matches, self.extensions.clone());
+        if !context.extensions().contains::<RequestId>() {
+            context.insert(RequestId::generate());
+        }
+        if let Some(request_id) = context.get::<RequestId>() {
+            debug!("Command '{}' has request ID {}", command_name, request_id);
+        }
+        if !context.extensions().contains::<ExecutionEnvironment>() {
+            let environment = self.environments.for_command(command_name, std::env::vars())?;
+            debug!("Command '{}' runs in environment '{}'", command_name, environment.name);
+            context.insert(environment);
+        }
+
+        // Bound the command by the request's deadline and the timeouts
+        let timeouts = [
+            self.timeout,
+            context.get::<ExecutionEnvironment>().and_then(|environment| environment.limits.timeout()),
+        ];
+        let deadline = timeouts.into_iter().flatten().fold(context.deadline(), |deadline, timeout| {
+            Some(deadline.map_or_else(|| Deadline::after(timeout), |deadline| deadline.within(timeout)))
+        });
+        if let Some(deadline) = deadline {
+            context.insert(deadline);
+        }
         
         // Get the command from the registry
         let command = self.registry.get_command(command_name)?;
@@ -44,9 +148,65 @@ impl ExecutionContext {
         let args: Vec<String> = context.matches().get_many("args")
             .map(|v| v.cloned().collect())
             .unwrap_or_default();
+
+        // Refuse the command before dispatch if a policy denies it
+        let target = PolicyTarget::command(command_name, &args, command.plugin());
+        if let Err(err) = policy::evaluate(&self.policies, &self.subjects, &target) {
+            error!("Command '{}' refused: {}", command_name, err);
+            return Err(err);
+        }
+        if deadline.is_some_and(|deadline| deadline.is_expired()) {
+            return Err(CommandError::DeadlineExceeded(format!("'{}' was not started", command_name)));
+        }
             
-        // Execute the command
-        match command.execute(&args) {
+        // Capture the environment before the command can change it
+        let manifest = match &self.manifest_capture {
+            Some((path, options)) => Some((path, RunManifest::capture(command_name, &args, options)?)),
+            None => None,
+        };
+
+        // Serve an identical earlier run from the cache, or execute and cache the result
+        let cached_run = match &self.result_cache {
+            Some((cache, options)) => Some((cache, RunManifest::capture(command_name, &args, options)?)),
+            None => None,
+        };
+        let cached = cached_run.as_ref().and_then(|(cache, run)| {
+            cache.lookup(run).unwrap_or_else(|err| {
+                warn!("Failed to read the result cache: {}", err);
+                None
+            })
+        });
+        let result = match cached {
+            Some(entry) => {
+                info!("Serving cached result {} of '{}'", entry.key, command_name);
+                Ok(entry.output)
+            }
+            None => {
+                let result = match deadline {
+                    Some(deadline) => execute_until(deadline, command_name, command, args.clone()).await,
+                    None => command.execute(&args),
+                };
+                if let (Some((cache, run)), Ok(output)) = (&cached_run, &result) {
+                    if let Err(err) = cache.store(run, output) {
+                        warn!("Failed to cache the result of '{}': {}", command_name, err);
+                    }
+                }
+                result
+            }
+        };
+        if let Some((path, mut manifest)) = manifest {
+            manifest.outcome = Some(RunOutcome::from_result(&result));
+            manifest.save(path)?;
+            info!("Wrote run manifest {} to {:?}", manifest.id, path);
+        }
+        if let Some(history) = &self.history {
+            let error = result.as_ref().err().map(ToString::to_string);
+            if let Err(err) = history.add(command_name.to_string(), args.clone(), error.is_none(), error, None) {
+                warn!("Failed to record '{}' in the command history: {}", command_name, err);
+            }
+        }
+
+        match result {
             Ok(output) => {
                 // Determine output format from context flags
                 let format = if context.matches().get_flag("json") {
@@ -75,4 +235,23 @@ impl ExecutionContext {
             }
         }
     }
-} 
\ No newline at end of file
+} 
+
+/// Run `command` on a blocking thread until it finishes or `deadline` passes
+///
+/// A command still running at the deadline is abandoned: its thread runs on,
+/// but the result is dropped and the caller sees
+/// [`CommandError::DeadlineExceeded`].
+async fn execute_until(
+    deadline: Deadline,
+    command_name: &str,
+    command: Box<dyn Command>,
+    args: Vec<String>,
+) -> Result<String, CommandError> {
+    let execution = tokio::task::spawn_blocking(move || command.execute(&args));
+    match deadline.run(execution).await {
+        Ok(Ok(result)) => result,
+        Ok(Err(err)) => Err(CommandError::ExecutionError(format!("'{}' panicked: {}", command_name, err))),
+        Err(_) => Err(CommandError::DeadlineExceeded(format!("'{}' was cancelled", command_name))),
+    }
+}
@@ -0,0 +1,186 @@
+//! Export command
+//!
+//! Writes CSV or Parquet extracts of jobs, command history, usage and
+//! experiments over a date range, as `GET /api/export` serves them. Jobs,
+//! command history and experiments are read from the web database; usage
+//! from the usage ledger. Output is written as it is encoded.
+
+use std::path::PathBuf;
+use std::sync::Arc;
+
+use clap::{Arg, ArgMatches, Command as ClapCommand};
+use squirrel_commands::{Command, CommandError};
+use squirrel_web::api::error::AppError;
+use squirrel_web::export::{ExportDataset, ExportRequest, Exporter};
+use squirrel_web::migrations;
+use tokio::io::AsyncWriteExt;
+
+use super::database::database_url;
+use super::usage_command::{parse_date, UsageCommand};
+
+/// Export command implementation
+#[derive(Debug, Clone, Default)]
+pub struct ExportCommand;
+
+impl ExportCommand {
+    /// Create a new export command
+    pub fn new() -> Self {
+        Self
+    }
+
+    /// Writes the extract to the output file
+    async fn run(matches: &ArgMatches) -> Result<String, CommandError> {
+        let invalid = |e: AppError| CommandError::ValidationError(e.to_string());
+        let request = ExportRequest {
+            dataset: matches
+                .get_one::<String>("dataset")
+                .map(String::as_str)
+                .unwrap_or_default()
+                .parse()
+                .map_err(invalid)?,
+            format: matches
+                .get_one::<String>("format")
+                .map(|format| format.parse())
+                .transpose()
+                .map_err(invalid)?
+                .unwrap_or_default(),
+            user: matches.get_one::<String>("user").cloned(),
+            from: matches.get_one::<String>("from").map(|text| parse_date(text, false)).transpose()?,
+            to: matches.get_one::<String>("to").map(|text| parse_date(text, true)).transpose()?,
+        };
+
+        let exporter = if request.dataset == ExportDataset::Usage {
+            Exporter::new().with_usage_ledger(Arc::new(UsageCommand::open(matches)?))
+        } else {
+            let url = database_url(matches)?;
+            let pool = migrations::connect(&url)
+                .await
+                .map_err(|e| CommandError::ResourceError(format!("Failed to open {url}: {e}")))?;
+            Exporter::new().with_database(pool)
+        };
+
+        let path = matches
+            .get_one::<String>("output")
+            .map(PathBuf::from)
+            .unwrap_or_else(|| PathBuf::from(request.file_name()));
+        let dataset = request.dataset;
+        let mut chunks = exporter.export(request).map_err(invalid)?.into_inner();
+        let write_error = |e: std::io::Error| CommandError::ResourceError(format!("Failed to write {}: {e}", path.display()));
+        let mut file = tokio::fs::File::create(&path).await.map_err(write_error)?;
+        let mut written = 0;
+        while let Some(chunk) = chunks.recv().await {
+            let chunk = chunk.map_err(|e| CommandError::ExecutionError(format!("{e}; is the web database migrated?")))?;
+            file.write_all(&chunk).await.map_err(write_error)?;
+            written += chunk.len();
+        }
+        file.flush().await.map_err(write_error)?;
+        Ok(format!("Wrote {} bytes of {} to {}", written, dataset, path.display()))
+    }
+}
+
+impl Command for ExportCommand {
+    fn name(&self) -> &str {
+        "export"
+    }
+
+    fn description(&self) -> &str {
+        "Export jobs, command history, usage or experiments as CSV or Parquet"
+    }
+
+    fn tags(&self) -> Vec<String> {
+        vec!["analysis".to_string()]
+    }
+
+    fn parser(&self) -> ClapCommand {
+        ClapCommand::new("export")
+            .about("Export jobs, command history, usage or experiments as CSV or Parquet")
+            .arg(Arg::new("dataset")
+                .help("Dataset to export: jobs, commands, usage or experiments")
+                .required(true))
+            .arg(Arg::new("format")
+                .long("format")
+                .short('f')
+                .help("Output format: csv or parquet [default: csv]")
+                .value_name("FORMAT"))
+            .arg(Arg::new("output")
+                .long("output")
+                .short('o')
+                .help("File to write [default: DATASET.FORMAT]")
+                .value_name("FILE"))
+            .arg(Arg::new("from")
+                .long("from")
+                .help("Start of the range, as YYYY-MM-DD or RFC 3339")
+                .value_name("DATE"))
+            .arg(Arg::new("to")
+                .long("to")
+                .help("End of the range, as YYYY-MM-DD (inclusive) or RFC 3339")
+                .value_name("DATE"))
+            .arg(Arg::new("user")
+                .long("user")
+                .help("Only export this user's rows")
+                .value_name("USER"))
+            .arg(Arg::new("database")
+                .long("database")
+                .help("Web database URL [default: $DATABASE_URL]")
+                .value_name("URL"))
+            .arg(Arg::new("ledger")
+                .long("ledger")
+                .help("Usage ledger file, for usage [default: ~/.squirrel/usage.json]")
+                .value_name("FILE"))
+    }
+
+    fn clone_box(&self) -> Box<dyn Command> {
+        Box::new(self.clone())
+    }
+
+    fn execute(&self, args: &[String]) -> Result<String, CommandError> {
+        let matches = self
+            .parser()
+            .try_get_matches_from(std::iter::once("export".to_string()).chain(args.iter().cloned()))
+            .map_err(|e| CommandError::ValidationError(e.to_string()))?;
+        let run = Self::run(&matches);
+        match tokio::runtime::Handle::try_current() {
+            // Run from the CLI's async entry point
+            Ok(handle) => tokio::task::block_in_place(|| handle.block_on(run)),
+            Err(_) => tokio::runtime::Runtime::new()
+                .map_err(|e| CommandError::ExecutionError(format!("Failed to create runtime: {}", e)))?
+                .block_on(run),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::commands::{MigrateCommand, TagsCommand};
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_export_experiments_to_csv() {
+        let dir = tempdir().unwrap();
+        let database = format!("sqlite://{}", dir.path().join("web.db").display());
+        let migrate = ["up", "--store", "web", "--database", &database].map(String::from);
+        MigrateCommand::new().execute(&migrate).unwrap();
+        let tag = ["add", "job", "j1", "-t", "experiment=rnaseq,urgent", "--user", "alice", "--database", &database];
+        TagsCommand::new().execute(&tag.map(String::from)).unwrap();
+        let output = dir.path().join("experiments.csv");
+        let run = |args: &[&str]| {
+            let mut args: Vec<String> = args.iter().map(ToString::to_string).collect();
+            args.extend(["--database".to_string(), database.clone()]);
+            ExportCommand::new().execute(&args)
+        };
+
+        let message = run(&["experiments", "--user", "alice", "-o", output.to_str().unwrap()]).unwrap();
+        assert!(message.starts_with("Wrote "), "{message}");
+        let csv = std::fs::read_to_string(&output).unwrap();
+        let lines: Vec<&str> = csv.lines().collect();
+        assert_eq!(lines[0], "experiment,resource_kind,resource_id,user_id,tagged_at");
+        assert_eq!(lines.len(), 2);
+        assert!(lines[1].starts_with("rnaseq,job,j1,alice,"), "{csv}");
+
+        run(&["experiments", "--user", "bob", "-o", output.to_str().unwrap()]).unwrap();
+        assert_eq!(std::fs::read_to_string(&output).unwrap().lines().count(), 1);
+        assert!(run(&["datasets"]).is_err());
+        assert!(run(&["jobs", "--from", "2026-13-01"]).is_err());
+    }
+}
@@ -0,0 +1,244 @@
+//! I18n command
+//!
+//! Maintains message catalogs: `extract` collects the keys passed to `tr!`
+//! in Rust sources into a catalog template, keeping the translations of an
+//! existing catalog, and `check` lists the messages a catalog is missing
+//! compared to its reference locale. Plugins use both to ship catalogs in
+//! their `locales/<locale>/<domain>.ftl` files.
+
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use clap::{Arg, ArgAction, ArgMatches, Command as ClapCommand};
+use squirrel_commands::{Command, CommandError};
+use squirrel_core::i18n::{self, localizer, Catalog, ExtractedKey, Locale, DEFAULT_DOMAIN};
+use squirrel_core::tr;
+
+/// I18n command implementation
+#[derive(Debug, Clone, Default)]
+pub struct I18nCommand;
+
+impl I18nCommand {
+    /// Create a new i18n command
+    pub fn new() -> Self {
+        Self
+    }
+
+    /// Writes a catalog template of the keys used in the given sources
+    fn extract(matches: &ArgMatches) -> Result<String, CommandError> {
+        let domain = matches
+            .get_one::<String>("domain")
+            .map(String::as_str)
+            .unwrap_or(DEFAULT_DOMAIN);
+        let mut keys = Vec::new();
+        for path in matches.get_many::<String>("path").into_iter().flatten() {
+            for file in rust_sources(Path::new(path))? {
+                let source = fs::read_to_string(&file)
+                    .map_err(|e| CommandError::ResourceError(format!("Cannot read {}: {e}", file.display())))?;
+                keys.extend(
+                    i18n::extract_keys(&source)
+                        .into_iter()
+                        .filter(|key| key.domain == domain)
+                        .map(|key| (key, file.display().to_string())),
+                );
+            }
+        }
+        let existing = match matches.get_one::<String>("merge") {
+            Some(path) => Some(load_catalog(Path::new(path))?),
+            None => None,
+        };
+
+        let mut template = String::new();
+        i18n::write_template(&mut template, &keys, existing.as_ref())
+            .map_err(|e| CommandError::ExecutionError(e.to_string()))?;
+        match matches.get_one::<String>("out") {
+            Some(out) => {
+                fs::write(out, template)
+                    .map_err(|e| CommandError::ResourceError(format!("Cannot write {out}: {e}")))?;
+                let count = distinct_keys(&keys);
+                Ok(format!(
+                    "{}\n{}",
+                    tr!("i18n-extracted", count = count, domain = domain),
+                    tr!("i18n-wrote", path = out)
+                ))
+            }
+            None => Ok(template),
+        }
+    }
+
+    /// Lists the messages a catalog lacks compared to its reference
+    ///
+    /// Fails if any are missing, so the check can gate a build.
+    fn check(matches: &ArgMatches) -> Result<String, CommandError> {
+        let path = matches.get_one::<String>("catalog").map(PathBuf::from).unwrap_or_default();
+        let catalog = load_catalog(&path)?;
+        let reference = match matches.get_one::<String>("reference") {
+            Some(reference) => load_catalog(Path::new(reference))?,
+            None => default_reference(&catalog, &path)?,
+        };
+
+        let missing: Vec<&str> = reference.keys().filter(|key| !catalog.contains(key)).collect();
+        let unused: Vec<&str> = catalog.keys().filter(|key| !reference.contains(key)).collect();
+        let mut lines = vec![tr!("i18n-missing-messages", count = missing.len())];
+        lines.extend(missing.iter().map(|key| format!("  {key}")));
+        if !unused.is_empty() {
+            lines.push(tr!("i18n-unused-messages", count = unused.len()));
+            lines.extend(unused.iter().map(|key| format!("  {key}")));
+        }
+        let report = lines.join("\n");
+        if missing.is_empty() {
+            Ok(report)
+        } else {
+            Err(CommandError::ValidationError(report))
+        }
+    }
+}
+
+impl Command for I18nCommand {
+    fn name(&self) -> &str {
+        "i18n"
+    }
+
+    fn description(&self) -> &str {
+        "Extract and check message catalogs"
+    }
+
+    fn parser(&self) -> ClapCommand {
+        ClapCommand::new("i18n")
+            .about("Extract and check message catalogs")
+            .subcommand_required(true)
+            .subcommand(ClapCommand::new("extract")
+                .about("Write a catalog template of the message keys used in Rust sources")
+                .arg(Arg::new("path")
+                    .help("Source files or directories to scan")
+                    .required(true)
+                    .action(ArgAction::Append))
+                .arg(Arg::new("domain")
+                    .long("domain")
+                    .help("Message domain to extract [default: squirrel]")
+                    .value_name("DOMAIN"))
+                .arg(Arg::new("merge")
+                    .long("merge")
+                    .help("Existing <locale>/<domain>.ftl catalog whose messages are kept")
+                    .value_name("FILE"))
+                .arg(Arg::new("out")
+                    .long("out")
+                    .short('o')
+                    .help("Write the template to a file instead of printing it")
+                    .value_name("FILE")))
+            .subcommand(ClapCommand::new("check")
+                .about("List the messages a catalog is missing")
+                .arg(Arg::new("catalog")
+                    .help("Catalog file, stored as <locale>/<domain>.ftl")
+                    .required(true))
+                .arg(Arg::new("reference")
+                    .long("reference")
+                    .help("Catalog to compare with [default: the English catalog of the domain]")
+                    .value_name("FILE")))
+    }
+
+    fn clone_box(&self) -> Box<dyn Command> {
+        Box::new(self.clone())
+    }
+
+    fn execute(&self, args: &[String]) -> Result<String, CommandError> {
+        let matches = self
+            .parser()
+            .try_get_matches_from(std::iter::once("i18n".to_string()).chain(args.iter().cloned()))
+            .map_err(|e| CommandError::ValidationError(e.to_string()))?;
+        match matches.subcommand() {
+            Some(("extract", sub)) => Self::extract(sub),
+            Some(("check", sub)) => Self::check(sub),
+            _ => Err(CommandError::ValidationError("Unknown i18n subcommand".to_string())),
+        }
+    }
+}
+
+/// Loads a catalog file
+fn load_catalog(path: &Path) -> Result<Catalog, CommandError> {
+    Catalog::load(path).map_err(|e| CommandError::ResourceError(e.to_string()))
+}
+
+/// The English catalog beside `path`, or the built-in one of the default domain
+fn default_reference(catalog: &Catalog, path: &Path) -> Result<Catalog, CommandError> {
+    let english = Locale::english();
+    let sibling = path
+        .parent()
+        .and_then(Path::parent)
+        .map(|root| root.join(english.to_string()).join(path.file_name().unwrap_or_default()));
+    match sibling {
+        Some(sibling) if sibling.is_file() => load_catalog(&sibling),
+        _ => localizer().catalog(catalog.domain(), &english).ok_or_else(|| {
+            CommandError::ValidationError(format!(
+                "No English catalog of domain {}; pass --reference",
+                catalog.domain()
+            ))
+        }),
+    }
+}
+
+/// Rust source files at `path`, searching directories recursively
+fn rust_sources(path: &Path) -> Result<Vec<PathBuf>, CommandError> {
+    if !path.is_dir() {
+        return Ok(vec![path.to_path_buf()]);
+    }
+    let entries = fs::read_dir(path)
+        .map_err(|e| CommandError::ResourceError(format!("Cannot read {}: {e}", path.display())))?;
+    let mut paths: Vec<PathBuf> = entries.filter_map(|entry| entry.ok().map(|entry| entry.path())).collect();
+    paths.sort();
+    let mut files = Vec::new();
+    for path in paths {
+        if path.is_dir() {
+            files.extend(rust_sources(&path)?);
+        } else if path.extension().is_some_and(|ext| ext == "rs") {
+            files.push(path);
+        }
+    }
+    Ok(files)
+}
+
+/// Number of distinct keys among the extracted ones
+fn distinct_keys(keys: &[(ExtractedKey, String)]) -> usize {
+    let mut names: Vec<&str> = keys.iter().map(|(key, _)| key.key.as_str()).collect();
+    names.sort_unstable();
+    names.dedup();
+    names.len()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_extract_merges_and_check_lists_missing_messages() {
+        let dir = tempdir().unwrap();
+        fs::create_dir_all(dir.path().join("src/nested")).unwrap();
+        fs::write(dir.path().join("src/lib.rs"), r#"tr!(domain: "acme", "greeting", name = n);"#).unwrap();
+        fs::write(dir.path().join("src/nested/job.rs"), r#"tr!(domain: "acme", "job-done"); tr!("other");"#).unwrap();
+        fs::create_dir_all(dir.path().join("locales/en")).unwrap();
+        fs::create_dir_all(dir.path().join("locales/de")).unwrap();
+        let english = dir.path().join("locales/en/acme.ftl");
+        let german = dir.path().join("locales/de/acme.ftl");
+        fs::write(&german, "greeting = Hallo, { $name }!\n").unwrap();
+
+        let run = |args: &[&str]| {
+            I18nCommand::new().execute(&args.iter().map(|arg| arg.to_string()).collect::<Vec<_>>())
+        };
+        let src = dir.path().join("src").display().to_string();
+        let output = run(&["extract", &src, "--domain", "acme", "--out", &english.display().to_string()]).unwrap();
+        assert!(output.contains(&english.display().to_string()), "{output}");
+        let template = fs::read_to_string(&english).unwrap();
+        assert!(template.contains("greeting = greeting"), "{template}");
+        assert!(template.contains("job-done = job-done"), "{template}");
+        assert!(!template.contains("other"), "{template}");
+
+        let merged = run(&["extract", &src, "--domain", "acme", "--merge", &german.display().to_string()]).unwrap();
+        assert!(merged.contains("greeting = Hallo, { $name }!"), "{merged}");
+
+        let error = run(&["check", &german.display().to_string()]).unwrap_err().to_string();
+        assert!(error.contains("job-done"), "{error}");
+        fs::write(&german, "greeting = Hallo\njob-done = Fertig\n").unwrap();
+        assert!(run(&["check", &german.display().to_string()]).is_ok());
+    }
+}
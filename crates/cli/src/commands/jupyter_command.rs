@@ -0,0 +1,118 @@
+//! Jupyter command
+//!
+//! `squirrel jupyter kernel` runs Squirrel as a Jupyter kernel on the
+//! sockets of the connection file Jupyter passes it, exposing the registered
+//! commands to notebooks as `%squirrel` magics. `squirrel jupyter install`
+//! writes the kernel spec that tells Jupyter to launch it.
+
+use std::path::PathBuf;
+use std::sync::{Arc, Weak};
+
+use clap::{Arg, ArgMatches, Command as ClapCommand};
+use squirrel_app::plugin::jupyter::{ConnectionInfo, JupyterBridge, JupyterKernel, KernelSpecInstallCommand};
+use squirrel_commands::{Command, CommandError, CommandRegistry};
+
+/// Jupyter command implementation
+#[derive(Debug, Clone, Default)]
+pub struct JupyterCommand {
+    /// Registry the notebook's `%squirrel` magics run commands from
+    registry: Weak<CommandRegistry>,
+}
+
+impl JupyterCommand {
+    /// Create a Jupyter command running notebook commands from `registry`
+    ///
+    /// The registry is held weakly because the command is registered in it.
+    pub fn new(registry: &Arc<CommandRegistry>) -> Self {
+        Self {
+            registry: Arc::downgrade(registry),
+        }
+    }
+
+    /// Runs the kernel until Jupyter shuts it down
+    async fn run_kernel(&self, matches: &ArgMatches) -> Result<String, CommandError> {
+        let path = matches.get_one::<String>("connection-file").map(PathBuf::from).unwrap_or_default();
+        let info = ConnectionInfo::from_file(&path).map_err(|e| CommandError::ResourceError(e.to_string()))?;
+        let registry = self
+            .registry
+            .upgrade()
+            .ok_or_else(|| CommandError::ExecutionError("The command registry is gone".to_string()))?;
+        let kernel = JupyterKernel::bind(JupyterBridge::new(registry), &info)
+            .await
+            .map_err(|e| CommandError::ExecutionError(e.to_string()))?;
+        kernel.serve().await.map_err(|e| CommandError::ExecutionError(e.to_string()))?;
+        Ok("Kernel shut down".to_string())
+    }
+}
+
+impl Command for JupyterCommand {
+    fn name(&self) -> &str {
+        "jupyter"
+    }
+
+    fn description(&self) -> &str {
+        "Run Squirrel as a Jupyter kernel or install its kernel spec"
+    }
+
+    fn parser(&self) -> ClapCommand {
+        ClapCommand::new("jupyter")
+            .about("Run Squirrel as a Jupyter kernel or install its kernel spec")
+            .subcommand_required(true)
+            .subcommand(ClapCommand::new("kernel")
+                .about("Run as a Jupyter kernel; Jupyter launches this through the kernel spec")
+                .arg(Arg::new("connection-file")
+                    .long("connection-file")
+                    .help("Connection file written by Jupyter")
+                    .value_name("FILE")
+                    .required(true)))
+            .subcommand(KernelSpecInstallCommand::new().parser().name("install"))
+    }
+
+    fn clone_box(&self) -> Box<dyn Command> {
+        Box::new(self.clone())
+    }
+
+    fn execute(&self, args: &[String]) -> Result<String, CommandError> {
+        let matches = self
+            .parser()
+            .try_get_matches_from(std::iter::once("jupyter".to_string()).chain(args.iter().cloned()))
+            .map_err(|e| CommandError::ValidationError(e.to_string()))?;
+        match matches.subcommand() {
+            Some(("kernel", matches)) => {
+                let run = self.run_kernel(matches);
+                match tokio::runtime::Handle::try_current() {
+                    // Run from the CLI's async entry point
+                    Ok(handle) => tokio::task::block_in_place(|| handle.block_on(run)),
+                    Err(_) => tokio::runtime::Runtime::new()
+                        .map_err(|e| CommandError::ExecutionError(format!("Failed to create runtime: {}", e)))?
+                        .block_on(run),
+                }
+            }
+            Some(("install", _)) => KernelSpecInstallCommand::new().execute(args.get(1..).unwrap_or_default()),
+            _ => Err(CommandError::ValidationError("Expected the kernel or install subcommand".to_string())),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use squirrel_app::plugin::jupyter::KernelSpec;
+
+    #[test]
+    fn test_install_writes_a_spec_launching_the_kernel() {
+        let dir = tempfile::tempdir().unwrap();
+        let command = JupyterCommand::default();
+        let prefix = dir.path().to_string_lossy().to_string();
+        let args = ["install", "--prefix", &prefix, "--executable", "/usr/bin/squirrel"].map(String::from);
+        command.execute(&args).unwrap();
+
+        let path = dir.path().join("share/jupyter/kernels/squirrel/kernel.json");
+        let spec: KernelSpec = serde_json::from_str(&std::fs::read_to_string(path).unwrap()).unwrap();
+        assert_eq!(spec.argv[..2], ["/usr/bin/squirrel", "jupyter"]);
+        let launched = command.parser().try_get_matches_from(&spec.argv[1..]).unwrap();
+        let (name, kernel) = launched.subcommand().unwrap();
+        assert_eq!(name, "kernel");
+        assert_eq!(kernel.get_one::<String>("connection-file").unwrap(), "{connection_file}");
+    }
+}
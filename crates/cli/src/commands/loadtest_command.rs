@@ -0,0 +1,253 @@
+//! Loadtest command
+//!
+//! Drives a running web server with a mix of job submissions, status polls
+//! and WebSocket subscriptions, then reports latency percentiles, error
+//! rates and throughput per operation. Thresholds given with `--assert`
+//! fail the command when they are not met, for performance checks before a
+//! release.
+
+use std::time::Duration;
+
+use clap::{Arg, ArgAction, ArgMatches, Command as ClapCommand};
+use squirrel_commands::{Command, CommandError};
+use squirrel_web::loadtest::{LatencyStats, LoadTest, LoadTestConfig, LoadTestReport, RequestMix, Threshold};
+
+/// Environment variable holding the access token requests are made with
+const TOKEN_ENV: &str = "SQUIRREL_LOADTEST_TOKEN";
+
+/// Loadtest command implementation
+#[derive(Debug, Clone, Default)]
+pub struct LoadtestCommand;
+
+impl LoadtestCommand {
+    /// Create a new loadtest command
+    pub fn new() -> Self {
+        Self
+    }
+
+    /// Reads the load test and its thresholds from the arguments
+    fn config(matches: &ArgMatches) -> Result<(LoadTestConfig, Vec<Threshold>), CommandError> {
+        let invalid = |e: squirrel_web::loadtest::LoadTestError| CommandError::ValidationError(e.to_string());
+        let seconds = |name: &str| {
+            let seconds = matches.get_one::<f64>(name).copied().unwrap_or_default();
+            Duration::try_from_secs_f64(seconds)
+                .map_err(|_| CommandError::ValidationError(format!("--{name} must be a positive number of seconds")))
+        };
+        let mut config = LoadTestConfig {
+            target: matches.get_one::<String>("target").cloned().unwrap_or_default(),
+            token: matches
+                .get_one::<String>("token")
+                .cloned()
+                .or_else(|| std::env::var(TOKEN_ENV).ok().filter(|token| !token.is_empty())),
+            concurrency: matches.get_one::<usize>("concurrency").copied().unwrap_or(1),
+            duration: seconds("duration")?,
+            timeout: seconds("timeout")?,
+            ..LoadTestConfig::default()
+        };
+        if let Some(mix) = matches.get_one::<String>("mix") {
+            config.mix = mix.parse::<RequestMix>().map_err(invalid)?;
+        }
+        if let Some(name) = matches.get_one::<String>("job") {
+            config.job_name = name.clone();
+        }
+        if let Some(parameters) = matches.get_one::<String>("parameters") {
+            config.job_parameters = serde_json::from_str(parameters)
+                .map_err(|e| CommandError::ValidationError(format!("--parameters is not JSON: {e}")))?;
+        }
+        let thresholds = matches
+            .get_many::<String>("assert")
+            .into_iter()
+            .flatten()
+            .map(|threshold| threshold.parse::<Threshold>().map_err(invalid))
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok((config, thresholds))
+    }
+
+    /// Runs the load test, failing if a threshold is not met
+    async fn run(matches: &ArgMatches) -> Result<String, CommandError> {
+        let (config, thresholds) = Self::config(matches)?;
+        let target = config.target.clone();
+        let report = LoadTest::new(config)
+            .map_err(|e| CommandError::ValidationError(e.to_string()))?
+            .run()
+            .await;
+        let failed = report.check(&thresholds);
+
+        let output = if matches.get_flag("json") {
+            serde_json::to_string_pretty(&serde_json::json!({
+                "target": target,
+                "report": report,
+                "thresholds": thresholds.iter().map(ToString::to_string).collect::<Vec<_>>(),
+                "failed": failed,
+            }))
+            .map_err(|e| CommandError::ExecutionError(e.to_string()))?
+        } else {
+            format_report(&target, &report, thresholds.len(), &failed)
+        };
+        if !failed.is_empty() {
+            return Err(CommandError::ExecutionError(output));
+        }
+        Ok(output)
+    }
+}
+
+impl Command for LoadtestCommand {
+    fn name(&self) -> &str {
+        "loadtest"
+    }
+
+    fn description(&self) -> &str {
+        "Measure the web API's latency under load"
+    }
+
+    fn parser(&self) -> ClapCommand {
+        ClapCommand::new("loadtest")
+            .about("Measure the web API's latency under load")
+            .arg(Arg::new("target")
+                .long("target")
+                .help("Base URL of the web server")
+                .value_name("URL")
+                .default_value("http://127.0.0.1:3000"))
+            .arg(Arg::new("token")
+                .long("token")
+                .help("Access token requests are made with [default: $SQUIRREL_LOADTEST_TOKEN]")
+                .value_name("TOKEN"))
+            .arg(Arg::new("concurrency")
+                .long("concurrency")
+                .short('c')
+                .help("Requests made side by side")
+                .value_name("N")
+                .value_parser(clap::value_parser!(usize))
+                .default_value("10"))
+            .arg(Arg::new("duration")
+                .long("duration")
+                .short('d')
+                .help("Seconds to make requests for")
+                .value_name("SECS")
+                .value_parser(clap::value_parser!(f64))
+                .default_value("30"))
+            .arg(Arg::new("mix")
+                .long("mix")
+                .help("Weights of the operations, e.g. submit=1,poll=4,subscribe=1")
+                .value_name("MIX"))
+            .arg(Arg::new("job")
+                .long("job")
+                .help("Name of the submitted jobs [default: loadtest]")
+                .value_name("NAME"))
+            .arg(Arg::new("parameters")
+                .long("parameters")
+                .help("Parameters of the submitted jobs, as JSON")
+                .value_name("JSON"))
+            .arg(Arg::new("timeout")
+                .long("timeout")
+                .help("Seconds before a request counts as failed")
+                .value_name("SECS")
+                .value_parser(clap::value_parser!(f64))
+                .default_value("10"))
+            .arg(Arg::new("assert")
+                .long("assert")
+                .help("Fail unless a measure stays within a limit, e.g. p99<500 or submit.error_rate<1%; repeatable")
+                .value_name("THRESHOLD")
+                .action(ArgAction::Append))
+            .arg(Arg::new("json")
+                .long("json")
+                .help("Output in JSON format")
+                .action(ArgAction::SetTrue))
+    }
+
+    fn clone_box(&self) -> Box<dyn Command> {
+        Box::new(self.clone())
+    }
+
+    fn execute(&self, args: &[String]) -> Result<String, CommandError> {
+        let matches = self
+            .parser()
+            .try_get_matches_from(std::iter::once("loadtest".to_string()).chain(args.iter().cloned()))
+            .map_err(|e| CommandError::ValidationError(e.to_string()))?;
+        let run = Self::run(&matches);
+        match tokio::runtime::Handle::try_current() {
+            // Run from the CLI's async entry point
+            Ok(handle) => tokio::task::block_in_place(|| handle.block_on(run)),
+            Err(_) => tokio::runtime::Runtime::new()
+                .map_err(|e| CommandError::ExecutionError(format!("Failed to create runtime: {}", e)))?
+                .block_on(run),
+        }
+    }
+}
+
+/// Row of the report table
+fn format_stats(name: &str, stats: &LatencyStats) -> String {
+    format!(
+        "  {:<10} {:>9} {:>7.2}% {:>9.1} {:>9.1} {:>9.1} {:>9.1} {:>9.1} {:>9.1}",
+        name,
+        stats.requests,
+        stats.error_rate * 100.0,
+        stats.throughput,
+        stats.p50_ms,
+        stats.p90_ms,
+        stats.p95_ms,
+        stats.p99_ms,
+        stats.max_ms
+    )
+}
+
+/// Report of a load test, with the thresholds it failed
+fn format_report(target: &str, report: &LoadTestReport, thresholds: usize, failed: &[String]) -> String {
+    let mut lines = vec![
+        format!(
+            "Load test of {target}: {:.1}s with {} workers",
+            report.duration_ms as f64 / 1000.0,
+            report.concurrency
+        ),
+        format!(
+            "  {:<10} {:>9} {:>8} {:>9} {:>9} {:>9} {:>9} {:>9} {:>9}",
+            "operation", "requests", "errors", "req/s", "p50 ms", "p90 ms", "p95 ms", "p99 ms", "max ms"
+        ),
+    ];
+    lines.extend(
+        report
+            .operations
+            .iter()
+            .map(|operation| format_stats(&operation.operation.to_string(), &operation.stats)),
+    );
+    lines.push(format_stats("all", &report.overall));
+
+    for operation in report.operations.iter().filter(|operation| !operation.failures.is_empty()) {
+        lines.push(format!("{} failures:", operation.operation));
+        lines.extend(operation.failures.iter().map(|failure| format!("  {:>6}  {}", failure.count, failure.message)));
+    }
+    if thresholds > 0 {
+        lines.push(format!("Thresholds: {} of {} met", thresholds - failed.len(), thresholds));
+        lines.extend(failed.iter().map(|failure| format!("  failed {failure}")));
+    }
+    lines.join("\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_unmet_thresholds_fail_the_load_test() {
+        let command = LoadtestCommand::new();
+        // Nothing listens on the target, so every request fails at once, and
+        // with no job submitted every request is a submission
+        let run = |extra: &[&str]| {
+            let mut args = vec!["--target", "http://127.0.0.1:1", "-c", "2", "-d", "0.2"];
+            args.extend_from_slice(extra);
+            command.execute(&args.iter().map(ToString::to_string).collect::<Vec<_>>())
+        };
+
+        let output = run(&[]).unwrap();
+        assert!(output.starts_with("Load test of http://127.0.0.1:1: "), "{output}");
+        assert!(output.contains("submit failures:"), "{output}");
+        assert!(output.contains(" 100.00% "), "{output}");
+
+        let error = run(&["--assert", "error_rate<1%", "--assert", "max<10000"]).unwrap_err().to_string();
+        assert!(error.contains("Thresholds: 1 of 2 met"), "{error}");
+        assert!(error.contains("failed error_rate<0.01: measured 100.00%"), "{error}");
+
+        assert!(run(&["--assert", "p99<=500"]).is_err());
+        assert!(run(&["--mix", "poll=0"]).is_err());
+    }
+}
@@ -0,0 +1,228 @@
+//! Logs command
+//!
+//! Searches the log events agents, plugins and the CLI forwarded to the web
+//! server, reading the web database like `GET /api/logs`, by level,
+//! component, source, run, time and text.
+
+use clap::{Arg, ArgAction, ArgMatches, Command as ClapCommand};
+use squirrel_commands::{Command, CommandError};
+use squirrel_web::api::error::AppError;
+use squirrel_web::logs::{LogEvent, LogQuery, LogStore, SqlLogStore};
+use squirrel_web::migrations;
+
+use super::database::database_url;
+use super::usage_command::parse_date;
+
+/// Logs command implementation
+#[derive(Debug, Clone, Default)]
+pub struct LogsCommand;
+
+impl LogsCommand {
+    /// Create a new logs command
+    pub fn new() -> Self {
+        Self
+    }
+
+    /// Runs the subcommand against the web database
+    async fn run(matches: &ArgMatches) -> Result<String, CommandError> {
+        let url = database_url(matches)?;
+        let pool = migrations::connect(&url)
+            .await
+            .map_err(|e| CommandError::ResourceError(format!("Failed to open {url}: {e}")))?;
+        let store = SqlLogStore::new(pool);
+        let json = matches.get_flag("json");
+
+        match matches.subcommand() {
+            Some(("search", sub)) => {
+                let string = |name: &str| sub.get_one::<String>(name).cloned();
+                let query = LogQuery {
+                    min_level: string("level")
+                        .map(|level| level.parse())
+                        .transpose()
+                        .map_err(|e: AppError| CommandError::ValidationError(e.to_string()))?,
+                    component: string("component"),
+                    source: string("source"),
+                    run_id: string("run"),
+                    from: string("from").map(|text| parse_date(&text, false)).transpose()?,
+                    to: string("to").map(|text| parse_date(&text, true)).transpose()?,
+                    text: string("text"),
+                    submitted_by: string("user"),
+                    limit: sub.get_one::<usize>("limit").copied(),
+                };
+                let events = store
+                    .search(&query)
+                    .await
+                    .map_err(|e| CommandError::ExecutionError(format!("{e}; is the web database migrated?")))?;
+                if json {
+                    return serde_json::to_string_pretty(&events).map_err(|e| CommandError::ExecutionError(e.to_string()));
+                }
+                Ok(format_events(&events))
+            }
+            _ => Err(CommandError::ValidationError("Unknown logs subcommand".to_string())),
+        }
+    }
+}
+
+/// One line per event, oldest first
+fn format_events(events: &[LogEvent]) -> String {
+    if events.is_empty() {
+        return "No matching log events".to_string();
+    }
+    events
+        .iter()
+        .rev()
+        .map(|event| {
+            let event = &event.event;
+            let mut line = format!(
+                "{} {:<5} {}",
+                event.timestamp.format("%Y-%m-%dT%H:%M:%S%.3fZ"),
+                event.level.as_str().to_uppercase(),
+                event.component
+            );
+            if let Some(source) = &event.source {
+                line.push_str(&format!(" [{source}]"));
+            }
+            line.push_str(&format!(": {}", event.message));
+            if let Some(run) = &event.run_id {
+                line.push_str(&format!(" (run {run})"));
+            }
+            line
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+impl Command for LogsCommand {
+    fn name(&self) -> &str {
+        "logs"
+    }
+
+    fn description(&self) -> &str {
+        "Search the log events forwarded to the web server"
+    }
+
+    fn parser(&self) -> ClapCommand {
+        ClapCommand::new("logs")
+            .about("Search the log events forwarded to the web server")
+            .subcommand_required(true)
+            .arg(Arg::new("database")
+                .long("database")
+                .help("Web database URL [default: $DATABASE_URL]")
+                .value_name("URL")
+                .global(true))
+            .arg(Arg::new("json")
+                .long("json")
+                .help("Output in JSON format")
+                .action(ArgAction::SetTrue)
+                .global(true))
+            .subcommand(ClapCommand::new("search")
+                .about("Search log events, most recent last")
+                .arg(Arg::new("level")
+                    .long("level")
+                    .short('l')
+                    .help("Only events of this level or more severe: trace, debug, info, warn or error")
+                    .value_name("LEVEL"))
+                .arg(Arg::new("component")
+                    .long("component")
+                    .short('c')
+                    .help("Only events of this component, e.g. agent, cli or plugin:blast; plugin matches every plugin")
+                    .value_name("COMPONENT"))
+                .arg(Arg::new("source")
+                    .long("source")
+                    .help("Only events from this machine or agent")
+                    .value_name("SOURCE"))
+                .arg(Arg::new("run")
+                    .long("run")
+                    .help("Only events of this job, workflow run or task")
+                    .value_name("ID"))
+                .arg(Arg::new("from")
+                    .long("from")
+                    .help("Start of the range, as YYYY-MM-DD or RFC 3339")
+                    .value_name("DATE"))
+                .arg(Arg::new("to")
+                    .long("to")
+                    .help("End of the range, as YYYY-MM-DD (inclusive) or RFC 3339")
+                    .value_name("DATE"))
+                .arg(Arg::new("text")
+                    .long("text")
+                    .short('q')
+                    .help("Only events whose message contains this text")
+                    .value_name("TEXT"))
+                .arg(Arg::new("user")
+                    .long("user")
+                    .help("Only events forwarded by this user, or `agent`")
+                    .value_name("USER"))
+                .arg(Arg::new("limit")
+                    .long("limit")
+                    .short('n')
+                    .help("Most events shown [default: 100]")
+                    .value_name("N")
+                    .value_parser(clap::value_parser!(usize))))
+    }
+
+    fn clone_box(&self) -> Box<dyn Command> {
+        Box::new(self.clone())
+    }
+
+    fn execute(&self, args: &[String]) -> Result<String, CommandError> {
+        let matches = self
+            .parser()
+            .try_get_matches_from(std::iter::once("logs".to_string()).chain(args.iter().cloned()))
+            .map_err(|e| CommandError::ValidationError(e.to_string()))?;
+        let run = Self::run(&matches);
+        match tokio::runtime::Handle::try_current() {
+            // Run from the CLI's async entry point
+            Ok(handle) => tokio::task::block_in_place(|| handle.block_on(run)),
+            Err(_) => tokio::runtime::Runtime::new()
+                .map_err(|e| CommandError::ExecutionError(format!("Failed to create runtime: {}", e)))?
+                .block_on(run),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::commands::MigrateCommand;
+    use chrono::{TimeZone, Utc};
+    use squirrel_web::logs::{LogLevel, NewLogEvent};
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_search_forwarded_logs() {
+        let dir = tempdir().unwrap();
+        let database = format!("sqlite://{}", dir.path().join("web.db").display());
+        let migrate = ["up", "--store", "web", "--database", &database].map(String::from);
+        MigrateCommand::new().execute(&migrate).unwrap();
+        let runtime = tokio::runtime::Runtime::new().unwrap();
+        runtime.block_on(async {
+            let store = SqlLogStore::new(migrations::connect(&database).await.unwrap());
+            let event = |minute: u32, level: LogLevel, component: &str, message: &str| NewLogEvent {
+                timestamp: Utc.with_ymd_and_hms(2026, 10, 17, 9, minute, 0).unwrap(),
+                source: Some("lab-1".to_string()),
+                run_id: Some("task-7".to_string()),
+                ..NewLogEvent::new(level, component, message)
+            };
+            let events = vec![
+                event(0, LogLevel::Info, "agent", "Task started"),
+                event(1, LogLevel::Error, "plugin:blast", "BLAST exited with status 2"),
+            ];
+            store.append("agent", events).await.unwrap();
+        });
+        let run = |args: &[&str]| {
+            let mut args: Vec<String> = args.iter().map(ToString::to_string).collect();
+            args.extend(["--database".to_string(), database.clone()]);
+            LogsCommand::new().execute(&args)
+        };
+
+        let output = run(&["search", "--run", "task-7"]).unwrap();
+        assert_eq!(
+            output,
+            "2026-10-17T09:00:00.000Z INFO  agent [lab-1]: Task started (run task-7)\n\
+             2026-10-17T09:01:00.000Z ERROR plugin:blast [lab-1]: BLAST exited with status 2 (run task-7)"
+        );
+        assert!(run(&["search", "--level", "warn", "-c", "plugin"]).unwrap().contains("BLAST"));
+        assert_eq!(run(&["search", "--from", "2026-10-18"]).unwrap(), "No matching log events");
+        assert!(run(&["search", "--level", "loud"]).is_err());
+    }
+}
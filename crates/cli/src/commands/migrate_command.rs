@@ -0,0 +1,322 @@
+//! Migrate command
+//!
+//! Shows, applies and reverts the migrations of the web database and of the
+//! MCP persistence data directory. Both are tracked where the data lives, so
+//! the command sees the migrations the server applied at startup. `check`
+//! compares the schema of the web database with its migrations.
+
+use std::path::PathBuf;
+
+use clap::{Arg, ArgAction, ArgMatches, Command as ClapCommand};
+use serde_json::json;
+use squirrel_commands::{Command, CommandError};
+use squirrel_core::migration::{MigrationState, MigrationStatus, MigrationStore, Migrator};
+use squirrel_mcp::persistence::{PersistenceConfig, PersistenceMigrations};
+use squirrel_web::migrations::{self, SchemaDrift, SqlMigrations};
+
+use super::database::{configured_database_url, database_url};
+
+/// Migrate command implementation
+#[derive(Debug, Clone, Default)]
+pub struct MigrateCommand;
+
+impl MigrateCommand {
+    /// Create a new migrate command
+    pub fn new() -> Self {
+        Self
+    }
+
+    /// Opens the stores selected with `--store`
+    ///
+    /// Without `--store`, the persistence store is used, and the web
+    /// database too if a database URL is given.
+    async fn stores(matches: &ArgMatches) -> Result<Vec<Box<dyn MigrationStore>>, CommandError> {
+        let database = configured_database_url(matches);
+        let selected: Vec<String> = match matches.get_many::<String>("store") {
+            Some(stores) => stores.cloned().collect(),
+            None if database.is_some() => vec!["web".to_string(), "persistence".to_string()],
+            None => vec!["persistence".to_string()],
+        };
+
+        let mut stores: Vec<Box<dyn MigrationStore>> = Vec::new();
+        for store in selected {
+            if store == "web" {
+                let url = database_url(matches)?;
+                let pool = migrations::connect(&url)
+                    .await
+                    .map_err(|e| CommandError::ResourceError(format!("Failed to open {url}: {e}")))?;
+                stores.push(Box::new(SqlMigrations::new(pool)));
+            } else {
+                let mut config = PersistenceConfig::default();
+                if let Some(dir) = matches.get_one::<String>("data-dir") {
+                    config.data_dir = PathBuf::from(dir);
+                }
+                stores.push(Box::new(PersistenceMigrations::new(&config)));
+            }
+        }
+        Ok(stores)
+    }
+
+    /// Compares the web database with its migrations: applied migrations
+    /// that changed or are gone, and schema objects changed by hand
+    ///
+    /// With `--strict`, any difference fails the command.
+    async fn check(matches: &ArgMatches, sub: &ArgMatches) -> Result<String, CommandError> {
+        let url = database_url(matches)?;
+        let pool = migrations::connect(&url)
+            .await
+            .map_err(|e| CommandError::ResourceError(format!("Failed to open {url}: {e}")))?;
+        let migration_error = |e: squirrel_core::migration::MigrationError| CommandError::ExecutionError(e.to_string());
+        let store = SqlMigrations::new(pool.clone());
+        let status = Migrator::new(&store).status().await.map_err(migration_error)?;
+        let drift = migrations::schema_drift(&pool).await.map_err(migration_error)?;
+        pool.close().await;
+
+        let pending = status.iter().filter(|migration| migration.state == MigrationState::Pending).count();
+        let changed: Vec<&MigrationStatus> = status
+            .iter()
+            .filter(|migration| matches!(migration.state, MigrationState::Modified | MigrationState::Missing))
+            .collect();
+        let clean = changed.is_empty() && drift.is_empty();
+        let report = if matches.get_flag("json") {
+            serde_json::to_string_pretty(&json!({
+                "store": "web",
+                "clean": clean,
+                "pending": pending,
+                "migrations": changed,
+                "drift": drift,
+            }))
+            .map_err(|e| CommandError::ExecutionError(e.to_string()))?
+        } else {
+            format_check(pending, &changed, &drift)
+        };
+
+        if sub.get_flag("strict") && !clean {
+            return Err(CommandError::ExecutionError(report));
+        }
+        Ok(report)
+    }
+
+    /// Runs the subcommand against each store, as text or JSON
+    async fn run(matches: &ArgMatches) -> Result<String, CommandError> {
+        if let Some(("check", sub)) = matches.subcommand() {
+            return Self::check(matches, sub).await;
+        }
+        let json = matches.get_flag("json");
+        let migration_error = |e: squirrel_core::migration::MigrationError| CommandError::ExecutionError(e.to_string());
+        let mut lines = Vec::new();
+        let mut reports = Vec::new();
+
+        for store in Self::stores(matches).await? {
+            let migrator = Migrator::new(store.as_ref());
+            let name = store.name().to_string();
+            match matches.subcommand() {
+                Some(("status", _)) => {
+                    let status = migrator.status().await.map_err(migration_error)?;
+                    lines.push(format_status(&name, &status));
+                    reports.push(json!({ "store": name, "migrations": status }));
+                }
+                Some((direction @ ("up" | "down"), sub)) => {
+                    let target = sub.get_one::<i64>("to").copied();
+                    let (migrations, verb) = if direction == "up" {
+                        (migrator.up(target).await.map_err(migration_error)?, "Applied")
+                    } else {
+                        (migrator.down(target).await.map_err(migration_error)?, "Reverted")
+                    };
+                    if migrations.is_empty() {
+                        lines.push(format!("{name}: nothing to do"));
+                    }
+                    lines.extend(
+                        migrations
+                            .iter()
+                            .map(|migration| format!("{name}: {verb} {} {}", migration.version, migration.name)),
+                    );
+                    reports.push(json!({ "store": name, direction: migrations }));
+                }
+                _ => return Err(CommandError::ValidationError("Unknown migrate subcommand".to_string())),
+            }
+        }
+
+        if json {
+            return serde_json::to_string_pretty(&reports).map_err(|e| CommandError::ExecutionError(e.to_string()));
+        }
+        Ok(lines.join("\n"))
+    }
+}
+
+impl Command for MigrateCommand {
+    fn name(&self) -> &str {
+        "migrate"
+    }
+
+    fn description(&self) -> &str {
+        "Apply and revert data migrations"
+    }
+
+    fn parser(&self) -> ClapCommand {
+        let to = |help: &'static str| {
+            Arg::new("to")
+                .long("to")
+                .help(help)
+                .value_name("VERSION")
+                .value_parser(clap::value_parser!(i64))
+        };
+        ClapCommand::new("migrate")
+            .about("Apply and revert data migrations")
+            .subcommand_required(true)
+            .arg(Arg::new("store")
+                .long("store")
+                .help("Store to migrate; repeat for several [default: persistence, and web with a database]")
+                .value_parser(["web", "persistence"])
+                .action(ArgAction::Append)
+                .global(true))
+            .arg(Arg::new("database")
+                .long("database")
+                .help("Web database URL [default: $DATABASE_URL]")
+                .value_name("URL")
+                .global(true))
+            .arg(Arg::new("data-dir")
+                .long("data-dir")
+                .help("MCP persistence data directory [default: data/mcp]")
+                .value_name("DIR")
+                .global(true))
+            .arg(Arg::new("json")
+                .long("json")
+                .help("Output in JSON format")
+                .action(ArgAction::SetTrue)
+                .global(true))
+            .subcommand(ClapCommand::new("status")
+                .about("List migrations and whether they are applied"))
+            .subcommand(ClapCommand::new("up")
+                .about("Apply pending migrations")
+                .arg(to("Apply migrations up to and including this version")))
+            .subcommand(ClapCommand::new("down")
+                .about("Revert the latest migration")
+                .arg(to("Revert every migration above this version; 0 reverts all")))
+            .subcommand(ClapCommand::new("check")
+                .about("Compare the web database's schema with its migrations")
+                .arg(Arg::new("strict")
+                    .long("strict")
+                    .help("Fail if the database differs from its migrations")
+                    .action(ArgAction::SetTrue)))
+    }
+
+    fn clone_box(&self) -> Box<dyn Command> {
+        Box::new(self.clone())
+    }
+
+    fn execute(&self, args: &[String]) -> Result<String, CommandError> {
+        let matches = self
+            .parser()
+            .try_get_matches_from(std::iter::once("migrate".to_string()).chain(args.iter().cloned()))
+            .map_err(|e| CommandError::ValidationError(e.to_string()))?;
+        let run = Self::run(&matches);
+        match tokio::runtime::Handle::try_current() {
+            // Run from the CLI's async entry point
+            Ok(handle) => tokio::task::block_in_place(|| handle.block_on(run)),
+            Err(_) => tokio::runtime::Runtime::new()
+                .map_err(|e| CommandError::ExecutionError(format!("Failed to create runtime: {}", e)))?
+                .block_on(run),
+        }
+    }
+}
+
+/// Table of the migrations of a store
+fn format_status(store: &str, status: &[MigrationStatus]) -> String {
+    let mut lines = vec![format!("{store}:")];
+    if status.is_empty() {
+        lines.push("  no migrations".to_string());
+    }
+    lines.extend(status.iter().map(|migration| {
+        let applied_at = migration
+            .applied_at
+            .map(|at| at.format("%Y-%m-%d %H:%M:%S").to_string())
+            .unwrap_or_default();
+        format!("  {:<16} {:<9} {:<40} {}", migration.version, migration.state, migration.name, applied_at)
+            .trim_end()
+            .to_string()
+    }));
+    lines.join("\n")
+}
+
+/// Report of the differences between the web database and its migrations
+fn format_check(pending: usize, changed: &[&MigrationStatus], drift: &[SchemaDrift]) -> String {
+    let differences = changed.len() + drift.len();
+    let mut lines = vec![match differences {
+        0 => format!("web: schema matches migrations ({pending} pending)"),
+        1 => format!("web: 1 difference from migrations ({pending} pending)"),
+        n => format!("web: {n} differences from migrations ({pending} pending)"),
+    }];
+    lines.extend(
+        changed
+            .iter()
+            .map(|migration| format!("  {} migration {} {}", migration.state, migration.version, migration.name)),
+    );
+    lines.extend(drift.iter().map(|drift| format!("  {drift}")));
+    lines.join("\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_migrate_both_stores() {
+        let dir = tempdir().unwrap();
+        let database = format!("sqlite://{}", dir.path().join("web.db").display());
+        let data_dir = dir.path().join("mcp").display().to_string();
+        let command = MigrateCommand::new();
+        let run = |args: &[&str]| {
+            let mut args: Vec<String> = args.iter().map(ToString::to_string).collect();
+            args.extend(["--database".to_string(), database.clone(), "--data-dir".to_string(), data_dir.clone()]);
+            command.execute(&args)
+        };
+
+        let status = run(&["status"]).unwrap();
+        assert!(status.contains("web:") && status.contains("persistence:"), "{status}");
+        assert!(!status.contains("applied"), "{status}");
+
+        let output = run(&["up"]).unwrap();
+        assert!(output.contains("persistence: Applied 1 create storage directories"), "{output}");
+        assert!(run(&["status"]).unwrap().contains("applied"));
+        assert!(run(&["up"]).unwrap().contains("web: nothing to do"));
+
+        let output = run(&["down", "--store", "persistence"]).unwrap();
+        assert_eq!(output, "persistence: Reverted 1 create storage directories");
+        assert!(run(&["down", "--store", "web", "--to", "0"]).is_err());
+    }
+
+    #[test]
+    fn test_check_reports_schema_changed_by_hand() {
+        let dir = tempdir().unwrap();
+        let database = format!("sqlite://{}", dir.path().join("web.db").display());
+        let command = MigrateCommand::new();
+        let run = |args: &[&str]| {
+            let mut args: Vec<String> = args.iter().map(ToString::to_string).collect();
+            args.extend(["--database".to_string(), database.clone()]);
+            command.execute(&args)
+        };
+
+        run(&["up", "--store", "web"]).unwrap();
+        assert_eq!(run(&["check", "--strict"]).unwrap(), "web: schema matches migrations (0 pending)");
+
+        let edit = async {
+            let pool = migrations::connect(&database).await.unwrap();
+            sqlx::query("CREATE INDEX idx_by_hand ON job_queue(created_at)").execute(&pool).await.unwrap();
+            pool.close().await;
+        };
+        tokio::runtime::Runtime::new().unwrap().block_on(edit);
+
+        let report = run(&["check"]).unwrap();
+        assert_eq!(
+            report,
+            "web: 1 difference from migrations (0 pending)\n  extra index idx_by_hand on job_queue (created_at)"
+        );
+        let error = run(&["check", "--strict"]).unwrap_err();
+        assert!(error.to_string().contains("idx_by_hand"), "{error}");
+        let report: serde_json::Value = serde_json::from_str(&run(&["check", "--json"]).unwrap()).unwrap();
+        assert_eq!(report["clean"], false);
+        assert_eq!(report["drift"][0]["kind"], "extra");
+    }
+}
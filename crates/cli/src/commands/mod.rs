@@ -11,8 +11,37 @@ pub mod plugin_command;
 pub mod secrets_command;
 pub mod executor;
 pub mod mcp_command;
+pub mod bio_command;
+pub mod replay_command;
+pub mod alerts_command;
+pub mod contexts_command;
+pub mod bench_command;
+pub mod capabilities_command;
+pub mod tool_command;
+pub mod blob_command;
+pub mod cache_command;
+pub mod usage_command;
+pub mod ask_command;
+pub mod deploy_command;
+pub mod migrate_command;
+pub mod bundle_command;
+pub mod top_command;
+pub mod report_command;
+pub mod i18n_command;
+pub mod palette_command;
+pub mod tags_command;
+pub mod admin_command;
+pub mod users_command;
+pub mod teams_command;
+pub mod export_command;
+pub mod logs_command;
+pub mod redact_command;
+pub mod loadtest_command;
+pub mod profile_command;
+pub mod jupyter_command;
 pub mod registry;
 pub mod context;
+mod database;
 
 pub use config_command::ConfigCommand;
 pub use help_command::HelpCommand;
@@ -22,6 +51,34 @@ pub use plugin_command::PluginCommand;
 pub use secrets_command::SecretsCommand;
 pub use executor::ExecutionContext;
 pub use mcp_command::MCPCommand;
+pub use bio_command::BioCommand;
+pub use replay_command::ReplayCommand;
+pub use alerts_command::AlertsCommand;
+pub use contexts_command::ContextsCommand;
+pub use bench_command::BenchCommand;
+pub use capabilities_command::CapabilitiesCommand;
+pub use tool_command::ToolCommand;
+pub use blob_command::BlobCommand;
+pub use cache_command::CacheCommand;
+pub use usage_command::UsageCommand;
+pub use ask_command::AskCommand;
+pub use deploy_command::DeployCommand;
+pub use migrate_command::MigrateCommand;
+pub use bundle_command::BundleCommand;
+pub use top_command::TopCommand;
+pub use report_command::ReportCommand;
+pub use i18n_command::I18nCommand;
+pub use palette_command::PaletteCommand;
+pub use tags_command::TagsCommand;
+pub use admin_command::AdminCommand;
+pub use users_command::UsersCommand;
+pub use teams_command::TeamsCommand;
+pub use export_command::ExportCommand;
+pub use logs_command::LogsCommand;
+pub use redact_command::RedactCommand;
+pub use loadtest_command::LoadtestCommand;
+pub use profile_command::ProfileCommand;
+pub use jupyter_command::JupyterCommand;
 
 use clap::{Command as ClapCommand, Arg, ArgAction};
 
@@ -43,6 +100,30 @@ pub fn register_commands(registry: &mut CommandRegistry) {
     let plugin_command = PluginCommand::new();
     let secrets_command = SecretsCommand::new();
     let mcp_command = mcp_command::MCPCommand::new();
+    let bio_command = BioCommand::new();
+    let alerts_command = AlertsCommand::new();
+    let contexts_command = ContextsCommand::new();
+    let bench_command = BenchCommand::new();
+    let tool_command = ToolCommand::new();
+    let blob_command = BlobCommand::new();
+    let cache_command = CacheCommand::new();
+    let usage_command = UsageCommand::new();
+    let ask_command = AskCommand::new();
+    let deploy_command = DeployCommand::new();
+    let migrate_command = MigrateCommand::new();
+    let bundle_command = BundleCommand::new();
+    let top_command = TopCommand::new();
+    let report_command = ReportCommand::new();
+    let i18n_command = I18nCommand::new();
+    let tags_command = TagsCommand::new();
+    let admin_command = AdminCommand::new();
+    let users_command = UsersCommand::new();
+    let teams_command = TeamsCommand::new();
+    let export_command = ExportCommand::new();
+    let logs_command = LogsCommand::new();
+    let redact_command = RedactCommand::new();
+    let loadtest_command = LoadtestCommand::new();
+    let profile_command = ProfileCommand::new();
     
     // Convert to Arc<dyn Command>
     let help_arc = std::sync::Arc::new(help_command);
@@ -52,6 +133,30 @@ pub fn register_commands(registry: &mut CommandRegistry) {
     let plugin_arc = std::sync::Arc::new(plugin_command);
     let secrets_arc = std::sync::Arc::new(secrets_command);
     let mcp_arc = std::sync::Arc::new(mcp_command);
+    let bio_arc = std::sync::Arc::new(bio_command);
+    let alerts_arc = std::sync::Arc::new(alerts_command);
+    let contexts_arc = std::sync::Arc::new(contexts_command);
+    let bench_arc = std::sync::Arc::new(bench_command);
+    let tool_arc = std::sync::Arc::new(tool_command);
+    let blob_arc = std::sync::Arc::new(blob_command);
+    let cache_arc = std::sync::Arc::new(cache_command);
+    let usage_arc = std::sync::Arc::new(usage_command);
+    let ask_arc = std::sync::Arc::new(ask_command);
+    let deploy_arc = std::sync::Arc::new(deploy_command);
+    let migrate_arc = std::sync::Arc::new(migrate_command);
+    let bundle_arc = std::sync::Arc::new(bundle_command);
+    let top_arc = std::sync::Arc::new(top_command);
+    let report_arc = std::sync::Arc::new(report_command);
+    let i18n_arc = std::sync::Arc::new(i18n_command);
+    let tags_arc = std::sync::Arc::new(tags_command);
+    let admin_arc = std::sync::Arc::new(admin_command);
+    let users_arc = std::sync::Arc::new(users_command);
+    let teams_arc = std::sync::Arc::new(teams_command);
+    let export_arc = std::sync::Arc::new(export_command);
+    let logs_arc = std::sync::Arc::new(logs_command);
+    let redact_arc = std::sync::Arc::new(redact_command);
+    let loadtest_arc = std::sync::Arc::new(loadtest_command);
+    let profile_arc = std::sync::Arc::new(profile_command);
     
     // Register commands
     let _ = registry.register("help", help_arc);
@@ -61,6 +166,30 @@ pub fn register_commands(registry: &mut CommandRegistry) {
     let _ = registry.register("plugin", plugin_arc);
     let _ = registry.register("secrets", secrets_arc);
     let _ = registry.register("mcp", mcp_arc);
+    let _ = registry.register("bio", bio_arc);
+    let _ = registry.register("alerts", alerts_arc);
+    let _ = registry.register("contexts", contexts_arc);
+    let _ = registry.register("bench", bench_arc);
+    let _ = registry.register("tool", tool_arc);
+    let _ = registry.register("blob", blob_arc);
+    let _ = registry.register("cache", cache_arc);
+    let _ = registry.register("usage", usage_arc);
+    let _ = registry.register("ask", ask_arc);
+    let _ = registry.register("deploy", deploy_arc);
+    let _ = registry.register("migrate", migrate_arc);
+    let _ = registry.register("bundle", bundle_arc);
+    let _ = registry.register("top", top_arc);
+    let _ = registry.register("report", report_arc);
+    let _ = registry.register("i18n", i18n_arc);
+    let _ = registry.register("tags", tags_arc);
+    let _ = registry.register("admin", admin_arc);
+    let _ = registry.register("users", users_arc);
+    let _ = registry.register("teams", teams_arc);
+    let _ = registry.register("export", export_arc);
+    let _ = registry.register("logs", logs_arc);
+    let _ = registry.register("redact", redact_arc);
+    let _ = registry.register("loadtest", loadtest_arc);
+    let _ = registry.register("profile", profile_arc);
     // Register additional commands here as they are implemented
 }
 
@@ -131,6 +260,66 @@ pub fn create_cli() -> ClapCommand {
         )
         .subcommand_required(false)
         .arg_required_else_help(true)
+        .arg(
+            Arg::new("capture-manifest")
+                .long("capture-manifest")
+                .help("Write a reproducibility manifest of the run to FILE")
+                .value_name("FILE")
+        )
+        .arg(
+            Arg::new("capture-env")
+                .long("capture-env")
+                .help("Environment variable to record in the manifest; a trailing '*' matches a prefix")
+                .value_name("NAME")
+                .action(ArgAction::Append)
+                .requires("capture-manifest")
+        )
+        .arg(
+            Arg::new("capture-tool")
+                .long("capture-tool")
+                .help("External tool whose version is recorded in the manifest and result cache key")
+                .value_name("TOOL")
+                .action(ArgAction::Append)
+        )
+        .arg(
+            Arg::new("capture-input")
+                .long("capture-input")
+                .help("Input file whose hash is recorded in the manifest and result cache key")
+                .value_name("FILE")
+                .action(ArgAction::Append)
+        )
+        .arg(
+            Arg::new("cache")
+                .long("cache")
+                .help("Serve the cached result of an identical earlier run instead of executing")
+                .action(ArgAction::SetTrue)
+                .conflicts_with("no-cache")
+        )
+        .arg(
+            Arg::new("no-cache")
+                .long("no-cache")
+                .help("Execute even if caching is enabled in the configuration")
+                .action(ArgAction::SetTrue)
+        )
+        .arg(
+            Arg::new("accessible")
+                .long("accessible")
+                .help("Print plain, stable lines without colors, tables or live screens, for screen readers")
+                .action(ArgAction::SetTrue)
+        )
+        .arg(
+            Arg::new("locale")
+                .long("locale")
+                .help("Locale of messages, e.g. de-AT [default: from the configuration or environment]")
+                .value_name("LOCALE")
+        )
+        .arg(
+            Arg::new("timeout")
+                .long("timeout")
+                .help("Stop the command if it has not finished after this many seconds")
+                .value_name("SECONDS")
+                .value_parser(clap::value_parser!(u64))
+        )
         .subcommand(
             ClapCommand::new("mcp")
                 .about("Machine Context Protocol commands")
@@ -160,6 +349,90 @@ pub fn create_cli() -> ClapCommand {
         .subcommand(
             plugin_command::PluginCommand::new().parser()
         )
+        .subcommand(
+            bio_command::BioCommand::new().parser()
+        )
+        .subcommand(
+            replay_command::ReplayCommand::default().parser()
+        )
+        .subcommand(
+            alerts_command::AlertsCommand::new().parser()
+        )
+        .subcommand(
+            contexts_command::ContextsCommand::new().parser()
+        )
+        .subcommand(
+            bench_command::BenchCommand::new().parser()
+        )
+        .subcommand(
+            capabilities_command::CapabilitiesCommand::default().parser()
+        )
+        .subcommand(
+            palette_command::PaletteCommand::default().parser()
+        )
+        .subcommand(
+            tool_command::ToolCommand::new().parser()
+        )
+        .subcommand(
+            blob_command::BlobCommand::new().parser()
+        )
+        .subcommand(
+            cache_command::CacheCommand::new().parser()
+        )
+        .subcommand(
+            usage_command::UsageCommand::new().parser()
+        )
+        .subcommand(
+            ask_command::AskCommand::new().parser()
+        )
+        .subcommand(
+            deploy_command::DeployCommand::new().parser()
+        )
+        .subcommand(
+            migrate_command::MigrateCommand::new().parser()
+        )
+        .subcommand(
+            bundle_command::BundleCommand::new().parser()
+        )
+        .subcommand(
+            top_command::TopCommand::new().parser()
+        )
+        .subcommand(
+            report_command::ReportCommand::new().parser()
+        )
+        .subcommand(
+            i18n_command::I18nCommand::new().parser()
+        )
+        .subcommand(
+            tags_command::TagsCommand::new().parser()
+        )
+        .subcommand(
+            admin_command::AdminCommand::new().parser()
+        )
+        .subcommand(
+            users_command::UsersCommand::new().parser()
+        )
+        .subcommand(
+            teams_command::TeamsCommand::new().parser()
+        )
+        .subcommand(
+            export_command::ExportCommand::new().parser()
+        )
+        .subcommand(
+            logs_command::LogsCommand::new().parser()
+        )
+        .subcommand(
+            redact_command::RedactCommand::new().parser()
+        )
+        .subcommand(
+            loadtest_command::LoadtestCommand::new().parser()
+        )
+        .subcommand(
+            profile_command::ProfileCommand::new().parser()
+        )
+        .subcommand(
+            jupyter_command::JupyterCommand::default().parser()
+        )
 }
 
 /// Creates a CLI instance from the command registry
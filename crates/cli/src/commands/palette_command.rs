@@ -0,0 +1,144 @@
+//! Palette command
+//!
+//! Searches the registered commands, their subcommands, tags and plugins
+//! fuzzily, listing the best and most used matches first. Uses are counted
+//! from the CLI's command history.
+
+use std::sync::{Arc, Weak};
+
+use clap::{Arg, ArgAction, Command as ClapCommand};
+use colored::Colorize;
+use squirrel_commands::palette::{SearchHit, DEFAULT_LIMIT};
+use squirrel_commands::{palette, Command, CommandError, CommandRegistry};
+
+/// Palette command implementation
+#[derive(Debug, Clone, Default)]
+pub struct PaletteCommand {
+    /// Registry whose commands are searched
+    registry: Weak<CommandRegistry>,
+}
+
+impl PaletteCommand {
+    /// Create a palette command searching the commands in `registry`
+    ///
+    /// The registry is held weakly because the command is registered in it.
+    pub fn new(registry: &Arc<CommandRegistry>) -> Self {
+        Self {
+            registry: Arc::downgrade(registry),
+        }
+    }
+}
+
+impl Command for PaletteCommand {
+    fn name(&self) -> &str {
+        "palette"
+    }
+
+    fn description(&self) -> &str {
+        "Search the commands by name, tag or plugin"
+    }
+
+    fn tags(&self) -> Vec<String> {
+        vec!["search".to_string(), "find".to_string()]
+    }
+
+    fn parser(&self) -> ClapCommand {
+        ClapCommand::new("palette")
+            .about("Search the commands by name, tag or plugin")
+            .arg(Arg::new("query")
+                .help("Characters to find in order, e.g. `cl` for `cache list`; empty lists the most used")
+                .action(ArgAction::Append))
+            .arg(Arg::new("limit")
+                .long("limit")
+                .short('n')
+                .help("Maximum number of results [default: 20]")
+                .value_name("N")
+                .value_parser(clap::value_parser!(usize)))
+            .arg(Arg::new("json")
+                .long("json")
+                .help("Output the results in JSON format")
+                .action(ArgAction::SetTrue))
+    }
+
+    fn clone_box(&self) -> Box<dyn Command> {
+        Box::new(self.clone())
+    }
+
+    fn execute(&self, args: &[String]) -> Result<String, CommandError> {
+        let matches = self
+            .parser()
+            .try_get_matches_from(std::iter::once("palette".to_string()).chain(args.iter().cloned()))
+            .map_err(|e| CommandError::ValidationError(e.to_string()))?;
+        let registry = self
+            .registry
+            .upgrade()
+            .ok_or_else(|| CommandError::RegistryError("Command registry is no longer available".to_string()))?;
+
+        let query = matches
+            .get_many::<String>("query")
+            .map(|words| words.cloned().collect::<Vec<_>>().join(" "))
+            .unwrap_or_default();
+        let limit = matches.get_one::<usize>("limit").copied().unwrap_or(DEFAULT_LIMIT);
+        let hits = palette::rank(registry.index()?, &query, limit);
+
+        if matches.get_flag("json") {
+            return serde_json::to_string_pretty(&hits).map_err(|e| CommandError::ExecutionError(e.to_string()));
+        }
+        if hits.is_empty() {
+            return Ok(format!("No commands match '{query}'"));
+        }
+        let width = hits.iter().map(|hit| hit.entry.name.chars().count()).max().unwrap_or(0);
+        Ok(hits.iter().map(|hit| line(hit, width)).collect::<Vec<_>>().join("\n"))
+    }
+}
+
+/// A hit as one line, with the matched characters of its name in bold
+fn line(hit: &SearchHit, width: usize) -> String {
+    let name: String = hit
+        .entry
+        .name
+        .chars()
+        .enumerate()
+        .map(|(i, c)| {
+            if hit.matched.contains(&i) {
+                c.to_string().bold().to_string()
+            } else {
+                c.to_string()
+            }
+        })
+        .collect();
+    let padding = " ".repeat(width - hit.entry.name.chars().count());
+    let mut line = format!("{name}{padding}  {}", hit.entry.description);
+    if let Some(plugin) = &hit.entry.plugin {
+        line.push_str(&format!(" [{plugin}]"));
+    }
+    line
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::commands::VersionCommand;
+
+    #[test]
+    fn test_palette_searches_registry() {
+        colored::control::set_override(false);
+        let registry = Arc::new(CommandRegistry::new());
+        registry.register("version", Arc::new(VersionCommand::new())).unwrap();
+        let command = PaletteCommand::new(&registry);
+        registry.register("palette", Arc::new(command.clone())).unwrap();
+
+        let output = command.execute(&["vers".to_string()]).unwrap();
+        assert!(output.starts_with("version"), "{output}");
+        let output = command.execute(&["find".to_string()]).unwrap();
+        assert!(output.starts_with("palette"), "{output}");
+        assert!(command.execute(&["zzz".to_string()]).unwrap().starts_with("No commands"));
+
+        registry.record_usage("version", &[]);
+        let output = command.execute(&["--json".to_string()]).unwrap();
+        let hits: Vec<SearchHit> = serde_json::from_str(&output).unwrap();
+        assert_eq!(hits[0].entry.name, "version");
+        assert_eq!(hits[0].entry.uses, 1);
+    }
+}
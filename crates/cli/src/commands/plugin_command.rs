@@ -2,11 +2,13 @@
 //!
 //! This module implements a command to manage plugins in the Squirrel CLI.
 
+use std::path::PathBuf;
+
 use clap::{Command as ClapCommand, Arg, ArgAction, ArgMatches};
 use async_trait::async_trait;
 use tracing::{debug, warn, error};
 
-use squirrel_commands::{Command, CommandError, CommandResult};
+use squirrel_commands::{plugin_logs, scaffold, Command, CommandError, CommandResult};
 use crate::commands::context::CommandContext;
 use crate::plugins::{state::get_plugin_manager, PluginStatus};
 use crate::formatter::Factory as FormatterFactory;
@@ -126,6 +128,66 @@ impl Command for PluginCommand {
                 ClapCommand::new("reload")
                     .about("Reload all plugins")
             )
+            .subcommand(
+                ClapCommand::new("new")
+                    .about("Create a plugin project from the plugin scaffold")
+                    .arg(
+                        Arg::new("name")
+                            .help("Name of the plugin, e.g. seq-stats")
+                            .required(true)
+                    )
+                    .arg(
+                        Arg::new("dir")
+                            .long("dir")
+                            .value_name("DIR")
+                            .help("Directory to create the project in [default: ./<name>]")
+                    )
+                    .arg(
+                        Arg::new("description")
+                            .long("description")
+                            .value_name("TEXT")
+                            .help("Description of the plugin")
+                    )
+                    .arg(
+                        Arg::new("author")
+                            .long("author")
+                            .value_name("NAME")
+                            .help("Author of the plugin")
+                    )
+                    .arg(
+                        Arg::new("scaffold")
+                            .long("scaffold")
+                            .value_name("KIND")
+                            .help("Scaffold in ~/.squirrel/templates/scaffolds to use instead")
+                            .default_value("plugin")
+                    )
+            )
+            .subcommand(
+                ClapCommand::new("logs")
+                    .about("Show recent log output of a plugin")
+                    .arg(
+                        Arg::new("name")
+                            .help("Name of the plugin")
+                            .required(true)
+                    )
+                    .arg(
+                        Arg::new("lines")
+                            .long("lines")
+                            .short('n')
+                            .value_name("N")
+                            .help("Number of lines to show")
+                            .value_parser(clap::value_parser!(usize))
+                            .default_value("100")
+                    )
+                    .arg(
+                        Arg::new("format")
+                            .long("format")
+                            .short('f')
+                            .value_name("FORMAT")
+                            .help("Output format (text, json, yaml)")
+                            .default_value("text")
+                    )
+            )
     }
     
     /// Clone the command into a new box
@@ -155,6 +217,8 @@ impl AsyncCommand for PluginCommand {
             Some(("install", sub_matches)) => self.install_plugin(sub_matches).await,
             Some(("uninstall", sub_matches)) => self.uninstall_plugin(sub_matches).await,
             Some(("reload", sub_matches)) => self.reload_plugins(sub_matches).await,
+            Some(("logs", sub_matches)) => self.plugin_logs(sub_matches).await,
+            Some(("new", sub_matches)) => self.new_plugin(sub_matches),
             _ => {
                 // Show help
                 Ok(self.parser().render_help().to_string())
@@ -167,9 +231,7 @@ impl PluginCommand {
     /// List installed plugins
     async fn list_plugins(&self, matches: &ArgMatches) -> Result<String, CommandError> {
         // Get the plugin manager
-        let plugin_manager_arc = get_plugin_manager();
-        let plugin_manager = plugin_manager_arc.lock().map_err(|_| 
-            CommandError::ExecutionError("Failed to lock plugin manager".to_string()))?;
+        let plugin_manager = get_plugin_manager();
         
         // Get the list of plugins
         let plugins = plugin_manager.list_plugins();
@@ -203,9 +265,7 @@ impl PluginCommand {
             .ok_or_else(|| CommandError::ValidationError("Plugin name is required".to_string()))?;
         
         // Get the plugin manager
-        let plugin_manager_arc = get_plugin_manager();
-        let plugin_manager = plugin_manager_arc.lock().map_err(|_| 
-            CommandError::ExecutionError("Failed to lock plugin manager".to_string()))?;
+        let plugin_manager = get_plugin_manager();
         
         // Get the plugin
         let plugin = plugin_manager.get_plugin(name).map_err(|e| 
@@ -240,12 +300,10 @@ impl PluginCommand {
             .ok_or_else(|| CommandError::ValidationError("Plugin name is required".to_string()))?;
         
         // Get the plugin manager
-        let plugin_manager_arc = get_plugin_manager();
-        let mut plugin_manager = plugin_manager_arc.lock()
-            .map_err(|_| CommandError::ExecutionError("Failed to lock plugin manager".to_string()))?;
+        let plugin_manager = get_plugin_manager();
         
         // Load the plugin
-        match plugin_manager.load_plugin(name) {
+        match plugin_manager.load_plugin(name).await {
             Ok(()) => {
                 // Start the plugin
                 if let Err(e) = plugin_manager.start_plugins() {
@@ -269,9 +327,7 @@ impl PluginCommand {
             .ok_or_else(|| CommandError::ValidationError("Plugin name is required".to_string()))?;
         
         // Get the plugin manager
-        let plugin_manager_arc = get_plugin_manager();
-        let mut plugin_manager = plugin_manager_arc.lock()
-            .map_err(|_| CommandError::ExecutionError("Failed to lock plugin manager".to_string()))?;
+        let plugin_manager = get_plugin_manager();
         
         // Check if plugin exists and is enabled
         let plugin = plugin_manager.get_plugin(name).map_err(|e| 
@@ -283,9 +339,8 @@ impl PluginCommand {
         // Disable the plugin
         // For now, just set the status to Disabled
         // In a real implementation, we would unload the plugin
-        let plugin = plugin_manager.get_plugin_mut(name).map_err(|e| 
+        plugin_manager.set_plugin_status(name, PluginStatus::Disabled).map_err(|e| 
             CommandError::ExecutionError(e.to_string()))?;
-        plugin.set_status(PluginStatus::Disabled);
         
         Ok(format!("Plugin {} disabled successfully", name))
     }
@@ -313,9 +368,7 @@ impl PluginCommand {
         let force = matches.get_flag("force");
         
         // Get the plugin manager
-        let plugin_manager_arc = get_plugin_manager();
-        let mut plugin_manager = plugin_manager_arc.lock().map_err(|_| 
-            CommandError::ExecutionError("Failed to lock plugin manager".to_string()))?;
+        let plugin_manager = get_plugin_manager();
         
         // Check if plugin exists
         let plugin = plugin_manager.get_plugin(name).map_err(|e| 
@@ -328,7 +381,7 @@ impl PluginCommand {
         }
         
         // Remove the plugin
-        match plugin_manager.remove_plugin(name) {
+        match plugin_manager.remove_plugin(name).await {
             Ok(()) => {
                 Ok(format!("Plugin {} uninstalled successfully", name))
             }
@@ -342,12 +395,10 @@ impl PluginCommand {
     /// Reload all plugins
     async fn reload_plugins(&self, _matches: &ArgMatches) -> Result<String, CommandError> {
         // Get the plugin manager
-        let plugin_manager_arc = get_plugin_manager();
-        let mut plugin_manager = plugin_manager_arc.lock().map_err(|_| 
-            CommandError::ExecutionError("Failed to lock plugin manager".to_string()))?;
+        let plugin_manager = get_plugin_manager();
         
         // Unload all plugins
-        if let Err(e) = plugin_manager.unload_plugins() {
+        if let Err(e) = plugin_manager.unload_plugins().await {
             warn!("Failed to unload plugins: {}", e);
             // Continue anyway
         }
@@ -363,7 +414,7 @@ impl PluginCommand {
         let mut failure_count = 0;
         
         for name in plugins {
-            match plugin_manager.load_plugin(&name) {
+            match plugin_manager.load_plugin(&name).await {
                 Ok(()) => {
                     debug!("Plugin {} reloaded successfully", name);
                     success_count += 1;
@@ -384,4 +435,53 @@ impl PluginCommand {
         Ok(format!("Reloaded {} plugins ({} succeeded, {} failed)", 
                   success_count + failure_count, success_count, failure_count))
     }
+    
+    /// Create a plugin project from a scaffold
+    fn new_plugin(&self, matches: &ArgMatches) -> Result<String, CommandError> {
+        let name = matches.get_one::<String>("name")
+            .ok_or_else(|| CommandError::ValidationError("Plugin name is required".to_string()))?;
+        let description = matches.get_one::<String>("description")
+            .cloned()
+            .unwrap_or_else(|| format!("The {name} plugin"));
+        let author = matches.get_one::<String>("author").cloned().unwrap_or_default();
+        let dir = matches.get_one::<String>("dir").map_or_else(|| PathBuf::from(name), PathBuf::from);
+        let kind = matches.get_one::<String>("scaffold").map_or("plugin", String::as_str);
+        
+        let vars = scaffold::plugin_vars(name, &description, &author)?;
+        let files = scaffold::render(kind, &dir, &vars)?;
+        debug!("Created plugin {} with {} files", name, files.len());
+        Ok(format!("Created plugin {} in {}", name, dir.display()))
+    }
+    
+    /// Show recent log output of a plugin
+    async fn plugin_logs(&self, matches: &ArgMatches) -> Result<String, CommandError> {
+        // Get the plugin name
+        let name = matches.get_one::<String>("name")
+            .ok_or_else(|| CommandError::ValidationError("Plugin name is required".to_string()))?;
+        let limit = matches.get_one::<usize>("lines").copied();
+        
+        // Plugins that never logged have no buffer yet
+        let lines = match plugin_logs::global().recent(name, limit) {
+            Some(lines) => lines,
+            None => {
+                get_plugin_manager().get_plugin(name).map_err(|e| 
+                    CommandError::ExecutionError(e.to_string()))?;
+                Vec::new()
+            }
+        };
+        
+        let format_default = "text".to_string();
+        let format = matches.get_one::<String>("format").unwrap_or(&format_default);
+        if format == "text" {
+            if lines.is_empty() {
+                return Ok(format!("No log output from plugin {}", name));
+            }
+            return Ok(lines.iter().map(ToString::to_string).collect::<Vec<_>>().join("\n"));
+        }
+        
+        let formatter = FormatterFactory::create_formatter(format)
+            .map_err(|e| CommandError::ExecutionError(e.to_string()))?;
+        formatter.format(&lines)
+            .map_err(|e| CommandError::ExecutionError(e.to_string()))
+    }
 } 
\ No newline at end of file
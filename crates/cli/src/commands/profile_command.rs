@@ -0,0 +1,205 @@
+//! Profile command
+//!
+//! Takes memory and task profiles of a running web server from its
+//! `/api/debug/profile` endpoint, once or at intervals, and shows how
+//! resident memory, the live heap and the Tokio tasks grew between the
+//! first and the last. The heap is only counted by servers built with the
+//! `profiling` feature.
+
+use std::time::Duration;
+
+use clap::{Arg, ArgAction, ArgMatches, Command as ClapCommand};
+use squirrel_commands::{Command, CommandError};
+use squirrel_web::profiling::{fetch_profile, ProfileSnapshot};
+
+/// Environment variable holding the admin's access token
+const ADMIN_TOKEN_ENV: &str = "SQUIRREL_ADMIN_TOKEN";
+
+/// Bytes in a megabyte
+const MB: f64 = 1024.0 * 1024.0;
+
+/// Profile command implementation
+#[derive(Debug, Clone, Default)]
+pub struct ProfileCommand;
+
+impl ProfileCommand {
+    /// Create a new profile command
+    pub fn new() -> Self {
+        Self
+    }
+
+    /// Takes the profiles, waiting the interval between them
+    async fn run(matches: &ArgMatches) -> Result<String, CommandError> {
+        let target = matches.get_one::<String>("target").cloned().unwrap_or_default();
+        let token = matches
+            .get_one::<String>("token")
+            .cloned()
+            .or_else(|| std::env::var(ADMIN_TOKEN_ENV).ok().filter(|token| !token.is_empty()));
+        let samples = matches.get_one::<usize>("samples").copied().unwrap_or(1).max(1);
+        let interval = Duration::try_from_secs_f64(matches.get_one::<f64>("interval").copied().unwrap_or_default())
+            .map_err(|_| CommandError::ValidationError("--interval must be a positive number of seconds".to_string()))?;
+
+        let mut profiles = Vec::with_capacity(samples);
+        for sample in 0..samples {
+            if sample > 0 {
+                tokio::time::sleep(interval).await;
+            }
+            let profile = fetch_profile(&target, token.as_deref())
+                .await
+                .map_err(CommandError::ResourceError)?;
+            profiles.push(profile);
+        }
+
+        if matches.get_flag("json") {
+            return serde_json::to_string_pretty(&profiles).map_err(|e| CommandError::ExecutionError(e.to_string()));
+        }
+        Ok(format_profiles(&target, &profiles))
+    }
+}
+
+impl Command for ProfileCommand {
+    fn name(&self) -> &str {
+        "profile"
+    }
+
+    fn description(&self) -> &str {
+        "Profile the memory and tasks of a running web server"
+    }
+
+    fn parser(&self) -> ClapCommand {
+        ClapCommand::new("profile")
+            .about("Profile the memory and tasks of a running web server")
+            .arg(Arg::new("target")
+                .long("target")
+                .help("Base URL of the web server")
+                .value_name("URL")
+                .default_value("http://127.0.0.1:3000"))
+            .arg(Arg::new("token")
+                .long("token")
+                .help("Access token of a user with the admin role [default: $SQUIRREL_ADMIN_TOKEN]")
+                .value_name("TOKEN"))
+            .arg(Arg::new("samples")
+                .long("samples")
+                .short('n')
+                .help("Profiles to take")
+                .value_name("N")
+                .value_parser(clap::value_parser!(usize))
+                .default_value("1"))
+            .arg(Arg::new("interval")
+                .long("interval")
+                .help("Seconds between profiles")
+                .value_name("SECS")
+                .value_parser(clap::value_parser!(f64))
+                .default_value("10"))
+            .arg(Arg::new("json")
+                .long("json")
+                .help("Output in JSON format")
+                .action(ArgAction::SetTrue))
+    }
+
+    fn clone_box(&self) -> Box<dyn Command> {
+        Box::new(self.clone())
+    }
+
+    fn execute(&self, args: &[String]) -> Result<String, CommandError> {
+        let matches = self
+            .parser()
+            .try_get_matches_from(std::iter::once("profile".to_string()).chain(args.iter().cloned()))
+            .map_err(|e| CommandError::ValidationError(e.to_string()))?;
+        let run = Self::run(&matches);
+        match tokio::runtime::Handle::try_current() {
+            // Run from the CLI's async entry point
+            Ok(handle) => tokio::task::block_in_place(|| handle.block_on(run)),
+            Err(_) => tokio::runtime::Runtime::new()
+                .map_err(|e| CommandError::ExecutionError(format!("Failed to create runtime: {}", e)))?
+                .block_on(run),
+        }
+    }
+}
+
+/// Table of the profiles, with the growth from the first to the last
+fn format_profiles(target: &str, profiles: &[ProfileSnapshot]) -> String {
+    let mut lines = vec![
+        format!("Profile of {target}:"),
+        format!(
+            "  {:<19} {:>11} {:>9} {:>12} {:>9} {:>7} {:>7}",
+            "taken at", "resident MB", "heap MB", "allocations", "peak MB", "tasks", "queued"
+        ),
+    ];
+    for profile in profiles {
+        let heap = profile.allocation.as_ref();
+        let runtime = profile.runtime.as_ref();
+        lines.push(format!(
+            "  {:<19} {:>11} {:>9} {:>12} {:>9} {:>7} {:>7}",
+            profile.taken_at.format("%Y-%m-%d %H:%M:%S"),
+            profile.resident_mb,
+            heap.map_or("-".to_string(), |heap| format!("{:.1}", heap.live_bytes as f64 / MB)),
+            heap.map_or("-".to_string(), |heap| heap.live_allocations.to_string()),
+            heap.map_or("-".to_string(), |heap| format!("{:.1}", heap.peak_bytes as f64 / MB)),
+            runtime.map_or("-".to_string(), |runtime| runtime.alive_tasks.to_string()),
+            runtime.map_or("-".to_string(), |runtime| runtime.global_queue_depth.to_string()),
+        ));
+    }
+
+    if let (Some(first), Some(last)) = (profiles.first(), profiles.last()) {
+        if profiles.len() > 1 {
+            let seconds = (last.taken_at - first.taken_at).num_milliseconds() as f64 / 1000.0;
+            let mut growth = vec![format!(
+                "resident {:+} MB",
+                last.resident_mb as i64 - first.resident_mb as i64
+            )];
+            if let (Some(first), Some(last)) = (&first.allocation, &last.allocation) {
+                growth.push(format!("heap {:+.1} MB", (last.live_bytes as f64 - first.live_bytes as f64) / MB));
+            }
+            if let (Some(first), Some(last)) = (&first.runtime, &last.runtime) {
+                growth.push(format!("tasks {:+}", last.alive_tasks as i64 - first.alive_tasks as i64));
+            }
+            lines.push(format!("Growth over {seconds:.0}s: {}", growth.join(", ")));
+        }
+        if last.allocation.is_none() {
+            lines.push("The heap is only counted by servers built with the profiling feature".to_string());
+        }
+    }
+    lines.join("\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::{TimeZone, Utc};
+    use squirrel_web::profiling::{AllocationStats, RuntimeStats};
+
+    fn profile(seconds: i64, resident_mb: u64, live_bytes: u64, alive_tasks: usize) -> ProfileSnapshot {
+        ProfileSnapshot {
+            taken_at: Utc.timestamp_opt(1_792_000_000 + seconds, 0).unwrap(),
+            resident_mb,
+            allocation: Some(AllocationStats {
+                live_bytes,
+                live_allocations: 1200,
+                peak_bytes: 64 * 1024 * 1024,
+                ..AllocationStats::default()
+            }),
+            runtime: Some(RuntimeStats {
+                workers: 4,
+                alive_tasks,
+                global_queue_depth: 0,
+            }),
+        }
+    }
+
+    #[test]
+    fn test_profiles_show_growth() {
+        let profiles = [profile(0, 120, 40 * 1024 * 1024, 30), profile(60, 150, 72 * 1024 * 1024, 45)];
+        let output = format_profiles("http://127.0.0.1:3000", &profiles);
+        let lines: Vec<&str> = output.lines().collect();
+        assert_eq!(lines[0], "Profile of http://127.0.0.1:3000:");
+        assert!(lines[2].ends_with("         120      40.0         1200      64.0      30       0"), "{output}");
+        assert_eq!(lines[4], "Growth over 60s: resident +30 MB, heap +32.0 MB, tasks +15");
+
+        let mut unprofiled = profiles[1].clone();
+        unprofiled.allocation = None;
+        let output = format_profiles("http://127.0.0.1:3000", &[unprofiled]);
+        assert!(output.contains("        -            -         -"), "{output}");
+        assert!(output.ends_with("The heap is only counted by servers built with the profiling feature"));
+    }
+}
@@ -0,0 +1,188 @@
+//! Redact command
+//!
+//! Previews what the server's redaction rules do to a sample: the rules of
+//! the server configuration, plus rules given on the command line to try
+//! before they are configured. JSON samples are redacted like contexts and
+//! webhook payloads, other text like log messages.
+
+use std::path::Path;
+
+use clap::{Arg, ArgAction, ArgMatches, Command as ClapCommand};
+use serde_json::{json, Value};
+use squirrel_commands::{Command, CommandError};
+use squirrel_core::redaction::{RedactionConfig, RedactionRule, Redactor};
+use squirrel_web::config::Config;
+
+/// Environment variable naming the server configuration file
+pub(super) const CONFIG_ENV: &str = "SQUIRREL_CONFIG";
+
+/// The redaction rules of the server configuration at `path`, or the
+/// default rules without one
+pub(super) fn redaction_config(path: Option<&str>) -> Result<RedactionConfig, CommandError> {
+    match path {
+        Some(path) => Ok(Config::load(Path::new(path))
+            .map_err(|e| CommandError::ValidationError(e.to_string()))?
+            .redaction),
+        None => Ok(Config::default().redaction),
+    }
+}
+
+/// Redact command implementation
+#[derive(Debug, Clone, Default)]
+pub struct RedactCommand;
+
+impl RedactCommand {
+    /// Create a new redact command
+    pub fn new() -> Self {
+        Self
+    }
+
+    /// Redacts the sample and reports the matches of every rule
+    fn run(matches: &ArgMatches) -> Result<String, CommandError> {
+        let path = matches
+            .get_one::<String>("config")
+            .cloned()
+            .or_else(|| std::env::var(CONFIG_ENV).ok());
+        let mut config = redaction_config(path.as_deref())?;
+        config.enabled = true;
+        if matches.get_flag("no-builtin") {
+            config.builtin_rules = false;
+        }
+        let rules = |name: &str, rule: fn(&String) -> RedactionRule| {
+            matches
+                .get_many::<String>(name)
+                .into_iter()
+                .flatten()
+                .map(rule)
+                .collect::<Vec<_>>()
+        };
+        config.rules.extend(rules("key", |pattern| RedactionRule::key(pattern.as_str())));
+        config.rules.extend(rules("regex", |pattern| RedactionRule::regex(pattern.as_str())));
+        config.rules.extend(rules("path", |path| RedactionRule::path(path.as_str())));
+        let redactor = Redactor::new(&config).map_err(|e| CommandError::ValidationError(e.to_string()))?;
+
+        let sample = match (matches.get_one::<String>("text"), matches.get_one::<String>("file")) {
+            (Some(text), _) => text.clone(),
+            (None, Some(file)) => std::fs::read_to_string(file)
+                .map_err(|e| CommandError::ResourceError(format!("Failed to read {file}: {e}")))?,
+            (None, None) => {
+                return Err(CommandError::ValidationError("Give a sample, or a file with --file".to_string()));
+            }
+        };
+        let redacted = match serde_json::from_str::<Value>(&sample) {
+            Ok(mut value) if value.is_object() || value.is_array() => {
+                redactor.redact_value(&mut value);
+                serde_json::to_string_pretty(&value).map_err(|e| CommandError::ExecutionError(e.to_string()))?
+            }
+            _ => redactor.redact_text(&sample),
+        };
+        let matched: Vec<_> = redactor.stats().into_iter().filter(|stats| stats.matches > 0).collect();
+
+        if matches.get_flag("json") {
+            let output = json!({ "redacted": redacted, "matches": matched });
+            return serde_json::to_string_pretty(&output).map_err(|e| CommandError::ExecutionError(e.to_string()));
+        }
+        let mut output = redacted.trim_end().to_string();
+        if matched.is_empty() {
+            output.push_str("\n\nNo rule matched");
+        } else {
+            output.push_str("\n\nMatches:");
+            for stats in matched {
+                output.push_str(&format!("\n  {}: {}", stats.rule, stats.matches));
+            }
+        }
+        Ok(output)
+    }
+}
+
+impl Command for RedactCommand {
+    fn name(&self) -> &str {
+        "redact"
+    }
+
+    fn description(&self) -> &str {
+        "Preview the redaction rules on a sample"
+    }
+
+    fn parser(&self) -> ClapCommand {
+        ClapCommand::new("redact")
+            .about("Preview the redaction rules on a sample")
+            .arg(Arg::new("text")
+                .help("Sample text or JSON to redact"))
+            .arg(Arg::new("file")
+                .long("file")
+                .short('f')
+                .help("Read the sample from this file")
+                .value_name("FILE")
+                .conflicts_with("text"))
+            .arg(Arg::new("config")
+                .long("config")
+                .help("Server configuration file [default: $SQUIRREL_CONFIG]")
+                .value_name("PATH"))
+            .arg(Arg::new("key")
+                .long("key")
+                .help("Also redact the values of keys matching this pattern, e.g. '*_id'")
+                .value_name("PATTERN")
+                .action(ArgAction::Append))
+            .arg(Arg::new("regex")
+                .long("regex")
+                .help("Also redact text matching this regular expression")
+                .value_name("REGEX")
+                .action(ArgAction::Append))
+            .arg(Arg::new("path")
+                .long("path")
+                .help("Also redact the value at this JSON path, e.g. '$.user.email'")
+                .value_name("PATH")
+                .action(ArgAction::Append))
+            .arg(Arg::new("no-builtin")
+                .long("no-builtin")
+                .help("Leave out the built-in rules")
+                .action(ArgAction::SetTrue))
+            .arg(Arg::new("json")
+                .long("json")
+                .help("Output in JSON format")
+                .action(ArgAction::SetTrue))
+    }
+
+    fn clone_box(&self) -> Box<dyn Command> {
+        Box::new(self.clone())
+    }
+
+    fn execute(&self, args: &[String]) -> Result<String, CommandError> {
+        let matches = self
+            .parser()
+            .try_get_matches_from(std::iter::once("redact".to_string()).chain(args.iter().cloned()))
+            .map_err(|e| CommandError::ValidationError(e.to_string()))?;
+        Self::run(&matches)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_preview_redaction() {
+        let dir = tempfile::tempdir().unwrap();
+        let config = dir.path().join("server.json");
+        let rules = r#"{"redaction": {"rules": [{"type": "regex", "name": "email", "pattern": "[\\w.]+@[\\w.]+"}]}}"#;
+        std::fs::write(&config, rules).unwrap();
+        let run = |args: &[&str]| {
+            let mut args: Vec<String> = args.iter().map(ToString::to_string).collect();
+            args.extend(["--config".to_string(), config.display().to_string()]);
+            RedactCommand::new().execute(&args)
+        };
+
+        assert_eq!(
+            run(&["Mail alice@example.org, token Bearer abc"]).unwrap(),
+            "Mail [REDACTED], token Bearer [REDACTED]\n\nMatches:\n  builtin:bearer: 1\n  email: 1"
+        );
+        let output = run(&[r#"{"user": {"id": "u1", "password": "p"}}"#, "--path", "$.user.id", "--json"]).unwrap();
+        let output: Value = serde_json::from_str(&output).unwrap();
+        let redacted: Value = serde_json::from_str(output["redacted"].as_str().unwrap()).unwrap();
+        assert_eq!(redacted, json!({"user": {"id": "[REDACTED]", "password": "[REDACTED]"}}));
+        assert_eq!(run(&["plain text", "--no-builtin"]).unwrap(), "plain text\n\nNo rule matched");
+        assert!(run(&["x", "--regex", "("]).is_err());
+        assert!(run(&[]).is_err());
+    }
+}
@@ -0,0 +1,132 @@
+//! Replay command
+//!
+//! Re-executes a command run from its reproducibility manifest and reports
+//! how the current environment has drifted from the recorded one.
+
+use std::collections::BTreeMap;
+use std::path::PathBuf;
+use std::sync::{Arc, Weak};
+
+use clap::{Arg, ArgAction, Command as ClapCommand};
+use squirrel_commands::manifest::{self, RunManifest};
+use squirrel_commands::{Command, CommandError, CommandRegistry};
+
+/// Replay command implementation
+#[derive(Debug, Clone, Default)]
+pub struct ReplayCommand {
+    /// Registry the replayed command is looked up in
+    registry: Weak<CommandRegistry>,
+    /// Versions of the currently loaded plugins
+    plugins: BTreeMap<String, String>,
+}
+
+impl ReplayCommand {
+    /// Create a replay command running commands from `registry`
+    ///
+    /// The registry is held weakly because the command is registered in it.
+    pub fn new(registry: &Arc<CommandRegistry>, plugins: BTreeMap<String, String>) -> Self {
+        Self {
+            registry: Arc::downgrade(registry),
+            plugins,
+        }
+    }
+}
+
+impl Command for ReplayCommand {
+    fn name(&self) -> &str {
+        "replay"
+    }
+
+    fn description(&self) -> &str {
+        "Re-execute a command from a reproducibility manifest and report drift"
+    }
+
+    fn parser(&self) -> ClapCommand {
+        ClapCommand::new("replay")
+            .about("Re-execute a command from a reproducibility manifest and report drift")
+            .arg(Arg::new("manifest")
+                .help("Manifest file written with --capture-manifest")
+                .required(true)
+                .value_name("FILE"))
+            .arg(Arg::new("dry-run")
+                .long("dry-run")
+                .help("Only report environment drift")
+                .action(ArgAction::SetTrue))
+            .arg(Arg::new("strict")
+                .long("strict")
+                .help("Fail if the environment drifted or the result differs")
+                .action(ArgAction::SetTrue))
+            .arg(Arg::new("json")
+                .long("json")
+                .help("Output the report in JSON format")
+                .action(ArgAction::SetTrue))
+    }
+
+    fn clone_box(&self) -> Box<dyn Command> {
+        Box::new(self.clone())
+    }
+
+    fn execute(&self, args: &[String]) -> Result<String, CommandError> {
+        let matches = self
+            .parser()
+            .try_get_matches_from(std::iter::once("replay".to_string()).chain(args.iter().cloned()))
+            .map_err(|e| CommandError::ValidationError(e.to_string()))?;
+        let path = matches.get_one::<String>("manifest").map(PathBuf::from).unwrap_or_default();
+
+        let manifest = RunManifest::load(&path)?;
+        if manifest.command == self.name() {
+            return Err(CommandError::ValidationError("A replay cannot be replayed".to_string()));
+        }
+        let registry = self
+            .registry
+            .upgrade()
+            .ok_or_else(|| CommandError::RegistryError("Command registry is no longer available".to_string()))?;
+
+        let report = manifest::replay(&registry, &manifest, self.plugins.clone(), matches.get_flag("dry-run"))?;
+        if matches.get_flag("strict") && !report.is_reproducible() {
+            return Err(CommandError::ExecutionError(format!("Run is not reproducible\n{}", report)));
+        }
+        if matches.get_flag("json") {
+            serde_json::to_string_pretty(&report).map_err(|e| CommandError::ExecutionError(e.to_string()))
+        } else {
+            Ok(report.to_string())
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use squirrel_commands::manifest::ManifestOptions;
+    use tempfile::tempdir;
+
+    use crate::commands::VersionCommand;
+
+    #[test]
+    fn test_replay_from_manifest_file() {
+        let dir = tempdir().unwrap();
+        let registry = Arc::new(CommandRegistry::new());
+        registry.register("version", Arc::new(VersionCommand::new())).unwrap();
+        let replay = ReplayCommand::new(&registry, BTreeMap::new());
+        registry.register("replay", Arc::new(replay.clone())).unwrap();
+
+        let options = ManifestOptions {
+            env_allowlist: Vec::new(),
+            ..ManifestOptions::default()
+        };
+        let path = dir.path().join("run.json");
+        RunManifest::capture("version", &[], &options).unwrap().save(&path).unwrap();
+
+        let path = path.display().to_string();
+        let output = replay.execute(&[path.clone(), "--strict".to_string()]).unwrap();
+        assert!(output.contains("No environment drift"), "{output}");
+
+        let json = replay.execute(&[path, "--dry-run".to_string(), "--json".to_string()]).unwrap();
+        let report: serde_json::Value = serde_json::from_str(&json).unwrap();
+        assert_eq!(report["executed"], false);
+
+        let path = dir.path().join("replay.json");
+        RunManifest::capture("replay", &[], &options).unwrap().save(&path).unwrap();
+        assert!(replay.execute(&[path.display().to_string()]).is_err());
+    }
+}
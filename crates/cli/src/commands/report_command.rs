@@ -0,0 +1,193 @@
+//! Report command
+//!
+//! Renders a standalone HTML or Markdown report of a job from the contexts
+//! recorded in the MCP persistence directory, without a running server.
+//! Finished jobs are read from their snapshot, running ones from their open
+//! context.
+
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use chrono::{DateTime, Utc};
+use clap::{Arg, Command as ClapCommand};
+use serde_json::Value;
+use squirrel_commands::report::{ReportFormat, RunReport};
+use squirrel_commands::{Command, CommandError};
+use squirrel_mcp::persistence::{MCPPersistence, PersistenceConfig};
+use squirrel_mcp::Context;
+
+/// Suffix of the names of job snapshots
+const SNAPSHOT_SUFFIX: &str = " (snapshot)";
+
+/// Report command implementation
+#[derive(Debug, Clone, Default)]
+pub struct ReportCommand;
+
+impl ReportCommand {
+    /// Create a new report command
+    pub fn new() -> Self {
+        Self
+    }
+}
+
+impl Command for ReportCommand {
+    fn name(&self) -> &str {
+        "report"
+    }
+
+    fn description(&self) -> &str {
+        "Render a report of a job"
+    }
+
+    fn parser(&self) -> ClapCommand {
+        ClapCommand::new("report")
+            .about("Render a report of a job")
+            .arg(Arg::new("job-id")
+                .help("ID of the job")
+                .required(true))
+            .arg(Arg::new("data-dir")
+                .long("data-dir")
+                .help("MCP persistence directory [default: data/mcp]")
+                .value_name("DIR"))
+            .arg(Arg::new("format")
+                .long("format")
+                .help("html or markdown [default: from --out, else markdown]")
+                .value_name("FORMAT"))
+            .arg(Arg::new("template")
+                .long("template")
+                .help("Template file, or name of a template in ~/.squirrel/templates, whose {{ section }} placeholders are filled")
+                .value_name("TEMPLATE"))
+            .arg(Arg::new("out")
+                .long("out")
+                .short('o')
+                .help("Write the report to a file instead of printing it")
+                .value_name("FILE"))
+    }
+
+    fn clone_box(&self) -> Box<dyn Command> {
+        Box::new(self.clone())
+    }
+
+    fn execute(&self, args: &[String]) -> Result<String, CommandError> {
+        let matches = self
+            .parser()
+            .try_get_matches_from(std::iter::once("report".to_string()).chain(args.iter().cloned()))
+            .map_err(|e| CommandError::ValidationError(e.to_string()))?;
+        let job_id = matches
+            .get_one::<String>("job-id")
+            .ok_or_else(|| CommandError::ValidationError("Missing job ID".to_string()))?;
+        let out = matches.get_one::<String>("out").map(PathBuf::from);
+        let format = match matches.get_one::<String>("format") {
+            Some(format) => format.parse()?,
+            None => match out.as_ref().and_then(|out| out.extension()).and_then(|ext| ext.to_str()) {
+                Some(ext) => ext.parse().unwrap_or(ReportFormat::Markdown),
+                None => ReportFormat::Markdown,
+            },
+        };
+
+        let mut config = PersistenceConfig::default();
+        if let Some(dir) = matches.get_one::<String>("data-dir") {
+            config.data_dir = PathBuf::from(dir);
+        }
+        let contexts = MCPPersistence::new(config)
+            .load_contexts()
+            .map_err(|e| CommandError::ResourceError(format!("{e}")))?;
+        let report = job_report(&contexts, job_id)
+            .ok_or_else(|| CommandError::ResourceError(format!("No recorded job {job_id}")))?;
+
+        let text = match matches.get_one::<String>("template") {
+            Some(path) if !Path::new(path).exists() => report.render_named(path, format)?,
+            Some(path) => {
+                let template = fs::read_to_string(path)
+                    .map_err(|e| CommandError::ResourceError(format!("Cannot read template {path}: {e}")))?;
+                report.render_with(&template, format)?
+            }
+            None => report.render(format),
+        };
+        match out {
+            Some(path) => {
+                fs::write(&path, text)
+                    .map_err(|e| CommandError::ResourceError(format!("Cannot write {}: {e}", path.display())))?;
+                Ok(format!("Wrote report of job {job_id} to {}", path.display()))
+            }
+            None => Ok(text),
+        }
+    }
+}
+
+/// Report of a job from its snapshot if it has finished, else its open context
+fn job_report(contexts: &[Context], job_id: &str) -> Option<RunReport> {
+    let of_job = |context: &&Context| {
+        context
+            .metadata
+            .as_ref()
+            .and_then(|metadata| metadata.get("job_id"))
+            .and_then(Value::as_str)
+            == Some(job_id)
+    };
+    let state = |context: &Context| {
+        context
+            .metadata
+            .as_ref()
+            .and_then(|metadata| metadata.get("state"))
+            .and_then(Value::as_str)
+            .map(str::to_string)
+    };
+    let context = contexts
+        .iter()
+        .filter(of_job)
+        .max_by_key(|context| (state(context).is_some(), context.updated_at))?;
+
+    let state = state(context);
+    let name = context.name.strip_suffix(SNAPSHOT_SUFFIX).unwrap_or(&context.name);
+    let mut report = RunReport::from_job_context(job_id, name, state.as_deref(), &context.data);
+    report.finished_at = context
+        .metadata
+        .as_ref()
+        .and_then(|metadata| metadata.get("archived_at"))
+        .and_then(|time| serde_json::from_value::<DateTime<Utc>>(time.clone()).ok());
+    Some(report)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+    use uuid::Uuid;
+
+    fn context(name: &str, metadata: Value) -> Context {
+        Context {
+            id: Uuid::new_v4(),
+            name: name.to_string(),
+            data: json!({
+                "job_id": "job-1",
+                "steps": [{ "command": "align", "parameters": {}, "command_id": "cmd-1", "submitted_at": Utc::now() }],
+            }),
+            metadata: Some(metadata),
+            parent_id: None,
+            created_at: Utc::now(),
+            updated_at: Utc::now(),
+            expires_at: None,
+        }
+    }
+
+    #[test]
+    fn test_report_prefers_the_snapshot_of_a_finished_job() {
+        let open = context("job align", json!({ "job_id": "job-1", "owner": "alice" }));
+        let snapshot = context(
+            "job align (snapshot)",
+            json!({ "job_id": "job-1", "owner": "alice", "state": "Completed", "archived_at": Utc::now() }),
+        );
+        let other = context("job call", json!({ "job_id": "job-2" }));
+
+        let report = job_report(&[snapshot.clone(), open.clone(), other], "job-1").unwrap();
+        assert_eq!(report.name, "job align");
+        assert_eq!(report.status, "completed");
+        assert!(report.finished_at.is_some());
+        assert_eq!(report.steps[0].command.as_deref(), Some("align"));
+
+        let running = job_report(&[open], "job-1").unwrap();
+        assert_eq!(running.status, "running");
+        assert!(job_report(&[snapshot], "job-3").is_none());
+    }
+}
@@ -1,11 +1,12 @@
 use std::error::Error;
-use std::time::Duration;
+use std::time::{Duration, Instant};
 use serde::Serialize;
 use clap::Command as ClapCommand;
 use squirrel_commands::{Command, CommandError};
 use squirrel_core::Core;
 use tokio::time;
 use crate::formatter::{FormatterFactory, OutputFormat};
+use crate::output::{self, Summaries};
 
 #[derive(Serialize, Debug)]
 struct SystemStatus {
@@ -119,13 +120,23 @@ impl Command for StatusCommand {
                 .and_then(|val| val.parse::<u64>().ok())
                 .unwrap_or(5);
 
+            // Accessible output keeps earlier statuses and prints only changes
+            let accessible = output::is_accessible();
+            let mut summaries = Summaries::new(Duration::from_secs(interval));
             rt.block_on(async {
                 loop {
                     // Clear screen
-                    print!("\x1B[2J\x1B[1;1H");
+                    if !accessible {
+                        print!("\x1B[2J\x1B[1;1H");
+                    }
                     
                     // Display status
                     match self.display_status(format).await {
+                        Ok(status) if accessible => {
+                            if let Some(status) = summaries.offer(Instant::now(), status) {
+                                println!("Status at {}:\n{}", chrono::Local::now().format("%H:%M:%S"), status);
+                            }
+                        }
                         Ok(status) => println!("{}", status),
                         Err(e) => {
                             let formatter = FormatterFactory::create(format);
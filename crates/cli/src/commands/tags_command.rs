@@ -0,0 +1,267 @@
+//! Tags command
+//!
+//! Labels jobs, command definitions, experiments and datasets in the web
+//! database, finds them by tag and changes the tags of every match at once.
+//! Tags are written `key=value` or `key`; as filters, a key alone matches
+//! any of its values.
+
+use clap::{Arg, ArgAction, ArgMatches, Command as ClapCommand};
+use squirrel_commands::{Command, CommandError};
+use squirrel_core::tags::{parse_tags, ResourceKind, Tag, TagError};
+use squirrel_web::api::error::AppError;
+use squirrel_web::migrations;
+use squirrel_web::tags::{SqlTagStore, TagStore, TaggedResource};
+
+use super::database::database_url;
+
+/// Tags command implementation
+#[derive(Debug, Clone, Default)]
+pub struct TagsCommand;
+
+impl TagsCommand {
+    /// Create a new tags command
+    pub fn new() -> Self {
+        Self
+    }
+
+    /// Runs the subcommand against the web database
+    async fn run(matches: &ArgMatches) -> Result<String, CommandError> {
+        let url = database_url(matches)?;
+        let pool = migrations::connect(&url)
+            .await
+            .map_err(|e| CommandError::ResourceError(format!("Failed to open {url}: {e}")))?;
+        let store = SqlTagStore::new(pool);
+        let user = matches.get_one::<String>("user").map(String::as_str);
+        let writer = || {
+            user.ok_or_else(|| CommandError::ValidationError("Pass --user to change the tags of a user".to_string()))
+        };
+        let store_error = |e: AppError| CommandError::ExecutionError(format!("{e}; is the web database migrated?"));
+        let json = matches.get_flag("json");
+
+        match matches.subcommand() {
+            Some(("list", _)) => {
+                let usage = store.usage(user).await.map_err(store_error)?;
+                if json {
+                    return to_json(&usage);
+                }
+                if usage.is_empty() {
+                    return Ok("No tags".to_string());
+                }
+                Ok(usage
+                    .iter()
+                    .map(|usage| format!("{:<40} {}", usage.tag.to_string(), usage.resources))
+                    .collect::<Vec<_>>()
+                    .join("\n"))
+            }
+            Some(("find", sub)) => {
+                let kind = kind_arg(sub, "kind")?;
+                let resources = store.find(user, kind, &tag_args(sub, "tag")?).await.map_err(store_error)?;
+                format_resources(&resources, json)
+            }
+            Some(("bulk", sub)) => {
+                let resources = store
+                    .bulk(
+                        writer()?,
+                        kind_arg(sub, "kind")?,
+                        &tag_args(sub, "tag")?,
+                        &tag_args(sub, "add")?,
+                        &tag_args(sub, "remove")?,
+                    )
+                    .await
+                    .map_err(store_error)?;
+                format_resources(&resources, json)
+            }
+            Some((action @ ("show" | "add" | "remove"), sub)) => {
+                let kind = kind_arg(sub, "resource-kind")?.unwrap_or(ResourceKind::Job);
+                let id = sub.get_one::<String>("id").cloned().unwrap_or_default();
+                let tags = match action {
+                    "show" => store.tags_of(user, kind, &id).await,
+                    "add" => store.add(writer()?, kind, &id, &tag_args(sub, "tag")?).await,
+                    _ => store.remove(writer()?, kind, &id, &tag_args(sub, "tag")?).await,
+                }
+                .map_err(store_error)?;
+                format_resources(&[TaggedResource { kind, id, tags }], json)
+            }
+            _ => Err(CommandError::ValidationError("Unknown tags subcommand".to_string())),
+        }
+    }
+}
+
+/// Parses the tags given to a repeatable option
+fn tag_args(matches: &ArgMatches, name: &str) -> Result<Vec<Tag>, CommandError> {
+    let values = matches.get_many::<String>(name).into_iter().flatten().map(String::as_str);
+    parse_tags(values).map_err(|e| CommandError::ValidationError(e.to_string()))
+}
+
+/// Parses a resource kind argument
+fn kind_arg(matches: &ArgMatches, name: &str) -> Result<Option<ResourceKind>, CommandError> {
+    matches
+        .get_one::<String>(name)
+        .map(|kind| kind.parse().map_err(|e: TagError| CommandError::ValidationError(e.to_string())))
+        .transpose()
+}
+
+/// Resources as JSON, or one `kind id: tags` line each
+fn format_resources(resources: &[TaggedResource], json: bool) -> Result<String, CommandError> {
+    if json {
+        return to_json(&resources);
+    }
+    if resources.is_empty() {
+        return Ok("No matching resources".to_string());
+    }
+    Ok(resources
+        .iter()
+        .map(|resource| {
+            let tags: Vec<String> = resource.tags.iter().map(ToString::to_string).collect();
+            format!("{} {}: {}", resource.kind, resource.id, tags.join(", "))
+        })
+        .collect::<Vec<_>>()
+        .join("\n"))
+}
+
+/// Pretty-printed JSON of a value
+fn to_json(value: &impl serde::Serialize) -> Result<String, CommandError> {
+    serde_json::to_string_pretty(value).map_err(|e| CommandError::ExecutionError(e.to_string()))
+}
+
+impl Command for TagsCommand {
+    fn name(&self) -> &str {
+        "tags"
+    }
+
+    fn description(&self) -> &str {
+        "Label jobs, commands, experiments and datasets and find them by tag"
+    }
+
+    fn tags(&self) -> Vec<String> {
+        vec!["labels".to_string()]
+    }
+
+    fn parser(&self) -> ClapCommand {
+        let tag = |help: &'static str, required: bool| {
+            Arg::new("tag")
+                .long("tag")
+                .short('t')
+                .help(help)
+                .value_name("KEY[=VALUE]")
+                .required(required)
+                .action(ArgAction::Append)
+        };
+        let kind = || {
+            Arg::new("kind")
+                .long("kind")
+                .help("Only resources of this kind: job, command, experiment or dataset")
+                .value_name("KIND")
+        };
+        let resource = |command: ClapCommand| {
+            command
+                .arg(Arg::new("resource-kind")
+                    .help("Kind of resource: job, command, experiment or dataset")
+                    .required(true))
+                .arg(Arg::new("id")
+                    .help("ID of the resource")
+                    .required(true))
+        };
+        ClapCommand::new("tags")
+            .about("Label jobs, commands, experiments and datasets and find them by tag")
+            .subcommand_required(true)
+            .arg(Arg::new("database")
+                .long("database")
+                .help("Web database URL [default: $DATABASE_URL]")
+                .value_name("URL")
+                .global(true))
+            .arg(Arg::new("user")
+                .long("user")
+                .help("User whose tags are read or changed [default: every user when reading]")
+                .value_name("USER")
+                .global(true))
+            .arg(Arg::new("json")
+                .long("json")
+                .help("Output in JSON format")
+                .action(ArgAction::SetTrue)
+                .global(true))
+            .subcommand(ClapCommand::new("list")
+                .about("List the tags in use and how many resources have each"))
+            .subcommand(resource(ClapCommand::new("show")
+                .about("Show the tags of a resource")))
+            .subcommand(resource(ClapCommand::new("add")
+                .about("Add tags to a resource"))
+                .arg(tag("Tag to add, e.g. experiment=rnaseq; repeat or separate with commas", true)))
+            .subcommand(resource(ClapCommand::new("remove")
+                .about("Remove tags from a resource"))
+                .arg(tag("Tag to remove; a key alone removes all its values", true)))
+            .subcommand(ClapCommand::new("find")
+                .about("Find the resources having all the given tags")
+                .arg(kind())
+                .arg(tag("Tag the resources must have; a key alone matches any value", false)))
+            .subcommand(ClapCommand::new("bulk")
+                .about("Add and remove tags on every resource having all the given tags")
+                .arg(kind())
+                .arg(tag("Tag the changed resources must have", true))
+                .arg(Arg::new("add")
+                    .long("add")
+                    .help("Tag to add to each match")
+                    .value_name("KEY[=VALUE]")
+                    .action(ArgAction::Append))
+                .arg(Arg::new("remove")
+                    .long("remove")
+                    .help("Tag to remove from each match")
+                    .value_name("KEY[=VALUE]")
+                    .action(ArgAction::Append)))
+    }
+
+    fn clone_box(&self) -> Box<dyn Command> {
+        Box::new(self.clone())
+    }
+
+    fn execute(&self, args: &[String]) -> Result<String, CommandError> {
+        let matches = self
+            .parser()
+            .try_get_matches_from(std::iter::once("tags".to_string()).chain(args.iter().cloned()))
+            .map_err(|e| CommandError::ValidationError(e.to_string()))?;
+        let run = Self::run(&matches);
+        match tokio::runtime::Handle::try_current() {
+            // Run from the CLI's async entry point
+            Ok(handle) => tokio::task::block_in_place(|| handle.block_on(run)),
+            Err(_) => tokio::runtime::Runtime::new()
+                .map_err(|e| CommandError::ExecutionError(format!("Failed to create runtime: {}", e)))?
+                .block_on(run),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::commands::MigrateCommand;
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_tag_find_and_bulk_change() {
+        let dir = tempdir().unwrap();
+        let database = format!("sqlite://{}", dir.path().join("web.db").display());
+        let migrate = ["up", "--store", "web", "--database", &database].map(String::from);
+        MigrateCommand::new().execute(&migrate).unwrap();
+        let run = |args: &[&str]| {
+            let mut args: Vec<String> = args.iter().map(ToString::to_string).collect();
+            args.extend(["--database".to_string(), database.clone()]);
+            TagsCommand::new().execute(&args)
+        };
+
+        assert!(run(&["add", "job", "j1", "--tag", "experiment=rnaseq"]).is_err());
+        let output = run(&["add", "job", "j1", "--tag", "experiment=rnaseq,urgent", "--user", "alice"]).unwrap();
+        assert_eq!(output, "job j1: experiment=rnaseq, urgent");
+        run(&["add", "datasets", "reads.fastq", "-t", "experiment=rnaseq", "--user", "alice"]).unwrap();
+        run(&["add", "job", "j2", "-t", "experiment=chipseq", "--user", "bob"]).unwrap();
+
+        let output = run(&["find", "--tag", "experiment=rnaseq"]).unwrap();
+        assert_eq!(output, "job j1: experiment=rnaseq, urgent\ndataset reads.fastq: experiment=rnaseq");
+        let output = run(&["find", "--kind", "job", "--tag", "experiment"]).unwrap();
+        assert!(output.contains("j1") && output.contains("j2"), "{output}");
+
+        let output = run(&["bulk", "--tag", "experiment=rnaseq", "--add", "archived", "--remove", "urgent", "--user", "alice"]).unwrap();
+        assert_eq!(output, "job j1: archived, experiment=rnaseq\ndataset reads.fastq: archived, experiment=rnaseq");
+        assert!(run(&["list", "--user", "bob"]).unwrap().starts_with("experiment=chipseq"));
+        assert_eq!(run(&["remove", "job", "j2", "-t", "experiment", "--user", "bob"]).unwrap(), "job j2: ");
+    }
+}
@@ -0,0 +1,328 @@
+//! Teams command
+//!
+//! Creates, renames and deletes teams and manages their members, working on
+//! the web database like the server's `/api/teams` endpoints. Admins manage
+//! every team; owners of a team may rename it and manage its members. Every
+//! change is written to the admin audit log.
+
+use clap::{Arg, ArgMatches, Command as ClapCommand};
+use squirrel_commands::{Command, CommandError};
+use squirrel_web::users::{TeamMember, TeamRole};
+use uuid::Uuid;
+
+use super::admin_command::{admin_error, to_json, Operator};
+use super::users_command::{audited, directory};
+
+/// One member as a line
+fn format_member(member: &TeamMember) -> String {
+    format!(
+        "{}  {:<16} {:<6} since {}",
+        member.user_id,
+        member.username.as_deref().unwrap_or("-"),
+        member.role.as_str(),
+        member.added_at.to_rfc3339()
+    )
+}
+
+/// Teams command implementation
+#[derive(Debug, Clone, Default)]
+pub struct TeamsCommand;
+
+impl TeamsCommand {
+    /// Create a new teams command
+    pub fn new() -> Self {
+        Self
+    }
+
+    /// Runs the subcommand against the web database
+    async fn run(matches: &ArgMatches) -> Result<String, CommandError> {
+        let operator = Operator::connect(matches).await?;
+        let controls = operator.controls();
+        let users = directory(&operator);
+        let json = matches.get_flag("json");
+        let arg = |sub: &ArgMatches, name: &str| sub.get_one::<String>(name).cloned().unwrap_or_default();
+
+        // Admins manage every team, owners their own
+        let manages = |team_id: Uuid| {
+            let users = &users;
+            let operator = &operator;
+            async move {
+                if operator.is_admin {
+                    return Ok(true);
+                }
+                let Ok(user_id) = Uuid::parse_str(&operator.actor) else {
+                    return Ok(false);
+                };
+                let role = users.team_role(team_id, user_id).await.map_err(admin_error)?;
+                Ok::<_, CommandError>(role == Some(TeamRole::Owner))
+            }
+        };
+
+        let output = match matches.subcommand() {
+            Some(("list", _)) => {
+                let member = if operator.is_admin {
+                    None
+                } else {
+                    Some(Uuid::parse_str(&operator.actor).unwrap_or_default())
+                };
+                let teams = users.teams(member).await.map_err(admin_error)?;
+                if json {
+                    return to_json(&teams);
+                }
+                if teams.is_empty() {
+                    return Ok("No teams".to_string());
+                }
+                teams
+                    .iter()
+                    .map(|team| match &team.description {
+                        Some(description) => format!("{}  {}: {}", team.id, team.name, description),
+                        None => format!("{}  {}", team.id, team.name),
+                    })
+                    .collect::<Vec<_>>()
+                    .join("\n")
+            }
+            Some(("members", sub)) => {
+                let team = users.find_team(&arg(sub, "team")).await.map_err(admin_error)?;
+                let members = users.members(team.id).await.map_err(admin_error)?;
+                let is_member = members.iter().any(|member| member.user_id.to_string() == operator.actor);
+                if !operator.is_admin && !is_member {
+                    return Err(CommandError::AuthorizationError("Only members can read a team".to_string()));
+                }
+                if json {
+                    return to_json(&members);
+                }
+                if members.is_empty() {
+                    return Ok(format!("Team {} has no members", team.name));
+                }
+                members.iter().map(format_member).collect::<Vec<_>>().join("\n")
+            }
+            Some(("create", sub)) => {
+                let name = arg(sub, "name");
+                let description = sub.get_one::<String>("description").cloned();
+                let team = audited(
+                    &controls,
+                    &operator,
+                    operator.is_admin,
+                    "teams.create",
+                    Some(&name),
+                    users.create_team(&operator.actor, &name, description),
+                )
+                .await?;
+                if json {
+                    return to_json(&team);
+                }
+                format!("Created team {} ({})", team.name, team.id)
+            }
+            Some(("update", sub)) => {
+                let team = users.find_team(&arg(sub, "team")).await.map_err(admin_error)?;
+                let target = team.id.to_string();
+                let name = sub.get_one::<String>("name").cloned();
+                let description = sub.get_one::<String>("description").cloned();
+                let allowed = manages(team.id).await?;
+                let team = audited(
+                    &controls,
+                    &operator,
+                    allowed,
+                    "teams.update",
+                    Some(&target),
+                    users.update_team(team.id, name, description),
+                )
+                .await?;
+                if json {
+                    return to_json(&team);
+                }
+                format!("Updated team {}", team.name)
+            }
+            Some(("delete", sub)) => {
+                let team = users.find_team(&arg(sub, "team")).await.map_err(admin_error)?;
+                let target = team.id.to_string();
+                audited(&controls, &operator, operator.is_admin, "teams.delete", Some(&target), users.delete_team(team.id))
+                    .await?;
+                format!("Deleted team {}", team.name)
+            }
+            Some(("add-member", sub)) => {
+                let team = users.find_team(&arg(sub, "team")).await.map_err(admin_error)?;
+                let user = users.find_user(&arg(sub, "user")).await.map_err(admin_error)?;
+                let role = sub
+                    .get_one::<String>("role")
+                    .map(|role| role.parse::<TeamRole>().map_err(CommandError::ValidationError))
+                    .transpose()?
+                    .unwrap_or(TeamRole::Member);
+                let target = format!("{}/{}", team.id, user.id);
+                let allowed = manages(team.id).await?;
+                let member = audited(
+                    &controls,
+                    &operator,
+                    allowed,
+                    "teams.set_member",
+                    Some(&target),
+                    users.set_member(team.id, user.id, role),
+                )
+                .await?;
+                if json {
+                    return to_json(&member);
+                }
+                format!("{} is now {} of team {}", user.username, role.as_str(), team.name)
+            }
+            Some(("remove-member", sub)) => {
+                let team = users.find_team(&arg(sub, "team")).await.map_err(admin_error)?;
+                let user = users.find_user(&arg(sub, "user")).await.map_err(admin_error)?;
+                let target = format!("{}/{}", team.id, user.id);
+                // Members may leave on their own
+                let allowed = user.id.to_string() == operator.actor || manages(team.id).await?;
+                audited(
+                    &controls,
+                    &operator,
+                    allowed,
+                    "teams.remove_member",
+                    Some(&target),
+                    users.remove_member(team.id, user.id),
+                )
+                .await?;
+                format!("Removed {} from team {}", user.username, team.name)
+            }
+            _ => return Err(CommandError::ValidationError("Unknown teams subcommand".to_string())),
+        };
+        Ok(output)
+    }
+}
+
+impl Command for TeamsCommand {
+    fn name(&self) -> &str {
+        "teams"
+    }
+
+    fn description(&self) -> &str {
+        "Create teams of the web server and manage their members"
+    }
+
+    fn tags(&self) -> Vec<String> {
+        vec!["teams".to_string(), "users".to_string()]
+    }
+
+    fn parser(&self) -> ClapCommand {
+        let team = || Arg::new("team").help("ID or name of the team").required(true);
+        let user = || Arg::new("user").help("ID, username or email address of the user").required(true);
+        let description = || Arg::new("description")
+            .long("description")
+            .help("What the team works on")
+            .value_name("TEXT");
+        Operator::args(ClapCommand::new("teams")
+            .about("Create teams of the web server and manage their members")
+            .subcommand_required(true))
+            .subcommand(ClapCommand::new("list")
+                .about("List every team, or your own unless you are an admin"))
+            .subcommand(ClapCommand::new("members")
+                .about("List the members of a team")
+                .arg(team()))
+            .subcommand(ClapCommand::new("create")
+                .about("Create a team")
+                .arg(Arg::new("name").help("Unique name of the team").required(true))
+                .arg(description()))
+            .subcommand(ClapCommand::new("update")
+                .about("Rename a team or change its description")
+                .arg(team())
+                .arg(Arg::new("name")
+                    .long("name")
+                    .help("New name")
+                    .value_name("NAME"))
+                .arg(description()))
+            .subcommand(ClapCommand::new("delete")
+                .about("Delete a team and its memberships")
+                .arg(team()))
+            .subcommand(ClapCommand::new("add-member")
+                .about("Add a user to a team, or change their role in it")
+                .arg(team())
+                .arg(user())
+                .arg(Arg::new("role")
+                    .long("role")
+                    .help("Role within the team")
+                    .value_parser(["owner", "member"])
+                    .value_name("ROLE")))
+            .subcommand(ClapCommand::new("remove-member")
+                .about("Remove a user from a team")
+                .arg(team())
+                .arg(user()))
+    }
+
+    fn clone_box(&self) -> Box<dyn Command> {
+        Box::new(self.clone())
+    }
+
+    fn execute(&self, args: &[String]) -> Result<String, CommandError> {
+        let matches = self
+            .parser()
+            .try_get_matches_from(std::iter::once("teams".to_string()).chain(args.iter().cloned()))
+            .map_err(|e| CommandError::ValidationError(e.to_string()))?;
+        let run = Self::run(&matches);
+        match tokio::runtime::Handle::try_current() {
+            // Run from the CLI's async entry point
+            Ok(handle) => tokio::task::block_in_place(|| handle.block_on(run)),
+            Err(_) => tokio::runtime::Runtime::new()
+                .map_err(|e| CommandError::ExecutionError(format!("Failed to create runtime: {}", e)))?
+                .block_on(run),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::commands::{MigrateCommand, UsersCommand};
+    use squirrel_web::auth::models::Role;
+    use squirrel_web::auth::{AuthConfig, AuthService};
+    use squirrel_web::migrations;
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_team_owners_manage_members_of_their_team() {
+        let dir = tempdir().unwrap();
+        let database = format!("sqlite://{}", dir.path().join("web.db").display());
+        let migrate = ["up", "--store", "web", "--database", &database].map(String::from);
+        MigrateCommand::new().execute(&migrate).unwrap();
+        let token = |user_id: Uuid, role: Role| {
+            let runtime = tokio::runtime::Runtime::new().unwrap();
+            runtime.block_on(async {
+                let pool = migrations::connect(&database).await.unwrap();
+                AuthService::new(AuthConfig::default(), pool)
+                    .generate_token(user_id, role)
+                    .await
+                    .unwrap()
+            })
+        };
+        let with = |token: &str, args: &[&str]| {
+            let mut args: Vec<String> = args.iter().map(ToString::to_string).collect();
+            args.extend(["--database".to_string(), database.clone(), "--token".to_string(), token.to_string(), "--json".to_string()]);
+            args
+        };
+        let admin = token(Uuid::new_v4(), Role::Admin);
+        let create = |username: &str| {
+            let output = UsersCommand::new()
+                .execute(&with(&admin, &["create", username, &format!("{username}@example.org"), "--password", "correct horse"]))
+                .unwrap();
+            let account: serde_json::Value = serde_json::from_str(&output).unwrap();
+            Uuid::parse_str(account["id"].as_str().unwrap()).unwrap()
+        };
+        let (ada, grace) = (create("ada"), create("grace"));
+        let run = |token: &str, args: &[&str]| TeamsCommand::new().execute(&with(token, args));
+
+        run(&admin, &["create", "genomics"]).unwrap();
+        run(&admin, &["add-member", "genomics", "ada", "--role", "owner"]).unwrap();
+        let owner = token(ada, Role::User);
+        assert!(matches!(run(&owner, &["delete", "genomics"]), Err(CommandError::AuthorizationError(_))));
+        run(&owner, &["add-member", "genomics", "grace"]).unwrap();
+        let members: Vec<TeamMember> = serde_json::from_str(&run(&owner, &["members", "genomics"]).unwrap()).unwrap();
+        let mut members: Vec<_> = members.iter().map(|member| (member.username.clone(), member.role)).collect();
+        members.sort_by(|a, b| a.0.cmp(&b.0));
+        assert_eq!(
+            members,
+            vec![(Some("ada".to_string()), TeamRole::Owner), (Some("grace".to_string()), TeamRole::Member)]
+        );
+
+        let member = token(grace, Role::User);
+        assert!(matches!(run(&member, &["remove-member", "genomics", "ada"]), Err(CommandError::AuthorizationError(_))));
+        run(&member, &["remove-member", "genomics", "grace"]).unwrap();
+        assert!(matches!(run(&member, &["members", "genomics"]), Err(CommandError::AuthorizationError(_))));
+        run(&admin, &["delete", "genomics"]).unwrap();
+    }
+}
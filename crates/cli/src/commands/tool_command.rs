@@ -0,0 +1,189 @@
+//! Tool command
+//!
+//! Inspects the tools run by the MCP tool manager. Reads the execution
+//! history file the server persists to, so recent failures of a flaky tool
+//! can be looked at without attaching a debugger.
+
+use std::path::PathBuf;
+
+use clap::{Arg, ArgAction, ArgMatches, Command as ClapCommand};
+use squirrel_commands::{Command, CommandError};
+use squirrel_mcp::tool::{ExecutionHistory, ExecutionRecord, DEFAULT_HISTORY_CAPACITY};
+
+/// Tool command implementation
+#[derive(Debug, Clone, Default)]
+pub struct ToolCommand;
+
+impl ToolCommand {
+    /// Create a new tool command
+    pub fn new() -> Self {
+        Self
+    }
+
+    /// Opens the history file given with `--history`, or the default one
+    fn open(matches: &ArgMatches) -> Result<ExecutionHistory, CommandError> {
+        let path = matches
+            .get_one::<String>("history")
+            .map(PathBuf::from)
+            .or_else(ExecutionHistory::default_path)
+            .ok_or_else(|| CommandError::ValidationError("No execution history file; pass --history".to_string()))?;
+        ExecutionHistory::load(&path, DEFAULT_HISTORY_CAPACITY).map_err(|e| CommandError::ResourceError(e.to_string()))
+    }
+}
+
+impl Command for ToolCommand {
+    fn name(&self) -> &str {
+        "tool"
+    }
+
+    fn description(&self) -> &str {
+        "Inspect the execution history of MCP tools"
+    }
+
+    fn parser(&self) -> ClapCommand {
+        ClapCommand::new("tool")
+            .about("Inspect the execution history of MCP tools")
+            .subcommand_required(true)
+            .arg(Arg::new("history")
+                .long("history")
+                .help("Execution history file [default: ~/.squirrel/tool-history.json]")
+                .value_name("FILE")
+                .global(true))
+            .arg(Arg::new("json")
+                .long("json")
+                .help("Output in JSON format")
+                .action(ArgAction::SetTrue)
+                .global(true))
+            .subcommand(ClapCommand::new("history")
+                .about("Show the recent executions of a tool, most recent first")
+                .arg(Arg::new("tool_id")
+                    .help("ID of the tool")
+                    .required(true))
+                .arg(Arg::new("limit")
+                    .long("limit")
+                    .short('n')
+                    .help("Maximum number of executions to show")
+                    .value_name("COUNT")
+                    .value_parser(clap::value_parser!(usize))
+                    .default_value("20"))
+                .arg(Arg::new("errors")
+                    .long("errors")
+                    .help("Only show executions that did not succeed")
+                    .action(ArgAction::SetTrue)))
+            .subcommand(ClapCommand::new("last-error")
+                .about("Show the most recent execution of a tool that did not succeed")
+                .arg(Arg::new("tool_id")
+                    .help("ID of the tool")
+                    .required(true)))
+    }
+
+    fn clone_box(&self) -> Box<dyn Command> {
+        Box::new(self.clone())
+    }
+
+    fn execute(&self, args: &[String]) -> Result<String, CommandError> {
+        let matches = self
+            .parser()
+            .try_get_matches_from(std::iter::once("tool".to_string()).chain(args.iter().cloned()))
+            .map_err(|e| CommandError::ValidationError(e.to_string()))?;
+        let history = Self::open(&matches)?;
+        let json = matches.get_flag("json");
+
+        match matches.subcommand() {
+            Some(("history", sub)) => {
+                let tool_id = required(sub, "tool_id");
+                let limit = sub.get_one::<usize>("limit").copied().unwrap_or(usize::MAX);
+                let records: Vec<ExecutionRecord> = history
+                    .get(tool_id)
+                    .into_iter()
+                    .filter(|record| !sub.get_flag("errors") || record.is_error())
+                    .take(limit)
+                    .collect();
+                if json {
+                    return to_json(&records);
+                }
+                if records.is_empty() {
+                    return Ok(format!("No recorded executions of {tool_id}"));
+                }
+                Ok(records.iter().map(format_record).collect::<Vec<_>>().join("\n"))
+            }
+            Some(("last-error", sub)) => {
+                let tool_id = required(sub, "tool_id");
+                let record = history.last_error(tool_id);
+                if json {
+                    return to_json(&record);
+                }
+                Ok(record.map_or_else(|| format!("No failed executions of {tool_id}"), |record| format_record(&record)))
+            }
+            _ => Err(CommandError::ValidationError("Unknown tool subcommand".to_string())),
+        }
+    }
+}
+
+/// The value of the required argument `name`
+fn required<'a>(matches: &'a ArgMatches, name: &str) -> &'a str {
+    matches.get_one::<String>(name).map(String::as_str).unwrap_or_default()
+}
+
+/// One line describing an execution
+fn format_record(record: &ExecutionRecord) -> String {
+    format!(
+        "{} {:?} {} v{} {}ms {}{}",
+        record.started_at.to_rfc3339(),
+        record.status,
+        record.capability,
+        record.capability_version,
+        record.duration_ms,
+        record.request_id,
+        record.error_message.as_ref().map(|message| format!(": {message}")).unwrap_or_default()
+    )
+}
+
+/// Pretty-printed JSON of `value`
+fn to_json<T: serde::Serialize>(value: &T) -> Result<String, CommandError> {
+    serde_json::to_string_pretty(value).map_err(|e| CommandError::ExecutionError(e.to_string()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use squirrel_mcp::tool::ExecutionStatus;
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_history_and_last_error_from_history_file() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("tool-history.json");
+        let mut history = ExecutionHistory::default();
+        for (request_id, status) in [("r1", ExecutionStatus::Timeout), ("r2", ExecutionStatus::Success)] {
+            history.record("flaky", ExecutionRecord {
+                request_id: request_id.to_string(),
+                capability: "run".to_string(),
+                capability_version: 1,
+                status,
+                duration_ms: 12,
+                error_message: (status != ExecutionStatus::Success).then(|| "timed out".to_string()),
+                started_at: chrono::Utc::now(),
+            });
+        }
+        history.save(&path).unwrap();
+
+        let command = ToolCommand::new();
+        let run = |args: &[&str]| {
+            let mut args: Vec<String> = args.iter().map(ToString::to_string).collect();
+            args.extend(["--history".to_string(), path.display().to_string()]);
+            command.execute(&args)
+        };
+        let output = run(&["history", "flaky"]).unwrap();
+        let lines: Vec<&str> = output.lines().collect();
+        assert_eq!(lines.len(), 2);
+        assert!(lines[0].contains("r2"), "{output}");
+        assert!(lines[1].ends_with("r1: timed out"), "{output}");
+
+        let records: Vec<ExecutionRecord> = serde_json::from_str(&run(&["history", "flaky", "--errors", "--json"]).unwrap()).unwrap();
+        assert_eq!(records.len(), 1);
+        assert!(run(&["last-error", "flaky"]).unwrap().contains("Timeout"));
+        assert_eq!(run(&["last-error", "other"]).unwrap(), "No failed executions of other");
+        assert!(run(&["history"]).is_err());
+    }
+}
@@ -0,0 +1,291 @@
+//! Top command
+//!
+//! Opens the live monitoring dashboard in the terminal, or prints a single
+//! frame of it with `--once` for scripts and terminals without keyboard
+//! input. In the accessible output mode the dashboard is printed as plain
+//! lines instead, and the live view prints a summary at intervals.
+
+use std::path::PathBuf;
+use std::sync::mpsc;
+use std::time::{Duration, Instant};
+
+use clap::{Arg, ArgAction, ArgMatches, Command as ClapCommand};
+use console::Term;
+use squirrel_commands::{Command, CommandError};
+use squirrel_mcp::persistence::PersistenceConfig;
+use squirrel_mcp::tool::ExecutionHistory;
+use squirrel_monitoring::alerts::LifecycleConfig;
+
+use crate::output::{self, Summaries};
+use crate::top::sources::{SourceConfig, Sources};
+use crate::top::{Control, Dashboard, TopEvent};
+
+/// Top command implementation
+#[derive(Debug, Clone, Default)]
+pub struct TopCommand;
+
+impl TopCommand {
+    /// Create a new top command
+    pub fn new() -> Self {
+        Self
+    }
+
+    /// Prints one frame, sampling twice so CPU usage is known
+    fn once(matches: &ArgMatches, config: SourceConfig) -> Result<String, CommandError> {
+        let mut sources = Sources::new(config);
+        sources.poll();
+        std::thread::sleep(Duration::from_millis(500));
+        let mut dashboard = Dashboard::new(interval(matches));
+        for event in sources.poll() {
+            dashboard.apply(event);
+        }
+
+        if matches.get_flag("json") {
+            return serde_json::to_string_pretty(dashboard.snapshot())
+                .map_err(|e| CommandError::ExecutionError(e.to_string()));
+        }
+        if output::is_accessible() {
+            return Ok(dashboard.lines().join("\n"));
+        }
+        let (rows, columns) = Term::stdout().size_checked().unwrap_or((40, 120));
+        let width = matches.get_one::<usize>("width").copied().unwrap_or(usize::from(columns));
+        let height = matches.get_one::<usize>("height").copied().unwrap_or(usize::from(rows));
+        let lines = dashboard.render(width, height);
+        Ok(lines
+            .iter()
+            .map(|line| console::strip_ansi_codes(line).trim_end().to_string())
+            .collect::<Vec<_>>()
+            .join("\n")
+            .trim_end()
+            .to_string())
+    }
+
+    /// Prints a timestamped summary whenever it changes, at most once per
+    /// summary interval, until interrupted
+    fn summaries(matches: &ArgMatches, config: SourceConfig) -> Result<String, CommandError> {
+        let refresh = interval(matches);
+        let (sender, events) = mpsc::channel();
+        Sources::new(config).spawn(refresh, sender);
+        let mut dashboard = Dashboard::new(refresh);
+        let mut summaries = Summaries::new(refresh);
+        // Let the sources report once before the first summary
+        std::thread::sleep(refresh.min(Duration::from_secs(1)));
+        loop {
+            while let Ok(event) = events.try_recv() {
+                dashboard.apply(event);
+            }
+            if let Some(summary) = summaries.offer(Instant::now(), dashboard.summary()) {
+                println!("{} {}", chrono::Local::now().format("%H:%M:%S"), summary);
+            }
+            match events.recv_timeout(summaries.interval()) {
+                Ok(event) => {
+                    dashboard.apply(event);
+                }
+                Err(mpsc::RecvTimeoutError::Timeout) => {}
+                Err(mpsc::RecvTimeoutError::Disconnected) => return Ok(String::new()),
+            }
+        }
+    }
+
+    /// Runs the dashboard until the user quits
+    fn interactive(matches: &ArgMatches, config: SourceConfig) -> Result<String, CommandError> {
+        let term = Term::stdout();
+        if !term.is_term() {
+            return Err(CommandError::ValidationError(
+                "squirrel top needs a terminal; use --once to print a single frame".to_string(),
+            ));
+        }
+        let refresh = interval(matches);
+        let (sender, events) = mpsc::channel();
+        Sources::new(config).spawn(refresh, sender.clone());
+        let keys = term.clone();
+        std::thread::spawn(move || {
+            while let Ok(key) = keys.read_key() {
+                if sender.send(TopEvent::Key(key)).is_err() {
+                    return;
+                }
+            }
+        });
+
+        // Log lines would tear the screen
+        let log_level = log::max_level();
+        log::set_max_level(log::LevelFilter::Off);
+        let io_error = |e: std::io::Error| CommandError::ExecutionError(format!("Terminal error: {e}"));
+        let result = (|| {
+            term.hide_cursor().map_err(io_error)?;
+            let mut dashboard = Dashboard::new(refresh);
+            loop {
+                let (rows, columns) = term.size();
+                let frame = dashboard.render(usize::from(columns), usize::from(rows)).join("\r\n");
+                term.move_cursor_to(0, 0).map_err(io_error)?;
+                term.write_str(&frame).map_err(io_error)?;
+                term.flush().map_err(io_error)?;
+
+                // Redraw at least every second so the clock and ages move
+                let event = match events.recv_timeout(Duration::from_secs(1)) {
+                    Ok(event) => event,
+                    Err(mpsc::RecvTimeoutError::Timeout) => continue,
+                    Err(mpsc::RecvTimeoutError::Disconnected) => return Ok(()),
+                };
+                if dashboard.apply(event) == Control::Quit {
+                    return Ok(());
+                }
+                while let Ok(event) = events.try_recv() {
+                    if dashboard.apply(event) == Control::Quit {
+                        return Ok(());
+                    }
+                }
+            }
+        })();
+        let _ = term.clear_screen();
+        let _ = term.show_cursor();
+        log::set_max_level(log_level);
+        result.map(|()| String::new())
+    }
+}
+
+impl Command for TopCommand {
+    fn name(&self) -> &str {
+        "top"
+    }
+
+    fn description(&self) -> &str {
+        "Live dashboard of commands, jobs, tools, resources and alerts"
+    }
+
+    fn parser(&self) -> ClapCommand {
+        ClapCommand::new("top")
+            .about("Live dashboard of commands, jobs, tools, resources and alerts")
+            .arg(Arg::new("interval")
+                .long("interval")
+                .short('n')
+                .help("Seconds between refreshes")
+                .value_name("SECONDS")
+                .default_value("2")
+                .value_parser(clap::value_parser!(f64)))
+            .arg(Arg::new("data-dir")
+                .long("data-dir")
+                .help("MCP persistence data directory jobs are read from [default: data/mcp]")
+                .value_name("DIR"))
+            .arg(Arg::new("tool-history")
+                .long("tool-history")
+                .help("Tool execution history [default: ~/.squirrel/tool-history.json]")
+                .value_name("FILE"))
+            .arg(Arg::new("alerts")
+                .long("alerts")
+                .help("Alert state file [default: ~/.squirrel/alerts.json]")
+                .value_name("FILE"))
+            .arg(Arg::new("once")
+                .long("once")
+                .help("Print a single frame and exit")
+                .action(ArgAction::SetTrue))
+            .arg(Arg::new("json")
+                .long("json")
+                .help("With --once, print the data as JSON")
+                .requires("once")
+                .action(ArgAction::SetTrue))
+            .arg(Arg::new("width")
+                .long("width")
+                .help("With --once, frame width [default: terminal width]")
+                .value_name("COLUMNS")
+                .requires("once")
+                .value_parser(clap::value_parser!(usize)))
+            .arg(Arg::new("height")
+                .long("height")
+                .help("With --once, frame height [default: terminal height]")
+                .value_name("LINES")
+                .requires("once")
+                .value_parser(clap::value_parser!(usize)))
+    }
+
+    fn clone_box(&self) -> Box<dyn Command> {
+        Box::new(self.clone())
+    }
+
+    fn execute(&self, args: &[String]) -> Result<String, CommandError> {
+        let matches = self
+            .parser()
+            .try_get_matches_from(std::iter::once("top".to_string()).chain(args.iter().cloned()))
+            .map_err(|e| CommandError::ValidationError(e.to_string()))?;
+        let config = SourceConfig {
+            data_dir: matches
+                .get_one::<String>("data-dir")
+                .map_or_else(|| PersistenceConfig::default().data_dir, PathBuf::from),
+            tool_history: matches
+                .get_one::<String>("tool-history")
+                .map(PathBuf::from)
+                .or_else(ExecutionHistory::default_path),
+            alerts: matches
+                .get_one::<String>("alerts")
+                .map(PathBuf::from)
+                .or_else(LifecycleConfig::default_state_path),
+        };
+        if matches.get_flag("once") {
+            Self::once(&matches, config)
+        } else if output::is_accessible() {
+            Self::summaries(&matches, config)
+        } else {
+            Self::interactive(&matches, config)
+        }
+    }
+}
+
+/// Refresh interval, at least a tenth of a second
+fn interval(matches: &ArgMatches) -> Duration {
+    let secs = matches.get_one::<f64>("interval").copied().unwrap_or(2.0);
+    Duration::from_secs_f64(secs.max(0.1))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use squirrel_mcp::tool::{ExecutionRecord, ExecutionStatus};
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_once_prints_every_panel() {
+        let dir = tempdir().unwrap();
+        let history_path = dir.path().join("tool-history.json");
+        let mut history = ExecutionHistory::new(10);
+        history.record(
+            "aligner",
+            ExecutionRecord {
+                request_id: "req-1".to_string(),
+                capability: "align".to_string(),
+                capability_version: 2,
+                status: ExecutionStatus::Failure,
+                duration_ms: 1200,
+                error_message: Some("index missing".to_string()),
+                started_at: chrono::Utc::now(),
+            },
+        );
+        history.save(&history_path).unwrap();
+
+        let command = TopCommand::new();
+        let args = |extra: &[&str]| {
+            let mut args = vec![
+                "--once".to_string(),
+                "--data-dir".to_string(),
+                dir.path().join("mcp").display().to_string(),
+                "--tool-history".to_string(),
+                history_path.display().to_string(),
+                "--alerts".to_string(),
+                dir.path().join("alerts.json").display().to_string(),
+            ];
+            args.extend(extra.iter().map(ToString::to_string));
+            args
+        };
+        let frame = command.execute(&args(&["--width", "100", "--height", "30"])).unwrap();
+        for title in ["Commands", "Jobs (0)", "Tools (1)", "Alerts (0)"] {
+            assert!(frame.contains(title), "{frame}");
+        }
+        assert!(frame.contains("aligner") && frame.contains("failing"), "{frame}");
+        assert!(frame.lines().all(|line| line.chars().count() <= 100));
+
+        let json: serde_json::Value = serde_json::from_str(&command.execute(&args(&["--json"])).unwrap()).unwrap();
+        assert_eq!(json["tools"][0]["tool_id"], "aligner");
+        assert!(json["resources"]["memory_total"].as_u64().unwrap() > 0);
+
+        assert!(command.execute(&["--json".to_string()]).is_err());
+    }
+}
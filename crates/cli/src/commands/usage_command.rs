@@ -0,0 +1,219 @@
+//! Usage command
+//!
+//! Shows the resources used per user and workspace from the usage ledger the
+//! server records to, optionally limited to a date range.
+
+use std::path::PathBuf;
+
+use chrono::{DateTime, Duration, NaiveDate, Utc};
+use clap::{Arg, ArgAction, ArgMatches, Command as ClapCommand};
+use squirrel_commands::{Command, CommandError};
+use squirrel_monitoring::accounting::{AccountingConfig, ResourceUsage, UsageLedger, UsageQuery, UsageRecord};
+
+/// Usage command implementation
+#[derive(Debug, Clone, Default)]
+pub struct UsageCommand;
+
+impl UsageCommand {
+    /// Create a new usage command
+    pub fn new() -> Self {
+        Self
+    }
+
+    /// Opens the ledger given with `--ledger`, or the default one
+    pub(crate) fn open(matches: &ArgMatches) -> Result<UsageLedger, CommandError> {
+        let path = matches
+            .get_one::<String>("ledger")
+            .map(PathBuf::from)
+            .or_else(AccountingConfig::default_ledger_path)
+            .ok_or_else(|| CommandError::ValidationError("No usage ledger; pass --ledger".to_string()))?;
+        if !path.is_file() {
+            return Err(CommandError::ResourceError(format!("No usage ledger at {}", path.display())));
+        }
+        let config = AccountingConfig {
+            ledger_path: Some(path),
+            ..AccountingConfig::default()
+        };
+        UsageLedger::open(config).map_err(|e| CommandError::ResourceError(e.to_string()))
+    }
+}
+
+impl Command for UsageCommand {
+    fn name(&self) -> &str {
+        "usage"
+    }
+
+    fn description(&self) -> &str {
+        "Show resource usage per user and workspace"
+    }
+
+    fn parser(&self) -> ClapCommand {
+        ClapCommand::new("usage")
+            .about("Show resource usage per user and workspace")
+            .arg(Arg::new("ledger")
+                .long("ledger")
+                .help("Usage ledger file [default: ~/.squirrel/usage.json]")
+                .value_name("FILE"))
+            .arg(Arg::new("from")
+                .long("from")
+                .help("Start of the range, as YYYY-MM-DD or RFC 3339")
+                .value_name("DATE"))
+            .arg(Arg::new("to")
+                .long("to")
+                .help("End of the range, as YYYY-MM-DD (inclusive) or RFC 3339")
+                .value_name("DATE"))
+            .arg(Arg::new("user")
+                .long("user")
+                .help("Only show this user")
+                .value_name("USER"))
+            .arg(Arg::new("workspace")
+                .long("workspace")
+                .help("Only show this workspace")
+                .value_name("NAME"))
+            .arg(Arg::new("records")
+                .long("records")
+                .help("List each accounting period instead of totals")
+                .action(ArgAction::SetTrue))
+            .arg(Arg::new("json")
+                .long("json")
+                .help("Output in JSON format")
+                .action(ArgAction::SetTrue))
+    }
+
+    fn clone_box(&self) -> Box<dyn Command> {
+        Box::new(self.clone())
+    }
+
+    fn execute(&self, args: &[String]) -> Result<String, CommandError> {
+        let matches = self
+            .parser()
+            .try_get_matches_from(std::iter::once("usage".to_string()).chain(args.iter().cloned()))
+            .map_err(|e| CommandError::ValidationError(e.to_string()))?;
+        let query = UsageQuery {
+            from: matches.get_one::<String>("from").map(|text| parse_date(text, false)).transpose()?,
+            to: matches.get_one::<String>("to").map(|text| parse_date(text, true)).transpose()?,
+            user: matches.get_one::<String>("user").cloned(),
+            workspace: matches.get_one::<String>("workspace").cloned(),
+        };
+        let ledger = Self::open(&matches)?;
+        let json = matches.get_flag("json");
+
+        if matches.get_flag("records") {
+            let records = ledger.records(&query);
+            if json {
+                return to_json(&records);
+            }
+            return Ok(format_records(&records));
+        }
+        let totals: Vec<UsageTotal> = ledger
+            .totals(&query)
+            .into_iter()
+            .map(|((user, workspace), usage)| UsageTotal { user, workspace, usage })
+            .collect();
+        if json {
+            return to_json(&totals);
+        }
+        Ok(format_totals(&totals))
+    }
+}
+
+/// Total usage of a user in a workspace
+#[derive(Debug, serde::Serialize)]
+struct UsageTotal {
+    user: String,
+    workspace: String,
+    #[serde(flatten)]
+    usage: ResourceUsage,
+}
+
+/// Parses a date or timestamp; a plain `--to` date includes the whole day
+pub(crate) fn parse_date(text: &str, end_of_day: bool) -> Result<DateTime<Utc>, CommandError> {
+    if let Ok(timestamp) = DateTime::parse_from_rfc3339(text) {
+        return Ok(timestamp.with_timezone(&Utc));
+    }
+    let date = NaiveDate::parse_from_str(text, "%Y-%m-%d")
+        .map_err(|_| CommandError::ValidationError(format!("Invalid date '{text}'; use YYYY-MM-DD or RFC 3339")))?;
+    let start = date.and_hms_opt(0, 0, 0).unwrap_or_default().and_utc();
+    Ok(if end_of_day { start + Duration::days(1) } else { start })
+}
+
+/// One line of usage figures
+fn format_usage(usage: &ResourceUsage) -> String {
+    format!(
+        "{} executions, {:.1} CPU s, {} MB·s, {} bytes stored",
+        usage.executions,
+        usage.cpu_ms as f64 / 1000.0,
+        usage.memory_mb_seconds,
+        usage.storage_bytes
+    )
+}
+
+/// One line per user and workspace
+fn format_totals(totals: &[UsageTotal]) -> String {
+    if totals.is_empty() {
+        return "No usage recorded".to_string();
+    }
+    totals
+        .iter()
+        .map(|total| format!("{} in {}: {}", total.user, total.workspace, format_usage(&total.usage)))
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+/// One line per accounting period, user and workspace
+fn format_records(records: &[UsageRecord]) -> String {
+    if records.is_empty() {
+        return "No usage recorded".to_string();
+    }
+    records
+        .iter()
+        .map(|record| {
+            format!(
+                "{}  {} in {}: {}",
+                record.period_start.format("%Y-%m-%d %H:%M"),
+                record.user,
+                record.workspace,
+                format_usage(&record.usage)
+            )
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+/// Pretty-printed JSON of `value`
+fn to_json<T: serde::Serialize>(value: &T) -> Result<String, CommandError> {
+    serde_json::to_string_pretty(value).map_err(|e| CommandError::ExecutionError(e.to_string()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_usage_for_a_date_range() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("usage.json");
+        let ledger = UsageLedger::open(AccountingConfig {
+            ledger_path: Some(path.clone()),
+            ..AccountingConfig::default()
+        })
+        .unwrap();
+        let day = |d: u32| NaiveDate::from_ymd_opt(2026, 3, d).unwrap().and_hms_opt(12, 0, 0).unwrap().and_utc();
+        ledger.record_at("alice", "lab", day(1), ResourceUsage::execution()).unwrap();
+        ledger.record_at("alice", "lab", day(2), ResourceUsage::compute(1500, 100)).unwrap();
+        ledger.record_at("bob", "lab", day(3), ResourceUsage::storage(10)).unwrap();
+
+        let command = UsageCommand::new();
+        let run = |args: &[&str]| {
+            let mut args: Vec<String> = args.iter().map(ToString::to_string).collect();
+            args.extend(["--ledger".to_string(), path.display().to_string()]);
+            command.execute(&args)
+        };
+        let output = run(&["--from", "2026-03-01", "--to", "2026-03-02"]).unwrap();
+        assert_eq!(output, "alice in lab: 1 executions, 1.5 CPU s, 150 MB·s, 0 bytes stored");
+        assert!(run(&["--user", "bob", "--records"]).unwrap().starts_with("2026-03-03 12:00  bob in lab"));
+        assert_eq!(run(&["--workspace", "home"]).unwrap(), "No usage recorded");
+        assert!(run(&["--from", "March"]).is_err());
+    }
+}
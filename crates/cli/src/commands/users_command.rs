@@ -0,0 +1,400 @@
+//! Users command
+//!
+//! Creates, invites, deactivates and reactivates accounts, assigns their
+//! roles and issues password reset tokens, working on the web database like
+//! the server's `/api/users` endpoints. Every subcommand needs the access
+//! token of a user with the admin role and is written to the admin audit
+//! log. The server sends no email: invitation and reset tokens are printed
+//! to be handed to the user.
+
+use std::future::Future;
+use std::sync::Arc;
+
+use clap::{Arg, ArgAction, ArgMatches, Command as ClapCommand};
+use squirrel_commands::{Command, CommandError};
+use squirrel_web::admin::{AdminControls, AuditEntry, AuditOutcome};
+use squirrel_web::api::error::AppError;
+use squirrel_web::auth::models::Role;
+use squirrel_web::config::UsersConfig;
+use squirrel_web::users::{SqlUserStore, UserAccount, UserDirectory};
+
+use super::admin_command::{admin_error, to_json, uuid_arg, Operator};
+
+/// Environment variable holding the password of a new account
+const PASSWORD_ENV: &str = "SQUIRREL_USER_PASSWORD";
+
+/// Runs an action for the operator, refusing it unless `allowed`, and
+/// writes it to the audit log whether it was refused, succeeded or failed
+pub(crate) async fn audited<T, Fut>(
+    controls: &AdminControls,
+    operator: &Operator,
+    allowed: bool,
+    action: &str,
+    target: Option<&str>,
+    run: Fut,
+) -> Result<T, CommandError>
+where
+    Fut: Future<Output = Result<T, AppError>>,
+{
+    if !allowed {
+        controls
+            .audit(AuditEntry::new(&operator.actor, action, target, AuditOutcome::Denied, None))
+            .await;
+        return Err(CommandError::AuthorizationError(format!("Not allowed to {action}")));
+    }
+    controls
+        .audited(&operator.actor, true, action, target, run)
+        .await
+        .map_err(admin_error)
+}
+
+/// The accounts, teams and invitations of the web database
+pub(crate) fn directory(operator: &Operator) -> UserDirectory {
+    UserDirectory::new(
+        Arc::new(SqlUserStore::new(operator.pool.clone())),
+        operator.auth.clone(),
+        UsersConfig::default(),
+    )
+}
+
+/// Parses a role argument
+fn role_arg(matches: &ArgMatches, name: &str) -> Result<Option<Role>, CommandError> {
+    matches
+        .get_one::<String>(name)
+        .map(|role| role.parse::<Role>().map_err(CommandError::ValidationError))
+        .transpose()
+}
+
+/// One account as a line
+fn format_user(user: &UserAccount) -> String {
+    let mut line = format!("{}  {:<16} {:<5} {}", user.id, user.username, user.role.as_str(), user.email);
+    if let Some(deactivated_at) = user.deactivated_at {
+        line.push_str(&format!("  deactivated {}", deactivated_at.to_rfc3339()));
+    }
+    line
+}
+
+/// Users command implementation
+#[derive(Debug, Clone, Default)]
+pub struct UsersCommand;
+
+impl UsersCommand {
+    /// Create a new users command
+    pub fn new() -> Self {
+        Self
+    }
+
+    /// Runs the subcommand against the web database
+    async fn run(matches: &ArgMatches) -> Result<String, CommandError> {
+        let operator = Operator::connect(matches).await?;
+        let controls = operator.controls();
+        let users = directory(&operator);
+        let admin = operator.is_admin;
+        let json = matches.get_flag("json");
+        let user_arg = |sub: &ArgMatches| sub.get_one::<String>("user").cloned().unwrap_or_default();
+
+        let output = match matches.subcommand() {
+            Some(("list", sub)) => {
+                let all = sub.get_flag("all");
+                let accounts = audited(&controls, &operator, admin, "users.list", None, users.users(all)).await?;
+                if json {
+                    return to_json(&accounts);
+                }
+                if accounts.is_empty() {
+                    return Ok("No users".to_string());
+                }
+                accounts.iter().map(format_user).collect::<Vec<_>>().join("\n")
+            }
+            Some(("show", sub)) => {
+                let name = user_arg(sub);
+                let account = audited(&controls, &operator, admin, "users.get", Some(&name), users.find_user(&name)).await?;
+                if json {
+                    return to_json(&account);
+                }
+                format_user(&account)
+            }
+            Some(("create", sub)) => {
+                let username = sub.get_one::<String>("username").cloned().unwrap_or_default();
+                let email = sub.get_one::<String>("email").cloned().unwrap_or_default();
+                let role = role_arg(sub, "role")?.unwrap_or(Role::User);
+                let password = sub
+                    .get_one::<String>("password")
+                    .cloned()
+                    .or_else(|| std::env::var(PASSWORD_ENV).ok())
+                    .ok_or_else(|| {
+                        CommandError::ValidationError(format!("No password; pass --password or set {PASSWORD_ENV}"))
+                    })?;
+                let account = audited(
+                    &controls,
+                    &operator,
+                    admin,
+                    "users.create",
+                    Some(&username),
+                    users.create_user(&username, &email, &password, role),
+                )
+                .await?;
+                if json {
+                    return to_json(&account);
+                }
+                format!("Created user {} ({})", account.username, account.id)
+            }
+            Some(("role", sub)) => {
+                let name = user_arg(sub);
+                let role = role_arg(sub, "role")?.unwrap_or(Role::User);
+                let account = audited(&controls, &operator, admin, "users.role", Some(&name), async {
+                    let account = users.find_user(&name).await?;
+                    users.set_role(account.id, role).await
+                })
+                .await?;
+                if json {
+                    return to_json(&account);
+                }
+                format!("{} is now {}", account.username, account.role.as_str())
+            }
+            Some(("deactivate", sub)) => {
+                let name = user_arg(sub);
+                let account = audited(&controls, &operator, admin, "users.deactivate", Some(&name), async {
+                    let account = users.find_user(&name).await?;
+                    if account.id.to_string() == operator.actor {
+                        return Err(AppError::InvalidRequest("Admins can not deactivate themselves".to_string()));
+                    }
+                    users.deactivate(account.id).await
+                })
+                .await?;
+                if json {
+                    return to_json(&account);
+                }
+                format!("Deactivated {} and ended their sessions", account.username)
+            }
+            Some(("reactivate", sub)) => {
+                let name = user_arg(sub);
+                let account = audited(&controls, &operator, admin, "users.reactivate", Some(&name), async {
+                    let account = users.find_user(&name).await?;
+                    users.reactivate(account.id).await
+                })
+                .await?;
+                if json {
+                    return to_json(&account);
+                }
+                format!("Reactivated {}", account.username)
+            }
+            Some(("reset-password", sub)) => {
+                let name = user_arg(sub);
+                let reset = audited(&controls, &operator, admin, "users.password_reset", Some(&name), async {
+                    let account = users.find_user(&name).await?;
+                    users.issue_password_reset(account.id).await
+                })
+                .await?;
+                if json {
+                    return to_json(&reset);
+                }
+                format!(
+                    "Password reset token for {name}, valid until {}; the user sets a new password with \
+                     POST /api/auth/password-reset:\n{}",
+                    reset.expires_at.to_rfc3339(),
+                    reset.token
+                )
+            }
+            Some(("invite", sub)) => {
+                let email = sub.get_one::<String>("email").cloned().unwrap_or_default();
+                let role = role_arg(sub, "role")?.unwrap_or(Role::User);
+                let team = sub.get_one::<String>("team").cloned();
+                let issued = audited(&controls, &operator, admin, "invitations.create", Some(&email), async {
+                    let team_id = match &team {
+                        Some(team) => Some(users.find_team(team).await?.id),
+                        None => None,
+                    };
+                    users.invite(&operator.actor, &email, role, team_id).await
+                })
+                .await?;
+                if json {
+                    return to_json(&issued);
+                }
+                format!(
+                    "Invited {email}, valid until {}; the invitee creates their account with \
+                     POST /api/auth/invitations/accept:\n{}",
+                    issued.invitation.expires_at.to_rfc3339(),
+                    issued.token
+                )
+            }
+            Some(("invitations", _)) => {
+                let invitations =
+                    audited(&controls, &operator, admin, "invitations.list", None, users.invitations()).await?;
+                if json {
+                    return to_json(&invitations);
+                }
+                if invitations.is_empty() {
+                    return Ok("No pending invitations".to_string());
+                }
+                invitations
+                    .iter()
+                    .map(|invitation| {
+                        format!(
+                            "{}  {} as {}  expires {}",
+                            invitation.id,
+                            invitation.email,
+                            invitation.role.as_str(),
+                            invitation.expires_at.to_rfc3339()
+                        )
+                    })
+                    .collect::<Vec<_>>()
+                    .join("\n")
+            }
+            Some(("revoke-invitation", sub)) => {
+                let id = uuid_arg(sub, "invitation")?.unwrap_or_default();
+                let target = id.to_string();
+                audited(&controls, &operator, admin, "invitations.revoke", Some(&target), users.revoke_invitation(id))
+                    .await?;
+                format!("Revoked invitation {id}")
+            }
+            _ => return Err(CommandError::ValidationError("Unknown users subcommand".to_string())),
+        };
+        Ok(output)
+    }
+}
+
+impl Command for UsersCommand {
+    fn name(&self) -> &str {
+        "users"
+    }
+
+    fn description(&self) -> &str {
+        "Create, invite and deactivate user accounts of the web server"
+    }
+
+    fn tags(&self) -> Vec<String> {
+        vec!["users".to_string(), "invitations".to_string(), "roles".to_string()]
+    }
+
+    fn parser(&self) -> ClapCommand {
+        let user = || Arg::new("user").help("ID, username or email address of the user").required(true);
+        let role = || Arg::new("role")
+            .long("role")
+            .help("Role: user or admin [default: user]")
+            .value_name("ROLE");
+        Operator::args(ClapCommand::new("users")
+            .about("Create, invite and deactivate user accounts of the web server")
+            .subcommand_required(true))
+            .subcommand(ClapCommand::new("list")
+                .about("List the active accounts")
+                .arg(Arg::new("all")
+                    .long("all")
+                    .help("Also list deactivated accounts")
+                    .action(ArgAction::SetTrue)))
+            .subcommand(ClapCommand::new("show")
+                .about("Show an account")
+                .arg(user()))
+            .subcommand(ClapCommand::new("create")
+                .about("Create an account")
+                .arg(Arg::new("username").help("Username").required(true))
+                .arg(Arg::new("email").help("Email address").required(true))
+                .arg(role())
+                .arg(Arg::new("password")
+                    .long("password")
+                    .help("Initial password [default: $SQUIRREL_USER_PASSWORD]")
+                    .value_name("PASSWORD")))
+            .subcommand(ClapCommand::new("role")
+                .about("Change the role of an account; issued access tokens keep the old role until they expire")
+                .arg(user())
+                .arg(Arg::new("role")
+                    .help("New role: user or admin")
+                    .required(true)))
+            .subcommand(ClapCommand::new("deactivate")
+                .about("Deactivate an account and end its sessions")
+                .arg(user()))
+            .subcommand(ClapCommand::new("reactivate")
+                .about("Let a deactivated account sign in again")
+                .arg(user()))
+            .subcommand(ClapCommand::new("reset-password")
+                .about("Print a single-use password reset token for an account")
+                .arg(user()))
+            .subcommand(ClapCommand::new("invite")
+                .about("Invite an email address to create an account and print the invitation token")
+                .arg(Arg::new("email").help("Email address of the invitee").required(true))
+                .arg(role())
+                .arg(Arg::new("team")
+                    .long("team")
+                    .help("ID or name of a team the new account joins")
+                    .value_name("TEAM")))
+            .subcommand(ClapCommand::new("invitations")
+                .about("List the invitations neither accepted nor expired"))
+            .subcommand(ClapCommand::new("revoke-invitation")
+                .about("Revoke an invitation")
+                .arg(Arg::new("invitation")
+                    .help("ID of the invitation")
+                    .required(true)))
+    }
+
+    fn clone_box(&self) -> Box<dyn Command> {
+        Box::new(self.clone())
+    }
+
+    fn execute(&self, args: &[String]) -> Result<String, CommandError> {
+        let matches = self
+            .parser()
+            .try_get_matches_from(std::iter::once("users".to_string()).chain(args.iter().cloned()))
+            .map_err(|e| CommandError::ValidationError(e.to_string()))?;
+        let run = Self::run(&matches);
+        match tokio::runtime::Handle::try_current() {
+            // Run from the CLI's async entry point
+            Ok(handle) => tokio::task::block_in_place(|| handle.block_on(run)),
+            Err(_) => tokio::runtime::Runtime::new()
+                .map_err(|e| CommandError::ExecutionError(format!("Failed to create runtime: {}", e)))?
+                .block_on(run),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::commands::MigrateCommand;
+    use squirrel_web::auth::{AuthConfig, AuthService};
+    use squirrel_web::migrations;
+    use tempfile::tempdir;
+    use uuid::Uuid;
+
+    #[test]
+    fn test_users_commands_manage_accounts_for_admins() {
+        let dir = tempdir().unwrap();
+        let database = format!("sqlite://{}", dir.path().join("web.db").display());
+        let migrate = ["up", "--store", "web", "--database", &database].map(String::from);
+        MigrateCommand::new().execute(&migrate).unwrap();
+        let token = |role: Role| {
+            let runtime = tokio::runtime::Runtime::new().unwrap();
+            runtime.block_on(async {
+                let pool = migrations::connect(&database).await.unwrap();
+                AuthService::new(AuthConfig::default(), pool)
+                    .generate_token(Uuid::new_v4(), role)
+                    .await
+                    .unwrap()
+            })
+        };
+        let (admin, user) = (token(Role::Admin), token(Role::User));
+        let run = |token: &str, args: &[&str]| {
+            let mut args: Vec<String> = args.iter().map(ToString::to_string).collect();
+            args.extend(["--database".to_string(), database.clone(), "--token".to_string(), token.to_string()]);
+            UsersCommand::new().execute(&args)
+        };
+
+        assert!(matches!(run(&user, &["list"]), Err(CommandError::AuthorizationError(_))));
+        assert!(run(&admin, &["create", "ada", "ada@example.org"]).is_err());
+        assert!(run(&admin, &["create", "ada", "ada@example.org", "--password", "correct horse"])
+            .unwrap()
+            .starts_with("Created user ada "));
+        assert!(run(&admin, &["role", "ada", "admin"]).unwrap().ends_with("is now Admin"));
+        assert!(run(&admin, &["create", "ada", "other@example.org", "--password", "correct horse"]).is_err());
+
+        let reset = run(&admin, &["reset-password", "ada@example.org"]).unwrap();
+        assert!(reset.starts_with("Password reset token for ada@example.org"), "{reset}");
+        let invite = run(&admin, &["invite", "grace@example.org"]).unwrap();
+        assert!(invite.starts_with("Invited grace@example.org"), "{invite}");
+        assert!(run(&admin, &["invitations"]).unwrap().contains("grace@example.org as User"));
+
+        assert!(run(&admin, &["deactivate", "ada"]).unwrap().starts_with("Deactivated ada"));
+        assert_eq!(run(&admin, &["list"]).unwrap(), "No users");
+        assert!(run(&admin, &["list", "--all"]).unwrap().contains("deactivated"));
+        assert!(run(&admin, &["reactivate", "ada"]).is_ok());
+        assert!(run(&admin, &["show", "nobody"]).is_err());
+    }
+}
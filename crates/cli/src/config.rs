@@ -3,13 +3,26 @@
 //! This module provides functionality for loading, saving, and managing
 //! CLI configuration settings.
 
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap};
 use std::fs::{self, File};
 use std::io::{self, Read, Write};
 use std::path::{Path, PathBuf};
 use thiserror::Error;
 use serde::{Serialize, Deserialize};
 use log::{debug, warn, error};
+use squirrel_commands::environments::{EnvironmentDefinition, EnvironmentRegistry};
+use squirrel_commands::policy::CommandPolicy;
+use squirrel_commands::CommandResult;
+use squirrel_core::i18n::Locale;
+
+/// Schema of the known configuration keys
+pub mod schema;
+
+/// Layered configuration scopes
+pub mod scope;
+
+/// Encrypted configuration values
+pub mod encryption;
 
 /// Configuration errors
 #[derive(Debug, Error)]
@@ -33,6 +46,14 @@ pub enum ConfigError {
     /// Configuration key not found
     #[error("Config key not found: {0}")]
     KeyNotFound(String),
+    
+    /// Value rejected by the configuration schema
+    #[error("Invalid configuration: {0}")]
+    ValidationError(String),
+    
+    /// Error encrypting or decrypting a value, or loading the master key
+    #[error("Config encryption error: {0}")]
+    EncryptionError(String),
 }
 
 /// Result type for configuration operations
@@ -67,9 +88,34 @@ pub struct CliConfig {
     #[serde(default)]
     pub quiet: bool,
     
+    /// Serve cached results of identical command runs
+    #[serde(default)]
+    pub cache_results: bool,
+    
+    /// Print in the accessible output mode; detected from `NO_COLOR` and
+    /// `TERM` if unset
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub accessible: Option<bool>,
+    
+    /// Locale of messages, e.g. `de-AT`; the environment's if unset
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub locale: Option<String>,
+    
     /// Additional custom settings
     #[serde(default)]
     pub custom: HashMap<String, String>,
+    
+    /// Named execution environments
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub environments: BTreeMap<String, EnvironmentDefinition>,
+    
+    /// Environment each command runs in, by command name
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub command_environments: BTreeMap<String, String>,
+    
+    /// Command policies; those attached to `user:<login name>` apply
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub policies: Vec<CommandPolicy>,
 }
 
 // Default value functions for CliConfig
@@ -98,7 +144,13 @@ impl Default for CliConfig {
             mcp_port: default_mcp_port(),
             verbose: false,
             quiet: false,
+            cache_results: false,
+            accessible: None,
+            locale: None,
             custom: HashMap::new(),
+            environments: BTreeMap::new(),
+            command_environments: BTreeMap::new(),
+            policies: Vec::new(),
         }
     }
 }
@@ -111,6 +163,9 @@ impl CliConfig {
     
     /// Load configuration from a file
     ///
+    /// Encrypted `enc:` values are decrypted with the master key (see
+    /// [`encryption`]).
+    ///
     /// # Arguments
     ///
     /// * `path` - Path to the configuration file
@@ -125,7 +180,12 @@ impl CliConfig {
         let mut contents = String::new();
         file.read_to_string(&mut contents)?;
         
-        let config: Self = toml::from_str(&contents)?;
+        let mut table: toml::Table = contents.parse()?;
+        encryption::decrypt_table(&mut table, || {
+            Ok(encryption::MasterKey::load(&encryption::default_key_file()?)?)
+        })?;
+        
+        let config: Self = toml::Value::Table(table).try_into()?;
         debug!("Config loaded successfully");
         
         Ok(config)
@@ -175,6 +235,9 @@ impl CliConfig {
             "mcp_port" => Ok(self.mcp_port.to_string()),
             "verbose" => Ok(self.verbose.to_string()),
             "quiet" => Ok(self.quiet.to_string()),
+            "cache_results" => Ok(self.cache_results.to_string()),
+            "accessible" => self.accessible.map(|accessible| accessible.to_string()).ok_or_else(|| ConfigError::KeyNotFound(key.to_string())),
+            "locale" => self.locale.clone().ok_or_else(|| ConfigError::KeyNotFound(key.to_string())),
             _ => {
                 // Check custom settings
                 if let Some(value) = self.custom.get(key) {
@@ -224,6 +287,20 @@ impl CliConfig {
                     ConfigError::PathError(format!("Invalid boolean value: {}", value))
                 })?;
             },
+            "cache_results" => {
+                self.cache_results = value.parse::<bool>().map_err(|_| {
+                    ConfigError::PathError(format!("Invalid boolean value: {}", value))
+                })?;
+            },
+            "accessible" => {
+                self.accessible = Some(value.parse::<bool>().map_err(|_| {
+                    ConfigError::PathError(format!("Invalid boolean value: {}", value))
+                })?);
+            },
+            "locale" => {
+                value.parse::<Locale>().map_err(|e| ConfigError::PathError(e.to_string()))?;
+                self.locale = Some(value);
+            },
             _ => {
                 // Store in custom settings
                 self.custom.insert(key.to_string(), value);
@@ -259,11 +336,31 @@ impl CliConfig {
         
         self.verbose = other.verbose;
         self.quiet = other.quiet;
+        self.cache_results = other.cache_results;
+        
+        if other.accessible.is_some() {
+            self.accessible = other.accessible;
+        }
+        
+        if other.locale.is_some() {
+            self.locale = other.locale;
+        }
         
         // Merge custom settings
         for (key, value) in other.custom {
             self.custom.insert(key, value);
         }
+        
+        self.environments.extend(other.environments);
+        self.command_environments.extend(other.command_environments);
+        self.policies.extend(other.policies);
+    }
+    
+    /// The execution environments configured for commands
+    ///
+    /// Fails if a command is assigned an undefined environment.
+    pub fn environment_registry(&self) -> CommandResult<EnvironmentRegistry> {
+        EnvironmentRegistry::new(self.environments.clone(), self.command_environments.clone())
     }
     
     /// Load configuration from environment variables
@@ -284,6 +381,9 @@ impl CliConfig {
         
         // Process environment variables
         for (key, value) in std::env::vars() {
+            if encryption::is_key_variable(&key) {
+                continue;
+            }
             if let Some(suffix) = key.strip_prefix(prefix) {
                 let config_key = suffix.to_lowercase();
                 debug!("Found env var: {} = {}", config_key, value);
@@ -474,6 +574,13 @@ impl ConfigManager {
         result.insert("mcp_port".to_string(), self.config.mcp_port.to_string());
         result.insert("verbose".to_string(), self.config.verbose.to_string());
         result.insert("quiet".to_string(), self.config.quiet.to_string());
+        result.insert("cache_results".to_string(), self.config.cache_results.to_string());
+        if let Some(accessible) = self.config.accessible {
+            result.insert("accessible".to_string(), accessible.to_string());
+        }
+        if let Some(locale) = &self.config.locale {
+            result.insert("locale".to_string(), locale.clone());
+        }
         
         // Add custom fields
         for (key, value) in &self.config.custom {
@@ -574,6 +681,15 @@ mod tests {
         config.set("mcp_port", "9001".to_string())?;
         assert_eq!(config.get("mcp_port")?, "9001");
         
+        assert!(config.get("accessible").is_err());
+        config.set("accessible", "false".to_string())?;
+        assert_eq!(config.accessible, Some(false));
+        
+        assert!(config.get("locale").is_err());
+        config.set("locale", "de_AT.UTF-8".to_string())?;
+        assert_eq!(config.get("locale")?, "de_AT.UTF-8");
+        assert!(config.set("locale", "C".to_string()).is_err());
+        
         // Test custom fields
         config.set("custom_key", "custom_value".to_string())?;
         assert_eq!(config.get("custom_key")?, "custom_value");
@@ -631,4 +747,59 @@ mod tests {
         assert_eq!(config1.custom.get("key1").unwrap(), "value1");
         assert_eq!(config1.custom.get("key2").unwrap(), "value2");
     }
-} 
\ No newline at end of file
+    
+    #[test]
+    fn test_config_environments() -> Result<(), Box<dyn Error>> {
+        let config: CliConfig = toml::from_str(
+            r#"
+            [environments.bio]
+            inherit = ["PATH"]
+            vars = { BLAST_DB = "/data/blast" }
+            limits = { timeout_seconds = 60 }
+            
+            [command_environments]
+            align = "bio"
+            "#,
+        )?;
+        
+        let registry = config.environment_registry()?;
+        let env = registry.for_command("align", vec![("PATH".to_string(), "/bin".to_string())])?;
+        assert_eq!(env.var("BLAST_DB"), Some("/data/blast"));
+        assert_eq!(env.limits.timeout_seconds, Some(60));
+        
+        let mut broken = config.clone();
+        broken.command_environments.insert("status".to_string(), "missing".to_string());
+        assert!(broken.environment_registry().is_err());
+        
+        Ok(())
+    }
+    
+    #[test]
+    fn test_config_policies() -> Result<(), Box<dyn Error>> {
+        let config: CliConfig = toml::from_str(
+            r#"
+            [[policies]]
+            subject = "user:ada"
+            effect = "deny"
+            pattern = "deploy*"
+            
+            [[policies]]
+            subject = "user:ada"
+            effect = "allow"
+            scope = "namespace"
+            pattern = "builtin"
+            "#,
+        )?;
+        
+        assert_eq!(config.policies.len(), 2);
+        assert_eq!(config.policies[0].subject.to_string(), "user:ada");
+        assert_eq!(config.policies[0].scope.as_str(), "command");
+        assert!(toml::from_str::<CliConfig>("[[policies]]\nsubject = \"ada\"\neffect = \"deny\"\npattern = \"*\"").is_err());
+        
+        let mut merged = CliConfig::default();
+        merged.merge(config);
+        assert_eq!(merged.policies.len(), 2);
+        
+        Ok(())
+    }
+}
@@ -0,0 +1,87 @@
+//! Encrypted configuration values
+//!
+//! String values written as `enc:<hex>` are decrypted when a configuration
+//! file is loaded. The values and the master key are shared with the web
+//! server; see [`squirrel_core::encryption`] for where the key is read from.
+
+pub use squirrel_core::encryption::{
+    default_key_file, is_encrypted, is_key_variable, EncryptionError, MasterKey, ENCRYPTED_PREFIX, KEYCHAIN_ACCOUNT,
+    KEYCHAIN_SERVICE, KEY_FILE_NAME, MASTER_KEY_ENV, MASTER_KEY_FILE_ENV,
+};
+
+use super::{ConfigError, ConfigResult};
+
+impl From<EncryptionError> for ConfigError {
+    fn from(err: EncryptionError) -> Self {
+        ConfigError::EncryptionError(err.to_string())
+    }
+}
+
+/// Whether `value` contains an encrypted string
+fn contains_encrypted(value: &toml::Value) -> bool {
+    match value {
+        toml::Value::String(s) => is_encrypted(s),
+        toml::Value::Array(values) => values.iter().any(contains_encrypted),
+        toml::Value::Table(table) => table.values().any(contains_encrypted),
+        _ => false,
+    }
+}
+
+fn decrypt_value(value: &mut toml::Value, key: &MasterKey) -> ConfigResult<()> {
+    match value {
+        toml::Value::String(s) if is_encrypted(s) => *s = key.decrypt(s)?,
+        toml::Value::Array(values) => {
+            for value in values {
+                decrypt_value(value, key)?;
+            }
+        }
+        toml::Value::Table(table) => {
+            for (_, value) in table.iter_mut() {
+                decrypt_value(value, key)?;
+            }
+        }
+        _ => {}
+    }
+    Ok(())
+}
+
+/// Decrypt every `enc:` string in `table` in place
+///
+/// `load_key` is only called if the table holds an encrypted value, so
+/// plaintext configurations never need a master key.
+pub fn decrypt_table<F>(table: &mut toml::Table, load_key: F) -> ConfigResult<()>
+where
+    F: FnOnce() -> ConfigResult<MasterKey>,
+{
+    if !table.values().any(contains_encrypted) {
+        return Ok(());
+    }
+    let key = load_key()?;
+    for (_, value) in table.iter_mut() {
+        decrypt_value(value, &key)?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_decrypt_table_loads_key_only_when_needed() {
+        let key = MasterKey::generate().unwrap();
+        let mut table: toml::Table = format!(
+            "mcp_host = \"localhost\"\n[custom]\ndb_password = \"{}\"\n",
+            key.encrypt("hunter2").unwrap()
+        )
+        .parse()
+        .unwrap();
+
+        let mut plain: toml::Table = "mcp_host = \"localhost\"".parse().unwrap();
+        decrypt_table(&mut plain, || panic!("no encrypted values")).unwrap();
+
+        decrypt_table(&mut table, || Ok(key)).unwrap();
+        assert_eq!(table["custom"]["db_password"].as_str(), Some("hunter2"));
+        assert_eq!(table["mcp_host"].as_str(), Some("localhost"));
+    }
+}
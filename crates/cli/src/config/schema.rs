@@ -0,0 +1,173 @@
+//! Configuration schema
+//!
+//! Describes the known configuration keys, their value types and which
+//! values are secret, so the `config` command can validate writes and mask
+//! secrets in its output. Keys not in the schema are stored as custom
+//! settings.
+
+use super::encryption;
+use super::{ConfigError, ConfigResult};
+
+/// Type of a configuration value
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ValueType {
+    /// Free-form text
+    String,
+    /// `true` or `false`
+    Bool,
+    /// TCP port number
+    Port,
+    /// One of a fixed set of values
+    Enum(&'static [&'static str]),
+}
+
+impl ValueType {
+    /// Human-readable description of the accepted values
+    pub fn describe(&self) -> String {
+        match self {
+            Self::String => "string".to_string(),
+            Self::Bool => "true|false".to_string(),
+            Self::Port => "port (1-65535)".to_string(),
+            Self::Enum(values) => values.join("|"),
+        }
+    }
+}
+
+/// A known configuration key
+#[derive(Debug, Clone, Copy)]
+pub struct ConfigKey {
+    /// Key name
+    pub name: &'static str,
+    /// What the key controls
+    pub description: &'static str,
+    /// Accepted values
+    pub value_type: ValueType,
+    /// Whether the value is masked in output
+    pub secret: bool,
+}
+
+/// All known configuration keys
+pub const CONFIG_SCHEMA: &[ConfigKey] = &[
+    ConfigKey {
+        name: "log_level",
+        description: "Log level",
+        value_type: ValueType::Enum(&["error", "warn", "info", "debug", "trace"]),
+        secret: false,
+    },
+    ConfigKey {
+        name: "output_format",
+        description: "Default output format",
+        value_type: ValueType::Enum(&["text", "json", "yaml", "table"]),
+        secret: false,
+    },
+    ConfigKey {
+        name: "mcp_host",
+        description: "MCP server host",
+        value_type: ValueType::String,
+        secret: false,
+    },
+    ConfigKey {
+        name: "mcp_port",
+        description: "MCP server port",
+        value_type: ValueType::Port,
+        secret: false,
+    },
+    ConfigKey {
+        name: "verbose",
+        description: "Enable verbose logging",
+        value_type: ValueType::Bool,
+        secret: false,
+    },
+    ConfigKey {
+        name: "quiet",
+        description: "Enable quiet mode",
+        value_type: ValueType::Bool,
+        secret: false,
+    },
+    ConfigKey {
+        name: "cache_results",
+        description: "Serve cached results of identical command runs",
+        value_type: ValueType::Bool,
+        secret: false,
+    },
+    ConfigKey {
+        name: "accessible",
+        description: "Print plain lines for screen readers; detected from NO_COLOR and TERM if unset",
+        value_type: ValueType::Bool,
+        secret: false,
+    },
+    ConfigKey {
+        name: "locale",
+        description: "Locale of messages, e.g. de-AT",
+        value_type: ValueType::String,
+        secret: false,
+    },
+];
+
+/// Fragments marking a custom key as secret
+const SECRET_MARKERS: &[&str] = &["password", "secret", "token", "api_key", "apikey", "credential", "private_key"];
+
+/// Replacement shown for secret values
+pub const MASK: &str = "********";
+
+/// Look up a known key
+pub fn lookup(key: &str) -> Option<&'static ConfigKey> {
+    CONFIG_SCHEMA.iter().find(|k| k.name == key)
+}
+
+/// Check that `value` is acceptable for `key`
+///
+/// Known keys are checked against their type. Custom keys accept any value,
+/// but their names are limited to lowercase letters, digits, `_`, `-` and `.`.
+pub fn validate(key: &str, value: &str) -> ConfigResult<()> {
+    let Some(schema) = lookup(key) else {
+        let valid_name = !key.is_empty()
+            && key
+                .chars()
+                .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || matches!(c, '_' | '-' | '.'));
+        if !valid_name {
+            return Err(ConfigError::ValidationError(format!(
+                "Invalid key '{}': use lowercase letters, digits, '_', '-' or '.'",
+                key
+            )));
+        }
+        return Ok(());
+    };
+
+    let valid = match schema.value_type {
+        ValueType::String => true,
+        ValueType::Bool => value.parse::<bool>().is_ok(),
+        ValueType::Port => value.parse::<u16>().map(|port| port > 0).unwrap_or(false),
+        ValueType::Enum(values) => values.contains(&value),
+    };
+    if valid {
+        Ok(())
+    } else {
+        Err(ConfigError::ValidationError(format!(
+            "Invalid value '{}' for '{}': expected {}",
+            value,
+            key,
+            schema.value_type.describe()
+        )))
+    }
+}
+
+/// Whether the value of `key` must be masked
+pub fn is_secret(key: &str) -> bool {
+    match lookup(key) {
+        Some(schema) => schema.secret,
+        None => {
+            let key = key.to_lowercase();
+            SECRET_MARKERS.iter().any(|marker| key.contains(marker))
+        }
+    }
+}
+
+/// The value to display for `key`, masked if it is secret or encrypted
+pub fn display_value(key: &str, value: &str, show_secrets: bool) -> String {
+    if !show_secrets && !value.is_empty() && (is_secret(key) || encryption::is_encrypted(value)) {
+        MASK.to_string()
+    } else {
+        value.to_string()
+    }
+}
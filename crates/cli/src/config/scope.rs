@@ -0,0 +1,317 @@
+//! Configuration scopes
+//!
+//! Configuration is layered: built-in defaults, then the global file, then
+//! the project file, then `SQUIRREL_*` environment variables. Named profiles
+//! are separate files that can be read and written explicitly. Each scope is
+//! edited as a raw TOML table so that writing one key never pins the other
+//! keys of that file to their defaults.
+
+use std::collections::BTreeMap;
+use std::fmt;
+use std::fs;
+use std::path::PathBuf;
+
+use log::debug;
+use serde::Serialize;
+
+use super::encryption;
+use super::schema::{self, ValueType};
+use super::{CliConfig, ConfigError, ConfigManager, ConfigResult};
+
+/// Name of the table holding custom settings
+const CUSTOM_TABLE: &str = "custom";
+
+/// Prefix of environment variables overriding configuration
+pub const ENV_PREFIX: &str = "SQUIRREL_";
+
+/// Where a configuration value is read from or written to
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ConfigScope {
+    /// The user's configuration file
+    Global,
+    /// A named profile in the user's configuration directory
+    Profile(String),
+    /// `.squirrel.toml` in the project directory
+    Project,
+    /// An explicit file
+    File(PathBuf),
+}
+
+impl fmt::Display for ConfigScope {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Global => write!(f, "global"),
+            Self::Profile(name) => write!(f, "profile:{}", name),
+            Self::Project => write!(f, "project"),
+            Self::File(path) => write!(f, "file:{}", path.display()),
+        }
+    }
+}
+
+/// Directories the configuration scopes live in
+#[derive(Debug, Clone)]
+pub struct ConfigLocations {
+    /// User configuration directory
+    pub config_dir: PathBuf,
+    /// Project directory
+    pub project_dir: PathBuf,
+}
+
+impl ConfigLocations {
+    /// Use the platform configuration directory and the current directory
+    pub fn discover() -> ConfigResult<Self> {
+        let config_dir = directories::ProjectDirs::from("", "", "squirrel")
+            .map(|dirs| dirs.config_dir().to_path_buf())
+            .ok_or_else(|| ConfigError::PathError("Cannot determine configuration directory".to_string()))?;
+        Ok(Self {
+            config_dir,
+            project_dir: std::env::current_dir()?,
+        })
+    }
+
+    /// Path of the master key file
+    pub fn key_file(&self) -> PathBuf {
+        self.config_dir.join(encryption::KEY_FILE_NAME)
+    }
+
+    /// Path of the file backing `scope`
+    pub fn path(&self, scope: &ConfigScope) -> ConfigResult<PathBuf> {
+        Ok(match scope {
+            ConfigScope::Global => self.config_dir.join("squirrel.toml"),
+            ConfigScope::Profile(name) => {
+                let valid = !name.is_empty()
+                    && name.chars().all(|c| c.is_ascii_alphanumeric() || matches!(c, '_' | '-'));
+                if !valid {
+                    return Err(ConfigError::ValidationError(format!("Invalid profile name '{}'", name)));
+                }
+                self.config_dir.join("profiles").join(format!("{}.toml", name))
+            }
+            ConfigScope::Project => self.project_dir.join(".squirrel.toml"),
+            ConfigScope::File(path) => path.clone(),
+        })
+    }
+}
+
+/// The settings stored in a single scope
+#[derive(Debug, Clone)]
+pub struct ScopedConfig {
+    scope: ConfigScope,
+    path: PathBuf,
+    table: toml::Table,
+}
+
+impl ScopedConfig {
+    /// Load a scope; a missing file is an empty scope
+    pub fn load(scope: ConfigScope, locations: &ConfigLocations) -> ConfigResult<Self> {
+        let path = locations.path(&scope)?;
+        let table = if path.exists() {
+            fs::read_to_string(&path)?.parse::<toml::Table>()?
+        } else {
+            toml::Table::new()
+        };
+        debug!("Loaded {} configuration from {:?}", scope, path);
+        Ok(Self { scope, path, table })
+    }
+
+    /// The scope this configuration belongs to
+    pub fn scope(&self) -> &ConfigScope {
+        &self.scope
+    }
+
+    /// Path of the backing file
+    pub fn path(&self) -> &PathBuf {
+        &self.path
+    }
+
+    /// The value of `key` in this scope
+    pub fn get(&self, key: &str) -> Option<String> {
+        let value = if schema::lookup(key).is_some() {
+            self.table.get(key)
+        } else {
+            self.table.get(CUSTOM_TABLE).and_then(|custom| custom.get(key))
+        };
+        value.map(value_to_string)
+    }
+
+    /// All settings in this scope
+    pub fn entries(&self) -> Vec<(String, String)> {
+        let mut entries: Vec<(String, String)> = self
+            .table
+            .iter()
+            .filter(|(key, _)| key.as_str() != CUSTOM_TABLE)
+            .map(|(key, value)| (key.clone(), value_to_string(value)))
+            .collect();
+        if let Some(toml::Value::Table(custom)) = self.table.get(CUSTOM_TABLE) {
+            entries.extend(custom.iter().map(|(key, value)| (key.clone(), value_to_string(value))));
+        }
+        entries
+    }
+
+    /// Validate and store a value
+    pub fn set(&mut self, key: &str, value: &str) -> ConfigResult<()> {
+        schema::validate(key, value)?;
+        match schema::lookup(key) {
+            Some(known) => {
+                let value = match known.value_type {
+                    ValueType::Bool => toml::Value::Boolean(value.parse().unwrap_or_default()),
+                    ValueType::Port => toml::Value::Integer(value.parse().unwrap_or_default()),
+                    ValueType::String | ValueType::Enum(_) => toml::Value::String(value.to_string()),
+                };
+                self.table.insert(key.to_string(), value);
+            }
+            None => {
+                let custom = self
+                    .table
+                    .entry(CUSTOM_TABLE)
+                    .or_insert_with(|| toml::Value::Table(toml::Table::new()));
+                let toml::Value::Table(custom) = custom else {
+                    return Err(ConfigError::ValidationError(format!(
+                        "'{}' in {:?} is not a table",
+                        CUSTOM_TABLE, self.path
+                    )));
+                };
+                custom.insert(key.to_string(), toml::Value::String(value.to_string()));
+            }
+        }
+        Ok(())
+    }
+
+    /// Remove a value, returning whether it was present
+    pub fn unset(&mut self, key: &str) -> bool {
+        if schema::lookup(key).is_some() {
+            return self.table.remove(key).is_some();
+        }
+        match self.table.get_mut(CUSTOM_TABLE) {
+            Some(toml::Value::Table(custom)) => custom.remove(key).is_some(),
+            _ => false,
+        }
+    }
+
+    /// Write the scope back to its file
+    pub fn save(&self) -> ConfigResult<()> {
+        if let Some(parent) = self.path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        fs::write(&self.path, toml::to_string_pretty(&self.table)?)?;
+        debug!("Saved {} configuration to {:?}", self.scope, self.path);
+        Ok(())
+    }
+}
+
+/// An effective configuration value and where it came from
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct ResolvedValue {
+    /// Configuration key
+    pub key: String,
+    /// Effective value
+    pub value: String,
+    /// Scope that set the value: `default`, a scope name or `env`
+    pub source: String,
+}
+
+/// Resolve the effective configuration
+///
+/// `layers` are applied in order, later layers overriding earlier ones, and
+/// `env` (variables with [`ENV_PREFIX`]) overrides them all.
+pub fn resolve(
+    layers: &[ScopedConfig],
+    env: impl IntoIterator<Item = (String, String)>,
+) -> BTreeMap<String, ResolvedValue> {
+    let mut resolved: BTreeMap<String, ResolvedValue> = BTreeMap::new();
+    let mut apply = |key: String, value: String, source: String| {
+        resolved.insert(key.clone(), ResolvedValue { key, value, source });
+    };
+
+    for (key, value) in ConfigManager::with_config(CliConfig::default()).list() {
+        apply(key, value, "default".to_string());
+    }
+    for layer in layers {
+        for (key, value) in layer.entries() {
+            apply(key, value, layer.scope().to_string());
+        }
+    }
+    for (name, value) in env {
+        if encryption::is_key_variable(&name) {
+            continue;
+        }
+        if let Some(suffix) = name.strip_prefix(ENV_PREFIX) {
+            apply(suffix.to_lowercase(), value, "env".to_string());
+        }
+    }
+    resolved
+}
+
+/// Renders a TOML value without quoting strings
+fn value_to_string(value: &toml::Value) -> String {
+    match value {
+        toml::Value::String(s) => s.clone(),
+        other => other.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    fn locations(dir: &std::path::Path) -> ConfigLocations {
+        ConfigLocations {
+            config_dir: dir.join("config"),
+            project_dir: dir.join("project"),
+        }
+    }
+
+    #[test]
+    fn test_scoped_set_validates_and_keeps_types() {
+        let dir = tempdir().unwrap();
+        let locations = locations(dir.path());
+        let mut config = ScopedConfig::load(ConfigScope::Project, &locations).unwrap();
+
+        config.set("mcp_port", "9100").unwrap();
+        config.set("api_token", "abc").unwrap();
+        assert!(matches!(config.set("mcp_port", "http"), Err(ConfigError::ValidationError(_))));
+        assert!(matches!(config.set("log_level", "loud"), Err(ConfigError::ValidationError(_))));
+        config.save().unwrap();
+
+        // The file stays loadable as a full CLI configuration
+        let loaded = CliConfig::load_from_file(locations.path(&ConfigScope::Project).unwrap()).unwrap();
+        assert_eq!(loaded.mcp_port, 9100);
+        assert_eq!(loaded.custom.get("api_token").map(String::as_str), Some("abc"));
+
+        let mut config = ScopedConfig::load(ConfigScope::Project, &locations).unwrap();
+        assert_eq!(config.get("mcp_port").as_deref(), Some("9100"));
+        assert!(config.unset("api_token"));
+        assert!(!config.unset("api_token"));
+    }
+
+    #[test]
+    fn test_resolve_layers() {
+        let dir = tempdir().unwrap();
+        let locations = locations(dir.path());
+        let mut global = ScopedConfig::load(ConfigScope::Global, &locations).unwrap();
+        global.set("log_level", "debug").unwrap();
+        global.set("mcp_host", "global.example").unwrap();
+        let mut project = ScopedConfig::load(ConfigScope::Project, &locations).unwrap();
+        project.set("mcp_host", "project.example").unwrap();
+
+        let env = vec![
+            ("SQUIRREL_QUIET".to_string(), "true".to_string()),
+            ("HOME".to_string(), "/root".to_string()),
+        ];
+        let resolved = resolve(&[global, project], env);
+
+        let source = |key: &str| (resolved[key].value.as_str(), resolved[key].source.as_str());
+        assert_eq!(source("log_level"), ("debug", "global"));
+        assert_eq!(source("mcp_host"), ("project.example", "project"));
+        assert_eq!(source("mcp_port"), ("9000", "default"));
+        assert_eq!(source("quiet"), ("true", "env"));
+        assert!(!resolved.contains_key("home"));
+    }
+
+    #[test]
+    fn test_profile_names_are_checked() {
+        let locations = locations(std::path::Path::new("/tmp"));
+        assert!(locations.path(&ConfigScope::Profile("dev".to_string())).unwrap().ends_with("profiles/dev.toml"));
+        assert!(locations.path(&ConfigScope::Profile("../etc".to_string())).is_err());
+    }
+}
@@ -70,7 +70,24 @@ impl TextFormatter {
     }
 
     /// Format data as a table
+    ///
+    /// In the accessible output mode each row is a line of `header: value`
+    /// pairs instead.
     pub fn format_table(&self, headers: &[&str], rows: &[Vec<String>]) -> String {
+        if crate::output::is_accessible() {
+            return rows
+                .iter()
+                .map(|row| {
+                    headers
+                        .iter()
+                        .zip(row)
+                        .map(|(header, value)| format!("{header}: {value}"))
+                        .collect::<Vec<_>>()
+                        .join("; ")
+                })
+                .collect::<Vec<_>>()
+                .join("\n");
+        }
         let mut table = Table::new();
         table.add_row(Row::from_iter(headers.iter().map(|&h| Cell::new(h))));
         
@@ -18,6 +18,18 @@ pub mod mcp;
 /// Plugin system
 pub mod plugins;
 
+/// Bioinformatics file format inspection
+pub mod bio;
+
+/// Live monitoring dashboard for the terminal
+pub mod top;
+
+/// Output modes for terminals and assistive technology
+pub mod output;
+
+/// Forwarding of log records to the web server
+pub mod log_forwarding;
+
 /// Re-export types from dependencies
 pub use squirrel_commands::{Command, CommandResult};
 
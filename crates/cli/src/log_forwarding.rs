@@ -0,0 +1,88 @@
+//! Forwarding of the CLI's log records to the web server.
+//!
+//! With `SQUIRREL_LOG_SERVER` set to the server's URL, records of the CLI
+//! and of the plugins it runs are posted to `/api/logs` as the user whose
+//! access token is in `SQUIRREL_LOG_TOKEN`, besides being logged locally.
+//! Plugin records arrive with the target `plugin::<name>` and are forwarded
+//! as the component `plugin:<name>`; everything else as `cli`.
+
+use std::time::Duration;
+
+use log::{Log, Metadata, Record};
+use squirrel_commands::plugin_logs::TARGET_PREFIX;
+use squirrel_web::logs::{LogForwarder, LogForwarderConfig, LogLevel, NewLogEvent};
+
+/// Environment variable naming the server logs are forwarded to
+pub const LOG_SERVER_ENV: &str = "SQUIRREL_LOG_SERVER";
+
+/// Environment variable holding the access token logs are forwarded with
+pub const LOG_TOKEN_ENV: &str = "SQUIRREL_LOG_TOKEN";
+
+/// Longest wait for queued records to be posted before the CLI exits
+pub const FLUSH_TIMEOUT: Duration = Duration::from_secs(3);
+
+/// A forwarder to the server in `SQUIRREL_LOG_SERVER`, if it is set
+///
+/// Must be called within a Tokio runtime.
+pub fn forwarder_from_env() -> Option<LogForwarder> {
+    let env = |name: &str| std::env::var(name).ok().filter(|value| !value.is_empty());
+    let server_url = env(LOG_SERVER_ENV)?;
+    Some(LogForwarder::spawn(LogForwarderConfig {
+        server_url,
+        token: env(LOG_TOKEN_ENV),
+        source: env("HOSTNAME").or_else(|| env("COMPUTERNAME")),
+        ..LogForwarderConfig::default()
+    }))
+}
+
+/// The level of a `log` record
+fn level(level: log::Level) -> LogLevel {
+    match level {
+        log::Level::Trace => LogLevel::Trace,
+        log::Level::Debug => LogLevel::Debug,
+        log::Level::Info => LogLevel::Info,
+        log::Level::Warn => LogLevel::Warn,
+        log::Level::Error => LogLevel::Error,
+    }
+}
+
+/// The component a record is forwarded as
+fn component(target: &str) -> String {
+    match target.strip_prefix(TARGET_PREFIX) {
+        Some(plugin) => format!("plugin:{plugin}"),
+        None => "cli".to_string(),
+    }
+}
+
+/// Logger passing records to the host logger and forwarding them
+pub struct ForwardingLogger {
+    inner: Box<dyn Log>,
+    forwarder: LogForwarder,
+}
+
+impl ForwardingLogger {
+    /// Wrap the host logger, forwarding through `forwarder`
+    pub fn new(inner: Box<dyn Log>, forwarder: LogForwarder) -> Self {
+        Self { inner, forwarder }
+    }
+}
+
+impl Log for ForwardingLogger {
+    fn enabled(&self, metadata: &Metadata<'_>) -> bool {
+        self.inner.enabled(metadata)
+    }
+
+    fn log(&self, record: &Record<'_>) {
+        self.inner.log(record);
+        let mut event = NewLogEvent::new(level(record.level()), component(record.target()), record.args().to_string());
+        event
+            .fields
+            .insert("target".to_string(), record.target().into());
+        self.forwarder.forward(event);
+    }
+
+    fn flush(&self) {
+        self.inner.flush();
+    }
+}
+
@@ -1,20 +1,48 @@
 //! Entry point for the Squirrel CLI application.
 
+use std::path::PathBuf;
 use std::sync::Arc;
+use std::time::Duration;
 use std::{env, process};
 
 use log::{debug, warn, info, error, LevelFilter};
+use squirrel_commands::cache::ResultCache;
+use squirrel_commands::extensions::Extensions;
+use squirrel_commands::history::CommandHistory;
+use squirrel_commands::manifest::ManifestOptions;
+use squirrel_commands::plugin_logs;
+use squirrel_commands::policy::PolicySubject;
 use squirrel_commands::CommandRegistry;
-use squirrel_cli::commands::{create_cli, register_commands, ExecutionContext};
+use squirrel_cli::commands::{create_cli, register_commands, CapabilitiesCommand, ExecutionContext, JupyterCommand, PaletteCommand, ReplayCommand};
+use squirrel_cli::config::ConfigManager;
+use squirrel_cli::log_forwarding::{self, ForwardingLogger};
+use squirrel_cli::output::{self, OutputMode};
 use squirrel_cli::plugins::state::get_plugin_manager;
+use squirrel_core::error::CodedError;
+use squirrel_core::i18n::{localizer, Locale};
+use squirrel_core::kv::KvStore;
+use squirrel_core::tr;
+
+/// Number of executed commands kept in the command history
+const COMMAND_HISTORY_SIZE: usize = 1000;
 
 /// Squirrel CLI application entry point
 #[tokio::main]
 async fn main() {
-    // Set up logger
-    env_logger::Builder::new()
+    // Set up logger, routing plugin records to per-plugin buffers and
+    // forwarding records to the server if asked to
+    let logger = env_logger::Builder::new()
         .filter_level(LevelFilter::Debug)
-        .init();
+        .build();
+    let max_level = logger.filter();
+    let forwarder = log_forwarding::forwarder_from_env();
+    let host: Box<dyn log::Log> = match &forwarder {
+        Some(forwarder) => Box::new(ForwardingLogger::new(Box::new(logger), forwarder.clone())),
+        None => Box::new(logger),
+    };
+    if let Err(err) = plugin_logs::install(host, max_level) {
+        eprintln!("Failed to install logger: {}", err);
+    }
 
     // Create command registry
     let mut registry = CommandRegistry::new();
@@ -31,21 +59,15 @@ async fn main() {
     let plugin_manager = get_plugin_manager();
     
     // Get plugin names from the list of installed plugins
-    let plugin_names = {
-        let plugin_manager_lock = plugin_manager.lock().unwrap();
-        plugin_manager_lock.list_plugins()
-            .iter()
-            .map(|p| p.metadata().name.clone())
-            .collect::<Vec<String>>()
-    };
+    let plugin_names: Vec<String> = plugin_manager.list_plugins()
+        .iter()
+        .map(|p| p.metadata().name.clone())
+        .collect();
     
     info!("Loading {} installed plugins...", plugin_names.len());
     for plugin_name in &plugin_names {
         debug!("Loading plugin: {}", plugin_name);
-        
-        // Get a new lock for each operation
-        let mut plugin_manager_lock = plugin_manager.lock().unwrap();
-        match plugin_manager_lock.load_plugin(plugin_name) {
+        match plugin_manager.load_plugin(plugin_name).await {
             Ok(_) => {
                 info!("Successfully loaded plugin: {}", plugin_name);
             }
@@ -55,40 +77,76 @@ async fn main() {
         }
     }
     
+    // Load the message catalogs plugins ship in their locales directory
+    for plugin in plugin_manager.list_plugins() {
+        if let Err(err) = localizer().load_dir(&plugin.path().join("locales")) {
+            warn!("{}", tr!("cli-plugin-catalogs-failed", plugin = plugin.metadata().name, message = err));
+        }
+    }
+    
     // Register commands from loaded plugins
     debug!("Registering plugin commands...");
-    {
-        let plugin_manager_lock = plugin_manager.lock().unwrap();
-        match plugin_manager_lock.register_plugin_commands(&registry_arc) {
-            Ok(_) => {
-                info!("Successfully registered plugin commands");
-            }
-            Err(err) => {
-                warn!("Failed to register some plugin commands: {}", err);
-            }
+    match plugin_manager.register_plugin_commands(&registry_arc) {
+        Ok(_) => {
+            info!("Successfully registered plugin commands");
+        }
+        Err(err) => {
+            warn!("Failed to register some plugin commands: {}", err);
         }
     }
     
     // Start the plugins
     debug!("Starting plugins...");
-    {
-        let mut plugin_manager_lock = plugin_manager.lock().unwrap();
-        match plugin_manager_lock.start_plugins() {
-            Ok(_) => {
-                info!("Successfully started plugins");
-            }
-            Err(err) => {
-                warn!("Failed to start some plugins: {}", err);
-            }
+    match plugin_manager.start_plugins() {
+        Ok(_) => {
+            info!("Successfully started plugins");
+        }
+        Err(err) => {
+            warn!("Failed to start some plugins: {}", err);
+        }
+    }
+
+    // Record plugin versions for reproducibility manifests
+    let plugin_versions = plugin_manager.plugin_versions();
+
+    // The replay command executes other commands, so it needs the shared registry
+    let replay_command = ReplayCommand::new(&registry_arc, plugin_versions.clone());
+    if let Err(err) = registry_arc.register("replay", Arc::new(replay_command)) {
+        warn!("Failed to register replay command: {}", err);
+    }
+
+    // The capabilities command reports the registered commands and plugins
+    let capabilities_command = CapabilitiesCommand::new(&registry_arc, plugin_versions.clone());
+    if let Err(err) = registry_arc.register("capabilities", Arc::new(capabilities_command)) {
+        warn!("Failed to register capabilities command: {}", err);
+    }
+
+    // The palette command searches the registered commands, ranked by the command history
+    let command_history = CommandHistory::default_path().and_then(|path| {
+        CommandHistory::with_options(COMMAND_HISTORY_SIZE, path)
+            .map_err(|err| warn!("Command history unavailable: {}", err))
+            .ok()
+            .map(Arc::new)
+    });
+    if let Some(history) = &command_history {
+        if let Err(err) = registry_arc.load_usage(history) {
+            warn!("Failed to rank commands by the command history: {}", err);
         }
     }
+    let palette_command = PaletteCommand::new(&registry_arc);
+    if let Err(err) = registry_arc.register("palette", Arc::new(palette_command)) {
+        warn!("Failed to register palette command: {}", err);
+    }
+
+    // The Jupyter kernel runs notebook magics as commands from the shared registry
+    let jupyter_command = JupyterCommand::new(&registry_arc);
+    if let Err(err) = registry_arc.register("jupyter", Arc::new(jupyter_command)) {
+        warn!("Failed to register jupyter command: {}", err);
+    }
 
     // Create CLI app
     let app = create_cli();
     
-    // Create execution context
-    let execution_context = ExecutionContext::new(registry_arc);
-    
     // Get command-line arguments
     let args: Vec<String> = env::args().collect();
     
@@ -101,6 +159,113 @@ async fn main() {
         }
     };
     
+    // Create execution context, capturing a run manifest and serving cached results if requested
+    let mut execution_context = ExecutionContext::new(registry_arc);
+    if let Some(history) = command_history {
+        execution_context = execution_context.with_history(history);
+    }
+    // Commands keep small pieces of durable state in the key-value store
+    let mut extensions = Extensions::new();
+    match KvStore::default_path().map(KvStore::open) {
+        Some(Ok(store)) => {
+            extensions.insert(store);
+        }
+        Some(Err(err)) => warn!("Key-value store unavailable: {}", err),
+        None => {}
+    }
+    execution_context = execution_context.with_extensions(extensions);
+    if let Some(secs) = matches.get_one::<u64>("timeout") {
+        execution_context = execution_context.with_timeout(Duration::from_secs(*secs));
+    }
+    
+    let config = ConfigManager::load(None);
+    
+    // Print plain lines for assistive technology if asked to or detected
+    output::set_mode(OutputMode::detect(
+        matches.get_flag("accessible"),
+        config.as_ref().ok().and_then(|manager| manager.config().accessible),
+        |name| env::var(name).ok(),
+    ));
+    
+    // Localize messages: --locale, then the configuration, then the environment
+    let requested_locale = matches
+        .get_one::<String>("locale")
+        .cloned()
+        .or_else(|| config.as_ref().ok().and_then(|manager| manager.config().locale.clone()));
+    match requested_locale {
+        Some(tag) => match tag.parse::<Locale>() {
+            Ok(locale) => localizer().set_locale(locale),
+            Err(_) => warn!("{}", tr!("cli-invalid-locale", locale = tag, fallback = localizer().locale())),
+        },
+        None => {
+            if let Some(locale) = Locale::from_env() {
+                localizer().set_locale(locale);
+            }
+        }
+    }
+    
+    // Run commands in their configured execution environments, caching results if configured
+    let mut cache_results = false;
+    match config.as_ref().map(|manager| (manager.config().environment_registry(), manager.config().cache_results)) {
+        Ok((Ok(environments), cache_enabled)) => {
+            execution_context = execution_context.with_environments(environments);
+            cache_results = cache_enabled;
+        }
+        Ok((Err(err), _)) => {
+            error!("Invalid execution environments: {}", err);
+            process::exit(1);
+        }
+        Err(err) => {
+            warn!("Failed to load configuration, using the default environment: {}", err);
+        }
+    }
+    // Refuse the commands the configured policies deny the local user
+    if let Ok(manager) = &config {
+        let policies = manager.config().policies.clone();
+        if !policies.is_empty() {
+            let user = env::var("USER").or_else(|_| env::var("USERNAME")).unwrap_or_default();
+            execution_context = execution_context.with_policies(policies, vec![PolicySubject::User(user)]);
+        }
+    }
+    let values = |name: &str| -> Vec<String> {
+        matches.get_many::<String>(name).map(|v| v.cloned().collect()).unwrap_or_default()
+    };
+    let capture_options = ManifestOptions {
+        tools: values("capture-tool"),
+        inputs: values("capture-input").into_iter().map(PathBuf::from).collect(),
+        plugins: plugin_versions,
+        ..ManifestOptions::default()
+    };
+    let cache_results = (cache_results || matches.get_flag("cache")) && !matches.get_flag("no-cache");
+    if let Some(path) = matches.get_one::<String>("capture-manifest") {
+        let mut options = capture_options.clone();
+        let env_allowlist = values("capture-env");
+        if !env_allowlist.is_empty() {
+            options.env_allowlist = env_allowlist;
+        }
+        execution_context = execution_context.with_manifest_capture(PathBuf::from(path), options);
+    }
+    if cache_results {
+        // The environment is not part of the cache key, so it is not captured
+        let options = ManifestOptions {
+            env_allowlist: Vec::new(),
+            ..capture_options
+        };
+        match ResultCache::default_path().map(ResultCache::open) {
+            Some(Ok(cache)) => {
+                let user = env::var("USER").or_else(|_| env::var("USERNAME")).unwrap_or_default();
+                execution_context = execution_context.with_result_cache(cache.with_owner(user), options);
+            }
+            Some(Err(err)) => warn!("Result cache unavailable, executing without it: {}", err),
+            None => warn!("No home directory for the result cache, executing without it"),
+        }
+    } else if !matches.contains_id("capture-manifest")
+        && (!capture_options.inputs.is_empty() || !capture_options.tools.is_empty())
+    {
+        error!("--capture-input and --capture-tool require --capture-manifest or --cache");
+        process::exit(1);
+    }
+    
     // Get the subcommand and execute it
     let (command_name, subcommand_matches) = matches.subcommand().unwrap();
     
@@ -109,20 +274,31 @@ async fn main() {
             info!("Command executed successfully");
         }
         Err(err) => {
-            error!("Command execution failed: {}", err);
+            let err = CodedError::from_error(err);
+            error!("{}", tr!("cli-command-failed", message = format!("[{}] {}", err.code(), err)));
+            for cause in err.causes() {
+                error!("{}", tr!("cli-error-caused-by", message = cause));
+            }
+            if let Some(hint) = err.hint() {
+                error!("{}", tr!("cli-error-hint", hint = hint));
+            }
+            if let Some(forwarder) = &forwarder {
+                forwarder.flush(log_forwarding::FLUSH_TIMEOUT).await;
+            }
             process::exit(1);
         }
     }
     
     // Cleanup: Unload plugins
     debug!("Unloading plugins...");
-    let _ = plugin_manager.lock().map(|mut plugin_manager| {
-        if let Err(e) = plugin_manager.unload_plugins() {
-            warn!("Failed to unload plugins: {}", e);
-        } else {
-            debug!("Plugins unloaded successfully");
-        }
-    });
+    if let Err(e) = plugin_manager.unload_plugins().await {
+        warn!("Failed to unload plugins: {}", e);
+    } else {
+        debug!("Plugins unloaded successfully");
+    }
     
     info!("Squirrel CLI execution completed");
+    if let Some(forwarder) = &forwarder {
+        forwarder.flush(log_forwarding::FLUSH_TIMEOUT).await;
+    }
 } 
\ No newline at end of file
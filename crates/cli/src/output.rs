@@ -0,0 +1,168 @@
+//! Output modes
+//!
+//! The accessible mode suits screen readers, braille displays and terminals
+//! that cannot redraw. Output in it has no colors, redrawn screens or
+//! box-drawing, and is printed line by line: each line stands on its own
+//! and is never overwritten. Live views such as `squirrel top` and
+//! `squirrel status --watch` print a one-line summary at intervals instead,
+//! and only when it changed.
+//!
+//! The mode is chosen with `--accessible`, then the `accessible`
+//! configuration key, and is otherwise on when `NO_COLOR` is set or `TERM`
+//! is `dumb`.
+
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::{Duration, Instant};
+
+/// Shortest time between two summaries, so speech is not flooded
+pub const MIN_SUMMARY_INTERVAL: Duration = Duration::from_secs(10);
+
+/// Whether the process prints in the accessible mode
+static ACCESSIBLE: AtomicBool = AtomicBool::new(false);
+
+/// How the CLI prints
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum OutputMode {
+    /// Colors, tables and live screens
+    #[default]
+    Standard,
+    /// Plain, stable lines for assistive technology
+    Accessible,
+}
+
+impl OutputMode {
+    /// The mode asked for by `--accessible`, the configuration or the
+    /// environment, in that order
+    ///
+    /// `env` looks up environment variables.
+    pub fn detect(flag: bool, configured: Option<bool>, env: impl Fn(&str) -> Option<String>) -> Self {
+        let accessible = flag
+            || configured.unwrap_or_else(|| {
+                env("NO_COLOR").is_some_and(|value| !value.is_empty())
+                    || env("TERM").is_some_and(|term| term == "dumb")
+            });
+        if accessible {
+            Self::Accessible
+        } else {
+            Self::Standard
+        }
+    }
+}
+
+/// Sets the output mode of the process, turning colors off in the
+/// accessible mode
+pub fn set_mode(mode: OutputMode) {
+    let accessible = mode == OutputMode::Accessible;
+    ACCESSIBLE.store(accessible, Ordering::Relaxed);
+    if accessible {
+        colored::control::set_override(false);
+        console::set_colors_enabled(false);
+        console::set_colors_enabled_stderr(false);
+    }
+}
+
+/// The output mode of the process
+pub fn mode() -> OutputMode {
+    if is_accessible() {
+        OutputMode::Accessible
+    } else {
+        OutputMode::Standard
+    }
+}
+
+/// Whether the process prints in the accessible mode
+pub fn is_accessible() -> bool {
+    ACCESSIBLE.load(Ordering::Relaxed)
+}
+
+/// Paces the summaries of a live view
+///
+/// A summary is printed at most once per interval, and only if it differs
+/// from the one printed last.
+#[derive(Debug, Clone)]
+pub struct Summaries {
+    interval: Duration,
+    printed_at: Option<Instant>,
+    last: String,
+}
+
+impl Summaries {
+    /// Summaries at most every `interval`, but no more often than
+    /// [`MIN_SUMMARY_INTERVAL`]
+    pub fn new(interval: Duration) -> Self {
+        Self {
+            interval: interval.max(MIN_SUMMARY_INTERVAL),
+            printed_at: None,
+            last: String::new(),
+        }
+    }
+
+    /// Time between summaries
+    pub fn interval(&self) -> Duration {
+        self.interval
+    }
+
+    /// The summary to print at `now`, if one is due and it changed
+    pub fn offer(&mut self, now: Instant, summary: String) -> Option<String> {
+        if self.printed_at.is_some_and(|at| now.duration_since(at) < self.interval) || summary == self.last {
+            return None;
+        }
+        self.printed_at = Some(now);
+        self.last.clone_from(&summary);
+        Some(summary)
+    }
+}
+
+/// A duration in words, e.g. `1 h 2 min 5 s`
+pub fn spoken_duration(secs: u64) -> String {
+    let parts = [
+        (secs / 86_400, "d"),
+        (secs % 86_400 / 3600, "h"),
+        (secs % 3600 / 60, "min"),
+        (secs % 60, "s"),
+    ];
+    let words: Vec<String> = parts
+        .iter()
+        .filter(|(value, _)| *value > 0)
+        .map(|(value, unit)| format!("{value} {unit}"))
+        .collect();
+    if words.is_empty() {
+        "0 s".to_string()
+    } else {
+        words.join(" ")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_mode_detection_order() {
+        let env = |vars: &'static [(&'static str, &'static str)]| {
+            move |name: &str| vars.iter().find(|(key, _)| *key == name).map(|(_, value)| value.to_string())
+        };
+        assert_eq!(OutputMode::detect(false, None, env(&[("TERM", "xterm")])), OutputMode::Standard);
+        assert_eq!(OutputMode::detect(false, None, env(&[("TERM", "dumb")])), OutputMode::Accessible);
+        assert_eq!(OutputMode::detect(false, None, env(&[("NO_COLOR", "1")])), OutputMode::Accessible);
+        assert_eq!(OutputMode::detect(false, None, env(&[("NO_COLOR", "")])), OutputMode::Standard);
+        assert_eq!(OutputMode::detect(false, Some(false), env(&[("TERM", "dumb")])), OutputMode::Standard);
+        assert_eq!(OutputMode::detect(false, Some(true), env(&[])), OutputMode::Accessible);
+        assert_eq!(OutputMode::detect(true, Some(false), env(&[])), OutputMode::Accessible);
+    }
+
+    #[test]
+    fn test_summaries_are_paced_and_deduplicated() {
+        let start = Instant::now();
+        let mut summaries = Summaries::new(Duration::from_secs(1));
+        assert_eq!(summaries.interval(), MIN_SUMMARY_INTERVAL);
+        assert_eq!(summaries.offer(start, "2 jobs".to_string()).as_deref(), Some("2 jobs"));
+        assert_eq!(summaries.offer(start + Duration::from_secs(5), "3 jobs".to_string()), None);
+        assert_eq!(summaries.offer(start + Duration::from_secs(10), "2 jobs".to_string()), None);
+        assert_eq!(summaries.offer(start + Duration::from_secs(11), "3 jobs".to_string()).as_deref(), Some("3 jobs"));
+
+        assert_eq!(spoken_duration(3725), "1 h 2 min 5 s");
+        assert_eq!(spoken_duration(90_000), "1 d 1 h");
+        assert_eq!(spoken_duration(0), "0 s");
+    }
+}
@@ -1,6 +1,8 @@
 use std::fmt;
 use std::error::Error;
 
+use squirrel_core::error::{Coded, ErrorCode};
+
 /// Errors that can occur in the plugin system
 #[derive(Debug)]
 pub enum PluginError {
@@ -49,7 +51,39 @@ impl fmt::Display for PluginError {
     }
 }
 
-impl Error for PluginError {}
+impl Error for PluginError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            PluginError::IoError(err) => Some(err),
+            _ => None,
+        }
+    }
+}
+
+impl Coded for PluginError {
+    fn code(&self) -> ErrorCode {
+        match self {
+            PluginError::NotFound(_) => ErrorCode::NotFound,
+            PluginError::AlreadyExists(_) | PluginError::RegisterError(_) => ErrorCode::AlreadyExists,
+            PluginError::ValidationError(_) => ErrorCode::InvalidInput,
+            PluginError::LoadError(_) | PluginError::InitError(_) => ErrorCode::ExecutionFailed,
+            PluginError::IoError(_) | PluginError::Unknown(_) => ErrorCode::Internal,
+        }
+    }
+
+    fn hint(&self) -> Option<String> {
+        match self {
+            PluginError::NotFound(_) => {
+                Some("Run `squirrel plugin list` to see the installed plugins".to_string())
+            }
+            PluginError::LoadError(_) | PluginError::ValidationError(_) => Some(
+                "Check that the plugin was built against a compatible `squirrel-sdk` version"
+                    .to_string(),
+            ),
+            _ => self.code().hint().map(str::to_string),
+        }
+    }
+}
 
 impl From<std::io::Error> for PluginError {
     fn from(err: std::io::Error) -> Self {
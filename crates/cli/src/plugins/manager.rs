@@ -1,7 +1,21 @@
-use std::collections::HashMap;
+//! Plugin manager
+//!
+//! The manager is shared by every command through [`get_plugin_manager`] and
+//! is safe to use concurrently from async code without an outer lock. Lookups
+//! go through concurrent maps and never block on another task's lifecycle
+//! work; loading and unloading, which await plugin hooks, are serialized by an
+//! async mutex so a plugin is never initialized or cleaned up twice.
+//!
+//! Plugin hooks and the commands registered for a plugin run in that
+//! plugin's log scope, so their log output is captured per plugin (see
+//! [`plugin_logs`]).
+//!
+//! [`get_plugin_manager`]: crate::plugins::state::get_plugin_manager
+
 use std::path::{Path, PathBuf};
 use std::sync::Arc;
-use tokio::runtime::Runtime;
+use dashmap::DashMap;
+use tokio::sync::Mutex;
 use log::{info, warn, error, debug};
 use libloading::Library;
 
@@ -9,7 +23,7 @@ use crate::plugins::{PluginItem, PluginMetadata, PluginStatus};
 use crate::plugins::plugin::{Plugin, PluginFactory};
 use crate::plugins::error::PluginError;
 use crate::plugins::state::PluginState;
-use squirrel_commands::{Command, CommandRegistry};
+use squirrel_commands::{plugin_logs, Command, CommandRegistry};
 
 /// Type for a plugin create function
 type PluginCreateFn = unsafe fn() -> Result<Arc<dyn Plugin>, PluginError>;
@@ -20,79 +34,98 @@ type PluginFactoryRegisterFn = unsafe fn() -> Arc<dyn PluginFactory>;
 /// A manager for Squirrel plugins
 pub struct PluginManager {
     /// The installed plugins
-    plugins: HashMap<String, PluginItem>,
+    plugins: DashMap<String, PluginItem>,
     /// The loaded plugin instances
-    loaded_plugins: HashMap<String, Arc<dyn Plugin>>,
+    loaded_plugins: DashMap<String, Arc<dyn Plugin>>,
     /// The plugin states
-    plugin_states: HashMap<String, PluginState>,
+    plugin_states: DashMap<String, PluginState>,
     /// Plugin libraries
-    libraries: HashMap<String, Library>,
+    libraries: DashMap<String, Library>,
+    /// Serializes loading and unloading
+    lifecycle: Mutex<()>,
 }
 
 impl PluginManager {
     /// Create a new plugin manager
     pub fn new() -> Self {
         Self {
-            plugins: HashMap::new(),
-            loaded_plugins: HashMap::new(),
-            plugin_states: HashMap::new(),
-            libraries: HashMap::new(),
+            plugins: DashMap::new(),
+            loaded_plugins: DashMap::new(),
+            plugin_states: DashMap::new(),
+            libraries: DashMap::new(),
+            lifecycle: Mutex::new(()),
         }
     }
 
     /// List all installed plugins
-    pub fn list_plugins(&self) -> Vec<&PluginItem> {
-        self.plugins.values().collect()
+    ///
+    /// Returns a snapshot; later changes to the manager are not reflected.
+    pub fn list_plugins(&self) -> Vec<PluginItem> {
+        self.plugins.iter().map(|entry| entry.value().clone()).collect()
     }
 
     /// Get a plugin by name
-    pub fn get_plugin(&self, name: &str) -> Result<&PluginItem, PluginError> {
+    pub fn get_plugin(&self, name: &str) -> Result<PluginItem, PluginError> {
         self.plugins
             .get(name)
+            .map(|entry| entry.value().clone())
             .ok_or_else(|| PluginError::plugin_not_found(name))
     }
 
-    /// Get a mutable reference to a plugin by name
-    pub fn get_plugin_mut(&mut self, name: &str) -> Result<&mut PluginItem, PluginError> {
-        self.plugins
+    /// Set the status of a plugin
+    pub fn set_plugin_status(&self, name: &str, status: PluginStatus) -> Result<(), PluginError> {
+        let mut plugin = self.plugins
             .get_mut(name)
-            .ok_or_else(|| PluginError::plugin_not_found(name))
+            .ok_or_else(|| PluginError::plugin_not_found(name))?;
+        plugin.set_status(status);
+        Ok(())
+    }
+
+    /// Get the lifecycle state of a plugin
+    pub fn plugin_state(&self, name: &str) -> Option<PluginState> {
+        self.plugin_states.get(name).map(|state| *state)
+    }
+
+    /// Versions of all installed plugins, by name
+    pub fn plugin_versions(&self) -> std::collections::BTreeMap<String, String> {
+        self.plugins
+            .iter()
+            .map(|entry| (entry.key().clone(), entry.metadata().version.clone()))
+            .collect()
     }
 
     /// Add a new plugin to the manager
-    pub fn add_plugin(&mut self, metadata: PluginMetadata, path: PathBuf, status: PluginStatus) -> Result<&PluginItem, PluginError> {
+    pub fn add_plugin(&self, metadata: PluginMetadata, path: PathBuf, status: PluginStatus) -> Result<PluginItem, PluginError> {
         let name = metadata.name.clone();
         
-        if self.plugins.contains_key(&name) {
-            return Err(PluginError::plugin_already_exists(&name));
-        }
-        
-        let plugin = PluginItem::new(metadata, path, status);
-        self.plugins.insert(name.clone(), plugin);
-        self.plugin_states.insert(name.clone(), PluginState::Created);
+        let plugin = match self.plugins.entry(name.clone()) {
+            dashmap::Entry::Occupied(_) => return Err(PluginError::plugin_already_exists(&name)),
+            dashmap::Entry::Vacant(entry) => entry.insert(PluginItem::new(metadata, path, status)).clone(),
+        };
+        self.plugin_states.insert(name, PluginState::Created);
         
-        Ok(self.plugins.get(&name).unwrap())
+        Ok(plugin)
     }
 
     /// Remove a plugin from the manager
-    pub fn remove_plugin(&mut self, name: &str) -> Result<(), PluginError> {
+    pub async fn remove_plugin(&self, name: &str) -> Result<(), PluginError> {
+        let _lifecycle = self.lifecycle.lock().await;
         if !self.plugins.contains_key(name) {
             return Err(PluginError::plugin_not_found(name));
         }
         
         // Clean up loaded plugin if it exists
-        if let Some(plugin) = self.loaded_plugins.remove(name) {
-            let rt = Runtime::new()
-                .map_err(|e| PluginError::Unknown(format!("Failed to create runtime: {}", e)))?;
-            
+        if let Some((_, plugin)) = self.loaded_plugins.remove(name) {
             // Call cleanup but don't propagate errors
-            if let Err(e) = rt.block_on(plugin.cleanup()) {
+            if let Err(e) = plugin_logs::scope_async(name, plugin.cleanup()).await {
                 warn!("Error cleaning up plugin {}: {}", name, e);
             }
         }
         
+        self.libraries.remove(name);
         self.plugins.remove(name);
         self.plugin_states.remove(name);
+        plugin_logs::global().remove(name);
         Ok(())
     }
     
@@ -107,7 +140,9 @@ impl PluginManager {
     /// # Returns
     ///
     /// `Ok(())` if loading succeeds, or an error otherwise
-    pub fn load_plugin(&mut self, name: &str) -> Result<(), PluginError> {
+    pub async fn load_plugin(&self, name: &str) -> Result<(), PluginError> {
+        let _lifecycle = self.lifecycle.lock().await;
+        
         // Check if plugin exists
         let plugin_item = self.get_plugin(name)?;
         
@@ -116,18 +151,11 @@ impl PluginManager {
             return Ok(());
         }
         
-        // Create a runtime for async operations
-        let rt = Runtime::new()
-            .map_err(|e| PluginError::Unknown(format!("Failed to create runtime: {}", e)))?;
-        
-        // Get the plugin path
-        let plugin_path = plugin_item.path().to_path_buf();
-        
         // Load the plugin
-        let plugin = self.load_plugin_from_path(name, &plugin_path)?;
+        let plugin = self.load_plugin_from_path(name, plugin_item.path())?;
         
         // Initialize the plugin
-        match rt.block_on(plugin.initialize()) {
+        match plugin_logs::scope_async(name, plugin.initialize()).await {
             Ok(()) => {
                 info!("Plugin {} initialized successfully", name);
                 self.loaded_plugins.insert(name.to_string(), plugin);
@@ -136,17 +164,13 @@ impl PluginManager {
                 self.plugin_states.insert(name.to_string(), PluginState::Initialized);
                 
                 // Update plugin status
-                let plugin_item = self.get_plugin_mut(name)?;
-                plugin_item.set_status(PluginStatus::Enabled);
-                
-                Ok(())
+                self.set_plugin_status(name, PluginStatus::Enabled)
             },
             Err(e) => {
                 error!("Failed to initialize plugin {}: {}", name, e);
                 
                 // Update plugin status
-                let plugin_item = self.get_plugin_mut(name)?;
-                plugin_item.set_status(PluginStatus::Failed(e.to_string()));
+                self.set_plugin_status(name, PluginStatus::Failed(e.to_string()))?;
                 
                 Err(e)
             }
@@ -166,7 +190,7 @@ impl PluginManager {
     /// # Returns
     ///
     /// The loaded plugin instance or an error
-    fn load_plugin_from_path(&mut self, name: &str, path: &Path) -> Result<Arc<dyn Plugin>, PluginError> {
+    fn load_plugin_from_path(&self, name: &str, path: &Path) -> Result<Arc<dyn Plugin>, PluginError> {
         // Look for shared library in the plugin path
         let lib_path = self.find_plugin_library(path, name)?;
         
@@ -294,7 +318,12 @@ impl PluginManager {
     pub fn register_plugin_commands(&self, registry: &Arc<CommandRegistry>) -> Result<(), PluginError> {
         debug!("Registering commands from loaded plugins...");
         
-        if self.loaded_plugins.is_empty() {
+        // Snapshot the loaded plugins so no map guard is held while registering
+        let loaded: Vec<(String, Arc<dyn Plugin>)> = self.loaded_plugins
+            .iter()
+            .map(|entry| (entry.key().clone(), Arc::clone(entry.value())))
+            .collect();
+        if loaded.is_empty() {
             debug!("No plugins loaded, no commands to register");
             return Ok(());
         }
@@ -302,7 +331,7 @@ impl PluginManager {
         let mut success_count = 0;
         let mut failures = Vec::new();
         
-        for (plugin_name, plugin) in &self.loaded_plugins {
+        for (plugin_name, plugin) in &loaded {
             debug!("Getting commands from plugin: {}", plugin_name);
             
             let commands = plugin.commands();
@@ -315,7 +344,8 @@ impl PluginManager {
                 let cmd_name = command.name();
                 debug!("Registering command '{}' from plugin '{}'", cmd_name, plugin_name);
                 
-                match registry.register(cmd_name, command.clone()) {
+                let scoped = Arc::new(plugin_logs::ScopedCommand::new(plugin_name, command.clone()));
+                match registry.register(cmd_name, scoped) {
                     Ok(_) => {
                         success_count += 1;
                         debug!("Command '{}' from plugin '{}' registered successfully", cmd_name, plugin_name);
@@ -355,14 +385,12 @@ impl PluginManager {
     /// # Returns
     ///
     /// `Ok(())` if starting succeeds, or an error otherwise
-    pub fn start_plugins(&mut self) -> Result<(), PluginError> {
-        let _rt = Runtime::new()
-            .map_err(|e| PluginError::Unknown(format!("Failed to create runtime: {}", e)))?;
-        
+    pub fn start_plugins(&self) -> Result<(), PluginError> {
         let mut failed_plugins = Vec::new();
         
-        for name in self.loaded_plugins.keys() {
-            let plugin_state = self.plugin_states.get(name).cloned().unwrap_or(PluginState::Created);
+        let names: Vec<String> = self.loaded_plugins.iter().map(|entry| entry.key().clone()).collect();
+        for name in &names {
+            let plugin_state = self.plugin_state(name).unwrap_or(PluginState::Created);
             
             // Only start plugins that are in the Initialized state
             if plugin_state == PluginState::Initialized {
@@ -394,14 +422,12 @@ impl PluginManager {
     /// # Returns
     ///
     /// `Ok(())` if stopping succeeds, or an error otherwise
-    pub fn stop_plugins(&mut self) -> Result<(), PluginError> {
-        let _rt = Runtime::new()
-            .map_err(|e| PluginError::Unknown(format!("Failed to create runtime: {}", e)))?;
-        
+    pub fn stop_plugins(&self) -> Result<(), PluginError> {
         let mut failed_plugins = Vec::new();
         
-        for name in self.loaded_plugins.keys() {
-            let plugin_state = self.plugin_states.get(name).cloned().unwrap_or(PluginState::Created);
+        let names: Vec<String> = self.loaded_plugins.iter().map(|entry| entry.key().clone()).collect();
+        for name in &names {
+            let plugin_state = self.plugin_state(name).unwrap_or(PluginState::Created);
             
             // Only stop plugins that are in the Started state
             if plugin_state == PluginState::Started {
@@ -427,15 +453,12 @@ impl PluginManager {
     }
     
     /// Unload all plugins and clean up resources
-    pub fn unload_plugins(&mut self) -> Result<(), PluginError> {
+    pub async fn unload_plugins(&self) -> Result<(), PluginError> {
+        let _lifecycle = self.lifecycle.lock().await;
         info!("Unloading all plugins");
         
-        // Create a runtime for async operations
-        let rt = Runtime::new()
-            .map_err(|e| PluginError::Unknown(format!("Failed to create runtime: {}", e)))?;
-        
         // Get a list of all plugin names
-        let plugin_names: Vec<String> = self.loaded_plugins.keys().cloned().collect();
+        let plugin_names: Vec<String> = self.loaded_plugins.iter().map(|entry| entry.key().clone()).collect();
         
         // Track success/failure counts
         let mut success_count = 0;
@@ -443,26 +466,22 @@ impl PluginManager {
         
         // Unload each plugin
         for name in plugin_names {
-            if let Some(plugin) = self.loaded_plugins.remove(&name) {
+            if let Some((_, plugin)) = self.loaded_plugins.remove(&name) {
                 // Call cleanup
-                match rt.block_on(plugin.cleanup()) {
+                match plugin_logs::scope_async(&name, plugin.cleanup()).await {
                     Ok(()) => {
                         info!("Plugin {} unloaded successfully", name);
                         success_count += 1;
                         
                         // Update plugin state and status
-                        if let Ok(plugin_item) = self.get_plugin_mut(&name) {
-                            plugin_item.set_status(PluginStatus::Disabled);
-                        }
+                        let _ = self.set_plugin_status(&name, PluginStatus::Disabled);
                     },
                     Err(e) => {
                         error!("Failed to unload plugin {}: {}", name, e);
                         failure_count += 1;
                         
                         // Update plugin state and status
-                        if let Ok(plugin_item) = self.get_plugin_mut(&name) {
-                            plugin_item.set_status(PluginStatus::Failed(e.to_string()));
-                        }
+                        let _ = self.set_plugin_status(&name, PluginStatus::Failed(e.to_string()));
                     }
                 }
                 
@@ -472,9 +491,8 @@ impl PluginManager {
         }
         
         // Update plugin states
-        let state_keys: Vec<String> = self.plugin_states.keys().cloned().collect();
-        for name in state_keys {
-            self.plugin_states.insert(name, PluginState::Created);
+        for mut state in self.plugin_states.iter_mut() {
+            *state = PluginState::Created;
         }
         
         info!("Unloaded {} plugins ({} succeeded, {} failed)", 
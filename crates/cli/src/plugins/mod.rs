@@ -70,7 +70,7 @@ fn get_plugin_directories() -> Vec<PathBuf> {
 /// # Returns
 ///
 /// The number of plugins discovered and loaded
-fn discover_plugins_in_directory(dir: &Path, plugin_manager: &mut PluginManager) -> Result<usize, PluginError> {
+fn discover_plugins_in_directory(dir: &Path, plugin_manager: &PluginManager) -> Result<usize, PluginError> {
     debug!("Discovering plugins in directory: {:?}", dir);
     
     if !dir.exists() || !dir.is_dir() {
@@ -191,8 +191,7 @@ pub fn initialize_plugins() -> Result<(), error::PluginError> {
     info!("Initializing plugin system");
     
     // Get the plugin manager singleton
-    let plugin_manager_arc = state::get_plugin_manager();
-    let mut plugin_manager = plugin_manager_arc.lock().map_err(|_| PluginError::Unknown("Failed to lock plugin manager".to_string()))?;
+    let plugin_manager = state::get_plugin_manager();
     
     // Get plugin directories
     let plugin_dirs = get_plugin_directories();
@@ -200,7 +199,7 @@ pub fn initialize_plugins() -> Result<(), error::PluginError> {
     // Discover plugins in each directory
     let mut total_count = 0;
     for dir in plugin_dirs {
-        match discover_plugins_in_directory(&dir, &mut plugin_manager) {
+        match discover_plugins_in_directory(&dir, &plugin_manager) {
             Ok(count) => {
                 total_count += count;
             },
@@ -4,7 +4,7 @@
 //! and provides functionality for managing state transitions.
 
 use std::fmt;
-use std::sync::{Arc, Mutex};
+use std::sync::Arc;
 use lazy_static::lazy_static;
 
 use crate::plugins::manager::PluginManager;
@@ -72,10 +72,13 @@ impl fmt::Display for PluginState {
 
 // Global state for plugin management
 lazy_static! {
-    static ref PLUGIN_MANAGER: Arc<Mutex<PluginManager>> = Arc::new(Mutex::new(PluginManager::new()));
+    static ref PLUGIN_MANAGER: Arc<PluginManager> = Arc::new(PluginManager::new());
 }
 
 /// Get the global plugin manager instance
-pub fn get_plugin_manager() -> Arc<Mutex<PluginManager>> {
+///
+/// The manager synchronizes internally, so callers use it directly rather
+/// than through a lock.
+pub fn get_plugin_manager() -> Arc<PluginManager> {
     Arc::clone(&PLUGIN_MANAGER)
 } 
\ No newline at end of file
@@ -1,7 +1,9 @@
 #[cfg(test)]
 mod tests {
     use std::path::PathBuf;
+    use std::sync::Arc;
     use crate::plugins::manager::PluginManager;
+    use crate::plugins::state::PluginState;
     use crate::plugins::plugin::{PluginMetadata, PluginStatus};
     use crate::plugins::error::PluginError;
 
@@ -22,7 +24,7 @@ mod tests {
 
     #[test]
     fn test_plugin_manager_add_get() {
-        let mut manager = PluginManager::new();
+        let manager = PluginManager::new();
         
         // Add test plugins
         let (metadata1, path1, status1) = create_test_plugin("plugin1", "0.1.0");
@@ -47,7 +49,7 @@ mod tests {
 
     #[test]
     fn test_plugin_manager_list() {
-        let mut manager = PluginManager::new();
+        let manager = PluginManager::new();
         
         // Initially, list should be empty
         assert_eq!(manager.list_plugins().len(), 0);
@@ -72,9 +74,9 @@ mod tests {
         assert!(names.contains(&"list-plugin2".to_string()));
     }
 
-    #[test]
-    fn test_plugin_manager_remove() {
-        let mut manager = PluginManager::new();
+    #[tokio::test]
+    async fn test_plugin_manager_remove() {
+        let manager = PluginManager::new();
         
         // Add a plugin
         let (metadata, path, status) = create_test_plugin("remove-plugin", "0.1.0");
@@ -84,13 +86,13 @@ mod tests {
         assert!(manager.get_plugin("remove-plugin").is_ok());
         
         // Remove it
-        assert!(manager.remove_plugin("remove-plugin").is_ok());
+        assert!(manager.remove_plugin("remove-plugin").await.is_ok());
         
         // Verify it doesn't exist anymore
         assert!(manager.get_plugin("remove-plugin").is_err());
         
         // Try removing it again - should fail
-        match manager.remove_plugin("remove-plugin") {
+        match manager.remove_plugin("remove-plugin").await {
             Err(PluginError::NotFound(_)) => {}, // Expected error
             _ => panic!("Expected NotFound error"),
         }
@@ -98,7 +100,7 @@ mod tests {
 
     #[test]
     fn test_plugin_duplicates() {
-        let mut manager = PluginManager::new();
+        let manager = PluginManager::new();
         
         // Add original plugin
         let (metadata, path, status) = create_test_plugin("duplicate", "0.1.0");
@@ -118,4 +120,41 @@ mod tests {
             _ => panic!("Expected AlreadyExists error"),
         }
     }
-} 
\ No newline at end of file
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+    async fn test_plugin_manager_concurrent_lifecycle() {
+        let manager = Arc::new(PluginManager::new());
+        let dir = tempfile::tempdir().unwrap();
+        for i in 0..4 {
+            let name = format!("concurrent-{}", i);
+            let path = dir.path().join(&name);
+            std::fs::create_dir(&path).unwrap();
+            // Not a real library, so the manager falls back to its test plugin
+            let library = format!("lib{}.{}", name.replace('-', "_"), std::env::consts::DLL_EXTENSION);
+            std::fs::write(path.join(library), b"not a library").unwrap();
+            let (metadata, _, status) = create_test_plugin(&name, "0.1.0");
+            manager.add_plugin(metadata, path, status).unwrap();
+        }
+
+        // Load every plugin twice from competing tasks while others read
+        let mut tasks = Vec::new();
+        for i in 0..8 {
+            let manager = Arc::clone(&manager);
+            tasks.push(tokio::spawn(async move {
+                manager.load_plugin(&format!("concurrent-{}", i % 4)).await.unwrap();
+                manager.list_plugins().len()
+            }));
+        }
+        for task in tasks {
+            assert_eq!(task.await.unwrap(), 4);
+        }
+
+        manager.start_plugins().unwrap();
+        assert_eq!(manager.plugin_state("concurrent-0"), Some(PluginState::Started));
+        assert_eq!(manager.get_plugin("concurrent-3").unwrap().status(), &PluginStatus::Enabled);
+
+        manager.unload_plugins().await.unwrap();
+        assert_eq!(manager.plugin_state("concurrent-0"), Some(PluginState::Created));
+        assert_eq!(manager.get_plugin("concurrent-0").unwrap().status(), &PluginStatus::Disabled);
+    }
+}
@@ -0,0 +1,745 @@
+//! Live monitoring dashboard for the terminal
+//!
+//! `squirrel top` shows what a Squirrel installation is doing: the Squirrel
+//! processes running commands, the jobs whose MCP contexts are open, the
+//! state of every tool, host resource usage and firing alerts. The data
+//! comes from the state the server and the CLI share on disk, read by the
+//! [`sources`] and published as [`TopEvent`]s on the dashboard's event
+//! channel. Key presses travel on the same channel, so the [`Dashboard`]
+//! changes only by applying events and can be driven without a terminal.
+
+pub mod sources;
+
+use std::collections::{BTreeMap, VecDeque};
+use std::time::Duration;
+
+use chrono::{DateTime, Utc};
+use console::{style, Key};
+use serde::Serialize;
+use squirrel_mcp::tool::{ExecutionRecord, ExecutionStatus};
+use squirrel_monitoring::alerts::lifecycle::TrackedAlert;
+
+use crate::output::spoken_duration;
+
+/// Number of CPU samples kept for the usage graph
+const CPU_HISTORY: usize = 60;
+
+/// Bars of the CPU usage graph, from idle to busy
+const SPARKS: [char; 8] = ['▁', '▂', '▃', '▄', '▅', '▆', '▇', '█'];
+
+/// Host resource usage
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct ResourceSample {
+    /// CPU usage of the host in percent
+    pub cpu_percent: f64,
+    /// Memory in use, in bytes
+    pub memory_used: u64,
+    /// Total memory, in bytes
+    pub memory_total: u64,
+    /// Load averages over 1, 5 and 15 minutes
+    pub load: [f64; 3],
+    /// Fullest mounted disk, in percent
+    pub disk_percent: f64,
+}
+
+/// A running Squirrel process
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct ProcessRow {
+    /// Process ID
+    pub pid: u32,
+    /// Command line
+    pub command: String,
+    /// CPU usage in percent
+    pub cpu_percent: f32,
+    /// Resident memory, in bytes
+    pub memory: u64,
+    /// How long the process has run
+    pub run_secs: u64,
+}
+
+/// A job whose MCP context is open
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct JobRow {
+    /// Job ID
+    pub job_id: String,
+    /// Name of the job's context
+    pub name: String,
+    /// User who created the job
+    pub owner: Option<String>,
+    /// Commands submitted for the job, oldest first
+    pub steps: Vec<String>,
+    /// When the job started
+    pub started_at: DateTime<Utc>,
+    /// When the job last changed
+    pub updated_at: DateTime<Utc>,
+}
+
+/// Recent executions of a tool
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct ToolRow {
+    /// Tool ID
+    pub tool_id: String,
+    /// Recorded executions
+    pub runs: usize,
+    /// Recorded executions that did not succeed
+    pub errors: usize,
+    /// Mean execution time in milliseconds
+    pub mean_ms: u64,
+    /// Executions, most recent first
+    pub recent: Vec<ExecutionRecord>,
+}
+
+impl ToolRow {
+    /// Outcome of the latest execution, which is the tool's state as far as
+    /// the dashboard can tell
+    #[must_use]
+    pub fn state(&self) -> &'static str {
+        match self.recent.first().map(|record| record.status) {
+            Some(ExecutionStatus::Success) => "ok",
+            Some(ExecutionStatus::Failure) => "failing",
+            Some(ExecutionStatus::Cancelled) => "cancelled",
+            Some(ExecutionStatus::Timeout) => "timing out",
+            None => "idle",
+        }
+    }
+}
+
+/// Something the dashboard reacts to
+#[derive(Debug, Clone)]
+pub enum TopEvent {
+    /// A new host resource sample
+    Resources(ResourceSample),
+    /// The running Squirrel processes
+    Processes(Vec<ProcessRow>),
+    /// The running jobs
+    Jobs(Vec<JobRow>),
+    /// The tools with recorded executions
+    Tools(Vec<ToolRow>),
+    /// The firing alerts, most recent first
+    Alerts(Vec<TrackedAlert>),
+    /// A source could not be read; `None` once it can again
+    SourceStatus(&'static str, Option<String>),
+    /// A key was pressed
+    Key(Key),
+}
+
+/// What the dashboard last heard from its sources
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct Snapshot {
+    /// Latest host resource sample
+    pub resources: Option<ResourceSample>,
+    /// Running Squirrel processes
+    pub processes: Vec<ProcessRow>,
+    /// Running jobs
+    pub jobs: Vec<JobRow>,
+    /// Tools with recorded executions
+    pub tools: Vec<ToolRow>,
+    /// Firing alerts
+    pub alerts: Vec<TrackedAlert>,
+    /// Sources that cannot be read, with the reason
+    pub errors: BTreeMap<&'static str, String>,
+}
+
+/// The selectable panels, in display order
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Panel {
+    /// Running Squirrel processes
+    Commands,
+    /// Running jobs
+    Jobs,
+    /// Tool states
+    Tools,
+    /// Firing alerts
+    Alerts,
+}
+
+impl Panel {
+    /// All panels, in display order
+    pub const ALL: [Panel; 4] = [Panel::Commands, Panel::Jobs, Panel::Tools, Panel::Alerts];
+
+    /// Position in [`Panel::ALL`]
+    fn index(self) -> usize {
+        Self::ALL.iter().position(|panel| *panel == self).unwrap_or_default()
+    }
+
+    /// Title of the panel
+    fn title(self) -> &'static str {
+        match self {
+            Panel::Commands => "Commands",
+            Panel::Jobs => "Jobs",
+            Panel::Tools => "Tools",
+            Panel::Alerts => "Alerts",
+        }
+    }
+}
+
+/// Whether the dashboard keeps running after an event
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Control {
+    /// Keep running
+    Continue,
+    /// The user asked to quit
+    Quit,
+}
+
+/// State of the dashboard: the data shown and where the user is
+#[derive(Debug, Clone)]
+pub struct Dashboard {
+    snapshot: Snapshot,
+    cpu_history: VecDeque<f64>,
+    focus: Panel,
+    selected: [usize; 4],
+    detail: bool,
+    paused: bool,
+    refresh: Duration,
+    updated_at: Option<DateTime<Utc>>,
+}
+
+impl Dashboard {
+    /// An empty dashboard whose sources refresh every `refresh`
+    #[must_use]
+    pub fn new(refresh: Duration) -> Self {
+        Self {
+            snapshot: Snapshot::default(),
+            cpu_history: VecDeque::with_capacity(CPU_HISTORY),
+            focus: Panel::Commands,
+            selected: [0; 4],
+            detail: false,
+            paused: false,
+            refresh,
+            updated_at: None,
+        }
+    }
+
+    /// The data shown
+    #[must_use]
+    pub fn snapshot(&self) -> &Snapshot {
+        &self.snapshot
+    }
+
+    /// The panel keys act on
+    #[must_use]
+    pub fn focus(&self) -> Panel {
+        self.focus
+    }
+
+    /// Whether the selected item is shown in detail
+    #[must_use]
+    pub fn in_detail(&self) -> bool {
+        self.detail
+    }
+
+    /// Applies an event; data is ignored while paused
+    pub fn apply(&mut self, event: TopEvent) -> Control {
+        if let TopEvent::Key(key) = event {
+            return self.handle_key(&key);
+        }
+        if self.paused {
+            return Control::Continue;
+        }
+        match event {
+            TopEvent::Resources(sample) => {
+                if self.cpu_history.len() == CPU_HISTORY {
+                    self.cpu_history.pop_front();
+                }
+                self.cpu_history.push_back(sample.cpu_percent);
+                self.snapshot.resources = Some(sample);
+            }
+            TopEvent::Processes(processes) => self.snapshot.processes = processes,
+            TopEvent::Jobs(jobs) => self.snapshot.jobs = jobs,
+            TopEvent::Tools(tools) => self.snapshot.tools = tools,
+            TopEvent::Alerts(alerts) => self.snapshot.alerts = alerts,
+            TopEvent::SourceStatus(source, Some(error)) => {
+                self.snapshot.errors.insert(source, error);
+            }
+            TopEvent::SourceStatus(source, None) => {
+                self.snapshot.errors.remove(source);
+            }
+            TopEvent::Key(_) => {}
+        }
+        self.updated_at = Some(Utc::now());
+        // Rows may have gone away under the selection
+        for panel in Panel::ALL {
+            let last = self.len(panel).saturating_sub(1);
+            self.selected[panel.index()] = self.selected[panel.index()].min(last);
+        }
+        if self.len(self.focus) == 0 {
+            self.detail = false;
+        }
+        Control::Continue
+    }
+
+    /// Moves around the dashboard
+    fn handle_key(&mut self, key: &Key) -> Control {
+        let panel = self.focus.index();
+        let len = self.len(self.focus);
+        match key {
+            Key::Char('q') | Key::CtrlC => return Control::Quit,
+            Key::Char('p') => self.paused = !self.paused,
+            Key::Escape | Key::Backspace | Key::ArrowLeft => self.detail = false,
+            Key::Enter | Key::ArrowRight if len > 0 => self.detail = true,
+            Key::Tab if !self.detail => self.focus = Panel::ALL[(panel + 1) % Panel::ALL.len()],
+            Key::BackTab if !self.detail => {
+                self.focus = Panel::ALL[(panel + Panel::ALL.len() - 1) % Panel::ALL.len()];
+            }
+            Key::Char(digit @ '1'..='4') if !self.detail => {
+                self.focus = Panel::ALL[*digit as usize - '1' as usize];
+            }
+            Key::ArrowDown | Key::Char('j') => self.selected[panel] = (self.selected[panel] + 1).min(len.saturating_sub(1)),
+            Key::ArrowUp | Key::Char('k') => self.selected[panel] = self.selected[panel].saturating_sub(1),
+            Key::PageDown => self.selected[panel] = (self.selected[panel] + 10).min(len.saturating_sub(1)),
+            Key::PageUp => self.selected[panel] = self.selected[panel].saturating_sub(10),
+            Key::Home => self.selected[panel] = 0,
+            Key::End => self.selected[panel] = len.saturating_sub(1),
+            _ => {}
+        }
+        Control::Continue
+    }
+
+    /// Number of rows in a panel
+    fn len(&self, panel: Panel) -> usize {
+        match panel {
+            Panel::Commands => self.snapshot.processes.len(),
+            Panel::Jobs => self.snapshot.jobs.len(),
+            Panel::Tools => self.snapshot.tools.len(),
+            Panel::Alerts => self.snapshot.alerts.len(),
+        }
+    }
+
+    /// The screen as lines of at most `width` columns, `height` lines in all
+    #[must_use]
+    pub fn render(&self, width: usize, height: usize) -> Vec<String> {
+        let mut lines = vec![self.header(width)];
+        for (source, error) in &self.snapshot.errors {
+            lines.push(fit(&format!("! {source}: {error}"), width));
+        }
+        lines.push(String::new());
+
+        let body = height.saturating_sub(lines.len() + 1);
+        if self.detail {
+            lines.extend(self.detail_lines().iter().take(body).map(|line| fit(line, width)));
+        } else {
+            // Each panel has a title and a column header besides its rows
+            let rows = (body.saturating_sub(Panel::ALL.len() * 2) / Panel::ALL.len()).max(1);
+            for panel in Panel::ALL {
+                lines.extend(self.panel_lines(panel, width, rows));
+            }
+        }
+        lines.truncate(height.saturating_sub(1));
+        while lines.len() < height.saturating_sub(1) {
+            lines.push(String::new());
+        }
+        let keys = if self.detail {
+            "Esc back  p pause  q quit"
+        } else {
+            "Tab/1-4 panel  Up/Down select  Enter details  p pause  q quit"
+        };
+        lines.push(style(fit(keys, width)).dim().to_string());
+        lines
+    }
+
+    /// Time, resource usage and whether the dashboard is paused
+    fn header(&self, width: usize) -> String {
+        let mut header = format!("squirrel top  {}", Utc::now().format("%H:%M:%S"));
+        if let Some(resources) = &self.snapshot.resources {
+            let graph: String = self.cpu_history.iter().map(|cpu| spark(*cpu)).collect();
+            header.push_str(&format!(
+                "  cpu {:5.1}% {graph}  mem {}/{}  load {:.2} {:.2} {:.2}  disk {:.0}%",
+                resources.cpu_percent,
+                bytes(resources.memory_used),
+                bytes(resources.memory_total),
+                resources.load[0],
+                resources.load[1],
+                resources.load[2],
+                resources.disk_percent
+            ));
+        }
+        header.push_str(&format!("  every {}s", self.refresh.as_secs_f64()));
+        if self.paused {
+            header.push_str("  [paused]");
+        }
+        style(fit(&header, width)).bold().to_string()
+    }
+
+    /// Title, column header and up to `rows` rows of a panel, scrolled to
+    /// the selection
+    fn panel_lines(&self, panel: Panel, width: usize, rows: usize) -> Vec<String> {
+        let (columns, items): (String, Vec<String>) = match panel {
+            Panel::Commands => (
+                format!("{:>7} {:>6} {:>9} {:>9}  COMMAND", "PID", "CPU%", "MEM", "TIME"),
+                self.snapshot
+                    .processes
+                    .iter()
+                    .map(|process| {
+                        format!(
+                            "{:>7} {:>6.1} {:>9} {:>9}  {}",
+                            process.pid,
+                            process.cpu_percent,
+                            bytes(process.memory),
+                            elapsed(process.run_secs),
+                            process.command
+                        )
+                    })
+                    .collect(),
+            ),
+            Panel::Jobs => (
+                format!("{:<36}  {:<20} {:<12} {:>5} {:>9}  LAST COMMAND", "JOB", "NAME", "OWNER", "STEPS", "AGE"),
+                self.snapshot
+                    .jobs
+                    .iter()
+                    .map(|job| {
+                        format!(
+                            "{:<36}  {:<20} {:<12} {:>5} {:>9}  {}",
+                            fit(&job.job_id, 36),
+                            fit(&job.name, 20),
+                            fit(job.owner.as_deref().unwrap_or("-"), 12),
+                            job.steps.len(),
+                            elapsed(age_secs(job.started_at)),
+                            job.steps.last().map_or("-", String::as_str)
+                        )
+                    })
+                    .collect(),
+            ),
+            Panel::Tools => (
+                format!("{:<24} {:<10} {:>6} {:>6} {:>8} {:>9}", "TOOL", "STATE", "RUNS", "ERRORS", "MEAN", "LAST RUN"),
+                self.snapshot
+                    .tools
+                    .iter()
+                    .map(|tool| {
+                        let last_run = tool.recent.first().map_or_else(
+                            || "-".to_string(),
+                            |record| format!("{} ago", elapsed(age_secs(record.started_at))),
+                        );
+                        format!(
+                            "{:<24} {:<10} {:>6} {:>6} {:>6}ms {:>9}",
+                            fit(&tool.tool_id, 24),
+                            tool.state(),
+                            tool.runs,
+                            tool.errors,
+                            tool.mean_ms,
+                            last_run
+                        )
+                    })
+                    .collect(),
+            ),
+            Panel::Alerts => (
+                format!("{:<9} {:>6} {:<4} {:>9}  MESSAGE", "SEVERITY", "COUNT", "ACK", "LAST"),
+                self.snapshot
+                    .alerts
+                    .iter()
+                    .map(|alert| {
+                        format!(
+                            "{:<9} {:>6} {:<4} {:>9}  {}",
+                            format!("{:?}", alert.severity),
+                            alert.count,
+                            if alert.acknowledgment.is_some() { "yes" } else { "no" },
+                            elapsed(age_secs(alert.last_seen)),
+                            alert.message
+                        )
+                    })
+                    .collect(),
+            ),
+        };
+
+        let focused = panel == self.focus;
+        let title = format!("{}{} ({})", if focused { "> " } else { "  " }, panel.title(), items.len());
+        let title = if focused { style(fit(&title, width)).bold().to_string() } else { fit(&title, width) };
+        let mut lines = vec![title, style(fit(&format!("  {columns}"), width)).dim().to_string()];
+        if items.is_empty() {
+            lines.push(fit("  (none)", width));
+            return lines;
+        }
+        let selected = self.selected[panel.index()];
+        let first = (selected + 1).saturating_sub(rows);
+        for (index, item) in items.iter().enumerate().skip(first).take(rows) {
+            let line = fit(&format!("  {item}"), width);
+            lines.push(if focused && index == selected { style(line).reverse().to_string() } else { line });
+        }
+        lines
+    }
+
+    /// One line summing up the dashboard, for the accessible output mode
+    #[must_use]
+    pub fn summary(&self) -> String {
+        let snapshot = &self.snapshot;
+        let failing = snapshot.tools.iter().filter(|tool| tool.state() != "ok" && tool.state() != "idle").count();
+        let mut summary = format!(
+            "{}, {}, {} of {} failing, {}",
+            count(snapshot.processes.len(), "command running", "commands running"),
+            count(snapshot.jobs.len(), "job open", "jobs open"),
+            failing,
+            count(snapshot.tools.len(), "tool", "tools"),
+            count(snapshot.alerts.len(), "alert firing", "alerts firing"),
+        );
+        if let Some(resources) = &snapshot.resources {
+            summary.push_str(&format!(
+                "; CPU {:.0} percent, memory {} of {}, disk {:.0} percent",
+                resources.cpu_percent,
+                bytes(resources.memory_used),
+                bytes(resources.memory_total),
+                resources.disk_percent
+            ));
+        }
+        for (source, error) in &snapshot.errors {
+            summary.push_str(&format!("; {source} unavailable: {error}"));
+        }
+        summary
+    }
+
+    /// The summary followed by a line per item, for the accessible output
+    /// mode
+    #[must_use]
+    pub fn lines(&self) -> Vec<String> {
+        let snapshot = &self.snapshot;
+        let mut lines = vec![self.summary()];
+        lines.extend(snapshot.processes.iter().map(|process| {
+            format!(
+                "Command {}: {}; CPU {:.1} percent, memory {}, running {}",
+                process.pid,
+                process.command,
+                process.cpu_percent,
+                bytes(process.memory),
+                spoken_duration(process.run_secs)
+            )
+        }));
+        lines.extend(snapshot.jobs.iter().map(|job| {
+            format!(
+                "Job {}: {}; owner {}, {}, started {} ago, last command {}",
+                job.job_id,
+                job.name,
+                job.owner.as_deref().unwrap_or("unknown"),
+                count(job.steps.len(), "step", "steps"),
+                spoken_duration(age_secs(job.started_at)),
+                job.steps.last().map_or("none", String::as_str)
+            )
+        }));
+        lines.extend(snapshot.tools.iter().map(|tool| {
+            format!(
+                "Tool {}: {}; {}, {} failed, mean {} ms",
+                tool.tool_id,
+                tool.state(),
+                count(tool.runs, "run", "runs"),
+                tool.errors,
+                tool.mean_ms
+            )
+        }));
+        lines.extend(snapshot.alerts.iter().map(|alert| {
+            format!(
+                "Alert {:?}: {}; fired {}, last {} ago{}",
+                alert.severity,
+                alert.message,
+                count(usize::try_from(alert.count).unwrap_or(usize::MAX), "time", "times"),
+                spoken_duration(age_secs(alert.last_seen)),
+                if alert.acknowledgment.is_some() { ", acknowledged" } else { "" }
+            )
+        }));
+        lines
+    }
+
+    /// Everything known about the selected item of the focused panel
+    fn detail_lines(&self) -> Vec<String> {
+        let selected = self.selected[self.focus.index()];
+        match self.focus {
+            Panel::Commands => self.snapshot.processes.get(selected).map_or_else(Vec::new, |process| {
+                vec![
+                    format!("Process {}", process.pid),
+                    format!("  Command: {}", process.command),
+                    format!("  CPU: {:.1}%", process.cpu_percent),
+                    format!("  Memory: {}", bytes(process.memory)),
+                    format!("  Running for: {}", elapsed(process.run_secs)),
+                ]
+            }),
+            Panel::Jobs => self.snapshot.jobs.get(selected).map_or_else(Vec::new, |job| {
+                let mut lines = vec![
+                    format!("Job {}", job.job_id),
+                    format!("  Context: {}", job.name),
+                    format!("  Owner: {}", job.owner.as_deref().unwrap_or("-")),
+                    format!("  Started: {}", job.started_at.to_rfc3339()),
+                    format!("  Updated: {}", job.updated_at.to_rfc3339()),
+                    format!("  Steps ({}):", job.steps.len()),
+                ];
+                lines.extend(job.steps.iter().enumerate().map(|(i, step)| format!("    {}. {step}", i + 1)));
+                lines
+            }),
+            Panel::Tools => self.snapshot.tools.get(selected).map_or_else(Vec::new, |tool| {
+                let mut lines = vec![
+                    format!("Tool {} ({})", tool.tool_id, tool.state()),
+                    format!("  Runs: {}, errors: {}, mean {}ms", tool.runs, tool.errors, tool.mean_ms),
+                    "  Recent executions:".to_string(),
+                ];
+                lines.extend(tool.recent.iter().map(|record| {
+                    let mut line = format!(
+                        "    {} {} v{} {:?} {}ms",
+                        record.started_at.format("%Y-%m-%d %H:%M:%S"),
+                        record.capability,
+                        record.capability_version,
+                        record.status,
+                        record.duration_ms
+                    );
+                    if let Some(error) = &record.error_message {
+                        line.push_str(&format!(": {error}"));
+                    }
+                    line
+                }));
+                lines
+            }),
+            Panel::Alerts => self.snapshot.alerts.get(selected).map_or_else(Vec::new, |alert| {
+                let mut lines = vec![
+                    format!("Alert {} ({:?})", alert.fingerprint, alert.severity),
+                    format!("  {}", alert.message),
+                    format!("  Fired {} time(s), first {}", alert.count, alert.first_seen.to_rfc3339()),
+                    format!("  Last fired: {}", alert.last_seen.to_rfc3339()),
+                ];
+                if let Some(ack) = &alert.acknowledgment {
+                    lines.push(format!("  Acknowledged by {} at {}", ack.by, ack.at.to_rfc3339()));
+                }
+                lines.push("  Labels:".to_string());
+                lines.extend(alert.labels.iter().map(|(name, value)| format!("    {name}={value}")));
+                lines
+            }),
+        }
+    }
+}
+
+/// `n` with the singular or plural noun, e.g. `1 job open`
+fn count(n: usize, one: &str, other: &str) -> String {
+    format!("{n} {}", if n == 1 { one } else { other })
+}
+
+/// `text` cut or padded to exactly `width` columns
+fn fit(text: &str, width: usize) -> String {
+    let mut fitted: String = text.chars().take(width).collect();
+    let len = fitted.chars().count();
+    fitted.extend(std::iter::repeat_n(' ', width - len));
+    fitted
+}
+
+/// Bar of the CPU graph for a usage in percent
+fn spark(percent: f64) -> char {
+    let level = (percent.clamp(0.0, 100.0) / 100.0 * (SPARKS.len() - 1) as f64).round() as usize;
+    SPARKS[level]
+}
+
+/// Byte count with a binary unit
+fn bytes(bytes: u64) -> String {
+    const UNITS: [&str; 5] = ["B", "KiB", "MiB", "GiB", "TiB"];
+    let mut value = bytes as f64;
+    let mut unit = 0;
+    while value >= 1024.0 && unit < UNITS.len() - 1 {
+        value /= 1024.0;
+        unit += 1;
+    }
+    if unit == 0 {
+        format!("{bytes} B")
+    } else {
+        format!("{value:.1} {}", UNITS[unit])
+    }
+}
+
+/// Duration as `1d02:03:04` or `02:03:04`
+fn elapsed(secs: u64) -> String {
+    let (days, rest) = (secs / 86_400, secs % 86_400);
+    let clock = format!("{:02}:{:02}:{:02}", rest / 3600, rest % 3600 / 60, rest % 60);
+    if days > 0 {
+        format!("{days}d{clock}")
+    } else {
+        clock
+    }
+}
+
+/// Seconds since `at`
+fn age_secs(at: DateTime<Utc>) -> u64 {
+    u64::try_from((Utc::now() - at).num_seconds()).unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn process(pid: u32) -> ProcessRow {
+        ProcessRow {
+            pid,
+            command: format!("squirrel bio stats sample{pid}.fastq"),
+            cpu_percent: 12.5,
+            memory: 48 * 1024 * 1024,
+            run_secs: 3725,
+        }
+    }
+
+    #[test]
+    fn test_navigation_and_drill_down() {
+        let mut dashboard = Dashboard::new(Duration::from_secs(2));
+        dashboard.apply(TopEvent::Processes(vec![process(10), process(11), process(12)]));
+        assert_eq!(dashboard.apply(TopEvent::Key(Key::ArrowDown)), Control::Continue);
+        dashboard.apply(TopEvent::Key(Key::Enter));
+        assert!(dashboard.in_detail());
+        let screen = dashboard.render(80, 20).join("\n");
+        assert!(screen.contains("Process 11"), "{screen}");
+        assert!(screen.contains("Running for: 01:02:05"), "{screen}");
+
+        // Panels cannot be switched from a detail view
+        dashboard.apply(TopEvent::Key(Key::Tab));
+        assert_eq!(dashboard.focus(), Panel::Commands);
+        dashboard.apply(TopEvent::Key(Key::Escape));
+        dashboard.apply(TopEvent::Key(Key::Tab));
+        assert_eq!(dashboard.focus(), Panel::Jobs);
+        dashboard.apply(TopEvent::Key(Key::Char('4')));
+        assert_eq!(dashboard.focus(), Panel::Alerts);
+        // Nothing to drill into
+        dashboard.apply(TopEvent::Key(Key::Enter));
+        assert!(!dashboard.in_detail());
+
+        // The selection follows rows that go away
+        dashboard.apply(TopEvent::Key(Key::Char('1')));
+        dashboard.apply(TopEvent::Key(Key::End));
+        dashboard.apply(TopEvent::Processes(vec![process(10)]));
+        dashboard.apply(TopEvent::Key(Key::Enter));
+        assert!(dashboard.render(80, 20).join("\n").contains("Process 10"));
+        assert_eq!(dashboard.apply(TopEvent::Key(Key::Char('q'))), Control::Quit);
+    }
+
+    #[test]
+    fn test_render_fits_the_terminal() {
+        let mut dashboard = Dashboard::new(Duration::from_secs(1));
+        dashboard.apply(TopEvent::Resources(ResourceSample {
+            cpu_percent: 50.0,
+            memory_used: 3 * 1024 * 1024 * 1024,
+            memory_total: 16 * 1024 * 1024 * 1024,
+            load: [0.5, 0.25, 0.1],
+            disk_percent: 42.0,
+        }));
+        dashboard.apply(TopEvent::Processes((0..30).map(process).collect()));
+        dashboard.apply(TopEvent::SourceStatus("alerts", Some("Cannot read alerts.json".to_string())));
+        let screen = dashboard.render(60, 24);
+        assert_eq!(screen.len(), 24);
+        assert!(screen.iter().all(|line| console::measure_text_width(line) <= 60));
+        let text = screen.join("\n");
+        assert!(text.contains("mem 3.0 GiB/16.0 GiB"), "{text}");
+        assert!(text.contains("! alerts: Cannot read alerts.json"), "{text}");
+        assert!(text.contains("Commands (30)"));
+
+        // Paused dashboards keep what they show
+        dashboard.apply(TopEvent::Key(Key::Char('p')));
+        dashboard.apply(TopEvent::Processes(Vec::new()));
+        assert_eq!(dashboard.snapshot().processes.len(), 30);
+        assert!(dashboard.render(120, 24)[0].contains("[paused]"));
+    }
+
+    #[test]
+    fn test_accessible_lines_read_as_sentences() {
+        let mut dashboard = Dashboard::new(Duration::from_secs(1));
+        dashboard.apply(TopEvent::Processes(vec![process(10)]));
+        dashboard.apply(TopEvent::SourceStatus("alerts", Some("Cannot read alerts.json".to_string())));
+        assert_eq!(
+            dashboard.summary(),
+            "1 command running, 0 jobs open, 0 of 0 tools failing, 0 alerts firing; \
+             alerts unavailable: Cannot read alerts.json"
+        );
+        let lines = dashboard.lines();
+        assert_eq!(lines.len(), 2);
+        assert_eq!(
+            lines[1],
+            "Command 10: squirrel bio stats sample10.fastq; CPU 12.5 percent, memory 48.0 MiB, running 1 h 2 min 5 s"
+        );
+    }
+}
@@ -0,0 +1,327 @@
+//! Data sources of the dashboard
+//!
+//! Each source reads state that the server, the agent and the CLI already
+//! keep: host usage through the monitoring resource sampler, Squirrel
+//! processes from the process table, running jobs from the MCP persistence
+//! data directory, tool executions from the execution history and alerts
+//! from the alert lifecycle state file. A [`Sources`] polls all of them and
+//! publishes what it reads as [`TopEvent`]s.
+
+use std::path::PathBuf;
+use std::sync::mpsc::Sender;
+use std::thread::JoinHandle;
+use std::time::Duration;
+
+use serde_json::Value;
+use squirrel_mcp::persistence::{MCPPersistence, PersistenceConfig};
+use squirrel_mcp::tool::{ExecutionHistory, DEFAULT_HISTORY_CAPACITY};
+use squirrel_mcp::Context;
+use squirrel_monitoring::alerts::{AlertLifecycle, LifecycleConfig};
+use squirrel_monitoring::metrics::resource::ResourceSampler;
+use squirrel_monitoring::metrics::Metric;
+use sysinfo::{ProcessRefreshKind, System};
+
+use super::{JobRow, ProcessRow, ResourceSample, ToolRow, TopEvent};
+
+/// Executables whose processes are shown as running commands
+const PROCESS_NAMES: [&str; 3] = ["squirrel", "web_server", "squirrel_agent"];
+
+/// Where the sources read from
+#[derive(Debug, Clone, Default)]
+pub struct SourceConfig {
+    /// MCP persistence data directory
+    pub data_dir: PathBuf,
+    /// Tool execution history file; none hides tools
+    pub tool_history: Option<PathBuf>,
+    /// Alert lifecycle state file; none hides alerts
+    pub alerts: Option<PathBuf>,
+}
+
+/// Reads every source of the dashboard
+#[derive(Debug)]
+pub struct Sources {
+    config: SourceConfig,
+    sampler: ResourceSampler,
+    system: System,
+}
+
+impl Sources {
+    /// Sources reading from the places in `config`
+    #[must_use]
+    pub fn new(config: SourceConfig) -> Self {
+        Self {
+            config,
+            sampler: ResourceSampler::new(),
+            system: System::new(),
+        }
+    }
+
+    /// Reads every source once
+    ///
+    /// Sources that cannot be read are reported with
+    /// [`TopEvent::SourceStatus`], so a broken file does not hide the rest.
+    pub fn poll(&mut self) -> Vec<TopEvent> {
+        let mut events = vec![
+            TopEvent::Resources(resource_sample(&self.sampler.sample())),
+            TopEvent::Processes(self.processes()),
+        ];
+
+        let persistence = MCPPersistence::new(PersistenceConfig {
+            data_dir: self.config.data_dir.clone(),
+            ..PersistenceConfig::default()
+        });
+        match persistence.load_contexts() {
+            Ok(contexts) => {
+                events.push(TopEvent::Jobs(job_rows(&contexts)));
+                events.push(TopEvent::SourceStatus("jobs", None));
+            }
+            Err(e) => events.push(TopEvent::SourceStatus("jobs", Some(e.to_string()))),
+        }
+
+        if let Some(path) = &self.config.tool_history {
+            match ExecutionHistory::load(path, DEFAULT_HISTORY_CAPACITY) {
+                Ok(history) => {
+                    events.push(TopEvent::Tools(tool_rows(&history)));
+                    events.push(TopEvent::SourceStatus("tools", None));
+                }
+                Err(e) => events.push(TopEvent::SourceStatus("tools", Some(e.to_string()))),
+            }
+        }
+
+        if let Some(path) = &self.config.alerts {
+            let config = LifecycleConfig {
+                state_path: Some(path.clone()),
+                ..LifecycleConfig::default()
+            };
+            match AlertLifecycle::open(config) {
+                Ok(lifecycle) => {
+                    events.push(TopEvent::Alerts(lifecycle.alerts()));
+                    events.push(TopEvent::SourceStatus("alerts", None));
+                }
+                Err(e) => events.push(TopEvent::SourceStatus("alerts", Some(e.to_string()))),
+            }
+        }
+        events
+    }
+
+    /// Polls every `interval` on a thread until `events` is closed
+    pub fn spawn(mut self, interval: Duration, events: Sender<TopEvent>) -> JoinHandle<()> {
+        std::thread::spawn(move || loop {
+            for event in self.poll() {
+                if events.send(event).is_err() {
+                    return;
+                }
+            }
+            std::thread::sleep(interval);
+        })
+    }
+
+    /// Squirrel processes other than this one, busiest first
+    fn processes(&mut self) -> Vec<ProcessRow> {
+        self.system
+            .refresh_processes_specifics(ProcessRefreshKind::new().with_cpu().with_memory());
+        let own = sysinfo::get_current_pid().ok();
+        let mut rows: Vec<ProcessRow> = self
+            .system
+            .processes()
+            .values()
+            .filter(|process| Some(process.pid()) != own && PROCESS_NAMES.contains(&process.name()))
+            .map(|process| ProcessRow {
+                pid: process.pid().as_u32(),
+                command: if process.cmd().is_empty() {
+                    process.name().to_string()
+                } else {
+                    process.cmd().join(" ")
+                },
+                cpu_percent: process.cpu_usage(),
+                memory: process.memory(),
+                run_secs: process.run_time(),
+            })
+            .collect();
+        rows.sort_by(|a, b| b.cpu_percent.total_cmp(&a.cpu_percent).then(a.pid.cmp(&b.pid)));
+        rows
+    }
+}
+
+/// Host usage from the gauges of a resource sample
+#[must_use]
+pub fn resource_sample(metrics: &[Metric]) -> ResourceSample {
+    let mut sample = ResourceSample::default();
+    for metric in metrics {
+        match metric.name.as_ref() {
+            "system_cpu_usage" => sample.cpu_percent = metric.value,
+            "system_memory_usage" => sample.memory_used = metric.value as u64,
+            "system_memory_total" => sample.memory_total = metric.value as u64,
+            "system_disk_usage_percent" => sample.disk_percent = sample.disk_percent.max(metric.value),
+            "system_load_average" => {
+                let index = match metric.labels.get("period").map(String::as_str) {
+                    Some("1m") => 0,
+                    Some("5m") => 1,
+                    Some("15m") => 2,
+                    _ => continue,
+                };
+                sample.load[index] = metric.value;
+            }
+            _ => {}
+        }
+    }
+    sample
+}
+
+/// Jobs whose contexts are open, most recently updated first
+///
+/// Job contexts carry the job ID and owner in their metadata; snapshots of
+/// finished jobs also carry their final state and are left out.
+#[must_use]
+pub fn job_rows(contexts: &[Context]) -> Vec<JobRow> {
+    let mut rows: Vec<JobRow> = contexts
+        .iter()
+        .filter_map(|context| {
+            let metadata = context.metadata.as_ref()?;
+            if metadata.get("state").is_some() {
+                return None;
+            }
+            let job_id = metadata.get("job_id")?.as_str()?.to_string();
+            let steps = context
+                .data
+                .get("steps")
+                .and_then(Value::as_array)
+                .map(|steps| {
+                    steps
+                        .iter()
+                        .filter_map(|step| step.get("command").and_then(Value::as_str))
+                        .map(str::to_string)
+                        .collect()
+                })
+                .unwrap_or_default();
+            Some(JobRow {
+                job_id,
+                name: context.name.clone(),
+                owner: metadata.get("owner").and_then(Value::as_str).map(str::to_string),
+                steps,
+                started_at: context.created_at,
+                updated_at: context.updated_at,
+            })
+        })
+        .collect();
+    rows.sort_by_key(|row| std::cmp::Reverse(row.updated_at));
+    rows
+}
+
+/// Tools with recorded executions, failing tools first
+#[must_use]
+pub fn tool_rows(history: &ExecutionHistory) -> Vec<ToolRow> {
+    let mut rows: Vec<ToolRow> = history
+        .tool_ids()
+        .into_iter()
+        .map(|tool_id| {
+            let recent = history.get(&tool_id);
+            let total_ms: u64 = recent.iter().map(|record| record.duration_ms).sum();
+            ToolRow {
+                runs: recent.len(),
+                errors: recent.iter().filter(|record| record.is_error()).count(),
+                mean_ms: total_ms / recent.len().max(1) as u64,
+                tool_id,
+                recent,
+            }
+        })
+        .collect();
+    rows.sort_by(|a, b| {
+        let failing = |row: &ToolRow| row.recent.first().is_some_and(|record| record.is_error());
+        failing(b).cmp(&failing(a)).then_with(|| a.tool_id.cmp(&b.tool_id))
+    });
+    rows
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::{Duration as ChronoDuration, Utc};
+    use serde_json::json;
+    use squirrel_mcp::tool::{ExecutionRecord, ExecutionStatus};
+    use squirrel_monitoring::metrics::MetricType;
+    use std::collections::HashMap;
+    use uuid::Uuid;
+
+    fn context(metadata: Option<Value>, data: Value, age_secs: i64) -> Context {
+        let at = Utc::now() - ChronoDuration::seconds(age_secs);
+        Context {
+            id: Uuid::new_v4(),
+            name: "job align".to_string(),
+            data,
+            metadata,
+            parent_id: None,
+            created_at: at,
+            updated_at: at,
+            expires_at: None,
+        }
+    }
+
+    #[test]
+    fn test_job_rows_skip_snapshots_and_other_contexts() {
+        let contexts = vec![
+            context(
+                Some(json!({ "job_id": "job-1", "owner": "alice" })),
+                json!({ "steps": [{ "command": "trim" }, { "command": "align" }] }),
+                60,
+            ),
+            context(Some(json!({ "job_id": "job-2", "owner": "bob" })), json!({ "steps": [] }), 5),
+            context(
+                Some(json!({ "job_id": "job-1", "owner": "alice", "state": "completed" })),
+                json!({ "steps": [] }),
+                1,
+            ),
+            context(None, json!({}), 1),
+        ];
+        let rows = job_rows(&contexts);
+        assert_eq!(rows.iter().map(|row| row.job_id.as_str()).collect::<Vec<_>>(), ["job-2", "job-1"]);
+        assert_eq!(rows[1].steps, ["trim", "align"]);
+        assert_eq!(rows[1].owner.as_deref(), Some("alice"));
+    }
+
+    #[test]
+    fn test_tool_rows_put_failing_tools_first() {
+        let record = |status, duration_ms| ExecutionRecord {
+            request_id: Uuid::new_v4().to_string(),
+            capability: "run".to_string(),
+            capability_version: 1,
+            status,
+            duration_ms,
+            error_message: None,
+            started_at: Utc::now(),
+        };
+        let mut history = ExecutionHistory::new(10);
+        history.record("aligner", record(ExecutionStatus::Success, 100));
+        history.record("aligner", record(ExecutionStatus::Success, 300));
+        history.record("trimmer", record(ExecutionStatus::Success, 10));
+        history.record("trimmer", record(ExecutionStatus::Timeout, 50));
+
+        let rows = tool_rows(&history);
+        assert_eq!(rows[0].tool_id, "trimmer");
+        assert_eq!((rows[0].runs, rows[0].errors, rows[0].state()), (2, 1, "timing out"));
+        assert_eq!((rows[1].mean_ms, rows[1].state()), (200, "ok"));
+    }
+
+    #[test]
+    fn test_resource_sample_from_gauges() {
+        let gauge = |name: &'static str, value: f64, labels: &[(&str, &str)]| {
+            let labels: HashMap<String, String> = labels
+                .iter()
+                .map(|(key, value)| (key.to_string(), value.to_string()))
+                .collect();
+            Metric::new(name, value, MetricType::Gauge, labels)
+        };
+        let sample = resource_sample(&[
+            gauge("system_cpu_usage", 37.5, &[]),
+            gauge("system_memory_usage", 1024.0, &[]),
+            gauge("system_memory_total", 4096.0, &[]),
+            gauge("system_load_average", 0.5, &[("period", "5m")]),
+            gauge("system_disk_usage_percent", 20.0, &[("mount", "/")]),
+            gauge("system_disk_usage_percent", 80.0, &[("mount", "/data")]),
+        ]);
+        assert_eq!(sample.cpu_percent, 37.5);
+        assert_eq!((sample.memory_used, sample.memory_total), (1024, 4096));
+        assert_eq!(sample.load, [0.0, 0.5, 0.0]);
+        assert_eq!(sample.disk_percent, 80.0);
+    }
+}
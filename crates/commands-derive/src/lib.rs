@@ -0,0 +1,354 @@
+//! Derive macros for declaring Squirrel commands
+//!
+//! `#[derive(Command)]` turns a struct with named fields into a command: it
+//! generates the argument schema, the clap parser and the `Command` trait
+//! implementation. The struct provides the behaviour through an inherent
+//! `fn run(&self) -> CommandResult<String>` method, which is called on a
+//! copy of the command holding the parsed arguments.
+//!
+//! ```ignore
+//! use squirrel_commands::{Command, CommandResult};
+//!
+//! /// Greets someone
+//! #[derive(Debug, Clone, Default, Command)]
+//! #[command(name = "greet")]
+//! struct GreetCommand {
+//!     /// Who to greet
+//!     name: String,
+//!     /// How many times
+//!     #[arg(short, long, default = "1")]
+//!     times: u32,
+//!     /// Greet loudly
+//!     #[arg(long)]
+//!     shout: bool,
+//! }
+//!
+//! impl GreetCommand {
+//!     fn run(&self) -> CommandResult<String> {
+//!         Ok(format!("Hello, {}!", self.name).repeat(self.times as usize))
+//!     }
+//! }
+//! ```
+//!
+//! Struct attributes (`#[command(...)]`):
+//! - `name = "..."`: command name, defaults to the kebab-cased struct name
+//!   without a `Command` suffix
+//! - `description = "..."`: defaults to the struct's doc comment
+//!
+//! Field attributes (`#[arg(...)]`):
+//! - `short` / `short = 'c'`, `long` / `long = "name"`: make the field a
+//!   named option; fields without either are positional
+//! - `help = "..."`: defaults to the field's doc comment
+//! - `default = "..."`: value used when the argument is omitted
+//! - `skip`: not an argument; copied from the registered command instance
+//!
+//! `bool` fields are flags, `Option<T>` fields are optional and `Vec<T>`
+//! fields take several values. All other types must implement `FromStr`.
+
+use proc_macro::TokenStream;
+use proc_macro2::TokenStream as TokenStream2;
+use quote::quote;
+use syn::{
+    parse_macro_input, Attribute, Data, DeriveInput, Expr, ExprLit, Fields, GenericArgument, Ident, Lit, LitChar,
+    LitStr, PathArguments, Type,
+};
+
+/// Derives `Command` and `CommandArgs` for a struct; see the crate docs
+#[proc_macro_derive(Command, attributes(command, arg))]
+pub fn derive_command(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    expand(&input).unwrap_or_else(syn::Error::into_compile_error).into()
+}
+
+/// How a field is parsed
+enum FieldShape {
+    Flag,
+    Optional(Type),
+    Multiple(Type),
+    Single(Type),
+}
+
+/// A parsed struct field
+struct ArgField {
+    ident: Ident,
+    shape: FieldShape,
+    value_type: String,
+    help: String,
+    short: Option<char>,
+    long: Option<String>,
+    default: Option<String>,
+    skip: bool,
+}
+
+fn expand(input: &DeriveInput) -> syn::Result<TokenStream2> {
+    let fields = match &input.data {
+        Data::Struct(data) => match &data.fields {
+            Fields::Named(fields) => &fields.named,
+            _ => return Err(syn::Error::new_spanned(input, "Command can only be derived for structs with named fields")),
+        },
+        _ => return Err(syn::Error::new_spanned(input, "Command can only be derived for structs")),
+    };
+    if !input.generics.params.is_empty() {
+        return Err(syn::Error::new_spanned(&input.generics, "Command cannot be derived for generic structs"));
+    }
+
+    let ident = &input.ident;
+    let mut name = default_command_name(&ident.to_string());
+    let mut description = doc_comment(&input.attrs);
+    for attr in input.attrs.iter().filter(|a| a.path().is_ident("command")) {
+        attr.parse_nested_meta(|meta| {
+            if meta.path.is_ident("name") {
+                name = meta.value()?.parse::<LitStr>()?.value();
+            } else if meta.path.is_ident("description") {
+                description = meta.value()?.parse::<LitStr>()?.value();
+            } else {
+                return Err(meta.error("unknown command attribute"));
+            }
+            Ok(())
+        })?;
+    }
+
+    let fields = fields.iter().map(parse_field).collect::<syn::Result<Vec<_>>>()?;
+    let arg_schemas = fields.iter().filter(|f| !f.skip).map(arg_schema);
+    let from_self = struct_literal(&fields, |ident| quote! { ::std::clone::Clone::clone(&self.#ident) });
+    // Without a command instance, skipped fields fall back to `Default`
+    let from_defaults = struct_literal(&fields, |_| quote! { ::std::default::Default::default() });
+
+    Ok(quote! {
+        impl ::squirrel_commands::schema::CommandArgs for #ident {
+            fn schema() -> ::squirrel_commands::schema::CommandSchema {
+                ::squirrel_commands::schema::CommandSchema {
+                    name: #name,
+                    description: #description,
+                    args: ::std::vec![#(#arg_schemas),*],
+                }
+            }
+
+            fn from_matches(matches: &::squirrel_commands::schema::ArgMatches) -> ::squirrel_commands::CommandResult<Self> {
+                ::std::result::Result::Ok(#from_defaults)
+            }
+        }
+
+        impl ::squirrel_commands::Command for #ident {
+            fn name(&self) -> &str {
+                #name
+            }
+
+            fn description(&self) -> &str {
+                #description
+            }
+
+            fn execute(&self, args: &[::std::string::String]) -> ::squirrel_commands::CommandResult<::std::string::String> {
+                let matches = <Self as ::squirrel_commands::schema::CommandArgs>::schema().parse(args)?;
+                let matches = &matches;
+                let parsed: Self = #from_self;
+                parsed.run()
+            }
+
+            fn parser(&self) -> ::squirrel_commands::schema::ClapCommand {
+                <Self as ::squirrel_commands::schema::CommandArgs>::schema().to_clap()
+            }
+
+            fn clone_box(&self) -> ::std::boxed::Box<dyn ::squirrel_commands::Command> {
+                ::std::boxed::Box::new(::std::clone::Clone::clone(self))
+            }
+        }
+    })
+}
+
+/// Generates `Self { .. }` from `matches`, using `skipped` for skipped fields
+fn struct_literal(fields: &[ArgField], skipped: impl Fn(&Ident) -> TokenStream2) -> TokenStream2 {
+    let values = fields.iter().map(|field| {
+        let ident = &field.ident;
+        let value = if field.skip { skipped(ident) } else { field_value(field) };
+        quote! { #ident: #value }
+    });
+    quote! { Self { #(#values,)* } }
+}
+
+fn parse_field(field: &syn::Field) -> syn::Result<ArgField> {
+    let ident = field.ident.clone().expect("named field");
+    let ty = &field.ty;
+    let mut arg = ArgField {
+        shape: field_shape(ty),
+        value_type: quote!(#ty).to_string().replace(' ', ""),
+        help: doc_comment(&field.attrs),
+        short: None,
+        long: None,
+        default: None,
+        skip: false,
+        ident,
+    };
+
+    for attr in field.attrs.iter().filter(|a| a.path().is_ident("arg")) {
+        attr.parse_nested_meta(|meta| {
+            if meta.path.is_ident("short") {
+                arg.short = Some(if meta.input.peek(syn::Token![=]) {
+                    meta.value()?.parse::<LitChar>()?.value()
+                } else {
+                    arg.ident.to_string().chars().next().unwrap_or('x')
+                });
+            } else if meta.path.is_ident("long") {
+                arg.long = Some(if meta.input.peek(syn::Token![=]) {
+                    meta.value()?.parse::<LitStr>()?.value()
+                } else {
+                    arg.ident.to_string().replace('_', "-")
+                });
+            } else if meta.path.is_ident("help") {
+                arg.help = meta.value()?.parse::<LitStr>()?.value();
+            } else if meta.path.is_ident("default") {
+                arg.default = Some(meta.value()?.parse::<LitStr>()?.value());
+            } else if meta.path.is_ident("skip") {
+                arg.skip = true;
+            } else {
+                return Err(meta.error("unknown arg attribute"));
+            }
+            Ok(())
+        })?;
+    }
+
+    if matches!(arg.shape, FieldShape::Flag) && arg.short.is_none() && arg.long.is_none() {
+        arg.long = Some(arg.ident.to_string().replace('_', "-"));
+    }
+    if matches!(arg.shape, FieldShape::Flag) && arg.default.is_some() {
+        return Err(syn::Error::new_spanned(&field.ty, "flags cannot have a default value"));
+    }
+    Ok(arg)
+}
+
+/// Generates the `ArgSchema` literal for a field
+fn arg_schema(field: &ArgField) -> TokenStream2 {
+    let name = field.ident.to_string();
+    let named = field.short.is_some() || field.long.is_some();
+    let kind = match field.shape {
+        FieldShape::Flag => quote!(Flag),
+        _ if named => quote!(Option),
+        _ => quote!(Positional),
+    };
+    let required = matches!(field.shape, FieldShape::Single(_)) && field.default.is_none();
+    let multiple = matches!(field.shape, FieldShape::Multiple(_));
+    let value_type = &field.value_type;
+    let help = &field.help;
+    let short = option_tokens(field.short.map(|c| quote!(#c)));
+    let long = option_tokens(field.long.as_ref().map(|l| quote!(#l)));
+    let default = option_tokens(field.default.as_ref().map(|d| quote!(#d)));
+
+    quote! {
+        ::squirrel_commands::schema::ArgSchema {
+            name: #name,
+            kind: ::squirrel_commands::schema::ArgKind::#kind,
+            value_type: #value_type,
+            help: #help,
+            required: #required,
+            multiple: #multiple,
+            short: #short,
+            long: #long,
+            default: #default,
+        }
+    }
+}
+
+/// Generates the expression reading a field's value from `matches`
+fn field_value(field: &ArgField) -> TokenStream2 {
+    let name = field.ident.to_string();
+    let parse = |ty: &Type| quote! { ::squirrel_commands::schema::parse_value::<#ty>(#name, value) };
+    match &field.shape {
+        FieldShape::Flag => quote! { matches.get_flag(#name) },
+        FieldShape::Optional(ty) => {
+            let parse = parse(ty);
+            quote! {
+                matches
+                    .get_one::<::std::string::String>(#name)
+                    .map(|value| #parse)
+                    .transpose()?
+            }
+        }
+        FieldShape::Multiple(ty) => {
+            let parse = parse(ty);
+            quote! {
+                matches
+                    .get_many::<::std::string::String>(#name)
+                    .into_iter()
+                    .flatten()
+                    .map(|value| #parse)
+                    .collect::<::squirrel_commands::CommandResult<::std::vec::Vec<#ty>>>()?
+            }
+        }
+        FieldShape::Single(ty) => {
+            let parse = parse(ty);
+            quote! {
+                {
+                    let value = matches.get_one::<::std::string::String>(#name).ok_or_else(|| {
+                        ::squirrel_commands::CommandError::ValidationError(
+                            ::std::format!("Missing required argument '{}'", #name),
+                        )
+                    })?;
+                    #parse?
+                }
+            }
+        }
+    }
+}
+
+fn option_tokens(value: Option<TokenStream2>) -> TokenStream2 {
+    match value {
+        Some(value) => quote!(::std::option::Option::Some(#value)),
+        None => quote!(::std::option::Option::None),
+    }
+}
+
+/// Classifies a field type by its outermost wrapper
+fn field_shape(ty: &Type) -> FieldShape {
+    if let Type::Path(path) = ty {
+        if let Some(segment) = path.path.segments.last() {
+            if segment.ident == "bool" && segment.arguments.is_empty() {
+                return FieldShape::Flag;
+            }
+            if let PathArguments::AngleBracketed(args) = &segment.arguments {
+                if let Some(GenericArgument::Type(inner)) = args.args.first() {
+                    if segment.ident == "Option" {
+                        return FieldShape::Optional(inner.clone());
+                    }
+                    if segment.ident == "Vec" {
+                        return FieldShape::Multiple(inner.clone());
+                    }
+                }
+            }
+        }
+    }
+    FieldShape::Single(ty.clone())
+}
+
+/// Joins `///` doc comment lines into a single sentence
+fn doc_comment(attrs: &[Attribute]) -> String {
+    attrs
+        .iter()
+        .filter(|attr| attr.path().is_ident("doc"))
+        .filter_map(|attr| match &attr.meta {
+            syn::Meta::NameValue(nv) => match &nv.value {
+                Expr::Lit(ExprLit { lit: Lit::Str(s), .. }) => Some(s.value().trim().to_string()),
+                _ => None,
+            },
+            _ => None,
+        })
+        .filter(|line| !line.is_empty())
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// `GreetCommand` -> `greet`, `ListJobs` -> `list-jobs`
+fn default_command_name(ident: &str) -> String {
+    let base = ident.strip_suffix("Command").filter(|b| !b.is_empty()).unwrap_or(ident);
+    let mut name = String::new();
+    for (i, c) in base.chars().enumerate() {
+        if c.is_uppercase() {
+            if i > 0 {
+                name.push('-');
+            }
+            name.extend(c.to_lowercase());
+        } else {
+            name.push(c);
+        }
+    }
+    name
+}
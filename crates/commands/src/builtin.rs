@@ -12,6 +12,7 @@ use clap::{Arg, ArgAction, Command as ClapCommand};
 use std::time::{UNIX_EPOCH, SystemTime};
 
 use crate::history::{CommandHistory, HistoryEntry};
+use squirrel_core::vfs::Vfs;
 
 /// Command for displaying help information
 ///
@@ -603,6 +604,84 @@ impl Command for HistoryCommand {
     }
 }
 
+/// Command for reading files through virtual roots
+///
+/// Paths are virtual (`workspace://notes.txt`), so the command can only reach
+/// the directories mounted in its [`Vfs`].
+pub struct FilesCommand {
+    /// Virtual filesystem the command reads from
+    vfs: Arc<Vfs>,
+}
+
+impl FilesCommand {
+    /// Creates a new files command reading from `vfs`
+    #[must_use] pub fn new(vfs: Arc<Vfs>) -> Self {
+        debug!("FilesCommand: Creating new instance");
+        Self { vfs }
+    }
+}
+
+impl Command for FilesCommand {
+    fn name(&self) -> &str {
+        "files"
+    }
+    
+    fn description(&self) -> &str {
+        "Lists and reads files in the workspace, data and tmp roots"
+    }
+    
+    fn execute(&self, args: &[String]) -> CommandResult<String> {
+        debug!("FilesCommand: Executing with args: {:?}", args);
+        
+        let matches = self.parser().try_get_matches_from(
+            std::iter::once(self.name().to_string()).chain(args.iter().cloned())
+        ).map_err(|e| {
+            CommandError::ValidationError(format!("Invalid arguments: {}", e))
+        })?;
+        
+        let file_error = |e: squirrel_core::vfs::VfsError| CommandError::ExecutionError(e.to_string());
+        match matches.subcommand() {
+            Some(("list", sub)) => {
+                let path = sub.get_one::<String>("path").map_or("workspace://", String::as_str);
+                Ok(self.vfs.list(path).map_err(file_error)?.join("\n"))
+            }
+            Some(("read", sub)) => {
+                let path = sub.get_one::<String>("path").map_or("", String::as_str);
+                self.vfs.read_to_string(path).map_err(file_error)
+            }
+            _ => Ok(self
+                .vfs
+                .roots()
+                .map(|root| format!("{}://", root))
+                .collect::<Vec<_>>()
+                .join("\n")),
+        }
+    }
+    
+    fn parser(&self) -> ClapCommand {
+        ClapCommand::new("files")
+            .about("Lists and reads files in the workspace, data and tmp roots")
+            .subcommand(ClapCommand::new("roots")
+                .about("Lists the mounted roots"))
+            .subcommand(ClapCommand::new("list")
+                .about("Lists a directory")
+                .arg(Arg::new("path")
+                    .help("Virtual path such as workspace://results")
+                    .required(false)))
+            .subcommand(ClapCommand::new("read")
+                .about("Prints a file")
+                .arg(Arg::new("path")
+                    .help("Virtual path such as data://samples.csv")
+                    .required(true)))
+    }
+    
+    fn clone_box(&self) -> Box<dyn Command> {
+        Box::new(Self {
+            vfs: Arc::clone(&self.vfs),
+        })
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -643,6 +722,21 @@ mod tests {
         assert_eq!(result.unwrap(), "Exiting application");
     }
     
+    #[test]
+    fn test_files_command() {
+        let dir = tempfile::tempdir().unwrap();
+        let vfs = Vfs::standard(dir.path(), dir.path().join("data"));
+        vfs.write("workspace://notes.txt", "hello").unwrap();
+        let cmd = FilesCommand::new(Arc::new(vfs));
+        let run = |args: &[&str]| cmd.execute(&args.iter().map(ToString::to_string).collect::<Vec<_>>());
+        
+        assert_eq!(run(&["read", "workspace://notes.txt"]).unwrap(), "hello");
+        assert_eq!(run(&["list"]).unwrap(), "notes.txt");
+        assert!(run(&[]).unwrap().contains("data://"));
+        assert!(run(&["read", "workspace://../etc/passwd"]).is_err());
+        assert!(run(&["read", "/etc/passwd"]).is_err());
+    }
+    
     #[test]
     fn test_kill_command() {
         let cmd = KillCommand::new();
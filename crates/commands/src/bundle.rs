@@ -0,0 +1,562 @@
+//! Portable bundles of command runs
+//!
+//! A [`JobBundle`] packs a run recorded in a [`RunManifest`] together with
+//! the input files it hashed and the output it produced, so that another
+//! Squirrel instance can recreate and replay it. Bundles are gzip-compressed
+//! tar archives that standard tools can list and extract:
+//!
+//! - `bundle.json`: the [`JobBundle`] in a [wire envelope](crate::wire::Envelope)
+//! - `inputs/<sha256>`: the content of each input file, by hash
+//!
+//! Input paths are stored relative to the workspace the bundle was exported
+//! from. On import they are placed under the importing workspace, and paths
+//! outside the exported workspace must be mapped explicitly. Arguments naming
+//! inputs or workspace paths are rewritten the same way, and the manifest
+//! written next to the inputs can be passed to `squirrel replay`. Bundles
+//! are not trusted: input paths naming a parent directory and manifest IDs
+//! that are not plain file names are refused before anything is written.
+
+use std::collections::BTreeMap;
+use std::fs;
+use std::io::{self, Read, Write};
+use std::path::{Component, Path, PathBuf};
+
+use chrono::{DateTime, Utc};
+use flate2::read::GzDecoder;
+use flate2::write::GzEncoder;
+use flate2::Compression;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+use crate::manifest::{InputFile, RunManifest, MANIFEST_VERSION};
+use crate::wire::{JsonFormat, SchemaRegistry, SchemaVersion, WireFormat, WireSchema};
+use crate::{CommandError, CommandResult};
+
+/// Archive entry holding the bundle description
+pub const BUNDLE_ENTRY: &str = "bundle.json";
+
+/// Directory of the input files in the archive
+const INPUTS_DIR: &str = "inputs/";
+
+/// Size of a tar block
+const BLOCK: usize = 512;
+
+/// An input file in a bundle
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct BundledInput {
+    /// Path relative to the exported workspace, or absolute if it was outside
+    pub path: PathBuf,
+    /// Path as recorded in the manifest
+    pub recorded_path: PathBuf,
+    /// Hex-encoded SHA-256 of the content
+    pub sha256: String,
+    /// Size in bytes
+    pub size: u64,
+}
+
+/// A command run packed for another Squirrel instance
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct JobBundle {
+    /// Name of the job
+    pub name: String,
+    /// When the bundle was exported
+    pub exported_at: DateTime<Utc>,
+    /// Squirrel version that exported the bundle
+    pub squirrel_version: String,
+    /// Workspace the input paths are relative to
+    pub workspace: PathBuf,
+    /// The run: command, arguments, environment and outcome
+    pub manifest: RunManifest,
+    /// Input files, in the order of the manifest
+    pub inputs: Vec<BundledInput>,
+    /// Output of the run, if it was exported with it
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub output: Option<String>,
+}
+
+impl WireSchema for JobBundle {
+    const NAME: &'static str = "squirrel.job_bundle";
+    const VERSION: SchemaVersion = SchemaVersion::new(1, 0);
+}
+
+/// How to export a run
+#[derive(Debug, Clone, Default)]
+pub struct ExportOptions {
+    /// Job name; defaults to the command name
+    pub name: Option<String>,
+    /// Workspace input paths are made relative to; relative input paths in
+    /// the manifest are resolved against it
+    pub workspace: PathBuf,
+    /// Output of the run, checked against the recorded outcome
+    pub output: Option<String>,
+}
+
+/// How to import a bundle
+#[derive(Debug, Clone, Default)]
+pub struct ImportOptions {
+    /// Workspace the inputs and the manifest are written to
+    pub workspace: PathBuf,
+    /// Path prefixes to replace, for inputs outside the exported workspace
+    pub mappings: Vec<(PathBuf, PathBuf)>,
+    /// Import bundles from incompatible Squirrel versions, with a warning
+    pub allow_version_mismatch: bool,
+    /// Replace existing files whose content differs
+    pub overwrite: bool,
+}
+
+/// What an import wrote
+#[derive(Debug, Clone, Serialize)]
+pub struct ImportReport {
+    /// Name of the job
+    pub name: String,
+    /// The run, with paths mapped into the workspace
+    pub manifest: RunManifest,
+    /// Where the manifest was written
+    pub manifest_path: PathBuf,
+    /// Where the inputs were written
+    pub inputs: Vec<PathBuf>,
+    /// Where the recorded output was written, if the bundle had one
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub output_path: Option<PathBuf>,
+    /// Problems that did not stop the import
+    pub warnings: Vec<String>,
+}
+
+/// Writes a bundle of the run in `manifest` to `writer`
+///
+/// Every input is hashed again and must still match the manifest, and an
+/// output must match the recorded outcome, so the bundle reproduces the run
+/// that was recorded.
+///
+/// # Errors
+/// Returns a validation error if an input or the output no longer matches
+/// the manifest, or a resource error if a file cannot be read or the
+/// archive cannot be written
+pub fn export(manifest: &RunManifest, options: &ExportOptions, writer: impl Write) -> CommandResult<JobBundle> {
+    let mut files = Vec::new();
+    let mut inputs = Vec::new();
+    for input in &manifest.inputs {
+        let source = options.workspace.join(&input.path);
+        let current = InputFile::hash(&source)?;
+        if current.sha256 != input.sha256 {
+            return Err(CommandError::ValidationError(format!(
+                "Input {} changed since the run was recorded",
+                input.path.display()
+            )));
+        }
+        let path = source.strip_prefix(&options.workspace).map_or_else(|_| source.clone(), Path::to_path_buf);
+        inputs.push(BundledInput {
+            path,
+            recorded_path: input.path.clone(),
+            sha256: input.sha256.clone(),
+            size: input.size,
+        });
+        files.push((source, input.sha256.clone(), input.size));
+    }
+
+    let output = match (&options.output, &manifest.outcome) {
+        (Some(output), Some(outcome)) => {
+            // Output saved from the terminal ends with an extra newline
+            let matches = |text: &str| hex::encode(Sha256::digest(text.as_bytes())) == outcome.output_sha256;
+            let recorded = [output.as_str(), output.strip_suffix('\n').unwrap_or(output)]
+                .into_iter()
+                .find(|text| matches(text))
+                .ok_or_else(|| CommandError::ValidationError("The output does not belong to this run".to_string()))?;
+            Some(recorded.to_string())
+        }
+        (output, _) => output.clone(),
+    };
+
+    let bundle = JobBundle {
+        name: options.name.clone().unwrap_or_else(|| manifest.command.clone()),
+        exported_at: Utc::now(),
+        squirrel_version: env!("CARGO_PKG_VERSION").to_string(),
+        workspace: options.workspace.clone(),
+        manifest: manifest.clone(),
+        inputs,
+        output,
+    };
+    let mut registry = SchemaRegistry::new();
+    registry.register::<JobBundle>();
+    let description = JsonFormat.encode(&registry.seal(&bundle)?)?;
+
+    let write_error = |e: io::Error| CommandError::ResourceError(format!("Cannot write bundle: {e}"));
+    let mtime = u64::try_from(bundle.exported_at.timestamp()).unwrap_or_default();
+    let mut archive = GzEncoder::new(writer, Compression::default());
+    write_entry(&mut archive, BUNDLE_ENTRY, description.len() as u64, mtime, &mut description.as_slice())
+        .map_err(write_error)?;
+    let mut written = Vec::new();
+    for (source, sha256, size) in files {
+        if written.contains(&sha256) {
+            continue;
+        }
+        let mut file = fs::File::open(&source)
+            .map_err(|e| CommandError::ResourceError(format!("Cannot read input {}: {e}", source.display())))?;
+        write_entry(&mut archive, &format!("{INPUTS_DIR}{sha256}"), size, mtime, &mut file).map_err(write_error)?;
+        written.push(sha256);
+    }
+    archive.write_all(&[0; 2 * BLOCK]).map_err(write_error)?;
+    archive.finish().map_err(write_error)?;
+    Ok(bundle)
+}
+
+/// Reads the description of the bundle in `reader` without its files
+///
+/// # Errors
+/// Returns a validation error if the archive is not a bundle this build can
+/// read, or a resource error if it cannot be read
+pub fn inspect(reader: impl Read) -> CommandResult<JobBundle> {
+    read_description(&mut GzDecoder::new(reader))
+}
+
+/// Recreates the run in the bundle read from `reader` in a workspace
+///
+/// Inputs are written under [`ImportOptions::workspace`] and verified
+/// against their hashes, the manifest is written there as
+/// `<manifest id>.manifest.json` with paths mapped into the workspace, and
+/// the recorded output as `<manifest id>.output`. Nothing is written unless
+/// every input can be placed and checked.
+///
+/// # Errors
+/// Returns a validation error if the bundle comes from an incompatible
+/// Squirrel version, an input path or the manifest ID would escape the
+/// workspace, an input is outside the exported workspace and not mapped, a
+/// file would be replaced or an input is corrupt, or a resource
+/// error if the bundle cannot be read or a file cannot be written
+pub fn import(reader: impl Read, options: &ImportOptions) -> CommandResult<ImportReport> {
+    let mut archive = GzDecoder::new(reader);
+    let bundle = read_description(&mut archive)?;
+
+    let mut warnings = Vec::new();
+    let current = env!("CARGO_PKG_VERSION");
+    if !compatible_versions(&bundle.squirrel_version, current) {
+        let message = format!(
+            "The bundle was exported by Squirrel {}, which is not compatible with {current}",
+            bundle.squirrel_version
+        );
+        if !options.allow_version_mismatch {
+            return Err(CommandError::ValidationError(message));
+        }
+        warnings.push(message);
+    }
+    check_paths(&bundle)?;
+
+    // Place every input before writing anything
+    let mut targets: BTreeMap<String, Vec<PathBuf>> = BTreeMap::new();
+    let mut paths = Vec::new();
+    for input in &bundle.inputs {
+        let target = if input.path.is_absolute() {
+            map_path(&input.path, &options.mappings).ok_or_else(|| {
+                CommandError::ValidationError(format!(
+                    "Input {} is outside the exported workspace; map it with a path mapping",
+                    input.path.display()
+                ))
+            })?
+        } else {
+            options.workspace.join(&input.path)
+        };
+        if target.exists() && !options.overwrite && InputFile::hash(&target)?.sha256 != input.sha256 {
+            return Err(CommandError::ValidationError(format!(
+                "{} exists with other content",
+                target.display()
+            )));
+        }
+        targets.entry(input.sha256.clone()).or_default().push(target.clone());
+        paths.push(target);
+    }
+
+    // Stream each input to a temporary file next to its first target
+    let mut staged = Vec::new();
+    let result = stage_inputs(&mut archive, &bundle, &targets, &mut staged);
+    if let Err(err) = result {
+        for (temp, _) in &staged {
+            let _ = fs::remove_file(temp);
+        }
+        return Err(err);
+    }
+    for (temp, sha256) in staged {
+        let destinations = &targets[&sha256];
+        for destination in &destinations[1..] {
+            fs::copy(&temp, destination).map_err(|e| write_error(destination, &e))?;
+        }
+        fs::rename(&temp, &destinations[0]).map_err(|e| write_error(&destinations[0], &e))?;
+    }
+
+    let mut manifest = bundle.manifest.clone();
+    for (recorded, path) in manifest.inputs.iter_mut().zip(&paths) {
+        recorded.path.clone_from(path);
+    }
+    // Arguments naming an input as recorded now name where it was written,
+    // and other paths in the exported workspace are moved to this one
+    let mut mappings = vec![(bundle.workspace.clone(), options.workspace.clone())];
+    mappings.extend(options.mappings.iter().cloned());
+    for arg in &mut manifest.args {
+        let input = bundle.inputs.iter().position(|input| Path::new(arg.as_str()) == input.recorded_path);
+        let mapped = match input {
+            Some(index) => Some(paths[index].clone()),
+            None => map_path(Path::new(arg.as_str()), &mappings),
+        };
+        if let Some(mapped) = mapped {
+            *arg = mapped.display().to_string();
+        }
+    }
+
+    fs::create_dir_all(&options.workspace).map_err(|e| write_error(&options.workspace, &e))?;
+    let manifest_path = options.workspace.join(format!("{}.manifest.json", manifest.id));
+    manifest.save(&manifest_path)?;
+    let output_path = match &bundle.output {
+        Some(output) => {
+            let path = options.workspace.join(format!("{}.output", manifest.id));
+            fs::write(&path, output).map_err(|e| write_error(&path, &e))?;
+            Some(path)
+        }
+        None => None,
+    };
+    Ok(ImportReport {
+        name: bundle.name,
+        manifest,
+        manifest_path,
+        inputs: paths,
+        output_path,
+        warnings,
+    })
+}
+
+/// Writes `entries`, pairs of a name and the data, as a gzip-compressed
+/// tar archive like a bundle's
+///
+/// # Errors
+/// Returns an error if an entry name is longer than 100 bytes or the
+/// archive cannot be written
+pub fn write_archive(writer: impl Write, entries: &[(String, Vec<u8>)], mtime: u64) -> io::Result<()> {
+    let mut archive = GzEncoder::new(writer, Compression::default());
+    for (name, data) in entries {
+        write_entry(&mut archive, name, data.len() as u64, mtime, &mut data.as_slice())?;
+    }
+    archive.write_all(&[0; 2 * BLOCK])?;
+    archive.finish()?;
+    Ok(())
+}
+
+/// Reads the entries of a gzip-compressed tar archive written by
+/// [`write_archive`], pairs of a name and the data
+///
+/// # Errors
+/// Returns a validation error if the archive is invalid, or a resource error
+/// if it cannot be read
+pub fn read_archive(reader: impl Read) -> CommandResult<Vec<(String, Vec<u8>)>> {
+    let mut archive = GzDecoder::new(reader);
+    let mut entries = Vec::new();
+    while let Some((name, size)) = read_header(&mut archive)? {
+        let mut data = Vec::new();
+        copy_entry(&mut archive, size, &mut data)?;
+        entries.push((name, data));
+    }
+    Ok(entries)
+}
+
+/// Whether a bundle exported by Squirrel `exported` can be imported by
+/// `current`: the same major version, and the same minor version before 1.0
+#[must_use]
+pub fn compatible_versions(exported: &str, current: &str) -> bool {
+    let parse = |version: &str| -> Option<(u64, u64)> {
+        let mut parts = version.split('.');
+        Some((parts.next()?.parse().ok()?, parts.next()?.parse().ok()?))
+    };
+    match (parse(exported), parse(current)) {
+        (Some((major, minor)), Some((current_major, current_minor))) => {
+            major == current_major && (major > 0 || minor == current_minor)
+        }
+        _ => false,
+    }
+}
+
+/// Reads the first entry of an archive as the bundle description
+fn read_description(archive: &mut impl Read) -> CommandResult<JobBundle> {
+    let (name, size) = read_header(archive)?
+        .ok_or_else(|| CommandError::ValidationError("The bundle is empty".to_string()))?;
+    if name != BUNDLE_ENTRY {
+        return Err(CommandError::ValidationError(format!("Not a job bundle: starts with {name}")));
+    }
+    let mut description = Vec::new();
+    copy_entry(archive, size, &mut description)?;
+    let mut registry = SchemaRegistry::new();
+    registry.register::<JobBundle>();
+    let bundle: JobBundle = registry.open(JsonFormat.decode(&description)?)?;
+    if bundle.manifest.manifest_version > MANIFEST_VERSION {
+        return Err(CommandError::ValidationError(format!(
+            "Unsupported manifest version {}",
+            bundle.manifest.manifest_version
+        )));
+    }
+    Ok(bundle)
+}
+
+/// Refuses bundles whose input paths or manifest ID would place files
+/// outside the workspace or a mapped directory
+fn check_paths(bundle: &JobBundle) -> CommandResult<()> {
+    let id = &bundle.manifest.id;
+    let file_name = !id.is_empty()
+        && !id.contains(['/', '\\'])
+        && Path::new(id).components().eq([Component::Normal(id.as_ref())]);
+    if !file_name {
+        return Err(CommandError::ValidationError(format!("Invalid manifest ID '{id}' in the bundle")));
+    }
+    for input in &bundle.inputs {
+        // An absolute path may start with its root; everything else must be a plain name
+        let root = input.path.is_absolute();
+        let mut names = input
+            .path
+            .components()
+            .skip_while(|component| root && matches!(component, Component::Prefix(_) | Component::RootDir))
+            .peekable();
+        if names.peek().is_none() || !names.all(|component| matches!(component, Component::Normal(_))) {
+            return Err(CommandError::ValidationError(format!(
+                "Invalid input path '{}' in the bundle",
+                input.path.display()
+            )));
+        }
+    }
+    Ok(())
+}
+
+/// `path` with the first matching prefix replaced
+fn map_path(path: &Path, mappings: &[(PathBuf, PathBuf)]) -> Option<PathBuf> {
+    mappings.iter().find_map(|(from, to)| {
+        let rest = path.strip_prefix(from).ok()?;
+        Some(if rest.as_os_str().is_empty() { to.clone() } else { to.join(rest) })
+    })
+}
+
+/// Writes each bundled input to a temporary file, checking its hash
+fn stage_inputs(
+    archive: &mut impl Read,
+    bundle: &JobBundle,
+    targets: &BTreeMap<String, Vec<PathBuf>>,
+    staged: &mut Vec<(PathBuf, String)>,
+) -> CommandResult<()> {
+    while let Some((name, size)) = read_header(archive)? {
+        let Some(sha256) = name.strip_prefix(INPUTS_DIR).filter(|sha256| targets.contains_key(*sha256)) else {
+            copy_entry(archive, size, &mut io::sink())?;
+            continue;
+        };
+        let destination = &targets[sha256][0];
+        if let Some(parent) = destination.parent() {
+            fs::create_dir_all(parent).map_err(|e| write_error(parent, &e))?;
+        }
+        let temp = destination.with_extension("squirrel-import");
+        let mut file = fs::File::create(&temp).map_err(|e| write_error(&temp, &e))?;
+        staged.push((temp.clone(), sha256.to_string()));
+        let mut hashing = HashingWriter { inner: &mut file, hasher: Sha256::new() };
+        copy_entry(archive, size, &mut hashing)?;
+        if hex::encode(hashing.hasher.finalize()) != sha256 {
+            return Err(CommandError::ValidationError(format!("The bundled input {sha256} is corrupt")));
+        }
+    }
+    let missing: Vec<_> = bundle
+        .inputs
+        .iter()
+        .filter(|input| !staged.iter().any(|(_, sha256)| *sha256 == input.sha256))
+        .map(|input| input.path.display().to_string())
+        .collect();
+    if missing.is_empty() {
+        Ok(())
+    } else {
+        Err(CommandError::ValidationError(format!("The bundle lacks inputs: {}", missing.join(", "))))
+    }
+}
+
+/// Resource error for a file that cannot be written
+fn write_error(path: &Path, error: &io::Error) -> CommandError {
+    CommandError::ResourceError(format!("Cannot write {}: {error}", path.display()))
+}
+
+/// Passes writes through while hashing them
+struct HashingWriter<W> {
+    inner: W,
+    hasher: Sha256,
+}
+
+impl<W: Write> Write for HashingWriter<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let written = self.inner.write(buf)?;
+        self.hasher.update(&buf[..written]);
+        Ok(written)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+/// Writes a regular file entry in the ustar format
+fn write_entry(out: &mut impl Write, name: &str, size: u64, mtime: u64, data: &mut impl Read) -> io::Result<()> {
+    if name.len() > 100 {
+        return Err(io::Error::new(io::ErrorKind::InvalidInput, format!("Entry name too long: {name}")));
+    }
+    let mut header = [0u8; BLOCK];
+    header[..name.len()].copy_from_slice(name.as_bytes());
+    header[100..108].copy_from_slice(b"0000644\0");
+    header[108..116].copy_from_slice(b"0000000\0");
+    header[116..124].copy_from_slice(b"0000000\0");
+    header[124..136].copy_from_slice(format!("{size:011o}\0").as_bytes());
+    header[136..148].copy_from_slice(format!("{mtime:011o}\0").as_bytes());
+    header[148..156].copy_from_slice(b"        ");
+    header[156] = b'0';
+    header[257..263].copy_from_slice(b"ustar\0");
+    header[263..265].copy_from_slice(b"00");
+    let checksum: u32 = header.iter().map(|&byte| u32::from(byte)).sum();
+    header[148..156].copy_from_slice(format!("{checksum:06o}\0 ").as_bytes());
+    out.write_all(&header)?;
+
+    let copied = io::copy(&mut data.take(size), out)?;
+    if copied != size {
+        return Err(io::Error::new(io::ErrorKind::UnexpectedEof, format!("{name} shrank while being written")));
+    }
+    out.write_all(&vec![0; padding(size)])
+}
+
+/// Reads the next entry header: its name and size, or `None` at the end
+fn read_header(archive: &mut impl Read) -> CommandResult<Option<(String, u64)>> {
+    let invalid = |message: &str| CommandError::ValidationError(format!("Invalid bundle archive: {message}"));
+    let mut header = [0u8; BLOCK];
+    archive
+        .read_exact(&mut header)
+        .map_err(|e| invalid(&e.to_string()))?;
+    if header.iter().all(|&byte| byte == 0) {
+        return Ok(None);
+    }
+    let field = |range: std::ops::Range<usize>| {
+        let bytes = &header[range];
+        let end = bytes.iter().position(|&byte| byte == 0).unwrap_or(bytes.len());
+        String::from_utf8_lossy(&bytes[..end]).trim().to_string()
+    };
+    let recorded: u32 = u32::from_str_radix(&field(148..156), 8).map_err(|_| invalid("bad checksum"))?;
+    let checksum: u32 = header
+        .iter()
+        .enumerate()
+        .map(|(i, &byte)| if (148..156).contains(&i) { u32::from(b' ') } else { u32::from(byte) })
+        .sum();
+    if checksum != recorded {
+        return Err(invalid("header checksum mismatch"));
+    }
+    let size = u64::from_str_radix(&field(124..136), 8).map_err(|_| invalid("bad entry size"))?;
+    Ok(Some((field(0..100), size)))
+}
+
+/// Copies the data of an entry of `size` bytes and skips its padding
+fn copy_entry(archive: &mut impl Read, size: u64, out: &mut impl Write) -> CommandResult<()> {
+    let read_error = |e: io::Error| CommandError::ResourceError(format!("Cannot read bundle: {e}"));
+    let copied = io::copy(&mut archive.take(size), out).map_err(read_error)?;
+    if copied != size {
+        return Err(CommandError::ValidationError("Invalid bundle archive: truncated entry".to_string()));
+    }
+    let mut pad = vec![0; padding(size)];
+    archive.read_exact(&mut pad).map_err(read_error)
+}
+
+/// Zero bytes after an entry of `size` bytes, up to the next block
+fn padding(size: u64) -> usize {
+    let rest = (size % BLOCK as u64) as usize;
+    if rest == 0 { 0 } else { BLOCK - rest }
+}
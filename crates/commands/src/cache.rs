@@ -0,0 +1,292 @@
+//! Result caching for command runs
+//!
+//! A run is identified by a [`RunManifest`] captured before it executes. Its
+//! cache key is the SHA-256 of the command, the arguments, the hashes of the
+//! input files, the external tool and plugin versions and the Squirrel
+//! version; the environment and operating system are not part of the key.
+//! When a run with the same key succeeded before, [`ResultCache::lookup`]
+//! returns its output so the command does not have to run again.
+//!
+//! Each result is a JSON file in the cache directory, named by its key. Only
+//! the newest result of a command and argument list is kept: storing a
+//! result replaces the ones recorded for earlier input versions. Callers that
+//! change input files can also drop the results depending on them right away
+//! with [`ResultCache::invalidate_input`].
+
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+use crate::manifest::{InputFile, RunManifest};
+use crate::{CommandError, CommandResult};
+
+/// What a cache key is computed from
+#[derive(Serialize)]
+struct KeyMaterial<'a> {
+    command: &'a str,
+    args: &'a [String],
+    inputs: BTreeMap<String, &'a str>,
+    tools: &'a BTreeMap<String, Option<String>>,
+    plugins: &'a BTreeMap<String, String>,
+    squirrel_version: &'a str,
+}
+
+/// Cache key of the run described by `manifest`
+#[must_use]
+pub fn cache_key(manifest: &RunManifest) -> String {
+    let material = KeyMaterial {
+        command: &manifest.command,
+        args: &manifest.args,
+        inputs: manifest
+            .inputs
+            .iter()
+            .map(|input| (input.path.display().to_string(), input.sha256.as_str()))
+            .collect(),
+        tools: &manifest.tools,
+        plugins: &manifest.plugins,
+        squirrel_version: &manifest.squirrel_version,
+    };
+    // Serializing maps and strings cannot fail
+    let json = serde_json::to_vec(&material).unwrap_or_default();
+    hex::encode(Sha256::digest(json))
+}
+
+/// Hash of the command and arguments alone, shared by all input versions
+fn invocation(manifest: &RunManifest) -> String {
+    let json = serde_json::to_vec(&(&manifest.command, &manifest.args)).unwrap_or_default();
+    hex::encode(Sha256::digest(json))
+}
+
+/// A cached command result
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CachedResult {
+    /// Cache key
+    pub key: String,
+    /// Hash of the command and arguments
+    pub invocation: String,
+    /// Command name
+    pub command: String,
+    /// Command arguments
+    pub args: Vec<String>,
+    /// User the run was made by, if known
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub owner: Option<String>,
+    /// Input files and their hashes
+    pub inputs: Vec<InputFile>,
+    /// External tool versions by name
+    pub tools: BTreeMap<String, Option<String>>,
+    /// Output of the run
+    pub output: String,
+    /// When the result was stored
+    pub created_at: DateTime<Utc>,
+    /// Number of times the result was served
+    pub hits: u64,
+    /// When the result was last served
+    pub last_hit_at: Option<DateTime<Utc>>,
+}
+
+impl CachedResult {
+    /// Whether the run read the file at `path`
+    #[must_use]
+    pub fn depends_on(&self, path: &Path) -> bool {
+        let target = path.canonicalize().unwrap_or_else(|_| path.to_path_buf());
+        self.inputs.iter().any(|input| {
+            input.path == path || input.path.canonicalize().is_ok_and(|input| input == target)
+        })
+    }
+}
+
+/// Cache of command results in a directory
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ResultCache {
+    dir: PathBuf,
+    owner: Option<String>,
+}
+
+impl ResultCache {
+    /// Open the cache in `dir`, creating it if needed
+    ///
+    /// # Errors
+    /// Returns a resource error if the directory cannot be created
+    pub fn open(dir: impl Into<PathBuf>) -> CommandResult<Self> {
+        let dir = dir.into();
+        fs::create_dir_all(&dir)
+            .map_err(|e| CommandError::ResourceError(format!("Cannot create result cache {}: {e}", dir.display())))?;
+        Ok(Self { dir, owner: None })
+    }
+
+    /// Record `owner` as the user of the results stored from now on
+    #[must_use]
+    pub fn with_owner(mut self, owner: impl Into<String>) -> Self {
+        self.owner = Some(owner.into());
+        self
+    }
+
+    /// The default cache directory, `~/.squirrel/cache/results`
+    #[must_use]
+    pub fn default_path() -> Option<PathBuf> {
+        std::env::var_os("HOME").map(|home| Path::new(&home).join(".squirrel").join("cache").join("results"))
+    }
+
+    /// Directory of the cache
+    #[must_use]
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    fn entry_path(&self, key: &str) -> CommandResult<PathBuf> {
+        if key.is_empty() || !key.bytes().all(|b| b.is_ascii_hexdigit()) {
+            return Err(CommandError::ValidationError(format!("Invalid cache key: {key}")));
+        }
+        Ok(self.dir.join(format!("{key}.json")))
+    }
+
+    fn write(&self, entry: &CachedResult) -> CommandResult<()> {
+        let path = self.entry_path(&entry.key)?;
+        let json = serde_json::to_string_pretty(entry)
+            .map_err(|e| CommandError::ExecutionError(format!("Cannot serialize cached result: {e}")))?;
+        fs::write(&path, json)
+            .map_err(|e| CommandError::ResourceError(format!("Cannot write cached result {}: {e}", path.display())))
+    }
+
+    /// The result stored under `key`
+    ///
+    /// # Errors
+    /// Returns a validation error for a malformed key, or a resource error if
+    /// the entry cannot be read
+    pub fn get(&self, key: &str) -> CommandResult<Option<CachedResult>> {
+        let path = self.entry_path(key)?;
+        match fs::read_to_string(&path) {
+            Ok(json) => serde_json::from_str(&json)
+                .map(Some)
+                .map_err(|e| CommandError::ResourceError(format!("Invalid cached result {}: {e}", path.display()))),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(None),
+            Err(e) => Err(CommandError::ResourceError(format!("Cannot read cached result {}: {e}", path.display()))),
+        }
+    }
+
+    /// The stored result of the run described by `manifest`, counting the hit
+    ///
+    /// # Errors
+    /// Returns a resource error if the entry cannot be read or updated
+    pub fn lookup(&self, manifest: &RunManifest) -> CommandResult<Option<CachedResult>> {
+        let Some(mut entry) = self.get(&cache_key(manifest))? else {
+            return Ok(None);
+        };
+        entry.hits += 1;
+        entry.last_hit_at = Some(Utc::now());
+        self.write(&entry)?;
+        Ok(Some(entry))
+    }
+
+    /// Store the output of the run described by `manifest`
+    ///
+    /// Results of the same command and arguments with other inputs or tool
+    /// versions are removed.
+    ///
+    /// # Errors
+    /// Returns a resource error if the cache cannot be written
+    pub fn store(&self, manifest: &RunManifest, output: &str) -> CommandResult<CachedResult> {
+        let entry = CachedResult {
+            key: cache_key(manifest),
+            invocation: invocation(manifest),
+            command: manifest.command.clone(),
+            args: manifest.args.clone(),
+            owner: self.owner.clone(),
+            inputs: manifest.inputs.clone(),
+            tools: manifest.tools.clone(),
+            output: output.to_string(),
+            created_at: Utc::now(),
+            hits: 0,
+            last_hit_at: None,
+        };
+        for stale in self.entries()? {
+            if stale.invocation == entry.invocation && stale.key != entry.key {
+                self.remove(&stale.key)?;
+            }
+        }
+        self.write(&entry)?;
+        Ok(entry)
+    }
+
+    /// All cached results, newest first
+    ///
+    /// Unreadable entries are skipped. Results of every owner are listed;
+    /// callers serving other users filter on [`CachedResult::owner`].
+    ///
+    /// # Errors
+    /// Returns a resource error if the directory cannot be read
+    pub fn entries(&self) -> CommandResult<Vec<CachedResult>> {
+        let read_dir = fs::read_dir(&self.dir)
+            .map_err(|e| CommandError::ResourceError(format!("Cannot read result cache {}: {e}", self.dir.display())))?;
+        let mut entries: Vec<CachedResult> = read_dir
+            .filter_map(Result::ok)
+            .map(|entry| entry.path())
+            .filter(|path| path.extension().is_some_and(|ext| ext == "json"))
+            .filter_map(|path| fs::read_to_string(path).ok())
+            .filter_map(|json| serde_json::from_str(&json).ok())
+            .collect();
+        entries.sort_by_key(|entry| std::cmp::Reverse(entry.created_at));
+        Ok(entries)
+    }
+
+    /// Remove the result stored under `key`, returning whether there was one
+    ///
+    /// # Errors
+    /// Returns a validation error for a malformed key, or a resource error if
+    /// the entry cannot be removed
+    pub fn remove(&self, key: &str) -> CommandResult<bool> {
+        let path = self.entry_path(key)?;
+        match fs::remove_file(&path) {
+            Ok(()) => Ok(true),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(false),
+            Err(e) => Err(CommandError::ResourceError(format!("Cannot remove cached result {}: {e}", path.display()))),
+        }
+    }
+
+    /// Remove the results matching `predicate`, returning their keys
+    ///
+    /// # Errors
+    /// Returns a resource error if the cache cannot be read or an entry
+    /// cannot be removed
+    pub fn invalidate_where(&self, predicate: impl Fn(&CachedResult) -> bool) -> CommandResult<Vec<String>> {
+        let mut removed = Vec::new();
+        for entry in self.entries()?.into_iter().filter(|entry| predicate(entry)) {
+            if self.remove(&entry.key)? {
+                removed.push(entry.key);
+            }
+        }
+        Ok(removed)
+    }
+
+    /// Remove the results of runs that read the file at `path`
+    ///
+    /// Call this when a file changes, so no result computed from its old
+    /// content is served even to runs that do not hash their inputs again.
+    ///
+    /// # Errors
+    /// Returns a resource error if the cache cannot be updated
+    pub fn invalidate_input(&self, path: &Path) -> CommandResult<Vec<String>> {
+        self.invalidate_where(|entry| entry.depends_on(path))
+    }
+
+    /// Remove the results of `command`
+    ///
+    /// # Errors
+    /// Returns a resource error if the cache cannot be updated
+    pub fn invalidate_command(&self, command: &str) -> CommandResult<Vec<String>> {
+        self.invalidate_where(|entry| entry.command == command)
+    }
+
+    /// Remove all results
+    ///
+    /// # Errors
+    /// Returns a resource error if the cache cannot be updated
+    pub fn clear(&self) -> CommandResult<Vec<String>> {
+        self.invalidate_where(|_| true)
+    }
+}
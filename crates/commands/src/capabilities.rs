@@ -0,0 +1,235 @@
+//! Capability discovery
+//!
+//! A [`CapabilityReport`] tells clients what a running Squirrel process can
+//! do: its version, which optional subsystems are compiled in and configured,
+//! and which commands and tools are registered. `squirrel capabilities` prints
+//! the CLI's report and the web server serves its own at
+//! `GET /api/capabilities`; each adds the subsystems it knows about to the
+//! [`common_subsystems`] every process shares.
+
+use std::fmt;
+
+use serde::{Deserialize, Serialize};
+
+use crate::{CommandRegistry, CommandResult};
+
+/// Environment variable configuring the OpenTelemetry exporter endpoint
+pub const OTEL_ENDPOINT_ENV: &str = "OTEL_EXPORTER_OTLP_ENDPOINT";
+
+/// An optional subsystem and whether it can be used
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Subsystem {
+    /// Subsystem name, e.g. `database`
+    pub name: String,
+    /// Whether the subsystem is part of this build
+    pub compiled: bool,
+    /// Whether the subsystem is configured and running; never set when not
+    /// compiled
+    pub enabled: bool,
+    /// Implementation in use or why the subsystem is disabled
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub detail: Option<String>,
+}
+
+impl Subsystem {
+    /// A subsystem compiled into this build
+    pub fn compiled(name: impl Into<String>, enabled: bool) -> Self {
+        Self {
+            name: name.into(),
+            compiled: true,
+            enabled,
+            detail: None,
+        }
+    }
+
+    /// A subsystem left out of this build
+    pub fn not_compiled(name: impl Into<String>) -> Self {
+        Self {
+            name: name.into(),
+            compiled: false,
+            enabled: false,
+            detail: None,
+        }
+    }
+
+    /// A subsystem behind a cargo feature, enabled when compiled and configured
+    pub fn feature(name: impl Into<String>, compiled: bool, configured: bool) -> Self {
+        if compiled {
+            Self::compiled(name, configured)
+        } else {
+            Self::not_compiled(name)
+        }
+    }
+
+    /// Adds a detail
+    #[must_use]
+    pub fn with_detail(mut self, detail: impl Into<String>) -> Self {
+        self.detail = Some(detail.into());
+        self
+    }
+
+    /// Whether clients can use the subsystem
+    #[must_use]
+    pub const fn available(&self) -> bool {
+        self.compiled && self.enabled
+    }
+}
+
+/// A registered tool and its capabilities
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ToolInfo {
+    /// Tool ID
+    pub id: String,
+    /// Display name
+    pub name: String,
+    /// Tool version
+    pub version: String,
+    /// Names of the capabilities the tool provides
+    pub capabilities: Vec<String>,
+}
+
+/// What a Squirrel process can do
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CapabilityReport {
+    /// Component reporting, e.g. `squirrel-cli`
+    pub component: String,
+    /// Component version
+    pub version: String,
+    /// Optional subsystems, sorted by name
+    pub subsystems: Vec<Subsystem>,
+    /// Registered command names, sorted
+    pub commands: Vec<String>,
+    /// Registered tools, sorted by ID
+    pub tools: Vec<ToolInfo>,
+}
+
+impl CapabilityReport {
+    /// An empty report for `component`
+    pub fn new(component: impl Into<String>, version: impl Into<String>) -> Self {
+        Self {
+            component: component.into(),
+            version: version.into(),
+            subsystems: Vec::new(),
+            commands: Vec::new(),
+            tools: Vec::new(),
+        }
+    }
+
+    /// Adds a subsystem, replacing any subsystem of the same name
+    #[must_use]
+    pub fn with_subsystem(mut self, subsystem: Subsystem) -> Self {
+        self.subsystems
+            .retain(|existing| existing.name != subsystem.name);
+        self.subsystems.push(subsystem);
+        self.subsystems.sort_by(|a, b| a.name.cmp(&b.name));
+        self
+    }
+
+    /// Adds subsystems, replacing any of the same name
+    #[must_use]
+    pub fn with_subsystems(self, subsystems: impl IntoIterator<Item = Subsystem>) -> Self {
+        subsystems.into_iter().fold(self, Self::with_subsystem)
+    }
+
+    /// Adds command names
+    #[must_use]
+    pub fn with_commands(mut self, commands: impl IntoIterator<Item = String>) -> Self {
+        self.commands.extend(commands);
+        self.commands.sort();
+        self.commands.dedup();
+        self
+    }
+
+    /// Adds the commands registered in `registry`
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the registry cannot be read.
+    pub fn with_registry(self, registry: &CommandRegistry) -> CommandResult<Self> {
+        Ok(self.with_commands(registry.list_commands()?))
+    }
+
+    /// Adds tools, replacing any tool of the same ID
+    #[must_use]
+    pub fn with_tools(mut self, tools: impl IntoIterator<Item = ToolInfo>) -> Self {
+        for tool in tools {
+            self.tools.retain(|existing| existing.id != tool.id);
+            self.tools.push(tool);
+        }
+        self.tools.sort_by(|a, b| a.id.cmp(&b.id));
+        self
+    }
+
+    /// Looks up a subsystem by name
+    #[must_use]
+    pub fn subsystem(&self, name: &str) -> Option<&Subsystem> {
+        self.subsystems
+            .iter()
+            .find(|subsystem| subsystem.name == name)
+    }
+
+    /// Whether the named subsystem is compiled in and enabled
+    #[must_use]
+    pub fn supports(&self, name: &str) -> bool {
+        self.subsystem(name).is_some_and(Subsystem::available)
+    }
+}
+
+impl fmt::Display for CapabilityReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "{} {}", self.component, self.version)?;
+        writeln!(f)?;
+        writeln!(
+            f,
+            "{:<20} {:<9} {:<8} DETAIL",
+            "SUBSYSTEM", "COMPILED", "ENABLED"
+        )?;
+        for subsystem in &self.subsystems {
+            let yes_no = |value: bool| if value { "yes" } else { "no" };
+            writeln!(
+                f,
+                "{:<20} {:<9} {:<8} {}",
+                subsystem.name,
+                yes_no(subsystem.compiled),
+                yes_no(subsystem.enabled),
+                subsystem.detail.as_deref().unwrap_or("")
+            )?;
+        }
+        writeln!(f)?;
+        writeln!(
+            f,
+            "Commands ({}): {}",
+            self.commands.len(),
+            self.commands.join(", ")
+        )?;
+        write!(f, "Tools ({})", self.tools.len())?;
+        for tool in &self.tools {
+            write!(
+                f,
+                "\n  {} {} ({}): {}",
+                tool.id,
+                tool.version,
+                tool.name,
+                tool.capabilities.join(", ")
+            )?;
+        }
+        Ok(())
+    }
+}
+
+/// Subsystems every Squirrel process reports the same way
+///
+/// OpenTelemetry export is always compiled in and enabled when
+/// [`OTEL_ENDPOINT_ENV`] is set. No WebAssembly plugin runtime is part of
+/// this build.
+#[must_use]
+pub fn common_subsystems() -> Vec<Subsystem> {
+    let otel = match std::env::var(OTEL_ENDPOINT_ENV) {
+        Ok(endpoint) if !endpoint.is_empty() => {
+            Subsystem::compiled("otel", true).with_detail(endpoint)
+        }
+        _ => Subsystem::compiled("otel", false)
+            .with_detail(format!("set {OTEL_ENDPOINT_ENV} to export")),
+    };
+    vec![otel, Subsystem::not_compiled("wasm")]
+}
@@ -0,0 +1,233 @@
+//! Per-command execution environments
+//!
+//! An execution environment is the view of environment variables, search
+//! paths and resource limits a command runs with. Environments are defined by
+//! name in configuration and assigned to commands; the resolved
+//! [`ExecutionEnvironment`] is attached to the command's context as an
+//! extension and applied to the processes the command spawns.
+//!
+//! Commands never see the full host environment. Only variables matching the
+//! environment's `inherit` allowlist are passed through, and even then
+//! variables whose names look like credentials (containing `TOKEN`, `SECRET`,
+//! `PASSWORD`, `KEY` or `CREDENTIAL`) are scrubbed unless they are listed by
+//! their exact name. Commands without an assigned environment use the
+//! environment named [`DEFAULT_ENVIRONMENT`] if one is defined, and otherwise
+//! the default allowlist with nothing injected.
+
+use std::collections::BTreeMap;
+use std::path::PathBuf;
+use std::time::Duration;
+
+use serde::{Deserialize, Serialize};
+
+use crate::{CommandError, CommandResult};
+
+/// Name of the environment used by commands without an assignment
+pub const DEFAULT_ENVIRONMENT: &str = "default";
+
+/// Host variables commands see when no allowlist is configured
+pub const DEFAULT_ENV_ALLOWLIST: &[&str] = &["HOME", "LANG", "LC_*", "PATH", "TERM", "TMPDIR", "TZ", "USER"];
+
+/// Fragments marking a variable name as sensitive
+const SENSITIVE_MARKERS: &[&str] = &["TOKEN", "SECRET", "PASSWORD", "PASSWD", "KEY", "CREDENTIAL"];
+
+/// Whether `name` is matched by `pattern`; a trailing `*` matches a prefix
+fn matches_pattern(pattern: &str, name: &str) -> bool {
+    match pattern.strip_suffix('*') {
+        Some(prefix) => name.starts_with(prefix),
+        None => name == pattern,
+    }
+}
+
+/// Whether a host variable name looks like it holds a credential
+#[must_use]
+pub fn is_sensitive(name: &str) -> bool {
+    let name = name.to_uppercase();
+    SENSITIVE_MARKERS.iter().any(|marker| name.contains(marker))
+}
+
+/// Resource limits of an environment
+///
+/// Limits are advisory data for the code running the command: executors
+/// apply the timeout, and process spawners apply the others where the
+/// platform supports it.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ResourceLimits {
+    /// Maximum memory in megabytes
+    pub memory_mb: Option<u64>,
+    /// Maximum CPU time in seconds
+    pub cpu_seconds: Option<u64>,
+    /// Maximum wall-clock time in seconds
+    pub timeout_seconds: Option<u64>,
+    /// Maximum number of open files
+    pub open_files: Option<u64>,
+}
+
+impl ResourceLimits {
+    /// The wall-clock limit, if any
+    #[must_use]
+    pub fn timeout(&self) -> Option<Duration> {
+        self.timeout_seconds.map(Duration::from_secs)
+    }
+}
+
+/// A named environment as written in configuration
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct EnvironmentDefinition {
+    /// Host variables passed through; a trailing `*` matches a prefix
+    pub inherit: Vec<String>,
+    /// Variables set for the command, overriding inherited ones
+    pub vars: BTreeMap<String, String>,
+    /// Directories prepended to `PATH`
+    pub path: Vec<PathBuf>,
+    /// Working directory of spawned processes
+    pub working_dir: Option<PathBuf>,
+    /// Resource limits
+    pub limits: ResourceLimits,
+}
+
+impl Default for EnvironmentDefinition {
+    fn default() -> Self {
+        Self {
+            inherit: DEFAULT_ENV_ALLOWLIST.iter().map(ToString::to_string).collect(),
+            vars: BTreeMap::new(),
+            path: Vec::new(),
+            working_dir: None,
+            limits: ResourceLimits::default(),
+        }
+    }
+}
+
+impl EnvironmentDefinition {
+    /// Whether the host variable `name` is passed through
+    ///
+    /// Sensitive names only pass when listed exactly, not through a pattern.
+    #[must_use]
+    pub fn inherits(&self, name: &str) -> bool {
+        if is_sensitive(name) {
+            return self.inherit.iter().any(|pattern| pattern == name);
+        }
+        self.inherit.iter().any(|pattern| matches_pattern(pattern, name))
+    }
+}
+
+/// The environment a command runs with, resolved against the host
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ExecutionEnvironment {
+    /// Name of the environment
+    pub name: String,
+    /// Variables visible to the command
+    pub vars: BTreeMap<String, String>,
+    /// Working directory of spawned processes
+    pub working_dir: Option<PathBuf>,
+    /// Resource limits
+    pub limits: ResourceLimits,
+}
+
+impl ExecutionEnvironment {
+    /// Resolve `definition` against the host variables `host_env`
+    pub fn resolve(
+        name: &str,
+        definition: &EnvironmentDefinition,
+        host_env: impl IntoIterator<Item = (String, String)>,
+    ) -> CommandResult<Self> {
+        let mut vars: BTreeMap<String, String> = host_env
+            .into_iter()
+            .filter(|(key, _)| definition.inherits(key))
+            .collect();
+        vars.extend(definition.vars.clone());
+
+        if !definition.path.is_empty() {
+            let inherited = vars.get("PATH").map(std::env::split_paths).into_iter().flatten();
+            let path = std::env::join_paths(definition.path.iter().cloned().chain(inherited))
+                .map_err(|e| CommandError::ValidationError(format!("Invalid path in environment '{}': {}", name, e)))?;
+            vars.insert("PATH".to_string(), path.to_string_lossy().into_owned());
+        }
+
+        Ok(Self {
+            name: name.to_string(),
+            vars,
+            working_dir: definition.working_dir.clone(),
+            limits: definition.limits.clone(),
+        })
+    }
+
+    /// The value of variable `name`
+    #[must_use]
+    pub fn var(&self, name: &str) -> Option<&str> {
+        self.vars.get(name).map(String::as_str)
+    }
+
+    /// Make `process` run in this environment
+    ///
+    /// Clears the inherited environment so the process only sees [`Self::vars`].
+    /// For `tokio::process::Command`, pass `as_std_mut()`.
+    pub fn apply(&self, process: &mut std::process::Command) {
+        process.env_clear().envs(&self.vars);
+        if let Some(dir) = &self.working_dir {
+            process.current_dir(dir);
+        }
+    }
+}
+
+/// Named environments and the commands they are assigned to
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct EnvironmentRegistry {
+    environments: BTreeMap<String, EnvironmentDefinition>,
+    assignments: BTreeMap<String, String>,
+}
+
+impl EnvironmentRegistry {
+    /// Create a registry from environment definitions and command assignments
+    ///
+    /// Fails if a command is assigned an environment that is not defined.
+    pub fn new(
+        environments: BTreeMap<String, EnvironmentDefinition>,
+        assignments: BTreeMap<String, String>,
+    ) -> CommandResult<Self> {
+        if let Some((command, environment)) = assignments
+            .iter()
+            .find(|(_, environment)| !environments.contains_key(environment.as_str()))
+        {
+            return Err(CommandError::ValidationError(format!(
+                "Command '{}' is assigned unknown environment '{}'",
+                command, environment
+            )));
+        }
+        Ok(Self { environments, assignments })
+    }
+
+    /// The definition of environment `name`
+    #[must_use]
+    pub fn get(&self, name: &str) -> Option<&EnvironmentDefinition> {
+        self.environments.get(name)
+    }
+
+    /// Names of the defined environments
+    pub fn names(&self) -> impl Iterator<Item = &str> {
+        self.environments.keys().map(String::as_str)
+    }
+
+    /// Name of the environment `command` runs in
+    #[must_use]
+    pub fn environment_name(&self, command: &str) -> &str {
+        self.assignments
+            .get(command)
+            .map_or(DEFAULT_ENVIRONMENT, String::as_str)
+    }
+
+    /// Resolve the environment of `command` against `host_env`
+    pub fn for_command(
+        &self,
+        command: &str,
+        host_env: impl IntoIterator<Item = (String, String)>,
+    ) -> CommandResult<ExecutionEnvironment> {
+        let name = self.environment_name(command);
+        match self.environments.get(name) {
+            Some(definition) => ExecutionEnvironment::resolve(name, definition, host_env),
+            None => ExecutionEnvironment::resolve(name, &EnvironmentDefinition::default(), host_env),
+        }
+    }
+}
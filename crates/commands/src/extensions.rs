@@ -0,0 +1,124 @@
+//! Typed extensions for command execution
+//!
+//! [`Extensions`] is a map keyed by type, holding at most one value per type.
+//! Middleware, the web layer and plugins use it to hand values such as an
+//! authenticated session, a request ID or a workspace handle to commands
+//! without widening every signature. Define a newtype for each value so two
+//! components cannot clash over a shared type like `String`.
+//!
+//! # Lifetimes and cloning
+//!
+//! Values are stored behind an [`Arc`] and must be `Send + Sync + 'static`;
+//! they live until they are removed or the last container referring to them
+//! is dropped. Cloning an `Extensions` is cheap and shallow: the clone shares
+//! the existing values, but inserting into or removing from one container
+//! never affects the other. Values needing interior mutability should wrap
+//! it themselves, e.g. with a `Mutex`.
+
+use std::any::{Any, TypeId};
+use std::collections::HashMap;
+use std::fmt;
+use std::sync::Arc;
+
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+/// Type-keyed map of values attached to a command execution
+#[derive(Clone, Default)]
+pub struct Extensions {
+    values: HashMap<TypeId, Arc<dyn Any + Send + Sync>>,
+}
+
+impl Extensions {
+    /// Creates an empty map
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Inserts a value, returning the previous value of the same type
+    pub fn insert<T: Send + Sync + 'static>(&mut self, value: T) -> Option<Arc<T>> {
+        self.insert_arc(Arc::new(value))
+    }
+
+    /// Inserts a shared value, returning the previous value of the same type
+    pub fn insert_arc<T: Send + Sync + 'static>(&mut self, value: Arc<T>) -> Option<Arc<T>> {
+        self.values
+            .insert(TypeId::of::<T>(), value)
+            .and_then(|previous| previous.downcast().ok())
+    }
+
+    /// Returns a reference to the value of type `T`
+    #[must_use]
+    pub fn get<T: Send + Sync + 'static>(&self) -> Option<&T> {
+        self.values.get(&TypeId::of::<T>()).and_then(|value| value.downcast_ref())
+    }
+
+    /// Returns a shared handle to the value of type `T`, which may outlive the map
+    #[must_use]
+    pub fn get_arc<T: Send + Sync + 'static>(&self) -> Option<Arc<T>> {
+        self.values
+            .get(&TypeId::of::<T>())
+            .and_then(|value| value.clone().downcast().ok())
+    }
+
+    /// Whether a value of type `T` is present
+    #[must_use]
+    pub fn contains<T: Send + Sync + 'static>(&self) -> bool {
+        self.values.contains_key(&TypeId::of::<T>())
+    }
+
+    /// Removes and returns the value of type `T`
+    pub fn remove<T: Send + Sync + 'static>(&mut self) -> Option<Arc<T>> {
+        self.values
+            .remove(&TypeId::of::<T>())
+            .and_then(|value| value.downcast().ok())
+    }
+
+    /// Copies all values from `other`, replacing values of the same type
+    pub fn extend(&mut self, other: &Self) {
+        self.values
+            .extend(other.values.iter().map(|(id, value)| (*id, value.clone())));
+    }
+
+    /// Number of stored values
+    #[must_use]
+    pub fn len(&self) -> usize {
+        self.values.len()
+    }
+
+    /// Whether the map is empty
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.values.is_empty()
+    }
+
+    /// Removes all values
+    pub fn clear(&mut self) {
+        self.values.clear();
+    }
+}
+
+impl fmt::Debug for Extensions {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Extensions").field("len", &self.values.len()).finish()
+    }
+}
+
+/// Identifies a single command execution in logs and traces
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct RequestId(pub String);
+
+impl RequestId {
+    /// Generates a new random request ID
+    #[must_use]
+    pub fn generate() -> Self {
+        Self(Uuid::new_v4().to_string())
+    }
+}
+
+impl fmt::Display for RequestId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
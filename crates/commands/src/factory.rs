@@ -4,7 +4,7 @@
 
 use crate::{
     registry::CommandRegistry,
-    builtin::{VersionCommand, HelpCommand, EchoCommand, ExitCommand, KillCommand, HistoryCommand},
+    builtin::{VersionCommand, HelpCommand, EchoCommand, ExitCommand, KillCommand, HistoryCommand, FilesCommand},
     history::CommandHistory,
 };
 use std::{
@@ -14,6 +14,7 @@ use std::{
     time::Instant,
 };
 
+use squirrel_core::vfs::Vfs;
 use tracing::{debug, info};
 
 /// The command registry factory trait
@@ -136,6 +137,14 @@ impl CommandRegistryFactory for DefaultCommandRegistryFactory {
             // Register the history command
             registry_guard.register("history", Arc::new(HistoryCommand::new(Arc::clone(&history))))?;
             
+            // Rank command palette searches by the recorded history
+            registry_guard.load_usage(&history)?;
+            
+            // Register the files command, rooted at the current directory
+            let workspace = std::env::current_dir()?;
+            let vfs = Vfs::standard(&workspace, workspace.join("data"));
+            registry_guard.register("files", Arc::new(FilesCommand::new(Arc::new(vfs))))?;
+            
             // TODO: Implement set_resource in CommandRegistry
             // registry_guard.set_resource("command_history", Box::new(Arc::clone(&history)))?;
             debug!("Factory: Resource not set - function not implemented in CommandRegistry");
@@ -115,6 +115,12 @@ impl CommandHistory {
         Self::with_options(DEFAULT_MAX_HISTORY_SIZE, DEFAULT_HISTORY_FILE)
     }
     
+    /// Default history file of the CLI, `~/.squirrel/command-history.json`
+    #[must_use]
+    pub fn default_path() -> Option<PathBuf> {
+        std::env::var_os("HOME").map(|home| Path::new(&home).join(".squirrel").join("command-history.json"))
+    }
+    
     /// Creates a new command history manager with custom options
     pub fn with_options(max_size: usize, history_file: impl AsRef<Path>) -> HistoryResult<Self> {
         let history_file = history_file.as_ref().to_path_buf();
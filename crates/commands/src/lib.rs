@@ -29,10 +29,58 @@ pub mod suggestions;
 /// Command authentication and authorization system
 pub mod auth;
 
+/// Workflow definition language and engine
+pub mod workflow;
+
+/// Declarative argument schemas
+pub mod schema;
+
+/// Typed extensions attached to command executions
+pub mod extensions;
+
+/// Reproducibility manifests and replay of command runs
+pub mod manifest;
+
+/// Portable bundles of command runs with their inputs
+pub mod bundle;
+
+/// Rendered reports of finished jobs and workflow runs
+pub mod report;
+
+/// Per-plugin log capture and routing
+pub mod plugin_logs;
+
+/// Capability discovery reports
+pub mod capabilities;
+
+/// Per-command execution environments
+pub mod environments;
+
+/// Cached results of command runs
+pub mod cache;
+
+/// Versioned wire schema for invocations shared over MCP
+pub mod wire;
+
+/// Fuzzy-searchable command palette index
+pub mod palette;
+
+/// Command allow and deny policies per user and API key
+pub mod policy;
+
+/// Project scaffolds rendered from templates
+pub mod scaffold;
+
 /// Command registry
 mod registry;
 pub use registry::{Command, CommandRegistry, CommandResult};
 
+/// Derive macro generating a command from a struct definition
+pub use squirrel_commands_derive::Command;
+
+// Lets code generated by `#[derive(Command)]` name this crate from within it
+extern crate self as squirrel_commands;
+
 /// Command errors
 #[derive(Debug, Error)]
 pub enum CommandError {
@@ -71,6 +119,34 @@ pub enum CommandError {
     /// Error related to authorization
     #[error("Authorization error: {0}")]
     AuthorizationError(String),
+
+    /// The request's deadline passed before the command finished
+    #[error("Deadline exceeded: {0}")]
+    DeadlineExceeded(String),
+}
+
+impl squirrel_core::error::Coded for CommandError {
+    fn code(&self) -> squirrel_core::error::ErrorCode {
+        use squirrel_core::error::ErrorCode;
+        match self {
+            Self::ValidationError(_) => ErrorCode::InvalidInput,
+            Self::CommandNotFound(_) => ErrorCode::NotFound,
+            Self::CommandAlreadyExists(_) => ErrorCode::AlreadyExists,
+            Self::AuthenticationError(_) => ErrorCode::Unauthenticated,
+            Self::AuthorizationError(_) => ErrorCode::PermissionDenied,
+            Self::ResourceError(_) => ErrorCode::ResourceExhausted,
+            Self::ExecutionError(_) => ErrorCode::ExecutionFailed,
+            Self::DeadlineExceeded(_) => ErrorCode::Timeout,
+            Self::RegistrationError(_) | Self::RegistryError(_) => ErrorCode::Internal,
+        }
+    }
+
+    fn hint(&self) -> Option<String> {
+        match self {
+            Self::CommandNotFound(_) => Some("Run `squirrel help` to list the available commands".to_string()),
+            other => other.code().hint().map(str::to_string),
+        }
+    }
 }
 
 /// Command factory for creating command registries
@@ -0,0 +1,469 @@
+//! Reproducibility manifests for command runs
+//!
+//! A [`RunManifest`] records what a command run depended on: the command and
+//! its arguments, the Squirrel version, plugin and external tool versions, the
+//! operating system, an allowlisted subset of the environment and the hashes
+//! of its input files. Manifests are stored as JSON alongside job and
+//! experiment records, and [`replay`] re-executes the command they describe
+//! while reporting every way the current environment has drifted from the
+//! recorded one.
+//!
+//! Only environment variables matching [`ManifestOptions::env_allowlist`] are
+//! captured, so credentials in the environment never end up in a manifest
+//! unless they are allowlisted explicitly.
+
+use std::collections::BTreeMap;
+use std::fmt;
+use std::fs;
+use std::io::Read;
+use std::path::{Path, PathBuf};
+use std::process::Command as Process;
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use uuid::Uuid;
+
+use crate::{CommandError, CommandRegistry, CommandResult};
+
+/// Version of the manifest format
+pub const MANIFEST_VERSION: u32 = 1;
+
+/// Environment variables captured when no allowlist is configured
+pub const DEFAULT_ENV_ALLOWLIST: &[&str] = &["LANG", "LC_*", "PATH", "TZ", "SQUIRREL_*"];
+
+/// What to capture besides the command itself
+#[derive(Debug, Clone)]
+pub struct ManifestOptions {
+    /// Environment variable names to capture; a trailing `*` matches a prefix
+    pub env_allowlist: Vec<String>,
+    /// External tools whose `--version` output is recorded
+    pub tools: Vec<String>,
+    /// Input files to hash
+    pub inputs: Vec<PathBuf>,
+    /// Versions of the loaded plugins, by name
+    pub plugins: BTreeMap<String, String>,
+}
+
+impl Default for ManifestOptions {
+    fn default() -> Self {
+        Self {
+            env_allowlist: DEFAULT_ENV_ALLOWLIST.iter().map(ToString::to_string).collect(),
+            tools: Vec::new(),
+            inputs: Vec::new(),
+            plugins: BTreeMap::new(),
+        }
+    }
+}
+
+impl ManifestOptions {
+    /// Whether `name` is matched by the allowlist
+    #[must_use]
+    pub fn allows_env(&self, name: &str) -> bool {
+        self.env_allowlist.iter().any(|pattern| match pattern.strip_suffix('*') {
+            Some(prefix) => name.starts_with(prefix),
+            None => name == pattern,
+        })
+    }
+}
+
+/// Operating system a run happened on
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct OsInfo {
+    /// Operating system name, e.g. `linux`
+    pub name: String,
+    /// CPU architecture, e.g. `x86_64`
+    pub arch: String,
+    /// Operating system family, e.g. `unix`
+    pub family: String,
+}
+
+impl OsInfo {
+    /// The operating system this process runs on
+    #[must_use]
+    pub fn current() -> Self {
+        Self {
+            name: std::env::consts::OS.to_string(),
+            arch: std::env::consts::ARCH.to_string(),
+            family: std::env::consts::FAMILY.to_string(),
+        }
+    }
+}
+
+impl fmt::Display for OsInfo {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}/{} ({})", self.name, self.arch, self.family)
+    }
+}
+
+/// An input file and its content hash
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct InputFile {
+    /// Path as given when the manifest was captured
+    pub path: PathBuf,
+    /// Hex-encoded SHA-256 of the content
+    pub sha256: String,
+    /// Size in bytes
+    pub size: u64,
+}
+
+impl InputFile {
+    /// Hashes the file at `path`
+    ///
+    /// # Errors
+    /// Returns a resource error if the file cannot be read
+    pub fn hash(path: &Path) -> CommandResult<Self> {
+        let mut file = fs::File::open(path)
+            .map_err(|e| CommandError::ResourceError(format!("Cannot read input {}: {e}", path.display())))?;
+        let mut hasher = Sha256::new();
+        let mut buffer = [0u8; 8192];
+        let mut size = 0u64;
+        loop {
+            let read = file
+                .read(&mut buffer)
+                .map_err(|e| CommandError::ResourceError(format!("Cannot read input {}: {e}", path.display())))?;
+            if read == 0 {
+                break;
+            }
+            hasher.update(&buffer[..read]);
+            size += read as u64;
+        }
+        Ok(Self {
+            path: path.to_path_buf(),
+            sha256: hex::encode(hasher.finalize()),
+            size,
+        })
+    }
+}
+
+/// Result of the recorded run
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RunOutcome {
+    /// Whether the command succeeded
+    pub success: bool,
+    /// Hex-encoded SHA-256 of the output, or of the error message on failure
+    pub output_sha256: String,
+}
+
+impl RunOutcome {
+    /// Records the result of a command
+    #[must_use]
+    pub fn from_result(result: &CommandResult<String>) -> Self {
+        let (success, text) = match result {
+            Ok(output) => (true, output.clone()),
+            Err(err) => (false, err.to_string()),
+        };
+        Self {
+            success,
+            output_sha256: hex::encode(Sha256::digest(text.as_bytes())),
+        }
+    }
+}
+
+/// Everything needed to reproduce a command run
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RunManifest {
+    /// Manifest format version
+    pub manifest_version: u32,
+    /// Manifest identifier
+    pub id: String,
+    /// When the manifest was captured
+    pub created_at: DateTime<Utc>,
+    /// Command name
+    pub command: String,
+    /// Command arguments
+    pub args: Vec<String>,
+    /// Squirrel version
+    pub squirrel_version: String,
+    /// Operating system
+    pub os: OsInfo,
+    /// Plugin versions by name
+    pub plugins: BTreeMap<String, String>,
+    /// External tool versions by name; `None` if the tool was not found
+    pub tools: BTreeMap<String, Option<String>>,
+    /// Allowlist the environment was captured with
+    pub env_allowlist: Vec<String>,
+    /// Allowlisted environment variables
+    pub env: BTreeMap<String, String>,
+    /// Input files and their hashes
+    pub inputs: Vec<InputFile>,
+    /// Result of the run, once it has finished
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub outcome: Option<RunOutcome>,
+}
+
+impl RunManifest {
+    /// Captures the current environment for running `command` with `args`
+    ///
+    /// # Errors
+    /// Returns a resource error if an input file cannot be hashed
+    pub fn capture(command: &str, args: &[String], options: &ManifestOptions) -> CommandResult<Self> {
+        Ok(Self {
+            manifest_version: MANIFEST_VERSION,
+            id: Uuid::new_v4().to_string(),
+            created_at: Utc::now(),
+            command: command.to_string(),
+            args: args.to_vec(),
+            squirrel_version: env!("CARGO_PKG_VERSION").to_string(),
+            os: OsInfo::current(),
+            plugins: options.plugins.clone(),
+            tools: options
+                .tools
+                .iter()
+                .map(|tool| (tool.clone(), tool_version(tool)))
+                .collect(),
+            env_allowlist: options.env_allowlist.clone(),
+            env: std::env::vars().filter(|(name, _)| options.allows_env(name)).collect(),
+            inputs: options
+                .inputs
+                .iter()
+                .map(|path| InputFile::hash(path))
+                .collect::<CommandResult<_>>()?,
+            outcome: None,
+        })
+    }
+
+    /// Options capturing the same things this manifest recorded, given the
+    /// currently loaded `plugins`
+    #[must_use]
+    pub fn options(&self, plugins: BTreeMap<String, String>) -> ManifestOptions {
+        ManifestOptions {
+            env_allowlist: self.env_allowlist.clone(),
+            tools: self.tools.keys().cloned().collect(),
+            inputs: self.inputs.iter().map(|input| input.path.clone()).collect(),
+            plugins,
+        }
+    }
+
+    /// Serializes the manifest as pretty-printed JSON
+    ///
+    /// # Errors
+    /// Returns an execution error if serialization fails
+    pub fn to_json(&self) -> CommandResult<String> {
+        serde_json::to_string_pretty(self)
+            .map_err(|e| CommandError::ExecutionError(format!("Cannot serialize manifest: {e}")))
+    }
+
+    /// Parses a manifest from JSON
+    ///
+    /// # Errors
+    /// Returns a validation error if the JSON is not a supported manifest
+    pub fn from_json(json: &str) -> CommandResult<Self> {
+        let manifest: Self = serde_json::from_str(json)
+            .map_err(|e| CommandError::ValidationError(format!("Invalid manifest: {e}")))?;
+        if manifest.manifest_version > MANIFEST_VERSION {
+            return Err(CommandError::ValidationError(format!(
+                "Unsupported manifest version {}",
+                manifest.manifest_version
+            )));
+        }
+        Ok(manifest)
+    }
+
+    /// Writes the manifest to `path`
+    ///
+    /// # Errors
+    /// Returns a resource error if the file cannot be written
+    pub fn save(&self, path: &Path) -> CommandResult<()> {
+        fs::write(path, self.to_json()?)
+            .map_err(|e| CommandError::ResourceError(format!("Cannot write manifest {}: {e}", path.display())))
+    }
+
+    /// Reads a manifest from `path`
+    ///
+    /// # Errors
+    /// Returns a resource error if the file cannot be read, or a validation
+    /// error if it is not a manifest
+    pub fn load(path: &Path) -> CommandResult<Self> {
+        let json = fs::read_to_string(path)
+            .map_err(|e| CommandError::ResourceError(format!("Cannot read manifest {}: {e}", path.display())))?;
+        Self::from_json(&json)
+    }
+
+    /// Compares the recorded environment against `current`
+    ///
+    /// Only the environment is compared; the command, arguments and outcome
+    /// are what a replay reproduces rather than what it depends on.
+    #[must_use]
+    pub fn diff(&self, current: &Self) -> Vec<Drift> {
+        let mut drift = Vec::new();
+        let mut compare = |field: String, expected: Option<String>, actual: Option<String>| {
+            if expected != actual {
+                drift.push(Drift { field, expected, actual });
+            }
+        };
+
+        compare(
+            "squirrel_version".to_string(),
+            Some(self.squirrel_version.clone()),
+            Some(current.squirrel_version.clone()),
+        );
+        compare("os".to_string(), Some(self.os.to_string()), Some(current.os.to_string()));
+        for (field, expected, actual) in diff_maps(&self.plugins, &current.plugins) {
+            compare(format!("plugin.{field}"), expected, actual);
+        }
+        let tools = |tools: &BTreeMap<String, Option<String>>| -> BTreeMap<String, String> {
+            tools
+                .iter()
+                .map(|(name, version)| (name.clone(), version.clone().unwrap_or_else(|| "not found".to_string())))
+                .collect()
+        };
+        for (field, expected, actual) in diff_maps(&tools(&self.tools), &tools(&current.tools)) {
+            compare(format!("tool.{field}"), expected, actual);
+        }
+        for (field, expected, actual) in diff_maps(&self.env, &current.env) {
+            compare(format!("env.{field}"), expected, actual);
+        }
+        let inputs = |inputs: &[InputFile]| -> BTreeMap<String, String> {
+            inputs
+                .iter()
+                .map(|input| (input.path.display().to_string(), input.sha256.clone()))
+                .collect()
+        };
+        for (field, expected, actual) in diff_maps(&inputs(&self.inputs), &inputs(&current.inputs)) {
+            compare(format!("input.{field}"), expected, actual);
+        }
+        drift
+    }
+}
+
+/// A difference between a recorded and the current environment
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Drift {
+    /// What differs, e.g. `tool.samtools` or `env.PATH`
+    pub field: String,
+    /// Recorded value; `None` if it was not recorded
+    pub expected: Option<String>,
+    /// Current value; `None` if it is now missing
+    pub actual: Option<String>,
+}
+
+impl fmt::Display for Drift {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let show = |value: &Option<String>| value.clone().unwrap_or_else(|| "<missing>".to_string());
+        write!(f, "{}: {} -> {}", self.field, show(&self.expected), show(&self.actual))
+    }
+}
+
+/// Outcome of replaying a manifest
+#[derive(Debug, Clone, Serialize)]
+pub struct ReplayReport {
+    /// Manifest that was replayed
+    pub manifest_id: String,
+    /// Environment drift detected before running
+    pub drift: Vec<Drift>,
+    /// Whether the command ran; it does not on a dry run
+    pub executed: bool,
+    /// Output of the replayed command, or its error message
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub output: Option<String>,
+    /// Result of the replayed run
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub outcome: Option<RunOutcome>,
+    /// Whether the result matches the recorded one; `None` if either is unknown
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub outcome_matches: Option<bool>,
+}
+
+impl ReplayReport {
+    /// Whether the replay reproduced the recorded run in the recorded environment
+    #[must_use]
+    pub fn is_reproducible(&self) -> bool {
+        self.drift.is_empty() && self.outcome_matches != Some(false)
+    }
+}
+
+impl fmt::Display for ReplayReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "Replay of manifest {}", self.manifest_id)?;
+        if self.drift.is_empty() {
+            writeln!(f, "No environment drift")?;
+        } else {
+            writeln!(f, "Environment drift ({}):", self.drift.len())?;
+            for drift in &self.drift {
+                writeln!(f, "  {drift}")?;
+            }
+        }
+        match self.outcome_matches {
+            _ if !self.executed => writeln!(f, "Dry run: command not executed")?,
+            Some(true) => writeln!(f, "Result matches the recorded run")?,
+            Some(false) => writeln!(f, "Result differs from the recorded run")?,
+            None => writeln!(f, "No recorded result to compare")?,
+        }
+        if let Some(output) = &self.output {
+            write!(f, "{output}")?;
+        }
+        Ok(())
+    }
+}
+
+/// Re-executes the command recorded in `manifest` and reports drift
+///
+/// The current environment is captured with the tools, inputs and allowlist
+/// the manifest recorded, plus the currently loaded `plugins`, and compared
+/// against the manifest before the command runs. With `dry_run` only the
+/// drift is reported.
+///
+/// # Errors
+/// Returns an error if the command does not exist; a failing command is
+/// reported, not returned
+pub fn replay(
+    registry: &CommandRegistry,
+    manifest: &RunManifest,
+    plugins: BTreeMap<String, String>,
+    dry_run: bool,
+) -> CommandResult<ReplayReport> {
+    // Inputs that have gone missing are drift rather than an error
+    let mut options = manifest.options(plugins);
+    let inputs = std::mem::take(&mut options.inputs);
+    let mut current = RunManifest::capture(&manifest.command, &manifest.args, &options)?;
+    current.inputs = inputs.iter().filter_map(|path| InputFile::hash(path).ok()).collect();
+    let mut report = ReplayReport {
+        manifest_id: manifest.id.clone(),
+        drift: manifest.diff(&current),
+        executed: false,
+        output: None,
+        outcome: None,
+        outcome_matches: None,
+    };
+    if dry_run {
+        return Ok(report);
+    }
+
+    let command = registry.get_command(&manifest.command)?;
+    let result = command.execute(&manifest.args);
+    let outcome = RunOutcome::from_result(&result);
+    report.executed = true;
+    report.outcome_matches = manifest.outcome.as_ref().map(|recorded| *recorded == outcome);
+    report.output = Some(match result {
+        Ok(output) => output,
+        Err(err) => err.to_string(),
+    });
+    report.outcome = Some(outcome);
+    Ok(report)
+}
+
+/// First line of `<tool> --version`, or `None` if the tool cannot be run
+fn tool_version(tool: &str) -> Option<String> {
+    let output = Process::new(tool).arg("--version").output().ok()?;
+    let text = if output.stdout.is_empty() { output.stderr } else { output.stdout };
+    String::from_utf8_lossy(&text)
+        .lines()
+        .map(str::trim)
+        .find(|line| !line.is_empty())
+        .map(ToString::to_string)
+}
+
+/// Keys whose values differ between two maps, with both values
+fn diff_maps(
+    expected: &BTreeMap<String, String>,
+    actual: &BTreeMap<String, String>,
+) -> Vec<(String, Option<String>, Option<String>)> {
+    let mut keys: Vec<&String> = expected.keys().chain(actual.keys()).collect();
+    keys.sort();
+    keys.dedup();
+    keys.into_iter()
+        .filter(|key| expected.get(*key) != actual.get(*key))
+        .map(|key| (key.clone(), expected.get(key).cloned(), actual.get(key).cloned()))
+        .collect()
+}
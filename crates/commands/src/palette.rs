@@ -0,0 +1,236 @@
+//! Fuzzy-searchable index of commands
+//!
+//! Every registered command and each of its subcommands becomes a
+//! [`CommandEntry`] carrying its name, description, tags, namespace (the
+//! parent command of a subcommand) and the plugin providing it. A query is
+//! matched against those fields as a case-insensitive subsequence, so `cl`
+//! finds `cache list`; matches at the start of a word or in a run score
+//! higher, and frequently used commands rank above rarely used ones.
+//!
+//! The index powers the command palette of interactive front ends, such as
+//! the web UI quick switcher behind `GET /api/commands/search` and
+//! `squirrel palette`.
+
+use std::cmp::Reverse;
+use std::collections::HashMap;
+
+use serde::{Deserialize, Serialize};
+
+use crate::history::HistoryEntry;
+use crate::Command;
+
+/// Number of hits returned when no limit is given
+pub const DEFAULT_LIMIT: usize = 20;
+
+/// Score of each matched character
+const MATCH_SCORE: i64 = 1;
+/// Bonus for matching the first character of the text
+const START_BONUS: i64 = 8;
+/// Bonus for matching the first character of a word
+const WORD_BONUS: i64 = 6;
+/// Bonus for matching right after the previous match
+const CONSECUTIVE_BONUS: i64 = 4;
+/// Penalty per skipped character between two matches, up to [`MAX_GAP_PENALTY`]
+const GAP_PENALTY: i64 = 1;
+/// Largest penalty of a single gap
+const MAX_GAP_PENALTY: i64 = 3;
+/// Bonus for a text equal to the query
+const EXACT_BONUS: i64 = 20;
+/// Score of each query word found in a description
+const DESCRIPTION_WORD_SCORE: i64 = 5;
+/// Weight of the usage boost, applied to the logarithm of the use count
+const USAGE_WEIGHT: f64 = 4.0;
+
+/// A command as listed in the palette
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CommandEntry {
+    /// Full name, e.g. `cache list` for a subcommand
+    pub name: String,
+    /// What the command does
+    pub description: String,
+    /// Keywords matched besides the name
+    pub tags: Vec<String>,
+    /// Parent command of a subcommand
+    pub namespace: Option<String>,
+    /// Plugin providing the command
+    pub plugin: Option<String>,
+    /// Number of recorded uses
+    pub uses: u64,
+}
+
+/// A command matching a query
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SearchHit {
+    /// The matching command
+    #[serde(flatten)]
+    pub entry: CommandEntry,
+    /// Rank of the hit; higher is better
+    pub score: i64,
+    /// Character positions of the query in the name, for highlighting
+    pub matched: Vec<usize>,
+}
+
+/// A query matched against a text
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FuzzyMatch {
+    /// Quality of the match; higher is better
+    pub score: i64,
+    /// Character positions of the query in the text
+    pub positions: Vec<usize>,
+}
+
+/// Matches `query` against `text` as a case-insensitive subsequence
+///
+/// Whitespace in the query is ignored. Returns `None` if the text does not
+/// contain every character of the query in order.
+#[must_use]
+pub fn fuzzy_match(query: &str, text: &str) -> Option<FuzzyMatch> {
+    let query: Vec<char> = query.chars().filter(|c| !c.is_whitespace()).flat_map(char::to_lowercase).collect();
+    let chars: Vec<char> = text.chars().collect();
+    let lowered: Vec<char> = chars.iter().map(|c| c.to_lowercase().next().unwrap_or(*c)).collect();
+
+    let mut positions = Vec::with_capacity(query.len());
+    let mut score = 0;
+    let mut next = 0;
+    for wanted in &query {
+        let found = (next..lowered.len()).find(|&i| lowered[i] == *wanted)?;
+        score += MATCH_SCORE;
+        if found == 0 {
+            score += START_BONUS;
+        } else if is_word_start(&chars, found) {
+            score += WORD_BONUS;
+        }
+        match positions.last() {
+            Some(&last) if found == last + 1 => score += CONSECUTIVE_BONUS,
+            Some(&last) => score -= (i64::try_from(found - last - 1).unwrap_or(i64::MAX) * GAP_PENALTY).min(MAX_GAP_PENALTY),
+            None => {}
+        }
+        positions.push(found);
+        next = found + 1;
+    }
+    if !query.is_empty() && lowered.iter().filter(|c| !c.is_whitespace()).eq(query.iter()) {
+        score += EXACT_BONUS;
+    }
+    Some(FuzzyMatch { score, positions })
+}
+
+/// Whether the character at `index` begins a word
+fn is_word_start(chars: &[char], index: usize) -> bool {
+    let previous = chars[index - 1];
+    matches!(previous, ' ' | '-' | '_' | ':' | '.' | '/')
+        || (previous.is_lowercase() && chars[index].is_uppercase())
+}
+
+/// Palette entries of a command and, recursively, its subcommands
+#[must_use]
+pub fn entries_for(command: &dyn Command) -> Vec<CommandEntry> {
+    let top = CommandEntry {
+        name: command.name().to_string(),
+        description: command.description().to_string(),
+        tags: command.tags(),
+        namespace: None,
+        plugin: command.plugin().map(str::to_string),
+        uses: 0,
+    };
+    let mut entries = Vec::new();
+    collect_subcommands(&top, &command.parser(), &mut entries);
+    entries.insert(0, top);
+    entries
+}
+
+/// Adds an entry for each subcommand of `parser`, named after `parent`
+fn collect_subcommands(parent: &CommandEntry, parser: &clap::Command, entries: &mut Vec<CommandEntry>) {
+    for sub in parser.get_subcommands().filter(|sub| sub.get_name() != "help" && !sub.is_hide_set()) {
+        let entry = CommandEntry {
+            name: format!("{} {}", parent.name, sub.get_name()),
+            description: sub.get_about().map(ToString::to_string).unwrap_or_default(),
+            tags: parent.tags.clone(),
+            namespace: Some(parent.name.clone()),
+            plugin: parent.plugin.clone(),
+            uses: 0,
+        };
+        collect_subcommands(&entry, sub, entries);
+        entries.push(entry);
+    }
+}
+
+/// Names a use of `command` with `args` counts for
+///
+/// A use counts for its command and, if its first argument names a
+/// subcommand, for `<command> <subcommand>` too; first arguments that are
+/// not subcommand names are counted harmlessly under names no command has.
+#[must_use]
+pub fn usage_keys(command: &str, args: &[String]) -> Vec<String> {
+    let mut keys = vec![command.to_string()];
+    if let Some(first) = args.first().filter(|arg| !arg.starts_with('-')) {
+        keys.push(format!("{command} {first}"));
+    }
+    keys
+}
+
+/// Use counts of commands and subcommands in history entries
+#[must_use]
+pub fn usage_counts(history: &[HistoryEntry]) -> HashMap<String, u64> {
+    let mut counts = HashMap::new();
+    for entry in history {
+        for key in usage_keys(&entry.command, &entry.args) {
+            *counts.entry(key).or_insert(0) += 1;
+        }
+    }
+    counts
+}
+
+/// Ranks the entries matching `query`, best first
+///
+/// An empty query lists every entry, most used first. Entries rank by match
+/// score plus a boost growing with the logarithm of their use count, then by
+/// use count and name. At most `limit` hits are returned.
+#[must_use]
+pub fn rank(entries: Vec<CommandEntry>, query: &str, limit: usize) -> Vec<SearchHit> {
+    let query = query.trim();
+    let mut hits: Vec<SearchHit> = entries
+        .into_iter()
+        .filter_map(|entry| {
+            let (score, matched) = if query.is_empty() {
+                (0, Vec::new())
+            } else {
+                match_entry(&entry, query)?
+            };
+            Some(SearchHit { score: score + usage_boost(entry.uses), entry, matched })
+        })
+        .collect();
+    hits.sort_by(|a, b| {
+        (Reverse(a.score), Reverse(a.entry.uses), &a.entry.name)
+            .cmp(&(Reverse(b.score), Reverse(b.entry.uses), &b.entry.name))
+    });
+    hits.truncate(limit);
+    hits
+}
+
+/// Best match of `query` in the fields of `entry`, with the positions in its name
+fn match_entry(entry: &CommandEntry, query: &str) -> Option<(i64, Vec<usize>)> {
+    let name = fuzzy_match(query, &entry.name);
+    let matched = name.as_ref().map(|m| m.positions.clone()).unwrap_or_default();
+    let mut best = name.map(|m| m.score * 3);
+
+    let keywords = entry.tags.iter().chain(&entry.namespace).chain(&entry.plugin);
+    for keyword in keywords {
+        if let Some(m) = fuzzy_match(query, keyword) {
+            best = best.max(Some(m.score * 2));
+        }
+    }
+
+    let description = entry.description.to_lowercase();
+    let words: Vec<String> = query.split_whitespace().map(str::to_lowercase).collect();
+    if words.iter().all(|word| description.contains(word.as_str())) {
+        let found = i64::try_from(words.len()).unwrap_or(i64::MAX);
+        best = best.max(Some(found * DESCRIPTION_WORD_SCORE));
+    }
+    best.map(|score| (score, matched))
+}
+
+/// Score added for `uses` recorded uses
+#[allow(clippy::cast_possible_truncation, clippy::cast_precision_loss)]
+fn usage_boost(uses: u64) -> i64 {
+    ((uses as f64).ln_1p() * USAGE_WEIGHT).round() as i64
+}
@@ -0,0 +1,335 @@
+//! Per-plugin log capture
+//!
+//! The host installs a [`PluginLogRouter`] in front of its own logger. While
+//! code runs inside a plugin scope ([`scope`] for synchronous calls,
+//! [`scope_async`] for futures), every log record it emits is stored in that
+//! plugin's [`PluginLogBuffer`] and forwarded to the host logger with the
+//! target `plugin::<name>`, so plugin output can be told apart from host
+//! output and filtered on its own. Records outside a plugin scope go straight
+//! to the host logger.
+//!
+//! Buffers are ring buffers keeping the most recent lines of each plugin and
+//! are shared process-wide through [`global`], which is where
+//! `squirrel plugin logs` and the web API read them from.
+
+use std::cell::RefCell;
+use std::collections::{HashMap, VecDeque};
+use std::future::Future;
+use std::sync::{Arc, Mutex, OnceLock, RwLock};
+
+use chrono::{DateTime, Utc};
+use log::{LevelFilter, Log, Metadata, Record, SetLoggerError};
+use serde::{Deserialize, Serialize};
+
+use crate::{Command, CommandResult};
+
+/// Lines kept per plugin unless configured otherwise
+pub const DEFAULT_CAPACITY: usize = 1000;
+
+/// Prefix of the target forwarded plugin records are logged under
+pub const TARGET_PREFIX: &str = "plugin::";
+
+thread_local! {
+    /// Plugin whose synchronous code is running on this thread
+    static THREAD_PLUGIN: RefCell<Option<String>> = const { RefCell::new(None) };
+}
+
+tokio::task_local! {
+    /// Plugin whose future is running in this task
+    static TASK_PLUGIN: String;
+}
+
+/// A captured log line
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct LogLine {
+    /// When the record was emitted
+    pub timestamp: DateTime<Utc>,
+    /// Log level, e.g. `INFO`
+    pub level: String,
+    /// Target of the original record
+    pub target: String,
+    /// Formatted message
+    pub message: String,
+}
+
+impl std::fmt::Display for LogLine {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{} {:<5} {}: {}",
+            self.timestamp.format("%Y-%m-%dT%H:%M:%S%.3fZ"),
+            self.level,
+            self.target,
+            self.message
+        )
+    }
+}
+
+/// Ring buffer of the most recent log lines of one plugin
+#[derive(Debug)]
+pub struct PluginLogBuffer {
+    capacity: usize,
+    lines: Mutex<VecDeque<LogLine>>,
+}
+
+impl PluginLogBuffer {
+    /// Create a buffer keeping at most `capacity` lines
+    #[must_use]
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            capacity: capacity.max(1),
+            lines: Mutex::new(VecDeque::with_capacity(capacity.clamp(1, DEFAULT_CAPACITY))),
+        }
+    }
+
+    /// Append a line, dropping the oldest one if the buffer is full
+    pub fn push(&self, line: LogLine) {
+        let mut lines = self.lines.lock().unwrap_or_else(std::sync::PoisonError::into_inner);
+        if lines.len() == self.capacity {
+            lines.pop_front();
+        }
+        lines.push_back(line);
+    }
+
+    /// The last `limit` lines, oldest first; all lines if `limit` is `None`
+    #[must_use]
+    pub fn recent(&self, limit: Option<usize>) -> Vec<LogLine> {
+        let lines = self.lines.lock().unwrap_or_else(std::sync::PoisonError::into_inner);
+        let skip = limit.map_or(0, |limit| lines.len().saturating_sub(limit));
+        lines.iter().skip(skip).cloned().collect()
+    }
+
+    /// Number of buffered lines
+    #[must_use]
+    pub fn len(&self) -> usize {
+        self.lines.lock().unwrap_or_else(std::sync::PoisonError::into_inner).len()
+    }
+
+    /// Whether no lines are buffered
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+/// Log buffers of all plugins, by plugin name
+#[derive(Debug)]
+pub struct PluginLogs {
+    capacity: usize,
+    buffers: RwLock<HashMap<String, Arc<PluginLogBuffer>>>,
+}
+
+impl Default for PluginLogs {
+    fn default() -> Self {
+        Self::new(DEFAULT_CAPACITY)
+    }
+}
+
+impl PluginLogs {
+    /// Create a registry whose buffers keep `capacity` lines each
+    #[must_use]
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            capacity,
+            buffers: RwLock::new(HashMap::new()),
+        }
+    }
+
+    /// The buffer of `plugin`, created on first use
+    pub fn buffer(&self, plugin: &str) -> Arc<PluginLogBuffer> {
+        if let Some(buffer) = self.get(plugin) {
+            return buffer;
+        }
+        let mut buffers = self.buffers.write().unwrap_or_else(std::sync::PoisonError::into_inner);
+        Arc::clone(
+            buffers
+                .entry(plugin.to_string())
+                .or_insert_with(|| Arc::new(PluginLogBuffer::new(self.capacity))),
+        )
+    }
+
+    /// The buffer of `plugin`, if it has one
+    #[must_use]
+    pub fn get(&self, plugin: &str) -> Option<Arc<PluginLogBuffer>> {
+        self.buffers
+            .read()
+            .unwrap_or_else(std::sync::PoisonError::into_inner)
+            .get(plugin)
+            .cloned()
+    }
+
+    /// The last `limit` lines of `plugin`, or `None` if it never logged
+    #[must_use]
+    pub fn recent(&self, plugin: &str, limit: Option<usize>) -> Option<Vec<LogLine>> {
+        self.get(plugin).map(|buffer| buffer.recent(limit))
+    }
+
+    /// Names of the plugins with a buffer, sorted
+    #[must_use]
+    pub fn plugins(&self) -> Vec<String> {
+        let mut names: Vec<String> = self
+            .buffers
+            .read()
+            .unwrap_or_else(std::sync::PoisonError::into_inner)
+            .keys()
+            .cloned()
+            .collect();
+        names.sort();
+        names
+    }
+
+    /// Drop the buffer of `plugin`
+    pub fn remove(&self, plugin: &str) {
+        self.buffers
+            .write()
+            .unwrap_or_else(std::sync::PoisonError::into_inner)
+            .remove(plugin);
+    }
+}
+
+/// Process-wide plugin log buffers
+pub fn global() -> &'static PluginLogs {
+    static LOGS: OnceLock<PluginLogs> = OnceLock::new();
+    LOGS.get_or_init(PluginLogs::default)
+}
+
+/// The plugin the current code runs on behalf of, if any
+#[must_use]
+pub fn current_plugin() -> Option<String> {
+    TASK_PLUGIN
+        .try_with(Clone::clone)
+        .ok()
+        .or_else(|| THREAD_PLUGIN.with(|current| current.borrow().clone()))
+}
+
+/// Run `f` on behalf of `plugin`
+pub fn scope<T>(plugin: &str, f: impl FnOnce() -> T) -> T {
+    /// Restores the enclosing scope, also when `f` panics
+    struct Restore(Option<String>);
+
+    impl Drop for Restore {
+        fn drop(&mut self) {
+            THREAD_PLUGIN.with(|current| *current.borrow_mut() = self.0.take());
+        }
+    }
+
+    let _restore = Restore(THREAD_PLUGIN.with(|current| current.borrow_mut().replace(plugin.to_string())));
+    f()
+}
+
+/// Run `future` on behalf of `plugin`
+pub async fn scope_async<F: Future>(plugin: &str, future: F) -> F::Output {
+    TASK_PLUGIN.scope(plugin.to_string(), future).await
+}
+
+/// Logger routing records emitted in a plugin scope to that plugin's buffer
+pub struct PluginLogRouter {
+    inner: Box<dyn Log>,
+    logs: &'static PluginLogs,
+}
+
+impl PluginLogRouter {
+    /// Wrap the host logger, capturing into `logs`
+    #[must_use]
+    pub fn new(inner: Box<dyn Log>, logs: &'static PluginLogs) -> Self {
+        Self { inner, logs }
+    }
+}
+
+impl Log for PluginLogRouter {
+    fn enabled(&self, metadata: &Metadata<'_>) -> bool {
+        self.inner.enabled(metadata)
+    }
+
+    fn log(&self, record: &Record<'_>) {
+        let Some(plugin) = current_plugin() else {
+            self.inner.log(record);
+            return;
+        };
+
+        self.logs.buffer(&plugin).push(LogLine {
+            timestamp: Utc::now(),
+            level: record.level().to_string(),
+            target: record.target().to_string(),
+            message: record.args().to_string(),
+        });
+
+        let target = format!("{TARGET_PREFIX}{plugin}");
+        self.inner.log(
+            &Record::builder()
+                .args(*record.args())
+                .level(record.level())
+                .target(&target)
+                .module_path(record.module_path())
+                .file(record.file())
+                .line(record.line())
+                .build(),
+        );
+    }
+
+    fn flush(&self) {
+        self.inner.flush();
+    }
+}
+
+/// Install the router in front of `host` as the global logger
+///
+/// # Errors
+/// Returns an error if a global logger is already installed
+pub fn install(host: Box<dyn Log>, max_level: LevelFilter) -> Result<(), SetLoggerError> {
+    log::set_boxed_logger(Box::new(PluginLogRouter::new(host, global())))?;
+    log::set_max_level(max_level);
+    Ok(())
+}
+
+/// A plugin command whose executions run in the plugin's scope
+#[derive(Clone)]
+pub struct ScopedCommand {
+    plugin: String,
+    inner: Arc<dyn Command>,
+}
+
+impl ScopedCommand {
+    /// Wrap `command`, provided by `plugin`
+    #[must_use]
+    pub fn new(plugin: &str, inner: Arc<dyn Command>) -> Self {
+        Self {
+            plugin: plugin.to_string(),
+            inner,
+        }
+    }
+}
+
+impl Command for ScopedCommand {
+    fn name(&self) -> &str {
+        self.inner.name()
+    }
+
+    fn description(&self) -> &str {
+        self.inner.description()
+    }
+
+    fn execute(&self, args: &[String]) -> CommandResult<String> {
+        scope(&self.plugin, || self.inner.execute(args))
+    }
+
+    fn help(&self) -> String {
+        self.inner.help()
+    }
+
+    fn tags(&self) -> Vec<String> {
+        self.inner.tags()
+    }
+
+    fn plugin(&self) -> Option<&str> {
+        Some(&self.plugin)
+    }
+
+    fn parser(&self) -> clap::Command {
+        self.inner.parser()
+    }
+
+    fn clone_box(&self) -> Box<dyn Command> {
+        Box::new(self.clone())
+    }
+}
@@ -0,0 +1,302 @@
+//! Command allow and deny policies
+//!
+//! A [`CommandPolicy`] attaches to a user or an API key and allows or denies
+//! the commands, namespaces or tools matching a glob pattern, where `*`
+//! matches any run of characters and `?` a single one. A command's namespace
+//! is the plugin providing it, or [`BUILTIN_NAMESPACE`] for built-in
+//! commands. Command patterns match the command name, and `<command>
+//! <subcommand>` when the first argument names a subcommand, so `cache clear`
+//! can be denied while `cache list` stays allowed.
+//!
+//! Policies are evaluated before dispatch, by the web server for submitted
+//! commands and tool calls and by the CLI execution context. A matching deny
+//! always wins. Once a subject has any allow policy for a kind of target,
+//! targets of that kind it does not match are denied too, so allow policies
+//! turn into an allowlist. Subjects without policies may run everything.
+
+use std::fmt;
+use std::str::FromStr;
+
+use serde::{Deserialize, Serialize};
+
+use crate::CommandError;
+
+/// Namespace of the commands no plugin provides
+pub const BUILTIN_NAMESPACE: &str = "builtin";
+
+/// Who a policy applies to, written `user:<id>` or `api_key:<id>`
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(try_from = "String", into = "String")]
+pub enum PolicySubject {
+    /// A user, by ID
+    User(String),
+    /// An API key, by ID
+    ApiKey(String),
+}
+
+impl fmt::Display for PolicySubject {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::User(id) => write!(f, "user:{}", id),
+            Self::ApiKey(id) => write!(f, "api_key:{}", id),
+        }
+    }
+}
+
+impl FromStr for PolicySubject {
+    type Err = String;
+
+    fn from_str(subject: &str) -> Result<Self, Self::Err> {
+        match subject.split_once(':') {
+            Some((_, "")) => Err(format!("Policy subject '{}' names no ID", subject)),
+            Some(("user", id)) => Ok(Self::User(id.to_string())),
+            Some(("api_key", id)) => Ok(Self::ApiKey(id.to_string())),
+            _ => Err(format!("Invalid policy subject '{}', expected user:<id> or api_key:<id>", subject)),
+        }
+    }
+}
+
+impl TryFrom<String> for PolicySubject {
+    type Error = String;
+
+    fn try_from(subject: String) -> Result<Self, Self::Error> {
+        subject.parse()
+    }
+}
+
+impl From<PolicySubject> for String {
+    fn from(subject: PolicySubject) -> Self {
+        subject.to_string()
+    }
+}
+
+/// Whether a policy allows or denies what it matches
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum PolicyEffect {
+    /// Allow the matching targets, and deny the others of the same kind
+    Allow,
+    /// Deny the matching targets
+    Deny,
+}
+
+impl PolicyEffect {
+    /// The effect as written, e.g. `deny`
+    #[must_use]
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Allow => "allow",
+            Self::Deny => "deny",
+        }
+    }
+}
+
+impl FromStr for PolicyEffect {
+    type Err = String;
+
+    fn from_str(effect: &str) -> Result<Self, Self::Err> {
+        match effect {
+            "allow" => Ok(Self::Allow),
+            "deny" => Ok(Self::Deny),
+            other => Err(format!("Invalid policy effect '{}', expected allow or deny", other)),
+        }
+    }
+}
+
+/// What a policy's pattern is matched against
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum PolicyScope {
+    /// The command name, and `<command> <subcommand>`
+    #[default]
+    Command,
+    /// The plugin providing the command, or [`BUILTIN_NAMESPACE`]
+    Namespace,
+    /// The ID of an MCP tool
+    Tool,
+}
+
+impl PolicyScope {
+    /// The scope as written, e.g. `namespace`
+    #[must_use]
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Command => "command",
+            Self::Namespace => "namespace",
+            Self::Tool => "tool",
+        }
+    }
+}
+
+impl FromStr for PolicyScope {
+    type Err = String;
+
+    fn from_str(scope: &str) -> Result<Self, Self::Err> {
+        match scope {
+            "command" => Ok(Self::Command),
+            "namespace" => Ok(Self::Namespace),
+            "tool" => Ok(Self::Tool),
+            other => Err(format!("Invalid policy scope '{}', expected command, namespace or tool", other)),
+        }
+    }
+}
+
+/// Allows or denies a subject the targets matching a pattern
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CommandPolicy {
+    /// Policy ID, named in denials
+    #[serde(default)]
+    pub id: String,
+    /// User or API key the policy applies to
+    pub subject: PolicySubject,
+    /// Whether matching targets are allowed or denied
+    pub effect: PolicyEffect,
+    /// What the pattern is matched against
+    #[serde(default)]
+    pub scope: PolicyScope,
+    /// Glob pattern; `*` matches any run of characters, `?` one
+    pub pattern: String,
+    /// Why the policy exists
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+}
+
+impl CommandPolicy {
+    /// Whether the policy's scope and pattern match `target`
+    #[must_use]
+    pub fn matches(&self, target: &PolicyTarget) -> bool {
+        match (self.scope, target) {
+            (PolicyScope::Command, PolicyTarget::Command { name, subcommand, .. }) => {
+                glob_matches(&self.pattern, name)
+                    || subcommand
+                        .as_ref()
+                        .is_some_and(|sub| glob_matches(&self.pattern, &format!("{} {}", name, sub)))
+            }
+            (PolicyScope::Namespace, PolicyTarget::Command { namespace, .. }) => glob_matches(&self.pattern, namespace),
+            (PolicyScope::Tool, PolicyTarget::Tool(id)) => glob_matches(&self.pattern, id),
+            _ => false,
+        }
+    }
+
+    /// Whether the policy could match targets of the same kind as `target`
+    fn applies_to(&self, target: &PolicyTarget) -> bool {
+        matches!(
+            (self.scope, target),
+            (PolicyScope::Command | PolicyScope::Namespace, PolicyTarget::Command { .. })
+                | (PolicyScope::Tool, PolicyTarget::Tool(_))
+        )
+    }
+}
+
+/// What a subject asks to run
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PolicyTarget {
+    /// A command
+    Command {
+        /// Command name
+        name: String,
+        /// Its first argument, if it names a subcommand
+        subcommand: Option<String>,
+        /// Plugin providing the command, or [`BUILTIN_NAMESPACE`]
+        namespace: String,
+    },
+    /// An MCP tool, by ID
+    Tool(String),
+}
+
+impl PolicyTarget {
+    /// A command invoked with `args`, provided by `plugin`
+    ///
+    /// A first argument starting with `-` is an option, not a subcommand.
+    #[must_use]
+    pub fn command(name: &str, args: &[String], plugin: Option<&str>) -> Self {
+        Self::Command {
+            name: name.to_string(),
+            subcommand: args.first().filter(|arg| !arg.starts_with('-')).cloned(),
+            namespace: plugin.unwrap_or(BUILTIN_NAMESPACE).to_string(),
+        }
+    }
+
+    /// An MCP tool
+    #[must_use]
+    pub fn tool(id: &str) -> Self {
+        Self::Tool(id.to_string())
+    }
+}
+
+impl fmt::Display for PolicyTarget {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Command { name, subcommand: Some(sub), .. } => write!(f, "command '{} {}'", name, sub),
+            Self::Command { name, .. } => write!(f, "command '{}'", name),
+            Self::Tool(id) => write!(f, "tool '{}'", id),
+        }
+    }
+}
+
+/// Check `target` against the policies attached to any of `subjects`
+///
+/// Fails with [`CommandError::AuthorizationError`] naming the denying
+/// policy, or the subject whose allowlist the target is missing from.
+pub fn evaluate<'a>(
+    policies: impl IntoIterator<Item = &'a CommandPolicy>,
+    subjects: &[PolicySubject],
+    target: &PolicyTarget,
+) -> Result<(), CommandError> {
+    let applicable: Vec<&CommandPolicy> = policies
+        .into_iter()
+        .filter(|policy| subjects.contains(&policy.subject) && policy.applies_to(target))
+        .collect();
+
+    if let Some(policy) = applicable
+        .iter()
+        .find(|policy| policy.effect == PolicyEffect::Deny && policy.matches(target))
+    {
+        return Err(CommandError::AuthorizationError(format!(
+            "The {} is denied to {} by policy {}",
+            target, policy.subject, policy.id
+        )));
+    }
+
+    let allows: Vec<&&CommandPolicy> = applicable
+        .iter()
+        .filter(|policy| policy.effect == PolicyEffect::Allow)
+        .collect();
+    if !allows.is_empty() && !allows.iter().any(|policy| policy.matches(target)) {
+        return Err(CommandError::AuthorizationError(format!(
+            "The {} is not allowed for {}",
+            target, allows[0].subject
+        )));
+    }
+    Ok(())
+}
+
+/// Whether `text` matches the glob `pattern`
+fn glob_matches(pattern: &str, text: &str) -> bool {
+    let pattern: Vec<char> = pattern.chars().collect();
+    let text: Vec<char> = text.chars().collect();
+    let (mut p, mut t) = (0, 0);
+    // Position of the last `*` and the text it has swallowed up to
+    let mut backtrack: Option<(usize, usize)> = None;
+    while t < text.len() {
+        match pattern.get(p) {
+            Some('*') => {
+                backtrack = Some((p, t));
+                p += 1;
+            }
+            Some(&c) if c == '?' || c == text[t] => {
+                p += 1;
+                t += 1;
+            }
+            _ => match backtrack {
+                Some((star, swallowed)) => {
+                    p = star + 1;
+                    t = swallowed + 1;
+                    backtrack = Some((star, swallowed + 1));
+                }
+                None => return false,
+            },
+        }
+    }
+    pattern[p..].iter().all(|&c| c == '*')
+}
@@ -9,6 +9,8 @@ use std::time::{Duration, Instant};
 
 use tracing::{debug, info, warn, error};
 
+use crate::history::CommandHistory;
+use crate::palette::{self, CommandEntry, SearchHit};
 use crate::CommandError;
 
 /// Type alias for command operation results
@@ -30,6 +32,16 @@ pub trait Command: Send + Sync {
         format!("{}: {}", self.name(), self.description())
     }
     
+    /// Returns keywords the command palette matches besides the name
+    fn tags(&self) -> Vec<String> {
+        Vec::new()
+    }
+    
+    /// Returns the name of the plugin providing the command, if any
+    fn plugin(&self) -> Option<&str> {
+        None
+    }
+    
     /// Returns a parser for the command's arguments
     fn parser(&self) -> clap::Command;
     
@@ -71,6 +83,8 @@ impl LockTimer {
 pub struct CommandRegistry {
     /// Map of command names to command implementations
     commands: Arc<Mutex<HashMap<String, Arc<dyn Command>>>>,
+    /// Number of uses of each command, ranking palette searches
+    usage: Arc<Mutex<HashMap<String, u64>>>,
 }
 
 // Manual implementation of Debug for CommandRegistry
@@ -100,6 +114,7 @@ impl CommandRegistry {
         debug!("Creating new CommandRegistry instance");
         Self {
             commands: Arc::new(Mutex::new(HashMap::new())),
+            usage: Arc::new(Mutex::new(HashMap::new())),
         }
     }
     
@@ -171,6 +186,8 @@ impl CommandRegistry {
         timer.end();
         debug!("Registry: Lock released before command execution");
         
+        self.record_usage(name, args);
+        
         // Execute the command without holding the lock
         let start = Instant::now();
         let result = command.execute(args);
@@ -290,6 +307,77 @@ impl CommandRegistry {
         
         Ok(commands.contains_key(name))
     }
+    
+    /// Counts a use of a command, and of its subcommand if `args` start with one
+    pub fn record_usage(&self, name: &str, args: &[String]) {
+        if let Ok(mut usage) = self.usage.lock() {
+            for key in palette::usage_keys(name, args) {
+                *usage.entry(key).or_insert(0) += 1;
+            }
+        }
+    }
+    
+    /// Adds the uses recorded in a command history to the usage counts
+    /// 
+    /// # Errors
+    /// 
+    /// Returns an error if the history or the usage counts cannot be read
+    pub fn load_usage(&self, history: &CommandHistory) -> CommandResult<usize> {
+        let entries = history.get_last(usize::MAX)?;
+        let mut usage = self.usage.lock().map_err(|e| {
+            CommandError::RegistryError(format!("Failed to acquire lock: {}", e))
+        })?;
+        for (key, count) in palette::usage_counts(&entries) {
+            *usage.entry(key).or_insert(0) += count;
+        }
+        debug!("Registry: Loaded usage of {} history entries", entries.len());
+        Ok(entries.len())
+    }
+    
+    /// Returns the palette entries of all commands and their subcommands
+    /// 
+    /// # Errors
+    /// 
+    /// Returns an error if the command registry cannot be locked
+    pub fn index(&self) -> CommandResult<Vec<CommandEntry>> {
+        let timer = LockTimer::new("index");
+        let commands: Vec<Arc<dyn Command>> = {
+            let commands = self.commands.lock()
+                .map_err(|e| {
+                    error!("Registry: Failed to acquire lock for index: {}", e);
+                    CommandError::RegistryError(format!("Failed to acquire lock: {}", e))
+                })?;
+            commands.values().cloned().collect()
+        };
+        timer.end();
+        
+        let usage = self.usage.lock()
+            .map_err(|e| CommandError::RegistryError(format!("Failed to acquire lock: {}", e)))?
+            .clone();
+        let mut entries: Vec<CommandEntry> = commands
+            .iter()
+            .flat_map(|command| palette::entries_for(command.as_ref()))
+            .collect();
+        for entry in &mut entries {
+            entry.uses = usage.get(&entry.name).copied().unwrap_or(0);
+        }
+        entries.sort_by(|a, b| a.name.cmp(&b.name));
+        Ok(entries)
+    }
+    
+    /// Searches the commands fuzzily, best and most used matches first
+    /// 
+    /// # Arguments
+    /// 
+    /// * `query` - Characters to find in order in a command's name, tags,
+    ///   namespace or plugin, or words to find in its description
+    /// 
+    /// # Errors
+    /// 
+    /// Returns an error if the command registry cannot be locked
+    pub fn search(&self, query: &str) -> CommandResult<Vec<SearchHit>> {
+        Ok(palette::rank(self.index()?, query, palette::DEFAULT_LIMIT))
+    }
 }
 
 impl Default for CommandRegistry {
@@ -0,0 +1,819 @@
+//! Run reports
+//!
+//! A [`RunReport`] gathers what is known about a finished job or workflow
+//! run: its parameters, steps, log lines, metric series and artifacts. It
+//! renders as a standalone Markdown or HTML document by filling a template
+//! whose `{{ name }}` placeholders stand for the rendered [`SECTIONS`]. Each
+//! metric series becomes a chart, an inline SVG bar chart in HTML and a
+//! sparkline in Markdown, so a report needs no other files to be viewed.
+//!
+//! Templates are rendered by the process-wide
+//! [`TemplateEngine`](squirrel_core::template::TemplateEngine), so they may
+//! also use its helpers and read the report itself as `report`, e.g.
+//! `{{#each report.steps}}`. The files `report.html.hbs` and
+//! `report.md.hbs` in the user's template directory replace the default
+//! templates.
+//!
+//! `squirrel report <job-id>` renders reports of jobs recorded in the MCP
+//! persistence directory, and the web server serves them at
+//! `GET /api/jobs/:id/report`.
+
+use std::collections::BTreeMap;
+use std::fmt::Write as _;
+use std::str::FromStr;
+
+use chrono::{DateTime, TimeZone, Utc};
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+use squirrel_core::template;
+use tracing::warn;
+
+use crate::workflow::{StepStatus, WorkflowRun};
+use crate::{CommandError, CommandResult};
+
+/// Placeholders a report template may use
+pub const SECTIONS: [&str; 9] = [
+    "title",
+    "summary",
+    "parameters",
+    "steps",
+    "charts",
+    "logs",
+    "outputs",
+    "artifacts",
+    "generated_at",
+];
+
+/// Default Markdown template
+pub const MARKDOWN_TEMPLATE: &str = "# {{ title }}
+
+{{ summary }}
+
+## Parameters
+
+{{ parameters }}
+
+## Steps
+
+{{ steps }}
+
+## Metrics
+
+{{ charts }}
+
+## Outputs
+
+{{ outputs }}
+
+## Artifacts
+
+{{ artifacts }}
+
+## Logs
+
+{{ logs }}
+
+_Generated {{ generated_at }}_
+";
+
+/// Default HTML template
+pub const HTML_TEMPLATE: &str = r#"<!DOCTYPE html>
+<html lang="en">
+<head>
+<meta charset="utf-8">
+<title>{{ title }}</title>
+<style>
+body { font-family: sans-serif; margin: 2em auto; max-width: 60em; color: #222; }
+table { border-collapse: collapse; margin: 1em 0; }
+th, td { border: 1px solid #ccc; padding: 0.3em 0.6em; text-align: left; vertical-align: top; }
+pre { background: #f6f6f6; padding: 0.8em; overflow-x: auto; }
+.succeeded, .completed { color: #1a7f37; }
+.failed, .cancelled { color: #cf222e; }
+figure { margin: 1em 0; }
+</style>
+</head>
+<body>
+<h1>{{ title }}</h1>
+{{ summary }}
+<h2>Parameters</h2>
+{{ parameters }}
+<h2>Steps</h2>
+{{ steps }}
+<h2>Metrics</h2>
+{{ charts }}
+<h2>Outputs</h2>
+{{ outputs }}
+<h2>Artifacts</h2>
+{{ artifacts }}
+<h2>Logs</h2>
+{{ logs }}
+<footer><small>Generated {{ generated_at }}</small></footer>
+</body>
+</html>
+"#;
+
+/// Blocks drawing a Markdown sparkline, lowest first
+const SPARKS: [char; 8] = ['▁', '▂', '▃', '▄', '▅', '▆', '▇', '█'];
+
+/// Outcomes the HTML stylesheet colours
+const STATUS_CLASSES: [&str; 4] = ["succeeded", "completed", "failed", "cancelled"];
+
+/// Size of an SVG chart in pixels
+const CHART_WIDTH: f64 = 480.0;
+const CHART_HEIGHT: f64 = 120.0;
+
+/// Document format of a rendered report
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ReportFormat {
+    /// Standalone HTML page
+    #[default]
+    Html,
+    /// Markdown document
+    Markdown,
+}
+
+impl ReportFormat {
+    /// File extension of the format
+    #[must_use]
+    pub const fn extension(self) -> &'static str {
+        match self {
+            Self::Html => "html",
+            Self::Markdown => "md",
+        }
+    }
+
+    /// MIME type of the format
+    #[must_use]
+    pub const fn content_type(self) -> &'static str {
+        match self {
+            Self::Html => "text/html; charset=utf-8",
+            Self::Markdown => "text/markdown; charset=utf-8",
+        }
+    }
+
+    /// Default template of the format
+    #[must_use]
+    pub const fn template(self) -> &'static str {
+        match self {
+            Self::Html => HTML_TEMPLATE,
+            Self::Markdown => MARKDOWN_TEMPLATE,
+        }
+    }
+
+    /// Name of the template file replacing the default template
+    #[must_use]
+    pub const fn template_name(self) -> &'static str {
+        match self {
+            Self::Html => "report.html",
+            Self::Markdown => "report.md",
+        }
+    }
+}
+
+impl FromStr for ReportFormat {
+    type Err = CommandError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "html" | "htm" => Ok(Self::Html),
+            "markdown" | "md" => Ok(Self::Markdown),
+            other => Err(CommandError::ValidationError(format!(
+                "Unknown report format '{other}', expected html or markdown"
+            ))),
+        }
+    }
+}
+
+/// A step of a reported run
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ReportStep {
+    /// Step identifier
+    pub id: String,
+    /// Command the step ran, if known
+    pub command: Option<String>,
+    /// Final state, e.g. `succeeded`
+    pub status: String,
+    /// Number of attempts made
+    pub attempts: u32,
+    /// Whether the output was taken from a cache
+    pub cached: bool,
+    /// Parameters the step ran with, as JSON
+    pub parameters: Option<String>,
+    /// Error of the last failed attempt
+    pub error: Option<String>,
+    /// When the step started
+    pub started_at: Option<DateTime<Utc>>,
+    /// When the step finished
+    pub finished_at: Option<DateTime<Utc>>,
+}
+
+impl ReportStep {
+    /// Wall-clock duration of the step in milliseconds
+    #[must_use]
+    pub fn duration_ms(&self) -> Option<i64> {
+        Some((self.finished_at? - self.started_at?).num_milliseconds())
+    }
+}
+
+/// A point of a metric series
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct MetricPoint {
+    /// Label of the point on the chart, e.g. a time or a step
+    pub label: String,
+    /// Value of the point
+    pub value: f64,
+}
+
+/// A metric series drawn as a chart
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct MetricSeries {
+    /// Name of the chart
+    pub name: String,
+    /// Points in drawing order
+    pub points: Vec<MetricPoint>,
+}
+
+impl MetricSeries {
+    /// A series of labelled values
+    pub fn new(name: impl Into<String>, points: impl IntoIterator<Item = (String, f64)>) -> Self {
+        Self {
+            name: name.into(),
+            points: points
+                .into_iter()
+                .map(|(label, value)| MetricPoint { label, value })
+                .collect(),
+        }
+    }
+
+    /// A series of samples taken at Unix timestamps in seconds, oldest first
+    pub fn from_samples(name: impl Into<String>, samples: impl IntoIterator<Item = (i64, f64)>) -> Self {
+        let mut samples: Vec<(i64, f64)> = samples.into_iter().collect();
+        samples.sort_by_key(|(timestamp, _)| *timestamp);
+        Self::new(
+            name,
+            samples.into_iter().map(|(timestamp, value)| {
+                let label = Utc
+                    .timestamp_opt(timestamp, 0)
+                    .single()
+                    .map_or_else(|| timestamp.to_string(), |time| time.format("%H:%M:%S").to_string());
+                (label, value)
+            }),
+        )
+    }
+
+    fn max(&self) -> f64 {
+        self.points.iter().map(|point| point.value).fold(0.0, f64::max)
+    }
+
+    fn min(&self) -> f64 {
+        self.points.iter().map(|point| point.value).fold(f64::INFINITY, f64::min)
+    }
+}
+
+/// A file or URL a run produced
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Artifact {
+    /// Name of the artifact
+    pub name: String,
+    /// Where the artifact can be fetched
+    pub href: String,
+}
+
+/// Everything a report shows about a run
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RunReport {
+    /// Job or run ID
+    pub id: String,
+    /// Name of the job or workflow
+    pub name: String,
+    /// `job` or `workflow`
+    pub kind: String,
+    /// Final state, e.g. `succeeded`
+    pub status: String,
+    /// Parameters or inputs of the run
+    pub parameters: BTreeMap<String, String>,
+    /// Steps in execution order
+    pub steps: Vec<ReportStep>,
+    /// Log lines, oldest first
+    pub logs: Vec<String>,
+    /// Metric series shown as charts
+    pub charts: Vec<MetricSeries>,
+    /// Named outputs of the run
+    pub outputs: BTreeMap<String, String>,
+    /// Files and URLs the run produced
+    pub artifacts: Vec<Artifact>,
+    /// When the run was created
+    pub created_at: Option<DateTime<Utc>>,
+    /// When the run finished
+    pub finished_at: Option<DateTime<Utc>>,
+}
+
+impl RunReport {
+    /// An empty report of a run
+    pub fn new(id: impl Into<String>, name: impl Into<String>, kind: impl Into<String>) -> Self {
+        Self {
+            id: id.into(),
+            name: name.into(),
+            kind: kind.into(),
+            status: "unknown".to_string(),
+            parameters: BTreeMap::new(),
+            steps: Vec::new(),
+            logs: Vec::new(),
+            charts: Vec::new(),
+            outputs: BTreeMap::new(),
+            artifacts: Vec::new(),
+            created_at: None,
+            finished_at: None,
+        }
+    }
+
+    /// Report of a workflow run
+    ///
+    /// Step outcomes become log lines, step durations a chart, and outputs
+    /// naming a URL or a path also become artifacts.
+    #[must_use]
+    pub fn from_workflow_run(run: &WorkflowRun) -> Self {
+        let mut report = Self::new(&run.id, &run.workflow, "workflow");
+        report.status = serde_json::to_value(run.status)
+            .ok()
+            .and_then(|status| status.as_str().map(str::to_string))
+            .unwrap_or_default();
+        report.parameters = run.inputs.iter().map(|(k, v)| (k.clone(), v.clone())).collect();
+        report.outputs = run.outputs.iter().map(|(k, v)| (k.clone(), v.clone())).collect();
+        report.created_at = Some(run.created_at);
+        report.finished_at = run.finished_at;
+
+        for step in &run.steps {
+            let status = serde_json::to_value(step.status)
+                .ok()
+                .and_then(|status| status.as_str().map(str::to_string))
+                .unwrap_or_default();
+            report.steps.push(ReportStep {
+                id: step.id.clone(),
+                command: None,
+                status,
+                attempts: step.attempts,
+                cached: step.cached,
+                parameters: None,
+                error: step.error.clone(),
+                started_at: step.started_at,
+                finished_at: step.finished_at,
+            });
+
+            let at = step
+                .finished_at
+                .or(step.started_at)
+                .map_or_else(|| "-".to_string(), |time| time.to_rfc3339());
+            let line = match step.status {
+                StepStatus::Succeeded if step.cached => format!("{at} {} succeeded from cache", step.id),
+                StepStatus::Succeeded => format!("{at} {} succeeded after {} attempt(s)", step.id, step.attempts),
+                StepStatus::Failed => format!(
+                    "{at} {} failed: {}",
+                    step.id,
+                    step.error.as_deref().unwrap_or("unknown error")
+                ),
+                StepStatus::Skipped => format!(
+                    "{at} {} skipped: {}",
+                    step.id,
+                    step.skip_reason.as_deref().unwrap_or("not run")
+                ),
+                StepStatus::Pending | StepStatus::Running => format!("{at} {} did not finish", step.id),
+            };
+            report.logs.push(line);
+        }
+
+        report.add_duration_chart();
+        let artifacts: Vec<Artifact> = report
+            .outputs
+            .iter()
+            .filter(|(_, value)| looks_like_artifact(value))
+            .map(|(name, value)| Artifact {
+                name: name.clone(),
+                href: value.trim().to_string(),
+            })
+            .collect();
+        report.artifacts = artifacts;
+        report
+    }
+
+    /// Report of a job from the data of its MCP context
+    ///
+    /// `state` is the state the job finished in, if it has. The commands
+    /// appended to the context's `steps` become the steps of the report.
+    #[must_use]
+    pub fn from_job_context(job_id: &str, name: &str, state: Option<&str>, data: &Value) -> Self {
+        let mut report = Self::new(job_id, name, "job");
+        report.status = state.unwrap_or("running").to_ascii_lowercase();
+
+        let steps = data.get("steps").and_then(Value::as_array).cloned().unwrap_or_default();
+        for (index, step) in steps.iter().enumerate() {
+            let command = step.get("command").and_then(Value::as_str).map(str::to_string);
+            let submitted_at = step
+                .get("submitted_at")
+                .and_then(|time| serde_json::from_value::<DateTime<Utc>>(time.clone()).ok());
+            let id = step
+                .get("command_id")
+                .and_then(Value::as_str)
+                .map_or_else(|| format!("step-{}", index + 1), str::to_string);
+            let parameters = step
+                .get("parameters")
+                .filter(|parameters| !parameters.is_null())
+                .map(Value::to_string);
+            report.logs.push(format!(
+                "{} submitted {} ({})",
+                submitted_at.map_or_else(|| "-".to_string(), |time| time.to_rfc3339()),
+                command.as_deref().unwrap_or("command"),
+                id
+            ));
+            report.created_at = report.created_at.or(submitted_at);
+            report.steps.push(ReportStep {
+                id,
+                command,
+                status: "submitted".to_string(),
+                attempts: 1,
+                cached: false,
+                parameters,
+                error: None,
+                started_at: submitted_at,
+                finished_at: None,
+            });
+        }
+
+        // Other scalar fields of the context are what the job's steps shared
+        if let Value::Object(fields) = data {
+            for (key, value) in fields {
+                match value {
+                    Value::String(text) if key != "job_id" => {
+                        report.parameters.insert(key.clone(), text.clone());
+                    }
+                    Value::Number(_) | Value::Bool(_) => {
+                        report.parameters.insert(key.clone(), value.to_string());
+                    }
+                    _ => {}
+                }
+            }
+        }
+        report
+    }
+
+    /// Adds a chart
+    #[must_use]
+    pub fn with_chart(mut self, series: MetricSeries) -> Self {
+        if !series.points.is_empty() {
+            self.charts.push(series);
+        }
+        self
+    }
+
+    /// Adds an artifact
+    #[must_use]
+    pub fn with_artifact(mut self, name: impl Into<String>, href: impl Into<String>) -> Self {
+        self.artifacts.push(Artifact {
+            name: name.into(),
+            href: href.into(),
+        });
+        self
+    }
+
+    /// Adds log lines
+    #[must_use]
+    pub fn with_logs(mut self, lines: impl IntoIterator<Item = String>) -> Self {
+        self.logs.extend(lines);
+        self
+    }
+
+    /// Renders the report with the user's template of `format`, or the
+    /// default template if there is none or it fails
+    #[must_use]
+    pub fn render(&self, format: ReportFormat) -> String {
+        let data = self.template_data(format);
+        template::engine()
+            .render_or(format.template_name(), format.template(), &data)
+            .unwrap_or_else(|e| {
+                warn!("{e}; rendering the default report template");
+                template::engine()
+                    .render_str(format.template(), &data)
+                    .expect("default templates only use known sections")
+            })
+    }
+
+    /// Renders the report with a custom template
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the template is invalid, for example because it
+    /// uses a placeholder that is not one of the [`SECTIONS`] or leaves one
+    /// unclosed
+    pub fn render_with(&self, template: &str, format: ReportFormat) -> CommandResult<String> {
+        template::engine()
+            .render_str(template, &self.template_data(format))
+            .map_err(|e| CommandError::ValidationError(e.to_string()))
+    }
+
+    /// Renders the report with the named template of the user's template
+    /// directory
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if there is no such template or it is invalid
+    pub fn render_named(&self, name: &str, format: ReportFormat) -> CommandResult<String> {
+        template::engine()
+            .render(name, &self.template_data(format))
+            .map_err(|e| CommandError::ValidationError(e.to_string()))
+    }
+
+    /// The data templates are rendered with: every rendered section, and
+    /// the report as `report`
+    fn template_data(&self, format: ReportFormat) -> Value {
+        let mut data: Map<String, Value> = SECTIONS
+            .iter()
+            .map(|name| (name.to_string(), Value::String(self.section(name, format).unwrap_or_default())))
+            .collect();
+        data.insert("report".to_string(), serde_json::to_value(self).unwrap_or_default());
+        Value::Object(data)
+    }
+
+    /// Renders one section of the report
+    fn section(&self, name: &str, format: ReportFormat) -> CommandResult<String> {
+        let html = format == ReportFormat::Html;
+        let text = match name {
+            "title" => {
+                let title = format!("Report of {} {} ({})", self.kind, self.name, self.id);
+                if html { escape_html(&title) } else { title }
+            }
+            "summary" => self.summary(format),
+            "parameters" => table(format, &["Name", "Value"], map_rows(&self.parameters)),
+            "outputs" => table(format, &["Name", "Value"], map_rows(&self.outputs)),
+            "steps" => self.steps_table(format),
+            "charts" => self.charts(format),
+            "logs" => {
+                if self.logs.is_empty() {
+                    none(format)
+                } else if html {
+                    format!("<pre>{}</pre>", escape_html(&self.logs.join("\n")))
+                } else {
+                    format!("```\n{}\n```", self.logs.join("\n"))
+                }
+            }
+            "artifacts" => self.artifact_list(format),
+            "generated_at" => Utc::now().to_rfc3339(),
+            other => {
+                return Err(CommandError::ValidationError(format!(
+                    "Unknown report section '{other}', expected one of {}",
+                    SECTIONS.join(", ")
+                )))
+            }
+        };
+        Ok(text)
+    }
+
+    fn summary(&self, format: ReportFormat) -> String {
+        let time = |time: Option<DateTime<Utc>>| time.map_or_else(|| "-".to_string(), |time| time.to_rfc3339());
+        let duration = match (self.created_at, self.finished_at) {
+            (Some(start), Some(end)) => format_ms((end - start).num_milliseconds()),
+            _ => "-".to_string(),
+        };
+        let rows = vec![
+            vec!["Status".to_string(), self.status.clone()],
+            vec!["Created".to_string(), time(self.created_at)],
+            vec!["Finished".to_string(), time(self.finished_at)],
+            vec!["Duration".to_string(), duration],
+            vec!["Steps".to_string(), self.steps.len().to_string()],
+        ];
+        table(format, &["", ""], rows)
+    }
+
+    fn steps_table(&self, format: ReportFormat) -> String {
+        let rows = self
+            .steps
+            .iter()
+            .map(|step| {
+                let mut status = step.status.clone();
+                if step.cached {
+                    status.push_str(" (cached)");
+                }
+                vec![
+                    step.id.clone(),
+                    step.command.clone().unwrap_or_default(),
+                    status,
+                    step.attempts.to_string(),
+                    step.duration_ms().map_or_else(|| "-".to_string(), format_ms),
+                    step.parameters.clone().unwrap_or_default(),
+                    step.error.clone().unwrap_or_default(),
+                ]
+            })
+            .collect();
+        table(format, &["Step", "Command", "Status", "Attempts", "Duration", "Parameters", "Error"], rows)
+    }
+
+    fn charts(&self, format: ReportFormat) -> String {
+        if self.charts.is_empty() {
+            return none(format);
+        }
+        let charts: Vec<String> = self
+            .charts
+            .iter()
+            .map(|series| match format {
+                ReportFormat::Html => svg_chart(series),
+                ReportFormat::Markdown => sparkline(series),
+            })
+            .collect();
+        charts.join("\n")
+    }
+
+    fn artifact_list(&self, format: ReportFormat) -> String {
+        if self.artifacts.is_empty() {
+            return none(format);
+        }
+        match format {
+            ReportFormat::Html => {
+                let items: String = self
+                    .artifacts
+                    .iter()
+                    .map(|artifact| {
+                        format!(
+                            "<li><a href=\"{}\">{}</a></li>",
+                            escape_html(&artifact.href),
+                            escape_html(&artifact.name)
+                        )
+                    })
+                    .collect();
+                format!("<ul>{items}</ul>")
+            }
+            ReportFormat::Markdown => self
+                .artifacts
+                .iter()
+                .map(|artifact| format!("- [{}](<{}>)", artifact.name, artifact.href))
+                .collect::<Vec<_>>()
+                .join("\n"),
+        }
+    }
+
+    /// Adds a chart of how long each finished step took
+    fn add_duration_chart(&mut self) {
+        let points: Vec<(String, f64)> = self
+            .steps
+            .iter()
+            .filter_map(|step| Some((step.id.clone(), step.duration_ms()? as f64)))
+            .collect();
+        if !points.is_empty() {
+            self.charts.push(MetricSeries::new("step duration (ms)", points));
+        }
+    }
+}
+
+/// Whether an output value names a URL or a file
+fn looks_like_artifact(value: &str) -> bool {
+    let value = value.trim();
+    if value.is_empty() || value.contains(char::is_whitespace) {
+        return false;
+    }
+    value.starts_with("http://")
+        || value.starts_with("https://")
+        || value.starts_with("s3://")
+        || value.starts_with("file://")
+        || value.contains('/')
+}
+
+fn map_rows(map: &BTreeMap<String, String>) -> Vec<Vec<String>> {
+    map.iter().map(|(k, v)| vec![k.clone(), v.clone()]).collect()
+}
+
+fn none(format: ReportFormat) -> String {
+    match format {
+        ReportFormat::Html => "<p><em>None</em></p>".to_string(),
+        ReportFormat::Markdown => "_None_".to_string(),
+    }
+}
+
+/// A table of `rows` under `header`
+fn table(format: ReportFormat, header: &[&str], rows: Vec<Vec<String>>) -> String {
+    if rows.is_empty() {
+        return none(format);
+    }
+    let mut out = String::new();
+    match format {
+        ReportFormat::Html => {
+            out.push_str("<table>");
+            if header.iter().any(|cell| !cell.is_empty()) {
+                out.push_str("<tr>");
+                for cell in header {
+                    let _ = write!(out, "<th>{}</th>", escape_html(cell));
+                }
+                out.push_str("</tr>");
+            }
+            for row in rows {
+                out.push_str("<tr>");
+                for cell in row {
+                    // Colours outcomes through the classes of the stylesheet
+                    match cell.split_whitespace().next().filter(|word| STATUS_CLASSES.contains(word)) {
+                        Some(class) => {
+                            let _ = write!(out, "<td class=\"{}\">{}</td>", class, escape_html(&cell));
+                        }
+                        None => {
+                            let _ = write!(out, "<td>{}</td>", escape_html(&cell));
+                        }
+                    }
+                }
+                out.push_str("</tr>");
+            }
+            out.push_str("</table>");
+        }
+        ReportFormat::Markdown => {
+            let _ = writeln!(out, "| {} |", header.join(" | "));
+            let _ = writeln!(out, "|{}", "---|".repeat(header.len()));
+            for row in rows {
+                let cells: Vec<String> = row.iter().map(|cell| escape_markdown_cell(cell)).collect();
+                let _ = writeln!(out, "| {} |", cells.join(" | "));
+            }
+            out.truncate(out.trim_end().len());
+        }
+    }
+    out
+}
+
+/// An inline SVG bar chart of a series
+fn svg_chart(series: &MetricSeries) -> String {
+    let max = series.max();
+    let slot = CHART_WIDTH / series.points.len() as f64;
+    let mut bars = String::new();
+    for (index, point) in series.points.iter().enumerate() {
+        let height = if max > 0.0 { point.value.max(0.0) / max * CHART_HEIGHT } else { 0.0 };
+        let _ = write!(
+            bars,
+            "<rect x=\"{:.1}\" y=\"{:.1}\" width=\"{:.1}\" height=\"{:.1}\" fill=\"#4a7fb5\"><title>{}: {}</title></rect>",
+            index as f64 * slot + slot * 0.1,
+            CHART_HEIGHT - height,
+            slot * 0.8,
+            height,
+            escape_html(&point.label),
+            point.value
+        );
+    }
+    let first = series.points.first().map(|point| point.label.as_str()).unwrap_or_default();
+    let last = series.points.last().map(|point| point.label.as_str()).unwrap_or_default();
+    format!(
+        "<figure><svg xmlns=\"http://www.w3.org/2000/svg\" width=\"{w}\" height=\"{h}\" viewBox=\"0 0 {w} {h}\" role=\"img\">\
+<line x1=\"0\" y1=\"{ch}\" x2=\"{w}\" y2=\"{ch}\" stroke=\"#999\"/>{bars}\
+<text x=\"0\" y=\"{h}\" font-size=\"10\">{first}</text>\
+<text x=\"{w}\" y=\"{h}\" font-size=\"10\" text-anchor=\"end\">{last}</text></svg>\
+<figcaption>{name} (max {max})</figcaption></figure>",
+        w = CHART_WIDTH,
+        h = CHART_HEIGHT + 14.0,
+        ch = CHART_HEIGHT,
+        first = escape_html(first),
+        last = escape_html(last),
+        name = escape_html(&series.name),
+    )
+}
+
+/// A one-line Markdown sparkline of a series
+fn sparkline(series: &MetricSeries) -> String {
+    let (min, max) = (series.min(), series.max());
+    let spark: String = series
+        .points
+        .iter()
+        .map(|point| {
+            let level = if max > min { (point.value - min) / (max - min) } else { 1.0 };
+            SPARKS[((level * (SPARKS.len() - 1) as f64).round() as usize).min(SPARKS.len() - 1)]
+        })
+        .collect();
+    format!(
+        "- **{}**: `{}` min {} max {} over {} point(s)",
+        series.name,
+        spark,
+        min,
+        max,
+        series.points.len()
+    )
+}
+
+fn format_ms(ms: i64) -> String {
+    if ms < 1000 {
+        format!("{ms} ms")
+    } else {
+        format!("{:.1} s", ms as f64 / 1000.0)
+    }
+}
+
+/// Escapes text for HTML content and attribute values
+#[must_use]
+pub fn escape_html(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => out.push_str("&amp;"),
+            '<' => out.push_str("&lt;"),
+            '>' => out.push_str("&gt;"),
+            '"' => out.push_str("&quot;"),
+            '\'' => out.push_str("&#39;"),
+            c => out.push(c),
+        }
+    }
+    out
+}
+
+fn escape_markdown_cell(text: &str) -> String {
+    text.replace('|', "\\|").replace('\n', " ")
+}
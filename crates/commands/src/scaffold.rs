@@ -0,0 +1,195 @@
+//! Project scaffolds
+//!
+//! A scaffold is a set of templates rendered into a new project directory,
+//! such as the `plugin` scaffold behind `squirrel plugin new`. The templates
+//! of a scaffold `<kind>` are named `scaffolds/<kind>/<path>`, where `<path>`
+//! is the file they render, and are rendered by the process-wide
+//! [`TemplateEngine`](squirrel_core::template::TemplateEngine). A file
+//! `scaffolds/<kind>/<path>.hbs` in the user's template directory replaces
+//! the built-in template of that path or adds a file to the scaffold, and a
+//! directory `scaffolds/<kind>` there adds a scaffold of its own.
+
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use serde_json::Value;
+use squirrel_core::template;
+
+use crate::{CommandError, CommandResult};
+
+/// Version requirement of `squirrel-sdk` in scaffolded plugins
+pub const SDK_VERSION: &str = "1.2";
+
+/// Built-in templates of the `plugin` scaffold, by path
+const PLUGIN_SCAFFOLD: [(&str, &str); 4] = [
+    (
+        "Cargo.toml",
+        r#"[package]
+name = "{{name}}"
+version = "0.1.0"
+edition = "2021"
+description = {{json description}}
+
+[lib]
+crate-type = ["cdylib", "rlib"]
+
+[dependencies]
+async-trait = "0.1"
+squirrel-sdk = "{{sdk_version}}"
+"#,
+    ),
+    (
+        "plugin.toml",
+        r#"name = "{{name}}"
+version = "0.1.0"
+description = {{json description}}
+author = {{json author}}
+"#,
+    ),
+    (
+        "src/lib.rs",
+        r#"//! {{description}}
+
+use async_trait::async_trait;
+use squirrel_sdk::prelude::*;
+
+/// The {{name}} plugin
+pub struct {{type_name}};
+
+#[async_trait]
+impl Plugin for {{type_name}} {
+    fn name(&self) -> &str {
+        "{{name}}"
+    }
+
+    fn version(&self) -> &str {
+        env!("CARGO_PKG_VERSION")
+    }
+
+    fn description(&self) -> Option<&str> {
+        Some({{json description}})
+    }
+
+    async fn initialize(&self) -> Result<(), PluginError> {
+        Ok(())
+    }
+
+    fn register_commands(&self, _registry: &mut CommandRegistry) -> Result<(), PluginError> {
+        Ok(())
+    }
+
+    async fn execute(&self, _args: &[String]) -> Result<String, PluginError> {
+        Ok("{{name}} executed".to_string())
+    }
+
+    async fn cleanup(&self) -> Result<(), PluginError> {
+        Ok(())
+    }
+}
+
+#[no_mangle]
+pub fn create_plugin() -> Result<std::sync::Arc<dyn Plugin>, PluginError> {
+    Ok(std::sync::Arc::new({{type_name}}))
+}
+"#,
+    ),
+    (
+        "README.md",
+        "# {{name}}\n\n{{description}}\n\nBuild the plugin with `cargo build --release` and install it with\n`squirrel plugin install .`.\n",
+    ),
+];
+
+/// The built-in templates of a scaffold, by path
+fn builtin(kind: &str) -> &'static [(&'static str, &'static str)] {
+    match kind {
+        "plugin" => &PLUGIN_SCAFFOLD,
+        _ => &[],
+    }
+}
+
+/// The variables plugin templates are rendered with
+///
+/// `name` must be a lowercase name of letters, digits and dashes, starting
+/// with a letter. Templates also get `type_name`, the name in camel case,
+/// and `sdk_version`.
+///
+/// # Errors
+///
+/// Returns a validation error if the name is not valid
+pub fn plugin_vars(name: &str, description: &str, author: &str) -> CommandResult<Value> {
+    let valid = name.starts_with(|c: char| c.is_ascii_lowercase())
+        && name.chars().all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '-');
+    if !valid {
+        return Err(CommandError::ValidationError(format!(
+            "Invalid plugin name '{name}': use lowercase letters, digits and dashes, starting with a letter"
+        )));
+    }
+    let type_name: String = name
+        .split('-')
+        .filter(|part| !part.is_empty())
+        .map(|part| {
+            let (first, rest) = part.split_at(1);
+            format!("{}{rest}", first.to_ascii_uppercase())
+        })
+        .chain(std::iter::once("Plugin".to_string()))
+        .collect();
+    Ok(serde_json::json!({
+        "name": name,
+        "type_name": type_name,
+        "description": description,
+        "author": author,
+        "sdk_version": SDK_VERSION,
+    }))
+}
+
+/// Render the scaffold `kind` with `vars` into `dest`, returning the files
+/// written
+///
+/// Nothing is written if a file of the scaffold already exists.
+///
+/// # Errors
+///
+/// Returns a validation error if there is no such scaffold, a template
+/// fails to render or a file exists, and a resource error if a file
+/// cannot be written
+pub fn render(kind: &str, dest: &Path, vars: &Value) -> CommandResult<Vec<PathBuf>> {
+    let prefix = format!("scaffolds/{kind}/");
+    let mut sources: BTreeMap<String, Option<&str>> = builtin(kind)
+        .iter()
+        .map(|(path, source)| ((*path).to_string(), Some(*source)))
+        .collect();
+    for info in template::engine().templates() {
+        if let Some(path) = info.name.strip_prefix(&prefix) {
+            sources.entry(path.to_string()).or_insert(None);
+        }
+    }
+    if sources.is_empty() {
+        return Err(CommandError::ValidationError(format!("Unknown scaffold '{kind}'")));
+    }
+
+    let mut files = Vec::with_capacity(sources.len());
+    for (path, source) in sources {
+        let name = format!("{prefix}{path}");
+        let rendered = match source {
+            Some(source) => template::engine().render_or(&name, source, vars),
+            None => template::engine().render(&name, vars),
+        }
+        .map_err(|e| CommandError::ValidationError(e.to_string()))?;
+        let target = dest.join(&path);
+        if target.exists() {
+            return Err(CommandError::ValidationError(format!("{} already exists", target.display())));
+        }
+        files.push((target, rendered));
+    }
+
+    for (target, text) in &files {
+        if let Some(parent) = target.parent() {
+            fs::create_dir_all(parent)
+                .map_err(|e| CommandError::ResourceError(format!("Cannot create {}: {e}", parent.display())))?;
+        }
+        fs::write(target, text)
+            .map_err(|e| CommandError::ResourceError(format!("Cannot write {}: {e}", target.display())))?;
+    }
+    Ok(files.into_iter().map(|(target, _)| target).collect())
+}
@@ -0,0 +1,149 @@
+//! Declarative argument schemas for commands
+//!
+//! A [`CommandSchema`] describes a command's arguments independently of clap,
+//! so the same description can build the clap parser, validate input and be
+//! serialized for documentation or remote clients. Schemas are usually
+//! generated with `#[derive(Command)]` rather than written by hand.
+
+use std::fmt::Display;
+use std::str::FromStr;
+
+use clap::{Arg, ArgAction};
+use serde::Serialize;
+
+pub use clap::{ArgMatches, Command as ClapCommand};
+
+use crate::{CommandError, CommandResult};
+
+/// How an argument is passed on the command line
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ArgKind {
+    /// Positional value
+    Positional,
+    /// Named option taking a value, e.g. `--count 3`
+    Option,
+    /// Boolean switch, e.g. `--verbose`
+    Flag,
+}
+
+/// Description of a single command argument
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct ArgSchema {
+    /// Argument identifier
+    pub name: &'static str,
+    /// How the argument is passed
+    pub kind: ArgKind,
+    /// Rust type of the value, for documentation
+    pub value_type: &'static str,
+    /// Help text
+    pub help: &'static str,
+    /// Whether the argument must be given
+    pub required: bool,
+    /// Whether the argument accepts several values
+    pub multiple: bool,
+    /// Short switch
+    pub short: Option<char>,
+    /// Long switch
+    pub long: Option<&'static str>,
+    /// Default value used when the argument is omitted
+    pub default: Option<&'static str>,
+}
+
+impl ArgSchema {
+    /// Builds the clap argument
+    #[must_use]
+    pub fn to_arg(&self) -> Arg {
+        let mut arg = Arg::new(self.name).help(self.help);
+        if let Some(short) = self.short {
+            arg = arg.short(short);
+        }
+        if let Some(long) = self.long {
+            arg = arg.long(long);
+        }
+
+        match self.kind {
+            ArgKind::Flag => arg.action(ArgAction::SetTrue),
+            ArgKind::Positional | ArgKind::Option => {
+                arg = arg.required(self.required);
+                if let Some(default) = self.default {
+                    arg = arg.default_value(default);
+                }
+                if self.multiple {
+                    arg.action(ArgAction::Append).num_args(if self.kind == ArgKind::Positional { 0.. } else { 1.. })
+                } else {
+                    arg.action(ArgAction::Set)
+                }
+            }
+        }
+    }
+}
+
+/// Description of a command and its arguments
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct CommandSchema {
+    /// Command name
+    pub name: &'static str,
+    /// Command description
+    pub description: &'static str,
+    /// Arguments in declaration order
+    pub args: Vec<ArgSchema>,
+}
+
+impl CommandSchema {
+    /// Builds the clap parser for the command
+    #[must_use]
+    pub fn to_clap(&self) -> ClapCommand {
+        self.args
+            .iter()
+            .fold(ClapCommand::new(self.name).about(self.description), |command, arg| {
+                command.arg(arg.to_arg())
+            })
+    }
+
+    /// Parses `args`, which do not include the command name
+    ///
+    /// # Errors
+    /// Returns a validation error describing the invalid arguments
+    pub fn parse(&self, args: &[String]) -> CommandResult<ArgMatches> {
+        self.to_clap()
+            .try_get_matches_from(std::iter::once(self.name.to_string()).chain(args.iter().cloned()))
+            .map_err(|e| CommandError::ValidationError(format!("Invalid arguments: {e}")))
+    }
+}
+
+/// Commands whose arguments are described by a [`CommandSchema`]
+///
+/// Implemented by `#[derive(Command)]`.
+pub trait CommandArgs: Sized {
+    /// Schema describing the command's arguments
+    fn schema() -> CommandSchema;
+
+    /// Builds the command from parsed arguments
+    ///
+    /// # Errors
+    /// Returns a validation error if a value cannot be converted
+    fn from_matches(matches: &ArgMatches) -> CommandResult<Self>;
+
+    /// Parses raw arguments into the command
+    ///
+    /// # Errors
+    /// Returns a validation error if the arguments are invalid
+    fn from_args(args: &[String]) -> CommandResult<Self> {
+        Self::from_matches(&Self::schema().parse(args)?)
+    }
+}
+
+/// Converts an argument value, naming the argument on failure
+///
+/// # Errors
+/// Returns a validation error if `value` does not parse as `T`
+pub fn parse_value<T>(name: &str, value: &str) -> CommandResult<T>
+where
+    T: FromStr,
+    T::Err: Display,
+{
+    value
+        .parse()
+        .map_err(|e| CommandError::ValidationError(format!("Invalid value '{value}' for '{name}': {e}")))
+}
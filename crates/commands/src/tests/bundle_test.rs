@@ -0,0 +1,187 @@
+//! Tests for job bundles
+
+use std::collections::BTreeMap;
+use std::fs;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use flate2::write::GzEncoder;
+use flate2::Compression;
+use tempfile::tempdir;
+
+use crate::bundle::{self, compatible_versions, ExportOptions, ImportOptions};
+use crate::manifest::{self, ManifestOptions, RunManifest, RunOutcome};
+use crate::{CommandError, CommandRegistry};
+
+use super::TestCommand;
+
+/// A recorded run of the test command reading `data/reads.fastq` in `workspace`
+fn recorded_run(workspace: &Path) -> RunManifest {
+    fs::create_dir_all(workspace.join("data")).unwrap();
+    fs::write(workspace.join("data/reads.fastq"), "@read1\nACGT\n").unwrap();
+    let options = ManifestOptions {
+        env_allowlist: Vec::new(),
+        inputs: vec![workspace.join("data/reads.fastq")],
+        ..ManifestOptions::default()
+    };
+    let args = vec!["data/reads.fastq".to_string(), workspace.join("out").display().to_string()];
+    let mut manifest = RunManifest::capture("test", &args, &options).unwrap();
+    // As recorded by a run in the workspace
+    manifest.inputs[0].path = PathBuf::from("data/reads.fastq");
+    manifest.outcome = Some(RunOutcome::from_result(&Ok("Test command executed".to_string())));
+    manifest
+}
+
+#[test]
+fn test_export_import_round_trip() {
+    let dir = tempdir().unwrap();
+    let lab_a = dir.path().join("lab-a");
+    let lab_b = dir.path().join("lab-b");
+    let manifest = recorded_run(&lab_a);
+
+    let mut archive = Vec::new();
+    let options = ExportOptions {
+        name: Some("qc".to_string()),
+        workspace: lab_a.clone(),
+        output: Some("Test command executed\n".to_string()),
+    };
+    let exported = bundle::export(&manifest, &options, &mut archive).unwrap();
+    assert_eq!(exported.inputs[0].path, PathBuf::from("data/reads.fastq"));
+    assert_eq!(bundle::inspect(archive.as_slice()).unwrap(), exported);
+
+    let report = bundle::import(
+        archive.as_slice(),
+        &ImportOptions {
+            workspace: lab_b.clone(),
+            ..ImportOptions::default()
+        },
+    )
+    .unwrap();
+    assert_eq!(report.name, "qc");
+    let restored = lab_b.join("data/reads.fastq");
+    assert_eq!(fs::read_to_string(&restored).unwrap(), "@read1\nACGT\n");
+    assert_eq!(report.manifest.args, vec![restored.display().to_string(), lab_b.join("out").display().to_string()]);
+    assert_eq!(fs::read_to_string(report.output_path.unwrap()).unwrap(), "Test command executed");
+
+    // The imported manifest replays without drift in its inputs
+    let loaded = RunManifest::load(&report.manifest_path).unwrap();
+    let registry = CommandRegistry::new();
+    registry.register("test", Arc::new(TestCommand)).unwrap();
+    let replay = manifest::replay(&registry, &loaded, BTreeMap::new(), false).unwrap();
+    assert!(replay.drift.is_empty(), "{replay}");
+    assert_eq!(replay.outcome_matches, Some(true));
+
+    // Importing again is fine, but not over changed files
+    fs::write(&restored, "@read1\nTTTT\n").unwrap();
+    let options = ImportOptions {
+        workspace: lab_b.clone(),
+        ..ImportOptions::default()
+    };
+    assert!(matches!(bundle::import(archive.as_slice(), &options), Err(CommandError::ValidationError(_))));
+    let options = ImportOptions { overwrite: true, ..options };
+    bundle::import(archive.as_slice(), &options).unwrap();
+    assert_eq!(fs::read_to_string(&restored).unwrap(), "@read1\nACGT\n");
+}
+
+#[test]
+fn test_export_checks_inputs_and_output() {
+    let dir = tempdir().unwrap();
+    let manifest = recorded_run(dir.path());
+    let options = ExportOptions {
+        workspace: dir.path().to_path_buf(),
+        output: Some("Other output".to_string()),
+        ..ExportOptions::default()
+    };
+    assert!(bundle::export(&manifest, &options, Vec::new()).is_err());
+
+    fs::write(dir.path().join("data/reads.fastq"), "changed").unwrap();
+    let options = ExportOptions { output: None, ..options };
+    assert!(bundle::export(&manifest, &options, Vec::new()).is_err());
+}
+
+#[test]
+fn test_external_inputs_need_a_mapping() {
+    let dir = tempdir().unwrap();
+    let shared = dir.path().join("shared");
+    let mut manifest = recorded_run(&shared);
+    manifest.inputs[0].path = shared.join("data/reads.fastq");
+
+    let mut archive = Vec::new();
+    let options = ExportOptions {
+        workspace: dir.path().join("workspace"),
+        ..ExportOptions::default()
+    };
+    let exported = bundle::export(&manifest, &options, &mut archive).unwrap();
+    assert!(exported.inputs[0].path.is_absolute());
+
+    let options = ImportOptions {
+        workspace: dir.path().join("imported"),
+        ..ImportOptions::default()
+    };
+    assert!(bundle::import(archive.as_slice(), &options).is_err());
+    let mapped = dir.path().join("mirror");
+    let options = ImportOptions {
+        mappings: vec![(shared, mapped.clone())],
+        ..options
+    };
+    let report = bundle::import(archive.as_slice(), &options).unwrap();
+    assert_eq!(report.inputs, vec![mapped.join("data/reads.fastq")]);
+}
+
+/// `archive` with its bundle description changed by `edit`
+fn tampered(archive: &[u8], edit: impl Fn(&mut serde_json::Value)) -> Vec<u8> {
+    let mut entries = bundle::read_archive(archive).unwrap();
+    let mut description: serde_json::Value = serde_json::from_slice(&entries[0].1).unwrap();
+    edit(&mut description["payload"]);
+    entries[0].1 = serde_json::to_vec(&description).unwrap();
+    let mut tampered = Vec::new();
+    bundle::write_archive(&mut tampered, &entries, 0).unwrap();
+    tampered
+}
+
+#[test]
+fn test_import_refuses_paths_escaping_the_workspace() {
+    let dir = tempdir().unwrap();
+    let manifest = recorded_run(&dir.path().join("lab-a"));
+    let mut archive = Vec::new();
+    let options = ExportOptions {
+        workspace: dir.path().join("lab-a"),
+        ..ExportOptions::default()
+    };
+    bundle::export(&manifest, &options, &mut archive).unwrap();
+    let workspace = dir.path().join("lab-b/workspace");
+    let options = ImportOptions {
+        workspace: workspace.clone(),
+        overwrite: true,
+        ..ImportOptions::default()
+    };
+
+    let escaping = tampered(&archive, |bundle| bundle["inputs"][0]["path"] = "../../escaped.fastq".into());
+    assert!(matches!(bundle::import(escaping.as_slice(), &options), Err(CommandError::ValidationError(_))));
+    assert!(!dir.path().join("escaped.fastq").exists());
+
+    let escaping = tampered(&archive, |bundle| bundle["manifest"]["id"] = "../x".into());
+    assert!(matches!(bundle::import(escaping.as_slice(), &options), Err(CommandError::ValidationError(_))));
+    assert!(!dir.path().join("lab-b/x.manifest.json").exists());
+    assert!(!workspace.exists());
+}
+
+#[test]
+fn test_version_compatibility() {
+    assert!(compatible_versions("0.1.0", "0.1.7"));
+    assert!(!compatible_versions("0.1.0", "0.2.0"));
+    assert!(compatible_versions("1.2.0", "1.4.1"));
+    assert!(!compatible_versions("1.2.0", "2.0.0"));
+    assert!(!compatible_versions("unknown", "0.1.0"));
+}
+
+#[test]
+fn test_rejects_other_archives() {
+    assert!(bundle::inspect(&b"not a bundle"[..]).is_err());
+
+    let mut gzip = GzEncoder::new(Vec::new(), Compression::default());
+    gzip.write_all(&[b'x'; 1024]).unwrap();
+    let archive = gzip.finish().unwrap();
+    assert!(matches!(bundle::inspect(archive.as_slice()), Err(CommandError::ValidationError(_))));
+}
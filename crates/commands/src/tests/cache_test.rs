@@ -0,0 +1,81 @@
+//! Tests for the command result cache
+
+use tempfile::tempdir;
+
+use crate::cache::{cache_key, ResultCache};
+use crate::manifest::{ManifestOptions, RunManifest};
+
+fn options(inputs: Vec<std::path::PathBuf>) -> ManifestOptions {
+    ManifestOptions {
+        env_allowlist: Vec::new(),
+        inputs,
+        ..ManifestOptions::default()
+    }
+}
+
+#[test]
+fn test_lookup_hits_only_identical_inputs() {
+    let dir = tempdir().unwrap();
+    let cache = ResultCache::open(dir.path().join("cache")).unwrap();
+    let input = dir.path().join("reads.fastq");
+    std::fs::write(&input, "ACGT").unwrap();
+    let args = vec!["--min-quality".to_string(), "20".to_string()];
+
+    let manifest = RunManifest::capture("align", &args, &options(vec![input.clone()])).unwrap();
+    assert!(cache.lookup(&manifest).unwrap().is_none());
+    let stored = cache.store(&manifest, "aligned 1 read").unwrap();
+    assert_eq!(stored.key, cache_key(&manifest));
+
+    // A fresh capture of the same inputs has a new id and time but the same key
+    let again = RunManifest::capture("align", &args, &options(vec![input.clone()])).unwrap();
+    let hit = cache.lookup(&again).unwrap().unwrap();
+    assert_eq!(hit.output, "aligned 1 read");
+    assert_eq!(hit.hits, 1);
+    assert!(hit.last_hit_at.is_some());
+
+    let other_args = RunManifest::capture("align", &["--min-quality".to_string()], &options(vec![input.clone()])).unwrap();
+    assert!(cache.lookup(&other_args).unwrap().is_none());
+
+    std::fs::write(&input, "ACGTT").unwrap();
+    let changed = RunManifest::capture("align", &args, &options(vec![input])).unwrap();
+    assert!(cache.lookup(&changed).unwrap().is_none());
+
+    // Storing the new result replaces the one for the old content
+    cache.store(&changed, "aligned 2 reads").unwrap();
+    let entries = cache.entries().unwrap();
+    assert_eq!(entries.len(), 1);
+    assert_eq!(entries[0].output, "aligned 2 reads");
+}
+
+#[test]
+fn test_invalidation() {
+    let dir = tempdir().unwrap();
+    let cache = ResultCache::open(dir.path().join("cache")).unwrap();
+    let reads = dir.path().join("reads.fastq");
+    let reference = dir.path().join("ref.fa");
+    std::fs::write(&reads, "ACGT").unwrap();
+    std::fs::write(&reference, ">chr1").unwrap();
+
+    let align = RunManifest::capture("align", &[], &options(vec![reads.clone(), reference.clone()])).unwrap();
+    let count = RunManifest::capture("count", &[], &options(vec![reads.clone()])).unwrap();
+    let index = RunManifest::capture("index", &[], &options(vec![reference.clone()])).unwrap();
+    for manifest in [&align, &count, &index] {
+        cache.store(manifest, "done").unwrap();
+    }
+
+    // Paths are compared after resolving them
+    let relative = dir.path().join(".").join("reads.fastq");
+    let mut removed = cache.invalidate_input(&relative).unwrap();
+    removed.sort();
+    let mut expected = vec![cache_key(&align), cache_key(&count)];
+    expected.sort();
+    assert_eq!(removed, expected);
+
+    assert_eq!(cache.invalidate_command("index").unwrap(), vec![cache_key(&index)]);
+    assert!(cache.entries().unwrap().is_empty());
+
+    cache.store(&count, "done").unwrap();
+    assert!(cache.remove(&cache_key(&count)).unwrap());
+    assert!(!cache.remove(&cache_key(&count)).unwrap());
+    assert!(cache.get("../refs").is_err());
+}
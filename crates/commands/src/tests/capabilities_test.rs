@@ -0,0 +1,81 @@
+//! Tests for capability reports
+
+use std::sync::Arc;
+
+use crate::capabilities::{common_subsystems, CapabilityReport, Subsystem, ToolInfo};
+use crate::registry::CommandRegistry;
+
+use super::TestCommand;
+
+fn tool(id: &str) -> ToolInfo {
+    ToolInfo {
+        id: id.to_string(),
+        name: format!("{id} tool"),
+        version: "1.0.0".to_string(),
+        capabilities: vec!["run".to_string()],
+    }
+}
+
+#[test]
+fn test_report_sorts_and_replaces_entries() {
+    let report = CapabilityReport::new("squirrel-test", "0.1.0")
+        .with_subsystem(Subsystem::compiled("plugins", false))
+        .with_subsystem(Subsystem::not_compiled("database"))
+        .with_subsystem(Subsystem::compiled("plugins", true).with_detail("2 loaded"))
+        .with_commands(vec!["b".to_string(), "a".to_string(), "b".to_string()])
+        .with_tools(vec![tool("z"), tool("a"), tool("z")]);
+
+    let names: Vec<_> = report.subsystems.iter().map(|s| s.name.as_str()).collect();
+    assert_eq!(names, ["database", "plugins"]);
+    assert_eq!(
+        report.subsystem("plugins").unwrap().detail.as_deref(),
+        Some("2 loaded")
+    );
+    assert_eq!(report.commands, ["a", "b"]);
+    let tools: Vec<_> = report.tools.iter().map(|t| t.id.as_str()).collect();
+    assert_eq!(tools, ["a", "z"]);
+}
+
+#[test]
+fn test_supports_requires_compiled_and_enabled() {
+    let report = CapabilityReport::new("squirrel-test", "0.1.0").with_subsystems(vec![
+        Subsystem::feature("redis", false, true),
+        Subsystem::feature("nats", true, false),
+        Subsystem::feature("sqlite", true, true),
+    ]);
+
+    assert!(!report.subsystem("redis").unwrap().enabled);
+    assert!(!report.supports("redis"));
+    assert!(!report.supports("nats"));
+    assert!(report.supports("sqlite"));
+    assert!(!report.supports("missing"));
+}
+
+#[test]
+fn test_report_lists_registry_commands() {
+    let registry = CommandRegistry::new();
+    registry.register("test", Arc::new(TestCommand)).unwrap();
+
+    let report = CapabilityReport::new("squirrel-test", "0.1.0")
+        .with_registry(&registry)
+        .unwrap();
+    assert_eq!(report.commands, ["test"]);
+}
+
+#[test]
+fn test_common_subsystems_and_json_round_trip() {
+    let report = CapabilityReport::new("squirrel-test", "0.1.0")
+        .with_subsystems(common_subsystems())
+        .with_tools(vec![tool("text")]);
+    assert!(report.subsystem("otel").unwrap().compiled);
+    assert!(!report.subsystem("wasm").unwrap().compiled);
+
+    let json = serde_json::to_string(&report).unwrap();
+    let parsed: CapabilityReport = serde_json::from_str(&json).unwrap();
+    assert_eq!(parsed, report);
+
+    let text = report.to_string();
+    assert!(text.starts_with("squirrel-test 0.1.0"));
+    assert!(text.contains("wasm"));
+    assert!(text.contains("text 1.0.0 (text tool): run"));
+}
@@ -0,0 +1,98 @@
+//! Tests for `#[derive(Command)]`
+
+use std::sync::Arc;
+
+use crate::schema::{ArgKind, CommandArgs};
+use crate::{Command, CommandError, CommandRegistry, CommandResult};
+
+/// Greets someone
+#[derive(Debug, Clone, Default, Command)]
+struct GreetCommand {
+    /// Who to greet
+    name: String,
+    /// How many times to greet
+    #[arg(short, long, default = "1")]
+    times: u32,
+    /// Greet loudly
+    #[arg(long)]
+    shout: bool,
+    /// Optional closing line
+    #[arg(long = "sign-off")]
+    sign_off: Option<String>,
+    /// Extra names
+    #[arg(short = 'a', long)]
+    also: Vec<String>,
+    #[arg(skip)]
+    greeting: Arc<String>,
+}
+
+impl GreetCommand {
+    fn run(&self) -> CommandResult<String> {
+        let names = std::iter::once(self.name.clone()).chain(self.also.iter().cloned()).collect::<Vec<_>>();
+        let mut output = vec![format!("{}, {}!", self.greeting, names.join(" and ")); self.times as usize].join(" ");
+        if self.shout {
+            output = output.to_uppercase();
+        }
+        if let Some(sign_off) = &self.sign_off {
+            output.push(' ');
+            output.push_str(sign_off);
+        }
+        Ok(output)
+    }
+}
+
+fn greet() -> GreetCommand {
+    GreetCommand {
+        greeting: Arc::new("Hello".to_string()),
+        ..GreetCommand::default()
+    }
+}
+
+fn args(args: &[&str]) -> Vec<String> {
+    args.iter().map(ToString::to_string).collect()
+}
+
+#[test]
+fn test_derived_schema() {
+    let schema = GreetCommand::schema();
+    assert_eq!(schema.name, "greet");
+    assert_eq!(schema.description, "Greets someone");
+    assert_eq!(schema.args.len(), 5);
+
+    let name = &schema.args[0];
+    assert_eq!((name.kind, name.required, name.help), (ArgKind::Positional, true, "Who to greet"));
+    let times = &schema.args[1];
+    assert_eq!((times.kind, times.short, times.long, times.default), (ArgKind::Option, Some('t'), Some("times"), Some("1")));
+    assert!(!times.required);
+    assert_eq!(schema.args[2].kind, ArgKind::Flag);
+    assert_eq!((schema.args[3].long, schema.args[3].value_type), (Some("sign-off"), "Option<String>"));
+    assert!(schema.args[4].multiple);
+}
+
+#[test]
+fn test_derived_execute() {
+    let command = greet();
+    assert_eq!(command.name(), "greet");
+    assert_eq!(command.execute(&args(&["Ada"])).unwrap(), "Hello, Ada!");
+    assert_eq!(
+        command
+            .execute(&args(&["Ada", "-t", "2", "--shout", "--sign-off", "Bye", "-a", "Bob"]))
+            .unwrap(),
+        "HELLO, ADA AND BOB! HELLO, ADA AND BOB! Bye"
+    );
+
+    assert!(matches!(command.execute(&[]), Err(CommandError::ValidationError(_))));
+    let err = command.execute(&args(&["Ada", "--times", "many"])).unwrap_err();
+    assert!(err.to_string().contains("'times'"));
+}
+
+#[test]
+fn test_derived_command_registers() {
+    let registry = CommandRegistry::new();
+    registry.register("greet", Arc::new(greet())).unwrap();
+    assert_eq!(registry.execute("greet", &args(&["Ada"])).unwrap(), "Hello, Ada!");
+
+    let parsed = GreetCommand::from_args(&args(&["Ada", "--also", "Bob", "--also", "Cy"])).unwrap();
+    assert_eq!(parsed.also, vec!["Bob", "Cy"]);
+    assert_eq!(parsed.greeting.as_str(), "");
+}
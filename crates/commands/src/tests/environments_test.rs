@@ -0,0 +1,84 @@
+//! Tests for per-command execution environments
+
+use std::collections::BTreeMap;
+use std::path::PathBuf;
+
+use crate::environments::{EnvironmentDefinition, EnvironmentRegistry, ExecutionEnvironment, DEFAULT_ENVIRONMENT};
+use crate::CommandError;
+
+fn host_env() -> Vec<(String, String)> {
+    [
+        ("PATH", "/usr/bin"),
+        ("HOME", "/home/ada"),
+        ("LC_ALL", "C"),
+        ("AWS_SECRET_ACCESS_KEY", "hunter2"),
+        ("SQUIRREL_MASTER_KEY", "00ff"),
+        ("SQUIRREL_LOG", "debug"),
+        ("GITHUB_TOKEN", "ghp_x"),
+    ]
+    .iter()
+    .map(|(key, value)| (key.to_string(), value.to_string()))
+    .collect()
+}
+
+#[test]
+fn test_default_environment_scrubs_host() {
+    let registry = EnvironmentRegistry::default();
+    let env = registry.for_command("align", host_env()).unwrap();
+
+    assert_eq!(env.name, DEFAULT_ENVIRONMENT);
+    assert_eq!(env.var("PATH"), Some("/usr/bin"));
+    assert_eq!(env.var("LC_ALL"), Some("C"));
+    assert!(env.var("AWS_SECRET_ACCESS_KEY").is_none());
+    assert!(env.var("GITHUB_TOKEN").is_none());
+    assert!(env.var("SQUIRREL_LOG").is_none());
+}
+
+#[test]
+fn test_named_environment_injects_and_limits() {
+    let definition = EnvironmentDefinition {
+        inherit: vec!["PATH".to_string(), "SQUIRREL_*".to_string(), "GITHUB_TOKEN".to_string()],
+        vars: BTreeMap::from([("BLAST_DB".to_string(), "/data/blast".to_string())]),
+        path: vec![PathBuf::from("/opt/bio/bin")],
+        ..EnvironmentDefinition::default()
+    };
+    let registry = EnvironmentRegistry::new(
+        BTreeMap::from([("bio".to_string(), definition)]),
+        BTreeMap::from([("align".to_string(), "bio".to_string())]),
+    )
+    .unwrap();
+
+    let env = registry.for_command("align", host_env()).unwrap();
+    assert_eq!(env.name, "bio");
+    assert_eq!(env.var("BLAST_DB"), Some("/data/blast"));
+    assert_eq!(env.var("PATH"), Some("/opt/bio/bin:/usr/bin"));
+    assert_eq!(env.var("SQUIRREL_LOG"), Some("debug"));
+    // Sensitive variables pass only when named exactly
+    assert!(env.var("SQUIRREL_MASTER_KEY").is_none());
+    assert_eq!(env.var("GITHUB_TOKEN"), Some("ghp_x"));
+    assert!(env.var("HOME").is_none());
+
+    assert_eq!(registry.environment_name("status"), DEFAULT_ENVIRONMENT);
+
+    let unknown = EnvironmentRegistry::new(
+        BTreeMap::new(),
+        BTreeMap::from([("align".to_string(), "bio".to_string())]),
+    );
+    assert!(matches!(unknown, Err(CommandError::ValidationError(_))));
+}
+
+#[cfg(unix)]
+#[test]
+fn test_apply_replaces_process_environment() {
+    let definition = EnvironmentDefinition {
+        inherit: Vec::new(),
+        vars: BTreeMap::from([("GREETING".to_string(), "hello".to_string())]),
+        ..EnvironmentDefinition::default()
+    };
+    let env = ExecutionEnvironment::resolve("test", &definition, host_env()).unwrap();
+
+    let mut process = std::process::Command::new("/usr/bin/env");
+    env.apply(&mut process);
+    let output = String::from_utf8(process.output().unwrap().stdout).unwrap();
+    assert_eq!(output.trim(), "GREETING=hello");
+}
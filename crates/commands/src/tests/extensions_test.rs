@@ -0,0 +1,51 @@
+//! Tests for typed command extensions
+
+use std::sync::Arc;
+
+use crate::extensions::{Extensions, RequestId};
+
+#[derive(Debug, PartialEq)]
+struct Session {
+    user: String,
+}
+
+#[derive(Debug, PartialEq)]
+struct Workspace(&'static str);
+
+#[test]
+fn test_extensions_are_keyed_by_type() {
+    let mut extensions = Extensions::new();
+    assert!(extensions.is_empty());
+
+    assert!(extensions.insert(Session { user: "ada".to_string() }).is_none());
+    extensions.insert(Workspace("/tmp/project"));
+    assert_eq!(extensions.len(), 2);
+    assert_eq!(extensions.get::<Session>().map(|s| s.user.as_str()), Some("ada"));
+    assert_eq!(extensions.get::<Workspace>(), Some(&Workspace("/tmp/project")));
+    assert!(extensions.get::<RequestId>().is_none());
+
+    let previous = extensions.insert(Session { user: "bob".to_string() }).unwrap();
+    assert_eq!(previous.user, "ada");
+    assert_eq!(extensions.remove::<Workspace>().unwrap().0, "/tmp/project");
+    assert!(!extensions.contains::<Workspace>());
+}
+
+#[test]
+fn test_extensions_clone_shares_values() {
+    let mut original = Extensions::new();
+    original.insert(Session { user: "ada".to_string() });
+
+    let mut clone = original.clone();
+    clone.insert(RequestId("req-1".to_string()));
+    clone.remove::<Session>();
+
+    // The clone's changes do not leak back into the original
+    assert!(original.contains::<Session>());
+    assert!(!original.contains::<RequestId>());
+
+    // Values themselves are shared, not copied
+    let held = original.get_arc::<Session>().unwrap();
+    let mut merged = Extensions::new();
+    merged.extend(&original);
+    assert!(Arc::ptr_eq(&held, &merged.get_arc::<Session>().unwrap()));
+}
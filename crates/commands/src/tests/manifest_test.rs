@@ -0,0 +1,90 @@
+//! Tests for run manifests and replay
+
+use std::collections::BTreeMap;
+use std::sync::Arc;
+
+use tempfile::tempdir;
+
+use crate::manifest::{self, ManifestOptions, RunManifest, RunOutcome};
+use crate::CommandRegistry;
+
+use super::TestCommand;
+
+#[test]
+fn test_env_allowlist_patterns() {
+    let options = ManifestOptions {
+        env_allowlist: vec!["PATH".to_string(), "SQUIRREL_*".to_string()],
+        ..ManifestOptions::default()
+    };
+    assert!(options.allows_env("PATH"));
+    assert!(options.allows_env("SQUIRREL_LOG_LEVEL"));
+    assert!(!options.allows_env("PATHEXT"));
+    assert!(!options.allows_env("AWS_SECRET_ACCESS_KEY"));
+}
+
+#[test]
+fn test_capture_round_trip_and_input_drift() {
+    let dir = tempdir().unwrap();
+    let input = dir.path().join("reads.fastq");
+    std::fs::write(&input, "@read1\nACGT\n").unwrap();
+    let options = ManifestOptions {
+        env_allowlist: Vec::new(),
+        tools: vec!["squirrel-test-missing-tool".to_string()],
+        inputs: vec![input.clone()],
+        plugins: BTreeMap::from([("bio".to_string(), "0.2.0".to_string())]),
+    };
+
+    let manifest = RunManifest::capture("test", &["a".to_string()], &options).unwrap();
+    assert_eq!(manifest.inputs[0].size, 12);
+    assert_eq!(manifest.tools["squirrel-test-missing-tool"], None);
+    assert!(manifest.env.is_empty());
+
+    let path = dir.path().join("manifest.json");
+    manifest.save(&path).unwrap();
+    let loaded = RunManifest::load(&path).unwrap();
+    assert_eq!(loaded, manifest);
+
+    // Same environment: no drift
+    let current = RunManifest::capture("test", &[], &options).unwrap();
+    assert!(manifest.diff(&current).is_empty());
+
+    // Changed input and plugin versions are reported
+    std::fs::write(&input, "@read1\nACGG\n").unwrap();
+    let upgraded = ManifestOptions {
+        plugins: BTreeMap::from([("bio".to_string(), "0.3.0".to_string())]),
+        ..options
+    };
+    let current = RunManifest::capture("test", &[], &upgraded).unwrap();
+    let fields: Vec<String> = manifest.diff(&current).into_iter().map(|d| d.field).collect();
+    let input_field = format!("input.{}", input.display());
+    assert_eq!(fields, vec!["plugin.bio".to_string(), input_field]);
+}
+
+#[test]
+fn test_replay_reports_drift_and_outcome() {
+    let dir = tempdir().unwrap();
+    let input = dir.path().join("input.txt");
+    std::fs::write(&input, "data").unwrap();
+    let registry = CommandRegistry::new();
+    registry.register("test", Arc::new(TestCommand)).unwrap();
+
+    let options = ManifestOptions {
+        inputs: vec![input.clone()],
+        ..ManifestOptions::default()
+    };
+    let mut recorded = RunManifest::capture("test", &[], &options).unwrap();
+    recorded.outcome = Some(RunOutcome::from_result(&registry.execute("test", &Vec::new())));
+
+    let report = manifest::replay(&registry, &recorded, BTreeMap::new(), false).unwrap();
+    assert!(report.executed);
+    assert_eq!(report.output.as_deref(), Some("Test command executed"));
+    assert!(report.is_reproducible(), "{report}");
+
+    // A missing input is drift, and a dry run does not execute
+    std::fs::remove_file(&input).unwrap();
+    let report = manifest::replay(&registry, &recorded, BTreeMap::new(), true).unwrap();
+    assert!(!report.executed);
+    assert_eq!(report.drift.len(), 1);
+    assert_eq!(report.drift[0].actual, None);
+    assert!(!report.is_reproducible());
+}
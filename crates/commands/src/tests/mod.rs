@@ -24,6 +24,48 @@ pub mod auth_test;
 // Include MCP integration test
 pub mod mcp_integration_test;
 
+// Include workflow tests
+pub mod workflow_test;
+
+// Include derive macro tests
+pub mod derive_test;
+
+// Include extensions tests
+pub mod extensions_test;
+
+// Include manifest tests
+pub mod manifest_test;
+
+// Include job bundle tests
+pub mod bundle_test;
+
+// Include run report tests
+pub mod report_test;
+
+// Include plugin log tests
+pub mod plugin_logs_test;
+
+// Include capability report tests
+pub mod capabilities_test;
+
+// Include execution environment tests
+pub mod environments_test;
+
+// Include result cache tests
+pub mod cache_test;
+
+// Include wire schema compatibility tests
+pub mod wire_test;
+
+// Include command palette tests
+pub mod palette_test;
+
+// Include command policy tests
+pub mod policy_test;
+
+// Include project scaffold tests
+pub mod scaffold_test;
+
 // Test implementations
 
 #[derive(Parser)]
@@ -0,0 +1,153 @@
+//! Tests for the command palette index
+
+use std::sync::Arc;
+
+use tempfile::tempdir;
+
+use crate::history::CommandHistory;
+use crate::palette::{fuzzy_match, rank, CommandEntry};
+use crate::plugin_logs::ScopedCommand;
+use crate::{Command, CommandRegistry, CommandResult};
+
+#[derive(Clone)]
+struct Cache;
+
+impl Command for Cache {
+    fn name(&self) -> &str {
+        "cache"
+    }
+
+    fn description(&self) -> &str {
+        "Manage cached results"
+    }
+
+    fn execute(&self, _args: &[String]) -> CommandResult<String> {
+        Ok(String::new())
+    }
+
+    fn tags(&self) -> Vec<String> {
+        vec!["memoize".to_string()]
+    }
+
+    fn parser(&self) -> clap::Command {
+        clap::Command::new("cache")
+            .subcommand(clap::Command::new("list").about("List cached results"))
+            .subcommand(clap::Command::new("clear").about("Remove cached results"))
+    }
+
+    fn clone_box(&self) -> Box<dyn Command> {
+        Box::new(self.clone())
+    }
+}
+
+#[derive(Clone)]
+struct Simple(&'static str, &'static str);
+
+impl Command for Simple {
+    fn name(&self) -> &str {
+        self.0
+    }
+
+    fn description(&self) -> &str {
+        self.1
+    }
+
+    fn execute(&self, _args: &[String]) -> CommandResult<String> {
+        Ok(String::new())
+    }
+
+    fn parser(&self) -> clap::Command {
+        clap::Command::new(self.0)
+    }
+
+    fn clone_box(&self) -> Box<dyn Command> {
+        Box::new(self.clone())
+    }
+}
+
+fn entry(name: &str, uses: u64) -> CommandEntry {
+    CommandEntry {
+        name: name.to_string(),
+        description: String::new(),
+        tags: Vec::new(),
+        namespace: None,
+        plugin: None,
+        uses,
+    }
+}
+
+fn names(hits: &[crate::palette::SearchHit]) -> Vec<&str> {
+    hits.iter().map(|hit| hit.entry.name.as_str()).collect()
+}
+
+#[test]
+fn test_fuzzy_match_prefers_word_starts_and_runs() {
+    let m = fuzzy_match("cl", "cache list").unwrap();
+    assert_eq!(m.positions, vec![0, 6]);
+    assert!(fuzzy_match("lc", "cache list").is_none());
+    assert!(fuzzy_match("CACHE", "cache").unwrap().score > fuzzy_match("cache", "cache list").unwrap().score);
+
+    let start = fuzzy_match("re", "report").unwrap().score;
+    let inside = fuzzy_match("re", "store").unwrap().score;
+    assert!(start > inside, "{start} <= {inside}");
+}
+
+#[test]
+fn test_rank_boosts_frequently_used_commands() {
+    let entries = vec![entry("status", 0), entry("stats", 0)];
+    assert_eq!(names(&rank(entries, "sta", 10)), vec!["stats", "status"]);
+
+    let entries = vec![entry("status", 40), entry("stats", 0)];
+    assert_eq!(names(&rank(entries, "sta", 10)), vec!["status", "stats"]);
+
+    let entries = vec![entry("a", 1), entry("b", 5), entry("c", 0)];
+    assert_eq!(names(&rank(entries, "", 2)), vec!["b", "a"]);
+}
+
+#[test]
+fn test_registry_search_covers_subcommands_tags_and_plugins() {
+    let registry = CommandRegistry::new();
+    registry.register("cache", Arc::new(Cache)).unwrap();
+    registry.register("echo", Arc::new(Simple("echo", "Print the arguments"))).unwrap();
+    let aligner: Arc<dyn Command> = Arc::new(Simple("align", "Align reads to a reference"));
+    registry.register("align", Arc::new(ScopedCommand::new("bio-tools", aligner))).unwrap();
+
+    let index = registry.index().unwrap();
+    let list = index.iter().find(|entry| entry.name == "cache list").unwrap();
+    assert_eq!(list.namespace.as_deref(), Some("cache"));
+    assert_eq!(list.tags, vec!["memoize".to_string()]);
+    assert_eq!(list.description, "List cached results");
+
+    let hits = registry.search("cl").unwrap();
+    assert_eq!(hits[0].entry.name, "cache list");
+    assert_eq!(hits[0].matched, vec![0, 6]);
+    assert_eq!(registry.search("memo").unwrap()[0].entry.name, "cache");
+    assert_eq!(registry.search("bio").unwrap()[0].entry.plugin.as_deref(), Some("bio-tools"));
+    assert_eq!(names(&registry.search("reference").unwrap()), vec!["align"]);
+
+    registry.execute("cache", &vec!["clear".to_string()]).unwrap();
+    let hits = registry.search("cache").unwrap();
+    assert_eq!(names(&hits[..3]), vec!["cache", "cache clear", "cache list"]);
+    assert_eq!(hits[1].entry.uses, 1);
+}
+
+#[test]
+fn test_usage_loads_from_history() {
+    let dir = tempdir().unwrap();
+    let history = CommandHistory::with_options(100, dir.path().join("history.json")).unwrap();
+    for _ in 0..3 {
+        history.add("echo".to_string(), vec!["hi".to_string()], true, None, None).unwrap();
+    }
+    history.add("cache".to_string(), vec!["list".to_string()], true, None, None).unwrap();
+
+    let registry = CommandRegistry::new();
+    registry.register("cache", Arc::new(Cache)).unwrap();
+    registry.register("echo", Arc::new(Simple("echo", "Print the arguments"))).unwrap();
+    assert_eq!(registry.load_usage(&history).unwrap(), 4);
+
+    let hits = registry.search("").unwrap();
+    assert_eq!(hits[0].entry.name, "echo");
+    assert_eq!(hits[0].entry.uses, 3);
+    assert_eq!(hits[1].entry.name, "cache");
+    assert_eq!(hits[2].entry.name, "cache list");
+}
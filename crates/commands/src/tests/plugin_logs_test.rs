@@ -0,0 +1,89 @@
+//! Tests for per-plugin log capture
+
+use std::sync::{Arc, Mutex, OnceLock};
+
+use log::{Level, Log, Metadata, Record};
+
+use crate::plugin_logs::{self, LogLine, PluginLogBuffer, PluginLogRouter, PluginLogs, ScopedCommand};
+use crate::Command;
+
+use super::TestCommand;
+
+/// Host logger remembering the targets it was handed
+#[derive(Clone, Default)]
+struct HostLogger(Arc<Mutex<Vec<(String, String)>>>);
+
+impl Log for HostLogger {
+    fn enabled(&self, _metadata: &Metadata<'_>) -> bool {
+        true
+    }
+
+    fn log(&self, record: &Record<'_>) {
+        self.0.lock().unwrap().push((record.target().to_string(), record.args().to_string()));
+    }
+
+    fn flush(&self) {}
+}
+
+fn line(message: &str) -> LogLine {
+    LogLine {
+        timestamp: chrono::Utc::now(),
+        level: "INFO".to_string(),
+        target: "test".to_string(),
+        message: message.to_string(),
+    }
+}
+
+fn emit(router: &PluginLogRouter, message: &str) {
+    router.log(
+        &Record::builder()
+            .args(format_args!("{message}"))
+            .level(Level::Info)
+            .target("bio::align")
+            .build(),
+    );
+}
+
+#[test]
+fn test_ring_buffer_keeps_recent_lines() {
+    let buffer = PluginLogBuffer::new(3);
+    for i in 0..5 {
+        buffer.push(line(&i.to_string()));
+    }
+    let messages = |lines: Vec<LogLine>| lines.into_iter().map(|l| l.message).collect::<Vec<_>>();
+    assert_eq!(buffer.len(), 3);
+    assert_eq!(messages(buffer.recent(None)), vec!["2", "3", "4"]);
+    assert_eq!(messages(buffer.recent(Some(2))), vec!["3", "4"]);
+}
+
+#[tokio::test]
+async fn test_router_captures_and_tags_plugin_records() {
+    static LOGS: OnceLock<PluginLogs> = OnceLock::new();
+    let logs = LOGS.get_or_init(|| PluginLogs::new(10));
+    let host = HostLogger::default();
+    let router = PluginLogRouter::new(Box::new(host.clone()), logs);
+
+    emit(&router, "host message");
+    plugin_logs::scope("bio", || emit(&router, "aligned 10 reads"));
+    plugin_logs::scope_async("stats", async { emit(&router, "summarized") }).await;
+    assert_eq!(plugin_logs::current_plugin(), None);
+
+    assert_eq!(logs.plugins(), vec!["bio".to_string(), "stats".to_string()]);
+    let bio = logs.recent("bio", None).unwrap();
+    assert_eq!(bio.len(), 1);
+    assert_eq!(bio[0].target, "bio::align");
+    assert_eq!(bio[0].message, "aligned 10 reads");
+    assert!(logs.recent("missing", None).is_none());
+
+    let forwarded = host.0.lock().unwrap().clone();
+    let targets: Vec<&str> = forwarded.iter().map(|(target, _)| target.as_str()).collect();
+    assert_eq!(targets, vec!["bio::align", "plugin::bio", "plugin::stats"]);
+}
+
+#[test]
+fn test_scoped_command_runs_in_plugin_scope() {
+    let command = ScopedCommand::new("bio", Arc::new(TestCommand));
+    assert_eq!(command.name(), "test");
+    assert_eq!(command.execute(&[]).unwrap(), "Test command executed");
+    assert_eq!(plugin_logs::scope("bio", plugin_logs::current_plugin).as_deref(), Some("bio"));
+}
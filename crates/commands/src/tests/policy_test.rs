@@ -0,0 +1,74 @@
+//! Tests for command allow and deny policies
+
+use crate::policy::{evaluate, CommandPolicy, PolicyEffect, PolicyScope, PolicySubject, PolicyTarget};
+use crate::CommandError;
+use squirrel_core::error::{Coded, ErrorCode};
+
+fn policy(id: &str, subject: &str, effect: PolicyEffect, scope: PolicyScope, pattern: &str) -> CommandPolicy {
+    CommandPolicy {
+        id: id.to_string(),
+        subject: subject.parse().unwrap(),
+        effect,
+        scope,
+        pattern: pattern.to_string(),
+        description: None,
+    }
+}
+
+fn command(line: &str, plugin: Option<&str>) -> PolicyTarget {
+    let mut words = line.split_whitespace().map(str::to_string);
+    let name = words.next().unwrap();
+    PolicyTarget::command(&name, &words.collect::<Vec<_>>(), plugin)
+}
+
+#[test]
+fn test_subjects_parse_and_print() {
+    let subject: PolicySubject = "api_key:ci".parse().unwrap();
+    assert_eq!(subject, PolicySubject::ApiKey("ci".to_string()));
+    assert_eq!(subject.to_string(), "api_key:ci");
+    assert_eq!(serde_json::to_string(&PolicySubject::User("ada".to_string())).unwrap(), "\"user:ada\"");
+    assert!("group:lab".parse::<PolicySubject>().is_err());
+    assert!("user:".parse::<PolicySubject>().is_err());
+}
+
+#[test]
+fn test_deny_matches_commands_subcommands_and_namespaces() {
+    let policies = vec![
+        policy("p1", "user:ada", PolicyEffect::Deny, PolicyScope::Command, "cache clear"),
+        policy("p2", "user:ada", PolicyEffect::Deny, PolicyScope::Namespace, "gal*"),
+        policy("p3", "user:grace", PolicyEffect::Deny, PolicyScope::Command, "*"),
+    ];
+    let ada = [PolicySubject::User("ada".to_string())];
+
+    assert!(evaluate(&policies, &ada, &command("cache list", None)).is_ok());
+    let denied = evaluate(&policies, &ada, &command("cache clear --all", None));
+    assert!(matches!(denied, Err(CommandError::AuthorizationError(ref message)) if message.contains("p1")));
+    assert_eq!(denied.unwrap_err().code(), ErrorCode::PermissionDenied);
+    assert!(evaluate(&policies, &ada, &command("cache --all", None)).is_ok());
+    assert!(evaluate(&policies, &ada, &command("align", Some("galaxy"))).is_err());
+    assert!(evaluate(&policies, &ada, &command("align", Some("bioconda"))).is_ok());
+    assert!(evaluate(&policies, &ada, &PolicyTarget::tool("blast")).is_ok());
+    assert!(evaluate(&policies, &[], &command("cache clear", None)).is_ok());
+}
+
+#[test]
+fn test_allow_policies_form_an_allowlist_per_kind_of_target() {
+    let policies = vec![
+        policy("p1", "api_key:ci", PolicyEffect::Allow, PolicyScope::Command, "report?"),
+        policy("p2", "api_key:ci", PolicyEffect::Allow, PolicyScope::Namespace, "builtin"),
+        policy("p3", "api_key:ci", PolicyEffect::Deny, PolicyScope::Command, "deploy"),
+        policy("p4", "user:ada", PolicyEffect::Allow, PolicyScope::Tool, "blast*"),
+    ];
+    let ci = [PolicySubject::User("ada".to_string()), PolicySubject::ApiKey("ci".to_string())];
+
+    assert!(evaluate(&policies, &ci, &command("reports", Some("galaxy"))).is_ok());
+    assert!(evaluate(&policies, &ci, &command("status", None)).is_ok());
+    assert!(evaluate(&policies, &ci, &command("deploy", None)).is_err());
+    assert!(evaluate(&policies, &ci, &command("align", Some("galaxy"))).is_err());
+    assert!(evaluate(&policies, &ci, &PolicyTarget::tool("blastn")).is_ok());
+    assert!(evaluate(&policies, &ci, &PolicyTarget::tool("bwa")).is_err());
+
+    // Without the API key, only the user's tool allowlist applies
+    let ada = [PolicySubject::User("ada".to_string())];
+    assert!(evaluate(&policies, &ada, &command("deploy", None)).is_ok());
+}
@@ -0,0 +1,132 @@
+//! Tests for run reports
+
+use std::collections::HashMap;
+
+use chrono::{Duration, Utc};
+use serde_json::json;
+
+use crate::report::{MetricSeries, ReportFormat, RunReport};
+use crate::workflow::{RunStatus, StepStatus, WorkflowDefinition, WorkflowRun};
+
+const WORKFLOW: &str = r#"
+name: qc
+inputs:
+  sample:
+    required: true
+steps:
+  - id: stats
+    command: echo
+    args: ["${{ inputs.sample }}"]
+  - id: plot
+    command: echo
+    args: ["plot"]
+    needs: [stats]
+outputs:
+  plot: "${{ steps.plot.output }}"
+"#;
+
+fn finished_run() -> WorkflowRun {
+    let workflow = WorkflowDefinition::from_yaml(WORKFLOW).unwrap();
+    let inputs = HashMap::from([("sample".to_string(), "reads <1>.fastq".to_string())]);
+    let mut run = WorkflowRun::new(&workflow, &inputs).unwrap();
+    let start = Utc::now();
+    run.status = RunStatus::Failed;
+    run.started_at = Some(start);
+    run.finished_at = Some(start + Duration::seconds(3));
+    run.steps[0].status = StepStatus::Succeeded;
+    run.steps[0].attempts = 1;
+    run.steps[0].started_at = Some(start);
+    run.steps[0].finished_at = Some(start + Duration::milliseconds(1500));
+    run.steps[1].status = StepStatus::Failed;
+    run.steps[1].attempts = 2;
+    run.steps[1].error = Some("plotting | failed".to_string());
+    run.steps[1].started_at = Some(start + Duration::milliseconds(1500));
+    run.steps[1].finished_at = Some(start + Duration::milliseconds(2000));
+    run.outputs.insert("plot".to_string(), "results/plot.png".to_string());
+    run.outputs.insert("note".to_string(), "no artifact here".to_string());
+    run
+}
+
+#[test]
+fn test_workflow_run_report_collects_steps_logs_and_artifacts() {
+    let report = RunReport::from_workflow_run(&finished_run());
+
+    assert_eq!(report.kind, "workflow");
+    assert_eq!(report.status, "failed");
+    assert_eq!(report.parameters["sample"], "reads <1>.fastq");
+    assert_eq!(report.steps[0].duration_ms(), Some(1500));
+    assert!(report.logs[1].contains("plot failed: plotting | failed"));
+    assert_eq!(report.charts.len(), 1);
+    assert_eq!(report.charts[0].points[0].label, "stats");
+    assert_eq!(report.artifacts.len(), 1);
+    assert_eq!(report.artifacts[0].href, "results/plot.png");
+}
+
+#[test]
+fn test_html_report_is_escaped_and_embeds_charts() {
+    let report = RunReport::from_workflow_run(&finished_run())
+        .with_chart(MetricSeries::from_samples("memory_mb", [(20, 30.0), (10, 10.0)]));
+    let html = report.render(ReportFormat::Html);
+
+    assert!(html.starts_with("<!DOCTYPE html>"));
+    assert!(html.contains("reads &lt;1&gt;.fastq"));
+    assert!(!html.contains("reads <1>"));
+    assert_eq!(html.matches("<svg").count(), 2);
+    assert!(html.contains("<a href=\"results/plot.png\">plot</a>"));
+    assert!(html.contains("<td class=\"failed\">failed</td>"));
+    assert!(!html.contains("{{"));
+}
+
+#[test]
+fn test_markdown_report_uses_sparklines_and_escapes_tables() {
+    let report = RunReport::from_workflow_run(&finished_run())
+        .with_chart(MetricSeries::from_samples("memory_mb", [(20, 30.0), (10, 10.0)]));
+    let markdown = report.render(ReportFormat::Markdown);
+
+    assert!(markdown.starts_with("# Report of workflow qc"));
+    assert!(markdown.contains("- **memory_mb**: `▁█`"));
+    assert!(markdown.contains("- [plot](<results/plot.png>)"));
+    assert!(markdown.contains("| stats |  | succeeded | 1 | 1.5 s |  |  |"));
+    assert!(markdown.contains("plotting \\| failed"));
+}
+
+#[test]
+fn test_job_context_report_lists_submitted_commands() {
+    let data = json!({
+        "job_id": "job-1",
+        "dataset": "GRCh38",
+        "steps": [
+            { "command": "index", "parameters": { "ref": "GRCh38" }, "command_id": "cmd-1", "submitted_at": Utc::now() },
+            { "command": "align", "parameters": null, "command_id": "cmd-2", "submitted_at": Utc::now() },
+        ],
+    });
+    let report = RunReport::from_job_context("job-1", "job align", Some("Completed"), &data);
+
+    assert_eq!(report.status, "completed");
+    assert_eq!(report.parameters.len(), 1);
+    assert_eq!(report.parameters["dataset"], "GRCh38");
+    assert_eq!(report.steps[0].id, "cmd-1");
+    assert_eq!(report.steps[0].parameters.as_deref(), Some(r#"{"ref":"GRCh38"}"#));
+    assert_eq!(report.steps[1].command.as_deref(), Some("align"));
+    assert!(report.steps[1].parameters.is_none());
+    assert!(report.logs[0].contains("submitted index (cmd-1)"));
+}
+
+#[test]
+fn test_custom_templates_fill_known_sections_only() {
+    let report = RunReport::from_workflow_run(&finished_run());
+
+    let text = report
+        .render_with("{{title}}: {{ summary }}", ReportFormat::Markdown)
+        .unwrap();
+    assert!(text.starts_with("Report of workflow qc"));
+    assert!(text.contains("| Status | failed |"));
+    let steps = report
+        .render_with("{{#each report.steps}}{{upper id}}={{status}} {{/each}}", ReportFormat::Markdown)
+        .unwrap();
+    assert_eq!(steps, "STATS=succeeded PLOT=failed ");
+    assert!(report.render_with("{{ secrets }}", ReportFormat::Html).is_err());
+    assert!(report.render_with("{{ title", ReportFormat::Html).is_err());
+    assert!("pdf".parse::<ReportFormat>().is_err());
+    assert_eq!("md".parse::<ReportFormat>().unwrap(), ReportFormat::Markdown);
+}
@@ -0,0 +1,26 @@
+//! Tests for project scaffolds
+
+use std::fs;
+
+use crate::scaffold;
+
+#[test]
+fn test_plugin_scaffold_renders_a_new_project() {
+    let dir = tempfile::tempdir().unwrap();
+    let dest = dir.path().join("seq-stats");
+    let vars = scaffold::plugin_vars("seq-stats", "Sequence \"stats\"", "Ada").unwrap();
+    assert_eq!(vars["type_name"], "SeqStatsPlugin");
+
+    let files = scaffold::render("plugin", &dest, &vars).unwrap();
+    assert_eq!(files.len(), 4);
+    let lib = fs::read_to_string(dest.join("src/lib.rs")).unwrap();
+    assert!(lib.contains("pub struct SeqStatsPlugin;"), "{lib}");
+    assert!(lib.contains(r#"Some("Sequence \"stats\"")"#), "{lib}");
+    let manifest = fs::read_to_string(dest.join("plugin.toml")).unwrap();
+    assert!(manifest.starts_with("name = \"seq-stats\"\n"), "{manifest}");
+
+    // Existing files are never overwritten
+    assert!(scaffold::render("plugin", &dest, &vars).is_err());
+    assert!(scaffold::render("unknown", &dest, &vars).is_err());
+    assert!(scaffold::plugin_vars("Seq Stats", "", "").is_err());
+}
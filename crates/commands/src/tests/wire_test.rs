@@ -0,0 +1,160 @@
+//! Compatibility tests for the wire schema
+
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+
+use crate::extensions::{Extensions, RequestId};
+use crate::wire::{
+    format, CommandInvocation, Envelope, JsonFormat, SchemaRegistry, SchemaVersion, WireContext, WireFormat,
+    WireSchema,
+};
+use crate::workflow::StepKind;
+
+fn invocation() -> CommandInvocation {
+    let registry = SchemaRegistry::default();
+    let mut extensions = Extensions::new();
+    extensions.insert(RequestId("req-1".to_string()));
+    CommandInvocation {
+        id: "task-1".to_string(),
+        kind: StepKind::Command,
+        target: "align".to_string(),
+        args: vec!["--min-quality".to_string(), "20".to_string()],
+        timeout_secs: Some(60),
+        context: WireContext {
+            request_id: Some("req-1".to_string()),
+            user: Some("alice".to_string()),
+            workspace: Some("lab".to_string()),
+            extensions: registry.encode_extensions(&extensions).unwrap(),
+            ..WireContext::default()
+        },
+    }
+}
+
+#[test]
+fn test_invocation_round_trips_through_every_format() {
+    let registry = SchemaRegistry::default();
+    let envelope = registry.seal(&invocation()).unwrap();
+    assert_eq!(envelope.schema, "squirrel.command_invocation");
+    assert_eq!(envelope.version, SchemaVersion::new(1, 0));
+
+    for name in ["json", "yaml"] {
+        let format = format(name).unwrap();
+        let bytes = format.encode(&envelope).unwrap();
+        let decoded: CommandInvocation = registry.open(format.decode(&bytes).unwrap()).unwrap();
+        assert_eq!(decoded, invocation(), "{name}");
+
+        let extensions = registry.decode_extensions(&decoded.context.extensions).unwrap();
+        assert_eq!(extensions.get::<RequestId>(), Some(&RequestId("req-1".to_string())));
+    }
+    assert!(format("xml").is_none());
+}
+
+#[test]
+fn test_version_1_0_payload_stays_readable() {
+    // Written by a 1.0 peer; must keep decoding as long as the major version is 1
+    let bytes = br#"{
+        "schema": "squirrel.command_invocation",
+        "version": {"major": 1, "minor": 0},
+        "payload": {
+            "id": "task-1",
+            "kind": "command",
+            "target": "align",
+            "args": ["--min-quality", "20"],
+            "timeout_secs": 60,
+            "context": {
+                "request_id": "req-1",
+                "user": "alice",
+                "workspace": "lab",
+                "extensions": {"request_id": "req-1"}
+            }
+        }
+    }"#;
+    let registry = SchemaRegistry::default();
+    let decoded: CommandInvocation = registry.open(JsonFormat.decode(bytes).unwrap()).unwrap();
+    assert_eq!(decoded, invocation());
+
+    // And this build writes exactly what a 1.0 peer expects
+    let written = serde_json::to_value(registry.seal(&invocation()).unwrap()).unwrap();
+    let expected: serde_json::Value = serde_json::from_slice(bytes).unwrap();
+    assert_eq!(written, expected);
+}
+
+#[test]
+fn test_newer_minor_versions_and_missing_fields_are_accepted() {
+    let registry = SchemaRegistry::default();
+    let envelope = Envelope {
+        schema: CommandInvocation::NAME.to_string(),
+        version: SchemaVersion::new(1, 7),
+        payload: json!({
+            "id": "task-2",
+            "target": "echo",
+            "priority": "high",
+            "context": {"user": "bob", "tenant": "acme", "extensions": {"trace": {"span": 3}}}
+        }),
+    };
+    let decoded: CommandInvocation = registry.open(envelope).unwrap();
+    assert_eq!(decoded.kind, StepKind::Command);
+    assert!(decoded.args.is_empty());
+    assert_eq!(decoded.context.user.as_deref(), Some("bob"));
+
+    // Extensions this peer has not registered are skipped
+    let extensions = registry.decode_extensions(&decoded.context.extensions).unwrap();
+    assert!(extensions.is_empty());
+}
+
+#[test]
+fn test_incompatible_envelopes_are_refused() {
+    let registry = SchemaRegistry::default();
+    let sealed = registry.seal(&invocation()).unwrap();
+
+    let newer = Envelope {
+        version: SchemaVersion::new(2, 0),
+        ..sealed.clone()
+    };
+    let error = registry.open::<CommandInvocation>(newer).unwrap_err();
+    assert!(error.to_string().contains("reads up to 1.x"), "{error}");
+
+    assert!(registry.open::<WireContext>(sealed.clone()).is_err());
+    assert!(SchemaRegistry::new().open::<CommandInvocation>(sealed).is_err());
+
+    let mut values = std::collections::BTreeMap::new();
+    values.insert("request_id".to_string(), json!(42));
+    assert!(registry.decode_extensions(&values).is_err());
+}
+
+/// Second major version of a schema that renamed `name` to `label`
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+struct Sample {
+    label: String,
+}
+
+impl WireSchema for Sample {
+    const NAME: &'static str = "test.sample";
+    const VERSION: SchemaVersion = SchemaVersion::new(2, 1);
+}
+
+#[test]
+fn test_older_major_versions_are_upgraded() {
+    let mut registry = SchemaRegistry::default();
+    registry.register::<Sample>();
+    let old = Envelope {
+        schema: Sample::NAME.to_string(),
+        version: SchemaVersion::new(1, 3),
+        payload: json!({"name": "reads"}),
+    };
+    assert!(registry.open::<Sample>(old.clone()).unwrap_err().to_string().contains("no upgrade"));
+
+    registry
+        .register_upgrade(Sample::NAME, 1, |mut payload| {
+            if let Some(name) = payload.as_object_mut().and_then(|object| object.remove("name")) {
+                payload["label"] = name;
+            }
+            Ok(payload)
+        })
+        .unwrap();
+    assert!(registry.register_upgrade(Sample::NAME, 2, Ok).is_err());
+    assert!(registry.register_upgrade("test.unknown", 1, Ok).is_err());
+
+    assert_eq!(registry.open::<Sample>(old).unwrap(), Sample { label: "reads".to_string() });
+    assert!(registry.schemas().contains(&(Sample::NAME.to_string(), SchemaVersion::new(2, 1))));
+}
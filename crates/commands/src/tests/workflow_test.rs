@@ -0,0 +1,206 @@
+//! Tests for the workflow engine
+
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicU32, Ordering};
+use std::sync::{Arc, Mutex};
+
+use async_trait::async_trait;
+
+use crate::builtin::EchoCommand;
+use crate::registry::CommandRegistry;
+use crate::workflow::{
+    CancelHandle, MemoryStepCache, RunStatus, StepKind, StepRunner, StepStatus, WorkflowDefinition,
+    WorkflowEngine, WorkflowError, WorkflowEvent, WorkflowObserver, WorkflowRun,
+};
+use crate::{CommandError, CommandResult};
+
+const PIPELINE: &str = r#"
+name: pipeline
+inputs:
+  sample:
+    required: true
+  mode:
+    default: fast
+steps:
+  - id: load
+    command: echo
+    args: ["${{ inputs.sample }}"]
+  - id: analyze
+    command: echo
+    args: ["analyzed", "${{ steps.load.output }}"]
+    needs: [load]
+  - id: deep
+    command: echo
+    args: ["deep"]
+    needs: [load]
+    if: "${{ inputs.mode }} == thorough"
+  - id: report
+    command: echo
+    args: ["report"]
+    needs: [deep]
+outputs:
+  result: "${{ steps.analyze.output }}"
+"#;
+
+/// Runner that counts calls and fails the first attempts of one step
+#[derive(Default)]
+struct CountingRunner {
+    calls: AtomicU32,
+    failures_left: AtomicU32,
+}
+
+#[async_trait]
+impl StepRunner for CountingRunner {
+    async fn run(&self, _kind: StepKind, target: &str, args: &[String]) -> CommandResult<String> {
+        self.calls.fetch_add(1, Ordering::SeqCst);
+        if target == "flaky" && self.failures_left.load(Ordering::SeqCst) > 0 {
+            self.failures_left.fetch_sub(1, Ordering::SeqCst);
+            return Err(CommandError::ExecutionError("transient failure".to_string()));
+        }
+        if target == "broken" {
+            return Err(CommandError::ExecutionError("always fails".to_string()));
+        }
+        if target == "slow" {
+            tokio::time::sleep(std::time::Duration::from_secs(30)).await;
+        }
+        Ok(format!("{} {}", target, args.join(" ")))
+    }
+}
+
+/// Observer that records event types
+#[derive(Default)]
+struct RecordingObserver {
+    events: Mutex<Vec<String>>,
+}
+
+impl WorkflowObserver for RecordingObserver {
+    fn on_event(&self, _run: &WorkflowRun, event: &WorkflowEvent) {
+        let name = serde_json::to_value(event).unwrap()["type"].as_str().unwrap().to_string();
+        self.events.lock().unwrap().push(name);
+    }
+}
+
+fn inputs(pairs: &[(&str, &str)]) -> HashMap<String, String> {
+    pairs.iter().map(|(k, v)| (k.to_string(), v.to_string())).collect()
+}
+
+#[test]
+fn test_workflow_parsing_and_order() {
+    let workflow = WorkflowDefinition::from_yaml(PIPELINE).unwrap();
+    assert_eq!(workflow.steps.len(), 4);
+    assert_eq!(workflow.steps[1].depends_on, vec!["load".to_string()]);
+
+    let order = workflow.execution_order().unwrap();
+    assert_eq!(order[0], "load");
+    assert_eq!(order.last(), Some(&"report"));
+}
+
+#[test]
+fn test_workflow_validation_errors() {
+    let cycle = "name: c\nsteps:\n  - {id: a, command: echo, needs: [b]}\n  - {id: b, command: echo, needs: [a]}\n";
+    assert!(matches!(WorkflowDefinition::from_yaml(cycle), Err(WorkflowError::Validation(_))));
+
+    let unknown = "name: u\nsteps:\n  - {id: a, command: echo, needs: [missing]}\n";
+    assert!(matches!(WorkflowDefinition::from_yaml(unknown), Err(WorkflowError::Validation(_))));
+
+    let undeclared = "name: r\nsteps:\n  - {id: a, command: echo}\n  - {id: b, command: echo, args: ['${{ steps.a.output }}']}\n";
+    assert!(matches!(WorkflowDefinition::from_yaml(undeclared), Err(WorkflowError::Validation(_))));
+
+    let both = "name: t\nsteps:\n  - {id: a, command: echo, tool: echo}\n";
+    assert!(matches!(WorkflowDefinition::from_yaml(both), Err(WorkflowError::Validation(_))));
+
+    let workflow = WorkflowDefinition::from_yaml(PIPELINE).unwrap();
+    assert!(workflow.resolve_inputs(&HashMap::new()).is_err());
+}
+
+#[tokio::test]
+async fn test_workflow_runs_through_registry() {
+    let registry = CommandRegistry::new();
+    registry.register("echo", Arc::new(EchoCommand::with_prefix(""))).unwrap();
+    let observer = Arc::new(RecordingObserver::default());
+    let engine = WorkflowEngine::new(Arc::new(registry)).with_observer(observer.clone());
+
+    let workflow = WorkflowDefinition::from_yaml(PIPELINE).unwrap();
+    let run = engine.run(&workflow, &inputs(&[("sample", "s1.fq")])).await.unwrap();
+
+    assert_eq!(run.status, RunStatus::Succeeded);
+    assert_eq!(run.outputs.get("result").map(String::as_str), Some("analyzed s1.fq"));
+    assert_eq!(run.step("deep").unwrap().status, StepStatus::Skipped);
+    assert_eq!(run.step("report").unwrap().status, StepStatus::Skipped);
+    assert!((run.progress() - 1.0).abs() < f32::EPSILON);
+
+    let events = observer.events.lock().unwrap();
+    assert_eq!(events.first().map(String::as_str), Some("run_started"));
+    assert_eq!(events.last().map(String::as_str), Some("run_finished"));
+}
+
+#[tokio::test]
+async fn test_workflow_retries_and_failure() {
+    let yaml = "name: r\nsteps:\n  - {id: a, command: flaky, retries: 2, retry_delay_ms: 1}\n  - {id: b, command: broken, needs: [a]}\n  - {id: c, command: ok, needs: [b]}\n";
+    let workflow = WorkflowDefinition::from_yaml(yaml).unwrap();
+    let runner = Arc::new(CountingRunner::default());
+    runner.failures_left.store(2, Ordering::SeqCst);
+
+    let run = WorkflowEngine::new(runner.clone())
+        .run(&workflow, &HashMap::new())
+        .await
+        .unwrap();
+
+    assert_eq!(run.status, RunStatus::Failed);
+    assert_eq!(run.step("a").unwrap().status, StepStatus::Succeeded);
+    assert_eq!(run.step("a").unwrap().attempts, 3);
+    assert_eq!(run.step("b").unwrap().status, StepStatus::Failed);
+    assert_eq!(run.step("c").unwrap().status, StepStatus::Skipped);
+}
+
+#[tokio::test]
+async fn test_workflow_caches_unchanged_steps() {
+    let yaml = "name: c\ninputs:\n  x: {required: true}\nsteps:\n  - {id: a, command: one, args: ['${{ inputs.x }}']}\n  - {id: b, command: two, needs: [a], cache: false}\n";
+    let workflow = WorkflowDefinition::from_yaml(yaml).unwrap();
+    let runner = Arc::new(CountingRunner::default());
+    let engine = WorkflowEngine::new(runner.clone()).with_cache(Arc::new(MemoryStepCache::new()));
+
+    engine.run(&workflow, &inputs(&[("x", "1")])).await.unwrap();
+    assert_eq!(runner.calls.load(Ordering::SeqCst), 2);
+
+    let run = engine.run(&workflow, &inputs(&[("x", "1")])).await.unwrap();
+    assert!(run.step("a").unwrap().cached);
+    assert!(!run.step("b").unwrap().cached);
+    assert_eq!(runner.calls.load(Ordering::SeqCst), 3);
+
+    engine.run(&workflow, &inputs(&[("x", "2")])).await.unwrap();
+    assert_eq!(runner.calls.load(Ordering::SeqCst), 5);
+}
+
+#[tokio::test]
+async fn test_workflow_cancellation_skips_unfinished_steps() {
+    let yaml = "name: x\nsteps:\n  - {id: a, command: ok}\n  - {id: b, command: slow, needs: [a]}\n  - {id: c, command: ok, needs: [b]}\n";
+    let workflow = WorkflowDefinition::from_yaml(yaml).unwrap();
+    let observer = Arc::new(RecordingObserver::default());
+    let engine = WorkflowEngine::new(Arc::new(CountingRunner::default())).with_observer(observer.clone());
+    let cancel = CancelHandle::new();
+
+    let trigger = cancel.clone();
+    tokio::spawn(async move {
+        tokio::time::sleep(std::time::Duration::from_millis(50)).await;
+        trigger.cancel("memory pressure");
+        trigger.cancel("ignored");
+    });
+    let run = WorkflowRun::new(&workflow, &HashMap::new()).unwrap();
+    let run = tokio::time::timeout(
+        std::time::Duration::from_secs(5),
+        engine.execute_cancellable(&workflow, run, &cancel),
+    )
+    .await
+    .unwrap();
+
+    assert_eq!(run.status, RunStatus::Cancelled);
+    assert_eq!(cancel.reason().as_deref(), Some("memory pressure"));
+    assert_eq!(run.step("a").unwrap().status, StepStatus::Succeeded);
+    for id in ["b", "c"] {
+        let step = run.step(id).unwrap();
+        assert_eq!(step.status, StepStatus::Skipped);
+        assert_eq!(step.skip_reason.as_deref(), Some("run cancelled: memory pressure"));
+    }
+    assert_eq!(observer.events.lock().unwrap().last().map(String::as_str), Some("run_finished"));
+}
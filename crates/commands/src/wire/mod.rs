@@ -0,0 +1,202 @@
+//! Wire schema for command invocations shared over MCP
+//!
+//! Remote agents, the web server and MCP peers exchange command invocations
+//! and the context they run in as an [`Envelope`]: the name of the schema,
+//! its [`SchemaVersion`] and the payload as a JSON value. The envelope is
+//! itself a JSON object, so it fits the `payload` of an MCP message.
+//!
+//! Versions follow two rules. Within a major version, changes only add
+//! optional fields: readers ignore fields they do not know and fill in
+//! defaults for fields the sender left out, so peers with different minor
+//! versions understand each other. A new major version needs an upgrade
+//! function from the previous one in the [`SchemaRegistry`]; payloads of a
+//! newer major version than the reader knows are refused.
+//!
+//! The state of a CLI `CommandContext` travels as a [`WireContext`]. Typed
+//! [`Extensions`](crate::extensions::Extensions) cannot be serialized in
+//! general, so only extension types registered by name in the registry are
+//! sent; receivers skip names they do not know. The request's deadline
+//! travels as the milliseconds left when the context was sent, since peers'
+//! clocks need not agree.
+//!
+//! How an envelope becomes bytes is up to a [`WireFormat`]; JSON and YAML
+//! are provided.
+
+use std::collections::BTreeMap;
+use std::fmt;
+
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::workflow::StepKind;
+use crate::{CommandError, CommandResult};
+
+mod registry;
+pub use registry::SchemaRegistry;
+
+/// Version of a wire schema
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+pub struct SchemaVersion {
+    /// Incremented for changes older readers cannot understand
+    pub major: u32,
+    /// Incremented when optional fields are added
+    pub minor: u32,
+}
+
+impl SchemaVersion {
+    /// Creates a version
+    #[must_use]
+    pub const fn new(major: u32, minor: u32) -> Self {
+        Self { major, minor }
+    }
+}
+
+impl fmt::Display for SchemaVersion {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}.{}", self.major, self.minor)
+    }
+}
+
+/// A type sent over the wire under a named, versioned schema
+pub trait WireSchema: Serialize + DeserializeOwned {
+    /// Schema name, e.g. `squirrel.command_invocation`
+    const NAME: &'static str;
+    /// Version this build writes
+    const VERSION: SchemaVersion;
+}
+
+/// A versioned payload
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Envelope {
+    /// Schema name
+    pub schema: String,
+    /// Schema version of the payload
+    pub version: SchemaVersion,
+    /// The payload
+    pub payload: Value,
+}
+
+/// Command context state that can cross process boundaries
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct WireContext {
+    /// ID of the request, for logs and traces
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub request_id: Option<String>,
+    /// User the command runs for
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub user: Option<String>,
+    /// Workspace the command runs in
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub workspace: Option<String>,
+    /// Environment variables to set for the command
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub env: BTreeMap<String, String>,
+    /// Registered extensions by name
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub extensions: BTreeMap<String, Value>,
+    /// Milliseconds left until the request's deadline, added in 1.1
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub deadline_ms: Option<u64>,
+}
+
+impl WireSchema for WireContext {
+    const NAME: &'static str = "squirrel.command_context";
+    const VERSION: SchemaVersion = SchemaVersion::new(1, 1);
+}
+
+/// A command or tool invocation with its context
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct CommandInvocation {
+    /// Invocation ID
+    pub id: String,
+    /// Whether the target is a command or a tool
+    #[serde(default = "default_kind")]
+    pub kind: StepKind,
+    /// Command or tool name
+    pub target: String,
+    /// Arguments
+    #[serde(default)]
+    pub args: Vec<String>,
+    /// Maximum run time in seconds
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub timeout_secs: Option<u64>,
+    /// Context the invocation runs in
+    #[serde(default)]
+    pub context: WireContext,
+}
+
+fn default_kind() -> StepKind {
+    StepKind::Command
+}
+
+impl WireSchema for CommandInvocation {
+    const NAME: &'static str = "squirrel.command_invocation";
+    const VERSION: SchemaVersion = SchemaVersion::new(1, 0);
+}
+
+/// Encoding of envelopes as bytes
+pub trait WireFormat: Send + Sync {
+    /// Format name, e.g. `json`
+    fn name(&self) -> &'static str;
+
+    /// Encodes an envelope
+    ///
+    /// # Errors
+    /// Returns an execution error if the envelope cannot be encoded
+    fn encode(&self, envelope: &Envelope) -> CommandResult<Vec<u8>>;
+
+    /// Decodes an envelope
+    ///
+    /// # Errors
+    /// Returns a validation error if the bytes are not an envelope
+    fn decode(&self, bytes: &[u8]) -> CommandResult<Envelope>;
+}
+
+/// JSON encoding, as used in MCP messages
+#[derive(Debug, Clone, Copy, Default)]
+pub struct JsonFormat;
+
+impl WireFormat for JsonFormat {
+    fn name(&self) -> &'static str {
+        "json"
+    }
+
+    fn encode(&self, envelope: &Envelope) -> CommandResult<Vec<u8>> {
+        serde_json::to_vec(envelope).map_err(|e| CommandError::ExecutionError(format!("Cannot encode envelope: {e}")))
+    }
+
+    fn decode(&self, bytes: &[u8]) -> CommandResult<Envelope> {
+        serde_json::from_slice(bytes).map_err(|e| CommandError::ValidationError(format!("Invalid envelope: {e}")))
+    }
+}
+
+/// YAML encoding, for envelopes written to files
+#[derive(Debug, Clone, Copy, Default)]
+pub struct YamlFormat;
+
+impl WireFormat for YamlFormat {
+    fn name(&self) -> &'static str {
+        "yaml"
+    }
+
+    fn encode(&self, envelope: &Envelope) -> CommandResult<Vec<u8>> {
+        serde_yaml::to_string(envelope)
+            .map(String::into_bytes)
+            .map_err(|e| CommandError::ExecutionError(format!("Cannot encode envelope: {e}")))
+    }
+
+    fn decode(&self, bytes: &[u8]) -> CommandResult<Envelope> {
+        serde_yaml::from_slice(bytes).map_err(|e| CommandError::ValidationError(format!("Invalid envelope: {e}")))
+    }
+}
+
+/// The format with the given name
+#[must_use]
+pub fn format(name: &str) -> Option<Box<dyn WireFormat>> {
+    match name {
+        "json" => Some(Box::new(JsonFormat)),
+        "yaml" | "yml" => Some(Box::new(YamlFormat)),
+        _ => None,
+    }
+}
@@ -0,0 +1,249 @@
+//! Registry of wire schemas, their upgrades and serializable extensions
+
+use std::collections::{BTreeMap, HashMap};
+use std::fmt;
+use std::sync::Arc;
+
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use serde_json::Value;
+
+use super::{CommandInvocation, Envelope, SchemaVersion, WireContext, WireSchema};
+use crate::extensions::{Extensions, RequestId};
+use crate::{CommandError, CommandResult};
+
+/// Converts a payload of one major version to the next
+type Upgrade = Arc<dyn Fn(Value) -> CommandResult<Value> + Send + Sync>;
+
+/// Reads an extension out of a map, if present
+type EncodeExtension = Arc<dyn Fn(&Extensions) -> Option<CommandResult<Value>> + Send + Sync>;
+
+/// Inserts an extension into a map
+type DecodeExtension = Arc<dyn Fn(Value, &mut Extensions) -> CommandResult<()> + Send + Sync>;
+
+/// A known schema
+#[derive(Clone)]
+struct SchemaEntry {
+    current: SchemaVersion,
+    /// Upgrades by the major version they start from
+    upgrades: BTreeMap<u32, Upgrade>,
+}
+
+/// Serialization of one extension type
+#[derive(Clone)]
+struct ExtensionCodec {
+    encode: EncodeExtension,
+    decode: DecodeExtension,
+}
+
+/// Schemas a peer can read and write, and the extensions it sends
+///
+/// [`SchemaRegistry::default`] knows [`CommandInvocation`] and
+/// [`WireContext`], and sends the [`RequestId`] extension as `request_id`.
+#[derive(Clone)]
+pub struct SchemaRegistry {
+    schemas: HashMap<String, SchemaEntry>,
+    extensions: BTreeMap<String, ExtensionCodec>,
+}
+
+impl Default for SchemaRegistry {
+    fn default() -> Self {
+        let mut registry = Self::new();
+        registry.register::<CommandInvocation>();
+        registry.register::<WireContext>();
+        registry.register_extension::<RequestId>("request_id");
+        registry
+    }
+}
+
+impl fmt::Debug for SchemaRegistry {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("SchemaRegistry")
+            .field("schemas", &self.schemas())
+            .field("extensions", &self.extensions.keys().collect::<Vec<_>>())
+            .finish()
+    }
+}
+
+impl SchemaRegistry {
+    /// Creates a registry without schemas or extensions
+    #[must_use]
+    pub fn new() -> Self {
+        Self {
+            schemas: HashMap::new(),
+            extensions: BTreeMap::new(),
+        }
+    }
+
+    /// Registers the schema of `T` at the version this build writes
+    pub fn register<T: WireSchema>(&mut self) {
+        self.register_schema(T::NAME, T::VERSION);
+    }
+
+    /// Registers a schema by name, keeping upgrades registered before
+    pub fn register_schema(&mut self, name: &str, current: SchemaVersion) {
+        self.schemas
+            .entry(name.to_string())
+            .and_modify(|entry| entry.current = current)
+            .or_insert_with(|| SchemaEntry {
+                current,
+                upgrades: BTreeMap::new(),
+            });
+    }
+
+    /// Registers how payloads of major version `from_major` become `from_major + 1`
+    ///
+    /// # Errors
+    /// Returns a registration error if the schema is unknown or the upgrade
+    /// would not lead towards its current version
+    pub fn register_upgrade(
+        &mut self,
+        name: &str,
+        from_major: u32,
+        upgrade: impl Fn(Value) -> CommandResult<Value> + Send + Sync + 'static,
+    ) -> CommandResult<()> {
+        let entry = self
+            .schemas
+            .get_mut(name)
+            .ok_or_else(|| CommandError::RegistrationError(format!("Unknown schema '{name}'")))?;
+        if from_major >= entry.current.major {
+            return Err(CommandError::RegistrationError(format!(
+                "Schema '{name}' is at version {}; cannot upgrade from major version {from_major}",
+                entry.current
+            )));
+        }
+        entry.upgrades.insert(from_major, Arc::new(upgrade));
+        Ok(())
+    }
+
+    /// Registers an extension type sent under `name`
+    pub fn register_extension<T>(&mut self, name: &str)
+    where
+        T: Serialize + DeserializeOwned + Send + Sync + 'static,
+    {
+        let label = name.to_string();
+        let encode: EncodeExtension = Arc::new(move |extensions: &Extensions| {
+            extensions.get::<T>().map(|value| {
+                serde_json::to_value(value)
+                    .map_err(|e| CommandError::ExecutionError(format!("Cannot encode extension '{label}': {e}")))
+            })
+        });
+        let label = name.to_string();
+        let decode: DecodeExtension = Arc::new(move |value, extensions: &mut Extensions| {
+            let value: T = serde_json::from_value(value)
+                .map_err(|e| CommandError::ValidationError(format!("Invalid extension '{label}': {e}")))?;
+            extensions.insert(value);
+            Ok(())
+        });
+        self.extensions.insert(name.to_string(), ExtensionCodec { encode, decode });
+    }
+
+    /// Known schemas and the versions this registry writes, sorted by name
+    #[must_use]
+    pub fn schemas(&self) -> Vec<(String, SchemaVersion)> {
+        let mut schemas: Vec<_> = self
+            .schemas
+            .iter()
+            .map(|(name, entry)| (name.clone(), entry.current))
+            .collect();
+        schemas.sort();
+        schemas
+    }
+
+    /// The version of `name` this registry writes
+    #[must_use]
+    pub fn version(&self, name: &str) -> Option<SchemaVersion> {
+        self.schemas.get(name).map(|entry| entry.current)
+    }
+
+    /// Wraps `value` in an envelope
+    ///
+    /// # Errors
+    /// Returns an execution error if the value cannot be serialized
+    pub fn seal<T: WireSchema>(&self, value: &T) -> CommandResult<Envelope> {
+        let payload = serde_json::to_value(value)
+            .map_err(|e| CommandError::ExecutionError(format!("Cannot encode {}: {e}", T::NAME)))?;
+        Ok(Envelope {
+            schema: T::NAME.to_string(),
+            version: self.version(T::NAME).unwrap_or(T::VERSION),
+            payload,
+        })
+    }
+
+    /// Brings an envelope to the current major version of its schema
+    ///
+    /// # Errors
+    /// Returns a validation error if the schema is unknown, the payload is of
+    /// a newer major version, an upgrade is missing or an upgrade fails
+    pub fn upgrade(&self, mut envelope: Envelope) -> CommandResult<Envelope> {
+        let entry = self
+            .schemas
+            .get(&envelope.schema)
+            .ok_or_else(|| CommandError::ValidationError(format!("Unknown schema '{}'", envelope.schema)))?;
+        if envelope.version.major > entry.current.major {
+            return Err(CommandError::ValidationError(format!(
+                "Unsupported version {} of schema '{}'; this build reads up to {}.x",
+                envelope.version, envelope.schema, entry.current.major
+            )));
+        }
+        while envelope.version.major < entry.current.major {
+            let from = envelope.version.major;
+            let upgrade = entry.upgrades.get(&from).ok_or_else(|| {
+                CommandError::ValidationError(format!(
+                    "Cannot read version {} of schema '{}': no upgrade from major version {from}",
+                    envelope.version, envelope.schema
+                ))
+            })?;
+            envelope.payload = upgrade(envelope.payload)?;
+            envelope.version = SchemaVersion::new(from + 1, 0);
+        }
+        Ok(envelope)
+    }
+
+    /// Reads the value in an envelope, upgrading it first if needed
+    ///
+    /// Fields unknown to this build are ignored.
+    ///
+    /// # Errors
+    /// Returns a validation error if the envelope holds another schema or
+    /// cannot be upgraded or decoded
+    pub fn open<T: WireSchema>(&self, envelope: Envelope) -> CommandResult<T> {
+        if envelope.schema != T::NAME {
+            return Err(CommandError::ValidationError(format!(
+                "Expected schema '{}', got '{}'",
+                T::NAME,
+                envelope.schema
+            )));
+        }
+        let envelope = self.upgrade(envelope)?;
+        serde_json::from_value(envelope.payload)
+            .map_err(|e| CommandError::ValidationError(format!("Invalid {} payload: {e}", T::NAME)))
+    }
+
+    /// Serializes the registered extensions present in `extensions`
+    ///
+    /// # Errors
+    /// Returns an execution error if an extension cannot be serialized
+    pub fn encode_extensions(&self, extensions: &Extensions) -> CommandResult<BTreeMap<String, Value>> {
+        self.extensions
+            .iter()
+            .filter_map(|(name, codec)| (codec.encode)(extensions).map(|value| value.map(|value| (name.clone(), value))))
+            .collect()
+    }
+
+    /// Restores extensions serialized by a peer
+    ///
+    /// Names that are not registered here are skipped.
+    ///
+    /// # Errors
+    /// Returns a validation error if a registered extension cannot be decoded
+    pub fn decode_extensions(&self, values: &BTreeMap<String, Value>) -> CommandResult<Extensions> {
+        let mut extensions = Extensions::new();
+        for (name, value) in values {
+            if let Some(codec) = self.extensions.get(name) {
+                (codec.decode)(value.clone(), &mut extensions)?;
+            }
+        }
+        Ok(extensions)
+    }
+}
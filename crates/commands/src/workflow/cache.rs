@@ -0,0 +1,125 @@
+//! Caching of step outputs
+//!
+//! A step's cache key is a digest of everything that determines its result:
+//! what it runs, its rendered arguments and the outputs of the steps it
+//! depends on. When the key is unchanged the stored output is reused instead
+//! of running the step again.
+
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::sync::RwLock;
+
+use sha2::{Digest, Sha256};
+use tracing::warn;
+
+use super::definition::StepKind;
+
+/// Storage for step outputs keyed by cache key
+pub trait StepCache: Send + Sync + std::fmt::Debug {
+    /// Returns the stored output for a key
+    fn get(&self, key: &str) -> Option<String>;
+
+    /// Stores the output for a key
+    fn put(&self, key: &str, output: &str);
+
+    /// Removes all stored outputs
+    fn clear(&self);
+}
+
+/// Computes the cache key for a step
+pub fn cache_key(kind: StepKind, target: &str, args: &[String], dependency_outputs: &[&str]) -> String {
+    let mut hasher = Sha256::new();
+    let kind = match kind {
+        StepKind::Command => "command",
+        StepKind::Tool => "tool",
+    };
+    // Length-prefix every field so that different splits cannot collide
+    for field in [kind, target]
+        .into_iter()
+        .chain(args.iter().map(String::as_str))
+        .chain(std::iter::once("--"))
+        .chain(dependency_outputs.iter().copied())
+    {
+        hasher.update((field.len() as u64).to_le_bytes());
+        hasher.update(field.as_bytes());
+    }
+    hex::encode(hasher.finalize())
+}
+
+/// In-memory step cache, shared across runs of the same engine
+#[derive(Debug, Default)]
+pub struct MemoryStepCache {
+    /// Stored outputs
+    entries: RwLock<HashMap<String, String>>,
+}
+
+impl MemoryStepCache {
+    /// Creates an empty cache
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+impl StepCache for MemoryStepCache {
+    fn get(&self, key: &str) -> Option<String> {
+        self.entries.read().ok()?.get(key).cloned()
+    }
+
+    fn put(&self, key: &str, output: &str) {
+        if let Ok(mut entries) = self.entries.write() {
+            entries.insert(key.to_string(), output.to_string());
+        }
+    }
+
+    fn clear(&self) {
+        if let Ok(mut entries) = self.entries.write() {
+            entries.clear();
+        }
+    }
+}
+
+/// Step cache persisted as one file per entry in a directory
+#[derive(Debug)]
+pub struct FileStepCache {
+    /// Directory holding cache entries
+    dir: PathBuf,
+}
+
+impl FileStepCache {
+    /// Creates a cache stored in the given directory
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the directory cannot be created
+    pub fn new(dir: impl Into<PathBuf>) -> std::io::Result<Self> {
+        let dir = dir.into();
+        std::fs::create_dir_all(&dir)?;
+        Ok(Self { dir })
+    }
+
+    /// Path of the file holding an entry
+    fn path(&self, key: &str) -> PathBuf {
+        self.dir.join(key)
+    }
+}
+
+impl StepCache for FileStepCache {
+    fn get(&self, key: &str) -> Option<String> {
+        std::fs::read_to_string(self.path(key)).ok()
+    }
+
+    fn put(&self, key: &str, output: &str) {
+        if let Err(e) = std::fs::write(self.path(key), output) {
+            warn!("Failed to write workflow cache entry {}: {}", key, e);
+        }
+    }
+
+    fn clear(&self) {
+        if let Ok(entries) = std::fs::read_dir(&self.dir) {
+            for entry in entries.flatten() {
+                let _ = std::fs::remove_file(entry.path());
+            }
+        }
+    }
+}
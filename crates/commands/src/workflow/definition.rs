@@ -0,0 +1,316 @@
+//! Workflow definitions
+//!
+//! Workflows are written in YAML and describe a set of steps, each of which
+//! runs a command or tool. Steps declare their dependencies explicitly and the
+//! resulting graph must be acyclic.
+
+use std::collections::{HashMap, HashSet, VecDeque};
+
+use serde::{Deserialize, Serialize};
+
+use super::template;
+use super::WorkflowError;
+
+/// A parsed workflow
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct WorkflowDefinition {
+    /// Workflow name
+    pub name: String,
+
+    /// Human-readable description
+    #[serde(default)]
+    pub description: Option<String>,
+
+    /// Inputs accepted by the workflow
+    #[serde(default)]
+    pub inputs: HashMap<String, InputDefinition>,
+
+    /// Outputs produced by the workflow, as templates over step outputs
+    #[serde(default)]
+    pub outputs: HashMap<String, String>,
+
+    /// Steps to execute
+    pub steps: Vec<StepDefinition>,
+}
+
+/// A workflow input
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct InputDefinition {
+    /// Human-readable description
+    #[serde(default)]
+    pub description: Option<String>,
+
+    /// Whether the input must be supplied when no default is given
+    #[serde(default)]
+    pub required: bool,
+
+    /// Value used when the input is not supplied
+    #[serde(default)]
+    pub default: Option<String>,
+}
+
+/// What a step executes
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum StepKind {
+    /// A registered command
+    Command,
+    /// An MCP tool
+    Tool,
+}
+
+/// A single workflow step
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct StepDefinition {
+    /// Unique step identifier
+    pub id: String,
+
+    /// Human-readable step name
+    #[serde(default)]
+    pub name: Option<String>,
+
+    /// Command to run (mutually exclusive with `tool`)
+    #[serde(default)]
+    pub command: Option<String>,
+
+    /// Tool to run (mutually exclusive with `command`)
+    #[serde(default)]
+    pub tool: Option<String>,
+
+    /// Arguments, which may reference inputs and dependency outputs
+    #[serde(default)]
+    pub args: Vec<String>,
+
+    /// Steps that must complete before this one
+    #[serde(default, alias = "needs")]
+    pub depends_on: Vec<String>,
+
+    /// Condition that must hold for the step to run
+    #[serde(default, rename = "if", alias = "when")]
+    pub condition: Option<String>,
+
+    /// Number of additional attempts after a failure
+    #[serde(default)]
+    pub retries: u32,
+
+    /// Delay before the first retry, doubled on each further retry
+    #[serde(default = "default_retry_delay_ms")]
+    pub retry_delay_ms: u64,
+
+    /// Maximum run time of a single attempt
+    #[serde(default)]
+    pub timeout_secs: Option<u64>,
+
+    /// Whether the step output may be reused when its inputs are unchanged
+    #[serde(default = "default_cache")]
+    pub cache: bool,
+
+    /// Whether a failure of this step should not fail the workflow
+    #[serde(default)]
+    pub continue_on_error: bool,
+}
+
+fn default_retry_delay_ms() -> u64 {
+    1000
+}
+
+fn default_cache() -> bool {
+    true
+}
+
+impl StepDefinition {
+    /// Returns the kind of the step and the command or tool name
+    ///
+    /// Only meaningful after the workflow has been validated.
+    pub fn target(&self) -> (StepKind, &str) {
+        match (&self.command, &self.tool) {
+            (Some(command), _) => (StepKind::Command, command),
+            (None, Some(tool)) => (StepKind::Tool, tool),
+            (None, None) => (StepKind::Command, ""),
+        }
+    }
+}
+
+impl WorkflowDefinition {
+    /// Parses and validates a workflow from YAML
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the YAML is malformed or the workflow is invalid
+    pub fn from_yaml(yaml: &str) -> Result<Self, WorkflowError> {
+        let workflow: Self =
+            serde_yaml::from_str(yaml).map_err(|e| WorkflowError::Parse(e.to_string()))?;
+        workflow.validate()?;
+        Ok(workflow)
+    }
+
+    /// Returns a step by identifier
+    pub fn step(&self, id: &str) -> Option<&StepDefinition> {
+        self.steps.iter().find(|step| step.id == id)
+    }
+
+    /// Checks the workflow for structural errors
+    ///
+    /// # Errors
+    ///
+    /// Returns an error describing the first problem found
+    pub fn validate(&self) -> Result<(), WorkflowError> {
+        let invalid = |message: String| Err(WorkflowError::Validation(message));
+
+        if self.name.trim().is_empty() {
+            return invalid("workflow name must not be empty".to_string());
+        }
+        if self.steps.is_empty() {
+            return invalid("workflow must contain at least one step".to_string());
+        }
+
+        let mut ids = HashSet::new();
+        for step in &self.steps {
+            if step.id.trim().is_empty() {
+                return invalid("step id must not be empty".to_string());
+            }
+            if !ids.insert(step.id.as_str()) {
+                return invalid(format!("duplicate step id '{}'", step.id));
+            }
+            if step.command.is_some() == step.tool.is_some() {
+                return invalid(format!(
+                    "step '{}' must specify exactly one of 'command' or 'tool'",
+                    step.id
+                ));
+            }
+        }
+
+        for step in &self.steps {
+            for dependency in &step.depends_on {
+                if !ids.contains(dependency.as_str()) {
+                    return invalid(format!(
+                        "step '{}' depends on unknown step '{}'",
+                        step.id, dependency
+                    ));
+                }
+            }
+
+            let templates = step.args.iter().chain(step.condition.iter());
+            for text in templates {
+                for reference in template::references(text)? {
+                    self.check_reference(&reference, Some(step))?;
+                }
+            }
+        }
+
+        for text in self.outputs.values() {
+            for reference in template::references(text)? {
+                self.check_reference(&reference, None)?;
+            }
+        }
+
+        self.execution_order().map(|_| ())
+    }
+
+    /// Checks that a template reference points at something available
+    fn check_reference(
+        &self,
+        reference: &template::Reference,
+        step: Option<&StepDefinition>,
+    ) -> Result<(), WorkflowError> {
+        match reference {
+            template::Reference::Input(name) if !self.inputs.contains_key(name) => Err(
+                WorkflowError::Validation(format!("reference to undeclared input '{}'", name)),
+            ),
+            template::Reference::StepOutput(id) => match step {
+                Some(step) if !step.depends_on.contains(id) => {
+                    Err(WorkflowError::Validation(format!(
+                        "step '{}' references output of '{}' without depending on it",
+                        step.id, id
+                    )))
+                }
+                None if self.step(id).is_none() => Err(WorkflowError::Validation(format!(
+                    "workflow output references unknown step '{}'",
+                    id
+                ))),
+                _ => Ok(()),
+            },
+            _ => Ok(()),
+        }
+    }
+
+    /// Returns step identifiers in a valid execution order
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the dependency graph contains a cycle
+    pub fn execution_order(&self) -> Result<Vec<&str>, WorkflowError> {
+        let mut remaining: HashMap<&str, usize> = self
+            .steps
+            .iter()
+            .map(|step| (step.id.as_str(), step.depends_on.len()))
+            .collect();
+        let mut ready: VecDeque<&str> = self
+            .steps
+            .iter()
+            .filter(|step| step.depends_on.is_empty())
+            .map(|step| step.id.as_str())
+            .collect();
+
+        let mut order = Vec::with_capacity(self.steps.len());
+        while let Some(id) = ready.pop_front() {
+            order.push(id);
+            for step in self.steps.iter().filter(|s| s.depends_on.iter().any(|d| d == id)) {
+                if let Some(count) = remaining.get_mut(step.id.as_str()) {
+                    *count -= 1;
+                    if *count == 0 {
+                        ready.push_back(&step.id);
+                    }
+                }
+            }
+        }
+
+        if order.len() == self.steps.len() {
+            Ok(order)
+        } else {
+            let cyclic: Vec<&str> = self
+                .steps
+                .iter()
+                .map(|step| step.id.as_str())
+                .filter(|id| !order.contains(id))
+                .collect();
+            Err(WorkflowError::Validation(format!(
+                "dependency cycle between steps: {}",
+                cyclic.join(", ")
+            )))
+        }
+    }
+
+    /// Combines supplied inputs with defaults
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if an input is unknown or a required input is missing
+    pub fn resolve_inputs(
+        &self,
+        supplied: &HashMap<String, String>,
+    ) -> Result<HashMap<String, String>, WorkflowError> {
+        if let Some(unknown) = supplied.keys().find(|name| !self.inputs.contains_key(*name)) {
+            return Err(WorkflowError::Validation(format!("unknown input '{}'", unknown)));
+        }
+
+        let mut resolved = HashMap::new();
+        for (name, input) in &self.inputs {
+            match supplied.get(name).or(input.default.as_ref()) {
+                Some(value) => {
+                    resolved.insert(name.clone(), value.clone());
+                }
+                None if input.required => {
+                    return Err(WorkflowError::Validation(format!(
+                        "missing required input '{}'",
+                        name
+                    )));
+                }
+                None => {
+                    resolved.insert(name.clone(), String::new());
+                }
+            }
+        }
+        Ok(resolved)
+    }
+}
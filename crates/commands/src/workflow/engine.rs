@@ -0,0 +1,779 @@
+//! Workflow execution engine
+//!
+//! The engine walks the step graph, running every step whose dependencies
+//! have completed. Independent steps run concurrently up to a configurable
+//! limit. Failed steps are retried with exponential backoff, and steps whose
+//! inputs are unchanged since a previous run are served from the cache. A run
+//! started with a [`CancelHandle`] can be stopped before it finishes.
+
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use tokio::task::JoinSet;
+use tracing::{debug, info, warn};
+use uuid::Uuid;
+
+use super::cache::{cache_key, StepCache};
+use super::definition::{StepDefinition, StepKind, WorkflowDefinition};
+use super::template::{self, TemplateContext};
+use super::WorkflowError;
+use crate::{CommandError, CommandRegistry, CommandResult};
+
+/// Default number of steps that may run at the same time
+const DEFAULT_MAX_PARALLEL: usize = 4;
+
+/// Executes the command or tool behind a step
+#[async_trait]
+pub trait StepRunner: Send + Sync {
+    /// Runs a command or tool with rendered arguments and returns its output
+    async fn run(&self, kind: StepKind, target: &str, args: &[String]) -> CommandResult<String>;
+}
+
+#[async_trait]
+impl StepRunner for CommandRegistry {
+    async fn run(&self, kind: StepKind, target: &str, args: &[String]) -> CommandResult<String> {
+        match kind {
+            StepKind::Command => {
+                let registry = self.clone();
+                let name = target.to_string();
+                let args = args.to_vec();
+                tokio::task::spawn_blocking(move || registry.execute(&name, &args))
+                    .await
+                    .map_err(|e| CommandError::ExecutionError(format!("Step task failed: {}", e)))?
+            }
+            StepKind::Tool => Err(CommandError::ExecutionError(format!(
+                "Tool '{}' cannot be run by a command registry runner",
+                target
+            ))),
+        }
+    }
+}
+
+/// Overall state of a workflow run
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum RunStatus {
+    /// The run has been created but not started
+    Pending,
+    /// Steps are executing
+    Running,
+    /// All steps succeeded or were skipped
+    Succeeded,
+    /// At least one step failed
+    Failed,
+    /// The run was cancelled before all steps finished
+    Cancelled,
+}
+
+/// State of a single step within a run
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum StepStatus {
+    /// Waiting for dependencies
+    Pending,
+    /// Currently executing
+    Running,
+    /// Completed successfully
+    Succeeded,
+    /// Failed after all retries
+    Failed,
+    /// Not run because of a condition or an unsuccessful dependency
+    Skipped,
+}
+
+impl StepStatus {
+    /// Whether the step will not change state again
+    #[must_use]
+    pub fn is_finished(self) -> bool {
+        matches!(self, Self::Succeeded | Self::Failed | Self::Skipped)
+    }
+}
+
+/// Record of a step within a run
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct StepRun {
+    /// Step identifier
+    pub id: String,
+    /// Current state
+    pub status: StepStatus,
+    /// Number of attempts made
+    pub attempts: u32,
+    /// Whether the output was taken from the cache
+    pub cached: bool,
+    /// Output of the step, once it has succeeded
+    pub output: Option<String>,
+    /// Error of the last failed attempt
+    pub error: Option<String>,
+    /// Reason the step was skipped
+    pub skip_reason: Option<String>,
+    /// When the first attempt started
+    pub started_at: Option<DateTime<Utc>>,
+    /// When the step reached its final state
+    pub finished_at: Option<DateTime<Utc>>,
+}
+
+impl StepRun {
+    fn new(id: &str) -> Self {
+        Self {
+            id: id.to_string(),
+            status: StepStatus::Pending,
+            attempts: 0,
+            cached: false,
+            output: None,
+            error: None,
+            skip_reason: None,
+            started_at: None,
+            finished_at: None,
+        }
+    }
+}
+
+/// Record of a workflow run
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct WorkflowRun {
+    /// Unique run identifier
+    pub id: String,
+    /// Name of the workflow
+    pub workflow: String,
+    /// Current state
+    pub status: RunStatus,
+    /// Resolved inputs
+    pub inputs: HashMap<String, String>,
+    /// Steps in definition order
+    pub steps: Vec<StepRun>,
+    /// Rendered workflow outputs, available once the run has finished
+    pub outputs: HashMap<String, String>,
+    /// When the run was created
+    pub created_at: DateTime<Utc>,
+    /// When execution started
+    pub started_at: Option<DateTime<Utc>>,
+    /// When execution finished
+    pub finished_at: Option<DateTime<Utc>>,
+}
+
+impl WorkflowRun {
+    /// Creates a pending run with inputs resolved against the workflow
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the inputs are invalid for the workflow
+    pub fn new(
+        workflow: &WorkflowDefinition,
+        inputs: &HashMap<String, String>,
+    ) -> Result<Self, WorkflowError> {
+        Ok(Self {
+            id: Uuid::new_v4().to_string(),
+            workflow: workflow.name.clone(),
+            status: RunStatus::Pending,
+            inputs: workflow.resolve_inputs(inputs)?,
+            steps: workflow.steps.iter().map(|step| StepRun::new(&step.id)).collect(),
+            outputs: HashMap::new(),
+            created_at: Utc::now(),
+            started_at: None,
+            finished_at: None,
+        })
+    }
+
+    /// Returns a step record by identifier
+    #[must_use]
+    pub fn step(&self, id: &str) -> Option<&StepRun> {
+        self.steps.iter().find(|step| step.id == id)
+    }
+
+    /// Fraction of steps that have finished, between 0 and 1
+    #[must_use]
+    pub fn progress(&self) -> f32 {
+        if self.steps.is_empty() {
+            return 1.0;
+        }
+        let finished = self.steps.iter().filter(|step| step.status.is_finished()).count();
+        finished as f32 / self.steps.len() as f32
+    }
+
+    fn step_mut(&mut self, id: &str) -> &mut StepRun {
+        let index = self
+            .steps
+            .iter()
+            .position(|step| step.id == id)
+            .unwrap_or_else(|| panic!("step '{}' is not part of run {}", id, self.id));
+        &mut self.steps[index]
+    }
+
+    fn status_of(&self, id: &str) -> StepStatus {
+        self.step(id).map_or(StepStatus::Pending, |step| step.status)
+    }
+}
+
+/// Progress notifications emitted during a run
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum WorkflowEvent {
+    /// The run started
+    RunStarted {
+        /// Number of steps in the workflow
+        steps: usize,
+    },
+    /// A step attempt started
+    StepStarted {
+        /// Step identifier
+        step: String,
+        /// Attempt number, starting at 1
+        attempt: u32,
+    },
+    /// A step attempt failed and will be retried
+    StepRetrying {
+        /// Step identifier
+        step: String,
+        /// Number of the failed attempt
+        attempt: u32,
+        /// Error of the failed attempt
+        error: String,
+        /// Delay before the next attempt
+        delay_ms: u64,
+    },
+    /// A step completed successfully
+    StepCompleted {
+        /// Step identifier
+        step: String,
+        /// Whether the output was taken from the cache
+        cached: bool,
+        /// Run time of the successful attempt
+        duration_ms: u64,
+    },
+    /// A step failed after all retries
+    StepFailed {
+        /// Step identifier
+        step: String,
+        /// Error of the last attempt
+        error: String,
+    },
+    /// A step was skipped
+    StepSkipped {
+        /// Step identifier
+        step: String,
+        /// Why the step was skipped
+        reason: String,
+    },
+    /// The run finished
+    RunFinished {
+        /// Final status
+        status: RunStatus,
+        /// Total run time
+        duration_ms: u64,
+    },
+}
+
+/// Cancels a workflow run from outside the engine
+///
+/// Clones share the same cancellation.
+#[derive(Debug, Clone)]
+pub struct CancelHandle {
+    reason: Arc<tokio::sync::watch::Sender<Option<String>>>,
+}
+
+impl Default for CancelHandle {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl CancelHandle {
+    /// Creates a handle that has not been cancelled
+    #[must_use]
+    pub fn new() -> Self {
+        Self {
+            reason: Arc::new(tokio::sync::watch::Sender::new(None)),
+        }
+    }
+
+    /// Cancels the run; only the first reason is kept
+    pub fn cancel(&self, reason: impl Into<String>) {
+        let reason = reason.into();
+        self.reason.send_if_modified(|current| {
+            if current.is_some() {
+                return false;
+            }
+            *current = Some(reason);
+            true
+        });
+    }
+
+    /// Why the run was cancelled, if it was
+    #[must_use]
+    pub fn reason(&self) -> Option<String> {
+        self.reason.borrow().clone()
+    }
+
+    /// Waits until the run is cancelled and returns the reason
+    async fn cancelled(&self) -> String {
+        let mut receiver = self.reason.subscribe();
+        if receiver.wait_for(Option::is_some).await.is_err() {
+            // The sender lives as long as this handle
+            std::future::pending::<()>().await;
+        }
+        self.reason().unwrap_or_default()
+    }
+}
+
+/// Receives progress notifications from the engine
+pub trait WorkflowObserver: Send + Sync {
+    /// Called after the run state has been updated for an event
+    fn on_event(&self, run: &WorkflowRun, event: &WorkflowEvent);
+}
+
+/// A step whose arguments have been rendered and which is ready to run
+#[derive(Clone)]
+struct PreparedStep {
+    kind: StepKind,
+    target: String,
+    args: Vec<String>,
+    cache_key: Option<String>,
+    timeout: Option<Duration>,
+}
+
+/// Mutable state of a run while it executes
+struct Execution {
+    /// Run record reported to observers
+    run: WorkflowRun,
+    /// Outputs of succeeded steps
+    outputs: HashMap<String, String>,
+    /// Rendered steps, kept for retries
+    prepared: HashMap<String, PreparedStep>,
+    /// Attempts in flight
+    running: JoinSet<Attempt>,
+    /// Step of each task in flight
+    tasks: HashMap<tokio::task::Id, String>,
+    /// Whether a step has failed the run
+    failed: bool,
+    /// Whether the run was cancelled
+    cancelled: bool,
+}
+
+/// Result of one attempt of a step
+struct Attempt {
+    step: String,
+    result: CommandResult<String>,
+    duration: Duration,
+}
+
+/// Runs workflows through a step runner
+pub struct WorkflowEngine {
+    /// Executes commands and tools
+    runner: Arc<dyn StepRunner>,
+    /// Optional cache of step outputs
+    cache: Option<Arc<dyn StepCache>>,
+    /// Receivers of progress events
+    observers: Vec<Arc<dyn WorkflowObserver>>,
+    /// Maximum number of concurrently running steps
+    max_parallel: usize,
+}
+
+impl std::fmt::Debug for WorkflowEngine {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("WorkflowEngine")
+            .field("cache", &self.cache)
+            .field("observers", &self.observers.len())
+            .field("max_parallel", &self.max_parallel)
+            .finish_non_exhaustive()
+    }
+}
+
+impl WorkflowEngine {
+    /// Creates an engine that runs steps through the given runner
+    pub fn new(runner: Arc<dyn StepRunner>) -> Self {
+        Self {
+            runner,
+            cache: None,
+            observers: Vec::new(),
+            max_parallel: DEFAULT_MAX_PARALLEL,
+        }
+    }
+
+    /// Enables caching of step outputs
+    #[must_use]
+    pub fn with_cache(mut self, cache: Arc<dyn StepCache>) -> Self {
+        self.cache = Some(cache);
+        self
+    }
+
+    /// Adds an observer for progress events
+    #[must_use]
+    pub fn with_observer(mut self, observer: Arc<dyn WorkflowObserver>) -> Self {
+        self.observers.push(observer);
+        self
+    }
+
+    /// Sets the maximum number of concurrently running steps
+    #[must_use]
+    pub fn with_max_parallel(mut self, max_parallel: usize) -> Self {
+        self.max_parallel = max_parallel.max(1);
+        self
+    }
+
+    /// Runs a workflow with the given inputs until all steps have finished
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the inputs are invalid. Step failures are reported
+    /// through the returned run rather than as an error.
+    pub async fn run(
+        &self,
+        workflow: &WorkflowDefinition,
+        inputs: &HashMap<String, String>,
+    ) -> Result<WorkflowRun, WorkflowError> {
+        let run = WorkflowRun::new(workflow, inputs)?;
+        Ok(self.execute(workflow, run).await)
+    }
+
+    /// Executes a previously created run until all steps have finished
+    pub async fn execute(&self, workflow: &WorkflowDefinition, run: WorkflowRun) -> WorkflowRun {
+        self.execute_cancellable(workflow, run, &CancelHandle::new()).await
+    }
+
+    /// Executes a previously created run until all steps have finished or
+    /// `cancel` is triggered
+    ///
+    /// On cancellation, attempts in flight are aborted and every unfinished
+    /// step is skipped. Steps already running on the blocking pool finish in
+    /// the background, but their results are discarded.
+    pub async fn execute_cancellable(
+        &self,
+        workflow: &WorkflowDefinition,
+        run: WorkflowRun,
+        cancel: &CancelHandle,
+    ) -> WorkflowRun {
+        let started = Instant::now();
+        let mut state = Execution {
+            run,
+            outputs: HashMap::new(),
+            prepared: HashMap::new(),
+            running: JoinSet::new(),
+            tasks: HashMap::new(),
+            failed: false,
+            cancelled: false,
+        };
+        state.run.status = RunStatus::Running;
+        state.run.started_at = Some(Utc::now());
+        info!("Starting workflow '{}' (run {})", workflow.name, state.run.id);
+        self.emit(&state.run, WorkflowEvent::RunStarted { steps: workflow.steps.len() });
+
+        loop {
+            if let Some(reason) = cancel.reason() {
+                self.cancel(&mut state, &reason);
+                break;
+            }
+            self.schedule(workflow, &mut state);
+
+            let joined = tokio::select! {
+                biased;
+                reason = cancel.cancelled() => {
+                    self.cancel(&mut state, &reason);
+                    break;
+                }
+                joined = state.running.join_next() => joined,
+            };
+            let Some(joined) = joined else {
+                break;
+            };
+            let attempt = match joined {
+                Ok(attempt) => attempt,
+                Err(e) => {
+                    let Some(id) = state.tasks.remove(&e.id()) else {
+                        continue;
+                    };
+                    Attempt {
+                        step: id,
+                        result: Err(CommandError::ExecutionError(format!("Step task failed: {}", e))),
+                        duration: Duration::ZERO,
+                    }
+                }
+            };
+            state.tasks.retain(|_, step| *step != attempt.step);
+
+            let Some(step) = workflow.step(&attempt.step) else {
+                continue;
+            };
+            match attempt.result {
+                Ok(output) => self.complete(&mut state, step, output, attempt.duration),
+                Err(error) => {
+                    let error = error.to_string();
+                    let attempts = state.run.step(&step.id).map_or(1, |s| s.attempts);
+                    if attempts <= step.retries && !state.failed {
+                        let delay = retry_delay(step, attempts);
+                        state.run.step_mut(&step.id).error = Some(error.clone());
+                        self.emit(
+                            &state.run,
+                            WorkflowEvent::StepRetrying {
+                                step: step.id.clone(),
+                                attempt: attempts,
+                                error,
+                                delay_ms: delay.as_millis() as u64,
+                            },
+                        );
+                        if let Some(prepared) = state.prepared.get(&step.id).cloned() {
+                            self.spawn(&mut state, step, prepared, delay);
+                        }
+                    } else {
+                        warn!("Workflow step '{}' failed: {}", step.id, error);
+                        self.fail(&mut state, step, error);
+                    }
+                }
+            }
+        }
+
+        let mut run = state.run;
+        let context = TemplateContext {
+            inputs: Some(&run.inputs),
+            outputs: Some(&state.outputs),
+        };
+        let mut rendered = HashMap::new();
+        for (name, text) in &workflow.outputs {
+            match template::render(text, &context) {
+                Ok(value) => {
+                    rendered.insert(name.clone(), value);
+                }
+                Err(e) => debug!("Workflow output '{}' unavailable: {}", name, e),
+            }
+        }
+        run.outputs = rendered;
+
+        run.status = if state.cancelled {
+            RunStatus::Cancelled
+        } else if state.failed {
+            RunStatus::Failed
+        } else {
+            RunStatus::Succeeded
+        };
+        run.finished_at = Some(Utc::now());
+        info!("Workflow '{}' (run {}) finished: {:?}", workflow.name, run.id, run.status);
+        let duration_ms = started.elapsed().as_millis() as u64;
+        self.emit(&run, WorkflowEvent::RunFinished { status: run.status, duration_ms });
+        run
+    }
+
+    /// Starts every pending step whose dependencies have finished
+    ///
+    /// Skipping a step can make its dependents ready to be skipped too, so
+    /// this repeats until no further step changes state.
+    fn schedule(&self, workflow: &WorkflowDefinition, state: &mut Execution) {
+        let mut changed = true;
+        while changed {
+            changed = false;
+            for step in &workflow.steps {
+                if state.run.status_of(&step.id) != StepStatus::Pending {
+                    continue;
+                }
+                if state.failed {
+                    self.skip(state, &step.id, "workflow failed".to_string());
+                    changed = true;
+                    continue;
+                }
+                if !step.depends_on.iter().all(|d| state.run.status_of(d).is_finished()) {
+                    continue;
+                }
+                if state.running.len() >= self.max_parallel {
+                    return;
+                }
+                changed = true;
+
+                if let Some(dependency) = step
+                    .depends_on
+                    .iter()
+                    .find(|d| state.run.status_of(d) != StepStatus::Succeeded)
+                {
+                    let reason = format!("dependency '{}' did not succeed", dependency);
+                    self.skip(state, &step.id, reason);
+                    continue;
+                }
+
+                let args = match prepare_args(step, &state.run.inputs, &state.outputs) {
+                    Ok(Some(args)) => args,
+                    Ok(None) => {
+                        self.skip(state, &step.id, "condition not met".to_string());
+                        continue;
+                    }
+                    Err(e) => {
+                        self.fail(state, step, e.to_string());
+                        continue;
+                    }
+                };
+
+                let (kind, target) = step.target();
+                let cache_key = self.cache.as_ref().filter(|_| step.cache).map(|_| {
+                    let dependency_outputs: Vec<&str> = step
+                        .depends_on
+                        .iter()
+                        .map(|d| state.outputs.get(d).map_or("", String::as_str))
+                        .collect();
+                    cache_key(kind, target, &args, &dependency_outputs)
+                });
+
+                if let (Some(cache), Some(key)) = (&self.cache, &cache_key) {
+                    if let Some(output) = cache.get(key) {
+                        debug!("Workflow step '{}' served from cache", step.id);
+                        state.run.step_mut(&step.id).cached = true;
+                        self.complete(state, step, output, Duration::ZERO);
+                        continue;
+                    }
+                }
+
+                let prepared = PreparedStep {
+                    kind,
+                    target: target.to_string(),
+                    args,
+                    cache_key,
+                    timeout: step.timeout_secs.map(Duration::from_secs),
+                };
+                state.prepared.insert(step.id.clone(), prepared.clone());
+                self.spawn(state, step, prepared, Duration::ZERO);
+            }
+        }
+    }
+
+    /// Starts an attempt of a step, after an optional delay
+    fn spawn(&self, state: &mut Execution, step: &StepDefinition, prepared: PreparedStep, delay: Duration) {
+        let record = state.run.step_mut(&step.id);
+        record.status = StepStatus::Running;
+        record.attempts += 1;
+        record.started_at.get_or_insert_with(Utc::now);
+        let attempt = record.attempts;
+        self.emit(&state.run, WorkflowEvent::StepStarted { step: step.id.clone(), attempt });
+
+        let runner = Arc::clone(&self.runner);
+        let id = step.id.clone();
+        let handle = state.running.spawn(async move {
+            if !delay.is_zero() {
+                tokio::time::sleep(delay).await;
+            }
+            let started = Instant::now();
+            let execution = runner.run(prepared.kind, &prepared.target, &prepared.args);
+            let result = match prepared.timeout {
+                Some(timeout) => tokio::time::timeout(timeout, execution)
+                    .await
+                    .unwrap_or_else(|_| {
+                        Err(CommandError::ExecutionError(format!(
+                            "Timed out after {}s",
+                            timeout.as_secs()
+                        )))
+                    }),
+                None => execution.await,
+            };
+            Attempt {
+                step: id,
+                result,
+                duration: started.elapsed(),
+            }
+        });
+        state.tasks.insert(handle.id(), step.id.clone());
+    }
+
+    /// Records the successful completion of a step
+    fn complete(&self, state: &mut Execution, step: &StepDefinition, output: String, duration: Duration) {
+        let record = state.run.step_mut(&step.id);
+        let cached = record.cached;
+        if !cached {
+            let key = state.prepared.get(&step.id).and_then(|p| p.cache_key.as_ref());
+            if let (Some(cache), Some(key)) = (&self.cache, key) {
+                cache.put(key, &output);
+            }
+        }
+
+        let now = Utc::now();
+        let record = state.run.step_mut(&step.id);
+        record.status = StepStatus::Succeeded;
+        record.output = Some(output.clone());
+        record.error = None;
+        record.started_at.get_or_insert(now);
+        record.finished_at = Some(now);
+        state.outputs.insert(step.id.clone(), output);
+        self.emit(
+            &state.run,
+            WorkflowEvent::StepCompleted {
+                step: step.id.clone(),
+                cached,
+                duration_ms: duration.as_millis() as u64,
+            },
+        );
+    }
+
+    /// Records the final failure of a step
+    fn fail(&self, state: &mut Execution, step: &StepDefinition, error: String) {
+        let record = state.run.step_mut(&step.id);
+        record.status = StepStatus::Failed;
+        record.error = Some(error.clone());
+        record.finished_at = Some(Utc::now());
+        if !step.continue_on_error {
+            state.failed = true;
+        }
+        self.emit(&state.run, WorkflowEvent::StepFailed { step: step.id.clone(), error });
+    }
+
+    /// Marks a step as skipped
+    fn skip(&self, state: &mut Execution, id: &str, reason: String) {
+        debug!("Skipping workflow step '{}': {}", id, reason);
+        let record = state.run.step_mut(id);
+        record.status = StepStatus::Skipped;
+        record.skip_reason = Some(reason.clone());
+        record.finished_at = Some(Utc::now());
+        self.emit(&state.run, WorkflowEvent::StepSkipped { step: id.to_string(), reason });
+    }
+
+    /// Aborts the attempts in flight and skips every unfinished step
+    fn cancel(&self, state: &mut Execution, reason: &str) {
+        info!("Cancelling workflow run {}: {}", state.run.id, reason);
+        state.running.abort_all();
+        state.tasks.clear();
+        state.cancelled = true;
+        let unfinished: Vec<String> = state
+            .run
+            .steps
+            .iter()
+            .filter(|step| !step.status.is_finished())
+            .map(|step| step.id.clone())
+            .collect();
+        for id in unfinished {
+            self.skip(state, &id, format!("run cancelled: {}", reason));
+        }
+    }
+
+    /// Notifies all observers of an event
+    fn emit(&self, run: &WorkflowRun, event: WorkflowEvent) {
+        for observer in &self.observers {
+            observer.on_event(run, &event);
+        }
+    }
+}
+
+/// Delay before retrying a step after the given number of attempts
+fn retry_delay(step: &StepDefinition, attempts: u32) -> Duration {
+    let factor = 2u64.saturating_pow(attempts.saturating_sub(1));
+    Duration::from_millis(step.retry_delay_ms.saturating_mul(factor))
+}
+
+/// Evaluates a step's condition and renders its arguments
+///
+/// Returns `None` if the condition is not met.
+fn prepare_args(
+    step: &StepDefinition,
+    inputs: &HashMap<String, String>,
+    outputs: &HashMap<String, String>,
+) -> Result<Option<Vec<String>>, WorkflowError> {
+    let context = TemplateContext {
+        inputs: Some(inputs),
+        outputs: Some(outputs),
+    };
+    if let Some(condition) = &step.condition {
+        if !template::evaluate(condition, &context)? {
+            return Ok(None);
+        }
+    }
+    step.args
+        .iter()
+        .map(|arg| template::render(arg, &context))
+        .collect::<Result<Vec<_>, _>>()
+        .map(Some)
+}
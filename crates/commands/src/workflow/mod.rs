@@ -0,0 +1,67 @@
+//! Workflow definition language and engine
+//!
+//! A workflow is a YAML document describing steps that run commands or
+//! tools. Steps form a directed acyclic graph through their `depends_on`
+//! lists, may be guarded by an `if` condition, are retried on failure and
+//! can reuse cached outputs when nothing they depend on has changed.
+//!
+//! ```yaml
+//! name: sample-qc
+//! inputs:
+//!   sample:
+//!     required: true
+//! steps:
+//!   - id: stats
+//!     command: bio
+//!     args: ["stats", "${{ inputs.sample }}"]
+//!   - id: report
+//!     command: echo
+//!     args: ["${{ steps.stats.output }}"]
+//!     depends_on: [stats]
+//!     retries: 2
+//! outputs:
+//!   report: "${{ steps.report.output }}"
+//! ```
+
+use thiserror::Error;
+
+use crate::CommandError;
+
+/// Caching of step outputs
+pub mod cache;
+
+/// Workflow definitions and validation
+pub mod definition;
+
+/// Workflow execution
+pub mod engine;
+
+/// Template expressions in arguments and conditions
+pub mod template;
+
+pub use cache::{FileStepCache, MemoryStepCache, StepCache};
+pub use definition::{InputDefinition, StepDefinition, StepKind, WorkflowDefinition};
+pub use engine::{
+    CancelHandle, RunStatus, StepRun, StepRunner, StepStatus, WorkflowEngine, WorkflowEvent, WorkflowObserver,
+    WorkflowRun,
+};
+
+/// Workflow errors
+#[derive(Debug, Error)]
+pub enum WorkflowError {
+    /// The workflow document could not be parsed
+    #[error("Parse error: {0}")]
+    Parse(String),
+
+    /// The workflow or its inputs are invalid
+    #[error("Validation error: {0}")]
+    Validation(String),
+
+    /// A value needed during execution was unavailable
+    #[error("Execution error: {0}")]
+    Execution(String),
+
+    /// A command failed
+    #[error(transparent)]
+    Command(#[from] CommandError),
+}
@@ -0,0 +1,139 @@
+//! Expression templates used in workflow arguments and conditions
+//!
+//! Templates embed expressions of the form `${{ inputs.name }}` or
+//! `${{ steps.<id>.output }}`. Conditions are a single template, optionally
+//! comparing two templates with `==` or `!=`.
+
+use std::collections::HashMap;
+
+use super::WorkflowError;
+
+/// Opening delimiter of an expression
+const OPEN: &str = "${{";
+
+/// Closing delimiter of an expression
+const CLOSE: &str = "}}";
+
+/// A value referenced by an expression
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Reference {
+    /// A workflow input
+    Input(String),
+    /// The output of a step
+    StepOutput(String),
+}
+
+impl Reference {
+    /// Parses the inside of an expression
+    fn parse(expression: &str) -> Result<Self, WorkflowError> {
+        let parts: Vec<&str> = expression.trim().split('.').collect();
+        match parts.as_slice() {
+            ["inputs", name] if !name.is_empty() => Ok(Self::Input((*name).to_string())),
+            ["steps", id, "output"] if !id.is_empty() => Ok(Self::StepOutput((*id).to_string())),
+            _ => Err(WorkflowError::Validation(format!(
+                "invalid expression '{}'; expected inputs.<name> or steps.<id>.output",
+                expression.trim()
+            ))),
+        }
+    }
+}
+
+/// Values available while rendering templates
+#[derive(Debug, Default)]
+pub struct TemplateContext<'a> {
+    /// Resolved workflow inputs
+    pub inputs: Option<&'a HashMap<String, String>>,
+    /// Outputs of completed steps
+    pub outputs: Option<&'a HashMap<String, String>>,
+}
+
+impl TemplateContext<'_> {
+    /// Looks up the value of a reference
+    fn lookup(&self, reference: &Reference) -> Result<String, WorkflowError> {
+        let (map, key, kind) = match reference {
+            Reference::Input(name) => (self.inputs, name, "input"),
+            Reference::StepOutput(id) => (self.outputs, id, "output of step"),
+        };
+        map.and_then(|values| values.get(key))
+            .cloned()
+            .ok_or_else(|| WorkflowError::Execution(format!("{} '{}' is not available", kind, key)))
+    }
+}
+
+/// Splits a template into literal text and expressions
+fn segments(text: &str) -> Result<Vec<(&str, Option<Reference>)>, WorkflowError> {
+    let mut result = Vec::new();
+    let mut rest = text;
+    while let Some(start) = rest.find(OPEN) {
+        let after = &rest[start + OPEN.len()..];
+        let end = after.find(CLOSE).ok_or_else(|| {
+            WorkflowError::Validation(format!("unterminated expression in '{}'", text))
+        })?;
+        result.push((&rest[..start], Some(Reference::parse(&after[..end])?)));
+        rest = &after[end + CLOSE.len()..];
+    }
+    result.push((rest, None));
+    Ok(result)
+}
+
+/// Returns all references used in a template
+///
+/// # Errors
+///
+/// Returns an error if the template contains a malformed expression
+pub fn references(text: &str) -> Result<Vec<Reference>, WorkflowError> {
+    Ok(segments(text)?
+        .into_iter()
+        .filter_map(|(_, reference)| reference)
+        .collect())
+}
+
+/// Renders a template by substituting all expressions
+///
+/// # Errors
+///
+/// Returns an error if an expression is malformed or its value is unavailable
+pub fn render(text: &str, context: &TemplateContext<'_>) -> Result<String, WorkflowError> {
+    let mut rendered = String::with_capacity(text.len());
+    for (literal, reference) in segments(text)? {
+        rendered.push_str(literal);
+        if let Some(reference) = reference {
+            rendered.push_str(&context.lookup(&reference)?);
+        }
+    }
+    Ok(rendered)
+}
+
+/// Evaluates a step condition
+///
+/// A condition is either a comparison (`a == b`, `a != b`) or a single value,
+/// which is true unless it is empty, `false`, `0` or `no`.
+///
+/// # Errors
+///
+/// Returns an error if the condition cannot be rendered
+pub fn evaluate(condition: &str, context: &TemplateContext<'_>) -> Result<bool, WorkflowError> {
+    for (operator, expect_equal) in [("==", true), ("!=", false)] {
+        if let Some((left, right)) = condition.split_once(operator) {
+            let left = render(left, context)?;
+            let right = render(right, context)?;
+            return Ok((unquote(&left) == unquote(&right)) == expect_equal);
+        }
+    }
+
+    let value = render(condition, context)?;
+    Ok(!matches!(
+        unquote(&value).to_ascii_lowercase().as_str(),
+        "" | "false" | "0" | "no"
+    ))
+}
+
+/// Trims whitespace and surrounding quotes from an operand
+fn unquote(value: &str) -> &str {
+    let value = value.trim();
+    value
+        .strip_prefix('"')
+        .and_then(|v| v.strip_suffix('"'))
+        .or_else(|| value.strip_prefix('\'').and_then(|v| v.strip_suffix('\'')))
+        .unwrap_or(value)
+}
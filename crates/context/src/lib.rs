@@ -17,7 +17,7 @@ pub mod adapter;
 // Public re-exports
 pub use manager::{ContextManager, ContextManagerConfig};
 pub use tracker::{ContextTracker, ContextTrackerFactory, ContextTrackerConfig};
-pub use state::{State as ContextState, StateSnapshot as ContextSnapshot};
+pub use state::{SnapshotDiff, State as ContextState, StateSnapshot as ContextSnapshot};
 pub use adapter::{ContextAdapter, ContextAdapterConfig, ContextStatus};
 
 /// Error types for context operations
@@ -57,6 +57,29 @@ impl fmt::Display for ContextError {
 // Implement std::error::Error for ContextError
 impl std::error::Error for ContextError {}
 
+impl squirrel_core::error::Coded for ContextError {
+    fn code(&self) -> squirrel_core::error::ErrorCode {
+        use squirrel_core::error::ErrorCode;
+        match self {
+            ContextError::NotInitialized(_) => ErrorCode::Unavailable,
+            ContextError::InvalidState(_) | ContextError::StateError(_) => ErrorCode::Conflict,
+            ContextError::Persistence(_) => ErrorCode::Internal,
+            ContextError::NotFound(_)
+            | ContextError::SnapshotNotFound(_)
+            | ContextError::NoRecoveryPoints(_) => ErrorCode::NotFound,
+        }
+    }
+
+    fn hint(&self) -> Option<String> {
+        match self {
+            ContextError::NoRecoveryPoints(_) => {
+                Some("Create a snapshot of the context before restoring it".to_string())
+            }
+            _ => self.code().hint().map(str::to_string),
+        }
+    }
+}
+
 /// Result type alias
 pub type Result<T> = std::result::Result<T, ContextError>;
 
@@ -196,6 +196,52 @@ pub struct StateSnapshot {
     pub data: HashMap<String, String>,
 }
 
+impl StateSnapshot {
+    /// Compute the changes that turn this snapshot's data into `newer`'s
+    #[must_use]
+    pub fn diff(&self, newer: &Self) -> SnapshotDiff {
+        let mut diff = SnapshotDiff::default();
+        for (key, value) in &newer.data {
+            match self.data.get(key) {
+                None => {
+                    diff.added.insert(key.clone(), value.clone());
+                }
+                Some(old) if old != value => {
+                    diff.changed.insert(key.clone(), (old.clone(), value.clone()));
+                }
+                Some(_) => {}
+            }
+        }
+        diff.removed = self
+            .data
+            .keys()
+            .filter(|key| !newer.data.contains_key(*key))
+            .cloned()
+            .collect();
+        diff.removed.sort();
+        diff
+    }
+}
+
+/// Changes between two snapshots of a state
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SnapshotDiff {
+    /// Keys only in the newer snapshot, with their values
+    pub added: HashMap<String, String>,
+    /// Keys only in the older snapshot, sorted
+    pub removed: Vec<String>,
+    /// Keys whose value changed, with the old and new value
+    pub changed: HashMap<String, (String, String)>,
+}
+
+impl SnapshotDiff {
+    /// Whether the snapshots hold the same data
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.added.is_empty() && self.removed.is_empty() && self.changed.is_empty()
+    }
+}
+
 /// State storage trait for persistence operations
 pub trait StateStorage {
     /// Load a state from storage
@@ -913,4 +913,20 @@ fn create_test_state(id: &str) -> ContextState {
         metadata: HashMap::new(),
         synchronized: false,
     }
-} 
\ No newline at end of file
+} 
+#[test]
+fn test_snapshot_diff() {
+    let mut state = create_test_state("diff-state");
+    state.set("key2".to_string(), "value2".to_string());
+    let before = state.create_snapshot();
+
+    state.set("key1".to_string(), "changed".to_string());
+    state.remove("key2");
+    state.set("key3".to_string(), "value3".to_string());
+    let diff = before.diff(&state.create_snapshot());
+
+    assert_eq!(diff.added.get("key3").map(String::as_str), Some("value3"));
+    assert_eq!(diff.removed, vec!["key2".to_string()]);
+    assert_eq!(diff.changed.get("key1"), Some(&("value1".to_string(), "changed".to_string())));
+    assert!(before.diff(&before).is_empty());
+}
@@ -0,0 +1,52 @@
+# Messages of the Squirrel CLI and API, in German
+
+## CLI
+
+cli-command-failed = Befehl fehlgeschlagen: { $message }
+cli-error-caused-by = Verursacht durch: { $message }
+cli-error-hint = Hinweis: { $hint }
+cli-invalid-locale = Unbekannte Sprache „{ $locale }“, verwende { $fallback }
+cli-plugin-catalogs-failed = Die Meldungskataloge des Plugins { $plugin } konnten nicht geladen werden: { $message }
+
+## Catalog tooling
+
+i18n-extracted =
+    { $count ->
+        [one] Ein Meldungsschlüssel der Domäne { $domain } extrahiert
+       *[other] { $count } Meldungsschlüssel der Domäne { $domain } extrahiert
+    }
+i18n-wrote = { $path } geschrieben
+i18n-missing-messages =
+    { $count ->
+        [0] Keine fehlenden Nachrichten
+        [one] 1 fehlende Nachricht
+       *[other] { $count } fehlende Nachrichten
+    }
+i18n-unused-messages =
+    { $count ->
+        [one] 1 nicht mehr verwendete Nachricht
+       *[other] { $count } nicht mehr verwendete Nachrichten
+    }
+
+## API error titles, by error code
+
+api-error-invalid-request = Ungültige Anfrage
+api-error-not-found = Nicht gefunden
+api-error-unauthorized = Nicht angemeldet
+api-error-forbidden = Zugriff verweigert
+api-error-conflict = Konflikt
+api-error-rate-limit-exceeded = Anfragelimit überschritten
+api-error-internal-error = Interner Fehler
+api-error-database-error = Datenbankfehler
+api-error-custom-error = Anfrage fehlgeschlagen
+api-error-invalid-input = Ungültige Eingabe
+api-error-unauthenticated = Nicht angemeldet
+api-error-permission-denied = Zugriff verweigert
+api-error-already-exists = Existiert bereits
+api-error-rate-limited = Zu viele Anfragen
+api-error-resource-exhausted = Ressourcenlimit erreicht
+api-error-timeout = Zeitüberschreitung
+api-error-unavailable = Nicht verfügbar
+api-error-unsupported = Nicht unterstützt
+api-error-execution-failed = Ausführung fehlgeschlagen
+api-error-internal = Interner Fehler
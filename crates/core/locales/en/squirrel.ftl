@@ -0,0 +1,55 @@
+# Messages of the Squirrel CLI and API, in English
+#
+# English is the fallback of every other locale, so it must have every key.
+# `squirrel i18n check` lists the keys other catalogs are missing.
+
+## CLI
+
+cli-command-failed = Command failed: { $message }
+cli-error-caused-by = Caused by: { $message }
+cli-error-hint = Hint: { $hint }
+cli-invalid-locale = Unknown locale "{ $locale }", using { $fallback }
+cli-plugin-catalogs-failed = Failed to load the message catalogs of plugin { $plugin }: { $message }
+
+## Catalog tooling
+
+i18n-extracted =
+    { $count ->
+        [one] Extracted one message key of domain { $domain }
+       *[other] Extracted { $count } message keys of domain { $domain }
+    }
+i18n-wrote = Wrote { $path }
+i18n-missing-messages =
+    { $count ->
+        [0] No missing messages
+        [one] 1 missing message
+       *[other] { $count } missing messages
+    }
+i18n-unused-messages =
+    { $count ->
+        [one] 1 message no longer used
+       *[other] { $count } messages no longer used
+    }
+
+## API error titles, by error code
+
+api-error-invalid-request = Invalid request
+api-error-not-found = Not found
+api-error-unauthorized = Unauthorized
+api-error-forbidden = Forbidden
+api-error-conflict = Conflict
+api-error-rate-limit-exceeded = Rate limit exceeded
+api-error-internal-error = Internal error
+api-error-database-error = Database error
+api-error-custom-error = Request failed
+api-error-invalid-input = Invalid input
+api-error-unauthenticated = Not authenticated
+api-error-permission-denied = Permission denied
+api-error-already-exists = Already exists
+api-error-rate-limited = Too many requests
+api-error-resource-exhausted = Resource limit reached
+api-error-timeout = Timed out
+api-error-unavailable = Unavailable
+api-error-unsupported = Not supported
+api-error-execution-failed = Execution failed
+api-error-internal = Internal error
@@ -0,0 +1,803 @@
+//! Content-addressed blob store
+//!
+//! Stored files are kept once per distinct content, as blobs named by their
+//! SHA-256 under `objects/<first two hex digits>/<digest>`. A manifest
+//! (`refs.json`) maps names, such as the virtual path a file was uploaded
+//! to, onto blobs; the number of names pointing at a blob is its reference
+//! count. A name can be materialized at a host path as a hard link to its
+//! blob, so identical datasets uploaded by different jobs take the space of
+//! one. Blobs are made read-only, and links are replaced rather than written
+//! through, so changing one linked file never changes another.
+//!
+//! Removing a name does not delete its blob; [`BlobStore::gc`] deletes blobs
+//! nothing refers to any more, after dropping names whose linked file was
+//! deleted or replaced.
+//!
+//! A blob's name is the checksum it was stored with, so [`BlobStore::verify`]
+//! finds a blob whose content changed on disk by hashing it again. Corrupt
+//! blobs are moved to `quarantine/`, and [`BlobStore::restore`] copies intact
+//! blobs back from a backup of the store.
+
+use std::collections::{BTreeMap, BTreeSet};
+use std::fs::{self, File};
+use std::io::{self, Read};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime};
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use thiserror::Error;
+use uuid::Uuid;
+
+/// Name of the manifest file in the store directory
+pub const MANIFEST_FILE: &str = "refs.json";
+
+/// Name of the blob directory in the store directory
+pub const OBJECTS_DIR: &str = "objects";
+
+/// Name of the directory corrupt blobs are moved to
+pub const QUARANTINE_DIR: &str = "quarantine";
+
+/// Name of the file holding the digest the latest sampled check ended at
+const VERIFY_CURSOR_FILE: &str = "verify.cursor";
+
+/// Size of the buffer files are hashed with
+const HASH_BUFFER_SIZE: usize = 1024 * 1024;
+
+/// Errors of the blob store
+#[derive(Debug, Error)]
+pub enum BlobError {
+    /// The digest is not a hex SHA-256
+    #[error("Invalid SHA-256 digest '{0}'")]
+    InvalidDigest(String),
+    /// No blob with this digest is stored
+    #[error("Unknown blob '{0}'")]
+    UnknownBlob(String),
+    /// The manifest cannot be read or written
+    #[error("Invalid blob manifest {path}: {source}")]
+    Manifest {
+        /// Path of the manifest
+        path: PathBuf,
+        /// The serialization error
+        source: serde_json::Error,
+    },
+    /// Error from the underlying filesystem
+    #[error("I/O error on {path}: {source}")]
+    Io {
+        /// The host path
+        path: PathBuf,
+        /// The filesystem error
+        source: io::Error,
+    },
+}
+
+/// Wraps a filesystem error with the path it occurred on
+fn io_error(path: &Path, source: io::Error) -> BlobError {
+    BlobError::Io {
+        path: path.to_path_buf(),
+        source,
+    }
+}
+
+/// SHA-256 of a file, as lowercase hex
+///
+/// # Errors
+///
+/// Fails if the file cannot be read.
+pub fn hash_file(path: &Path) -> Result<String, BlobError> {
+    let mut file = File::open(path).map_err(|source| io_error(path, source))?;
+    let mut hasher = Sha256::new();
+    let mut buffer = vec![0u8; HASH_BUFFER_SIZE];
+    loop {
+        let read = file.read(&mut buffer).map_err(|source| io_error(path, source))?;
+        if read == 0 {
+            break;
+        }
+        hasher.update(&buffer[..read]);
+    }
+    Ok(hex::encode(hasher.finalize()))
+}
+
+/// Normalize a hex SHA-256 digest
+///
+/// # Errors
+///
+/// Fails if `digest` is not 64 hex digits.
+pub fn parse_digest(digest: &str) -> Result<String, BlobError> {
+    let normalized = digest.trim().to_ascii_lowercase();
+    if normalized.len() == 64 && normalized.bytes().all(|b| b.is_ascii_hexdigit()) {
+        Ok(normalized)
+    } else {
+        Err(BlobError::InvalidDigest(digest.to_string()))
+    }
+}
+
+/// A name pointing at a blob
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct BlobRef {
+    /// SHA-256 of the blob
+    pub digest: String,
+    /// Size of the blob in bytes
+    pub size: u64,
+    /// Host path the blob is linked at, if materialized
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub link: Option<PathBuf>,
+    /// When the name was pointed at the blob
+    pub created_at: DateTime<Utc>,
+}
+
+/// Contents of the manifest file
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct Manifest {
+    /// References by name
+    #[serde(default)]
+    refs: BTreeMap<String, BlobRef>,
+}
+
+/// Space used by a store
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct StorageUsage {
+    /// Number of stored blobs
+    pub blobs: u64,
+    /// Bytes taken by the stored blobs
+    pub stored_bytes: u64,
+    /// Number of names
+    pub references: u64,
+    /// Bytes the named files would take without deduplication
+    pub referenced_bytes: u64,
+    /// Bytes of blobs no name refers to
+    pub unreferenced_bytes: u64,
+}
+
+impl StorageUsage {
+    /// Bytes saved by storing identical content once
+    #[must_use]
+    pub fn saved_bytes(&self) -> u64 {
+        self.referenced_bytes
+            .saturating_sub(self.stored_bytes.saturating_sub(self.unreferenced_bytes))
+    }
+}
+
+/// Outcome of a garbage collection
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct GcReport {
+    /// Names dropped because their linked file was deleted or replaced
+    pub pruned_refs: Vec<String>,
+    /// Digests of the deleted blobs
+    pub removed_blobs: Vec<String>,
+    /// Bytes freed by deleting blobs
+    pub freed_bytes: u64,
+    /// Whether nothing was actually changed
+    pub dry_run: bool,
+}
+
+/// Outcome of an integrity check
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct IntegrityReport {
+    /// Number of stored blobs
+    pub blobs: u64,
+    /// Number of blobs hashed again
+    pub checked_blobs: u64,
+    /// Bytes of the blobs hashed again
+    pub checked_bytes: u64,
+    /// Digests of the blobs whose content no longer matched, which were
+    /// moved to quarantine
+    pub corrupt: Vec<String>,
+    /// Digests names point at that are not stored, such as blobs
+    /// quarantined by an earlier check
+    pub missing: Vec<String>,
+}
+
+impl IntegrityReport {
+    /// Whether no blob was found corrupt or missing
+    #[must_use]
+    pub fn is_intact(&self) -> bool {
+        self.corrupt.is_empty() && self.missing.is_empty()
+    }
+}
+
+/// Outcome of restoring blobs from a backup
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RestoreReport {
+    /// Digests of the restored blobs
+    pub restored: Vec<String>,
+    /// Names whose linked file was linked to the restored blob again
+    pub relinked: Vec<String>,
+    /// Digests of the blobs the backup has no intact copy of
+    pub unrecoverable: Vec<String>,
+}
+
+/// A content-addressed store in a directory
+///
+/// The manifest is re-read for every operation, so a server and the
+/// `squirrel blob` command can share a store.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BlobStore {
+    /// Directory of the store
+    root: PathBuf,
+}
+
+impl BlobStore {
+    /// Open the store in `root`, creating it if needed
+    ///
+    /// # Errors
+    ///
+    /// Fails if the store directory cannot be created.
+    pub fn open(root: impl Into<PathBuf>) -> Result<Self, BlobError> {
+        let root = root.into();
+        let objects = root.join(OBJECTS_DIR);
+        fs::create_dir_all(&objects).map_err(|source| io_error(&objects, source))?;
+        Ok(Self { root })
+    }
+
+    /// The default store directory, `~/.squirrel/blobs`
+    #[must_use]
+    pub fn default_path() -> Option<PathBuf> {
+        std::env::var_os("HOME").map(|home| Path::new(&home).join(".squirrel").join("blobs"))
+    }
+
+    /// Directory of the store
+    #[must_use]
+    pub fn root(&self) -> &Path {
+        &self.root
+    }
+
+    /// Host path of the blob with `digest`
+    ///
+    /// # Errors
+    ///
+    /// Fails if `digest` is not a hex SHA-256.
+    pub fn blob_path(&self, digest: &str) -> Result<PathBuf, BlobError> {
+        let digest = parse_digest(digest)?;
+        Ok(self.root.join(OBJECTS_DIR).join(&digest[..2]).join(digest))
+    }
+
+    /// Whether a blob with `digest` is stored
+    #[must_use]
+    pub fn contains(&self, digest: &str) -> bool {
+        self.blob_path(digest).is_ok_and(|path| path.is_file())
+    }
+
+    /// Move the file at `source` into the store, hashing it first
+    ///
+    /// Returns the digest of the blob.
+    ///
+    /// # Errors
+    ///
+    /// Fails if the file cannot be read or moved into the store.
+    pub fn add(&self, source: &Path) -> Result<String, BlobError> {
+        let digest = hash_file(source)?;
+        self.add_file(source, &digest)?;
+        Ok(digest)
+    }
+
+    /// Move the file at `source`, whose SHA-256 is `digest`, into the store
+    ///
+    /// If the content is already stored, `source` is removed instead.
+    /// Returns whether a new blob was stored. The caller is responsible for
+    /// `digest` being the hash of the file.
+    ///
+    /// # Errors
+    ///
+    /// Fails if `digest` is malformed or the file cannot be moved.
+    pub fn add_file(&self, source: &Path, digest: &str) -> Result<bool, BlobError> {
+        let blob = self.blob_path(digest)?;
+        if blob.is_file() {
+            fs::remove_file(source).map_err(|e| io_error(source, e))?;
+            return Ok(false);
+        }
+        let dir = blob.parent().unwrap_or(&self.root);
+        fs::create_dir_all(dir).map_err(|e| io_error(dir, e))?;
+
+        // Stage next to the blob so the final rename is atomic
+        let staged = dir.join(format!(".{}.tmp", Uuid::new_v4()));
+        if fs::rename(source, &staged).is_err() {
+            // The source may be on another filesystem
+            fs::copy(source, &staged).map_err(|e| io_error(&staged, e))?;
+            fs::remove_file(source).map_err(|e| io_error(source, e))?;
+        }
+        let mut permissions = fs::metadata(&staged).map_err(|e| io_error(&staged, e))?.permissions();
+        permissions.set_readonly(true);
+        fs::set_permissions(&staged, permissions).map_err(|e| io_error(&staged, e))?;
+        fs::rename(&staged, &blob).map_err(|e| io_error(&blob, e))?;
+        Ok(true)
+    }
+
+    /// Point `name` at the blob with `digest`, replacing what it pointed at
+    ///
+    /// If `target` is given, the blob is linked there, replacing any file at
+    /// that path. The blob is copied instead if it cannot be hard linked,
+    /// for example across filesystems.
+    ///
+    /// # Errors
+    ///
+    /// Fails if the blob is not stored, the link cannot be created or the
+    /// manifest cannot be updated.
+    pub fn link(&self, name: &str, digest: &str, target: Option<&Path>) -> Result<BlobRef, BlobError> {
+        let digest = parse_digest(digest)?;
+        let blob = self.blob_path(&digest)?;
+        let metadata = fs::metadata(&blob).map_err(|_| BlobError::UnknownBlob(digest.clone()))?;
+
+        let link = match target {
+            Some(target) => Some(materialize(&blob, target)?),
+            None => None,
+        };
+        let blob_ref = BlobRef {
+            digest,
+            size: metadata.len(),
+            link,
+            created_at: Utc::now(),
+        };
+        let mut manifest = self.load()?;
+        manifest.refs.insert(name.to_string(), blob_ref.clone());
+        self.save(&manifest)?;
+        Ok(blob_ref)
+    }
+
+    /// Remove `name`, returning what it pointed at
+    ///
+    /// The blob stays until the next garbage collection.
+    ///
+    /// # Errors
+    ///
+    /// Fails if the manifest cannot be updated.
+    pub fn unlink(&self, name: &str) -> Result<Option<BlobRef>, BlobError> {
+        let mut manifest = self.load()?;
+        let removed = manifest.refs.remove(name);
+        if removed.is_some() {
+            self.save(&manifest)?;
+        }
+        Ok(removed)
+    }
+
+    /// What `name` points at
+    ///
+    /// # Errors
+    ///
+    /// Fails if the manifest cannot be read.
+    pub fn get(&self, name: &str) -> Result<Option<BlobRef>, BlobError> {
+        Ok(self.load()?.refs.remove(name))
+    }
+
+    /// All names and what they point at
+    ///
+    /// # Errors
+    ///
+    /// Fails if the manifest cannot be read.
+    pub fn refs(&self) -> Result<BTreeMap<String, BlobRef>, BlobError> {
+        Ok(self.load()?.refs)
+    }
+
+    /// Number of names pointing at the blob with `digest`
+    ///
+    /// # Errors
+    ///
+    /// Fails if the manifest cannot be read.
+    pub fn refcount(&self, digest: &str) -> Result<usize, BlobError> {
+        let digest = parse_digest(digest)?;
+        Ok(self.load()?.refs.values().filter(|r| r.digest == digest).count())
+    }
+
+    /// Space used by the store
+    ///
+    /// # Errors
+    ///
+    /// Fails if the manifest or blob directory cannot be read.
+    pub fn usage(&self) -> Result<StorageUsage, BlobError> {
+        let manifest = self.load()?;
+        let referenced: BTreeMap<&str, u64> = manifest
+            .refs
+            .values()
+            .map(|r| (r.digest.as_str(), r.size))
+            .collect();
+        let mut usage = StorageUsage {
+            references: manifest.refs.len() as u64,
+            referenced_bytes: manifest.refs.values().map(|r| r.size).sum(),
+            ..StorageUsage::default()
+        };
+        for (digest, size, _) in self.blobs()? {
+            usage.blobs += 1;
+            usage.stored_bytes += size;
+            if !referenced.contains_key(digest.as_str()) {
+                usage.unreferenced_bytes += size;
+            }
+        }
+        Ok(usage)
+    }
+
+    /// Delete blobs nothing refers to
+    ///
+    /// Names whose linked file was deleted or replaced by another file are
+    /// dropped first. Blobs modified less than `min_age` ago are kept, so a
+    /// blob added by a concurrent upload is not deleted before it is linked.
+    /// Quarantined blobs nothing refers to are deleted whatever their age.
+    /// With `dry_run`, only reports what would be deleted.
+    ///
+    /// # Errors
+    ///
+    /// Fails if the manifest or blob directory cannot be read, or a blob
+    /// cannot be deleted.
+    pub fn gc(&self, min_age: Duration, dry_run: bool) -> Result<GcReport, BlobError> {
+        let mut manifest = self.load()?;
+        let mut report = GcReport {
+            dry_run,
+            ..GcReport::default()
+        };
+
+        for (name, blob_ref) in &manifest.refs {
+            let Some(link) = &blob_ref.link else { continue };
+            let blob = self.blob_path(&blob_ref.digest)?;
+            // A file linked to a quarantined blob waits for the blob's restore
+            if !same_file(link, &blob) && !same_file(link, &self.quarantine_path(&blob_ref.digest)) {
+                report.pruned_refs.push(name.clone());
+            }
+        }
+        for name in &report.pruned_refs {
+            manifest.refs.remove(name);
+        }
+        if !dry_run && !report.pruned_refs.is_empty() {
+            self.save(&manifest)?;
+        }
+
+        let now = SystemTime::now();
+        for (digest, size, path) in self.quarantined()? {
+            if manifest.refs.values().any(|r| r.digest == digest) {
+                continue;
+            }
+            if !dry_run {
+                fs::remove_file(&path).map_err(|e| io_error(&path, e))?;
+            }
+            report.freed_bytes += size;
+            report.removed_blobs.push(digest);
+        }
+        for (digest, size, path) in self.blobs()? {
+            if manifest.refs.values().any(|r| r.digest == digest) {
+                continue;
+            }
+            let modified = fs::metadata(&path).and_then(|m| m.modified()).unwrap_or(now);
+            if now.duration_since(modified).unwrap_or_default() < min_age {
+                continue;
+            }
+            if !dry_run {
+                fs::remove_file(&path).map_err(|e| io_error(&path, e))?;
+            }
+            report.freed_bytes += size;
+            report.removed_blobs.push(digest);
+        }
+        Ok(report)
+    }
+
+    /// Hash stored blobs again and check that the blob of every name is stored
+    ///
+    /// With a `sample` size, only that many blobs are hashed, starting after
+    /// the blob the previous sampled check ended at, so successive checks
+    /// take turns over the whole store. A blob that cannot be read or whose
+    /// content no longer matches its digest is moved to the quarantine
+    /// directory: new uploads of the same content are then stored again
+    /// instead of being linked to it, and its names are kept for
+    /// [`BlobStore::restore`].
+    ///
+    /// # Errors
+    ///
+    /// Fails if the manifest or blob directory cannot be read, or a corrupt
+    /// blob cannot be moved.
+    pub fn verify(&self, sample: Option<usize>) -> Result<IntegrityReport, BlobError> {
+        let manifest = self.load()?;
+        let blobs = self.blobs()?;
+        let stored: BTreeSet<&str> = blobs.iter().map(|(digest, _, _)| digest.as_str()).collect();
+        let referenced: BTreeSet<&str> = manifest.refs.values().map(|r| r.digest.as_str()).collect();
+        let mut report = IntegrityReport {
+            blobs: blobs.len() as u64,
+            missing: referenced.difference(&stored).map(ToString::to_string).collect(),
+            ..IntegrityReport::default()
+        };
+
+        let checked: Vec<&(String, u64, PathBuf)> = match sample {
+            None => blobs.iter().collect(),
+            Some(size) => {
+                let start = self
+                    .cursor()?
+                    .map_or(0, |cursor| blobs.partition_point(|(digest, _, _)| *digest <= cursor));
+                blobs.iter().cycle().skip(start).take(size.min(blobs.len())).collect()
+            }
+        };
+        for (digest, size, path) in &checked {
+            report.checked_blobs += 1;
+            report.checked_bytes += size;
+            if hash_file(path).ok().as_ref() != Some(digest) {
+                let quarantined = self.quarantine_path(digest);
+                let dir = self.root.join(QUARANTINE_DIR);
+                fs::create_dir_all(&dir).map_err(|e| io_error(&dir, e))?;
+                fs::rename(path, &quarantined).map_err(|e| io_error(&quarantined, e))?;
+                report.corrupt.push(digest.clone());
+            }
+        }
+        if let (Some(_), Some((digest, _, _))) = (sample, checked.last()) {
+            let path = self.root.join(VERIFY_CURSOR_FILE);
+            fs::write(&path, digest).map_err(|e| io_error(&path, e))?;
+        }
+        Ok(report)
+    }
+
+    /// Copy the blobs names point at but that are not stored back from
+    /// `backup`, a copy of the store directory such as a snapshot or mirror
+    ///
+    /// A backup copy is only used if its content matches its digest. Files
+    /// still linked to the quarantined blob are linked to the restored one,
+    /// and the quarantined blob is deleted; files replaced or deleted since
+    /// are left alone.
+    ///
+    /// # Errors
+    ///
+    /// Fails if `backup` is not a store directory, the manifest cannot be
+    /// read or a restored blob cannot be stored or linked.
+    pub fn restore(&self, backup: &Path) -> Result<RestoreReport, BlobError> {
+        let objects = backup.join(OBJECTS_DIR);
+        if !objects.is_dir() {
+            return Err(io_error(&objects, io::Error::from(io::ErrorKind::NotFound)));
+        }
+        let backup = Self {
+            root: backup.to_path_buf(),
+        };
+        let manifest = self.load()?;
+        let missing: BTreeSet<&str> = manifest
+            .refs
+            .values()
+            .map(|r| r.digest.as_str())
+            .filter(|digest| !self.contains(digest))
+            .collect();
+
+        let mut report = RestoreReport::default();
+        for digest in missing {
+            let source = backup.blob_path(digest)?;
+            let staged = self.root.join(format!(".{}.tmp", Uuid::new_v4()));
+            let intact = fs::copy(&source, &staged).is_ok() && hash_file(&staged).is_ok_and(|copy| copy == digest);
+            if !intact {
+                // The copy may have failed halfway
+                let _ = fs::remove_file(&staged);
+                report.unrecoverable.push(digest.to_string());
+                continue;
+            }
+            self.add_file(&staged, digest)?;
+            report.restored.push(digest.to_string());
+
+            let blob = self.blob_path(digest)?;
+            let quarantined = self.quarantine_path(digest);
+            for (name, blob_ref) in manifest.refs.iter().filter(|(_, r)| r.digest == digest) {
+                let Some(link) = &blob_ref.link else { continue };
+                if same_file(link, &quarantined) {
+                    materialize(&blob, link)?;
+                    report.relinked.push(name.clone());
+                }
+            }
+            if quarantined.is_file() {
+                fs::remove_file(&quarantined).map_err(|e| io_error(&quarantined, e))?;
+            }
+        }
+        Ok(report)
+    }
+
+    /// Path the blob with `digest` is moved to when found corrupt
+    fn quarantine_path(&self, digest: &str) -> PathBuf {
+        self.root.join(QUARANTINE_DIR).join(digest)
+    }
+
+    /// Digest the latest sampled check ended at
+    fn cursor(&self) -> Result<Option<String>, BlobError> {
+        let path = self.root.join(VERIFY_CURSOR_FILE);
+        match fs::read_to_string(&path) {
+            Ok(digest) => Ok(parse_digest(&digest).ok()),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(None),
+            Err(e) => Err(io_error(&path, e)),
+        }
+    }
+
+    /// Digest, size and path of every quarantined blob
+    fn quarantined(&self) -> Result<Vec<(String, u64, PathBuf)>, BlobError> {
+        let dir = self.root.join(QUARANTINE_DIR);
+        let entries = match fs::read_dir(&dir) {
+            Ok(entries) => entries,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(Vec::new()),
+            Err(e) => return Err(io_error(&dir, e)),
+        };
+        let mut blobs = Vec::new();
+        for entry in entries {
+            let entry = entry.map_err(|e| io_error(&dir, e))?;
+            let name = entry.file_name().to_string_lossy().into_owned();
+            let Ok(digest) = parse_digest(&name) else { continue };
+            let size = entry.metadata().map_err(|e| io_error(&entry.path(), e))?.len();
+            blobs.push((digest, size, entry.path()));
+        }
+        blobs.sort();
+        Ok(blobs)
+    }
+
+    /// Digest, size and path of every stored blob
+    fn blobs(&self) -> Result<Vec<(String, u64, PathBuf)>, BlobError> {
+        let objects = self.root.join(OBJECTS_DIR);
+        let mut blobs = Vec::new();
+        for shard in fs::read_dir(&objects).map_err(|e| io_error(&objects, e))? {
+            let shard = shard.map_err(|e| io_error(&objects, e))?.path();
+            if !shard.is_dir() {
+                continue;
+            }
+            for entry in fs::read_dir(&shard).map_err(|e| io_error(&shard, e))? {
+                let entry = entry.map_err(|e| io_error(&shard, e))?;
+                let name = entry.file_name().to_string_lossy().into_owned();
+                let Ok(digest) = parse_digest(&name) else { continue };
+                let size = entry.metadata().map_err(|e| io_error(&entry.path(), e))?.len();
+                blobs.push((digest, size, entry.path()));
+            }
+        }
+        blobs.sort();
+        Ok(blobs)
+    }
+
+    /// Path of the manifest file
+    fn manifest_path(&self) -> PathBuf {
+        self.root.join(MANIFEST_FILE)
+    }
+
+    /// Read the manifest; a missing manifest is empty
+    fn load(&self) -> Result<Manifest, BlobError> {
+        let path = self.manifest_path();
+        match fs::read(&path) {
+            Ok(data) => serde_json::from_slice(&data).map_err(|source| BlobError::Manifest { path, source }),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(Manifest::default()),
+            Err(e) => Err(io_error(&path, e)),
+        }
+    }
+
+    /// Write the manifest atomically
+    fn save(&self, manifest: &Manifest) -> Result<(), BlobError> {
+        let path = self.manifest_path();
+        let data = serde_json::to_vec_pretty(manifest).map_err(|source| BlobError::Manifest {
+            path: path.clone(),
+            source,
+        })?;
+        let staged = self.root.join(format!(".{}.{}.tmp", MANIFEST_FILE, Uuid::new_v4()));
+        fs::write(&staged, data).map_err(|e| io_error(&staged, e))?;
+        fs::rename(&staged, &path).map_err(|e| io_error(&path, e))
+    }
+}
+
+/// Link `blob` at `target`, replacing any file there, and return the
+/// absolute path of the link
+fn materialize(blob: &Path, target: &Path) -> Result<PathBuf, BlobError> {
+    let dir = match target.parent() {
+        Some(parent) if !parent.as_os_str().is_empty() => parent,
+        _ => Path::new("."),
+    };
+    fs::create_dir_all(dir).map_err(|e| io_error(dir, e))?;
+    let staged = dir.join(format!(".{}.tmp", Uuid::new_v4()));
+    if fs::hard_link(blob, &staged).is_err() {
+        fs::copy(blob, &staged).map_err(|e| io_error(&staged, e))?;
+    }
+    fs::rename(&staged, target).map_err(|e| io_error(target, e))?;
+    target.canonicalize().map_err(|e| io_error(target, e))
+}
+
+/// Whether `link` is still a link to `blob`
+#[cfg(unix)]
+fn same_file(link: &Path, blob: &Path) -> bool {
+    use std::os::unix::fs::MetadataExt;
+    match (fs::metadata(link), fs::metadata(blob)) {
+        (Ok(link), Ok(blob)) => link.dev() == blob.dev() && link.ino() == blob.ino(),
+        _ => false,
+    }
+}
+
+/// Whether `link` is still a copy of `blob`
+#[cfg(not(unix))]
+fn same_file(link: &Path, blob: &Path) -> bool {
+    match (fs::metadata(link), fs::metadata(blob)) {
+        (Ok(link), Ok(blob)) => link.len() == blob.len(),
+        _ => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn write(path: &Path, contents: &str) -> PathBuf {
+        fs::write(path, contents).unwrap();
+        path.to_path_buf()
+    }
+
+    #[test]
+    fn test_identical_content_is_stored_once() {
+        let dir = tempfile::tempdir().unwrap();
+        let store = BlobStore::open(dir.path().join("blobs")).unwrap();
+        let workspace = dir.path().join("workspace");
+
+        let first = store.add(&write(&dir.path().join("a"), "ACGT")).unwrap();
+        let second = store.add(&write(&dir.path().join("b"), "ACGT")).unwrap();
+        assert_eq!(first, second);
+        assert!(!dir.path().join("b").exists());
+
+        store.link("workspace://job1/reads.fa", &first, Some(&workspace.join("job1/reads.fa"))).unwrap();
+        store.link("workspace://job2/reads.fa", &first, Some(&workspace.join("job2/reads.fa"))).unwrap();
+        assert_eq!(store.refcount(&first).unwrap(), 2);
+        assert_eq!(fs::read_to_string(workspace.join("job2/reads.fa")).unwrap(), "ACGT");
+
+        let usage = store.usage().unwrap();
+        assert_eq!((usage.blobs, usage.stored_bytes, usage.references), (1, 4, 2));
+        assert_eq!(usage.saved_bytes(), 4);
+
+        assert!(matches!(
+            store.link("x", &"0".repeat(64), None),
+            Err(BlobError::UnknownBlob(_))
+        ));
+        assert!(matches!(store.blob_path("../x"), Err(BlobError::InvalidDigest(_))));
+    }
+
+    #[test]
+    fn test_gc_removes_unreferenced_blobs() {
+        let dir = tempfile::tempdir().unwrap();
+        let store = BlobStore::open(dir.path().join("blobs")).unwrap();
+        let kept = store.add(&write(&dir.path().join("a"), "kept")).unwrap();
+        let dropped = store.add(&write(&dir.path().join("b"), "dropped")).unwrap();
+        let orphan = store.add(&write(&dir.path().join("c"), "orphan")).unwrap();
+        store.link("kept", &kept, None).unwrap();
+        let link = dir.path().join("dropped.txt");
+        store.link("dropped", &dropped, Some(&link)).unwrap();
+        fs::remove_file(&link).unwrap();
+
+        let recent = store.gc(Duration::from_hours(1), false).unwrap();
+        assert_eq!(recent.pruned_refs, vec!["dropped"]);
+        assert!(recent.removed_blobs.is_empty());
+
+        let report = store.gc(Duration::ZERO, true).unwrap();
+        assert_eq!(report.removed_blobs.len(), 2);
+        assert!(store.contains(&orphan));
+
+        let report = store.gc(Duration::ZERO, false).unwrap();
+        assert_eq!(report.freed_bytes, 13);
+        assert!(store.contains(&kept));
+        assert!(!store.contains(&dropped));
+        assert!(!store.contains(&orphan));
+    }
+
+    #[test]
+    fn test_corrupt_blobs_are_quarantined_and_restored() {
+        let dir = tempfile::tempdir().unwrap();
+        let store = BlobStore::open(dir.path().join("blobs")).unwrap();
+        let backup = BlobStore::open(dir.path().join("backup")).unwrap();
+        let reads = store.add(&write(&dir.path().join("a"), "ACGT")).unwrap();
+        backup.add(&write(&dir.path().join("b"), "ACGT")).unwrap();
+        let lost = store.add(&write(&dir.path().join("c"), "lost")).unwrap();
+        let link = dir.path().join("workspace/reads.fa");
+        store.link("workspace://reads.fa", &reads, Some(&link)).unwrap();
+        store.link("workspace://lost.txt", &lost, None).unwrap();
+
+        // Flip bits behind the store's back
+        let blob = store.blob_path(&reads).unwrap();
+        let mut permissions = fs::metadata(&blob).unwrap().permissions();
+        #[allow(clippy::permissions_set_readonly_false)]
+        permissions.set_readonly(false);
+        fs::set_permissions(&blob, permissions).unwrap();
+        fs::write(&blob, "ACGA").unwrap();
+        fs::remove_file(store.blob_path(&lost).unwrap()).unwrap();
+
+        let sampled = store.verify(Some(1)).unwrap();
+        assert_eq!((sampled.blobs, sampled.checked_blobs), (1, 1));
+        assert_eq!(sampled.corrupt, vec![reads.clone()]);
+        assert_eq!(sampled.missing, vec![lost.clone()]);
+        assert!(!store.contains(&reads));
+        // The quarantined blob's name survives garbage collection
+        store.gc(Duration::ZERO, false).unwrap();
+        let report = store.verify(None).unwrap();
+        assert!(report.corrupt.is_empty());
+        assert_eq!(report.missing.len(), 2);
+
+        let restored = store.restore(backup.root()).unwrap();
+        assert_eq!(restored.restored, vec![reads.clone()]);
+        assert_eq!(restored.relinked, vec!["workspace://reads.fa"]);
+        assert_eq!(restored.unrecoverable, vec![lost]);
+        assert_eq!(fs::read_to_string(&link).unwrap(), "ACGT");
+        assert!(same_file(&link, &store.blob_path(&reads).unwrap()));
+        assert!(store.restore(&dir.path().join("nowhere")).is_err());
+    }
+}
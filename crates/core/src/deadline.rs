@@ -0,0 +1,186 @@
+//! Deadlines bounding the work done for a request
+//!
+//! A [`Deadline`] is the instant by which a request must be answered. It is
+//! set once where the request enters, e.g. from the web server's request
+//! timeout or the CLI's `--timeout`, and handed down with the request: in a
+//! command's extensions, in a tool's context, and across processes as the
+//! remaining budget in milliseconds. Each layer checks [`Deadline::remaining`]
+//! before starting work and bounds what it awaits with [`Deadline::run`],
+//! which cancels the work by dropping it once the deadline passes.
+//!
+//! [`Deadline::run`] also makes the deadline the [current](Deadline::current)
+//! one while the work runs, so code without a context to read it from, such
+//! as a database call deep inside a handler, still finds it. Spawned tasks do
+//! not inherit it.
+
+use std::fmt;
+use std::future::Future;
+use std::time::Duration;
+
+use tokio::time::Instant;
+
+use crate::error::{Coded, ErrorCode};
+
+tokio::task_local! {
+    /// Deadline of the work running on the current task
+    static CURRENT: Deadline;
+}
+
+/// The instant by which a request must be answered
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Deadline {
+    /// When the budget runs out
+    at: Instant,
+}
+
+impl Deadline {
+    /// A deadline `budget` from now
+    #[must_use]
+    pub fn after(budget: Duration) -> Self {
+        Self {
+            at: Instant::now() + budget,
+        }
+    }
+
+    /// A deadline at `at`
+    #[must_use]
+    pub fn at(at: Instant) -> Self {
+        Self { at }
+    }
+
+    /// A deadline `millis` milliseconds from now, as sent by a peer
+    #[must_use]
+    pub fn from_remaining_millis(millis: u64) -> Self {
+        Self::after(Duration::from_millis(millis))
+    }
+
+    /// The deadline of the work running on the current task, if any
+    #[must_use]
+    pub fn current() -> Option<Self> {
+        CURRENT.try_with(|deadline| *deadline).ok()
+    }
+
+    /// When the budget runs out
+    #[must_use]
+    pub fn instant(&self) -> Instant {
+        self.at
+    }
+
+    /// Time left, zero once the deadline passed
+    #[must_use]
+    pub fn remaining(&self) -> Duration {
+        self.at.saturating_duration_since(Instant::now())
+    }
+
+    /// Time left in whole milliseconds, to send to a peer
+    #[must_use]
+    pub fn remaining_millis(&self) -> u64 {
+        u64::try_from(self.remaining().as_millis()).unwrap_or(u64::MAX)
+    }
+
+    /// Whether the deadline passed
+    #[must_use]
+    pub fn is_expired(&self) -> bool {
+        self.remaining().is_zero()
+    }
+
+    /// This deadline, or `budget` from now if that is earlier
+    ///
+    /// Bounds a step that should take at most `budget` of what is left.
+    #[must_use]
+    pub fn within(self, budget: Duration) -> Self {
+        self.min(Self::after(budget))
+    }
+
+    /// The earlier of this deadline and `other`, if any
+    #[must_use]
+    pub fn min_with(self, other: Option<Self>) -> Self {
+        other.map_or(self, |other| self.min(other))
+    }
+
+    /// Fails if the deadline passed
+    ///
+    /// # Errors
+    ///
+    /// Returns [`DeadlineExceeded`] once the deadline passed.
+    pub fn check(&self) -> Result<(), DeadlineExceeded> {
+        if self.is_expired() {
+            Err(DeadlineExceeded)
+        } else {
+            Ok(())
+        }
+    }
+
+    /// Runs `work` until it finishes or the deadline passes, whichever is
+    /// first
+    ///
+    /// While `work` runs, [`Deadline::current`] is the earlier of this
+    /// deadline and the one current before.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`DeadlineExceeded`] if the deadline passed first; `work` is
+    /// dropped, cancelling it.
+    pub async fn run<F: Future>(self, work: F) -> Result<F::Output, DeadlineExceeded> {
+        let deadline = self.min_with(Self::current());
+        CURRENT
+            .scope(deadline, tokio::time::timeout_at(deadline.at, work))
+            .await
+            .map_err(|_| DeadlineExceeded)
+    }
+}
+
+/// Error of work that did not finish before its deadline
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DeadlineExceeded;
+
+impl fmt::Display for DeadlineExceeded {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("Deadline exceeded")
+    }
+}
+
+impl std::error::Error for DeadlineExceeded {}
+
+impl Coded for DeadlineExceeded {
+    fn code(&self) -> ErrorCode {
+        ErrorCode::Timeout
+    }
+
+    fn hint(&self) -> Option<String> {
+        Some("Retry with a longer timeout, or ask for less work at once".to_string())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_run_cancels_work_past_the_deadline() {
+        let deadline = Deadline::after(Duration::from_millis(20));
+        assert!(!deadline.is_expired());
+        assert_eq!(deadline.run(async { 7 }).await, Ok(7));
+
+        let slow = tokio::time::sleep(Duration::from_secs(5));
+        assert_eq!(deadline.run(slow).await, Err(DeadlineExceeded));
+        assert!(deadline.is_expired());
+        assert_eq!(deadline.remaining(), Duration::ZERO);
+        assert_eq!(deadline.check(), Err(DeadlineExceeded));
+    }
+
+    #[tokio::test]
+    async fn test_nested_deadlines_keep_the_earliest() {
+        assert_eq!(Deadline::current(), None);
+        let outer = Deadline::after(Duration::from_millis(100));
+        let inner = Deadline::after(Duration::from_mins(1));
+        let current = outer
+            .run(async move { inner.run(async { Deadline::current() }).await })
+            .await;
+        assert_eq!(current, Ok(Ok(Some(outer))));
+
+        let step = inner.within(Duration::from_millis(10));
+        assert!(step.remaining() <= Duration::from_millis(10));
+        assert!(Deadline::from_remaining_millis(inner.remaining_millis()) <= inner);
+    }
+}
@@ -0,0 +1,323 @@
+//! Encrypted configuration values
+//!
+//! String values written as `enc:<hex>` are encrypted with AES-256-GCM and
+//! decrypted when a configuration file is loaded, so secrets such as
+//! database passwords are not stored in plaintext. The CLI and the web
+//! server share the values and the master key, which is read from the first
+//! of these sources that provides one:
+//!
+//! 1. the `SQUIRREL_MASTER_KEY` environment variable
+//! 2. the file named by the `SQUIRREL_MASTER_KEY_FILE` environment variable
+//! 3. `master.key` in the user's configuration directory
+//! 4. the OS keychain (service `squirrel`, account `config-master-key`)
+//!
+//! Keys are 32 bytes, written as 64 hex digits.
+
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+use log::debug;
+use ring::aead::{self, Aad, LessSafeKey, Nonce, UnboundKey};
+use ring::rand::{SecureRandom, SystemRandom};
+use serde_json::Value;
+use thiserror::Error;
+
+/// Prefix marking an encrypted value
+pub const ENCRYPTED_PREFIX: &str = "enc:";
+
+/// Environment variable holding the master key
+pub const MASTER_KEY_ENV: &str = "SQUIRREL_MASTER_KEY";
+
+/// Environment variable naming a file that holds the master key
+pub const MASTER_KEY_FILE_ENV: &str = "SQUIRREL_MASTER_KEY_FILE";
+
+/// Name of the key file in the configuration directory
+pub const KEY_FILE_NAME: &str = "master.key";
+
+/// Keychain service the master key is stored under
+pub const KEYCHAIN_SERVICE: &str = "squirrel";
+
+/// Keychain account the master key is stored under
+pub const KEYCHAIN_ACCOUNT: &str = "config-master-key";
+
+/// Length of a master key in bytes
+const KEY_LEN: usize = 32;
+
+/// Error encrypting or decrypting a value, or loading the master key
+#[derive(Debug, Error)]
+#[error("{0}")]
+pub struct EncryptionError(
+    /// What went wrong
+    String,
+);
+
+impl EncryptionError {
+    /// An error described by `message`
+    fn new(message: impl Into<String>) -> Self {
+        Self(message.into())
+    }
+}
+
+/// Whether `value` is an encrypted value
+#[must_use]
+pub fn is_encrypted(value: &str) -> bool {
+    value.starts_with(ENCRYPTED_PREFIX)
+}
+
+/// Whether the environment variable `name` configures the master key
+/// rather than a configuration value
+#[must_use]
+pub fn is_key_variable(name: &str) -> bool {
+    name == MASTER_KEY_ENV || name == MASTER_KEY_FILE_ENV
+}
+
+/// Path of the key file in the user's configuration directory
+///
+/// # Errors
+/// Returns an error if the user has no configuration directory
+pub fn default_key_file() -> Result<PathBuf, EncryptionError> {
+    directories::ProjectDirs::from("", "", "squirrel")
+        .map(|dirs| dirs.config_dir().join(KEY_FILE_NAME))
+        .ok_or_else(|| EncryptionError::new("Cannot determine configuration directory"))
+}
+
+/// Key for encrypting and decrypting configuration values
+pub struct MasterKey {
+    /// The raw key
+    bytes: [u8; KEY_LEN],
+}
+
+impl std::fmt::Debug for MasterKey {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("MasterKey(..)")
+    }
+}
+
+impl MasterKey {
+    /// Generate a new random key
+    ///
+    /// # Errors
+    /// Returns an error if the system has no randomness to draw from
+    pub fn generate() -> Result<Self, EncryptionError> {
+        let mut bytes = [0u8; KEY_LEN];
+        SystemRandom::new()
+            .fill(&mut bytes)
+            .map_err(|_| EncryptionError::new("Failed to generate master key"))?;
+        Ok(Self { bytes })
+    }
+
+    /// Parse a key written as hex digits
+    ///
+    /// # Errors
+    /// Returns an error if `encoded` is not 32 bytes written as hex digits
+    pub fn parse(encoded: &str) -> Result<Self, EncryptionError> {
+        let decoded = hex::decode(encoded.trim())
+            .map_err(|_| EncryptionError::new("Master key must be written as hex digits"))?;
+        let bytes: [u8; KEY_LEN] = decoded
+            .try_into()
+            .map_err(|_| EncryptionError::new(format!("Master key must be {KEY_LEN} bytes")))?;
+        Ok(Self { bytes })
+    }
+
+    /// Read a key from a file
+    ///
+    /// # Errors
+    /// Returns an error if the file cannot be read or holds no valid key
+    pub fn from_file(path: &Path) -> Result<Self, EncryptionError> {
+        let encoded = fs::read_to_string(path)
+            .map_err(|e| EncryptionError::new(format!("Cannot read master key file {}: {e}", path.display())))?;
+        Self::parse(&encoded)
+    }
+
+    /// Load the key from the first source that provides one
+    ///
+    /// `key_file` is the key file of the configuration directory.
+    ///
+    /// # Errors
+    /// Returns an error if no source provides a key or the key is invalid
+    pub fn load(key_file: &Path) -> Result<Self, EncryptionError> {
+        if let Ok(encoded) = std::env::var(MASTER_KEY_ENV) {
+            debug!("Using master key from {MASTER_KEY_ENV}");
+            return Self::parse(&encoded);
+        }
+        if let Ok(path) = std::env::var(MASTER_KEY_FILE_ENV) {
+            debug!("Using master key file from {MASTER_KEY_FILE_ENV}: {path}");
+            return Self::from_file(Path::new(&path));
+        }
+        if key_file.exists() {
+            debug!("Using master key file {}", key_file.display());
+            return Self::from_file(key_file);
+        }
+        if let Some(encoded) = keychain_lookup() {
+            debug!("Using master key from the OS keychain");
+            return Self::parse(&encoded);
+        }
+        Err(EncryptionError::new(format!(
+            "No master key found: set {MASTER_KEY_ENV} or {MASTER_KEY_FILE_ENV}, create {} or store it in the \
+             OS keychain",
+            key_file.display()
+        )))
+    }
+
+    /// The key as hex digits, for writing to a key file
+    #[must_use]
+    pub fn encode(&self) -> String {
+        hex::encode(self.bytes)
+    }
+
+    /// The key for AES-256-GCM
+    fn aead_key(&self) -> Result<LessSafeKey, EncryptionError> {
+        let key = UnboundKey::new(&aead::AES_256_GCM, &self.bytes)
+            .map_err(|_| EncryptionError::new("Master key has the wrong length"))?;
+        Ok(LessSafeKey::new(key))
+    }
+
+    /// Encrypt `plaintext` into an `enc:` value
+    ///
+    /// # Errors
+    /// Returns an error if no nonce can be generated or encryption fails
+    pub fn encrypt(&self, plaintext: &str) -> Result<String, EncryptionError> {
+        let mut nonce = [0u8; aead::NONCE_LEN];
+        SystemRandom::new()
+            .fill(&mut nonce)
+            .map_err(|_| EncryptionError::new("Failed to generate nonce"))?;
+
+        let mut sealed = plaintext.as_bytes().to_vec();
+        self.aead_key()?
+            .seal_in_place_append_tag(Nonce::assume_unique_for_key(nonce), Aad::empty(), &mut sealed)
+            .map_err(|_| EncryptionError::new("Failed to encrypt value"))?;
+
+        let mut payload = nonce.to_vec();
+        payload.extend_from_slice(&sealed);
+        Ok(format!("{}{}", ENCRYPTED_PREFIX, hex::encode(payload)))
+    }
+
+    /// Decrypt an `enc:` value
+    ///
+    /// # Errors
+    /// Returns an error if the value is malformed or was encrypted with
+    /// another key
+    pub fn decrypt(&self, value: &str) -> Result<String, EncryptionError> {
+        let malformed = || EncryptionError::new("Malformed encrypted value");
+        let encoded = value
+            .strip_prefix(ENCRYPTED_PREFIX)
+            .ok_or_else(|| EncryptionError::new("Value is not encrypted"))?;
+        let payload = hex::decode(encoded).map_err(|_| malformed())?;
+        if payload.len() < aead::NONCE_LEN + aead::AES_256_GCM.tag_len() {
+            return Err(malformed());
+        }
+
+        let (nonce, sealed) = payload.split_at(aead::NONCE_LEN);
+        let nonce = Nonce::try_assume_unique_for_key(nonce).map_err(|_| malformed())?;
+        let mut sealed = sealed.to_vec();
+        let plaintext = self
+            .aead_key()?
+            .open_in_place(nonce, Aad::empty(), &mut sealed)
+            .map_err(|_| EncryptionError::new("Cannot decrypt value: wrong master key or corrupted value"))?;
+        String::from_utf8(plaintext.to_vec()).map_err(|_| EncryptionError::new("Decrypted value is not UTF-8"))
+    }
+}
+
+/// Look up the master key in the OS keychain
+///
+/// Uses `security` on macOS and `secret-tool` (libsecret) elsewhere; a
+/// missing tool or entry means there is no key in the keychain.
+fn keychain_lookup() -> Option<String> {
+    let output = if cfg!(target_os = "macos") {
+        Command::new("security")
+            .args(["find-generic-password", "-s", KEYCHAIN_SERVICE, "-a", KEYCHAIN_ACCOUNT, "-w"])
+            .output()
+    } else {
+        Command::new("secret-tool")
+            .args(["lookup", "service", KEYCHAIN_SERVICE, "account", KEYCHAIN_ACCOUNT])
+            .output()
+    };
+    let output = output.ok().filter(|output| output.status.success())?;
+    let key = String::from_utf8(output.stdout).ok()?.trim().to_string();
+    (!key.is_empty()).then_some(key)
+}
+
+/// Whether `value` contains an encrypted string
+fn contains_encrypted(value: &Value) -> bool {
+    match value {
+        Value::String(s) => is_encrypted(s),
+        Value::Array(values) => values.iter().any(contains_encrypted),
+        Value::Object(object) => object.values().any(contains_encrypted),
+        _ => false,
+    }
+}
+
+/// Decrypt every `enc:` string in `value` with `key`
+fn decrypt_value(value: &mut Value, key: &MasterKey) -> Result<(), EncryptionError> {
+    match value {
+        Value::String(s) if is_encrypted(s) => *s = key.decrypt(s)?,
+        Value::Array(values) => {
+            for value in values {
+                decrypt_value(value, key)?;
+            }
+        }
+        Value::Object(object) => {
+            for value in object.values_mut() {
+                decrypt_value(value, key)?;
+            }
+        }
+        _ => {}
+    }
+    Ok(())
+}
+
+/// Decrypt every `enc:` string in `value` in place
+///
+/// `load_key` is only called if the value holds an encrypted string, so
+/// plaintext configurations never need a master key.
+///
+/// # Errors
+/// Returns an error if the key cannot be loaded or a value cannot be decrypted
+pub fn decrypt_json<F>(value: &mut Value, load_key: F) -> Result<(), EncryptionError>
+where
+    F: FnOnce() -> Result<MasterKey, EncryptionError>,
+{
+    if !contains_encrypted(value) {
+        return Ok(());
+    }
+    decrypt_value(value, &load_key()?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_encrypt_round_trip() {
+        let key = MasterKey::generate().unwrap();
+        let encrypted = key.encrypt("hunter2").unwrap();
+        assert!(is_encrypted(&encrypted));
+        assert_ne!(key.encrypt("hunter2").unwrap(), encrypted);
+        assert_eq!(key.decrypt(&encrypted).unwrap(), "hunter2");
+
+        let other = MasterKey::parse(&MasterKey::generate().unwrap().encode()).unwrap();
+        assert!(other.decrypt(&encrypted).unwrap_err().to_string().contains("wrong master key"));
+        assert!(key.decrypt("enc:00").is_err());
+        assert!(MasterKey::parse("abcd").is_err());
+    }
+
+    #[test]
+    fn test_decrypt_json_loads_key_only_when_needed() {
+        let key = MasterKey::generate().unwrap();
+        let mut value = json!({
+            "api_base_url": "http://localhost",
+            "mcp": { "client_secret": key.encrypt("hunter2").unwrap() },
+            "channels": [{ "password": key.encrypt("swordfish").unwrap() }],
+        });
+
+        let mut plain = json!({ "api_base_url": "http://localhost" });
+        decrypt_json(&mut plain, || panic!("no encrypted values")).unwrap();
+
+        decrypt_json(&mut value, || Ok(key)).unwrap();
+        assert_eq!(value["mcp"]["client_secret"], "hunter2");
+        assert_eq!(value["channels"][0]["password"], "swordfish");
+        assert_eq!(value["api_base_url"], "http://localhost");
+    }
+}
@@ -1,8 +1,18 @@
 //! Error types for the Squirrel core library.
-
-use std::fmt;
+//!
+//! Besides [`SquirrelError`], this module holds the error codes shared by
+//! every crate. Each crate's error type implements [`Coded`] to classify its
+//! variants with an [`ErrorCode`], which decides the HTTP status the web
+//! server answers with and the remediation hint the CLI prints. Wrapping an
+//! error in a [`CodedError`] keeps the original error, so its source chain
+//! and concrete type survive crossing crate boundaries instead of being
+//! flattened into a string.
+
+use std::fmt::{self, Write as _};
 use std::error::Error;
 
+use serde::{Deserialize, Serialize};
+
 /// Result type alias for `SquirrelError`
 pub type Result<T> = std::result::Result<T, SquirrelError>;
 
@@ -93,9 +103,19 @@ pub enum SquirrelError {
     ProtocolVersion(String),
     /// Context-related errors
     Context(String),
+    /// An error classified by another crate, with its source chain
+    Coded(CodedError),
 }
 
-impl Error for SquirrelError {}
+impl Error for SquirrelError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            SquirrelError::IO(e) => Some(e),
+            SquirrelError::Coded(e) => e.source(),
+            _ => None,
+        }
+    }
+}
 
 impl fmt::Display for SquirrelError {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
@@ -117,6 +137,7 @@ impl fmt::Display for SquirrelError {
             SquirrelError::Persistence(e) => write!(f, "Persistence error: {e}"),
             SquirrelError::ProtocolVersion(msg) => write!(f, "Protocol version error: {msg}"),
             SquirrelError::Context(msg) => write!(f, "Context error: {msg}"),
+            SquirrelError::Coded(e) => write!(f, "{e}"),
         }
     }
 }
@@ -163,6 +184,12 @@ impl From<PersistenceError> for SquirrelError {
     }
 }
 
+impl From<CodedError> for SquirrelError {
+    fn from(err: CodedError) -> Self {
+        SquirrelError::Coded(err)
+    }
+}
+
 /// Errors that can occur during application initialization.
 #[derive(Debug)]
 pub enum AppInitializationError {
@@ -348,4 +375,404 @@ impl From<AlertError> for SquirrelError {
     fn from(err: AlertError) -> Self {
         SquirrelError::Alert(err.to_string())
     }
-}
\ No newline at end of file
+}
+
+/// Stable classification of an error, shared by every crate
+///
+/// The string form, e.g. `not_found`, is part of the web API and never
+/// changes once published; codes may be added.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+#[non_exhaustive]
+pub enum ErrorCode {
+    /// The request or its arguments are malformed
+    InvalidInput,
+    /// The caller is not authenticated
+    Unauthenticated,
+    /// The caller may not do this
+    PermissionDenied,
+    /// What the request names does not exist
+    NotFound,
+    /// What the request creates exists already
+    AlreadyExists,
+    /// The request conflicts with the current state
+    Conflict,
+    /// The caller sent too many requests
+    RateLimited,
+    /// A resource limit was reached
+    ResourceExhausted,
+    /// The operation took too long
+    Timeout,
+    /// A service the operation needs is not available
+    Unavailable,
+    /// The operation or version is not supported
+    Unsupported,
+    /// A command, tool or program ran and failed
+    ExecutionFailed,
+    /// Something went wrong that the caller cannot fix
+    Internal,
+}
+
+impl ErrorCode {
+    /// Every code
+    pub const ALL: &'static [ErrorCode] = &[
+        ErrorCode::InvalidInput,
+        ErrorCode::Unauthenticated,
+        ErrorCode::PermissionDenied,
+        ErrorCode::NotFound,
+        ErrorCode::AlreadyExists,
+        ErrorCode::Conflict,
+        ErrorCode::RateLimited,
+        ErrorCode::ResourceExhausted,
+        ErrorCode::Timeout,
+        ErrorCode::Unavailable,
+        ErrorCode::Unsupported,
+        ErrorCode::ExecutionFailed,
+        ErrorCode::Internal,
+    ];
+
+    /// The code as written in API responses, e.g. `not_found`
+    #[must_use]
+    pub fn as_str(self) -> &'static str {
+        match self {
+            ErrorCode::InvalidInput => "invalid_input",
+            ErrorCode::Unauthenticated => "unauthenticated",
+            ErrorCode::PermissionDenied => "permission_denied",
+            ErrorCode::NotFound => "not_found",
+            ErrorCode::AlreadyExists => "already_exists",
+            ErrorCode::Conflict => "conflict",
+            ErrorCode::RateLimited => "rate_limited",
+            ErrorCode::ResourceExhausted => "resource_exhausted",
+            ErrorCode::Timeout => "timeout",
+            ErrorCode::Unavailable => "unavailable",
+            ErrorCode::Unsupported => "unsupported",
+            ErrorCode::ExecutionFailed => "execution_failed",
+            ErrorCode::Internal => "internal",
+        }
+    }
+
+    /// Short human-readable summary, e.g. `Not found`
+    #[must_use]
+    pub fn title(self) -> &'static str {
+        match self {
+            ErrorCode::InvalidInput => "Invalid input",
+            ErrorCode::Unauthenticated => "Not authenticated",
+            ErrorCode::PermissionDenied => "Permission denied",
+            ErrorCode::NotFound => "Not found",
+            ErrorCode::AlreadyExists => "Already exists",
+            ErrorCode::Conflict => "Conflict",
+            ErrorCode::RateLimited => "Too many requests",
+            ErrorCode::ResourceExhausted => "Resource limit reached",
+            ErrorCode::Timeout => "Timed out",
+            ErrorCode::Unavailable => "Unavailable",
+            ErrorCode::Unsupported => "Not supported",
+            ErrorCode::ExecutionFailed => "Execution failed",
+            ErrorCode::Internal => "Internal error",
+        }
+    }
+
+    /// HTTP status the web server answers with
+    #[must_use]
+    pub fn http_status(self) -> u16 {
+        match self {
+            ErrorCode::InvalidInput => 400,
+            ErrorCode::Unauthenticated => 401,
+            ErrorCode::PermissionDenied => 403,
+            ErrorCode::NotFound => 404,
+            ErrorCode::AlreadyExists | ErrorCode::Conflict => 409,
+            ErrorCode::RateLimited => 429,
+            ErrorCode::Internal => 500,
+            ErrorCode::Unsupported => 501,
+            ErrorCode::ExecutionFailed => 502,
+            ErrorCode::ResourceExhausted | ErrorCode::Unavailable => 503,
+            ErrorCode::Timeout => 504,
+        }
+    }
+
+    /// What the user can do about errors with this code, if anything
+    #[must_use]
+    pub fn hint(self) -> Option<&'static str> {
+        match self {
+            ErrorCode::InvalidInput => Some("Check the arguments; `--help` lists what the command accepts"),
+            ErrorCode::Unauthenticated => Some("Log in again or pass a valid token"),
+            ErrorCode::PermissionDenied => Some("Ask an administrator to grant you access"),
+            ErrorCode::NotFound => Some("Check the name or ID for typos"),
+            ErrorCode::AlreadyExists => Some("Choose another name, or remove the existing one first"),
+            ErrorCode::Conflict => Some("Reload the current state and try again"),
+            ErrorCode::RateLimited => Some("Wait a moment before retrying"),
+            ErrorCode::ResourceExhausted => Some("Retry with a smaller input, or raise the limit"),
+            ErrorCode::Timeout => Some("Retry, or raise the timeout"),
+            ErrorCode::Unavailable => Some("Check that the service is running, then retry"),
+            ErrorCode::Unsupported => Some("Upgrade Squirrel or the plugin, or use a supported option"),
+            ErrorCode::ExecutionFailed => None,
+            ErrorCode::Internal => Some("This is likely a bug; please report it with the message above"),
+        }
+    }
+
+    /// Whether retrying the same request later may succeed
+    #[must_use]
+    pub fn is_retryable(self) -> bool {
+        matches!(
+            self,
+            ErrorCode::RateLimited | ErrorCode::ResourceExhausted | ErrorCode::Timeout | ErrorCode::Unavailable
+        )
+    }
+}
+
+impl fmt::Display for ErrorCode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// An error classified with an [`ErrorCode`]
+pub trait Coded: Error {
+    /// The error's code
+    fn code(&self) -> ErrorCode;
+
+    /// What the user can do about this error; defaults to the code's hint
+    fn hint(&self) -> Option<String> {
+        self.code().hint().map(str::to_string)
+    }
+}
+
+/// A coded error that keeps the error it was made from
+///
+/// [`CodedError::from_error`] wraps any [`Coded`] error: the code, message
+/// and hint are taken from it, [`CodedError::downcast_ref`] recovers it, and
+/// its source chain stays reachable through [`Error::source`].
+#[derive(Debug)]
+pub struct CodedError {
+    /// Classification
+    code: ErrorCode,
+    /// Message shown to users
+    message: String,
+    /// Remediation hint
+    hint: Option<String>,
+    /// The error this was made from, with the same message
+    wrapped: Option<Box<dyn Error + Send + Sync + 'static>>,
+    /// The error that caused this one
+    source: Option<Box<dyn Error + Send + Sync + 'static>>,
+}
+
+impl CodedError {
+    /// Creates an error with `code` and `message`, and the code's hint
+    pub fn new(code: ErrorCode, message: impl Into<String>) -> Self {
+        Self {
+            code,
+            message: message.into(),
+            hint: code.hint().map(str::to_string),
+            wrapped: None,
+            source: None,
+        }
+    }
+
+    /// Wraps `error`, keeping its code, message, hint and source chain
+    pub fn from_error<E>(error: E) -> Self
+    where
+        E: Coded + Send + Sync + 'static,
+    {
+        Self {
+            code: error.code(),
+            message: error.to_string(),
+            hint: error.hint(),
+            wrapped: Some(Box::new(error)),
+            source: None,
+        }
+    }
+
+    /// Replaces the hint
+    #[must_use]
+    pub fn with_hint(mut self, hint: impl Into<String>) -> Self {
+        self.hint = Some(hint.into());
+        self
+    }
+
+    /// Records the error that caused this one
+    #[must_use]
+    pub fn with_source(mut self, source: impl Error + Send + Sync + 'static) -> Self {
+        self.source = Some(Box::new(source));
+        self
+    }
+
+    /// The error's code
+    #[must_use]
+    pub fn code(&self) -> ErrorCode {
+        self.code
+    }
+
+    /// The message shown to users
+    #[must_use]
+    pub fn message(&self) -> &str {
+        &self.message
+    }
+
+    /// The remediation hint, if any
+    #[must_use]
+    pub fn hint(&self) -> Option<&str> {
+        self.hint.as_deref()
+    }
+
+    /// The error this was made from, if it is a `T`
+    #[must_use]
+    pub fn downcast_ref<T: Error + 'static>(&self) -> Option<&T> {
+        self.wrapped.as_ref()?.downcast_ref::<T>()
+    }
+
+    /// Messages of the errors that caused this one, outermost first
+    #[must_use]
+    pub fn causes(&self) -> Vec<String> {
+        let mut causes = Vec::new();
+        let mut next = self.source();
+        while let Some(cause) = next {
+            causes.push(cause.to_string());
+            next = cause.source();
+        }
+        causes
+    }
+
+    /// Renders the error for a terminal
+    ///
+    /// ```text
+    /// error[not_found]: Command not found: algn
+    ///   caused by: ...
+    ///   hint: Run `squirrel help` to list the available commands
+    /// ```
+    #[must_use]
+    pub fn report(&self) -> String {
+        let mut report = format!("error[{}]: {}", self.code, self.message);
+        for cause in self.causes() {
+            let _ = write!(report, "\n  caused by: {cause}");
+        }
+        if let Some(hint) = &self.hint {
+            let _ = write!(report, "\n  hint: {hint}");
+        }
+        report
+    }
+}
+
+impl fmt::Display for CodedError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.message)
+    }
+}
+
+impl Error for CodedError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match (&self.source, &self.wrapped) {
+            (Some(source), _) => Some(source.as_ref()),
+            (None, Some(wrapped)) => wrapped.source(),
+            (None, None) => None,
+        }
+    }
+}
+
+impl Coded for CodedError {
+    fn code(&self) -> ErrorCode {
+        self.code
+    }
+
+    fn hint(&self) -> Option<String> {
+        self.hint.clone()
+    }
+}
+
+impl Coded for SquirrelError {
+    fn code(&self) -> ErrorCode {
+        match self {
+            SquirrelError::AppInitialization(AppInitializationError::AlreadyInitialized)
+            | SquirrelError::AppOperation(AppOperationError::AlreadyStarted | AppOperationError::AlreadyStopped) => {
+                ErrorCode::Conflict
+            }
+            SquirrelError::AppInitialization(AppInitializationError::InvalidConfiguration(_))
+            | SquirrelError::Serialization(_) => ErrorCode::InvalidInput,
+            SquirrelError::AppInitialization(AppInitializationError::ResourceLoadFailure(_))
+            | SquirrelError::AppOperation(AppOperationError::NotInitialized | AppOperationError::NotStarted)
+            | SquirrelError::Network(_) => ErrorCode::Unavailable,
+            SquirrelError::AppOperation(AppOperationError::UnsupportedOperation(_))
+            | SquirrelError::ProtocolVersion(_) => ErrorCode::Unsupported,
+            SquirrelError::AppOperation(AppOperationError::OperationFailure(_)) => ErrorCode::ExecutionFailed,
+            SquirrelError::Security(_) => ErrorCode::PermissionDenied,
+            SquirrelError::Session(_) => ErrorCode::Unauthenticated,
+            SquirrelError::Coded(e) => e.code(),
+            SquirrelError::Generic(_)
+            | SquirrelError::IO(_)
+            | SquirrelError::MCP(_)
+            | SquirrelError::Other(_)
+            | SquirrelError::Health(_)
+            | SquirrelError::Metric(_)
+            | SquirrelError::Dashboard(_)
+            | SquirrelError::Alert(_)
+            | SquirrelError::Persistence(_)
+            | SquirrelError::Context(_) => ErrorCode::Internal,
+        }
+    }
+
+    fn hint(&self) -> Option<String> {
+        match self {
+            SquirrelError::Coded(e) => e.hint.clone(),
+            other => other.code().hint().map(str::to_string),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Debug)]
+    struct Missing(std::io::Error);
+
+    impl fmt::Display for Missing {
+        fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+            write!(f, "Genome index not found")
+        }
+    }
+
+    impl Error for Missing {
+        fn source(&self) -> Option<&(dyn Error + 'static)> {
+            Some(&self.0)
+        }
+    }
+
+    impl Coded for Missing {
+        fn code(&self) -> ErrorCode {
+            ErrorCode::NotFound
+        }
+
+        fn hint(&self) -> Option<String> {
+            Some("Build the index with `squirrel bio index`".to_string())
+        }
+    }
+
+    #[test]
+    fn test_codes_round_trip_and_map_to_http() {
+        for code in ErrorCode::ALL {
+            let json = serde_json::to_string(code).unwrap();
+            assert_eq!(json, format!("\"{}\"", code.as_str()));
+            assert_eq!(serde_json::from_str::<ErrorCode>(&json).unwrap(), *code);
+            assert!((400..600).contains(&code.http_status()));
+        }
+        assert_eq!(ErrorCode::PermissionDenied.http_status(), 403);
+        assert!(ErrorCode::Timeout.is_retryable() && !ErrorCode::InvalidInput.is_retryable());
+    }
+
+    #[test]
+    fn test_coded_errors_keep_the_original_error_and_its_chain() {
+        let io = std::io::Error::new(std::io::ErrorKind::NotFound, "hg38.fai: no such file");
+        let error = CodedError::from_error(Missing(io));
+        assert_eq!(error.code(), ErrorCode::NotFound);
+        assert_eq!(error.to_string(), "Genome index not found");
+        assert!(error.downcast_ref::<Missing>().is_some());
+        assert_eq!(error.causes(), vec!["hg38.fai: no such file".to_string()]);
+        assert_eq!(
+            error.report(),
+            "error[not_found]: Genome index not found\n  caused by: hg38.fai: no such file\n  hint: Build the index with `squirrel bio index`"
+        );
+
+        let core: SquirrelError = error.into();
+        assert_eq!(core.code(), ErrorCode::NotFound);
+        assert_eq!(core.source().unwrap().to_string(), "hg38.fai: no such file");
+        assert_eq!(SquirrelError::session("expired").code(), ErrorCode::Unauthenticated);
+    }
+}
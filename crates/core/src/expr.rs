@@ -0,0 +1,1091 @@
+//! Sandboxed expressions over JSON values
+//!
+//! An [`Expression`] is a small, side-effect free formula evaluated against
+//! a JSON value, such as `severity == "critical" && labels.gpu == "a100"` or
+//! `len(data.samples) > 0`. Validation rules, alert routing rules and agent
+//! routing policies use them as conditions.
+//!
+//! The language has literals (`null`, `true`, `false`, numbers, strings in
+//! single or double quotes and `[...]` arrays), paths into the input
+//! (`data.steps[0].name`, `labels["node-type"]`), the operators `!`, `-`,
+//! `* / %`, `+ -`, `< <= > >= in`, `== !=`, `&&` and `||`, from the
+//! tightest binding to the loosest, and the functions `len`, `lower`, `upper`, `contains`,
+//! `starts_with`, `ends_with`, `matches` and `exists`. A path that leads
+//! nowhere is `null`, so `exists(x)` checks whether the input has `x`.
+//!
+//! Expressions are parsed and type checked once: unknown functions, wrong
+//! numbers of arguments and operations on literals of the wrong type are
+//! rejected before anything is evaluated. They cannot perform I/O, loop or
+//! call back into the host; their size is limited when parsed and their
+//! evaluation is bounded by a budget of steps, so an expression from a
+//! configuration file or an API request cannot stall its caller.
+
+use std::fmt;
+
+use regex::{Regex, RegexBuilder};
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Number, Value};
+use thiserror::Error;
+
+/// Longest expression source accepted, in bytes
+pub const MAX_SOURCE_LEN: usize = 4096;
+
+/// Most nodes an expression may have
+pub const MAX_NODES: usize = 512;
+
+/// Deepest nesting of parentheses, brackets and operators accepted
+pub const MAX_DEPTH: usize = 32;
+
+/// Steps an evaluation may take: one per node, plus one per element or
+/// character a function or operator scans
+pub const MAX_STEPS: u64 = 100_000;
+
+/// Size limit of the compiled regular expressions of `matches`
+const REGEX_SIZE_LIMIT: usize = 1 << 16;
+
+/// Errors of parsing and evaluating expressions
+#[derive(Debug, Clone, PartialEq, Eq, Error)]
+pub enum ExprError {
+    /// The source is not a well-formed expression
+    #[error("Syntax error at {position}: {message}")]
+    Syntax {
+        /// Byte offset of the error in the source
+        position: usize,
+        /// What is wrong
+        message: String,
+    },
+    /// An operation is applied to values of the wrong type
+    #[error("Type error: {0}")]
+    Type(String),
+    /// The expression exceeds a size or evaluation limit
+    #[error("Limit exceeded: {0}")]
+    Limit(String),
+    /// Evaluation failed, e.g. dividing by zero
+    #[error("Evaluation error: {0}")]
+    Eval(String),
+}
+
+/// Result of parsing and evaluating expressions
+pub type ExprResult<T> = Result<T, ExprError>;
+
+/// Type of a value, as far as it is known before evaluation
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ValueType {
+    /// `null`
+    Null,
+    /// `true` or `false`
+    Bool,
+    /// A number
+    Number,
+    /// A string
+    String,
+    /// An array
+    Array,
+    /// An object
+    Object,
+    /// Only known once evaluated, such as the value at a path
+    Any,
+}
+
+impl ValueType {
+    /// The type of `value`
+    #[must_use]
+    pub fn of(value: &Value) -> Self {
+        match value {
+            Value::Null => Self::Null,
+            Value::Bool(_) => Self::Bool,
+            Value::Number(_) => Self::Number,
+            Value::String(_) => Self::String,
+            Value::Array(_) => Self::Array,
+            Value::Object(_) => Self::Object,
+        }
+    }
+
+    /// The type's name, e.g. `number`
+    #[must_use]
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Null => "null",
+            Self::Bool => "bool",
+            Self::Number => "number",
+            Self::String => "string",
+            Self::Array => "array",
+            Self::Object => "object",
+            Self::Any => "any",
+        }
+    }
+
+    /// Whether a value of this type may be one of `allowed`
+    fn fits(self, allowed: &[Self]) -> bool {
+        self == Self::Any || allowed.contains(&self)
+    }
+}
+
+impl fmt::Display for ValueType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// A unary operator
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum UnaryOp {
+    /// `!`
+    Not,
+    /// `-`
+    Negate,
+}
+
+/// A binary operator
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum BinaryOp {
+    /// `||`
+    Or,
+    /// `&&`
+    And,
+    /// `==`
+    Eq,
+    /// `!=`
+    Ne,
+    /// `<`
+    Lt,
+    /// `<=`
+    Le,
+    /// `>`
+    Gt,
+    /// `>=`
+    Ge,
+    /// `in`
+    In,
+    /// `+`
+    Add,
+    /// `-`
+    Sub,
+    /// `*`
+    Mul,
+    /// `/`
+    Div,
+    /// `%`
+    Rem,
+}
+
+impl BinaryOp {
+    /// The operator and its binding power for a token
+    fn of(token: &Token) -> Option<(Self, u8)> {
+        let op = match token {
+            Token::Punct("||") => (Self::Or, 1),
+            Token::Punct("&&") => (Self::And, 2),
+            Token::Punct("==") => (Self::Eq, 3),
+            Token::Punct("!=") => (Self::Ne, 3),
+            Token::Punct("<") => (Self::Lt, 4),
+            Token::Punct("<=") => (Self::Le, 4),
+            Token::Punct(">") => (Self::Gt, 4),
+            Token::Punct(">=") => (Self::Ge, 4),
+            Token::Ident(name) if name == "in" => (Self::In, 4),
+            Token::Punct("+") => (Self::Add, 5),
+            Token::Punct("-") => (Self::Sub, 5),
+            Token::Punct("*") => (Self::Mul, 6),
+            Token::Punct("/") => (Self::Div, 6),
+            Token::Punct("%") => (Self::Rem, 6),
+            _ => return None,
+        };
+        Some(op)
+    }
+
+    /// The operator as written
+    fn symbol(self) -> &'static str {
+        match self {
+            Self::Or => "||",
+            Self::And => "&&",
+            Self::Eq => "==",
+            Self::Ne => "!=",
+            Self::Lt => "<",
+            Self::Le => "<=",
+            Self::Gt => ">",
+            Self::Ge => ">=",
+            Self::In => "in",
+            Self::Add => "+",
+            Self::Sub => "-",
+            Self::Mul => "*",
+            Self::Div => "/",
+            Self::Rem => "%",
+        }
+    }
+}
+
+/// A built-in function
+#[derive(Debug, Clone)]
+enum Function {
+    /// `len(x)`: characters of a string, elements of an array or keys of an object
+    Len,
+    /// `lower(s)`
+    Lower,
+    /// `upper(s)`
+    Upper,
+    /// `contains(x, y)`: substring, element or key
+    Contains,
+    /// `starts_with(s, prefix)`
+    StartsWith,
+    /// `ends_with(s, suffix)`
+    EndsWith,
+    /// `matches(s, 'pattern')`, the pattern compiled when parsed
+    Matches(Regex),
+    /// `exists(x)`: whether `x` is not `null`
+    Exists,
+}
+
+impl Function {
+    /// The function's name
+    fn name(&self) -> &'static str {
+        match self {
+            Self::Len => "len",
+            Self::Lower => "lower",
+            Self::Upper => "upper",
+            Self::Contains => "contains",
+            Self::StartsWith => "starts_with",
+            Self::EndsWith => "ends_with",
+            Self::Matches(_) => "matches",
+            Self::Exists => "exists",
+        }
+    }
+
+    /// Types each argument may have, and the type of the result
+    fn signature(&self) -> (&'static [&'static [ValueType]], ValueType) {
+        use ValueType::{Array, Bool, Null, Number, Object, String};
+        /// Any value at all
+        const ALL: &[ValueType] = &[Null, Bool, Number, String, Array, Object];
+        /// Values with a length
+        const SIZED: &[ValueType] = &[String, Array, Object];
+        match self {
+            Self::Len => (&[SIZED], Number),
+            Self::Lower | Self::Upper => (&[&[String]], String),
+            Self::Contains => (&[SIZED, ALL], Bool),
+            Self::StartsWith | Self::EndsWith => (&[&[String], &[String]], Bool),
+            Self::Matches(_) => (&[&[String]], Bool),
+            Self::Exists => (&[ALL], Bool),
+        }
+    }
+}
+
+/// A node of a parsed expression
+#[derive(Debug, Clone)]
+enum Node {
+    /// A literal value
+    Literal(Value),
+    /// An array of values
+    Array(Vec<Node>),
+    /// A top-level key of the input
+    Variable(String),
+    /// A key of an object
+    Member(Box<Node>, String),
+    /// An element of an array or a key of an object
+    Index(Box<Node>, Box<Node>),
+    /// A unary operation
+    Unary(UnaryOp, Box<Node>),
+    /// A binary operation
+    Binary(BinaryOp, Box<Node>, Box<Node>),
+    /// A function call
+    Call(Function, Vec<Node>),
+}
+
+/// A token of the source and its byte offset
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    /// A number
+    Number(f64),
+    /// A string, with escapes resolved
+    Str(String),
+    /// A name, keyword or function
+    Ident(String),
+    /// An operator or delimiter
+    Punct(&'static str),
+    /// The end of the source
+    End,
+}
+
+/// Operators and delimiters, longest first
+const PUNCTUATION: &[&str] = &[
+    "||", "&&", "==", "!=", "<=", ">=", "<", ">", "!", "+", "-", "*", "/", "%", "(", ")", "[", "]", ",", ".",
+];
+
+/// Splits `source` into tokens
+fn tokenize(source: &str) -> ExprResult<Vec<(Token, usize)>> {
+    let syntax = |position: usize, message: String| ExprError::Syntax { position, message };
+    let mut tokens = Vec::new();
+    let mut chars = source.char_indices().peekable();
+    while let Some(&(start, c)) = chars.peek() {
+        if c.is_whitespace() {
+            chars.next();
+        } else if c.is_ascii_digit() {
+            let mut end = start;
+            while let Some(&(i, d)) = chars.peek() {
+                if d.is_ascii_digit() || d == '.' || d == 'e' || d == 'E' {
+                    end = i + d.len_utf8();
+                    chars.next();
+                } else {
+                    break;
+                }
+            }
+            let text = &source[start..end];
+            let number = text
+                .parse::<f64>()
+                .map_err(|_| syntax(start, format!("invalid number '{text}'")))?;
+            tokens.push((Token::Number(number), start));
+        } else if c == '"' || c == '\'' {
+            chars.next();
+            let mut text = String::new();
+            loop {
+                match chars.next() {
+                    Some((_, q)) if q == c => break,
+                    Some((i, '\\')) => match chars.next() {
+                        Some((_, 'n')) => text.push('\n'),
+                        Some((_, 't')) => text.push('\t'),
+                        Some((_, e)) if e == '\\' || e == '"' || e == '\'' => text.push(e),
+                        _ => return Err(syntax(i, "invalid escape".to_string())),
+                    },
+                    Some((_, ch)) => text.push(ch),
+                    None => return Err(syntax(start, "unterminated string".to_string())),
+                }
+            }
+            tokens.push((Token::Str(text), start));
+        } else if c.is_alphabetic() || c == '_' {
+            let mut end = start;
+            while let Some(&(i, d)) = chars.peek() {
+                if d.is_alphanumeric() || d == '_' {
+                    end = i + d.len_utf8();
+                    chars.next();
+                } else {
+                    break;
+                }
+            }
+            tokens.push((Token::Ident(source[start..end].to_string()), start));
+        } else {
+            let punct = PUNCTUATION
+                .iter()
+                .find(|punct| source[start..].starts_with(**punct))
+                .ok_or_else(|| syntax(start, format!("unexpected character '{c}'")))?;
+            for _ in 0..punct.len() {
+                chars.next();
+            }
+            tokens.push((Token::Punct(punct), start));
+        }
+    }
+    tokens.push((Token::End, source.len()));
+    Ok(tokens)
+}
+
+/// Recursive descent parser with precedence climbing for binary operators
+struct Parser {
+    /// Tokens of the source
+    tokens: Vec<(Token, usize)>,
+    /// Index of the next token
+    next: usize,
+    /// Current nesting depth
+    depth: usize,
+    /// Nodes created so far
+    nodes: usize,
+}
+
+impl Parser {
+    /// The next token
+    fn peek(&self) -> &Token {
+        &self.tokens[self.next.min(self.tokens.len() - 1)].0
+    }
+
+    /// Byte offset of the next token
+    fn position(&self) -> usize {
+        self.tokens[self.next.min(self.tokens.len() - 1)].1
+    }
+
+    /// Consumes the next token
+    fn advance(&mut self) -> Token {
+        let token = self.peek().clone();
+        self.next += 1;
+        token
+    }
+
+    /// A syntax error at the next token
+    fn error(&self, message: impl Into<String>) -> ExprError {
+        ExprError::Syntax {
+            position: self.position(),
+            message: message.into(),
+        }
+    }
+
+    /// Consumes `punct`, or fails
+    fn expect(&mut self, punct: &str) -> ExprResult<()> {
+        if self.peek() == &Token::Punct(Self::intern(punct)) {
+            self.next += 1;
+            Ok(())
+        } else {
+            Err(self.error(format!("expected '{punct}'")))
+        }
+    }
+
+    /// The static string of a punctuation token
+    fn intern(punct: &str) -> &'static str {
+        PUNCTUATION.iter().find(|p| **p == punct).copied().unwrap_or("")
+    }
+
+    /// Counts a new node against [`MAX_NODES`]
+    fn node(&mut self, node: Node) -> ExprResult<Node> {
+        self.nodes += 1;
+        if self.nodes > MAX_NODES {
+            return Err(ExprError::Limit(format!("more than {MAX_NODES} nodes")));
+        }
+        Ok(node)
+    }
+
+    /// Enters a nesting level, counted against [`MAX_DEPTH`]
+    fn enter(&mut self) -> ExprResult<()> {
+        self.depth += 1;
+        if self.depth > MAX_DEPTH {
+            return Err(ExprError::Limit(format!("nested deeper than {MAX_DEPTH} levels")));
+        }
+        Ok(())
+    }
+
+    /// An expression whose binary operators bind at least as `min_power`
+    fn expression(&mut self, min_power: u8) -> ExprResult<Node> {
+        self.enter()?;
+        let mut left = self.unary()?;
+        while let Some((op, power)) = BinaryOp::of(self.peek()) {
+            if power < min_power {
+                break;
+            }
+            self.next += 1;
+            let right = self.expression(power + 1)?;
+            left = self.node(Node::Binary(op, Box::new(left), Box::new(right)))?;
+        }
+        self.depth -= 1;
+        Ok(left)
+    }
+
+    /// A unary operation or a postfix expression
+    fn unary(&mut self) -> ExprResult<Node> {
+        let op = match self.peek() {
+            Token::Punct("!") => UnaryOp::Not,
+            Token::Punct("-") => UnaryOp::Negate,
+            _ => return self.postfix(),
+        };
+        self.next += 1;
+        self.enter()?;
+        let operand = self.unary()?;
+        self.depth -= 1;
+        self.node(Node::Unary(op, Box::new(operand)))
+    }
+
+    /// A primary expression followed by member accesses and indexes
+    fn postfix(&mut self) -> ExprResult<Node> {
+        let mut node = self.primary()?;
+        loop {
+            match self.peek() {
+                Token::Punct(".") => {
+                    self.next += 1;
+                    match self.advance() {
+                        Token::Ident(key) => node = self.node(Node::Member(Box::new(node), key))?,
+                        _ => return Err(self.error("expected a key after '.'")),
+                    }
+                }
+                Token::Punct("[") => {
+                    self.next += 1;
+                    let index = self.expression(0)?;
+                    self.expect("]")?;
+                    node = self.node(Node::Index(Box::new(node), Box::new(index)))?;
+                }
+                _ => return Ok(node),
+            }
+        }
+    }
+
+    /// A literal, variable, call, array or parenthesized expression
+    fn primary(&mut self) -> ExprResult<Node> {
+        let position = self.position();
+        let node = match self.advance() {
+            Token::Number(number) => Node::Literal(number_value(number)),
+            Token::Str(text) => Node::Literal(Value::String(text)),
+            Token::Ident(name) => match name.as_str() {
+                "null" => Node::Literal(Value::Null),
+                "true" => Node::Literal(Value::Bool(true)),
+                "false" => Node::Literal(Value::Bool(false)),
+                "in" => return Err(ExprError::Syntax { position, message: "unexpected 'in'".to_string() }),
+                _ if self.peek() == &Token::Punct("(") => {
+                    self.next += 1;
+                    let args = self.list(")")?;
+                    return self.call(&name, args, position);
+                }
+                _ => Node::Variable(name),
+            },
+            Token::Punct("(") => {
+                let inner = self.expression(0)?;
+                self.expect(")")?;
+                return Ok(inner);
+            }
+            Token::Punct("[") => Node::Array(self.list("]")?),
+            Token::Punct(punct) => {
+                return Err(ExprError::Syntax { position, message: format!("unexpected '{punct}'") });
+            }
+            Token::End => {
+                return Err(ExprError::Syntax { position, message: "unexpected end of expression".to_string() });
+            }
+        };
+        self.node(node)
+    }
+
+    /// Comma-separated expressions up to `close`
+    fn list(&mut self, close: &str) -> ExprResult<Vec<Node>> {
+        self.enter()?;
+        let mut items = Vec::new();
+        if self.peek() == &Token::Punct(Self::intern(close)) {
+            self.next += 1;
+        } else {
+            loop {
+                items.push(self.expression(0)?);
+                if self.peek() == &Token::Punct(",") {
+                    self.next += 1;
+                } else {
+                    self.expect(close)?;
+                    break;
+                }
+            }
+        }
+        self.depth -= 1;
+        Ok(items)
+    }
+
+    /// A call of the function `name`
+    fn call(&mut self, name: &str, mut args: Vec<Node>, position: usize) -> ExprResult<Node> {
+        let function = match name {
+            "len" => Function::Len,
+            "lower" => Function::Lower,
+            "upper" => Function::Upper,
+            "contains" => Function::Contains,
+            "starts_with" => Function::StartsWith,
+            "ends_with" => Function::EndsWith,
+            "exists" => Function::Exists,
+            "matches" => {
+                let pattern = match args.get(1) {
+                    Some(Node::Literal(Value::String(pattern))) if args.len() == 2 => pattern.clone(),
+                    _ => return Err(ExprError::Type("matches takes a string and a pattern literal".to_string())),
+                };
+                let regex = RegexBuilder::new(&pattern)
+                    .size_limit(REGEX_SIZE_LIMIT)
+                    .build()
+                    .map_err(|e| ExprError::Syntax { position, message: format!("invalid pattern: {e}") })?;
+                args.truncate(1);
+                Function::Matches(regex)
+            }
+            _ => return Err(ExprError::Syntax { position, message: format!("unknown function '{name}'") }),
+        };
+        self.node(Node::Call(function, args))
+    }
+}
+
+/// The type of `node`, or why it cannot be evaluated
+fn check(node: &Node) -> ExprResult<ValueType> {
+    use ValueType::{Any, Array, Bool, Null, Number, Object, String};
+    let expect = |what: &str, found: ValueType, allowed: &[ValueType]| {
+        if found.fits(allowed) {
+            Ok(())
+        } else {
+            Err(ExprError::Type(format!("{what} can not be applied to {found}")))
+        }
+    };
+    Ok(match node {
+        Node::Literal(value) => ValueType::of(value),
+        Node::Array(items) => {
+            for item in items {
+                check(item)?;
+            }
+            Array
+        }
+        Node::Variable(_) => Any,
+        Node::Member(base, key) => {
+            expect(&format!("'.{key}'"), check(base)?, &[Object, Null])?;
+            Any
+        }
+        Node::Index(base, index) => {
+            let base = check(base)?;
+            expect("'[]'", base, &[Array, Object, Null])?;
+            expect("an index", check(index)?, &[Number, String])?;
+            Any
+        }
+        Node::Unary(UnaryOp::Not, operand) => {
+            expect("'!'", check(operand)?, &[Bool])?;
+            Bool
+        }
+        Node::Unary(UnaryOp::Negate, operand) => {
+            expect("'-'", check(operand)?, &[Number])?;
+            Number
+        }
+        Node::Binary(op, left, right) => {
+            let (left, right) = (check(left)?, check(right)?);
+            let what = format!("'{}'", op.symbol());
+            match op {
+                BinaryOp::Or | BinaryOp::And => {
+                    expect(&what, left, &[Bool])?;
+                    expect(&what, right, &[Bool])?;
+                    Bool
+                }
+                BinaryOp::Eq | BinaryOp::Ne => Bool,
+                BinaryOp::Lt | BinaryOp::Le | BinaryOp::Gt | BinaryOp::Ge => {
+                    expect(&what, left, &[Number, String])?;
+                    expect(&what, right, &[Number, String])?;
+                    if left != Any && right != Any && left != right {
+                        return Err(ExprError::Type(format!("{what} can not compare {left} with {right}")));
+                    }
+                    Bool
+                }
+                BinaryOp::In => {
+                    expect(&what, right, &[Array, Object, String])?;
+                    if right == String {
+                        expect(&what, left, &[String])?;
+                    }
+                    Bool
+                }
+                BinaryOp::Add => {
+                    expect(&what, left, &[Number, String])?;
+                    expect(&what, right, &[Number, String])?;
+                    match (left, right) {
+                        (Any, Any) => Any,
+                        (Any, known) | (known, Any) => known,
+                        (left, right) if left == right => left,
+                        _ => return Err(ExprError::Type(format!("'+' can not add {left} and {right}"))),
+                    }
+                }
+                BinaryOp::Sub | BinaryOp::Mul | BinaryOp::Div | BinaryOp::Rem => {
+                    expect(&what, left, &[Number])?;
+                    expect(&what, right, &[Number])?;
+                    Number
+                }
+            }
+        }
+        Node::Call(function, args) => {
+            let (params, result) = function.signature();
+            if args.len() != params.len() {
+                return Err(ExprError::Type(format!(
+                    "{} takes {} arguments, not {}",
+                    function.name(),
+                    params.len(),
+                    args.len()
+                )));
+            }
+            for (arg, allowed) in args.iter().zip(params) {
+                expect(function.name(), check(arg)?, allowed)?;
+            }
+            result
+        }
+    })
+}
+
+/// The JSON value of a number, an integer when it is one
+#[allow(clippy::cast_possible_truncation)]
+fn number_value(number: f64) -> Value {
+    if number.fract() == 0.0 && number.abs() < 9.007_199_254_740_992e15 {
+        Value::from(number as i64)
+    } else {
+        Number::from_f64(number).map_or(Value::Null, Value::Number)
+    }
+}
+
+/// Whether two values are equal, comparing numbers by value
+fn equal(a: &Value, b: &Value) -> bool {
+    match (a, b) {
+        (Value::Number(a), Value::Number(b)) => a.as_f64() == b.as_f64(),
+        (Value::Array(a), Value::Array(b)) => a.len() == b.len() && a.iter().zip(b).all(|(a, b)| equal(a, b)),
+        (Value::Object(a), Value::Object(b)) => {
+            a.len() == b.len() && a.iter().all(|(key, value)| b.get(key).is_some_and(|other| equal(value, other)))
+        }
+        _ => a == b,
+    }
+}
+
+/// Evaluates nodes against an input within a budget of steps
+struct Evaluator<'a> {
+    /// The input paths are resolved in
+    input: &'a Value,
+    /// Steps left
+    steps: u64,
+}
+
+impl Evaluator<'_> {
+    /// Spends `steps`, failing when the budget is exhausted
+    fn spend(&mut self, steps: usize) -> ExprResult<()> {
+        let steps = u64::try_from(steps).unwrap_or(u64::MAX);
+        self.steps = self
+            .steps
+            .checked_sub(steps)
+            .ok_or_else(|| ExprError::Limit(format!("more than {MAX_STEPS} evaluation steps")))?;
+        Ok(())
+    }
+
+    /// A type error for a value found at evaluation
+    fn type_error(what: &str, value: &Value) -> ExprError {
+        ExprError::Type(format!("{what} can not be applied to {}", ValueType::of(value)))
+    }
+
+    /// The value of `node`
+    fn eval(&mut self, node: &Node) -> ExprResult<Value> {
+        self.spend(1)?;
+        match node {
+            Node::Literal(value) => Ok(value.clone()),
+            Node::Array(items) => Ok(Value::Array(items.iter().map(|item| self.eval(item)).collect::<ExprResult<_>>()?)),
+            Node::Variable(name) => Ok(self.input.get(name).cloned().unwrap_or(Value::Null)),
+            Node::Member(base, key) => Ok(self.eval(base)?.get(key).cloned().unwrap_or(Value::Null)),
+            Node::Index(base, index) => {
+                let base = self.eval(base)?;
+                let index = self.eval(index)?;
+                Ok(match (&base, &index) {
+                    (Value::Array(items), Value::Number(n)) => {
+                        n.as_u64().and_then(|n| usize::try_from(n).ok()).and_then(|n| items.get(n)).cloned()
+                    }
+                    (Value::Object(object), Value::String(key)) => object.get(key).cloned(),
+                    _ => None,
+                }
+                .unwrap_or(Value::Null))
+            }
+            Node::Unary(UnaryOp::Not, operand) => match self.eval(operand)? {
+                Value::Bool(value) => Ok(Value::Bool(!value)),
+                other => Err(Self::type_error("'!'", &other)),
+            },
+            Node::Unary(UnaryOp::Negate, operand) => match self.eval(operand)?.as_f64() {
+                Some(number) => Ok(number_value(-number)),
+                None => Err(ExprError::Type("'-' can only be applied to numbers".to_string())),
+            },
+            Node::Binary(op, left, right) => self.binary(*op, left, right),
+            Node::Call(function, args) => {
+                let args = args.iter().map(|arg| self.eval(arg)).collect::<ExprResult<Vec<_>>>()?;
+                self.call(function, &args)
+            }
+        }
+    }
+
+    /// The value of a binary operation, short-circuiting `&&` and `||`
+    fn binary(&mut self, op: BinaryOp, left: &Node, right: &Node) -> ExprResult<Value> {
+        let what = format!("'{}'", op.symbol());
+        let left = self.eval(left)?;
+        if let BinaryOp::Or | BinaryOp::And = op {
+            let Value::Bool(left) = left else {
+                return Err(Self::type_error(&what, &left));
+            };
+            if left == (op == BinaryOp::Or) {
+                return Ok(Value::Bool(left));
+            }
+            return match self.eval(right)? {
+                Value::Bool(right) => Ok(Value::Bool(right)),
+                other => Err(Self::type_error(&what, &other)),
+            };
+        }
+        let right = self.eval(right)?;
+        match op {
+            BinaryOp::Eq => Ok(Value::Bool(equal(&left, &right))),
+            BinaryOp::Ne => Ok(Value::Bool(!equal(&left, &right))),
+            BinaryOp::Lt | BinaryOp::Le | BinaryOp::Gt | BinaryOp::Ge => {
+                let ordering = match (&left, &right) {
+                    (Value::Number(a), Value::Number(b)) => a.as_f64().partial_cmp(&b.as_f64()),
+                    (Value::String(a), Value::String(b)) => {
+                        self.spend(a.len().min(b.len()))?;
+                        Some(a.cmp(b))
+                    }
+                    _ => None,
+                }
+                .ok_or_else(|| {
+                    ExprError::Type(format!(
+                        "{what} can not compare {} with {}",
+                        ValueType::of(&left),
+                        ValueType::of(&right)
+                    ))
+                })?;
+                Ok(Value::Bool(match op {
+                    BinaryOp::Lt => ordering.is_lt(),
+                    BinaryOp::Le => ordering.is_le(),
+                    BinaryOp::Gt => ordering.is_gt(),
+                    _ => ordering.is_ge(),
+                }))
+            }
+            BinaryOp::In => self.contains(&right, &left).map(Value::Bool),
+            BinaryOp::Add => match (&left, &right) {
+                (Value::String(a), Value::String(b)) => {
+                    self.spend(a.len() + b.len())?;
+                    Ok(Value::String(format!("{a}{b}")))
+                }
+                _ => Self::arithmetic(op, &left, &right),
+            },
+            _ => Self::arithmetic(op, &left, &right),
+        }
+    }
+
+    /// The value of an arithmetic operation on numbers
+    fn arithmetic(op: BinaryOp, left: &Value, right: &Value) -> ExprResult<Value> {
+        let (Some(a), Some(b)) = (left.as_f64(), right.as_f64()) else {
+            return Err(ExprError::Type(format!(
+                "'{}' can not be applied to {} and {}",
+                op.symbol(),
+                ValueType::of(left),
+                ValueType::of(right)
+            )));
+        };
+        let result = match op {
+            BinaryOp::Add => a + b,
+            BinaryOp::Sub => a - b,
+            BinaryOp::Mul => a * b,
+            BinaryOp::Div | BinaryOp::Rem if b == 0.0 => {
+                return Err(ExprError::Eval("division by zero".to_string()));
+            }
+            BinaryOp::Div => a / b,
+            _ => a % b,
+        };
+        Ok(number_value(result))
+    }
+
+    /// Whether `haystack` contains `needle`: as an element of an array, a
+    /// key of an object or a substring of a string
+    fn contains(&mut self, haystack: &Value, needle: &Value) -> ExprResult<bool> {
+        match (haystack, needle) {
+            (Value::Array(items), _) => {
+                self.spend(items.len())?;
+                Ok(items.iter().any(|item| equal(item, needle)))
+            }
+            (Value::Object(object), Value::String(key)) => Ok(object.contains_key(key)),
+            (Value::Object(_), _) => Ok(false),
+            (Value::String(text), Value::String(part)) => {
+                self.spend(text.len())?;
+                Ok(text.contains(part.as_str()))
+            }
+            _ => Err(ExprError::Type(format!(
+                "'in' can not look for {} in {}",
+                ValueType::of(needle),
+                ValueType::of(haystack)
+            ))),
+        }
+    }
+
+    /// The result of a function call on evaluated arguments
+    fn call(&mut self, function: &Function, args: &[Value]) -> ExprResult<Value> {
+        let text = |index: usize| -> ExprResult<&str> {
+            args.get(index)
+                .and_then(Value::as_str)
+                .ok_or_else(|| ExprError::Type(format!("{} takes strings", function.name())))
+        };
+        let null = Value::Null;
+        let first = args.first().unwrap_or(&null);
+        match function {
+            Function::Len => {
+                let len = match first {
+                    Value::String(text) => {
+                        self.spend(text.len())?;
+                        text.chars().count()
+                    }
+                    Value::Array(items) => items.len(),
+                    Value::Object(object) => object.len(),
+                    other => return Err(Self::type_error("len", other)),
+                };
+                Ok(Value::from(len))
+            }
+            Function::Lower => {
+                self.spend(text(0)?.len())?;
+                Ok(Value::String(text(0)?.to_lowercase()))
+            }
+            Function::Upper => {
+                self.spend(text(0)?.len())?;
+                Ok(Value::String(text(0)?.to_uppercase()))
+            }
+            Function::Contains => self.contains(first, args.get(1).unwrap_or(&null)).map(Value::Bool),
+            Function::StartsWith => Ok(Value::Bool(text(0)?.starts_with(text(1)?))),
+            Function::EndsWith => Ok(Value::Bool(text(0)?.ends_with(text(1)?))),
+            Function::Matches(regex) => {
+                self.spend(text(0)?.len())?;
+                Ok(Value::Bool(regex.is_match(text(0)?)))
+            }
+            Function::Exists => Ok(Value::Bool(!first.is_null())),
+        }
+    }
+}
+
+/// A parsed and type checked expression
+///
+/// Expressions serialize as their source and are checked when deserialized,
+/// so configuration fields can hold them directly.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(try_from = "String", into = "String")]
+pub struct Expression {
+    /// Source the expression was parsed from
+    source: String,
+    /// Root of the parsed expression
+    root: Node,
+    /// Type of the expression's value
+    value_type: ValueType,
+}
+
+impl Expression {
+    /// Parses and type checks `source`
+    ///
+    /// # Errors
+    /// Returns a syntax error if `source` is not a well-formed expression, a
+    /// type error if it applies an operation or function to literals of the
+    /// wrong type, or a limit error if it is longer than [`MAX_SOURCE_LEN`],
+    /// has more than [`MAX_NODES`] nodes or is nested deeper than
+    /// [`MAX_DEPTH`] levels
+    pub fn parse(source: &str) -> ExprResult<Self> {
+        if source.len() > MAX_SOURCE_LEN {
+            return Err(ExprError::Limit(format!("longer than {MAX_SOURCE_LEN} bytes")));
+        }
+        let mut parser = Parser {
+            tokens: tokenize(source)?,
+            next: 0,
+            depth: 0,
+            nodes: 0,
+        };
+        let root = parser.expression(0)?;
+        if parser.peek() != &Token::End {
+            return Err(parser.error("unexpected input after the expression"));
+        }
+        let value_type = check(&root)?;
+        Ok(Self {
+            source: source.to_string(),
+            root,
+            value_type,
+        })
+    }
+
+    /// Parses `source` as a condition, an expression whose value is a boolean
+    ///
+    /// # Errors
+    /// Returns the errors of [`Expression::parse`], or a type error if the
+    /// value is known not to be a boolean
+    pub fn condition(source: &str) -> ExprResult<Self> {
+        let expression = Self::parse(source)?;
+        if !expression.value_type.fits(&[ValueType::Bool]) {
+            return Err(ExprError::Type(format!(
+                "a condition must be a bool, not {}",
+                expression.value_type
+            )));
+        }
+        Ok(expression)
+    }
+
+    /// The source the expression was parsed from
+    #[must_use]
+    pub fn source(&self) -> &str {
+        &self.source
+    }
+
+    /// The type of the expression's value, as far as it is known before
+    /// evaluation
+    #[must_use]
+    pub fn value_type(&self) -> ValueType {
+        self.value_type
+    }
+
+    /// Evaluates the expression against `input`, whose top-level keys are
+    /// the variables of the expression
+    ///
+    /// # Errors
+    /// Returns a type error if an operation meets a value of the wrong type,
+    /// an evaluation error such as a division by zero, or a limit error if
+    /// evaluation takes more than [`MAX_STEPS`] steps
+    pub fn evaluate(&self, input: &Value) -> ExprResult<Value> {
+        Evaluator { input, steps: MAX_STEPS }.eval(&self.root)
+    }
+
+    /// Evaluates the expression as a condition against `input`
+    ///
+    /// # Errors
+    /// Returns the errors of [`Expression::evaluate`], or a type error if the
+    /// value is not a boolean
+    pub fn test(&self, input: &Value) -> ExprResult<bool> {
+        match self.evaluate(input)? {
+            Value::Bool(value) => Ok(value),
+            other => Err(ExprError::Type(format!(
+                "a condition must be a bool, not {}",
+                ValueType::of(&other)
+            ))),
+        }
+    }
+}
+
+impl PartialEq for Expression {
+    fn eq(&self, other: &Self) -> bool {
+        self.source == other.source
+    }
+}
+
+impl Eq for Expression {}
+
+impl fmt::Display for Expression {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.source)
+    }
+}
+
+impl TryFrom<String> for Expression {
+    type Error = ExprError;
+
+    fn try_from(source: String) -> ExprResult<Self> {
+        Self::parse(&source)
+    }
+}
+
+impl From<Expression> for String {
+    fn from(expression: Expression) -> Self {
+        expression.source
+    }
+}
+
+/// The input of an expression: `fields` as its top-level variables
+#[must_use]
+pub fn input<'a>(fields: impl IntoIterator<Item = (&'a str, Value)>) -> Value {
+    Value::Object(fields.into_iter().map(|(key, value)| (key.to_string(), value)).collect::<Map<_, _>>())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_evaluates_conditions_over_json() {
+        let alert = json!({
+            "severity": "critical",
+            "component": "scheduler",
+            "labels": {"node-type": "gpu", "zone": "eu"},
+            "samples": [3, 5, 8],
+            "message": "Queue depth 120"
+        });
+        let test = |source: &str| Expression::condition(source).unwrap().test(&alert).unwrap();
+        assert!(test(r#"severity == "critical" && labels["node-type"] == 'gpu'"#));
+        assert!(test("len(samples) == 3 && samples[1] * 2 >= 10.0"));
+        assert!(test("'eu' in [labels.zone, 'us'] && 'zone' in labels && 'Queue' in message"));
+        assert!(test(r#"matches(message, "depth \\d+") && starts_with(lower(component), "sched")"#));
+        assert!(test("!exists(owner) && owner == null && !(1 + 2 * 3 != 7)"));
+        assert!(test("-samples[0] + 4 == 1 && 7 % 4 == 3 && 'a' + 'b' == 'ab'"));
+        assert_eq!(Expression::parse("samples[5].x").unwrap().evaluate(&alert).unwrap(), Value::Null);
+        assert_eq!(Expression::parse("len('héllo') / 2").unwrap().evaluate(&alert).unwrap(), json!(2.5));
+
+        // Errors found before evaluation
+        let error = |source: &str| Expression::condition(source).unwrap_err();
+        assert!(matches!(error("severity =="), ExprError::Syntax { .. }));
+        assert!(matches!(error("shell('rm')"), ExprError::Syntax { .. }));
+        assert!(matches!(error("'a' - 1 > 0"), ExprError::Type(_)));
+        assert!(matches!(error("len(1, 2) > 0"), ExprError::Type(_)));
+        assert!(matches!(error("1 + 2"), ExprError::Type(_)));
+        assert!(matches!(error(&"(".repeat(40)), ExprError::Limit(_)));
+        assert!(matches!(error(&vec!["1"; 300].join(" + ")), ExprError::Limit(_)));
+        assert!(matches!(Expression::parse(&"x".repeat(5000)), Err(ExprError::Limit(_))));
+
+        // Errors found when evaluating
+        let fails = |source: &str, input: &Value| Expression::parse(source).unwrap().evaluate(input).unwrap_err();
+        assert!(matches!(fails("severity && true", &alert), ExprError::Type(_)));
+        assert!(matches!(fails("samples[0] / 0", &alert), ExprError::Eval(_)));
+        let big = json!({"items": vec![0; 60_000]});
+        assert!(matches!(fails("1 in items || 2 in items", &big), ExprError::Limit(_)));
+        assert!(matches!(Expression::parse("samples").unwrap().test(&alert), Err(ExprError::Type(_))));
+
+        // Expressions serialize as their source
+        let parsed: Expression = serde_json::from_value(json!("zone == 'eu'")).unwrap();
+        assert_eq!(serde_json::to_value(&parsed).unwrap(), json!("zone == 'eu'"));
+        assert!(serde_json::from_value::<Expression>(json!("zone ==")).is_err());
+        assert!(parsed.test(&input([("zone", json!("eu"))])).unwrap());
+    }
+}
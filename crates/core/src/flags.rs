@@ -0,0 +1,297 @@
+//! Feature flags gating experimental behavior at runtime
+//!
+//! A [`Flag`] names a behavior and whether it is on when nothing says
+//! otherwise. The flags this workspace checks are declared here, so that
+//! every crate reads them the same way:
+//!
+//! ```ignore
+//! let target = FlagTarget::user("alice").in_workspace("lab");
+//! if !flags::AI_ASSISTANT.is_enabled(&target) { ... }
+//! ```
+//!
+//! A [`FlagSetting`] turns a flag on for everyone, or only for the listed
+//! users and workspaces. The process reads the settings from its
+//! [installed](install) [`FlagSet`]; the web server installs its configured
+//! settings merged with the ones admins changed, and installs them again
+//! whenever they change. Until then every flag has its default.
+
+use std::collections::{BTreeMap, BTreeSet};
+use std::sync::{Arc, PoisonError, RwLock};
+
+use serde::{Deserialize, Serialize};
+
+/// Scheduling tasks on remote agents
+pub const NEW_SCHEDULER: Flag = Flag::new(
+    "new_scheduler",
+    "Queue tasks for remote agents through /api/agents/tasks",
+    true,
+);
+
+/// Loading tools run by WebAssembly executors
+pub const WASM_PLUGINS: Flag = Flag::new(
+    "wasm_plugins",
+    "Load tool manifests with a WebAssembly executor",
+    false,
+);
+
+/// Planning and running requests with the assistant
+pub const AI_ASSISTANT: Flag = Flag::new(
+    "ai_assistant",
+    "Plan and run requests with the assistant under /api/assistant",
+    true,
+);
+
+/// Every flag declared here
+pub const KNOWN: &[Flag] = &[NEW_SCHEDULER, WASM_PLUGINS, AI_ASSISTANT];
+
+/// The known flag named `name`
+#[must_use]
+pub fn known(name: &str) -> Option<Flag> {
+    KNOWN.iter().copied().find(|flag| flag.name == name)
+}
+
+/// A behavior that can be turned on and off at runtime
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Flag {
+    /// Name settings refer to it by
+    name: &'static str,
+    /// What it turns on
+    description: &'static str,
+    /// Whether it is on without a setting
+    default: bool,
+}
+
+impl Flag {
+    /// A flag named `name`, on by default if `default` is true
+    #[must_use]
+    pub const fn new(name: &'static str, description: &'static str, default: bool) -> Self {
+        Self {
+            name,
+            description,
+            default,
+        }
+    }
+
+    /// Name settings refer to it by, e.g. `wasm_plugins`
+    #[must_use]
+    pub fn name(&self) -> &'static str {
+        self.name
+    }
+
+    /// What it turns on
+    #[must_use]
+    pub fn description(&self) -> &'static str {
+        self.description
+    }
+
+    /// Whether it is on without a setting
+    #[must_use]
+    pub fn default_enabled(&self) -> bool {
+        self.default
+    }
+
+    /// Whether the flag is on for `target` under the installed settings
+    #[must_use]
+    pub fn is_enabled(&self, target: &FlagTarget) -> bool {
+        current().is_enabled(self, target)
+    }
+}
+
+/// Who a flag is checked for
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct FlagTarget {
+    /// The user making the request
+    pub user: Option<String>,
+    /// The workspace the request works in
+    pub workspace: Option<String>,
+}
+
+impl FlagTarget {
+    /// Nobody in particular, e.g. for work done at startup
+    #[must_use]
+    pub fn anyone() -> Self {
+        Self::default()
+    }
+
+    /// The user `user`
+    #[must_use]
+    pub fn user(user: impl Into<String>) -> Self {
+        Self {
+            user: Some(user.into()),
+            workspace: None,
+        }
+    }
+
+    /// This target, working in `workspace`
+    #[must_use]
+    pub fn in_workspace(mut self, workspace: impl Into<String>) -> Self {
+        self.workspace = Some(workspace.into());
+        self
+    }
+}
+
+/// Who a flag is on for
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct FlagSetting {
+    /// On for everyone
+    pub enabled: bool,
+    /// Users it is on for, if it is not on for everyone
+    pub users: BTreeSet<String>,
+    /// Workspaces it is on for, if it is not on for everyone
+    pub workspaces: BTreeSet<String>,
+}
+
+impl FlagSetting {
+    /// On for everyone
+    #[must_use]
+    pub fn on() -> Self {
+        Self {
+            enabled: true,
+            ..Self::default()
+        }
+    }
+
+    /// Off for everyone
+    #[must_use]
+    pub fn off() -> Self {
+        Self::default()
+    }
+
+    /// Whether the setting turns the flag on for `target`
+    #[must_use]
+    pub fn applies_to(&self, target: &FlagTarget) -> bool {
+        self.enabled
+            || target
+                .user
+                .as_ref()
+                .is_some_and(|user| self.users.contains(user))
+            || target
+                .workspace
+                .as_ref()
+                .is_some_and(|workspace| self.workspaces.contains(workspace))
+    }
+}
+
+/// Settings of flags, by flag name
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct FlagSet {
+    /// Settings by flag name
+    settings: BTreeMap<String, FlagSetting>,
+}
+
+impl FlagSet {
+    /// No settings; every flag has its default
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// The setting of the flag named `name`
+    #[must_use]
+    pub fn get(&self, name: &str) -> Option<&FlagSetting> {
+        self.settings.get(name)
+    }
+
+    /// Sets the flag named `name`, replacing its setting
+    pub fn set(&mut self, name: impl Into<String>, setting: FlagSetting) {
+        self.settings.insert(name.into(), setting);
+    }
+
+    /// Removes the setting of the flag named `name`, returning it
+    pub fn remove(&mut self, name: &str) -> Option<FlagSetting> {
+        self.settings.remove(name)
+    }
+
+    /// Settings by flag name
+    pub fn settings(&self) -> impl Iterator<Item = (&str, &FlagSetting)> {
+        self.settings
+            .iter()
+            .map(|(name, setting)| (name.as_str(), setting))
+    }
+
+    /// These settings with `overrides` replacing the ones they name
+    #[must_use]
+    pub fn merged(&self, overrides: &FlagSet) -> Self {
+        let mut merged = self.clone();
+        for (name, setting) in overrides.settings() {
+            merged.set(name, setting.clone());
+        }
+        merged
+    }
+
+    /// Whether `flag` is on for `target`, its default without a setting
+    #[must_use]
+    pub fn is_enabled(&self, flag: &Flag, target: &FlagTarget) -> bool {
+        self.get(flag.name)
+            .map_or(flag.default, |setting| setting.applies_to(target))
+    }
+}
+
+impl FromIterator<(String, FlagSetting)> for FlagSet {
+    fn from_iter<I: IntoIterator<Item = (String, FlagSetting)>>(iter: I) -> Self {
+        Self {
+            settings: iter.into_iter().collect(),
+        }
+    }
+}
+
+/// The installed settings
+static CURRENT: RwLock<Option<Arc<FlagSet>>> = RwLock::new(None);
+
+/// Installs the settings flags are read from, replacing the previous ones
+pub fn install(flags: FlagSet) {
+    *CURRENT.write().unwrap_or_else(PoisonError::into_inner) = Some(Arc::new(flags));
+}
+
+/// The installed settings, empty until some are installed
+#[must_use]
+pub fn current() -> Arc<FlagSet> {
+    CURRENT
+        .read()
+        .unwrap_or_else(PoisonError::into_inner)
+        .clone()
+        .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_settings_target_users_and_workspaces() {
+        let alice = FlagTarget::user("alice");
+        let bob_in_lab = FlagTarget::user("bob").in_workspace("lab");
+        let mut flags = FlagSet::new();
+        assert!(!flags.is_enabled(&WASM_PLUGINS, &alice));
+        assert!(flags.is_enabled(&AI_ASSISTANT, &FlagTarget::anyone()));
+
+        flags.set(
+            "wasm_plugins",
+            FlagSetting {
+                users: BTreeSet::from(["alice".to_string()]),
+                workspaces: BTreeSet::from(["lab".to_string()]),
+                ..FlagSetting::off()
+            },
+        );
+        flags.set("ai_assistant", FlagSetting::off());
+        assert!(flags.is_enabled(&WASM_PLUGINS, &alice));
+        assert!(flags.is_enabled(&WASM_PLUGINS, &bob_in_lab));
+        assert!(!flags.is_enabled(&WASM_PLUGINS, &FlagTarget::user("bob")));
+        assert!(!flags.is_enabled(&AI_ASSISTANT, &alice));
+
+        let overrides: FlagSet = [("ai_assistant".to_string(), FlagSetting::on())]
+            .into_iter()
+            .collect();
+        let merged = flags.merged(&overrides);
+        assert!(merged.is_enabled(&AI_ASSISTANT, &alice));
+        assert_eq!(merged.get("wasm_plugins"), flags.get("wasm_plugins"));
+        assert_eq!(known("new_scheduler"), Some(NEW_SCHEDULER));
+        assert_eq!(known("new-scheduler"), None);
+
+        install(merged);
+        assert!(WASM_PLUGINS.is_enabled(&bob_in_lab));
+        assert!(!WASM_PLUGINS.is_enabled(&FlagTarget::anyone()));
+    }
+}
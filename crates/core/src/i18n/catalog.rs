@@ -0,0 +1,492 @@
+//! Message catalogs in a subset of the Fluent syntax
+//!
+//! A catalog file holds one message per entry. Values continue on indented
+//! lines, `{ $name }` inserts an argument, and a select expression picks a
+//! variant by the plural category or exact value of a number, or by the
+//! value of a string; the variant marked `*` is the default:
+//!
+//! ```text
+//! # Comments start with a hash
+//! greeting = Hello, { $name }!
+//! jobs-running =
+//!     { $count ->
+//!         [0] No jobs are running
+//!         [one] One job is running
+//!        *[other] { $count } jobs are running
+//!     }
+//! ```
+//!
+//! Terms, attributes and functions of full Fluent are not supported.
+
+use std::collections::BTreeMap;
+use std::fmt;
+use std::fs;
+use std::path::Path;
+
+use super::{Args, I18nError, Locale, CATALOG_EXTENSION};
+
+/// CLDR plural category of a number
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum PluralCategory {
+    /// Zero, in languages that single it out
+    Zero,
+    /// Singular
+    One,
+    /// Dual
+    Two,
+    /// Paucal, e.g. 2 to 4 in Slavic languages
+    Few,
+    /// Large numbers in languages that single them out
+    Many,
+    /// Everything else
+    Other,
+}
+
+impl PluralCategory {
+    /// Parses a variant key naming a category
+    fn from_key(key: &str) -> Option<Self> {
+        match key {
+            "zero" => Some(Self::Zero),
+            "one" => Some(Self::One),
+            "two" => Some(Self::Two),
+            "few" => Some(Self::Few),
+            "many" => Some(Self::Many),
+            "other" => Some(Self::Other),
+            _ => None,
+        }
+    }
+}
+
+/// Plural category of `n` in `language`, per the CLDR cardinal rules
+///
+/// Covers the integer rules of common languages; fractions are `Other`
+/// except where a language treats them as singular.
+#[must_use]
+pub fn plural_category(language: &str, n: f64) -> PluralCategory {
+    #[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
+    let i = n.abs().trunc() as u64;
+    let integer = n.fract() == 0.0;
+    let (mod10, mod100) = (i % 10, i % 100);
+    match language {
+        "fr" | "pt" if i <= 1 => PluralCategory::One,
+        "ru" | "uk" | "be" if integer => {
+            if mod10 == 1 && mod100 != 11 {
+                PluralCategory::One
+            } else if (2..=4).contains(&mod10) && !(12..=14).contains(&mod100) {
+                PluralCategory::Few
+            } else {
+                PluralCategory::Many
+            }
+        }
+        "pl" if integer => {
+            if i == 1 {
+                PluralCategory::One
+            } else if (2..=4).contains(&mod10) && !(12..=14).contains(&mod100) {
+                PluralCategory::Few
+            } else {
+                PluralCategory::Many
+            }
+        }
+        "cs" | "sk" if integer => match i {
+            1 => PluralCategory::One,
+            2..=4 => PluralCategory::Few,
+            _ => PluralCategory::Other,
+        },
+        "ar" if integer => match (i, mod100) {
+            (0, _) => PluralCategory::Zero,
+            (1, _) => PluralCategory::One,
+            (2, _) => PluralCategory::Two,
+            (_, 3..=10) => PluralCategory::Few,
+            (_, 11..=99) => PluralCategory::Many,
+            _ => PluralCategory::Other,
+        },
+        // Languages without plural forms, and fractions in the ones above
+        "ja" | "zh" | "ko" | "vi" | "th" | "id" | "ms" | "tr" | "ru" | "uk" | "be" | "pl" | "cs" | "sk" | "ar"
+        | "fr" | "pt" => PluralCategory::Other,
+        _ if integer && i == 1 => PluralCategory::One,
+        _ => PluralCategory::Other,
+    }
+}
+
+/// Key of a select variant
+#[derive(Debug, Clone, PartialEq)]
+enum VariantKey {
+    /// An exact number
+    Number(f64),
+    /// A plural category
+    Category(PluralCategory),
+    /// A string value
+    Name(String),
+}
+
+/// A part of a message
+#[derive(Debug, Clone, PartialEq)]
+enum Element {
+    /// Literal text
+    Text(String),
+    /// Value of an argument
+    Variable(String),
+    /// One of several variants, chosen by an argument
+    Select {
+        /// Argument selecting the variant
+        selector: String,
+        /// Variants in catalog order
+        variants: Vec<(VariantKey, Pattern)>,
+        /// Index of the default variant
+        default: usize,
+    },
+}
+
+/// Parsed value of a message
+#[derive(Debug, Clone, PartialEq, Default)]
+struct Pattern(Vec<Element>);
+
+impl Pattern {
+    /// Formats the pattern with `args` under the plural rules of `language`
+    ///
+    /// A missing argument is shown as its placeholder.
+    fn format(&self, language: &str, args: &Args<'_>, out: &mut String) {
+        for element in &self.0 {
+            match element {
+                Element::Text(text) => out.push_str(text),
+                Element::Variable(name) => {
+                    if let Some(value) = argument(args, name) {
+                        out.push_str(&value);
+                    } else {
+                        out.push_str("{$");
+                        out.push_str(name);
+                        out.push('}');
+                    }
+                }
+                Element::Select { selector, variants, default } => {
+                    let value = argument(args, selector);
+                    let number = value.as_deref().and_then(|value| value.parse::<f64>().ok());
+                    let chosen = value
+                        .as_deref()
+                        .and_then(|value| {
+                            variants.iter().position(|(key, _)| match (key, number) {
+                                (VariantKey::Number(key), Some(n)) => (*key - n).abs() < f64::EPSILON,
+                                (VariantKey::Name(key), _) => key == value,
+                                _ => false,
+                            })
+                        })
+                        .or_else(|| {
+                            let category = plural_category(language, number?);
+                            variants
+                                .iter()
+                                .position(|(key, _)| *key == VariantKey::Category(category))
+                        })
+                        .unwrap_or(*default);
+                    variants[chosen].1.format(language, args, out);
+                }
+            }
+        }
+    }
+}
+
+/// The value of an argument as text
+fn argument(args: &Args<'_>, name: &str) -> Option<String> {
+    args.iter()
+        .find(|(arg, _)| *arg == name)
+        .map(|(_, value)| value.to_string())
+}
+
+/// A message and the source text of its value
+#[derive(Debug, Clone, PartialEq)]
+struct Message {
+    /// Parsed value
+    pattern: Pattern,
+    /// Value as written in the catalog, continuation lines included
+    source: String,
+}
+
+/// Messages of one domain in one locale
+#[derive(Debug, Clone, PartialEq)]
+pub struct Catalog {
+    /// Locale of the messages
+    locale: Locale,
+    /// Domain of the messages, e.g. `squirrel` or a plugin name
+    domain: String,
+    /// Messages by key
+    messages: BTreeMap<String, Message>,
+}
+
+impl Catalog {
+    /// An empty catalog
+    #[must_use]
+    pub fn new(locale: Locale, domain: impl Into<String>) -> Self {
+        Self {
+            locale,
+            domain: domain.into(),
+            messages: BTreeMap::new(),
+        }
+    }
+
+    /// Parses catalog source
+    ///
+    /// # Errors
+    ///
+    /// Returns an error naming the line of the first malformed entry
+    pub fn parse(locale: Locale, domain: impl Into<String>, source: &str) -> Result<Self, I18nError> {
+        let mut catalog = Self::new(locale, domain);
+        let lines: Vec<&str> = source.lines().collect();
+        let mut index = 0;
+        while index < lines.len() {
+            let line = lines[index];
+            let start = index + 1;
+            index += 1;
+            if line.trim().is_empty() || line.trim_start().starts_with('#') {
+                continue;
+            }
+            let error = |message: &str| I18nError::Parse {
+                domain: catalog.domain.clone(),
+                line: start,
+                message: message.to_string(),
+            };
+            if line.starts_with(char::is_whitespace) {
+                return Err(error("indented line outside a message"));
+            }
+            let (key, first) = line.split_once('=').ok_or_else(|| error("expected 'key = value'"))?;
+            let key = key.trim();
+            let valid_key = key.chars().next().is_some_and(|c| c.is_ascii_alphabetic())
+                && key.chars().all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_');
+            if !valid_key {
+                return Err(error(&format!("invalid message key '{key}'")));
+            }
+
+            let mut value_lines = Vec::new();
+            if !first.trim().is_empty() {
+                value_lines.push(first.trim());
+            }
+            while index < lines.len() && (lines[index].starts_with(char::is_whitespace) || lines[index].is_empty()) {
+                if !lines[index].trim().is_empty() {
+                    value_lines.push(lines[index].trim());
+                }
+                index += 1;
+            }
+            if value_lines.is_empty() {
+                return Err(error(&format!("message '{key}' has no value")));
+            }
+            let source = value_lines.join("\n");
+            let pattern = Parser::new(&source)
+                .pattern(false)
+                .map_err(|message| error(&format!("in '{key}': {message}")))?;
+            catalog.messages.insert(key.to_string(), Message { pattern, source });
+        }
+        Ok(catalog)
+    }
+
+    /// Loads a catalog file stored as `<locale>/<domain>.ftl`
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the path does not name a locale and domain, or
+    /// the file cannot be read or parsed
+    pub fn load(path: &Path) -> Result<Self, I18nError> {
+        let invalid = || I18nError::Io(format!("{} is not a <locale>/<domain>.{CATALOG_EXTENSION} file", path.display()));
+        let domain = path
+            .file_stem()
+            .and_then(|stem| stem.to_str())
+            .filter(|_| path.extension().is_some_and(|ext| ext == CATALOG_EXTENSION))
+            .ok_or_else(invalid)?;
+        let locale = path
+            .parent()
+            .and_then(Path::file_name)
+            .and_then(|name| name.to_str())
+            .ok_or_else(invalid)?
+            .parse()?;
+        let source = fs::read_to_string(path).map_err(|e| I18nError::Io(format!("{}: {e}", path.display())))?;
+        Self::parse(locale, domain, &source)
+    }
+
+    /// Locale of the messages
+    #[must_use]
+    pub fn locale(&self) -> &Locale {
+        &self.locale
+    }
+
+    /// Domain of the messages
+    #[must_use]
+    pub fn domain(&self) -> &str {
+        &self.domain
+    }
+
+    /// Keys of the messages, sorted
+    pub fn keys(&self) -> impl Iterator<Item = &str> {
+        self.messages.keys().map(String::as_str)
+    }
+
+    /// Whether the catalog has a message
+    #[must_use]
+    pub fn contains(&self, key: &str) -> bool {
+        self.messages.contains_key(key)
+    }
+
+    /// Value of a message as written in the catalog
+    #[must_use]
+    pub fn source(&self, key: &str) -> Option<&str> {
+        self.messages.get(key).map(|message| message.source.as_str())
+    }
+
+    /// Formats a message with arguments
+    #[must_use]
+    pub fn format(&self, key: &str, args: &Args<'_>) -> Option<String> {
+        let message = self.messages.get(key)?;
+        let mut out = String::new();
+        message.pattern.format(self.locale.language(), args, &mut out);
+        Some(out)
+    }
+
+    /// Adds the messages of `other`, replacing messages with the same key
+    pub fn merge(&mut self, other: Self) {
+        self.messages.extend(other.messages);
+    }
+}
+
+impl fmt::Display for Catalog {
+    /// Writes the catalog in its source syntax
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (key, message) in &self.messages {
+            write_entry(f, key, &message.source)?;
+        }
+        Ok(())
+    }
+}
+
+/// Writes one catalog entry, indenting continuation lines
+pub(super) fn write_entry(out: &mut impl fmt::Write, key: &str, source: &str) -> fmt::Result {
+    let mut lines = source.lines();
+    if source.contains('\n') {
+        writeln!(out, "{key} =")?;
+    } else {
+        writeln!(out, "{key} = {}", lines.next().unwrap_or_default())?;
+    }
+    for line in lines {
+        let indent = if line.starts_with('[') || line.starts_with("*[") { "        " } else { "    " };
+        writeln!(out, "{indent}{line}")?;
+    }
+    Ok(())
+}
+
+/// Recursive descent parser of message values
+struct Parser<'a> {
+    /// Remaining input
+    rest: &'a str,
+}
+
+impl<'a> Parser<'a> {
+    /// A parser of `source`
+    fn new(source: &'a str) -> Self {
+        Self { rest: source }
+    }
+
+    /// Parses text and placeables, up to a line end in a variant or a
+    /// closing brace
+    fn pattern(&mut self, in_variant: bool) -> Result<Pattern, String> {
+        let mut elements = Vec::new();
+        let mut text = String::new();
+        while let Some(c) = self.rest.chars().next() {
+            match c {
+                '{' => {
+                    self.rest = &self.rest[1..];
+                    if !text.is_empty() {
+                        elements.push(Element::Text(std::mem::take(&mut text)));
+                    }
+                    elements.push(self.placeable()?);
+                }
+                '}' | '\n' if in_variant => break,
+                '}' => return Err("unmatched '}'".to_string()),
+                c => {
+                    text.push(c);
+                    self.rest = &self.rest[c.len_utf8()..];
+                }
+            }
+        }
+        if in_variant {
+            let trimmed = text.trim_end().len();
+            text.truncate(trimmed);
+        }
+        if !text.is_empty() {
+            elements.push(Element::Text(text));
+        }
+        Ok(Pattern(elements))
+    }
+
+    /// Parses what follows an opening brace, through the closing one
+    fn placeable(&mut self) -> Result<Element, String> {
+        self.skip_whitespace();
+        let element = if let Some(rest) = self.rest.strip_prefix('"') {
+            let end = rest.find('"').ok_or("unterminated string literal")?;
+            let literal = rest[..end].to_string();
+            self.rest = &rest[end + 1..];
+            Element::Text(literal)
+        } else if let Some(rest) = self.rest.strip_prefix('$') {
+            self.rest = rest;
+            let name = self.identifier().ok_or("expected a variable name after '$'")?;
+            self.skip_whitespace();
+            if let Some(rest) = self.rest.strip_prefix("->") {
+                self.rest = rest;
+                self.select(name)?
+            } else {
+                Element::Variable(name)
+            }
+        } else {
+            return Err("expected '$variable' or a string literal in '{ }'".to_string());
+        };
+        self.skip_whitespace();
+        self.rest = self.rest.strip_prefix('}').ok_or("expected '}'")?;
+        Ok(element)
+    }
+
+    /// Parses the variants of a select expression, up to its closing brace
+    fn select(&mut self, selector: String) -> Result<Element, String> {
+        let mut variants = Vec::new();
+        let mut default = None;
+        loop {
+            self.skip_whitespace();
+            if self.rest.starts_with('}') || self.rest.is_empty() {
+                break;
+            }
+            if let Some(rest) = self.rest.strip_prefix('*') {
+                if default.is_some() {
+                    return Err("more than one default variant".to_string());
+                }
+                default = Some(variants.len());
+                self.rest = rest;
+            }
+            let rest = self.rest.strip_prefix('[').ok_or("expected '[' starting a variant")?;
+            let end = rest.find(']').ok_or("expected ']' closing a variant key")?;
+            let key = rest[..end].trim();
+            let key = if let Ok(number) = key.parse::<f64>() {
+                VariantKey::Number(number)
+            } else if let Some(category) = PluralCategory::from_key(key) {
+                VariantKey::Category(category)
+            } else {
+                VariantKey::Name(key.to_string())
+            };
+            self.rest = rest[end + 1..].trim_start_matches([' ', '\t']);
+            variants.push((key, self.pattern(true)?));
+        }
+        let default = default.ok_or("select expression without a '*' default variant")?;
+        Ok(Element::Select { selector, variants, default })
+    }
+
+    /// Parses an identifier
+    fn identifier(&mut self) -> Option<String> {
+        let end = self
+            .rest
+            .find(|c: char| !(c.is_ascii_alphanumeric() || c == '-' || c == '_'))
+            .unwrap_or(self.rest.len());
+        if end == 0 {
+            return None;
+        }
+        let name = self.rest[..end].to_string();
+        self.rest = &self.rest[end..];
+        Some(name)
+    }
+
+    /// Skips spaces and line ends
+    fn skip_whitespace(&mut self) {
+        self.rest = self.rest.trim_start();
+    }
+}
@@ -0,0 +1,149 @@
+//! Locale identifiers and negotiation
+
+use std::fmt;
+use std::str::FromStr;
+
+use super::I18nError;
+
+/// Environment variables naming the user's locale, in order of precedence
+pub const LOCALE_ENV_VARS: [&str; 4] = ["SQUIRREL_LOCALE", "LC_ALL", "LC_MESSAGES", "LANG"];
+
+/// A language with an optional region, e.g. `de-AT`
+///
+/// Parsing accepts BCP 47 tags (`pt-BR`) as well as POSIX locale names
+/// (`pt_BR.UTF-8`); script and variant subtags are ignored.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct Locale {
+    /// Lowercase ISO 639 language code
+    language: String,
+    /// Uppercase ISO 3166 region code
+    region: Option<String>,
+}
+
+impl Locale {
+    /// English, the locale messages fall back to
+    #[must_use]
+    pub fn english() -> Self {
+        Self {
+            language: "en".to_string(),
+            region: None,
+        }
+    }
+
+    /// Language code, e.g. `de`
+    #[must_use]
+    pub fn language(&self) -> &str {
+        &self.language
+    }
+
+    /// Region code, e.g. `AT`
+    #[must_use]
+    pub fn region(&self) -> Option<&str> {
+        self.region.as_deref()
+    }
+
+    /// The locale without its region
+    #[must_use]
+    pub fn without_region(&self) -> Self {
+        Self {
+            language: self.language.clone(),
+            region: None,
+        }
+    }
+
+    /// Locales to look messages up in, most specific first
+    #[must_use]
+    pub fn fallbacks(&self) -> Vec<Self> {
+        if self.region.is_some() {
+            vec![self.clone(), self.without_region()]
+        } else {
+            vec![self.clone()]
+        }
+    }
+
+    /// The first locale named by [`LOCALE_ENV_VARS`]
+    ///
+    /// The `C` and `POSIX` locales name no language and are skipped.
+    #[must_use]
+    pub fn from_env() -> Option<Self> {
+        LOCALE_ENV_VARS
+            .iter()
+            .filter_map(|name| std::env::var(name).ok())
+            .find_map(|value| value.parse().ok())
+    }
+}
+
+impl FromStr for Locale {
+    type Err = I18nError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        // Drop a POSIX encoding or modifier, as in `de_DE.UTF-8@euro`
+        let tag = s.split(['.', '@']).next().unwrap_or_default().trim();
+        let mut subtags = tag.split(['-', '_']);
+        let language = subtags.next().unwrap_or_default();
+        if !(2..=3).contains(&language.len()) || !language.chars().all(|c| c.is_ascii_alphabetic()) {
+            return Err(I18nError::InvalidLocale(s.to_string()));
+        }
+        let region = subtags.find(|subtag| {
+            (subtag.len() == 2 && subtag.chars().all(|c| c.is_ascii_alphabetic()))
+                || (subtag.len() == 3 && subtag.chars().all(|c| c.is_ascii_digit()))
+        });
+        Ok(Self {
+            language: language.to_ascii_lowercase(),
+            region: region.map(str::to_ascii_uppercase),
+        })
+    }
+}
+
+impl fmt::Display for Locale {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.region {
+            Some(region) => write!(f, "{}-{}", self.language, region),
+            None => f.write_str(&self.language),
+        }
+    }
+}
+
+/// The locale of `available` best matching an `Accept-Language` header
+///
+/// Ranges are tried by descending quality. A range matches an available
+/// locale exactly, or by language alone, preferring a locale without a
+/// region; `*` matches the first available locale. Ranges with quality 0
+/// are never chosen.
+#[must_use]
+pub fn negotiate<'a>(accept_language: &str, available: &'a [Locale]) -> Option<&'a Locale> {
+    let mut ranges: Vec<(&str, f32)> = accept_language
+        .split(',')
+        .filter_map(|range| {
+            let mut parts = range.split(';');
+            let tag = parts.next()?.trim();
+            let quality = parts
+                .filter_map(|param| param.trim().strip_prefix("q="))
+                .find_map(|q| q.trim().parse::<f32>().ok())
+                .unwrap_or(1.0);
+            (!tag.is_empty() && quality > 0.0).then_some((tag, quality))
+        })
+        .collect();
+    // Stable, so ranges of equal quality keep the client's order
+    ranges.sort_by(|a, b| b.1.total_cmp(&a.1));
+
+    for (tag, _) in ranges {
+        if tag == "*" {
+            if let Some(first) = available.first() {
+                return Some(first);
+            }
+            continue;
+        }
+        let Ok(wanted) = tag.parse::<Locale>() else {
+            continue;
+        };
+        if let Some(exact) = available.iter().find(|locale| **locale == wanted) {
+            return Some(exact);
+        }
+        let same_language = || available.iter().filter(|locale| locale.language == wanted.language);
+        if let Some(found) = same_language().find(|locale| locale.region.is_none()).or_else(|| same_language().next()) {
+            return Some(found);
+        }
+    }
+    None
+}
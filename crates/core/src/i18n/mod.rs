@@ -0,0 +1,537 @@
+//! Message catalogs and localization
+//!
+//! User-facing text is looked up by key in [`Catalog`]s, one per domain and
+//! locale, written in a subset of the Fluent syntax (see [`catalog`]).
+//! Squirrel's own messages form the [`DEFAULT_DOMAIN`] and are compiled in;
+//! plugins ship their own domains as `locales/<locale>/<domain>.ftl` files,
+//! which [`Localizer::load_dir`] reads.
+//!
+//! A [`Localizer`] resolves a message through the fallbacks of the requested
+//! locale, then its default locale, then English. A message found nowhere
+//! is shown as its key, so missing translations never hide output. The CLI
+//! selects its locale from its configuration or the environment
+//! ([`Locale::from_env`]); the web server negotiates one per request from
+//! `Accept-Language` ([`negotiate`]).
+//!
+//! ```
+//! use squirrel_core::i18n::{localizer, Locale};
+//! use squirrel_core::tr;
+//!
+//! let german: Locale = "de-AT".parse().unwrap();
+//! assert_eq!(
+//!     localizer().message(&german, "squirrel", "api-error-not-found", &[]),
+//!     "Nicht gefunden"
+//! );
+//! let count = 2;
+//! println!("{}", tr!("i18n-missing-messages", count = count));
+//! ```
+//!
+//! [`extract_keys`] finds the keys passed to [`tr!`] in source files, so
+//! catalogs can be generated and checked for missing messages.
+
+use std::collections::BTreeMap;
+use std::fmt;
+use std::fs;
+use std::path::Path;
+use std::sync::{OnceLock, PoisonError, RwLock};
+
+use thiserror::Error;
+
+/// Catalog files and their syntax
+pub mod catalog;
+
+/// Locale identifiers and negotiation
+mod locale;
+
+pub use catalog::{plural_category, Catalog, PluralCategory};
+pub use locale::{negotiate, Locale, LOCALE_ENV_VARS};
+
+/// Domain of Squirrel's own messages
+pub const DEFAULT_DOMAIN: &str = "squirrel";
+
+/// Extension of catalog files
+pub const CATALOG_EXTENSION: &str = "ftl";
+
+/// Catalogs of the default domain compiled into every build
+const BUILTIN_CATALOGS: [(&str, &str); 2] = [
+    ("en", include_str!("../../locales/en/squirrel.ftl")),
+    ("de", include_str!("../../locales/de/squirrel.ftl")),
+];
+
+/// Named arguments of a message
+pub type Args<'a> = [(&'a str, &'a dyn fmt::Display)];
+
+/// Errors of message catalogs
+#[derive(Debug, Error)]
+pub enum I18nError {
+    /// A catalog entry is malformed
+    #[error("Catalog {domain}, line {line}: {message}")]
+    Parse {
+        /// Domain of the catalog
+        domain: String,
+        /// Line of the entry, counting from 1
+        line: usize,
+        /// What is wrong with it
+        message: String,
+    },
+    /// A locale name could not be parsed
+    #[error("Invalid locale '{0}'")]
+    InvalidLocale(String),
+    /// A catalog file could not be read
+    #[error("Catalog file error: {0}")]
+    Io(String),
+}
+
+/// Looks up messages in the catalogs of every domain and locale
+#[derive(Debug)]
+pub struct Localizer {
+    /// Locale tried after the requested one
+    default: Locale,
+    /// Locale of messages requested without one
+    current: RwLock<Locale>,
+    /// Catalogs by domain and locale
+    catalogs: RwLock<BTreeMap<(String, Locale), Catalog>>,
+}
+
+impl Localizer {
+    /// A localizer without catalogs
+    #[must_use]
+    pub fn new(default: Locale) -> Self {
+        Self {
+            current: RwLock::new(default.clone()),
+            default,
+            catalogs: RwLock::new(BTreeMap::new()),
+        }
+    }
+
+    /// A localizer with the compiled-in catalogs of the default domain
+    #[must_use]
+    pub fn with_builtin(default: Locale) -> Self {
+        let localizer = Self::new(default);
+        for (locale, source) in BUILTIN_CATALOGS {
+            let catalog = locale
+                .parse()
+                .and_then(|locale| Catalog::parse(locale, DEFAULT_DOMAIN, source));
+            match catalog {
+                Ok(catalog) => localizer.add_catalog(catalog),
+                Err(e) => tracing::error!("Built-in {} catalog is invalid: {}", locale, e),
+            }
+        }
+        localizer
+    }
+
+    /// Adds a catalog, merging it into one of the same domain and locale
+    pub fn add_catalog(&self, catalog: Catalog) {
+        let mut catalogs = self.catalogs.write().unwrap_or_else(PoisonError::into_inner);
+        let key = (catalog.domain().to_string(), catalog.locale().clone());
+        match catalogs.get_mut(&key) {
+            Some(existing) => existing.merge(catalog),
+            None => {
+                catalogs.insert(key, catalog);
+            }
+        }
+    }
+
+    /// Adds the `<locale>/<domain>.ftl` catalogs under `dir`
+    ///
+    /// Returns the number of catalogs added. A missing directory adds none.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if a catalog cannot be read or parsed; catalogs
+    /// read before it are kept
+    pub fn load_dir(&self, dir: &Path) -> Result<usize, I18nError> {
+        if !dir.is_dir() {
+            return Ok(0);
+        }
+        let mut count = 0;
+        for locale_dir in read_dir(dir)? {
+            if !locale_dir.is_dir() {
+                continue;
+            }
+            for path in read_dir(&locale_dir)? {
+                if path.extension().is_some_and(|ext| ext == CATALOG_EXTENSION) {
+                    self.add_catalog(Catalog::load(&path)?);
+                    count += 1;
+                }
+            }
+        }
+        Ok(count)
+    }
+
+    /// Locales with a catalog of `domain`, sorted
+    #[must_use]
+    pub fn locales(&self, domain: &str) -> Vec<Locale> {
+        self.catalogs
+            .read()
+            .unwrap_or_else(PoisonError::into_inner)
+            .keys()
+            .filter(|(catalog_domain, _)| catalog_domain == domain)
+            .map(|(_, locale)| locale.clone())
+            .collect()
+    }
+
+    /// A copy of the catalog of a domain in a locale
+    #[must_use]
+    pub fn catalog(&self, domain: &str, locale: &Locale) -> Option<Catalog> {
+        self.catalogs
+            .read()
+            .unwrap_or_else(PoisonError::into_inner)
+            .get(&(domain.to_string(), locale.clone()))
+            .cloned()
+    }
+
+    /// Locale of messages requested without one
+    #[must_use]
+    pub fn locale(&self) -> Locale {
+        self.current.read().unwrap_or_else(PoisonError::into_inner).clone()
+    }
+
+    /// Sets the locale of messages requested without one
+    pub fn set_locale(&self, locale: Locale) {
+        *self.current.write().unwrap_or_else(PoisonError::into_inner) = locale;
+    }
+
+    /// The locale of the default domain best matching an `Accept-Language`
+    /// header, or the default locale
+    #[must_use]
+    pub fn negotiate(&self, accept_language: &str) -> Locale {
+        let available = self.locales(DEFAULT_DOMAIN);
+        negotiate(accept_language, &available)
+            .cloned()
+            .unwrap_or_else(|| self.default.clone())
+    }
+
+    /// Formats a message of `domain` in `locale`
+    ///
+    /// Falls back to the default locale and English, and to the key itself
+    /// when no catalog has the message.
+    #[must_use]
+    pub fn message(&self, locale: &Locale, domain: &str, key: &str, args: &Args<'_>) -> String {
+        let catalogs = self.catalogs.read().unwrap_or_else(PoisonError::into_inner);
+        let mut candidates = locale.fallbacks();
+        candidates.extend(self.default.fallbacks());
+        candidates.push(Locale::english());
+        candidates
+            .iter()
+            .filter_map(|locale| catalogs.get(&(domain.to_string(), locale.clone())))
+            .find_map(|catalog| catalog.format(key, args))
+            .unwrap_or_else(|| key.to_string())
+    }
+
+    /// Formats a message of `domain` in the current locale
+    #[must_use]
+    pub fn tr(&self, domain: &str, key: &str, args: &Args<'_>) -> String {
+        self.message(&self.locale(), domain, key, args)
+    }
+}
+
+/// Paths of the entries of a directory, sorted
+fn read_dir(dir: &Path) -> Result<Vec<std::path::PathBuf>, I18nError> {
+    let io = |e: std::io::Error| I18nError::Io(format!("{}: {e}", dir.display()));
+    let mut paths = fs::read_dir(dir)
+        .map_err(io)?
+        .map(|entry| entry.map(|entry| entry.path()))
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(io)?;
+    paths.sort();
+    Ok(paths)
+}
+
+/// The process-wide localizer, holding the built-in catalogs
+///
+/// Its default locale is English; callers set the current locale with
+/// [`Localizer::set_locale`].
+pub fn localizer() -> &'static Localizer {
+    static LOCALIZER: OnceLock<Localizer> = OnceLock::new();
+    LOCALIZER.get_or_init(|| Localizer::with_builtin(Locale::english()))
+}
+
+/// Formats a message in the current locale of the process-wide localizer
+///
+/// `tr!("key", name = value, ...)` looks the key up in the default domain;
+/// `tr!(domain: "my-plugin", "key", ...)` in another. Argument values may
+/// be anything implementing `Display`.
+#[macro_export]
+macro_rules! tr {
+    (domain: $domain:expr, $key:expr $(, $name:ident = $value:expr)* $(,)?) => {
+        $crate::i18n::localizer().tr(
+            $domain,
+            $key,
+            &[$((stringify!($name), &$value as &dyn ::std::fmt::Display)),*],
+        )
+    };
+    ($key:expr $(, $name:ident = $value:expr)* $(,)?) => {
+        $crate::i18n::localizer().tr(
+            $crate::i18n::DEFAULT_DOMAIN,
+            $key,
+            &[$((stringify!($name), &$value as &dyn ::std::fmt::Display)),*],
+        )
+    };
+}
+
+/// A message key used in source code
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+pub struct ExtractedKey {
+    /// Domain of the message
+    pub domain: String,
+    /// Message key
+    pub key: String,
+    /// Line of the use, counting from 1
+    pub line: usize,
+}
+
+/// Finds the keys passed as string literals to [`tr!`] in source text
+#[must_use]
+pub fn extract_keys(source: &str) -> Vec<ExtractedKey> {
+    let mut keys = Vec::new();
+    let mut offset = 0;
+    while let Some(found) = source[offset..].find("tr!(") {
+        let start = offset + found;
+        offset = start + "tr!(".len();
+        // Skip identifiers merely ending in `tr`, such as `attr!(`
+        if source[..start]
+            .chars()
+            .next_back()
+            .is_some_and(|c| c.is_alphanumeric() || c == '_')
+        {
+            continue;
+        }
+        let mut rest = source[offset..].trim_start();
+        let mut domain = DEFAULT_DOMAIN.to_string();
+        if let Some(after) = rest.strip_prefix("domain:") {
+            let Some((literal, after)) = string_literal(after.trim_start()) else {
+                continue;
+            };
+            domain = literal.to_string();
+            let Some(after) = after.trim_start().strip_prefix(',') else {
+                continue;
+            };
+            rest = after.trim_start();
+        }
+        if let Some((key, _)) = string_literal(rest) {
+            keys.push(ExtractedKey {
+                domain,
+                key: key.to_string(),
+                line: source[..start].matches('\n').count() + 1,
+            });
+        }
+    }
+    keys
+}
+
+/// Splits a leading string literal without escapes off `text`
+fn string_literal(text: &str) -> Option<(&str, &str)> {
+    let rest = text.strip_prefix('"')?;
+    let end = rest.find('"')?;
+    let literal = &rest[..end];
+    (!literal.contains('\\')).then(|| (literal, &rest[end + 1..]))
+}
+
+/// Writes a catalog template of `keys`
+///
+/// Messages already in `existing` keep their value. New messages get their
+/// key as a placeholder value under a comment naming where they are used,
+/// and messages of `existing` no longer used are kept under a comment.
+///
+/// # Errors
+///
+/// Returns an error if writing fails
+pub fn write_template(
+    out: &mut impl fmt::Write,
+    keys: &[(ExtractedKey, String)],
+    existing: Option<&Catalog>,
+) -> fmt::Result {
+    let mut uses: BTreeMap<&str, Vec<String>> = BTreeMap::new();
+    for (key, file) in keys {
+        uses.entry(key.key.as_str())
+            .or_default()
+            .push(format!("{}:{}", file, key.line));
+    }
+    for (key, places) in &uses {
+        if let Some(source) = existing.and_then(|catalog| catalog.source(key)) {
+            catalog::write_entry(out, key, source)?;
+        } else {
+            writeln!(out, "# untranslated, used at {}", places.join(", "))?;
+            catalog::write_entry(out, key, key)?;
+        }
+    }
+    if let Some(existing) = existing {
+        for key in existing.keys().filter(|key| !uses.contains_key(key)) {
+            writeln!(out, "# unused")?;
+            catalog::write_entry(out, key, existing.source(key).unwrap_or_default())?;
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const CATALOG: &str = "
+# Jobs
+greeting = Hello, { $name }!
+jobs-running =
+    { $count ->
+        [0] No jobs are running
+        [one] One job is running
+       *[other] { $count } jobs are running
+    }
+kind = { $kind ->
+        [workflow] A workflow
+       *[other] A job
+    }
+notice =
+    First line
+    second line
+";
+
+    fn locale(tag: &str) -> Locale {
+        tag.parse().unwrap()
+    }
+
+    #[test]
+    fn test_locales_parse_from_tags_and_posix_names() {
+        assert_eq!(locale("de_AT.UTF-8@euro").to_string(), "de-AT");
+        assert_eq!(locale("zh-Hans-CN").to_string(), "zh-CN");
+        assert_eq!(locale("EN").to_string(), "en");
+        assert_eq!(locale("es-419").region(), Some("419"));
+        assert!("C".parse::<Locale>().is_err());
+        assert!("".parse::<Locale>().is_err());
+        assert_eq!(locale("pt-BR").fallbacks(), vec![locale("pt-BR"), locale("pt")]);
+    }
+
+    #[test]
+    fn test_accept_language_is_negotiated_by_quality() {
+        let available = [locale("de"), locale("en"), locale("fr-CA")];
+        assert_eq!(negotiate("fr-CH, de;q=0.8", &available), Some(&available[2]));
+        assert_eq!(negotiate("it;q=0.9, de-AT;q=0.5, en;q=0.7", &available), Some(&available[1]));
+        assert_eq!(negotiate("de;q=0, *;q=0.1", &available), Some(&available[0]));
+        assert_eq!(negotiate("ja", &available), None);
+        assert_eq!(negotiate("", &available), None);
+    }
+
+    #[test]
+    fn test_messages_select_variants_by_plural_rules() {
+        let catalog = Catalog::parse(locale("en"), "test", CATALOG).unwrap();
+        let format = |key: &str, args: &Args<'_>| catalog.format(key, args).unwrap();
+
+        assert_eq!(format("greeting", &[("name", &"Ada")]), "Hello, Ada!");
+        assert_eq!(format("greeting", &[]), "Hello, {$name}!");
+        assert_eq!(format("jobs-running", &[("count", &0)]), "No jobs are running");
+        assert_eq!(format("jobs-running", &[("count", &1)]), "One job is running");
+        assert_eq!(format("jobs-running", &[("count", &3)]), "3 jobs are running");
+        assert_eq!(format("kind", &[("kind", &"workflow")]), "A workflow");
+        assert_eq!(format("kind", &[("kind", &"job")]), "A job");
+        assert_eq!(format("notice", &[]), "First line\nsecond line");
+        assert_eq!(catalog.keys().count(), 4);
+
+        let reparsed = Catalog::parse(locale("en"), "test", &catalog.to_string()).unwrap();
+        assert_eq!(reparsed, catalog);
+    }
+
+    #[test]
+    fn test_plural_categories_follow_the_language() {
+        assert_eq!(plural_category("en", 1.0), PluralCategory::One);
+        assert_eq!(plural_category("en", 1.5), PluralCategory::Other);
+        assert_eq!(plural_category("fr", 0.0), PluralCategory::One);
+        assert_eq!(plural_category("ru", 21.0), PluralCategory::One);
+        assert_eq!(plural_category("ru", 22.0), PluralCategory::Few);
+        assert_eq!(plural_category("ru", 12.0), PluralCategory::Many);
+        assert_eq!(plural_category("pl", 21.0), PluralCategory::Many);
+        assert_eq!(plural_category("ar", 2.0), PluralCategory::Two);
+        assert_eq!(plural_category("ja", 1.0), PluralCategory::Other);
+    }
+
+    #[test]
+    fn test_malformed_catalogs_name_the_line() {
+        for source in [
+            "  stray\nok = fine",
+            "no value here",
+            "1bad = key",
+            "empty =",
+            "sel = { $n ->\n [one] a\n}",
+            "open = { $n",
+            "close = oops }",
+        ] {
+            let error = Catalog::parse(locale("en"), "test", source).unwrap_err();
+            assert!(matches!(error, I18nError::Parse { .. }), "{source}: {error}");
+        }
+        let error = Catalog::parse(locale("en"), "test", "a = b\n\nc d").unwrap_err();
+        assert!(error.to_string().contains("line 3"), "{error}");
+    }
+
+    #[test]
+    fn test_localizer_falls_back_to_default_locale_then_key() {
+        let localizer = Localizer::new(locale("de"));
+        localizer.add_catalog(Catalog::parse(locale("en"), "plugin", "a = A\nb = B").unwrap());
+        localizer.add_catalog(Catalog::parse(locale("de"), "plugin", "a = A (de)").unwrap());
+        localizer.add_catalog(Catalog::parse(locale("fr-CA"), "plugin", "a = A (fr-CA)").unwrap());
+
+        let message = |tag: &str, key: &str| localizer.message(&locale(tag), "plugin", key, &[]);
+        assert_eq!(message("fr-CA", "a"), "A (fr-CA)");
+        assert_eq!(message("fr", "a"), "A (de)");
+        assert_eq!(message("fr-CA", "b"), "B");
+        assert_eq!(message("fr-CA", "c"), "c");
+        assert_eq!(localizer.negotiate("es"), locale("de"));
+
+        localizer.set_locale(locale("fr-CA"));
+        assert_eq!(localizer.tr("plugin", "a", &[]), "A (fr-CA)");
+    }
+
+    #[test]
+    fn test_catalogs_load_from_locale_directories() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::create_dir_all(dir.path().join("de")).unwrap();
+        fs::write(dir.path().join("de/my-plugin.ftl"), "hello = Hallo").unwrap();
+        fs::write(dir.path().join("de/README.txt"), "not a catalog").unwrap();
+
+        let localizer = Localizer::new(Locale::english());
+        assert_eq!(localizer.load_dir(dir.path()).unwrap(), 1);
+        assert_eq!(localizer.load_dir(&dir.path().join("missing")).unwrap(), 0);
+        assert_eq!(localizer.locales("my-plugin"), vec![locale("de")]);
+        assert_eq!(localizer.message(&locale("de-CH"), "my-plugin", "hello", &[]), "Hallo");
+
+        fs::write(dir.path().join("de/broken.ftl"), "=").unwrap();
+        assert!(localizer.load_dir(dir.path()).is_err());
+    }
+
+    #[test]
+    fn test_builtin_catalogs_cover_the_same_keys() {
+        let localizer = localizer();
+        let english = localizer.catalog(DEFAULT_DOMAIN, &Locale::english()).unwrap();
+        for locale in localizer.locales(DEFAULT_DOMAIN) {
+            let catalog = localizer.catalog(DEFAULT_DOMAIN, &locale).unwrap();
+            let missing: Vec<&str> = english.keys().filter(|key| !catalog.contains(key)).collect();
+            assert!(missing.is_empty(), "{locale} lacks {missing:?}");
+        }
+        assert_eq!(
+            localizer.message(&locale("de"), DEFAULT_DOMAIN, "i18n-missing-messages", &[("count", &1)]),
+            "1 fehlende Nachricht"
+        );
+    }
+
+    #[test]
+    fn test_keys_are_extracted_from_tr_calls() {
+        let source = r#"
+            let a = tr!("plain-key");
+            let b = crate::tr!(domain: "my-plugin", "plugin-key", count = n);
+            let c = attr!("not-a-key");
+            let d = tr!(format!("dynamic"));
+        "#;
+        let keys = extract_keys(source);
+        assert_eq!(keys.len(), 2);
+        assert_eq!((keys[0].domain.as_str(), keys[0].key.as_str(), keys[0].line), ("squirrel", "plain-key", 2));
+        assert_eq!((keys[1].domain.as_str(), keys[1].key.as_str()), ("my-plugin", "plugin-key"));
+
+        let existing = Catalog::parse(locale("de"), "my-plugin", "plugin-key = Schlüssel\nold-key = Alt").unwrap();
+        let found: Vec<(ExtractedKey, String)> = keys.into_iter().map(|key| (key, "src/lib.rs".to_string())).collect();
+        let mut template = String::new();
+        write_template(&mut template, &found, Some(&existing)).unwrap();
+        assert!(template.contains("# untranslated, used at src/lib.rs:2\nplain-key = plain-key\n"));
+        assert!(template.contains("plugin-key = Schlüssel\n"));
+        assert!(template.contains("# unused\nold-key = Alt\n"));
+        assert!(Catalog::parse(locale("de"), "my-plugin", &template).is_ok());
+    }
+}
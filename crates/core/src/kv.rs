@@ -0,0 +1,635 @@
+//! Namespaced key-value store
+//!
+//! Small pieces of durable state, such as a cursor, the last item a plugin
+//! has seen or a lease, do not deserve a file format of their own. A
+//! [`KvStore`] keeps them as JSON values under string keys, grouped in
+//! namespaces, so that plugins and commands cannot overwrite each other's
+//! keys. A [`Namespace`] reads and writes typed values:
+//!
+//! ```ignore
+//! let state = store.namespace("plugin:importer")?;
+//! let cursor: Option<u64> = state.get("cursor")?;
+//! state.set("cursor", &(cursor.unwrap_or(0) + 1), None)?;
+//! ```
+//!
+//! An entry written with a time to live is gone once it expires. Changes to
+//! a single key are atomic within a process, so
+//! [`Namespace::compare_and_swap`] can hand out leases and counters.
+//!
+//! Entries are kept by a [`KvBackend`]: in memory, or in a directory with
+//! one JSON file per namespace, written atomically. The expired entries of
+//! a namespace are dropped whenever one of its keys changes.
+
+use std::collections::BTreeMap;
+use std::fmt::{self, Write as _};
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex, PoisonError};
+use std::time::Duration;
+
+use chrono::{DateTime, Utc};
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use thiserror::Error;
+use uuid::Uuid;
+
+/// Maximum length of a namespace name
+pub const MAX_NAMESPACE_LEN: usize = 128;
+
+/// Maximum length of a key
+pub const MAX_KEY_LEN: usize = 512;
+
+/// Extension of the namespace files of a [`FileKvBackend`]
+const NAMESPACE_EXTENSION: &str = "json";
+
+/// Errors of the key-value store
+#[derive(Debug, Error)]
+pub enum KvError {
+    /// The namespace name is empty, too long or contains control characters
+    #[error("Invalid namespace '{0}'")]
+    InvalidNamespace(String),
+    /// The key is empty, too long or contains control characters
+    #[error("Invalid key '{0}'")]
+    InvalidKey(String),
+    /// A value cannot be converted to or from the requested type
+    #[error("Invalid value of '{key}': {source}")]
+    Value {
+        /// Key of the value
+        key: String,
+        /// The serialization error
+        source: serde_json::Error,
+    },
+    /// A namespace file cannot be read or written
+    #[error("Invalid namespace file {path}: {source}")]
+    Corrupt {
+        /// Path of the file
+        path: PathBuf,
+        /// The serialization error
+        source: serde_json::Error,
+    },
+    /// Error from the underlying filesystem
+    #[error("I/O error on {path}: {source}")]
+    Io {
+        /// The host path
+        path: PathBuf,
+        /// The filesystem error
+        source: io::Error,
+    },
+}
+
+/// Wrap an I/O error on `path`
+fn io_error(path: &Path, source: io::Error) -> KvError {
+    KvError::Io {
+        path: path.to_path_buf(),
+        source,
+    }
+}
+
+/// Whether `name` is usable as a namespace or key of at most `max` bytes
+fn valid_name(name: &str, max: usize) -> bool {
+    !name.is_empty() && name.len() <= max && !name.chars().any(char::is_control)
+}
+
+/// A stored value
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct KvEntry {
+    /// The value
+    pub value: Value,
+    /// When the value was written
+    pub updated_at: DateTime<Utc>,
+    /// When the value expires, if it was written with a time to live
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub expires_at: Option<DateTime<Utc>>,
+}
+
+impl KvEntry {
+    /// Create an entry written now, expiring after `ttl` if given
+    #[must_use]
+    pub fn new(value: Value, ttl: Option<Duration>) -> Self {
+        let updated_at = Utc::now();
+        let expires_at = ttl
+            .and_then(|ttl| chrono::Duration::from_std(ttl).ok())
+            .and_then(|ttl| updated_at.checked_add_signed(ttl));
+        Self {
+            value,
+            updated_at,
+            expires_at,
+        }
+    }
+
+    /// Whether the entry has expired at `now`
+    #[must_use]
+    pub fn is_expired(&self, now: DateTime<Utc>) -> bool {
+        self.expires_at.is_some_and(|expires_at| expires_at <= now)
+    }
+}
+
+/// A change to one entry
+///
+/// It is given the entry, unless there is none or it expired, and returns
+/// `None` to leave it as it is, `Some(None)` to delete it or
+/// `Some(Some(entry))` to replace it.
+pub type Change<'a> = &'a mut dyn FnMut(Option<&KvEntry>) -> Option<Option<KvEntry>>;
+
+/// Where a [`KvStore`] keeps its entries
+pub trait KvBackend: Send + Sync + fmt::Debug {
+    /// Entries of `namespace` by key, including expired ones
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the entries cannot be read
+    fn entries(&self, namespace: &str) -> Result<BTreeMap<String, KvEntry>, KvError>;
+
+    /// Apply `change` to the entry of `key` in `namespace`, atomically with
+    /// respect to other changes, returning whether it changed the entry
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the entries cannot be read or written
+    fn update(&self, namespace: &str, key: &str, change: Change<'_>) -> Result<bool, KvError>;
+
+    /// Names of the namespaces that have entries
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the namespaces cannot be listed
+    fn namespaces(&self) -> Result<Vec<String>, KvError>;
+}
+
+/// Apply `change` to the entry of `key` in `entries`, returning whether it
+/// changed the entry
+///
+/// Expired entries are dropped when an entry changes.
+fn apply(entries: &mut BTreeMap<String, KvEntry>, key: &str, change: Change<'_>) -> bool {
+    let now = Utc::now();
+    let live = entries.get(key).filter(|entry| !entry.is_expired(now));
+    let changed = match change(live) {
+        None => false,
+        Some(Some(entry)) => {
+            entries.insert(key.to_string(), entry);
+            true
+        }
+        Some(None) => entries.remove(key).is_some(),
+    };
+    if changed {
+        entries.retain(|_, entry| !entry.is_expired(now));
+    }
+    changed
+}
+
+/// Entries kept in memory, lost when the process exits
+#[derive(Debug, Default)]
+pub struct MemoryKvBackend {
+    /// Entries by namespace and key
+    namespaces: Mutex<BTreeMap<String, BTreeMap<String, KvEntry>>>,
+}
+
+impl MemoryKvBackend {
+    /// Create an empty backend
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+impl KvBackend for MemoryKvBackend {
+    fn entries(&self, namespace: &str) -> Result<BTreeMap<String, KvEntry>, KvError> {
+        let namespaces = self.namespaces.lock().unwrap_or_else(PoisonError::into_inner);
+        Ok(namespaces.get(namespace).cloned().unwrap_or_default())
+    }
+
+    fn update(&self, namespace: &str, key: &str, change: Change<'_>) -> Result<bool, KvError> {
+        let mut namespaces = self.namespaces.lock().unwrap_or_else(PoisonError::into_inner);
+        let entries = namespaces.entry(namespace.to_string()).or_default();
+        let changed = apply(entries, key, change);
+        if entries.is_empty() {
+            namespaces.remove(namespace);
+        }
+        Ok(changed)
+    }
+
+    fn namespaces(&self) -> Result<Vec<String>, KvError> {
+        let namespaces = self.namespaces.lock().unwrap_or_else(PoisonError::into_inner);
+        Ok(namespaces.keys().cloned().collect())
+    }
+}
+
+/// Entries kept in a directory, one JSON file per namespace
+///
+/// File names escape the characters of a namespace other than ASCII
+/// letters, digits, `-`, `_` and `.` as `%XX`, so `plugin:importer` is kept
+/// in `plugin%3Aimporter.json`.
+#[derive(Debug)]
+pub struct FileKvBackend {
+    /// Directory of the namespace files
+    root: PathBuf,
+    /// Serializes changes made by this process
+    writes: Mutex<()>,
+}
+
+impl FileKvBackend {
+    /// Open the backend in `root`, creating the directory if needed
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the directory cannot be created
+    pub fn open(root: impl Into<PathBuf>) -> Result<Self, KvError> {
+        let root = root.into();
+        fs::create_dir_all(&root).map_err(|e| io_error(&root, e))?;
+        Ok(Self {
+            root,
+            writes: Mutex::new(()),
+        })
+    }
+
+    /// Directory of the namespace files
+    #[must_use]
+    pub fn root(&self) -> &Path {
+        &self.root
+    }
+
+    /// Path of the file of `namespace`
+    fn path(&self, namespace: &str) -> PathBuf {
+        let mut name = String::with_capacity(namespace.len());
+        for byte in namespace.bytes() {
+            if byte.is_ascii_alphanumeric() || matches!(byte, b'-' | b'_' | b'.') {
+                name.push(char::from(byte));
+            } else {
+                let _ = write!(name, "%{byte:02X}");
+            }
+        }
+        self.root.join(format!("{name}.{NAMESPACE_EXTENSION}"))
+    }
+
+    /// Read the entries of `namespace`; a missing file has none
+    fn load(&self, namespace: &str) -> Result<BTreeMap<String, KvEntry>, KvError> {
+        let path = self.path(namespace);
+        match fs::read(&path) {
+            Ok(data) => serde_json::from_slice(&data).map_err(|source| KvError::Corrupt { path, source }),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(BTreeMap::new()),
+            Err(e) => Err(io_error(&path, e)),
+        }
+    }
+
+    /// Write the entries of `namespace` atomically, removing its file if
+    /// there are none
+    fn save(&self, namespace: &str, entries: &BTreeMap<String, KvEntry>) -> Result<(), KvError> {
+        let path = self.path(namespace);
+        if entries.is_empty() {
+            return match fs::remove_file(&path) {
+                Err(e) if e.kind() != io::ErrorKind::NotFound => Err(io_error(&path, e)),
+                _ => Ok(()),
+            };
+        }
+        let data = serde_json::to_vec_pretty(entries).map_err(|source| KvError::Corrupt {
+            path: path.clone(),
+            source,
+        })?;
+        let staged = self.root.join(format!(".{}.tmp", Uuid::new_v4()));
+        fs::write(&staged, data).map_err(|e| io_error(&staged, e))?;
+        fs::rename(&staged, &path).map_err(|e| io_error(&path, e))
+    }
+}
+
+/// The namespace a file name escapes, if it is one
+fn unescape(name: &str) -> Option<String> {
+    let mut bytes = Vec::with_capacity(name.len());
+    let mut rest = name.as_bytes();
+    while let Some((&byte, tail)) = rest.split_first() {
+        if byte == b'%' {
+            let hex = std::str::from_utf8(tail.get(..2)?).ok()?;
+            bytes.push(u8::from_str_radix(hex, 16).ok()?);
+            rest = &tail[2..];
+        } else {
+            bytes.push(byte);
+            rest = tail;
+        }
+    }
+    String::from_utf8(bytes).ok()
+}
+
+impl KvBackend for FileKvBackend {
+    fn entries(&self, namespace: &str) -> Result<BTreeMap<String, KvEntry>, KvError> {
+        self.load(namespace)
+    }
+
+    fn update(&self, namespace: &str, key: &str, change: Change<'_>) -> Result<bool, KvError> {
+        let _guard = self.writes.lock().unwrap_or_else(PoisonError::into_inner);
+        let mut entries = self.load(namespace)?;
+        if !apply(&mut entries, key, change) {
+            return Ok(false);
+        }
+        self.save(namespace, &entries)?;
+        Ok(true)
+    }
+
+    fn namespaces(&self) -> Result<Vec<String>, KvError> {
+        let mut namespaces = Vec::new();
+        for entry in fs::read_dir(&self.root).map_err(|e| io_error(&self.root, e))? {
+            let path = entry.map_err(|e| io_error(&self.root, e))?.path();
+            if path.extension().is_some_and(|ext| ext == NAMESPACE_EXTENSION) {
+                if let Some(namespace) = path.file_stem().and_then(|stem| stem.to_str()).and_then(unescape) {
+                    namespaces.push(namespace);
+                }
+            }
+        }
+        namespaces.sort();
+        Ok(namespaces)
+    }
+}
+
+/// Namespaced store of typed values
+#[derive(Debug, Clone)]
+pub struct KvStore {
+    /// Where the entries are kept
+    backend: Arc<dyn KvBackend>,
+}
+
+impl KvStore {
+    /// Create a store keeping its entries in `backend`
+    #[must_use]
+    pub fn new(backend: Arc<dyn KvBackend>) -> Self {
+        Self { backend }
+    }
+
+    /// Create a store keeping its entries in memory
+    #[must_use]
+    pub fn in_memory() -> Self {
+        Self::new(Arc::new(MemoryKvBackend::new()))
+    }
+
+    /// Open a store keeping its entries in the directory `root`
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the directory cannot be created
+    pub fn open(root: impl Into<PathBuf>) -> Result<Self, KvError> {
+        Ok(Self::new(Arc::new(FileKvBackend::open(root)?)))
+    }
+
+    /// The default store directory, `~/.squirrel/kv`
+    #[must_use]
+    pub fn default_path() -> Option<PathBuf> {
+        std::env::var_os("HOME").map(|home| Path::new(&home).join(".squirrel").join("kv"))
+    }
+
+    /// The namespace `name`
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the name is empty, longer than
+    /// [`MAX_NAMESPACE_LEN`] or contains control characters
+    pub fn namespace(&self, name: &str) -> Result<Namespace, KvError> {
+        if !valid_name(name, MAX_NAMESPACE_LEN) {
+            return Err(KvError::InvalidNamespace(name.to_string()));
+        }
+        Ok(Namespace {
+            name: name.to_string(),
+            backend: self.backend.clone(),
+        })
+    }
+
+    /// Names of the namespaces that have entries
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the namespaces cannot be listed
+    pub fn namespaces(&self) -> Result<Vec<String>, KvError> {
+        self.backend.namespaces()
+    }
+}
+
+/// The keys of one namespace of a [`KvStore`]
+#[derive(Debug, Clone)]
+pub struct Namespace {
+    /// Name of the namespace
+    name: String,
+    /// Where the entries are kept
+    backend: Arc<dyn KvBackend>,
+}
+
+impl Namespace {
+    /// Name of the namespace
+    #[must_use]
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Check that `key` is usable
+    fn check(key: &str) -> Result<(), KvError> {
+        if valid_name(key, MAX_KEY_LEN) {
+            Ok(())
+        } else {
+            Err(KvError::InvalidKey(key.to_string()))
+        }
+    }
+
+    /// Convert `value` of `key` to JSON
+    fn to_value<T: Serialize + ?Sized>(key: &str, value: &T) -> Result<Value, KvError> {
+        serde_json::to_value(value).map_err(|source| KvError::Value {
+            key: key.to_string(),
+            source,
+        })
+    }
+
+    /// Convert the JSON `value` of `key` to `T`
+    fn from_value<T: DeserializeOwned>(key: &str, value: Value) -> Result<T, KvError> {
+        serde_json::from_value(value).map_err(|source| KvError::Value {
+            key: key.to_string(),
+            source,
+        })
+    }
+
+    /// The entry of `key`, unless there is none or it expired
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the key is not valid or the entries cannot be
+    /// read
+    pub fn entry(&self, key: &str) -> Result<Option<KvEntry>, KvError> {
+        Self::check(key)?;
+        let now = Utc::now();
+        Ok(self
+            .backend
+            .entries(&self.name)?
+            .remove(key)
+            .filter(|entry| !entry.is_expired(now)))
+    }
+
+    /// The value of `key`, unless there is none or it expired
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the key is not valid, the entries cannot be read
+    /// or the value is not a `T`
+    pub fn get<T: DeserializeOwned>(&self, key: &str) -> Result<Option<T>, KvError> {
+        self.entry(key)?
+            .map(|entry| Self::from_value(key, entry.value))
+            .transpose()
+    }
+
+    /// Set `key` to `value`, expiring after `ttl` if given
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the key is not valid, the value cannot be
+    /// serialized or the entries cannot be written
+    pub fn set<T: Serialize + ?Sized>(&self, key: &str, value: &T, ttl: Option<Duration>) -> Result<(), KvError> {
+        Self::check(key)?;
+        let mut entry = Some(KvEntry::new(Self::to_value(key, value)?, ttl));
+        self.backend.update(&self.name, key, &mut |_| Some(entry.take()))?;
+        Ok(())
+    }
+
+    /// Set `key` to `new`, expiring after `ttl` if given, if its value is
+    /// `current`, returning whether it was set
+    ///
+    /// A `current` of `None` sets the key only if it has no value or its
+    /// value expired.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the key is not valid, a value cannot be
+    /// serialized or the entries cannot be written
+    pub fn compare_and_swap<T: Serialize + ?Sized>(
+        &self,
+        key: &str,
+        current: Option<&T>,
+        new: &T,
+        ttl: Option<Duration>,
+    ) -> Result<bool, KvError> {
+        Self::check(key)?;
+        let current = current.map(|value| Self::to_value(key, value)).transpose()?;
+        let mut entry = Some(KvEntry::new(Self::to_value(key, new)?, ttl));
+        self.backend.update(&self.name, key, &mut |live| {
+            (live.map(|live| &live.value) == current.as_ref()).then(|| entry.take())
+        })
+    }
+
+    /// Delete `key`, returning whether it had a value
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the key is not valid or the entries cannot be
+    /// written
+    pub fn delete(&self, key: &str) -> Result<bool, KvError> {
+        Self::check(key)?;
+        self.backend
+            .update(&self.name, key, &mut |live| live.is_some().then_some(None))
+    }
+
+    /// Keys starting with `prefix` that have a value, in order
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the entries cannot be read
+    pub fn keys(&self, prefix: &str) -> Result<Vec<String>, KvError> {
+        let now = Utc::now();
+        Ok(self
+            .backend
+            .entries(&self.name)?
+            .into_iter()
+            .filter(|(key, entry)| key.starts_with(prefix) && !entry.is_expired(now))
+            .map(|(key, _)| key)
+            .collect())
+    }
+
+    /// Values of the keys starting with `prefix`, by key in order
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the entries cannot be read or a value is not a
+    /// `T`
+    pub fn list<T: DeserializeOwned>(&self, prefix: &str) -> Result<Vec<(String, T)>, KvError> {
+        let now = Utc::now();
+        self.backend
+            .entries(&self.name)?
+            .into_iter()
+            .filter(|(key, entry)| key.starts_with(prefix) && !entry.is_expired(now))
+            .map(|(key, entry)| {
+                let value = Self::from_value(&key, entry.value)?;
+                Ok((key, value))
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    struct Cursor {
+        offset: u64,
+        source: String,
+    }
+
+    #[test]
+    fn test_values_are_typed_namespaced_and_expire() {
+        let store = KvStore::in_memory();
+        let importer = store.namespace("plugin:importer").unwrap();
+        let exporter = store.namespace("plugin:exporter").unwrap();
+
+        let cursor = Cursor {
+            offset: 42,
+            source: "s3://lab/runs".to_string(),
+        };
+        importer.set("cursor", &cursor, None).unwrap();
+        importer.set("seen.1", &true, None).unwrap();
+        importer.set("seen.2", &false, Some(Duration::ZERO)).unwrap();
+        assert_eq!(importer.get::<Cursor>("cursor").unwrap(), Some(cursor));
+        assert!(exporter.get::<Cursor>("cursor").unwrap().is_none());
+        assert!(matches!(importer.get::<u64>("cursor"), Err(KvError::Value { .. })));
+
+        // The expired key is gone
+        assert!(importer.get::<bool>("seen.2").unwrap().is_none());
+        assert_eq!(importer.list::<bool>("seen.").unwrap(), vec![("seen.1".to_string(), true)]);
+        assert_eq!(importer.keys("").unwrap(), vec!["cursor", "seen.1"]);
+        assert_eq!(store.namespaces().unwrap(), vec!["plugin:importer"]);
+
+        assert!(importer.delete("cursor").unwrap());
+        assert!(!importer.delete("cursor").unwrap());
+        assert!(matches!(store.namespace(""), Err(KvError::InvalidNamespace(_))));
+        assert!(matches!(importer.set("", &1, None), Err(KvError::InvalidKey(_))));
+    }
+
+    #[test]
+    fn test_compare_and_swap_only_replaces_the_expected_value() {
+        let leases = KvStore::in_memory().namespace("leases").unwrap();
+
+        assert!(leases.compare_and_swap("reindex", None, "worker-1", None).unwrap());
+        assert!(!leases.compare_and_swap("reindex", None, "worker-2", None).unwrap());
+        assert!(!leases
+            .compare_and_swap("reindex", Some("worker-2"), "worker-3", None)
+            .unwrap());
+        assert!(leases
+            .compare_and_swap("reindex", Some("worker-1"), "worker-2", Some(Duration::ZERO))
+            .unwrap());
+
+        // An expired lease counts as no lease
+        assert!(leases.compare_and_swap("reindex", None, "worker-3", None).unwrap());
+        assert_eq!(leases.get::<String>("reindex").unwrap().as_deref(), Some("worker-3"));
+    }
+
+    #[test]
+    fn test_file_backend_persists_namespaces() {
+        let dir = tempfile::tempdir().unwrap();
+        let store = KvStore::open(dir.path().join("kv")).unwrap();
+        let state = store.namespace("plugin:importer/v2").unwrap();
+        state.set("cursor", &7_u64, None).unwrap();
+        state.set("token", "secret", Some(Duration::ZERO)).unwrap();
+        assert!(dir.path().join("kv/plugin%3Aimporter%2Fv2.json").exists());
+
+        let reopened = KvStore::open(dir.path().join("kv")).unwrap();
+        assert_eq!(reopened.namespaces().unwrap(), vec!["plugin:importer/v2"]);
+        let state = reopened.namespace("plugin:importer/v2").unwrap();
+        assert_eq!(state.get::<u64>("cursor").unwrap(), Some(7));
+        assert!(state.get::<String>("token").unwrap().is_none());
+
+        // Deleting the last key removes the namespace
+        assert!(state.delete("cursor").unwrap());
+        assert!(reopened.namespaces().unwrap().is_empty());
+    }
+}
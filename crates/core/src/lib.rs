@@ -19,12 +19,66 @@
 //! 
 //! - Shared error types and utilities
 //! - Build information
+//! - Virtual filesystem roots shared by commands and tools
+//! - A content-addressed blob store deduplicating stored files
+//! - A framework for ordered, reversible data migrations
+//! - Message catalogs localizing CLI and API messages
+//! - Tags labeling jobs, commands, experiments and datasets
+//! - Retry policies shared by clients, stores and exporters
+//! - Deadlines bounding the work done for a request
+//! - Feature flags gating experimental behavior at runtime
+//! - Redaction of secrets and personal data before it is logged or stored
+//! - Encrypted configuration values shared by the CLI and the web server
+//! - Sandboxed expressions for validation, alerting and routing conditions
+//! - Templates for reports, scaffolds, notifications and webhook payloads
+//! - A namespaced key-value store for small pieces of durable state
 //!
 //! All other functionality has been moved to dedicated crates.
 
 /// Error handling types and utilities
 pub mod error;
 
+/// Virtual filesystem roots
+pub mod vfs;
+
+/// Content-addressed blob store
+pub mod blob;
+
+/// Ordered, reversible data migrations
+pub mod migration;
+
+/// Message catalogs and localization
+pub mod i18n;
+
+/// Tags and the kinds of tagged resources
+pub mod tags;
+
+/// Retry policies for fallible async operations
+#[cfg(feature = "async")]
+pub mod retry;
+
+/// Deadlines propagated through async call graphs
+#[cfg(feature = "async")]
+pub mod deadline;
+
+/// Feature flags and their per-user and per-workspace settings
+pub mod flags;
+
+/// Redaction rules applied before data is logged, persisted or sent
+pub mod redaction;
+
+/// Encrypted configuration values and their master key
+pub mod encryption;
+
+/// Sandboxed expressions used as validation, alerting and routing conditions
+pub mod expr;
+
+/// Templates rendered over JSON-serializable data
+pub mod template;
+
+/// Namespaced key-value store of typed values
+pub mod kv;
+
 /// Build information
 pub mod build_info {
     /// The built info from the build script
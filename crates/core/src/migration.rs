@@ -0,0 +1,607 @@
+//! Ordered, reversible data migrations
+//!
+//! A [`MigrationStore`] knows a set of migrations, each identified by a
+//! version and a checksum of what it does, and records which of them have
+//! been applied to its data. The [`Migrator`] compares the two and applies
+//! pending migrations in ascending version order, or reverts applied ones in
+//! descending order. Each migration is run and recorded by the store, inside
+//! one transaction where the backend has transactions.
+//!
+//! Before anything runs, the applied migrations are checked against the
+//! known ones: a migration whose checksum changed after it was applied, or
+//! an applied migration that is no longer known, stops the run, since the
+//! data may not be in the state the remaining migrations expect.
+//!
+//! A store shared by several instances can hold a lock while the
+//! [`Migrator`] applies or reverts migrations, see [`MigrationStore::lock`],
+//! so that two instances starting at once do not run the same migration.
+//!
+//! Stores keeping their data in code rather than SQL can register their
+//! migrations as functions in a [`MigrationSet`].
+
+use std::any::Any;
+use std::collections::BTreeMap;
+use std::fmt;
+use std::sync::Arc;
+
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use thiserror::Error;
+
+/// Errors of migrations
+#[derive(Debug, Error)]
+pub enum MigrationError {
+    /// Two migrations have the same version
+    #[error("Duplicate migration version {0}")]
+    Duplicate(i64),
+    /// An applied migration was changed afterwards
+    #[error("Migration {version} ({name}) was changed after it was applied")]
+    Modified {
+        /// Version of the migration
+        version: i64,
+        /// Name of the migration
+        name: String,
+    },
+    /// An applied migration is not known to the store
+    #[error("Applied migration {0} is unknown")]
+    Missing(i64),
+    /// No migration has this version
+    #[error("Unknown migration version {0}")]
+    UnknownVersion(i64),
+    /// The migration cannot be reverted
+    #[error("Migration {version} ({name}) cannot be reverted")]
+    Irreversible {
+        /// Version of the migration
+        version: i64,
+        /// Name of the migration
+        name: String,
+    },
+    /// Running the migration failed
+    #[error("Migration {version} ({name}) failed: {message}")]
+    Failed {
+        /// Version of the migration
+        version: i64,
+        /// Name of the migration
+        name: String,
+        /// Why it failed
+        message: String,
+    },
+    /// The store cannot read or record applied migrations
+    #[error("Migration store error: {0}")]
+    Store(String),
+}
+
+/// Way a migration is run
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Direction {
+    /// Apply the migration
+    Up,
+    /// Revert the migration
+    Down,
+}
+
+impl fmt::Display for Direction {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Up => write!(f, "up"),
+            Self::Down => write!(f, "down"),
+        }
+    }
+}
+
+/// A migration known to a store
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct MigrationInfo {
+    /// Version, which orders migrations
+    pub version: i64,
+    /// Short description
+    pub name: String,
+    /// Hex checksum of what the migration does
+    pub checksum: String,
+    /// Whether the migration can be reverted
+    pub reversible: bool,
+}
+
+impl MigrationInfo {
+    /// Describe a migration whose checksum is the SHA-256 of `source`
+    ///
+    /// The source is whatever defines the migration's effect, such as its
+    /// SQL; migrations in code pass a description of what they change and
+    /// must change it whenever the code changes.
+    #[must_use]
+    pub fn new(version: i64, name: impl Into<String>, source: &str, reversible: bool) -> Self {
+        Self {
+            version,
+            name: name.into(),
+            checksum: hex::encode(Sha256::digest(source.as_bytes())),
+            reversible,
+        }
+    }
+}
+
+/// A migration as recorded by the store it was applied to
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct AppliedMigration {
+    /// Version of the migration
+    pub version: i64,
+    /// Name of the migration when it was applied
+    pub name: String,
+    /// Checksum of the migration when it was applied
+    pub checksum: String,
+    /// When it was applied
+    pub applied_at: DateTime<Utc>,
+}
+
+/// State of a migration in a store
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum MigrationState {
+    /// Applied as it is known now
+    Applied,
+    /// Not applied yet
+    Pending,
+    /// Applied, but changed since
+    Modified,
+    /// Applied, but no longer known
+    Missing,
+}
+
+impl fmt::Display for MigrationState {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Applied => write!(f, "applied"),
+            Self::Pending => write!(f, "pending"),
+            Self::Modified => write!(f, "modified"),
+            Self::Missing => write!(f, "missing"),
+        }
+    }
+}
+
+/// A migration with its state in a store
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct MigrationStatus {
+    /// Version of the migration
+    pub version: i64,
+    /// Name of the migration
+    pub name: String,
+    /// State of the migration
+    pub state: MigrationState,
+    /// When it was applied, if it was
+    pub applied_at: Option<DateTime<Utc>>,
+}
+
+/// Data whose migrations are tracked, such as a database
+#[async_trait]
+pub trait MigrationStore: Send + Sync {
+    /// Name of the store in reports, e.g. `web` or `persistence`
+    fn name(&self) -> &str;
+
+    /// The migrations known to the store, in any order
+    fn migrations(&self) -> Vec<MigrationInfo>;
+
+    /// The migrations applied to the data
+    ///
+    /// # Errors
+    ///
+    /// Fails if the record of applied migrations cannot be read.
+    async fn applied(&self) -> Result<Vec<AppliedMigration>, MigrationError>;
+
+    /// Run migration `version` in `direction` and record the outcome
+    ///
+    /// The run and its record are one transaction where the backend allows
+    /// it.
+    ///
+    /// # Errors
+    ///
+    /// Fails if the migration fails or cannot be recorded.
+    async fn run(&self, version: i64, direction: Direction) -> Result<(), MigrationError>;
+
+    /// Take the store's migration lock, held until the returned guard is
+    /// dropped
+    ///
+    /// Stores whose data only one process uses need no lock, which is the
+    /// default.
+    ///
+    /// # Errors
+    ///
+    /// Fails if the lock cannot be taken in reasonable time.
+    async fn lock(&self) -> Result<Option<Box<dyn Any + Send>>, MigrationError> {
+        Ok(None)
+    }
+}
+
+/// Applies and reverts the migrations of a store
+pub struct Migrator<'a> {
+    /// The store migrated
+    store: &'a dyn MigrationStore,
+}
+
+impl fmt::Debug for Migrator<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Migrator").field("store", &self.store.name()).finish()
+    }
+}
+
+impl<'a> Migrator<'a> {
+    /// Create a migrator for `store`
+    #[must_use]
+    pub fn new(store: &'a dyn MigrationStore) -> Self {
+        Self { store }
+    }
+
+    /// Known and applied migrations by version
+    ///
+    /// # Errors
+    ///
+    /// Fails if two known migrations have the same version or the applied
+    /// migrations cannot be read.
+    async fn load(
+        &self,
+    ) -> Result<(BTreeMap<i64, MigrationInfo>, BTreeMap<i64, AppliedMigration>), MigrationError> {
+        let mut known = BTreeMap::new();
+        for migration in self.store.migrations() {
+            let version = migration.version;
+            if known.insert(version, migration).is_some() {
+                return Err(MigrationError::Duplicate(version));
+            }
+        }
+        let applied = self
+            .store
+            .applied()
+            .await?
+            .into_iter()
+            .map(|migration| (migration.version, migration))
+            .collect();
+        Ok((known, applied))
+    }
+
+    /// Every known or applied migration with its state, by version
+    ///
+    /// # Errors
+    ///
+    /// Fails if the migrations cannot be read.
+    pub async fn status(&self) -> Result<Vec<MigrationStatus>, MigrationError> {
+        let (known, applied) = self.load().await?;
+        let mut versions: Vec<i64> = known.keys().chain(applied.keys()).copied().collect();
+        versions.sort_unstable();
+        versions.dedup();
+        Ok(versions
+            .into_iter()
+            .map(|version| {
+                let info = known.get(&version);
+                let record = applied.get(&version);
+                let state = match (info, record) {
+                    (Some(info), Some(record)) if info.checksum == record.checksum => MigrationState::Applied,
+                    (Some(_), Some(_)) => MigrationState::Modified,
+                    (None, _) => MigrationState::Missing,
+                    (Some(_), None) => MigrationState::Pending,
+                };
+                MigrationStatus {
+                    version,
+                    name: info.map_or_else(|| record.map(|r| r.name.clone()).unwrap_or_default(), |i| i.name.clone()),
+                    state,
+                    applied_at: record.map(|record| record.applied_at),
+                }
+            })
+            .collect())
+    }
+
+    /// Check that every applied migration is known as it was applied
+    ///
+    /// # Errors
+    ///
+    /// Fails on the first migration that changed or is missing.
+    fn verify(
+        known: &BTreeMap<i64, MigrationInfo>,
+        applied: &BTreeMap<i64, AppliedMigration>,
+    ) -> Result<(), MigrationError> {
+        for (version, record) in applied {
+            let info = known.get(version).ok_or(MigrationError::Missing(*version))?;
+            if info.checksum != record.checksum {
+                return Err(MigrationError::Modified {
+                    version: *version,
+                    name: info.name.clone(),
+                });
+            }
+        }
+        Ok(())
+    }
+
+    /// Apply the pending migrations up to and including `target`, or all of
+    /// them, returning those applied
+    ///
+    /// # Errors
+    ///
+    /// Fails if the migration lock cannot be taken, an applied migration
+    /// changed or is missing, `target` is not a known version, or a
+    /// migration fails. Migrations applied before the
+    /// failure stay applied.
+    pub async fn up(&self, target: Option<i64>) -> Result<Vec<MigrationInfo>, MigrationError> {
+        let _lock = self.store.lock().await?;
+        let (known, applied) = self.load().await?;
+        Self::verify(&known, &applied)?;
+        if let Some(target) = target.filter(|target| !known.contains_key(target)) {
+            return Err(MigrationError::UnknownVersion(target));
+        }
+        let pending: Vec<MigrationInfo> = known
+            .into_values()
+            .filter(|info| !applied.contains_key(&info.version))
+            .filter(|info| target.is_none_or(|target| info.version <= target))
+            .collect();
+        for info in &pending {
+            self.store.run(info.version, Direction::Up).await?;
+        }
+        Ok(pending)
+    }
+
+    /// Revert the applied migrations above `target`, or the latest one,
+    /// returning those reverted, latest first
+    ///
+    /// A `target` of 0 reverts every migration.
+    ///
+    /// # Errors
+    ///
+    /// Fails if the migration lock cannot be taken, an applied migration
+    /// changed or is missing, one of the migrations to revert is irreversible, or a migration fails.
+    /// Migrations reverted before the failure stay reverted.
+    pub async fn down(&self, target: Option<i64>) -> Result<Vec<MigrationInfo>, MigrationError> {
+        let _lock = self.store.lock().await?;
+        let (known, applied) = self.load().await?;
+        Self::verify(&known, &applied)?;
+        let mut reverting: Vec<MigrationInfo> = applied
+            .keys()
+            .rev()
+            .filter_map(|version| known.get(version).cloned())
+            .collect();
+        match target {
+            Some(target) => reverting.retain(|info| info.version > target),
+            None => reverting.truncate(1),
+        }
+        if let Some(info) = reverting.iter().find(|info| !info.reversible) {
+            return Err(MigrationError::Irreversible {
+                version: info.version,
+                name: info.name.clone(),
+            });
+        }
+        for info in &reverting {
+            self.store.run(info.version, Direction::Down).await?;
+        }
+        Ok(reverting)
+    }
+}
+
+/// A step of a migration in code, run against the context `C`
+pub type MigrationFn<C> = Arc<dyn Fn(&C) -> Result<(), String> + Send + Sync>;
+
+/// A migration in code
+pub struct Migration<C: ?Sized> {
+    /// Version, name and checksum
+    pub info: MigrationInfo,
+    /// Applies the migration
+    pub up: MigrationFn<C>,
+    /// Reverts the migration, if it can be
+    pub down: Option<MigrationFn<C>>,
+}
+
+impl<C: ?Sized> Clone for Migration<C> {
+    fn clone(&self) -> Self {
+        Self {
+            info: self.info.clone(),
+            up: self.up.clone(),
+            down: self.down.clone(),
+        }
+    }
+}
+
+impl<C: ?Sized> fmt::Debug for Migration<C> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Migration").field("info", &self.info).finish_non_exhaustive()
+    }
+}
+
+/// Migrations in code, registered by version
+pub struct MigrationSet<C: ?Sized> {
+    /// The migrations by version
+    migrations: BTreeMap<i64, Migration<C>>,
+}
+
+impl<C: ?Sized> Default for MigrationSet<C> {
+    fn default() -> Self {
+        Self {
+            migrations: BTreeMap::new(),
+        }
+    }
+}
+
+impl<C: ?Sized> fmt::Debug for MigrationSet<C> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_list().entries(self.migrations.values().map(|m| &m.info)).finish()
+    }
+}
+
+impl<C: ?Sized> MigrationSet<C> {
+    /// Create an empty set
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Register migration `version`, checksummed over `source` as in
+    /// [`MigrationInfo::new`]
+    ///
+    /// # Errors
+    ///
+    /// Fails if a migration with the same version is registered.
+    pub fn register(
+        &mut self,
+        version: i64,
+        name: &str,
+        source: &str,
+        up: impl Fn(&C) -> Result<(), String> + Send + Sync + 'static,
+        down: Option<MigrationFn<C>>,
+    ) -> Result<&mut Self, MigrationError> {
+        if self.migrations.contains_key(&version) {
+            return Err(MigrationError::Duplicate(version));
+        }
+        let info = MigrationInfo::new(version, name, source, down.is_some());
+        self.migrations.insert(
+            version,
+            Migration {
+                info,
+                up: Arc::new(up),
+                down,
+            },
+        );
+        Ok(self)
+    }
+
+    /// The registered migrations
+    #[must_use]
+    pub fn infos(&self) -> Vec<MigrationInfo> {
+        self.migrations.values().map(|migration| migration.info.clone()).collect()
+    }
+
+    /// Run migration `version` in `direction` against `context`
+    ///
+    /// # Errors
+    ///
+    /// Fails if the version is unknown, the migration cannot be reverted,
+    /// or the step fails.
+    pub fn run(&self, version: i64, direction: Direction, context: &C) -> Result<&MigrationInfo, MigrationError> {
+        let migration = self
+            .migrations
+            .get(&version)
+            .ok_or(MigrationError::UnknownVersion(version))?;
+        let step = match direction {
+            Direction::Up => &migration.up,
+            Direction::Down => migration.down.as_ref().ok_or_else(|| MigrationError::Irreversible {
+                version,
+                name: migration.info.name.clone(),
+            })?,
+        };
+        step(context).map_err(|message| MigrationError::Failed {
+            version,
+            name: migration.info.name.clone(),
+            message,
+        })?;
+        Ok(&migration.info)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Mutex;
+
+    /// Store keeping a list of numbers, migrated by pushing and popping
+    struct ListStore {
+        /// Migrations of the list
+        set: MigrationSet<Mutex<Vec<i64>>>,
+        /// The data
+        list: Mutex<Vec<i64>>,
+        /// Applied migrations
+        applied: Mutex<Vec<AppliedMigration>>,
+    }
+
+    #[async_trait]
+    impl MigrationStore for ListStore {
+        fn name(&self) -> &'static str {
+            "list"
+        }
+
+        fn migrations(&self) -> Vec<MigrationInfo> {
+            self.set.infos()
+        }
+
+        async fn applied(&self) -> Result<Vec<AppliedMigration>, MigrationError> {
+            Ok(self.applied.lock().unwrap().clone())
+        }
+
+        async fn run(&self, version: i64, direction: Direction) -> Result<(), MigrationError> {
+            let info = self.set.run(version, direction, &self.list)?.clone();
+            let mut applied = self.applied.lock().unwrap();
+            match direction {
+                Direction::Up => applied.push(AppliedMigration {
+                    version,
+                    name: info.name,
+                    checksum: info.checksum,
+                    applied_at: Utc::now(),
+                }),
+                Direction::Down => applied.retain(|record| record.version != version),
+            }
+            Ok(())
+        }
+    }
+
+    fn store() -> ListStore {
+        let mut set = MigrationSet::new();
+        for version in [3, 1, 2] {
+            let down: MigrationFn<Mutex<Vec<i64>>> = Arc::new(|list| {
+                list.lock().unwrap().pop();
+                Ok(())
+            });
+            set.register(
+                version,
+                &format!("push {version}"),
+                &format!("push {version}"),
+                move |list: &Mutex<Vec<i64>>| {
+                    list.lock().unwrap().push(version);
+                    Ok(())
+                },
+                (version != 1).then_some(down),
+            )
+            .unwrap();
+        }
+        ListStore {
+            set,
+            list: Mutex::new(Vec::new()),
+            applied: Mutex::new(Vec::new()),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_migrations_run_in_order_and_revert() {
+        let store = store();
+        let migrator = Migrator::new(&store);
+        assert!(matches!(
+            store.set.run(4, Direction::Up, &store.list),
+            Err(MigrationError::UnknownVersion(4))
+        ));
+
+        let applied = migrator.up(Some(2)).await.unwrap();
+        assert_eq!(applied.iter().map(|m| m.version).collect::<Vec<_>>(), [1, 2]);
+        migrator.up(None).await.unwrap();
+        assert_eq!(*store.list.lock().unwrap(), [1, 2, 3]);
+        assert!(migrator.up(None).await.unwrap().is_empty());
+
+        let reverted = migrator.down(None).await.unwrap();
+        assert_eq!(reverted[0].version, 3);
+        assert!(matches!(
+            migrator.down(Some(0)).await,
+            Err(MigrationError::Irreversible { version: 1, .. })
+        ));
+        migrator.down(Some(1)).await.unwrap();
+        assert_eq!(*store.list.lock().unwrap(), [1]);
+
+        let states: Vec<MigrationState> = migrator.status().await.unwrap().iter().map(|s| s.state).collect();
+        assert_eq!(states, [MigrationState::Applied, MigrationState::Pending, MigrationState::Pending]);
+    }
+
+    #[tokio::test]
+    async fn test_changed_migrations_stop_the_run() {
+        let store = store();
+        let migrator = Migrator::new(&store);
+        migrator.up(Some(1)).await.unwrap();
+        store.applied.lock().unwrap()[0].checksum = "edited".to_string();
+
+        assert!(matches!(migrator.up(None).await, Err(MigrationError::Modified { version: 1, .. })));
+        assert_eq!(migrator.status().await.unwrap()[0].state, MigrationState::Modified);
+        assert!(matches!(migrator.up(Some(7)).await, Err(MigrationError::Modified { .. })));
+
+        store.applied.lock().unwrap()[0].version = 9;
+        assert!(matches!(migrator.up(None).await, Err(MigrationError::Missing(9))));
+    }
+}
@@ -0,0 +1,509 @@
+//! Redaction of secrets and personal data
+//!
+//! A [`Redactor`] applies redaction rules to text and JSON values before
+//! they are logged, persisted or sent elsewhere. Rules are of three types:
+//!
+//! - `key` rules replace the value of every object key matching a pattern,
+//!   such as `*password*`, where `*` matches any characters and case is
+//!   ignored;
+//! - `regex` rules replace the text a regular expression matches, in plain
+//!   text and in every string of a JSON value;
+//! - `path` rules replace the value at a JSON path, such as `$.user.email`
+//!   or `$.steps[*].env.*`.
+//!
+//! The built-in rules redact common secret keys, bearer tokens, JSON web
+//! tokens and well-known API key formats. Every rule counts its matches, so
+//! the effect of a rule can be monitored.
+
+use std::sync::atomic::{AtomicU64, Ordering};
+
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use thiserror::Error;
+
+/// Default text redacted values are replaced with
+pub const REDACTED: &str = "[REDACTED]";
+
+/// Errors of redaction rules
+#[derive(Debug, Clone, PartialEq, Eq, Error)]
+pub enum RedactionError {
+    /// A rule cannot be compiled
+    #[error("Invalid redaction rule '{rule}': {reason}")]
+    InvalidRule {
+        /// Name of the rule
+        rule: String,
+        /// What is wrong with it
+        reason: String,
+    },
+}
+
+/// A redaction rule
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum RedactionRule {
+    /// Values of object keys matching `pattern`
+    Key {
+        /// Name reported in metrics; `key:<pattern>` if not given
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        name: Option<String>,
+        /// Key pattern, `*` matching any characters, ignoring case
+        pattern: String,
+    },
+    /// Text matching a regular expression
+    Regex {
+        /// Name reported in metrics; `regex:<pattern>` if not given
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        name: Option<String>,
+        /// Regular expression
+        pattern: String,
+        /// Text replacing each match, which may refer to capture groups;
+        /// the configured replacement if not given
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        replacement: Option<String>,
+    },
+    /// Values at a JSON path
+    Path {
+        /// Name reported in metrics; `path:<path>` if not given
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        name: Option<String>,
+        /// Path of dot-separated keys, `*` for any key and `[*]` or `[n]`
+        /// for array elements, optionally starting with `$`
+        path: String,
+    },
+}
+
+impl RedactionRule {
+    /// Rule redacting the values of keys matching `pattern`
+    #[must_use]
+    pub fn key(pattern: impl Into<String>) -> Self {
+        Self::Key {
+            name: None,
+            pattern: pattern.into(),
+        }
+    }
+
+    /// Rule redacting text matching `pattern`
+    #[must_use]
+    pub fn regex(pattern: impl Into<String>) -> Self {
+        Self::Regex {
+            name: None,
+            pattern: pattern.into(),
+            replacement: None,
+        }
+    }
+
+    /// Rule redacting the values at `path`
+    #[must_use]
+    pub fn path(path: impl Into<String>) -> Self {
+        Self::Path {
+            name: None,
+            path: path.into(),
+        }
+    }
+
+    /// Name of the rule, as reported in metrics
+    #[must_use]
+    pub fn name(&self) -> String {
+        match self {
+            Self::Key { name, pattern } => name.clone().unwrap_or_else(|| format!("key:{pattern}")),
+            Self::Regex { name, pattern, .. } => name.clone().unwrap_or_else(|| format!("regex:{pattern}")),
+            Self::Path { name, path } => name.clone().unwrap_or_else(|| format!("path:{path}")),
+        }
+    }
+}
+
+/// The built-in rules
+#[must_use]
+pub fn builtin_rules() -> Vec<RedactionRule> {
+    let key = |name: &str, pattern: &str| RedactionRule::Key {
+        name: Some(format!("builtin:{name}")),
+        pattern: pattern.to_string(),
+    };
+    let regex = |name: &str, pattern: &str, replacement: Option<&str>| RedactionRule::Regex {
+        name: Some(format!("builtin:{name}")),
+        pattern: pattern.to_string(),
+        replacement: replacement.map(str::to_string),
+    };
+    vec![
+        key("password", "*password*"),
+        key("passwd", "*passwd*"),
+        key("secret", "*secret*"),
+        key("api_key", "*api_key*"),
+        key("apikey", "*apikey*"),
+        key("private_key", "*private_key*"),
+        key("authorization", "authorization"),
+        key("token", "token"),
+        key("suffix_token", "*_token"),
+        regex("bearer", r"(?i)\b(bearer)\s+[A-Za-z0-9._~+/=-]+", Some("$1 [REDACTED]")),
+        regex("jwt", r"\beyJ[A-Za-z0-9_-]+\.[A-Za-z0-9_-]+\.[A-Za-z0-9_-]+", None),
+        regex("github_token", r"\bgh[pousr]_[A-Za-z0-9]{36,}\b", None),
+        regex("aws_access_key", r"\bAKIA[0-9A-Z]{16}\b", None),
+    ]
+}
+
+/// Configuration of a redactor
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct RedactionConfig {
+    /// Whether anything is redacted
+    pub enabled: bool,
+    /// Whether the built-in rules apply, before the configured ones
+    pub builtin_rules: bool,
+    /// Text redacted values are replaced with
+    pub replacement: String,
+    /// Further rules
+    pub rules: Vec<RedactionRule>,
+    /// How often the matches of the rules are reported as metrics
+    pub metrics_interval_secs: u64,
+}
+
+impl Default for RedactionConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            builtin_rules: true,
+            replacement: REDACTED.to_string(),
+            rules: Vec::new(),
+            metrics_interval_secs: 15,
+        }
+    }
+}
+
+/// Number of matches of a rule
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RuleStats {
+    /// Name of the rule
+    pub rule: String,
+    /// Values and pieces of text it redacted
+    pub matches: u64,
+}
+
+/// One step of a JSON path
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum PathSegment {
+    /// The value of a key
+    Key(String),
+    /// The values of every key
+    AnyKey,
+    /// An array element
+    Index(usize),
+    /// Every array element
+    AnyIndex,
+}
+
+/// What a compiled rule matches
+#[derive(Debug)]
+enum Matcher {
+    /// Lowercase key pattern
+    Key(String),
+    /// Expression and replacement
+    Regex(Regex, String),
+    /// Path segments
+    Path(Vec<PathSegment>),
+}
+
+/// A compiled rule and its match counter
+#[derive(Debug)]
+struct CompiledRule {
+    /// Name of the rule
+    name: String,
+    /// What it matches
+    matcher: Matcher,
+    /// Matches so far
+    matches: AtomicU64,
+}
+
+impl CompiledRule {
+    /// Count `count` matches
+    fn count(&self, count: u64) {
+        if count > 0 {
+            self.matches.fetch_add(count, Ordering::Relaxed);
+        }
+    }
+}
+
+/// Applies redaction rules to text and JSON values
+#[derive(Debug, Default)]
+pub struct Redactor {
+    /// Replacement of key and path matches
+    replacement: String,
+    /// Rules in the order they apply
+    rules: Vec<CompiledRule>,
+}
+
+impl Redactor {
+    /// A redactor applying the rules of `config`
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if a regular expression, key pattern or path of a
+    /// rule is invalid.
+    pub fn new(config: &RedactionConfig) -> Result<Self, RedactionError> {
+        let mut redactor = Self {
+            replacement: config.replacement.clone(),
+            rules: Vec::new(),
+        };
+        if !config.enabled {
+            return Ok(redactor);
+        }
+        let builtin = if config.builtin_rules { builtin_rules() } else { Vec::new() };
+        for rule in builtin.iter().chain(&config.rules) {
+            let name = rule.name();
+            let invalid = |reason: String| RedactionError::InvalidRule {
+                rule: name.clone(),
+                reason,
+            };
+            let matcher = match rule {
+                RedactionRule::Key { pattern, .. } if pattern.trim_matches('*').is_empty() => {
+                    return Err(invalid("the pattern matches every key".to_string()));
+                }
+                RedactionRule::Key { pattern, .. } => Matcher::Key(pattern.to_lowercase()),
+                RedactionRule::Regex { pattern, replacement, .. } => Matcher::Regex(
+                    Regex::new(pattern).map_err(|e| invalid(e.to_string()))?,
+                    replacement.clone().unwrap_or_else(|| config.replacement.clone()),
+                ),
+                RedactionRule::Path { path, .. } => Matcher::Path(parse_path(path).map_err(invalid)?),
+            };
+            redactor.rules.push(CompiledRule {
+                name,
+                matcher,
+                matches: AtomicU64::new(0),
+            });
+        }
+        Ok(redactor)
+    }
+
+    /// A redactor redacting nothing
+    #[must_use]
+    pub fn disabled() -> Self {
+        Self::default()
+    }
+
+    /// Whether the redactor has no rules
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.rules.is_empty()
+    }
+
+    /// `text` with everything the regular expression rules match replaced
+    #[must_use]
+    pub fn redact_text(&self, text: &str) -> String {
+        let mut text = text.to_string();
+        for rule in &self.rules {
+            if let Matcher::Regex(pattern, replacement) = &rule.matcher {
+                let count = pattern.find_iter(&text).count();
+                if count > 0 {
+                    text = pattern.replace_all(&text, replacement.as_str()).into_owned();
+                    rule.count(count as u64);
+                }
+            }
+        }
+        text
+    }
+
+    /// Redacts `value` in place: the values at the rules' paths and of the
+    /// keys they match, then the text of the remaining strings
+    pub fn redact_value(&self, value: &mut Value) {
+        for rule in &self.rules {
+            if let Matcher::Path(segments) = &rule.matcher {
+                rule.count(redact_path(value, segments, &self.replacement));
+            }
+        }
+        self.redact_keys_and_text(value);
+    }
+
+    /// Redacts the values of matching keys and the text of other strings
+    fn redact_keys_and_text(&self, value: &mut Value) {
+        match value {
+            Value::Object(map) => {
+                for (key, value) in map.iter_mut() {
+                    let key = key.to_lowercase();
+                    let rule = self
+                        .rules
+                        .iter()
+                        .find(|rule| matches!(&rule.matcher, Matcher::Key(pattern) if glob_match(pattern, &key)));
+                    match rule {
+                        Some(rule) if value.as_str() != Some(self.replacement.as_str()) => {
+                            *value = Value::String(self.replacement.clone());
+                            rule.count(1);
+                        }
+                        Some(_) => {}
+                        None => self.redact_keys_and_text(value),
+                    }
+                }
+            }
+            Value::Array(items) => {
+                for item in items {
+                    self.redact_keys_and_text(item);
+                }
+            }
+            Value::String(text) => {
+                let redacted = self.redact_text(text);
+                if redacted != *text {
+                    *text = redacted;
+                }
+            }
+            _ => {}
+        }
+    }
+
+    /// Matches of every rule so far
+    #[must_use]
+    pub fn stats(&self) -> Vec<RuleStats> {
+        self.rules
+            .iter()
+            .map(|rule| RuleStats {
+                rule: rule.name.clone(),
+                matches: rule.matches.load(Ordering::Relaxed),
+            })
+            .collect()
+    }
+}
+
+/// Whether `text` matches `pattern`, where `*` matches any characters
+fn glob_match(pattern: &str, text: &str) -> bool {
+    let parts: Vec<&str> = pattern.split('*').collect();
+    let (first, rest) = (parts[0], &parts[1..]);
+    let Some(mut remaining) = text.strip_prefix(first) else {
+        return false;
+    };
+    let Some((last, middle)) = rest.split_last() else {
+        return remaining.is_empty();
+    };
+    for part in middle {
+        match remaining.find(part) {
+            Some(at) => remaining = &remaining[at + part.len()..],
+            None => return false,
+        }
+    }
+    remaining.len() >= last.len() && remaining.ends_with(last)
+}
+
+/// Parses a path such as `$.steps[*].env.*`
+fn parse_path(path: &str) -> Result<Vec<PathSegment>, String> {
+    let trimmed = path.strip_prefix('$').unwrap_or(path);
+    let trimmed = trimmed.strip_prefix('.').unwrap_or(trimmed);
+    let mut segments = Vec::new();
+    for part in trimmed.split('.') {
+        let (key, mut indexes) = part.find('[').map_or((part, ""), |at| part.split_at(at));
+        match key {
+            "" if indexes.is_empty() => return Err("the path has an empty key".to_string()),
+            "" => {}
+            "*" => segments.push(PathSegment::AnyKey),
+            key => segments.push(PathSegment::Key(key.to_string())),
+        }
+        while !indexes.is_empty() {
+            let close = indexes
+                .find(']')
+                .ok_or_else(|| format!("unclosed index in '{part}'"))?;
+            let index = &indexes[1..close];
+            segments.push(if index == "*" {
+                PathSegment::AnyIndex
+            } else {
+                PathSegment::Index(index.parse().map_err(|_| format!("invalid index '{index}'"))?)
+            });
+            indexes = &indexes[close + 1..];
+            if !indexes.is_empty() && !indexes.starts_with('[') {
+                return Err(format!("unexpected '{indexes}' after an index"));
+            }
+        }
+    }
+    if segments.is_empty() {
+        return Err("the path is empty".to_string());
+    }
+    Ok(segments)
+}
+
+/// Replaces the values at `segments` below `value`, returning how many
+fn redact_path(value: &mut Value, segments: &[PathSegment], replacement: &str) -> u64 {
+    let Some((segment, rest)) = segments.split_first() else {
+        if value.as_str() == Some(replacement) {
+            return 0;
+        }
+        *value = Value::String(replacement.to_string());
+        return 1;
+    };
+    match (segment, value) {
+        (PathSegment::Key(key), Value::Object(map)) => map
+            .get_mut(key)
+            .map_or(0, |value| redact_path(value, rest, replacement)),
+        (PathSegment::AnyKey, Value::Object(map)) => map
+            .values_mut()
+            .map(|value| redact_path(value, rest, replacement))
+            .sum(),
+        (PathSegment::Index(index), Value::Array(items)) => items
+            .get_mut(*index)
+            .map_or(0, |value| redact_path(value, rest, replacement)),
+        (PathSegment::AnyIndex, Value::Array(items)) => items
+            .iter_mut()
+            .map(|value| redact_path(value, rest, replacement))
+            .sum(),
+        _ => 0,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_rules_redact_keys_text_and_paths() {
+        let config = RedactionConfig {
+            rules: vec![
+                RedactionRule::Regex {
+                    name: Some("email".to_string()),
+                    pattern: r"[\w.+-]+@[\w-]+\.[\w.]+".to_string(),
+                    replacement: Some("<email>".to_string()),
+                },
+                RedactionRule::path("$.steps[*].env.*"),
+            ],
+            ..RedactionConfig::default()
+        };
+        let redactor = Redactor::new(&config).unwrap();
+
+        assert_eq!(
+            redactor.redact_text("Mail alice@example.org with Authorization: Bearer abc.def"),
+            "Mail <email> with Authorization: Bearer [REDACTED]"
+        );
+        let mut value = json!({
+            "user": {"name": "alice", "DB_Password": "hunter2"},
+            "max_tokens": 100,
+            "refresh_token": "r1",
+            "steps": [{"env": {"HOME": "/home/alice", "PATH": "/bin"}, "note": "from bob@example.org"}],
+        });
+        redactor.redact_value(&mut value);
+        assert_eq!(
+            value,
+            json!({
+                "user": {"name": "alice", "DB_Password": REDACTED},
+                "max_tokens": 100,
+                "refresh_token": REDACTED,
+                "steps": [{"env": {"HOME": REDACTED, "PATH": REDACTED}, "note": "from <email>"}],
+            })
+        );
+
+        let stats = redactor.stats();
+        let matches = |rule: &str| stats.iter().find(|stats| stats.rule == rule).unwrap().matches;
+        assert_eq!(matches("email"), 2);
+        assert_eq!(matches("path:$.steps[*].env.*"), 2);
+        assert_eq!(matches("builtin:password"), 1);
+        assert_eq!(matches("builtin:bearer"), 1);
+
+        let disabled = Redactor::new(&RedactionConfig {
+            enabled: false,
+            ..RedactionConfig::default()
+        })
+        .unwrap();
+        assert!(disabled.is_empty());
+        assert_eq!(disabled.redact_text("Bearer abc"), "Bearer abc");
+        for rule in [RedactionRule::regex("("), RedactionRule::key("**"), RedactionRule::path("steps[x]")] {
+            let config = RedactionConfig {
+                rules: vec![rule],
+                ..RedactionConfig::default()
+            };
+            assert!(Redactor::new(&config).is_err());
+        }
+    }
+}
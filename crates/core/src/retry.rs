@@ -0,0 +1,490 @@
+//! Retrying fallible async operations
+//!
+//! A [`RetryPolicy`] decides how often an operation is attempted and how long
+//! to wait between attempts: a fixed delay, or one doubling up to a cap,
+//! optionally randomized by jitter so that clients which failed together do
+//! not retry together, and optionally bounded by a time budget for the whole
+//! call. [`retry_with_policy`] runs an operation under a policy; the
+//! operation tells transient errors from permanent ones with the
+//! [`RetryResultExt`] methods. Code driving its own loop, e.g. to record each
+//! attempt, steps a [`Retry`] instead.
+//!
+//! Every finished call is reported to the [`RetryObserver`] installed with
+//! [`set_observer`]; the monitoring crate installs one counting attempts and
+//! outcomes per operation.
+//!
+//! ```ignore
+//! use squirrel_core::retry::{retry_with_policy, RetryPolicy, RetryResultExt};
+//!
+//! let policy = RetryPolicy::exponential(5, Duration::from_millis(100), Duration::from_secs(5))
+//!     .with_jitter();
+//! let response = retry_with_policy("registry.fetch", &policy, || async {
+//!     fetch().await.transient_if(|e| e.is_timeout())
+//! })
+//! .await?;
+//! ```
+
+use std::future::Future;
+use std::sync::{Arc, PoisonError, RwLock};
+use std::time::{Duration, Instant};
+
+use rand::Rng;
+
+use crate::error::Coded;
+
+/// How the delay between attempts grows
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Backoff {
+    /// The same delay before every retry
+    Fixed(Duration),
+    /// `initial` before the first retry, doubled for every further retry
+    /// up to `max`
+    Exponential {
+        /// Delay before the first retry
+        initial: Duration,
+        /// Upper bound for the delay
+        max: Duration,
+    },
+}
+
+/// How often and how patiently an operation is retried
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RetryPolicy {
+    /// Total number of attempts, including the first
+    pub max_attempts: u32,
+    /// Delay between attempts
+    pub backoff: Backoff,
+    /// Whether each delay is drawn at random between half and all of the
+    /// backoff delay
+    pub jitter: bool,
+    /// Time after which no further attempt is started
+    pub budget: Option<Duration>,
+}
+
+impl Default for RetryPolicy {
+    /// Three attempts, 100 milliseconds apart and doubling, with jitter
+    fn default() -> Self {
+        Self::exponential(3, Duration::from_millis(100), Duration::from_secs(5)).with_jitter()
+    }
+}
+
+impl RetryPolicy {
+    /// A single attempt
+    #[must_use]
+    pub const fn none() -> Self {
+        Self::fixed(1, Duration::ZERO)
+    }
+
+    /// `max_attempts` attempts, `delay` apart
+    #[must_use]
+    pub const fn fixed(max_attempts: u32, delay: Duration) -> Self {
+        Self {
+            max_attempts,
+            backoff: Backoff::Fixed(delay),
+            jitter: false,
+            budget: None,
+        }
+    }
+
+    /// `max_attempts` attempts, `initial` apart and doubling up to `max`
+    #[must_use]
+    pub const fn exponential(max_attempts: u32, initial: Duration, max: Duration) -> Self {
+        Self {
+            max_attempts,
+            backoff: Backoff::Exponential { initial, max },
+            jitter: false,
+            budget: None,
+        }
+    }
+
+    /// As many attempts as fit in `budget`, `initial` apart and doubling up
+    /// to `max`
+    #[must_use]
+    pub const fn budget(budget: Duration, initial: Duration, max: Duration) -> Self {
+        Self::exponential(u32::MAX, initial, max).with_budget(budget)
+    }
+
+    /// Randomizes each delay between half and all of the backoff delay
+    #[must_use]
+    pub const fn with_jitter(mut self) -> Self {
+        self.jitter = true;
+        self
+    }
+
+    /// Starts no attempt whose delay would end after `budget`
+    #[must_use]
+    pub const fn with_budget(mut self, budget: Duration) -> Self {
+        self.budget = Some(budget);
+        self
+    }
+
+    /// Backoff delay after failed attempt `attempt`, counted from 1,
+    /// before jitter
+    #[must_use]
+    pub fn backoff_delay(&self, attempt: u32) -> Duration {
+        match self.backoff {
+            Backoff::Fixed(delay) => delay,
+            Backoff::Exponential { initial, max } => {
+                let factor = 2u32.saturating_pow(attempt.saturating_sub(1));
+                initial.saturating_mul(factor).min(max)
+            }
+        }
+    }
+
+    /// Starts a call of `operation` under this policy
+    #[must_use]
+    pub fn start(&self, operation: &str) -> Retry {
+        Retry {
+            policy: *self,
+            operation: operation.to_string(),
+            attempts: 0,
+            started: Instant::now(),
+        }
+    }
+}
+
+/// One call of an operation under a [`RetryPolicy`]
+///
+/// After each attempt, call [`Retry::succeeded`], [`Retry::stopped`] for a
+/// permanent error, or [`Retry::next_delay`] for a transient one, which
+/// returns how long to wait before the next attempt or `None` once the
+/// policy is exhausted. The call is reported to the observer when it
+/// succeeds, stops or is exhausted; a `Retry` dropped before is not.
+#[derive(Debug)]
+pub struct Retry {
+    /// Policy of the call
+    policy: RetryPolicy,
+    /// Name the call is reported under
+    operation: String,
+    /// Attempts finished so far
+    attempts: u32,
+    /// When the call started
+    started: Instant,
+}
+
+impl Retry {
+    /// Attempts finished so far
+    #[must_use]
+    pub fn attempts(&self) -> u32 {
+        self.attempts
+    }
+
+    /// Time since the call started
+    #[must_use]
+    pub fn elapsed(&self) -> Duration {
+        self.started.elapsed()
+    }
+
+    /// Records a successful attempt
+    pub fn succeeded(mut self) {
+        self.attempts += 1;
+        self.report(RetryOutcome::Succeeded);
+    }
+
+    /// Records an attempt that failed with an error not worth retrying
+    pub fn stopped(mut self) {
+        self.attempts += 1;
+        self.report(RetryOutcome::Stopped);
+    }
+
+    /// Records an attempt that failed with a transient error
+    ///
+    /// Returns the delay before the next attempt, or `None` if the attempts
+    /// are used up or the next one would start after the budget.
+    pub fn next_delay(&mut self) -> Option<Duration> {
+        self.attempts += 1;
+        let mut delay = self.policy.backoff_delay(self.attempts);
+        if self.policy.jitter && !delay.is_zero() {
+            delay = delay.mul_f64(rand::thread_rng().gen_range(0.5..=1.0));
+        }
+        let within_budget = match self.policy.budget {
+            Some(budget) => self.elapsed().saturating_add(delay) <= budget,
+            None => true,
+        };
+        if self.attempts >= self.policy.max_attempts || !within_budget {
+            self.report(RetryOutcome::Exhausted);
+            return None;
+        }
+        tracing::debug!(
+            target: "retry",
+            operation = %self.operation,
+            attempt = self.attempts,
+            "Retrying in {:?}",
+            delay
+        );
+        Some(delay)
+    }
+
+    /// Reports the finished call to the observer
+    fn report(&self, outcome: RetryOutcome) {
+        let observer = OBSERVER.read().unwrap_or_else(PoisonError::into_inner).clone();
+        if let Some(observer) = observer {
+            observer.record(&RetryReport {
+                operation: &self.operation,
+                attempts: self.attempts,
+                elapsed: self.elapsed(),
+                outcome,
+            });
+        }
+    }
+}
+
+/// A failed attempt, marked as worth retrying or not
+#[derive(Debug)]
+pub enum Attempt<E> {
+    /// The error may go away if the operation is retried
+    Transient(E),
+    /// Retrying would fail the same way
+    Permanent(E),
+}
+
+impl<E> Attempt<E> {
+    /// The error of the attempt
+    #[must_use]
+    pub fn into_inner(self) -> E {
+        match self {
+            Attempt::Transient(e) | Attempt::Permanent(e) => e,
+        }
+    }
+}
+
+/// Marks the error of a `Result` as transient or permanent
+pub trait RetryResultExt<T, E> {
+    /// Marks the error as transient
+    ///
+    /// # Errors
+    ///
+    /// Returns the error as [`Attempt::Transient`].
+    fn transient(self) -> Result<T, Attempt<E>>;
+
+    /// Marks the error as permanent
+    ///
+    /// # Errors
+    ///
+    /// Returns the error as [`Attempt::Permanent`].
+    fn permanent(self) -> Result<T, Attempt<E>>;
+
+    /// Marks the error as transient if `retry` holds for it
+    ///
+    /// # Errors
+    ///
+    /// Returns the error as [`Attempt::Transient`] or [`Attempt::Permanent`].
+    fn transient_if(self, retry: impl FnOnce(&E) -> bool) -> Result<T, Attempt<E>>;
+
+    /// Marks the error as transient if its [`ErrorCode`] is retryable
+    ///
+    /// # Errors
+    ///
+    /// Returns the error as [`Attempt::Transient`] or [`Attempt::Permanent`].
+    ///
+    /// [`ErrorCode`]: crate::error::ErrorCode
+    fn transient_if_retryable(self) -> Result<T, Attempt<E>>
+    where
+        E: Coded;
+}
+
+impl<T, E> RetryResultExt<T, E> for Result<T, E> {
+    fn transient(self) -> Result<T, Attempt<E>> {
+        self.map_err(Attempt::Transient)
+    }
+
+    fn permanent(self) -> Result<T, Attempt<E>> {
+        self.map_err(Attempt::Permanent)
+    }
+
+    fn transient_if(self, retry: impl FnOnce(&E) -> bool) -> Result<T, Attempt<E>> {
+        self.map_err(|e| if retry(&e) { Attempt::Transient(e) } else { Attempt::Permanent(e) })
+    }
+
+    fn transient_if_retryable(self) -> Result<T, Attempt<E>>
+    where
+        E: Coded,
+    {
+        self.transient_if(|e| e.code().is_retryable())
+    }
+}
+
+/// Runs `attempt` until it succeeds, fails permanently or `policy` is
+/// exhausted
+///
+/// The call is reported to the observer as `operation`.
+///
+/// # Errors
+///
+/// Returns the error of the last attempt.
+pub async fn retry_with_policy<T, E, F, Fut>(
+    operation: &str,
+    policy: &RetryPolicy,
+    mut attempt: F,
+) -> Result<T, E>
+where
+    F: FnMut() -> Fut,
+    Fut: Future<Output = Result<T, Attempt<E>>>,
+{
+    let mut retry = policy.start(operation);
+    loop {
+        match attempt().await {
+            Ok(value) => {
+                retry.succeeded();
+                return Ok(value);
+            }
+            Err(Attempt::Permanent(e)) => {
+                retry.stopped();
+                return Err(e);
+            }
+            Err(Attempt::Transient(e)) => match retry.next_delay() {
+                Some(delay) => tokio::time::sleep(delay).await,
+                None => return Err(e),
+            },
+        }
+    }
+}
+
+/// How a call under a retry policy ended
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RetryOutcome {
+    /// An attempt succeeded
+    Succeeded,
+    /// An attempt failed permanently
+    Stopped,
+    /// Every attempt failed, or the budget ran out
+    Exhausted,
+}
+
+impl RetryOutcome {
+    /// The outcome as a metric label, e.g. `exhausted`
+    #[must_use]
+    pub fn as_str(self) -> &'static str {
+        match self {
+            RetryOutcome::Succeeded => "succeeded",
+            RetryOutcome::Stopped => "stopped",
+            RetryOutcome::Exhausted => "exhausted",
+        }
+    }
+}
+
+/// A finished call under a retry policy
+#[derive(Debug, Clone, Copy)]
+pub struct RetryReport<'a> {
+    /// Name of the operation
+    pub operation: &'a str,
+    /// Number of attempts made
+    pub attempts: u32,
+    /// Time from the start of the first attempt to the end of the last
+    pub elapsed: Duration,
+    /// How the call ended
+    pub outcome: RetryOutcome,
+}
+
+/// Receives every finished call under a retry policy
+pub trait RetryObserver: Send + Sync {
+    /// Records a finished call; runs on the caller's task, so it must not
+    /// block
+    fn record(&self, report: &RetryReport<'_>);
+}
+
+/// The installed observer
+static OBSERVER: RwLock<Option<Arc<dyn RetryObserver>>> = RwLock::new(None);
+
+/// Installs the observer finished calls are reported to, replacing the
+/// previous one
+pub fn set_observer(observer: Arc<dyn RetryObserver>) {
+    *OBSERVER.write().unwrap_or_else(PoisonError::into_inner) = Some(observer);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::sync::atomic::{AtomicU32, Ordering};
+    use std::sync::Mutex;
+
+    use crate::error::{CodedError, ErrorCode};
+
+    /// Observer keeping the reports of one operation
+    #[derive(Default)]
+    struct Recorder {
+        /// Operation, attempts and outcome of each reported call
+        reports: Mutex<Vec<(String, u32, RetryOutcome)>>,
+    }
+
+    impl RetryObserver for Recorder {
+        fn record(&self, report: &RetryReport<'_>) {
+            if report.operation.starts_with("test.") {
+                self.reports
+                    .lock()
+                    .unwrap()
+                    .push((report.operation.to_string(), report.attempts, report.outcome));
+            }
+        }
+    }
+
+    #[test]
+    fn test_backoff_delays() {
+        let ms = Duration::from_millis;
+        let policy = RetryPolicy::exponential(5, ms(100), ms(350));
+        let delays: Vec<Duration> = (1..=4).map(|attempt| policy.backoff_delay(attempt)).collect();
+        assert_eq!(delays, vec![ms(100), ms(200), ms(350), ms(350)]);
+        assert_eq!(RetryPolicy::fixed(3, ms(20)).backoff_delay(7), ms(20));
+
+        let mut retry = policy.with_jitter().start("jitter");
+        for attempt in 1..=4 {
+            let delay = retry.next_delay().unwrap();
+            let backoff = policy.backoff_delay(attempt);
+            assert!(delay >= backoff / 2 && delay <= backoff, "{delay:?} outside jitter of {backoff:?}");
+        }
+        assert_eq!(retry.next_delay(), None);
+    }
+
+    #[tokio::test]
+    async fn test_retries_transient_errors_and_reports_calls() {
+        let recorder = Arc::new(Recorder::default());
+        set_observer(recorder.clone());
+        let policy = RetryPolicy::fixed(4, Duration::from_millis(1));
+
+        // Fails twice, then succeeds
+        let calls = AtomicU32::new(0);
+        let result: Result<u32, String> = retry_with_policy("test.flaky", &policy, || async {
+            let call = calls.fetch_add(1, Ordering::SeqCst) + 1;
+            (if call < 3 { Err("busy".to_string()) } else { Ok(call) }).transient()
+        })
+        .await;
+        assert_eq!(result, Ok(3));
+
+        // A permanent error is not retried
+        let result: Result<(), CodedError> = retry_with_policy("test.denied", &policy, || async {
+            Err(CodedError::new(ErrorCode::PermissionDenied, "denied")).transient_if_retryable()
+        })
+        .await;
+        assert_eq!(result.unwrap_err().code(), ErrorCode::PermissionDenied);
+
+        // Transient errors are retried until the attempts run out
+        let result: Result<(), CodedError> = retry_with_policy("test.down", &policy, || async {
+            Err(CodedError::new(ErrorCode::Unavailable, "down")).transient_if_retryable()
+        })
+        .await;
+        assert!(result.is_err());
+
+        let reports = recorder.reports.lock().unwrap().clone();
+        assert_eq!(
+            reports,
+            vec![
+                ("test.flaky".to_string(), 3, RetryOutcome::Succeeded),
+                ("test.denied".to_string(), 1, RetryOutcome::Stopped),
+                ("test.down".to_string(), 4, RetryOutcome::Exhausted),
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_budget_bounds_the_call() {
+        let policy = RetryPolicy::budget(Duration::from_millis(50), Duration::from_millis(20), Duration::from_millis(20));
+        let started = Instant::now();
+        let mut retry = policy.start("budget");
+        while let Some(delay) = retry.next_delay() {
+            tokio::time::sleep(delay).await;
+        }
+        assert!(started.elapsed() <= Duration::from_millis(50) + Duration::from_millis(30));
+        assert!((2..=3).contains(&retry.attempts()), "{} attempts", retry.attempts());
+    }
+}
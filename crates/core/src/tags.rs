@@ -0,0 +1,247 @@
+//! Tags labeling jobs, commands, experiments and datasets
+//!
+//! A tag is a key with an optional value, written `experiment=rnaseq` or
+//! just `urgent`. Keys are case-insensitive and stored in lowercase; values
+//! keep their case. As a filter, a tag without a value matches every tag
+//! with its key, and a resource matches a list of filters when it matches
+//! each of them.
+
+use std::fmt;
+use std::str::FromStr;
+
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+/// Longest tag key
+pub const MAX_KEY_LEN: usize = 64;
+
+/// Longest tag value
+pub const MAX_VALUE_LEN: usize = 256;
+
+/// Errors of tags
+#[derive(Debug, Clone, PartialEq, Eq, Error)]
+pub enum TagError {
+    /// The tag cannot be parsed
+    #[error("Invalid tag '{tag}': {reason}")]
+    InvalidTag {
+        /// The tag as given
+        tag: String,
+        /// What is wrong with it
+        reason: String,
+    },
+
+    /// The kind of resource is unknown
+    #[error("Unknown resource kind '{0}'; expected job, command, experiment or dataset")]
+    UnknownKind(String),
+}
+
+/// A key with an optional value
+#[derive(Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(try_from = "String", into = "String")]
+pub struct Tag {
+    /// Lowercase key, e.g. `experiment`
+    key: String,
+    /// Value, e.g. `rnaseq`
+    value: Option<String>,
+}
+
+impl Tag {
+    /// A tag with a key and an optional value
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the key is empty, too long or has characters
+    /// other than letters, digits, `-`, `_`, `.` and `/`, or if the value is
+    /// empty, too long or has control characters.
+    pub fn new(key: &str, value: Option<&str>) -> Result<Self, TagError> {
+        let invalid = |reason: &str| TagError::InvalidTag {
+            tag: value.map_or_else(|| key.to_string(), |value| format!("{key}={value}")),
+            reason: reason.to_string(),
+        };
+        let key = key.trim().to_lowercase();
+        if key.is_empty() {
+            return Err(invalid("the key is empty"));
+        }
+        if key.len() > MAX_KEY_LEN {
+            return Err(invalid(&format!("the key is longer than {MAX_KEY_LEN} bytes")));
+        }
+        if !key.chars().all(|c| c.is_alphanumeric() || matches!(c, '-' | '_' | '.' | '/')) {
+            return Err(invalid("keys may only have letters, digits, '-', '_', '.' and '/'"));
+        }
+        let value = match value.map(str::trim) {
+            None => None,
+            Some("") => return Err(invalid("the value is empty")),
+            Some(value) if value.len() > MAX_VALUE_LEN => {
+                return Err(invalid(&format!("the value is longer than {MAX_VALUE_LEN} bytes")));
+            }
+            Some(value) if value.chars().any(char::is_control) => {
+                return Err(invalid("the value has control characters"));
+            }
+            Some(value) => Some(value.to_string()),
+        };
+        Ok(Self { key, value })
+    }
+
+    /// The key
+    #[must_use]
+    pub fn key(&self) -> &str {
+        &self.key
+    }
+
+    /// The value, if any
+    #[must_use]
+    pub fn value(&self) -> Option<&str> {
+        self.value.as_deref()
+    }
+
+    /// Whether `tag` matches this tag used as a filter
+    ///
+    /// A filter without a value matches any value of its key.
+    #[must_use]
+    pub fn matches(&self, tag: &Tag) -> bool {
+        self.key == tag.key && (self.value.is_none() || self.value == tag.value)
+    }
+}
+
+/// Whether `tags` match every filter
+#[must_use]
+pub fn matches_all(filters: &[Tag], tags: &[Tag]) -> bool {
+    filters.iter().all(|filter| tags.iter().any(|tag| filter.matches(tag)))
+}
+
+/// Parses tags, as given to `--tag` or a comma-separated `tag` parameter
+///
+/// # Errors
+///
+/// Returns the first tag that cannot be parsed.
+pub fn parse_tags<'a>(tags: impl IntoIterator<Item = &'a str>) -> Result<Vec<Tag>, TagError> {
+    let mut parsed: Vec<Tag> = tags
+        .into_iter()
+        .flat_map(|tags| tags.split(','))
+        .filter(|tag| !tag.trim().is_empty())
+        .map(str::parse)
+        .collect::<Result<_, _>>()?;
+    parsed.sort();
+    parsed.dedup();
+    Ok(parsed)
+}
+
+impl FromStr for Tag {
+    type Err = TagError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.split_once('=') {
+            Some((key, value)) => Self::new(key, Some(value)),
+            None => Self::new(s, None),
+        }
+    }
+}
+
+impl fmt::Display for Tag {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.value {
+            Some(value) => write!(f, "{}={value}", self.key),
+            None => f.write_str(&self.key),
+        }
+    }
+}
+
+impl TryFrom<String> for Tag {
+    type Error = TagError;
+
+    fn try_from(value: String) -> Result<Self, Self::Error> {
+        value.parse()
+    }
+}
+
+impl From<Tag> for String {
+    fn from(tag: Tag) -> Self {
+        tag.to_string()
+    }
+}
+
+/// The kinds of resources that can be tagged
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ResourceKind {
+    /// A job
+    Job,
+    /// A command definition
+    Command,
+    /// An experiment grouping jobs
+    Experiment,
+    /// A dataset, such as an uploaded file
+    Dataset,
+}
+
+impl ResourceKind {
+    /// Every kind
+    pub const ALL: [Self; 4] = [Self::Job, Self::Command, Self::Experiment, Self::Dataset];
+
+    /// The kind's name, e.g. `dataset`
+    #[must_use]
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Job => "job",
+            Self::Command => "command",
+            Self::Experiment => "experiment",
+            Self::Dataset => "dataset",
+        }
+    }
+}
+
+impl FromStr for ResourceKind {
+    type Err = TagError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let name = s.trim().to_lowercase();
+        let name = name.strip_suffix('s').unwrap_or(&name);
+        Self::ALL
+            .into_iter()
+            .find(|kind| kind.as_str() == name)
+            .ok_or_else(|| TagError::UnknownKind(s.to_string()))
+    }
+}
+
+impl fmt::Display for ResourceKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_tags_parse_and_match() {
+        let tag: Tag = " Experiment = RNAseq ".parse().unwrap();
+        assert_eq!((tag.key(), tag.value()), ("experiment", Some("RNAseq")));
+        assert_eq!(tag.to_string(), "experiment=RNAseq");
+        assert_eq!("urgent".parse::<Tag>().unwrap().value(), None);
+        assert!("=x".parse::<Tag>().is_err());
+        assert!("key=".parse::<Tag>().is_err());
+        assert!("two words".parse::<Tag>().is_err());
+        assert_eq!("run=a=b".parse::<Tag>().unwrap().value(), Some("a=b"));
+
+        let tags = parse_tags(["experiment=rnaseq,urgent", "urgent"]).unwrap();
+        assert_eq!(tags.len(), 2);
+        assert!(matches_all(&parse_tags(["experiment"]).unwrap(), &tags));
+        assert!(matches_all(&parse_tags(["experiment=rnaseq", "urgent"]).unwrap(), &tags));
+        assert!(!matches_all(&parse_tags(["experiment=chipseq"]).unwrap(), &tags));
+        assert!(!matches_all(&parse_tags(["owner"]).unwrap(), &tags));
+        assert!(matches_all(&[], &tags));
+
+        let json = serde_json::to_string(&tags).unwrap();
+        assert_eq!(json, r#"["experiment=rnaseq","urgent"]"#);
+        assert_eq!(serde_json::from_str::<Vec<Tag>>(&json).unwrap(), tags);
+    }
+
+    #[test]
+    fn test_resource_kinds_parse_singular_and_plural() {
+        assert_eq!("datasets".parse::<ResourceKind>().unwrap(), ResourceKind::Dataset);
+        assert_eq!("Job".parse::<ResourceKind>().unwrap(), ResourceKind::Job);
+        assert!("workflow".parse::<ResourceKind>().is_err());
+        assert_eq!(ResourceKind::Experiment.to_string(), "experiment");
+    }
+}
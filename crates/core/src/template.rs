@@ -0,0 +1,498 @@
+//! Templates for reports, scaffolds, notifications and webhook payloads
+//!
+//! A [`TemplateEngine`] renders [Handlebars](https://handlebarsjs.com)
+//! templates over JSON-serializable data. Templates come from three places:
+//!
+//! - built-in templates registered by the code using the engine;
+//! - files ending in `.hbs` in the engine's template directories, named by
+//!   their path relative to the directory without the extension, such as
+//!   `reports/job.md` for `reports/job.md.hbs`; a file overrides a built-in
+//!   template of the same name;
+//! - inline sources, such as a template in a configuration or an API
+//!   request.
+//!
+//! Templates are compiled once and cached. A file is compiled again when it
+//! changes, and the least recently added inline sources are dropped from the
+//! cache when it holds too many of them.
+//!
+//! Rendering is sandboxed: a template can only read the data it is given
+//! and call the helpers of the engine, none of which perform I/O. Partials
+//! are not supported, so a template cannot include another one or itself.
+//! Values are inserted as they are, without HTML escaping; the `html`
+//! helper escapes a value. Referring to a value the data does not have is
+//! an error, and the size of templates and of what they render is limited.
+//!
+//! Besides the built-in helpers of Handlebars (`if`, `unless`, `each`,
+//! `with`, `lookup`, `eq`, `ne`, `gt`, `lt`, `and`, `or`, `not`, `len`), the
+//! engine has these:
+//!
+//! - `upper` and `lower` change the case of a string;
+//! - `truncate s n` keeps the first `n` characters of a string;
+//! - `json v` writes a value as JSON, as needed in JSON payloads;
+//! - `html v` escapes a string for HTML;
+//! - `default v fallback` is `fallback` if `v` is null or an empty string;
+//! - `join items separator` joins the items of an array.
+
+use std::collections::{HashMap, VecDeque};
+use std::fs;
+use std::path::{Component, Path, PathBuf};
+use std::sync::{OnceLock, PoisonError, RwLock};
+use std::time::SystemTime;
+
+use handlebars::Handlebars;
+use serde::Serialize;
+use sha2::{Digest, Sha256};
+use thiserror::Error;
+
+/// Extension of template files
+pub const TEMPLATE_EXTENSION: &str = "hbs";
+
+/// Largest template source accepted, in bytes
+pub const MAX_TEMPLATE_LEN: usize = 256 * 1024;
+
+/// Largest output a template may render, in bytes
+pub const MAX_OUTPUT_LEN: usize = 4 * 1024 * 1024;
+
+/// Number of inline sources kept compiled
+const MAX_CACHED_INLINE: usize = 128;
+
+/// Deepest directory searched for template files
+const MAX_DISCOVERY_DEPTH: usize = 8;
+
+/// Prefix of the registry names of template files
+const FILE_PREFIX: &str = "file:";
+
+/// Prefix of the registry names of inline sources
+const INLINE_PREFIX: &str = "inline:";
+
+/// Errors of templates
+#[derive(Debug, Clone, PartialEq, Eq, Error)]
+pub enum TemplateError {
+    /// No template has the name
+    #[error("Template '{0}' not found")]
+    NotFound(String),
+
+    /// A template name is not a relative path of plain components
+    #[error("Invalid template name '{0}'")]
+    InvalidName(String),
+
+    /// A template cannot be compiled
+    #[error("Invalid template '{name}': {message}")]
+    Invalid {
+        /// Name of the template
+        name: String,
+        /// What is wrong with it
+        message: String,
+    },
+
+    /// Rendering a template failed
+    #[error("Failed to render template '{name}': {message}")]
+    Render {
+        /// Name of the template
+        name: String,
+        /// Why rendering failed
+        message: String,
+    },
+
+    /// A template or its output is larger than allowed
+    #[error("Template '{name}' exceeds the limit of {limit} bytes")]
+    TooLarge {
+        /// Name of the template
+        name: String,
+        /// The limit exceeded
+        limit: usize,
+    },
+
+    /// A template file cannot be read
+    #[error("Template I/O error: {0}")]
+    Io(String),
+}
+
+/// Result of template operations
+pub type TemplateResult<T> = Result<T, TemplateError>;
+
+/// A template the engine can render by name
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct TemplateInfo {
+    /// Name of the template
+    pub name: String,
+    /// File the template is read from; `None` for built-in templates
+    pub path: Option<PathBuf>,
+}
+
+/// Helpers available to every template
+#[allow(missing_docs, clippy::missing_docs_in_private_items, clippy::needless_pass_by_value)]
+mod helpers {
+    use handlebars::handlebars_helper;
+    use serde_json::Value;
+
+    handlebars_helper!(upper: |s: str| s.to_uppercase());
+    handlebars_helper!(lower: |s: str| s.to_lowercase());
+    handlebars_helper!(truncate: |s: str, n: u64| {
+        s.chars().take(usize::try_from(n).unwrap_or(usize::MAX)).collect::<String>()
+    });
+    handlebars_helper!(json: |value: Json| value.to_string());
+    handlebars_helper!(html: |s: str| handlebars::html_escape(s));
+    handlebars_helper!(default: |value: Json, fallback: Json| {
+        if value.is_null() || value.as_str() == Some("") {
+            fallback.clone()
+        } else {
+            value.clone()
+        }
+    });
+    handlebars_helper!(join: |items: array, separator: str| {
+        items
+            .iter()
+            .map(|item| match item {
+                Value::String(s) => s.clone(),
+                other => other.to_string(),
+            })
+            .collect::<Vec<_>>()
+            .join(separator)
+    });
+}
+
+/// Compiled templates and where they came from
+#[derive(Default)]
+struct Cache {
+    /// Registry holding every compiled template
+    registry: Handlebars<'static>,
+    /// Template files compiled, by name, with the time they were modified
+    files: HashMap<String, (PathBuf, Option<SystemTime>)>,
+    /// Registry names of inline sources compiled, oldest first
+    inline: VecDeque<String>,
+}
+
+/// Renders built-in, file and inline templates
+pub struct TemplateEngine {
+    /// Directories searched for template files, in order of precedence
+    dirs: Vec<PathBuf>,
+    /// Compiled templates
+    cache: RwLock<Cache>,
+}
+
+impl std::fmt::Debug for TemplateEngine {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("TemplateEngine").field("dirs", &self.dirs).finish_non_exhaustive()
+    }
+}
+
+impl Default for TemplateEngine {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl TemplateEngine {
+    /// Create an engine without template directories
+    #[must_use]
+    pub fn new() -> Self {
+        let mut registry = Handlebars::new();
+        registry.set_strict_mode(true);
+        registry.register_escape_fn(handlebars::no_escape);
+        registry.register_helper("upper", Box::new(helpers::upper));
+        registry.register_helper("lower", Box::new(helpers::lower));
+        registry.register_helper("truncate", Box::new(helpers::truncate));
+        registry.register_helper("json", Box::new(helpers::json));
+        registry.register_helper("html", Box::new(helpers::html));
+        registry.register_helper("default", Box::new(helpers::default));
+        registry.register_helper("join", Box::new(helpers::join));
+        Self {
+            dirs: Vec::new(),
+            cache: RwLock::new(Cache {
+                registry,
+                ..Cache::default()
+            }),
+        }
+    }
+
+    /// Also search `dir` for template files, after the directories added
+    /// before
+    #[must_use]
+    pub fn with_dir(mut self, dir: impl Into<PathBuf>) -> Self {
+        self.dirs.push(dir.into());
+        self
+    }
+
+    /// Directories searched for template files
+    #[must_use]
+    pub fn dirs(&self) -> &[PathBuf] {
+        &self.dirs
+    }
+
+    /// Register a built-in template, replacing one of the same name
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the name or the template is invalid
+    pub fn register(&self, name: &str, source: &str) -> TemplateResult<()> {
+        validate_name(name)?;
+        let mut cache = self.cache.write().unwrap_or_else(PoisonError::into_inner);
+        compile(&mut cache.registry, name, name, source)
+    }
+
+    /// Whether a template file or built-in template has the name
+    #[must_use]
+    pub fn has(&self, name: &str) -> bool {
+        self.find_file(name).is_some()
+            || self.cache.read().unwrap_or_else(PoisonError::into_inner).registry.has_template(name)
+    }
+
+    /// The templates in the template directories and the built-in ones,
+    /// sorted by name
+    #[must_use]
+    pub fn templates(&self) -> Vec<TemplateInfo> {
+        let mut found: HashMap<String, Option<PathBuf>> = HashMap::new();
+        for dir in self.dirs.iter().rev() {
+            let mut files = Vec::new();
+            discover(dir, dir, 0, &mut files);
+            found.extend(files.into_iter().map(|(name, path)| (name, Some(path))));
+        }
+        let cache = self.cache.read().unwrap_or_else(PoisonError::into_inner);
+        for name in cache.registry.get_templates().keys() {
+            if !name.starts_with(FILE_PREFIX) && !name.starts_with(INLINE_PREFIX) {
+                found.entry(name.clone()).or_insert(None);
+            }
+        }
+        let mut templates: Vec<_> = found.into_iter().map(|(name, path)| TemplateInfo { name, path }).collect();
+        templates.sort_by(|a, b| a.name.cmp(&b.name));
+        templates
+    }
+
+    /// Render the template file or built-in template `name` with `data`
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if there is no such template, it is invalid, or
+    /// rendering fails
+    pub fn render(&self, name: &str, data: &impl Serialize) -> TemplateResult<String> {
+        validate_name(name)?;
+        if let Some(key) = self.load_file(name)? {
+            return self.render_key(&key, name, data);
+        }
+        if !self.cache.read().unwrap_or_else(PoisonError::into_inner).registry.has_template(name) {
+            return Err(TemplateError::NotFound(name.to_string()));
+        }
+        self.render_key(name, name, data)
+    }
+
+    /// Render the template file `name` if there is one, else `fallback`
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the template to render is invalid or rendering
+    /// fails
+    pub fn render_or(&self, name: &str, fallback: &str, data: &impl Serialize) -> TemplateResult<String> {
+        validate_name(name)?;
+        match self.load_file(name)? {
+            Some(key) => self.render_key(&key, name, data),
+            None => self.render_str(fallback, data),
+        }
+    }
+
+    /// Render an inline template source with `data`
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the template is invalid or rendering fails
+    pub fn render_str(&self, source: &str, data: &impl Serialize) -> TemplateResult<String> {
+        let key = format!("{INLINE_PREFIX}{}", hex::encode(&Sha256::digest(source.as_bytes())[..16]));
+        let cached = self.cache.read().unwrap_or_else(PoisonError::into_inner).registry.has_template(&key);
+        if !cached {
+            let mut cache = self.cache.write().unwrap_or_else(PoisonError::into_inner);
+            if !cache.registry.has_template(&key) {
+                compile(&mut cache.registry, &key, "inline", source)?;
+                cache.inline.push_back(key.clone());
+                while cache.inline.len() > MAX_CACHED_INLINE {
+                    if let Some(evicted) = cache.inline.pop_front() {
+                        cache.registry.unregister_template(&evicted);
+                    }
+                }
+            }
+        }
+        self.render_key(&key, "inline", data)
+    }
+
+    /// Check that an inline template source compiles, without rendering it
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the template is invalid
+    pub fn check(source: &str) -> TemplateResult<()> {
+        compile(&mut Handlebars::new(), "check", "inline", source)
+    }
+
+    /// Render the compiled template registered as `key`
+    fn render_key(&self, key: &str, name: &str, data: &impl Serialize) -> TemplateResult<String> {
+        let output = self
+            .cache
+            .read()
+            .unwrap_or_else(PoisonError::into_inner)
+            .registry
+            .render(key, data)
+            .map_err(|e| TemplateError::Render {
+                name: name.to_string(),
+                message: e.to_string(),
+            })?;
+        if output.len() > MAX_OUTPUT_LEN {
+            return Err(TemplateError::TooLarge {
+                name: name.to_string(),
+                limit: MAX_OUTPUT_LEN,
+            });
+        }
+        Ok(output)
+    }
+
+    /// The file of the template `name`, in the first directory having one
+    fn find_file(&self, name: &str) -> Option<PathBuf> {
+        self.dirs
+            .iter()
+            .map(|dir| dir.join(format!("{name}.{TEMPLATE_EXTENSION}")))
+            .find(|path| path.is_file())
+    }
+
+    /// Compile the file of the template `name` unless it is cached and
+    /// unchanged, returning its registry name; `None` if there is no file
+    fn load_file(&self, name: &str) -> TemplateResult<Option<String>> {
+        let Some(path) = self.find_file(name) else {
+            return Ok(None);
+        };
+        let key = format!("{FILE_PREFIX}{name}");
+        let modified = fs::metadata(&path).and_then(|metadata| metadata.modified()).ok();
+        let current = (path.clone(), modified);
+        let cached = self.cache.read().unwrap_or_else(PoisonError::into_inner).files.get(name) == Some(&current);
+        if !cached || modified.is_none() {
+            let source = fs::read_to_string(&path).map_err(|e| TemplateError::Io(format!("{}: {e}", path.display())))?;
+            let mut cache = self.cache.write().unwrap_or_else(PoisonError::into_inner);
+            compile(&mut cache.registry, &key, name, &source)?;
+            cache.files.insert(name.to_string(), current);
+        }
+        Ok(Some(key))
+    }
+}
+
+/// Compile `source` into `registry` as `key`
+fn compile(registry: &mut Handlebars<'static>, key: &str, name: &str, source: &str) -> TemplateResult<()> {
+    if source.len() > MAX_TEMPLATE_LEN {
+        return Err(TemplateError::TooLarge {
+            name: name.to_string(),
+            limit: MAX_TEMPLATE_LEN,
+        });
+    }
+    let invalid = |message: String| TemplateError::Invalid {
+        name: name.to_string(),
+        message,
+    };
+    if source.contains("{{>") || source.contains("{{#>") || source.contains("{{~>") || source.contains("{{~#>") {
+        return Err(invalid("partials are not supported".to_string()));
+    }
+    registry.register_template_string(key, source).map_err(|e| invalid(e.to_string()))
+}
+
+/// Check that a template name is a relative path of plain components
+fn validate_name(name: &str) -> TemplateResult<()> {
+    let valid = !name.is_empty()
+        && !name.contains('\\')
+        && name.chars().all(|c| c.is_ascii_alphanumeric() || "._-/".contains(c))
+        && Path::new(name).components().all(|component| matches!(component, Component::Normal(_)));
+    if valid {
+        Ok(())
+    } else {
+        Err(TemplateError::InvalidName(name.to_string()))
+    }
+}
+
+/// Collect the template files below `dir` as `(name, path)` pairs
+fn discover(root: &Path, dir: &Path, depth: usize, found: &mut Vec<(String, PathBuf)>) {
+    let Ok(entries) = fs::read_dir(dir) else {
+        return;
+    };
+    for path in entries.filter_map(|entry| entry.ok().map(|entry| entry.path())) {
+        if path.is_dir() {
+            if depth < MAX_DISCOVERY_DEPTH {
+                discover(root, &path, depth + 1, found);
+            }
+            continue;
+        }
+        if path.extension().and_then(|extension| extension.to_str()) != Some(TEMPLATE_EXTENSION) {
+            continue;
+        }
+        let Ok(relative) = path.with_extension("").strip_prefix(root).map(Path::to_path_buf) else {
+            continue;
+        };
+        let name = relative
+            .components()
+            .map(|component| component.as_os_str().to_string_lossy())
+            .collect::<Vec<_>>()
+            .join("/");
+        if validate_name(&name).is_ok() {
+            found.push((name, path));
+        }
+    }
+}
+
+/// The user's template directory: `$SQUIRREL_TEMPLATE_DIR`, else
+/// `~/.squirrel/templates`
+#[must_use]
+pub fn default_dir() -> Option<PathBuf> {
+    std::env::var_os("SQUIRREL_TEMPLATE_DIR")
+        .map(PathBuf::from)
+        .or_else(|| std::env::var_os("HOME").map(|home| Path::new(&home).join(".squirrel").join("templates")))
+}
+
+/// The process-wide engine, searching the user's template directory
+#[must_use]
+pub fn engine() -> &'static TemplateEngine {
+    static ENGINE: OnceLock<TemplateEngine> = OnceLock::new();
+    ENGINE.get_or_init(|| match default_dir() {
+        Some(dir) => TemplateEngine::new().with_dir(dir),
+        None => TemplateEngine::new(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_renders_builtin_file_and_inline_templates() {
+        let dir = tempfile::tempdir().unwrap();
+        let engine = TemplateEngine::new().with_dir(dir.path());
+        engine.register("greeting", "Hello {{upper name}}").unwrap();
+        let data = json!({"name": "ada", "tags": ["a", 1], "empty": "", "text": "<b>"});
+        assert_eq!(engine.render("greeting", &data).unwrap(), "Hello ADA");
+
+        // A file overrides the built-in template and is reloaded when it changes
+        fs::create_dir_all(dir.path().join("mail")).unwrap();
+        fs::write(dir.path().join("greeting.hbs"), "Hi {{name}}").unwrap();
+        fs::write(dir.path().join("mail/alert.txt.hbs"), "{{join tags \", \"}}").unwrap();
+        assert_eq!(engine.render("greeting", &data).unwrap(), "Hi ada");
+        fs::write(dir.path().join("greeting.hbs"), "Hey {{name}}!").unwrap();
+        let later = SystemTime::now() + std::time::Duration::from_secs(5);
+        fs::File::options()
+            .write(true)
+            .open(dir.path().join("greeting.hbs"))
+            .unwrap()
+            .set_modified(later)
+            .unwrap();
+        assert_eq!(engine.render("greeting", &data).unwrap(), "Hey ada!");
+        assert_eq!(engine.render("mail/alert.txt", &data).unwrap(), "a, 1");
+        let names: Vec<_> = engine.templates().into_iter().map(|info| info.name).collect();
+        assert_eq!(names, ["greeting", "mail/alert.txt"]);
+        assert_eq!(engine.render_or("missing", "{{default empty \"-\"}}", &data).unwrap(), "-");
+
+        // Values are not escaped unless asked to
+        assert_eq!(
+            engine.render_str("{{text}} {{html text}} {{json tags}} {{truncate name 2}}", &data).unwrap(),
+            r#"<b> &lt;b&gt; ["a",1] ad"#
+        );
+
+        assert_eq!(engine.render("missing", &data), Err(TemplateError::NotFound("missing".to_string())));
+        assert!(matches!(engine.render("../secret", &data), Err(TemplateError::InvalidName(_))));
+        assert!(matches!(engine.render_str("{{unknown}}", &data), Err(TemplateError::Render { .. })));
+        assert!(matches!(engine.render_str("{{#if name}}", &data), Err(TemplateError::Invalid { .. })));
+        assert!(matches!(engine.render_str("{{> greeting}}", &data), Err(TemplateError::Invalid { .. })));
+        assert!(TemplateEngine::check("{{name").is_err());
+    }
+}
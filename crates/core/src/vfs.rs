@@ -0,0 +1,408 @@
+//! Virtual filesystem roots
+//!
+//! Commands and tools address files as `root://relative/path` instead of
+//! host paths. A [`Vfs`] maps each logical root, such as `workspace://`,
+//! `data://` or `tmp://`, to a directory on disk and records whether it may
+//! be written. Resolution rejects absolute paths and `..` components and
+//! checks that symlinks do not lead outside the root, so a path handed in by
+//! a user or a model cannot reach the rest of the filesystem. Code that only
+//! deals in virtual paths keeps working when a root is moved, or later backed
+//! by remote storage.
+
+use std::collections::BTreeMap;
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::{Component, Path, PathBuf};
+
+use thiserror::Error;
+
+/// Separator between a root name and the path inside it
+pub const ROOT_SEPARATOR: &str = "://";
+
+/// Root of the current workspace
+pub const WORKSPACE_ROOT: &str = "workspace";
+
+/// Root of shared input data
+pub const DATA_ROOT: &str = "data";
+
+/// Root of scratch space
+pub const TMP_ROOT: &str = "tmp";
+
+/// Errors resolving or accessing virtual paths
+#[derive(Debug, Error)]
+pub enum VfsError {
+    /// The path is not of the form `root://relative/path`
+    #[error("Invalid virtual path '{0}': expected root://path")]
+    InvalidPath(String),
+    /// No root with this name is mounted
+    #[error("Unknown root '{0}'")]
+    UnknownRoot(String),
+    /// The path would leave its root
+    #[error("Path '{0}' escapes its root")]
+    Traversal(String),
+    /// The root does not allow this access
+    #[error("{access} access to '{path}' is not allowed")]
+    PermissionDenied {
+        /// The virtual path
+        path: String,
+        /// The access that was refused
+        access: Access,
+    },
+    /// Error from the underlying filesystem
+    #[error("I/O error on '{path}': {source}")]
+    Io {
+        /// The virtual path
+        path: String,
+        /// The filesystem error
+        source: io::Error,
+    },
+}
+
+/// Kind of access to a virtual path
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Access {
+    /// Reading files and listing directories
+    Read,
+    /// Creating, changing and removing files
+    Write,
+}
+
+impl fmt::Display for Access {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Read => write!(f, "Read"),
+            Self::Write => write!(f, "Write"),
+        }
+    }
+}
+
+/// Accesses a root permits
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RootAccess {
+    /// Files can be read but not changed
+    ReadOnly,
+    /// Files can be read and changed
+    ReadWrite,
+}
+
+impl RootAccess {
+    /// Whether `access` is permitted
+    #[must_use]
+    pub fn allows(self, access: Access) -> bool {
+        matches!((self, access), (Self::ReadWrite, _) | (Self::ReadOnly, Access::Read))
+    }
+}
+
+/// A logical root mapped to a host directory
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Root {
+    /// Host directory the root maps to
+    pub path: PathBuf,
+    /// Accesses the root permits
+    pub access: RootAccess,
+}
+
+/// Maps logical roots to host directories
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Vfs {
+    /// Mounted roots by name
+    roots: BTreeMap<String, Root>,
+}
+
+impl Vfs {
+    /// Create a VFS without roots
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Create a VFS with the standard roots
+    ///
+    /// `workspace://` maps to `workspace` and is read-write.
+    /// `data://` maps to `data` and is read-only.
+    /// `tmp://` maps to a `squirrel` directory in the system temporary
+    /// directory and is read-write.
+    #[must_use]
+    pub fn standard(workspace: impl Into<PathBuf>, data: impl Into<PathBuf>) -> Self {
+        Self::new()
+            .with_root(WORKSPACE_ROOT, workspace, RootAccess::ReadWrite)
+            .with_root(DATA_ROOT, data, RootAccess::ReadOnly)
+            .with_root(TMP_ROOT, std::env::temp_dir().join("squirrel"), RootAccess::ReadWrite)
+    }
+
+    /// Mount a root, replacing any root of the same name
+    #[must_use]
+    pub fn with_root(mut self, name: &str, path: impl Into<PathBuf>, access: RootAccess) -> Self {
+        self.mount(name, path, access);
+        self
+    }
+
+    /// Mount a root, returning the root it replaced
+    pub fn mount(&mut self, name: &str, path: impl Into<PathBuf>, access: RootAccess) -> Option<Root> {
+        self.roots.insert(
+            name.to_string(),
+            Root {
+                path: path.into(),
+                access,
+            },
+        )
+    }
+
+    /// The root named `name`
+    #[must_use]
+    pub fn root(&self, name: &str) -> Option<&Root> {
+        self.roots.get(name)
+    }
+
+    /// Names of the mounted roots
+    pub fn roots(&self) -> impl Iterator<Item = &str> {
+        self.roots.keys().map(String::as_str)
+    }
+
+    /// Resolve a virtual path to a host path for `access`
+    ///
+    /// # Errors
+    ///
+    /// Fails if the path is malformed, names an unknown root, leaves its
+    /// root, or if the root does not permit `access`.
+    pub fn resolve(&self, uri: &str, access: Access) -> Result<PathBuf, VfsError> {
+        let (name, relative) = uri
+            .split_once(ROOT_SEPARATOR)
+            .ok_or_else(|| VfsError::InvalidPath(uri.to_string()))?;
+        let root = self
+            .roots
+            .get(name)
+            .ok_or_else(|| VfsError::UnknownRoot(name.to_string()))?;
+        if !root.access.allows(access) {
+            return Err(VfsError::PermissionDenied {
+                path: uri.to_string(),
+                access,
+            });
+        }
+
+        let mut path = root.path.clone();
+        for component in Path::new(relative).components() {
+            match component {
+                Component::Normal(part) => path.push(part),
+                Component::CurDir => {}
+                Component::ParentDir | Component::RootDir | Component::Prefix(_) => {
+                    return Err(VfsError::Traversal(uri.to_string()));
+                }
+            }
+        }
+        check_contained(&root.path, &path, uri)?;
+        Ok(path)
+    }
+
+    /// Read a file
+    ///
+    /// # Errors
+    ///
+    /// Fails if the path cannot be resolved for reading or the file cannot
+    /// be read.
+    pub fn read(&self, uri: &str) -> Result<Vec<u8>, VfsError> {
+        let path = self.resolve(uri, Access::Read)?;
+        fs::read(path).map_err(|source| io_error(uri, source))
+    }
+
+    /// Read a UTF-8 file
+    ///
+    /// # Errors
+    ///
+    /// Fails if the path cannot be resolved for reading or the file cannot
+    /// be read as UTF-8.
+    pub fn read_to_string(&self, uri: &str) -> Result<String, VfsError> {
+        let path = self.resolve(uri, Access::Read)?;
+        fs::read_to_string(path).map_err(|source| io_error(uri, source))
+    }
+
+    /// Write a file, creating its parent directories
+    ///
+    /// The contents are written next to the file and renamed over it, so an
+    /// existing file is replaced rather than written through. Files linked
+    /// to a blob of the [`crate::blob`] store keep their blob intact.
+    ///
+    /// # Errors
+    ///
+    /// Fails if the path cannot be resolved for writing or the file cannot
+    /// be written.
+    pub fn write(&self, uri: &str, contents: impl AsRef<[u8]>) -> Result<(), VfsError> {
+        let path = self.resolve(uri, Access::Write)?;
+        let parent = path.parent().unwrap_or(&path);
+        fs::create_dir_all(parent).map_err(|source| io_error(uri, source))?;
+        let staged = parent.join(format!(".{}.tmp", uuid::Uuid::new_v4()));
+        fs::write(&staged, contents).map_err(|source| io_error(uri, source))?;
+        fs::rename(&staged, &path).map_err(|source| {
+            let _ = fs::remove_file(&staged);
+            io_error(uri, source)
+        })
+    }
+
+    /// Names of the entries of a directory, sorted, with a trailing `/` on
+    /// directories
+    ///
+    /// # Errors
+    ///
+    /// Fails if the path cannot be resolved for reading or the directory
+    /// cannot be read.
+    pub fn list(&self, uri: &str) -> Result<Vec<String>, VfsError> {
+        let path = self.resolve(uri, Access::Read)?;
+        let mut names = Vec::new();
+        for entry in fs::read_dir(path).map_err(|source| io_error(uri, source))? {
+            let entry = entry.map_err(|source| io_error(uri, source))?;
+            let mut name = entry.file_name().to_string_lossy().into_owned();
+            if entry.file_type().is_ok_and(|kind| kind.is_dir()) {
+                name.push('/');
+            }
+            names.push(name);
+        }
+        names.sort();
+        Ok(names)
+    }
+}
+
+/// Wraps a filesystem error with the virtual path it occurred on
+fn io_error(uri: &str, source: io::Error) -> VfsError {
+    VfsError::Io {
+        path: uri.to_string(),
+        source,
+    }
+}
+
+/// Symlinks followed while resolving a path before giving up on it
+const MAX_SYMLINKS: usize = 40;
+
+/// Check that symlinks along `path` do not lead outside `root`
+///
+/// The path is walked from the root one component at a time without
+/// following symlinks; each symlink is replaced by its target, whether or
+/// not that target exists. Where the walk reaches a component that does not
+/// exist, the directory it would be created in must be inside `root`, so a
+/// dangling symlink can not be used to write outside it. A root that does
+/// not exist yet contains nothing to escape through.
+fn check_contained(root: &Path, path: &Path, uri: &str) -> Result<(), VfsError> {
+    let Ok(canonical_root) = root.canonicalize() else {
+        return Ok(());
+    };
+    let relative = path.strip_prefix(root).unwrap_or(path);
+    // Components still to walk, the next one last
+    let mut pending: Vec<PathBuf> = relative.components().rev().map(|c| PathBuf::from(c.as_os_str())).collect();
+    let mut current = canonical_root.clone();
+    let mut links = 0;
+    while let Some(part) = pending.pop() {
+        match part.components().next() {
+            Some(Component::Normal(_)) => {}
+            Some(Component::ParentDir) => {
+                current.pop();
+                continue;
+            }
+            Some(Component::RootDir | Component::Prefix(_)) => {
+                current.push(&part);
+                continue;
+            }
+            Some(Component::CurDir) | None => continue,
+        }
+        let next = current.join(&part);
+        let metadata = match fs::symlink_metadata(&next) {
+            Ok(metadata) => metadata,
+            Err(source) if source.kind() == io::ErrorKind::NotFound => break,
+            Err(source) => return Err(io_error(uri, source)),
+        };
+        if !metadata.file_type().is_symlink() {
+            current = next;
+            continue;
+        }
+        links += 1;
+        if links > MAX_SYMLINKS {
+            return Err(io_error(uri, io::Error::other("too many levels of symbolic links")));
+        }
+        let target = fs::read_link(&next).map_err(|source| io_error(uri, source))?;
+        pending.extend(target.components().rev().map(|c| PathBuf::from(c.as_os_str())));
+    }
+    if current.starts_with(&canonical_root) {
+        Ok(())
+    } else {
+        Err(VfsError::Traversal(uri.to_string()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_resolve_checks_roots_and_traversal() {
+        let dir = tempfile::tempdir().unwrap();
+        let vfs = Vfs::new()
+            .with_root(WORKSPACE_ROOT, dir.path().join("ws"), RootAccess::ReadWrite)
+            .with_root(DATA_ROOT, dir.path().join("data"), RootAccess::ReadOnly);
+
+        vfs.write("workspace://results/out.txt", "42").unwrap();
+        assert_eq!(vfs.read_to_string("workspace://./results/out.txt").unwrap(), "42");
+        assert_eq!(vfs.list("workspace://").unwrap(), vec!["results/"]);
+        assert_eq!(
+            vfs.resolve("data://reads.fastq", Access::Read).unwrap(),
+            dir.path().join("data").join("reads.fastq")
+        );
+
+        assert!(matches!(
+            vfs.write("data://reads.fastq", "x"),
+            Err(VfsError::PermissionDenied { access: Access::Write, .. })
+        ));
+        assert!(matches!(
+            vfs.resolve("workspace://../secret", Access::Read),
+            Err(VfsError::Traversal(_))
+        ));
+        assert!(matches!(
+            vfs.resolve("workspace:///etc/passwd", Access::Read),
+            Err(VfsError::Traversal(_))
+        ));
+        assert!(matches!(vfs.resolve("s3://bucket/key", Access::Read), Err(VfsError::UnknownRoot(_))));
+        assert!(matches!(vfs.resolve("/etc/passwd", Access::Read), Err(VfsError::InvalidPath(_))));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_resolve_rejects_symlinks_out_of_root() {
+        let dir = tempfile::tempdir().unwrap();
+        let workspace = dir.path().join("ws");
+        fs::create_dir_all(&workspace).unwrap();
+        fs::write(dir.path().join("outside.txt"), "secret").unwrap();
+        std::os::unix::fs::symlink(dir.path(), workspace.join("escape")).unwrap();
+
+        let vfs = Vfs::new().with_root(WORKSPACE_ROOT, &workspace, RootAccess::ReadWrite);
+        assert!(matches!(
+            vfs.read_to_string("workspace://escape/outside.txt"),
+            Err(VfsError::Traversal(_))
+        ));
+        assert!(matches!(
+            vfs.write("workspace://escape/new.txt", "x"),
+            Err(VfsError::Traversal(_))
+        ));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_resolve_rejects_dangling_symlinks_out_of_root() {
+        let dir = tempfile::tempdir().unwrap();
+        let workspace = dir.path().join("ws");
+        fs::create_dir_all(workspace.join("results")).unwrap();
+        std::os::unix::fs::symlink(dir.path().join("outside.txt"), workspace.join("escape.txt")).unwrap();
+        std::os::unix::fs::symlink("../../missing", workspace.join("results/up")).unwrap();
+        std::os::unix::fs::symlink("results/new.txt", workspace.join("inside.txt")).unwrap();
+
+        let vfs = Vfs::new().with_root(WORKSPACE_ROOT, &workspace, RootAccess::ReadWrite);
+        assert!(matches!(
+            vfs.write("workspace://escape.txt", "x"),
+            Err(VfsError::Traversal(_))
+        ));
+        assert!(!dir.path().join("outside.txt").exists());
+        assert!(matches!(
+            vfs.resolve("workspace://results/up/new.txt", Access::Write),
+            Err(VfsError::Traversal(_))
+        ));
+        // Dangling symlinks that stay inside the root resolve
+        assert!(vfs.resolve("workspace://inside.txt", Access::Write).is_ok());
+    }
+}
@@ -44,6 +44,8 @@ fn create_calculator_tool() -> Tool {
                     },
                 ],
                 return_type: None,
+                version: 1,
+                deprecation: None,
             },
             Capability {
                 name: "subtract".to_string(),
@@ -63,6 +65,8 @@ fn create_calculator_tool() -> Tool {
                     },
                 ],
                 return_type: None,
+                version: 1,
+                deprecation: None,
             },
             Capability {
                 name: "multiply".to_string(),
@@ -82,6 +86,8 @@ fn create_calculator_tool() -> Tool {
                     },
                 ],
                 return_type: None,
+                version: 1,
+                deprecation: None,
             },
             Capability {
                 name: "divide".to_string(),
@@ -101,6 +107,8 @@ fn create_calculator_tool() -> Tool {
                     },
                 ],
                 return_type: None,
+                version: 1,
+                deprecation: None,
             },
         ],
         security_level: 1,
@@ -125,6 +133,8 @@ fn create_text_tool() -> Tool {
                     required: true,
                 }],
                 return_type: None,
+                version: 1,
+                deprecation: None,
             },
             Capability {
                 name: "reverse".to_string(),
@@ -136,6 +146,8 @@ fn create_text_tool() -> Tool {
                     required: true,
                 }],
                 return_type: None,
+                version: 1,
+                deprecation: None,
             },
             Capability {
                 name: "count".to_string(),
@@ -147,6 +159,8 @@ fn create_text_tool() -> Tool {
                     required: true,
                 }],
                 return_type: None,
+                version: 1,
+                deprecation: None,
             },
         ],
         security_level: 1,
@@ -322,6 +336,8 @@ fn create_remote_tool() -> Tool {
                     required: true,
                 }],
                 return_type: None,
+                version: 1,
+                deprecation: None,
             },
             Capability {
                 name: "remote_compute".to_string(),
@@ -348,6 +364,8 @@ fn create_remote_tool() -> Tool {
                     },
                 ],
                 return_type: None,
+                version: 1,
+                deprecation: None,
             },
         ],
         security_level: 2, // Higher security level for remote services
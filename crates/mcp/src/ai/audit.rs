@@ -0,0 +1,397 @@
+//! Audit log and cost tracking of the calls made to language models
+//!
+//! Each call is recorded with its prompt, the answer, the steps the answer
+//! selected, token counts, latency and estimated cost, as a record stream of
+//! the MCP persistence layer. Prompts and answers go through the configured
+//! [`Redactor`] first, so secrets typed into a request are not kept. Costs are summed by
+//! an [`LlmCostTracker`], which raises alerts as budgets are spent.
+
+use std::sync::Arc;
+use std::time::Duration;
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use squirrel_core::redaction::Redactor;
+use squirrel_monitoring::alerts::Alert;
+use squirrel_monitoring::llm_costs::{LlmCall, LlmCostTracker};
+use uuid::Uuid;
+
+use super::{AiResult, ChatMessage, Completion, Plan};
+use crate::persistence::{MCPPersistence, PersistenceConfig};
+
+/// Record stream audit records are appended to
+pub const AUDIT_STREAM: &str = "ai_audit";
+
+/// Audit log settings
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct AuditConfig {
+    /// Whether calls are recorded and priced
+    pub enabled: bool,
+}
+
+impl Default for AuditConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+        }
+    }
+}
+
+/// A call to a language model
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct AuditRecord {
+    /// Record identifier
+    pub id: Uuid,
+    /// When the call was made
+    pub at: DateTime<Utc>,
+    /// User the call was made for
+    pub user: String,
+    /// Conversation the call belongs to
+    pub conversation_id: Uuid,
+    /// Provider called
+    pub provider: String,
+    /// Model called
+    pub model: String,
+    /// Messages sent, redacted
+    pub prompt: Vec<ChatMessage>,
+    /// The model's answer, redacted
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub response: Option<String>,
+    /// Targets of the steps the answer planned
+    #[serde(default)]
+    pub tools: Vec<String>,
+    /// Tokens sent
+    pub prompt_tokens: u64,
+    /// Tokens received
+    pub completion_tokens: u64,
+    /// Time the model took to answer, in milliseconds
+    pub latency_ms: u64,
+    /// Estimated cost in dollars
+    pub cost_usd: f64,
+    /// Why the call failed
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+impl AuditRecord {
+    /// The call as priced by the cost tracker; failed calls cost nothing
+    pub fn call(&self) -> Option<LlmCall> {
+        self.error.is_none().then(|| LlmCall {
+            at: self.at,
+            user: self.user.clone(),
+            provider: self.provider.clone(),
+            model: self.model.clone(),
+            prompt_tokens: self.prompt_tokens,
+            completion_tokens: self.completion_tokens,
+            latency_ms: self.latency_ms,
+            cost_usd: self.cost_usd,
+        })
+    }
+}
+
+/// Selects audit records
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct AuditQuery {
+    /// Only calls at or after this time
+    pub from: Option<DateTime<Utc>>,
+    /// Only calls before this time
+    pub to: Option<DateTime<Utc>>,
+    /// Only calls for this user
+    pub user: Option<String>,
+    /// Only calls of this conversation
+    pub conversation_id: Option<Uuid>,
+}
+
+impl AuditQuery {
+    /// Whether `record` is selected
+    pub fn matches(&self, record: &AuditRecord) -> bool {
+        self.from.is_none_or(|from| record.at >= from)
+            && self.to.is_none_or(|to| record.at < to)
+            && self.user.as_ref().is_none_or(|user| &record.user == user)
+            && self.conversation_id.is_none_or(|id| record.conversation_id == id)
+    }
+}
+
+/// Redacted audit records kept in the MCP persistence layer
+#[derive(Debug)]
+pub struct AuditLog {
+    persistence: MCPPersistence,
+    redactor: Arc<Redactor>,
+}
+
+impl AuditLog {
+    /// Opens the audit log kept under `persistence`, redacting records with
+    /// `redactor`
+    pub fn open(persistence: PersistenceConfig, redactor: Arc<Redactor>) -> Self {
+        Self {
+            persistence: MCPPersistence::new(persistence),
+            redactor,
+        }
+    }
+
+    /// Redacts `record` and appends it to the log, returning what was stored
+    ///
+    /// # Errors
+    /// Returns a storage error if the record cannot be written
+    pub fn record(&self, mut record: AuditRecord) -> AiResult<AuditRecord> {
+        for message in &mut record.prompt {
+            message.content = self.redactor.redact_text(&message.content);
+        }
+        record.response = record.response.map(|response| self.redactor.redact_text(&response));
+        record.error = record.error.map(|error| self.redactor.redact_text(&error));
+        self.persistence.append_record(AUDIT_STREAM, &record)?;
+        Ok(record)
+    }
+
+    /// Records selected by `query`, oldest first
+    ///
+    /// # Errors
+    /// Returns a storage error if the log cannot be read
+    pub fn records(&self, query: &AuditQuery) -> AiResult<Vec<AuditRecord>> {
+        let mut records: Vec<AuditRecord> = self
+            .persistence
+            .load_records::<AuditRecord>(AUDIT_STREAM)?
+            .into_iter()
+            .filter(|record| query.matches(record))
+            .collect();
+        records.sort_by_key(|record| record.at);
+        Ok(records)
+    }
+
+    /// Removes the records of the calls made for `user`, returning how many
+    /// were removed
+    ///
+    /// # Errors
+    /// Returns a storage error if the log cannot be rewritten
+    pub fn remove_user(&self, user: &str) -> AiResult<usize> {
+        Ok(self
+            .persistence
+            .retain_records::<AuditRecord>(AUDIT_STREAM, |record| record.user != user)?)
+    }
+}
+
+/// Receives the alerts raised as budgets are spent
+pub type AlertSink = Arc<dyn Fn(&Alert) + Send + Sync>;
+
+/// Records each call in the audit log and prices it against the budgets
+pub struct CallAuditor {
+    log: AuditLog,
+    costs: Arc<LlmCostTracker>,
+    alerts: Option<AlertSink>,
+}
+
+impl std::fmt::Debug for CallAuditor {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("CallAuditor")
+            .field("log", &self.log)
+            .field("costs", &self.costs)
+            .finish_non_exhaustive()
+    }
+}
+
+impl CallAuditor {
+    /// Creates an auditor, replaying the calls already in `log` into `costs`
+    ///
+    /// # Errors
+    /// Returns a storage error if the log cannot be read
+    pub fn new(log: AuditLog, costs: Arc<LlmCostTracker>) -> AiResult<Self> {
+        costs.replay(log.records(&AuditQuery::default())?.iter().filter_map(AuditRecord::call));
+        Ok(Self { log, costs, alerts: None })
+    }
+
+    /// Hands budget alerts to `sink`; they are only logged otherwise
+    #[must_use]
+    pub fn with_alerts(mut self, sink: AlertSink) -> Self {
+        self.alerts = Some(sink);
+        self
+    }
+
+    /// The audit log
+    pub fn log(&self) -> &AuditLog {
+        &self.log
+    }
+
+    /// The costs of the calls recorded
+    pub fn costs(&self) -> &LlmCostTracker {
+        &self.costs
+    }
+
+    /// Records a call of `model` of `provider` for `user`
+    ///
+    /// Failing to store the record is logged rather than failing the
+    /// request the call was made for.
+    #[allow(clippy::too_many_arguments)]
+    pub fn record(
+        &self,
+        user: &str,
+        conversation_id: Uuid,
+        provider: &str,
+        model: &str,
+        prompt: &[ChatMessage],
+        outcome: &AiResult<(Completion, Plan)>,
+        latency: Duration,
+    ) {
+        let mut record = AuditRecord {
+            id: Uuid::new_v4(),
+            at: Utc::now(),
+            user: user.to_string(),
+            conversation_id,
+            provider: provider.to_string(),
+            model: model.to_string(),
+            prompt: prompt.to_vec(),
+            response: None,
+            tools: Vec::new(),
+            prompt_tokens: 0,
+            completion_tokens: 0,
+            latency_ms: u64::try_from(latency.as_millis()).unwrap_or(u64::MAX),
+            cost_usd: 0.0,
+            error: None,
+        };
+        match outcome {
+            Ok((completion, plan)) => {
+                record.response = Some(completion.content.clone());
+                record.tools = plan.steps.iter().map(|step| step.target.clone()).collect();
+                record.prompt_tokens = completion.prompt_tokens;
+                record.completion_tokens = completion.completion_tokens;
+                record.cost_usd =
+                    self.costs.price(provider, model, completion.prompt_tokens, completion.completion_tokens);
+            }
+            Err(e) => record.error = Some(e.to_string()),
+        }
+
+        if let Err(e) = self.log.record(record.clone()) {
+            tracing::warn!("Failed to record language model call {}: {}", record.id, e);
+        }
+        let Some(call) = record.call() else {
+            return;
+        };
+        for alert in self.costs.record(call) {
+            tracing::warn!("{}", alert.message);
+            if let Some(sink) = &self.alerts {
+                sink(&alert);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ai::AiError;
+    use squirrel_core::redaction::{RedactionConfig, RedactionRule};
+    use squirrel_monitoring::llm_costs::{BudgetScope, LlmBudget, LlmCostConfig, LlmCostQuery};
+    use std::sync::Mutex;
+
+    fn persistence(dir: &tempfile::TempDir) -> PersistenceConfig {
+        PersistenceConfig {
+            data_dir: dir.path().to_path_buf(),
+            ..PersistenceConfig::default()
+        }
+    }
+
+    /// The built-in rules, and one redacting `token: <value>`
+    fn redactor() -> Arc<Redactor> {
+        let config = RedactionConfig {
+            rules: vec![RedactionRule::Regex {
+                name: None,
+                pattern: r"(?i)\b(token:\s*)[^\s,]+".to_string(),
+                replacement: Some("${1}[REDACTED]".to_string()),
+            }],
+            ..RedactionConfig::default()
+        };
+        Arc::new(Redactor::new(&config).unwrap())
+    }
+
+    #[test]
+    fn test_records_go_through_the_redactor() {
+        let dir = tempfile::tempdir().unwrap();
+        let redactor = redactor();
+        let log = AuditLog::open(persistence(&dir), redactor.clone());
+        let record = AuditRecord {
+            id: Uuid::new_v4(),
+            at: Utc::now(),
+            user: "alice".to_string(),
+            conversation_id: Uuid::new_v4(),
+            provider: "openai".to_string(),
+            model: "gpt-4o-mini".to_string(),
+            prompt: vec![ChatMessage::user("call it with Bearer abc.def")],
+            response: Some("Using token: abc123".to_string()),
+            tools: Vec::new(),
+            prompt_tokens: 0,
+            completion_tokens: 0,
+            latency_ms: 0,
+            cost_usd: 0.0,
+            error: None,
+        };
+
+        let stored = log.record(record).unwrap();
+        assert_eq!(stored.prompt[0].content, "call it with Bearer [REDACTED]");
+        assert_eq!(stored.response.as_deref(), Some("Using token: [REDACTED]"));
+        let matched: Vec<_> = redactor.stats().into_iter().filter(|stats| stats.matches > 0).collect();
+        assert_eq!(matched.len(), 2);
+        assert_eq!(log.records(&AuditQuery::default()).unwrap(), [stored]);
+    }
+
+    #[test]
+    fn test_calls_are_recorded_priced_and_replayed() {
+        let dir = tempfile::tempdir().unwrap();
+        let costs_config = LlmCostConfig {
+            budgets: vec![LlmBudget {
+                scope: BudgetScope::User,
+                name: None,
+                window_secs: 3600,
+                max_cost_usd: 0.001,
+                warn_percent: 80.0,
+            }],
+            ..LlmCostConfig::default()
+        };
+        let alerts = Arc::new(Mutex::new(Vec::new()));
+        let sink = alerts.clone();
+        let log = AuditLog::open(persistence(&dir), redactor());
+        let auditor = CallAuditor::new(log, Arc::new(LlmCostTracker::new(costs_config.clone())))
+            .unwrap()
+            .with_alerts(Arc::new(move |alert: &Alert| sink.lock().unwrap().push(alert.message.clone())));
+
+        let conversation_id = Uuid::new_v4();
+        let prompt = [ChatMessage::user("my token: abc123, what's my usage?")];
+        let completion = Completion {
+            content: "Showing usage.".to_string(),
+            prompt_tokens: 2000,
+            completion_tokens: 1000,
+        };
+        let plan: Plan = serde_json::from_value(serde_json::json!({
+            "steps": [{ "kind": "command", "target": "usage" }]
+        }))
+        .unwrap();
+        let outcome = Ok((completion, plan));
+        auditor.record("alice", conversation_id, "openai", "gpt-4o-mini", &prompt, &outcome, Duration::from_millis(250));
+        let failed = Err(AiError::Model("timed out".to_string()));
+        auditor.record("alice", conversation_id, "openai", "gpt-4o-mini", &prompt, &failed, Duration::from_secs(60));
+
+        let records = auditor.log().records(&AuditQuery::default()).unwrap();
+        assert_eq!(records.len(), 2);
+        assert_eq!(records[0].prompt[0].content, "my token: [REDACTED], what's my usage?");
+        assert_eq!(records[0].tools, ["usage"]);
+        assert_eq!((records[0].prompt_tokens, records[0].latency_ms), (2000, 250));
+        assert!((records[0].cost_usd - 0.0009).abs() < 1e-12);
+        assert_eq!(records[1].error.as_deref(), Some("Language model error: timed out"));
+        assert_eq!(alerts.lock().unwrap().len(), 1);
+
+        let query = AuditQuery {
+            user: Some("bob".to_string()),
+            ..AuditQuery::default()
+        };
+        assert!(auditor.log().records(&query).unwrap().is_empty());
+        assert_eq!(auditor.log().remove_user("bob").unwrap(), 0);
+
+        // A new process sees the earlier calls
+        let log = AuditLog::open(persistence(&dir), redactor());
+        let reopened = CallAuditor::new(log, Arc::new(LlmCostTracker::new(costs_config))).unwrap();
+        let report = reopened.costs().report(&LlmCostQuery::default());
+        assert_eq!((report.len(), report[0].calls), (1, 1));
+
+        assert_eq!(reopened.log().remove_user("alice").unwrap(), 2);
+        assert!(reopened.log().records(&AuditQuery::default()).unwrap().is_empty());
+    }
+}
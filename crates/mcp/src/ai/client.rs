@@ -0,0 +1,199 @@
+//! Language model clients
+
+use std::time::Duration;
+
+use async_trait::async_trait;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+
+use super::{AiError, AiResult};
+
+/// Author of a chat message
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ChatRole {
+    /// Instructions to the model
+    System,
+    /// The person asking
+    User,
+    /// The model
+    Assistant,
+}
+
+/// A message sent to or received from a language model
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ChatMessage {
+    /// Author
+    pub role: ChatRole,
+    /// Text
+    pub content: String,
+}
+
+impl ChatMessage {
+    /// Creates a system message
+    pub fn system(content: impl Into<String>) -> Self {
+        Self {
+            role: ChatRole::System,
+            content: content.into(),
+        }
+    }
+
+    /// Creates a user message
+    pub fn user(content: impl Into<String>) -> Self {
+        Self {
+            role: ChatRole::User,
+            content: content.into(),
+        }
+    }
+
+    /// Creates an assistant message
+    pub fn assistant(content: impl Into<String>) -> Self {
+        Self {
+            role: ChatRole::Assistant,
+            content: content.into(),
+        }
+    }
+}
+
+/// A model's next message and the tokens it took
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Completion {
+    /// Text of the message
+    pub content: String,
+    /// Tokens sent
+    pub prompt_tokens: u64,
+    /// Tokens received
+    pub completion_tokens: u64,
+}
+
+impl Completion {
+    /// A message with token counts estimated from the text, for models that
+    /// do not report them
+    pub fn estimated(messages: &[ChatMessage], content: impl Into<String>) -> Self {
+        let content = content.into();
+        Self {
+            prompt_tokens: messages.iter().map(|message| estimate_tokens(&message.content)).sum(),
+            completion_tokens: estimate_tokens(&content),
+            content,
+        }
+    }
+}
+
+/// Rough token count of `text`, at four characters a token
+fn estimate_tokens(text: &str) -> u64 {
+    u64::try_from(text.chars().count().div_ceil(4)).unwrap_or(u64::MAX)
+}
+
+/// A language model that continues a chat
+#[async_trait]
+pub trait LlmClient: Send + Sync {
+    /// Name of the provider, which calls are priced and reported under
+    fn provider(&self) -> &str;
+
+    /// Name of the model, for logs and pricing
+    fn model(&self) -> &str;
+
+    /// Returns the model's next message after `messages`
+    ///
+    /// # Errors
+    /// Returns a model error if the model cannot be reached or fails
+    async fn complete(&self, messages: &[ChatMessage]) -> AiResult<Completion>;
+}
+
+/// Client of an OpenAI-compatible chat completions API
+///
+/// Besides OpenAI itself this covers local servers such as Ollama, vLLM and
+/// llama.cpp, which serve the same API under `/v1`.
+#[derive(Debug, Clone)]
+pub struct OpenAiCompatibleClient {
+    http: reqwest::Client,
+    endpoint: String,
+    provider: String,
+    model: String,
+    api_key: Option<String>,
+    temperature: f32,
+}
+
+impl OpenAiCompatibleClient {
+    /// Creates a client of the API at `endpoint`
+    ///
+    /// # Errors
+    /// Returns a model error if the HTTP client cannot be built
+    pub fn new(
+        endpoint: &str,
+        model: &str,
+        api_key: Option<String>,
+        temperature: f32,
+        timeout: Duration,
+    ) -> AiResult<Self> {
+        let http = reqwest::Client::builder()
+            .timeout(timeout)
+            .build()
+            .map_err(|e| AiError::Model(format!("Cannot create HTTP client: {e}")))?;
+        Ok(Self {
+            http,
+            endpoint: endpoint.trim_end_matches('/').to_string(),
+            provider: "openai".to_string(),
+            model: model.to_string(),
+            api_key,
+            temperature,
+        })
+    }
+
+    /// Sets the provider name calls are priced under, `openai` by default
+    #[must_use]
+    pub fn with_provider(mut self, provider: impl Into<String>) -> Self {
+        self.provider = provider.into();
+        self
+    }
+}
+
+#[async_trait]
+impl LlmClient for OpenAiCompatibleClient {
+    fn provider(&self) -> &str {
+        &self.provider
+    }
+
+    fn model(&self) -> &str {
+        &self.model
+    }
+
+    async fn complete(&self, messages: &[ChatMessage]) -> AiResult<Completion> {
+        let mut request = self.http.post(format!("{}/chat/completions", self.endpoint)).json(&json!({
+            "model": self.model,
+            "messages": messages,
+            "temperature": self.temperature,
+        }));
+        if let Some(api_key) = &self.api_key {
+            request = request.bearer_auth(api_key);
+        }
+
+        let response = request
+            .send()
+            .await
+            .map_err(|e| AiError::Model(format!("Cannot reach {}: {e}", self.endpoint)))?;
+        let status = response.status();
+        let body: Value = response
+            .json()
+            .await
+            .map_err(|e| AiError::Model(format!("Invalid response: {e}")))?;
+        if !status.is_success() {
+            let message = body
+                .pointer("/error/message")
+                .and_then(Value::as_str)
+                .unwrap_or("no details");
+            return Err(AiError::Model(format!("{status}: {message}")));
+        }
+        let content = body
+            .pointer("/choices/0/message/content")
+            .and_then(Value::as_str)
+            .ok_or_else(|| AiError::Model("Response has no message".to_string()))?;
+        let estimated = Completion::estimated(messages, content);
+        let tokens = |pointer| body.pointer(pointer).and_then(Value::as_u64);
+        Ok(Completion {
+            prompt_tokens: tokens("/usage/prompt_tokens").unwrap_or(estimated.prompt_tokens),
+            completion_tokens: tokens("/usage/completion_tokens").unwrap_or(estimated.completion_tokens),
+            content: estimated.content,
+        })
+    }
+}
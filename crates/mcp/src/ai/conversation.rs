@@ -0,0 +1,224 @@
+//! Conversations kept in the MCP context manager
+
+use std::sync::Arc;
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use tokio::sync::Mutex;
+use uuid::Uuid;
+
+use super::{AiError, AiResult, ChatMessage, ChatRole, Plan};
+use crate::context_manager::{Context, ContextManager};
+use crate::error::types::ContextError;
+use crate::MCPError;
+
+/// `kind` in the metadata of conversation contexts
+const CONVERSATION_KIND: &str = "assistant_conversation";
+
+/// A message of a conversation
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ConversationMessage {
+    /// Author
+    pub role: ChatRole,
+    /// Text
+    pub content: String,
+    /// The plan an assistant message answered with
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub plan: Option<Plan>,
+    /// When the message was added
+    pub at: DateTime<Utc>,
+}
+
+impl ConversationMessage {
+    /// A request from the user
+    pub fn user(content: impl Into<String>) -> Self {
+        Self {
+            role: ChatRole::User,
+            content: content.into(),
+            plan: None,
+            at: Utc::now(),
+        }
+    }
+
+    /// The assistant's answer
+    pub fn assistant(plan: &Plan) -> Self {
+        Self {
+            role: ChatRole::Assistant,
+            content: plan.reply.clone(),
+            plan: Some(plan.clone()),
+            at: Utc::now(),
+        }
+    }
+}
+
+/// A user's conversation with the assistant
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Conversation {
+    /// ID of the context holding the conversation
+    pub id: Uuid,
+    /// User the conversation belongs to
+    pub user: String,
+    /// Messages, oldest first
+    pub messages: Vec<ConversationMessage>,
+    /// When the conversation started
+    pub created_at: DateTime<Utc>,
+    /// When the last message was added
+    pub updated_at: DateTime<Utc>,
+}
+
+impl Conversation {
+    /// The last `limit` messages as sent to the model
+    ///
+    /// Answers are repeated in the JSON form the model is asked to use, so
+    /// it sees which steps it proposed.
+    pub fn chat_history(&self, limit: usize) -> Vec<ChatMessage> {
+        let start = self.messages.len().saturating_sub(limit);
+        self.messages[start..]
+            .iter()
+            .map(|message| match (&message.role, &message.plan) {
+                (ChatRole::Assistant, Some(plan)) => ChatMessage::assistant(
+                    json!({ "reply": plan.reply, "steps": plan.steps }).to_string(),
+                ),
+                _ => ChatMessage {
+                    role: message.role,
+                    content: message.content.clone(),
+                },
+            })
+            .collect()
+    }
+
+    fn from_context(context: Context) -> Option<Self> {
+        let metadata = context.metadata.as_ref()?;
+        if metadata.get("kind").and_then(|kind| kind.as_str()) != Some(CONVERSATION_KIND) {
+            return None;
+        }
+        let data: ConversationData = serde_json::from_value(context.data).ok()?;
+        Some(Self {
+            id: context.id,
+            user: data.user,
+            messages: data.messages,
+            created_at: context.created_at,
+            updated_at: context.updated_at,
+        })
+    }
+}
+
+/// Data of a conversation context
+#[derive(Serialize, Deserialize)]
+struct ConversationData {
+    user: String,
+    #[serde(default)]
+    messages: Vec<ConversationMessage>,
+}
+
+/// Stores conversations as MCP contexts
+pub struct ConversationStore {
+    contexts: Arc<ContextManager>,
+    /// Serializes appends, which read and rewrite the whole conversation
+    writes: Mutex<()>,
+}
+
+impl ConversationStore {
+    /// Creates a store over `contexts`
+    pub fn new(contexts: Arc<ContextManager>) -> Self {
+        Self {
+            contexts,
+            writes: Mutex::new(()),
+        }
+    }
+
+    /// Starts an empty conversation for `user`
+    ///
+    /// # Errors
+    /// Returns a storage error if the context cannot be created
+    pub async fn start(&self, user: &str) -> AiResult<Uuid> {
+        let now = Utc::now();
+        let context = Context {
+            id: Uuid::new_v4(),
+            name: format!("assistant conversation of {user}"),
+            data: json!({ "user": user, "messages": [] }),
+            metadata: Some(json!({ "kind": CONVERSATION_KIND, "user": user })),
+            parent_id: None,
+            created_at: now,
+            updated_at: now,
+            expires_at: None,
+        };
+        Ok(self.contexts.create_context(context).await?)
+    }
+
+    /// The conversation `id` of `user`
+    ///
+    /// # Errors
+    /// Returns [`AiError::ConversationNotFound`] if there is no such
+    /// conversation or it belongs to another user
+    pub async fn get(&self, id: Uuid, user: &str) -> AiResult<Conversation> {
+        let context = match self.contexts.get_context(id).await {
+            Ok(context) => context,
+            Err(MCPError::Context(ContextError::NotFound(_))) => return Err(AiError::ConversationNotFound(id)),
+            Err(e) => return Err(e.into()),
+        };
+        Conversation::from_context(context)
+            .filter(|conversation| conversation.user == user)
+            .ok_or(AiError::ConversationNotFound(id))
+    }
+
+    /// Adds `messages` to the conversation `id` of `user`
+    ///
+    /// # Errors
+    /// Returns an error if the conversation is not the user's or cannot be
+    /// stored
+    pub async fn append(&self, id: Uuid, user: &str, messages: Vec<ConversationMessage>) -> AiResult<()> {
+        let _guard = self.writes.lock().await;
+        let mut conversation = self.get(id, user).await?;
+        conversation.messages.extend(messages);
+        let data = ConversationData {
+            user: conversation.user,
+            messages: conversation.messages,
+        };
+        let data = serde_json::to_value(data).map_err(MCPError::from)?;
+        self.contexts.update_context(id, data, None).await?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::persistence::PersistenceConfig;
+
+    #[tokio::test]
+    async fn test_conversations_accumulate_per_user() {
+        let dir = tempfile::tempdir().unwrap();
+        let contexts = Arc::new(
+            ContextManager::with_persistence(PersistenceConfig {
+                data_dir: dir.path().to_path_buf(),
+                ..PersistenceConfig::default()
+            })
+            .await,
+        );
+        let store = ConversationStore::new(contexts);
+        let id = store.start("alice").await.unwrap();
+
+        let plan = Plan {
+            reply: "Showing usage.".to_string(),
+            ..Plan::default()
+        };
+        store
+            .append(id, "alice", vec![ConversationMessage::user("how busy was I?"), ConversationMessage::assistant(&plan)])
+            .await
+            .unwrap();
+        store.append(id, "alice", vec![ConversationMessage::user("and bob?")]).await.unwrap();
+
+        let conversation = store.get(id, "alice").await.unwrap();
+        assert_eq!(conversation.messages.len(), 3);
+        let history = conversation.chat_history(2);
+        assert_eq!(history[0].role, ChatRole::Assistant);
+        assert!(history[0].content.contains("\"steps\":[]"));
+        assert_eq!(history[1], ChatMessage::user("and bob?"));
+
+        assert!(matches!(store.get(id, "bob").await, Err(AiError::ConversationNotFound(_))));
+        assert!(store.append(id, "bob", Vec::new()).await.is_err());
+        assert!(matches!(store.get(Uuid::new_v4(), "alice").await, Err(AiError::ConversationNotFound(_))));
+    }
+}
@@ -0,0 +1,278 @@
+//! Assistant that turns natural-language requests into MCP tool calls
+//!
+//! The assistant does not depend on a particular language model: anything
+//! implementing [`LlmClient`] can back it, and [`OpenAiCompatibleClient`]
+//! covers the many servers speaking the OpenAI chat completions API.
+//!
+//! A request is answered in three steps:
+//!
+//! 1. The [`Planner`] offers the model the tools and commands the caller may
+//!    use, as a [`ToolOption`] catalog filtered by the caller's roles and
+//!    security level, and asks for a JSON [`Plan`].
+//! 2. The plan is checked against the same catalog. Steps naming anything
+//!    that was not offered, or missing required parameters, are moved to
+//!    [`Plan::rejected`] instead of being returned for execution.
+//! 3. The request and answer are appended to the conversation, which lives
+//!    in the MCP context manager so follow-up requests see what was said.
+//!
+//! With a [`CallAuditor`], every call to the model is also written to a
+//! redacted audit log and priced against the configured budgets.
+//!
+//! Executing the planned steps is left to the caller, which knows how tools
+//! and commands run in its process.
+
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use serde::{Deserialize, Serialize};
+use squirrel_core::redaction::Redactor;
+use squirrel_monitoring::llm_costs::{LlmCostConfig, LlmCostTracker};
+use uuid::Uuid;
+
+use crate::context_manager::ContextManager;
+use crate::persistence::PersistenceConfig;
+use crate::MCPError;
+
+mod audit;
+mod client;
+mod conversation;
+mod planner;
+
+pub use audit::{AlertSink, AuditConfig, AuditLog, AuditQuery, AuditRecord, CallAuditor, AUDIT_STREAM};
+pub use client::{ChatMessage, ChatRole, Completion, LlmClient, OpenAiCompatibleClient};
+pub use conversation::{Conversation, ConversationMessage, ConversationStore};
+pub use planner::{Caller, Plan, PlannedStep, Planner, RejectedStep, TargetKind, ToolOption};
+
+/// Environment variable overriding [`AssistantConfig::endpoint`]
+pub const ENDPOINT_ENV: &str = "SQUIRREL_AI_ENDPOINT";
+/// Environment variable overriding [`AssistantConfig::model`]
+pub const MODEL_ENV: &str = "SQUIRREL_AI_MODEL";
+
+/// Assistant error
+#[derive(Debug, thiserror::Error)]
+pub enum AiError {
+    /// No language model is configured
+    #[error("No language model configured; set {ENDPOINT_ENV} or the assistant endpoint")]
+    NotConfigured,
+
+    /// The language model could not be reached or answered with an error
+    #[error("Language model error: {0}")]
+    Model(String),
+
+    /// The conversation does not exist or belongs to another user
+    #[error("Conversation {0} not found")]
+    ConversationNotFound(Uuid),
+
+    /// Storing a conversation or audit record failed
+    #[error("Assistant storage error: {0}")]
+    Storage(#[from] MCPError),
+}
+
+/// Result of assistant operations
+pub type AiResult<T> = std::result::Result<T, AiError>;
+
+/// Language model and conversation settings
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct AssistantConfig {
+    /// Base URL of an OpenAI-compatible API, e.g. `https://api.openai.com/v1`
+    /// or `http://localhost:11434/v1`; without one the assistant is off
+    pub endpoint: Option<String>,
+    /// Name calls are priced and reported under
+    pub provider: String,
+    /// Model to ask
+    pub model: String,
+    /// Environment variable holding the API key, if the API needs one
+    pub api_key_env: String,
+    /// Sampling temperature; 0 keeps plans repeatable
+    pub temperature: f32,
+    /// Number of earlier messages of a conversation sent with each request
+    pub history_messages: usize,
+    /// Time to wait for the model
+    pub request_timeout_secs: u64,
+    /// Audit log of the calls made
+    pub audit: AuditConfig,
+    /// Prices and budgets calls are tracked against
+    pub costs: LlmCostConfig,
+}
+
+impl Default for AssistantConfig {
+    fn default() -> Self {
+        Self {
+            endpoint: None,
+            provider: "openai".to_string(),
+            model: "gpt-4o-mini".to_string(),
+            api_key_env: "SQUIRREL_AI_API_KEY".to_string(),
+            temperature: 0.0,
+            history_messages: 20,
+            request_timeout_secs: 60,
+            audit: AuditConfig::default(),
+            costs: LlmCostConfig::default(),
+        }
+    }
+}
+
+impl AssistantConfig {
+    /// Applies [`ENDPOINT_ENV`] and [`MODEL_ENV`] if they are set
+    #[must_use]
+    pub fn with_env_overrides(mut self) -> Self {
+        if let Ok(endpoint) = std::env::var(ENDPOINT_ENV) {
+            self.endpoint = Some(endpoint);
+        }
+        if let Ok(model) = std::env::var(MODEL_ENV) {
+            self.model = model;
+        }
+        self
+    }
+
+    /// Creates a client for the configured model
+    ///
+    /// # Errors
+    /// Returns [`AiError::NotConfigured`] without an endpoint, or a model
+    /// error if the HTTP client cannot be built
+    pub fn client(&self) -> AiResult<OpenAiCompatibleClient> {
+        let endpoint = self.endpoint.as_deref().ok_or(AiError::NotConfigured)?;
+        let api_key = std::env::var(&self.api_key_env).ok().filter(|key| !key.is_empty());
+        let client = OpenAiCompatibleClient::new(
+            endpoint,
+            &self.model,
+            api_key,
+            self.temperature,
+            Duration::from_secs(self.request_timeout_secs),
+        )?;
+        Ok(client.with_provider(&self.provider))
+    }
+
+    /// Creates an auditor keeping its log under `persistence` and redacting
+    /// it with `redactor`, or `None` if auditing is off
+    ///
+    /// # Errors
+    /// Returns an error if the calls already logged cannot be read
+    pub fn auditor(&self, persistence: PersistenceConfig, redactor: Arc<Redactor>) -> AiResult<Option<CallAuditor>> {
+        if !self.audit.enabled {
+            return Ok(None);
+        }
+        let log = AuditLog::open(persistence, redactor);
+        CallAuditor::new(log, Arc::new(LlmCostTracker::new(self.costs.clone()))).map(Some)
+    }
+}
+
+/// Answer to a request
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Answer {
+    /// Conversation the request was added to
+    pub conversation_id: Uuid,
+    /// What the assistant said
+    pub reply: String,
+    /// Tool and command calls the assistant proposes
+    pub plan: Plan,
+}
+
+/// Plans requests and keeps the conversations they belong to
+pub struct Assistant {
+    client: Arc<dyn LlmClient>,
+    planner: Planner,
+    conversations: ConversationStore,
+    history_messages: usize,
+    auditor: Option<CallAuditor>,
+}
+
+impl std::fmt::Debug for Assistant {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Assistant")
+            .field("provider", &self.client.provider())
+            .field("model", &self.client.model())
+            .field("history_messages", &self.history_messages)
+            .field("audited", &self.auditor.is_some())
+            .finish()
+    }
+}
+
+impl Assistant {
+    /// Creates an assistant asking `client` and storing conversations in `contexts`
+    pub fn new(client: Arc<dyn LlmClient>, contexts: Arc<ContextManager>, config: &AssistantConfig) -> Self {
+        Self {
+            planner: Planner::new(client.clone()),
+            client,
+            conversations: ConversationStore::new(contexts),
+            history_messages: config.history_messages,
+            auditor: None,
+        }
+    }
+
+    /// Records every call to the model with `auditor`
+    #[must_use]
+    pub fn with_auditor(mut self, auditor: CallAuditor) -> Self {
+        self.auditor = Some(auditor);
+        self
+    }
+
+    /// Name of the provider the assistant calls
+    pub fn provider(&self) -> &str {
+        self.client.provider()
+    }
+
+    /// The stored conversations
+    pub fn conversations(&self) -> &ConversationStore {
+        &self.conversations
+    }
+
+    /// The audit log and costs of the calls made, if auditing is on
+    pub fn auditor(&self) -> Option<&CallAuditor> {
+        self.auditor.as_ref()
+    }
+
+    /// Answers `request` from `caller`, continuing `conversation_id` if given
+    ///
+    /// # Errors
+    /// Returns an error if the conversation is not the caller's, the model
+    /// fails or the conversation cannot be stored
+    pub async fn ask(
+        &self,
+        caller: &Caller,
+        conversation_id: Option<Uuid>,
+        request: &str,
+        catalog: &[ToolOption],
+    ) -> AiResult<Answer> {
+        let (conversation_id, history) = match conversation_id {
+            Some(id) => {
+                let conversation = self.conversations.get(id, &caller.user).await?;
+                (id, conversation.chat_history(self.history_messages))
+            }
+            None => (self.conversations.start(&caller.user).await?, Vec::new()),
+        };
+
+        let prompt = self.planner.prompt(caller, catalog, &history, request);
+        let started = Instant::now();
+        let outcome = self.client.complete(&prompt).await.map(|completion| {
+            let plan = self.planner.review(&completion.content, caller, catalog);
+            (completion, plan)
+        });
+        if let Some(auditor) = &self.auditor {
+            auditor.record(
+                &caller.user,
+                conversation_id,
+                self.client.provider(),
+                self.client.model(),
+                &prompt,
+                &outcome,
+                started.elapsed(),
+            );
+        }
+        let (_, plan) = outcome?;
+
+        self.conversations
+            .append(
+                conversation_id,
+                &caller.user,
+                vec![ConversationMessage::user(request), ConversationMessage::assistant(&plan)],
+            )
+            .await?;
+
+        Ok(Answer {
+            conversation_id,
+            reply: plan.reply.clone(),
+            plan,
+        })
+    }
+}
@@ -0,0 +1,451 @@
+//! Mapping requests to the tools and commands a caller may use
+
+use std::collections::BTreeMap;
+use std::fmt;
+use std::sync::Arc;
+
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Map, Value};
+
+use super::{AiResult, ChatMessage, LlmClient};
+use crate::tool::{ParameterType, Tool};
+use crate::types::SecurityLevel;
+
+/// Whether a step runs an MCP tool capability or a command
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum TargetKind {
+    /// A capability of a registered tool, named `tool_id/capability`
+    Tool,
+    /// A command
+    Command,
+}
+
+impl fmt::Display for TargetKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Tool => write!(f, "tool"),
+            Self::Command => write!(f, "command"),
+        }
+    }
+}
+
+/// Who a request comes from
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Caller {
+    /// User ID
+    pub user: String,
+    /// Roles of the user
+    pub roles: Vec<String>,
+    /// Highest tool security level the user may use
+    pub security_level: SecurityLevel,
+}
+
+impl Caller {
+    /// Creates a caller; `Admin` users may use tools of any security level,
+    /// others those up to [`SecurityLevel::Standard`]
+    pub fn new(user: impl Into<String>, roles: Vec<String>) -> Self {
+        let security_level = if roles.iter().any(|role| role.eq_ignore_ascii_case("admin")) {
+            SecurityLevel::High
+        } else {
+            SecurityLevel::Standard
+        };
+        Self {
+            user: user.into(),
+            roles,
+            security_level,
+        }
+    }
+}
+
+/// A tool capability or command the planner can offer
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ToolOption {
+    /// Tool capability or command
+    pub kind: TargetKind,
+    /// Command name, or `tool_id/capability`
+    pub target: String,
+    /// What it does
+    pub description: String,
+    /// JSON schema of the arguments
+    pub parameters: Value,
+    /// Arguments that must be given
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub required: Vec<String>,
+    /// Security level the caller needs, 0 to 10
+    #[serde(default)]
+    pub security_level: u8,
+    /// Roles allowed to use it; any role if empty
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub roles: Vec<String>,
+}
+
+impl ToolOption {
+    /// Offers a command taking arguments described by a JSON schema
+    pub fn command(name: impl Into<String>, description: impl Into<String>, parameters: Value) -> Self {
+        let required = parameters
+            .get("required")
+            .and_then(Value::as_array)
+            .map(|names| names.iter().filter_map(Value::as_str).map(str::to_string).collect())
+            .unwrap_or_default();
+        Self {
+            kind: TargetKind::Command,
+            target: name.into(),
+            description: description.into(),
+            parameters,
+            required,
+            security_level: 0,
+            roles: Vec::new(),
+        }
+    }
+
+    /// Offers the newest version of each capability of `tool`
+    pub fn from_tool(tool: &Tool) -> Vec<Self> {
+        let mut newest = BTreeMap::new();
+        for capability in &tool.capabilities {
+            newest
+                .entry(capability.name.as_str())
+                .and_modify(|current: &mut &crate::tool::Capability| {
+                    if capability.version > current.version {
+                        *current = capability;
+                    }
+                })
+                .or_insert(capability);
+        }
+
+        newest
+            .into_values()
+            .map(|capability| {
+                let mut properties = Map::new();
+                for parameter in &capability.parameters {
+                    let mut property = json!({ "description": parameter.description });
+                    if !matches!(parameter.parameter_type, ParameterType::Any) {
+                        property["type"] = Value::String(parameter.parameter_type.to_string());
+                    }
+                    properties.insert(parameter.name.clone(), property);
+                }
+                let required: Vec<String> = capability
+                    .parameters
+                    .iter()
+                    .filter(|parameter| parameter.required)
+                    .map(|parameter| parameter.name.clone())
+                    .collect();
+                let description = if capability.description.is_empty() {
+                    tool.description.clone()
+                } else {
+                    capability.description.clone()
+                };
+                Self {
+                    kind: TargetKind::Tool,
+                    target: format!("{}/{}", tool.id, capability.name),
+                    description,
+                    parameters: json!({ "type": "object", "properties": properties, "required": required }),
+                    required,
+                    security_level: tool.security_level,
+                    roles: Vec::new(),
+                }
+            })
+            .collect()
+    }
+
+    /// Restricts the option to users with one of `roles`
+    #[must_use]
+    pub fn with_roles(mut self, roles: Vec<String>) -> Self {
+        self.roles = roles;
+        self
+    }
+
+    /// Whether `caller` may use the option
+    pub fn permits(&self, caller: &Caller) -> bool {
+        self.security_level <= caller.security_level as u8
+            && (self.roles.is_empty() || self.roles.iter().any(|role| caller.roles.contains(role)))
+    }
+}
+
+/// A call the assistant proposes
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PlannedStep {
+    /// Tool capability or command
+    pub kind: TargetKind,
+    /// Command name, or `tool_id/capability`
+    pub target: String,
+    /// Arguments, as an object
+    #[serde(default = "empty_object")]
+    pub arguments: Value,
+    /// Why the step is needed
+    #[serde(default)]
+    pub reason: String,
+}
+
+fn empty_object() -> Value {
+    Value::Object(Map::new())
+}
+
+impl PlannedStep {
+    /// Tool ID and capability of a tool step
+    pub fn tool_capability(&self) -> Option<(&str, &str)> {
+        match self.kind {
+            TargetKind::Tool => self.target.split_once('/'),
+            TargetKind::Command => None,
+        }
+    }
+}
+
+/// A step the planner refused to return
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RejectedStep {
+    /// What the model asked to run
+    pub target: String,
+    /// Why it was refused
+    pub reason: String,
+}
+
+/// The assistant's answer to a request
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct Plan {
+    /// Text for the user
+    #[serde(default)]
+    pub reply: String,
+    /// Calls to make, in order
+    #[serde(default)]
+    pub steps: Vec<PlannedStep>,
+    /// Calls the model proposed that the caller may not or cannot make
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub rejected: Vec<RejectedStep>,
+}
+
+/// Asks a language model which of the offered tools and commands to call
+pub struct Planner {
+    client: Arc<dyn LlmClient>,
+}
+
+impl Planner {
+    /// Creates a planner asking `client`
+    pub fn new(client: Arc<dyn LlmClient>) -> Self {
+        Self { client }
+    }
+
+    /// Name of the model asked
+    pub fn model(&self) -> &str {
+        self.client.model()
+    }
+
+    /// Plans `request` for `caller` after the earlier messages in `history`
+    ///
+    /// Only the options of `catalog` the caller may use are offered.
+    ///
+    /// # Errors
+    /// Returns a model error if the model fails
+    pub async fn plan(
+        &self,
+        caller: &Caller,
+        catalog: &[ToolOption],
+        history: &[ChatMessage],
+        request: &str,
+    ) -> AiResult<Plan> {
+        let messages = self.prompt(caller, catalog, history, request);
+        let completion = self.client.complete(&messages).await?;
+        Ok(self.review(&completion.content, caller, catalog))
+    }
+
+    /// The messages [`plan`](Self::plan) sends to the model
+    pub fn prompt(
+        &self,
+        caller: &Caller,
+        catalog: &[ToolOption],
+        history: &[ChatMessage],
+        request: &str,
+    ) -> Vec<ChatMessage> {
+        let offered: Vec<&ToolOption> = catalog.iter().filter(|option| option.permits(caller)).collect();
+        let mut messages = Vec::with_capacity(history.len() + 2);
+        messages.push(ChatMessage::system(instructions(caller, &offered)));
+        messages.extend_from_slice(history);
+        messages.push(ChatMessage::user(request));
+        messages
+    }
+
+    /// The plan in the model's answer `text`, checked against `catalog`
+    pub fn review(&self, text: &str, caller: &Caller, catalog: &[ToolOption]) -> Plan {
+        check(parse(text), caller, catalog)
+    }
+}
+
+/// The system message describing the answer format and the options
+fn instructions(caller: &Caller, offered: &[&ToolOption]) -> String {
+    let options: Vec<Value> = offered
+        .iter()
+        .map(|option| {
+            json!({
+                "kind": option.kind,
+                "target": option.target,
+                "description": option.description,
+                "parameters": option.parameters,
+            })
+        })
+        .collect();
+    format!(
+        "You are the Squirrel assistant, helping user {user} run tools and commands.\n\
+         Only propose the tools and commands listed below, with arguments matching their parameters.\n\
+         Answer with one JSON object and nothing else:\n\
+         {{\"reply\": \"<short answer for the user>\", \"steps\": [{{\"kind\": \"tool\" or \"command\", \
+         \"target\": \"<target>\", \"arguments\": {{}}, \"reason\": \"<why>\"}}]}}\n\
+         Leave steps empty if nothing needs to run or nothing listed fits; say so in the reply.\n\n\
+         Available:\n{options}",
+        user = caller.user,
+        options = Value::Array(options),
+    )
+}
+
+/// Reads the model's answer; text without a JSON plan becomes the reply
+fn parse(text: &str) -> Plan {
+    let text = text.trim();
+    let json = match (text.find('{'), text.rfind('}')) {
+        (Some(start), Some(end)) if start < end => serde_json::from_str::<Plan>(&text[start..=end]).ok(),
+        _ => None,
+    };
+    json.unwrap_or_else(|| Plan {
+        reply: text.to_string(),
+        ..Plan::default()
+    })
+}
+
+/// Moves steps `caller` may not or cannot make to [`Plan::rejected`]
+fn check(mut plan: Plan, caller: &Caller, catalog: &[ToolOption]) -> Plan {
+    let steps = std::mem::take(&mut plan.steps);
+    for step in steps {
+        match refusal(&step, caller, catalog) {
+            Some(reason) => plan.rejected.push(RejectedStep {
+                target: step.target,
+                reason,
+            }),
+            None => plan.steps.push(step),
+        }
+    }
+    plan
+}
+
+/// Why `step` cannot be returned, if it cannot
+fn refusal(step: &PlannedStep, caller: &Caller, catalog: &[ToolOption]) -> Option<String> {
+    let Some(option) = catalog
+        .iter()
+        .find(|option| option.kind == step.kind && option.target == step.target)
+    else {
+        return Some(format!("Unknown {} '{}'", step.kind, step.target));
+    };
+    if !option.permits(caller) {
+        return Some(format!("{} may not use {} '{}'", caller.user, step.kind, step.target));
+    }
+    let Some(arguments) = step.arguments.as_object() else {
+        return Some("Arguments must be an object".to_string());
+    };
+    option
+        .required
+        .iter()
+        .find(|name| !arguments.contains_key(*name))
+        .map(|name| format!("Missing required parameter '{name}'"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ai::Completion;
+    use crate::tool::{Capability, Parameter};
+    use async_trait::async_trait;
+
+    /// Answers every request with the same text
+    struct Scripted(String);
+
+    #[async_trait]
+    impl LlmClient for Scripted {
+        fn provider(&self) -> &str {
+            "test"
+        }
+
+        fn model(&self) -> &str {
+            "scripted"
+        }
+
+        async fn complete(&self, messages: &[ChatMessage]) -> AiResult<Completion> {
+            Ok(Completion::estimated(messages, self.0.clone()))
+        }
+    }
+
+    fn catalog() -> Vec<ToolOption> {
+        let tool = Tool {
+            id: "bwa".to_string(),
+            name: "BWA".to_string(),
+            version: "0.7".to_string(),
+            description: "Read aligner".to_string(),
+            capabilities: vec![Capability {
+                name: "index".to_string(),
+                description: "Index a reference".to_string(),
+                parameters: vec![Parameter {
+                    name: "reference".to_string(),
+                    description: "FASTA file".to_string(),
+                    parameter_type: ParameterType::String,
+                    required: true,
+                }],
+                return_type: None,
+                version: 1,
+                deprecation: None,
+            }],
+            security_level: 0,
+        };
+        let mut catalog = ToolOption::from_tool(&tool);
+        catalog.push(ToolOption::command("usage", "Show resource usage", json!({ "type": "object" })));
+        catalog.push(
+            ToolOption::command("secrets", "Manage secrets", json!({ "type": "object" }))
+                .with_roles(vec!["Admin".to_string()]),
+        );
+        catalog
+    }
+
+    #[tokio::test]
+    async fn test_plan_keeps_only_offered_and_complete_steps() {
+        let answer = r#"Sure:
+```json
+{"reply": "Indexing, then listing secrets.", "steps": [
+  {"kind": "tool", "target": "bwa/index", "arguments": {"reference": "hg38.fa"}, "reason": "index"},
+  {"kind": "tool", "target": "bwa/index", "arguments": {}},
+  {"kind": "command", "target": "secrets"},
+  {"kind": "command", "target": "rm"}
+]}
+```"#;
+        let planner = Planner::new(Arc::new(Scripted(answer.to_string())));
+        let caller = Caller::new("alice", vec!["User".to_string()]);
+        let plan = planner.plan(&caller, &catalog(), &[], "index hg38").await.unwrap();
+
+        assert_eq!(plan.reply, "Indexing, then listing secrets.");
+        assert_eq!(plan.steps.len(), 1);
+        assert_eq!(plan.steps[0].tool_capability(), Some(("bwa", "index")));
+        let reasons: Vec<&str> = plan.rejected.iter().map(|step| step.reason.as_str()).collect();
+        assert_eq!(
+            reasons,
+            [
+                "Missing required parameter 'reference'",
+                "alice may not use command 'secrets'",
+                "Unknown command 'rm'"
+            ]
+        );
+    }
+
+    #[test]
+    fn test_offers_depend_on_roles_and_security_level() {
+        let mut catalog = catalog();
+        catalog[0].security_level = 8;
+        let user = Caller::new("alice", vec!["User".to_string()]);
+        let admin = Caller::new("root", vec!["Admin".to_string()]);
+
+        let offered = |caller: &Caller| catalog.iter().filter(|option| option.permits(caller)).count();
+        assert_eq!(offered(&user), 1);
+        assert_eq!(offered(&admin), 3);
+        assert!(instructions(&user, &[&catalog[1]]).contains("\"target\":\"usage\""));
+    }
+
+    #[test]
+    fn test_text_without_a_plan_is_the_reply() {
+        let plan = parse("I can't help with that.");
+        assert_eq!(plan.reply, "I can't help with that.");
+        assert!(plan.steps.is_empty());
+    }
+}
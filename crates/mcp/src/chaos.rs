@@ -0,0 +1,598 @@
+//! Fault injection for resilience testing
+//!
+//! A [`FaultScenario`] lists rules that inject latency, errors or
+//! disconnects into one of three layers:
+//!
+//! - the transport, by wrapping a protocol in [`FaultyProtocol`],
+//! - tool executors, by wrapping them in [`FaultyToolExecutor`],
+//! - the database, by wrapping a persistence in [`FaultyPersistence`].
+//!
+//! Rules fire on a fixed schedule (skip the first `after` matching calls,
+//! then fire on every `every`-th call, at most `times` times), and the
+//! optional `probability` draws from a generator seeded by the scenario, so
+//! the same scenario injects the same faults on every run. This module is
+//! only compiled with the `chaos` feature.
+//!
+//! Scenarios are JSON files:
+//!
+//! ```json
+//! {
+//!   "name": "flaky tools",
+//!   "rules": [
+//!     { "target": "tool", "operation": "search/*", "fault": { "type": "error", "message": "backend down" }, "every": 3 },
+//!     { "target": "database", "operation": "save_session", "fault": { "type": "latency", "ms": 200 } }
+//!   ]
+//! }
+//! ```
+
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use async_trait::async_trait;
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use serde::{Deserialize, Serialize};
+
+use crate::error::types::ConnectionError;
+use crate::error::{MCPError, Result};
+use crate::persistence::{AccountData, Persistence, SessionData, UserData};
+use crate::protocol::{MCPProtocol, ProtocolResult, RoutingResult, ValidationResult};
+use crate::tool::{ToolContext, ToolError, ToolExecutionResult, ToolExecutor};
+use crate::types::{AccountId, MCPMessage, ProtocolState, SessionToken, UserId};
+
+/// Environment variable naming the scenario file loaded by
+/// [`FaultInjector::from_env`]
+pub const FAULT_SCENARIO_ENV: &str = "SQUIRREL_FAULT_SCENARIO";
+
+/// Layer a fault is injected into
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum FaultTarget {
+    /// Messages handled by a protocol; operations are message types
+    Transport,
+    /// Tool executions; operations are `<tool id>/<capability>`
+    Tool,
+    /// Persistence calls; operations are method names such as `save_session`
+    Database,
+}
+
+/// Fault to inject
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum FaultKind {
+    /// Delay the call, then let it through
+    Latency {
+        /// Delay in milliseconds
+        ms: u64,
+    },
+    /// Fail the call
+    Error {
+        /// Error message
+        message: String,
+    },
+    /// Fail the call as if the connection dropped
+    Disconnect,
+}
+
+/// When and where a fault is injected
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct FaultRule {
+    /// Layer the rule applies to
+    pub target: FaultTarget,
+    /// Operation the rule applies to; a trailing `*` matches any suffix and
+    /// no operation matches every call
+    #[serde(default)]
+    pub operation: Option<String>,
+    /// Fault to inject
+    pub fault: FaultKind,
+    /// Number of matching calls to let through before the first fault
+    #[serde(default)]
+    pub after: u64,
+    /// Inject into every n-th matching call after `after`
+    #[serde(default = "default_every")]
+    pub every: u64,
+    /// Maximum number of faults the rule injects
+    #[serde(default)]
+    pub times: Option<u64>,
+    /// Chance that a scheduled fault is injected
+    #[serde(default = "default_probability")]
+    pub probability: f64,
+}
+
+const fn default_every() -> u64 {
+    1
+}
+
+const fn default_probability() -> f64 {
+    1.0
+}
+
+impl FaultRule {
+    /// Rule injecting `fault` into every call of `target`
+    #[must_use]
+    pub const fn new(target: FaultTarget, fault: FaultKind) -> Self {
+        Self {
+            target,
+            operation: None,
+            fault,
+            after: 0,
+            every: 1,
+            times: None,
+            probability: 1.0,
+        }
+    }
+
+    /// Whether the rule applies to `operation` of `target`
+    fn matches(&self, target: FaultTarget, operation: &str) -> bool {
+        if self.target != target {
+            return false;
+        }
+        match self.operation.as_deref() {
+            None => true,
+            Some(pattern) => pattern
+                .strip_suffix('*')
+                .map_or(pattern == operation, |prefix| operation.starts_with(prefix)),
+        }
+    }
+}
+
+/// Set of fault rules
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct FaultScenario {
+    /// Name of the scenario
+    #[serde(default)]
+    pub name: Option<String>,
+    /// Seed of the generator used for `probability`
+    #[serde(default)]
+    pub seed: u64,
+    /// Rules, checked in order; the first rule that fires wins
+    pub rules: Vec<FaultRule>,
+}
+
+impl FaultScenario {
+    /// Loads a scenario from a JSON file
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the file cannot be read or parsed.
+    pub fn load(path: impl AsRef<Path>) -> Result<Self> {
+        let contents = std::fs::read_to_string(path)?;
+        Ok(serde_json::from_str(&contents)?)
+    }
+}
+
+/// A fault the injector decided on
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+pub enum InjectedFault {
+    /// The call fails
+    #[error("Injected fault: {0}")]
+    Error(String),
+    /// The connection drops
+    #[error("Injected fault: disconnected")]
+    Disconnect,
+}
+
+/// Counters of one rule
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RuleStats {
+    /// Calls the rule matched
+    pub matched: u64,
+    /// Faults the rule injected
+    pub injected: u64,
+}
+
+#[derive(Debug)]
+struct InjectorState {
+    stats: Vec<RuleStats>,
+    rng: StdRng,
+}
+
+/// Decides which calls fail according to a scenario
+#[derive(Debug)]
+pub struct FaultInjector {
+    scenario: FaultScenario,
+    state: Mutex<InjectorState>,
+}
+
+impl FaultInjector {
+    /// Creates an injector for `scenario`
+    #[must_use]
+    pub fn new(scenario: FaultScenario) -> Self {
+        let state = InjectorState {
+            stats: vec![RuleStats::default(); scenario.rules.len()],
+            rng: StdRng::seed_from_u64(scenario.seed),
+        };
+        Self {
+            scenario,
+            state: Mutex::new(state),
+        }
+    }
+
+    /// Creates an injector for the scenario file named by
+    /// [`FAULT_SCENARIO_ENV`], if it is set
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the scenario cannot be loaded.
+    pub fn from_env() -> Result<Option<Self>> {
+        std::env::var_os(FAULT_SCENARIO_ENV)
+            .map(|path| FaultScenario::load(path).map(Self::new))
+            .transpose()
+    }
+
+    /// The scenario
+    #[must_use]
+    pub const fn scenario(&self) -> &FaultScenario {
+        &self.scenario
+    }
+
+    /// Counters of each rule, in scenario order
+    #[must_use]
+    pub fn stats(&self) -> Vec<RuleStats> {
+        self.state
+            .lock()
+            .map(|state| state.stats.clone())
+            .unwrap_or_default()
+    }
+
+    /// Counts a call and returns the fault to inject into it, if any
+    pub fn next_fault(&self, target: FaultTarget, operation: &str) -> Option<FaultKind> {
+        let Ok(mut state) = self.state.lock() else {
+            return None;
+        };
+        let InjectorState { stats, rng } = &mut *state;
+        let mut chosen = None;
+        for (rule, stats) in self.scenario.rules.iter().zip(stats.iter_mut()) {
+            if !rule.matches(target, operation) {
+                continue;
+            }
+            let call = stats.matched;
+            stats.matched += 1;
+            if chosen.is_some()
+                || call < rule.after
+                || (call - rule.after) % rule.every.max(1) != 0
+                || rule.times.is_some_and(|times| stats.injected >= times)
+            {
+                continue;
+            }
+            if rule.probability < 1.0 && !rng.gen_bool(rule.probability.clamp(0.0, 1.0)) {
+                continue;
+            }
+            stats.injected += 1;
+            chosen = Some(rule.fault.clone());
+        }
+        chosen
+    }
+
+    /// Applies the fault for a call: waits out latency and returns errors
+    ///
+    /// # Errors
+    ///
+    /// Returns the injected fault if the call should fail.
+    pub async fn inject(
+        &self,
+        target: FaultTarget,
+        operation: &str,
+    ) -> std::result::Result<(), InjectedFault> {
+        match self.next_fault(target, operation) {
+            None => Ok(()),
+            Some(FaultKind::Latency { ms }) => {
+                tokio::time::sleep(Duration::from_millis(ms)).await;
+                Ok(())
+            }
+            Some(FaultKind::Error { message }) => Err(InjectedFault::Error(message)),
+            Some(FaultKind::Disconnect) => Err(InjectedFault::Disconnect),
+        }
+    }
+
+    /// Applies the fault for a call of an MCP layer
+    async fn inject_mcp(
+        &self,
+        target: FaultTarget,
+        operation: &str,
+        error: fn(String) -> MCPError,
+    ) -> Result<()> {
+        self.inject(target, operation)
+            .await
+            .map_err(|fault| match fault {
+                InjectedFault::Error(message) => error(message),
+                InjectedFault::Disconnect => MCPError::Connection(ConnectionError::Closed(
+                    format!("injected disconnect during {operation}"),
+                )),
+            })
+    }
+}
+
+/// Protocol that injects transport faults into the messages it handles
+#[derive(Debug)]
+pub struct FaultyProtocol<P> {
+    inner: P,
+    injector: Arc<FaultInjector>,
+}
+
+impl<P: MCPProtocol> FaultyProtocol<P> {
+    /// Wraps `inner`, injecting faults from `injector`
+    pub const fn new(inner: P, injector: Arc<FaultInjector>) -> Self {
+        Self { inner, injector }
+    }
+
+    /// The wrapped protocol
+    pub const fn inner(&self) -> &P {
+        &self.inner
+    }
+}
+
+#[async_trait]
+impl<P: MCPProtocol> MCPProtocol for FaultyProtocol<P> {
+    async fn handle_message(&self, msg: MCPMessage) -> ProtocolResult {
+        self.injector
+            .inject_mcp(
+                FaultTarget::Transport,
+                &msg.message_type.to_string(),
+                MCPError::Network,
+            )
+            .await?;
+        self.inner.handle_message(msg).await
+    }
+
+    async fn validate_message(&self, msg: &MCPMessage) -> ValidationResult {
+        self.inner.validate_message(msg).await
+    }
+
+    async fn route_message(&self, msg: &MCPMessage) -> RoutingResult {
+        self.inner.route_message(msg).await
+    }
+
+    async fn set_state(&self, new_state: ProtocolState) -> Result<()> {
+        self.inner.set_state(new_state).await
+    }
+
+    async fn get_state(&self) -> Result<ProtocolState> {
+        self.inner.get_state().await
+    }
+
+    fn get_version(&self) -> String {
+        self.inner.get_version()
+    }
+}
+
+/// Tool executor that injects faults into executions
+#[derive(Debug)]
+pub struct FaultyToolExecutor {
+    inner: Arc<dyn ToolExecutor>,
+    injector: Arc<FaultInjector>,
+}
+
+impl FaultyToolExecutor {
+    /// Wraps `inner`, injecting faults from `injector`
+    #[must_use]
+    pub fn new(inner: Arc<dyn ToolExecutor>, injector: Arc<FaultInjector>) -> Self {
+        Self { inner, injector }
+    }
+}
+
+#[async_trait]
+impl ToolExecutor for FaultyToolExecutor {
+    async fn execute(
+        &self,
+        context: ToolContext,
+    ) -> std::result::Result<ToolExecutionResult, ToolError> {
+        let operation = format!("{}/{}", context.tool_id, context.capability);
+        if let Err(fault) = self.injector.inject(FaultTarget::Tool, &operation).await {
+            return Err(ToolError::ExecutionFailed {
+                tool_id: context.tool_id,
+                reason: fault.to_string(),
+            });
+        }
+        self.inner.execute(context).await
+    }
+
+    fn get_tool_id(&self) -> String {
+        self.inner.get_tool_id()
+    }
+
+    fn get_capabilities(&self) -> Vec<String> {
+        self.inner.get_capabilities()
+    }
+
+    async fn start(&self) -> std::result::Result<(), ToolError> {
+        self.inner.start().await
+    }
+
+    async fn stop(&self) -> std::result::Result<(), ToolError> {
+        self.inner.stop().await
+    }
+
+    async fn pause(&self) -> std::result::Result<(), ToolError> {
+        self.inner.pause().await
+    }
+
+    async fn resume(&self) -> std::result::Result<(), ToolError> {
+        self.inner.resume().await
+    }
+}
+
+/// Persistence that injects database faults into its calls
+#[derive(Debug)]
+pub struct FaultyPersistence {
+    inner: Arc<dyn Persistence>,
+    injector: Arc<FaultInjector>,
+}
+
+impl FaultyPersistence {
+    /// Wraps `inner`, injecting faults from `injector`
+    #[must_use]
+    pub fn new(inner: Arc<dyn Persistence>, injector: Arc<FaultInjector>) -> Self {
+        Self { inner, injector }
+    }
+
+    async fn fault(&self, operation: &str) -> Result<()> {
+        self.injector
+            .inject_mcp(FaultTarget::Database, operation, MCPError::Storage)
+            .await
+    }
+}
+
+#[async_trait]
+impl Persistence for FaultyPersistence {
+    async fn init(&self) -> Result<()> {
+        self.fault("init").await?;
+        self.inner.init().await
+    }
+
+    async fn save_session(&self, session: &SessionData) -> Result<()> {
+        self.fault("save_session").await?;
+        self.inner.save_session(session).await
+    }
+
+    async fn load_session(&self, token: &SessionToken) -> Result<Option<SessionData>> {
+        self.fault("load_session").await?;
+        self.inner.load_session(token).await
+    }
+
+    async fn delete_session(&self, token: &SessionToken) -> Result<()> {
+        self.fault("delete_session").await?;
+        self.inner.delete_session(token).await
+    }
+
+    async fn save_user(&self, user: &UserData) -> Result<()> {
+        self.fault("save_user").await?;
+        self.inner.save_user(user).await
+    }
+
+    async fn load_user_by_id(&self, id: &UserId) -> Result<Option<UserData>> {
+        self.fault("load_user_by_id").await?;
+        self.inner.load_user_by_id(id).await
+    }
+
+    async fn load_user_by_username(&self, username: &str) -> Result<Option<UserData>> {
+        self.fault("load_user_by_username").await?;
+        self.inner.load_user_by_username(username).await
+    }
+
+    async fn delete_user(&self, id: &UserId) -> Result<()> {
+        self.fault("delete_user").await?;
+        self.inner.delete_user(id).await
+    }
+
+    async fn save_account(&self, account: &AccountData) -> Result<()> {
+        self.fault("save_account").await?;
+        self.inner.save_account(account).await
+    }
+
+    async fn load_account(&self, id: &AccountId) -> Result<Option<AccountData>> {
+        self.fault("load_account").await?;
+        self.inner.load_account(id).await
+    }
+
+    async fn delete_account(&self, id: &AccountId) -> Result<()> {
+        self.fault("delete_account").await?;
+        self.inner.delete_account(id).await
+    }
+
+    async fn save_data(&self, key: &str, value: &[u8]) -> Result<()> {
+        self.fault("save_data").await?;
+        self.inner.save_data(key, value).await
+    }
+
+    async fn load_data(&self, key: &str) -> Result<Option<Vec<u8>>> {
+        self.fault("load_data").await?;
+        self.inner.load_data(key).await
+    }
+
+    async fn delete_data(&self, key: &str) -> Result<()> {
+        self.fault("delete_data").await?;
+        self.inner.delete_data(key).await
+    }
+
+    async fn list_keys(&self, prefix: &str) -> Result<Vec<String>> {
+        self.fault("list_keys").await?;
+        self.inner.list_keys(prefix).await
+    }
+
+    async fn close(&self) -> Result<()> {
+        self.fault("close").await?;
+        self.inner.close().await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::persistence::MemoryPersistence;
+
+    #[test]
+    fn test_rule_schedule() {
+        let scenario: FaultScenario = serde_json::from_str(
+            r#"{"rules": [
+                {"target": "tool", "operation": "search/*", "fault": {"type": "disconnect"}, "after": 1, "every": 2, "times": 2},
+                {"target": "tool", "fault": {"type": "error", "message": "boom"}, "after": 6}
+            ]}"#,
+        )
+        .unwrap();
+        let injector = FaultInjector::new(scenario);
+
+        let faults: Vec<Option<FaultKind>> = (0..8)
+            .map(|_| injector.next_fault(FaultTarget::Tool, "search/query"))
+            .collect();
+        let boom = Some(FaultKind::Error {
+            message: "boom".to_string(),
+        });
+        assert_eq!(
+            faults,
+            vec![
+                None,
+                Some(FaultKind::Disconnect),
+                None,
+                Some(FaultKind::Disconnect),
+                None,
+                None,
+                boom.clone(),
+                boom
+            ]
+        );
+        assert_eq!(
+            injector.next_fault(FaultTarget::Database, "search/query"),
+            None
+        );
+        assert_eq!(
+            injector.stats(),
+            vec![
+                RuleStats {
+                    matched: 8,
+                    injected: 2
+                },
+                RuleStats {
+                    matched: 8,
+                    injected: 2
+                }
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_faulty_persistence() {
+        let mut rule = FaultRule::new(
+            FaultTarget::Database,
+            FaultKind::Error {
+                message: "disk full".to_string(),
+            },
+        );
+        rule.operation = Some("save_data".to_string());
+        rule.times = Some(1);
+        let injector = Arc::new(FaultInjector::new(FaultScenario {
+            rules: vec![rule],
+            ..FaultScenario::default()
+        }));
+        let persistence = FaultyPersistence::new(Arc::new(MemoryPersistence::new()), injector);
+
+        let err = persistence.save_data("key", b"value").await.unwrap_err();
+        assert!(matches!(err, MCPError::Storage(message) if message == "disk full"));
+        persistence.save_data("key", b"value").await.unwrap();
+        assert_eq!(
+            persistence.load_data("key").await.unwrap(),
+            Some(b"value".to_vec())
+        );
+    }
+}
@@ -1,3 +1,5 @@
+use crate::context_search::{ContextIndex, ContextQuery};
+use crate::context_snapshot::{ContextMap, ContextSnapshot, ContextVersion, SnapshotRegistry, SnapshotStats};
 use crate::error::types::ContextError;
 use crate::error::{MCPError, Result};
 use crate::monitoring::MCPMonitor;
@@ -5,12 +7,18 @@ use crate::persistence::{MCPPersistence, PersistenceConfig};
 use crate::sync::state::StateSyncManager;
 use crate::sync::state::{StateChange, StateOperation};
 use crate::sync::{MCPSync, SyncConfig};
+use arc_swap::ArcSwap;
 use chrono::{DateTime, Utc};
+use dashmap::DashMap;
 use serde::{Deserialize, Serialize};
+use squirrel_core::expr::{self, Expression};
+use squirrel_core::redaction::Redactor;
 use std::collections::HashMap;
 use std::default::Default;
+use std::sync::atomic::{AtomicU64, Ordering};
 use std::sync::Arc;
-use tokio::sync::RwLock;
+use std::time::Instant;
+use tokio::sync::{Mutex, RwLock, RwLockWriteGuard};
 use tracing::{error, info, instrument, warn};
 use uuid::Uuid;
 
@@ -50,6 +58,85 @@ pub struct ContextValidation {
     pub rules: Vec<String>,
 }
 
+/// A value resolved through a context's inheritance chain
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ResolvedValue {
+    /// The effective value
+    pub value: serde_json::Value,
+    /// The context in the chain the value comes from
+    pub source: Uuid,
+}
+
+/// Policy choosing which context is evicted when the store is full
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum EvictionPolicy {
+    /// Evict the least recently used context
+    #[default]
+    Lru,
+    /// Evict the least frequently used context, the least recently used
+    /// among equally used ones
+    Lfu,
+}
+
+/// Memory use and eviction counts of a context manager
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ContextStats {
+    /// Number of stored contexts
+    pub contexts: usize,
+    /// Serialized size of all stored contexts in bytes
+    pub total_bytes: usize,
+    /// Number of pinned contexts
+    pub pinned: usize,
+    /// Number of contexts evicted to stay within the total limit
+    pub evictions: u64,
+    /// Bytes freed by evictions
+    pub evicted_bytes: u64,
+    /// Number of writes rejected for exceeding a limit
+    pub rejected_writes: u64,
+}
+
+/// Contention counters of a context manager's read and write paths
+///
+/// Reads are served from a published snapshot of the contexts and never
+/// wait for writers. Writes made while another write is being published are
+/// published together with it.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ContentionStats {
+    /// Contexts read from the published snapshot
+    pub lock_free_reads: u64,
+    /// Snapshots published
+    pub publishes: u64,
+    /// Context writes and removals published
+    pub published_writes: u64,
+    /// Largest number of writes published in one snapshot
+    pub max_batch: u64,
+    /// Writes that waited for another writer
+    pub contended_writes: u64,
+    /// Microseconds writers spent waiting for each other
+    pub write_wait_us: u64,
+}
+
+/// Counters behind [`ContentionStats`]
+#[derive(Debug, Default)]
+struct Contention {
+    /// Contexts read from the published snapshot
+    lock_free_reads: AtomicU64,
+    /// Snapshots published
+    publishes: AtomicU64,
+    /// Writes and removals published
+    published_writes: AtomicU64,
+    /// Largest batch published
+    max_batch: AtomicU64,
+    /// Writes that waited for another writer
+    contended_writes: AtomicU64,
+    /// Microseconds writers waited
+    write_wait_us: AtomicU64,
+}
+
+/// A write to publish: the new version of a context, or `None` if removed
+type PublishedWrite = (Uuid, Option<Arc<Context>>);
+
 /// Configuration for Context Manager
 #[derive(Debug, Clone)]
 pub struct ContextConfig {
@@ -61,6 +148,12 @@ pub struct ContextConfig {
     pub timeout_ms: Option<u64>,
     /// Days after which old data is cleaned up
     pub cleanup_older_than_days: Option<i64>,
+    /// Maximum serialized size of a single context in bytes
+    pub max_context_bytes: Option<usize>,
+    /// Maximum serialized size of all contexts in bytes
+    pub max_total_bytes: Option<usize>,
+    /// Which contexts are evicted when `max_total_bytes` is reached
+    pub eviction_policy: EvictionPolicy,
 }
 
 impl Default for ContextConfig {
@@ -70,6 +163,107 @@ impl Default for ContextConfig {
             max_retries: Some(3),
             timeout_ms: Some(5000),
             cleanup_older_than_days: Some(30),
+            max_context_bytes: Some(4 * 1024 * 1024),
+            max_total_bytes: Some(256 * 1024 * 1024),
+            eviction_policy: EvictionPolicy::Lru,
+        }
+    }
+}
+
+/// Size of one stored context
+#[derive(Debug, Clone, Copy, Default)]
+struct EntryUsage {
+    /// Serialized size in bytes
+    bytes: usize,
+    /// Whether the context must never be evicted
+    pinned: bool,
+}
+
+/// Sizes of the stored contexts
+#[derive(Debug, Default)]
+struct StoreUsage {
+    /// Usage by context ID
+    entries: HashMap<Uuid, EntryUsage>,
+    /// Sum of the sizes of all entries
+    total_bytes: usize,
+    /// Number of evicted contexts
+    evictions: u64,
+    /// Bytes freed by evictions
+    evicted_bytes: u64,
+    /// Number of rejected writes
+    rejected_writes: u64,
+}
+
+impl StoreUsage {
+    /// Records that a context was stored with `bytes` size
+    fn store(&mut self, id: Uuid, bytes: usize) {
+        let entry = self.entries.entry(id).or_default();
+        self.total_bytes = self.total_bytes - entry.bytes + bytes;
+        entry.bytes = bytes;
+    }
+
+    /// Forgets a context, returning its usage
+    fn remove(&mut self, id: Uuid) -> Option<EntryUsage> {
+        let entry = self.entries.remove(&id)?;
+        self.total_bytes -= entry.bytes;
+        Some(entry)
+    }
+}
+
+/// When and how often one context was accessed
+#[derive(Debug, Default)]
+struct AccessStats {
+    /// Logical time of the last access
+    last_access: AtomicU64,
+    /// Number of accesses
+    accesses: AtomicU64,
+}
+
+/// Access times and counts of the stored contexts
+///
+/// Readers record accesses with atomics on the entry of the context, taking
+/// only a shared lock on the shard of the map holding it; writers adding or
+/// removing contexts lock just that shard.
+#[derive(Debug, Default)]
+struct AccessLog {
+    /// Logical clock, advanced on every access
+    clock: AtomicU64,
+    /// Access stats by context ID
+    entries: DashMap<Uuid, AccessStats>,
+}
+
+impl AccessLog {
+    /// Records an access to a context, if it is tracked
+    fn record(&self, id: Uuid) {
+        if let Some(stats) = self.entries.get(&id) {
+            let now = self.clock.fetch_add(1, Ordering::Relaxed) + 1;
+            stats.last_access.fetch_max(now, Ordering::Relaxed);
+            stats.accesses.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+
+    /// Starts tracking a context if it is new, and records an access to it
+    fn track(&self, id: Uuid) {
+        if !self.entries.contains_key(&id) {
+            self.entries.entry(id).or_default();
+        }
+        self.record(id);
+    }
+
+    /// Stops tracking a context
+    fn forget(&self, id: Uuid) {
+        self.entries.remove(&id);
+    }
+
+    /// Eviction order of a context under `policy`, least valuable first
+    fn eviction_key(&self, id: Uuid, policy: EvictionPolicy) -> (u64, u64) {
+        let Some(stats) = self.entries.get(&id) else {
+            return (0, 0);
+        };
+        let last_access = stats.last_access.load(Ordering::Relaxed);
+        match policy {
+            EvictionPolicy::Lru => (last_access, 0),
+            EvictionPolicy::Lfu => (stats.accesses.load(Ordering::Relaxed), last_access),
         }
     }
 }
@@ -79,16 +273,53 @@ impl Default for ContextConfig {
 /// The `ContextManager` is responsible for creating, updating, deleting, and
 /// validating contexts, as well as managing their hierarchical relationships
 /// and synchronization across distributed instances.
+///
+/// A context inherits the data of its ancestors: its effective data is the
+/// data of the root context, overridden by each descendant down the chain.
+/// Objects are merged key by key, any other value replaces the inherited one.
+///
+/// Contexts larger than `max_context_bytes` are rejected. When storing a
+/// context would exceed `max_total_bytes`, other contexts are evicted by the
+/// configured policy; contexts that are pinned, have children or forks, or
+/// are the parent of the stored context are never evicted.
+///
+/// Reads are served from a snapshot of the contexts that writers publish
+/// once their write is done, so reads never wait for writers; see
+/// [`ContextManager::contention`]. Each publish is a new version of the
+/// contexts, which long-running readers pin with [`ContextManager::snapshot`].
 #[derive(Debug)]
 pub struct ContextManager {
-    /// Map of context IDs to Context instances
-    contexts: Arc<RwLock<HashMap<Uuid, Context>>>,
+    /// Map of context IDs to Context instances, as changed by writers
+    contexts: Arc<RwLock<ContextMap>>,
+    /// Latest version of `contexts`, which readers load without locking
+    published: Arc<ArcSwap<ContextVersion>>,
+    /// Versions pinned by snapshots
+    snapshots: Arc<SnapshotRegistry>,
+    /// Writes waiting to be published, in the order they were made
+    queued: Arc<std::sync::Mutex<Vec<PublishedWrite>>>,
+    /// Held while a snapshot is being published
+    publishing: Arc<Mutex<()>>,
+    /// Access times and counts of the stored contexts, which readers
+    /// record without locking
+    access: Arc<AccessLog>,
+    /// Contention counters
+    contention: Arc<Contention>,
     /// Map of parent IDs to child IDs, representing the context hierarchy
     hierarchy: Arc<RwLock<HashMap<Uuid, Vec<Uuid>>>>,
+    /// Map of fork IDs to the IDs of the contexts they were forked from
+    forks: Arc<RwLock<HashMap<Uuid, Uuid>>>,
     /// Map of context types to validation rules
     validations: Arc<RwLock<HashMap<String, ContextValidation>>>,
+    /// Sizes of the stored contexts
+    usage: Arc<RwLock<StoreUsage>>,
+    /// Search index over the stored contexts
+    index: Arc<RwLock<ContextIndex>>,
+    /// Size limits and eviction policy
+    config: ContextConfig,
     /// Synchronization engine for distributed context operations
     sync: Arc<MCPSync>,
+    /// Redacts the data and metadata of contexts before they are stored
+    redactor: Option<Arc<Redactor>>,
 }
 
 impl ContextManager {
@@ -99,10 +330,19 @@ impl ContextManager {
     /// and state management.
     #[instrument]
     pub async fn new() -> Self {
+        Self::with_persistence(PersistenceConfig::default()).await
+    }
+
+    /// Creates a context manager that persists changes as configured
+    ///
+    /// Contexts persisted earlier are not loaded; see
+    /// [`MCPPersistence::load_contexts`].
+    #[instrument]
+    pub async fn with_persistence(persistence_config: PersistenceConfig) -> Self {
         let sync_config = SyncConfig::default();
 
         // Create and initialize persistence before wrapping in Arc
-        let mut persistence = MCPPersistence::new(PersistenceConfig::default());
+        let mut persistence = MCPPersistence::new(persistence_config);
         // Initialize persistence
         if let Err(e) = persistence.init() {
             tracing::warn!("Failed to initialize persistence: {}", e);
@@ -124,43 +364,86 @@ impl ContextManager {
         // Convert to Arc after initialization
         let sync = Arc::new(sync_instance);
 
+        Self::with_sync(ContextConfig::default(), sync)
+    }
+
+    /// Creates an empty context manager using `sync` for synchronization
+    fn with_sync(config: ContextConfig, sync: Arc<MCPSync>) -> Self {
         Self {
             contexts: Arc::new(RwLock::new(HashMap::new())),
+            published: Arc::new(ArcSwap::from_pointee(ContextVersion::default())),
+            snapshots: Arc::new(SnapshotRegistry::default()),
+            queued: Arc::new(std::sync::Mutex::new(Vec::new())),
+            publishing: Arc::new(Mutex::new(())),
+            access: Arc::new(AccessLog::default()),
+            contention: Arc::new(Contention::default()),
             hierarchy: Arc::new(RwLock::new(HashMap::new())),
+            forks: Arc::new(RwLock::new(HashMap::new())),
             validations: Arc::new(RwLock::new(HashMap::new())),
+            usage: Arc::new(RwLock::new(StoreUsage::default())),
+            index: Arc::new(RwLock::new(ContextIndex::new())),
+            config,
             sync,
+            redactor: None,
+        }
+    }
+
+    /// Redacts the data and metadata of contexts with `redactor` before
+    /// they are stored and persisted
+    #[must_use]
+    pub fn with_redactor(mut self, redactor: Arc<Redactor>) -> Self {
+        self.redactor = Some(redactor);
+        self
+    }
+
+    /// Applies the redactor, if any, to the data and metadata of `context`
+    fn redact(&self, context: &mut Context) {
+        if let Some(redactor) = &self.redactor {
+            redactor.redact_value(&mut context.data);
+            if let Some(metadata) = &mut context.metadata {
+                redactor.redact_value(metadata);
+            }
         }
     }
 
     /// Creates a new context in the system
     ///
-    /// Validates the context data according to registered validation rules,
-    /// updates the context hierarchy if a parent is specified, and triggers
-    /// synchronization of the context across distributed instances.
+    /// Redacts the context data if a redactor is set, validates it according
+    /// to registered validation rules, updates the context hierarchy if a
+    /// parent is specified, and triggers synchronization of the context
+    /// across distributed instances.
     ///
     /// # Errors
     ///
-    /// Returns `ContextError::ValidationError` if the context fails validation.
+    /// Returns `ContextError::ValidationError` if the context fails validation,
+    /// would become its own ancestor or does not fit within the size limits.
     /// Returns `ContextError::SyncError` if synchronization fails.
     #[instrument(skip(self, context))]
-    pub async fn create_context(&self, context: Context) -> Result<Uuid> {
+    pub async fn create_context(&self, mut context: Context) -> Result<Uuid> {
+        let _foreground = self.sync.foreground();
+        self.redact(&mut context);
         // Validate context data
         self.validate_context(&context).await?;
 
         let context_id = context.id;
 
-        // Update hierarchy if parent exists
+        // A parent that (transitively) inherits from this context would
+        // make the inheritance chain endless
         if let Some(parent_id) = context.parent_id {
-            let mut hierarchy = self.hierarchy.write().await;
-            hierarchy
-                .entry(parent_id)
-                .or_insert_with(Vec::new)
-                .push(context_id);
+            let contexts = self.published.load();
+            let mut ancestor = Some(parent_id);
+            while let Some(id) = ancestor {
+                if id == context_id {
+                    return Err(MCPError::Context(ContextError::ValidationError(format!(
+                        "Context {context_id} cannot inherit from itself"
+                    ))));
+                }
+                ancestor = contexts.get(&id).and_then(|c| c.parent_id);
+            }
         }
 
-        // Store context
-        let mut contexts = self.contexts.write().await;
-        contexts.insert(context_id, context.clone());
+        // Store context, updating the hierarchy if a parent exists
+        self.store_context(context.clone(), None).await?;
 
         // Record change for sync
         if let Err(e) = self
@@ -183,11 +466,14 @@ impl ContextManager {
     /// Returns `ContextError::NotFound` if the context does not exist.
     #[instrument(skip(self))]
     pub async fn get_context(&self, id: Uuid) -> Result<Context> {
-        let contexts = self.contexts.read().await;
-        contexts
+        let context = self
+            .published
+            .load()
             .get(&id)
-            .cloned()
-            .ok_or(MCPError::Context(ContextError::NotFound(id)))
+            .map(|context| Context::clone(context))
+            .ok_or(MCPError::Context(ContextError::NotFound(id)))?;
+        self.touch([id]);
+        Ok(context)
     }
 
     /// Updates an existing context with new data and metadata
@@ -195,6 +481,8 @@ impl ContextManager {
     /// # Errors
     ///
     /// Returns `ContextError::NotFound` if the context does not exist.
+    /// Returns `ContextError::ValidationError` if the updated context does not
+    /// fit within the size limits.
     /// Returns `ContextError::SyncError` if synchronization fails.
     #[instrument(skip(self, data, metadata))]
     pub async fn update_context(
@@ -203,21 +491,18 @@ impl ContextManager {
         data: serde_json::Value,
         metadata: Option<serde_json::Value>,
     ) -> Result<()> {
-        let mut contexts = self.contexts.write().await;
-
-        let context = contexts
-            .get_mut(&id)
-            .ok_or(MCPError::Context(ContextError::NotFound(id)))?;
+        let _foreground = self.sync.foreground();
+        let mut updated_context = self.get_context(id).await?;
 
         // Update context fields
-        context.data = data;
+        updated_context.data = data;
         if let Some(meta) = metadata {
-            context.metadata = Some(meta);
+            updated_context.metadata = Some(meta);
         }
-        context.updated_at = Utc::now();
+        updated_context.updated_at = Utc::now();
+        self.redact(&mut updated_context);
 
-        // Clone for sync operation
-        let updated_context = context.clone();
+        self.store_context(updated_context.clone(), None).await?;
 
         // Record change for sync
         if let Err(e) = self
@@ -241,14 +526,16 @@ impl ContextManager {
     /// Returns `ContextError::SyncError` if synchronization fails.
     #[instrument(skip(self))]
     pub async fn delete_context(&self, id: Uuid) -> Result<()> {
+        let _foreground = self.sync.foreground();
         // Get context for sync before removing
         let context = self.get_context(id).await?;
 
         // Remove from storage
-        let mut contexts = self.contexts.write().await;
+        let mut contexts = self.write_contexts().await;
         contexts
             .remove(&id)
             .ok_or(MCPError::Context(ContextError::NotFound(id)))?;
+        self.queue_writes([(id, None)]);
 
         // Remove from hierarchy
         let mut hierarchy = self.hierarchy.write().await;
@@ -260,6 +547,12 @@ impl ContextManager {
 
         // Remove any children that this context was a parent of
         hierarchy.remove(&id);
+        self.forks.write().await.remove(&id);
+        self.usage.write().await.remove(id);
+        self.access.forget(id);
+        self.index.write().await.remove(id);
+        drop((contexts, hierarchy));
+        self.publish().await;
 
         // Record change for sync
         if let Err(e) = self
@@ -279,7 +572,8 @@ impl ContextManager {
     ///
     /// # Errors
     ///
-    /// Returns `ContextError::ValidationError` if the validation schema is invalid.
+    /// Returns `ContextError::ValidationError` if the validation schema is
+    /// invalid, or an `expr:` rule is not a valid condition.
     #[instrument(skip(self, validation))]
     pub async fn register_validation(
         &self,
@@ -293,6 +587,13 @@ impl ContextManager {
                 "Invalid validation schema".into(),
             )));
         }
+        for rule in &validation.rules {
+            if let Some(source) = rule.strip_prefix(EXPR_RULE_PREFIX) {
+                Expression::condition(source).map_err(|e| {
+                    MCPError::Context(ContextError::ValidationError(format!("Rule '{rule}': {e}")))
+                })?;
+            }
+        }
 
         let mut validations = self.validations.write().await;
         validations.insert(context_type.clone(), validation);
@@ -367,6 +668,27 @@ impl ContextManager {
             return Ok(());
         }
 
+        // Expression rules, over the context's name, data and metadata
+        if let Some(source) = rule.strip_prefix(EXPR_RULE_PREFIX) {
+            let input = expr::input([
+                ("name", serde_json::Value::from(context.name.clone())),
+                ("data", context.data.clone()),
+                ("metadata", context.metadata.clone().unwrap_or_default()),
+            ]);
+            let passed = Expression::condition(source).and_then(|condition| condition.test(&input));
+            return match passed {
+                Ok(true) => Ok(()),
+                Ok(false) => Err(MCPError::Context(ContextError::ValidationError(format!(
+                    "Rule '{}' failed for context '{}'",
+                    rule, context.name
+                )))),
+                Err(e) => Err(MCPError::Context(ContextError::ValidationError(format!(
+                    "Rule '{}' could not be evaluated for context '{}': {e}",
+                    rule, context.name
+                )))),
+            };
+        }
+
         // Check if the rule function exists and apply it
         if !rule_validator(rule, context) {
             return Err(MCPError::Context(ContextError::ValidationError(format!(
@@ -388,18 +710,434 @@ impl ContextManager {
         // Verify parent exists
         self.get_context(parent_id).await?;
 
-        let hierarchy = self.hierarchy.read().await;
-        let contexts = self.contexts.read().await;
-
-        let child_ids = hierarchy.get(&parent_id).cloned().unwrap_or_default();
+        let child_ids = self.hierarchy.read().await.get(&parent_id).cloned().unwrap_or_default();
+        let contexts = self.read_contexts(child_ids.len());
         let children = child_ids
             .into_iter()
-            .filter_map(|id| contexts.get(&id).cloned())
+            .filter_map(|id| contexts.get(&id).map(|context| Context::clone(context)))
             .collect();
 
         Ok(children)
     }
 
+    /// Lists the inheritance chain of a context, from the context itself up
+    /// to its root
+    ///
+    /// The chain ends at the first ancestor that no longer exists.
+    ///
+    /// # Errors
+    ///
+    /// Returns `ContextError::NotFound` if the context does not exist.
+    #[instrument(skip(self))]
+    pub async fn get_inheritance_chain(&self, id: Uuid) -> Result<Vec<Context>> {
+        let contexts = self.published.load();
+        let mut chain = vec![contexts
+            .get(&id)
+            .map(|context| Context::clone(context))
+            .ok_or(MCPError::Context(ContextError::NotFound(id)))?];
+        while let Some(parent) = chain
+            .last()
+            .and_then(|c| c.parent_id)
+            .and_then(|parent_id| contexts.get(&parent_id))
+        {
+            chain.push(Context::clone(parent));
+        }
+        self.touch(chain.iter().map(|context| context.id));
+        Ok(chain)
+    }
+
+    /// Resolves the effective data of a context: its own data on top of the
+    /// data inherited from its ancestors
+    ///
+    /// # Errors
+    ///
+    /// Returns `ContextError::NotFound` if the context does not exist.
+    #[instrument(skip(self))]
+    pub async fn resolve_context(&self, id: Uuid) -> Result<serde_json::Value> {
+        let chain = self.get_inheritance_chain(id).await?;
+        Ok(effective_data(&chain))
+    }
+
+    /// Resolves the effective value at a dot-separated `path` (e.g.
+    /// `tool.timeout`) and the context it is inherited from
+    ///
+    /// Returns `None` if no context in the chain sets the path, or if a
+    /// nearer context overrides one of its parents with a non-object value.
+    ///
+    /// # Errors
+    ///
+    /// Returns `ContextError::NotFound` if the context does not exist.
+    #[instrument(skip(self))]
+    pub async fn resolve_value(&self, id: Uuid, path: &str) -> Result<Option<ResolvedValue>> {
+        let chain = self.get_inheritance_chain(id).await?;
+        let effective = effective_data(&chain);
+        let Some(value) = value_at(&effective, path) else {
+            return Ok(None);
+        };
+        // The nearest context setting the path is the one whose value won
+        let source = chain
+            .iter()
+            .find(|context| value_at(&context.data, path).is_some())
+            .map_or(id, |context| context.id);
+        Ok(Some(ResolvedValue {
+            value: value.clone(),
+            source,
+        }))
+    }
+
+    /// Forks a context for a speculative run
+    ///
+    /// The fork starts out empty and inherits every value from the original
+    /// context, so nothing is copied until the fork overrides a value with
+    /// [`update_context`](Self::update_context). Changes to the fork never
+    /// affect the original; [`commit_fork`](Self::commit_fork) applies them
+    /// and [`delete_context`](Self::delete_context) discards them.
+    ///
+    /// Forks are not listed as children of the original context.
+    ///
+    /// # Errors
+    ///
+    /// Returns `ContextError::NotFound` if the context does not exist.
+    /// Returns `ContextError::ValidationError` if the fork does not fit
+    /// within the size limits.
+    /// Returns `ContextError::SyncError` if synchronization fails.
+    #[instrument(skip(self))]
+    pub async fn fork_context(&self, id: Uuid, name: &str) -> Result<Uuid> {
+        let origin = self.get_context(id).await?;
+        let now = Utc::now();
+        let fork = Context {
+            id: Uuid::new_v4(),
+            name: name.to_string(),
+            data: serde_json::Value::Object(serde_json::Map::new()),
+            metadata: origin.metadata,
+            parent_id: Some(id),
+            created_at: now,
+            updated_at: now,
+            expires_at: origin.expires_at,
+        };
+        let fork_id = fork.id;
+
+        self.store_context(fork.clone(), Some(id)).await?;
+
+        if let Err(e) = self
+            .sync
+            .record_context_change(&fork, StateOperation::Create)
+            .await
+        {
+            error!("Failed to record context change: {}", e);
+            return Err(MCPError::Context(ContextError::SyncError(e.to_string())));
+        }
+
+        info!(context_id = %id, fork_id = %fork_id, "Context forked");
+        Ok(fork_id)
+    }
+
+    /// Applies the values a fork overrides to the context it was forked from
+    /// and removes the fork
+    ///
+    /// # Errors
+    ///
+    /// Returns `ContextError::NotFound` if the fork or its original context
+    /// does not exist.
+    /// Returns `ContextError::ValidationError` if the context is not a fork.
+    /// Returns `ContextError::SyncError` if synchronization fails.
+    #[instrument(skip(self))]
+    pub async fn commit_fork(&self, fork_id: Uuid) -> Result<Uuid> {
+        let fork = self.get_context(fork_id).await?;
+        let origin_id = self.forks.read().await.get(&fork_id).copied().ok_or_else(|| {
+            MCPError::Context(ContextError::ValidationError(format!(
+                "Context {fork_id} is not a fork"
+            )))
+        })?;
+        let mut data = self.get_context(origin_id).await?.data;
+        merge_values(&mut data, &fork.data);
+
+        self.update_context(origin_id, data, None).await?;
+        self.delete_context(fork_id).await?;
+
+        info!(context_id = %origin_id, fork_id = %fork_id, "Fork committed");
+        Ok(origin_id)
+    }
+
+    /// Finds the contexts matching a search query, most recently updated
+    /// first
+    ///
+    /// See [`crate::context_search`] for the query syntax, e.g.
+    /// `params.dataset = GRCh38 and tool ~ bwa`.
+    ///
+    /// # Errors
+    ///
+    /// Returns `ContextError::ValidationError` if the query is invalid.
+    #[instrument(skip(self))]
+    pub async fn search(&self, query: &str) -> Result<Vec<Context>> {
+        let query: ContextQuery = query.parse()?;
+        let ids = self.index.read().await.search(&query);
+        let contexts = self.read_contexts(ids.len());
+        let mut found: Vec<Context> = ids
+            .iter()
+            .filter_map(|id| contexts.get(id).map(|context| Context::clone(context)))
+            .collect();
+        found.sort_by_key(|context| std::cmp::Reverse(context.updated_at));
+        Ok(found)
+    }
+
+    /// Pins a context so it is never evicted
+    ///
+    /// # Errors
+    ///
+    /// Returns `ContextError::NotFound` if the context does not exist.
+    #[instrument(skip(self))]
+    pub async fn pin_context(&self, id: Uuid) -> Result<()> {
+        self.set_pinned(id, true).await
+    }
+
+    /// Unpins a context so it can be evicted again
+    ///
+    /// # Errors
+    ///
+    /// Returns `ContextError::NotFound` if the context does not exist.
+    #[instrument(skip(self))]
+    pub async fn unpin_context(&self, id: Uuid) -> Result<()> {
+        self.set_pinned(id, false).await
+    }
+
+    async fn set_pinned(&self, id: Uuid, pinned: bool) -> Result<()> {
+        let mut usage = self.usage.write().await;
+        let entry = usage
+            .entries
+            .get_mut(&id)
+            .ok_or(MCPError::Context(ContextError::NotFound(id)))?;
+        entry.pinned = pinned;
+        Ok(())
+    }
+
+    /// Returns the memory use of the stored contexts and how many were
+    /// evicted or rejected
+    pub async fn stats(&self) -> ContextStats {
+        let usage = self.usage.read().await;
+        ContextStats {
+            contexts: usage.entries.len(),
+            total_bytes: usage.total_bytes,
+            pinned: usage.entries.values().filter(|entry| entry.pinned).count(),
+            evictions: usage.evictions,
+            evicted_bytes: usage.evicted_bytes,
+            rejected_writes: usage.rejected_writes,
+        }
+    }
+
+    /// Stores a context within the size limits, evicting other contexts to
+    /// make room
+    ///
+    /// A new context is linked to its parent, or recorded as a fork of
+    /// `fork_of`. The context, and the removal of evicted contexts, are
+    /// visible to readers once this returns.
+    async fn store_context(&self, context: Context, fork_of: Option<Uuid>) -> Result<()> {
+        let stored = self.store_unpublished(context, fork_of).await;
+        // Evictions are published even if the context was then rejected
+        self.publish().await;
+        stored
+    }
+
+    /// Stores a context as [`store_context`](Self::store_context) does,
+    /// queueing the writes for publishing
+    async fn store_unpublished(&self, context: Context, fork_of: Option<Uuid>) -> Result<()> {
+        let id = context.id;
+        let bytes = serde_json::to_vec(&context).map_or(0, |encoded| encoded.len());
+
+        let mut contexts = self.write_contexts().await;
+        let mut hierarchy = self.hierarchy.write().await;
+        let mut forks = self.forks.write().await;
+        let mut usage = self.usage.write().await;
+        let mut index = self.index.write().await;
+
+        if let Some(max) = self.config.max_context_bytes.filter(|max| bytes > *max) {
+            usage.rejected_writes += 1;
+            return Err(MCPError::Context(ContextError::ValidationError(format!(
+                "Context {id} is {bytes} bytes, over the limit of {max} bytes"
+            ))));
+        }
+
+        if let Some(max) = self.config.max_total_bytes {
+            let replaced = usage.entries.get(&id).map_or(0, |entry| entry.bytes);
+            while usage.total_bytes - replaced + bytes > max {
+                let victim = usage
+                    .entries
+                    .iter()
+                    .filter(|(candidate, entry)| {
+                        **candidate != id
+                            && Some(**candidate) != context.parent_id
+                            && !entry.pinned
+                            && hierarchy.get(*candidate).is_none_or(Vec::is_empty)
+                            && !forks.values().any(|origin| origin == *candidate)
+                    })
+                    .min_by_key(|(candidate, _)| self.access.eviction_key(**candidate, self.config.eviction_policy))
+                    .map(|(candidate, _)| *candidate);
+                let Some(victim) = victim else {
+                    usage.rejected_writes += 1;
+                    return Err(MCPError::Context(ContextError::ValidationError(format!(
+                        "Context store is full ({max} bytes) and has nothing left to evict"
+                    ))));
+                };
+
+                let freed = usage.remove(victim).map_or(0, |entry| entry.bytes);
+                usage.evictions += 1;
+                usage.evicted_bytes += freed as u64;
+                self.access.forget(victim);
+                forks.remove(&victim);
+                index.remove(victim);
+                self.queue_writes([(victim, None)]);
+                if let Some(parent_id) = contexts.remove(&victim).and_then(|evicted| evicted.parent_id) {
+                    if let Some(children) = hierarchy.get_mut(&parent_id) {
+                        children.retain(|child_id| *child_id != victim);
+                    }
+                }
+                info!(context_id = %victim, bytes = freed, "Context evicted");
+            }
+        }
+
+        if !contexts.contains_key(&id) {
+            match (fork_of, context.parent_id) {
+                (Some(origin), _) => {
+                    forks.insert(id, origin);
+                }
+                (None, Some(parent_id)) => hierarchy.entry(parent_id).or_default().push(id),
+                (None, None) => {}
+            }
+        }
+        index.insert(&context);
+        let context = Arc::new(context);
+        self.queue_writes([(id, Some(Arc::clone(&context)))]);
+        contexts.insert(id, context);
+        usage.store(id, bytes);
+        self.access.track(id);
+        Ok(())
+    }
+
+    /// Locks the contexts for writing, counting the wait if another writer
+    /// holds them
+    async fn write_contexts(&self) -> RwLockWriteGuard<'_, ContextMap> {
+        if let Ok(contexts) = self.contexts.try_write() {
+            return contexts;
+        }
+        let started = Instant::now();
+        let contexts = self.contexts.write().await;
+        self.contention.contended_writes.fetch_add(1, Ordering::Relaxed);
+        self.contention.write_wait_us.fetch_add(
+            u64::try_from(started.elapsed().as_micros()).unwrap_or(u64::MAX),
+            Ordering::Relaxed,
+        );
+        contexts
+    }
+
+    /// The published contexts, counting `reads` lock-free reads
+    fn read_contexts(&self, reads: usize) -> arc_swap::Guard<Arc<ContextVersion>> {
+        self.contention.lock_free_reads.fetch_add(reads as u64, Ordering::Relaxed);
+        self.published.load()
+    }
+
+    /// Queues writes for publishing
+    ///
+    /// Called with the contexts locked for writing, so writes are queued in
+    /// the order they are made.
+    fn queue_writes(&self, writes: impl IntoIterator<Item = PublishedWrite>) {
+        self.queued
+            .lock()
+            .unwrap_or_else(std::sync::PoisonError::into_inner)
+            .extend(writes);
+    }
+
+    /// Publishes the queued writes to readers
+    ///
+    /// Writes queued while another snapshot is being published are published
+    /// together by the first writer to get the lock; the others find the
+    /// queue empty and return once their writes are visible.
+    async fn publish(&self) {
+        let _publishing = self.publishing.lock().await;
+        let batch = std::mem::take(
+            &mut *self
+                .queued
+                .lock()
+                .unwrap_or_else(std::sync::PoisonError::into_inner),
+        );
+        if batch.is_empty() {
+            return;
+        }
+
+        let current = self.published.load_full();
+        let mut contexts = current.contexts.clone();
+        for (id, context) in &batch {
+            match context {
+                Some(context) => contexts.insert(*id, Arc::clone(context)),
+                None => contexts.remove(id),
+            };
+        }
+        self.published.store(Arc::new(ContextVersion {
+            number: current.number + 1,
+            contexts,
+        }));
+
+        let published = batch.len() as u64;
+        self.contention.publishes.fetch_add(1, Ordering::Relaxed);
+        self.contention.published_writes.fetch_add(published, Ordering::Relaxed);
+        self.contention.max_batch.fetch_max(published, Ordering::Relaxed);
+    }
+
+    /// Records reader accesses to contexts for eviction
+    ///
+    /// Accesses are recorded with atomics in the published access log, so
+    /// readers never wait for writers or for each other.
+    fn touch(&self, ids: impl IntoIterator<Item = Uuid>) {
+        let mut reads = 0;
+        for id in ids {
+            self.access.record(id);
+            reads += 1;
+        }
+        self.contention.lock_free_reads.fetch_add(reads, Ordering::Relaxed);
+    }
+
+    /// Pins the latest version of the contexts for a consistent read
+    ///
+    /// Writers are not held up by the snapshot, and it does not see their
+    /// writes; the version is kept until the snapshot is dropped.
+    #[must_use]
+    pub fn snapshot(&self) -> ContextSnapshot {
+        self.snapshots.pin(self.published.load_full())
+    }
+
+    /// Pins a version of the contexts again, for comparing it with a newer one
+    ///
+    /// Returns `None` once the version was superseded and no snapshot pins
+    /// it any more.
+    #[must_use]
+    pub fn snapshot_at(&self, version: u64) -> Option<ContextSnapshot> {
+        let current = self.published.load_full();
+        if current.number == version {
+            return Some(self.snapshots.pin(current));
+        }
+        self.snapshots.repin(version)
+    }
+
+    /// Returns the latest version and the versions snapshots hold on to
+    #[must_use]
+    pub fn snapshot_stats(&self) -> SnapshotStats {
+        self.snapshots.stats(self.published.load().number)
+    }
+
+    /// Returns how often reads and writes of the contexts contended, and
+    /// how writes were batched
+    #[must_use]
+    pub fn contention(&self) -> ContentionStats {
+        let load = |counter: &AtomicU64| counter.load(Ordering::Relaxed);
+        ContentionStats {
+            lock_free_reads: load(&self.contention.lock_free_reads),
+            publishes: load(&self.contention.publishes),
+            published_writes: load(&self.contention.published_writes),
+            max_batch: load(&self.contention.max_batch),
+            contended_writes: load(&self.contention.contended_writes),
+            write_wait_us: load(&self.contention.write_wait_us),
+        }
+    }
+
     /// Synchronizes context data with remote instances
     ///
     /// # Errors
@@ -414,6 +1152,19 @@ impl ContextManager {
         Ok(())
     }
 
+    /// Writes the pending snapshot of synchronized changes and waits for it
+    ///
+    /// # Errors
+    ///
+    /// Returns `ContextError::SyncError` if the snapshot cannot be written.
+    #[instrument(skip(self))]
+    pub async fn flush_snapshots(&self) -> Result<()> {
+        self.sync
+            .flush_snapshots()
+            .await
+            .map_err(|e| MCPError::Context(ContextError::SyncError(e.to_string())))
+    }
+
     /// Subscribes to context change notifications
     ///
     /// # Errors
@@ -452,15 +1203,45 @@ impl ContextManager {
             }
         };
 
-        Ok(Self {
-            contexts: Arc::new(RwLock::new(HashMap::new())),
-            hierarchy: Arc::new(RwLock::new(HashMap::new())),
-            validations: Arc::new(RwLock::new(HashMap::new())),
-            sync,
-        })
+        Ok(Self::with_sync(config, sync))
+    }
+}
+
+/// Merges the data of an inheritance chain, nearest context last
+fn effective_data(chain: &[Context]) -> serde_json::Value {
+    let mut effective = serde_json::Value::Object(serde_json::Map::new());
+    for context in chain.iter().rev() {
+        merge_values(&mut effective, &context.data);
     }
+    effective
 }
 
+/// Overrides `base` with `overlay`, merging objects key by key
+fn merge_values(base: &mut serde_json::Value, overlay: &serde_json::Value) {
+    match (base, overlay) {
+        (serde_json::Value::Object(base), serde_json::Value::Object(overlay)) => {
+            for (key, value) in overlay {
+                match base.get_mut(key) {
+                    Some(existing) => merge_values(existing, value),
+                    None => {
+                        base.insert(key.clone(), value.clone());
+                    }
+                }
+            }
+        }
+        (base, overlay) => *base = overlay.clone(),
+    }
+}
+
+/// The value at a dot-separated path of object keys
+fn value_at<'a>(value: &'a serde_json::Value, path: &str) -> Option<&'a serde_json::Value> {
+    path.split('.').try_fold(value, |value, key| value.as_object()?.get(key))
+}
+
+/// Prefix of validation rules that are sandboxed expressions, e.g.
+/// `expr:len(data.items) > 0`
+const EXPR_RULE_PREFIX: &str = "expr:";
+
 /// Validates if a rule applies to a context
 ///
 /// This function checks whether a given rule applies to a specific context
@@ -537,12 +1318,7 @@ mod tests {
         let sync = create_test_sync().await;
 
         // Create ContextManager with pre-initialized sync
-        let manager = ContextManager {
-            contexts: Arc::new(RwLock::new(HashMap::new())),
-            hierarchy: Arc::new(RwLock::new(HashMap::new())),
-            validations: Arc::new(RwLock::new(HashMap::new())),
-            sync,
-        };
+        let manager = ContextManager::with_sync(ContextConfig::default(), sync);
 
         let context = Context {
             id: Uuid::new_v4(),
@@ -581,18 +1357,42 @@ mod tests {
         assert!(manager.get_context(id).await.is_err());
     }
 
+    #[tokio::test]
+    async fn test_contexts_are_redacted_before_they_are_stored() {
+        let redactor = Redactor::new(&squirrel_core::redaction::RedactionConfig::default()).unwrap();
+        let manager = ContextManager::with_sync(ContextConfig::default(), create_test_sync().await)
+            .with_redactor(Arc::new(redactor));
+        let context = Context {
+            id: Uuid::new_v4(),
+            name: "job".to_string(),
+            data: serde_json::json!({"db_password": "hunter2", "step": "align"}),
+            metadata: Some(serde_json::json!({"auth": "Bearer abc123"})),
+            parent_id: None,
+            created_at: Utc::now(),
+            updated_at: Utc::now(),
+            expires_at: None,
+        };
+
+        let id = manager.create_context(context).await.unwrap();
+        let stored = manager.get_context(id).await.unwrap();
+        assert_eq!(stored.data, serde_json::json!({"db_password": "[REDACTED]", "step": "align"}));
+        assert_eq!(stored.metadata, Some(serde_json::json!({"auth": "Bearer [REDACTED]"})));
+
+        manager
+            .update_context(id, serde_json::json!({"api_key": "k1"}), None)
+            .await
+            .unwrap();
+        let updated = manager.get_context(id).await.unwrap();
+        assert_eq!(updated.data, serde_json::json!({"api_key": "[REDACTED]"}));
+    }
+
     #[tokio::test]
     async fn test_context_hierarchy() {
         // Use the helper to create a pre-initialized MCPSync instance
         let sync = create_test_sync().await;
 
         // Create ContextManager with pre-initialized sync
-        let manager = ContextManager {
-            contexts: Arc::new(RwLock::new(HashMap::new())),
-            hierarchy: Arc::new(RwLock::new(HashMap::new())),
-            validations: Arc::new(RwLock::new(HashMap::new())),
-            sync,
-        };
+        let manager = ContextManager::with_sync(ContextConfig::default(), sync);
 
         let parent_id = Uuid::new_v4();
         let child_id = Uuid::new_v4();
@@ -635,12 +1435,7 @@ mod tests {
         let sync = create_test_sync().await;
 
         // Create ContextManager with pre-initialized sync
-        let manager = ContextManager {
-            contexts: Arc::new(RwLock::new(HashMap::new())),
-            hierarchy: Arc::new(RwLock::new(HashMap::new())),
-            validations: Arc::new(RwLock::new(HashMap::new())),
-            sync,
-        };
+        let manager = ContextManager::with_sync(ContextConfig::default(), sync);
 
         // Register validation
         let validation = ContextValidation {
@@ -685,4 +1480,252 @@ mod tests {
         let result = manager.create_context(invalid_context).await;
         println!("Invalid context creation result: {:?}", result);
     }
+
+    #[tokio::test]
+    async fn test_expression_validation_rules() {
+        let sync = create_test_sync().await;
+        let manager = ContextManager::with_sync(ContextConfig::default(), sync);
+        let validation = |rule: &str| ContextValidation {
+            schema: serde_json::json!({"type": "object"}),
+            rules: vec![format!("expr:{rule}")],
+        };
+        assert!(manager.register_validation("order".to_string(), validation("len(data.items) +")).await.is_err());
+        assert!(manager.register_validation("order".to_string(), validation("len(data.items)")).await.is_err());
+        manager
+            .register_validation("order".to_string(), validation(r#"len(data.items) > 0 && metadata.type == "order""#))
+            .await
+            .unwrap();
+
+        let context = |data: serde_json::Value| Context {
+            id: Uuid::new_v4(),
+            name: "order".to_string(),
+            data,
+            metadata: Some(serde_json::json!({"type": "order"})),
+            parent_id: None,
+            created_at: Utc::now(),
+            updated_at: Utc::now(),
+            expires_at: None,
+        };
+        assert!(manager.create_context(context(serde_json::json!({"items": [1]}))).await.is_ok());
+        assert!(manager.create_context(context(serde_json::json!({"items": []}))).await.is_err());
+        assert!(manager.create_context(context(serde_json::json!({"items": 3}))).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_context_inheritance_and_forks() {
+        let manager = ContextManager::with_sync(ContextConfig::default(), create_test_sync().await);
+        let context = |name: &str, data: serde_json::Value, parent_id: Option<Uuid>| Context {
+            id: Uuid::new_v4(),
+            name: name.to_string(),
+            data,
+            metadata: None,
+            parent_id,
+            created_at: Utc::now(),
+            updated_at: Utc::now(),
+            expires_at: None,
+        };
+
+        let root = context("root", serde_json::json!({"model": "a", "tool": {"timeout": 30, "retries": 2}}), None);
+        let root_id = manager.create_context(root).await.unwrap();
+        let child = context("child", serde_json::json!({"tool": {"timeout": 60}}), Some(root_id));
+        let child_id = manager.create_context(child).await.unwrap();
+
+        let effective = manager.resolve_context(child_id).await.unwrap();
+        assert_eq!(effective, serde_json::json!({"model": "a", "tool": {"timeout": 60, "retries": 2}}));
+        let timeout = manager.resolve_value(child_id, "tool.timeout").await.unwrap().unwrap();
+        assert_eq!((timeout.value, timeout.source), (serde_json::json!(60), child_id));
+        let retries = manager.resolve_value(child_id, "tool.retries").await.unwrap().unwrap();
+        assert_eq!(retries.source, root_id);
+        assert!(manager.resolve_value(child_id, "missing").await.unwrap().is_none());
+
+        let chain: Vec<Uuid> = manager.get_inheritance_chain(child_id).await.unwrap().iter().map(|c| c.id).collect();
+        assert_eq!(chain, vec![child_id, root_id]);
+
+        // A context cannot become its own ancestor
+        let mut cycle = context("cycle", serde_json::json!({}), Some(child_id));
+        cycle.id = root_id;
+        assert!(manager.create_context(cycle).await.is_err());
+
+        // A fork sees the original's values, but its overrides stay private
+        let fork_id = manager.fork_context(child_id, "speculative").await.unwrap();
+        manager.update_context(fork_id, serde_json::json!({"model": "b"}), None).await.unwrap();
+        assert_eq!(manager.resolve_value(fork_id, "tool.timeout").await.unwrap().unwrap().value, 60);
+        assert_eq!(manager.resolve_value(child_id, "model").await.unwrap().unwrap().value, "a");
+        assert!(manager.get_child_contexts(child_id).await.unwrap().is_empty());
+
+        assert_eq!(manager.commit_fork(fork_id).await.unwrap(), child_id);
+        assert_eq!(manager.resolve_value(child_id, "model").await.unwrap().unwrap().value, "b");
+        assert!(manager.get_context(fork_id).await.is_err());
+        assert!(manager.commit_fork(child_id).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_size_limits_and_eviction() {
+        // Fixed timestamps keep every entry the same serialized size
+        let context = |data: serde_json::Value| Context {
+            id: Uuid::new_v4(),
+            name: "entry".to_string(),
+            data,
+            metadata: None,
+            parent_id: None,
+            created_at: DateTime::UNIX_EPOCH,
+            updated_at: DateTime::UNIX_EPOCH,
+            expires_at: None,
+        };
+        let entry_bytes = serde_json::to_vec(&context(serde_json::json!({"n": 0}))).unwrap().len();
+        let config = ContextConfig {
+            max_context_bytes: Some(entry_bytes + 100),
+            max_total_bytes: Some(entry_bytes * 3),
+            ..ContextConfig::default()
+        };
+        let manager = ContextManager::with_sync(config, create_test_sync().await);
+
+        let pinned = manager.create_context(context(serde_json::json!({"n": 1}))).await.unwrap();
+        let old = manager.create_context(context(serde_json::json!({"n": 2}))).await.unwrap();
+        let recent = manager.create_context(context(serde_json::json!({"n": 3}))).await.unwrap();
+        manager.pin_context(pinned).await.unwrap();
+        manager.get_context(recent).await.unwrap();
+
+        // The least recently used unpinned context makes room
+        let new = manager.create_context(context(serde_json::json!({"n": 4}))).await.unwrap();
+        assert!(manager.get_context(old).await.is_err());
+        assert!(manager.get_context(pinned).await.is_ok());
+        assert!(manager.get_context(recent).await.is_ok());
+
+        let too_large = context(serde_json::json!({"n": "x".repeat(200)}));
+        assert!(manager.create_context(too_large).await.is_err());
+        assert!(manager.update_context(new, serde_json::json!({"n": "x".repeat(200)}), None).await.is_err());
+
+        let stats = manager.stats().await;
+        assert_eq!((stats.contexts, stats.pinned, stats.evictions, stats.rejected_writes), (3, 1, 1, 2));
+        assert_eq!(stats.total_bytes, entry_bytes * 3);
+
+        // Nothing can be evicted once every context is pinned
+        manager.pin_context(recent).await.unwrap();
+        manager.pin_context(new).await.unwrap();
+        assert!(manager.create_context(context(serde_json::json!({"n": 5}))).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_search_follows_updates_and_deletes() {
+        let manager = ContextManager::with_sync(ContextConfig::default(), create_test_sync().await);
+        let context = Context {
+            id: Uuid::new_v4(),
+            name: "align".to_string(),
+            data: serde_json::json!({"params": {"dataset": "GRCh38"}}),
+            metadata: None,
+            parent_id: None,
+            created_at: Utc::now(),
+            updated_at: Utc::now(),
+            expires_at: None,
+        };
+        let id = manager.create_context(context).await.unwrap();
+
+        let found = manager.search("params.dataset = GRCh38").await.unwrap();
+        assert_eq!(found.iter().map(|c| c.id).collect::<Vec<_>>(), vec![id]);
+        assert!(manager.search("params.dataset =").await.is_err());
+
+        manager.update_context(id, serde_json::json!({"params": {"dataset": "hg19"}}), None).await.unwrap();
+        assert!(manager.search("params.dataset = GRCh38").await.unwrap().is_empty());
+        assert_eq!(manager.search("hg19").await.unwrap().len(), 1);
+
+        manager.delete_context(id).await.unwrap();
+        assert!(manager.search("hg19").await.unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_reads_never_wait_for_writers() {
+        let manager = Arc::new(ContextManager::with_sync(ContextConfig::default(), create_test_sync().await));
+        let context = Context {
+            id: Uuid::new_v4(),
+            name: "hot".to_string(),
+            data: serde_json::json!({"n": 0}),
+            metadata: None,
+            parent_id: None,
+            created_at: Utc::now(),
+            updated_at: Utc::now(),
+            expires_at: None,
+        };
+        let id = manager.create_context(context).await.unwrap();
+
+        // Readers are served while a writer holds the store
+        let writer = manager.contexts.write().await;
+        let read = tokio::time::timeout(std::time::Duration::from_secs(1), manager.get_context(id)).await;
+        assert_eq!(read.unwrap().unwrap().data["n"], 0);
+        let update = |n: u64| {
+            let manager = Arc::clone(&manager);
+            tokio::spawn(async move { manager.update_context(id, serde_json::json!({"n": n}), None).await })
+        };
+        let waiting = update(1);
+        tokio::task::yield_now().await;
+        drop(writer);
+        waiting.await.unwrap().unwrap();
+        assert_eq!(manager.get_context(id).await.unwrap().data["n"], 1);
+
+        // Every write is visible once it returns
+        let updates: Vec<_> = (2..10).map(update).collect();
+        for update in updates {
+            update.await.unwrap().unwrap();
+        }
+        assert!(manager.get_context(id).await.unwrap().data["n"].as_u64().unwrap() >= 2);
+
+        let contention = manager.contention();
+        assert!(contention.contended_writes >= 1);
+        assert!(contention.lock_free_reads >= 3 + 9);
+        assert_eq!(contention.published_writes, 10);
+        assert!(contention.publishes <= 10 && contention.max_batch >= 1);
+    }
+
+    #[tokio::test]
+    async fn test_snapshots_pin_versions_while_writers_continue() {
+        let manager = ContextManager::with_sync(ContextConfig::default(), create_test_sync().await);
+        let context = |name: &str, parent_id: Option<Uuid>| Context {
+            id: Uuid::new_v4(),
+            name: name.to_string(),
+            data: serde_json::json!({"step": 1}),
+            metadata: None,
+            parent_id,
+            created_at: Utc::now(),
+            updated_at: Utc::now(),
+            expires_at: None,
+        };
+        let root = manager.create_context(context("root", None)).await.unwrap();
+        let child = manager.create_context(context("child", Some(root))).await.unwrap();
+
+        let before = manager.snapshot();
+        manager.update_context(child, serde_json::json!({"step": 2}), None).await.unwrap();
+        let added = manager.create_context(context("late", None)).await.unwrap();
+        manager.delete_context(added).await.unwrap();
+        let added = manager.create_context(context("later", Some(root))).await.unwrap();
+
+        // The pinned version is unaffected by the writes
+        assert_eq!(before.len(), 2);
+        assert_eq!(before.get(child).unwrap().data["step"], 1);
+        assert!(before.get(added).is_none());
+        assert_eq!(before.inheritance_chain(child).iter().map(|c| c.id).collect::<Vec<_>>(), vec![child, root]);
+        assert_eq!(before.children(root).len(), 1);
+
+        let after = manager.snapshot();
+        assert!(after.version() > before.version());
+        assert_eq!(after.get(child).unwrap().data["step"], 2);
+        let diff = before.diff(&after);
+        assert_eq!((diff.added, diff.changed, diff.removed), (vec![added], vec![child], vec![]));
+        assert!(after.diff(&manager.snapshot()).is_empty());
+
+        let stats = manager.snapshot_stats();
+        assert_eq!(stats.current_version, after.version());
+        assert_eq!((stats.pinned_versions, stats.retained_versions, stats.open_snapshots), (2, 1, 2));
+        assert_eq!(stats.oldest_pinned_version, Some(before.version()));
+
+        // An old version is collected once its last reader lets go
+        let version = before.version();
+        let again = manager.snapshot_at(version).unwrap();
+        drop(before);
+        assert_eq!(again.get(child).unwrap().data["step"], 1);
+        drop(again);
+        assert!(manager.snapshot_at(version).is_none());
+        let stats = manager.snapshot_stats();
+        assert_eq!((stats.retained_versions, stats.collected_versions), (0, 1));
+        assert!(manager.snapshot_at(after.version()).is_some());
+    }
 }
@@ -0,0 +1,389 @@
+//! Search over stored contexts
+//!
+//! [`ContextIndex`] is an inverted index over the names, data and metadata of
+//! contexts. Every value is indexed under its dot-separated path (`name`,
+//! `params.dataset`, `metadata.type`, ...) for structured matches, and every
+//! word of the keys and values is indexed for full-text matches.
+//!
+//! A [`ContextQuery`] is a list of clauses that must all match, optionally
+//! joined with `and`:
+//!
+//! - `path = value` matches contexts whose value at `path` equals `value`;
+//!   an array matches if any of its elements does
+//! - `path != value` matches contexts whose value at `path` differs,
+//!   including contexts without one
+//! - `path ~ word` matches contexts whose value at `path` contains `word`
+//! - a bare `word` or `"quoted words"` matches contexts containing every
+//!   word anywhere in their keys or values
+//!
+//! For example: `params.dataset = GRCh38 and tool ~ bwa "paired end"`.
+//! Word matches are case-insensitive, value matches are exact.
+
+use std::collections::{HashMap, HashSet};
+use std::str::FromStr;
+
+use uuid::Uuid;
+
+use crate::context_manager::Context;
+use crate::error::types::ContextError;
+use crate::error::MCPError;
+
+/// One condition of a [`ContextQuery`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum QueryClause {
+    /// The value at the path equals the value
+    Equals(String, String),
+    /// The value at the path is missing or differs from the value
+    NotEquals(String, String),
+    /// The value at the path contains the word
+    Contains(String, String),
+    /// The word appears anywhere in the context
+    Term(String),
+}
+
+/// A parsed context search query
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ContextQuery {
+    /// Clauses that must all match
+    pub clauses: Vec<QueryClause>,
+}
+
+/// Token of the query language
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Token {
+    Word(String),
+    Quoted(String),
+    Equals,
+    NotEquals,
+    Contains,
+}
+
+impl FromStr for ContextQuery {
+    type Err = MCPError;
+
+    fn from_str(query: &str) -> Result<Self, Self::Err> {
+        let invalid = |message: String| MCPError::Context(ContextError::ValidationError(message));
+        let tokens = lex(query).map_err(invalid)?;
+
+        let mut clauses = Vec::new();
+        let mut tokens = tokens.into_iter().peekable();
+        while let Some(token) = tokens.next() {
+            match token {
+                Token::Word(word) if word.eq_ignore_ascii_case("and") => {}
+                Token::Word(path) if matches!(tokens.peek(), Some(Token::Equals | Token::NotEquals | Token::Contains)) => {
+                    let operator = tokens.next();
+                    let value = match tokens.next() {
+                        Some(Token::Word(value) | Token::Quoted(value)) => value,
+                        _ => return Err(invalid(format!("Missing value after '{path}'"))),
+                    };
+                    clauses.push(match operator {
+                        Some(Token::Equals) => QueryClause::Equals(path, value),
+                        Some(Token::NotEquals) => QueryClause::NotEquals(path, value),
+                        _ => {
+                            let words = words(&value);
+                            if words.is_empty() {
+                                return Err(invalid(format!("Nothing to search for in '{path}'")));
+                            }
+                            clauses.extend(words.into_iter().map(|word| QueryClause::Contains(path.clone(), word)));
+                            continue;
+                        }
+                    });
+                }
+                Token::Word(text) | Token::Quoted(text) => {
+                    clauses.extend(words(&text).into_iter().map(QueryClause::Term));
+                }
+                Token::Equals | Token::NotEquals | Token::Contains => {
+                    return Err(invalid("Operator without a path".to_string()));
+                }
+            }
+        }
+
+        if clauses.is_empty() {
+            return Err(invalid("Empty search query".to_string()));
+        }
+        Ok(Self { clauses })
+    }
+}
+
+/// Splits a query into tokens
+fn lex(query: &str) -> Result<Vec<Token>, String> {
+    let mut tokens = Vec::new();
+    let mut chars = query.chars().peekable();
+    while let Some(&c) = chars.peek() {
+        match c {
+            c if c.is_whitespace() => {
+                chars.next();
+            }
+            '"' => {
+                chars.next();
+                let mut text = String::new();
+                loop {
+                    match chars.next() {
+                        Some('"') => break,
+                        Some('\\') => text.extend(chars.next()),
+                        Some(c) => text.push(c),
+                        None => return Err("Unterminated quote".to_string()),
+                    }
+                }
+                tokens.push(Token::Quoted(text));
+            }
+            '=' => {
+                chars.next();
+                tokens.push(Token::Equals);
+            }
+            '~' => {
+                chars.next();
+                tokens.push(Token::Contains);
+            }
+            '!' => {
+                chars.next();
+                if chars.next() != Some('=') {
+                    return Err("Expected '=' after '!'".to_string());
+                }
+                tokens.push(Token::NotEquals);
+            }
+            _ => {
+                let mut word = String::new();
+                while let Some(&c) = chars.peek() {
+                    if c.is_whitespace() || matches!(c, '"' | '=' | '!' | '~') {
+                        break;
+                    }
+                    word.push(c);
+                    chars.next();
+                }
+                tokens.push(Token::Word(word));
+            }
+        }
+    }
+    Ok(tokens)
+}
+
+/// Lowercase words of a text
+fn words(text: &str) -> Vec<String> {
+    text.split(|c: char| !c.is_alphanumeric())
+        .filter(|word| !word.is_empty())
+        .map(str::to_lowercase)
+        .collect()
+}
+
+/// Inverted index over contexts
+#[derive(Debug, Default)]
+pub struct ContextIndex {
+    /// Contexts by word
+    terms: HashMap<String, HashSet<Uuid>>,
+    /// Contexts by path and value
+    values: HashMap<String, HashMap<String, HashSet<Uuid>>>,
+    /// Contexts by path and word of the value
+    value_terms: HashMap<String, HashMap<String, HashSet<Uuid>>>,
+    /// Indexed `(path, value)` pairs by context, to unindex them
+    documents: HashMap<Uuid, Vec<(String, String)>>,
+}
+
+impl ContextIndex {
+    /// Creates an empty index
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Creates an index of `contexts`
+    pub fn from_contexts<'a>(contexts: impl IntoIterator<Item = &'a Context>) -> Self {
+        let mut index = Self::new();
+        for context in contexts {
+            index.insert(context);
+        }
+        index
+    }
+
+    /// Number of indexed contexts
+    #[must_use]
+    pub fn len(&self) -> usize {
+        self.documents.len()
+    }
+
+    /// Whether no context is indexed
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.documents.is_empty()
+    }
+
+    /// Indexes a context, replacing its previous entry
+    pub fn insert(&mut self, context: &Context) {
+        self.remove(context.id);
+
+        let mut entries = vec![("name".to_string(), context.name.clone())];
+        flatten("", &context.data, &mut entries);
+        if let Some(metadata) = &context.metadata {
+            flatten("metadata", metadata, &mut entries);
+        }
+
+        for (path, value) in &entries {
+            for term in words(path).into_iter().chain(words(value)) {
+                self.terms.entry(term).or_default().insert(context.id);
+            }
+            for term in words(value) {
+                self.value_terms
+                    .entry(path.clone())
+                    .or_default()
+                    .entry(term)
+                    .or_default()
+                    .insert(context.id);
+            }
+            self.values
+                .entry(path.clone())
+                .or_default()
+                .entry(value.clone())
+                .or_default()
+                .insert(context.id);
+        }
+        self.documents.insert(context.id, entries);
+    }
+
+    /// Removes a context from the index
+    pub fn remove(&mut self, id: Uuid) {
+        let Some(entries) = self.documents.remove(&id) else {
+            return;
+        };
+        for (path, value) in entries {
+            for term in words(&path).into_iter().chain(words(&value)) {
+                remove_posting(&mut self.terms, &term, id);
+            }
+            if let Some(terms) = self.value_terms.get_mut(&path) {
+                for term in words(&value) {
+                    remove_posting(terms, &term, id);
+                }
+                if terms.is_empty() {
+                    self.value_terms.remove(&path);
+                }
+            }
+            if let Some(values) = self.values.get_mut(&path) {
+                remove_posting(values, &value, id);
+                if values.is_empty() {
+                    self.values.remove(&path);
+                }
+            }
+        }
+    }
+
+    /// IDs of the contexts matching every clause of `query`
+    #[must_use]
+    pub fn search(&self, query: &ContextQuery) -> HashSet<Uuid> {
+        let mut matches: Option<HashSet<Uuid>> = None;
+        for clause in &query.clauses {
+            let clause_matches = match clause {
+                QueryClause::Equals(path, value) => postings(self.values.get(path), value),
+                QueryClause::NotEquals(path, value) => {
+                    let equal = postings(self.values.get(path), value);
+                    self.documents.keys().filter(|id| !equal.contains(id)).copied().collect()
+                }
+                QueryClause::Contains(path, word) => postings(self.value_terms.get(path), word),
+                QueryClause::Term(word) => self.terms.get(word).cloned().unwrap_or_default(),
+            };
+            let narrowed = match matches {
+                Some(previous) => previous.intersection(&clause_matches).copied().collect(),
+                None => clause_matches,
+            };
+            if narrowed.is_empty() {
+                return narrowed;
+            }
+            matches = Some(narrowed);
+        }
+        matches.unwrap_or_default()
+    }
+}
+
+/// The contexts under `key` of a posting map
+fn postings(map: Option<&HashMap<String, HashSet<Uuid>>>, key: &str) -> HashSet<Uuid> {
+    map.and_then(|map| map.get(key)).cloned().unwrap_or_default()
+}
+
+/// Removes `id` from the postings under `key`, dropping emptied keys
+fn remove_posting(map: &mut HashMap<String, HashSet<Uuid>>, key: &str, id: Uuid) {
+    if let Some(ids) = map.get_mut(key) {
+        ids.remove(&id);
+        if ids.is_empty() {
+            map.remove(key);
+        }
+    }
+}
+
+/// Collects the scalar values of `value` with their dot-separated paths;
+/// array elements share the path of their array
+fn flatten(path: &str, value: &serde_json::Value, entries: &mut Vec<(String, String)>) {
+    match value {
+        serde_json::Value::Object(map) => {
+            for (key, value) in map {
+                let child = if path.is_empty() { key.clone() } else { format!("{path}.{key}") };
+                flatten(&child, value, entries);
+            }
+        }
+        serde_json::Value::Array(items) => {
+            for item in items {
+                flatten(path, item, entries);
+            }
+        }
+        serde_json::Value::String(text) => entries.push((path.to_string(), text.clone())),
+        scalar => entries.push((path.to_string(), scalar.to_string())),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::Utc;
+
+    fn context(name: &str, data: serde_json::Value) -> Context {
+        Context {
+            id: Uuid::new_v4(),
+            name: name.to_string(),
+            data,
+            metadata: Some(serde_json::json!({"type": "run"})),
+            parent_id: None,
+            created_at: Utc::now(),
+            updated_at: Utc::now(),
+            expires_at: None,
+        }
+    }
+
+    #[test]
+    fn test_query_parsing() {
+        let query: ContextQuery = r#"params.dataset=GRCh38 and tool ~ "BWA mem" status != failed aligned"#.parse().unwrap();
+        assert_eq!(
+            query.clauses,
+            vec![
+                QueryClause::Equals("params.dataset".to_string(), "GRCh38".to_string()),
+                QueryClause::Contains("tool".to_string(), "bwa".to_string()),
+                QueryClause::Contains("tool".to_string(), "mem".to_string()),
+                QueryClause::NotEquals("status".to_string(), "failed".to_string()),
+                QueryClause::Term("aligned".to_string()),
+            ]
+        );
+        assert!("".parse::<ContextQuery>().is_err());
+        assert!("dataset =".parse::<ContextQuery>().is_err());
+        assert!("\"open".parse::<ContextQuery>().is_err());
+    }
+
+    #[test]
+    fn test_structured_and_full_text_search() {
+        let grch38 = context("align sample", serde_json::json!({"params": {"dataset": "GRCh38", "threads": 8}, "tags": ["dna", "wgs"]}));
+        let hg19 = context("align legacy", serde_json::json!({"params": {"dataset": "hg19", "threads": 8}, "tags": ["dna"]}));
+        let mut index = ContextIndex::from_contexts([&grch38, &hg19]);
+
+        let search = |index: &ContextIndex, query: &str| index.search(&query.parse().unwrap());
+        assert_eq!(search(&index, "params.dataset = GRCh38"), HashSet::from([grch38.id]));
+        assert_eq!(search(&index, "params.threads = 8 and tags = dna").len(), 2);
+        assert_eq!(search(&index, "tags = wgs"), HashSet::from([grch38.id]));
+        assert_eq!(search(&index, "params.dataset != GRCh38"), HashSet::from([hg19.id]));
+        assert_eq!(search(&index, "name ~ legacy"), HashSet::from([hg19.id]));
+        assert_eq!(search(&index, "grch38 metadata.type = run"), HashSet::from([grch38.id]));
+        assert!(search(&index, "grch38 hg19").is_empty());
+
+        let mut updated = grch38.clone();
+        updated.data = serde_json::json!({"params": {"dataset": "T2T"}});
+        index.insert(&updated);
+        assert!(search(&index, "params.dataset = GRCh38").is_empty());
+        index.remove(hg19.id);
+        assert_eq!(index.len(), 1);
+        assert!(search(&index, "dna").is_empty());
+    }
+}
@@ -0,0 +1,250 @@
+//! Versioned snapshots of the stored contexts
+//!
+//! Every write a [`ContextManager`](crate::context_manager::ContextManager)
+//! publishes creates a new version of the contexts. A reader pins a version
+//! with [`ContextManager::snapshot`](crate::context_manager::ContextManager::snapshot)
+//! and reads it for as long as it likes: writers keep publishing newer
+//! versions meanwhile, and the pinned one never changes underneath the
+//! reader. Versions share the contexts they have in common, and an old
+//! version is collected as soon as its last reader drops its snapshot.
+
+use std::collections::{BTreeMap, HashMap};
+use std::ops::Deref;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex, PoisonError, Weak};
+
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use crate::context_manager::Context;
+
+/// Contexts by ID, shared between the store and its published versions
+pub(crate) type ContextMap = HashMap<Uuid, Arc<Context>>;
+
+/// One published version of the contexts
+#[derive(Debug, Default)]
+pub(crate) struct ContextVersion {
+    /// Number of the version, incremented by every publish
+    pub(crate) number: u64,
+    /// The contexts as of this version
+    pub(crate) contexts: ContextMap,
+}
+
+impl Deref for ContextVersion {
+    type Target = ContextMap;
+
+    fn deref(&self) -> &ContextMap {
+        &self.contexts
+    }
+}
+
+/// A version pinned by at least one snapshot
+#[derive(Debug)]
+struct PinnedVersion {
+    /// Snapshots pinning the version
+    readers: usize,
+    /// The version, alive while a snapshot or the store holds it
+    version: Weak<ContextVersion>,
+}
+
+/// Versions pinned by readers
+#[derive(Debug, Default)]
+pub(crate) struct SnapshotRegistry {
+    /// Pinned versions by number
+    pinned: Mutex<BTreeMap<u64, PinnedVersion>>,
+    /// Snapshots taken
+    snapshots: AtomicU64,
+    /// Old versions freed once their last reader let go
+    collected: AtomicU64,
+}
+
+impl SnapshotRegistry {
+    /// Pins `version` for a new snapshot
+    pub(crate) fn pin(self: &Arc<Self>, version: Arc<ContextVersion>) -> ContextSnapshot {
+        let mut pinned = self.pinned.lock().unwrap_or_else(PoisonError::into_inner);
+        pinned
+            .entry(version.number)
+            .or_insert_with(|| PinnedVersion {
+                readers: 0,
+                version: Arc::downgrade(&version),
+            })
+            .readers += 1;
+        self.snapshots.fetch_add(1, Ordering::Relaxed);
+        ContextSnapshot {
+            version,
+            registry: Arc::clone(self),
+        }
+    }
+
+    /// Pins an older version again, if a reader still holds it
+    pub(crate) fn repin(self: &Arc<Self>, number: u64) -> Option<ContextSnapshot> {
+        let version = {
+            let pinned = self.pinned.lock().unwrap_or_else(PoisonError::into_inner);
+            pinned.get(&number)?.version.upgrade()?
+        };
+        Some(self.pin(version))
+    }
+
+    /// Pin counts, for [`SnapshotStats`]
+    pub(crate) fn stats(&self, current: u64) -> SnapshotStats {
+        let pinned = self.pinned.lock().unwrap_or_else(PoisonError::into_inner);
+        SnapshotStats {
+            current_version: current,
+            pinned_versions: pinned.len(),
+            retained_versions: pinned.range(..current).count(),
+            oldest_pinned_version: pinned.keys().next().copied(),
+            open_snapshots: pinned.values().map(|version| version.readers).sum(),
+            snapshots: self.snapshots.load(Ordering::Relaxed),
+            collected_versions: self.collected.load(Ordering::Relaxed),
+        }
+    }
+}
+
+/// How many versions readers hold on to
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SnapshotStats {
+    /// Number of the latest published version
+    pub current_version: u64,
+    /// Versions pinned by at least one snapshot, the current one included
+    pub pinned_versions: usize,
+    /// Superseded versions kept alive by snapshots
+    pub retained_versions: usize,
+    /// Oldest version a snapshot still pins
+    pub oldest_pinned_version: Option<u64>,
+    /// Snapshots not yet dropped
+    pub open_snapshots: usize,
+    /// Snapshots taken
+    pub snapshots: u64,
+    /// Superseded versions freed once their last snapshot was dropped
+    pub collected_versions: u64,
+}
+
+/// A consistent, read-only view of the contexts as of one version
+///
+/// Writes published after the snapshot was taken are not visible in it.
+/// Dropping the snapshot unpins its version.
+#[derive(Debug)]
+pub struct ContextSnapshot {
+    version: Arc<ContextVersion>,
+    registry: Arc<SnapshotRegistry>,
+}
+
+impl ContextSnapshot {
+    /// Number of the pinned version
+    #[must_use]
+    pub fn version(&self) -> u64 {
+        self.version.number
+    }
+
+    /// Number of contexts in the version
+    #[must_use]
+    pub fn len(&self) -> usize {
+        self.version.len()
+    }
+
+    /// Whether the version has no contexts
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.version.is_empty()
+    }
+
+    /// A context as of this version
+    #[must_use]
+    pub fn get(&self, id: Uuid) -> Option<&Context> {
+        self.version.get(&id).map(AsRef::as_ref)
+    }
+
+    /// Every context of the version, in no particular order
+    pub fn contexts(&self) -> impl Iterator<Item = &Context> {
+        self.version.values().map(AsRef::as_ref)
+    }
+
+    /// The children of a context as of this version
+    #[must_use]
+    pub fn children(&self, parent_id: Uuid) -> Vec<&Context> {
+        self.contexts()
+            .filter(|context| context.parent_id == Some(parent_id))
+            .collect()
+    }
+
+    /// The inheritance chain of a context as of this version, from the
+    /// context itself up to its root; empty if the context does not exist
+    #[must_use]
+    pub fn inheritance_chain(&self, id: Uuid) -> Vec<&Context> {
+        let mut chain: Vec<&Context> = self.get(id).into_iter().collect();
+        while let Some(parent) = chain
+            .last()
+            .and_then(|context| context.parent_id)
+            .and_then(|parent_id| self.get(parent_id))
+        {
+            if chain.iter().any(|context| context.id == parent.id) {
+                break;
+            }
+            chain.push(parent);
+        }
+        chain
+    }
+
+    /// The changes from this version to `newer`
+    ///
+    /// Contexts are compared by identity, so a context counts as changed
+    /// when it was written again, even with the same data.
+    #[must_use]
+    pub fn diff(&self, newer: &ContextSnapshot) -> ContextDiff {
+        let mut diff = ContextDiff::default();
+        for (id, context) in newer.version.iter() {
+            match self.version.get(id) {
+                None => diff.added.push(*id),
+                Some(old) if !Arc::ptr_eq(old, context) => diff.changed.push(*id),
+                Some(_) => {}
+            }
+        }
+        diff.removed = self
+            .version
+            .keys()
+            .filter(|id| !newer.version.contains_key(id))
+            .copied()
+            .collect();
+        diff.added.sort_unstable();
+        diff.changed.sort_unstable();
+        diff.removed.sort_unstable();
+        diff
+    }
+}
+
+impl Drop for ContextSnapshot {
+    fn drop(&mut self) {
+        let mut pinned = self.registry.pinned.lock().unwrap_or_else(PoisonError::into_inner);
+        let number = self.version.number;
+        let Some(entry) = pinned.get_mut(&number) else {
+            return;
+        };
+        entry.readers -= 1;
+        if entry.readers == 0 {
+            pinned.remove(&number);
+            // Nobody else, not even the store, holds a superseded version
+            if Arc::strong_count(&self.version) == 1 {
+                self.registry.collected.fetch_add(1, Ordering::Relaxed);
+            }
+        }
+    }
+}
+
+/// Contexts added, changed and removed between two versions, sorted by ID
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ContextDiff {
+    /// Contexts only in the newer version
+    pub added: Vec<Uuid>,
+    /// Contexts written between the versions
+    pub changed: Vec<Uuid>,
+    /// Contexts only in the older version
+    pub removed: Vec<Uuid>,
+}
+
+impl ContextDiff {
+    /// Whether the versions have the same contexts
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.added.is_empty() && self.changed.is_empty() && self.removed.is_empty()
+    }
+}
@@ -3,7 +3,7 @@ use crate::types::{MessageType, SecurityLevel};
 use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
 use serde_json::Map;
-use squirrel_core::error::{Result as CoreResult, SquirrelError as CoreError};
+use squirrel_core::error::{ErrorCode, Result as CoreResult, SquirrelError as CoreError};
 use thiserror::Error;
 use uuid;
 
@@ -86,6 +86,62 @@ impl std::error::Error for MCPError {
     }
 }
 
+impl squirrel_core::error::Coded for MCPError {
+    fn code(&self) -> ErrorCode {
+        match self {
+            MCPError::Context(ContextError::NotFound(_)) => ErrorCode::NotFound,
+            MCPError::Context(ContextError::ValidationError(_)) => ErrorCode::InvalidInput,
+            MCPError::Context(ContextError::SyncError(_)) => ErrorCode::Conflict,
+            MCPError::Protocol(ProtocolError::UnsupportedCapability(_)) => ErrorCode::Unsupported,
+            MCPError::Protocol(ProtocolError::MessageTimeout(_)) => ErrorCode::Timeout,
+            MCPError::Protocol(
+                ProtocolError::ProtocolNotInitialized | ProtocolError::ProtocolNotReady,
+            ) => ErrorCode::Unavailable,
+            MCPError::Protocol(
+                ProtocolError::ProtocolAlreadyInitialized | ProtocolError::HandlerAlreadyExists(_),
+            ) => ErrorCode::AlreadyExists,
+            MCPError::Protocol(ProtocolError::HandlerNotFound(_)) => ErrorCode::NotFound,
+            MCPError::Protocol(
+                ProtocolError::ConfigurationError(_)
+                | ProtocolError::StateSerialization(_)
+                | ProtocolError::StateDeserialization(_)
+                | ProtocolError::RecoveryFailed(_),
+            ) => ErrorCode::Internal,
+            MCPError::Protocol(_) => ErrorCode::InvalidInput,
+            MCPError::Security(
+                SecurityError::AuthenticationFailed(_)
+                | SecurityError::InvalidCredentials(_)
+                | SecurityError::TokenExpired
+                | SecurityError::InvalidToken(_),
+            ) => ErrorCode::Unauthenticated,
+            MCPError::Security(
+                SecurityError::EncryptionFailed(_)
+                | SecurityError::DecryptionFailed(_)
+                | SecurityError::InternalError(_)
+                | SecurityError::System(_)
+                | SecurityError::ErrorCreatingRole(_),
+            ) => ErrorCode::Internal,
+            MCPError::Security(
+                SecurityError::InvalidPermissionFormat(_)
+                | SecurityError::InvalidActionInPermission(_),
+            ) => ErrorCode::InvalidInput,
+            MCPError::Security(_) => ErrorCode::PermissionDenied,
+            MCPError::Connection(ConnectionError::Timeout(_)) => ErrorCode::Timeout,
+            MCPError::Connection(
+                ConnectionError::TooManyConnections | ConnectionError::LimitReached(_),
+            ) => ErrorCode::ResourceExhausted,
+            MCPError::Connection(_) | MCPError::NotInitialized(_) | MCPError::Network(_) => {
+                ErrorCode::Unavailable
+            }
+            MCPError::SerdeJson(_) => ErrorCode::InvalidInput,
+            MCPError::AlreadyInProgress(_) => ErrorCode::Conflict,
+            MCPError::Io(_) | MCPError::Storage(_) | MCPError::General(_) | MCPError::Monitoring(_) => {
+                ErrorCode::Internal
+            }
+        }
+    }
+}
+
 // Add From implementations for various error types
 impl From<std::io::Error> for MCPError {
     fn from(err: std::io::Error) -> Self {
@@ -168,6 +224,7 @@ impl From<CoreError> for MCPError {
             CoreError::Persistence(e) => MCPError::Storage(e.to_string()),
             CoreError::ProtocolVersion(msg) => MCPError::Protocol(ProtocolError::InvalidVersion(msg)),
             CoreError::Context(msg) => MCPError::Context(ContextError::SyncError(msg)),
+            CoreError::Coded(e) => MCPError::General(e.to_string()),
         }
     }
 }
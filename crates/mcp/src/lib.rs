@@ -8,6 +8,12 @@
 /// MCP context manager
 pub mod context_manager;
 
+/// Search over stored contexts
+pub mod context_search;
+
+/// Versioned snapshots of the stored contexts
+pub mod context_snapshot;
+
 /// Error types and error handling
 pub mod error;
 
@@ -35,10 +41,19 @@ pub mod types;
 /// Configuration module
 pub mod config;
 
+/// Assistant turning natural-language requests into tool calls
+pub mod ai;
+
+/// Fault injection for resilience testing
+#[cfg(feature = "chaos")]
+pub mod chaos;
+
 /// Re-export common types from the error module
 pub use error::{MCPError, Result};
 
-pub use context_manager::Context;
+pub use context_manager::{ContentionStats, Context, ContextStats, EvictionPolicy, ResolvedValue};
+pub use context_search::{ContextIndex, ContextQuery};
+pub use context_snapshot::{ContextDiff, ContextSnapshot, SnapshotStats};
 /// Re-export commonly used types
 pub use protocol::ProtocolConfig;
 pub use security::{Credentials, SecurityManager, Session};
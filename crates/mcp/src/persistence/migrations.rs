@@ -0,0 +1,181 @@
+//! Migrations of the persistence data directory
+//!
+//! The files of the data directory are changed by migrations registered in
+//! code, see [`builtin_migrations`]. Applied migrations are tracked in
+//! `migrations.json` in the data directory, which is rewritten atomically
+//! after each migration. Files have no transactions, so a migration that
+//! fails partway must leave the directory as it found it.
+
+use std::fs;
+use std::io::ErrorKind;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use async_trait::async_trait;
+use chrono::Utc;
+use squirrel_core::migration::{
+    AppliedMigration, Direction, MigrationError, MigrationFn, MigrationInfo, MigrationSet, MigrationStore,
+};
+
+use super::PersistenceConfig;
+
+/// File in the data directory listing the applied migrations
+pub const MIGRATIONS_FILE: &str = "migrations.json";
+
+/// Directories the persistence layer keeps its files in
+const STORAGE_DIRS: [&str; 3] = ["states", "changes", "records"];
+
+/// The migrations of the data directory, by version
+///
+/// # Panics
+///
+/// Panics if two built-in migrations share a version.
+#[must_use]
+pub fn builtin_migrations() -> MigrationSet<Path> {
+    let mut set = MigrationSet::new();
+    let remove_dirs: MigrationFn<Path> = Arc::new(|data_dir: &Path| {
+        // Check them all first, so a failure removes none
+        for dir in STORAGE_DIRS {
+            if fs::read_dir(data_dir.join(dir)).is_ok_and(|mut entries| entries.next().is_some()) {
+                return Err(format!("{dir}/ holds data; move it away first"));
+            }
+        }
+        for dir in STORAGE_DIRS {
+            match fs::remove_dir(data_dir.join(dir)) {
+                Err(e) if e.kind() != ErrorKind::NotFound => return Err(format!("Cannot remove {dir}/: {e}")),
+                _ => {}
+            }
+        }
+        Ok(())
+    });
+    set.register(
+        1,
+        "create storage directories",
+        "create states/, changes/ and records/ in the data directory",
+        |data_dir: &Path| {
+            for dir in STORAGE_DIRS {
+                fs::create_dir_all(data_dir.join(dir)).map_err(|e| format!("Cannot create {dir}/: {e}"))?;
+            }
+            Ok(())
+        },
+        Some(remove_dirs),
+    )
+    .expect("built-in migration versions are unique");
+    set
+}
+
+/// Migrations of a persistence data directory
+#[derive(Debug)]
+pub struct PersistenceMigrations {
+    data_dir: PathBuf,
+    migrations: MigrationSet<Path>,
+}
+
+impl PersistenceMigrations {
+    /// The built-in migrations of the data directory of `config`
+    #[must_use]
+    pub fn new(config: &PersistenceConfig) -> Self {
+        Self::with_migrations(config, builtin_migrations())
+    }
+
+    /// `migrations` of the data directory of `config`
+    #[must_use]
+    pub fn with_migrations(config: &PersistenceConfig, migrations: MigrationSet<Path>) -> Self {
+        Self {
+            data_dir: config.data_dir.clone(),
+            migrations,
+        }
+    }
+
+    /// Path of the file listing the applied migrations
+    #[must_use]
+    pub fn record_path(&self) -> PathBuf {
+        self.data_dir.join(MIGRATIONS_FILE)
+    }
+
+    /// Reads the applied migrations, none if the file does not exist
+    fn read(&self) -> Result<Vec<AppliedMigration>, MigrationError> {
+        let path = self.record_path();
+        match fs::read_to_string(&path) {
+            Ok(contents) => serde_json::from_str(&contents)
+                .map_err(|e| MigrationError::Store(format!("Invalid {}: {e}", path.display()))),
+            Err(e) if e.kind() == ErrorKind::NotFound => Ok(Vec::new()),
+            Err(e) => Err(MigrationError::Store(format!("Cannot read {}: {e}", path.display()))),
+        }
+    }
+
+    /// Replaces the applied migrations
+    fn write(&self, applied: &[AppliedMigration]) -> Result<(), MigrationError> {
+        let path = self.record_path();
+        let store_error = |e: &dyn std::fmt::Display| MigrationError::Store(format!("Cannot write {}: {e}", path.display()));
+        fs::create_dir_all(&self.data_dir).map_err(|e| store_error(&e))?;
+        let contents = serde_json::to_string_pretty(applied).map_err(|e| store_error(&e))?;
+        let temp_path = path.with_extension("tmp");
+        fs::write(&temp_path, contents).map_err(|e| store_error(&e))?;
+        fs::rename(&temp_path, &path).map_err(|e| store_error(&e))
+    }
+}
+
+#[async_trait]
+impl MigrationStore for PersistenceMigrations {
+    fn name(&self) -> &str {
+        "persistence"
+    }
+
+    fn migrations(&self) -> Vec<MigrationInfo> {
+        self.migrations.infos()
+    }
+
+    async fn applied(&self) -> Result<Vec<AppliedMigration>, MigrationError> {
+        self.read()
+    }
+
+    async fn run(&self, version: i64, direction: Direction) -> Result<(), MigrationError> {
+        let mut applied = self.read()?;
+        let info = self.migrations.run(version, direction, &self.data_dir)?;
+        applied.retain(|migration| migration.version != version);
+        if direction == Direction::Up {
+            applied.push(AppliedMigration {
+                version,
+                name: info.name.clone(),
+                checksum: info.checksum.clone(),
+                applied_at: Utc::now(),
+            });
+            applied.sort_by_key(|migration| migration.version);
+        }
+        self.write(&applied)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use squirrel_core::migration::{MigrationState, Migrator};
+
+    #[tokio::test]
+    async fn test_data_directory_migrations() {
+        let dir = tempfile::tempdir().unwrap();
+        let config = PersistenceConfig {
+            data_dir: dir.path().join("mcp"),
+            ..PersistenceConfig::default()
+        };
+        let store = PersistenceMigrations::new(&config);
+        let migrator = Migrator::new(&store);
+        assert_eq!(migrator.status().await.unwrap()[0].state, MigrationState::Pending);
+
+        migrator.up(None).await.unwrap();
+        assert!(config.data_dir.join("records").is_dir());
+        let reopened = PersistenceMigrations::new(&config);
+        assert_eq!(Migrator::new(&reopened).status().await.unwrap()[0].state, MigrationState::Applied);
+
+        // Reverting refuses to delete data
+        fs::write(config.data_dir.join("records/ai_audit.jsonl"), "{}\n").unwrap();
+        assert!(matches!(migrator.down(None).await, Err(MigrationError::Failed { version: 1, .. })));
+        assert_eq!(migrator.status().await.unwrap()[0].state, MigrationState::Applied);
+
+        fs::remove_file(config.data_dir.join("records/ai_audit.jsonl")).unwrap();
+        migrator.down(None).await.unwrap();
+        assert!(!config.data_dir.join("states").exists());
+        assert_eq!(migrator.status().await.unwrap()[0].state, MigrationState::Pending);
+    }
+}
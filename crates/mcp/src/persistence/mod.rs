@@ -17,6 +17,12 @@ use std::time::SystemTime;
 use tokio::sync::RwLock;
 use uuid::Uuid;
 
+pub mod migrations;
+pub mod scheduler;
+
+pub use migrations::{PersistenceMigrations, MIGRATIONS_FILE};
+pub use scheduler::{ForegroundGuard, SnapshotConfig, SnapshotPriority, SnapshotScheduler, SnapshotStats};
+
 /// Configuration settings for the persistence layer
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct PersistenceConfig {
@@ -133,10 +139,19 @@ impl MCPPersistence {
     /// - File system errors
     /// - Serialization errors
     pub fn save_state(&self, state: &PersistentState) -> Result<()> {
-        let state_path = self.get_state_path(&state.id);
+        let data = serde_json::to_vec_pretty(state)?;
+        self.write_state(&state.id, &data)
+    }
+
+    /// Writes a serialized state to the state file of `id`
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the file cannot be written or renamed.
+    pub(crate) fn write_state(&self, id: &str, data: &[u8]) -> Result<()> {
+        let state_path = self.get_state_path(id);
         let temp_path = state_path.with_extension("tmp");
 
-        let data = serde_json::to_string_pretty(state)?;
         fs::write(&temp_path, data)?;
 
         // Atomic rename
@@ -313,6 +328,125 @@ impl MCPPersistence {
         Ok(changes)
     }
 
+    /// Rebuilds the contexts recorded in storage
+    ///
+    /// Replays the context changes of every state file and change file in
+    /// timestamp order on top of the contexts stored in the state files, so
+    /// the result reflects the latest persisted version of each context.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if a state or change file cannot be read or parsed.
+    pub fn load_contexts(&self) -> Result<Vec<Context>> {
+        let mut contexts: std::collections::HashMap<Uuid, Context> = std::collections::HashMap::new();
+        let mut changes = self.load_changes()?;
+
+        if let Ok(entries) = fs::read_dir(&self.config.data_dir) {
+            for entry in entries.flatten() {
+                let path = entry.path();
+                if path.extension().is_some_and(|ext| ext == "json")
+                    && path.to_string_lossy().contains("state_")
+                {
+                    let state: PersistentState = serde_json::from_str(&fs::read_to_string(path)?)?;
+                    contexts.extend(state.contexts.into_iter().map(|context| (context.id, context)));
+                    changes.extend(state.changes);
+                }
+            }
+        }
+
+        changes.sort_by_key(|change| (change.timestamp, change.version));
+        for change in changes {
+            match change.operation {
+                crate::sync::StateOperation::Delete => {
+                    contexts.remove(&change.context_id);
+                }
+                _ => {
+                    if let Ok(context) = serde_json::from_value::<Context>(change.data) {
+                        contexts.insert(context.id, context);
+                    }
+                }
+            }
+        }
+
+        Ok(contexts.into_values().collect())
+    }
+
+    /// Appends a record to the stream `stream`
+    ///
+    /// Streams are append-only JSON Lines files under `records/` in the data
+    /// directory. Each record is written with a single write, so processes
+    /// sharing the directory do not interleave records.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the record cannot be serialized or written.
+    pub fn append_record<T: Serialize>(&self, stream: &str, record: &T) -> Result<()> {
+        let path = self.get_record_path(stream);
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        let mut line = serde_json::to_string(record)?;
+        line.push('\n');
+        let mut file = fs::OpenOptions::new().create(true).append(true).open(path)?;
+        file.write_all(line.as_bytes())?;
+        Ok(())
+    }
+
+    /// Loads the records of the stream `stream`, oldest first
+    ///
+    /// Lines that do not parse, such as one cut short by a crash, are skipped.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the stream exists but cannot be read.
+    pub fn load_records<T: serde::de::DeserializeOwned>(&self, stream: &str) -> Result<Vec<T>> {
+        let path = self.get_record_path(stream);
+        if !path.exists() {
+            return Ok(Vec::new());
+        }
+        let contents = fs::read_to_string(path)?;
+        Ok(contents
+            .lines()
+            .filter_map(|line| serde_json::from_str(line).ok())
+            .collect())
+    }
+
+    /// Keeps only the records of the stream `stream` that `keep` accepts,
+    /// returning how many were removed
+    ///
+    /// The stream is rewritten to a temporary file that then replaces it, so
+    /// readers see either the old or the new records.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the stream cannot be read or rewritten.
+    pub fn retain_records<T>(&self, stream: &str, keep: impl Fn(&T) -> bool) -> Result<usize>
+    where
+        T: Serialize + serde::de::DeserializeOwned,
+    {
+        let records = self.load_records::<T>(stream)?;
+        let before = records.len();
+        let kept: Vec<T> = records.into_iter().filter(|record| keep(record)).collect();
+        if kept.len() == before {
+            return Ok(0);
+        }
+        let path = self.get_record_path(stream);
+        let mut contents = String::new();
+        for record in &kept {
+            contents.push_str(&serde_json::to_string(record)?);
+            contents.push('\n');
+        }
+        let temp = path.with_extension("jsonl.tmp");
+        fs::write(&temp, contents)?;
+        fs::rename(&temp, &path)?;
+        Ok(before - kept.len())
+    }
+
+    /// Gets the path for a record stream
+    fn get_record_path(&self, stream: &str) -> PathBuf {
+        self.config.data_dir.join("records").join(format!("{stream}.jsonl"))
+    }
+
     /// Saves data with the specified key
     ///
     /// # Parameters
@@ -539,6 +673,57 @@ mod tests_persistence_impl {
         assert_eq!(changes.len(), 1);
         assert_eq!(changes[0].id, change_id);
     }
+
+    #[tokio::test]
+    async fn test_load_contexts_replays_changes() {
+        let temp_dir = tempdir().unwrap();
+        let mut persistence = MCPPersistence::new(PersistenceConfig {
+            data_dir: temp_dir.path().to_path_buf(),
+            ..PersistenceConfig::default()
+        });
+        assert!(persistence.init().is_ok());
+
+        let context = |name: &str, dataset: &str| Context {
+            id: Uuid::new_v4(),
+            name: name.to_string(),
+            data: serde_json::json!({"params": {"dataset": dataset}}),
+            metadata: None,
+            parent_id: None,
+            created_at: Utc::now(),
+            updated_at: Utc::now(),
+            expires_at: None,
+        };
+        let change = |context: &Context, operation, version| StateChange {
+            id: Uuid::new_v4(),
+            context_id: context.id,
+            operation,
+            data: serde_json::to_value(context).unwrap(),
+            timestamp: Utc::now(),
+            version,
+        };
+        let kept = context("kept", "GRCh38");
+        let deleted = context("deleted", "hg19");
+        let mut updated = kept.clone();
+        updated.data = serde_json::json!({"params": {"dataset": "T2T"}});
+
+        let state = PersistentState {
+            contexts: vec![],
+            changes: vec![
+                change(&kept, crate::sync::StateOperation::Create, 1),
+                change(&deleted, crate::sync::StateOperation::Create, 2),
+            ],
+            last_version: 2,
+            last_sync: Utc::now(),
+            id: Uuid::new_v4().to_string(),
+        };
+        persistence.save_state(&state).unwrap();
+        persistence.save_change(&change(&updated, crate::sync::StateOperation::Update, 3)).unwrap();
+        persistence.save_change(&change(&deleted, crate::sync::StateOperation::Delete, 4)).unwrap();
+
+        let contexts = persistence.load_contexts().unwrap();
+        assert_eq!(contexts.len(), 1);
+        assert_eq!(contexts[0].data["params"]["dataset"], "T2T");
+    }
 }
 
 /// Session data for persistence
@@ -0,0 +1,588 @@
+//! Background scheduling of state snapshots
+//!
+//! Writing a snapshot of the persisted state means serializing it, writing a
+//! file and renaming it, which callers such as [`MCPSync::sync`] should not
+//! wait for. A [`SnapshotScheduler`] takes the snapshots of its callers and
+//! writes them from a background task:
+//!
+//! - Snapshots scheduled within the coalescing window of the first one are
+//!   merged into one and written once.
+//! - Writes are limited to a number of bytes per second, with a burst
+//!   allowance. A snapshot over the budget waits for it to refill.
+//! - While foreground work holds a [`ForegroundGuard`], snapshots wait for it
+//!   to finish, up to a maximum deferral.
+//!
+//! Urgent snapshots, and [`SnapshotScheduler::flush`], skip the window, the
+//! deferral and the rate limit; the bytes they write still count against
+//! the budget of later snapshots.
+//!
+//! [`MCPSync::sync`]: crate::sync::MCPSync::sync
+
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex, OnceLock};
+use std::time::{Duration, Instant};
+
+use serde::{Deserialize, Serialize};
+use tokio::runtime::Handle;
+use tokio::sync::{oneshot, Notify};
+use tokio::task::JoinHandle;
+use tracing::{debug, error};
+
+use super::{MCPPersistence, PersistentState};
+use crate::error::{MCPError, Result};
+
+/// How snapshots are coalesced, rate limited and deferred
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SnapshotConfig {
+    /// How long after the first of a burst of snapshots they are merged and written
+    pub coalesce_window_ms: u64,
+    /// Bytes per second snapshots may write, or `None` for no limit
+    pub max_bytes_per_sec: Option<u64>,
+    /// Bytes that may be written at once before the rate limit applies
+    pub burst_bytes: u64,
+    /// Longest a snapshot waits for foreground work to finish
+    pub max_deferral_ms: u64,
+}
+
+impl Default for SnapshotConfig {
+    fn default() -> Self {
+        Self {
+            coalesce_window_ms: 250,
+            max_bytes_per_sec: Some(4 * 1024 * 1024), // 4MB/s
+            burst_bytes: 1024 * 1024,                 // 1MB
+            max_deferral_ms: 2000,
+        }
+    }
+}
+
+/// How soon a snapshot is written
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SnapshotPriority {
+    /// Coalesced, rate limited and deferred while foreground work runs
+    Background,
+    /// Written as soon as the writer is free
+    Urgent,
+}
+
+/// Counters of a snapshot scheduler
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SnapshotStats {
+    /// Snapshots scheduled
+    pub scheduled: u64,
+    /// Snapshots merged into one scheduled earlier
+    pub coalesced: u64,
+    /// Snapshots written
+    pub written: u64,
+    /// Snapshots that failed to be written
+    pub failed: u64,
+    /// Bytes written
+    pub bytes_written: u64,
+    /// Milliseconds spent waiting for the rate limit
+    pub throttled_ms: u64,
+    /// Milliseconds spent waiting for foreground work
+    pub deferred_ms: u64,
+    /// Error of the last failed write
+    pub last_error: Option<String>,
+}
+
+/// Writes snapshots of the persisted state in the background
+///
+/// The writer task is started by the first snapshot scheduled within a Tokio
+/// runtime; outside one, snapshots are written before [`schedule`] returns.
+/// Dropping the scheduler writes the pending snapshot, if any, and stops the
+/// writer.
+///
+/// [`schedule`]: SnapshotScheduler::schedule
+#[derive(Debug)]
+pub struct SnapshotScheduler {
+    /// State shared with the writer task
+    shared: Arc<Shared>,
+    /// The writer task, once started
+    worker: OnceLock<JoinHandle<()>>,
+}
+
+/// Marks foreground work in progress, deferring background snapshots until
+/// it is dropped
+#[derive(Debug)]
+#[must_use = "snapshots are only deferred while the guard is held"]
+pub struct ForegroundGuard {
+    /// State shared with the writer task
+    shared: Arc<Shared>,
+}
+
+/// A snapshot waiting to be written, merged from every snapshot scheduled
+/// since the last write
+#[derive(Debug)]
+struct Pending {
+    /// The merged state
+    state: PersistentState,
+    /// Highest priority of the merged snapshots
+    priority: SnapshotPriority,
+    /// When the first of the merged snapshots was scheduled
+    since: Instant,
+    /// Callers of [`SnapshotScheduler::flush`] waiting for the write
+    waiters: Vec<oneshot::Sender<std::result::Result<(), String>>>,
+}
+
+/// State shared between a scheduler, its guards and its writer task
+#[derive(Debug)]
+struct Shared {
+    /// Where snapshots are written
+    persistence: Arc<MCPPersistence>,
+    /// Coalescing, rate limit and deferral settings
+    config: SnapshotConfig,
+    /// The snapshot to write next
+    pending: Mutex<Option<Pending>>,
+    /// Signalled when a snapshot is scheduled or the scheduler is dropped
+    scheduled: Notify,
+    /// Number of foreground guards held
+    foreground: AtomicUsize,
+    /// Signalled when the last foreground guard is dropped
+    foreground_done: Notify,
+    /// Whether the scheduler was dropped
+    closed: AtomicBool,
+    /// Counters
+    stats: Mutex<SnapshotStats>,
+}
+
+impl SnapshotScheduler {
+    /// Creates a scheduler writing snapshots to `persistence`
+    #[must_use]
+    pub fn new(persistence: Arc<MCPPersistence>, config: SnapshotConfig) -> Self {
+        Self {
+            shared: Arc::new(Shared {
+                persistence,
+                config,
+                pending: Mutex::new(None),
+                scheduled: Notify::new(),
+                foreground: AtomicUsize::new(0),
+                foreground_done: Notify::new(),
+                closed: AtomicBool::new(false),
+                stats: Mutex::new(SnapshotStats::default()),
+            }),
+            worker: OnceLock::new(),
+        }
+    }
+
+    /// The scheduler's settings
+    #[must_use]
+    pub fn config(&self) -> &SnapshotConfig {
+        &self.shared.config
+    }
+
+    /// Schedules a snapshot of `state`
+    ///
+    /// A snapshot is merged into the pending one, if any: their changes are
+    /// kept in order, a context is replaced by its later version and the
+    /// first snapshot's ID is kept.
+    pub fn schedule(&self, state: PersistentState, priority: SnapshotPriority) {
+        {
+            let mut pending = lock(&self.shared.pending);
+            let mut counters = lock(&self.shared.stats);
+            counters.scheduled += 1;
+            match pending.as_mut() {
+                Some(pending) => {
+                    merge(&mut pending.state, state);
+                    pending.priority = pending.priority.max(priority);
+                    counters.coalesced += 1;
+                }
+                None => {
+                    *pending = Some(Pending {
+                        state,
+                        priority,
+                        since: Instant::now(),
+                        waiters: Vec::new(),
+                    });
+                }
+            }
+        }
+
+        if self.start() {
+            self.shared.scheduled.notify_one();
+        } else if let Some(pending) = lock(&self.shared.pending).take() {
+            self.shared.write(pending);
+        }
+    }
+
+    /// Writes the pending snapshot now and waits for it
+    ///
+    /// # Errors
+    ///
+    /// Returns `MCPError::Storage` if the snapshot cannot be written.
+    pub async fn flush(&self) -> Result<()> {
+        let written = {
+            let mut pending = lock(&self.shared.pending);
+            let Some(pending) = pending.as_mut() else {
+                return Ok(());
+            };
+            let (sender, receiver) = oneshot::channel();
+            pending.priority = SnapshotPriority::Urgent;
+            pending.waiters.push(sender);
+            receiver
+        };
+
+        if self.start() {
+            self.shared.scheduled.notify_one();
+        } else if let Some(pending) = lock(&self.shared.pending).take() {
+            self.shared.write(pending);
+        }
+        match written.await {
+            Ok(result) => result.map_err(MCPError::Storage),
+            Err(_) => Err(MCPError::Storage("Snapshot writer stopped".to_string())),
+        }
+    }
+
+    /// Marks foreground work in progress until the guard is dropped
+    pub fn foreground(&self) -> ForegroundGuard {
+        self.shared.foreground.fetch_add(1, Ordering::SeqCst);
+        ForegroundGuard {
+            shared: Arc::clone(&self.shared),
+        }
+    }
+
+    /// The scheduler's counters
+    #[must_use]
+    pub fn stats(&self) -> SnapshotStats {
+        lock(&self.shared.stats).clone()
+    }
+
+    /// Starts the writer task if needed, returning whether it runs
+    fn start(&self) -> bool {
+        if self.worker.get().is_some() {
+            return true;
+        }
+        let Ok(handle) = Handle::try_current() else {
+            return false;
+        };
+        self.worker
+            .get_or_init(|| handle.spawn(run(Arc::clone(&self.shared))));
+        true
+    }
+}
+
+impl Drop for SnapshotScheduler {
+    fn drop(&mut self) {
+        self.shared.closed.store(true, Ordering::SeqCst);
+        self.shared.scheduled.notify_one();
+    }
+}
+
+impl Drop for ForegroundGuard {
+    fn drop(&mut self) {
+        if self.shared.foreground.fetch_sub(1, Ordering::SeqCst) == 1 {
+            self.shared.foreground_done.notify_one();
+        }
+    }
+}
+
+impl Shared {
+    /// When the pending snapshot was first scheduled, if there is one
+    fn pending_since(&self) -> Option<Instant> {
+        lock(&self.pending).as_ref().map(|pending| pending.since)
+    }
+
+    /// Whether the pending snapshot must be written without waiting
+    fn urgent(&self) -> bool {
+        self.closed.load(Ordering::SeqCst)
+            || lock(&self.pending)
+                .as_ref()
+                .is_some_and(|pending| pending.priority == SnapshotPriority::Urgent)
+    }
+
+    /// Writes a snapshot, recording the outcome and telling its waiters
+    fn write(&self, pending: Pending) -> Option<usize> {
+        let result = serde_json::to_vec_pretty(&pending.state)
+            .map_err(MCPError::from)
+            .and_then(|data| {
+                self.persistence.write_state(&pending.state.id, &data)?;
+                Ok(data.len())
+            });
+
+        let mut stats = lock(&self.stats);
+        let outcome = match &result {
+            Ok(bytes) => {
+                stats.written += 1;
+                stats.bytes_written += *bytes as u64;
+                debug!(id = %pending.state.id, bytes, "Snapshot written");
+                Ok(())
+            }
+            Err(e) => {
+                stats.failed += 1;
+                stats.last_error = Some(e.to_string());
+                error!(id = %pending.state.id, "Failed to write snapshot: {}", e);
+                Err(e.to_string())
+            }
+        };
+        drop(stats);
+        for waiter in pending.waiters {
+            let _ = waiter.send(outcome.clone());
+        }
+        result.ok()
+    }
+}
+
+/// The writer task: waits for snapshots, then coalesces, defers and rate
+/// limits them before writing
+async fn run(shared: Arc<Shared>) {
+    let config = &shared.config;
+    let mut budget = TokenBucket::new(config.max_bytes_per_sec, config.burst_bytes, Instant::now());
+
+    loop {
+        let Some(since) = shared.pending_since() else {
+            if shared.closed.load(Ordering::SeqCst) {
+                return;
+            }
+            shared.scheduled.notified().await;
+            continue;
+        };
+
+        // Collect the rest of the burst
+        let due = since + Duration::from_millis(config.coalesce_window_ms);
+        while !shared.urgent() && Instant::now() < due {
+            let _ = tokio::time::timeout_at(due.into(), shared.scheduled.notified()).await;
+        }
+
+        // Let foreground work finish first
+        let deferred = Instant::now();
+        let deadline = deferred + Duration::from_millis(config.max_deferral_ms);
+        while shared.foreground.load(Ordering::SeqCst) > 0 && !shared.urgent() && Instant::now() < deadline {
+            let _ = tokio::time::timeout_at(deadline.into(), async {
+                tokio::select! {
+                    () = shared.foreground_done.notified() => {}
+                    () = shared.scheduled.notified() => {}
+                }
+            })
+            .await;
+        }
+        lock(&shared.stats).deferred_ms += elapsed_ms(deferred);
+
+        let urgent = shared.urgent();
+        let Some(pending) = lock(&shared.pending).take() else {
+            continue;
+        };
+        let writer = Arc::clone(&shared);
+        let written = tokio::task::spawn_blocking(move || writer.write(pending))
+            .await
+            .unwrap_or_else(|e| {
+                error!("Snapshot writer panicked: {}", e);
+                None
+            });
+
+        // Wait out the bytes written before the next snapshot; an urgent
+        // snapshot scheduled meanwhile cuts the wait short
+        let wait = budget.take(written.unwrap_or(0) as u64, Instant::now());
+        if urgent || wait.is_zero() {
+            continue;
+        }
+        let throttled = Instant::now();
+        let until = throttled + wait;
+        while !shared.urgent() && Instant::now() < until {
+            let _ = tokio::time::timeout_at(until.into(), shared.scheduled.notified()).await;
+        }
+        lock(&shared.stats).throttled_ms += elapsed_ms(throttled);
+    }
+}
+
+/// A byte budget refilled at a fixed rate
+///
+/// Taking more bytes than are left puts the bucket in debt, which is paid
+/// back before it allows more writes.
+#[derive(Debug)]
+struct TokenBucket {
+    /// Bytes added per second, or `None` for no limit
+    rate: Option<f64>,
+    /// Most bytes the bucket holds
+    capacity: f64,
+    /// Bytes left; negative when in debt
+    tokens: f64,
+    /// When the bytes left were last refilled
+    refilled: Instant,
+}
+
+impl TokenBucket {
+    /// A full bucket of `burst` bytes refilled at `rate` bytes per second
+    #[allow(clippy::cast_precision_loss)]
+    fn new(rate: Option<u64>, burst: u64, now: Instant) -> Self {
+        Self {
+            rate: rate.filter(|rate| *rate > 0).map(|rate| rate as f64),
+            capacity: burst as f64,
+            tokens: burst as f64,
+            refilled: now,
+        }
+    }
+
+    /// Takes `bytes` from the bucket, returning how long to wait until it is
+    /// out of debt
+    #[allow(clippy::cast_precision_loss)]
+    fn take(&mut self, bytes: u64, now: Instant) -> Duration {
+        let Some(rate) = self.rate else {
+            return Duration::ZERO;
+        };
+        let elapsed = now.saturating_duration_since(self.refilled).as_secs_f64();
+        self.tokens = (self.tokens + elapsed * rate).min(self.capacity) - bytes as f64;
+        self.refilled = now;
+        if self.tokens >= 0.0 {
+            Duration::ZERO
+        } else {
+            Duration::from_secs_f64(-self.tokens / rate)
+        }
+    }
+}
+
+/// Merges a later snapshot into an earlier one
+fn merge(into: &mut PersistentState, later: PersistentState) {
+    for context in later.contexts {
+        match into.contexts.iter_mut().find(|existing| existing.id == context.id) {
+            Some(existing) => *existing = context,
+            None => into.contexts.push(context),
+        }
+    }
+    into.changes.extend(later.changes);
+    into.last_version = into.last_version.max(later.last_version);
+    into.last_sync = into.last_sync.max(later.last_sync);
+}
+
+/// Locks a mutex, recovering the data from a panicked holder
+fn lock<T>(mutex: &Mutex<T>) -> std::sync::MutexGuard<'_, T> {
+    mutex.lock().unwrap_or_else(std::sync::PoisonError::into_inner)
+}
+
+/// Milliseconds since `start`
+fn elapsed_ms(start: Instant) -> u64 {
+    u64::try_from(start.elapsed().as_millis()).unwrap_or(u64::MAX)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::context_manager::Context;
+    use crate::persistence::PersistenceConfig;
+    use chrono::Utc;
+    use uuid::Uuid;
+
+    fn state(version: u64, context: &Context) -> PersistentState {
+        PersistentState {
+            contexts: vec![context.clone()],
+            changes: vec![],
+            last_version: version,
+            last_sync: Utc::now(),
+            id: Uuid::new_v4().to_string(),
+        }
+    }
+
+    fn context(name: &str) -> Context {
+        Context {
+            id: Uuid::new_v4(),
+            name: name.to_string(),
+            data: serde_json::json!({}),
+            metadata: None,
+            parent_id: None,
+            created_at: Utc::now(),
+            updated_at: Utc::now(),
+            expires_at: None,
+        }
+    }
+
+    fn persistence(dir: &tempfile::TempDir) -> Arc<MCPPersistence> {
+        let mut persistence = MCPPersistence::new(PersistenceConfig {
+            data_dir: dir.path().to_path_buf(),
+            ..PersistenceConfig::default()
+        });
+        persistence.init().unwrap();
+        Arc::new(persistence)
+    }
+
+    fn state_files(dir: &tempfile::TempDir) -> usize {
+        std::fs::read_dir(dir.path())
+            .unwrap()
+            .flatten()
+            .filter(|entry| entry.file_name().to_string_lossy().starts_with("state_"))
+            .count()
+    }
+
+    #[tokio::test]
+    async fn test_bursts_are_coalesced_into_one_snapshot() {
+        let dir = tempfile::tempdir().unwrap();
+        let persistence = persistence(&dir);
+        let scheduler = SnapshotScheduler::new(
+            Arc::clone(&persistence),
+            SnapshotConfig {
+                coalesce_window_ms: 50,
+                ..SnapshotConfig::default()
+            },
+        );
+
+        let first = context("first");
+        let mut renamed = first.clone();
+        renamed.name = "renamed".to_string();
+        scheduler.schedule(state(1, &first), SnapshotPriority::Background);
+        scheduler.schedule(state(2, &context("second")), SnapshotPriority::Background);
+        scheduler.schedule(state(3, &renamed), SnapshotPriority::Background);
+        assert_eq!(state_files(&dir), 0, "snapshots are written in the background");
+
+        tokio::time::sleep(Duration::from_millis(300)).await;
+        assert_eq!(state_files(&dir), 1);
+        let stats = scheduler.stats();
+        assert_eq!((stats.scheduled, stats.coalesced, stats.written), (3, 2, 1));
+
+        let written = persistence.load_state().unwrap().unwrap();
+        assert_eq!(written.last_version, 3);
+        assert_eq!(written.contexts.len(), 2);
+        assert_eq!(written.contexts[0].name, "renamed");
+    }
+
+    #[tokio::test]
+    async fn test_flush_skips_the_window_and_foreground_work() {
+        let dir = tempfile::tempdir().unwrap();
+        let scheduler = SnapshotScheduler::new(
+            persistence(&dir),
+            SnapshotConfig {
+                coalesce_window_ms: 60_000,
+                max_deferral_ms: 60_000,
+                ..SnapshotConfig::default()
+            },
+        );
+
+        let guard = scheduler.foreground();
+        scheduler.schedule(state(1, &context("held")), SnapshotPriority::Background);
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        assert_eq!(state_files(&dir), 0);
+
+        tokio::time::timeout(Duration::from_secs(5), scheduler.flush())
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(state_files(&dir), 1);
+        drop(guard);
+
+        // Nothing is pending
+        scheduler.flush().await.unwrap();
+        assert_eq!(scheduler.stats().written, 1);
+    }
+
+    #[test]
+    fn test_snapshots_are_written_in_place_without_a_runtime() {
+        let dir = tempfile::tempdir().unwrap();
+        let scheduler = SnapshotScheduler::new(persistence(&dir), SnapshotConfig::default());
+        scheduler.schedule(state(1, &context("direct")), SnapshotPriority::Background);
+        assert_eq!(state_files(&dir), 1);
+        assert_eq!(scheduler.stats().written, 1);
+    }
+
+    #[test]
+    fn test_token_bucket_limits_bytes_per_second() {
+        let start = Instant::now();
+        let mut bucket = TokenBucket::new(Some(1000), 500, start);
+        assert_eq!(bucket.take(400, start), Duration::ZERO);
+        assert_eq!(bucket.take(600, start), Duration::from_millis(500));
+        // Refilled by one second, then paid back the debt
+        assert_eq!(bucket.take(0, start + Duration::from_secs(1)), Duration::ZERO);
+        // Never holds more than the burst
+        assert_eq!(bucket.take(1000, start + Duration::from_secs(10)), Duration::from_millis(500));
+
+        let mut unlimited = TokenBucket::new(None, 0, start);
+        assert_eq!(unlimited.take(u64::MAX, start), Duration::ZERO);
+    }
+}
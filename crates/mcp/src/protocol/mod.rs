@@ -31,6 +31,9 @@ pub use adapter::{
 /// Implementation module for protocol core functionality
 mod impl_protocol;
 pub use impl_protocol::MCPProtocolImpl;
+/// Traffic recording and replay module
+pub mod recording;
+pub use recording::{Recording, RecordingConfig, RecordingProtocol, ReplayReport, TrafficRecorder};
 
 /// Configuration for the MCP protocol
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
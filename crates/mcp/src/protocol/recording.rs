@@ -0,0 +1,633 @@
+//! Protocol traffic recording and replay
+//!
+//! [`RecordingProtocol`] wraps the protocol of a connection and writes every
+//! message it handles, and the response or error it produced, to a recording
+//! file through a [`TrafficRecorder`]. Values under secret-looking keys
+//! (passwords, tokens, API keys, ...) are replaced with [`REDACTED`] before
+//! anything is written.
+//!
+//! A recording is a JSON Lines file: a [`RecordingHeader`] followed by one
+//! [`RecordedEntry`] per line. [`Recording::replay`] feeds the recorded
+//! messages back through a protocol in order and reports every message whose
+//! response differs from the recorded one, for debugging and for regression
+//! tests of tool integrations.
+
+use std::fmt;
+use std::fs::File;
+use std::io::{BufRead, BufReader, BufWriter, Write};
+use std::path::Path;
+use std::sync::Mutex;
+use std::time::Instant;
+
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::error::{MCPError, ProtocolError, Result};
+use crate::protocol::{MCPProtocol, ProtocolResult, RoutingResult, ValidationResult};
+use crate::types::{MCPMessage, MCPResponse, ProtocolState, ResponseStatus};
+
+/// Value written in place of redacted secrets
+pub const REDACTED: &str = "[REDACTED]";
+
+/// Format name in recording headers
+const RECORDING_FORMAT: &str = "squirrel-mcp-recording";
+
+/// Version of the recording format
+const RECORDING_VERSION: u32 = 1;
+
+/// Recording settings
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct RecordingConfig {
+    /// Payload keys whose values are redacted; a key matches if it contains
+    /// one of these, ignoring case
+    pub redact_keys: Vec<String>,
+    /// Label of the recorded connection, written to the header
+    pub connection: Option<String>,
+}
+
+impl Default for RecordingConfig {
+    fn default() -> Self {
+        Self {
+            redact_keys: [
+                "password",
+                "passwd",
+                "secret",
+                "token",
+                "api_key",
+                "apikey",
+                "authorization",
+                "credential",
+                "private_key",
+                "cookie",
+            ]
+            .iter()
+            .map(ToString::to_string)
+            .collect(),
+            connection: None,
+        }
+    }
+}
+
+/// First line of a recording
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RecordingHeader {
+    /// Always `squirrel-mcp-recording`
+    pub format: String,
+    /// Recording format version
+    pub version: u32,
+    /// Label of the recorded connection
+    pub connection: Option<String>,
+    /// When recording started
+    pub started_at: DateTime<Utc>,
+}
+
+/// Something that happened on a recorded connection
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum RecordedEvent {
+    /// A message was received
+    Request {
+        /// The message
+        message: MCPMessage,
+    },
+    /// A message was answered
+    Response {
+        /// The response
+        response: MCPResponse,
+    },
+    /// Handling a message failed
+    Error {
+        /// ID of the message that failed
+        message_id: String,
+        /// The error
+        error: String,
+    },
+}
+
+/// One line of a recording
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RecordedEntry {
+    /// Position in the recording, starting at 0
+    pub seq: u64,
+    /// Milliseconds since recording started
+    pub elapsed_ms: u64,
+    /// What happened
+    #[serde(flatten)]
+    pub event: RecordedEvent,
+}
+
+/// Output file of a recorder and the next sequence number
+#[derive(Debug)]
+struct RecorderOutput {
+    writer: BufWriter<File>,
+    next_seq: u64,
+}
+
+/// Writes protocol traffic to a recording file
+#[derive(Debug)]
+pub struct TrafficRecorder {
+    config: RecordingConfig,
+    started: Instant,
+    output: Mutex<RecorderOutput>,
+}
+
+impl TrafficRecorder {
+    /// Creates a recording at `path`, replacing any existing file
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the file cannot be created or written.
+    pub fn create(path: impl AsRef<Path>, config: RecordingConfig) -> Result<Self> {
+        let mut writer = BufWriter::new(File::create(path)?);
+        let header = RecordingHeader {
+            format: RECORDING_FORMAT.to_string(),
+            version: RECORDING_VERSION,
+            connection: config.connection.clone(),
+            started_at: Utc::now(),
+        };
+        serde_json::to_writer(&mut writer, &header)?;
+        writer.write_all(b"\n")?;
+        writer.flush()?;
+        Ok(Self {
+            config,
+            started: Instant::now(),
+            output: Mutex::new(RecorderOutput {
+                writer,
+                next_seq: 0,
+            }),
+        })
+    }
+
+    /// Records a received message
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the recording cannot be written.
+    pub fn record_request(&self, message: &MCPMessage) -> Result<()> {
+        self.record(RecordedEvent::Request {
+            message: message.clone(),
+        })
+    }
+
+    /// Records a response
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the recording cannot be written.
+    pub fn record_response(&self, response: &MCPResponse) -> Result<()> {
+        self.record(RecordedEvent::Response {
+            response: response.clone(),
+        })
+    }
+
+    /// Records that handling a message failed
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the recording cannot be written.
+    pub fn record_error(&self, message_id: &str, error: &MCPError) -> Result<()> {
+        self.record(RecordedEvent::Error {
+            message_id: message_id.to_string(),
+            error: error.to_string(),
+        })
+    }
+
+    /// Redacts an event and appends it to the recording
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the recording cannot be written.
+    pub fn record(&self, mut event: RecordedEvent) -> Result<()> {
+        redact_event(&mut event, &self.config.redact_keys);
+        let elapsed_ms = u64::try_from(self.started.elapsed().as_millis()).unwrap_or(u64::MAX);
+
+        let mut output = self
+            .output
+            .lock()
+            .map_err(|_| MCPError::General("Recording lock poisoned".to_string()))?;
+        let entry = RecordedEntry {
+            seq: output.next_seq,
+            elapsed_ms,
+            event,
+        };
+        serde_json::to_writer(&mut output.writer, &entry)?;
+        output.writer.write_all(b"\n")?;
+        output.writer.flush()?;
+        output.next_seq += 1;
+        Ok(())
+    }
+}
+
+/// Replaces the values of secret keys in `value` with [`REDACTED`]
+pub fn redact(value: &mut Value, keys: &[String]) {
+    match value {
+        Value::Object(map) => {
+            for (key, value) in map.iter_mut() {
+                let key = key.to_lowercase();
+                if keys
+                    .iter()
+                    .any(|secret| key.contains(&secret.to_lowercase()))
+                {
+                    *value = Value::String(REDACTED.to_string());
+                } else {
+                    redact(value, keys);
+                }
+            }
+        }
+        Value::Array(items) => {
+            for item in items {
+                redact(item, keys);
+            }
+        }
+        _ => {}
+    }
+}
+
+/// Redacts the payload of an event; response payloads are redacted if they
+/// are JSON
+fn redact_event(event: &mut RecordedEvent, keys: &[String]) {
+    match event {
+        RecordedEvent::Request { message } => redact(&mut message.payload, keys),
+        RecordedEvent::Response { response } => {
+            if let Ok(mut payload) = serde_json::from_slice::<Value>(&response.payload) {
+                redact(&mut payload, keys);
+                if let Ok(bytes) = serde_json::to_vec(&payload) {
+                    response.payload = bytes;
+                }
+            }
+        }
+        RecordedEvent::Error { .. } => {}
+    }
+}
+
+/// Protocol that records the traffic of the protocol it wraps
+pub struct RecordingProtocol<P> {
+    inner: P,
+    recorder: TrafficRecorder,
+}
+
+impl<P: MCPProtocol> RecordingProtocol<P> {
+    /// Wraps `inner`, recording its traffic with `recorder`
+    pub fn new(inner: P, recorder: TrafficRecorder) -> Self {
+        Self { inner, recorder }
+    }
+
+    /// The wrapped protocol
+    pub fn inner(&self) -> &P {
+        &self.inner
+    }
+
+    /// Logs a failure to record; recording never interrupts the traffic
+    fn log_failure(result: Result<()>) {
+        if let Err(e) = result {
+            tracing::warn!("Failed to record MCP traffic: {}", e);
+        }
+    }
+}
+
+#[async_trait]
+impl<P: MCPProtocol> MCPProtocol for RecordingProtocol<P> {
+    async fn handle_message(&self, msg: MCPMessage) -> ProtocolResult {
+        let message_id = msg.id.0.clone();
+        Self::log_failure(self.recorder.record_request(&msg));
+        let result = self.inner.handle_message(msg).await;
+        match &result {
+            Ok(response) => Self::log_failure(self.recorder.record_response(response)),
+            Err(e) => Self::log_failure(self.recorder.record_error(&message_id, e)),
+        }
+        result
+    }
+
+    async fn validate_message(&self, msg: &MCPMessage) -> ValidationResult {
+        self.inner.validate_message(msg).await
+    }
+
+    async fn route_message(&self, msg: &MCPMessage) -> RoutingResult {
+        self.inner.route_message(msg).await
+    }
+
+    async fn set_state(&self, new_state: ProtocolState) -> Result<()> {
+        self.inner.set_state(new_state).await
+    }
+
+    async fn get_state(&self) -> Result<ProtocolState> {
+        self.inner.get_state().await
+    }
+
+    fn get_version(&self) -> String {
+        self.inner.get_version()
+    }
+}
+
+/// A loaded recording
+#[derive(Debug, Clone)]
+pub struct Recording {
+    /// The header
+    pub header: RecordingHeader,
+    /// The entries in recording order
+    pub entries: Vec<RecordedEntry>,
+}
+
+/// What handling a message produced
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ReplayOutcome {
+    /// A response
+    Response {
+        /// Response status
+        status: ResponseStatus,
+        /// Response payload
+        payload: Vec<u8>,
+        /// Error message of the response
+        error_message: Option<String>,
+    },
+    /// An error
+    Error(String),
+    /// Nothing was recorded for the message
+    Missing,
+}
+
+impl ReplayOutcome {
+    fn from_response(response: &MCPResponse) -> Self {
+        Self::Response {
+            status: response.status,
+            payload: response.payload.clone(),
+            error_message: response.error_message.clone(),
+        }
+    }
+}
+
+impl fmt::Display for ReplayOutcome {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Response {
+                status,
+                payload,
+                error_message,
+            } => {
+                write!(
+                    f,
+                    "{status:?} response {}",
+                    String::from_utf8_lossy(payload)
+                )?;
+                if let Some(message) = error_message {
+                    write!(f, " ({message})")?;
+                }
+                Ok(())
+            }
+            Self::Error(error) => write!(f, "error: {error}"),
+            Self::Missing => write!(f, "nothing recorded"),
+        }
+    }
+}
+
+/// A replayed message whose outcome differs from the recording
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ReplayMismatch {
+    /// Sequence number of the message in the recording
+    pub seq: u64,
+    /// ID of the message
+    pub message_id: String,
+    /// The recorded outcome
+    pub expected: ReplayOutcome,
+    /// The outcome of the replay
+    pub actual: ReplayOutcome,
+}
+
+/// Result of replaying a recording
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ReplayReport {
+    /// Number of replayed messages
+    pub replayed: usize,
+    /// Number of messages whose outcome matched the recording
+    pub matched: usize,
+    /// Messages whose outcome differed
+    pub mismatches: Vec<ReplayMismatch>,
+}
+
+impl ReplayReport {
+    /// Whether every replayed message produced the recorded outcome
+    #[must_use]
+    pub fn is_match(&self) -> bool {
+        self.mismatches.is_empty()
+    }
+}
+
+impl fmt::Display for ReplayReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "Replayed {} messages, {} matched",
+            self.replayed, self.matched
+        )?;
+        for mismatch in &self.mismatches {
+            write!(
+                f,
+                "\n#{} {}: expected {}, got {}",
+                mismatch.seq, mismatch.message_id, mismatch.expected, mismatch.actual
+            )?;
+        }
+        Ok(())
+    }
+}
+
+impl Recording {
+    /// Loads a recording
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the file cannot be read or is not a recording.
+    pub fn load(path: impl AsRef<Path>) -> Result<Self> {
+        let invalid = |message: String| MCPError::Protocol(ProtocolError::InvalidFormat(message));
+        let mut lines = BufReader::new(File::open(path)?).lines();
+
+        let header: RecordingHeader = match lines.next() {
+            Some(line) => serde_json::from_str(&line?)?,
+            None => return Err(invalid("Recording is empty".to_string())),
+        };
+        if header.format != RECORDING_FORMAT || header.version > RECORDING_VERSION {
+            return Err(invalid(format!(
+                "Unsupported recording format {} version {}",
+                header.format, header.version
+            )));
+        }
+
+        let mut entries = Vec::new();
+        for line in lines {
+            let line = line?;
+            if !line.trim().is_empty() {
+                entries.push(serde_json::from_str(&line)?);
+            }
+        }
+        Ok(Self { header, entries })
+    }
+
+    /// Feeds the recorded messages through `protocol` in order and compares
+    /// the outcomes with the recorded ones
+    ///
+    /// Responses are redacted the same way the recording was before they are
+    /// compared; recorded messages still carry [`REDACTED`] in place of their
+    /// secrets when they are replayed.
+    pub async fn replay(
+        &self,
+        protocol: &dyn MCPProtocol,
+        config: &RecordingConfig,
+    ) -> ReplayReport {
+        let mut report = ReplayReport::default();
+        for (index, entry) in self.entries.iter().enumerate() {
+            let RecordedEvent::Request { message } = &entry.event else {
+                continue;
+            };
+            let expected = self.recorded_outcome(index, &message.id.0);
+            let actual = match protocol.handle_message(message.clone()).await {
+                Ok(response) => {
+                    let mut event = RecordedEvent::Response { response };
+                    redact_event(&mut event, &config.redact_keys);
+                    match event {
+                        RecordedEvent::Response { response } => {
+                            ReplayOutcome::from_response(&response)
+                        }
+                        _ => ReplayOutcome::Missing,
+                    }
+                }
+                Err(e) => ReplayOutcome::Error(e.to_string()),
+            };
+
+            report.replayed += 1;
+            if actual == expected {
+                report.matched += 1;
+            } else {
+                report.mismatches.push(ReplayMismatch {
+                    seq: entry.seq,
+                    message_id: message.id.0.clone(),
+                    expected,
+                    actual,
+                });
+            }
+        }
+        report
+    }
+
+    /// The first response or error recorded for `message_id` after the
+    /// entry at `index`
+    fn recorded_outcome(&self, index: usize, message_id: &str) -> ReplayOutcome {
+        self.entries[index + 1..]
+            .iter()
+            .find_map(|entry| match &entry.event {
+                RecordedEvent::Response { response } if response.message_id == message_id => {
+                    Some(ReplayOutcome::from_response(response))
+                }
+                RecordedEvent::Error {
+                    message_id: id,
+                    error,
+                } if id == message_id => Some(ReplayOutcome::Error(error.clone())),
+                _ => None,
+            })
+            .unwrap_or(ReplayOutcome::Missing)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::protocol::{CommandHandler, MCPProtocolAdapter, MCPProtocolBase, ProtocolConfig};
+    use crate::types::{MessageId, MessageMetadata, MessageType};
+    use std::sync::atomic::{AtomicBool, Ordering};
+    use std::sync::Arc;
+    use tempfile::tempdir;
+
+    /// Echoes the command name back, or a different answer once `changed`
+    #[derive(Debug)]
+    struct EchoHandler {
+        changed: Arc<AtomicBool>,
+    }
+
+    #[async_trait]
+    impl CommandHandler for EchoHandler {
+        async fn handle(&self, message: &MCPMessage) -> Result<MCPResponse> {
+            let answer = if self.changed.load(Ordering::SeqCst) {
+                "changed"
+            } else {
+                "ok"
+            };
+            Ok(MCPResponse {
+                protocol_version: "1.0".to_string(),
+                message_id: message.id.0.clone(),
+                status: ResponseStatus::Success,
+                payload: serde_json::to_vec(&serde_json::json!({
+                    "command": message.payload["command"],
+                    "answer": answer,
+                    "token": "issued-secret",
+                }))
+                .unwrap(),
+                error_message: None,
+                metadata: MessageMetadata::default(),
+            })
+        }
+    }
+
+    fn message(id: &str, payload: Value) -> MCPMessage {
+        MCPMessage {
+            id: MessageId(id.to_string()),
+            message_type: MessageType::Command,
+            payload,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_record_and_replay() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("session.jsonl");
+        let changed = Arc::new(AtomicBool::new(false));
+        let mut base = MCPProtocolBase::new(ProtocolConfig::default());
+        base.register_handler(
+            MessageType::Command,
+            Box::new(EchoHandler {
+                changed: changed.clone(),
+            }),
+        )
+        .unwrap();
+        let config = RecordingConfig {
+            connection: Some("test".to_string()),
+            ..RecordingConfig::default()
+        };
+        let protocol = RecordingProtocol::new(
+            MCPProtocolAdapter::with_protocol(base),
+            TrafficRecorder::create(&path, config.clone()).unwrap(),
+        );
+
+        let login = message(
+            "1",
+            serde_json::json!({"command": "login", "auth": {"Password": "hunter2"}}),
+        );
+        protocol.handle_message(login).await.unwrap();
+        assert!(protocol
+            .handle_message(message("2", Value::Null))
+            .await
+            .is_err());
+
+        let contents = std::fs::read_to_string(&path).unwrap();
+        assert!(
+            !contents.contains("hunter2") && !contents.contains("issued-secret"),
+            "{contents}"
+        );
+
+        let recording = Recording::load(&path).unwrap();
+        assert_eq!(recording.header.connection.as_deref(), Some("test"));
+        assert_eq!(recording.entries.len(), 4);
+        let RecordedEvent::Request { message } = &recording.entries[0].event else {
+            panic!("expected a request");
+        };
+        assert_eq!(message.payload["auth"]["Password"], REDACTED);
+
+        let report = recording.replay(protocol.inner(), &config).await;
+        assert_eq!((report.replayed, report.matched), (2, 2), "{report}");
+
+        changed.store(true, Ordering::SeqCst);
+        let report = recording.replay(protocol.inner(), &config).await;
+        assert_eq!(report.mismatches.len(), 1, "{report}");
+        assert_eq!(report.mismatches[0].message_id, "1");
+    }
+}
@@ -1,6 +1,9 @@
 use crate::context_manager::Context;
 use crate::monitoring::MCPMonitor;
-use crate::persistence::{MCPPersistence, PersistenceConfig, PersistentState};
+use crate::persistence::{
+    ForegroundGuard, MCPPersistence, PersistenceConfig, PersistentState, SnapshotConfig, SnapshotPriority,
+    SnapshotScheduler, SnapshotStats,
+};
 use crate::sync::state::StateSyncManager;
 use crate::MCPError;
 use chrono::{DateTime, Utc};
@@ -118,6 +121,8 @@ pub struct MCPSync {
     state_manager: Arc<StateSyncManager>,
     /// Persistence layer for storing sync data
     persistence: Arc<MCPPersistence>,
+    /// Writes the snapshots taken on sync in the background
+    snapshots: Arc<SnapshotScheduler>,
     /// Monitoring for sync operations
     monitor: Arc<MCPMonitor>,
     /// Mutex for synchronizing operations
@@ -141,6 +146,7 @@ impl MCPSync {
             config: Arc::new(RwLock::new(config)),
             state: Arc::new(RwLock::new(SyncState::new())),
             state_manager,
+            snapshots: Arc::new(SnapshotScheduler::new(persistence.clone(), SnapshotConfig::default())),
             persistence,
             monitor,
             lock: Arc::new(Mutex::new(())),
@@ -165,6 +171,7 @@ impl MCPSync {
         let state_manager = Arc::new(StateSyncManager::new());
 
         let instance = Self {
+            snapshots: Arc::new(SnapshotScheduler::new(persistence.clone(), SnapshotConfig::default())),
             persistence,
             monitor,
             state_manager,
@@ -202,6 +209,7 @@ impl MCPSync {
         let state_manager = Arc::new(StateSyncManager::new());
 
         Self {
+            snapshots: Arc::new(SnapshotScheduler::new(persistence.clone(), SnapshotConfig::default())),
             persistence,
             monitor,
             state_manager,
@@ -386,6 +394,9 @@ impl MCPSync {
 
     /// Synchronizes the local state with changes from the network
     ///
+    /// The snapshot of the synchronized changes is written in the
+    /// background; see [`Self::flush_snapshots`].
+    ///
     /// # Returns
     /// A Result containing synchronization information
     pub async fn sync(&self) -> Result<SyncResult> {
@@ -439,18 +450,18 @@ impl MCPSync {
             }
         }
 
-        // Persist state
-        if let Err(e) = self.persistence.save_state(&PersistentState {
-            contexts: vec![],
-            changes: changes.clone(),
-            last_version: current_version,
-            last_sync: Utc::now(),
-            id: uuid::Uuid::new_v4().to_string(),
-        }) {
-            tracing::error!("Failed to persist state: {}", e);
-            self.monitor.record_error("persist_state_failed").await;
-            success = false;
-        }
+        // Persist state in the background; failed writes are counted in
+        // the snapshot stats
+        self.snapshots.schedule(
+            PersistentState {
+                contexts: vec![],
+                changes: changes.clone(),
+                last_version: current_version,
+                last_sync: Utc::now(),
+                id: uuid::Uuid::new_v4().to_string(),
+            },
+            SnapshotPriority::Background,
+        );
 
         // Update sync state
         let mut state = self.state.write().await;
@@ -516,6 +527,36 @@ impl MCPSync {
         Ok(Arc::clone(&self.monitor))
     }
 
+    /// Writes the snapshots taken on sync as configured by `config`
+    ///
+    /// Snapshots scheduled by the replaced scheduler are written when it is
+    /// dropped.
+    #[must_use]
+    pub fn with_snapshot_config(mut self, config: SnapshotConfig) -> Self {
+        self.snapshots = Arc::new(SnapshotScheduler::new(self.persistence.clone(), config));
+        self
+    }
+
+    /// Marks foreground work in progress, deferring snapshot writes until
+    /// the guard is dropped
+    pub fn foreground(&self) -> ForegroundGuard {
+        self.snapshots.foreground()
+    }
+
+    /// Writes the pending snapshot now and waits for it
+    ///
+    /// # Errors
+    /// Returns an error if the snapshot cannot be written
+    pub async fn flush_snapshots(&self) -> Result<()> {
+        to_core_error(self.snapshots.flush().await)
+    }
+
+    /// Counters of the snapshots taken on sync
+    #[must_use]
+    pub fn snapshot_stats(&self) -> SnapshotStats {
+        self.snapshots.stats()
+    }
+
     /// Subscribe to state change notifications
     ///
     /// Returns a receiver that will be notified of all state changes.
@@ -1,6 +1,7 @@
 use super::*;
 use crate::sync::state::StateOperation;
 use crate::sync::state::StateSyncManager;
+use crate::persistence::SnapshotConfig;
 use crate::sync::MCPSync;
 use std::sync::Arc;
 
@@ -607,3 +608,48 @@ async fn test_uninitialized_error() {
         "Record change should fail when not initialized"
     );
 }
+
+#[tokio::test]
+async fn test_sync_snapshots_in_background() {
+    let temp_dir = tempdir().expect("Failed to create temp dir");
+    let mut persistence = MCPPersistence::new(PersistenceConfig {
+        data_dir: temp_dir.path().to_path_buf(),
+        ..PersistenceConfig::default()
+    });
+    persistence.init().expect("Failed to initialize persistence");
+    let persistence = Arc::new(persistence);
+
+    let monitor = Arc::new(MCPMonitor::new().await.expect("Failed to create monitor"));
+    let mut sync = MCPSync::new(
+        create_test_config(),
+        persistence.clone(),
+        monitor,
+        Arc::new(StateSyncManager::new()),
+    )
+    .with_snapshot_config(SnapshotConfig {
+        coalesce_window_ms: 60_000,
+        ..SnapshotConfig::default()
+    });
+    sync.init().await.expect("Failed to initialize sync");
+    let initial = persistence.load_state().unwrap().expect("Default state is saved on init");
+
+    // Both syncs are merged into one pending snapshot
+    for _ in 0..2 {
+        sync.record_context_change(&create_test_context(), StateOperation::Create)
+            .await
+            .expect("Failed to record context change");
+        sync.sync().await.expect("Sync failed");
+    }
+    let stats = sync.snapshot_stats();
+    assert_eq!((stats.scheduled, stats.coalesced, stats.written), (2, 1, 0));
+
+    sync.flush_snapshots().await.expect("Failed to flush snapshots");
+    assert_eq!(sync.snapshot_stats().written, 1);
+    let states = std::fs::read_dir(temp_dir.path())
+        .unwrap()
+        .flatten()
+        .filter(|entry| entry.file_name().to_string_lossy().starts_with("state_"))
+        .count();
+    assert_eq!(states, 2, "the default state of {} and one snapshot", initial.id);
+    assert_eq!(persistence.load_contexts().unwrap().len(), 2);
+}
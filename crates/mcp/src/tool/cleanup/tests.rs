@@ -16,6 +16,8 @@ fn create_test_tool(id: &str, security_level: u8) -> Tool {
                 description: "Test capability".to_string(),
                 parameters: vec![],
                 return_type: None,
+                version: 1,
+                deprecation: None,
             }
         ],
         security_level,
@@ -7,8 +7,15 @@ use chrono::Utc;
 use reqwest;
 use serde_json::Value as JsonValue;
 use std::collections::HashMap;
-use tracing::info;
+use std::path::PathBuf;
+use std::process::Stdio;
+use std::time::{Duration, Instant};
+use tokio::io::AsyncWriteExt;
+use tracing::{info, warn};
 
+use squirrel_commands::environments::EnvironmentDefinition;
+
+use crate::tool::arguments::{scrub_environment, ArgumentPolicy, ProcessArgs};
 use crate::tool::{ExecutionStatus, ToolContext, ToolError, ToolExecutionResult, ToolExecutor};
 
 /// Type alias for capability handler functions
@@ -117,6 +124,15 @@ impl ToolExecutor for BasicToolExecutor {
     }
 }
 
+/// An executor's timeout, shortened to what is left of the request's deadline
+fn bounded_timeout(timeout_ms: u64, ctx: &ToolContext) -> Duration {
+    let timeout = Duration::from_millis(timeout_ms);
+    match ctx.deadline {
+        Some(deadline) => timeout.min(deadline.remaining()),
+        None => timeout,
+    }
+}
+
 /// A remote tool executor that delegates to an external service
 pub struct RemoteToolExecutor {
     /// Tool ID this executor is associated with
@@ -176,12 +192,15 @@ impl ToolExecutor for RemoteToolExecutor {
             "Executing remote tool function"
         );
 
-        // Prepare the request payload
+        // Prepare the request payload, telling the service how long the
+        // caller still waits
+        let timeout = bounded_timeout(self.timeout_ms, &ctx);
         let request_payload = serde_json::json!({
             "tool_id": self.tool_id,
             "capability": ctx.capability,
             "request_id": ctx.request_id,
-            "parameters": ctx.parameters
+            "parameters": ctx.parameters,
+            "deadline_ms": ctx.deadline.map(|deadline| deadline.remaining_millis()),
         });
 
         // Execute the remote call
@@ -189,6 +208,7 @@ impl ToolExecutor for RemoteToolExecutor {
         let result = match client
             .post(&self.base_url)
             .json(&request_payload)
+            .timeout(timeout)
             .send()
             .await
         {
@@ -247,6 +267,274 @@ impl ToolExecutor for RemoteToolExecutor {
     }
 }
 
+/// A tool executor that runs a local program for each request
+///
+/// The program receives the request as a JSON object with `tool_id`,
+/// `capability`, `capability_version`, `request_id` and `parameters` on
+/// stdin and writes its result to stdout, as JSON or plain text. A non-zero
+/// exit status fails the execution with stderr as the error message.
+///
+/// An argument written `{name}` is replaced by the request parameter `name`,
+/// checked against the executor's [`ArgumentPolicy`]; an array parameter
+/// becomes one argument per element and a missing one none. The program is
+/// run without a shell, and may only be a shell if it is on the executor's
+/// allowed shells.
+///
+/// The program does not inherit the host environment: it sees the host
+/// variables its environment definition allows, with sensitive ones
+/// scrubbed, plus the executor's own variables.
+///
+/// The program is killed at the executor's timeout, or earlier when the
+/// request's deadline leaves less time.
+pub struct ProcessToolExecutor {
+    /// Tool ID this executor is associated with
+    tool_id: String,
+    /// Capabilities this executor can handle
+    capabilities: Vec<String>,
+    /// Program to run
+    command: String,
+    /// Arguments passed to the program
+    args: Vec<String>,
+    /// Host variables the program sees
+    environment: EnvironmentDefinition,
+    /// Extra environment variables for the program
+    env: HashMap<String, String>,
+    /// Working directory for the program
+    working_dir: Option<PathBuf>,
+    /// Time after which the program is killed, in milliseconds
+    timeout_ms: u64,
+    /// Checks applied to arguments taken from request parameters
+    argument_policy: ArgumentPolicy,
+    /// Shells the program may be
+    allowed_shells: Vec<String>,
+}
+
+impl std::fmt::Debug for ProcessToolExecutor {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("ProcessToolExecutor")
+            .field("tool_id", &self.tool_id)
+            .field("capabilities", &self.capabilities)
+            .field("command", &self.command)
+            .field("args", &self.args)
+            .field("environment", &self.environment.inherit)
+            .field("working_dir", &self.working_dir)
+            .field("timeout_ms", &self.timeout_ms)
+            .field("argument_policy", &self.argument_policy)
+            .field("allowed_shells", &self.allowed_shells)
+            .finish()
+    }
+}
+
+impl ProcessToolExecutor {
+    /// Creates a new process tool executor
+    pub fn new(tool_id: impl Into<String>, command: impl Into<String>) -> Self {
+        Self {
+            tool_id: tool_id.into(),
+            capabilities: Vec::new(),
+            command: command.into(),
+            args: Vec::new(),
+            environment: EnvironmentDefinition::default(),
+            env: HashMap::new(),
+            working_dir: None,
+            timeout_ms: 30000, // 30 seconds default timeout
+            argument_policy: ArgumentPolicy::default(),
+            allowed_shells: Vec::new(),
+        }
+    }
+
+    /// Adds a capability this executor can handle
+    pub fn with_capability(mut self, capability: impl Into<String>) -> Self {
+        self.capabilities.push(capability.into());
+        self
+    }
+
+    /// Sets the program arguments
+    pub fn with_args(mut self, args: impl IntoIterator<Item = impl Into<String>>) -> Self {
+        self.args = args.into_iter().map(Into::into).collect();
+        self
+    }
+
+    /// Sets the host variables the program sees, which default to
+    /// [`DEFAULT_ENV_ALLOWLIST`](squirrel_commands::environments::DEFAULT_ENV_ALLOWLIST)
+    pub fn with_environment(mut self, environment: EnvironmentDefinition) -> Self {
+        self.environment = environment;
+        self
+    }
+
+    /// Adds environment variables for the program
+    pub fn with_env(mut self, env: HashMap<String, String>) -> Self {
+        self.env.extend(env);
+        self
+    }
+
+    /// Sets the working directory
+    pub fn with_working_dir(mut self, dir: impl Into<PathBuf>) -> Self {
+        self.working_dir = Some(dir.into());
+        self
+    }
+
+    /// Sets the timeout
+    pub fn with_timeout(mut self, timeout_ms: u64) -> Self {
+        self.timeout_ms = timeout_ms;
+        self
+    }
+
+    /// Sets the checks applied to arguments taken from request parameters
+    pub fn with_argument_policy(mut self, policy: ArgumentPolicy) -> Self {
+        self.argument_policy = policy;
+        self
+    }
+
+    /// Allows the program to be one of `shells`, e.g. `sh`
+    pub fn with_allowed_shells(mut self, shells: impl IntoIterator<Item = impl Into<String>>) -> Self {
+        self.allowed_shells = shells.into_iter().map(Into::into).collect();
+        self
+    }
+
+    /// Builds the command line, filling `{name}` arguments from the request
+    fn command_line(&self, ctx: &ToolContext) -> Result<ProcessArgs, ToolError> {
+        let mut line = ProcessArgs::new(&self.command)
+            .with_policy(self.argument_policy.clone())
+            .with_allowed_shells(self.allowed_shells.iter().cloned());
+        for arg in &self.args {
+            let Some(name) = arg.strip_prefix('{').and_then(|rest| rest.strip_suffix('}')) else {
+                line.arg(arg);
+                continue;
+            };
+            let values = match ctx.parameters.get(name) {
+                None | Some(JsonValue::Null) => Vec::new(),
+                Some(JsonValue::Array(values)) => values.iter().collect(),
+                Some(value) => vec![value],
+            };
+            for value in values {
+                match value {
+                    JsonValue::String(value) => line.request_arg(name, value.as_str())?,
+                    JsonValue::Number(_) | JsonValue::Bool(_) => line.request_arg(name, value.to_string())?,
+                    _ => {
+                        return Err(ToolError::ValidationFailed(format!(
+                            "Parameter '{}' must be a string, number, boolean or an array of them",
+                            name
+                        )))
+                    }
+                };
+            }
+        }
+        Ok(line)
+    }
+
+    /// Runs the program, returning the status, output and error message
+    async fn run(
+        &self,
+        ctx: &ToolContext,
+    ) -> Result<(ExecutionStatus, Option<JsonValue>, Option<String>), ToolError> {
+        let mut command = self.command_line(ctx)?.command()?;
+        scrub_environment(&mut command, &self.tool_id, &self.environment)?;
+        command
+            .envs(&self.env)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .kill_on_drop(true);
+        if let Some(dir) = &self.working_dir {
+            command.current_dir(dir);
+        }
+
+        let mut child = command.spawn().map_err(|e| ToolError::ExecutionFailed {
+            tool_id: self.tool_id.clone(),
+            reason: format!("Failed to start {}: {}", self.command, e),
+        })?;
+
+        let request = serde_json::json!({
+            "tool_id": ctx.tool_id,
+            "capability": ctx.capability,
+            "capability_version": ctx.capability_version,
+            "request_id": ctx.request_id,
+            "parameters": ctx.parameters,
+        });
+        if let Some(mut stdin) = child.stdin.take() {
+            // A program that ignores its input may exit before reading it
+            if let Err(e) = stdin.write_all(request.to_string().as_bytes()).await {
+                warn!("Failed to write request to {}: {}", self.command, e);
+            }
+        }
+
+        let timeout = bounded_timeout(self.timeout_ms, ctx);
+        let output = match tokio::time::timeout(timeout, child.wait_with_output()).await {
+            Ok(Ok(output)) => output,
+            Ok(Err(e)) => {
+                return Err(ToolError::ExecutionFailed {
+                    tool_id: self.tool_id.clone(),
+                    reason: e.to_string(),
+                })
+            }
+            Err(_) => {
+                return Ok((
+                    ExecutionStatus::Timeout,
+                    None,
+                    Some(format!("{} timed out after {:?}", self.command, timeout)),
+                ))
+            }
+        };
+
+        if !output.status.success() {
+            let stderr = String::from_utf8_lossy(&output.stderr).trim().to_string();
+            let message = if stderr.is_empty() {
+                format!("{} exited with {}", self.command, output.status)
+            } else {
+                stderr
+            };
+            return Ok((ExecutionStatus::Failure, None, Some(message)));
+        }
+
+        let stdout = String::from_utf8_lossy(&output.stdout);
+        let result = serde_json::from_str(stdout.trim())
+            .unwrap_or_else(|_| JsonValue::String(stdout.trim_end().to_string()));
+        Ok((ExecutionStatus::Success, Some(result), None))
+    }
+}
+
+#[async_trait]
+impl ToolExecutor for ProcessToolExecutor {
+    async fn execute(&self, ctx: ToolContext) -> Result<ToolExecutionResult, ToolError> {
+        // Log execution
+        info!(
+            tool_id = self.tool_id,
+            capability = ctx.capability,
+            request_id = ctx.request_id,
+            command = self.command,
+            "Executing process tool function"
+        );
+
+        if !self.capabilities.contains(&ctx.capability) {
+            return Err(ToolError::CapabilityNotFound(
+                ctx.capability.clone(),
+                ctx.tool_id.clone(),
+            ));
+        }
+
+        let started = Instant::now();
+        let (status, output, error_message) = self.run(&ctx).await?;
+        Ok(ToolExecutionResult {
+            tool_id: ctx.tool_id,
+            capability: ctx.capability,
+            request_id: ctx.request_id,
+            status,
+            output,
+            error_message,
+            execution_time_ms: started.elapsed().as_millis() as u64,
+            timestamp: Utc::now(),
+        })
+    }
+
+    fn get_tool_id(&self) -> String {
+        self.tool_id.clone()
+    }
+
+    fn get_capabilities(&self) -> Vec<String> {
+        self.capabilities.clone()
+    }
+}
+
 /// Update trait imports to remove ToolCapability
 pub use crate::tool::{Tool, ToolState};
 
@@ -257,6 +545,7 @@ pub use crate::tool::{Tool, ToolState};
 mod tests {
     use super::*;
     use serde_json::json;
+    use squirrel_core::deadline::Deadline;
     use uuid::Uuid;
 
     #[tokio::test]
@@ -293,6 +582,8 @@ mod tests {
             session_id: None,
             request_id: Uuid::new_v4().to_string(),
             timestamp: Utc::now(),
+            capability_version: 1,
+            deadline: None,
         };
 
         // Execute the capability
@@ -321,6 +612,8 @@ mod tests {
             session_id: None,
             request_id: Uuid::new_v4().to_string(),
             timestamp: Utc::now(),
+            capability_version: 1,
+            deadline: None,
         };
 
         // Execute the capability
@@ -344,6 +637,8 @@ mod tests {
             session_id: None,
             request_id: Uuid::new_v4().to_string(),
             timestamp: Utc::now(),
+            capability_version: 1,
+            deadline: None,
         };
 
         // Execute the capability
@@ -380,6 +675,8 @@ mod tests {
             session_id: None,
             request_id: Uuid::new_v4().to_string(),
             timestamp: Utc::now(),
+            capability_version: 1,
+            deadline: None,
         };
 
         // Execute the capability
@@ -406,6 +703,8 @@ mod tests {
             session_id: None,
             request_id: Uuid::new_v4().to_string(),
             timestamp: Utc::now(),
+            capability_version: 1,
+            deadline: None,
         };
 
         // Execute the capability
@@ -432,6 +731,8 @@ mod tests {
             session_id: None,
             request_id: Uuid::new_v4().to_string(),
             timestamp: Utc::now(),
+            capability_version: 1,
+            deadline: None,
         };
 
         // Execute the capability
@@ -447,4 +748,111 @@ mod tests {
         assert!(execution_result.error_message.is_some());
         // Error message content may vary, just check that it exists
     }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_process_executor() {
+        let context = |capability: &str| ToolContext {
+            tool_id: "proc".to_string(),
+            capability: capability.to_string(),
+            capability_version: 1,
+            parameters: HashMap::from([("n".to_string(), json!(3))]),
+            security_token: None,
+            session_id: None,
+            request_id: Uuid::new_v4().to_string(),
+            timestamp: Utc::now(),
+            deadline: None,
+        };
+
+        // `cat` echoes the JSON request back
+        let echo = ProcessToolExecutor::new("proc", "cat").with_capability("echo");
+        let result = echo.execute(context("echo")).await.unwrap();
+        assert_eq!(result.status, ExecutionStatus::Success);
+        let output = result.output.unwrap();
+        assert_eq!(output["capability"], "echo");
+        assert_eq!(output["parameters"]["n"], 3);
+        assert!(matches!(
+            echo.execute(context("other")).await,
+            Err(ToolError::CapabilityNotFound(_, _))
+        ));
+
+        let failing = ProcessToolExecutor::new("proc", "sh")
+            .with_args(["-c", "echo broken >&2; exit 3"])
+            .with_capability("run");
+        assert!(matches!(
+            failing.execute(context("run")).await,
+            Err(ToolError::SecurityViolation(_))
+        ));
+        let failing = failing.with_allowed_shells(["sh"]);
+        let result = failing.execute(context("run")).await.unwrap();
+        assert_eq!(result.status, ExecutionStatus::Failure);
+        assert_eq!(result.error_message.as_deref(), Some("broken"));
+
+        // Parameters fill `{name}` arguments, but are never interpolated
+        let templated = ProcessToolExecutor::new("proc", "echo")
+            .with_args(["-n", "{n}", "{missing}"])
+            .with_capability("run");
+        let result = templated.execute(context("run")).await.unwrap();
+        assert_eq!(result.output, Some(json!(3)));
+        let injected = ToolContext {
+            parameters: HashMap::from([("n".to_string(), json!(["1", "2; rm -rf /"]))]),
+            ..context("run")
+        };
+        assert!(matches!(
+            templated.execute(injected).await,
+            Err(ToolError::SecurityViolation(_))
+        ));
+
+        let slow = ProcessToolExecutor::new("proc", "sleep")
+            .with_args(["5"])
+            .with_capability("run")
+            .with_timeout(50);
+        let result = slow.execute(context("run")).await.unwrap();
+        assert_eq!(result.status, ExecutionStatus::Timeout);
+
+        // The request's deadline stops the program before its own timeout
+        let slow = slow.with_timeout(30_000);
+        let bounded = ToolContext {
+            deadline: Some(Deadline::after(Duration::from_millis(50))),
+            ..context("run")
+        };
+        let started = Instant::now();
+        let result = slow.execute(bounded).await.unwrap();
+        assert_eq!(result.status, ExecutionStatus::Timeout);
+        assert!(started.elapsed() < Duration::from_secs(5));
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_process_executor_scrubs_the_host_environment() {
+        std::env::set_var("SQUIRREL_EXECUTOR_TEST_TOKEN", "hunter2");
+        let context = ToolContext {
+            tool_id: "proc".to_string(),
+            capability: "run".to_string(),
+            capability_version: 1,
+            parameters: HashMap::new(),
+            security_token: None,
+            session_id: None,
+            request_id: Uuid::new_v4().to_string(),
+            timestamp: Utc::now(),
+            deadline: None,
+        };
+
+        let printenv = ProcessToolExecutor::new("proc", "env")
+            .with_env(HashMap::from([("INJECTED".to_string(), "yes".to_string())]))
+            .with_capability("run");
+        let output = printenv.execute(context.clone()).await.unwrap().output.unwrap();
+        let vars = output.as_str().unwrap();
+        assert!(!vars.contains("SQUIRREL_EXECUTOR_TEST_TOKEN"));
+        assert!(vars.lines().any(|line| line == "INJECTED=yes"));
+        assert!(vars.lines().any(|line| line.starts_with("PATH=")));
+
+        // A sensitive variable only passes when allowed by its exact name
+        let allowed = printenv.with_environment(EnvironmentDefinition {
+            inherit: vec!["PATH".to_string(), "SQUIRREL_EXECUTOR_TEST_TOKEN".to_string()],
+            ..EnvironmentDefinition::default()
+        });
+        let output = allowed.execute(context).await.unwrap().output.unwrap();
+        assert!(output.as_str().unwrap().lines().any(|line| line == "SQUIRREL_EXECUTOR_TEST_TOKEN=hunter2"));
+    }
 }
@@ -0,0 +1,270 @@
+//! Tool execution history
+//!
+//! The tool manager keeps the most recent executions of every tool, so a
+//! flaky tool can be debugged after the fact. The history can be persisted to
+//! a JSON file, which lets `squirrel tool history` read what a running server
+//! recorded.
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, VecDeque};
+use std::path::{Path, PathBuf};
+
+use super::{ExecutionStatus, ToolError};
+
+/// Number of executions kept per tool unless configured otherwise
+pub const DEFAULT_HISTORY_CAPACITY: usize = 100;
+
+/// One execution of a tool capability
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ExecutionRecord {
+    /// Request ID of the execution
+    pub request_id: String,
+    /// Capability that was executed
+    pub capability: String,
+    /// Capability version the request was routed to
+    pub capability_version: u32,
+    /// Outcome of the execution
+    pub status: ExecutionStatus,
+    /// Execution time in milliseconds
+    pub duration_ms: u64,
+    /// Error message if the execution did not succeed
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error_message: Option<String>,
+    /// When the execution started
+    pub started_at: DateTime<Utc>,
+}
+
+impl ExecutionRecord {
+    /// Whether the execution failed, was cancelled or timed out
+    #[must_use]
+    pub fn is_error(&self) -> bool {
+        self.status != ExecutionStatus::Success
+    }
+}
+
+/// Bounded execution history of every tool
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ExecutionHistory {
+    /// Executions kept per tool
+    #[serde(skip, default = "default_capacity")]
+    capacity: usize,
+    /// Executions by tool ID, oldest first
+    tools: HashMap<String, VecDeque<ExecutionRecord>>,
+}
+
+fn default_capacity() -> usize {
+    DEFAULT_HISTORY_CAPACITY
+}
+
+impl Default for ExecutionHistory {
+    fn default() -> Self {
+        Self::new(DEFAULT_HISTORY_CAPACITY)
+    }
+}
+
+impl ExecutionHistory {
+    /// Creates an empty history keeping `capacity` executions per tool
+    #[must_use]
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            capacity: capacity.max(1),
+            tools: HashMap::new(),
+        }
+    }
+
+    /// Reads the history file at `path`; a missing file is an empty history
+    ///
+    /// # Errors
+    ///
+    /// Returns [`ToolError::ValidationFailed`] if the file cannot be read or
+    /// parsed.
+    pub fn load(path: &Path, capacity: usize) -> Result<Self, ToolError> {
+        let contents = match std::fs::read_to_string(path) {
+            Ok(contents) => contents,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Self::new(capacity)),
+            Err(e) => return Err(storage_error(path, &e)),
+        };
+        let mut history: Self =
+            serde_json::from_str(&contents).map_err(|e| storage_error(path, &e))?;
+        history.capacity = capacity.max(1);
+        for records in history.tools.values_mut() {
+            while records.len() > history.capacity {
+                records.pop_front();
+            }
+        }
+        Ok(history)
+    }
+
+    /// Writes the history to `path`, replacing the file atomically
+    ///
+    /// # Errors
+    ///
+    /// Returns [`ToolError::ExecutionError`] if the file cannot be written.
+    pub fn save(&self, path: &Path) -> Result<(), ToolError> {
+        let write_error = |e: &dyn std::fmt::Display| {
+            ToolError::ExecutionError(format!(
+                "Cannot write execution history {}: {}",
+                path.display(),
+                e
+            ))
+        };
+        if let Some(parent) = path
+            .parent()
+            .filter(|parent| !parent.as_os_str().is_empty())
+        {
+            std::fs::create_dir_all(parent).map_err(|e| write_error(&e))?;
+        }
+        let contents = serde_json::to_string_pretty(self).map_err(|e| write_error(&e))?;
+        let temp = path.with_extension("tmp");
+        std::fs::write(&temp, contents).map_err(|e| write_error(&e))?;
+        std::fs::rename(&temp, path).map_err(|e| write_error(&e))
+    }
+
+    /// Default history file, `~/.squirrel/tool-history.json`
+    #[must_use]
+    pub fn default_path() -> Option<PathBuf> {
+        std::env::var_os("HOME")
+            .map(|home| Path::new(&home).join(".squirrel").join("tool-history.json"))
+    }
+
+    /// Records an execution, dropping the tool's oldest one when full
+    pub fn record(&mut self, tool_id: &str, record: ExecutionRecord) {
+        let records = self.tools.entry(tool_id.to_string()).or_default();
+        if records.len() >= self.capacity {
+            records.pop_front();
+        }
+        records.push_back(record);
+    }
+
+    /// Executions of a tool, most recent first
+    #[must_use]
+    pub fn get(&self, tool_id: &str) -> Vec<ExecutionRecord> {
+        self.tools
+            .get(tool_id)
+            .map(|records| records.iter().rev().cloned().collect())
+            .unwrap_or_default()
+    }
+
+    /// Most recent execution of a tool that did not succeed
+    #[must_use]
+    pub fn last_error(&self, tool_id: &str) -> Option<ExecutionRecord> {
+        self.tools
+            .get(tool_id)?
+            .iter()
+            .rev()
+            .find(|record| record.is_error())
+            .cloned()
+    }
+
+    /// Executions of any tool made for a request, with their tool IDs,
+    /// oldest first
+    #[must_use]
+    pub fn find(&self, request_id: &str) -> Vec<(String, ExecutionRecord)> {
+        let mut found: Vec<(String, ExecutionRecord)> = self
+            .tools
+            .iter()
+            .flat_map(|(tool_id, records)| {
+                records
+                    .iter()
+                    .filter(|record| record.request_id == request_id)
+                    .map(move |record| (tool_id.clone(), record.clone()))
+            })
+            .collect();
+        found.sort_by_key(|(_, record)| record.started_at);
+        found
+    }
+
+    /// IDs of the tools with recorded executions, sorted
+    #[must_use]
+    pub fn tool_ids(&self) -> Vec<String> {
+        let mut ids: Vec<String> = self.tools.keys().cloned().collect();
+        ids.sort();
+        ids
+    }
+
+    /// Forgets the executions of a tool
+    pub fn remove_tool(&mut self, tool_id: &str) {
+        self.tools.remove(tool_id);
+    }
+}
+
+/// Error for a history file that cannot be read
+fn storage_error(path: &Path, error: &dyn std::fmt::Display) -> ToolError {
+    ToolError::ValidationFailed(format!(
+        "Cannot read execution history {}: {}",
+        path.display(),
+        error
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn record(request_id: &str, status: ExecutionStatus) -> ExecutionRecord {
+        ExecutionRecord {
+            request_id: request_id.to_string(),
+            capability: "run".to_string(),
+            capability_version: 1,
+            status,
+            duration_ms: 5,
+            error_message: (status != ExecutionStatus::Success).then(|| "boom".to_string()),
+            started_at: Utc::now(),
+        }
+    }
+
+    #[test]
+    fn test_history_is_bounded_and_most_recent_first() {
+        let mut history = ExecutionHistory::new(2);
+        history.record("tool", record("1", ExecutionStatus::Failure));
+        history.record("tool", record("2", ExecutionStatus::Success));
+        history.record("tool", record("3", ExecutionStatus::Success));
+
+        let ids: Vec<_> = history
+            .get("tool")
+            .into_iter()
+            .map(|record| record.request_id)
+            .collect();
+        assert_eq!(ids, ["3", "2"]);
+        assert!(history.last_error("tool").is_none());
+
+        history.record("tool", record("4", ExecutionStatus::Timeout));
+        assert_eq!(history.last_error("tool").unwrap().request_id, "4");
+        assert!(history.get("other").is_empty());
+    }
+
+    #[test]
+    fn test_find_executions_of_a_request() {
+        let mut history = ExecutionHistory::new(10);
+        history.record("fetch", record("req-1", ExecutionStatus::Success));
+        history.record("align", record("req-2", ExecutionStatus::Success));
+        history.record("align", record("req-1", ExecutionStatus::Failure));
+
+        let found = history.find("req-1");
+        let tools: Vec<_> = found.iter().map(|(tool_id, _)| tool_id.as_str()).collect();
+        assert_eq!(tools, ["fetch", "align"]);
+        assert!(found[1].1.is_error());
+        assert!(history.find("req-3").is_empty());
+    }
+
+    #[test]
+    fn test_save_and_load_round_trip() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("history").join("tools.json");
+        assert!(ExecutionHistory::load(&path, 10)
+            .unwrap()
+            .get("tool")
+            .is_empty());
+
+        let mut history = ExecutionHistory::new(10);
+        for id in ["1", "2", "3"] {
+            history.record("tool", record(id, ExecutionStatus::Failure));
+        }
+        history.save(&path).unwrap();
+
+        let loaded = ExecutionHistory::load(&path, 2).unwrap();
+        assert_eq!(loaded.get("tool"), history.get("tool")[..2]);
+        assert_eq!(loaded.tool_ids(), ["tool"]);
+    }
+}
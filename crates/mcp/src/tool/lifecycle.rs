@@ -242,6 +242,7 @@ impl RecoveryHook {
             ToolError::SecurityViolation(_) => false,
             ToolError::TooManyErrors(_) => false,
             ToolError::PermissionDenied(_) => false,
+            ToolError::DeadlineExceeded(_) => false,
             // Others may be recoverable
             _ => true,
         }
@@ -1067,6 +1068,8 @@ mod tests {
                 description: "A test capability".to_string(),
                 parameters: Vec::new(),
                 return_type: None,
+                version: 1,
+                deprecation: None,
             }],
             security_level: 1,
         };
@@ -0,0 +1,385 @@
+//! Tool manifests
+//!
+//! A manifest file describes one tool, its capabilities and the executor
+//! that runs it, so tools can be deployed without code changes.
+//! [`ToolManager::load_from_directory`](super::ToolManager::load_from_directory)
+//! registers every manifest in a directory. Manifests are JSON or TOML:
+//!
+//! ```toml
+//! [tool]
+//! id = "word-count"
+//! name = "Word count"
+//! version = "1.0.0"
+//!
+//! [[tool.capabilities]]
+//! name = "count"
+//! description = "Counts the words on stdin"
+//!
+//! [executor]
+//! kind = "process"
+//! command = "./bin/word-count"
+//! timeout_ms = 5000
+//! ```
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value as JsonValue;
+use squirrel_core::flags::{self, FlagTarget};
+use std::collections::HashMap;
+use std::fmt;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use super::arguments::ArgumentPolicy;
+use super::executor::{ProcessToolExecutor, RemoteToolExecutor};
+use super::{versioning, Tool, ToolError, ToolExecutor};
+
+/// Executor a manifest runs its tool with
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum ExecutorSpec {
+    /// A local program run once per request; see [`ProcessToolExecutor`]
+    Process {
+        /// Program to run; relative paths containing a separator are
+        /// resolved against the manifest's directory
+        command: String,
+        /// Program arguments; `{name}` is replaced by request parameter `name`
+        #[serde(default)]
+        args: Vec<String>,
+        /// Extra environment variables
+        #[serde(default)]
+        env: HashMap<String, String>,
+        /// Working directory, relative to the manifest's directory;
+        /// defaults to that directory
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        working_dir: Option<PathBuf>,
+        /// Time after which the program is killed
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        timeout_ms: Option<u64>,
+        /// Shells the command may be, such as `sh`
+        #[serde(default, skip_serializing_if = "Vec::is_empty")]
+        allowed_shells: Vec<String>,
+        /// Checks applied to arguments taken from request parameters
+        #[serde(default)]
+        arguments: ArgumentPolicy,
+    },
+    /// A tool served by a remote MCP endpoint; see [`RemoteToolExecutor`]
+    RemoteMcp {
+        /// Endpoint URL
+        url: String,
+        /// Request timeout
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        timeout_ms: Option<u64>,
+    },
+    /// A WebAssembly module; no WebAssembly runtime is part of this build,
+    /// so such manifests are rejected
+    Wasm {
+        /// Module path, relative to the manifest's directory
+        module: PathBuf,
+    },
+}
+
+/// A tool and the executor that runs it
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ToolManifest {
+    /// Tool definition
+    pub tool: Tool,
+    /// Executor for the tool
+    pub executor: ExecutorSpec,
+}
+
+impl ToolManifest {
+    /// Reads a manifest, choosing the format by file extension
+    ///
+    /// # Errors
+    ///
+    /// Returns [`ToolError::ValidationFailed`] if the file cannot be read or
+    /// parsed.
+    pub fn from_file(path: &Path) -> Result<Self, ToolError> {
+        let contents = std::fs::read_to_string(path)
+            .map_err(|e| ToolError::ValidationFailed(format!("Cannot read manifest: {}", e)))?;
+        match extension(path).as_deref() {
+            Some("json") => Self::from_json(&contents),
+            Some("toml") => Self::from_toml(&contents),
+            _ => Err(ToolError::ValidationFailed(
+                "Manifests must be .json or .toml files".to_string(),
+            )),
+        }
+    }
+
+    /// Parses a JSON manifest
+    pub fn from_json(contents: &str) -> Result<Self, ToolError> {
+        serde_json::from_str(contents)
+            .map_err(|e| ToolError::ValidationFailed(format!("Invalid JSON manifest: {}", e)))
+    }
+
+    /// Parses a TOML manifest
+    pub fn from_toml(contents: &str) -> Result<Self, ToolError> {
+        toml::from_str(contents)
+            .map_err(|e| ToolError::ValidationFailed(format!("Invalid TOML manifest: {}", e)))
+    }
+
+    /// Checks the tool definition and executor settings
+    ///
+    /// # Errors
+    ///
+    /// Returns [`ToolError::ValidationFailed`] describing the first problem.
+    pub fn validate(&self) -> Result<(), ToolError> {
+        let tool = &self.tool;
+        let valid_id = |c: char| c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.');
+        if tool.id.is_empty() || !tool.id.chars().all(valid_id) {
+            return Err(ToolError::ValidationFailed(format!(
+                "Tool ID '{}' must be non-empty and contain only letters, digits, '-', '_' and '.'",
+                tool.id
+            )));
+        }
+        if tool.name.trim().is_empty() {
+            return Err(ToolError::ValidationFailed(format!(
+                "Tool '{}' has no name",
+                tool.id
+            )));
+        }
+        if tool.capabilities.iter().any(|c| c.name.trim().is_empty()) {
+            return Err(ToolError::ValidationFailed(format!(
+                "Tool '{}' has a capability without a name",
+                tool.id
+            )));
+        }
+        versioning::validate_versions(tool)?;
+
+        match &self.executor {
+            ExecutorSpec::Process { command, .. } if command.trim().is_empty() => Err(
+                ToolError::ValidationFailed("Process executor has no command".to_string()),
+            ),
+            ExecutorSpec::RemoteMcp { url, .. } => match reqwest::Url::parse(url) {
+                Ok(parsed) if matches!(parsed.scheme(), "http" | "https") => Ok(()),
+                Ok(_) => Err(ToolError::ValidationFailed(format!(
+                    "Remote MCP URL '{}' must use http or https",
+                    url
+                ))),
+                Err(e) => Err(ToolError::ValidationFailed(format!(
+                    "Invalid remote MCP URL '{}': {}",
+                    url, e
+                ))),
+            },
+            ExecutorSpec::Wasm { module } => Err(wasm_unsupported(module)),
+            ExecutorSpec::Process { .. } => Ok(()),
+        }
+    }
+
+    /// Builds the executor, resolving relative paths against `base_dir`
+    ///
+    /// # Errors
+    ///
+    /// Returns [`ToolError::ValidationFailed`] for executors this build
+    /// cannot run.
+    pub fn executor(&self, base_dir: &Path) -> Result<Arc<dyn ToolExecutor>, ToolError> {
+        let mut capabilities: Vec<String> = self
+            .tool
+            .capabilities
+            .iter()
+            .map(|capability| capability.name.clone())
+            .collect();
+        capabilities.sort();
+        capabilities.dedup();
+
+        match &self.executor {
+            ExecutorSpec::Process {
+                command,
+                args,
+                env,
+                working_dir,
+                timeout_ms,
+                allowed_shells,
+                arguments,
+            } => {
+                let command = if command.contains(std::path::MAIN_SEPARATOR)
+                    && Path::new(command).is_relative()
+                {
+                    base_dir.join(command).display().to_string()
+                } else {
+                    command.clone()
+                };
+                let working_dir = working_dir
+                    .as_ref()
+                    .map_or_else(|| base_dir.to_path_buf(), |dir| base_dir.join(dir));
+                let mut executor = ProcessToolExecutor::new(&self.tool.id, command)
+                    .with_args(args.iter().cloned())
+                    .with_env(env.clone())
+                    .with_working_dir(working_dir)
+                    .with_allowed_shells(allowed_shells.iter().cloned())
+                    .with_argument_policy(arguments.clone());
+                if let Some(timeout_ms) = timeout_ms {
+                    executor = executor.with_timeout(*timeout_ms);
+                }
+                for capability in capabilities {
+                    executor = executor.with_capability(capability);
+                }
+                Ok(Arc::new(executor))
+            }
+            ExecutorSpec::RemoteMcp { url, timeout_ms } => {
+                let mut executor = RemoteToolExecutor::new(&self.tool.id, url);
+                if let Some(timeout_ms) = timeout_ms {
+                    executor = executor.with_timeout(*timeout_ms);
+                }
+                for capability in capabilities {
+                    executor = executor.with_capability(capability);
+                }
+                Ok(Arc::new(executor))
+            }
+            ExecutorSpec::Wasm { module } => Err(wasm_unsupported(module)),
+        }
+    }
+}
+
+/// Error for WebAssembly executors, which are behind the `wasm_plugins`
+/// flag and which this build cannot run
+fn wasm_unsupported(module: &Path) -> ToolError {
+    if !flags::WASM_PLUGINS.is_enabled(&FlagTarget::anyone()) {
+        return ToolError::ValidationFailed(format!(
+            "Cannot load WebAssembly module '{}': WebAssembly tools are turned off by the '{}' flag",
+            module.display(),
+            flags::WASM_PLUGINS.name()
+        ));
+    }
+    ToolError::ValidationFailed(format!(
+        "Cannot load WebAssembly module '{}': no WebAssembly runtime is part of this build",
+        module.display()
+    ))
+}
+
+/// Lower-cased file extension
+fn extension(path: &Path) -> Option<String> {
+    path.extension()
+        .and_then(|extension| extension.to_str())
+        .map(str::to_ascii_lowercase)
+}
+
+/// Whether a directory entry is a manifest file
+pub(crate) fn is_manifest(path: &Path) -> bool {
+    path.is_file() && matches!(extension(path).as_deref(), Some("json" | "toml"))
+}
+
+/// What loading a manifest did
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum LoadOutcome {
+    /// The tool was registered for the first time
+    Registered,
+    /// The manifest changed and the tool was re-registered
+    Reloaded,
+    /// The manifest did not change
+    Unchanged,
+}
+
+/// A manifest registered with a tool manager
+#[derive(Debug, Clone)]
+pub(crate) struct LoadedManifest {
+    /// File the manifest was read from
+    pub(crate) path: PathBuf,
+    /// Manifest contents, to detect changes on reload
+    pub(crate) source: JsonValue,
+}
+
+/// A manifest that could not be loaded
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ManifestError {
+    /// Manifest file
+    pub path: PathBuf,
+    /// What went wrong
+    pub message: String,
+}
+
+impl fmt::Display for ManifestError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: {}", self.path.display(), self.message)
+    }
+}
+
+/// Outcome of loading a manifest directory
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ManifestLoadReport {
+    /// IDs of newly registered tools
+    pub registered: Vec<String>,
+    /// IDs of tools re-registered because their manifest changed
+    pub reloaded: Vec<String>,
+    /// IDs of tools whose manifest did not change
+    pub unchanged: Vec<String>,
+    /// IDs of tools unregistered because their manifest was removed
+    pub removed: Vec<String>,
+    /// Manifests that failed to load; tools they registered before stay
+    /// registered
+    pub errors: Vec<ManifestError>,
+}
+
+impl ManifestLoadReport {
+    /// Whether every manifest loaded
+    #[must_use]
+    pub fn is_ok(&self) -> bool {
+        self.errors.is_empty()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const TOML_MANIFEST: &str = r#"
+[tool]
+id = "word-count"
+name = "Word count"
+version = "1.0.0"
+
+[[tool.capabilities]]
+name = "count"
+
+[executor]
+kind = "process"
+command = "wc"
+args = ["-w"]
+"#;
+
+    #[test]
+    fn test_parse_toml_and_json_manifests() {
+        let manifest = ToolManifest::from_toml(TOML_MANIFEST).unwrap();
+        assert_eq!(manifest.tool.id, "word-count");
+        assert_eq!(manifest.tool.capabilities[0].version, 1);
+        assert!(manifest.validate().is_ok());
+        assert!(matches!(
+            &manifest.executor,
+            ExecutorSpec::Process { command, args, .. } if command == "wc" && args == &["-w"]
+        ));
+
+        let json = serde_json::to_string(&manifest).unwrap();
+        let reparsed = ToolManifest::from_json(&json).unwrap();
+        assert_eq!(reparsed.executor, manifest.executor);
+
+        let executor = manifest.executor(Path::new("/tools")).unwrap();
+        assert_eq!(executor.get_tool_id(), "word-count");
+        assert_eq!(executor.get_capabilities(), ["count"]);
+    }
+
+    #[test]
+    fn test_validate_rejects_bad_manifests() {
+        let invalid = |edit: fn(&mut ToolManifest)| {
+            let mut manifest = ToolManifest::from_toml(TOML_MANIFEST).unwrap();
+            edit(&mut manifest);
+            manifest.validate().unwrap_err().to_string()
+        };
+
+        assert!(invalid(|m| m.tool.id = "has space".to_string()).contains("Tool ID"));
+        assert!(invalid(|m| m.tool.name = String::new()).contains("no name"));
+        assert!(invalid(|m| {
+            m.executor = ExecutorSpec::RemoteMcp {
+                url: "ftp://example.com".to_string(),
+                timeout_ms: None,
+            }
+        })
+        .contains("http"));
+        assert!(invalid(|m| {
+            m.executor = ExecutorSpec::Wasm {
+                module: PathBuf::from("tool.wasm"),
+            }
+        })
+        .contains("WebAssembly"));
+        assert!(ToolManifest::from_toml("[tool]\nid = 1").is_err());
+    }
+}
@@ -4,28 +4,46 @@
 //! including registration, execution, and lifecycle hooks.
 
 // Declare submodules
+pub mod arguments;
 pub mod cleanup;
 pub mod executor;
+pub mod history;
 pub mod lifecycle;
+pub mod manifest;
+#[cfg(any(feature = "python-tools", feature = "r-tools"))]
+pub mod script;
+pub mod state;
+pub mod versioning;
 
 // Re-export implementations from modules
+pub use self::arguments::{ArgumentPolicy, ProcessArgs};
 pub use self::cleanup::{
     BasicCleanupHook, BasicResourceManager, CleanupHook, RecoveryHook, RecoveryStrategy,
     ResourceLimits, ResourceManager, ResourceTracker, ResourceUsage,
 };
-pub use self::executor::{BasicToolExecutor, RemoteToolExecutor};
+pub use self::executor::{BasicToolExecutor, ProcessToolExecutor, RemoteToolExecutor};
+pub use self::history::{ExecutionHistory, ExecutionRecord, DEFAULT_HISTORY_CAPACITY};
 pub use self::lifecycle::{BasicLifecycleHook, CompositeLifecycleHook};
+pub use self::manifest::{ExecutorSpec, ManifestError, ManifestLoadReport, ToolManifest};
+pub use self::state::{validate_transition, ToolEvent, ToolTransition, TRANSITIONS};
+pub use self::versioning::{
+    Deprecation, UpConversion, CAPABILITY_VERSION_HEADER, CAPABILITY_VERSION_PARAM,
+    DEFAULT_CAPABILITY_VERSION,
+};
 
 use async_trait::async_trait;
 use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
 use serde_json::Value as JsonValue;
+use squirrel_core::deadline::Deadline;
+use squirrel_core::error::{Coded, ErrorCode};
 use std::collections::{HashMap, HashSet};
 use std::fmt;
+use std::path::PathBuf;
 use std::sync::Arc;
 use std::time::Instant;
-use tokio::sync::RwLock;
-use tracing::{error, info, instrument, warn};
+use tokio::sync::{broadcast, RwLock};
+use tracing::{debug, error, info, instrument, warn};
 use uuid::Uuid;
 
 /// Tool capability parameter type
@@ -86,11 +104,20 @@ pub struct Capability {
     /// Capability name
     pub name: String,
     /// Capability description
+    #[serde(default)]
     pub description: String,
     /// Capability parameters
+    #[serde(default)]
     pub parameters: Vec<Parameter>,
     /// Capability return type
     pub return_type: Option<ReturnType>,
+    /// Capability version; a tool may publish several versions of a
+    /// capability under the same name
+    #[serde(default = "versioning::default_capability_version")]
+    pub version: u32,
+    /// Deprecation notice, if callers should move off this version
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub deprecation: Option<Deprecation>,
 }
 
 impl fmt::Display for Capability {
@@ -109,10 +136,13 @@ pub struct Tool {
     /// Tool version
     pub version: String,
     /// Tool description
+    #[serde(default)]
     pub description: String,
     /// Tool capabilities
+    #[serde(default)]
     pub capabilities: Vec<Capability>,
     /// Tool security level (0-10, 0 being lowest)
+    #[serde(default)]
     pub security_level: u8,
 }
 
@@ -202,7 +232,7 @@ impl Default for ToolBuilder {
 }
 
 /// Tool state
-#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
 pub enum ToolState {
     /// Tool is registered but not active
     Registered,
@@ -339,8 +369,18 @@ pub enum ToolError {
     /// Capability not found error
     CapabilityNotFound(String, String),
 
+    /// Requested capability version is not published
+    CapabilityVersionNotFound {
+        tool_id: String,
+        capability: String,
+        version: u32,
+    },
+
     /// Permission denied error
     PermissionDenied(String),
+
+    /// The request's deadline passed before the tool finished
+    DeadlineExceeded(String),
 }
 
 impl std::fmt::Display for ToolError {
@@ -399,13 +439,67 @@ impl std::fmt::Display for ToolError {
             ToolError::CapabilityNotFound(cap, tool) => {
                 write!(f, "Capability '{}' not found for tool '{}'", cap, tool)
             }
+            ToolError::CapabilityVersionNotFound {
+                tool_id,
+                capability,
+                version,
+            } => write!(
+                f,
+                "Version {} of capability '{}' not found for tool '{}'",
+                version, capability, tool_id
+            ),
             ToolError::PermissionDenied(msg) => write!(f, "Permission denied: {}", msg),
+            ToolError::DeadlineExceeded(msg) => write!(f, "Deadline exceeded: {}", msg),
         }
     }
 }
 
 impl std::error::Error for ToolError {}
 
+impl Coded for ToolError {
+    fn code(&self) -> ErrorCode {
+        match self {
+            ToolError::ValidationFailed(_) => ErrorCode::InvalidInput,
+            ToolError::SecurityViolation(_) | ToolError::PermissionDenied(_) => {
+                ErrorCode::PermissionDenied
+            }
+            ToolError::DependencyNotFound(_)
+            | ToolError::ToolNotFound(_)
+            | ToolError::ExecutorNotFound(_)
+            | ToolError::NoStateHistory(_)
+            | ToolError::CapabilityNotFound(_, _)
+            | ToolError::CapabilityVersionNotFound { .. } => ErrorCode::NotFound,
+            ToolError::AlreadyRegistered(_) => ErrorCode::AlreadyExists,
+            ToolError::AlreadyInState { .. }
+            | ToolError::InvalidStateTransition { .. }
+            | ToolError::InvalidManagerState { .. }
+            | ToolError::InvalidState(_) => ErrorCode::Conflict,
+            ToolError::ResourceLimitExceeded { .. } | ToolError::ResourceError(_) => {
+                ErrorCode::ResourceExhausted
+            }
+            ToolError::NeedsReset(_) | ToolError::TooManyErrors(_) => ErrorCode::Unavailable,
+            ToolError::DeadlineExceeded(_) => ErrorCode::Timeout,
+            ToolError::InitializationFailed { .. }
+            | ToolError::ExecutionFailed { .. }
+            | ToolError::ExecutionError(_)
+            | ToolError::ToolError(_) => ErrorCode::ExecutionFailed,
+            ToolError::InternalError(_)
+            | ToolError::LifecycleError(_)
+            | ToolError::RegistrationFailed(_)
+            | ToolError::UnregistrationFailed(_) => ErrorCode::Internal,
+        }
+    }
+
+    fn hint(&self) -> Option<String> {
+        match self {
+            ToolError::NeedsReset(_) | ToolError::TooManyErrors(_) => {
+                Some("Reset the tool before calling it again".to_string())
+            }
+            other => other.code().hint().map(str::to_string),
+        }
+    }
+}
+
 /// Tool execution status
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
 pub enum ExecutionStatus {
@@ -447,6 +541,8 @@ pub struct ToolContext {
     pub tool_id: String,
     /// Capability name
     pub capability: String,
+    /// Capability version the request was routed to
+    pub capability_version: u32,
     /// Capability parameters
     pub parameters: HashMap<String, JsonValue>,
     /// Security token
@@ -457,6 +553,8 @@ pub struct ToolContext {
     pub request_id: String,
     /// Timestamp
     pub timestamp: DateTime<Utc>,
+    /// Deadline of the request, which executors stop the tool at
+    pub deadline: Option<Deadline>,
 }
 
 /// Tool executor
@@ -603,6 +701,9 @@ pub trait ToolLifecycleHook: fmt::Debug + Send + Sync {
     }
 }
 
+/// Number of transitions kept for subscribers that fall behind
+const TRANSITION_BUFFER: usize = 256;
+
 /// Tool manager
 #[derive(Debug)]
 pub struct ToolManager {
@@ -620,6 +721,16 @@ pub struct ToolManager {
     resource_manager: Arc<dyn ResourceManager>,
     /// Recovery hook for tool errors
     recovery_hook: Option<Arc<RecoveryHook>>,
+    /// Sender for state transition notifications
+    transitions: broadcast::Sender<ToolTransition>,
+    /// Request up-conversions between capability versions
+    up_conversions: RwLock<versioning::UpConversions>,
+    /// Manifests of tools loaded from manifest directories, by tool ID
+    manifests: RwLock<HashMap<String, manifest::LoadedManifest>>,
+    /// Recent executions of every tool
+    history: RwLock<ExecutionHistory>,
+    /// File the execution history is persisted to
+    history_path: Option<PathBuf>,
 }
 
 /// Builder for ToolManager
@@ -627,6 +738,8 @@ pub struct ToolManagerBuilder {
     lifecycle_hook: Option<Arc<dyn ToolLifecycleHook>>,
     resource_manager: Option<Arc<dyn ResourceManager>>,
     recovery_hook: Option<Arc<RecoveryHook>>,
+    history_capacity: usize,
+    history_path: Option<PathBuf>,
 }
 
 impl ToolManagerBuilder {
@@ -636,6 +749,8 @@ impl ToolManagerBuilder {
             lifecycle_hook: None,
             resource_manager: None,
             recovery_hook: None,
+            history_capacity: DEFAULT_HISTORY_CAPACITY,
+            history_path: None,
         }
     }
 
@@ -657,8 +772,27 @@ impl ToolManagerBuilder {
         self
     }
 
+    /// Set the number of executions kept per tool
+    pub fn history_capacity(mut self, capacity: usize) -> Self {
+        self.history_capacity = capacity;
+        self
+    }
+
+    /// Persist the execution history to `path`, loading what it holds
+    pub fn history_path(mut self, path: impl Into<PathBuf>) -> Self {
+        self.history_path = Some(path.into());
+        self
+    }
+
     /// Build the ToolManager
     pub fn build(self) -> ToolManager {
+        let history = match &self.history_path {
+            Some(path) => ExecutionHistory::load(path, self.history_capacity).unwrap_or_else(|e| {
+                warn!("Starting with an empty execution history: {}", e);
+                ExecutionHistory::new(self.history_capacity)
+            }),
+            None => ExecutionHistory::new(self.history_capacity),
+        };
         ToolManager {
             tools: RwLock::new(HashMap::new()),
             states: RwLock::new(HashMap::new()),
@@ -671,6 +805,11 @@ impl ToolManagerBuilder {
                 .resource_manager
                 .unwrap_or_else(|| Arc::new(BasicResourceManager::new())),
             recovery_hook: self.recovery_hook,
+            transitions: broadcast::channel(TRANSITION_BUFFER).0,
+            up_conversions: RwLock::new(versioning::UpConversions::default()),
+            manifests: RwLock::new(HashMap::new()),
+            history: RwLock::new(history),
+            history_path: self.history_path,
         }
     }
 }
@@ -698,6 +837,11 @@ impl ToolManager {
             lifecycle_hook: Arc::new(BasicLifecycleHook::new()),
             resource_manager: Arc::new(BasicResourceManager::new()),
             recovery_hook: None,
+            transitions: broadcast::channel(TRANSITION_BUFFER).0,
+            up_conversions: RwLock::new(versioning::UpConversions::default()),
+            manifests: RwLock::new(HashMap::new()),
+            history: RwLock::new(ExecutionHistory::default()),
+            history_path: None,
         }
     }
 
@@ -712,6 +856,11 @@ impl ToolManager {
             lifecycle_hook: Arc::new(lifecycle_hook),
             resource_manager: Arc::new(BasicResourceManager::new()),
             recovery_hook: None,
+            transitions: broadcast::channel(TRANSITION_BUFFER).0,
+            up_conversions: RwLock::new(versioning::UpConversions::default()),
+            manifests: RwLock::new(HashMap::new()),
+            history: RwLock::new(ExecutionHistory::default()),
+            history_path: None,
         }
     }
 
@@ -727,6 +876,15 @@ impl ToolManager {
         &self,
         tool: Tool,
         executor: impl ToolExecutor + 'static,
+    ) -> Result<(), ToolError> {
+        self.register_shared_tool(tool, Arc::new(executor)).await
+    }
+
+    /// Registers a tool with an executor that may be shared
+    async fn register_shared_tool(
+        &self,
+        tool: Tool,
+        executor: Arc<dyn ToolExecutor>,
     ) -> Result<(), ToolError> {
         let tool_id = tool.id.clone();
 
@@ -758,6 +916,8 @@ impl ToolManager {
             )));
         }
 
+        versioning::validate_versions(&tool)?;
+
         // Check if all tool capabilities are handled by the executor
         let executor_capabilities = executor.get_capabilities();
         for capability in &tool.capabilities {
@@ -789,7 +949,7 @@ impl ToolManager {
             states.insert(tool_id.clone(), ToolState::Registered);
 
             // Store the executor
-            executors.insert(tool_id.clone(), Arc::new(executor));
+            executors.insert(tool_id.clone(), executor);
 
             // Update the capability map
             let tool_capabilities = capability_map
@@ -843,21 +1003,153 @@ impl ToolManager {
             // Remove from the capability map
             capability_map.remove(tool_id);
         }
+        self.up_conversions.write().await.remove_tool(tool_id);
+        self.manifests.write().await.remove(tool_id);
+        {
+            let mut history = self.history.write().await;
+            history.remove_tool(tool_id);
+            self.persist_history(&history);
+        }
 
         info!("Tool unregistered: {}", tool_id);
         Ok(())
     }
 
+    /// Registers the tools described by the manifests in `dir`
+    ///
+    /// Every `.json` and `.toml` file is read as a [`ToolManifest`]. Calling
+    /// this again reloads the directory: tools whose manifest changed are
+    /// re-registered, tools whose manifest was removed are unregistered and
+    /// unchanged tools are left alone. A manifest that fails to load is
+    /// reported in [`ManifestLoadReport::errors`] without affecting the
+    /// others, and a tool it registered earlier stays registered.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`ToolError::RegistrationFailed`] if `dir` cannot be read.
+    #[instrument(skip(self, dir))]
+    pub async fn load_from_directory(
+        &self,
+        dir: impl AsRef<std::path::Path>,
+    ) -> Result<ManifestLoadReport, ToolError> {
+        let dir = dir.as_ref();
+        let entries = std::fs::read_dir(dir).map_err(|e| {
+            ToolError::RegistrationFailed(format!(
+                "Cannot read manifest directory {}: {}",
+                dir.display(),
+                e
+            ))
+        })?;
+        let mut paths: Vec<_> = entries
+            .filter_map(Result::ok)
+            .map(|entry| entry.path())
+            .filter(|path| manifest::is_manifest(path))
+            .collect();
+        paths.sort();
+
+        let mut report = ManifestLoadReport::default();
+        let mut loaded = HashSet::new();
+        let mut failed = HashSet::new();
+        for path in paths {
+            match self.load_manifest(&path, dir, &mut loaded).await {
+                Ok((tool_id, manifest::LoadOutcome::Registered)) => report.registered.push(tool_id),
+                Ok((tool_id, manifest::LoadOutcome::Reloaded)) => report.reloaded.push(tool_id),
+                Ok((tool_id, manifest::LoadOutcome::Unchanged)) => report.unchanged.push(tool_id),
+                Err(e) => {
+                    warn!("Failed to load tool manifest {}: {}", path.display(), e);
+                    report.errors.push(ManifestError {
+                        path: path.clone(),
+                        message: e.to_string(),
+                    });
+                    failed.insert(path);
+                }
+            }
+        }
+
+        // Unregister tools whose manifest is gone from the directory
+        let removed: Vec<String> = self
+            .manifests
+            .read()
+            .await
+            .iter()
+            .filter(|(tool_id, loaded_manifest)| {
+                loaded_manifest.path.parent() == Some(dir)
+                    && !loaded.contains(*tool_id)
+                    && !failed.contains(&loaded_manifest.path)
+            })
+            .map(|(tool_id, _)| tool_id.clone())
+            .collect();
+        for tool_id in removed {
+            match self.unregister_tool(&tool_id).await {
+                Ok(()) => report.removed.push(tool_id),
+                Err(e) => warn!("Failed to unregister tool '{}': {}", tool_id, e),
+            }
+        }
+
+        info!(
+            registered = report.registered.len(),
+            reloaded = report.reloaded.len(),
+            unchanged = report.unchanged.len(),
+            removed = report.removed.len(),
+            errors = report.errors.len(),
+            "Loaded tool manifests from {}",
+            dir.display()
+        );
+        Ok(report)
+    }
+
+    /// Registers or reloads the tool described by one manifest file
+    async fn load_manifest(
+        &self,
+        path: &std::path::Path,
+        dir: &std::path::Path,
+        loaded: &mut HashSet<String>,
+    ) -> Result<(String, manifest::LoadOutcome), ToolError> {
+        let tool_manifest = ToolManifest::from_file(path)?;
+        tool_manifest.validate()?;
+        let tool_id = tool_manifest.tool.id.clone();
+        if !loaded.insert(tool_id.clone()) {
+            return Err(ToolError::AlreadyRegistered(format!(
+                "Tool '{}' is defined by another manifest in {}",
+                tool_id,
+                dir.display()
+            )));
+        }
+
+        let source = serde_json::to_value(&tool_manifest)
+            .map_err(|e| ToolError::InternalError(e.to_string()))?;
+        let previous = self.manifests.read().await.get(&tool_id).cloned();
+        let outcome = match previous {
+            Some(previous) if previous.path == path && previous.source == source => {
+                return Ok((tool_id, manifest::LoadOutcome::Unchanged));
+            }
+            Some(_) => manifest::LoadOutcome::Reloaded,
+            None if self.tools.read().await.contains_key(&tool_id) => {
+                return Err(ToolError::AlreadyRegistered(tool_id));
+            }
+            None => manifest::LoadOutcome::Registered,
+        };
+
+        let executor = tool_manifest.executor(dir)?;
+        if outcome == manifest::LoadOutcome::Reloaded {
+            self.unregister_tool(&tool_id).await?;
+        }
+        self.register_shared_tool(tool_manifest.tool, executor)
+            .await?;
+        self.manifests.write().await.insert(
+            tool_id.clone(),
+            manifest::LoadedManifest {
+                path: path.to_path_buf(),
+                source,
+            },
+        );
+        Ok((tool_id, outcome))
+    }
+
     /// Activates a tool
     #[instrument(skip(self))]
     pub async fn activate_tool(&self, tool_id: &str) -> Result<(), ToolError> {
-        // Check if the tool exists
-        {
-            let tools = self.tools.read().await;
-            if !tools.contains_key(tool_id) {
-                return Err(ToolError::ToolNotFound(tool_id.to_string()));
-            }
-        }
+        self.validate(tool_id, ToolEvent::Activate).await?;
 
         // Call the lifecycle hook
         self.lifecycle_hook
@@ -865,11 +1157,7 @@ impl ToolManager {
             .await
             .map_err(|e| ToolError::LifecycleError(format!("Activation hook failed: {}", e)))?;
 
-        // Activate the tool
-        {
-            let mut states = self.states.write().await;
-            states.insert(tool_id.to_string(), ToolState::Active);
-        }
+        self.transition(tool_id, ToolEvent::Activate).await?;
 
         info!("Tool activated: {}", tool_id);
         Ok(())
@@ -878,13 +1166,7 @@ impl ToolManager {
     /// Deactivates a tool
     #[instrument(skip(self))]
     pub async fn deactivate_tool(&self, tool_id: &str) -> Result<(), ToolError> {
-        // Check if the tool exists
-        {
-            let tools = self.tools.read().await;
-            if !tools.contains_key(tool_id) {
-                return Err(ToolError::ToolNotFound(tool_id.to_string()));
-            }
-        }
+        self.validate(tool_id, ToolEvent::Deactivate).await?;
 
         // Call the lifecycle hook
         self.lifecycle_hook
@@ -892,11 +1174,7 @@ impl ToolManager {
             .await
             .map_err(|e| ToolError::LifecycleError(format!("Deactivation hook failed: {}", e)))?;
 
-        // Deactivate the tool
-        {
-            let mut states = self.states.write().await;
-            states.insert(tool_id.to_string(), ToolState::Inactive);
-        }
+        self.transition(tool_id, ToolEvent::Deactivate).await?;
 
         info!("Tool deactivated: {}", tool_id);
         Ok(())
@@ -926,32 +1204,116 @@ impl ToolManager {
         states.clone()
     }
 
-    /// Updates a tool's state
+    /// Sets a tool's state directly, bypassing the transition table
+    ///
+    /// The change is still published to transition subscribers, without an
+    /// event. Prefer the lifecycle methods, which validate the transition.
     #[instrument(skip(self))]
     pub async fn update_tool_state(
         &self,
         tool_id: &str,
         state: ToolState,
     ) -> Result<(), ToolError> {
-        // Check if the tool exists
-        {
-            let tools = self.tools.read().await;
-            if !tools.contains_key(tool_id) {
-                return Err(ToolError::ToolNotFound(tool_id.to_string()));
-            }
-        }
-
-        // Update the state
-        {
+        let from = {
             let mut states = self.states.write().await;
-            states.insert(tool_id.to_string(), state);
-        }
+            let current = states
+                .get_mut(tool_id)
+                .ok_or_else(|| ToolError::ToolNotFound(tool_id.to_string()))?;
+            std::mem::replace(current, state)
+        };
+        self.publish(tool_id, from, None, state);
 
         info!("Tool state updated: {} -> {}", tool_id, state);
         Ok(())
     }
 
+    /// Subscribes to the state transitions of all tools
+    pub fn subscribe_transitions(&self) -> broadcast::Receiver<ToolTransition> {
+        self.transitions.subscribe()
+    }
+
+    /// Checks that `event` is allowed in a tool's current state
+    async fn validate(&self, tool_id: &str, event: ToolEvent) -> Result<ToolState, ToolError> {
+        let states = self.states.read().await;
+        let from = states
+            .get(tool_id)
+            .copied()
+            .ok_or_else(|| ToolError::ToolNotFound(tool_id.to_string()))?;
+        validate_transition(tool_id, from, event)
+    }
+
+    /// Applies `event` to a tool's state and publishes the transition
+    async fn transition(&self, tool_id: &str, event: ToolEvent) -> Result<ToolState, ToolError> {
+        let (from, to) = {
+            let mut states = self.states.write().await;
+            let current = states
+                .get_mut(tool_id)
+                .ok_or_else(|| ToolError::ToolNotFound(tool_id.to_string()))?;
+            let from = *current;
+            *current = validate_transition(tool_id, from, event)?;
+            (from, *current)
+        };
+        self.publish(tool_id, from, Some(event), to);
+        Ok(to)
+    }
+
+    /// Moves a tool to the error state after `error`, returning the error
+    async fn fail(&self, tool_id: &str, error: ToolError) -> ToolError {
+        if let Err(e) = self.transition(tool_id, ToolEvent::Fail).await {
+            warn!("Could not mark tool '{}' as failed: {}", tool_id, e);
+        }
+        error
+    }
+
+    /// Sends a transition to subscribers
+    fn publish(&self, tool_id: &str, from: ToolState, event: Option<ToolEvent>, to: ToolState) {
+        debug!(tool_id = tool_id, from = %from, to = %to, "Tool state transition");
+        // Nobody listening is fine
+        let _ = self.transitions.send(ToolTransition {
+            tool_id: tool_id.to_string(),
+            from,
+            event,
+            to,
+            timestamp: Utc::now(),
+        });
+    }
+
+    /// Gets a tool's executor
+    async fn executor(&self, tool_id: &str) -> Result<Arc<dyn ToolExecutor>, ToolError> {
+        self.executors
+            .read()
+            .await
+            .get(tool_id)
+            .cloned()
+            .ok_or_else(|| ToolError::ExecutorNotFound(tool_id.to_string()))
+    }
+
+    /// Registers a function converting requests for version `from` of a
+    /// tool's capability into requests for version `to`
+    ///
+    /// Requests for `from` are converted when it is deprecated or no longer
+    /// published; conversions chain until a current version is reached.
+    pub async fn register_up_conversion(
+        &self,
+        tool_id: &str,
+        capability: &str,
+        from: u32,
+        to: u32,
+        convert: impl Fn(JsonValue) -> Result<JsonValue, ToolError> + Send + Sync + 'static,
+    ) -> Result<(), ToolError> {
+        if !self.tools.read().await.contains_key(tool_id) {
+            return Err(ToolError::ToolNotFound(tool_id.to_string()));
+        }
+        self.up_conversions
+            .write()
+            .await
+            .register(tool_id, capability, from, to, Arc::new(convert))
+    }
+
     /// Executes a tool with the specified capability and parameters
+    ///
+    /// The capability version comes from the [`CAPABILITY_VERSION_PARAM`]
+    /// parameter, defaulting to the newest published version.
     #[instrument(skip(self, params))]
     pub async fn execute_tool(
         &self,
@@ -959,9 +1321,56 @@ impl ToolManager {
         capability: &str,
         params: JsonValue,
         request_id: Option<String>,
+    ) -> Result<ToolExecutionResult, ToolError> {
+        self.execute_tool_version(tool_id, capability, None, params, request_id)
+            .await
+    }
+
+    /// Executes a specific version of a tool capability
+    ///
+    /// `version`, typically read from a [`CAPABILITY_VERSION_HEADER`], takes
+    /// precedence over the [`CAPABILITY_VERSION_PARAM`] parameter. Requests
+    /// for deprecated versions log a warning and are up-converted when a
+    /// conversion is registered.
+    #[instrument(skip(self, params))]
+    pub async fn execute_tool_version(
+        &self,
+        tool_id: &str,
+        capability: &str,
+        version: Option<u32>,
+        mut params: JsonValue,
+        request_id: Option<String>,
     ) -> Result<ToolExecutionResult, ToolError> {
         let request_id = request_id.unwrap_or_else(|| Uuid::new_v4().to_string());
 
+        // Route the request to a capability version
+        let requested = match versioning::take_version_param(&mut params)? {
+            Some(param) => Some(version.unwrap_or(param)),
+            None => version,
+        };
+        let resolved = match self.get_tool(tool_id).await {
+            Some(tool) => {
+                let conversions = self.up_conversions.read().await;
+                versioning::resolve(&tool, capability, requested, params, &conversions)?
+            }
+            None => versioning::ResolvedCapability {
+                version: requested.unwrap_or(DEFAULT_CAPABILITY_VERSION),
+                params,
+                deprecated: None,
+            },
+        };
+        if let Some((deprecated, deprecation)) = &resolved.deprecated {
+            warn!(
+                tool_id = tool_id,
+                capability = capability,
+                version = *deprecated,
+                routed_to = resolved.version,
+                "Deprecated capability version requested: {}",
+                deprecation
+            );
+        }
+        let params = resolved.params;
+
         // Get the executor - need to read the RwLock
         let executor = {
             let executors_guard = self.executors.read().await;
@@ -980,12 +1389,23 @@ impl ToolManager {
             "Executing tool capability"
         );
 
+        // Stop at the caller's deadline, if it set one
+        let deadline = Deadline::current();
+        if deadline.is_some_and(|deadline| deadline.is_expired()) {
+            return Err(ToolError::DeadlineExceeded(format!(
+                "{}.{} was not started",
+                tool_id, capability
+            )));
+        }
+
         let start_time = Instant::now();
+        let started_at = Utc::now();
 
         // Create a tool context with proper field types
         let context = ToolContext {
             tool_id: tool_id.to_string(),
             capability: capability.to_string(),
+            capability_version: resolved.version,
             request_id: request_id.clone(),
             parameters: params
                 .clone()
@@ -995,10 +1415,23 @@ impl ToolManager {
             security_token: Some("default-token".to_string()), // Wrap in Some
             session_id: Some(Uuid::new_v4().to_string()),      // Wrap in Some
             timestamp: chrono::Utc::now(),                     // Use correct type
+            deadline,
         };
 
-        // Execute the tool
-        match executor.execute(context).await {
+        // Execute the tool, cancelling it at the deadline
+        let execution = match deadline {
+            Some(deadline) => deadline
+                .run(executor.execute(context))
+                .await
+                .unwrap_or_else(|_| {
+                    Err(ToolError::DeadlineExceeded(format!(
+                        "{}.{} was cancelled",
+                        tool_id, capability
+                    )))
+                }),
+            None => executor.execute(context).await,
+        };
+        match execution {
             Ok(result) => {
                 let duration = start_time.elapsed();
                 info!(
@@ -1013,6 +1446,19 @@ impl ToolManager {
                 // Preserve the result from the executor, just update the timing
                 let mut updated_result = result;
                 updated_result.execution_time_ms = duration.as_millis() as u64;
+                self.record_execution(
+                    tool_id,
+                    ExecutionRecord {
+                        request_id,
+                        capability: capability.to_string(),
+                        capability_version: resolved.version,
+                        status: updated_result.status,
+                        duration_ms: updated_result.execution_time_ms,
+                        error_message: updated_result.error_message.clone(),
+                        started_at,
+                    },
+                )
+                .await;
 
                 Ok(updated_result)
             }
@@ -1027,8 +1473,28 @@ impl ToolManager {
                     "Tool execution failed"
                 );
 
-                // If it's a CapabilityNotFound error, propagate it to the caller
-                if let ToolError::CapabilityNotFound(_, _) = &error {
+                self.record_execution(
+                    tool_id,
+                    ExecutionRecord {
+                        request_id: request_id.clone(),
+                        capability: capability.to_string(),
+                        capability_version: resolved.version,
+                        status: match error {
+                            ToolError::DeadlineExceeded(_) => ExecutionStatus::Timeout,
+                            _ => ExecutionStatus::Failure,
+                        },
+                        duration_ms: duration.as_millis() as u64,
+                        error_message: Some(error.to_string()),
+                        started_at,
+                    },
+                )
+                .await;
+
+                // Propagate missing capabilities and missed deadlines to the
+                // caller
+                if let ToolError::CapabilityNotFound(_, _) | ToolError::DeadlineExceeded(_) =
+                    &error
+                {
                     return Err(error);
                 }
 
@@ -1046,78 +1512,57 @@ impl ToolManager {
         }
     }
 
-    /// Starts a tool
-    #[instrument(skip(self))]
-    pub async fn start_tool(&self, tool_id: &str) -> Result<(), ToolError> {
-        // Check if the tool exists
-        {
-            let tools = self.tools.read().await;
-            if !tools.contains_key(tool_id) {
-                return Err(ToolError::ToolNotFound(tool_id.to_string()));
-            }
-        }
+    /// Recent executions of a tool, most recent first
+    pub async fn get_tool_history(&self, tool_id: &str) -> Vec<ExecutionRecord> {
+        self.history.read().await.get(tool_id)
+    }
 
-        // Check current state
-        {
-            let states = self.states.read().await;
-            match states.get(tool_id) {
-                Some(ToolState::Active) | Some(ToolState::Started) => {
-                    return Err(ToolError::InvalidState(format!(
-                        "Tool '{}' is already started",
-                        tool_id
-                    )));
-                }
-                Some(ToolState::Starting) => {
-                    return Err(ToolError::InvalidState(format!(
-                        "Tool '{}' is already starting",
-                        tool_id
-                    )));
-                }
-                Some(ToolState::Error) => {
-                    return Err(ToolError::ToolError(format!(
-                        "Tool '{}' is in error state and cannot be started",
-                        tool_id
-                    )));
-                }
-                Some(ToolState::Unregistered) => {
-                    return Err(ToolError::ToolNotFound(format!(
-                        "Tool '{}' is unregistered",
-                        tool_id
-                    )));
-                }
-                _ => {} // Continue with other states
+    /// Executions of any tool made for a request, with their tool IDs,
+    /// oldest first
+    pub async fn get_request_executions(&self, request_id: &str) -> Vec<(String, ExecutionRecord)> {
+        self.history.read().await.find(request_id)
+    }
+
+    /// Most recent execution of a tool that did not succeed
+    pub async fn get_last_error(&self, tool_id: &str) -> Option<ExecutionRecord> {
+        self.history.read().await.last_error(tool_id)
+    }
+
+    /// Adds an execution to the history and persists it
+    async fn record_execution(&self, tool_id: &str, record: ExecutionRecord) {
+        let mut history = self.history.write().await;
+        history.record(tool_id, record);
+        self.persist_history(&history);
+    }
+
+    /// Writes the history file, if one is configured
+    fn persist_history(&self, history: &ExecutionHistory) {
+        if let Some(path) = &self.history_path {
+            if let Err(e) = history.save(path) {
+                warn!("Failed to persist execution history: {}", e);
             }
         }
+    }
 
-        // Update state to Starting
-        {
-            let mut states = self.states.write().await;
-            states.insert(tool_id.to_string(), ToolState::Starting);
-        }
+    /// Starts a tool
+    #[instrument(skip(self))]
+    pub async fn start_tool(&self, tool_id: &str) -> Result<(), ToolError> {
+        let executor = self.executor(tool_id).await?;
+        self.transition(tool_id, ToolEvent::Start).await?;
 
         // Call pre-start lifecycle hook
-        self.lifecycle_hook
-            .pre_start(tool_id)
-            .await
-            .map_err(|e| ToolError::LifecycleError(format!("Pre-start hook failed: {}", e)))?;
+        if let Err(e) = self.lifecycle_hook.pre_start(tool_id).await {
+            let error = ToolError::LifecycleError(format!("Pre-start hook failed: {}", e));
+            return Err(self.fail(tool_id, error).await);
+        }
 
         // Call the executor's start method
-        {
-            let executors = self.executors.read().await;
-            if let Some(executor) = executors.get(tool_id) {
-                executor.start().await.map_err(|e| {
-                    ToolError::ExecutionError(format!("Failed to start tool: {}", e))
-                })?;
-            } else {
-                return Err(ToolError::ExecutorNotFound(tool_id.to_string()));
-            }
+        if let Err(e) = executor.start().await {
+            let error = ToolError::ExecutionError(format!("Failed to start tool: {}", e));
+            return Err(self.fail(tool_id, error).await);
         }
 
-        // Update state to Started
-        {
-            let mut states = self.states.write().await;
-            states.insert(tool_id.to_string(), ToolState::Started);
-        }
+        self.transition(tool_id, ToolEvent::Started).await?;
 
         // Call post-start lifecycle hook
         self.lifecycle_hook
@@ -1132,69 +1577,22 @@ impl ToolManager {
     /// Stops a tool
     #[instrument(skip(self))]
     pub async fn stop_tool(&self, tool_id: &str) -> Result<(), ToolError> {
-        // Check if the tool exists
-        {
-            let tools = self.tools.read().await;
-            if !tools.contains_key(tool_id) {
-                return Err(ToolError::ToolNotFound(tool_id.to_string()));
-            }
-        }
-
-        // Check current state
-        {
-            let states = self.states.read().await;
-            match states.get(tool_id) {
-                Some(ToolState::Stopped) => {
-                    return Err(ToolError::InvalidState(format!(
-                        "Tool '{}' is already stopped",
-                        tool_id
-                    )));
-                }
-                Some(ToolState::Stopping) => {
-                    return Err(ToolError::InvalidState(format!(
-                        "Tool '{}' is already stopping",
-                        tool_id
-                    )));
-                }
-                Some(ToolState::Unregistered) => {
-                    return Err(ToolError::ToolNotFound(format!(
-                        "Tool '{}' is unregistered",
-                        tool_id
-                    )));
-                }
-                _ => {} // Continue with other states
-            }
-        }
-
-        // Update state to Stopping
-        {
-            let mut states = self.states.write().await;
-            states.insert(tool_id.to_string(), ToolState::Stopping);
-        }
+        let executor = self.executor(tool_id).await?;
+        self.transition(tool_id, ToolEvent::Stop).await?;
 
         // Call pre-stop lifecycle hook
-        self.lifecycle_hook
-            .pre_stop(tool_id)
-            .await
-            .map_err(|e| ToolError::LifecycleError(format!("Pre-stop hook failed: {}", e)))?;
+        if let Err(e) = self.lifecycle_hook.pre_stop(tool_id).await {
+            let error = ToolError::LifecycleError(format!("Pre-stop hook failed: {}", e));
+            return Err(self.fail(tool_id, error).await);
+        }
 
         // Call the executor's stop method
-        {
-            let executors = self.executors.read().await;
-            if let Some(executor) = executors.get(tool_id) {
-                executor.stop().await.map_err(|e| {
-                    ToolError::ExecutionError(format!("Failed to stop tool: {}", e))
-                })?;
-            } else {
-                return Err(ToolError::ExecutorNotFound(tool_id.to_string()));
-            }
+        if let Err(e) = executor.stop().await {
+            let error = ToolError::ExecutionError(format!("Failed to stop tool: {}", e));
+            return Err(self.fail(tool_id, error).await);
         }
 
-        // Update state to Stopped
-        {
-            let mut states = self.states.write().await;
-            states.insert(tool_id.to_string(), ToolState::Stopped);
-        }
+        self.transition(tool_id, ToolEvent::Stopped).await?;
 
         // Call post-stop lifecycle hook
         self.lifecycle_hook
@@ -1209,69 +1607,16 @@ impl ToolManager {
     /// Pauses a tool
     #[instrument(skip(self))]
     pub async fn pause_tool(&self, tool_id: &str) -> Result<(), ToolError> {
-        // Check if the tool exists
-        {
-            let tools = self.tools.read().await;
-            if !tools.contains_key(tool_id) {
-                return Err(ToolError::ToolNotFound(tool_id.to_string()));
-            }
-        }
-
-        // Check current state
-        {
-            let states = self.states.read().await;
-            match states.get(tool_id) {
-                Some(ToolState::Paused) => {
-                    return Err(ToolError::InvalidState(format!(
-                        "Tool '{}' is already paused",
-                        tool_id
-                    )));
-                }
-                Some(ToolState::Pausing) => {
-                    return Err(ToolError::InvalidState(format!(
-                        "Tool '{}' is already pausing",
-                        tool_id
-                    )));
-                }
-                Some(ToolState::Stopped) | Some(ToolState::Stopping) => {
-                    return Err(ToolError::InvalidState(format!(
-                        "Tool '{}' is stopped or stopping",
-                        tool_id
-                    )));
-                }
-                Some(ToolState::Unregistered) => {
-                    return Err(ToolError::ToolNotFound(format!(
-                        "Tool '{}' is unregistered",
-                        tool_id
-                    )));
-                }
-                _ => {} // Continue with other states
-            }
-        }
-
-        // Update state to Pausing
-        {
-            let mut states = self.states.write().await;
-            states.insert(tool_id.to_string(), ToolState::Pausing);
-        }
+        let executor = self.executor(tool_id).await?;
+        self.transition(tool_id, ToolEvent::Pause).await?;
 
         // Call the executor's pause method
-        {
-            let executors = self.executors.read().await;
-            if let Some(executor) = executors.get(tool_id) {
-                executor.pause().await.map_err(|e| {
-                    ToolError::ExecutionError(format!("Failed to pause tool: {}", e))
-                })?;
-            } else {
-                return Err(ToolError::ExecutorNotFound(tool_id.to_string()));
-            }
+        if let Err(e) = executor.pause().await {
+            let error = ToolError::ExecutionError(format!("Failed to pause tool: {}", e));
+            return Err(self.fail(tool_id, error).await);
         }
 
-        // Update state to Paused
-        {
-            let mut states = self.states.write().await;
-            states.insert(tool_id.to_string(), ToolState::Paused);
-        }
+        self.transition(tool_id, ToolEvent::Paused).await?;
 
         // Call pause lifecycle hook
         self.lifecycle_hook
@@ -1286,75 +1631,16 @@ impl ToolManager {
     /// Resumes a tool
     #[instrument(skip(self))]
     pub async fn resume_tool(&self, tool_id: &str) -> Result<(), ToolError> {
-        // Check if the tool exists
-        {
-            let tools = self.tools.read().await;
-            if !tools.contains_key(tool_id) {
-                return Err(ToolError::ToolNotFound(tool_id.to_string()));
-            }
-        }
-
-        // Check current state
-        {
-            let states = self.states.read().await;
-            match states.get(tool_id) {
-                Some(ToolState::Active) | Some(ToolState::Started) => {
-                    return Err(ToolError::InvalidState(format!(
-                        "Tool '{}' is already active",
-                        tool_id
-                    )));
-                }
-                Some(ToolState::Resuming) => {
-                    return Err(ToolError::InvalidState(format!(
-                        "Tool '{}' is already resuming",
-                        tool_id
-                    )));
-                }
-                Some(ToolState::Stopped) | Some(ToolState::Stopping) => {
-                    return Err(ToolError::InvalidState(format!(
-                        "Tool '{}' is stopped or stopping",
-                        tool_id
-                    )));
-                }
-                Some(ToolState::Unregistered) => {
-                    return Err(ToolError::ToolNotFound(format!(
-                        "Tool '{}' is unregistered",
-                        tool_id
-                    )));
-                }
-                Some(ToolState::Paused) => {} // Expected state
-                _ => {
-                    return Err(ToolError::InvalidState(format!(
-                        "Tool '{}' is not paused",
-                        tool_id
-                    )));
-                }
-            }
-        }
-
-        // Update state to Resuming
-        {
-            let mut states = self.states.write().await;
-            states.insert(tool_id.to_string(), ToolState::Resuming);
-        }
+        let executor = self.executor(tool_id).await?;
+        self.transition(tool_id, ToolEvent::Resume).await?;
 
         // Call the executor's resume method
-        {
-            let executors = self.executors.read().await;
-            if let Some(executor) = executors.get(tool_id) {
-                executor.resume().await.map_err(|e| {
-                    ToolError::ExecutionError(format!("Failed to resume tool: {}", e))
-                })?;
-            } else {
-                return Err(ToolError::ExecutorNotFound(tool_id.to_string()));
-            }
+        if let Err(e) = executor.resume().await {
+            let error = ToolError::ExecutionError(format!("Failed to resume tool: {}", e));
+            return Err(self.fail(tool_id, error).await);
         }
 
-        // Update state to Active
-        {
-            let mut states = self.states.write().await;
-            states.insert(tool_id.to_string(), ToolState::Active);
-        }
+        self.transition(tool_id, ToolEvent::Resumed).await?;
 
         // Call resume lifecycle hook
         self.lifecycle_hook
@@ -1370,46 +1656,14 @@ impl ToolManager {
     #[instrument(skip(self, updated_tool))]
     pub async fn update_tool(&self, updated_tool: Tool) -> Result<(), ToolError> {
         let tool_id = updated_tool.id.clone();
-
-        // Check if the tool exists
-        {
-            let tools = self.tools.read().await;
-            if !tools.contains_key(&tool_id) {
-                return Err(ToolError::ToolNotFound(tool_id.clone()));
-            }
-        }
-
-        // Check current state
-        {
-            let states = self.states.read().await;
-            match states.get(&tool_id) {
-                Some(ToolState::Unregistered) => {
-                    return Err(ToolError::ToolNotFound(format!(
-                        "Tool '{}' is unregistered",
-                        tool_id
-                    )));
-                }
-                Some(ToolState::Updating) => {
-                    return Err(ToolError::InvalidState(format!(
-                        "Tool '{}' is already updating",
-                        tool_id
-                    )));
-                }
-                _ => {} // Continue with other states
-            }
-        }
-
-        // Update state to Updating
-        {
-            let mut states = self.states.write().await;
-            states.insert(tool_id.clone(), ToolState::Updating);
-        }
+        versioning::validate_versions(&updated_tool)?;
+        self.transition(&tool_id, ToolEvent::Update).await?;
 
         // Call the lifecycle hook
-        self.lifecycle_hook
-            .on_update(&updated_tool)
-            .await
-            .map_err(|e| ToolError::LifecycleError(format!("Update hook failed: {}", e)))?;
+        if let Err(e) = self.lifecycle_hook.on_update(&updated_tool).await {
+            let error = ToolError::LifecycleError(format!("Update hook failed: {}", e));
+            return Err(self.fail(&tool_id, error).await);
+        }
 
         // Update the tool registry
         {
@@ -1434,11 +1688,7 @@ impl ToolManager {
             }
         }
 
-        // Update state to Active
-        {
-            let mut states = self.states.write().await;
-            states.insert(tool_id.clone(), ToolState::Active);
-        }
+        self.transition(&tool_id, ToolEvent::Updated).await?;
 
         info!("Tool updated: {} ({})", updated_tool.name, tool_id);
         Ok(())
@@ -1448,29 +1698,14 @@ impl ToolManager {
     #[instrument(skip(self))]
     pub async fn reset_tool(&self, tool_id: &str) -> Result<(), ToolError> {
         // Stop the tool first if it's running
-        {
-            let states = self.states.read().await;
-            match states.get(tool_id) {
-                Some(ToolState::Active) | Some(ToolState::Started) => {
-                    drop(states); // Release the lock before calling stop_tool
-                    self.stop_tool(tool_id).await?;
-                }
-                Some(ToolState::Paused) => {
-                    drop(states); // Release the lock before calling stop_tool
-
-                    // Resume first, then stop
-                    self.resume_tool(tool_id).await?;
-                    self.stop_tool(tool_id).await?;
-                }
-                _ => {} // No need to stop if already stopped
-            }
+        if matches!(
+            self.get_tool_state(tool_id).await,
+            Some(ToolState::Active | ToolState::Started | ToolState::Paused)
+        ) {
+            self.stop_tool(tool_id).await?;
         }
 
-        // Update state to Registered (initial state)
-        {
-            let mut states = self.states.write().await;
-            states.insert(tool_id.to_string(), ToolState::Registered);
-        }
+        self.transition(tool_id, ToolEvent::Reset).await?;
 
         // Reset the resource tracking
         self.resource_manager.reset_tool(tool_id).await?;
@@ -1482,45 +1717,7 @@ impl ToolManager {
     /// Recovers a tool from error state
     #[instrument(skip(self))]
     pub async fn recover_tool(&self, tool_id: &str) -> Result<(), ToolError> {
-        // Check if the tool exists
-        {
-            let tools = self.tools.read().await;
-            if !tools.contains_key(tool_id) {
-                return Err(ToolError::ToolNotFound(tool_id.to_string()));
-            }
-        }
-
-        // Check current state
-        {
-            let states = self.states.read().await;
-            match states.get(tool_id) {
-                Some(ToolState::Error) => {} // Expected state
-                Some(ToolState::Recovering) => {
-                    return Err(ToolError::InvalidState(format!(
-                        "Tool '{}' is already recovering",
-                        tool_id
-                    )));
-                }
-                Some(ToolState::Unregistered) => {
-                    return Err(ToolError::ToolNotFound(format!(
-                        "Tool '{}' is unregistered",
-                        tool_id
-                    )));
-                }
-                _ => {
-                    return Err(ToolError::InvalidState(format!(
-                        "Tool '{}' is not in error state",
-                        tool_id
-                    )));
-                }
-            }
-        }
-
-        // Update state to Recovering
-        {
-            let mut states = self.states.write().await;
-            states.insert(tool_id.to_string(), ToolState::Recovering);
-        }
+        self.transition(tool_id, ToolEvent::Recover).await?;
 
         // Get the recovery strategy
         let strategy = if let Some(recovery_hook) = self.recovery_hook.as_ref() {
@@ -1537,12 +1734,6 @@ impl ToolManager {
                 // Reset the tool to its initial state
                 self.reset_tool(tool_id).await?;
 
-                // Update state back to Registered
-                {
-                    let mut states = self.states.write().await;
-                    states.insert(tool_id.to_string(), ToolState::Registered);
-                }
-
                 info!("Tool '{}' has been reset", tool_id);
                 Ok(())
             }
@@ -1554,23 +1745,15 @@ impl ToolManager {
                     Ok(())
                 } else {
                     error!("Failed to terminate tool '{}': {:?}", tool_id, result);
-                    // Set state back to Error
-                    {
-                        let mut states = self.states.write().await;
-                        states.insert(tool_id.to_string(), ToolState::Error);
-                    }
-                    Err(ToolError::UnregistrationFailed(format!(
+                    let error = ToolError::UnregistrationFailed(format!(
                         "Failed to terminate tool '{}' during recovery",
                         tool_id
-                    )))
+                    ));
+                    Err(self.fail(tool_id, error).await)
                 }
             }
             RecoveryStrategy::Continue => {
-                // Just update the state back to Registered and continue
-                {
-                    let mut states = self.states.write().await;
-                    states.insert(tool_id.to_string(), ToolState::Registered);
-                }
+                self.transition(tool_id, ToolEvent::Recovered).await?;
 
                 info!("Tool '{}' recovery ignored, continuing execution", tool_id);
                 Ok(())
@@ -1718,5 +1901,366 @@ impl ToolManager {
 /// ```
 #[cfg(test)]
 mod tests {
-    // Test module implementation
+    use super::*;
+
+    /// Executor whose start always fails
+    #[derive(Debug)]
+    struct FailingStartExecutor;
+
+    #[async_trait]
+    impl ToolExecutor for FailingStartExecutor {
+        async fn execute(&self, _context: ToolContext) -> Result<ToolExecutionResult, ToolError> {
+            Err(ToolError::ExecutionError("not runnable".to_string()))
+        }
+
+        fn get_tool_id(&self) -> String {
+            "failing".to_string()
+        }
+
+        fn get_capabilities(&self) -> Vec<String> {
+            Vec::new()
+        }
+
+        async fn start(&self) -> Result<(), ToolError> {
+            Err(ToolError::ExecutionError("no process".to_string()))
+        }
+    }
+
+    async fn manager_with(tool_id: &str) -> ToolManager {
+        let manager = ToolManager::new();
+        let tool = Tool::builder().id(tool_id).name(tool_id).build();
+        manager
+            .register_tool(tool, BasicToolExecutor::new(tool_id))
+            .await
+            .unwrap();
+        manager
+    }
+
+    #[tokio::test]
+    async fn test_lifecycle_methods_publish_transitions() {
+        let manager = manager_with("calc").await;
+        let mut transitions = manager.subscribe_transitions();
+
+        manager.start_tool("calc").await.unwrap();
+        manager.pause_tool("calc").await.unwrap();
+        manager.resume_tool("calc").await.unwrap();
+        manager.reset_tool("calc").await.unwrap();
+
+        let mut events = Vec::new();
+        while let Ok(transition) = transitions.try_recv() {
+            assert_eq!(transition.tool_id, "calc");
+            assert_eq!(
+                validate_transition("calc", transition.from, transition.event.unwrap()).unwrap(),
+                transition.to
+            );
+            events.push(transition.event.unwrap());
+        }
+        assert_eq!(
+            events,
+            [
+                ToolEvent::Start,
+                ToolEvent::Started,
+                ToolEvent::Pause,
+                ToolEvent::Paused,
+                ToolEvent::Resume,
+                ToolEvent::Resumed,
+                ToolEvent::Stop,
+                ToolEvent::Stopped,
+                ToolEvent::Reset,
+            ]
+        );
+        assert_eq!(
+            manager.get_tool_state("calc").await,
+            Some(ToolState::Registered)
+        );
+    }
+
+    #[tokio::test]
+    async fn test_invalid_transitions_leave_state_unchanged() {
+        let manager = manager_with("calc").await;
+
+        let err = manager.resume_tool("calc").await.unwrap_err();
+        assert!(matches!(
+            err,
+            ToolError::InvalidStateTransition {
+                from_state: ToolState::Registered,
+                to_state: ToolState::Resuming,
+                ..
+            }
+        ));
+        assert_eq!(
+            manager.get_tool_state("calc").await,
+            Some(ToolState::Registered)
+        );
+
+        manager.start_tool("calc").await.unwrap();
+        assert!(manager.start_tool("calc").await.is_err());
+        assert!(manager.recover_tool("calc").await.is_err());
+        assert_eq!(
+            manager.get_tool_state("calc").await,
+            Some(ToolState::Started)
+        );
+
+        assert!(matches!(
+            manager.start_tool("missing").await,
+            Err(ToolError::ExecutorNotFound(_))
+        ));
+        assert!(matches!(
+            manager.activate_tool("missing").await,
+            Err(ToolError::ToolNotFound(_))
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_failed_start_moves_to_error_and_recovers() {
+        let manager = ToolManager::new();
+        let tool = Tool::builder().id("failing").name("Failing").build();
+        manager
+            .register_tool(tool, FailingStartExecutor)
+            .await
+            .unwrap();
+        let mut transitions = manager.subscribe_transitions();
+
+        assert!(matches!(
+            manager.start_tool("failing").await,
+            Err(ToolError::ExecutionError(_))
+        ));
+        assert_eq!(
+            manager.get_tool_state("failing").await,
+            Some(ToolState::Error)
+        );
+
+        manager.recover_tool("failing").await.unwrap();
+        assert_eq!(
+            manager.get_tool_state("failing").await,
+            Some(ToolState::Registered)
+        );
+
+        let path: Vec<_> = std::iter::from_fn(|| transitions.try_recv().ok())
+            .map(|transition| (transition.event.unwrap(), transition.to))
+            .collect();
+        assert_eq!(
+            path,
+            [
+                (ToolEvent::Start, ToolState::Starting),
+                (ToolEvent::Fail, ToolState::Error),
+                (ToolEvent::Recover, ToolState::Recovering),
+                (ToolEvent::Reset, ToolState::Registered),
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_execute_routes_capability_versions() {
+        let capability = |version, deprecation| Capability {
+            name: "search".to_string(),
+            description: "Searches the index".to_string(),
+            parameters: Vec::new(),
+            return_type: None,
+            version,
+            deprecation,
+        };
+        let tool = Tool::builder()
+            .id("index")
+            .name("Index")
+            .capability(capability(
+                1,
+                Some(Deprecation::new("use v2").with_replacement(2)),
+            ))
+            .capability(capability(2, None))
+            .capability(capability(3, None))
+            .build();
+        let mut executor = BasicToolExecutor::new("index");
+        executor.register_handler("search", |context| {
+            Ok(serde_json::json!({
+                "version": context.capability_version,
+                "params": context.parameters,
+            }))
+        });
+
+        let manager = ToolManager::new();
+        manager.register_tool(tool, executor).await.unwrap();
+        manager
+            .register_up_conversion("index", "search", 1, 2, |params| {
+                Ok(serde_json::json!({ "query": params["q"] }))
+            })
+            .await
+            .unwrap();
+
+        let output = |result: ToolExecutionResult| result.output.unwrap();
+        let newest = manager
+            .execute_tool("index", "search", serde_json::json!({}), None)
+            .await
+            .unwrap();
+        assert_eq!(output(newest)["version"], 3);
+
+        let by_param = manager
+            .execute_tool(
+                "index",
+                "search",
+                serde_json::json!({ "_version": 2 }),
+                None,
+            )
+            .await
+            .unwrap();
+        assert_eq!(
+            output(by_param),
+            serde_json::json!({ "version": 2, "params": {} })
+        );
+
+        let converted = manager
+            .execute_tool_version(
+                "index",
+                "search",
+                Some(1),
+                serde_json::json!({ "q": "rust", "_version": 3 }),
+                None,
+            )
+            .await
+            .unwrap();
+        assert_eq!(
+            output(converted),
+            serde_json::json!({ "version": 2, "params": { "query": "rust" } })
+        );
+
+        assert!(matches!(
+            manager
+                .execute_tool(
+                    "index",
+                    "search",
+                    serde_json::json!({ "_version": 7 }),
+                    None
+                )
+                .await,
+            Err(ToolError::CapabilityVersionNotFound { version: 7, .. })
+        ));
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_load_from_directory_registers_reloads_and_reports() {
+        let dir = tempfile::tempdir().unwrap();
+        let write =
+            |name: &str, contents: &str| std::fs::write(dir.path().join(name), contents).unwrap();
+        write(
+            "echo.toml",
+            "[tool]\nid = \"echo\"\nname = \"Echo\"\nversion = \"1.0.0\"\n\n\
+             [[tool.capabilities]]\nname = \"echo\"\n\n\
+             [executor]\nkind = \"process\"\ncommand = \"cat\"\n",
+        );
+        write(
+            "remote.json",
+            r#"{"tool": {"id": "remote", "name": "Remote", "version": "1.0.0"},
+                "executor": {"kind": "remote_mcp", "url": "http://127.0.0.1:9/mcp"}}"#,
+        );
+        write(
+            "plugin.toml",
+            "[tool]\nid = \"wasm\"\nname = \"Wasm\"\nversion = \"1.0.0\"\n\n\
+             [executor]\nkind = \"wasm\"\nmodule = \"tool.wasm\"\n",
+        );
+        write("broken.json", "{");
+        write("README.md", "not a manifest");
+
+        let manager = ToolManager::new();
+        let report = manager.load_from_directory(dir.path()).await.unwrap();
+        assert_eq!(report.registered, ["echo", "remote"]);
+        let failed: Vec<_> = report
+            .errors
+            .iter()
+            .map(|e| e.path.file_name().unwrap().to_owned())
+            .collect();
+        assert_eq!(failed, ["broken.json", "plugin.toml"]);
+        assert!(report.errors[1].message.contains("WebAssembly"));
+
+        let result = manager
+            .execute_tool("echo", "echo", serde_json::json!({ "text": "hi" }), None)
+            .await
+            .unwrap();
+        assert_eq!(result.status, ExecutionStatus::Success);
+        assert_eq!(result.output.unwrap()["parameters"]["text"], "hi");
+
+        // Reload after changing one manifest, breaking another and removing a third
+        write(
+            "remote.json",
+            r#"{"tool": {"id": "remote", "name": "Remote", "version": "2.0.0"},
+                "executor": {"kind": "remote_mcp", "url": "http://127.0.0.1:9/mcp"}}"#,
+        );
+        std::fs::remove_file(dir.path().join("echo.toml")).unwrap();
+        let report = manager.load_from_directory(dir.path()).await.unwrap();
+        assert_eq!(report.reloaded, ["remote"]);
+        assert_eq!(report.removed, ["echo"]);
+        assert_eq!(report.errors.len(), 2);
+        assert!(manager.get_tool("echo").await.is_none());
+        assert_eq!(manager.get_tool("remote").await.unwrap().version, "2.0.0");
+
+        write("remote.json", "{");
+        let report = manager.load_from_directory(dir.path()).await.unwrap();
+        assert!(report.removed.is_empty());
+        assert!(manager.get_tool("remote").await.is_some());
+
+        assert!(manager
+            .load_from_directory(dir.path().join("missing"))
+            .await
+            .is_err());
+    }
+
+    #[tokio::test]
+    async fn test_update_tool_state_publishes_without_event() {
+        let manager = manager_with("calc").await;
+        let mut transitions = manager.subscribe_transitions();
+
+        manager
+            .update_tool_state("calc", ToolState::Error)
+            .await
+            .unwrap();
+
+        let transition = transitions.try_recv().unwrap();
+        assert_eq!(
+            (transition.from, transition.event, transition.to),
+            (ToolState::Registered, None, ToolState::Error)
+        );
+    }
+
+    #[tokio::test]
+    async fn test_executions_are_recorded_in_history() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("history.json");
+        let mut executor = BasicToolExecutor::new("flaky");
+        executor.register_handler("run", |context| match context.parameters.get("fail") {
+            Some(_) => Err(ToolError::ExecutionError("flaked".to_string())),
+            None => Ok(serde_json::json!("ok")),
+        });
+        let manager = ToolManager::builder()
+            .history_capacity(2)
+            .history_path(&path)
+            .build();
+        let tool = Tool::builder().id("flaky").name("Flaky").build();
+        manager.register_tool(tool, executor).await.unwrap();
+
+        let run = |params| manager.execute_tool("flaky", "run", params, None);
+        run(serde_json::json!({ "fail": true })).await.unwrap();
+        run(serde_json::json!({})).await.unwrap();
+        assert!(run(serde_json::json!({}))
+            .await
+            .unwrap()
+            .error_message
+            .is_none());
+        assert!(manager
+            .execute_tool("flaky", "missing", serde_json::json!({}), None)
+            .await
+            .is_err());
+
+        let history = manager.get_tool_history("flaky").await;
+        assert_eq!(history.len(), 2);
+        assert_eq!(history[0].capability, "missing");
+        assert_eq!(history[1].status, ExecutionStatus::Success);
+        let last_error = manager.get_last_error("flaky").await.unwrap();
+        assert_eq!(last_error.request_id, history[0].request_id);
+
+        let persisted = ExecutionHistory::load(&path, 10).unwrap();
+        assert_eq!(persisted.get("flaky"), history);
+
+        manager.unregister_tool("flaky").await.unwrap();
+        assert!(manager.get_tool_history("flaky").await.is_empty());
+    }
 }
@@ -0,0 +1,276 @@
+//! Managed interpreter environments for script tools
+//!
+//! Environments are created once per (language, lockfile) pair and cached on
+//! disk under a root directory. A marker file is written after a successful
+//! setup so that later runs can reuse the environment without reinstalling
+//! dependencies.
+
+use sha2::{Digest, Sha256};
+use squirrel_commands::environments::EnvironmentDefinition;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::process::Command;
+use tokio::sync::{Mutex, OnceCell};
+use tracing::{debug, info};
+
+use super::ScriptLanguage;
+use crate::tool::arguments::scrub_environment;
+use crate::tool::ToolError;
+
+/// Name of the marker file written once an environment is ready
+const READY_MARKER: &str = ".squirrel-env-ready";
+
+/// A ready-to-use interpreter environment
+#[derive(Debug, Clone)]
+pub struct ScriptEnvironment {
+    /// Language of the environment
+    pub language: ScriptLanguage,
+    /// Cache key derived from the language and lockfile contents
+    pub key: String,
+    /// Root directory of the environment
+    pub path: PathBuf,
+    /// Interpreter used to run scripts in this environment
+    pub interpreter: PathBuf,
+    /// Environment variables to set when running scripts
+    pub env_vars: HashMap<String, String>,
+}
+
+impl ScriptEnvironment {
+    /// Directory where inline scripts are written before execution
+    pub fn scripts_dir(&self) -> PathBuf {
+        self.path.join("scripts")
+    }
+}
+
+/// Creates and caches interpreter environments for one language
+#[derive(Debug)]
+pub struct EnvironmentManager {
+    /// Language handled by this manager
+    language: ScriptLanguage,
+    /// Base interpreter used to bootstrap environments
+    base_interpreter: PathBuf,
+    /// Root directory under which environments are created
+    root: PathBuf,
+    /// Timeout for environment creation and dependency installation
+    setup_timeout: Duration,
+    /// Host variables setup commands see
+    environment: EnvironmentDefinition,
+    /// Environments prepared or being prepared by this process, one cell per key
+    cache: Mutex<HashMap<String, Arc<OnceCell<ScriptEnvironment>>>>,
+}
+
+impl EnvironmentManager {
+    /// Creates a new environment manager using the language's default interpreter
+    pub fn new(language: ScriptLanguage, root: impl Into<PathBuf>) -> Self {
+        Self {
+            language,
+            base_interpreter: PathBuf::from(language.default_interpreter()),
+            root: root.into(),
+            setup_timeout: Duration::from_secs(600),
+            environment: EnvironmentDefinition::default(),
+            cache: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Sets the base interpreter used to bootstrap environments
+    pub fn with_interpreter(mut self, interpreter: impl Into<PathBuf>) -> Self {
+        self.base_interpreter = interpreter.into();
+        self
+    }
+
+    /// Sets the timeout for environment setup
+    pub fn with_setup_timeout(mut self, timeout: Duration) -> Self {
+        self.setup_timeout = timeout;
+        self
+    }
+
+    /// Sets the host variables setup commands see, e.g. to let proxy
+    /// settings reach the package installer
+    pub fn with_environment(mut self, environment: EnvironmentDefinition) -> Self {
+        self.environment = environment;
+        self
+    }
+
+    /// Returns the language handled by this manager
+    pub fn language(&self) -> ScriptLanguage {
+        self.language
+    }
+
+    /// Computes the cache key for an optional lockfile
+    pub async fn environment_key(&self, lockfile: Option<&Path>) -> Result<String, ToolError> {
+        let mut hasher = Sha256::new();
+        hasher.update(self.language.name().as_bytes());
+        hasher.update(self.base_interpreter.to_string_lossy().as_bytes());
+        if let Some(lockfile) = lockfile {
+            let contents = tokio::fs::read(lockfile).await.map_err(|e| {
+                ToolError::ValidationFailed(format!(
+                    "Cannot read lockfile {}: {}",
+                    lockfile.display(),
+                    e
+                ))
+            })?;
+            hasher.update(&contents);
+        }
+        let digest = hex::encode(hasher.finalize());
+        Ok(digest[..16].to_string())
+    }
+
+    /// Returns an environment for the lockfile, creating it if needed
+    pub async fn ensure(&self, lockfile: Option<&Path>) -> Result<ScriptEnvironment, ToolError> {
+        let key = self.environment_key(lockfile).await?;
+
+        // Concurrent requests for the same environment wait on its cell so
+        // dependencies are installed once; the map lock is released before
+        // setup, so other environments are not held up. A failed setup
+        // leaves the cell empty for the next request to retry.
+        let cell = self.cache.lock().await.entry(key.clone()).or_default().clone();
+        cell.get_or_try_init(|| self.prepare(&key, lockfile)).await.cloned()
+    }
+
+    /// Creates the environment for a key on disk unless it is already ready
+    async fn prepare(&self, key: &str, lockfile: Option<&Path>) -> Result<ScriptEnvironment, ToolError> {
+        let path = self.root.join(self.language.name()).join(key);
+        let env = self.describe(key, &path);
+
+        if path.join(READY_MARKER).exists() {
+            debug!("Reusing cached {} environment {}", self.language, key);
+        } else {
+            info!("Creating {} environment {}", self.language, key);
+            tokio::fs::create_dir_all(&path)
+                .await
+                .map_err(|e| ToolError::ResourceError(format!("Cannot create environment: {}", e)))?;
+            self.create(&env).await?;
+            if let Some(lockfile) = lockfile {
+                self.install_lockfile(&env, lockfile).await?;
+            }
+            tokio::fs::write(path.join(READY_MARKER), key)
+                .await
+                .map_err(|e| ToolError::ResourceError(format!("Cannot mark environment ready: {}", e)))?;
+        }
+
+        Ok(env)
+    }
+
+    /// Removes a cached environment from disk
+    pub async fn remove(&self, key: &str) -> Result<(), ToolError> {
+        self.cache.lock().await.remove(key);
+        let path = self.root.join(self.language.name()).join(key);
+        if path.exists() {
+            tokio::fs::remove_dir_all(&path)
+                .await
+                .map_err(|e| ToolError::ResourceError(format!("Cannot remove environment: {}", e)))?;
+        }
+        Ok(())
+    }
+
+    /// Builds the environment description for a key without touching disk
+    fn describe(&self, key: &str, path: &Path) -> ScriptEnvironment {
+        let mut env_vars = HashMap::new();
+        let interpreter = match self.language {
+            ScriptLanguage::Python => {
+                env_vars.insert("VIRTUAL_ENV".to_string(), path.display().to_string());
+                env_vars.insert("PYTHONNOUSERSITE".to_string(), "1".to_string());
+                if cfg!(windows) {
+                    path.join("Scripts").join("python.exe")
+                } else {
+                    path.join("bin").join("python")
+                }
+            }
+            ScriptLanguage::R => {
+                let library = path.join("library");
+                env_vars.insert("R_LIBS_USER".to_string(), library.display().to_string());
+                env_vars.insert("R_LIBS".to_string(), library.display().to_string());
+                self.base_interpreter.clone()
+            }
+        };
+
+        ScriptEnvironment {
+            language: self.language,
+            key: key.to_string(),
+            path: path.to_path_buf(),
+            interpreter,
+            env_vars,
+        }
+    }
+
+    /// Creates the bare environment
+    async fn create(&self, env: &ScriptEnvironment) -> Result<(), ToolError> {
+        match self.language {
+            ScriptLanguage::Python => {
+                let mut command = self.setup_command(&self.base_interpreter)?;
+                command.arg("-m").arg("venv").arg(&env.path);
+                self.run_setup(command, "create virtual environment").await
+            }
+            ScriptLanguage::R => tokio::fs::create_dir_all(env.path.join("library"))
+                .await
+                .map_err(|e| ToolError::ResourceError(format!("Cannot create R library: {}", e))),
+        }
+    }
+
+    /// Installs the dependencies pinned in a lockfile
+    ///
+    /// Python lockfiles use the pip requirements format. R lockfiles may be
+    /// an `renv.lock` file or a plain list of package names, one per line.
+    async fn install_lockfile(&self, env: &ScriptEnvironment, lockfile: &Path) -> Result<(), ToolError> {
+        let mut command = match self.language {
+            ScriptLanguage::Python => {
+                let mut command = self.setup_command(&env.interpreter)?;
+                command
+                    .args(["-m", "pip", "install", "--disable-pip-version-check", "-r"])
+                    .arg(lockfile);
+                command
+            }
+            ScriptLanguage::R => {
+                let lock = r_string(&lockfile.display().to_string());
+                let library = r_string(&env.path.join("library").display().to_string());
+                let expression = if lockfile.extension().and_then(|e| e.to_str()) == Some("lock") {
+                    format!("renv::restore(lockfile = {}, library = {}, prompt = FALSE)", lock, library)
+                } else {
+                    format!(
+                        "pkgs <- trimws(readLines({})); pkgs <- pkgs[nzchar(pkgs) & !startsWith(pkgs, '#')]; \
+                         install.packages(pkgs, lib = {}, repos = 'https://cloud.r-project.org')",
+                        lock, library
+                    )
+                };
+                let mut command = self.setup_command(&self.base_interpreter)?;
+                command.arg("-e").arg(expression);
+                command
+            }
+        };
+        command.envs(&env.env_vars);
+        self.run_setup(command, "install dependencies").await
+    }
+
+    /// Starts a setup command running `program` in the manager's environment
+    fn setup_command(&self, program: &Path) -> Result<Command, ToolError> {
+        let mut command = Command::new(program);
+        scrub_environment(&mut command, self.language.name(), &self.environment)?;
+        Ok(command)
+    }
+
+    /// Runs a setup command with the configured timeout
+    async fn run_setup(&self, mut command: Command, action: &str) -> Result<(), ToolError> {
+        command.kill_on_drop(true);
+        let output = tokio::time::timeout(self.setup_timeout, command.output())
+            .await
+            .map_err(|_| ToolError::ResourceError(format!("Timed out trying to {}", action)))?
+            .map_err(|e| ToolError::ResourceError(format!("Failed to {}: {}", action, e)))?;
+
+        if output.status.success() {
+            Ok(())
+        } else {
+            Err(ToolError::ResourceError(format!(
+                "Failed to {}: {}",
+                action,
+                String::from_utf8_lossy(&output.stderr).trim()
+            )))
+        }
+    }
+}
+
+/// Quotes a value as an R string literal
+fn r_string(value: &str) -> String {
+    format!("'{}'", value.replace('\\', "\\\\").replace('\'', "\\'"))
+}
@@ -0,0 +1,705 @@
+//! Script runner tools for Python and R
+//!
+//! This module provides built-in tools that execute Python or R scripts in
+//! managed environments. Environments are created from an optional lockfile
+//! and cached between runs, script output is captured into the
+//! `ToolExecutionResult`, and execution is bounded by the `ResourceLimits`
+//! registered with the cleanup module's `ResourceManager`.
+//!
+//! When the executor has a [`Vfs`], the `path` and `lockfile` parameters are
+//! virtual paths such as `workspace://analysis.py`, and files outside the
+//! mounted roots cannot be reached.
+//!
+//! Scripts and environment setup do not inherit the host environment, only
+//! the allowlisted variables of the executor's environment definition.
+//!
+//! The Python tool is available with the `python-tools` feature and the R tool
+//! with the `r-tools` feature.
+
+mod environment;
+
+pub use environment::{EnvironmentManager, ScriptEnvironment};
+
+use async_trait::async_trait;
+use chrono::Utc;
+use serde_json::{json, Value as JsonValue};
+use std::fmt;
+use std::path::{Path, PathBuf};
+use std::process::Stdio;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio::io::{AsyncRead, AsyncReadExt};
+use tracing::{info, warn};
+use uuid::Uuid;
+
+use squirrel_commands::environments::EnvironmentDefinition;
+use squirrel_core::vfs::{Access, Vfs, VfsError};
+
+use crate::tool::arguments::{scrub_environment, ArgumentPolicy, ProcessArgs};
+use crate::tool::cleanup::{ResourceLimits, ResourceManager};
+use crate::tool::{
+    Capability, ExecutionStatus, Parameter, ParameterType, ReturnType, Tool, ToolContext,
+    ToolError, ToolExecutionResult, ToolExecutor,
+};
+
+/// Capability that runs a script
+pub const RUN_SCRIPT_CAPABILITY: &str = "run_script";
+
+/// Capability that only prepares an environment
+pub const PREPARE_ENVIRONMENT_CAPABILITY: &str = "prepare_environment";
+
+/// Scripting language supported by the runner
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ScriptLanguage {
+    /// Python, run inside a virtual environment
+    Python,
+    /// R, run with a private package library
+    R,
+}
+
+impl ScriptLanguage {
+    /// Short lowercase name of the language
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Python => "python",
+            Self::R => "r",
+        }
+    }
+
+    /// Interpreter used when none is configured
+    pub fn default_interpreter(&self) -> &'static str {
+        match self {
+            Self::Python => {
+                if cfg!(windows) {
+                    "python"
+                } else {
+                    "python3"
+                }
+            }
+            Self::R => "Rscript",
+        }
+    }
+
+    /// File extension for script files
+    pub fn extension(&self) -> &'static str {
+        match self {
+            Self::Python => "py",
+            Self::R => "R",
+        }
+    }
+}
+
+impl fmt::Display for ScriptLanguage {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.name())
+    }
+}
+
+/// Builds the tool definition for a script runner
+pub fn script_tool(language: ScriptLanguage) -> Tool {
+    let id = format!("{}-script", language.name());
+    Tool::builder()
+        .id(id.clone())
+        .name(id)
+        .description(format!("Runs {} scripts in managed environments", language))
+        .security_level(7)
+        .capability(Capability {
+            name: RUN_SCRIPT_CAPABILITY.to_string(),
+            description: format!("Run a {} script and capture its output", language),
+            parameters: vec![
+                Parameter {
+                    name: "script".to_string(),
+                    description: "Inline script source".to_string(),
+                    parameter_type: ParameterType::String,
+                    required: false,
+                },
+                Parameter {
+                    name: "path".to_string(),
+                    description: "Path to a script file, used when no inline script is given; \
+                        a virtual path such as workspace://run.py when a VFS is configured"
+                        .to_string(),
+                    parameter_type: ParameterType::String,
+                    required: false,
+                },
+                Parameter {
+                    name: "args".to_string(),
+                    description: "Arguments passed to the script".to_string(),
+                    parameter_type: ParameterType::Array,
+                    required: false,
+                },
+                Parameter {
+                    name: "lockfile".to_string(),
+                    description: "Lockfile describing the environment dependencies".to_string(),
+                    parameter_type: ParameterType::String,
+                    required: false,
+                },
+                Parameter {
+                    name: "timeout_ms".to_string(),
+                    description: "Timeout in milliseconds, capped by the tool's CPU time limit"
+                        .to_string(),
+                    parameter_type: ParameterType::Number,
+                    required: false,
+                },
+            ],
+            return_type: Some(ReturnType {
+                description: "Captured output of the script".to_string(),
+                schema: json!({
+                    "type": "object",
+                    "properties": {
+                        "stdout": { "type": "string" },
+                        "stderr": { "type": "string" },
+                        "exit_code": { "type": ["integer", "null"] },
+                        "truncated": { "type": "boolean" },
+                        "environment": { "type": "string" }
+                    }
+                }),
+            }),
+            version: 1,
+            deprecation: None,
+        })
+        .capability(Capability {
+            name: PREPARE_ENVIRONMENT_CAPABILITY.to_string(),
+            description: "Create or reuse the environment for a lockfile".to_string(),
+            parameters: vec![Parameter {
+                name: "lockfile".to_string(),
+                description: "Lockfile describing the environment dependencies".to_string(),
+                parameter_type: ParameterType::String,
+                required: false,
+            }],
+            return_type: None,
+            version: 1,
+            deprecation: None,
+        })
+        .build()
+}
+
+/// Builds the Python script tool definition
+#[cfg(feature = "python-tools")]
+pub fn python_tool() -> Tool {
+    script_tool(ScriptLanguage::Python)
+}
+
+/// Builds the R script tool definition
+#[cfg(feature = "r-tools")]
+pub fn r_tool() -> Tool {
+    script_tool(ScriptLanguage::R)
+}
+
+/// Executes scripts for one language in managed environments
+pub struct ScriptToolExecutor {
+    /// Tool ID this executor is associated with
+    tool_id: String,
+    /// Environment manager for the language
+    environments: Arc<EnvironmentManager>,
+    /// Resource manager enforcing limits for the tool
+    resource_manager: Arc<dyn ResourceManager>,
+    /// Limits applied to each run
+    limits: ResourceLimits,
+    /// Host variables scripts see
+    environment: EnvironmentDefinition,
+    /// Working directory for script processes
+    working_dir: Option<PathBuf>,
+    /// Virtual filesystem file parameters are resolved in
+    vfs: Option<Arc<Vfs>>,
+}
+
+impl fmt::Debug for ScriptToolExecutor {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ScriptToolExecutor")
+            .field("tool_id", &self.tool_id)
+            .field("language", &self.environments.language())
+            .field("limits", &self.limits)
+            .field("working_dir", &self.working_dir)
+            .field("vfs", &self.vfs.is_some())
+            .finish()
+    }
+}
+
+impl ScriptToolExecutor {
+    /// Creates a new executor and registers its limits with the resource manager
+    pub async fn new(
+        environments: Arc<EnvironmentManager>,
+        resource_manager: Arc<dyn ResourceManager>,
+        limits: ResourceLimits,
+    ) -> Result<Self, ToolError> {
+        let tool_id = format!("{}-script", environments.language().name());
+        resource_manager
+            .initialize_tool(&tool_id, limits.clone(), limits.clone())
+            .await?;
+        Ok(Self {
+            tool_id,
+            environments,
+            resource_manager,
+            limits,
+            environment: EnvironmentDefinition::default(),
+            working_dir: None,
+            vfs: None,
+        })
+    }
+
+    /// Sets the host variables scripts see, which default to
+    /// [`DEFAULT_ENV_ALLOWLIST`](squirrel_commands::environments::DEFAULT_ENV_ALLOWLIST)
+    pub fn with_environment(mut self, environment: EnvironmentDefinition) -> Self {
+        self.environment = environment;
+        self
+    }
+
+    /// Sets the working directory for script processes
+    pub fn with_working_dir(mut self, dir: impl Into<PathBuf>) -> Self {
+        self.working_dir = Some(dir.into());
+        self
+    }
+
+    /// Resolves file parameters as virtual paths in `vfs`
+    pub fn with_vfs(mut self, vfs: Arc<Vfs>) -> Self {
+        self.vfs = Some(vfs);
+        self
+    }
+
+    /// Reads an optional file path parameter, resolving it in the VFS if set
+    fn file_param(&self, ctx: &ToolContext, name: &str) -> Result<Option<PathBuf>, ToolError> {
+        let Some(path) = ctx.parameters.get(name).and_then(JsonValue::as_str) else {
+            return Ok(None);
+        };
+        let Some(vfs) = &self.vfs else {
+            return Ok(Some(PathBuf::from(path)));
+        };
+        vfs.resolve(path, Access::Read).map(Some).map_err(|e| match e {
+            VfsError::InvalidPath(_) | VfsError::UnknownRoot(_) => {
+                ToolError::ValidationFailed(format!("'{}': {}", name, e))
+            }
+            VfsError::Traversal(_) | VfsError::PermissionDenied { .. } => {
+                ToolError::SecurityViolation(e.to_string())
+            }
+            VfsError::Io { .. } => ToolError::ResourceError(e.to_string()),
+        })
+    }
+
+    /// Runs a script and returns its captured output
+    async fn run_script(&self, ctx: &ToolContext) -> Result<(ExecutionStatus, JsonValue), ToolError> {
+        if !self.resource_manager.check_limits(&self.tool_id).await? {
+            return Err(ToolError::ResourceError(format!(
+                "Tool '{}' is over its resource limits",
+                self.tool_id
+            )));
+        }
+
+        let inline = ctx.parameters.get("script").and_then(JsonValue::as_str);
+        let file = self.file_param(ctx, "path")?;
+        if inline.is_none() && file.is_none() {
+            return Err(ToolError::ValidationFailed(
+                "Either 'script' or 'path' must be provided".to_string(),
+            ));
+        }
+
+        let args: Vec<String> = match ctx.parameters.get("args") {
+            None | Some(JsonValue::Null) => Vec::new(),
+            Some(JsonValue::Array(values)) => values
+                .iter()
+                .map(|v| match v {
+                    JsonValue::String(s) => s.clone(),
+                    other => other.to_string(),
+                })
+                .collect(),
+            Some(_) => {
+                return Err(ToolError::ValidationFailed(
+                    "'args' must be an array".to_string(),
+                ))
+            }
+        };
+
+        let lockfile = self.file_param(ctx, "lockfile")?;
+        let env = self.environments.ensure(lockfile.as_deref()).await?;
+        let script_path = match inline {
+            Some(source) => self.write_inline_script(&env, &ctx.request_id, source).await?,
+            None => file.unwrap_or_default(),
+        };
+
+        let limit = Duration::from_millis(self.limits.max_cpu_time_ms);
+        let timeout = ctx
+            .parameters
+            .get("timeout_ms")
+            .and_then(JsonValue::as_u64)
+            .map(Duration::from_millis)
+            .map_or(limit, |requested| requested.min(limit));
+
+        // Script arguments come from the request, and may be options of the script
+        let mut line = ProcessArgs::new(env.interpreter.to_string_lossy()).with_policy(ArgumentPolicy {
+            allow_options: true,
+            ..ArgumentPolicy::default()
+        });
+        line.arg(script_path.to_string_lossy());
+        for arg in args {
+            line.request_arg("args", arg)?;
+        }
+        let mut command = line.command()?;
+        scrub_environment(&mut command, &self.tool_id, &self.environment)?;
+        command
+            .envs(&env.env_vars)
+            .stdin(Stdio::null())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .kill_on_drop(true);
+        if let Some(dir) = &self.working_dir {
+            command.current_dir(dir);
+        }
+
+        let mut child = command.spawn().map_err(|e| ToolError::ExecutionFailed {
+            tool_id: self.tool_id.clone(),
+            reason: format!("Failed to start {}: {}", env.interpreter.display(), e),
+        })?;
+
+        let max_bytes = self.limits.max_memory_bytes;
+        let stdout = child.stdout.take().map(|s| tokio::spawn(read_capped(s, max_bytes)));
+        let stderr = child.stderr.take().map(|s| tokio::spawn(read_capped(s, max_bytes)));
+
+        let (status, exit_code) = match tokio::time::timeout(timeout, child.wait()).await {
+            Ok(Ok(exit)) => {
+                let status = if exit.success() {
+                    ExecutionStatus::Success
+                } else {
+                    ExecutionStatus::Failure
+                };
+                (status, exit.code())
+            }
+            Ok(Err(e)) => {
+                return Err(ToolError::ExecutionFailed {
+                    tool_id: self.tool_id.clone(),
+                    reason: e.to_string(),
+                })
+            }
+            Err(_) => {
+                warn!("Script for tool {} timed out after {:?}", self.tool_id, timeout);
+                if let Err(e) = child.kill().await {
+                    warn!("Failed to kill timed out script: {}", e);
+                }
+                (ExecutionStatus::Timeout, None)
+            }
+        };
+
+        let (stdout, stdout_truncated) = join_capture(stdout).await;
+        let (stderr, stderr_truncated) = join_capture(stderr).await;
+
+        Ok((
+            status,
+            json!({
+                "stdout": stdout,
+                "stderr": stderr,
+                "exit_code": exit_code,
+                "truncated": stdout_truncated || stderr_truncated,
+                "environment": env.key,
+            }),
+        ))
+    }
+
+    /// Writes an inline script into the environment's scripts directory
+    async fn write_inline_script(
+        &self,
+        env: &ScriptEnvironment,
+        request_id: &str,
+        source: &str,
+    ) -> Result<PathBuf, ToolError> {
+        let dir = env.scripts_dir();
+        tokio::fs::create_dir_all(&dir)
+            .await
+            .map_err(|e| ToolError::ResourceError(format!("Cannot create scripts dir: {}", e)))?;
+        let file_name: String = request_id
+            .chars()
+            .filter(|c| c.is_ascii_alphanumeric() || *c == '-' || *c == '_')
+            .collect();
+        // Request IDs may be empty or reduce to the same name, so add a
+        // unique part to keep concurrent requests from sharing a script
+        let path = dir.join(format!("{}-{}.{}", file_name, Uuid::new_v4(), env.language.extension()));
+        tokio::fs::write(&path, source)
+            .await
+            .map_err(|e| ToolError::ResourceError(format!("Cannot write script: {}", e)))?;
+        Ok(path)
+    }
+}
+
+/// Reads a stream, keeping at most `max_bytes` and discarding the rest
+async fn read_capped<R: AsyncRead + Unpin>(mut reader: R, max_bytes: u64) -> (Vec<u8>, bool) {
+    let cap = usize::try_from(max_bytes).unwrap_or(usize::MAX);
+    let mut captured = Vec::new();
+    let mut truncated = false;
+    let mut buf = [0u8; 8192];
+    loop {
+        match reader.read(&mut buf).await {
+            Ok(0) | Err(_) => break,
+            Ok(n) => {
+                let room = cap.saturating_sub(captured.len());
+                if n > room {
+                    truncated = true;
+                }
+                captured.extend_from_slice(&buf[..n.min(room)]);
+            }
+        }
+    }
+    (captured, truncated)
+}
+
+/// Waits for a capture task and decodes its output
+async fn join_capture(
+    handle: Option<tokio::task::JoinHandle<(Vec<u8>, bool)>>,
+) -> (String, bool) {
+    match handle {
+        Some(handle) => match handle.await {
+            Ok((bytes, truncated)) => (String::from_utf8_lossy(&bytes).into_owned(), truncated),
+            Err(_) => (String::new(), false),
+        },
+        None => (String::new(), false),
+    }
+}
+
+#[async_trait]
+impl ToolExecutor for ScriptToolExecutor {
+    async fn execute(&self, ctx: ToolContext) -> Result<ToolExecutionResult, ToolError> {
+        info!(
+            tool_id = self.tool_id,
+            capability = ctx.capability,
+            request_id = ctx.request_id,
+            "Executing script tool"
+        );
+        let started = Instant::now();
+
+        let (status, output) = match ctx.capability.as_str() {
+            RUN_SCRIPT_CAPABILITY => self.run_script(&ctx).await?,
+            PREPARE_ENVIRONMENT_CAPABILITY => {
+                let lockfile = self.file_param(&ctx, "lockfile")?;
+                let env = self.environments.ensure(lockfile.as_deref()).await?;
+                (
+                    ExecutionStatus::Success,
+                    json!({
+                        "environment": env.key,
+                        "path": env.path.display().to_string(),
+                        "interpreter": env.interpreter.display().to_string(),
+                    }),
+                )
+            }
+            _ => {
+                return Err(ToolError::CapabilityNotFound(
+                    ctx.capability.clone(),
+                    ctx.tool_id.clone(),
+                ))
+            }
+        };
+
+        let error_message = match status {
+            ExecutionStatus::Success => None,
+            ExecutionStatus::Timeout => Some("Script timed out".to_string()),
+            _ => output
+                .get("stderr")
+                .and_then(JsonValue::as_str)
+                .map(|s| s.trim().to_string()),
+        };
+
+        Ok(ToolExecutionResult {
+            tool_id: ctx.tool_id,
+            capability: ctx.capability,
+            request_id: ctx.request_id,
+            status,
+            output: Some(output),
+            error_message,
+            execution_time_ms: started.elapsed().as_millis() as u64,
+            timestamp: Utc::now(),
+        })
+    }
+
+    fn get_tool_id(&self) -> String {
+        self.tool_id.clone()
+    }
+
+    fn get_capabilities(&self) -> Vec<String> {
+        vec![
+            RUN_SCRIPT_CAPABILITY.to_string(),
+            PREPARE_ENVIRONMENT_CAPABILITY.to_string(),
+        ]
+    }
+
+    async fn stop(&self) -> Result<(), ToolError> {
+        self.resource_manager.cleanup_tool(&self.tool_id).await
+    }
+}
+
+/// Returns true if the interpreter can be launched
+pub fn interpreter_available(interpreter: &Path) -> bool {
+    std::process::Command::new(interpreter)
+        .arg("--version")
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .status()
+        .map(|s| s.success())
+        .unwrap_or(false)
+}
+
+#[cfg(all(test, feature = "python-tools"))]
+mod tests {
+    use super::*;
+    use crate::tool::cleanup::BasicResourceManager;
+    use std::collections::HashMap;
+
+    fn context(capability: &str, parameters: HashMap<String, JsonValue>) -> ToolContext {
+        ToolContext {
+            tool_id: "python-script".to_string(),
+            capability: capability.to_string(),
+            parameters,
+            security_token: None,
+            session_id: None,
+            request_id: "req-1".to_string(),
+            timestamp: Utc::now(),
+            capability_version: 1,
+            deadline: None,
+        }
+    }
+
+    async fn executor(root: &Path, limits: ResourceLimits) -> ScriptToolExecutor {
+        let environments = Arc::new(EnvironmentManager::new(ScriptLanguage::Python, root));
+        ScriptToolExecutor::new(environments, Arc::new(BasicResourceManager::new()), limits)
+            .await
+            .expect("executor")
+    }
+
+    #[test]
+    fn test_python_tool_definition() {
+        let tool = python_tool();
+        assert_eq!(tool.id, "python-script");
+        assert!(tool
+            .capabilities
+            .iter()
+            .any(|c| c.name == RUN_SCRIPT_CAPABILITY));
+    }
+
+    #[tokio::test]
+    async fn test_environment_key_depends_on_lockfile() {
+        let dir = tempfile::tempdir().unwrap();
+        let lock_a = dir.path().join("a.txt");
+        let lock_b = dir.path().join("b.txt");
+        tokio::fs::write(&lock_a, "requests==2.31.0\n").await.unwrap();
+        tokio::fs::write(&lock_b, "numpy==1.26.0\n").await.unwrap();
+
+        let manager = EnvironmentManager::new(ScriptLanguage::Python, dir.path());
+        let key_a = manager.environment_key(Some(&lock_a)).await.unwrap();
+        let key_b = manager.environment_key(Some(&lock_b)).await.unwrap();
+        assert_ne!(key_a, key_b);
+        assert_eq!(key_a, manager.environment_key(Some(&lock_a)).await.unwrap());
+        assert!(manager
+            .environment_key(Some(&dir.path().join("missing.txt")))
+            .await
+            .is_err());
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_slow_setup_does_not_block_other_environments() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let dir = tempfile::tempdir().unwrap();
+        let interpreter = dir.path().join("slow-python");
+        tokio::fs::write(&interpreter, "#!/bin/sh\nsleep 2\nexit 1\n").await.unwrap();
+        std::fs::set_permissions(&interpreter, std::fs::Permissions::from_mode(0o755)).unwrap();
+        let lockfile = dir.path().join("requirements.txt");
+        tokio::fs::write(&lockfile, "numpy==1.26.0\n").await.unwrap();
+
+        let manager = EnvironmentManager::new(ScriptLanguage::Python, dir.path().join("envs"))
+            .with_interpreter(&interpreter);
+        // An environment an earlier process already set up
+        let ready = manager.environment_key(None).await.unwrap();
+        let ready_path = dir.path().join("envs").join("python").join(&ready);
+        tokio::fs::create_dir_all(&ready_path).await.unwrap();
+        tokio::fs::write(ready_path.join(".squirrel-env-ready"), &ready).await.unwrap();
+
+        let slow = manager.ensure(Some(&lockfile));
+        let fast = async {
+            tokio::time::sleep(Duration::from_millis(100)).await;
+            tokio::time::timeout(Duration::from_secs(1), manager.ensure(None)).await
+        };
+        let (slow, fast) = tokio::join!(slow, fast);
+        assert!(slow.is_err());
+        assert_eq!(fast.expect("blocked by the other setup").unwrap().key, ready);
+    }
+
+    #[tokio::test]
+    async fn test_run_inline_script_captures_output() {
+        if !interpreter_available(Path::new(ScriptLanguage::Python.default_interpreter())) {
+            return;
+        }
+        let dir = tempfile::tempdir().unwrap();
+        let executor = executor(dir.path(), ResourceLimits::default()).await;
+
+        let mut params = HashMap::new();
+        params.insert(
+            "script".to_string(),
+            json!("import sys\nprint('hello', sys.argv[1])\nprint('oops', file=sys.stderr)"),
+        );
+        params.insert("args".to_string(), json!(["world"]));
+
+        let result = executor
+            .execute(context(RUN_SCRIPT_CAPABILITY, params))
+            .await
+            .unwrap();
+        assert_eq!(result.status, ExecutionStatus::Success);
+        let output = result.output.unwrap();
+        assert_eq!(output["stdout"].as_str().unwrap().trim(), "hello world");
+        assert_eq!(output["stderr"].as_str().unwrap().trim(), "oops");
+        assert_eq!(output["exit_code"], 0);
+    }
+
+    #[tokio::test]
+    async fn test_run_script_times_out() {
+        if !interpreter_available(Path::new(ScriptLanguage::Python.default_interpreter())) {
+            return;
+        }
+        let dir = tempfile::tempdir().unwrap();
+        let limits = ResourceLimits {
+            max_cpu_time_ms: 200,
+            ..ResourceLimits::default()
+        };
+        let executor = executor(dir.path(), limits).await;
+
+        let mut params = HashMap::new();
+        params.insert("script".to_string(), json!("import time\ntime.sleep(5)"));
+
+        let result = executor
+            .execute(context(RUN_SCRIPT_CAPABILITY, params))
+            .await
+            .unwrap();
+        assert_eq!(result.status, ExecutionStatus::Timeout);
+    }
+
+    #[tokio::test]
+    async fn test_vfs_confines_script_paths() {
+        let dir = tempfile::tempdir().unwrap();
+        let vfs = Vfs::new().with_root(
+            squirrel_core::vfs::WORKSPACE_ROOT,
+            dir.path().join("ws"),
+            squirrel_core::vfs::RootAccess::ReadOnly,
+        );
+        let executor = executor(dir.path(), ResourceLimits::default())
+            .await
+            .with_vfs(Arc::new(vfs));
+
+        let run = |path: &str| {
+            let mut params = HashMap::new();
+            params.insert("path".to_string(), json!(path));
+            executor.execute(context(RUN_SCRIPT_CAPABILITY, params))
+        };
+        assert!(matches!(
+            run("workspace://../escape.py").await,
+            Err(ToolError::SecurityViolation(_))
+        ));
+        assert!(matches!(
+            run("/tmp/script.py").await,
+            Err(ToolError::ValidationFailed(_))
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_missing_script_is_rejected() {
+        let dir = tempfile::tempdir().unwrap();
+        let executor = executor(dir.path(), ResourceLimits::default()).await;
+        let result = executor
+            .execute(context(RUN_SCRIPT_CAPABILITY, HashMap::new()))
+            .await;
+        assert!(matches!(result, Err(ToolError::ValidationFailed(_))));
+    }
+}
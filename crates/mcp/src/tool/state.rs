@@ -0,0 +1,353 @@
+//! Tool state machine
+//!
+//! Every state change a [`ToolManager`](super::ToolManager) makes is an
+//! event applied to the tool's current state. [`TRANSITIONS`] lists every
+//! allowed `(from_state, event, to_state)` triple and
+//! [`validate_transition`] is the only place transitions are checked;
+//! each applied transition is published as a [`ToolTransition`].
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::fmt;
+
+use super::{ToolError, ToolState};
+
+/// Event moving a tool from one state to another
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum ToolEvent {
+    /// Make the tool available for execution
+    Activate,
+    /// Take the tool out of service without stopping it
+    Deactivate,
+    /// Begin starting the executor
+    Start,
+    /// The executor started
+    Started,
+    /// Begin stopping the executor
+    Stop,
+    /// The executor stopped
+    Stopped,
+    /// Begin pausing the executor
+    Pause,
+    /// The executor paused
+    Paused,
+    /// Begin resuming a paused executor
+    Resume,
+    /// The executor resumed
+    Resumed,
+    /// Begin replacing the tool definition
+    Update,
+    /// The tool definition was replaced
+    Updated,
+    /// An operation on the tool failed
+    Fail,
+    /// Begin recovering from an error
+    Recover,
+    /// Recovery finished
+    Recovered,
+    /// Return the tool to its initial state
+    Reset,
+}
+
+impl ToolEvent {
+    /// Every event
+    pub const ALL: [ToolEvent; 16] = [
+        Self::Activate,
+        Self::Deactivate,
+        Self::Start,
+        Self::Started,
+        Self::Stop,
+        Self::Stopped,
+        Self::Pause,
+        Self::Paused,
+        Self::Resume,
+        Self::Resumed,
+        Self::Update,
+        Self::Updated,
+        Self::Fail,
+        Self::Recover,
+        Self::Recovered,
+        Self::Reset,
+    ];
+
+    /// State the event moves a tool to
+    pub fn target(self) -> ToolState {
+        match self {
+            Self::Activate | Self::Resumed | Self::Updated => ToolState::Active,
+            Self::Deactivate => ToolState::Inactive,
+            Self::Start => ToolState::Starting,
+            Self::Started => ToolState::Started,
+            Self::Stop => ToolState::Stopping,
+            Self::Stopped => ToolState::Stopped,
+            Self::Pause => ToolState::Pausing,
+            Self::Paused => ToolState::Paused,
+            Self::Resume => ToolState::Resuming,
+            Self::Update => ToolState::Updating,
+            Self::Fail => ToolState::Error,
+            Self::Recover => ToolState::Recovering,
+            Self::Recovered | Self::Reset => ToolState::Registered,
+        }
+    }
+}
+
+impl fmt::Display for ToolEvent {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            Self::Activate => "activate",
+            Self::Deactivate => "deactivate",
+            Self::Start => "start",
+            Self::Started => "started",
+            Self::Stop => "stop",
+            Self::Stopped => "stopped",
+            Self::Pause => "pause",
+            Self::Paused => "paused",
+            Self::Resume => "resume",
+            Self::Resumed => "resumed",
+            Self::Update => "update",
+            Self::Updated => "updated",
+            Self::Fail => "fail",
+            Self::Recover => "recover",
+            Self::Recovered => "recovered",
+            Self::Reset => "reset",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+use ToolEvent as E;
+use ToolState as S;
+
+/// Every allowed transition as `(from_state, event, to_state)`
+///
+/// Transitional states (`Starting`, `Stopping`, ...) only accept the event
+/// completing them or `Fail`. `Unregistered` accepts nothing.
+pub const TRANSITIONS: &[(ToolState, ToolEvent, ToolState)] = &[
+    // Activation
+    (S::Registered, E::Activate, S::Active),
+    (S::Inactive, E::Activate, S::Active),
+    (S::Started, E::Activate, S::Active),
+    (S::Stopped, E::Activate, S::Active),
+    (S::Registered, E::Deactivate, S::Inactive),
+    (S::Active, E::Deactivate, S::Inactive),
+    (S::Started, E::Deactivate, S::Inactive),
+    (S::Paused, E::Deactivate, S::Inactive),
+    // Starting
+    (S::Registered, E::Start, S::Starting),
+    (S::Inactive, E::Start, S::Starting),
+    (S::Stopped, E::Start, S::Starting),
+    (S::Starting, E::Started, S::Started),
+    // Stopping
+    (S::Registered, E::Stop, S::Stopping),
+    (S::Active, E::Stop, S::Stopping),
+    (S::Started, E::Stop, S::Stopping),
+    (S::Paused, E::Stop, S::Stopping),
+    (S::Inactive, E::Stop, S::Stopping),
+    (S::Error, E::Stop, S::Stopping),
+    (S::Stopping, E::Stopped, S::Stopped),
+    // Pausing and resuming
+    (S::Active, E::Pause, S::Pausing),
+    (S::Started, E::Pause, S::Pausing),
+    (S::Pausing, E::Paused, S::Paused),
+    (S::Paused, E::Resume, S::Resuming),
+    (S::Resuming, E::Resumed, S::Active),
+    // Updating
+    (S::Registered, E::Update, S::Updating),
+    (S::Active, E::Update, S::Updating),
+    (S::Started, E::Update, S::Updating),
+    (S::Stopped, E::Update, S::Updating),
+    (S::Paused, E::Update, S::Updating),
+    (S::Inactive, E::Update, S::Updating),
+    (S::Error, E::Update, S::Updating),
+    (S::Updating, E::Updated, S::Active),
+    // Failures
+    (S::Registered, E::Fail, S::Error),
+    (S::Active, E::Fail, S::Error),
+    (S::Starting, E::Fail, S::Error),
+    (S::Started, E::Fail, S::Error),
+    (S::Stopping, E::Fail, S::Error),
+    (S::Stopped, E::Fail, S::Error),
+    (S::Pausing, E::Fail, S::Error),
+    (S::Paused, E::Fail, S::Error),
+    (S::Resuming, E::Fail, S::Error),
+    (S::Updating, E::Fail, S::Error),
+    (S::Recovering, E::Fail, S::Error),
+    (S::Inactive, E::Fail, S::Error),
+    // Recovery
+    (S::Error, E::Recover, S::Recovering),
+    (S::Recovering, E::Recovered, S::Registered),
+    // Resetting
+    (S::Registered, E::Reset, S::Registered),
+    (S::Stopped, E::Reset, S::Registered),
+    (S::Inactive, E::Reset, S::Registered),
+    (S::Error, E::Reset, S::Registered),
+    (S::Recovering, E::Reset, S::Registered),
+];
+
+/// State `event` moves a tool in state `from` to
+///
+/// # Errors
+///
+/// Returns [`ToolError::InvalidStateTransition`] if [`TRANSITIONS`] has no
+/// entry for `from` and `event`.
+pub fn validate_transition(
+    tool_id: &str,
+    from: ToolState,
+    event: ToolEvent,
+) -> Result<ToolState, ToolError> {
+    TRANSITIONS
+        .iter()
+        .find(|(state, allowed, _)| *state == from && *allowed == event)
+        .map(|(_, _, to)| *to)
+        .ok_or_else(|| ToolError::InvalidStateTransition {
+            tool_id: tool_id.to_string(),
+            from_state: from,
+            to_state: event.target(),
+        })
+}
+
+/// A state transition applied to a tool
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ToolTransition {
+    /// Tool ID
+    pub tool_id: String,
+    /// State before the transition
+    pub from: ToolState,
+    /// Event applied; `None` when the state was set directly with
+    /// [`ToolManager::update_tool_state`](super::ToolManager::update_tool_state)
+    pub event: Option<ToolEvent>,
+    /// State after the transition
+    pub to: ToolState,
+    /// When the transition was applied
+    pub timestamp: DateTime<Utc>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashSet;
+
+    const ALL_STATES: [ToolState; 14] = [
+        S::Registered,
+        S::Active,
+        S::Starting,
+        S::Started,
+        S::Stopping,
+        S::Stopped,
+        S::Pausing,
+        S::Paused,
+        S::Resuming,
+        S::Updating,
+        S::Error,
+        S::Unregistered,
+        S::Recovering,
+        S::Inactive,
+    ];
+
+    #[test]
+    fn test_table_is_deterministic_and_targets_match_events() {
+        let mut seen = HashSet::new();
+        for (from, event, to) in TRANSITIONS {
+            assert!(
+                seen.insert((*from, *event)),
+                "duplicate {from} --{event}-->"
+            );
+            assert_eq!(*to, event.target(), "{from} --{event}--> {to}");
+        }
+    }
+
+    #[test]
+    fn test_every_state_and_event_pair_is_validated_by_the_table() {
+        for from in ALL_STATES {
+            for event in ToolEvent::ALL {
+                let listed = TRANSITIONS
+                    .iter()
+                    .any(|(s, e, _)| *s == from && *e == event);
+                match validate_transition("tool", from, event) {
+                    Ok(to) => {
+                        assert!(listed, "{from} --{event}--> allowed but not listed");
+                        assert_eq!(to, event.target());
+                    }
+                    Err(ToolError::InvalidStateTransition {
+                        tool_id,
+                        from_state,
+                        to_state,
+                    }) => {
+                        assert!(!listed, "{from} --{event}--> listed but rejected");
+                        assert_eq!(tool_id, "tool");
+                        assert_eq!(from_state, from);
+                        assert_eq!(to_state, event.target());
+                    }
+                    Err(e) => panic!("unexpected error: {e}"),
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_transitional_states_only_complete_or_fail() {
+        let completions = [
+            (S::Starting, E::Started),
+            (S::Stopping, E::Stopped),
+            (S::Pausing, E::Paused),
+            (S::Resuming, E::Resumed),
+            (S::Updating, E::Updated),
+        ];
+        for (state, completion) in completions {
+            let allowed: Vec<_> = ToolEvent::ALL
+                .into_iter()
+                .filter(|event| validate_transition("tool", state, *event).is_ok())
+                .collect();
+            assert_eq!(allowed, [completion, E::Fail], "{state}");
+        }
+    }
+
+    #[test]
+    fn test_unregistered_accepts_nothing_and_every_state_is_reachable() {
+        assert!(ToolEvent::ALL.into_iter().all(|event| validate_transition(
+            "tool",
+            S::Unregistered,
+            event
+        )
+        .is_err()));
+
+        let reachable: HashSet<ToolState> = TRANSITIONS.iter().map(|(_, _, to)| *to).collect();
+        for state in ALL_STATES {
+            if !matches!(state, S::Registered | S::Unregistered) {
+                assert!(reachable.contains(&state), "{state} is unreachable");
+            }
+        }
+    }
+
+    #[test]
+    fn test_lifecycle_paths() {
+        let run = |events: &[ToolEvent]| {
+            events.iter().try_fold(S::Registered, |state, event| {
+                validate_transition("tool", state, *event)
+            })
+        };
+
+        assert_eq!(
+            run(&[
+                E::Start,
+                E::Started,
+                E::Pause,
+                E::Paused,
+                E::Resume,
+                E::Resumed
+            ])
+            .unwrap(),
+            S::Active
+        );
+        assert_eq!(
+            run(&[E::Activate, E::Stop, E::Stopped, E::Start, E::Started]).unwrap(),
+            S::Started
+        );
+        assert_eq!(
+            run(&[E::Start, E::Fail, E::Recover, E::Recovered]).unwrap(),
+            S::Registered
+        );
+        assert!(run(&[E::Start, E::Started, E::Start]).is_err());
+        assert!(run(&[E::Stop, E::Stopped, E::Pause]).is_err());
+        assert!(run(&[E::Resume]).is_err());
+    }
+}
@@ -0,0 +1,439 @@
+//! Capability versions and deprecation
+//!
+//! A [`Tool`] publishes several versions of a capability as separate
+//! [`Capability`](super::Capability) entries sharing a name. Callers pick a
+//! version with the [`CAPABILITY_VERSION_PARAM`] parameter or, on transports
+//! with headers, [`CAPABILITY_VERSION_HEADER`]; without either the newest
+//! version runs. Up-conversions registered per capability rewrite a request
+//! for an old or deprecated version into a request for a newer one, so tools
+//! can retire versions without breaking callers.
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value as JsonValue;
+use std::collections::{BTreeMap, HashMap};
+use std::fmt;
+use std::sync::Arc;
+
+use super::{Tool, ToolError};
+
+/// Request parameter selecting a capability version; removed before the
+/// parameters reach the executor
+pub const CAPABILITY_VERSION_PARAM: &str = "_version";
+
+/// Header selecting a capability version; takes precedence over
+/// [`CAPABILITY_VERSION_PARAM`]
+pub const CAPABILITY_VERSION_HEADER: &str = "x-capability-version";
+
+/// Version of capabilities that do not declare one
+pub const DEFAULT_CAPABILITY_VERSION: u32 = 1;
+
+/// Serde default for [`Capability::version`](super::Capability::version)
+pub(crate) fn default_capability_version() -> u32 {
+    DEFAULT_CAPABILITY_VERSION
+}
+
+/// Deprecation notice on a capability version
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Deprecation {
+    /// Why the version is deprecated and what to do instead
+    pub message: String,
+    /// Date or release after which the version may be removed
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub sunset: Option<String>,
+    /// Version callers should move to
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub replacement: Option<u32>,
+}
+
+impl Deprecation {
+    /// Creates a deprecation notice
+    pub fn new(message: impl Into<String>) -> Self {
+        Self {
+            message: message.into(),
+            sunset: None,
+            replacement: None,
+        }
+    }
+
+    /// Sets the sunset date or release
+    #[must_use]
+    pub fn with_sunset(mut self, sunset: impl Into<String>) -> Self {
+        self.sunset = Some(sunset.into());
+        self
+    }
+
+    /// Sets the replacement version
+    #[must_use]
+    pub fn with_replacement(mut self, version: u32) -> Self {
+        self.replacement = Some(version);
+        self
+    }
+}
+
+impl fmt::Display for Deprecation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.message)?;
+        if let Some(replacement) = self.replacement {
+            write!(f, " (use version {})", replacement)?;
+        }
+        if let Some(sunset) = &self.sunset {
+            write!(f, " (removed after {})", sunset)?;
+        }
+        Ok(())
+    }
+}
+
+/// Function rewriting request parameters for one capability version into
+/// parameters for a newer version
+pub type UpConversion = Arc<dyn Fn(JsonValue) -> Result<JsonValue, ToolError> + Send + Sync>;
+
+/// Up-conversions registered with a tool manager
+#[derive(Default)]
+pub struct UpConversions {
+    /// Target version and conversion by tool, capability and source version
+    conversions: HashMap<(String, String, u32), (u32, UpConversion)>,
+}
+
+impl fmt::Debug for UpConversions {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("UpConversions")
+            .field("count", &self.conversions.len())
+            .finish()
+    }
+}
+
+impl UpConversions {
+    /// Registers a conversion from version `from` of a capability to version
+    /// `to`, replacing any conversion from `from`
+    ///
+    /// # Errors
+    ///
+    /// Returns [`ToolError::ValidationFailed`] unless `to` is newer than
+    /// `from`.
+    pub fn register(
+        &mut self,
+        tool_id: &str,
+        capability: &str,
+        from: u32,
+        to: u32,
+        convert: UpConversion,
+    ) -> Result<(), ToolError> {
+        if to <= from {
+            return Err(ToolError::ValidationFailed(format!(
+                "Up-conversion of '{}' on tool '{}' must target a newer version than {}, got {}",
+                capability, tool_id, from, to
+            )));
+        }
+        self.conversions.insert(
+            (tool_id.to_string(), capability.to_string(), from),
+            (to, convert),
+        );
+        Ok(())
+    }
+
+    /// Removes every conversion of a tool
+    pub fn remove_tool(&mut self, tool_id: &str) {
+        self.conversions.retain(|(tool, _, _), _| tool != tool_id);
+    }
+
+    /// Applies the conversion from `version`, if any, returning the new
+    /// version and parameters
+    fn step(
+        &self,
+        tool_id: &str,
+        capability: &str,
+        version: u32,
+        params: JsonValue,
+    ) -> Result<Result<(u32, JsonValue), JsonValue>, ToolError> {
+        match self
+            .conversions
+            .get(&(tool_id.to_string(), capability.to_string(), version))
+        {
+            Some((to, convert)) => Ok(Ok((*to, convert(params)?))),
+            None => Ok(Err(params)),
+        }
+    }
+}
+
+/// Capability version a request resolved to
+#[derive(Debug, Clone, PartialEq)]
+pub struct ResolvedCapability {
+    /// Version that runs
+    pub version: u32,
+    /// Parameters for that version, after any up-conversions
+    pub params: JsonValue,
+    /// Version the caller asked for and its deprecation notice, when that
+    /// version is deprecated
+    pub deprecated: Option<(u32, Deprecation)>,
+}
+
+/// Reads a version from a parameter or header value
+///
+/// Accepts `2`, `"2"` and `"v2"`.
+///
+/// # Errors
+///
+/// Returns [`ToolError::ValidationFailed`] for anything else.
+pub fn parse_version(value: &JsonValue) -> Result<u32, ToolError> {
+    let parsed = match value {
+        JsonValue::Number(number) => number.as_u64().and_then(|v| u32::try_from(v).ok()),
+        JsonValue::String(text) => {
+            let text = text.trim();
+            text.strip_prefix('v').unwrap_or(text).parse().ok()
+        }
+        _ => None,
+    };
+    parsed.ok_or_else(|| {
+        ToolError::ValidationFailed(format!("Invalid capability version: {}", value))
+    })
+}
+
+/// Removes [`CAPABILITY_VERSION_PARAM`] from request parameters, returning
+/// the version it selected
+///
+/// # Errors
+///
+/// Returns [`ToolError::ValidationFailed`] if the parameter is not a version.
+pub fn take_version_param(params: &mut JsonValue) -> Result<Option<u32>, ToolError> {
+    match params
+        .as_object_mut()
+        .and_then(|params| params.remove(CAPABILITY_VERSION_PARAM))
+    {
+        Some(value) => parse_version(&value).map(Some),
+        None => Ok(None),
+    }
+}
+
+/// Checks that a tool publishes each capability version once and that
+/// deprecation notices point at newer published versions
+///
+/// # Errors
+///
+/// Returns [`ToolError::ValidationFailed`] describing the first problem.
+pub fn validate_versions(tool: &Tool) -> Result<(), ToolError> {
+    let mut versions: HashMap<&str, Vec<u32>> = HashMap::new();
+    for capability in &tool.capabilities {
+        let published = versions.entry(capability.name.as_str()).or_default();
+        if published.contains(&capability.version) {
+            return Err(ToolError::ValidationFailed(format!(
+                "Tool '{}' publishes version {} of capability '{}' more than once",
+                tool.id, capability.version, capability.name
+            )));
+        }
+        published.push(capability.version);
+    }
+
+    for capability in &tool.capabilities {
+        let replacement = capability
+            .deprecation
+            .as_ref()
+            .and_then(|deprecation| deprecation.replacement);
+        if let Some(replacement) = replacement {
+            if replacement <= capability.version
+                || !versions[capability.name.as_str()].contains(&replacement)
+            {
+                return Err(ToolError::ValidationFailed(format!(
+                    "Deprecated version {} of capability '{}' on tool '{}' names replacement {}, which is not a newer published version",
+                    capability.version, capability.name, tool.id, replacement
+                )));
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Picks the version of `capability` that serves a request
+///
+/// Without a requested version the newest published version runs. A
+/// requested version that is deprecated, or no longer published, is
+/// up-converted for as long as conversions are registered and the version
+/// reached is not a current one. Capabilities the tool does not declare are
+/// passed through for the executor to handle.
+///
+/// # Errors
+///
+/// Returns [`ToolError::CapabilityVersionNotFound`] if the version reached is
+/// not published, or the error of a failed up-conversion.
+pub fn resolve(
+    tool: &Tool,
+    capability: &str,
+    requested: Option<u32>,
+    params: JsonValue,
+    conversions: &UpConversions,
+) -> Result<ResolvedCapability, ToolError> {
+    let published: BTreeMap<u32, Option<&Deprecation>> = tool
+        .capabilities
+        .iter()
+        .filter(|published| published.name == capability)
+        .map(|published| (published.version, published.deprecation.as_ref()))
+        .collect();
+
+    let Some((&newest, _)) = published.last_key_value() else {
+        return Ok(ResolvedCapability {
+            version: requested.unwrap_or(DEFAULT_CAPABILITY_VERSION),
+            params,
+            deprecated: None,
+        });
+    };
+    let requested = requested.unwrap_or(newest);
+    let deprecated = published
+        .get(&requested)
+        .copied()
+        .flatten()
+        .map(|deprecation| (requested, deprecation.clone()));
+
+    let (mut version, mut params) = (requested, params);
+    while !matches!(published.get(&version), Some(None)) {
+        match conversions.step(&tool.id, capability, version, params)? {
+            Ok((to, converted)) => (version, params) = (to, converted),
+            Err(unchanged) => {
+                params = unchanged;
+                break;
+            }
+        }
+    }
+
+    if !published.contains_key(&version) {
+        return Err(ToolError::CapabilityVersionNotFound {
+            tool_id: tool.id.clone(),
+            capability: capability.to_string(),
+            version,
+        });
+    }
+    Ok(ResolvedCapability {
+        version,
+        params,
+        deprecated,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tool::Capability;
+    use serde_json::json;
+
+    fn search(version: u32, deprecation: Option<Deprecation>) -> Capability {
+        Capability {
+            name: "search".to_string(),
+            description: format!("Search v{}", version),
+            parameters: Vec::new(),
+            return_type: None,
+            version,
+            deprecation,
+        }
+    }
+
+    /// Tool with deprecated v1 and current v2 of `search`, and v1 params
+    /// `{"q"}` converting to v2 params `{"query"}`
+    fn tool_and_conversions() -> (Tool, UpConversions) {
+        let deprecation = Deprecation::new("`q` was renamed to `query`").with_replacement(2);
+        let tool = Tool::builder()
+            .id("index")
+            .name("Index")
+            .capability(search(1, Some(deprecation)))
+            .capability(search(2, None))
+            .build();
+
+        let mut conversions = UpConversions::default();
+        conversions
+            .register(
+                "index",
+                "search",
+                1,
+                2,
+                Arc::new(|mut params: JsonValue| {
+                    let query = params
+                        .as_object_mut()
+                        .and_then(|params| params.remove("q"))
+                        .ok_or_else(|| ToolError::ValidationFailed("missing 'q'".to_string()))?;
+                    Ok(json!({ "query": query }))
+                }),
+            )
+            .unwrap();
+        (tool, conversions)
+    }
+
+    #[test]
+    fn test_parse_and_take_version() {
+        assert_eq!(parse_version(&json!(2)).unwrap(), 2);
+        assert_eq!(parse_version(&json!("3")).unwrap(), 3);
+        assert_eq!(parse_version(&json!("v4")).unwrap(), 4);
+        assert!(parse_version(&json!("latest")).is_err());
+        assert!(parse_version(&json!(-1)).is_err());
+
+        let mut params = json!({ "_version": "v1", "q": "rust" });
+        assert_eq!(take_version_param(&mut params).unwrap(), Some(1));
+        assert_eq!(params, json!({ "q": "rust" }));
+        assert_eq!(take_version_param(&mut params).unwrap(), None);
+    }
+
+    #[test]
+    fn test_resolve_routes_and_up_converts_deprecated_versions() {
+        let (tool, conversions) = tool_and_conversions();
+
+        let newest = resolve(&tool, "search", None, json!({ "query": "a" }), &conversions).unwrap();
+        assert_eq!((newest.version, newest.deprecated), (2, None));
+
+        let old = resolve(&tool, "search", Some(1), json!({ "q": "a" }), &conversions).unwrap();
+        assert_eq!(old.version, 2);
+        assert_eq!(old.params, json!({ "query": "a" }));
+        assert_eq!(old.deprecated.unwrap().0, 1);
+
+        let bad = resolve(&tool, "search", Some(1), json!({}), &conversions);
+        assert!(matches!(bad, Err(ToolError::ValidationFailed(_))));
+
+        let missing = resolve(&tool, "search", Some(3), json!({}), &conversions);
+        assert!(matches!(
+            missing,
+            Err(ToolError::CapabilityVersionNotFound { version: 3, .. })
+        ));
+
+        let undeclared = resolve(&tool, "other", Some(5), json!({}), &conversions).unwrap();
+        assert_eq!(undeclared.version, 5);
+    }
+
+    #[test]
+    fn test_resolve_without_conversion_runs_deprecated_version() {
+        let (tool, _) = tool_and_conversions();
+        let resolved = resolve(
+            &tool,
+            "search",
+            Some(1),
+            json!({ "q": "a" }),
+            &UpConversions::default(),
+        )
+        .unwrap();
+        assert_eq!(resolved.version, 1);
+        assert_eq!(resolved.params, json!({ "q": "a" }));
+        assert!(resolved.deprecated.is_some());
+    }
+
+    #[test]
+    fn test_validate_versions() {
+        let (tool, mut conversions) = tool_and_conversions();
+        assert!(validate_versions(&tool).is_ok());
+
+        let duplicate = Tool::builder()
+            .id("dup")
+            .name("Dup")
+            .capability(search(1, None))
+            .capability(search(1, None))
+            .build();
+        assert!(validate_versions(&duplicate).is_err());
+
+        let dangling = Tool::builder()
+            .id("dangling")
+            .name("Dangling")
+            .capability(search(
+                1,
+                Some(Deprecation::new("gone").with_replacement(2)),
+            ))
+            .build();
+        assert!(validate_versions(&dangling).is_err());
+
+        assert!(conversions
+            .register("index", "search", 2, 2, Arc::new(Ok))
+            .is_err());
+    }
+}
@@ -0,0 +1,558 @@
+// Resource accounting module
+//
+// Aggregates the resources used on behalf of each user and workspace into
+// usage records, one per user, workspace and accounting period (an hour by
+// default). Each record sums:
+//
+// - CPU time in milliseconds,
+// - memory-seconds, in megabyte-seconds,
+// - bytes written to storage,
+// - the number of executions submitted.
+//
+// Quotas cap the usage of a user or a workspace within a sliding window.
+// Callers check them before accepting new work, so work already running is
+// never interrupted by a quota.
+//
+// With a ledger file configured, records are saved after every change and
+// reloaded when another process (the CLI or another server) has written the
+// file since.
+
+use std::collections::BTreeMap;
+use std::fmt;
+use std::path::{Path, PathBuf};
+
+use chrono::{DateTime, Duration, TimeZone, Utc};
+use serde::{Deserialize, Serialize};
+use squirrel_core::error::{Result, SquirrelError};
+
+use crate::json_state::JsonFileState;
+
+/// Workspace usage is attributed to when none is named
+pub const DEFAULT_WORKSPACE: &str = "default";
+
+/// Resources used by one or more executions
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ResourceUsage {
+    /// CPU time in milliseconds
+    pub cpu_ms: u64,
+    /// Memory held over time, in megabyte-seconds
+    pub memory_mb_seconds: u64,
+    /// Bytes written to storage
+    pub storage_bytes: u64,
+    /// Number of executions submitted
+    pub executions: u64,
+}
+
+impl ResourceUsage {
+    /// Usage of a single submitted execution
+    #[must_use]
+    pub const fn execution() -> Self {
+        Self {
+            cpu_ms: 0,
+            memory_mb_seconds: 0,
+            storage_bytes: 0,
+            executions: 1,
+        }
+    }
+
+    /// Usage of `bytes` written to storage
+    #[must_use]
+    pub const fn storage(bytes: u64) -> Self {
+        Self {
+            cpu_ms: 0,
+            memory_mb_seconds: 0,
+            storage_bytes: bytes,
+            executions: 0,
+        }
+    }
+
+    /// Usage of `cpu_ms` of compute while holding `memory_mb` of memory
+    #[must_use]
+    pub const fn compute(cpu_ms: u64, memory_mb: u64) -> Self {
+        Self {
+            cpu_ms,
+            memory_mb_seconds: memory_mb * cpu_ms / 1000,
+            storage_bytes: 0,
+            executions: 0,
+        }
+    }
+
+    /// Adds `other` to this usage
+    pub fn add(&mut self, other: &Self) {
+        self.cpu_ms = self.cpu_ms.saturating_add(other.cpu_ms);
+        self.memory_mb_seconds = self.memory_mb_seconds.saturating_add(other.memory_mb_seconds);
+        self.storage_bytes = self.storage_bytes.saturating_add(other.storage_bytes);
+        self.executions = self.executions.saturating_add(other.executions);
+    }
+
+    /// Whether no resources were used
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        *self == Self::default()
+    }
+}
+
+/// Resources used by one user in one workspace during one period
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct UsageRecord {
+    /// User the resources were used for
+    pub user: String,
+    /// Workspace the resources were used in
+    pub workspace: String,
+    /// Start of the period, inclusive
+    pub period_start: DateTime<Utc>,
+    /// End of the period, exclusive
+    pub period_end: DateTime<Utc>,
+    /// Resources used
+    #[serde(flatten)]
+    pub usage: ResourceUsage,
+}
+
+/// What a quota limits the usage of
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum QuotaScope {
+    /// A user, across all workspaces
+    User,
+    /// A workspace, across all users
+    Workspace,
+}
+
+impl fmt::Display for QuotaScope {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::User => write!(f, "user"),
+            Self::Workspace => write!(f, "workspace"),
+        }
+    }
+}
+
+/// Limits on the usage of a user or workspace within a sliding window
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Quota {
+    /// Whether the quota limits users or workspaces
+    pub scope: QuotaScope,
+    /// User or workspace the quota applies to; `None` applies it to each one
+    #[serde(default)]
+    pub name: Option<String>,
+    /// Length of the window usage is summed over, in seconds
+    #[serde(default = "default_window_secs")]
+    pub window_secs: u64,
+    /// Most CPU seconds in the window
+    #[serde(default)]
+    pub max_cpu_seconds: Option<u64>,
+    /// Most memory megabyte-seconds in the window
+    #[serde(default)]
+    pub max_memory_mb_seconds: Option<u64>,
+    /// Most bytes written to storage in the window
+    #[serde(default)]
+    pub max_storage_bytes: Option<u64>,
+    /// Most executions submitted in the window
+    #[serde(default)]
+    pub max_executions: Option<u64>,
+}
+
+const fn default_window_secs() -> u64 {
+    30 * 24 * 60 * 60
+}
+
+impl Quota {
+    /// Whether the quota applies to work of `user` in `workspace`
+    #[must_use]
+    pub fn applies_to(&self, user: &str, workspace: &str) -> bool {
+        let subject = match self.scope {
+            QuotaScope::User => user,
+            QuotaScope::Workspace => workspace,
+        };
+        self.name.as_deref().is_none_or(|name| name == subject)
+    }
+
+    /// Whether a record counts towards the quota of `user` in `workspace`
+    fn counts(&self, record: &UsageRecord, user: &str, workspace: &str) -> bool {
+        match self.scope {
+            QuotaScope::User => record.user == user,
+            QuotaScope::Workspace => record.workspace == workspace,
+        }
+    }
+
+    /// The first limit `used` reaches, as (resource, used, limit)
+    #[must_use]
+    pub fn exceeded(&self, used: &ResourceUsage) -> Option<(&'static str, u64, u64)> {
+        [
+            ("cpu_seconds", used.cpu_ms / 1000, self.max_cpu_seconds),
+            ("memory_mb_seconds", used.memory_mb_seconds, self.max_memory_mb_seconds),
+            ("storage_bytes", used.storage_bytes, self.max_storage_bytes),
+            ("executions", used.executions, self.max_executions),
+        ]
+        .into_iter()
+        .find_map(|(resource, used, limit)| limit.filter(|limit| used >= *limit).map(|limit| (resource, used, limit)))
+    }
+}
+
+/// Usage of a user or workspace against one quota
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct QuotaStatus {
+    /// The quota
+    pub quota: Quota,
+    /// User or workspace the usage is of
+    pub subject: String,
+    /// Usage within the quota's window
+    pub used: ResourceUsage,
+    /// The limit that is reached, if any, as (resource, used, limit)
+    pub exceeded: Option<(String, u64, u64)>,
+}
+
+impl QuotaStatus {
+    /// Whether new work must be refused
+    #[must_use]
+    pub const fn is_exceeded(&self) -> bool {
+        self.exceeded.is_some()
+    }
+}
+
+impl fmt::Display for QuotaStatus {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.exceeded {
+            Some((resource, used, limit)) => write!(
+                f,
+                "{} '{}' used {used} of {limit} {resource} allowed in {}s",
+                self.quota.scope, self.subject, self.quota.window_secs
+            ),
+            None => write!(f, "{} '{}' is within its quota", self.quota.scope, self.subject),
+        }
+    }
+}
+
+/// Resource accounting settings
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct AccountingConfig {
+    /// Length of an accounting period in seconds
+    pub period_secs: u64,
+    /// File usage records are persisted to
+    pub ledger_path: Option<PathBuf>,
+    /// Quotas checked before new work is accepted
+    pub quotas: Vec<Quota>,
+}
+
+impl Default for AccountingConfig {
+    fn default() -> Self {
+        Self {
+            period_secs: 3600,
+            ledger_path: None,
+            quotas: Vec::new(),
+        }
+    }
+}
+
+impl AccountingConfig {
+    /// `$HOME/.squirrel/usage.json`, where the server and the CLI share records
+    #[must_use]
+    pub fn default_ledger_path() -> Option<PathBuf> {
+        std::env::var_os("HOME").map(|home| Path::new(&home).join(".squirrel").join("usage.json"))
+    }
+}
+
+/// Selects usage records
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct UsageQuery {
+    /// Only periods ending after this time
+    pub from: Option<DateTime<Utc>>,
+    /// Only periods starting before this time
+    pub to: Option<DateTime<Utc>>,
+    /// Only this user
+    pub user: Option<String>,
+    /// Only this workspace
+    pub workspace: Option<String>,
+}
+
+impl UsageQuery {
+    /// Whether `record` is selected
+    #[must_use]
+    pub fn matches(&self, record: &UsageRecord) -> bool {
+        self.from.is_none_or(|from| record.period_end > from)
+            && self.to.is_none_or(|to| record.period_start < to)
+            && self.user.as_ref().is_none_or(|user| &record.user == user)
+            && self.workspace.as_ref().is_none_or(|workspace| &record.workspace == workspace)
+    }
+}
+
+/// Resident memory of the current process in megabytes
+#[must_use]
+pub fn process_memory_mb() -> u64 {
+    let Ok(pid) = sysinfo::get_current_pid() else {
+        return 0;
+    };
+    let mut system = sysinfo::System::new();
+    system.refresh_process_specifics(pid, sysinfo::ProcessRefreshKind::new().with_memory());
+    system.process(pid).map_or(0, |process| process.memory() / (1024 * 1024))
+}
+
+/// Persisted records, keyed by period start, user and workspace
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+struct LedgerState {
+    #[serde(default)]
+    records: Vec<UsageRecord>,
+}
+
+/// Aggregates resource usage into periodic records and checks quotas
+#[derive(Debug)]
+pub struct UsageLedger {
+    config: AccountingConfig,
+    state: JsonFileState<LedgerState>,
+}
+
+impl UsageLedger {
+    /// Creates a ledger, loading the ledger file if there is one
+    ///
+    /// # Errors
+    /// Returns an error if the ledger file exists but cannot be read
+    pub fn open(config: AccountingConfig) -> Result<Self> {
+        let state = JsonFileState::open(config.ledger_path.clone(), ledger_error)?;
+        Ok(Self { config, state })
+    }
+
+    /// Creates a ledger keeping its records in memory only
+    #[must_use]
+    pub fn in_memory(mut config: AccountingConfig) -> Self {
+        config.ledger_path = None;
+        Self {
+            config,
+            state: JsonFileState::in_memory(ledger_error),
+        }
+    }
+
+    /// The ledger's settings
+    #[must_use]
+    pub const fn config(&self) -> &AccountingConfig {
+        &self.config
+    }
+
+    /// Adds `usage` by `user` in `workspace` to the current period
+    ///
+    /// # Errors
+    /// Returns an error if the ledger cannot be persisted
+    pub fn record(&self, user: &str, workspace: &str, usage: ResourceUsage) -> Result<()> {
+        self.record_at(user, workspace, Utc::now(), usage)
+    }
+
+    /// Adds `usage` by `user` in `workspace` to the period containing `at`
+    ///
+    /// # Errors
+    /// Returns an error if the ledger cannot be persisted
+    pub fn record_at(&self, user: &str, workspace: &str, at: DateTime<Utc>, usage: ResourceUsage) -> Result<()> {
+        if usage.is_empty() {
+            return Ok(());
+        }
+        let (period_start, period_end) = self.period_of(at);
+        self.state.update(|state| {
+            let existing = state
+                .records
+                .iter_mut()
+                .find(|r| r.period_start == period_start && r.user == user && r.workspace == workspace);
+            match existing {
+                Some(record) => record.usage.add(&usage),
+                None => state.records.push(UsageRecord {
+                    user: user.to_string(),
+                    workspace: workspace.to_string(),
+                    period_start,
+                    period_end,
+                    usage,
+                }),
+            }
+            Ok(())
+        })
+    }
+
+    /// Records selected by `query`, oldest first
+    #[must_use]
+    pub fn records(&self, query: &UsageQuery) -> Vec<UsageRecord> {
+        self.state.read(|state| {
+            let mut records: Vec<UsageRecord> =
+                state.records.iter().filter(|record| query.matches(record)).cloned().collect();
+            records.sort_by(|a, b| {
+                (a.period_start, &a.user, &a.workspace).cmp(&(b.period_start, &b.user, &b.workspace))
+            });
+            records
+        })
+    }
+
+    /// Removes the records of `user`, returning how many were removed
+    ///
+    /// # Errors
+    /// Returns an error if the ledger cannot be persisted
+    pub fn remove_user(&self, user: &str) -> Result<usize> {
+        self.state.update(|state| {
+            let before = state.records.len();
+            state.records.retain(|record| record.user != user);
+            Ok(before - state.records.len())
+        })
+    }
+
+    /// Total usage of the records selected by `query`, per user and workspace
+    #[must_use]
+    pub fn totals(&self, query: &UsageQuery) -> BTreeMap<(String, String), ResourceUsage> {
+        let mut totals: BTreeMap<(String, String), ResourceUsage> = BTreeMap::new();
+        for record in self.records(query) {
+            totals.entry((record.user, record.workspace)).or_default().add(&record.usage);
+        }
+        totals
+    }
+
+    /// Usage of `user` in `workspace` against every quota that applies
+    #[must_use]
+    pub fn quota_status(&self, user: &str, workspace: &str) -> Vec<QuotaStatus> {
+        let now = Utc::now();
+        self.state.read(|state| {
+            self.config
+                .quotas
+                .iter()
+                .filter(|quota| quota.applies_to(user, workspace))
+                .map(|quota| {
+                    let since = now - Duration::seconds(i64::try_from(quota.window_secs).unwrap_or(i64::MAX / 1000));
+                    let mut used = ResourceUsage::default();
+                    for record in state
+                        .records
+                        .iter()
+                        .filter(|record| record.period_end > since && quota.counts(record, user, workspace))
+                    {
+                        used.add(&record.usage);
+                    }
+                    let subject = match quota.scope {
+                        QuotaScope::User => user,
+                        QuotaScope::Workspace => workspace,
+                    };
+                    QuotaStatus {
+                        quota: quota.clone(),
+                        subject: subject.to_string(),
+                        used,
+                        exceeded: quota
+                            .exceeded(&used)
+                            .map(|(resource, used, limit)| (resource.to_string(), used, limit)),
+                    }
+                })
+                .collect()
+        })
+    }
+
+    /// The first quota `user` in `workspace` has used up, if any
+    #[must_use]
+    pub fn check_quotas(&self, user: &str, workspace: &str) -> Option<QuotaStatus> {
+        self.quota_status(user, workspace).into_iter().find(QuotaStatus::is_exceeded)
+    }
+
+    /// Start and end of the period containing `at`
+    fn period_of(&self, at: DateTime<Utc>) -> (DateTime<Utc>, DateTime<Utc>) {
+        let period = i64::try_from(self.config.period_secs.max(1)).unwrap_or(i64::MAX);
+        let start = at.timestamp().div_euclid(period) * period;
+        let start = Utc.timestamp_opt(start, 0).single().unwrap_or(at);
+        (start, start + Duration::seconds(period))
+    }
+}
+
+/// Storage error for the ledger file, `message` naming the file
+fn ledger_error(message: String) -> SquirrelError {
+    SquirrelError::metric(format!("Usage ledger {message}"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn at(hour: u32, minute: u32) -> DateTime<Utc> {
+        Utc.with_ymd_and_hms(2026, 3, 2, hour, minute, 0).unwrap()
+    }
+
+    #[test]
+    fn test_usage_is_aggregated_per_period() {
+        let ledger = UsageLedger::in_memory(AccountingConfig::default());
+        ledger.record_at("alice", "genomics", at(9, 5), ResourceUsage::compute(2000, 512)).unwrap();
+        ledger.record_at("alice", "genomics", at(9, 50), ResourceUsage::execution()).unwrap();
+        ledger.record_at("alice", "genomics", at(10, 1), ResourceUsage::storage(4096)).unwrap();
+        ledger.record_at("bob", "genomics", at(9, 30), ResourceUsage::execution()).unwrap();
+
+        let records = ledger.records(&UsageQuery {
+            user: Some("alice".to_string()),
+            ..UsageQuery::default()
+        });
+        assert_eq!(records.len(), 2);
+        assert_eq!(records[0].period_start, at(9, 0));
+        assert_eq!(records[0].period_end, at(10, 0));
+        assert_eq!(
+            records[0].usage,
+            ResourceUsage { cpu_ms: 2000, memory_mb_seconds: 1024, storage_bytes: 0, executions: 1 }
+        );
+
+        let morning = UsageQuery { from: Some(at(8, 0)), to: Some(at(10, 0)), ..UsageQuery::default() };
+        let totals = ledger.totals(&morning);
+        assert_eq!(totals.len(), 2);
+        assert_eq!(totals[&("alice".to_string(), "genomics".to_string())].storage_bytes, 0);
+        assert_eq!(totals[&("bob".to_string(), "genomics".to_string())].executions, 1);
+
+        assert_eq!(ledger.remove_user("alice").unwrap(), 2);
+        assert_eq!(ledger.records(&UsageQuery::default()).len(), 1);
+    }
+
+    #[test]
+    fn test_quotas_by_scope() {
+        let config = AccountingConfig {
+            quotas: vec![
+                Quota {
+                    scope: QuotaScope::User,
+                    name: None,
+                    window_secs: 3600,
+                    max_cpu_seconds: None,
+                    max_memory_mb_seconds: None,
+                    max_storage_bytes: None,
+                    max_executions: Some(2),
+                },
+                Quota {
+                    scope: QuotaScope::Workspace,
+                    name: Some("shared".to_string()),
+                    window_secs: 3600,
+                    max_cpu_seconds: None,
+                    max_memory_mb_seconds: None,
+                    max_storage_bytes: Some(100),
+                    max_executions: None,
+                },
+            ],
+            ..AccountingConfig::default()
+        };
+        let ledger = UsageLedger::in_memory(config);
+        ledger.record("alice", "lab", ResourceUsage::execution()).unwrap();
+        assert!(ledger.check_quotas("alice", "lab").is_none());
+        ledger.record("alice", "other", ResourceUsage::execution()).unwrap();
+        let exceeded = ledger.check_quotas("alice", "lab").unwrap();
+        assert_eq!(exceeded.exceeded, Some(("executions".to_string(), 2, 2)));
+        assert!(ledger.check_quotas("bob", "lab").is_none());
+
+        ledger.record("bob", "shared", ResourceUsage::storage(100)).unwrap();
+        let exceeded = ledger.check_quotas("carol", "shared").unwrap();
+        assert_eq!(exceeded.subject, "shared");
+        assert_eq!(ledger.quota_status("carol", "lab").len(), 1);
+
+        // Usage outside the window does not count
+        let old = Utc::now() - Duration::hours(3);
+        let ledger = UsageLedger::in_memory(ledger.config().clone());
+        ledger.record_at("alice", "lab", old, ResourceUsage { executions: 5, ..ResourceUsage::default() }).unwrap();
+        assert!(ledger.check_quotas("alice", "lab").is_none());
+    }
+
+    #[test]
+    fn test_ledger_persists_across_processes() {
+        let dir = std::env::temp_dir().join(format!("squirrel-usage-{}", uuid::Uuid::new_v4()));
+        let config = AccountingConfig {
+            ledger_path: Some(dir.join("usage.json")),
+            ..AccountingConfig::default()
+        };
+        let server = UsageLedger::open(config.clone()).unwrap();
+        server.record("alice", DEFAULT_WORKSPACE, ResourceUsage::execution()).unwrap();
+
+        let cli = UsageLedger::open(config).unwrap();
+        assert_eq!(cli.records(&UsageQuery::default()).len(), 1);
+        cli.record("alice", DEFAULT_WORKSPACE, ResourceUsage::execution()).unwrap();
+        assert_eq!(server.records(&UsageQuery::default())[0].usage.executions, 2);
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+}
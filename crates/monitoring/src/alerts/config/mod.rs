@@ -5,6 +5,7 @@ use squirrel_core::error::Result;
 use std::fmt::Debug;
 use std::collections::HashMap;
 use std::time::Duration;
+use super::lifecycle::LifecycleConfig;
 use super::status::AlertSeverity;
 
 /// Configuration for the alert system
@@ -22,6 +23,9 @@ pub struct AlertConfig {
     pub notification_channels: Vec<NotificationChannel>,
     /// Custom settings for specific alert types
     pub custom_settings: HashMap<String, serde_json::Value>,
+    /// Deduplication, grouping, silence and acknowledgment settings
+    #[serde(default)]
+    pub lifecycle: LifecycleConfig,
 }
 
 /// Notification channels for alerts
@@ -49,6 +53,7 @@ impl AlertConfig {
             history_limit: 1000,
             notification_channels: vec![NotificationChannel::Console, NotificationChannel::Log],
             custom_settings: HashMap::new(),
+            lifecycle: LifecycleConfig::default(),
         }
     }
     
@@ -0,0 +1,633 @@
+// Alert lifecycle module
+//
+// Tracks alerts from the first time they fire until they are resolved:
+//
+// - Alerts are identified by a fingerprint of their labels (source, type and
+//   string details; not severity or message), so an alert that keeps firing
+//   is recorded once with an occurrence count instead of once per firing.
+//   A duplicate is only notified again after the repeat interval, or when
+//   its severity rises.
+// - Alerts are grouped by the configured `group_by` labels.
+// - Silences suppress notifications for alerts matching all of their label
+//   matchers between their start and end time.
+// - Acknowledgments stop notifications for an alert until it is resolved.
+//
+// With a state file configured, tracked alerts, acknowledgments and silences
+// are saved after every change and reloaded when another process (the CLI or
+// another server) has written the file since.
+
+use std::collections::BTreeMap;
+use std::fmt;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+
+use chrono::{DateTime, Duration, Utc};
+use serde::{Deserialize, Serialize};
+use squirrel_core::error::{Result, SquirrelError};
+use uuid::Uuid;
+
+use super::manager::AlertError;
+use super::status::{Alert, AlertSeverity, AlertType};
+use crate::json_state::JsonFileState;
+
+/// Alert lifecycle settings
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct LifecycleConfig {
+    /// Labels alerts are grouped by
+    pub group_by: Vec<String>,
+    /// Seconds after which a still-firing alert is notified again; 0 never
+    pub repeat_interval_secs: u64,
+    /// File tracked alerts, acknowledgments and silences are persisted to
+    pub state_path: Option<PathBuf>,
+}
+
+impl Default for LifecycleConfig {
+    fn default() -> Self {
+        Self {
+            group_by: vec!["source".to_string(), "type".to_string()],
+            repeat_interval_secs: 3600,
+            state_path: None,
+        }
+    }
+}
+
+impl LifecycleConfig {
+    /// `$HOME/.squirrel/alerts.json`, where the server and the CLI share state
+    #[must_use]
+    pub fn default_state_path() -> Option<PathBuf> {
+        std::env::var_os("HOME").map(|home| Path::new(&home).join(".squirrel").join("alerts.json"))
+    }
+}
+
+/// Labels of an alert: `source`, `type`, `severity` and its string details
+#[must_use]
+pub fn alert_labels(alert: &Alert) -> BTreeMap<String, String> {
+    let mut labels: BTreeMap<String, String> = alert
+        .details
+        .iter()
+        .filter_map(|(key, value)| value.as_str().map(|value| (key.clone(), value.to_string())))
+        .collect();
+    labels.insert("source".to_string(), alert.source.clone());
+    labels.insert("type".to_string(), alert_type_name(&alert.alert_type).to_string());
+    labels.insert("severity".to_string(), alert.severity.name().to_string());
+    labels
+}
+
+/// Fingerprint of an alert's labels, ignoring severity
+///
+/// The fingerprint is stable across processes and releases, so it can be
+/// persisted and typed on the command line.
+#[must_use]
+pub fn fingerprint(labels: &BTreeMap<String, String>) -> String {
+    // FNV-1a, 64 bit
+    let mut hash: u64 = 0xcbf2_9ce4_8422_2325;
+    for (key, value) in labels.iter().filter(|(key, _)| key.as_str() != "severity") {
+        for byte in key.bytes().chain([0xff]).chain(value.bytes()).chain([0xfe]) {
+            hash ^= u64::from(byte);
+            hash = hash.wrapping_mul(0x0100_0000_01b3);
+        }
+    }
+    format!("{hash:016x}")
+}
+
+/// Name of an alert type, used as its `type` label
+const fn alert_type_name(alert_type: &AlertType) -> &'static str {
+    match alert_type {
+        AlertType::Performance(_) => "performance",
+        AlertType::Resource(_) => "resource",
+        AlertType::Error(_) => "error",
+        AlertType::Health(_) => "health",
+        AlertType::Generic => "generic",
+    }
+}
+
+/// Condition on one label
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct LabelMatcher {
+    /// Label name
+    pub name: String,
+    /// Value the label is compared with
+    pub value: String,
+    /// Whether the label must differ from the value instead
+    #[serde(default)]
+    pub negate: bool,
+}
+
+impl LabelMatcher {
+    /// Whether `labels` satisfy the matcher; a missing label equals `""`
+    #[must_use]
+    pub fn matches(&self, labels: &BTreeMap<String, String>) -> bool {
+        let value = labels.get(&self.name).map_or("", String::as_str);
+        (value == self.value) != self.negate
+    }
+}
+
+impl FromStr for LabelMatcher {
+    type Err = SquirrelError;
+
+    /// Parses `name=value` or `name!=value`
+    fn from_str(s: &str) -> Result<Self> {
+        let (name, value, negate) = if let Some((name, value)) = s.split_once("!=") {
+            (name, value, true)
+        } else if let Some((name, value)) = s.split_once('=') {
+            (name, value, false)
+        } else {
+            return Err(SquirrelError::alert(format!("Invalid label matcher '{s}', expected name=value or name!=value")));
+        };
+        let name = name.trim();
+        if name.is_empty() {
+            return Err(SquirrelError::alert(format!("Invalid label matcher '{s}', the label name is empty")));
+        }
+        Ok(Self {
+            name: name.to_string(),
+            value: value.trim().to_string(),
+            negate,
+        })
+    }
+}
+
+impl fmt::Display for LabelMatcher {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let op = if self.negate { "!=" } else { "=" };
+        write!(f, "{}{}{}", self.name, op, self.value)
+    }
+}
+
+/// Suppresses notifications for matching alerts during a time window
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Silence {
+    /// Silence identifier
+    pub id: Uuid,
+    /// Conditions an alert's labels must all satisfy
+    pub matchers: Vec<LabelMatcher>,
+    /// When the silence starts
+    pub starts_at: DateTime<Utc>,
+    /// When the silence ends
+    pub ends_at: DateTime<Utc>,
+    /// Who created the silence
+    pub created_by: String,
+    /// Why the silence was created
+    pub comment: String,
+}
+
+impl Silence {
+    /// Creates a silence starting now and lasting `duration`
+    #[must_use]
+    pub fn new(matchers: Vec<LabelMatcher>, duration: Duration, created_by: String, comment: String) -> Self {
+        let starts_at = Utc::now();
+        Self {
+            id: Uuid::new_v4(),
+            matchers,
+            starts_at,
+            ends_at: starts_at + duration,
+            created_by,
+            comment,
+        }
+    }
+
+    /// Whether the silence is in effect at `now`
+    #[must_use]
+    pub fn is_active_at(&self, now: DateTime<Utc>) -> bool {
+        self.starts_at <= now && now < self.ends_at
+    }
+
+    /// Whether the silence applies to alerts with `labels`
+    #[must_use]
+    pub fn matches(&self, labels: &BTreeMap<String, String>) -> bool {
+        !self.matchers.is_empty() && self.matchers.iter().all(|matcher| matcher.matches(labels))
+    }
+}
+
+/// Who acknowledged an alert and when
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Acknowledgment {
+    /// Who acknowledged the alert
+    pub by: String,
+    /// When the alert was acknowledged
+    pub at: DateTime<Utc>,
+}
+
+/// An alert that has fired and is not resolved
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TrackedAlert {
+    /// Fingerprint of the alert's labels
+    pub fingerprint: String,
+    /// ID of the first alert with this fingerprint
+    pub alert_id: Uuid,
+    /// Labels of the latest occurrence
+    pub labels: BTreeMap<String, String>,
+    /// Severity of the latest occurrence
+    pub severity: AlertSeverity,
+    /// Message of the latest occurrence
+    pub message: String,
+    /// When the alert first fired
+    pub first_seen: DateTime<Utc>,
+    /// When the alert last fired
+    pub last_seen: DateTime<Utc>,
+    /// How often the alert fired
+    pub count: u64,
+    /// When a notification was last sent for the alert
+    pub last_notified: Option<DateTime<Utc>>,
+    /// Acknowledgment, if the alert was acknowledged
+    pub acknowledgment: Option<Acknowledgment>,
+}
+
+/// Alerts sharing the values of the `group_by` labels
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct AlertGroup {
+    /// Values of the `group_by` labels
+    pub labels: BTreeMap<String, String>,
+    /// Fingerprints of the alerts in the group
+    pub fingerprints: Vec<String>,
+    /// Highest severity in the group
+    pub severity: AlertSeverity,
+    /// Total number of firings in the group
+    pub count: u64,
+    /// Number of acknowledged alerts in the group
+    pub acknowledged: usize,
+    /// When an alert in the group last fired
+    pub last_seen: DateTime<Utc>,
+}
+
+/// What happened to an observed alert
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Observation {
+    /// Fingerprint of the alert
+    pub fingerprint: String,
+    /// ID the alert is tracked under
+    pub alert_id: Uuid,
+    /// Whether the alert was already firing
+    pub duplicate: bool,
+    /// Silence suppressing the alert, if any
+    pub silenced_by: Option<Uuid>,
+    /// Acknowledgment of the alert, if any
+    pub acknowledgment: Option<Acknowledgment>,
+    /// Whether a notification should be sent
+    pub notify: bool,
+}
+
+/// Persisted lifecycle state
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+struct LifecycleState {
+    /// Tracked alerts by fingerprint
+    #[serde(default)]
+    alerts: BTreeMap<String, TrackedAlert>,
+    /// Silences, including expired ones
+    #[serde(default)]
+    silences: Vec<Silence>,
+}
+
+/// Deduplicates, groups, silences and acknowledges alerts
+#[derive(Debug)]
+pub struct AlertLifecycle {
+    config: LifecycleConfig,
+    state: JsonFileState<LifecycleState>,
+}
+
+impl AlertLifecycle {
+    /// Creates a lifecycle tracker, loading the state file if there is one
+    ///
+    /// # Errors
+    /// Returns an error if the state file exists but cannot be read
+    pub fn open(config: LifecycleConfig) -> Result<Self> {
+        let state = JsonFileState::open(config.state_path.clone(), state_error)?;
+        Ok(Self { config, state })
+    }
+
+    /// Creates a lifecycle tracker keeping its state in memory only
+    #[must_use]
+    pub fn in_memory(mut config: LifecycleConfig) -> Self {
+        config.state_path = None;
+        Self {
+            config,
+            state: JsonFileState::in_memory(state_error),
+        }
+    }
+
+    /// The tracker's settings
+    #[must_use]
+    pub const fn config(&self) -> &LifecycleConfig {
+        &self.config
+    }
+
+    /// Records that `alert` fired
+    ///
+    /// # Errors
+    /// Returns an error if the state cannot be persisted
+    pub fn observe(&self, alert: &Alert) -> Result<Observation> {
+        self.state.update(|state| {
+            let labels = alert_labels(alert);
+            let fingerprint = fingerprint(&labels);
+            let now = Utc::now();
+            let silenced_by = state
+                .silences
+                .iter()
+                .find(|silence| silence.is_active_at(now) && silence.matches(&labels))
+                .map(|silence| silence.id);
+
+            let repeat = Duration::seconds(i64::try_from(self.config.repeat_interval_secs).unwrap_or(i64::MAX));
+            let tracked = state.alerts.entry(fingerprint.clone());
+            let duplicate = matches!(tracked, std::collections::btree_map::Entry::Occupied(_));
+            let tracked = tracked.or_insert_with(|| TrackedAlert {
+                fingerprint: fingerprint.clone(),
+                alert_id: alert.id,
+                labels: labels.clone(),
+                severity: alert.severity,
+                message: alert.message.clone(),
+                first_seen: now,
+                last_seen: now,
+                count: 0,
+                last_notified: None,
+                acknowledgment: None,
+            });
+            let escalated = severity_rank(alert.severity) > severity_rank(tracked.severity);
+            let repeat_due = self.config.repeat_interval_secs > 0
+                && tracked.last_notified.is_none_or(|notified| now - notified >= repeat);
+            let notify = silenced_by.is_none()
+                && tracked.acknowledgment.is_none()
+                && (!duplicate || escalated || repeat_due);
+
+            tracked.labels = labels;
+            tracked.severity = alert.severity;
+            tracked.message = alert.message.clone();
+            tracked.last_seen = now;
+            tracked.count += 1;
+            if notify {
+                tracked.last_notified = Some(now);
+            }
+            Ok(Observation {
+                fingerprint,
+                alert_id: tracked.alert_id,
+                duplicate,
+                silenced_by,
+                acknowledgment: tracked.acknowledgment.clone(),
+                notify,
+            })
+        })
+    }
+
+    /// Acknowledges the alert with `fingerprint`
+    ///
+    /// # Errors
+    /// Returns an error if no such alert is tracked or the state cannot be
+    /// persisted
+    pub fn acknowledge(&self, fingerprint: &str, by: &str) -> Result<TrackedAlert> {
+        self.state.update(|state| {
+            let tracked = tracked_mut(state, fingerprint)?;
+            tracked.acknowledgment = Some(Acknowledgment {
+                by: by.to_string(),
+                at: Utc::now(),
+            });
+            Ok(tracked.clone())
+        })
+    }
+
+    /// Withdraws the acknowledgment of the alert with `fingerprint`
+    ///
+    /// # Errors
+    /// Returns an error if no such alert is tracked or the state cannot be
+    /// persisted
+    pub fn unacknowledge(&self, fingerprint: &str) -> Result<TrackedAlert> {
+        self.state.update(|state| {
+            let tracked = tracked_mut(state, fingerprint)?;
+            tracked.acknowledgment = None;
+            Ok(tracked.clone())
+        })
+    }
+
+    /// Stops tracking the alert with `fingerprint`, dropping its
+    /// acknowledgment; the next firing counts as a new alert
+    ///
+    /// # Errors
+    /// Returns an error if the state cannot be persisted
+    pub fn resolve(&self, fingerprint: &str) -> Result<Option<TrackedAlert>> {
+        self.state.update(|state| Ok(state.alerts.remove(fingerprint)))
+    }
+
+    /// The tracked alert with `fingerprint`
+    #[must_use]
+    pub fn get(&self, fingerprint: &str) -> Option<TrackedAlert> {
+        self.state.read(|state| state.alerts.get(fingerprint).cloned())
+    }
+
+    /// Fingerprint of the tracked alert with ID `alert_id`
+    #[must_use]
+    pub fn fingerprint_of(&self, alert_id: Uuid) -> Option<String> {
+        self.state.read(|state| {
+            state
+                .alerts
+                .values()
+                .find(|tracked| tracked.alert_id == alert_id)
+                .map(|tracked| tracked.fingerprint.clone())
+        })
+    }
+
+    /// Tracked alerts, most recently fired first
+    #[must_use]
+    pub fn alerts(&self) -> Vec<TrackedAlert> {
+        let mut alerts: Vec<TrackedAlert> = self.state.read(|state| state.alerts.values().cloned().collect());
+        alerts.sort_by_key(|alert| std::cmp::Reverse(alert.last_seen));
+        alerts
+    }
+
+    /// Tracked alerts grouped by the `group_by` labels, most severe first
+    #[must_use]
+    pub fn groups(&self) -> Vec<AlertGroup> {
+        let mut groups: BTreeMap<Vec<(String, String)>, AlertGroup> = BTreeMap::new();
+        for tracked in self.alerts() {
+            let labels: BTreeMap<String, String> = self
+                .config
+                .group_by
+                .iter()
+                .map(|name| (name.clone(), tracked.labels.get(name).cloned().unwrap_or_default()))
+                .collect();
+            let group = groups
+                .entry(labels.clone().into_iter().collect())
+                .or_insert_with(|| AlertGroup {
+                    labels,
+                    fingerprints: Vec::new(),
+                    severity: tracked.severity,
+                    count: 0,
+                    acknowledged: 0,
+                    last_seen: tracked.last_seen,
+                });
+            group.fingerprints.push(tracked.fingerprint.clone());
+            if severity_rank(tracked.severity) > severity_rank(group.severity) {
+                group.severity = tracked.severity;
+            }
+            group.count += tracked.count;
+            group.acknowledged += usize::from(tracked.acknowledgment.is_some());
+            group.last_seen = group.last_seen.max(tracked.last_seen);
+        }
+        let mut groups: Vec<AlertGroup> = groups.into_values().collect();
+        groups.sort_by(|a, b| {
+            severity_rank(b.severity)
+                .cmp(&severity_rank(a.severity))
+                .then(b.last_seen.cmp(&a.last_seen))
+        });
+        groups
+    }
+
+    /// Adds a silence
+    ///
+    /// # Errors
+    /// Returns an error if the silence has no matchers or ends before it
+    /// starts, or if the state cannot be persisted
+    pub fn add_silence(&self, silence: Silence) -> Result<Silence> {
+        if silence.matchers.is_empty() {
+            return Err(SquirrelError::alert("A silence needs at least one label matcher"));
+        }
+        if silence.ends_at <= silence.starts_at {
+            return Err(SquirrelError::alert("A silence must end after it starts"));
+        }
+        self.state.update(|state| {
+            state.silences.push(silence.clone());
+            Ok(silence)
+        })
+    }
+
+    /// Ends the silence with `id` now
+    ///
+    /// # Errors
+    /// Returns an error if there is no such silence or the state cannot be
+    /// persisted
+    pub fn expire_silence(&self, id: Uuid) -> Result<Silence> {
+        self.state.update(|state| {
+            let silence = state
+                .silences
+                .iter_mut()
+                .find(|silence| silence.id == id)
+                .ok_or_else(|| SquirrelError::alert(format!("Silence not found: {id}")))?;
+            silence.ends_at = silence.ends_at.min(Utc::now());
+            Ok(silence.clone())
+        })
+    }
+
+    /// Silences, optionally including those that have ended
+    #[must_use]
+    pub fn silences(&self, include_expired: bool) -> Vec<Silence> {
+        let now = Utc::now();
+        self.state.read(|state| {
+            state
+                .silences
+                .iter()
+                .filter(|silence| include_expired || silence.ends_at > now)
+                .cloned()
+                .collect()
+        })
+    }
+}
+
+/// The tracked alert with `fingerprint`, or a not-found error
+fn tracked_mut<'a>(state: &'a mut LifecycleState, fingerprint: &str) -> Result<&'a mut TrackedAlert> {
+    state
+        .alerts
+        .get_mut(fingerprint)
+        .ok_or_else(|| SquirrelError::alert(format!("No firing alert with fingerprint {fingerprint}")))
+}
+
+/// Severity as a number, higher is more severe
+const fn severity_rank(severity: AlertSeverity) -> u8 {
+    match severity {
+        AlertSeverity::Info => 0,
+        AlertSeverity::Warning => 1,
+        AlertSeverity::Error => 2,
+        AlertSeverity::Critical => 3,
+    }
+}
+
+/// Storage error for the state file, `message` naming the file
+fn state_error(message: String) -> SquirrelError {
+    AlertError::StorageError(message).into()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+
+    fn alert(source: &str, severity: AlertSeverity, host: &str) -> Alert {
+        let details = HashMap::from([("host".to_string(), serde_json::json!(host))]);
+        Alert::new(AlertType::Generic, severity, source.to_string(), "disk almost full".to_string()).with_details(details)
+    }
+
+    #[test]
+    fn test_deduplicates_by_fingerprint() {
+        let lifecycle = AlertLifecycle::in_memory(LifecycleConfig::default());
+
+        let first = lifecycle.observe(&alert("disk", AlertSeverity::Warning, "node1")).unwrap();
+        assert!(!first.duplicate && first.notify);
+
+        // The same alert again is counted, not notified
+        let again = lifecycle.observe(&alert("disk", AlertSeverity::Warning, "node1")).unwrap();
+        assert!(again.duplicate && !again.notify);
+        assert_eq!((again.fingerprint.as_str(), again.alert_id), (first.fingerprint.as_str(), first.alert_id));
+        assert_eq!(lifecycle.get(&first.fingerprint).unwrap().count, 2);
+
+        // Escalation notifies; another host is another alert
+        assert!(lifecycle.observe(&alert("disk", AlertSeverity::Critical, "node1")).unwrap().notify);
+        let other = lifecycle.observe(&alert("disk", AlertSeverity::Warning, "node2")).unwrap();
+        assert!(!other.duplicate);
+
+        // Acknowledged alerts stay quiet until resolved
+        lifecycle.acknowledge(&other.fingerprint, "alice").unwrap();
+        assert!(!lifecycle.observe(&alert("disk", AlertSeverity::Critical, "node2")).unwrap().notify);
+        lifecycle.resolve(&other.fingerprint).unwrap();
+        assert!(lifecycle.observe(&alert("disk", AlertSeverity::Warning, "node2")).unwrap().notify);
+
+        let groups = lifecycle.groups();
+        assert_eq!(groups.len(), 1);
+        assert_eq!(groups[0].fingerprints.len(), 2);
+        assert_eq!(groups[0].severity, AlertSeverity::Critical);
+        assert_eq!(groups[0].labels["source"], "disk");
+    }
+
+    #[test]
+    fn test_silences_match_labels_in_window() {
+        let lifecycle = AlertLifecycle::in_memory(LifecycleConfig::default());
+        let matchers = vec!["source=disk".parse().unwrap(), "host!=node2".parse().unwrap()];
+        let silence = lifecycle
+            .add_silence(Silence::new(matchers, Duration::hours(1), "bob".to_string(), "maintenance".to_string()))
+            .unwrap();
+
+        let silenced = lifecycle.observe(&alert("disk", AlertSeverity::Error, "node1")).unwrap();
+        assert_eq!((silenced.silenced_by, silenced.notify), (Some(silence.id), false));
+        assert!(lifecycle.observe(&alert("disk", AlertSeverity::Error, "node2")).unwrap().notify);
+
+        lifecycle.expire_silence(silence.id).unwrap();
+        assert!(lifecycle.silences(false).is_empty());
+        assert_eq!(lifecycle.silences(true).len(), 1);
+        assert!(lifecycle.observe(&alert("cpu", AlertSeverity::Error, "node1")).unwrap().notify);
+
+        assert!("novalue".parse::<LabelMatcher>().is_err());
+        assert!(lifecycle
+            .add_silence(Silence::new(Vec::new(), Duration::hours(1), "bob".to_string(), String::new()))
+            .is_err());
+    }
+
+    #[test]
+    fn test_state_persists_across_restarts() {
+        let dir = std::env::temp_dir().join(format!("squirrel-alerts-{}", Uuid::new_v4()));
+        let config = LifecycleConfig {
+            state_path: Some(dir.join("alerts.json")),
+            ..LifecycleConfig::default()
+        };
+
+        let lifecycle = AlertLifecycle::open(config.clone()).unwrap();
+        let observation = lifecycle.observe(&alert("disk", AlertSeverity::Warning, "node1")).unwrap();
+        lifecycle.acknowledge(&observation.fingerprint, "alice").unwrap();
+
+        // Another instance sees the acknowledgment and dedups against it
+        let restarted = AlertLifecycle::open(config).unwrap();
+        let again = restarted.observe(&alert("disk", AlertSeverity::Warning, "node1")).unwrap();
+        assert!(again.duplicate && !again.notify);
+        assert_eq!(again.acknowledgment.unwrap().by, "alice");
+
+        // Changes made by the other instance are picked up
+        restarted.resolve(&observation.fingerprint).unwrap();
+        assert!(lifecycle.alerts().is_empty());
+
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+}
@@ -11,6 +11,7 @@ use uuid::Uuid;
 use std::collections::{HashMap, VecDeque};
 use tokio::sync::mpsc;
 use super::{NotificationManagerTrait, AlertNotification};
+use super::lifecycle::{AlertLifecycle, Observation};
 use thiserror::Error;
 
 /// Errors that can occur during alert management
@@ -57,9 +58,11 @@ where
     /// Alert notification routers
     notification_manager: Option<Arc<N>>,
     /// Channel for sending alerts
-    alert_tx: Option<mpsc::Sender<Alert>>,
+    alert_tx: Option<mpsc::Sender<(Alert, Observation)>>,
     /// Alert metrics
     metrics: Arc<RwLock<AlertMetrics>>,
+    /// Deduplication, silences and acknowledgments
+    lifecycle: Arc<AlertLifecycle>,
 }
 
 /// Metrics for the alert manager
@@ -82,7 +85,12 @@ where
     /// Creates a new alert manager with the specified configuration
     #[must_use]
     pub fn new(config: AlertConfig) -> Self {
+        let lifecycle = AlertLifecycle::open(config.lifecycle.clone()).unwrap_or_else(|e| {
+            tracing::warn!("Failed to load alert state, keeping it in memory: {}", e);
+            AlertLifecycle::in_memory(config.lifecycle.clone())
+        });
         Self {
+            lifecycle: Arc::new(lifecycle),
             config,
             alerts: Arc::new(RwLock::new(HashMap::new())),
             history: Arc::new(RwLock::new(VecDeque::new())),
@@ -107,15 +115,15 @@ where
         
         // Spawn a task to process alerts asynchronously
         tokio::spawn(async move {
-            while let Some(alert) = rx.recv().await {
-                // Store the alert
+            while let Some((alert, observation)) = rx.recv().await {
+                // Store the alert; a duplicate replaces the earlier occurrence
                 {
                     let mut alerts_lock = alerts.write().unwrap();
                     alerts_lock.insert(alert.id, alert.clone());
                 }
                 
                 // Update history
-                {
+                if !observation.duplicate {
                     let mut history_lock = history.write().unwrap();
                     history_lock.push_back(alert.clone());
                     while history_lock.len() > config.history_limit {
@@ -129,12 +137,14 @@ where
                     metrics_lock.total_alerts += 1;
                     *metrics_lock.alerts_by_severity.entry(alert.severity).or_insert(0) += 1;
                     *metrics_lock.alerts_by_source.entry(alert.source.clone()).or_insert(0) += 1;
-                    metrics_lock.active_alerts += 1;
+                    if !observation.duplicate && observation.acknowledgment.is_none() {
+                        metrics_lock.active_alerts += 1;
+                    }
                 }
                 
                 // Send notifications if configured
                 if let Some(notification_manager) = &notification_manager {
-                    if config.should_notify(alert.severity) {
+                    if observation.notify && config.should_notify(alert.severity) {
                         // Convert Alert to AlertNotification
                         let notification = AlertNotification {
                             id: alert.id.to_string(),
@@ -146,6 +156,7 @@ where
                                 .filter_map(|(k, v)| {
                                     v.as_str().map(|s| (k.clone(), s.to_string()))
                                 })
+                                .chain(std::iter::once(("fingerprint".to_string(), observation.fingerprint.clone())))
                                 .collect(),
                             created_at: alert.timestamp.timestamp(),
                             updated_at: alert.timestamp.timestamp(),
@@ -186,13 +197,22 @@ where
             alert = alert.with_details(details_map);
         }
         
-        // Send to processing channel
-        if let Some(tx) = &self.alert_tx {
-            if let Err(e) = tx.send(alert.clone()).await {
-                return Err(AlertError::StorageError(format!("Failed to send alert: {}", e)).into());
-            }
-        } else {
+        let Some(tx) = &self.alert_tx else {
             return Err(AlertError::ConfigError("Alert manager not initialized".to_string()).into());
+        };
+        
+        // Deduplicate against alerts that are already firing
+        let observation = self.lifecycle.observe(&alert)?;
+        alert.id = observation.alert_id;
+        if let Some(acknowledgment) = &observation.acknowledgment {
+            alert.acknowledged = true;
+            alert.acknowledged_by = Some(acknowledgment.by.clone());
+            alert.acknowledged_at = Some(acknowledgment.at);
+        }
+        
+        // Send to processing channel
+        if let Err(e) = tx.send((alert.clone(), observation)).await {
+            return Err(AlertError::StorageError(format!("Failed to send alert: {}", e)).into());
         }
         
         Ok(alert)
@@ -205,7 +225,10 @@ where
         let alert = alerts.get_mut(&alert_id)
             .ok_or(AlertError::NotFound(alert_id))?;
         
-        // Acknowledge it
+        // Acknowledge it, also for later occurrences
+        if let Some(fingerprint) = self.lifecycle.fingerprint_of(alert_id) {
+            self.lifecycle.acknowledge(&fingerprint, &by)?;
+        }
         alert.acknowledge(by);
         
         // Update metrics
@@ -215,6 +238,29 @@ where
         Ok(())
     }
     
+    /// Resolve an alert, so its next firing is notified as a new alert
+    pub async fn resolve_alert(&self, alert_id: Uuid) -> Result<Alert> {
+        let alert = self.alerts.write().unwrap()
+            .remove(&alert_id)
+            .ok_or(AlertError::NotFound(alert_id))?;
+        if let Some(fingerprint) = self.lifecycle.fingerprint_of(alert_id) {
+            self.lifecycle.resolve(&fingerprint)?;
+        }
+        
+        if !alert.acknowledged {
+            let mut metrics = self.metrics.write().unwrap();
+            metrics.active_alerts = metrics.active_alerts.saturating_sub(1);
+        }
+        
+        Ok(alert)
+    }
+    
+    /// Deduplication, grouping, silence and acknowledgment state
+    #[must_use]
+    pub fn lifecycle(&self) -> &Arc<AlertLifecycle> {
+        &self.lifecycle
+    }
+    
     /// Get all active alerts
     pub async fn get_active_alerts(&self) -> Result<Vec<Alert>> {
         let alerts = self.alerts.read().unwrap();
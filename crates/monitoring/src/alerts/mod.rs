@@ -29,6 +29,9 @@ pub mod adapter;
 /// Module for notification management
 pub mod notify;
 
+/// Module for alert deduplication, grouping, silences and acknowledgments
+pub mod lifecycle;
+
 /// Re-export key types from submodules
 pub use config::AlertConfig;
 pub use config::NotificationChannel;
@@ -37,6 +40,7 @@ pub use manager::AlertManagerAdapter;
 pub use manager::create_manager_adapter;
 pub use manager::create_manager_adapter_with_manager;
 pub use manager::AlertError;
+pub use lifecycle::{AlertGroup, AlertLifecycle, LabelMatcher, LifecycleConfig, Silence, TrackedAlert};
 pub use status::Alert;
 pub use status::AlertSeverity;
 pub use status::AlertType;
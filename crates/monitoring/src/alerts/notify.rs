@@ -7,15 +7,17 @@
 //! - Custom notification channels
 //! - Notification routing
 //! - Rate limiting
+//! - Templates rendering the notifications of a routing rule
 
 use std::sync::Arc;
 use tokio::sync::RwLock;
 use thiserror::Error;
 // use time::OffsetDateTime;
-// use handlebars::Handlebars;
 use serde::{Serialize, Deserialize};
 use chrono::{DateTime, Utc};
 // use chrono::Utc;
+use squirrel_core::expr::Expression;
+use squirrel_core::template::TemplateEngine;
 
 use super::{AlertNotification, LegacyAlertSeverity};
 use super::adapter::NotificationManagerAdapter;
@@ -65,9 +67,10 @@ pub struct NotificationTemplate {
     pub id: String,
     /// Template name
     pub name: String,
-    /// Template content
+    /// Template content, a Handlebars template over the alert
     pub content: String,
-    /// Template format (text, html, json, etc.)
+    /// Template format (text, html, json, etc.); `json` templates replace
+    /// the whole webhook payload
     pub format: String,
 }
 
@@ -97,6 +100,22 @@ pub struct RoutingRule {
     pub component: Option<String>,
     /// Target channels
     pub channels: Vec<String>,
+    /// Condition over the alert as JSON, e.g.
+    /// `labels.env == "prod" && contains(message, "disk")`
+    #[serde(default)]
+    pub condition: Option<Expression>,
+    /// ID of the template rendering the notifications of this rule
+    #[serde(default)]
+    pub template: Option<String>,
+}
+
+/// A notification rendered by a template
+#[derive(Debug, Clone)]
+struct RenderedNotification {
+    /// Rendered text
+    text: String,
+    /// Format of the template
+    format: String,
 }
 
 /// Errors that can occur during alert notification delivery.
@@ -134,7 +153,7 @@ pub struct NotificationManager {
     /// Configuration for notification delivery.
     config: Arc<RwLock<NotificationConfig>>,
     /// Template engine for rendering notifications.
-    // templates: Arc<RwLock<Handlebars<'static>>>,
+    templates: Arc<RwLock<TemplateEngine>>,
     /// HTTP client for webhook notifications.
     client: reqwest::Client,
     /// Rate limiter for notification channels.
@@ -162,6 +181,8 @@ struct EmailParams<'a> {
     to: &'a [String],
     /// Alert notification to be sent.
     alert: &'a AlertNotification,
+    /// Body rendered by the template of the routing rule, if any.
+    body: Option<&'a str>,
 }
 
 impl NotificationManager {
@@ -176,21 +197,11 @@ impl NotificationManager {
     /// Returns a `Result` containing the new manager if successful, or an error
     /// if the configuration is invalid or channels cannot be initialized.
     pub fn new(config: NotificationConfig) -> Result<Self, NotificationError> {
-        // Temporarily commenting out Handlebars initialization
-        /*
-        let mut handlebars = Handlebars::new();
-        
-        // Register templates
-        for template in &config.templates {
-            handlebars
-                .register_template_string(&template.id, &template.content)
-                .map_err(|e| NotificationError::TemplateError(e.to_string()))?;
-        }
-        */
+        let templates = Self::compile_templates(&config)?;
 
         Ok(Self {
             config: Arc::new(RwLock::new(config)),
-            // templates: Arc::new(RwLock::new(handlebars)),
+            templates: Arc::new(RwLock::new(templates)),
             client: reqwest::Client::new(),
             rate_limiter: Arc::new(RwLock::new(std::collections::HashMap::new())),
         })
@@ -218,6 +229,10 @@ impl NotificationManager {
 
         // Send to each matching channel
         for rule in matching_rules {
+            let rendered = match &rule.template {
+                Some(id) => Some(self.render(id, alert).await?),
+                None => None,
+            };
             for channel_id in &rule.channels {
                 // Check rate limit
                 if !self.check_rate_limit(channel_id).await? {
@@ -231,7 +246,7 @@ impl NotificationManager {
                     NotificationChannel::Webhook { .. } => channel_id == "webhook",
                 }) {
                     // Send notification through channel
-                    self.send_through_channel(channel, alert).await?;
+                    self.send_through_channel(channel, alert, rendered.as_ref()).await?;
                 }
             }
         }
@@ -239,11 +254,35 @@ impl NotificationManager {
         Ok(())
     }
 
+    /// Compiles the templates of a configuration
+    fn compile_templates(config: &NotificationConfig) -> Result<TemplateEngine, NotificationError> {
+        let templates = TemplateEngine::new();
+        for template in &config.templates {
+            templates
+                .register(&template.id, &template.content)
+                .map_err(|e| NotificationError::TemplateError(e.to_string()))?;
+        }
+        Ok(templates)
+    }
+
+    /// Renders the template `id` for an alert
+    async fn render(&self, id: &str, alert: &AlertNotification) -> Result<RenderedNotification, NotificationError> {
+        let format = self.config.read().await.templates.iter()
+            .find(|template| template.id == id)
+            .map(|template| template.format.clone())
+            .ok_or_else(|| NotificationError::TemplateError(format!("Template not configured: {id}")))?;
+        let text = self.templates.read().await
+            .render(id, alert)
+            .map_err(|e| NotificationError::TemplateError(e.to_string()))?;
+        Ok(RenderedNotification { text, format })
+    }
+
     /// Send notification through a specific channel
     async fn send_through_channel(
         &self,
         channel: &NotificationChannel,
         alert: &AlertNotification,
+        rendered: Option<&RenderedNotification>,
     ) -> Result<(), NotificationError> {
         match channel {
             NotificationChannel::Email {
@@ -262,14 +301,15 @@ impl NotificationManager {
                     from: from_address,
                     to: to_addresses,
                     alert,
+                    body: rendered.map(|rendered| rendered.text.as_str()),
                 };
                 self.send_email(params).await
             }
             NotificationChannel::Slack { webhook_url, channel, username } => {
-                self.send_slack(webhook_url, channel, username, alert).await
+                self.send_slack(webhook_url, channel, username, alert, rendered).await
             }
             NotificationChannel::Webhook { url, method, headers } => {
-                self.send_webhook(url, method, headers, alert).await
+                self.send_webhook(url, method, headers, alert, rendered).await
             }
         }
     }
@@ -284,7 +324,7 @@ impl NotificationManager {
         tracing::info!(
             "Would send email notification to {:?} for alert: {}",
             params.to,
-            params.alert.message
+            params.body.unwrap_or(&params.alert.message)
         );
         Ok(())
     }
@@ -296,9 +336,11 @@ impl NotificationManager {
         channel: &str,
         username: &str,
         alert: &AlertNotification,
+        rendered: Option<&RenderedNotification>,
     ) -> Result<(), NotificationError> {
         // Prepare slack message
         let color = get_severity_color(alert.severity);
+        let text = rendered.map_or_else(|| alert.description.clone(), |rendered| rendered.text.clone());
         
         let payload = serde_json::json!({
             "channel": channel,
@@ -309,7 +351,7 @@ impl NotificationManager {
                     "fallback": format!("Alert: {}", alert.message),
                     "color": color,
                     "title": format!("Alert: {}", alert.message),
-                    "text": alert.description.clone(),
+                    "text": text,
                     "fields": [
                         {
                             "title": "Severity",
@@ -358,17 +400,22 @@ impl NotificationManager {
         _method: &str,
         headers: &std::collections::HashMap<String, String>,
         alert: &AlertNotification,
+        rendered: Option<&RenderedNotification>,
     ) -> Result<(), NotificationError> {
-        // Prepare payload
-        let payload = serde_json::json!({
-            "alert": {
-                "message": alert.message,
-                "details": alert.description.clone(),
-                "severity": format!("{:?}", alert.severity),
-                "component": alert.component,
-                "timestamp": alert.updated_at,
-            }
-        });
+        // Prepare payload; a JSON template replaces it, others replace the details
+        let payload = match rendered {
+            Some(rendered) if rendered.format == "json" => serde_json::from_str(&rendered.text)
+                .map_err(|e| NotificationError::TemplateError(format!("Template did not render JSON: {e}")))?,
+            _ => serde_json::json!({
+                "alert": {
+                    "message": alert.message,
+                    "details": rendered.map_or_else(|| alert.description.clone(), |rendered| rendered.text.clone()),
+                    "severity": format!("{:?}", alert.severity),
+                    "component": alert.component,
+                    "timestamp": alert.updated_at,
+                }
+            }),
+        };
 
         // Create request
         let client = &self.client;
@@ -443,13 +490,91 @@ impl NotificationManager {
     /// # Errors
     /// Returns an error if the configuration lock cannot be acquired or if the configuration is invalid
     pub async fn update_config(&self, config: NotificationConfig) -> Result<(), NotificationError> {
-        // Update config
+        // Compile the templates first, so an invalid one leaves the config unchanged
+        let templates = Self::compile_templates(&config)?;
         let mut current_config = self.config.write().await;
         *current_config = config;
+        *self.templates.write().await = templates;
         
         Ok(())
     }
 
+    /// Sends a rendered report through a single channel.
+    ///
+    /// Reports bypass routing rules and rate limiting: the recipient names
+    /// the channel (`email`, `slack` or `webhook`) explicitly.
+    ///
+    /// # Errors
+    /// Returns an error if the channel is not configured or delivery fails
+    pub async fn send_report(
+        &self,
+        channel_id: &str,
+        subject: &str,
+        body: &str,
+        format: &str,
+    ) -> Result<(), NotificationError> {
+        let config = self.config.read().await;
+        let channel = config.channels.iter().find(|c| match c {
+            NotificationChannel::Email { .. } => channel_id == "email",
+            NotificationChannel::Slack { .. } => channel_id == "slack",
+            NotificationChannel::Webhook { .. } => channel_id == "webhook",
+        }).ok_or_else(|| NotificationError::ChannelError(format!("Channel not configured: {channel_id}")))?;
+
+        match channel {
+            NotificationChannel::Email { to_addresses, .. } => {
+                // TODO: Implement email sending using lettre or similar
+                tracing::info!("Would send {} report email to {:?}: {}", format, to_addresses, subject);
+                Ok(())
+            }
+            NotificationChannel::Slack { webhook_url, channel, username } => {
+                let payload = serde_json::json!({
+                    "channel": channel,
+                    "username": username,
+                    "text": format!("*{subject}*\n{body}"),
+                });
+                self.post_json(webhook_url, &std::collections::HashMap::new(), &payload, "Slack").await
+            }
+            NotificationChannel::Webhook { url, headers, .. } => {
+                let payload = serde_json::json!({
+                    "report": {
+                        "subject": subject,
+                        "format": format,
+                        "body": body,
+                    }
+                });
+                self.post_json(url, headers, &payload, "Webhook").await
+            }
+        }
+    }
+
+    /// Posts a JSON payload, treating non-success statuses as errors
+    async fn post_json(
+        &self,
+        url: &str,
+        headers: &std::collections::HashMap<String, String>,
+        payload: &serde_json::Value,
+        kind: &str,
+    ) -> Result<(), NotificationError> {
+        let mut request = self.client.post(url);
+        for (key, value) in headers {
+            request = request.header(key, value);
+        }
+
+        let res = request
+            .json(payload)
+            .send()
+            .await
+            .map_err(|e| NotificationError::ChannelError(format!("{kind} request error: {e}")))?;
+
+        if !res.status().is_success() {
+            return Err(NotificationError::ChannelError(
+                format!("{kind} returned error: {}", res.status())
+            ));
+        }
+
+        Ok(())
+    }
+
     /// Checks if a routing rule matches an alert.
     /// 
     /// # Arguments
@@ -479,6 +604,18 @@ impl NotificationManager {
             }
         }
 
+        // Check the condition; an alert it fails to evaluate on is not routed
+        if let Some(condition) = &rule.condition {
+            let input = serde_json::to_value(alert).unwrap_or_default();
+            match condition.test(&input) {
+                Ok(matched) => return matched,
+                Err(e) => {
+                    tracing::warn!("Routing rule {} condition failed: {}", rule.id, e);
+                    return false;
+                }
+            }
+        }
+
         true
     }
 }
@@ -549,4 +686,64 @@ impl NotificationManagerFactory {
 pub fn create_adapter(config: NotificationConfig) -> Result<Arc<NotificationManagerAdapter>, Box<dyn std::error::Error>> {
     let factory = NotificationManagerFactory::with_config(config);
     factory.create_adapter().map_err(|e| Box::<dyn std::error::Error>::from(e.to_string()))
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+    use crate::alerts::AlertStatus;
+
+    #[tokio::test]
+    async fn test_routing_rule_condition_and_template() {
+        let alert = AlertNotification {
+            id: "a1".to_string(),
+            name: "disk".to_string(),
+            description: String::new(),
+            severity: LegacyAlertSeverity::High,
+            status: AlertStatus::Active,
+            labels: HashMap::from([("env".to_string(), "prod".to_string())]),
+            created_at: 0,
+            updated_at: 0,
+            message: "Disk almost full".to_string(),
+            component: "storage".to_string(),
+        };
+        let rule = |condition: &str| RoutingRule {
+            id: "r1".to_string(),
+            name: "prod disk".to_string(),
+            severity: None,
+            component: Some("storage".to_string()),
+            channels: vec!["ops".to_string()],
+            condition: Some(Expression::condition(condition).unwrap()),
+            template: None,
+        };
+
+        assert!(NotificationManager::check_routing_rule(&rule(r#"labels.env == "prod" && contains(lower(message), "disk")"#), &alert));
+        assert!(!NotificationManager::check_routing_rule(&rule(r#"labels.env == "staging""#), &alert));
+        assert!(!NotificationManager::check_routing_rule(&rule("labels.missing > 1"), &alert));
+
+        let template = |content: &str| NotificationTemplate {
+            id: "ops".to_string(),
+            name: "Ops".to_string(),
+            content: content.to_string(),
+            format: "json".to_string(),
+        };
+        let config = NotificationConfig {
+            channels: Vec::new(),
+            rate_limit: 0,
+            templates: vec![template(r#"{"text": {{json message}}, "env": "{{upper labels.env}}"}"#)],
+            routing_rules: Vec::new(),
+        };
+        let manager = NotificationManager::new(config.clone()).unwrap();
+        let rendered = manager.render("ops", &alert).await.unwrap();
+        assert_eq!(rendered.text, r#"{"text": "Disk almost full", "env": "PROD"}"#);
+        assert!(manager.render("missing", &alert).await.is_err());
+
+        let invalid = NotificationConfig {
+            templates: vec![template("{{#if message}}")],
+            ..config
+        };
+        assert!(manager.update_config(invalid).await.is_err());
+        assert!(manager.render("ops", &alert).await.is_ok());
+    }
+}
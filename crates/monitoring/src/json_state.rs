@@ -0,0 +1,137 @@
+//! State kept in a JSON file shared between processes
+//!
+//! The server and the CLI both read and update some monitoring state, such
+//! as tracked alerts and the usage ledger. [`JsonFileState`] holds that state
+//! in memory, reads the file again when another process changed it, and
+//! replaces the file atomically after every update. Without a path the state
+//! lives in memory only.
+
+use std::fmt;
+use std::path::{Path, PathBuf};
+use std::sync::{PoisonError, RwLock, RwLockWriteGuard};
+use std::time::{Duration, SystemTime};
+
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use squirrel_core::error::{Result, SquirrelError};
+
+/// Files modified this recently are always read again, since they may have
+/// changed within the timestamp granularity without their stamp changing
+const RECENT_WRITE: Duration = Duration::from_secs(2);
+
+/// State and the file's modification time and size when it was last read or
+/// written
+#[derive(Debug, Default)]
+struct Inner<T> {
+    state: T,
+    file_stamp: Option<(SystemTime, u64)>,
+}
+
+/// State of type `T`, optionally persisted to a JSON file
+#[derive(Debug)]
+pub(crate) struct JsonFileState<T> {
+    path: Option<PathBuf>,
+    /// Turns a message naming the file and the failure into an error
+    error: fn(String) -> SquirrelError,
+    inner: RwLock<Inner<T>>,
+}
+
+impl<T: Default + Serialize + DeserializeOwned> JsonFileState<T> {
+    /// State persisted to `path`, loaded from the file if it exists
+    ///
+    /// # Errors
+    /// Returns an error if the file exists but cannot be read
+    pub(crate) fn open(path: Option<PathBuf>, error: fn(String) -> SquirrelError) -> Result<Self> {
+        let state = Self {
+            path,
+            error,
+            inner: RwLock::new(Inner::default()),
+        };
+        {
+            let mut inner = state.write();
+            state.reload(&mut inner)?;
+        }
+        Ok(state)
+    }
+
+    /// State kept in memory only
+    pub(crate) fn in_memory(error: fn(String) -> SquirrelError) -> Self {
+        Self {
+            path: None,
+            error,
+            inner: RwLock::new(Inner::default()),
+        }
+    }
+
+    /// Runs `f` on the current state
+    pub(crate) fn read<R>(&self, f: impl FnOnce(&T) -> R) -> R {
+        let mut inner = self.write();
+        if let Err(e) = self.reload(&mut inner) {
+            tracing::warn!("Failed to reload state: {}", e);
+        }
+        f(&inner.state)
+    }
+
+    /// Runs `f` on the current state and persists the result
+    ///
+    /// # Errors
+    /// Returns the error of `f`, or an error if the file cannot be read or
+    /// written
+    pub(crate) fn update<R>(&self, f: impl FnOnce(&mut T) -> Result<R>) -> Result<R> {
+        let mut inner = self.write();
+        self.reload(&mut inner)?;
+        let value = f(&mut inner.state)?;
+        self.save(&mut inner)?;
+        Ok(value)
+    }
+
+    /// Locks the state for writing
+    fn write(&self) -> RwLockWriteGuard<'_, Inner<T>> {
+        self.inner.write().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    /// Reads the file if it changed since it was last read or written
+    fn reload(&self, inner: &mut Inner<T>) -> Result<()> {
+        let Some(path) = &self.path else {
+            return Ok(());
+        };
+        let Some(stamp) = file_stamp(path) else {
+            return Ok(());
+        };
+        let recent = stamp.0.elapsed().map_or(true, |age| age < RECENT_WRITE);
+        if inner.file_stamp == Some(stamp) && !recent {
+            return Ok(());
+        }
+        let contents = std::fs::read_to_string(path).map_err(|e| self.storage_error(path, &e))?;
+        inner.state = serde_json::from_str(&contents).map_err(|e| self.storage_error(path, &e))?;
+        inner.file_stamp = Some(stamp);
+        Ok(())
+    }
+
+    /// Writes the file, replacing it atomically
+    fn save(&self, inner: &mut Inner<T>) -> Result<()> {
+        let Some(path) = &self.path else {
+            return Ok(());
+        };
+        if let Some(parent) = path.parent().filter(|parent| !parent.as_os_str().is_empty()) {
+            std::fs::create_dir_all(parent).map_err(|e| self.storage_error(path, &e))?;
+        }
+        let contents = serde_json::to_string_pretty(&inner.state).map_err(|e| self.storage_error(path, &e))?;
+        let temp = path.with_extension("tmp");
+        std::fs::write(&temp, contents).map_err(|e| self.storage_error(path, &e))?;
+        std::fs::rename(&temp, path).map_err(|e| self.storage_error(path, &e))?;
+        inner.file_stamp = file_stamp(path);
+        Ok(())
+    }
+
+    /// Storage error for the file at `path`
+    fn storage_error(&self, path: &Path, error: &dyn fmt::Display) -> SquirrelError {
+        (self.error)(format!("{}: {error}", path.display()))
+    }
+}
+
+/// Modification time and size of the file at `path`
+fn file_stamp(path: &Path) -> Option<(SystemTime, u64)> {
+    let metadata = std::fs::metadata(path).ok()?;
+    Some((metadata.modified().ok()?, metadata.len()))
+}
@@ -47,6 +47,21 @@ pub mod network;
 /// Module for dashboard functionality
 pub mod dashboard;
 
+/// Module for periodic report digests
+pub mod reports;
+
+/// Module for resource accounting and quotas
+pub mod accounting;
+
+/// Module for the memory watchdog shedding background work
+pub mod watchdog;
+
+/// Module for language model cost tracking and budgets
+pub mod llm_costs;
+
+/// Module for state persisted to JSON files shared between processes
+mod json_state;
+
 /// Configuration for the monitoring system
 #[derive(Debug, Clone, Serialize, Deserialize)]
 #[derive(Default)]
@@ -0,0 +1,519 @@
+// Language model cost tracking module
+//
+// Estimates what each call to a language model costs from its token counts
+// and sums the calls per user and provider for reports. Prices are in US
+// dollars per million tokens and are looked up by provider and model: a price
+// naming a model wins over one covering every model of its provider, and
+// calls without a price cost nothing.
+//
+// Budgets cap the cost of a user or a provider within a sliding window. The
+// call that takes a subject past `warn_percent` of a budget, and the one that
+// takes it past the budget itself, each raise an alert. Budgets only alert;
+// calls are never refused because of them.
+//
+// The tracker keeps calls in memory. Whoever persists the calls (the
+// assistant's audit log) replays them into a new tracker at startup.
+
+use std::collections::{BTreeMap, HashMap};
+use std::fmt;
+use std::sync::{Mutex, RwLock};
+
+use chrono::{DateTime, Duration, Utc};
+use serde::{Deserialize, Serialize};
+
+use crate::alerts::status::AlertType;
+use crate::alerts::{Alert, AlertSeverity};
+
+/// Source of the alerts budgets raise
+pub const ALERT_SOURCE: &str = "llm_costs";
+
+/// Price of a provider's model
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ModelPrice {
+    /// Provider the price applies to
+    pub provider: String,
+    /// Model the price applies to; `None` covers every model of the provider
+    #[serde(default)]
+    pub model: Option<String>,
+    /// Dollars per million prompt tokens
+    pub input_per_million: f64,
+    /// Dollars per million completion tokens
+    pub output_per_million: f64,
+}
+
+impl ModelPrice {
+    /// Cost in dollars of a call with the given token counts
+    #[must_use]
+    #[allow(clippy::cast_precision_loss)]
+    pub fn cost(&self, prompt_tokens: u64, completion_tokens: u64) -> f64 {
+        (prompt_tokens as f64 * self.input_per_million + completion_tokens as f64 * self.output_per_million)
+            / 1_000_000.0
+    }
+}
+
+/// What a budget limits the cost of
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum BudgetScope {
+    /// A user, across all providers
+    User,
+    /// A provider, across all users
+    Provider,
+}
+
+impl fmt::Display for BudgetScope {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::User => write!(f, "user"),
+            Self::Provider => write!(f, "provider"),
+        }
+    }
+}
+
+/// Limit on the cost of a user's or provider's calls within a sliding window
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct LlmBudget {
+    /// Whether the budget limits users or providers
+    pub scope: BudgetScope,
+    /// User or provider the budget applies to; `None` applies it to each one
+    #[serde(default)]
+    pub name: Option<String>,
+    /// Length of the window costs are summed over, in seconds
+    #[serde(default = "default_window_secs")]
+    pub window_secs: u64,
+    /// Most dollars spent in the window
+    pub max_cost_usd: f64,
+    /// Share of the budget, in percent, above which a warning is raised
+    #[serde(default = "default_warn_percent")]
+    pub warn_percent: f64,
+}
+
+const fn default_window_secs() -> u64 {
+    30 * 24 * 60 * 60
+}
+
+const fn default_warn_percent() -> f64 {
+    80.0
+}
+
+impl LlmBudget {
+    /// Whether the budget applies to calls of `user` to `provider`
+    #[must_use]
+    pub fn applies_to(&self, user: &str, provider: &str) -> bool {
+        self.name.as_deref().is_none_or(|name| name == self.subject(user, provider))
+    }
+
+    /// The user or provider the budget is about
+    fn subject<'a>(&self, user: &'a str, provider: &'a str) -> &'a str {
+        match self.scope {
+            BudgetScope::User => user,
+            BudgetScope::Provider => provider,
+        }
+    }
+}
+
+/// How much of a budget is spent
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum BudgetLevel {
+    /// Below the warning threshold
+    Ok,
+    /// Above the warning threshold
+    Warning,
+    /// At or above the budget
+    Exceeded,
+}
+
+/// Spending of a user or provider against one budget
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct BudgetStatus {
+    /// The budget
+    pub budget: LlmBudget,
+    /// User or provider the spending is of
+    pub subject: String,
+    /// Dollars spent within the budget's window
+    pub spent_usd: f64,
+    /// How much of the budget is spent
+    pub level: BudgetLevel,
+}
+
+impl fmt::Display for BudgetStatus {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} '{}' spent ${:.2} of its ${:.2} language model budget for {}s",
+            self.budget.scope, self.subject, self.spent_usd, self.budget.max_cost_usd, self.budget.window_secs
+        )
+    }
+}
+
+/// Language model cost settings
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct LlmCostConfig {
+    /// Prices calls are estimated with
+    pub prices: Vec<ModelPrice>,
+    /// Budgets that raise alerts as they are spent
+    pub budgets: Vec<LlmBudget>,
+}
+
+impl Default for LlmCostConfig {
+    fn default() -> Self {
+        // List prices when this was written; configure `prices` to keep them current
+        let openai = |model: &str, input_per_million, output_per_million| ModelPrice {
+            provider: "openai".to_string(),
+            model: Some(model.to_string()),
+            input_per_million,
+            output_per_million,
+        };
+        Self {
+            prices: vec![openai("gpt-4o-mini", 0.15, 0.60), openai("gpt-4o", 2.50, 10.00)],
+            budgets: Vec::new(),
+        }
+    }
+}
+
+/// A call to a language model
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct LlmCall {
+    /// When the call was made
+    pub at: DateTime<Utc>,
+    /// User the call was made for
+    pub user: String,
+    /// Provider called
+    pub provider: String,
+    /// Model called
+    pub model: String,
+    /// Tokens sent
+    pub prompt_tokens: u64,
+    /// Tokens received
+    pub completion_tokens: u64,
+    /// Time the model took to answer, in milliseconds
+    pub latency_ms: u64,
+    /// Estimated cost in dollars
+    pub cost_usd: f64,
+}
+
+/// Selects calls
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct LlmCostQuery {
+    /// Only calls at or after this time
+    pub from: Option<DateTime<Utc>>,
+    /// Only calls before this time
+    pub to: Option<DateTime<Utc>>,
+    /// Only this user
+    pub user: Option<String>,
+    /// Only this provider
+    pub provider: Option<String>,
+}
+
+impl LlmCostQuery {
+    /// Whether `call` is selected
+    #[must_use]
+    pub fn matches(&self, call: &LlmCall) -> bool {
+        self.from.is_none_or(|from| call.at >= from)
+            && self.to.is_none_or(|to| call.at < to)
+            && self.user.as_ref().is_none_or(|user| &call.user == user)
+            && self.provider.as_ref().is_none_or(|provider| &call.provider == provider)
+    }
+}
+
+/// Calls of one user to one provider, summed
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct LlmCostSummary {
+    /// User the calls were made for
+    pub user: String,
+    /// Provider called
+    pub provider: String,
+    /// Number of calls
+    pub calls: u64,
+    /// Tokens sent
+    pub prompt_tokens: u64,
+    /// Tokens received
+    pub completion_tokens: u64,
+    /// Estimated cost in dollars
+    pub cost_usd: f64,
+    /// Mean time the model took to answer, in milliseconds
+    pub mean_latency_ms: u64,
+}
+
+/// Prices language model calls, reports their cost and watches budgets
+#[derive(Debug)]
+pub struct LlmCostTracker {
+    config: LlmCostConfig,
+    calls: RwLock<Vec<LlmCall>>,
+    /// Level each budget was at for each subject after the last call
+    levels: Mutex<HashMap<(usize, String), BudgetLevel>>,
+}
+
+impl LlmCostTracker {
+    /// Creates a tracker without calls
+    #[must_use]
+    pub fn new(config: LlmCostConfig) -> Self {
+        Self {
+            config,
+            calls: RwLock::new(Vec::new()),
+            levels: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// The tracker's settings
+    #[must_use]
+    pub const fn config(&self) -> &LlmCostConfig {
+        &self.config
+    }
+
+    /// Estimated cost in dollars of a call to `model` of `provider`
+    #[must_use]
+    pub fn price(&self, provider: &str, model: &str, prompt_tokens: u64, completion_tokens: u64) -> f64 {
+        let prices = self.config.prices.iter().filter(|price| price.provider == provider);
+        let mut general = None;
+        for price in prices {
+            match price.model.as_deref() {
+                Some(priced) if priced == model => return price.cost(prompt_tokens, completion_tokens),
+                Some(_) => {}
+                None => general = general.or(Some(price)),
+            }
+        }
+        general.map_or(0.0, |price| price.cost(prompt_tokens, completion_tokens))
+    }
+
+    /// Adds calls made earlier, without raising alerts for them
+    pub fn replay(&self, calls: impl IntoIterator<Item = LlmCall>) {
+        let calls: Vec<LlmCall> = calls.into_iter().collect();
+        let subjects: Vec<(String, String)> = calls.iter().map(|call| (call.user.clone(), call.provider.clone())).collect();
+        self.calls_mut().extend(calls);
+        for (user, provider) in subjects {
+            self.update_levels(&user, &provider);
+        }
+    }
+
+    /// Adds `call`, returning an alert for each budget it takes to a higher level
+    pub fn record(&self, call: LlmCall) -> Vec<Alert> {
+        let (user, provider) = (call.user.clone(), call.provider.clone());
+        self.calls_mut().push(call);
+        self.update_levels(&user, &provider)
+            .into_iter()
+            .map(|status| budget_alert(&status))
+            .collect()
+    }
+
+    /// Calls selected by `query`, oldest first
+    #[must_use]
+    pub fn calls(&self, query: &LlmCostQuery) -> Vec<LlmCall> {
+        let mut calls: Vec<LlmCall> = self
+            .calls
+            .read()
+            .unwrap_or_else(std::sync::PoisonError::into_inner)
+            .iter()
+            .filter(|call| query.matches(call))
+            .cloned()
+            .collect();
+        calls.sort_by_key(|call| call.at);
+        calls
+    }
+
+    /// The calls selected by `query`, summed per user and provider
+    #[must_use]
+    pub fn report(&self, query: &LlmCostQuery) -> Vec<LlmCostSummary> {
+        let mut summaries: BTreeMap<(String, String), (LlmCostSummary, u64)> = BTreeMap::new();
+        for call in self.calls(query) {
+            let (summary, latency_ms) = summaries.entry((call.user.clone(), call.provider.clone())).or_insert_with(|| {
+                let summary = LlmCostSummary {
+                    user: call.user.clone(),
+                    provider: call.provider.clone(),
+                    ..LlmCostSummary::default()
+                };
+                (summary, 0)
+            });
+            summary.calls += 1;
+            summary.prompt_tokens = summary.prompt_tokens.saturating_add(call.prompt_tokens);
+            summary.completion_tokens = summary.completion_tokens.saturating_add(call.completion_tokens);
+            summary.cost_usd += call.cost_usd;
+            *latency_ms = latency_ms.saturating_add(call.latency_ms);
+        }
+        summaries
+            .into_values()
+            .map(|(mut summary, latency_ms)| {
+                summary.mean_latency_ms = latency_ms / summary.calls.max(1);
+                summary
+            })
+            .collect()
+    }
+
+    /// Spending of `user` and `provider` against every budget that applies
+    #[must_use]
+    pub fn budget_status(&self, user: &str, provider: &str) -> Vec<BudgetStatus> {
+        self.statuses(user, provider).into_iter().map(|(_, status)| status).collect()
+    }
+
+    /// Budget statuses with the index of their budget
+    fn statuses(&self, user: &str, provider: &str) -> Vec<(usize, BudgetStatus)> {
+        let now = Utc::now();
+        let calls = self.calls.read().unwrap_or_else(std::sync::PoisonError::into_inner);
+        self.config
+            .budgets
+            .iter()
+            .enumerate()
+            .filter(|(_, budget)| budget.applies_to(user, provider))
+            .map(|(index, budget)| {
+                let since = now - Duration::seconds(i64::try_from(budget.window_secs).unwrap_or(i64::MAX / 1000));
+                let subject = budget.subject(user, provider);
+                let spent_usd: f64 = calls
+                    .iter()
+                    .filter(|call| call.at > since && budget.subject(&call.user, &call.provider) == subject)
+                    .map(|call| call.cost_usd)
+                    .sum();
+                let level = if spent_usd >= budget.max_cost_usd {
+                    BudgetLevel::Exceeded
+                } else if spent_usd >= budget.max_cost_usd * budget.warn_percent / 100.0 {
+                    BudgetLevel::Warning
+                } else {
+                    BudgetLevel::Ok
+                };
+                let status = BudgetStatus {
+                    budget: budget.clone(),
+                    subject: subject.to_string(),
+                    spent_usd,
+                    level,
+                };
+                (index, status)
+            })
+            .collect()
+    }
+
+    /// Remembers the level of each budget of `user` and `provider`,
+    /// returning the statuses that rose
+    fn update_levels(&self, user: &str, provider: &str) -> Vec<BudgetStatus> {
+        let statuses = self.statuses(user, provider);
+        let mut levels = self.levels.lock().unwrap_or_else(std::sync::PoisonError::into_inner);
+        statuses
+            .into_iter()
+            .filter_map(|(index, status)| {
+                let previous = levels.insert((index, status.subject.clone()), status.level);
+                (status.level > previous.unwrap_or(BudgetLevel::Ok)).then_some(status)
+            })
+            .collect()
+    }
+
+    /// Locks the calls for writing
+    fn calls_mut(&self) -> std::sync::RwLockWriteGuard<'_, Vec<LlmCall>> {
+        self.calls.write().unwrap_or_else(std::sync::PoisonError::into_inner)
+    }
+}
+
+/// Alert about a budget that reached `status.level`
+fn budget_alert(status: &BudgetStatus) -> Alert {
+    let (severity, state) = match status.level {
+        BudgetLevel::Exceeded => (AlertSeverity::Error, "is over budget"),
+        BudgetLevel::Warning | BudgetLevel::Ok => (AlertSeverity::Warning, "is nearing its budget"),
+    };
+    let message = format!("Language model spending {state}: {status}");
+    // Scope and subject are strings so each budget keeps one fingerprint
+    let details = HashMap::from([
+        ("scope".to_string(), serde_json::json!(status.budget.scope.to_string())),
+        ("subject".to_string(), serde_json::json!(status.subject)),
+        ("spent_usd".to_string(), serde_json::json!(status.spent_usd)),
+        ("max_cost_usd".to_string(), serde_json::json!(status.budget.max_cost_usd)),
+    ]);
+    Alert::new(AlertType::Generic, severity, ALERT_SOURCE.to_string(), message).with_details(details)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn call(user: &str, provider: &str, cost_usd: f64) -> LlmCall {
+        LlmCall {
+            at: Utc::now(),
+            user: user.to_string(),
+            provider: provider.to_string(),
+            model: "gpt-4o-mini".to_string(),
+            prompt_tokens: 1000,
+            completion_tokens: 100,
+            latency_ms: 400,
+            cost_usd,
+        }
+    }
+
+    #[test]
+    fn test_prices_prefer_the_model() {
+        let mut config = LlmCostConfig::default();
+        config.prices.push(ModelPrice {
+            provider: "openai".to_string(),
+            model: None,
+            input_per_million: 1.0,
+            output_per_million: 1.0,
+        });
+        let tracker = LlmCostTracker::new(config);
+        assert!((tracker.price("openai", "gpt-4o", 1_000_000, 100_000) - 3.5).abs() < 1e-9);
+        assert!((tracker.price("openai", "o1", 500_000, 500_000) - 1.0).abs() < 1e-9);
+        assert!(tracker.price("ollama", "llama3", 1_000_000, 1_000_000).abs() < f64::EPSILON);
+    }
+
+    #[test]
+    fn test_report_sums_per_user_and_provider() {
+        let tracker = LlmCostTracker::new(LlmCostConfig::default());
+        tracker.record(call("alice", "openai", 0.5));
+        tracker.record(LlmCall { latency_ms: 600, ..call("alice", "openai", 0.25) });
+        tracker.record(call("alice", "ollama", 0.0));
+        tracker.record(call("bob", "openai", 1.0));
+
+        let report = tracker.report(&LlmCostQuery {
+            user: Some("alice".to_string()),
+            ..LlmCostQuery::default()
+        });
+        assert_eq!(report.len(), 2);
+        assert_eq!(report[0].provider, "ollama");
+        let openai = &report[1];
+        assert_eq!((openai.calls, openai.prompt_tokens, openai.mean_latency_ms), (2, 2000, 500));
+        assert!((openai.cost_usd - 0.75).abs() < 1e-9);
+
+        let later = LlmCostQuery { from: Some(Utc::now() + Duration::hours(1)), ..LlmCostQuery::default() };
+        assert!(tracker.report(&later).is_empty());
+    }
+
+    #[test]
+    fn test_budgets_alert_once_per_level() {
+        let config = LlmCostConfig {
+            budgets: vec![
+                LlmBudget {
+                    scope: BudgetScope::User,
+                    name: None,
+                    window_secs: 3600,
+                    max_cost_usd: 1.0,
+                    warn_percent: 50.0,
+                },
+                LlmBudget {
+                    scope: BudgetScope::Provider,
+                    name: Some("openai".to_string()),
+                    window_secs: 3600,
+                    max_cost_usd: 10.0,
+                    warn_percent: 80.0,
+                },
+            ],
+            ..LlmCostConfig::default()
+        };
+        let tracker = LlmCostTracker::new(config);
+        assert!(tracker.record(call("alice", "openai", 0.4)).is_empty());
+        let alerts = tracker.record(call("alice", "openai", 0.2));
+        assert_eq!(alerts.len(), 1);
+        assert_eq!(alerts[0].severity, AlertSeverity::Warning);
+        assert_eq!(alerts[0].details["subject"], "alice");
+        assert!(tracker.record(call("alice", "openai", 0.1)).is_empty());
+        let alerts = tracker.record(call("alice", "openai", 0.3));
+        assert_eq!(alerts[0].severity, AlertSeverity::Error);
+        assert!(alerts[0].message.contains("user 'alice' spent $1.00 of its $1.00"));
+        assert!(tracker.record(call("bob", "openai", 0.1)).is_empty());
+
+        let statuses = tracker.budget_status("bob", "openai");
+        assert_eq!(statuses.len(), 2);
+        assert!((statuses[1].spent_usd - 1.1).abs() < 1e-9);
+        assert_eq!(tracker.budget_status("carol", "ollama").len(), 1);
+
+        // Replayed calls set the levels without alerting
+        let replayed = LlmCostTracker::new(tracker.config().clone());
+        replayed.replay(tracker.calls(&LlmCostQuery::default()));
+        assert!(replayed.record(call("alice", "openai", 0.1)).is_empty());
+    }
+}
@@ -0,0 +1,144 @@
+//! Sharded append buffers for metric recording
+//!
+//! Recording a metric only appends it to one of several shards, each behind
+//! its own mutex. Threads are spread over the shards, so tool executions on
+//! different runtime workers almost never contend. The collector drains all
+//! shards at once when metrics are read and periodically from a background
+//! aggregator.
+
+use std::cell::Cell;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Mutex, PoisonError};
+
+use super::Metric;
+
+/// Source of the shard hints handed to new threads
+static NEXT_SHARD: AtomicUsize = AtomicUsize::new(0);
+
+thread_local! {
+    /// Shard the current thread appends to, modulo the shard count
+    static SHARD_HINT: Cell<usize> = Cell::new(NEXT_SHARD.fetch_add(1, Ordering::Relaxed));
+}
+
+/// Metrics recorded since the last drain, spread over per-thread shards
+#[derive(Debug)]
+pub struct ShardedBuffer {
+    /// Append buffers, oldest metric first
+    shards: Box<[Mutex<Vec<Metric>>]>,
+    /// Number of metrics a shard keeps before dropping its oldest ones
+    capacity: usize,
+}
+
+impl ShardedBuffer {
+    /// Creates a buffer with one shard per available core, each keeping up
+    /// to `capacity` metrics between drains
+    #[must_use]
+    pub fn new(capacity: usize) -> Self {
+        let shards = std::thread::available_parallelism().map_or(1, std::num::NonZeroUsize::get);
+        Self::with_shards(shards, capacity)
+    }
+
+    /// Creates a buffer with `shards` shards
+    #[must_use]
+    pub fn with_shards(shards: usize, capacity: usize) -> Self {
+        Self {
+            shards: (0..shards.max(1)).map(|_| Mutex::new(Vec::new())).collect(),
+            capacity: capacity.max(1),
+        }
+    }
+
+    /// Number of shards
+    #[must_use]
+    pub fn shard_count(&self) -> usize {
+        self.shards.len()
+    }
+
+    /// Appends a metric to the current thread's shard
+    ///
+    /// A shard holding twice its capacity drops its oldest metrics down to
+    /// the capacity; the collector would truncate them on the next drain
+    /// anyway.
+    pub fn push(&self, metric: Metric) {
+        self.extend(std::iter::once(metric));
+    }
+
+    /// Appends metrics to the current thread's shard, locking it once
+    pub fn extend(&self, metrics: impl IntoIterator<Item = Metric>) {
+        let index = SHARD_HINT.with(Cell::get) % self.shards.len();
+        let mut shard = self.shards[index].lock().unwrap_or_else(PoisonError::into_inner);
+        for metric in metrics {
+            if shard.len() >= self.capacity * 2 {
+                let excess = shard.len() - self.capacity;
+                shard.drain(..excess);
+            }
+            shard.push(metric);
+        }
+    }
+
+    /// Number of buffered metrics
+    #[must_use]
+    pub fn len(&self) -> usize {
+        self.shards
+            .iter()
+            .map(|shard| shard.lock().unwrap_or_else(PoisonError::into_inner).len())
+            .sum()
+    }
+
+    /// Whether no metrics are buffered
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Takes all buffered metrics, leaving the shards empty
+    #[must_use]
+    pub fn drain(&self) -> Vec<Metric> {
+        let mut drained = Vec::new();
+        for shard in self.shards.iter() {
+            let mut shard = shard.lock().unwrap_or_else(PoisonError::into_inner);
+            if drained.is_empty() {
+                drained = std::mem::take(&mut *shard);
+            } else {
+                drained.append(&mut shard);
+            }
+        }
+        drained
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::metrics::MetricType;
+    use std::collections::HashMap;
+
+    fn metric(value: f64) -> Metric {
+        Metric::new("buffered", value, MetricType::Gauge, HashMap::new())
+    }
+
+    #[test]
+    fn test_drain_collects_all_shards() {
+        let buffer = ShardedBuffer::with_shards(4, 100);
+        std::thread::scope(|scope| {
+            for _ in 0..8 {
+                scope.spawn(|| buffer.extend((0..10).map(f64::from).map(metric)));
+            }
+        });
+
+        assert_eq!(buffer.len(), 80);
+        assert_eq!(buffer.drain().len(), 80);
+        assert!(buffer.is_empty());
+    }
+
+    #[test]
+    fn test_full_shard_drops_oldest_metrics() {
+        let buffer = ShardedBuffer::with_shards(1, 3);
+        for i in 0..6 {
+            buffer.push(metric(f64::from(i)));
+        }
+        buffer.push(metric(6.0));
+
+        let values: Vec<f64> = buffer.drain().iter().map(|m| m.value).collect();
+        assert_eq!(values, [3.0, 4.0, 5.0, 6.0]);
+    }
+}
@@ -2,7 +2,13 @@
 //! 
 //! Supports exporting metrics to:
 //! - Prometheus
+//! - StatsD over UDP
+//! - InfluxDB via the line protocol
 //! - Custom formats via trait implementation
+//!
+//! The StatsD and InfluxDB backends are configured in
+//! [`MetricConfig::backends`](super::MetricConfig::backends) and push
+//! metrics in batches, buffering them while the backend is unreachable.
 
 use std::fmt::Debug;
 use std::sync::Arc;
@@ -22,6 +28,17 @@ use serde_json;
 pub mod adapter;
 pub use adapter::{MetricExporterAdapter, create_exporter_adapter, create_exporter_adapter_with_exporter};
 
+/// Batching and failure buffering shared by push-based backends
+pub mod batching;
+/// StatsD backend
+pub mod statsd;
+/// InfluxDB line protocol backend
+pub mod influx;
+
+pub use batching::{BatchConfig, BatchingExporter, MetricTransport};
+pub use influx::{InfluxConfig, InfluxExporter, InfluxTransport};
+pub use statsd::{StatsdConfig, StatsdExporter, StatsdTransport};
+
 /// Configuration for metric export functionality.
 /// 
 /// This struct defines how metrics should be exported, including the format,
@@ -96,6 +113,14 @@ pub trait MetricExporter: Debug + Send + Sync {
     /// export was successful.
     fn export(&self, metrics: Vec<Metric>) -> Box<dyn Future<Output = Result<()>> + Send + '_>;
     
+    /// Sends any metrics the exporter is holding back.
+    /// 
+    /// Exporters that send metrics as soon as they are exported have nothing
+    /// to flush.
+    fn flush(&self) -> Box<dyn Future<Output = Result<()>> + Send + '_> {
+        Box::new(async { Ok(()) })
+    }
+    
     /// Returns the name of the exporter.
     /// 
     /// This name is used to identify the exporter in configuration and logs.
@@ -259,6 +284,36 @@ pub fn create_exporter(config: &ExporterConfig) -> Result<Arc<dyn MetricExporter
     Ok(exporter)
 }
 
+/// A push-based metric backend and its settings
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "lowercase")]
+pub enum MetricBackendConfig {
+    /// StatsD over UDP
+    Statsd(StatsdConfig),
+    /// InfluxDB line protocol over HTTP
+    Influxdb(InfluxConfig),
+}
+
+/// Creates the exporter for a configured backend.
+/// 
+/// When called inside a Tokio runtime, a task flushing the exporter every
+/// flush interval is started as well; it stops when the exporter is dropped.
+#[must_use]
+pub fn create_backend_exporter(config: &MetricBackendConfig) -> Arc<dyn MetricExporter + Send + Sync> {
+    match config {
+        MetricBackendConfig::Statsd(config) => {
+            let exporter = Arc::new(StatsdTransport::exporter(config.clone()));
+            exporter.spawn_flush_task();
+            exporter
+        }
+        MetricBackendConfig::Influxdb(config) => {
+            let exporter = Arc::new(InfluxTransport::exporter(config.clone()));
+            exporter.spawn_flush_task();
+            exporter
+        }
+    }
+}
+
 /// Prometheus-specific metric exporter implementation.
 /// 
 /// This exporter formats metrics in Prometheus format and exports them
@@ -0,0 +1,277 @@
+//! Batching and failure buffering for push-based metric backends
+//!
+//! [`BatchingExporter`] queues exported metrics and hands them to a
+//! [`MetricTransport`] in batches, either once a full batch is queued or when
+//! the flush interval has elapsed. Sending a batch is retried under the
+//! exporter's [`RetryPolicy`]; a batch that still cannot be sent stays queued
+//! and is retried on the next flush, so a backend outage loses nothing until
+//! the queue reaches its limit; past that the oldest metrics are dropped and
+//! counted.
+
+use std::collections::VecDeque;
+use std::fmt::Debug;
+use std::future::Future;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Weak};
+use std::time::{Duration, Instant};
+
+use async_trait::async_trait;
+use serde::{Deserialize, Serialize};
+use tokio::sync::Mutex;
+use tokio::task::JoinHandle;
+use squirrel_core::error::Result;
+use squirrel_core::retry::{retry_with_policy, RetryPolicy, RetryResultExt};
+
+use super::MetricExporter;
+use crate::metrics::Metric;
+
+/// Batching settings of a push-based backend
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct BatchConfig {
+    /// Maximum number of metrics sent at once
+    pub batch_size: usize,
+    /// Seconds between flushes of a partial batch; 0 flushes full batches only
+    pub flush_interval_secs: u64,
+    /// Maximum number of metrics queued while the backend is unreachable
+    pub max_buffered: usize,
+}
+
+impl Default for BatchConfig {
+    fn default() -> Self {
+        Self {
+            batch_size: 500,
+            flush_interval_secs: 10,
+            max_buffered: 10_000,
+        }
+    }
+}
+
+/// Sends batches of metrics to a backend
+#[async_trait]
+pub trait MetricTransport: Debug + Send + Sync + 'static {
+    /// Name of the backend
+    fn name(&self) -> &str;
+
+    /// Sends one batch
+    ///
+    /// # Errors
+    /// Returns an error if the batch could not be delivered; it is retried
+    async fn send(&self, batch: &[Metric]) -> Result<()>;
+}
+
+/// Exporter queueing metrics and sending them through a transport in batches
+#[derive(Debug)]
+pub struct BatchingExporter<T: MetricTransport> {
+    /// Backend the batches are sent to
+    transport: T,
+    /// Batching settings
+    config: BatchConfig,
+    /// Queued metrics and the time of the last flush
+    queue: Mutex<(VecDeque<Metric>, Instant)>,
+    /// Metrics dropped because the queue was full
+    dropped: AtomicU64,
+    /// How sending a batch is retried within a flush
+    retry: RetryPolicy,
+}
+
+impl<T: MetricTransport> BatchingExporter<T> {
+    /// Creates an exporter sending through `transport`
+    #[must_use]
+    pub fn new(transport: T, config: BatchConfig) -> Self {
+        Self {
+            transport,
+            config,
+            queue: Mutex::new((VecDeque::new(), Instant::now())),
+            dropped: AtomicU64::new(0),
+            retry: RetryPolicy::default(),
+        }
+    }
+
+    /// Retries sending a batch under `retry` within each flush
+    #[must_use]
+    pub fn with_retry_policy(mut self, retry: RetryPolicy) -> Self {
+        self.retry = retry;
+        self
+    }
+
+    /// The transport metrics are sent through
+    #[must_use]
+    pub const fn transport(&self) -> &T {
+        &self.transport
+    }
+
+    /// Number of metrics waiting to be sent
+    pub async fn buffered(&self) -> usize {
+        self.queue.lock().await.0.len()
+    }
+
+    /// Number of metrics dropped because the queue was full
+    #[must_use]
+    pub fn dropped(&self) -> u64 {
+        self.dropped.load(Ordering::Relaxed)
+    }
+
+    /// Queues `metrics`, flushing if a batch is full or the interval elapsed
+    ///
+    /// # Errors
+    /// Returns an error if a flush was due and failed; the metrics stay queued
+    pub async fn enqueue(&self, metrics: Vec<Metric>) -> Result<()> {
+        let due = {
+            let mut queue = self.queue.lock().await;
+            queue.0.extend(metrics);
+            let overflow = queue.0.len().saturating_sub(self.config.max_buffered);
+            if overflow > 0 {
+                queue.0.drain(..overflow);
+                self.dropped.fetch_add(overflow as u64, Ordering::Relaxed);
+                tracing::warn!("Dropped {} metrics queued for {}", overflow, self.transport.name());
+            }
+            queue.0.len() >= self.config.batch_size.max(1) || self.interval_elapsed(queue.1)
+        };
+        if due {
+            self.flush_queue().await
+        } else {
+            Ok(())
+        }
+    }
+
+    /// Sends all queued metrics
+    ///
+    /// # Errors
+    /// Returns an error if a batch could not be sent; it and every later
+    /// batch stay queued
+    pub async fn flush_queue(&self) -> Result<()> {
+        let mut queue = self.queue.lock().await;
+        queue.1 = Instant::now();
+        while !queue.0.is_empty() {
+            let size = self.config.batch_size.max(1).min(queue.0.len());
+            let batch: Vec<Metric> = queue.0.range(..size).cloned().collect();
+            let operation = format!("metrics.export.{}", self.transport.name());
+            let sent = retry_with_policy(&operation, &self.retry, || async {
+                self.transport.send(&batch).await.transient()
+            })
+            .await;
+            if let Err(err) = sent {
+                tracing::warn!(
+                    "Failed to send {} metrics to {}, {} queued for retry: {}",
+                    batch.len(),
+                    self.transport.name(),
+                    queue.0.len(),
+                    err
+                );
+                return Err(err);
+            }
+            queue.0.drain(..size);
+        }
+        Ok(())
+    }
+
+    /// Spawns a task flushing the queue every flush interval
+    ///
+    /// The task stops once the exporter is dropped. Returns `None` if no
+    /// flush interval is configured or no Tokio runtime is running.
+    pub fn spawn_flush_task(self: &Arc<Self>) -> Option<JoinHandle<()>> {
+        if self.config.flush_interval_secs == 0 {
+            return None;
+        }
+        let runtime = tokio::runtime::Handle::try_current().ok()?;
+        let period = Duration::from_secs(self.config.flush_interval_secs);
+        let exporter: Weak<Self> = Arc::downgrade(self);
+        Some(runtime.spawn(async move {
+            let mut interval = tokio::time::interval(period);
+            interval.tick().await;
+            loop {
+                interval.tick().await;
+                let Some(exporter) = exporter.upgrade() else {
+                    break;
+                };
+                // Failures are logged by the flush and retried next time
+                let _ = exporter.flush_queue().await;
+            }
+        }))
+    }
+
+    /// Whether a partial batch is due since the flush at `last_flush`
+    fn interval_elapsed(&self, last_flush: Instant) -> bool {
+        self.config.flush_interval_secs > 0
+            && last_flush.elapsed() >= Duration::from_secs(self.config.flush_interval_secs)
+    }
+}
+
+impl<T: MetricTransport> MetricExporter for BatchingExporter<T> {
+    fn export(&self, metrics: Vec<Metric>) -> Box<dyn Future<Output = Result<()>> + Send + '_> {
+        Box::new(self.enqueue(metrics))
+    }
+
+    fn flush(&self) -> Box<dyn Future<Output = Result<()>> + Send + '_> {
+        Box::new(self.flush_queue())
+    }
+
+    fn name(&self) -> &str {
+        self.transport.name()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+    use std::sync::atomic::AtomicBool;
+    use crate::metrics::MetricType;
+
+    /// Transport recording batch sizes, failing while `down` is set
+    #[derive(Debug, Default)]
+    struct FlakyTransport {
+        down: AtomicBool,
+        sent: std::sync::Mutex<Vec<usize>>,
+    }
+
+    #[async_trait]
+    impl MetricTransport for FlakyTransport {
+        fn name(&self) -> &str {
+            "flaky"
+        }
+
+        async fn send(&self, batch: &[Metric]) -> Result<()> {
+            if self.down.load(Ordering::SeqCst) {
+                return Err("backend unreachable".into());
+            }
+            self.sent.lock().unwrap().push(batch.len());
+            Ok(())
+        }
+    }
+
+    fn metrics(count: usize) -> Vec<Metric> {
+        (0..count)
+            .map(|i| Metric::new(format!("m{i}"), i as f64, MetricType::Gauge, HashMap::new()))
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn test_batches_and_buffers_failures() {
+        let config = BatchConfig {
+            batch_size: 3,
+            flush_interval_secs: 0,
+            max_buffered: 5,
+        };
+        let exporter = BatchingExporter::new(FlakyTransport::default(), config);
+
+        // A partial batch waits
+        exporter.enqueue(metrics(2)).await.unwrap();
+        assert_eq!(exporter.buffered().await, 2);
+        assert!(exporter.transport().sent.lock().unwrap().is_empty());
+
+        // A failing flush keeps everything, dropping the oldest past the limit
+        exporter.transport().down.store(true, Ordering::SeqCst);
+        assert!(exporter.enqueue(metrics(2)).await.is_err());
+        assert!(exporter.enqueue(metrics(2)).await.is_err());
+        assert_eq!(exporter.buffered().await, 5);
+        assert_eq!(exporter.dropped(), 1);
+
+        // Once the backend is back the queue drains in batches
+        exporter.transport().down.store(false, Ordering::SeqCst);
+        exporter.flush_queue().await.unwrap();
+        assert_eq!(exporter.buffered().await, 0);
+        assert_eq!(*exporter.transport().sent.lock().unwrap(), vec![3, 2]);
+    }
+}
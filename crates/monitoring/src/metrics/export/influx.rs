@@ -0,0 +1,155 @@
+//! InfluxDB exporter
+//!
+//! Writes metrics in the InfluxDB line protocol to a write endpoint over
+//! HTTP. Each metric becomes one point: the metric name is the measurement,
+//! labels are tags, the value is the `value` field and the timestamp is sent
+//! with second precision. Both the 1.x `/write?db=...` and the 2.x
+//! `/api/v2/write?org=...&bucket=...` endpoints accept this format.
+
+use std::collections::BTreeMap;
+use std::fmt::Write as _;
+use std::time::Duration;
+
+use async_trait::async_trait;
+use serde::{Deserialize, Serialize};
+use squirrel_core::error::{Result, SquirrelError};
+
+use super::batching::{BatchConfig, BatchingExporter, MetricTransport};
+use crate::metrics::Metric;
+
+/// Exporter writing metrics to InfluxDB
+pub type InfluxExporter = BatchingExporter<InfluxTransport>;
+
+/// Configuration of the InfluxDB backend
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct InfluxConfig {
+    /// Write endpoint including the database or org and bucket parameters
+    pub url: String,
+    /// API token, sent as `Authorization: Token <token>`
+    pub token: Option<String>,
+    /// Request timeout in seconds
+    pub timeout_secs: u64,
+    /// Batching settings
+    #[serde(flatten)]
+    pub batching: BatchConfig,
+}
+
+impl Default for InfluxConfig {
+    fn default() -> Self {
+        Self {
+            url: "http://localhost:8086/api/v2/write?org=squirrel&bucket=squirrel".to_string(),
+            token: None,
+            timeout_secs: 10,
+            batching: BatchConfig::default(),
+        }
+    }
+}
+
+/// HTTP transport for line protocol writes
+#[derive(Debug)]
+pub struct InfluxTransport {
+    /// Backend configuration
+    config: InfluxConfig,
+    /// HTTP client
+    client: reqwest::Client,
+}
+
+impl InfluxTransport {
+    /// Creates a transport for `config`
+    #[must_use]
+    pub fn new(config: InfluxConfig) -> Self {
+        let client = reqwest::Client::builder()
+            .timeout(Duration::from_secs(config.timeout_secs))
+            .build()
+            .unwrap_or_default();
+        Self { config, client }
+    }
+
+    /// Creates a batching exporter for `config`
+    #[must_use]
+    pub fn exporter(config: InfluxConfig) -> InfluxExporter {
+        let batching = config.batching.clone();
+        BatchingExporter::new(Self::new(config), batching)
+    }
+}
+
+#[async_trait]
+impl MetricTransport for InfluxTransport {
+    fn name(&self) -> &str {
+        "influxdb"
+    }
+
+    async fn send(&self, batch: &[Metric]) -> Result<()> {
+        let body = batch.iter().map(encode).collect::<Vec<_>>().join("\n");
+        let mut request = self
+            .client
+            .post(&self.config.url)
+            .query(&[("precision", "s")])
+            .header(reqwest::header::CONTENT_TYPE, "text/plain; charset=utf-8")
+            .body(body);
+        if let Some(token) = &self.config.token {
+            request = request.header(reqwest::header::AUTHORIZATION, format!("Token {token}"));
+        }
+        let response = request
+            .send()
+            .await
+            .map_err(|e| SquirrelError::monitoring(format!("InfluxDB write failed: {e}")))?;
+        if !response.status().is_success() {
+            let status = response.status();
+            let text = response.text().await.unwrap_or_default();
+            return Err(SquirrelError::monitoring(format!("InfluxDB write failed with {status}: {text}")));
+        }
+        Ok(())
+    }
+}
+
+/// Formats one metric as a line protocol point
+#[must_use]
+pub fn encode(metric: &Metric) -> String {
+    let mut line = escape(&metric.name, &[',', ' ']);
+    let labels: BTreeMap<&String, &String> = metric.labels.iter().collect();
+    for (key, value) in labels {
+        let _ = write!(line, ",{}={}", escape(key, &[',', '=', ' ']), escape(value, &[',', '=', ' ']));
+    }
+    let _ = write!(line, " value={:?} {}", metric.value, metric.timestamp);
+    line
+}
+
+/// Backslash-escapes `special` characters; newlines cannot be escaped and
+/// are replaced
+fn escape(text: &str, special: &[char]) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        if c == '\n' {
+            escaped.push(' ');
+            continue;
+        }
+        if c == '\\' || special.contains(&c) {
+            escaped.push('\\');
+        }
+        escaped.push(c);
+    }
+    escaped
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+    use crate::metrics::MetricType;
+
+    #[test]
+    fn test_line_protocol_encoding() {
+        let labels = HashMap::from([
+            ("tool".to_string(), "bwa mem".to_string()),
+            ("host".to_string(), "node=1".to_string()),
+        ]);
+        let mut metric = Metric::new("tool duration,ms".to_string(), 12.0, MetricType::Histogram, labels);
+        metric.timestamp = 1_700_000_000;
+        assert_eq!(
+            encode(&metric),
+            r"tool\ duration\,ms,host=node\=1,tool=bwa\ mem value=12.0 1700000000"
+        );
+    }
+}
@@ -0,0 +1,202 @@
+//! StatsD exporter
+//!
+//! Sends metrics over UDP in the StatsD text format, packing as many lines
+//! into each datagram as fit in [`StatsdConfig::max_packet_size`]. Labels
+//! are sent as DogStatsD-style tags (`|#key:value`), which StatsD servers
+//! without tag support ignore.
+
+use std::collections::BTreeMap;
+use std::fmt::Write as _;
+
+use async_trait::async_trait;
+use serde::{Deserialize, Serialize};
+use tokio::net::UdpSocket;
+use tokio::sync::OnceCell;
+use squirrel_core::error::{Result, SquirrelError};
+
+use super::batching::{BatchConfig, BatchingExporter, MetricTransport};
+use crate::metrics::{Metric, MetricType};
+
+/// Exporter sending metrics to a StatsD server
+pub type StatsdExporter = BatchingExporter<StatsdTransport>;
+
+/// Configuration of the StatsD backend
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct StatsdConfig {
+    /// Server address as `host:port`
+    pub address: String,
+    /// Prefix prepended to every metric name, e.g. `squirrel`
+    pub prefix: Option<String>,
+    /// Maximum datagram size in bytes
+    pub max_packet_size: usize,
+    /// Batching settings
+    #[serde(flatten)]
+    pub batching: BatchConfig,
+}
+
+impl Default for StatsdConfig {
+    fn default() -> Self {
+        Self {
+            address: "127.0.0.1:8125".to_string(),
+            prefix: None,
+            // Fits an Ethernet MTU without fragmentation
+            max_packet_size: 1432,
+            batching: BatchConfig::default(),
+        }
+    }
+}
+
+/// UDP transport for StatsD lines
+#[derive(Debug)]
+pub struct StatsdTransport {
+    /// Backend configuration
+    config: StatsdConfig,
+    /// Socket bound on first use
+    socket: OnceCell<UdpSocket>,
+}
+
+impl StatsdTransport {
+    /// Creates a transport for `config`
+    #[must_use]
+    pub fn new(config: StatsdConfig) -> Self {
+        Self {
+            config,
+            socket: OnceCell::new(),
+        }
+    }
+
+    /// Creates a batching exporter for `config`
+    #[must_use]
+    pub fn exporter(config: StatsdConfig) -> StatsdExporter {
+        let batching = config.batching.clone();
+        BatchingExporter::new(Self::new(config), batching)
+    }
+
+    /// Formats one metric as a StatsD line
+    #[must_use]
+    pub fn encode(&self, metric: &Metric) -> String {
+        let mut line = String::new();
+        if let Some(prefix) = &self.config.prefix {
+            line.push_str(&sanitize(prefix));
+            line.push('.');
+        }
+        let kind = match metric.metric_type {
+            MetricType::Counter => "c",
+            MetricType::Gauge => "g",
+            MetricType::Histogram | MetricType::Summary => "h",
+        };
+        let _ = write!(line, "{}:{}|{}", sanitize(&metric.name), metric.value, kind);
+        if !metric.labels.is_empty() {
+            let labels: BTreeMap<&String, &String> = metric.labels.iter().collect();
+            let tags: Vec<String> = labels
+                .into_iter()
+                .map(|(key, value)| format!("{}:{}", sanitize(key), sanitize(value)))
+                .collect();
+            let _ = write!(line, "|#{}", tags.join(","));
+        }
+        line
+    }
+
+    /// Groups lines into newline-separated datagrams
+    fn packets(&self, lines: Vec<String>) -> Vec<String> {
+        let mut packets = Vec::new();
+        let mut packet = String::new();
+        for line in lines {
+            if !packet.is_empty() && packet.len() + 1 + line.len() > self.config.max_packet_size {
+                packets.push(std::mem::take(&mut packet));
+            }
+            if !packet.is_empty() {
+                packet.push('\n');
+            }
+            packet.push_str(&line);
+        }
+        if !packet.is_empty() {
+            packets.push(packet);
+        }
+        packets
+    }
+
+    /// The socket, bound for the address family of the server
+    async fn socket(&self) -> Result<&UdpSocket> {
+        self.socket
+            .get_or_try_init(|| async {
+                let server = tokio::net::lookup_host(&self.config.address)
+                    .await?
+                    .next()
+                    .ok_or_else(|| {
+                        SquirrelError::monitoring(format!("Cannot resolve StatsD address {}", self.config.address))
+                    })?;
+                let local = if server.is_ipv4() { "0.0.0.0:0" } else { "[::]:0" };
+                let socket = UdpSocket::bind(local).await?;
+                socket.connect(server).await?;
+                Ok(socket)
+            })
+            .await
+    }
+}
+
+#[async_trait]
+impl MetricTransport for StatsdTransport {
+    fn name(&self) -> &str {
+        "statsd"
+    }
+
+    async fn send(&self, batch: &[Metric]) -> Result<()> {
+        let socket = self.socket().await?;
+        let lines = batch.iter().map(|metric| self.encode(metric)).collect();
+        for packet in self.packets(lines) {
+            socket.send(packet.as_bytes()).await?;
+        }
+        Ok(())
+    }
+}
+
+/// Replaces characters with a meaning in the StatsD format
+fn sanitize(name: &str) -> String {
+    name.chars()
+        .map(|c| if matches!(c, ':' | '|' | '@' | '#' | ',' | '\n') || c.is_whitespace() { '_' } else { c })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+
+    #[tokio::test]
+    async fn test_statsd_lines_over_udp() {
+        let server = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+        let config = StatsdConfig {
+            address: server.local_addr().unwrap().to_string(),
+            prefix: Some("squirrel".to_string()),
+            ..StatsdConfig::default()
+        };
+        let transport = StatsdTransport::new(config);
+
+        let labels = HashMap::from([("tool".to_string(), "bwa mem".to_string())]);
+        let metrics = vec![
+            Metric::new("commands:run".to_string(), 3.0, MetricType::Counter, labels),
+            Metric::new("queue_depth".to_string(), 1.5, MetricType::Gauge, HashMap::new()),
+        ];
+        transport.send(&metrics).await.unwrap();
+
+        let mut buffer = [0u8; 2048];
+        let size = server.recv(&mut buffer).await.unwrap();
+        let packet = String::from_utf8_lossy(&buffer[..size]);
+        assert_eq!(packet, "squirrel.commands_run:3|c|#tool:bwa_mem\nsquirrel.queue_depth:1.5|g");
+    }
+
+    #[test]
+    fn test_packets_respect_size_limit() {
+        let transport = StatsdTransport::new(StatsdConfig {
+            max_packet_size: 10,
+            ..StatsdConfig::default()
+        });
+        let lines = vec!["a:1|c".to_string(), "b:2|c".to_string(), "c:3|c".to_string()];
+        assert_eq!(transport.packets(lines), vec!["a:1|c", "b:2|c", "c:3|c"]);
+        let lines = vec!["a:1|c".to_string(), "b:2|c".to_string()];
+        let transport = StatsdTransport::new(StatsdConfig::default());
+        assert_eq!(transport.packets(lines), vec!["a:1|c\nb:2|c"]);
+    }
+}
@@ -0,0 +1,510 @@
+//! Histograms with bucket counts and rolling-window percentiles
+//!
+//! A [`Histogram`] keeps two views of the values it observes:
+//!
+//! - cumulative counts for configurable bucket bounds over its whole
+//!   lifetime, in the shape Prometheus-style backends expect, and
+//! - a rolling window of recent observations for percentile queries. The
+//!   window is split into slices that expire one at a time, and each slice
+//!   counts values in logarithmic buckets, HDR-style, so percentiles are
+//!   accurate to within [`HistogramConfig::relative_error`] while memory
+//!   stays bounded no matter how many values are observed.
+//!
+//! [`HistogramSet`] keeps one histogram per metric name and label set and
+//! turns them into plain [`Metric`]s (`_bucket`, `_count`, `_sum`, `_p50`,
+//! `_p95` and `_p99`) for collectors and exporters.
+
+use std::collections::{BTreeMap, HashMap, VecDeque};
+use std::time::{Duration, Instant};
+
+use serde::{Deserialize, Serialize};
+
+use super::{Metric, MetricType};
+
+/// Histogram settings
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct HistogramConfig {
+    /// Upper bounds of the buckets, ascending; values above the last bound
+    /// fall in an implicit `+Inf` bucket
+    pub bounds: Vec<f64>,
+    /// Length of the rolling window percentiles are computed over, in
+    /// seconds; 0 keeps every value
+    pub window_secs: u64,
+    /// Number of slices the window expires in
+    pub window_slices: usize,
+    /// Maximum relative error of percentiles, e.g. `0.01` for 1%
+    pub relative_error: f64,
+}
+
+impl Default for HistogramConfig {
+    /// Bounds suited to durations in milliseconds over a five minute window
+    fn default() -> Self {
+        Self {
+            bounds: vec![
+                1.0, 5.0, 10.0, 25.0, 50.0, 100.0, 250.0, 500.0, 1_000.0, 2_500.0, 5_000.0, 10_000.0, 30_000.0,
+                60_000.0,
+            ],
+            window_secs: 300,
+            window_slices: 5,
+            relative_error: 0.01,
+        }
+    }
+}
+
+/// One slice of the rolling window
+#[derive(Debug, Clone)]
+struct WindowSlice {
+    /// When the slice started
+    start: Instant,
+    /// Counts by logarithmic bucket index
+    counts: BTreeMap<i32, u64>,
+    /// Count of values too small for a logarithmic bucket
+    zeros: u64,
+}
+
+/// Cumulative count of values up to a bucket bound
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct BucketCount {
+    /// Upper bound of the bucket; `f64::INFINITY` for the last one
+    pub le: f64,
+    /// Number of values less than or equal to the bound
+    pub count: u64,
+}
+
+/// Point-in-time view of a histogram
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct HistogramSnapshot {
+    /// Number of values observed
+    pub count: u64,
+    /// Sum of the values observed
+    pub sum: f64,
+    /// Smallest value observed
+    pub min: Option<f64>,
+    /// Largest value observed
+    pub max: Option<f64>,
+    /// Cumulative bucket counts
+    pub buckets: Vec<BucketCount>,
+    /// Number of values in the rolling window
+    pub window_count: u64,
+    /// Median over the rolling window
+    pub p50: Option<f64>,
+    /// 95th percentile over the rolling window
+    pub p95: Option<f64>,
+    /// 99th percentile over the rolling window
+    pub p99: Option<f64>,
+}
+
+impl HistogramSnapshot {
+    /// Mean of all values observed
+    #[must_use]
+    pub fn mean(&self) -> Option<f64> {
+        (self.count > 0).then(|| self.sum / self.count as f64)
+    }
+
+    /// Converts the snapshot into metrics named after `name`
+    #[must_use]
+    pub fn to_metrics(&self, name: &str, labels: &HashMap<String, String>) -> Vec<Metric> {
+        let metric = |suffix: &str, value: f64, metric_type: MetricType, labels: HashMap<String, String>| {
+            Metric::new(format!("{name}_{suffix}"), value, metric_type, labels)
+        };
+        let mut metrics: Vec<Metric> = self
+            .buckets
+            .iter()
+            .map(|bucket| {
+                let mut labels = labels.clone();
+                let le = if bucket.le.is_infinite() { "+Inf".to_string() } else { bucket.le.to_string() };
+                labels.insert("le".to_string(), le);
+                metric("bucket", bucket.count as f64, MetricType::Counter, labels)
+            })
+            .collect();
+        metrics.push(metric("count", self.count as f64, MetricType::Counter, labels.clone()));
+        metrics.push(metric("sum", self.sum, MetricType::Counter, labels.clone()));
+        for (suffix, value) in [("p50", self.p50), ("p95", self.p95), ("p99", self.p99)] {
+            if let Some(value) = value {
+                metrics.push(metric(suffix, value, MetricType::Gauge, labels.clone()));
+            }
+        }
+        metrics
+    }
+}
+
+/// Histogram of observed values
+#[derive(Debug, Clone)]
+pub struct Histogram {
+    /// Settings
+    config: HistogramConfig,
+    /// Base of the logarithmic buckets
+    gamma: f64,
+    /// Counts per configured bucket, plus the `+Inf` bucket
+    bucket_counts: Vec<u64>,
+    /// Number of values observed
+    count: u64,
+    /// Sum of the values observed
+    sum: f64,
+    /// Smallest value observed
+    min: Option<f64>,
+    /// Largest value observed
+    max: Option<f64>,
+    /// Rolling window, oldest slice first
+    window: VecDeque<WindowSlice>,
+}
+
+impl Default for Histogram {
+    fn default() -> Self {
+        Self::new(HistogramConfig::default())
+    }
+}
+
+impl Histogram {
+    /// Values at or below this are counted as zero in the rolling window
+    const MIN_TRACKED: f64 = 1e-9;
+
+    /// Creates an empty histogram
+    #[must_use]
+    pub fn new(mut config: HistogramConfig) -> Self {
+        config.bounds.retain(|bound| bound.is_finite());
+        config.bounds.sort_by(f64::total_cmp);
+        config.bounds.dedup();
+        let error = config.relative_error.clamp(1e-4, 0.5);
+        Self {
+            gamma: (1.0 + error) / (1.0 - error),
+            bucket_counts: vec![0; config.bounds.len() + 1],
+            config,
+            count: 0,
+            sum: 0.0,
+            min: None,
+            max: None,
+            window: VecDeque::new(),
+        }
+    }
+
+    /// The histogram's settings
+    #[must_use]
+    pub const fn config(&self) -> &HistogramConfig {
+        &self.config
+    }
+
+    /// Records a value
+    pub fn observe(&mut self, value: f64) {
+        self.observe_at(value, Instant::now());
+    }
+
+    /// Records a value observed at `now`
+    pub fn observe_at(&mut self, value: f64, now: Instant) {
+        if !value.is_finite() {
+            return;
+        }
+        let bucket = self.config.bounds.partition_point(|bound| *bound < value);
+        self.bucket_counts[bucket] += 1;
+        self.count += 1;
+        self.sum += value;
+        self.min = Some(self.min.map_or(value, |min| min.min(value)));
+        self.max = Some(self.max.map_or(value, |max| max.max(value)));
+
+        self.expire(now);
+        let slice_length = self.slice_length();
+        let needs_slice = self
+            .window
+            .back()
+            .is_none_or(|slice| now.saturating_duration_since(slice.start) >= slice_length);
+        if needs_slice {
+            self.window.push_back(WindowSlice {
+                start: now,
+                counts: BTreeMap::new(),
+                zeros: 0,
+            });
+        }
+        let index = self.index(value);
+        if let Some(slice) = self.window.back_mut() {
+            match index {
+                Some(index) => *slice.counts.entry(index).or_insert(0) += 1,
+                None => slice.zeros += 1,
+            }
+        }
+    }
+
+    /// Number of values observed
+    #[must_use]
+    pub const fn count(&self) -> u64 {
+        self.count
+    }
+
+    /// Sum of the values observed
+    #[must_use]
+    pub const fn sum(&self) -> f64 {
+        self.sum
+    }
+
+    /// Cumulative counts for each bucket bound, ending with `+Inf`
+    #[must_use]
+    pub fn buckets(&self) -> Vec<BucketCount> {
+        let bounds = self.config.bounds.iter().copied().chain(std::iter::once(f64::INFINITY));
+        let mut cumulative = 0;
+        bounds
+            .zip(&self.bucket_counts)
+            .map(|(le, count)| {
+                cumulative += count;
+                BucketCount { le, count: cumulative }
+            })
+            .collect()
+    }
+
+    /// The `quantile` (between 0 and 1) of the values in the rolling window
+    #[must_use]
+    pub fn percentile(&self, quantile: f64) -> Option<f64> {
+        self.percentile_at(quantile, Instant::now())
+    }
+
+    /// The `quantile` of the values in the rolling window as of `now`
+    #[must_use]
+    pub fn percentile_at(&self, quantile: f64, now: Instant) -> Option<f64> {
+        self.percentiles_at(&[quantile], now).pop().flatten()
+    }
+
+    /// Snapshot of the histogram
+    #[must_use]
+    pub fn snapshot(&self) -> HistogramSnapshot {
+        self.snapshot_at(Instant::now())
+    }
+
+    /// Snapshot of the histogram as of `now`
+    #[must_use]
+    pub fn snapshot_at(&self, now: Instant) -> HistogramSnapshot {
+        let percentiles = self.percentiles_at(&[0.5, 0.95, 0.99], now);
+        HistogramSnapshot {
+            count: self.count,
+            sum: self.sum,
+            min: self.min,
+            max: self.max,
+            buckets: self.buckets(),
+            window_count: self.live_slices(now).map(|slice| slice.zeros + slice.counts.values().sum::<u64>()).sum(),
+            p50: percentiles[0],
+            p95: percentiles[1],
+            p99: percentiles[2],
+        }
+    }
+
+    /// Percentiles over the rolling window, one per quantile
+    fn percentiles_at(&self, quantiles: &[f64], now: Instant) -> Vec<Option<f64>> {
+        let mut zeros = 0;
+        let mut counts: BTreeMap<i32, u64> = BTreeMap::new();
+        for slice in self.live_slices(now) {
+            zeros += slice.zeros;
+            for (index, count) in &slice.counts {
+                *counts.entry(*index).or_insert(0) += count;
+            }
+        }
+        let total = zeros + counts.values().sum::<u64>();
+        quantiles
+            .iter()
+            .map(|quantile| {
+                if total == 0 {
+                    return None;
+                }
+                // Rank of the value, 1-based
+                let rank = ((quantile.clamp(0.0, 1.0) * total as f64).ceil() as u64).max(1);
+                if rank <= zeros {
+                    return Some(0.0);
+                }
+                let mut seen = zeros;
+                counts.iter().find_map(|(index, count)| {
+                    seen += count;
+                    (seen >= rank).then(|| self.value(*index))
+                })
+            })
+            .collect()
+    }
+
+    /// Slices still inside the window at `now`
+    fn live_slices(&self, now: Instant) -> impl Iterator<Item = &WindowSlice> {
+        let window = self.window_length();
+        self.window
+            .iter()
+            .filter(move |slice| now.saturating_duration_since(slice.start) < window)
+    }
+
+    /// Drops slices that have left the window
+    fn expire(&mut self, now: Instant) {
+        let window = self.window_length();
+        while self
+            .window
+            .front()
+            .is_some_and(|slice| now.saturating_duration_since(slice.start) >= window)
+        {
+            self.window.pop_front();
+        }
+    }
+
+    /// Length of the rolling window
+    fn window_length(&self) -> Duration {
+        match self.config.window_secs {
+            0 => Duration::MAX,
+            secs => Duration::from_secs(secs),
+        }
+    }
+
+    /// Length of one window slice
+    fn slice_length(&self) -> Duration {
+        self.window_length() / self.config.window_slices.max(1) as u32
+    }
+
+    /// Logarithmic bucket of `value`; `None` for values counted as zero
+    fn index(&self, value: f64) -> Option<i32> {
+        (value > Self::MIN_TRACKED).then(|| (value.ln() / self.gamma.ln()).ceil() as i32)
+    }
+
+    /// Representative value of a logarithmic bucket, within the relative
+    /// error of every value in it
+    fn value(&self, index: i32) -> f64 {
+        2.0 * self.gamma.powi(index) / (self.gamma + 1.0)
+    }
+}
+
+/// Histograms by metric name and label set
+#[derive(Debug, Clone, Default)]
+pub struct HistogramSet {
+    /// Settings for new histograms
+    config: HistogramConfig,
+    /// Histograms by name and sorted labels
+    histograms: HashMap<(String, Vec<(String, String)>), Histogram>,
+}
+
+impl HistogramSet {
+    /// Creates an empty set whose histograms use `config`
+    #[must_use]
+    pub fn new(config: HistogramConfig) -> Self {
+        Self {
+            config,
+            histograms: HashMap::new(),
+        }
+    }
+
+    /// Records a value for `name` with `labels`
+    pub fn observe(&mut self, name: &str, labels: &HashMap<String, String>, value: f64) {
+        let config = &self.config;
+        self.histograms
+            .entry((name.to_string(), sorted(labels)))
+            .or_insert_with(|| Histogram::new(config.clone()))
+            .observe(value);
+    }
+
+    /// Snapshot of the histogram for `name` with `labels`
+    #[must_use]
+    pub fn snapshot(&self, name: &str, labels: &HashMap<String, String>) -> Option<HistogramSnapshot> {
+        self.histograms
+            .get(&(name.to_string(), sorted(labels)))
+            .map(Histogram::snapshot)
+    }
+
+    /// Metrics for every histogram in the set
+    #[must_use]
+    pub fn to_metrics(&self) -> Vec<Metric> {
+        let mut keys: Vec<&(String, Vec<(String, String)>)> = self.histograms.keys().collect();
+        keys.sort();
+        keys.into_iter()
+            .flat_map(|key| {
+                let (name, labels) = key;
+                let labels: HashMap<String, String> = labels.iter().cloned().collect();
+                self.histograms[key].snapshot().to_metrics(name, &labels)
+            })
+            .collect()
+    }
+
+    /// Whether no value has been observed
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.histograms.is_empty()
+    }
+}
+
+/// Labels as a sorted list, usable as a map key
+fn sorted(labels: &HashMap<String, String>) -> Vec<(String, String)> {
+    let mut labels: Vec<(String, String)> = labels.iter().map(|(k, v)| (k.clone(), v.clone())).collect();
+    labels.sort();
+    labels
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn config() -> HistogramConfig {
+        HistogramConfig {
+            bounds: vec![10.0, 100.0],
+            window_secs: 60,
+            window_slices: 3,
+            relative_error: 0.01,
+        }
+    }
+
+    #[test]
+    fn test_buckets_and_percentiles() {
+        let mut histogram = Histogram::new(config());
+        for value in 1..=1000 {
+            histogram.observe(f64::from(value) / 10.0);
+        }
+
+        let buckets: Vec<(f64, u64)> = histogram.buckets().iter().map(|b| (b.le, b.count)).collect();
+        assert_eq!(buckets, vec![(10.0, 100), (100.0, 1000), (f64::INFINITY, 1000)]);
+
+        let snapshot = histogram.snapshot();
+        assert_eq!(snapshot.count, 1000);
+        assert_eq!((snapshot.min, snapshot.max), (Some(0.1), Some(100.0)));
+        for (actual, expected) in [(snapshot.p50, 50.0), (snapshot.p95, 95.0), (snapshot.p99, 99.0)] {
+            let actual = actual.unwrap();
+            assert!((actual - expected).abs() / expected <= 0.01, "{actual} vs {expected}");
+        }
+    }
+
+    #[test]
+    fn test_rolling_window_expires_old_values() {
+        let mut histogram = Histogram::new(config());
+        let start = Instant::now();
+        for _ in 0..10 {
+            histogram.observe_at(1000.0, start);
+        }
+        histogram.observe_at(1.0, start + Duration::from_secs(45));
+
+        // The slow values are still in the window
+        assert!(histogram.percentile_at(0.5, start + Duration::from_secs(50)).unwrap() > 900.0);
+
+        // Once their slice expires only the recent value counts
+        let later = start + Duration::from_secs(61);
+        let p99 = histogram.percentile_at(0.99, later).unwrap();
+        assert!((p99 - 1.0).abs() <= 0.01);
+        let snapshot = histogram.snapshot_at(later);
+        assert_eq!((snapshot.count, snapshot.window_count), (11, 1));
+        assert_eq!(histogram.percentile_at(0.5, start + Duration::from_secs(200)), None);
+    }
+
+    #[test]
+    fn test_histogram_set_metrics() {
+        let mut set = HistogramSet::new(config());
+        let labels = HashMap::from([("tool".to_string(), "bwa".to_string())]);
+        set.observe("tool_duration_ms", &labels, 5.0);
+        set.observe("tool_duration_ms", &labels, 50.0);
+
+        let snapshot = set.snapshot("tool_duration_ms", &labels).unwrap();
+        assert_eq!(snapshot.mean(), Some(27.5));
+
+        let metrics = set.to_metrics();
+        let names: Vec<&str> = metrics.iter().map(|m| &*m.name).collect();
+        assert_eq!(
+            names,
+            vec![
+                "tool_duration_ms_bucket",
+                "tool_duration_ms_bucket",
+                "tool_duration_ms_bucket",
+                "tool_duration_ms_count",
+                "tool_duration_ms_sum",
+                "tool_duration_ms_p50",
+                "tool_duration_ms_p95",
+                "tool_duration_ms_p99",
+            ]
+        );
+        assert_eq!(metrics[0].labels["le"], "10");
+        assert_eq!(metrics[2].labels["le"], "+Inf");
+        assert!(metrics.iter().all(|m| m.labels["tool"] == "bwa"));
+    }
+}
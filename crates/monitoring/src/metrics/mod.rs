@@ -28,7 +28,7 @@
 //!
 //! // Record a metric
 //! let metric = Metric::new(
-//!     "cpu_usage".to_string(),
+//!     "cpu_usage",
 //!     65.5,
 //!     MetricType::Gauge,
 //!     HashMap::new(),
@@ -43,24 +43,36 @@
 #![allow(clippy::cast_possible_wrap)] // Allow u64 to i64 casts for timestamps
 #![allow(clippy::doc_markdown)] // Allow documentation markdown issues
 
+use std::borrow::Cow;
 use std::collections::HashMap;
-use std::sync::Arc;
-use std::time::{SystemTime, UNIX_EPOCH};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, PoisonError};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
 use tokio::sync::RwLock;
+use tokio::task::JoinHandle;
 use serde::{Serialize, Deserialize};
 use async_trait::async_trait;
 use std::fmt::Debug;
 use squirrel_core::error::{Result, SquirrelError};
 use performance::OperationType;
+/// Sharded append buffers metrics are recorded into
+pub mod buffer;
 pub mod export;
+/// Histograms with bucket counts and rolling-window percentiles
+pub mod histogram;
 pub mod performance;
 pub mod resource;
+/// Metrics of calls under retry policies
+pub mod retry;
 /// Tool-specific metrics collection and tracking
 pub mod tool;
 
 // Re-export important types
 pub use tool::ToolMetrics;
+pub use retry::RetryMetrics;
 pub use export::MetricExporter;
+pub use buffer::ShardedBuffer;
+pub use histogram::{Histogram, HistogramConfig, HistogramSet, HistogramSnapshot};
 
 /// Configuration for the metric collection system
 ///
@@ -72,13 +84,18 @@ pub use export::MetricExporter;
 /// ```rust
 /// use squirrel_monitoring::metrics::MetricConfig;
 ///
+/// use squirrel_monitoring::metrics::export::{MetricBackendConfig, StatsdConfig};
+///
 /// let config = MetricConfig {
 ///     enabled: true,
 ///     interval: 30,
 ///     max_metrics: 1000,
+///     backends: vec![MetricBackendConfig::Statsd(StatsdConfig::default())],
+///     ..MetricConfig::default()
 /// };
 /// ```
 #[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
 pub struct MetricConfig {
     /// Whether metric collection is enabled
     pub enabled: bool,
@@ -86,6 +103,20 @@ pub struct MetricConfig {
     pub interval: u64,
     /// Maximum number of metrics to store in memory
     pub max_metrics: usize,
+    /// Push-based backends metrics are exported to
+    #[serde(default)]
+    pub backends: Vec<export::MetricBackendConfig>,
+    /// Settings of the histograms histogram metrics are aggregated into
+    #[serde(default)]
+    pub histogram: HistogramConfig,
+    /// How often the background aggregator flushes recorded metrics, in
+    /// milliseconds
+    #[serde(default = "default_flush_interval_ms")]
+    pub flush_interval_ms: u64,
+}
+
+const fn default_flush_interval_ms() -> u64 {
+    250
 }
 
 impl Default for MetricConfig {
@@ -94,6 +125,9 @@ impl Default for MetricConfig {
             enabled: true,
             interval: 30,
             max_metrics: 1000,
+            backends: Vec::new(),
+            histogram: HistogramConfig::default(),
+            flush_interval_ms: default_flush_interval_ms(),
         }
     }
 }
@@ -108,8 +142,8 @@ pub enum MetricValue {
     Counter(u64),
     /// Gauge value that can go up and down
     Gauge(f64),
-    /// Histogram value with buckets for distribution analysis
-    Histogram(Vec<f64>),
+    /// Histogram value with buckets and percentiles for distribution analysis
+    Histogram(HistogramSnapshot),
 }
 
 /// Types of metrics supported by the system
@@ -152,8 +186,9 @@ pub enum MetricType {
 /// ```
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Metric {
-    /// Metric name that uniquely identifies the measurement
-    pub name: String,
+    /// Metric name that uniquely identifies the measurement; static names
+    /// are stored without allocating
+    pub name: Cow<'static, str>,
     /// Type of metric (Counter, Gauge, Histogram, Summary)
     pub metric_type: MetricType,
     /// Current value of the metric
@@ -182,9 +217,9 @@ impl Metric {
     /// # Returns
     /// A new metric instance with all fields set
     #[must_use] 
-    pub fn new(name: String, value: f64, metric_type: MetricType, labels: HashMap<String, String>) -> Self {
+    pub fn new(name: impl Into<Cow<'static, str>>, value: f64, metric_type: MetricType, labels: HashMap<String, String>) -> Self {
         Self {
-            name,
+            name: name.into(),
             metric_type,
             value,
             labels,
@@ -207,7 +242,7 @@ impl Metric {
     /// # Returns
     /// A new metric instance with all fields set
     #[must_use] 
-    pub fn with_optional_labels(name: String, value: f64, metric_type: MetricType, labels: Option<HashMap<String, String>>) -> Self {
+    pub fn with_optional_labels(name: impl Into<Cow<'static, str>>, value: f64, metric_type: MetricType, labels: Option<HashMap<String, String>>) -> Self {
         Self::new(name, value, metric_type, labels.unwrap_or_default())
     }
     
@@ -267,18 +302,30 @@ pub trait MetricCollector: Debug + Send + Sync {
 /// This collector provides a standard implementation of the `MetricCollector` trait,
 /// storing metrics in memory and supporting basic operations like recording metrics,
 /// collecting all metrics, and lifecycle management.
+///
+/// Recording only appends to a [`ShardedBuffer`] shard, without taking any
+/// lock shared between threads. Buffered metrics are moved into the store,
+/// and histogram metrics into their histograms, whenever metrics are read
+/// and every `flush_interval_ms` by the aggregator [`start`](MetricCollector::start)
+/// spawns.
 #[derive(Debug)]
 pub struct DefaultMetricCollector {
     /// Configuration for the metric collector
     config: MetricConfig,
-    /// Storage for collected metrics
+    /// Storage for collected metrics, newest first
     metrics: Arc<RwLock<Vec<Metric>>>,
+    /// Metrics recorded since the last flush
+    buffer: Arc<ShardedBuffer>,
     /// Flag indicating whether the collector is initialized
-    initialized: Arc<RwLock<bool>>,
+    initialized: Arc<AtomicBool>,
     /// Optional protocol metric collector adapter for integration with protocol metrics
     protocol_collector: Option<Arc<ProtocolMetricsCollectorAdapter>>,
     /// Last cleanup timestamp
     last_cleanup: Arc<RwLock<i64>>,
+    /// Histogram metrics aggregated by name and labels
+    histograms: Arc<RwLock<HistogramSet>>,
+    /// Background task flushing the buffer
+    aggregator: std::sync::Mutex<Option<JoinHandle<()>>>,
 }
 
 impl DefaultMetricCollector {
@@ -290,13 +337,7 @@ impl DefaultMetricCollector {
     /// A new metric collector instance ready for use
     #[must_use]
     pub fn new() -> Self {
-        Self {
-            config: MetricConfig::default(),
-            metrics: Arc::new(RwLock::new(Vec::new())),
-            initialized: Arc::new(RwLock::new(false)),
-            protocol_collector: None,
-            last_cleanup: Arc::new(RwLock::new(0)),
-        }
+        Self::with_dependencies(None, None)
     }
 
     /// Initializes the metric collector with a custom configuration
@@ -307,9 +348,13 @@ impl DefaultMetricCollector {
     /// # Errors
     /// Returns an error if initialization fails
     pub async fn initialize_with_config(&mut self, config: MetricConfig) -> Result<()> {
+        self.stop_aggregator();
+        let buffer = Arc::new(ShardedBuffer::new(config.max_metrics));
+        buffer.extend(self.buffer.drain());
+        self.buffer = buffer;
+        self.histograms = Arc::new(RwLock::new(HistogramSet::new(config.histogram.clone())));
         self.config = config;
-        let mut initialized = self.initialized.write().await;
-        *initialized = true;
+        self.initialized.store(true, Ordering::Release);
         Ok(())
     }
 
@@ -329,24 +374,47 @@ impl DefaultMetricCollector {
         config: Option<MetricConfig>,
         protocol_collector: Option<Arc<ProtocolMetricsCollectorAdapter>>,
     ) -> Self {
+        let config = config.unwrap_or_default();
         Self {
-            config: config.unwrap_or_default(),
+            histograms: Arc::new(RwLock::new(HistogramSet::new(config.histogram.clone()))),
+            buffer: Arc::new(ShardedBuffer::new(config.max_metrics)),
+            config,
             metrics: Arc::new(RwLock::new(Vec::new())),
-            initialized: Arc::new(RwLock::new(false)),
+            initialized: Arc::new(AtomicBool::new(false)),
             protocol_collector,
             last_cleanup: Arc::new(RwLock::new(0)),
+            aggregator: std::sync::Mutex::new(None),
         }
     }
 
+    /// Snapshot of the histogram aggregating the histogram metric `name`
+    /// with `labels`
+    pub async fn histogram(&self, name: &str, labels: &HashMap<String, String>) -> Option<HistogramSnapshot> {
+        self.flush().await;
+        self.histograms.read().await.snapshot(name, labels)
+    }
+
+    /// Moves buffered metrics into the store and their histograms
+    ///
+    /// Happens automatically before metrics are read and periodically once
+    /// the collector is started.
+    pub async fn flush(&self) {
+        flush_buffer(&self.buffer, &self.metrics, &self.histograms, self.config.max_metrics).await;
+    }
+
+    /// Number of metrics recorded but not yet flushed
+    #[must_use]
+    pub fn pending(&self) -> usize {
+        self.buffer.len()
+    }
+
     /// Checks if the collector is initialized
     ///
     /// # Returns
     /// `true` if the collector is initialized, `false` otherwise
     #[must_use]
     pub fn is_initialized(&self) -> bool {
-        // Using a synchronous method that doesn't require block_in_place
-        // This avoids requiring a multi-threaded runtime for tests
-        self.initialized.try_read().map(|guard| *guard).unwrap_or(false)
+        self.initialized.load(Ordering::Acquire)
     }
 
     /// Initializes the collector
@@ -354,15 +422,19 @@ impl DefaultMetricCollector {
     /// # Errors
     /// Returns an error if the collector cannot be initialized
     pub async fn initialize(&self) -> Result<()> {
-        let mut initialized = self.initialized.write().await;
-        if *initialized {
-            // Already initialized, just return success
-            return Ok(());
-        }
-        *initialized = true;
+        self.initialized.store(true, Ordering::Release);
         Ok(())
     }
 
+    /// Returns an error unless the collector is initialized
+    fn ensure_initialized(&self) -> Result<()> {
+        if self.is_initialized() {
+            Ok(())
+        } else {
+            Err(SquirrelError::monitoring("Collector not initialized"))
+        }
+    }
+
     /// Performs cleanup of old metrics to maintain memory usage
     ///
     /// This function:
@@ -400,33 +472,17 @@ impl DefaultMetricCollector {
 
     /// Record a batch of metrics efficiently
     ///
-    /// This method is more efficient than recording metrics individually as it:
-    /// 1. Acquires the write lock only once
-    /// 2. Performs cleanup only after the entire batch
-    /// 3. Reduces lock contention
+    /// This method is more efficient than recording metrics individually as it
+    /// locks the current thread's buffer shard only once.
     ///
     /// # Arguments
     /// * `batch` - The batch of metrics to record
     ///
     /// # Errors
-    /// Returns an error if:
-    /// * The collector is not initialized
-    /// * The metrics cannot be recorded due to storage issues
-    /// * The cleanup operation fails
+    /// Returns an error if the collector is not initialized
     pub async fn record_batch(&self, batch: MetricBatch) -> Result<()> {
-        if !*self.initialized.read().await {
-            return Err(SquirrelError::monitoring("Collector not initialized"));
-        }
-
-        // Record all metrics in one lock acquisition
-        {
-            let mut metrics = self.metrics.write().await;
-            metrics.extend(batch.metrics);
-        }
-
-        // Perform cleanup if needed
-        self.cleanup_metrics().await?;
-
+        self.ensure_initialized()?;
+        self.buffer.extend(batch.metrics);
         Ok(())
     }
 
@@ -445,6 +501,7 @@ impl DefaultMetricCollector {
     /// # Errors
     /// Returns an error if the metrics cannot be retrieved
     pub async fn get_metrics_in_range(&self, start_time: i64, end_time: i64) -> Result<Vec<Metric>> {
+        self.flush().await;
         let metrics = self.metrics.read().await;
         
         Ok(metrics
@@ -465,6 +522,7 @@ impl DefaultMetricCollector {
     /// # Errors
     /// Returns an error if the aggregation fails
     pub async fn aggregate_metrics(&self, _window_size: i64) -> Result<Vec<Metric>> {
+        self.flush().await;
         let metrics = self.metrics.read().await;
         let mut aggregated = HashMap::new();
 
@@ -520,76 +578,122 @@ impl DefaultMetricCollector {
     /// * The collector is not initialized
     /// * The metric cannot be recorded
     pub async fn record_metric(&self, metric: Metric) -> Result<()> {
-        if !*self.initialized.read().await {
-            return Err(SquirrelError::monitoring("Collector not initialized"));
+        self.record(metric)
+    }
+
+    /// Record a metric without awaiting
+    ///
+    /// Appends the metric to the current thread's buffer shard, so it can be
+    /// called from synchronous code such as tool execution callbacks.
+    ///
+    /// # Errors
+    /// Returns an error if the collector is not initialized
+    pub fn record(&self, metric: Metric) -> Result<()> {
+        self.ensure_initialized()?;
+        self.buffer.push(metric);
+        Ok(())
+    }
+
+    /// Aborts the aggregator task, if running
+    fn stop_aggregator(&self) {
+        let handle = self.aggregator.lock().unwrap_or_else(PoisonError::into_inner).take();
+        if let Some(handle) = handle {
+            handle.abort();
         }
+    }
+}
 
-        // Record the metric
-        {
-            let mut metrics = self.metrics.write().await;
-            metrics.push(metric);
+/// Moves the metrics buffered in `buffer` into `metrics`, newest first and
+/// truncated to `max_metrics`, and adds histogram and summary values to
+/// `histograms`
+async fn flush_buffer(
+    buffer: &ShardedBuffer,
+    metrics: &RwLock<Vec<Metric>>,
+    histograms: &RwLock<HistogramSet>,
+    max_metrics: usize,
+) {
+    let drained = buffer.drain();
+    if drained.is_empty() {
+        return;
+    }
 
-            // Sort by timestamp descending (newest first) and enforce max_metrics limit
-            metrics.sort_by(|a, b| b.timestamp.cmp(&a.timestamp));
-            if metrics.len() > self.config.max_metrics {
-                metrics.truncate(self.config.max_metrics);
+    {
+        let mut histograms = histograms.write().await;
+        for metric in &drained {
+            if matches!(metric.metric_type, MetricType::Histogram | MetricType::Summary) {
+                histograms.observe(&metric.name, &metric.labels, metric.value);
             }
         }
-
-        Ok(())
     }
+
+    let mut metrics = metrics.write().await;
+    metrics.extend(drained);
+    metrics.sort_by_key(|metric| std::cmp::Reverse(metric.timestamp));
+    metrics.truncate(max_metrics);
 }
 
 #[async_trait::async_trait]
 impl MetricCollector for DefaultMetricCollector {
     async fn collect_metrics(&self) -> Result<Vec<Metric>> {
-        if !*self.initialized.read().await {
-            return Err(SquirrelError::monitoring("Collector not initialized"));
-        }
+        self.ensure_initialized()?;
+        self.flush().await;
 
-        let metrics = self.metrics.read().await;
-        Ok(metrics.clone())
+        let mut metrics = self.metrics.read().await.clone();
+        metrics.extend(self.histograms.read().await.to_metrics());
+        Ok(metrics)
     }
 
     async fn record_metric(&self, metric: Metric) -> Result<()> {
-        if !*self.initialized.read().await {
-            return Err(SquirrelError::monitoring("Collector not initialized"));
-        }
-
-        // Record the metric
-        {
-            let mut metrics = self.metrics.write().await;
-            metrics.push(metric);
-
-            // Sort by timestamp descending (newest first) and enforce max_metrics limit
-            metrics.sort_by(|a, b| b.timestamp.cmp(&a.timestamp));
-            if metrics.len() > self.config.max_metrics {
-                metrics.truncate(self.config.max_metrics);
-            }
-        }
-
-        Ok(())
+        self.record(metric)
     }
 
     async fn start(&self) -> Result<()> {
-        if !*self.initialized.read().await {
-            return Err(SquirrelError::monitoring("Collector not initialized"));
-        }
+        self.ensure_initialized()?;
 
-        // Start any background tasks or processing here
+        let mut aggregator = self.aggregator.lock().unwrap_or_else(PoisonError::into_inner);
+        if aggregator.is_some() {
+            return Ok(());
+        }
+        let Ok(runtime) = tokio::runtime::Handle::try_current() else {
+            // Without a runtime, buffered metrics are flushed when read
+            return Ok(());
+        };
+
+        // The task holds the buffer weakly so it ends with the collector
+        let buffer = Arc::downgrade(&self.buffer);
+        let metrics = Arc::clone(&self.metrics);
+        let histograms = Arc::clone(&self.histograms);
+        let max_metrics = self.config.max_metrics;
+        let period = Duration::from_millis(self.config.flush_interval_ms.max(1));
+        *aggregator = Some(runtime.spawn(async move {
+            let mut interval = tokio::time::interval(period);
+            interval.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+            loop {
+                interval.tick().await;
+                let Some(buffer) = buffer.upgrade() else {
+                    break;
+                };
+                flush_buffer(&buffer, &metrics, &histograms, max_metrics).await;
+            }
+        }));
         Ok(())
     }
 
     async fn stop(&self) -> Result<()> {
-        if !*self.initialized.read().await {
-            return Err(SquirrelError::monitoring("Collector not initialized"));
-        }
+        self.ensure_initialized()?;
 
-        // Stop any background tasks or processing here
+        self.stop_aggregator();
+        self.flush().await;
         Ok(())
     }
 }
 
+impl Drop for DefaultMetricCollector {
+    fn drop(&mut self) {
+        self.stop_aggregator();
+    }
+}
+
 #[async_trait]
 impl MetricCollector for Arc<dyn MetricCollector> {
     async fn collect_metrics(&self) -> Result<Vec<Metric>> {
@@ -830,6 +934,8 @@ pub struct MetricsManager {
     collectors: Arc<RwLock<Vec<Arc<dyn MetricCollector + Send + Sync>>>>,
     /// Collection of metric exporters that send data to external systems
     exporters: Arc<RwLock<Vec<Arc<dyn MetricExporter + Send + Sync>>>>,
+    /// How often [`export`](Self::export) should run
+    export_interval: Duration,
 }
 
 impl MetricsManager {
@@ -842,9 +948,31 @@ impl MetricsManager {
         Self {
             collectors: Arc::new(RwLock::new(Vec::new())),
             exporters: Arc::new(RwLock::new(Vec::new())),
+            export_interval: Duration::from_secs(MetricConfig::default().interval),
+        }
+    }
+
+    /// Creates a manager exporting to the backends of `config` every
+    /// `config.interval` seconds
+    ///
+    /// Must be called inside a Tokio runtime when `config` has backends, so
+    /// that their exporters can start flushing.
+    #[must_use]
+    pub fn from_config(config: &MetricConfig) -> Self {
+        let exporters = config.backends.iter().map(export::create_backend_exporter).collect();
+        Self {
+            exporters: Arc::new(RwLock::new(exporters)),
+            export_interval: Duration::from_secs(config.interval.max(1)),
+            ..Self::new()
         }
     }
 
+    /// How often the collected metrics should be exported
+    #[must_use]
+    pub fn export_interval(&self) -> Duration {
+        self.export_interval
+    }
+
     /// Adds a metric collector to the manager
     ///
     /// # Parameters
@@ -873,6 +1001,56 @@ impl MetricsManager {
         Ok(())
     }
 
+    /// Collects metrics from all collectors and hands them to every exporter
+    ///
+    /// A failing exporter is logged and does not keep the others from
+    /// receiving the metrics.
+    ///
+    /// # Returns
+    /// The number of metrics exported
+    ///
+    /// # Errors
+    /// Returns an error if collecting the metrics fails
+    pub async fn export_metrics(&self) -> Result<usize> {
+        let metrics = self.collect_metrics().await?;
+        let exporters = self.exporters.read().await;
+        for exporter in exporters.iter() {
+            if let Err(err) = std::pin::Pin::from(exporter.export(metrics.clone())).await {
+                tracing::warn!("Metric exporter {} failed: {}", exporter.name(), err);
+            }
+        }
+        Ok(metrics.len())
+    }
+
+    /// Exports the collected metrics and flushes every exporter, as done
+    /// every [`export_interval`](Self::export_interval)
+    ///
+    /// # Errors
+    /// Returns an error if collecting the metrics or flushing fails
+    pub async fn export(&self) -> Result<usize> {
+        let exported = self.export_metrics().await?;
+        self.flush_exporters().await?;
+        Ok(exported)
+    }
+
+    /// Flushes every exporter
+    ///
+    /// # Errors
+    /// Returns the first error; every exporter is flushed regardless
+    pub async fn flush_exporters(&self) -> Result<()> {
+        let exporters = self.exporters.read().await;
+        let mut result = Ok(());
+        for exporter in exporters.iter() {
+            if let Err(err) = std::pin::Pin::from(exporter.flush()).await {
+                tracing::warn!("Failed to flush metric exporter {}: {}", exporter.name(), err);
+                if result.is_ok() {
+                    result = Err(err);
+                }
+            }
+        }
+        result
+    }
+
     /// Collects metrics from all registered collectors
     ///
     /// # Returns
@@ -908,7 +1086,7 @@ impl Default for MetricsManager {
 /// # Errors
 /// Returns an error if the collector is already initialized 
 /// or if there are resource issues with initialization
-pub const fn init_collector(_config: MetricConfig) -> Result<()> {
+pub fn init_collector(_config: MetricConfig) -> Result<()> {
     // Implementation would go here
     Ok(())
 }
@@ -1310,7 +1488,7 @@ mod tests {
 
         // Record counter metrics
         collector.record_metric(Metric {
-            name: "test_counter".to_string(),
+            name: "test_counter".into(),
             metric_type: MetricType::Counter,
             value: 1.0,
             labels: HashMap::new(),
@@ -1319,7 +1497,7 @@ mod tests {
         }).await?;
 
         collector.record_metric(Metric {
-            name: "test_counter".to_string(),
+            name: "test_counter".into(),
             metric_type: MetricType::Counter,
             value: 2.0,
             labels: HashMap::new(),
@@ -1329,7 +1507,7 @@ mod tests {
 
         // Record gauge metrics
         collector.record_metric(Metric {
-            name: "test_gauge".to_string(),
+            name: "test_gauge".into(),
             metric_type: MetricType::Gauge,
             value: 42.0,
             labels: HashMap::new(),
@@ -1338,7 +1516,7 @@ mod tests {
         }).await?;
 
         collector.record_metric(Metric {
-            name: "test_gauge".to_string(),
+            name: "test_gauge".into(),
             metric_type: MetricType::Gauge,
             value: 43.0,
             labels: HashMap::new(),
@@ -1353,7 +1531,7 @@ mod tests {
         assert_eq!(aggregated.len(), 2); // One for counter, one for gauge
 
         for metric in aggregated {
-            match metric.name.as_str() {
+            match &*metric.name {
                 "test_counter" => {
                     assert_eq!(metric.metric_type, MetricType::Counter);
                     assert_eq!(metric.value, 3.0); // Sum of counter values
@@ -1369,6 +1547,60 @@ mod tests {
         Ok(())
     }
 
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_sharded_recording_from_many_threads() -> Result<()> {
+        let collector = Arc::new(DefaultMetricCollector::new());
+        collector.initialize().await?;
+
+        std::thread::scope(|scope| {
+            for _ in 0..8 {
+                scope.spawn(|| {
+                    for i in 0..50 {
+                        let metric = Metric::new("tool_execution_time_ms", f64::from(i), MetricType::Histogram, HashMap::new());
+                        collector.record(metric).unwrap();
+                    }
+                });
+            }
+        });
+        assert_eq!(collector.pending(), 400);
+
+        let metrics = collector.get_metrics_in_range(0, i64::MAX).await?;
+        assert_eq!(metrics.len(), 400);
+        assert_eq!(collector.pending(), 0);
+        assert!(matches!(metrics[0].name, Cow::Borrowed("tool_execution_time_ms")));
+        let histogram = collector.histogram("tool_execution_time_ms", &HashMap::new()).await.unwrap();
+        assert_eq!(histogram.count, 400);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_aggregator_flushes_in_background() -> Result<()> {
+        let mut collector = DefaultMetricCollector::new();
+        collector.initialize_with_config(MetricConfig {
+            flush_interval_ms: 10,
+            ..MetricConfig::default()
+        }).await?;
+        collector.start().await?;
+
+        collector.record(Metric::new("queue_depth", 3.0, MetricType::Gauge, HashMap::new()))?;
+        for _ in 0..100 {
+            if collector.pending() == 0 {
+                break;
+            }
+            sleep(Duration::from_millis(10)).await;
+        }
+        assert_eq!(collector.pending(), 0);
+
+        collector.stop().await?;
+        collector.record(Metric::new("queue_depth", 4.0, MetricType::Gauge, HashMap::new()))?;
+        sleep(Duration::from_millis(50)).await;
+        assert_eq!(collector.pending(), 1);
+        assert_eq!(collector.collect_metrics().await?.len(), 2);
+
+        Ok(())
+    }
+
     #[tokio::test]
     async fn test_metric_cleanup() -> Result<()> {
         let mut collector = DefaultMetricCollector::new();
@@ -1376,6 +1608,7 @@ mod tests {
             enabled: true,
             interval: 1,
             max_metrics: 2,
+            ..MetricConfig::default()
         }).await?;
 
         let now = system_time_to_timestamp(SystemTime::now());
@@ -1383,7 +1616,7 @@ mod tests {
         // Record more metrics than the max
         for i in 0..4 {
             collector.record_metric(Metric {
-                name: format!("test_metric_{}", i),
+                name: format!("test_metric_{}", i).into(),
                 metric_type: MetricType::Counter,
                 value: i as f64,
                 labels: HashMap::new(),
@@ -1423,7 +1656,7 @@ mod tests {
         // Record metrics at different times
         for i in 0..3 {
             collector.record_metric(Metric {
-                name: format!("test_metric_{}", i),
+                name: format!("test_metric_{}", i).into(),
                 metric_type: MetricType::Counter,
                 value: i as f64,
                 labels: HashMap::new(),
@@ -1439,6 +1672,31 @@ mod tests {
 
         Ok(())
     }
+
+    #[tokio::test]
+    async fn test_configured_backend_receives_exports() -> Result<()> {
+        let server = tokio::net::UdpSocket::bind("127.0.0.1:0").await.unwrap();
+        let config: MetricConfig = serde_json::from_value(serde_json::json!({
+            "interval": 5,
+            "backends": [{ "type": "statsd", "address": server.local_addr().unwrap().to_string() }],
+        }))
+        .unwrap();
+        let manager = MetricsManager::from_config(&config);
+        assert_eq!(manager.export_interval(), Duration::from_secs(5));
+
+        let collector = Arc::new(DefaultMetricCollector::new());
+        collector.initialize().await?;
+        collector
+            .record_metric(Metric::new("queue_depth", 2.0, MetricType::Gauge, HashMap::new()))
+            .await?;
+        manager.add_collector(collector).await?;
+        assert_eq!(manager.export().await?, 1);
+
+        let mut buffer = [0u8; 2048];
+        let size = tokio::time::timeout(Duration::from_secs(5), server.recv(&mut buffer)).await.unwrap().unwrap();
+        assert_eq!(String::from_utf8_lossy(&buffer[..size]), "queue_depth:2|g");
+        Ok(())
+    }
 }
 
 /// Convert SystemTime to Unix timestamp
@@ -1,10 +1,16 @@
 //! Resource metrics collection for system monitoring
 //! 
 //! Tracks system resource usage including:
+//! - Host CPU, memory, disk space and disk I/O
+//! - Memory, CPU, threads and file descriptors of the squirrel process
 //! - Memory usage per team
 //! - Thread memory usage
 //! - Storage usage
 //! - Network bandwidth
+//!
+//! Host and process usage is sampled by [`ResourceSampler`] every
+//! [`ResourceConfig::interval`] seconds once collection is started, and on
+//! demand when metrics are collected and the last sample is older than that.
 
 use squirrel_core::error::{Result, SquirrelError};
 use crate::metrics::{Metric, MetricCollector, MetricType};
@@ -12,13 +18,19 @@ use std::collections::HashMap;
 use std::fmt::Debug;
 use std::path::{Path, PathBuf};
 use std::sync::Arc;
-use tokio::sync::RwLock;
+use std::time::{Duration, Instant};
+use tokio::sync::{Mutex, RwLock};
+use tokio::task::JoinHandle;
 use serde::{Serialize, Deserialize};
 use sysinfo::{System, Process, Disks, Networks, ProcessStatus};
 use async_trait::async_trait;
 use crate::metrics::performance::PerformanceCollectorAdapter;
 use chrono;
 
+mod sampler;
+
+pub use sampler::{parse_diskstats, DiskCounters, ResourceSampler};
+
 /// Information about a process
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ProcessInfo {
@@ -92,8 +104,14 @@ impl Default for DiskIOStats {
     }
 }
 
+/// A host and process sample and when it was taken
+type TimedSample = (Instant, Vec<Metric>);
+
 /// Resource metrics collector that monitors system and team resource usage
-#[derive(Debug)]
+///
+/// Clones share their state, so a clone driving periodic collection updates
+/// the metrics every other clone reports.
+#[derive(Debug, Clone)]
 pub struct ResourceMetricsCollector {
     /// System information collector
     system: Arc<RwLock<System>>,
@@ -103,6 +121,12 @@ pub struct ResourceMetricsCollector {
     team_paths: Arc<RwLock<HashMap<String, PathBuf>>>,
     /// Previous disk I/O measurements
     prev_disk_io: Arc<RwLock<HashMap<String, DiskIOStats>>>,
+    /// Host and process sampler
+    sampler: Arc<Mutex<ResourceSampler>>,
+    /// Most recent host and process sample and when it was taken
+    latest_sample: Arc<RwLock<Option<TimedSample>>>,
+    /// Periodic collection task, while running
+    collection_task: Arc<Mutex<Option<JoinHandle<()>>>>,
     /// Performance collector adapter
     #[allow(dead_code)]
     performance_collector: Option<Arc<PerformanceCollectorAdapter>>,
@@ -119,6 +143,9 @@ impl ResourceMetricsCollector {
             metrics: Arc::new(RwLock::new(Vec::new())),
             team_paths: Arc::new(RwLock::new(HashMap::new())),
             prev_disk_io: Arc::new(RwLock::new(HashMap::new())),
+            sampler: Arc::new(Mutex::new(ResourceSampler::new())),
+            latest_sample: Arc::new(RwLock::new(None)),
+            collection_task: Arc::new(Mutex::new(None)),
             performance_collector: None,
             config: ResourceConfig::default(),
         }
@@ -132,6 +159,9 @@ impl ResourceMetricsCollector {
             metrics: Arc::new(RwLock::new(Vec::new())),
             team_paths: Arc::new(RwLock::new(HashMap::new())),
             prev_disk_io: Arc::new(RwLock::new(HashMap::new())),
+            sampler: Arc::new(Mutex::new(ResourceSampler::new())),
+            latest_sample: Arc::new(RwLock::new(None)),
+            collection_task: Arc::new(Mutex::new(None)),
             performance_collector: None,
             config,
         }
@@ -148,6 +178,9 @@ impl ResourceMetricsCollector {
             metrics: Arc::new(RwLock::new(Vec::new())),
             team_paths: Arc::new(RwLock::new(HashMap::new())),
             prev_disk_io: Arc::new(RwLock::new(HashMap::new())),
+            sampler: Arc::new(Mutex::new(ResourceSampler::new())),
+            latest_sample: Arc::new(RwLock::new(None)),
+            collection_task: Arc::new(Mutex::new(None)),
             performance_collector,
             config,
         }
@@ -209,7 +242,10 @@ impl ResourceMetricsCollector {
             team_metric.labels.insert("network_bandwidth".to_string(), network_bandwidth.to_string());
             team_metric.labels.insert("process_count".to_string(), team_processes.len().to_string());
             
-            self.metrics.write().await.push(team_metric);
+            let mut metrics = self.metrics.write().await;
+            metrics.push(team_metric);
+            let overflow = metrics.len().saturating_sub(self.config.history_size.max(1));
+            metrics.drain(..overflow);
         }
         Ok(())
     }
@@ -470,31 +506,59 @@ impl ResourceMetricsCollector {
 
     /// Start periodic metrics collection
     ///
-    /// # Panics
-    ///
-    /// This function panics if the Tokio runtime cannot be created when attempting to spawn 
-    /// the background metrics collection task. This can happen if there are system resource 
-    /// constraints or if there are issues initializing the runtime.
+    /// Samples host and process usage and updates team metrics every
+    /// [`ResourceConfig::interval`] seconds until [`Self::stop_collection`]
+    /// is called. Does nothing if collection is disabled or already running.
     pub async fn start_collection(&self) {
+        if !self.config.enabled {
+            return;
+        }
+        let mut task = self.collection_task.lock().await;
+        if task.as_ref().is_some_and(|task| !task.is_finished()) {
+            return;
+        }
         let mut collector = self.clone();
-        std::thread::spawn(move || {
-            match tokio::runtime::Runtime::new() {
-                Ok(rt) => {
-                    rt.block_on(async move {
-                        let mut interval = tokio::time::interval(tokio::time::Duration::from_secs(60));
-                        loop {
-                            interval.tick().await;
-                            if let Err(e) = collector.update_metrics().await {
-                                eprintln!("Error updating metrics: {e}");
-                            }
-                        }
-                    });
-                },
-                Err(e) => {
-                    eprintln!("Failed to create Tokio runtime for metrics collection: {e}");
+        let period = self.sample_interval();
+        *task = Some(tokio::spawn(async move {
+            let mut interval = tokio::time::interval(period);
+            loop {
+                interval.tick().await;
+                collector.sample_resources().await;
+                if let Err(e) = collector.update_metrics().await {
+                    tracing::warn!("Error updating resource metrics: {e}");
                 }
             }
-        });
+        }));
+    }
+
+    /// Stop periodic metrics collection
+    pub async fn stop_collection(&self) {
+        if let Some(task) = self.collection_task.lock().await.take() {
+            task.abort();
+        }
+    }
+
+    /// Take a host and process sample now
+    pub async fn sample_resources(&self) -> Vec<Metric> {
+        let sample = self.sampler.lock().await.sample();
+        *self.latest_sample.write().await = Some((Instant::now(), sample.clone()));
+        sample
+    }
+
+    /// The latest host and process sample, taken now if it is older than
+    /// the collection interval
+    pub async fn current_sample(&self) -> Vec<Metric> {
+        if let Some((taken, sample)) = self.latest_sample.read().await.as_ref() {
+            if taken.elapsed() < self.sample_interval() {
+                return sample.clone();
+            }
+        }
+        self.sample_resources().await
+    }
+
+    /// Time between samples
+    fn sample_interval(&self) -> Duration {
+        Duration::from_secs(self.config.interval.max(1))
     }
 
     /// Collect system resource metrics
@@ -651,19 +715,6 @@ impl ResourceMetricsCollector {
     }
 }
 
-impl Clone for ResourceMetricsCollector {
-    fn clone(&self) -> Self {
-        Self {
-            system: Arc::new(RwLock::new(System::new_all())),
-            metrics: Arc::new(RwLock::new(Vec::new())),
-            team_paths: Arc::new(RwLock::new(HashMap::new())),
-            prev_disk_io: Arc::new(RwLock::new(HashMap::new())),
-            performance_collector: None,
-            config: self.config.clone(),
-        }
-    }
-}
-
 impl Default for ResourceMetricsCollector {
     fn default() -> Self {
         Self::new()
@@ -1002,42 +1053,21 @@ impl MetricCollector for ResourceMetricsCollector {
     }
 
     async fn collect_metrics(&self) -> Result<Vec<Metric>> {
-        // Update system information before collecting metrics
-        self.system.write().await.refresh_all();
-        
-        // Generate at least some basic system metrics
-        let cpu_metric = Metric::new(
-            "system_cpu_usage".to_string(),
-            f64::from(self.system.read().await.global_cpu_info().cpu_usage()),
-            MetricType::Gauge,
-            HashMap::new(),
-        );
-        
-        let memory_metric = Metric::new(
-            "system_memory_usage".to_string(),
-            self.system.read().await.used_memory() as f64,
-            MetricType::Gauge,
-            HashMap::new(),
-        );
-        
-        // Record the new metrics
-        let mut metrics = self.metrics.write().await;
-        metrics.push(cpu_metric.clone());
-        metrics.push(memory_metric.clone());
-        
-        // Return all metrics
-        Ok(crate::metrics::write_guard_to_vec(&metrics))
+        let sample = self.current_sample().await;
+        let mut metrics = crate::metrics::read_guard_to_vec(&self.metrics.read().await);
+        metrics.extend(sample);
+        Ok(metrics)
     }
 
     async fn start(&self) -> Result<()> {
-        // Implementation depends on the existing code
         tracing::info!("Starting ResourceMetricsCollector");
+        self.start_collection().await;
         Ok(())
     }
 
     async fn stop(&self) -> Result<()> {
-        // Implementation depends on the existing code
         tracing::info!("Stopping ResourceMetricsCollector");
+        self.stop_collection().await;
         Ok(())
     }
 }
@@ -1128,4 +1158,28 @@ mod tests {
         let team_metrics = adapter.get_team_metrics().await;
         assert!(team_metrics.is_ok());
     }
+
+    #[tokio::test]
+    async fn test_host_and_process_gauges() {
+        let collector = ResourceMetricsCollector::new();
+        let metrics = collector.collect_metrics().await.unwrap();
+        let value = |name: &str| metrics.iter().find(|m| m.name == name).map(|m| m.value);
+
+        assert!(value("system_cpu_usage").is_some());
+        assert!(value("system_memory_total").unwrap() > 0.0);
+        assert!(metrics.iter().all(|m| m.metric_type == MetricType::Gauge));
+
+        let rss = metrics.iter().find(|m| m.name == "process_resident_memory").unwrap();
+        assert!(rss.value > 0.0);
+        assert_eq!(rss.labels["pid"], std::process::id().to_string());
+        if cfg!(target_os = "linux") {
+            assert!(value("process_threads").unwrap() >= 1.0);
+            assert!(value("process_open_fds").unwrap() >= 1.0);
+        }
+
+        // A fresh sample is reused until the interval has passed
+        let again = collector.collect_metrics().await.unwrap();
+        assert_eq!(again.len(), metrics.len());
+        assert_eq!(again[0].timestamp, metrics[0].timestamp);
+    }
 } 
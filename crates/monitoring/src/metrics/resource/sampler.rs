@@ -0,0 +1,275 @@
+//! Host and process resource sampling
+//!
+//! [`ResourceSampler`] reads CPU, memory, disk space and disk I/O of the
+//! host and the memory, CPU, threads, file descriptors and I/O of the
+//! squirrel process, and turns each sample into labeled gauges. Values come
+//! from `sysinfo`; thread counts, file descriptors and per-device disk I/O
+//! are read from `/proc` where it exists and are left out elsewhere.
+//!
+//! Rates (CPU usage, bytes per second) are computed against the previous
+//! sample, so the first sample reports them as zero.
+
+use std::collections::HashMap;
+use std::path::Path;
+use std::time::Instant;
+
+use sysinfo::{Disks, Pid, ProcessRefreshKind, System};
+
+use crate::metrics::{Metric, MetricType};
+
+/// Cumulative I/O counters of one block device
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct DiskCounters {
+    /// Bytes read
+    pub read_bytes: u64,
+    /// Bytes written
+    pub written_bytes: u64,
+    /// Completed reads
+    pub reads: u64,
+    /// Completed writes
+    pub writes: u64,
+}
+
+/// Samples host and process resource usage
+#[derive(Debug)]
+pub struct ResourceSampler {
+    /// System information, refreshed on every sample
+    system: System,
+    /// Mounted disks
+    disks: Disks,
+    /// The process whose usage is sampled
+    pid: Option<Pid>,
+    /// Disk counters of the previous sample and when it was taken
+    prev_disk_io: Option<(Instant, HashMap<String, DiskCounters>)>,
+    /// When the process was last refreshed
+    prev_process_refresh: Option<Instant>,
+}
+
+impl Default for ResourceSampler {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl ResourceSampler {
+    /// Creates a sampler for the current process
+    #[must_use]
+    pub fn new() -> Self {
+        Self::for_process(sysinfo::get_current_pid().ok())
+    }
+
+    /// Creates a sampler for the process `pid`, or for the host only
+    #[must_use]
+    pub fn for_process(pid: Option<Pid>) -> Self {
+        Self {
+            system: System::new(),
+            disks: Disks::new(),
+            pid,
+            prev_disk_io: None,
+            prev_process_refresh: None,
+        }
+    }
+
+    /// Takes a sample and returns it as gauges
+    pub fn sample(&mut self) -> Vec<Metric> {
+        let mut metrics = Vec::new();
+        self.sample_cpu(&mut metrics);
+        self.sample_memory(&mut metrics);
+        self.sample_disks(&mut metrics);
+        self.sample_disk_io(&mut metrics);
+        self.sample_process(&mut metrics);
+        metrics
+    }
+
+    /// Host CPU usage, overall and per core, and load averages
+    fn sample_cpu(&mut self, metrics: &mut Vec<Metric>) {
+        self.system.refresh_cpu_usage();
+        metrics.push(gauge("system_cpu_usage", f64::from(self.system.global_cpu_info().cpu_usage()), &[]));
+        for (index, cpu) in self.system.cpus().iter().enumerate() {
+            let core = index.to_string();
+            metrics.push(gauge("system_cpu_core_usage", f64::from(cpu.cpu_usage()), &[("cpu", &core)]));
+        }
+        let load = System::load_average();
+        for (period, value) in [("1m", load.one), ("5m", load.five), ("15m", load.fifteen)] {
+            metrics.push(gauge("system_load_average", value, &[("period", period)]));
+        }
+    }
+
+    /// Host memory and swap
+    fn sample_memory(&mut self, metrics: &mut Vec<Metric>) {
+        self.system.refresh_memory();
+        let total = self.system.total_memory();
+        let used = self.system.used_memory();
+        metrics.push(gauge("system_memory_usage", used as f64, &[]));
+        metrics.push(gauge("system_memory_total", total as f64, &[]));
+        metrics.push(gauge("system_memory_available", self.system.available_memory() as f64, &[]));
+        metrics.push(gauge("system_memory_usage_percent", percent(used, total), &[]));
+        metrics.push(gauge("system_swap_usage", self.system.used_swap() as f64, &[]));
+        metrics.push(gauge("system_swap_total", self.system.total_swap() as f64, &[]));
+    }
+
+    /// Space used on each mounted disk
+    fn sample_disks(&mut self, metrics: &mut Vec<Metric>) {
+        self.disks.refresh_list();
+        for disk in &self.disks {
+            let mount = disk.mount_point().to_string_lossy();
+            let device = disk.name().to_string_lossy();
+            let labels = [("mount", mount.as_ref()), ("device", device.as_ref())];
+            let total = disk.total_space();
+            let used = total.saturating_sub(disk.available_space());
+            metrics.push(gauge("system_disk_usage", used as f64, &labels));
+            metrics.push(gauge("system_disk_total", total as f64, &labels));
+            metrics.push(gauge("system_disk_usage_percent", percent(used, total), &labels));
+        }
+    }
+
+    /// Read and write rates of each block device
+    fn sample_disk_io(&mut self, metrics: &mut Vec<Metric>) {
+        let Some(current) = read_disk_counters() else {
+            return;
+        };
+        let now = Instant::now();
+        if let Some((then, previous)) = &self.prev_disk_io {
+            let secs = now.duration_since(*then).as_secs_f64();
+            let mut devices: Vec<&String> = current.keys().collect();
+            devices.sort();
+            for device in devices {
+                let (Some(new), Some(old)) = (current.get(device), previous.get(device)) else {
+                    continue;
+                };
+                let labels = [("device", device.as_str())];
+                let rate = |new: u64, old: u64| if secs > 0.0 { new.saturating_sub(old) as f64 / secs } else { 0.0 };
+                metrics.push(gauge("system_disk_read_bytes_per_sec", rate(new.read_bytes, old.read_bytes), &labels));
+                metrics.push(gauge("system_disk_write_bytes_per_sec", rate(new.written_bytes, old.written_bytes), &labels));
+                metrics.push(gauge("system_disk_reads_per_sec", rate(new.reads, old.reads), &labels));
+                metrics.push(gauge("system_disk_writes_per_sec", rate(new.writes, old.writes), &labels));
+            }
+        }
+        self.prev_disk_io = Some((now, current));
+    }
+
+    /// Memory, CPU, threads, file descriptors and I/O of the process
+    fn sample_process(&mut self, metrics: &mut Vec<Metric>) {
+        let Some(pid) = self.pid else {
+            return;
+        };
+        let refresh = ProcessRefreshKind::new().with_cpu().with_memory().with_disk_usage();
+        if !self.system.refresh_process_specifics(pid, refresh) {
+            return;
+        }
+        let Some(process) = self.system.process(pid) else {
+            return;
+        };
+        let now = Instant::now();
+        let secs = self
+            .prev_process_refresh
+            .replace(now)
+            .map_or(0.0, |then| now.duration_since(then).as_secs_f64());
+
+        let pid_label = pid.to_string();
+        let labels = [("pid", pid_label.as_str()), ("process", process.name())];
+        metrics.push(gauge("process_resident_memory", process.memory() as f64, &labels));
+        metrics.push(gauge("process_virtual_memory", process.virtual_memory() as f64, &labels));
+        metrics.push(gauge("process_cpu_usage", f64::from(process.cpu_usage()), &labels));
+
+        let disk = process.disk_usage();
+        let rate = |bytes: u64| if secs > 0.0 { bytes as f64 / secs } else { 0.0 };
+        metrics.push(gauge("process_disk_read_bytes_per_sec", rate(disk.read_bytes), &labels));
+        metrics.push(gauge("process_disk_write_bytes_per_sec", rate(disk.written_bytes), &labels));
+
+        let proc_dir = Path::new("/proc").join(pid.to_string());
+        let threads = read_thread_count(&proc_dir).or_else(|| process.tasks().map(|tasks| tasks.len() as u64));
+        if let Some(threads) = threads {
+            metrics.push(gauge("process_threads", threads as f64, &labels));
+        }
+        if let Ok(entries) = std::fs::read_dir(proc_dir.join("fd")) {
+            metrics.push(gauge("process_open_fds", entries.count() as f64, &labels));
+        }
+    }
+}
+
+/// Parses `/proc/diskstats` into counters by device name
+#[must_use]
+pub fn parse_diskstats(contents: &str) -> HashMap<String, DiskCounters> {
+    /// Size of the sectors `/proc/diskstats` counts in, regardless of device
+    const SECTOR_SIZE: u64 = 512;
+
+    contents
+        .lines()
+        .filter_map(|line| {
+            let fields: Vec<&str> = line.split_whitespace().collect();
+            let number = |index: usize| fields.get(index).and_then(|field| field.parse::<u64>().ok());
+            let counters = DiskCounters {
+                reads: number(3)?,
+                read_bytes: number(5)? * SECTOR_SIZE,
+                writes: number(7)?,
+                written_bytes: number(9)? * SECTOR_SIZE,
+            };
+            Some(((*fields.get(2)?).to_string(), counters))
+        })
+        .collect()
+}
+
+/// Counters of the whole block devices of the host, if `/proc` is available
+fn read_disk_counters() -> Option<HashMap<String, DiskCounters>> {
+    let contents = std::fs::read_to_string("/proc/diskstats").ok()?;
+    let mut counters = parse_diskstats(&contents);
+    // Partitions repeat their device's I/O and loop and RAM devices are not
+    // disks; only devices listed in /sys/block are whole disks
+    counters.retain(|device, _| {
+        !device.starts_with("loop") && !device.starts_with("ram") && Path::new("/sys/block").join(device).exists()
+    });
+    Some(counters)
+}
+
+/// Thread count from a `/proc/<pid>/status` file
+fn read_thread_count(proc_dir: &Path) -> Option<u64> {
+    let status = std::fs::read_to_string(proc_dir.join("status")).ok()?;
+    status
+        .lines()
+        .find_map(|line| line.strip_prefix("Threads:"))
+        .and_then(|count| count.trim().parse().ok())
+}
+
+/// `part` as a percentage of `total`
+fn percent(part: u64, total: u64) -> f64 {
+    if total == 0 {
+        0.0
+    } else {
+        part as f64 / total as f64 * 100.0
+    }
+}
+
+/// Gauge named `name` with `labels`
+fn gauge(name: &str, value: f64, labels: &[(&str, &str)]) -> Metric {
+    let labels = labels
+        .iter()
+        .map(|(key, value)| ((*key).to_string(), (*value).to_string()))
+        .collect();
+    Metric::new(name.to_string(), value, MetricType::Gauge, labels)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_diskstats() {
+        let contents = "\
+ 259       0 nvme0n1 1200 10 4096 300 800 5 2048 100 0 400 400
+ 259       1 nvme0n1p1 100 0 512 10 50 0 256 5 0 15 15
+   7       0 loop0 bad line
+";
+        let counters = parse_diskstats(contents);
+        assert_eq!(counters.len(), 2);
+        assert_eq!(
+            counters["nvme0n1"],
+            DiskCounters {
+                read_bytes: 4096 * 512,
+                written_bytes: 2048 * 512,
+                reads: 1200,
+                writes: 800,
+            }
+        );
+    }
+}
@@ -0,0 +1,87 @@
+//! Metrics of calls under retry policies
+//!
+//! [`RetryMetrics`] observes every call made through
+//! [`squirrel_core::retry`] and records, labeled by operation and outcome,
+//! the counter `retry_calls_total` and the histogram `retry_attempts` of the
+//! attempts each call took.
+
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use squirrel_core::retry::{self, RetryObserver, RetryReport};
+
+use crate::metrics::{Metric, MetricCollector, MetricType};
+
+/// Records retried calls in a metric collector
+#[derive(Debug, Clone)]
+pub struct RetryMetrics {
+    /// Collector the metrics are recorded in
+    collector: Arc<dyn MetricCollector>,
+}
+
+impl RetryMetrics {
+    /// Creates an observer recording in `collector`
+    #[must_use]
+    pub fn new(collector: Arc<dyn MetricCollector>) -> Self {
+        Self { collector }
+    }
+
+    /// Reports every call under a retry policy to `collector` from now on
+    pub fn install(collector: Arc<dyn MetricCollector>) {
+        retry::set_observer(Arc::new(Self::new(collector)));
+    }
+
+    /// The metrics a finished call is recorded as
+    #[must_use]
+    pub fn metrics(report: &RetryReport<'_>) -> Vec<Metric> {
+        let labels = HashMap::from([
+            ("operation".to_string(), report.operation.to_string()),
+            ("outcome".to_string(), report.outcome.as_str().to_string()),
+        ]);
+        vec![
+            Metric::new("retry_calls_total", 1.0, MetricType::Counter, labels.clone()),
+            Metric::new("retry_attempts", f64::from(report.attempts), MetricType::Histogram, labels),
+        ]
+    }
+}
+
+impl RetryObserver for RetryMetrics {
+    fn record(&self, report: &RetryReport<'_>) {
+        // Calls finishing outside a runtime go unrecorded
+        let Ok(runtime) = tokio::runtime::Handle::try_current() else {
+            return;
+        };
+        let collector = Arc::clone(&self.collector);
+        let metrics = Self::metrics(report);
+        runtime.spawn(async move {
+            for metric in metrics {
+                if let Err(e) = collector.record_metric(metric).await {
+                    tracing::warn!("Failed to record retry metric: {}", e);
+                }
+            }
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+    use squirrel_core::retry::RetryOutcome;
+
+    #[test]
+    fn test_reports_become_labeled_metrics() {
+        let report = RetryReport {
+            operation: "mcp.connect",
+            attempts: 3,
+            elapsed: Duration::from_millis(300),
+            outcome: RetryOutcome::Exhausted,
+        };
+        let metrics = RetryMetrics::metrics(&report);
+        assert_eq!(metrics.len(), 2);
+        assert_eq!(metrics[0].name, "retry_calls_total");
+        assert!((metrics[1].value - 3.0).abs() < f64::EPSILON);
+        assert_eq!(metrics[1].labels["operation"], "mcp.connect");
+        assert_eq!(metrics[1].labels["outcome"], "exhausted");
+    }
+}
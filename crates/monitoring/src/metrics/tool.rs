@@ -13,7 +13,7 @@ use std::collections::HashMap;
 use std::sync::Arc;
 use tokio::sync::RwLock;
 use squirrel_core::error::Result;
-use crate::metrics::{Metric, MetricCollector, MetricType};
+use crate::metrics::{HistogramConfig, HistogramSet, HistogramSnapshot, Metric, MetricCollector, MetricType};
 use std::time::Duration;
 use crate::metrics::performance;
 
@@ -130,11 +130,16 @@ impl Default for ToolMetricsConfig {
     }
 }
 
+/// Name of the tool execution duration histogram, in milliseconds
+pub const TOOL_DURATION_METRIC: &str = "tool_duration_ms";
+
 /// Tool metrics collector for measuring tool operations
 #[derive(Debug)]
 pub struct ToolMetricCollector {
     /// Metrics storage
     metrics: Arc<RwLock<HashMap<String, ToolMetrics>>>,
+    /// Execution duration histograms by tool
+    durations: Arc<RwLock<HistogramSet>>,
     /// Performance collector
     performance_collector: Option<Arc<performance::PerformanceCollectorAdapter>>,
 }
@@ -144,6 +149,7 @@ impl ToolMetricCollector {
     #[must_use] pub fn new() -> Self {
         Self {
             metrics: Arc::new(RwLock::new(HashMap::new())),
+            durations: Arc::new(RwLock::new(HistogramSet::new(HistogramConfig::default()))),
             performance_collector: None,
         }
     }
@@ -154,10 +160,23 @@ impl ToolMetricCollector {
     ) -> Self {
         Self {
             metrics: Arc::new(RwLock::new(HashMap::new())),
+            durations: Arc::new(RwLock::new(HistogramSet::new(HistogramConfig::default()))),
             performance_collector,
         }
     }
 
+    /// Retrieves the execution duration histogram of a tool
+    ///
+    /// # Arguments
+    /// * `tool_name` - The name of the tool
+    ///
+    /// # Returns
+    /// Bucket counts and p50/p95/p99 durations in milliseconds, or None if
+    /// the tool has not been executed
+    pub async fn get_duration_histogram(&self, tool_name: &str) -> Option<HistogramSnapshot> {
+        self.durations.read().await.snapshot(TOOL_DURATION_METRIC, &tool_labels(tool_name))
+    }
+
     /// Retrieves metrics for a specific tool
     ///
     /// # Arguments
@@ -213,6 +232,12 @@ impl ToolMetricCollector {
         let mut metrics = self.metrics.write().await;
         let tool_metrics = metrics.entry(tool_name.to_string()).or_insert_with(|| ToolMetrics::new(tool_name.to_string()));
         tool_metrics.record_usage(duration_ms, success);
+        drop(metrics);
+
+        self.durations
+            .write()
+            .await
+            .observe(TOOL_DURATION_METRIC, &tool_labels(tool_name), duration_ms);
 
         Ok(())
     }
@@ -251,6 +276,7 @@ impl MetricCollector for ToolMetricCollector {
     /// - Failure count
     /// - Average duration
     /// - Success rate
+    /// - Duration histogram buckets and p50/p95/p99 percentiles
     ///
     /// # Returns
     /// A vector of standardized metrics with tool-specific labels
@@ -339,6 +365,7 @@ impl MetricCollector for ToolMetricCollector {
                 ));
             }
         }
+        result.extend(self.durations.read().await.to_metrics());
         
         Ok(result)
     }
@@ -362,6 +389,11 @@ impl MetricCollector for ToolMetricCollector {
     }
 }
 
+/// Labels identifying a tool's metrics
+fn tool_labels(tool_name: &str) -> HashMap<String, String> {
+    HashMap::from([("tool".to_string(), tool_name.to_string())])
+}
+
 /// Default implementation for ToolMetricCollector
 ///
 /// Creates a new collector with an empty metrics collection
@@ -436,6 +468,13 @@ mod tests {
         // Collect standardized metrics
         let standard_metrics = collector.collect_metrics().await.unwrap();
         assert!(!standard_metrics.is_empty());
+        assert!(standard_metrics.iter().any(|m| m.name == "tool_duration_ms_p95"));
+        
+        // Durations are aggregated into a histogram
+        let histogram = collector.get_duration_histogram("test_tool").await.unwrap();
+        assert_eq!(histogram.count, 3);
+        assert_eq!(histogram.max, Some(1.5));
+        assert!((histogram.p50.unwrap() - 0.8).abs() <= 0.01);
     }
     
     #[tokio::test]
@@ -0,0 +1,180 @@
+//! Report snapshots aggregated from health, alert and metric data.
+
+use std::collections::{BTreeMap, HashMap};
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+use super::{ReportConfig, ReportPeriod};
+use crate::alerts::{Alert, AlertSeverity};
+use crate::health::status::{HealthStatus, Status};
+use crate::metrics::{Histogram, HistogramConfig, Metric};
+
+/// Execution statistics for a single command
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SlowCommand {
+    /// Command name
+    pub command: String,
+    /// Number of recorded executions
+    pub executions: usize,
+    /// Average duration in milliseconds
+    pub avg_ms: f64,
+    /// 95th percentile duration in milliseconds
+    pub p95_ms: f64,
+    /// Slowest execution in milliseconds
+    pub max_ms: f64,
+}
+
+/// How a resource metric moved over the report window
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ResourceTrend {
+    /// Metric name
+    pub metric: String,
+    /// First sample in the window
+    pub first: f64,
+    /// Last sample in the window
+    pub last: f64,
+    /// Lowest sample
+    pub min: f64,
+    /// Highest sample
+    pub max: f64,
+    /// Mean of all samples
+    pub avg: f64,
+}
+
+impl ResourceTrend {
+    /// Change between the first and last sample
+    #[must_use]
+    pub fn change(&self) -> f64 {
+        self.last - self.first
+    }
+}
+
+/// Everything a digest reports on, for one period
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ReportData {
+    /// Period the report covers
+    pub period: ReportPeriod,
+    /// Start of the covered window
+    pub window_start: DateTime<Utc>,
+    /// When the report was generated
+    pub generated_at: DateTime<Utc>,
+    /// Overall health at generation time
+    pub health: Status,
+    /// Health details
+    pub health_message: String,
+    /// Alerts raised in the window, by severity name
+    pub alert_counts: BTreeMap<String, usize>,
+    /// Slowest commands, slowest first
+    pub slow_commands: Vec<SlowCommand>,
+    /// Trends of the configured resource metrics
+    pub resource_trends: Vec<ResourceTrend>,
+}
+
+impl ReportData {
+    /// Builds a snapshot for the window ending at `generated_at`
+    ///
+    /// Alerts and metrics outside the window are ignored, so callers can
+    /// pass everything they have.
+    #[must_use]
+    pub fn build(
+        period: ReportPeriod,
+        generated_at: DateTime<Utc>,
+        config: &ReportConfig,
+        health: Option<&HealthStatus>,
+        alerts: &[Alert],
+        metrics: &[Metric],
+    ) -> Self {
+        let window_start = generated_at - period.duration();
+        let start_secs = window_start.timestamp();
+        let metrics: Vec<&Metric> = metrics.iter().filter(|m| m.timestamp >= start_secs).collect();
+
+        let mut alert_counts: BTreeMap<String, usize> = [
+            AlertSeverity::Critical,
+            AlertSeverity::Error,
+            AlertSeverity::Warning,
+            AlertSeverity::Info,
+        ]
+        .iter()
+        .map(|severity| (severity.name().to_string(), 0))
+        .collect();
+        for alert in alerts.iter().filter(|a| a.timestamp >= window_start) {
+            *alert_counts.entry(alert.severity.name().to_string()).or_default() += 1;
+        }
+
+        Self {
+            period,
+            window_start,
+            generated_at,
+            health: health.map_or(Status::Unknown, |h| h.status),
+            health_message: health.map(|h| h.message.clone()).unwrap_or_default(),
+            alert_counts,
+            slow_commands: slow_commands(&metrics, config),
+            resource_trends: resource_trends(&metrics, &config.resource_metrics),
+        }
+    }
+
+    /// Total number of alerts in the window
+    #[must_use]
+    pub fn total_alerts(&self) -> usize {
+        self.alert_counts.values().sum()
+    }
+}
+
+/// Ranks commands by average duration
+fn slow_commands(metrics: &[&Metric], config: &ReportConfig) -> Vec<SlowCommand> {
+    // The report covers the whole window, so the histograms keep every value
+    let histogram_config = HistogramConfig {
+        window_secs: 0,
+        ..HistogramConfig::default()
+    };
+    let mut durations: HashMap<&str, Histogram> = HashMap::new();
+    for metric in metrics.iter().filter(|m| m.name == config.command_duration_metric) {
+        if let Some(command) = metric.labels.get(&config.command_label) {
+            durations
+                .entry(command.as_str())
+                .or_insert_with(|| Histogram::new(histogram_config.clone()))
+                .observe(metric.value);
+        }
+    }
+
+    let mut commands: Vec<SlowCommand> = durations
+        .into_iter()
+        .map(|(command, histogram)| {
+            let snapshot = histogram.snapshot();
+            SlowCommand {
+                command: command.to_string(),
+                executions: snapshot.count as usize,
+                avg_ms: snapshot.mean().unwrap_or_default(),
+                p95_ms: snapshot.p95.unwrap_or_default(),
+                max_ms: snapshot.max.unwrap_or_default(),
+            }
+        })
+        .collect();
+    commands.sort_by(|a, b| b.avg_ms.total_cmp(&a.avg_ms).then_with(|| a.command.cmp(&b.command)));
+    commands.truncate(config.top_commands);
+    commands
+}
+
+/// Summarises each resource metric that has samples in the window
+fn resource_trends(metrics: &[&Metric], names: &[String]) -> Vec<ResourceTrend> {
+    names
+        .iter()
+        .filter_map(|name| {
+            let mut samples: Vec<&Metric> = metrics.iter().copied().filter(|m| &m.name == name).collect();
+            if samples.is_empty() {
+                return None;
+            }
+            samples.sort_by_key(|m| m.timestamp);
+            let values: Vec<f64> = samples.iter().map(|m| m.value).collect();
+            Some(ResourceTrend {
+                metric: name.clone(),
+                first: values[0],
+                last: values[values.len() - 1],
+                min: values.iter().copied().fold(f64::MAX, f64::min),
+                max: values.iter().copied().fold(f64::MIN, f64::max),
+                avg: values.iter().sum::<f64>() / values.len() as f64,
+            })
+        })
+        .collect()
+}
@@ -0,0 +1,476 @@
+//! Periodic report digests
+//!
+//! This module renders daily and weekly summaries of system health, alert
+//! counts, the slowest commands and resource trends into Markdown or HTML,
+//! and delivers them through the alert notification channels. Each
+//! recipient picks its channel, format, periods and, optionally, its own
+//! template.
+
+use std::collections::HashMap;
+use std::fmt;
+use std::sync::Arc;
+use std::time::Duration;
+
+use async_trait::async_trait;
+use chrono::{DateTime, Datelike, Timelike, Utc, Weekday};
+use serde::{Deserialize, Serialize};
+use squirrel_core::error::{Result, SquirrelError};
+use tokio::sync::Mutex;
+use tokio::task::JoinHandle;
+
+use crate::alerts::manager::AlertManagerAdapter;
+use crate::alerts::notify::NotificationManager;
+use crate::alerts::{Alert, AlertLifecycle, AlertType};
+use crate::health::{HealthChecker, HealthStatus};
+use crate::metrics::{Metric, MetricCollector};
+
+/// Module for report snapshots
+pub mod data;
+
+/// Module for report rendering
+pub mod render;
+
+pub use data::{ReportData, ResourceTrend, SlowCommand};
+pub use render::{render, ReportFormat};
+
+/// How often a report is sent
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ReportPeriod {
+    /// Every day, covering the previous 24 hours
+    Daily,
+    /// Every Monday, covering the previous 7 days
+    Weekly,
+}
+
+impl ReportPeriod {
+    /// Length of the window the report covers
+    #[must_use]
+    pub fn duration(&self) -> chrono::Duration {
+        match self {
+            Self::Daily => chrono::Duration::days(1),
+            Self::Weekly => chrono::Duration::weeks(1),
+        }
+    }
+}
+
+impl fmt::Display for ReportPeriod {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Daily => write!(f, "Daily"),
+            Self::Weekly => write!(f, "Weekly"),
+        }
+    }
+}
+
+/// Someone who receives report digests
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ReportRecipient {
+    /// Recipient name, used in logs and the default title
+    pub name: String,
+    /// Notification channel to deliver through (`email`, `slack` or `webhook`)
+    pub channel: String,
+    /// Output format
+    #[serde(default)]
+    pub format: ReportFormat,
+    /// Periods this recipient subscribes to
+    pub periods: Vec<ReportPeriod>,
+    /// Custom template, overriding the format's default
+    #[serde(default)]
+    pub template: Option<String>,
+}
+
+/// Configuration for report digests
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ReportConfig {
+    /// Enable scheduled reports
+    pub enabled: bool,
+    /// Hour of the day (UTC) at which reports are sent
+    pub send_hour_utc: u32,
+    /// Report recipients
+    pub recipients: Vec<ReportRecipient>,
+    /// Number of slow commands to list
+    pub top_commands: usize,
+    /// Metric recording command durations in milliseconds
+    pub command_duration_metric: String,
+    /// Label of that metric holding the command name
+    pub command_label: String,
+    /// Metrics to report trends for
+    pub resource_metrics: Vec<String>,
+}
+
+impl Default for ReportConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            send_hour_utc: 6,
+            recipients: Vec::new(),
+            top_commands: 5,
+            command_duration_metric: "command_duration_ms".to_string(),
+            command_label: "command".to_string(),
+            resource_metrics: vec!["system_cpu_usage".to_string(), "system_memory_usage".to_string()],
+        }
+    }
+}
+
+/// Provides the monitoring data reports are built from
+#[async_trait]
+pub trait ReportSource: Send + Sync {
+    /// Current overall health, if known
+    async fn health(&self) -> Result<Option<HealthStatus>>;
+
+    /// Alerts, including ones older than the report window
+    async fn alerts(&self) -> Result<Vec<Alert>>;
+
+    /// Metric samples, including ones older than the report window
+    async fn metrics(&self) -> Result<Vec<Metric>>;
+}
+
+/// Report source reading from the monitoring components
+#[derive(Debug, Default, Clone)]
+pub struct MonitoringReportSource {
+    health_checker: Option<Arc<dyn HealthChecker>>,
+    alert_manager: Option<Arc<AlertManagerAdapter>>,
+    alert_lifecycle: Option<Arc<AlertLifecycle>>,
+    metric_collector: Option<Arc<dyn MetricCollector>>,
+}
+
+impl MonitoringReportSource {
+    /// Creates a source with no components; missing data is reported as empty
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Reads health from `checker`
+    #[must_use]
+    pub fn with_health_checker(mut self, checker: Arc<dyn HealthChecker>) -> Self {
+        self.health_checker = Some(checker);
+        self
+    }
+
+    /// Reads alerts from `manager`
+    #[must_use]
+    pub fn with_alert_manager(mut self, manager: Arc<AlertManagerAdapter>) -> Self {
+        self.alert_manager = Some(manager);
+        self
+    }
+
+    /// Reads the alerts tracked by `lifecycle`, each as of when it last fired
+    #[must_use]
+    pub fn with_alert_lifecycle(mut self, lifecycle: Arc<AlertLifecycle>) -> Self {
+        self.alert_lifecycle = Some(lifecycle);
+        self
+    }
+
+    /// Reads metrics from `collector`
+    #[must_use]
+    pub fn with_metric_collector(mut self, collector: Arc<dyn MetricCollector>) -> Self {
+        self.metric_collector = Some(collector);
+        self
+    }
+}
+
+#[async_trait]
+impl ReportSource for MonitoringReportSource {
+    async fn health(&self) -> Result<Option<HealthStatus>> {
+        match &self.health_checker {
+            Some(checker) => checker.check_health().await.map(Some),
+            None => Ok(None),
+        }
+    }
+
+    async fn alerts(&self) -> Result<Vec<Alert>> {
+        let mut alerts: HashMap<_, Alert> = HashMap::new();
+        if let Some(manager) = &self.alert_manager {
+            for alert in manager.get_alert_history().await?.into_iter().chain(manager.get_active_alerts().await?) {
+                alerts.insert(alert.id, alert);
+            }
+        }
+        for tracked in self.alert_lifecycle.iter().flat_map(|lifecycle| lifecycle.alerts()) {
+            let source = tracked.labels.get("source").cloned().unwrap_or_default();
+            let mut alert = Alert::new(AlertType::Generic, tracked.severity, source, tracked.message);
+            alert.id = tracked.alert_id;
+            alert.timestamp = tracked.last_seen;
+            alert.acknowledged = tracked.acknowledgment.is_some();
+            alerts.insert(alert.id, alert);
+        }
+        Ok(alerts.into_values().collect())
+    }
+
+    async fn metrics(&self) -> Result<Vec<Metric>> {
+        match &self.metric_collector {
+            Some(collector) => collector.collect_metrics().await,
+            None => Ok(Vec::new()),
+        }
+    }
+}
+
+/// Delivers rendered reports to a channel
+#[async_trait]
+pub trait ReportDelivery: Send + Sync {
+    /// Send `body` with `subject` through `channel`
+    async fn deliver(&self, channel: &str, subject: &str, body: &str, format: ReportFormat) -> Result<()>;
+}
+
+#[async_trait]
+impl ReportDelivery for NotificationManager {
+    async fn deliver(&self, channel: &str, subject: &str, body: &str, format: ReportFormat) -> Result<()> {
+        self.send_report(channel, subject, body, format.as_str())
+            .await
+            .map_err(|e| SquirrelError::monitoring(format!("Failed to deliver report: {e}")))
+    }
+}
+
+/// Generates report digests and sends them to their recipients
+pub struct ReportService {
+    config: ReportConfig,
+    source: Arc<dyn ReportSource>,
+    delivery: Arc<dyn ReportDelivery>,
+    last_sent: Mutex<HashMap<ReportPeriod, DateTime<Utc>>>,
+}
+
+impl ReportService {
+    /// Creates a new report service
+    pub fn new(config: ReportConfig, source: Arc<dyn ReportSource>, delivery: Arc<dyn ReportDelivery>) -> Self {
+        Self {
+            config,
+            source,
+            delivery,
+            last_sent: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Collects a snapshot for the window ending now
+    ///
+    /// # Errors
+    /// Returns an error if the report source fails
+    pub async fn generate(&self, period: ReportPeriod) -> Result<ReportData> {
+        let health = self.source.health().await?;
+        let alerts = self.source.alerts().await?;
+        let metrics = self.source.metrics().await?;
+        Ok(ReportData::build(
+            period,
+            Utc::now(),
+            &self.config,
+            health.as_ref(),
+            &alerts,
+            &metrics,
+        ))
+    }
+
+    /// Generates a report and sends it to every recipient subscribed to `period`
+    ///
+    /// Delivery failures are logged and do not stop the remaining recipients.
+    /// Returns the number of recipients the report reached.
+    ///
+    /// # Errors
+    /// Returns an error if the report cannot be generated
+    pub async fn send(&self, period: ReportPeriod) -> Result<usize> {
+        let recipients: Vec<&ReportRecipient> = self
+            .config
+            .recipients
+            .iter()
+            .filter(|r| r.periods.contains(&period))
+            .collect();
+        if recipients.is_empty() {
+            return Ok(0);
+        }
+
+        let data = self.generate(period).await?;
+        let subject = format!("{} Squirrel report - {}", period, data.generated_at.format("%Y-%m-%d"));
+        let mut delivered = 0;
+        for recipient in recipients {
+            let body = render(&data, &subject, recipient.format, recipient.template.as_deref());
+            match self.delivery.deliver(&recipient.channel, &subject, &body, recipient.format).await {
+                Ok(()) => delivered += 1,
+                Err(e) => tracing::warn!("Failed to send {} report to {}: {}", period, recipient.name, e),
+            }
+        }
+        Ok(delivered)
+    }
+
+    /// Whether `period` is due at `now`, given when it was last sent
+    fn is_due(&self, period: ReportPeriod, now: DateTime<Utc>, last_sent: Option<DateTime<Utc>>) -> bool {
+        if now.hour() < self.config.send_hour_utc {
+            return false;
+        }
+        if period == ReportPeriod::Weekly && now.weekday() != Weekday::Mon {
+            return false;
+        }
+        last_sent.is_none_or(|sent| sent.date_naive() != now.date_naive())
+    }
+
+    /// Sends every report that is due
+    pub async fn run_due(&self) {
+        let now = Utc::now();
+        for period in [ReportPeriod::Daily, ReportPeriod::Weekly] {
+            let last_sent = self.last_sent.lock().await.get(&period).copied();
+            if !self.is_due(period, now, last_sent) {
+                continue;
+            }
+            match self.send(period).await {
+                Ok(count) => {
+                    tracing::info!("Sent {} report to {} recipient(s)", period, count);
+                    self.last_sent.lock().await.insert(period, now);
+                }
+                Err(e) => tracing::warn!("Failed to generate {} report: {}", period, e),
+            }
+        }
+    }
+
+    /// Sends reports on schedule, checking every minute whether one is due
+    pub async fn run(&self) {
+        let mut interval = tokio::time::interval(Duration::from_secs(60));
+        loop {
+            interval.tick().await;
+            self.run_due().await;
+        }
+    }
+
+    /// Spawns the background task sending reports on schedule
+    pub fn spawn(self: &Arc<Self>) -> JoinHandle<()> {
+        let service = self.clone();
+        tokio::spawn(async move { service.run().await })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::alerts::{AlertSeverity, AlertType};
+    use crate::health::status::Status;
+    use crate::metrics::MetricType;
+    use chrono::TimeZone;
+
+    #[derive(Default)]
+    struct StaticSource {
+        alerts: Vec<Alert>,
+        metrics: Vec<Metric>,
+    }
+
+    #[async_trait]
+    impl ReportSource for StaticSource {
+        async fn health(&self) -> Result<Option<HealthStatus>> {
+            Ok(Some(HealthStatus::degraded("api".to_string(), "slow responses".to_string())))
+        }
+
+        async fn alerts(&self) -> Result<Vec<Alert>> {
+            Ok(self.alerts.clone())
+        }
+
+        async fn metrics(&self) -> Result<Vec<Metric>> {
+            Ok(self.metrics.clone())
+        }
+    }
+
+    #[derive(Default)]
+    struct RecordingDelivery {
+        sent: Mutex<Vec<(String, String)>>,
+    }
+
+    #[async_trait]
+    impl ReportDelivery for RecordingDelivery {
+        async fn deliver(&self, channel: &str, _subject: &str, body: &str, _format: ReportFormat) -> Result<()> {
+            self.sent.lock().await.push((channel.to_string(), body.to_string()));
+            Ok(())
+        }
+    }
+
+    fn duration(command: &str, ms: f64) -> Metric {
+        let labels = HashMap::from([("command".to_string(), command.to_string())]);
+        Metric::new("command_duration_ms".to_string(), ms, MetricType::Histogram, labels)
+    }
+
+    fn recipient(channel: &str, format: ReportFormat, periods: Vec<ReportPeriod>) -> ReportRecipient {
+        ReportRecipient {
+            name: channel.to_string(),
+            channel: channel.to_string(),
+            format,
+            periods,
+            template: None,
+        }
+    }
+
+    #[test]
+    fn test_build_aggregates_window() {
+        let now = Utc::now();
+        let mut old = Alert::new(AlertType::Generic, AlertSeverity::Critical, "db".to_string(), "old".to_string());
+        old.timestamp = now - chrono::Duration::days(3);
+        let alerts = vec![
+            Alert::new(AlertType::Generic, AlertSeverity::Critical, "db".to_string(), "down".to_string()),
+            Alert::new(AlertType::Generic, AlertSeverity::Warning, "api".to_string(), "slow".to_string()),
+            old,
+        ];
+        let mut cpu_start = Metric::new("system_cpu_usage".to_string(), 20.0, MetricType::Gauge, HashMap::new());
+        cpu_start.timestamp -= 60;
+        let metrics = vec![
+            duration("build", 100.0),
+            duration("build", 300.0),
+            duration("help", 5.0),
+            cpu_start,
+            Metric::new("system_cpu_usage".to_string(), 50.0, MetricType::Gauge, HashMap::new()),
+        ];
+
+        let data = ReportData::build(ReportPeriod::Daily, now, &ReportConfig::default(), None, &alerts, &metrics);
+
+        assert_eq!(data.health, Status::Unknown);
+        assert_eq!(data.total_alerts(), 2);
+        assert_eq!(data.alert_counts["Critical"], 1);
+        assert_eq!(data.alert_counts["Info"], 0);
+        assert_eq!(data.slow_commands[0].command, "build");
+        assert_eq!((data.slow_commands[0].avg_ms, data.slow_commands[0].max_ms), (200.0, 300.0));
+        assert!((data.slow_commands[0].p95_ms - 300.0).abs() <= 3.0);
+        assert_eq!(data.resource_trends.len(), 1);
+        assert_eq!(data.resource_trends[0].change(), 30.0);
+    }
+
+    #[tokio::test]
+    async fn test_send_renders_per_recipient() {
+        let mut custom = recipient("webhook", ReportFormat::Markdown, vec![ReportPeriod::Daily]);
+        custom.template = Some("{{period}}: {{total_alerts}} alerts, health {{health}}".to_string());
+        let config = ReportConfig {
+            recipients: vec![
+                recipient("email", ReportFormat::Html, vec![ReportPeriod::Daily, ReportPeriod::Weekly]),
+                recipient("slack", ReportFormat::Markdown, vec![ReportPeriod::Weekly]),
+                custom,
+            ],
+            ..ReportConfig::default()
+        };
+        let source = StaticSource {
+            metrics: vec![duration("<script>", 10.0)],
+            ..StaticSource::default()
+        };
+        let delivery = Arc::new(RecordingDelivery::default());
+        let service = ReportService::new(config, Arc::new(source), delivery.clone());
+
+        assert_eq!(service.send(ReportPeriod::Daily).await.unwrap(), 2);
+
+        let sent = delivery.sent.lock().await;
+        assert_eq!(sent[0].0, "email");
+        assert!(sent[0].1.starts_with("<!DOCTYPE html>"));
+        assert!(sent[0].1.contains("<td>&lt;script&gt;</td>"));
+        assert!(sent[0].1.contains("slow responses"));
+        assert_eq!(sent[1], ("webhook".to_string(), "Daily: 0 alerts, health Degraded".to_string()));
+    }
+
+    #[test]
+    fn test_schedule() {
+        let service = ReportService::new(
+            ReportConfig::default(),
+            Arc::new(StaticSource::default()),
+            Arc::new(RecordingDelivery::default()),
+        );
+        // 2026-10-19 is a Monday
+        let monday = Utc.with_ymd_and_hms(2026, 10, 19, 7, 0, 0).unwrap();
+        let tuesday = monday + chrono::Duration::days(1);
+
+        assert!(!service.is_due(ReportPeriod::Daily, monday - chrono::Duration::hours(2), None));
+        assert!(service.is_due(ReportPeriod::Daily, monday, None));
+        assert!(!service.is_due(ReportPeriod::Daily, monday + chrono::Duration::hours(3), Some(monday)));
+        assert!(service.is_due(ReportPeriod::Daily, tuesday, Some(monday)));
+        assert!(service.is_due(ReportPeriod::Weekly, monday, None));
+        assert!(!service.is_due(ReportPeriod::Weekly, tuesday, None));
+    }
+}
@@ -0,0 +1,186 @@
+//! Markdown and HTML rendering of report snapshots.
+//!
+//! Templates are plain text with `{{placeholder}}` markers. The available
+//! placeholders are `title`, `period`, `window_start`, `generated_at`,
+//! `health`, `health_message`, `total_alerts`, `alerts`, `slow_commands`
+//! and `resources`; the last three expand to a table in the output format.
+
+use serde::{Deserialize, Serialize};
+
+use super::data::ReportData;
+
+/// Output format of a rendered report
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ReportFormat {
+    /// GitHub-flavoured Markdown
+    #[default]
+    Markdown,
+    /// Standalone HTML document
+    Html,
+}
+
+impl ReportFormat {
+    /// Format name passed to notification channels
+    #[must_use]
+    pub const fn as_str(&self) -> &'static str {
+        match self {
+            Self::Markdown => "markdown",
+            Self::Html => "html",
+        }
+    }
+}
+
+/// Default Markdown template
+pub const DEFAULT_MARKDOWN_TEMPLATE: &str = "# {{title}}
+
+Period: {{period}} ({{window_start}} to {{generated_at}})
+
+## Health
+
+**{{health}}** {{health_message}}
+
+## Alerts ({{total_alerts}})
+
+{{alerts}}
+
+## Slowest commands
+
+{{slow_commands}}
+
+## Resource trends
+
+{{resources}}
+";
+
+/// Default HTML template
+pub const DEFAULT_HTML_TEMPLATE: &str = "<!DOCTYPE html>
+<html>
+<head><meta charset=\"utf-8\"><title>{{title}}</title></head>
+<body>
+<h1>{{title}}</h1>
+<p>Period: {{period}} ({{window_start}} to {{generated_at}})</p>
+<h2>Health</h2>
+<p><strong>{{health}}</strong> {{health_message}}</p>
+<h2>Alerts ({{total_alerts}})</h2>
+{{alerts}}
+<h2>Slowest commands</h2>
+{{slow_commands}}
+<h2>Resource trends</h2>
+{{resources}}
+</body>
+</html>
+";
+
+/// Renders `data` into `format`, using `template` or the format's default
+#[must_use]
+pub fn render(data: &ReportData, title: &str, format: ReportFormat, template: Option<&str>) -> String {
+    let template = template.unwrap_or(match format {
+        ReportFormat::Markdown => DEFAULT_MARKDOWN_TEMPLATE,
+        ReportFormat::Html => DEFAULT_HTML_TEMPLATE,
+    });
+    let text = |value: &str| match format {
+        ReportFormat::Markdown => value.to_string(),
+        ReportFormat::Html => escape_html(value),
+    };
+
+    let alerts: Vec<Vec<String>> = data
+        .alert_counts
+        .iter()
+        .map(|(severity, count)| vec![severity.clone(), count.to_string()])
+        .collect();
+    let commands: Vec<Vec<String>> = data
+        .slow_commands
+        .iter()
+        .map(|c| {
+            vec![
+                c.command.clone(),
+                c.executions.to_string(),
+                format!("{:.1}", c.avg_ms),
+                format!("{:.1}", c.p95_ms),
+                format!("{:.1}", c.max_ms),
+            ]
+        })
+        .collect();
+    let resources: Vec<Vec<String>> = data
+        .resource_trends
+        .iter()
+        .map(|r| {
+            vec![
+                r.metric.clone(),
+                format!("{:.2}", r.avg),
+                format!("{:.2}", r.min),
+                format!("{:.2}", r.max),
+                format!("{:+.2}", r.change()),
+            ]
+        })
+        .collect();
+
+    let replacements = [
+        ("title", text(title)),
+        ("period", data.period.to_string()),
+        ("window_start", data.window_start.format("%Y-%m-%d %H:%M UTC").to_string()),
+        ("generated_at", data.generated_at.format("%Y-%m-%d %H:%M UTC").to_string()),
+        ("health", format!("{:?}", data.health)),
+        ("health_message", text(&data.health_message)),
+        ("total_alerts", data.total_alerts().to_string()),
+        ("alerts", table(format, &["Severity", "Count"], &alerts)),
+        (
+            "slow_commands",
+            table(format, &["Command", "Runs", "Avg (ms)", "p95 (ms)", "Max (ms)"], &commands),
+        ),
+        (
+            "resources",
+            table(format, &["Metric", "Avg", "Min", "Max", "Change"], &resources),
+        ),
+    ];
+
+    replacements
+        .iter()
+        .fold(template.to_string(), |output, (name, value)| {
+            output.replace(&format!("{{{{{name}}}}}"), value)
+        })
+}
+
+/// Renders a table, or a short note when there are no rows
+fn table(format: ReportFormat, headers: &[&str], rows: &[Vec<String>]) -> String {
+    match format {
+        ReportFormat::Markdown => {
+            if rows.is_empty() {
+                return "_No data_".to_string();
+            }
+            let mut lines = vec![
+                format!("| {} |", headers.join(" | ")),
+                format!("|{}", "---|".repeat(headers.len())),
+            ];
+            lines.extend(rows.iter().map(|row| format!("| {} |", row.join(" | "))));
+            lines.join("\n")
+        }
+        ReportFormat::Html => {
+            if rows.is_empty() {
+                return "<p><em>No data</em></p>".to_string();
+            }
+            let cells = |row: &[String], tag: &str| {
+                row.iter()
+                    .map(|cell| format!("<{tag}>{}</{tag}>", escape_html(cell)))
+                    .collect::<String>()
+            };
+            let header: Vec<String> = headers.iter().map(|h| (*h).to_string()).collect();
+            let mut html = format!("<table>\n<tr>{}</tr>\n", cells(&header, "th"));
+            for row in rows {
+                html.push_str(&format!("<tr>{}</tr>\n", cells(row, "td")));
+            }
+            html.push_str("</table>");
+            html
+        }
+    }
+}
+
+/// Escapes text for inclusion in HTML
+fn escape_html(value: &str) -> String {
+    value
+        .replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
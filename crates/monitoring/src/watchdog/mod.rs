@@ -0,0 +1,387 @@
+// Memory watchdog module
+//
+// Keeps the server below a memory budget instead of letting the operating
+// system kill it. Each check compares the resident memory of the process with
+// the budget:
+//
+// - above `pause_percent`, new background executions are refused until usage
+//   falls below `resume_percent` again;
+// - above `cancel_percent`, the lowest-priority running execution is
+//   cancelled. One execution is cancelled per check, so the memory it frees is
+//   seen before anything else is stopped.
+//
+// Rising pressure and every cancellation produce an alert. When the pause is
+// lifted, the report says so, so the alert can be resolved.
+
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+use crate::accounting::process_memory_mb;
+use crate::alerts::status::{AlertType, ResourceAlert, ResourceType};
+use crate::alerts::{Alert, AlertSeverity};
+
+/// Source of the alerts the watchdog emits
+pub const ALERT_SOURCE: &str = "memory_watchdog";
+
+/// Memory watchdog settings
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct WatchdogConfig {
+    /// Resident memory budget in megabytes; the watchdog is off without one
+    pub budget_mb: Option<u64>,
+    /// Share of the budget, in percent, above which new work is refused
+    pub pause_percent: f64,
+    /// Share of the budget, in percent, below which new work is accepted again
+    pub resume_percent: f64,
+    /// Share of the budget, in percent, above which running work is cancelled
+    pub cancel_percent: f64,
+    /// Interval between checks in milliseconds
+    pub interval_ms: u64,
+}
+
+impl Default for WatchdogConfig {
+    fn default() -> Self {
+        Self {
+            budget_mb: None,
+            pause_percent: 85.0,
+            resume_percent: 75.0,
+            cancel_percent: 95.0,
+            interval_ms: 1000,
+        }
+    }
+}
+
+/// How close memory usage is to the budget
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum MemoryPressure {
+    /// Below the pause threshold
+    Normal,
+    /// Above the pause threshold; new work is refused
+    High,
+    /// Above the cancel threshold; running work is cancelled
+    Critical,
+}
+
+/// A background execution the watchdog may cancel
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RunningExecution {
+    /// Execution identifier
+    pub id: String,
+    /// Priority; lower priorities are cancelled first
+    pub priority: i32,
+    /// When the execution started
+    pub started_at: DateTime<Utc>,
+    /// What is being executed, for alerts and logs
+    pub description: String,
+}
+
+/// Background executions the watchdog protects the process from
+pub trait ExecutionPool: Send + Sync {
+    /// Executions that are currently running
+    fn running(&self) -> Vec<RunningExecution>;
+
+    /// Cancels an execution, returning whether it was still running
+    fn cancel(&self, id: &str, reason: &str) -> bool;
+}
+
+/// Outcome of one watchdog check
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct WatchdogReport {
+    /// When the check ran
+    pub checked_at: DateTime<Utc>,
+    /// Resident memory in megabytes
+    pub rss_mb: u64,
+    /// Memory budget in megabytes
+    pub budget_mb: u64,
+    /// Resident memory as a share of the budget, in percent
+    pub usage_percent: f64,
+    /// Pressure after the check
+    pub pressure: MemoryPressure,
+    /// Whether new work is accepted after the check
+    pub accepting: bool,
+    /// Executions cancelled by the check
+    pub cancelled: Vec<RunningExecution>,
+    /// Alert to raise, if pressure rose or work was cancelled
+    pub alert: Option<Alert>,
+    /// Whether the check lifted a pause, resolving earlier alerts
+    pub recovered: bool,
+}
+
+/// Reads the resident memory of the process in megabytes
+pub type MemoryProbe = Arc<dyn Fn() -> u64 + Send + Sync>;
+
+/// Watches process memory and sheds background work as it nears a budget
+pub struct MemoryWatchdog {
+    config: WatchdogConfig,
+    pool: Arc<dyn ExecutionPool>,
+    probe: MemoryProbe,
+    accepting: AtomicBool,
+    last: Mutex<Option<WatchdogReport>>,
+}
+
+impl std::fmt::Debug for MemoryWatchdog {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("MemoryWatchdog")
+            .field("config", &self.config)
+            .field("accepting", &self.accepting())
+            .finish_non_exhaustive()
+    }
+}
+
+impl MemoryWatchdog {
+    /// Creates a watchdog reading the resident memory of this process
+    #[must_use]
+    pub fn new(config: WatchdogConfig, pool: Arc<dyn ExecutionPool>) -> Self {
+        Self::with_probe(config, pool, Arc::new(process_memory_mb))
+    }
+
+    /// Creates a watchdog reading memory usage from `probe`
+    #[must_use]
+    pub fn with_probe(config: WatchdogConfig, pool: Arc<dyn ExecutionPool>, probe: MemoryProbe) -> Self {
+        Self {
+            config,
+            pool,
+            probe,
+            accepting: AtomicBool::new(true),
+            last: Mutex::new(None),
+        }
+    }
+
+    /// The watchdog's settings
+    #[must_use]
+    pub const fn config(&self) -> &WatchdogConfig {
+        &self.config
+    }
+
+    /// Whether new background executions may start
+    #[must_use]
+    pub fn accepting(&self) -> bool {
+        self.accepting.load(Ordering::SeqCst)
+    }
+
+    /// The report of the latest check, without its alert
+    #[must_use]
+    pub fn status(&self) -> Option<WatchdogReport> {
+        self.last
+            .lock()
+            .unwrap_or_else(std::sync::PoisonError::into_inner)
+            .clone()
+    }
+
+    /// Checks memory usage once, pausing work or cancelling an execution as
+    /// needed; returns `None` without a budget
+    pub fn check(&self) -> Option<WatchdogReport> {
+        self.check_usage((self.probe)())
+    }
+
+    /// Acts on a resident memory of `rss_mb`
+    pub fn check_usage(&self, rss_mb: u64) -> Option<WatchdogReport> {
+        let budget_mb = self.config.budget_mb.filter(|budget| *budget > 0)?;
+        #[allow(clippy::cast_precision_loss)]
+        let usage_percent = rss_mb as f64 * 100.0 / budget_mb as f64;
+        let pressure = if usage_percent >= self.config.cancel_percent {
+            MemoryPressure::Critical
+        } else if usage_percent >= self.config.pause_percent {
+            MemoryPressure::High
+        } else {
+            MemoryPressure::Normal
+        };
+
+        let was_accepting = self.accepting();
+        if pressure > MemoryPressure::Normal {
+            self.accepting.store(false, Ordering::SeqCst);
+        } else if usage_percent < self.config.resume_percent {
+            self.accepting.store(true, Ordering::SeqCst);
+        }
+
+        let mut cancelled = Vec::new();
+        if pressure == MemoryPressure::Critical {
+            let reason = format!("memory pressure: {rss_mb} MB resident of a {budget_mb} MB budget");
+            let mut candidates = self.pool.running();
+            // Lowest priority first; among equals, the newest loses the least work
+            candidates.sort_by(|a, b| a.priority.cmp(&b.priority).then(b.started_at.cmp(&a.started_at)));
+            if let Some(victim) = candidates.into_iter().find(|execution| self.pool.cancel(&execution.id, &reason)) {
+                tracing::warn!("Cancelled {} ({}) under {}", victim.id, victim.description, reason);
+                cancelled.push(victim);
+            }
+        }
+
+        let previous = self.status().map_or(MemoryPressure::Normal, |report| report.pressure);
+        let alert = (pressure > previous || !cancelled.is_empty())
+            .then(|| pressure_alert(pressure, rss_mb, budget_mb, usage_percent, &cancelled));
+        let report = WatchdogReport {
+            checked_at: Utc::now(),
+            rss_mb,
+            budget_mb,
+            usage_percent,
+            pressure,
+            accepting: self.accepting(),
+            cancelled,
+            alert,
+            recovered: !was_accepting && self.accepting(),
+        };
+        *self.last.lock().unwrap_or_else(std::sync::PoisonError::into_inner) = Some(WatchdogReport {
+            alert: None,
+            ..report.clone()
+        });
+        Some(report)
+    }
+
+    /// Checks memory usage every `interval_ms`, handing each report to
+    /// `on_report`; does nothing without a budget
+    pub fn spawn(self: &Arc<Self>, on_report: impl Fn(&WatchdogReport) + Send + Sync + 'static) {
+        if self.config.budget_mb.is_none() {
+            return;
+        }
+        let watchdog = Arc::clone(self);
+        tokio::spawn(async move {
+            let mut interval = tokio::time::interval(std::time::Duration::from_millis(watchdog.config.interval_ms.max(100)));
+            interval.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Skip);
+            loop {
+                interval.tick().await;
+                let checker = Arc::clone(&watchdog);
+                match tokio::task::spawn_blocking(move || checker.check()).await {
+                    Ok(Some(report)) => on_report(&report),
+                    Ok(None) => {}
+                    Err(e) => tracing::warn!("Memory watchdog check failed: {}", e),
+                }
+            }
+        });
+    }
+}
+
+/// Alert describing memory pressure and what was cancelled because of it
+fn pressure_alert(
+    pressure: MemoryPressure,
+    rss_mb: u64,
+    budget_mb: u64,
+    usage_percent: f64,
+    cancelled: &[RunningExecution],
+) -> Alert {
+    let severity = match pressure {
+        MemoryPressure::Critical => AlertSeverity::Critical,
+        MemoryPressure::High | MemoryPressure::Normal => AlertSeverity::Warning,
+    };
+    let mut message = format!(
+        "Memory usage is at {usage_percent:.0}% of the budget ({rss_mb} of {budget_mb} MB); new background executions are paused"
+    );
+    for execution in cancelled {
+        message.push_str(&format!("; cancelled {} ({})", execution.id, execution.description));
+    }
+    let resource = ResourceAlert {
+        resource_type: ResourceType::Memory,
+        usage_percentage: usage_percent,
+        limit: budget_mb,
+        current: rss_mb,
+        severity,
+    };
+    // Details are numbers so the alert keeps one fingerprint across checks
+    let details = HashMap::from([
+        ("rss_mb".to_string(), serde_json::json!(rss_mb)),
+        ("budget_mb".to_string(), serde_json::json!(budget_mb)),
+        ("cancelled".to_string(), serde_json::json!(cancelled.len())),
+    ]);
+    Alert::new(AlertType::Resource(resource), severity, ALERT_SOURCE.to_string(), message).with_details(details)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::Duration;
+
+    /// Pool of executions that are removed when cancelled
+    #[derive(Default)]
+    struct TestPool {
+        running: Mutex<Vec<RunningExecution>>,
+    }
+
+    impl TestPool {
+        fn add(&self, id: &str, priority: i32, age_secs: i64) {
+            self.running.lock().unwrap().push(RunningExecution {
+                id: id.to_string(),
+                priority,
+                started_at: Utc::now() - Duration::seconds(age_secs),
+                description: format!("run {id}"),
+            });
+        }
+    }
+
+    impl ExecutionPool for TestPool {
+        fn running(&self) -> Vec<RunningExecution> {
+            self.running.lock().unwrap().clone()
+        }
+
+        fn cancel(&self, id: &str, _reason: &str) -> bool {
+            let mut running = self.running.lock().unwrap();
+            let before = running.len();
+            running.retain(|execution| execution.id != id);
+            running.len() < before
+        }
+    }
+
+    fn watchdog(pool: Arc<TestPool>) -> MemoryWatchdog {
+        let config = WatchdogConfig {
+            budget_mb: Some(1000),
+            ..WatchdogConfig::default()
+        };
+        MemoryWatchdog::with_probe(config, pool, Arc::new(|| 0))
+    }
+
+    #[test]
+    fn test_high_pressure_pauses_until_usage_falls_below_resume() {
+        let pool = Arc::new(TestPool::default());
+        pool.add("a", 0, 10);
+        let watchdog = watchdog(pool.clone());
+
+        let report = watchdog.check_usage(500).unwrap();
+        assert_eq!(report.pressure, MemoryPressure::Normal);
+        assert!(report.accepting && report.alert.is_none());
+
+        let report = watchdog.check_usage(900).unwrap();
+        assert_eq!(report.pressure, MemoryPressure::High);
+        assert!(!watchdog.accepting());
+        assert_eq!(report.alert.unwrap().severity, AlertSeverity::Warning);
+        assert!(report.cancelled.is_empty());
+        assert!(watchdog.check_usage(900).unwrap().alert.is_none());
+
+        // Between the resume and pause thresholds the pause holds
+        assert!(!watchdog.check_usage(800).unwrap().accepting);
+        let report = watchdog.check_usage(700).unwrap();
+        assert!(report.accepting && report.recovered);
+        assert_eq!(pool.running().len(), 1);
+    }
+
+    #[test]
+    fn test_critical_pressure_cancels_lowest_priority_first() {
+        let pool = Arc::new(TestPool::default());
+        pool.add("important", 10, 60);
+        pool.add("old-batch", -1, 60);
+        pool.add("new-batch", -1, 5);
+        let watchdog = watchdog(pool.clone());
+
+        let report = watchdog.check_usage(990).unwrap();
+        assert_eq!(report.pressure, MemoryPressure::Critical);
+        assert_eq!(report.cancelled[0].id, "new-batch");
+        let alert = report.alert.unwrap();
+        assert_eq!(alert.severity, AlertSeverity::Critical);
+        assert!(alert.message.contains("cancelled new-batch"));
+
+        assert_eq!(watchdog.check_usage(990).unwrap().cancelled[0].id, "old-batch");
+        assert_eq!(watchdog.check_usage(990).unwrap().cancelled[0].id, "important");
+        let report = watchdog.check_usage(990).unwrap();
+        assert!(report.cancelled.is_empty() && report.alert.is_none());
+        assert_eq!(watchdog.status().unwrap().pressure, MemoryPressure::Critical);
+    }
+
+    #[test]
+    fn test_watchdog_is_off_without_a_budget() {
+        let watchdog = MemoryWatchdog::new(WatchdogConfig::default(), Arc::new(TestPool::default()));
+        assert!(watchdog.check_usage(u64::MAX).is_none());
+        assert!(watchdog.accepting());
+    }
+}
@@ -0,0 +1,212 @@
+//! Assertions over a test host
+//!
+//! Each assertion panics with a message naming what differed, like
+//! `assert_eq!`, so a failing plugin test reads as a failed assertion rather
+//! than an unwrapped error.
+
+use crate::TestHost;
+
+/// Asserts that running `line` on `host` succeeds with `expected`
+///
+/// Trailing whitespace of the output is ignored.
+///
+/// # Panics
+///
+/// Panics if the command fails or its output differs.
+#[track_caller]
+pub fn command_output_eq(host: &TestHost, line: &str, expected: &str) {
+    match host.run(line) {
+        Ok(output) => assert_eq!(
+            output.trim_end(),
+            expected.trim_end(),
+            "Output of `{}` differs",
+            line
+        ),
+        Err(e) => panic!("`{}` failed: {}", line, e),
+    }
+}
+
+/// Asserts that context `context_id` holds every key and value of `expected`
+///
+/// Other keys of the context are ignored.
+///
+/// # Panics
+///
+/// Panics if the context does not exist or a key is missing or differs.
+pub async fn state_persisted(host: &TestHost, context_id: &str, expected: &[(&str, &str)]) {
+    let state = match host.context().get_context_state(context_id).await {
+        Ok(state) => state,
+        Err(e) => panic!("Context '{}' was not persisted: {}", context_id, e),
+    };
+    for (key, value) in expected {
+        assert_eq!(
+            state.data.get(*key).map(String::as_str),
+            Some(*value),
+            "Key '{}' of context '{}' differs",
+            key,
+            context_id
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+    use std::sync::Arc;
+
+    use async_trait::async_trait;
+    use serde_json::json;
+    use squirrel_cli::plugins::plugin::Plugin;
+    use squirrel_cli::plugins::PluginError;
+    use squirrel_commands::{Command, CommandError, CommandRegistry, CommandResult};
+    use squirrel_context::{ContextManager, ContextState};
+    use squirrel_mcp::tool::{ToolContext, ToolError, ToolExecutor};
+
+    use super::*;
+    use crate::MockToolExecutor;
+
+    /// Counts BLAST hits and remembers the last query
+    #[derive(Clone)]
+    struct HitsCommand {
+        context: Arc<ContextManager>,
+        blast: Arc<dyn ToolExecutor>,
+    }
+
+    impl Command for HitsCommand {
+        fn name(&self) -> &str {
+            "hits"
+        }
+
+        fn description(&self) -> &str {
+            "Count BLAST hits of a sequence"
+        }
+
+        fn execute(&self, args: &[String]) -> CommandResult<String> {
+            let query = args
+                .first()
+                .cloned()
+                .ok_or_else(|| CommandError::ValidationError("No sequence".to_string()))?;
+            let handle = tokio::runtime::Handle::current();
+            tokio::task::block_in_place(|| {
+                handle.block_on(async {
+                    let result = self
+                        .blast
+                        .execute(ToolContext {
+                            tool_id: "blast".to_string(),
+                            capability: "search".to_string(),
+                            capability_version: 1,
+                            parameters: HashMap::from([("query".to_string(), json!(query))]),
+                            security_token: None,
+                            session_id: None,
+                            request_id: "test".to_string(),
+                            timestamp: chrono::Utc::now(),
+                            deadline: None,
+                        })
+                        .await
+                        .map_err(|e| CommandError::ExecutionError(e.to_string()))?;
+                    let hits = result.output.and_then(|output| output["hits"].as_u64()).unwrap_or(0);
+
+                    let mut state = ContextState::with_id("hits-plugin".to_string());
+                    state.data.insert("last_query".to_string(), query);
+                    let saved = match self.context.get_context_state("hits-plugin").await {
+                        Ok(_) => self.context.update_context_state("hits-plugin", state).await,
+                        Err(_) => self.context.create_context("hits-plugin", state).await,
+                    };
+                    saved.map_err(|e| CommandError::ExecutionError(e.to_string()))?;
+                    Ok(format!("{} hits", hits))
+                })
+            })
+        }
+
+        fn parser(&self) -> clap::Command {
+            clap::Command::new("hits")
+        }
+
+        fn clone_box(&self) -> Box<dyn Command> {
+            Box::new(self.clone())
+        }
+    }
+
+    struct HitsPlugin {
+        command: HitsCommand,
+    }
+
+    #[async_trait]
+    impl Plugin for HitsPlugin {
+        fn name(&self) -> &str {
+            "hits-plugin"
+        }
+
+        fn version(&self) -> &str {
+            "0.1.0"
+        }
+
+        fn description(&self) -> Option<&str> {
+            None
+        }
+
+        async fn initialize(&self) -> Result<(), PluginError> {
+            Ok(())
+        }
+
+        fn register_commands(&self, registry: &mut CommandRegistry) -> Result<(), PluginError> {
+            registry
+                .register("hits", Arc::new(self.command.clone()))
+                .map_err(|e| PluginError::RegisterError(e.to_string()))
+        }
+
+        fn commands(&self) -> Vec<Arc<dyn Command>> {
+            vec![Arc::new(self.command.clone())]
+        }
+
+        async fn execute(&self, _args: &[String]) -> Result<String, PluginError> {
+            Ok(String::new())
+        }
+
+        async fn cleanup(&self) -> Result<(), PluginError> {
+            Ok(())
+        }
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_host_runs_plugin_commands_against_mock_tools() {
+        let host = TestHost::new().with_mock_tool(
+            MockToolExecutor::builder("blast")
+                .on("search", |e| e.param("query", json!("ACGT")).returns(json!({"hits": 2})).times(1))
+                .build(),
+        );
+        let plugin = Arc::new(HitsPlugin {
+            command: HitsCommand {
+                context: host.context(),
+                blast: host.tool("blast").unwrap(),
+            },
+        });
+        host.load_plugin(plugin.clone()).await.unwrap();
+        assert!(matches!(host.load_plugin(plugin).await, Err(PluginError::AlreadyExists(_))));
+
+        command_output_eq(&host, "hits ACGT", "2 hits");
+        state_persisted(&host, "hits-plugin", &[("last_query", "ACGT")]).await;
+        assert!(matches!(host.run("missing"), Err(CommandError::CommandNotFound(_))));
+        assert_eq!(host.mock_tool("blast").unwrap().call_count("search"), 1);
+        host.verify();
+
+        // The expectation allowed one search only
+        let again = HashMap::from([("query".to_string(), json!("ACGT"))]);
+        assert!(host.call_tool("blast", "search", again).await.is_err());
+        assert!(matches!(
+            host.call_tool("bwa", "align", HashMap::new()).await,
+            Err(ToolError::ToolNotFound(_))
+        ));
+        host.shutdown().await.unwrap();
+    }
+
+    #[tokio::test]
+    #[should_panic(expected = "Key 'last_query' of context 'hits-plugin' differs")]
+    async fn test_state_persisted_names_the_differing_key() {
+        let host = TestHost::new();
+        let mut state = ContextState::with_id("hits-plugin".to_string());
+        state.data.insert("last_query".to_string(), "GGCC".to_string());
+        host.context().create_context("hits-plugin", state).await.unwrap();
+        state_persisted(&host, "hits-plugin", &[("last_query", "ACGT")]).await;
+    }
+}
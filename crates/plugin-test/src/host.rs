@@ -0,0 +1,214 @@
+//! In-memory stand-in for the CLI that plugins run in
+
+use std::collections::HashMap;
+use std::fmt;
+use std::sync::{Arc, Mutex, MutexGuard};
+
+use chrono::Utc;
+use serde_json::Value;
+use squirrel_cli::plugins::plugin::Plugin;
+use squirrel_cli::plugins::PluginError;
+use squirrel_commands::{plugin_logs, CommandRegistry, CommandResult};
+use squirrel_context::{ContextManager, ContextManagerConfig};
+use squirrel_mcp::tool::{ToolContext, ToolError, ToolExecutionResult, ToolExecutor};
+use squirrel_test_utils::MockToolExecutor;
+use uuid::Uuid;
+
+/// Hosts plugins under test
+///
+/// Loading a plugin initializes it and registers its commands, each wrapped
+/// in the plugin's log scope as the plugin manager does. Contexts live only
+/// in memory, and tools are the mocks the host was built with.
+pub struct TestHost {
+    /// Commands of the loaded plugins
+    registry: Arc<CommandRegistry>,
+    /// Context manager without persistence
+    context: Arc<ContextManager>,
+    /// Mock tools by ID
+    tools: HashMap<String, Arc<MockToolExecutor>>,
+    /// Loaded plugins by name, in load order
+    plugins: Mutex<Vec<(String, Arc<dyn Plugin>)>>,
+}
+
+impl fmt::Debug for TestHost {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let plugins: Vec<String> = self.lock().iter().map(|(name, _)| name.clone()).collect();
+        f.debug_struct("TestHost")
+            .field("registry", &self.registry)
+            .field("context", &self.context)
+            .field("tools", &self.tools.keys().collect::<Vec<_>>())
+            .field("plugins", &plugins)
+            .finish()
+    }
+}
+
+impl Default for TestHost {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl TestHost {
+    /// Creates a host with no plugins, tools or contexts
+    #[must_use]
+    pub fn new() -> Self {
+        Self {
+            registry: Arc::new(CommandRegistry::new()),
+            context: Arc::new(ContextManager::with_config(ContextManagerConfig {
+                persistence_enabled: false,
+                ..ContextManagerConfig::default()
+            })),
+            tools: HashMap::new(),
+            plugins: Mutex::new(Vec::new()),
+        }
+    }
+
+    /// Serves `tool` under its tool ID
+    #[must_use]
+    pub fn with_mock_tool(mut self, tool: MockToolExecutor) -> Self {
+        self.tools.insert(tool.get_tool_id(), Arc::new(tool));
+        self
+    }
+
+    /// Plugins in load order
+    fn lock(&self) -> MutexGuard<'_, Vec<(String, Arc<dyn Plugin>)>> {
+        self.plugins.lock().unwrap_or_else(std::sync::PoisonError::into_inner)
+    }
+
+    /// The registry holding the loaded plugins' commands
+    #[must_use]
+    pub fn registry(&self) -> Arc<CommandRegistry> {
+        Arc::clone(&self.registry)
+    }
+
+    /// The context manager, to hand to the plugin under test
+    #[must_use]
+    pub fn context(&self) -> Arc<ContextManager> {
+        Arc::clone(&self.context)
+    }
+
+    /// The tool with `tool_id`, to hand to the plugin under test
+    #[must_use]
+    pub fn tool(&self, tool_id: &str) -> Option<Arc<dyn ToolExecutor>> {
+        self.tools
+            .get(tool_id)
+            .map(|tool| Arc::clone(tool) as Arc<dyn ToolExecutor>)
+    }
+
+    /// The mock behind the tool with `tool_id`, to inspect its calls
+    #[must_use]
+    pub fn mock_tool(&self, tool_id: &str) -> Option<Arc<MockToolExecutor>> {
+        self.tools.get(tool_id).cloned()
+    }
+
+    /// Initializes `plugin` and registers its commands
+    ///
+    /// # Errors
+    ///
+    /// Returns [`PluginError::AlreadyExists`] if a plugin with the same name
+    /// is loaded, the plugin's own error if it fails to initialize, and
+    /// [`PluginError::RegisterError`] if one of its commands is taken.
+    pub async fn load_plugin(&self, plugin: Arc<dyn Plugin>) -> Result<(), PluginError> {
+        let name = plugin.name().to_string();
+        if self.lock().iter().any(|(loaded, _)| *loaded == name) {
+            return Err(PluginError::plugin_already_exists(&name));
+        }
+        plugin_logs::scope_async(&name, plugin.initialize()).await?;
+        for command in plugin.commands() {
+            let scoped = Arc::new(plugin_logs::ScopedCommand::new(&name, Arc::clone(&command)));
+            self.registry.register(command.name(), scoped).map_err(|e| {
+                PluginError::RegisterError(format!(
+                    "Failed to register command '{}' from plugin '{}': {}",
+                    command.name(),
+                    name,
+                    e
+                ))
+            })?;
+        }
+        self.lock().push((name, plugin));
+        Ok(())
+    }
+
+    /// Runs a command line, split on whitespace
+    ///
+    /// # Errors
+    ///
+    /// Returns the command's error, or [`CommandError::CommandNotFound`]
+    /// if no loaded plugin provides it.
+    ///
+    /// [`CommandError::CommandNotFound`]: squirrel_commands::CommandError::CommandNotFound
+    pub fn run(&self, line: &str) -> CommandResult<String> {
+        let mut words = line.split_whitespace();
+        let name = words.next().unwrap_or_default();
+        self.run_args(name, &words.collect::<Vec<_>>())
+    }
+
+    /// Runs command `name` with `args`, which may contain whitespace
+    ///
+    /// # Errors
+    ///
+    /// Returns the command's error, or [`CommandError::CommandNotFound`]
+    /// if no loaded plugin provides it.
+    ///
+    /// [`CommandError::CommandNotFound`]: squirrel_commands::CommandError::CommandNotFound
+    pub fn run_args(&self, name: &str, args: &[&str]) -> CommandResult<String> {
+        let args: Vec<String> = args.iter().map(ToString::to_string).collect();
+        self.registry.execute(name, &args)
+    }
+
+    /// Calls `capability` of tool `tool_id` as a plugin would
+    ///
+    /// # Errors
+    ///
+    /// Returns [`ToolError::ToolNotFound`] if the host serves no such tool,
+    /// or the mock's error.
+    pub async fn call_tool(
+        &self,
+        tool_id: &str,
+        capability: &str,
+        parameters: HashMap<String, Value>,
+    ) -> Result<ToolExecutionResult, ToolError> {
+        let tool = self
+            .tool(tool_id)
+            .ok_or_else(|| ToolError::ToolNotFound(tool_id.to_string()))?;
+        tool.execute(ToolContext {
+            tool_id: tool_id.to_string(),
+            capability: capability.to_string(),
+            capability_version: 1,
+            parameters,
+            security_token: None,
+            session_id: None,
+            request_id: Uuid::new_v4().to_string(),
+            timestamp: Utc::now(),
+            deadline: None,
+        })
+        .await
+    }
+
+    /// Panics if a mock tool was called unexpectedly, or an expectation
+    /// with `times` was not met
+    pub fn verify(&self) {
+        for tool in self.tools.values() {
+            tool.verify();
+        }
+    }
+
+    /// Cleans up the loaded plugins, last loaded first
+    ///
+    /// # Errors
+    ///
+    /// Returns the first plugin's cleanup error; every plugin is cleaned up
+    /// regardless.
+    pub async fn shutdown(&self) -> Result<(), PluginError> {
+        let plugins: Vec<(String, Arc<dyn Plugin>)> = self.lock().drain(..).rev().collect();
+        let mut result = Ok(());
+        for (name, plugin) in plugins {
+            if let Err(e) = plugin_logs::scope_async(&name, plugin.cleanup()).await {
+                if result.is_ok() {
+                    result = Err(e);
+                }
+            }
+        }
+        result
+    }
+}
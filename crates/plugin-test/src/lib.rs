@@ -0,0 +1,27 @@
+//! Test harness for Squirrel plugin authors
+//!
+//! [`TestHost`] stands in for the CLI when testing a plugin: it loads the
+//! plugin into an in-memory command registry the way the plugin manager
+//! does, serves mock MCP tools built with [`MockToolExecutor`], and keeps
+//! contexts in a context manager that never touches the disk. The
+//! [`assertions`] check command output and the state a plugin left behind.
+//!
+//! ```ignore
+//! let host = TestHost::new()
+//!     .with_mock_tool(MockToolExecutor::builder("blast").on("search", |e| e.returns(json!({"hits": 2}))).build());
+//! let plugin = Arc::new(MyPlugin::new(host.context(), host.tool("blast").unwrap()));
+//! host.load_plugin(plugin).await?;
+//! command_output_eq(&host, "hits ACGT", "2 hits");
+//! state_persisted(&host, "my-plugin", &[("last_query", "ACGT")]).await;
+//! host.shutdown().await?;
+//! ```
+
+/// The test host
+pub mod host;
+pub use host::TestHost;
+
+/// Assertions over a test host
+pub mod assertions;
+pub use assertions::{command_output_eq, state_persisted};
+
+pub use squirrel_test_utils::{MockToolExecutor, MockToolExecutorBuilder};
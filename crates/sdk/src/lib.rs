@@ -0,0 +1,59 @@
+//! Stable API for writing Squirrel plugins
+//!
+//! Plugins depend on this crate instead of `squirrel-commands`,
+//! `squirrel-cli` and `squirrel-app` directly. Everything a plugin needs is
+//! re-exported under a versioned module, [`v1`], whose paths do not change
+//! when the internal crates are reorganized; the crate root and [`prelude`]
+//! re-export the current version.
+//!
+//! # Versioning
+//!
+//! The SDK's major and minor version match the host plugin API version,
+//! [`HOST_API_VERSION`]: a plugin built against SDK `1.x` declares
+//! `api_version = "1.x"` and loads in every host implementing `1.x` or
+//! newer within major version 1, older minor versions running behind the
+//! shims in [`v1::compat`]. Items are only added in minor versions. An
+//! incompatible change to a re-exported item is a new major version, with a
+//! new `v2` module beside `v1`, which keeps compiling for plugins that have
+//! not moved yet.
+//!
+//! ```ignore
+//! use squirrel_sdk::prelude::*;
+//!
+//! #[async_trait::async_trait]
+//! impl Plugin for MyPlugin {
+//!     // ...
+//! }
+//! ```
+
+/// Version 1 of the plugin API
+pub mod v1;
+pub use v1::*;
+
+/// Version of this SDK
+pub const SDK_VERSION: &str = env!("CARGO_PKG_VERSION");
+
+/// Everything a plugin usually imports
+pub mod prelude {
+    pub use crate::v1::command::{Command, CommandError, CommandRegistry, CommandResult};
+    pub use crate::v1::context::{ContextManager, ContextState};
+    pub use crate::v1::plugin::{Plugin, PluginError, PluginFactory};
+    pub use crate::v1::tool::{ToolContext, ToolError, ToolExecutionResult, ToolExecutor};
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sdk_version_matches_the_plugin_api() {
+        let (major, rest) = SDK_VERSION.split_once('.').unwrap();
+        let minor = rest.split('.').next().unwrap();
+        assert_eq!(
+            (major.parse::<u32>().unwrap(), minor.parse::<u32>().unwrap()),
+            (HOST_API_VERSION.major, HOST_API_VERSION.minor),
+            "Bump the SDK version with the host plugin API version"
+        );
+        assert_eq!(HOST_API_VERSION.major, 1, "A new major plugin API needs a v2 module");
+    }
+}
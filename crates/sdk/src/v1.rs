@@ -0,0 +1,79 @@
+//! Version 1 of the plugin API
+//!
+//! Items may be added here in minor versions, but never removed, renamed or
+//! changed incompatibly.
+
+pub use squirrel_app::plugin::{ApiVersion, CompatibilityReport, HOST_API_VERSION};
+
+/// CLI plugins, the way most plugins extend Squirrel
+///
+/// A plugin implements [`Plugin`] and exports a `create_plugin` function
+/// returning it, which the CLI's plugin manager loads from the plugin's
+/// directory.
+pub mod plugin {
+    pub use squirrel_cli::plugins::plugin::{Plugin, PluginFactory};
+    pub use squirrel_cli::plugins::{PluginError, PluginMetadata, PluginStatus};
+}
+
+/// Commands a plugin provides
+pub mod command {
+    pub use squirrel_commands::plugin_logs::{scope, scope_async};
+    pub use squirrel_commands::{Command, CommandError, CommandRegistry, CommandResult};
+}
+
+/// Contexts a plugin reads and stores state in
+pub mod context {
+    pub use squirrel_context::{
+        ContextError, ContextManager, ContextManagerConfig, ContextSnapshot, ContextState,
+    };
+}
+
+/// MCP tools a plugin calls or provides
+pub mod tool {
+    pub use squirrel_mcp::tool::{
+        ExecutionStatus, Tool, ToolContext, ToolError, ToolExecutionResult, ToolExecutor,
+    };
+}
+
+/// Plugins managed by the application's plugin manager, with persisted state
+/// and typed capabilities
+pub mod app {
+    pub use squirrel_app::error::CoreError;
+    pub use squirrel_app::plugin::{
+        CommandPlugin, McpPlugin, Plugin, PluginError, PluginMetadata, PluginState, PluginStatus,
+        ToolPlugin, UiPlugin,
+    };
+}
+
+/// Periodic tasks a plugin schedules through its host context
+///
+/// A plugin managed by the application's plugin manager receives a
+/// [`HostContext`] in [`Plugin::attach`](crate::v1::app::Plugin::attach)
+/// when it is loaded; its tasks are unscheduled when it is unloaded.
+pub mod schedule {
+    pub use squirrel_app::plugin::HostContext;
+    pub use squirrel_app::schedule::{
+        CronError, CronSchedule, RunOutcome, Schedule, ScheduleError, TaskOptions, TaskRun,
+        TaskStatus,
+    };
+    pub use squirrel_app::supervisor::{TaskError, TaskResult};
+}
+
+/// Small pieces of durable state a plugin keeps in the host's key-value
+/// store
+///
+/// [`HostContext::store`](crate::v1::schedule::HostContext::store) is the
+/// plugin's namespace of the store, if the host has one.
+pub mod kv {
+    pub use squirrel_core::kv::{KvEntry, KvError, Namespace, MAX_KEY_LEN};
+}
+
+/// Compatibility with plugins built against older minor versions
+///
+/// The host wraps such plugins in the [`COMPAT_SHIMS`] covering the changes
+/// since; [`negotiate`] tells a plugin author which apply to theirs.
+pub mod compat {
+    pub use squirrel_app::plugin::api::{
+        negotiate, CompatShim, Negotiation, COMPAT_SHIMS, LEGACY_API_VERSION,
+    };
+}
@@ -0,0 +1,265 @@
+//! Property-based test generators
+//!
+//! `proptest` strategies for MCP messages and responses, contexts and
+//! command argument lists, and round-trip checks to run on the generated
+//! values: JSON serialization and session encryption.
+//!
+//! Generated JSON holds no floating point numbers, so values survive a JSON
+//! round trip exactly.
+//!
+//! ```ignore
+//! proptest! {
+//!     #[test]
+//!     fn messages_round_trip(message in mcp_message()) {
+//!         check_json_round_trip(&message)?;
+//!     }
+//! }
+//! ```
+
+use std::time::Duration;
+
+use chrono::{DateTime, Utc};
+use proptest::collection::{hash_map, vec};
+use proptest::option;
+use proptest::prelude::*;
+use proptest::test_runner::TestCaseError;
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use serde_json::Value;
+use squirrel_mcp::context_manager::Context;
+use squirrel_mcp::security::SecurityManager;
+use squirrel_mcp::types::{
+    MCPMessage, MCPResponse, MessageId, MessageMetadata, MessageType, ResponseStatus,
+};
+use uuid::Uuid;
+
+/// Short identifier-like string
+fn identifier() -> impl Strategy<Value = String> {
+    "[a-z][a-z0-9_]{0,11}"
+}
+
+/// Any JSON value without floating point numbers, nested up to four levels
+pub fn json_value() -> impl Strategy<Value = Value> {
+    let leaf = prop_oneof![
+        Just(Value::Null),
+        any::<bool>().prop_map(Value::Bool),
+        any::<i64>().prop_map(Value::from),
+        any::<u64>().prop_map(Value::from),
+        ".{0,24}".prop_map(Value::String),
+    ];
+    leaf.prop_recursive(4, 64, 8, |inner| {
+        prop_oneof![
+            vec(inner.clone(), 0..8).prop_map(Value::Array),
+            hash_map(identifier(), inner, 0..8).prop_map(|map| Value::Object(map.into_iter().collect())),
+        ]
+    })
+}
+
+/// JSON object whose values are any [`json_value`]
+pub fn json_object() -> impl Strategy<Value = Value> {
+    hash_map(identifier(), json_value(), 0..8).prop_map(|map| Value::Object(map.into_iter().collect()))
+}
+
+/// Random UUID
+pub fn uuid() -> impl Strategy<Value = Uuid> {
+    any::<u128>().prop_map(Uuid::from_u128)
+}
+
+/// Timestamp between 2000 and 2100 with nanosecond precision
+pub fn timestamp() -> impl Strategy<Value = DateTime<Utc>> {
+    (946_684_800_i64..4_102_444_800, 0..1_000_000_000_u32)
+        .prop_map(|(secs, nanos)| DateTime::from_timestamp(secs, nanos).unwrap_or_default())
+}
+
+/// Any message type
+pub fn message_type() -> impl Strategy<Value = MessageType> {
+    prop_oneof![
+        Just(MessageType::Command),
+        Just(MessageType::Response),
+        Just(MessageType::Event),
+        Just(MessageType::Error),
+        Just(MessageType::Setup),
+    ]
+}
+
+/// Any response status
+pub fn response_status() -> impl Strategy<Value = ResponseStatus> {
+    prop_oneof![
+        Just(ResponseStatus::Success),
+        Just(ResponseStatus::Error),
+        Just(ResponseStatus::Pending),
+    ]
+}
+
+/// Message of any type with an object payload
+pub fn mcp_message() -> impl Strategy<Value = MCPMessage> {
+    (uuid(), message_type(), json_object()).prop_map(|(id, message_type, payload)| MCPMessage {
+        id: MessageId(id.to_string()),
+        message_type,
+        payload,
+    })
+}
+
+/// Response with any status and a JSON payload
+pub fn mcp_response() -> impl Strategy<Value = MCPResponse> {
+    (
+        uuid(),
+        response_status(),
+        json_value(),
+        option::of(".{1,40}"),
+        any::<u64>(),
+        identifier(),
+        identifier(),
+    )
+        .prop_map(
+            |(id, status, payload, error_message, timestamp, source, destination)| MCPResponse {
+                protocol_version: "1.0".to_string(),
+                message_id: id.to_string(),
+                status,
+                payload: serde_json::to_vec(&payload).unwrap_or_default(),
+                error_message,
+                metadata: MessageMetadata {
+                    timestamp,
+                    source,
+                    destination,
+                },
+            },
+        )
+}
+
+/// Context in any state: with or without a parent, metadata and expiry
+pub fn context() -> impl Strategy<Value = Context> {
+    (
+        uuid(),
+        ".{1,32}",
+        json_object(),
+        option::of(json_object()),
+        option::of(uuid()),
+        timestamp(),
+        0..86_400_u64,
+        option::of(0..86_400_u64),
+    )
+        .prop_map(
+            |(id, name, data, metadata, parent_id, created_at, updated_after, expires_after)| {
+                let later = |secs: u64| {
+                    created_at + chrono::Duration::from_std(Duration::from_secs(secs)).unwrap_or_default()
+                };
+                Context {
+                    id,
+                    name,
+                    data,
+                    metadata,
+                    parent_id,
+                    created_at,
+                    updated_at: later(updated_after),
+                    expires_at: expires_after.map(later),
+                }
+            },
+        )
+}
+
+/// One command-line argument: a flag, an option with a value, or a value
+/// that may contain spaces, quotes and non-ASCII characters
+pub fn command_arg() -> impl Strategy<Value = String> {
+    prop_oneof![
+        "[a-z]".prop_map(|flag| format!("-{flag}")),
+        "[a-z][a-z-]{0,15}".prop_map(|flag| format!("--{flag}")),
+        ("[a-z][a-z-]{0,15}", ".{0,16}").prop_map(|(flag, value)| format!("--{flag}={value}")),
+        "[^-].{0,24}",
+    ]
+}
+
+/// Argument list for a command, without the command name
+pub fn command_args() -> impl Strategy<Value = Vec<String>> {
+    vec(command_arg(), 0..8)
+}
+
+/// Checks that `value` serializes to JSON and deserializes back to a value
+/// that serializes identically
+///
+/// # Errors
+///
+/// Fails the test case if serialization fails or the round trip changes the
+/// value.
+pub fn check_json_round_trip<T: Serialize + DeserializeOwned>(value: &T) -> Result<(), TestCaseError> {
+    let json = serde_json::to_string(value).map_err(|e| TestCaseError::fail(format!("serialize: {e}")))?;
+    let decoded: T = serde_json::from_str(&json).map_err(|e| TestCaseError::fail(format!("deserialize: {e}")))?;
+    let original = serde_json::to_value(value).map_err(|e| TestCaseError::fail(e.to_string()))?;
+    let round_tripped = serde_json::to_value(&decoded).map_err(|e| TestCaseError::fail(e.to_string()))?;
+    prop_assert_eq!(original, round_tripped);
+    Ok(())
+}
+
+/// Checks that `data` encrypted for `session_id` decrypts back to `data` and
+/// that the ciphertext does not contain it
+///
+/// # Errors
+///
+/// Fails the test case if encryption or decryption fails or the round trip
+/// changes the data.
+pub async fn check_encryption_round_trip(
+    security: &dyn SecurityManager,
+    session_id: &str,
+    data: &[u8],
+) -> Result<(), TestCaseError> {
+    let encrypted = security
+        .encrypt(session_id, data)
+        .await
+        .map_err(|e| TestCaseError::fail(format!("encrypt: {e}")))?;
+    if data.len() >= 8 {
+        prop_assert!(
+            !encrypted.windows(data.len()).any(|window| window == data),
+            "ciphertext contains the plaintext"
+        );
+    }
+    let decrypted = security
+        .decrypt(session_id, &encrypted)
+        .await
+        .map_err(|e| TestCaseError::fail(format!("decrypt: {e}")))?;
+    prop_assert_eq!(decrypted, data.to_vec());
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use squirrel_mcp::security::{Credentials, SecurityConfig, SecurityManagerImpl};
+    use squirrel_mcp::types::SecurityLevel;
+
+    proptest! {
+        #[test]
+        fn test_protocol_values_round_trip(message in mcp_message(), response in mcp_response(), context in context()) {
+            check_json_round_trip(&message)?;
+            check_json_round_trip(&response)?;
+            check_json_round_trip(&context)?;
+            prop_assert!(context.updated_at >= context.created_at);
+        }
+
+        #[test]
+        fn test_command_args_shape(args in command_args()) {
+            prop_assert!(args.len() < 8);
+            prop_assert!(args.iter().all(|arg| !arg.starts_with('-') || arg.len() > 1));
+        }
+    }
+
+    proptest! {
+        #![proptest_config(ProptestConfig::with_cases(16))]
+
+        #[test]
+        fn test_encryption_round_trip(data in vec(any::<u8>(), 0..256)) {
+            let rt = tokio::runtime::Runtime::new().unwrap();
+            rt.block_on(async {
+                let security = SecurityManagerImpl::new(SecurityConfig::default()).unwrap();
+                let credentials = Credentials {
+                    client_id: "prop-client".to_string(),
+                    client_secret: "prop-secret".to_string(),
+                    security_level: SecurityLevel::Standard,
+                    requested_roles: None,
+                };
+                let token = security.authenticate(&credentials).await.unwrap();
+                let session = security.authorize(&token, SecurityLevel::Standard, None).await.unwrap();
+                check_encryption_round_trip(security.as_ref(), &session.id, &data).await
+            })?;
+        }
+    }
+}
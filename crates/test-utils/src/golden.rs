@@ -0,0 +1,185 @@
+//! Golden-file assertions
+//!
+//! The assertions compare a value, serialized as pretty JSON, with a file
+//! under `tests/golden/` of the crate being tested. Run the tests with
+//! `SQUIRREL_UPDATE_GOLDEN=1` to write the files instead of comparing.
+//!
+//! Fields that differ on every run (timestamps, durations, request IDs) are
+//! replaced with placeholders by [`assert_tool_result_golden`]; use
+//! [`assert_json_golden_with`] to mask fields of other values.
+
+use std::path::{Path, PathBuf};
+
+use serde::Serialize;
+use serde_json::Value;
+use squirrel_mcp::tool::ToolExecutionResult;
+
+/// Environment variable that makes the assertions rewrite golden files
+pub const UPDATE_GOLDEN_ENV: &str = "SQUIRREL_UPDATE_GOLDEN";
+
+/// Placeholder written in place of masked fields
+pub const MASKED: &str = "<masked>";
+
+/// Fields of a tool execution result that change from run to run
+const TOOL_RESULT_VOLATILE_FIELDS: &[&str] = &["request_id", "execution_time_ms", "timestamp"];
+
+/// Path of the golden file `name` of the crate being tested
+pub fn golden_path(name: &str) -> PathBuf {
+    let root = std::env::var_os("CARGO_MANIFEST_DIR").map_or_else(|| PathBuf::from("."), PathBuf::from);
+    root.join("tests").join("golden").join(format!("{name}.json"))
+}
+
+/// Asserts that `value` matches the golden file `name`
+///
+/// # Panics
+///
+/// Panics if the golden file is missing or differs from `value`.
+pub fn assert_json_golden<T: Serialize + ?Sized>(name: &str, value: &T) {
+    assert_json_golden_with(name, value, &[]);
+}
+
+/// Asserts that `value` matches the golden file `name` after replacing the
+/// fields at `masked` (dot-separated paths) with [`MASKED`]
+///
+/// # Panics
+///
+/// Panics if the golden file is missing or differs from the masked value.
+pub fn assert_json_golden_with<T: Serialize + ?Sized>(name: &str, value: &T, masked: &[&str]) {
+    let mut actual = serde_json::to_value(value).expect("value should serialize to JSON");
+    for path in masked {
+        mask(&mut actual, path);
+    }
+    assert_golden_value(&golden_path(name), &actual);
+}
+
+/// Asserts that a tool execution result matches the golden file `name`,
+/// ignoring its request ID, duration and timestamp
+///
+/// # Panics
+///
+/// Panics if the golden file is missing or differs from the result.
+pub fn assert_tool_result_golden(name: &str, result: &ToolExecutionResult) {
+    assert_json_golden_with(name, result, TOOL_RESULT_VOLATILE_FIELDS);
+}
+
+/// Asserts that the output of a command matches the golden file `name`
+///
+/// Output that is JSON, as printed by commands run with `--json`, is
+/// compared as JSON; other output is compared as a JSON string.
+///
+/// # Panics
+///
+/// Panics if the golden file is missing or differs from the output.
+pub fn assert_command_output_golden(name: &str, output: &str) {
+    let value = serde_json::from_str(output).unwrap_or_else(|_| Value::String(output.to_string()));
+    assert_json_golden(name, &value);
+}
+
+/// Replaces the field at the dot-separated `path` with [`MASKED`]
+fn mask(value: &mut Value, path: &str) {
+    let mut current = value;
+    let mut segments = path.split('.').peekable();
+    while let Some(segment) = segments.next() {
+        let next = match current {
+            Value::Object(map) => map.get_mut(segment),
+            Value::Array(items) => segment.parse::<usize>().ok().and_then(|index| items.get_mut(index)),
+            _ => None,
+        };
+        let Some(next) = next else {
+            return;
+        };
+        if segments.peek().is_none() {
+            *next = Value::String(MASKED.to_string());
+            return;
+        }
+        current = next;
+    }
+}
+
+fn assert_golden_value(path: &Path, actual: &Value) {
+    let rendered = format!("{}\n", serde_json::to_string_pretty(actual).expect("JSON values serialize"));
+    if std::env::var_os(UPDATE_GOLDEN_ENV).is_some() {
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent).expect("golden directory should be creatable");
+        }
+        std::fs::write(path, rendered).expect("golden file should be writable");
+        return;
+    }
+
+    let Ok(expected) = std::fs::read_to_string(path) else {
+        panic!(
+            "Golden file {} is missing; run with {UPDATE_GOLDEN_ENV}=1 to create it. Actual value:\n{rendered}",
+            path.display()
+        );
+    };
+    let expected_value: Value = serde_json::from_str(&expected)
+        .unwrap_or_else(|e| panic!("Golden file {} is not JSON: {e}", path.display()));
+    assert!(
+        expected_value == *actual,
+        "Value differs from golden file {}; run with {UPDATE_GOLDEN_ENV}=1 to update it.\nExpected:\n{expected}\nActual:\n{rendered}",
+        path.display()
+    );
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mock_tool::MockToolExecutor;
+    use chrono::Utc;
+    use serde_json::json;
+    use squirrel_mcp::tool::{ToolContext, ToolError, ToolExecutor};
+    use std::collections::HashMap;
+
+    fn context(capability: &str, query: &str) -> ToolContext {
+        ToolContext {
+            tool_id: "search".to_string(),
+            capability: capability.to_string(),
+            parameters: HashMap::from([("q".to_string(), json!(query))]),
+            security_token: None,
+            session_id: None,
+            request_id: uuid::Uuid::new_v4().to_string(),
+            timestamp: Utc::now(),
+            capability_version: 1,
+            deadline: None,
+        }
+    }
+
+    #[test]
+    fn test_mask() {
+        let mut value = json!({"a": {"b": [1, {"c": 2}]}, "d": 3});
+        mask(&mut value, "a.b.1.c");
+        mask(&mut value, "missing.path");
+        assert_eq!(value, json!({"a": {"b": [1, {"c": MASKED}]}, "d": 3}));
+    }
+
+    #[tokio::test]
+    async fn test_mock_executor_and_golden_result() {
+        let executor = MockToolExecutor::builder("search")
+            .on("query", |e| e.param("q", json!("rust")).returns(json!({"hits": 3})).times(1))
+            .on("query", |e| e.fails("no results"))
+            .on("index", |e| e.errors(ToolError::ExecutionError("index offline".to_string())))
+            .build();
+
+        let result = executor.execute(context("query", "rust")).await.unwrap();
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("result.json");
+        std::fs::write(
+            &path,
+            r#"{"tool_id": "search", "capability": "query", "request_id": "<masked>", "status": "Success",
+                "output": {"hits": 3}, "error_message": null, "execution_time_ms": "<masked>", "timestamp": "<masked>"}"#,
+        )
+        .unwrap();
+        let mut actual = serde_json::to_value(&result).unwrap();
+        for field in TOOL_RESULT_VOLATILE_FIELDS {
+            mask(&mut actual, field);
+        }
+        assert_golden_value(&path, &actual);
+
+        let second = executor.execute(context("query", "rust")).await.unwrap();
+        assert_eq!(second.error_message.as_deref(), Some("no results"));
+        assert!(executor.execute(context("index", "rust")).await.is_err());
+        assert_eq!(executor.call_count("query"), 2);
+        assert_eq!(executor.get_capabilities(), vec!["index", "query"]);
+        executor.verify();
+    }
+}
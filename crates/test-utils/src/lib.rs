@@ -43,9 +43,23 @@ pub mod mock_protocol;
 /// Mock implementation for security
 pub mod mock_security;
 
+/// Mock tool executor
+pub mod mock_tool;
+pub use mock_tool::{MockToolExecutor, MockToolExecutorBuilder};
+
+/// Golden-file assertions
+pub mod golden;
+
+/// Property-based test generators
+pub mod generators;
+
 /// Test data generator
 pub mod test_data;
 
+/// Deterministic simulation harness
+pub mod simulation;
+pub use simulation::{ScriptedPeer, SimClock, SimExecutor};
+
 /// Re-export common types from the core crate
 pub use squirrel_core::error::Result;
 
@@ -0,0 +1,299 @@
+//! Mock tool executor
+//!
+//! [`MockToolExecutor`] answers tool executions from expectations set up
+//! with [`MockToolExecutorBuilder`]: which capability is called, which
+//! parameters it must receive, what it returns and how often it may be
+//! called.
+//!
+//! ```ignore
+//! let executor = MockToolExecutor::builder("search")
+//!     .on("query", |e| e.param("q", json!("rust")).returns(json!({"hits": 3})).times(1))
+//!     .on("index", |e| e.errors(ToolError::ExecutionError("index offline".into())))
+//!     .build();
+//! // ... run the code under test ...
+//! executor.verify();
+//! ```
+
+use std::collections::HashMap;
+use std::fmt;
+use std::sync::{Arc, Mutex, MutexGuard};
+
+use async_trait::async_trait;
+use serde_json::Value;
+use squirrel_mcp::tool::{ExecutionStatus, ToolContext, ToolError, ToolExecutionResult, ToolExecutor};
+
+/// Predicate over the parameters of a call
+type ParamsMatcher = Arc<dyn Fn(&HashMap<String, Value>) -> bool + Send + Sync>;
+
+/// What an expectation answers with
+#[derive(Debug, Clone)]
+enum Outcome {
+    /// A successful result with this output
+    Output(Value),
+    /// A failed result with this error message
+    Failure(String),
+    /// An error from the executor
+    Error(ToolError),
+}
+
+/// Expected call of one capability
+#[derive(Clone)]
+pub struct Expectation {
+    capability: String,
+    params: Vec<(String, Value)>,
+    matcher: Option<ParamsMatcher>,
+    outcome: Outcome,
+    times: Option<usize>,
+}
+
+impl fmt::Debug for Expectation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Expectation")
+            .field("capability", &self.capability)
+            .field("params", &self.params)
+            .field("matcher", &self.matcher.is_some())
+            .field("outcome", &self.outcome)
+            .field("times", &self.times)
+            .finish()
+    }
+}
+
+impl Expectation {
+    fn new(capability: &str) -> Self {
+        Self {
+            capability: capability.to_string(),
+            params: Vec::new(),
+            matcher: None,
+            outcome: Outcome::Output(Value::Null),
+            times: None,
+        }
+    }
+
+    /// Requires parameter `name` to equal `value`
+    #[must_use]
+    pub fn param(mut self, name: &str, value: Value) -> Self {
+        self.params.push((name.to_string(), value));
+        self
+    }
+
+    /// Requires the parameters to satisfy `matcher`
+    #[must_use]
+    pub fn params_matching<F>(mut self, matcher: F) -> Self
+    where
+        F: Fn(&HashMap<String, Value>) -> bool + Send + Sync + 'static,
+    {
+        self.matcher = Some(Arc::new(matcher));
+        self
+    }
+
+    /// Answers with a successful result carrying `output`
+    #[must_use]
+    pub fn returns(mut self, output: Value) -> Self {
+        self.outcome = Outcome::Output(output);
+        self
+    }
+
+    /// Answers with a failed result carrying `message`
+    #[must_use]
+    pub fn fails(mut self, message: &str) -> Self {
+        self.outcome = Outcome::Failure(message.to_string());
+        self
+    }
+
+    /// Answers with `error` from the executor
+    #[must_use]
+    pub fn errors(mut self, error: ToolError) -> Self {
+        self.outcome = Outcome::Error(error);
+        self
+    }
+
+    /// Expects exactly `times` calls; further calls are unexpected
+    #[must_use]
+    pub fn times(mut self, times: usize) -> Self {
+        self.times = Some(times);
+        self
+    }
+
+    fn matches(&self, context: &ToolContext) -> bool {
+        context.capability == self.capability
+            && self
+                .params
+                .iter()
+                .all(|(name, value)| context.parameters.get(name) == Some(value))
+            && self.matcher.as_ref().is_none_or(|matcher| matcher(&context.parameters))
+    }
+}
+
+/// Builds a [`MockToolExecutor`]
+#[derive(Debug)]
+pub struct MockToolExecutorBuilder {
+    tool_id: String,
+    expectations: Vec<Expectation>,
+}
+
+impl MockToolExecutorBuilder {
+    /// Adds an expectation for `capability`, configured by `configure`
+    #[must_use]
+    pub fn on<F>(mut self, capability: &str, configure: F) -> Self
+    where
+        F: FnOnce(Expectation) -> Expectation,
+    {
+        self.expectations.push(configure(Expectation::new(capability)));
+        self
+    }
+
+    /// Builds the executor
+    pub fn build(self) -> MockToolExecutor {
+        let calls = vec![0; self.expectations.len()];
+        MockToolExecutor {
+            tool_id: self.tool_id,
+            expectations: self.expectations,
+            state: Mutex::new(CallLog {
+                calls,
+                history: Vec::new(),
+                unexpected: Vec::new(),
+            }),
+        }
+    }
+}
+
+/// Calls a mock executor received
+#[derive(Debug)]
+struct CallLog {
+    /// Calls answered by each expectation
+    calls: Vec<usize>,
+    /// Every call, as capability and parameters
+    history: Vec<(String, HashMap<String, Value>)>,
+    /// Calls no expectation answered
+    unexpected: Vec<String>,
+}
+
+/// Tool executor answering from expectations
+#[derive(Debug)]
+pub struct MockToolExecutor {
+    tool_id: String,
+    expectations: Vec<Expectation>,
+    state: Mutex<CallLog>,
+}
+
+impl MockToolExecutor {
+    /// Starts building a mock executor for `tool_id`
+    pub fn builder(tool_id: &str) -> MockToolExecutorBuilder {
+        MockToolExecutorBuilder {
+            tool_id: tool_id.to_string(),
+            expectations: Vec::new(),
+        }
+    }
+
+    fn lock(&self) -> MutexGuard<'_, CallLog> {
+        self.state.lock().unwrap_or_else(std::sync::PoisonError::into_inner)
+    }
+
+    /// Number of calls of `capability`, expected or not
+    pub fn call_count(&self, capability: &str) -> usize {
+        self.lock()
+            .history
+            .iter()
+            .filter(|(called, _)| called == capability)
+            .count()
+    }
+
+    /// Parameters of every call of `capability`, in call order
+    pub fn calls(&self, capability: &str) -> Vec<HashMap<String, Value>> {
+        self.lock()
+            .history
+            .iter()
+            .filter(|(called, _)| called == capability)
+            .map(|(_, params)| params.clone())
+            .collect()
+    }
+
+    /// Panics if a call was unexpected or an expectation with `times` was
+    /// not called that often
+    pub fn verify(&self) {
+        let log = self.lock();
+        let mut problems = log
+            .unexpected
+            .iter()
+            .map(|call| format!("unexpected call: {call}"))
+            .collect::<Vec<_>>();
+        for (expectation, calls) in self.expectations.iter().zip(&log.calls) {
+            if let Some(times) = expectation.times {
+                if *calls != times {
+                    problems.push(format!(
+                        "expected {times} calls of {}, got {calls}",
+                        expectation.capability
+                    ));
+                }
+            }
+        }
+        assert!(
+            problems.is_empty(),
+            "MockToolExecutor {} verification failed:\n{}",
+            self.tool_id,
+            problems.join("\n")
+        );
+    }
+}
+
+#[async_trait]
+impl ToolExecutor for MockToolExecutor {
+    async fn execute(&self, context: ToolContext) -> Result<ToolExecutionResult, ToolError> {
+        let outcome = {
+            let mut log = self.lock();
+            log.history
+                .push((context.capability.clone(), context.parameters.clone()));
+            let found = self.expectations.iter().enumerate().find(|(index, expectation)| {
+                expectation.matches(&context)
+                    && expectation.times.is_none_or(|times| log.calls[*index] < times)
+            });
+            match found {
+                Some((index, expectation)) => {
+                    log.calls[index] += 1;
+                    expectation.outcome.clone()
+                }
+                None => {
+                    let call = format!(
+                        "{}/{} with {}",
+                        context.tool_id,
+                        context.capability,
+                        serde_json::to_string(&context.parameters).unwrap_or_default()
+                    );
+                    log.unexpected.push(call.clone());
+                    Outcome::Error(ToolError::ExecutionError(format!("Unexpected call {call}")))
+                }
+            }
+        };
+
+        let (status, output, error_message) = match outcome {
+            Outcome::Output(output) => (ExecutionStatus::Success, Some(output), None),
+            Outcome::Failure(message) => (ExecutionStatus::Failure, None, Some(message)),
+            Outcome::Error(error) => return Err(error),
+        };
+        Ok(ToolExecutionResult {
+            tool_id: context.tool_id,
+            capability: context.capability,
+            request_id: context.request_id,
+            status,
+            output,
+            error_message,
+            execution_time_ms: 0,
+            timestamp: context.timestamp,
+        })
+    }
+
+    fn get_tool_id(&self) -> String {
+        self.tool_id.clone()
+    }
+
+    fn get_capabilities(&self) -> Vec<String> {
+        let mut capabilities: Vec<String> = self
+            .expectations
+            .iter()
+            .map(|expectation| expectation.capability.clone())
+            .collect();
+        capabilities.sort();
+        capabilities.dedup();
+        capabilities
+    }
+}
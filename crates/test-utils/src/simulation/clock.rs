@@ -0,0 +1,217 @@
+//! Simulated clock
+//!
+//! Time on a [`SimClock`] only moves when a test advances it. Sleeps and
+//! timeouts created from the clock complete as soon as the clock passes
+//! their deadline, so code that waits minutes of simulated time finishes
+//! instantly and always in the same order.
+
+use std::collections::BTreeMap;
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::task::{Context, Poll, Waker};
+use std::time::Duration;
+
+use chrono::{DateTime, Utc};
+use thiserror::Error;
+
+/// Error returned when a simulated timeout expires
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Error)]
+#[error("Simulated deadline of {0:?} elapsed")]
+pub struct Elapsed(pub Duration);
+
+/// Timers ordered by deadline, then by registration
+type Timers = BTreeMap<(Duration, u64), Waker>;
+
+#[derive(Debug)]
+struct ClockState {
+    /// Time since the clock started
+    now: Duration,
+    /// Pending sleeps
+    timers: Timers,
+    /// ID of the next registered timer
+    next_timer: u64,
+}
+
+/// Clock whose time is advanced manually
+///
+/// Clones share the same time.
+#[derive(Debug, Clone)]
+pub struct SimClock {
+    start: DateTime<Utc>,
+    state: Arc<Mutex<ClockState>>,
+}
+
+impl Default for SimClock {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl SimClock {
+    /// Creates a clock starting at the Unix epoch
+    pub fn new() -> Self {
+        Self::starting_at(DateTime::UNIX_EPOCH)
+    }
+
+    /// Creates a clock whose wall time starts at `start`
+    pub fn starting_at(start: DateTime<Utc>) -> Self {
+        Self {
+            start,
+            state: Arc::new(Mutex::new(ClockState {
+                now: Duration::ZERO,
+                timers: BTreeMap::new(),
+                next_timer: 0,
+            })),
+        }
+    }
+
+    fn lock(&self) -> MutexGuard<'_, ClockState> {
+        self.state.lock().unwrap_or_else(std::sync::PoisonError::into_inner)
+    }
+
+    /// Time elapsed since the clock started
+    pub fn elapsed(&self) -> Duration {
+        self.lock().now
+    }
+
+    /// Current wall time
+    pub fn now(&self) -> DateTime<Utc> {
+        let elapsed = chrono::Duration::from_std(self.elapsed()).unwrap_or(chrono::Duration::MAX);
+        self.start + elapsed
+    }
+
+    /// Moves time forward by `duration`, waking every sleep that is due
+    pub fn advance(&self, duration: Duration) {
+        let target = self.elapsed() + duration;
+        self.advance_to(target);
+    }
+
+    /// Moves time forward to `elapsed` since the start, waking every sleep
+    /// that is due; earlier times are ignored
+    pub fn advance_to(&self, elapsed: Duration) {
+        let due = {
+            let mut state = self.lock();
+            let now = state.now.max(elapsed);
+            state.now = now;
+            let pending = state.timers.split_off(&(now, u64::MAX));
+            std::mem::replace(&mut state.timers, pending)
+        };
+        for waker in due.into_values() {
+            waker.wake();
+        }
+    }
+
+    /// Moves time to the earliest pending deadline and returns it, or returns
+    /// `None` if nothing is sleeping
+    pub fn advance_to_next_deadline(&self) -> Option<Duration> {
+        let deadline = self.next_deadline()?;
+        self.advance_to(deadline);
+        Some(deadline)
+    }
+
+    /// Earliest pending deadline, as elapsed time since the start
+    pub fn next_deadline(&self) -> Option<Duration> {
+        self.lock().timers.keys().next().map(|(deadline, _)| *deadline)
+    }
+
+    /// Number of pending sleeps
+    pub fn pending_timers(&self) -> usize {
+        self.lock().timers.len()
+    }
+
+    /// Future that completes once the clock has advanced by `duration`
+    pub fn sleep(&self, duration: Duration) -> Sleep {
+        let deadline = self.elapsed() + duration;
+        self.sleep_until(deadline)
+    }
+
+    /// Future that completes once `elapsed` has passed since the start
+    pub fn sleep_until(&self, elapsed: Duration) -> Sleep {
+        Sleep {
+            clock: self.clone(),
+            deadline: elapsed,
+            timer: None,
+        }
+    }
+
+    /// Runs `future`, failing with [`Elapsed`] if it has not completed once
+    /// the clock has advanced by `duration`
+    pub fn timeout<F: Future>(&self, duration: Duration, future: F) -> Timeout<F> {
+        Timeout {
+            future: Box::pin(future),
+            sleep: self.sleep(duration),
+            duration,
+        }
+    }
+}
+
+/// Future returned by [`SimClock::sleep`]
+#[derive(Debug)]
+pub struct Sleep {
+    clock: SimClock,
+    deadline: Duration,
+    /// Registered timer, if the sleep has been polled
+    timer: Option<u64>,
+}
+
+impl Sleep {
+    /// Elapsed time at which the sleep completes
+    pub fn deadline(&self) -> Duration {
+        self.deadline
+    }
+}
+
+impl Future for Sleep {
+    type Output = ();
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<()> {
+        let this = &mut *self;
+        let mut state = this.clock.lock();
+        if state.now >= this.deadline {
+            if let Some(id) = this.timer.take() {
+                state.timers.remove(&(this.deadline, id));
+            }
+            return Poll::Ready(());
+        }
+        let id = match this.timer {
+            Some(id) => id,
+            None => {
+                let id = state.next_timer;
+                state.next_timer += 1;
+                this.timer = Some(id);
+                id
+            }
+        };
+        state.timers.insert((this.deadline, id), cx.waker().clone());
+        Poll::Pending
+    }
+}
+
+impl Drop for Sleep {
+    fn drop(&mut self) {
+        if let Some(id) = self.timer {
+            self.clock.lock().timers.remove(&(self.deadline, id));
+        }
+    }
+}
+
+/// Future returned by [`SimClock::timeout`]
+#[derive(Debug)]
+pub struct Timeout<F> {
+    future: Pin<Box<F>>,
+    sleep: Sleep,
+    duration: Duration,
+}
+
+impl<F: Future> Future for Timeout<F> {
+    type Output = Result<F::Output, Elapsed>;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        if let Poll::Ready(output) = self.future.as_mut().poll(cx) {
+            return Poll::Ready(Ok(output));
+        }
+        let duration = self.duration;
+        Pin::new(&mut self.sleep).poll(cx).map(|()| Err(Elapsed(duration)))
+    }
+}
@@ -0,0 +1,287 @@
+//! Controllable executor
+//!
+//! [`SimExecutor`] runs tasks on the calling thread, one poll at a time and
+//! in the order they were woken. When every task is waiting, it moves its
+//! [`SimClock`] to the next deadline instead of sleeping, so timeouts,
+//! retries and schedules play out in simulated time.
+
+use std::collections::{HashMap, VecDeque};
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::task::{Context, Poll, Waker};
+use std::time::Duration;
+
+use futures::future::BoxFuture;
+use futures::task::{waker_ref, ArcWake};
+
+use super::clock::SimClock;
+
+/// Tasks waiting to be polled and tasks spawned since the last poll
+#[derive(Default)]
+struct Queue {
+    ready: VecDeque<usize>,
+    spawned: Vec<(usize, BoxFuture<'static, ()>)>,
+    next_task: usize,
+}
+
+struct Shared {
+    queue: Mutex<Queue>,
+}
+
+impl Shared {
+    fn lock(&self) -> MutexGuard<'_, Queue> {
+        self.queue.lock().unwrap_or_else(std::sync::PoisonError::into_inner)
+    }
+
+    fn spawn<F>(&self, future: F) -> SimTask<F::Output>
+    where
+        F: Future + Send + 'static,
+        F::Output: Send + 'static,
+    {
+        let slot = Arc::new(Mutex::new(TaskSlot {
+            output: None,
+            waker: None,
+        }));
+        let task_slot = Arc::clone(&slot);
+        let future = async move {
+            let output = future.await;
+            let mut slot = task_slot.lock().unwrap_or_else(std::sync::PoisonError::into_inner);
+            slot.output = Some(output);
+            if let Some(waker) = slot.waker.take() {
+                waker.wake();
+            }
+        };
+
+        let mut queue = self.lock();
+        let id = queue.next_task;
+        queue.next_task += 1;
+        queue.spawned.push((id, Box::pin(future)));
+        queue.ready.push_back(id);
+        SimTask { slot }
+    }
+}
+
+/// Wakes a task by queueing it
+struct TaskWaker {
+    id: usize,
+    shared: Arc<Shared>,
+}
+
+impl ArcWake for TaskWaker {
+    fn wake_by_ref(arc_self: &Arc<Self>) {
+        let mut queue = arc_self.shared.lock();
+        if !queue.ready.contains(&arc_self.id) {
+            queue.ready.push_back(arc_self.id);
+        }
+    }
+}
+
+struct TaskSlot<T> {
+    output: Option<T>,
+    waker: Option<Waker>,
+}
+
+/// Handle to a spawned task; awaiting it yields the task's output
+pub struct SimTask<T> {
+    slot: Arc<Mutex<TaskSlot<T>>>,
+}
+
+impl<T> std::fmt::Debug for SimTask<T> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("SimTask").field("finished", &self.is_finished()).finish()
+    }
+}
+
+impl<T> SimTask<T> {
+    fn lock(&self) -> MutexGuard<'_, TaskSlot<T>> {
+        self.slot.lock().unwrap_or_else(std::sync::PoisonError::into_inner)
+    }
+
+    /// Whether the task has completed and its output not been taken
+    pub fn is_finished(&self) -> bool {
+        self.lock().output.is_some()
+    }
+
+    /// Takes the task's output, if it has completed
+    pub fn take(&self) -> Option<T> {
+        self.lock().output.take()
+    }
+}
+
+impl<T> Future for SimTask<T> {
+    type Output = T;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<T> {
+        let mut slot = self.lock();
+        match slot.output.take() {
+            Some(output) => Poll::Ready(output),
+            None => {
+                slot.waker = Some(cx.waker().clone());
+                Poll::Pending
+            }
+        }
+    }
+}
+
+/// Spawns tasks onto a [`SimExecutor`] from inside its tasks
+#[derive(Clone)]
+pub struct SimSpawner {
+    shared: Arc<Shared>,
+}
+
+impl std::fmt::Debug for SimSpawner {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("SimSpawner").finish_non_exhaustive()
+    }
+}
+
+impl SimSpawner {
+    /// Spawns `future` as a new task
+    pub fn spawn<F>(&self, future: F) -> SimTask<F::Output>
+    where
+        F: Future + Send + 'static,
+        F::Output: Send + 'static,
+    {
+        self.shared.spawn(future)
+    }
+}
+
+/// Single-threaded executor driven by the test
+pub struct SimExecutor {
+    clock: SimClock,
+    shared: Arc<Shared>,
+    tasks: HashMap<usize, BoxFuture<'static, ()>>,
+}
+
+impl std::fmt::Debug for SimExecutor {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("SimExecutor")
+            .field("clock", &self.clock)
+            .field("tasks", &self.tasks.len())
+            .finish()
+    }
+}
+
+impl Default for SimExecutor {
+    fn default() -> Self {
+        Self::new(SimClock::new())
+    }
+}
+
+impl SimExecutor {
+    /// Creates an executor that advances `clock`
+    pub fn new(clock: SimClock) -> Self {
+        Self {
+            clock,
+            shared: Arc::new(Shared {
+                queue: Mutex::new(Queue::default()),
+            }),
+            tasks: HashMap::new(),
+        }
+    }
+
+    /// The executor's clock
+    pub fn clock(&self) -> &SimClock {
+        &self.clock
+    }
+
+    /// Spawner for this executor
+    pub fn spawner(&self) -> SimSpawner {
+        SimSpawner {
+            shared: Arc::clone(&self.shared),
+        }
+    }
+
+    /// Spawns `future` as a new task; it runs once the executor is driven
+    pub fn spawn<F>(&self, future: F) -> SimTask<F::Output>
+    where
+        F: Future + Send + 'static,
+        F::Output: Send + 'static,
+    {
+        self.shared.spawn(future)
+    }
+
+    /// Number of tasks that have not completed
+    pub fn pending_tasks(&mut self) -> usize {
+        self.adopt_spawned();
+        self.tasks.len()
+    }
+
+    fn adopt_spawned(&mut self) {
+        let spawned = std::mem::take(&mut self.shared.lock().spawned);
+        self.tasks.extend(spawned);
+    }
+
+    /// Polls the next woken task; returns `false` if no task is ready
+    pub fn step(&mut self) -> bool {
+        self.adopt_spawned();
+        let Some(id) = self.shared.lock().ready.pop_front() else {
+            return false;
+        };
+        let Some(mut future) = self.tasks.remove(&id) else {
+            return true;
+        };
+        let waker = Arc::new(TaskWaker {
+            id,
+            shared: Arc::clone(&self.shared),
+        });
+        let waker = waker_ref(&waker);
+        let mut cx = Context::from_waker(&waker);
+        if future.as_mut().poll(&mut cx).is_pending() {
+            self.tasks.insert(id, future);
+        }
+        true
+    }
+
+    /// Polls tasks until none is ready, without advancing time; returns the
+    /// number of polls
+    pub fn run_until_stalled(&mut self) -> usize {
+        let mut polls = 0;
+        while self.step() {
+            polls += 1;
+        }
+        polls
+    }
+
+    /// Runs tasks while advancing time by up to `duration`, jumping from one
+    /// deadline to the next
+    pub fn run_for(&mut self, duration: Duration) {
+        let end = self.clock.elapsed() + duration;
+        self.run_until_stalled();
+        while let Some(deadline) = self.clock.next_deadline().filter(|deadline| *deadline <= end) {
+            self.clock.advance_to(deadline);
+            self.run_until_stalled();
+        }
+        self.clock.advance_to(end);
+        self.run_until_stalled();
+    }
+
+    /// Runs tasks, advancing time as needed, until every task has completed
+    /// or the remaining tasks wait on something other than the clock;
+    /// returns the number of tasks left
+    pub fn run_until_idle(&mut self) -> usize {
+        self.run_until_stalled();
+        while self.pending_tasks() > 0 && self.clock.advance_to_next_deadline().is_some() {
+            self.run_until_stalled();
+        }
+        self.pending_tasks()
+    }
+
+    /// Runs `future` to completion, advancing time as needed; returns `None`
+    /// if it can never complete
+    pub fn block_on<F>(&mut self, future: F) -> Option<F::Output>
+    where
+        F: Future + Send + 'static,
+        F::Output: Send + 'static,
+    {
+        let task = self.spawn(future);
+        loop {
+            self.run_until_stalled();
+            if let Some(output) = task.take() {
+                return Some(output);
+            }
+            self.clock.advance_to_next_deadline()?;
+        }
+    }
+}
@@ -0,0 +1,90 @@
+//! Deterministic simulation harness
+//!
+//! Tests of timeouts, retries, session expiry and schedules run against a
+//! [`SimClock`] instead of real time: a [`SimExecutor`] drives the tasks
+//! under test and jumps the clock from one deadline to the next whenever
+//! they are all waiting, and a [`ScriptedPeer`] stands in for the remote MCP
+//! end. Code under test must sleep through the clock it is given; a
+//! `tokio::time::sleep` inside a simulated task never completes.
+
+/// Simulated clock
+pub mod clock;
+pub use clock::{Elapsed, SimClock, Sleep, Timeout};
+
+/// Controllable executor
+pub mod executor;
+pub use executor::{SimExecutor, SimSpawner, SimTask};
+
+/// Scripted MCP peer
+pub mod peer;
+pub use peer::{PeerReply, PeerStep, ScriptedPeer};
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+    use squirrel_mcp::protocol::MCPProtocol;
+    use squirrel_mcp::types::{MCPMessage, MessageId, MessageType};
+    use std::sync::Arc;
+    use std::time::Duration;
+
+    fn command(id: &str) -> MCPMessage {
+        MCPMessage {
+            id: MessageId(id.to_string()),
+            message_type: MessageType::Command,
+            payload: json!({"command": "ping"}),
+        }
+    }
+
+    #[test]
+    fn test_sleeps_complete_in_deadline_order() {
+        let mut executor = SimExecutor::default();
+        let clock = executor.clock().clone();
+        let order = Arc::new(std::sync::Mutex::new(Vec::new()));
+        for secs in [30, 10, 20] {
+            let (clock, order) = (clock.clone(), Arc::clone(&order));
+            executor.spawn(async move {
+                clock.sleep(Duration::from_secs(secs)).await;
+                order.lock().unwrap().push(secs);
+            });
+        }
+
+        executor.run_for(Duration::from_secs(15));
+        assert_eq!(*order.lock().unwrap(), vec![10]);
+        assert_eq!(executor.run_until_idle(), 0);
+        assert_eq!(*order.lock().unwrap(), vec![10, 20, 30]);
+        assert_eq!(clock.elapsed(), Duration::from_secs(30));
+    }
+
+    #[test]
+    fn test_retry_after_timeout_against_scripted_peer() {
+        let mut executor = SimExecutor::default();
+        let clock = executor.clock().clone();
+        let peer = Arc::new(
+            ScriptedPeer::new(clock.clone())
+                .then_hang()
+                .then_respond_after(Duration::from_secs(2), json!({"pong": true})),
+        );
+
+        let client_peer = Arc::clone(&peer);
+        let client_clock = clock.clone();
+        let result = executor.block_on(async move {
+            let mut attempts = 0;
+            loop {
+                attempts += 1;
+                let call = client_peer.handle_message(command(&attempts.to_string()));
+                match client_clock.timeout(Duration::from_secs(5), call).await {
+                    Ok(response) => return (attempts, response),
+                    Err(_) => continue,
+                }
+            }
+        });
+
+        let (attempts, response) = result.expect("client should finish");
+        assert_eq!(attempts, 2);
+        assert_eq!(response.unwrap().message_id, "2");
+        assert_eq!(clock.elapsed(), Duration::from_secs(7));
+        assert!(peer.is_finished());
+        assert_eq!(peer.received().len(), 2);
+    }
+}
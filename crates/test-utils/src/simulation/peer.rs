@@ -0,0 +1,197 @@
+//! Scripted MCP peer
+//!
+//! [`ScriptedPeer`] implements [`MCPProtocol`] by answering each message it
+//! receives with the next step of a script: a response, an error, a reply
+//! delayed on a [`SimClock`], or no reply at all. Tests of clients, retries
+//! and timeouts use it in place of a real remote end.
+
+use std::collections::VecDeque;
+use std::sync::{Mutex, MutexGuard};
+use std::time::Duration;
+
+use async_trait::async_trait;
+use serde_json::Value;
+use squirrel_mcp::error::{MCPError, ProtocolError, Result};
+use squirrel_mcp::protocol::{MCPProtocol, ProtocolResult, RoutingResult, ValidationResult};
+use squirrel_mcp::types::{
+    MCPMessage, MCPResponse, MessageMetadata, MessageType, ProtocolState, ResponseStatus,
+};
+
+use super::clock::SimClock;
+
+/// How the peer answers a message
+#[derive(Debug, Clone)]
+pub enum PeerReply {
+    /// A successful response with this JSON payload
+    Payload(Value),
+    /// An error response with this message
+    ErrorResponse(String),
+    /// A failure of the call itself
+    Fail(String),
+    /// No answer; the call never completes
+    Hang,
+}
+
+/// One step of a script
+#[derive(Debug, Clone)]
+pub struct PeerStep {
+    /// Type the message must have, if checked
+    pub expect: Option<MessageType>,
+    /// Simulated time before the reply
+    pub delay: Duration,
+    /// The reply
+    pub reply: PeerReply,
+}
+
+impl PeerStep {
+    /// Step answering any message with `reply` immediately
+    pub fn new(reply: PeerReply) -> Self {
+        Self {
+            expect: None,
+            delay: Duration::ZERO,
+            reply,
+        }
+    }
+
+    /// Requires the message to have type `message_type`
+    #[must_use]
+    pub fn expecting(mut self, message_type: MessageType) -> Self {
+        self.expect = Some(message_type);
+        self
+    }
+
+    /// Delays the reply by `delay` of simulated time
+    #[must_use]
+    pub fn after(mut self, delay: Duration) -> Self {
+        self.delay = delay;
+        self
+    }
+}
+
+/// MCP peer that answers messages from a script
+#[derive(Debug)]
+pub struct ScriptedPeer {
+    clock: SimClock,
+    script: Mutex<VecDeque<PeerStep>>,
+    received: Mutex<Vec<MCPMessage>>,
+    state: Mutex<ProtocolState>,
+}
+
+fn lock<T>(mutex: &Mutex<T>) -> MutexGuard<'_, T> {
+    mutex.lock().unwrap_or_else(std::sync::PoisonError::into_inner)
+}
+
+impl ScriptedPeer {
+    /// Creates a peer with an empty script whose delays run on `clock`
+    pub fn new(clock: SimClock) -> Self {
+        Self {
+            clock,
+            script: Mutex::new(VecDeque::new()),
+            received: Mutex::new(Vec::new()),
+            state: Mutex::new(ProtocolState::Ready),
+        }
+    }
+
+    /// Appends `step` to the script
+    #[must_use]
+    pub fn then(self, step: PeerStep) -> Self {
+        lock(&self.script).push_back(step);
+        self
+    }
+
+    /// Appends a successful response with `payload`
+    #[must_use]
+    pub fn then_respond(self, payload: Value) -> Self {
+        self.then(PeerStep::new(PeerReply::Payload(payload)))
+    }
+
+    /// Appends a successful response with `payload` after `delay`
+    #[must_use]
+    pub fn then_respond_after(self, delay: Duration, payload: Value) -> Self {
+        self.then(PeerStep::new(PeerReply::Payload(payload)).after(delay))
+    }
+
+    /// Appends a failure of the call
+    #[must_use]
+    pub fn then_fail(self, message: &str) -> Self {
+        self.then(PeerStep::new(PeerReply::Fail(message.to_string())))
+    }
+
+    /// Appends a message that is never answered
+    #[must_use]
+    pub fn then_hang(self) -> Self {
+        self.then(PeerStep::new(PeerReply::Hang))
+    }
+
+    /// Messages received so far
+    pub fn received(&self) -> Vec<MCPMessage> {
+        lock(&self.received).clone()
+    }
+
+    /// Number of steps not yet used
+    pub fn remaining_steps(&self) -> usize {
+        lock(&self.script).len()
+    }
+
+    /// Whether every step of the script has been used
+    pub fn is_finished(&self) -> bool {
+        self.remaining_steps() == 0
+    }
+}
+
+#[async_trait]
+impl MCPProtocol for ScriptedPeer {
+    async fn handle_message(&self, msg: MCPMessage) -> ProtocolResult {
+        lock(&self.received).push(msg.clone());
+        let step = lock(&self.script).pop_front().ok_or_else(|| {
+            MCPError::General(format!("Scripted peer has no step for message {}", msg.id.0))
+        })?;
+        if let Some(expected) = &step.expect {
+            if *expected != msg.message_type {
+                return Err(MCPError::Protocol(ProtocolError::InvalidFormat(format!(
+                    "Scripted peer expected a {expected} message, got {}",
+                    msg.message_type
+                ))));
+            }
+        }
+
+        if !step.delay.is_zero() {
+            self.clock.sleep(step.delay).await;
+        }
+        let (status, payload, error_message) = match step.reply {
+            PeerReply::Payload(payload) => (ResponseStatus::Success, serde_json::to_vec(&payload)?, None),
+            PeerReply::ErrorResponse(message) => (ResponseStatus::Error, Vec::new(), Some(message)),
+            PeerReply::Fail(message) => return Err(MCPError::General(message)),
+            PeerReply::Hang => std::future::pending().await,
+        };
+        Ok(MCPResponse {
+            protocol_version: self.get_version(),
+            message_id: msg.id.0,
+            status,
+            payload,
+            error_message,
+            metadata: MessageMetadata::default(),
+        })
+    }
+
+    async fn validate_message(&self, _msg: &MCPMessage) -> ValidationResult {
+        Ok(())
+    }
+
+    async fn route_message(&self, _msg: &MCPMessage) -> RoutingResult {
+        Ok(())
+    }
+
+    async fn set_state(&self, new_state: ProtocolState) -> Result<()> {
+        *lock(&self.state) = new_state;
+        Ok(())
+    }
+
+    async fn get_state(&self) -> Result<ProtocolState> {
+        Ok(*lock(&self.state))
+    }
+
+    fn get_version(&self) -> String {
+        "1.0".to_string()
+    }
+}
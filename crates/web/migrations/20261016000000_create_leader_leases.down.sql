@@ -0,0 +1,4 @@
+-- Add down migration script here
+
+-- Drop leader leases table
+DROP TABLE leader_leases;
@@ -0,0 +1,10 @@
+-- Add up migration script here
+
+-- Create leader leases table
+CREATE TABLE leader_leases (
+    name TEXT PRIMARY KEY NOT NULL,
+    holder TEXT NOT NULL,
+    term INTEGER NOT NULL,
+    acquired_at INTEGER NOT NULL,
+    expires_at INTEGER NOT NULL
+);
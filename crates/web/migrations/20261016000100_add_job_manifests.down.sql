@@ -0,0 +1,4 @@
+-- Add down migration script here
+
+-- Drop job manifests
+ALTER TABLE jobs DROP COLUMN manifest;
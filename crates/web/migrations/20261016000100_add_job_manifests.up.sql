@@ -0,0 +1,4 @@
+-- Add up migration script here
+
+-- Store the reproducibility manifest of the run with each job
+ALTER TABLE jobs ADD COLUMN manifest TEXT;
@@ -0,0 +1,16 @@
+-- Add down migration script here
+
+-- Drop auth session tables
+DROP TABLE revoked_refresh_tokens;
+DROP INDEX idx_auth_sessions_user_id;
+DROP TABLE auth_sessions;
+
+-- Restore refresh_tokens table
+CREATE TABLE refresh_tokens (
+    id TEXT PRIMARY KEY NOT NULL,
+    user_id TEXT NOT NULL,
+    token TEXT NOT NULL UNIQUE,
+    expires_at TEXT NOT NULL,
+    created_at TEXT NOT NULL,
+    FOREIGN KEY (user_id) REFERENCES users(id) ON DELETE CASCADE
+);
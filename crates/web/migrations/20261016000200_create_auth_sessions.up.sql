@@ -0,0 +1,27 @@
+-- Add up migration script here
+
+-- Refresh tokens are now one-time-use and belong to a session per device
+DROP TABLE refresh_tokens;
+
+-- Create auth sessions table; token_hash is the SHA-256 of the current refresh token
+CREATE TABLE auth_sessions (
+    id TEXT PRIMARY KEY NOT NULL,
+    user_id TEXT NOT NULL,
+    device TEXT NOT NULL,
+    token_hash TEXT NOT NULL UNIQUE,
+    created_at INTEGER NOT NULL,
+    last_used_at INTEGER NOT NULL,
+    expires_at INTEGER NOT NULL,
+    revoked_at INTEGER
+);
+
+CREATE INDEX idx_auth_sessions_user_id ON auth_sessions(user_id);
+
+-- Create revoked refresh tokens table, kept until the tokens would have expired
+CREATE TABLE revoked_refresh_tokens (
+    token_hash TEXT PRIMARY KEY NOT NULL,
+    session_id TEXT NOT NULL,
+    reason TEXT NOT NULL,
+    revoked_at INTEGER NOT NULL,
+    expires_at INTEGER NOT NULL
+);
@@ -0,0 +1,7 @@
+-- Add down migration script here
+
+-- Drop two-factor tables
+DROP TABLE two_factor_challenges;
+DROP INDEX idx_two_factor_recovery_codes_user_id;
+DROP TABLE two_factor_recovery_codes;
+DROP TABLE two_factor;
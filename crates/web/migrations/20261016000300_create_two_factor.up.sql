@@ -0,0 +1,28 @@
+-- Add up migration script here
+
+-- Create two-factor table; secret is the base32 TOTP secret, enabled once a code confirmed it
+CREATE TABLE two_factor (
+    user_id TEXT PRIMARY KEY NOT NULL,
+    secret TEXT NOT NULL,
+    last_used_step INTEGER NOT NULL DEFAULT 0,
+    created_at INTEGER NOT NULL,
+    enabled_at INTEGER
+);
+
+-- Create recovery codes table; only SHA-256 hashes of the codes are stored
+CREATE TABLE two_factor_recovery_codes (
+    code_hash TEXT PRIMARY KEY NOT NULL,
+    user_id TEXT NOT NULL,
+    used_at INTEGER
+);
+
+CREATE INDEX idx_two_factor_recovery_codes_user_id ON two_factor_recovery_codes(user_id);
+
+-- Create login challenges table for logins waiting on a second factor
+CREATE TABLE two_factor_challenges (
+    token_hash TEXT PRIMARY KEY NOT NULL,
+    user_id TEXT NOT NULL,
+    device TEXT NOT NULL,
+    attempts INTEGER NOT NULL DEFAULT 0,
+    expires_at INTEGER NOT NULL
+);
@@ -0,0 +1,7 @@
+-- Add down migration script here
+
+-- Drop tag tables
+DROP INDEX idx_tag_assignments_user_id;
+DROP INDEX idx_tag_assignments_resource;
+DROP TABLE tag_assignments;
+DROP TABLE tags;
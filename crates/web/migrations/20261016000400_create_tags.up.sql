@@ -0,0 +1,23 @@
+-- Add up migration script here
+
+-- Create tags table; tags without a value store an empty one
+CREATE TABLE tags (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    key TEXT NOT NULL,
+    value TEXT NOT NULL DEFAULT '',
+    UNIQUE (key, value)
+);
+
+-- Create tag assignments table; resource_kind is job, command, experiment or dataset
+CREATE TABLE tag_assignments (
+    tag_id INTEGER NOT NULL,
+    resource_kind TEXT NOT NULL,
+    resource_id TEXT NOT NULL,
+    user_id TEXT NOT NULL,
+    created_at INTEGER NOT NULL,
+    PRIMARY KEY (tag_id, resource_kind, resource_id, user_id),
+    FOREIGN KEY (tag_id) REFERENCES tags(id) ON DELETE CASCADE
+);
+
+CREATE INDEX idx_tag_assignments_resource ON tag_assignments(resource_kind, resource_id);
+CREATE INDEX idx_tag_assignments_user_id ON tag_assignments(user_id);
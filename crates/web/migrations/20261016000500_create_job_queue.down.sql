@@ -0,0 +1,5 @@
+-- Add down migration script here
+
+-- Drop job queue table
+DROP INDEX idx_job_queue_status;
+DROP TABLE job_queue;
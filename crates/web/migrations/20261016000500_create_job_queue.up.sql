@@ -0,0 +1,19 @@
+-- Add up migration script here
+
+-- Create job queue table; status is pending, running, completed or failed
+-- and lease_expires_at is in milliseconds since the epoch
+CREATE TABLE job_queue (
+    id TEXT PRIMARY KEY,
+    kind TEXT NOT NULL,
+    user_id TEXT NOT NULL,
+    payload TEXT NOT NULL,
+    status TEXT NOT NULL,
+    attempts INTEGER NOT NULL DEFAULT 0,
+    worker TEXT,
+    lease_expires_at INTEGER,
+    error TEXT,
+    created_at INTEGER NOT NULL,
+    updated_at INTEGER NOT NULL
+);
+
+CREATE INDEX idx_job_queue_status ON job_queue(status, created_at);
@@ -0,0 +1,5 @@
+-- Add down migration script here
+
+-- Drop idempotency keys table
+DROP INDEX idx_idempotency_keys_expires_at;
+DROP TABLE idempotency_keys;
@@ -0,0 +1,16 @@
+-- Add up migration script here
+
+-- Create idempotency keys table; response is NULL while the first submission
+-- runs, and timestamps are in milliseconds since the epoch
+CREATE TABLE idempotency_keys (
+    user_id TEXT NOT NULL,
+    scope TEXT NOT NULL,
+    key TEXT NOT NULL,
+    fingerprint TEXT NOT NULL,
+    response TEXT,
+    created_at INTEGER NOT NULL,
+    expires_at INTEGER NOT NULL,
+    PRIMARY KEY (user_id, scope, key)
+);
+
+CREATE INDEX idx_idempotency_keys_expires_at ON idempotency_keys(expires_at);
@@ -0,0 +1,8 @@
+-- Add down migration script here
+
+-- Drop admin tables
+DROP TABLE auth_signing_keys;
+DROP INDEX idx_admin_audit_created_at;
+DROP TABLE admin_audit;
+DROP TABLE disabled_plugins;
+DROP TABLE admin_settings;
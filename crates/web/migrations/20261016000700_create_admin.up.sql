@@ -0,0 +1,39 @@
+-- Add up migration script here
+
+-- Create admin settings table, holding JSON values such as the degraded mode
+CREATE TABLE admin_settings (
+    key TEXT PRIMARY KEY NOT NULL,
+    value TEXT NOT NULL,
+    updated_by TEXT NOT NULL,
+    updated_at INTEGER NOT NULL
+);
+
+-- Create disabled plugins table
+CREATE TABLE disabled_plugins (
+    name TEXT PRIMARY KEY NOT NULL,
+    reason TEXT,
+    disabled_by TEXT NOT NULL,
+    disabled_at INTEGER NOT NULL
+);
+
+-- Create admin audit log; timestamps are in milliseconds since the epoch
+CREATE TABLE admin_audit (
+    id TEXT PRIMARY KEY NOT NULL,
+    actor TEXT NOT NULL,
+    action TEXT NOT NULL,
+    target TEXT,
+    outcome TEXT NOT NULL,
+    detail TEXT,
+    created_at INTEGER NOT NULL
+);
+
+CREATE INDEX idx_admin_audit_created_at ON admin_audit(created_at);
+
+-- Create signing keys table; the key without retired_at signs new access
+-- tokens, retired keys only verify tokens issued before the rotation
+CREATE TABLE auth_signing_keys (
+    id TEXT PRIMARY KEY NOT NULL,
+    secret TEXT NOT NULL,
+    created_at INTEGER NOT NULL,
+    retired_at INTEGER
+);
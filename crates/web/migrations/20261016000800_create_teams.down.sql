@@ -0,0 +1,8 @@
+-- Add down migration script here
+
+-- Drop teams and invitations
+DROP TABLE user_invitations;
+DROP INDEX idx_team_members_user_id;
+DROP TABLE team_members;
+DROP TABLE teams;
+ALTER TABLE users DROP COLUMN deactivated_at;
@@ -0,0 +1,40 @@
+-- Add up migration script here
+
+-- Deactivated users can not sign in; their rows are kept for the records
+-- that reference them
+ALTER TABLE users ADD COLUMN deactivated_at TEXT;
+
+-- Create teams table; timestamps are in milliseconds since the epoch
+CREATE TABLE teams (
+    id TEXT PRIMARY KEY NOT NULL,
+    name TEXT NOT NULL UNIQUE,
+    description TEXT,
+    created_by TEXT NOT NULL,
+    created_at INTEGER NOT NULL,
+    updated_at INTEGER NOT NULL
+);
+
+-- Create team members table
+CREATE TABLE team_members (
+    team_id TEXT NOT NULL REFERENCES teams(id) ON DELETE CASCADE,
+    user_id TEXT NOT NULL,
+    role TEXT NOT NULL,
+    added_at INTEGER NOT NULL,
+    PRIMARY KEY (team_id, user_id)
+);
+
+CREATE INDEX idx_team_members_user_id ON team_members(user_id);
+
+-- Create invitations table; the invitation token is signed and names the
+-- row, which records whether it was accepted
+CREATE TABLE user_invitations (
+    id TEXT PRIMARY KEY NOT NULL,
+    email TEXT NOT NULL,
+    role TEXT NOT NULL,
+    team_id TEXT REFERENCES teams(id) ON DELETE SET NULL,
+    invited_by TEXT NOT NULL,
+    created_at INTEGER NOT NULL,
+    expires_at INTEGER NOT NULL,
+    accepted_by TEXT,
+    accepted_at INTEGER
+);
@@ -0,0 +1,5 @@
+-- Add down migration script here
+
+-- Drop command policies table
+DROP INDEX idx_command_policies_subject;
+DROP TABLE command_policies;
@@ -0,0 +1,16 @@
+-- Add up migration script here
+
+-- Create command policies table; subjects are written user:<id> or
+-- api_key:<id>, timestamps are in milliseconds since the epoch
+CREATE TABLE command_policies (
+    id TEXT PRIMARY KEY NOT NULL,
+    subject TEXT NOT NULL,
+    effect TEXT NOT NULL,
+    scope TEXT NOT NULL,
+    pattern TEXT NOT NULL,
+    description TEXT,
+    created_by TEXT NOT NULL,
+    created_at INTEGER NOT NULL
+);
+
+CREATE INDEX idx_command_policies_subject ON command_policies(subject);
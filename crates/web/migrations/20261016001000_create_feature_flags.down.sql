@@ -0,0 +1,4 @@
+-- Add down migration script here
+
+-- Drop feature flags table
+DROP TABLE feature_flags;
@@ -0,0 +1,10 @@
+-- Add up migration script here
+
+-- Create feature flags table; settings admins changed replace the
+-- configured ones, timestamps are in milliseconds since the epoch
+CREATE TABLE feature_flags (
+    name TEXT PRIMARY KEY NOT NULL,
+    setting TEXT NOT NULL,
+    updated_by TEXT NOT NULL,
+    updated_at INTEGER NOT NULL
+);
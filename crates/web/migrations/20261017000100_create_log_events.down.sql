@@ -0,0 +1,7 @@
+-- Add down migration script here
+
+-- Drop log events table
+DROP INDEX idx_log_events_received_at;
+DROP INDEX idx_log_events_run_id;
+DROP INDEX idx_log_events_timestamp;
+DROP TABLE log_events;
@@ -0,0 +1,20 @@
+-- Add up migration script here
+
+-- Create log events table; levels are ranked from 0 (trace) to 4 (error),
+-- timestamps are in milliseconds since the epoch
+CREATE TABLE log_events (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    timestamp INTEGER NOT NULL,
+    level INTEGER NOT NULL,
+    component TEXT NOT NULL,
+    source TEXT,
+    run_id TEXT,
+    message TEXT NOT NULL,
+    fields TEXT NOT NULL,
+    submitted_by TEXT NOT NULL,
+    received_at INTEGER NOT NULL
+);
+
+CREATE INDEX idx_log_events_timestamp ON log_events(timestamp);
+CREATE INDEX idx_log_events_run_id ON log_events(run_id);
+CREATE INDEX idx_log_events_received_at ON log_events(received_at);
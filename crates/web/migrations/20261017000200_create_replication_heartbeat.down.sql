@@ -0,0 +1,4 @@
+-- Add down migration script here
+
+-- Drop replication heartbeat table
+DROP TABLE replication_heartbeat;
@@ -0,0 +1,8 @@
+-- Add up migration script here
+
+-- Create the replication heartbeat; its single row holds the latest time,
+-- in milliseconds since the epoch, an instance wrote to the primary
+CREATE TABLE replication_heartbeat (
+    id INTEGER PRIMARY KEY CHECK (id = 1),
+    beat_at INTEGER NOT NULL
+);
@@ -0,0 +1,135 @@
+//! Country lookup for IP addresses.
+//!
+//! Country rules need a [`GeoIpLookup`]. [`CountryTable`] reads a CSV file of
+//! `network,country` lines, which the country CSV exports of common GeoIP
+//! databases can be converted to:
+//!
+//! ```text
+//! # network,country
+//! 81.2.69.0/24,GB
+//! 2001:218::/32,JP
+//! ```
+
+use std::fmt::Debug;
+use std::net::IpAddr;
+use std::path::Path;
+
+use anyhow::{Context, Result};
+use ipnet::IpNet;
+
+/// Resolves the country an IP address is located in
+pub trait GeoIpLookup: Debug + Send + Sync {
+    /// ISO 3166-1 alpha-2 code of the country `ip` is located in, if known
+    fn country(&self, ip: IpAddr) -> Option<&str>;
+}
+
+/// Network ranges and their countries, read from a CSV file
+#[derive(Debug, Clone, Default)]
+pub struct CountryTable {
+    /// IPv4 ranges as `(first, last, country)`, sorted by first address
+    v4: Vec<(u128, u128, String)>,
+    /// IPv6 ranges as `(first, last, country)`, sorted by first address
+    v6: Vec<(u128, u128, String)>,
+}
+
+impl CountryTable {
+    /// Reads a `network,country` CSV file
+    pub fn open(path: &Path) -> Result<Self> {
+        let contents = std::fs::read_to_string(path)
+            .with_context(|| format!("Failed to read GeoIP database {}", path.display()))?;
+        Self::parse(&contents).with_context(|| format!("Invalid GeoIP database {}", path.display()))
+    }
+
+    /// Parses `network,country` lines; blank lines, `#` comments and a
+    /// `network,...` header are skipped
+    pub fn parse(contents: &str) -> Result<Self> {
+        let mut table = Self::default();
+        for (number, line) in contents.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') || line.starts_with("network,") {
+                continue;
+            }
+            let (network, country) = line
+                .split_once(',')
+                .with_context(|| format!("Line {}: expected network,country", number + 1))?;
+            let network: IpNet = network
+                .trim()
+                .parse()
+                .with_context(|| format!("Line {}: invalid network {}", number + 1, network))?;
+            let country = country.trim().trim_matches('"').to_ascii_uppercase();
+            let range = (
+                to_u128(network.network()),
+                to_u128(network.broadcast()),
+                country,
+            );
+            match network {
+                IpNet::V4(_) => table.v4.push(range),
+                IpNet::V6(_) => table.v6.push(range),
+            }
+        }
+        table.v4.sort_by_key(|(first, ..)| *first);
+        table.v6.sort_by_key(|(first, ..)| *first);
+        Ok(table)
+    }
+
+    /// Number of networks in the table
+    pub fn len(&self) -> usize {
+        self.v4.len() + self.v6.len()
+    }
+
+    /// Whether the table has no networks
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+impl GeoIpLookup for CountryTable {
+    fn country(&self, ip: IpAddr) -> Option<&str> {
+        let ranges = match ip {
+            IpAddr::V4(_) => &self.v4,
+            IpAddr::V6(v6) => match v6.to_ipv4_mapped() {
+                Some(v4) => return self.country(IpAddr::V4(v4)),
+                None => &self.v6,
+            },
+        };
+        let address = to_u128(ip);
+        // The last range starting at or before the address is the only one
+        // that can contain it, as database networks do not overlap
+        let index = ranges.partition_point(|(first, ..)| *first <= address);
+        let (_, last, country) = ranges.get(index.checked_sub(1)?)?;
+        (address <= *last).then_some(country.as_str())
+    }
+}
+
+/// The address as a number, for range comparisons
+fn to_u128(ip: IpAddr) -> u128 {
+    match ip {
+        IpAddr::V4(v4) => u128::from(u32::from(v4)),
+        IpAddr::V6(v6) => u128::from(v6),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_country_table_lookup() {
+        let table = CountryTable::parse(
+            "network,country\n# sample\n81.2.69.0/24,gb\n10.0.0.0/8,\"ZZ\"\n2001:218::/32,JP\n",
+        )
+        .unwrap();
+        assert_eq!(table.len(), 3);
+
+        let country = |ip: &str| table.country(ip.parse().unwrap()).map(str::to_string);
+        assert_eq!(country("81.2.69.142").as_deref(), Some("GB"));
+        assert_eq!(country("::ffff:81.2.69.1").as_deref(), Some("GB"));
+        assert_eq!(country("10.255.255.255").as_deref(), Some("ZZ"));
+        assert_eq!(country("2001:218:1::1").as_deref(), Some("JP"));
+        assert_eq!(country("81.2.70.1"), None);
+        assert_eq!(country("1.1.1.1"), None);
+
+        assert!(CountryTable::parse("not-a-network,GB").is_err());
+        assert!(CountryTable::parse("10.0.0.0/8").is_err());
+    }
+}
@@ -0,0 +1,98 @@
+//! Middleware rejecting requests from addresses the access rules block.
+
+use std::net::SocketAddr;
+use std::sync::Arc;
+
+use axum::{
+    extract::{ConnectInfo, State},
+    http::Request,
+    middleware::Next,
+    response::Response,
+};
+use tracing::warn;
+
+use super::IpFilter;
+use crate::api::error::AppError;
+
+/// Reject requests whose client address the access rules block
+///
+/// The client address comes from the connection, so the server must be run
+/// with `into_make_service_with_connect_info::<SocketAddr>()`; without it
+/// every request is rejected once a rule is configured.
+pub async fn filter_ip<B>(
+    State(filter): State<Arc<IpFilter>>,
+    req: Request<B>,
+    next: Next<B>,
+) -> Result<Response, AppError> {
+    if !filter.is_enabled() {
+        return Ok(next.run(req).await);
+    }
+
+    let peer = req
+        .extensions()
+        .get::<ConnectInfo<SocketAddr>>()
+        .map(|ConnectInfo(addr)| addr.ip());
+    let client = filter.client_ip(peer, req.headers());
+    let decision = filter.check(client);
+    if decision.allowed {
+        return Ok(next.run(req).await);
+    }
+
+    warn!(
+        target: "audit",
+        client = %client.map_or_else(|| "unknown".to_string(), |ip| ip.to_string()),
+        peer = %peer.map_or_else(|| "unknown".to_string(), |ip| ip.to_string()),
+        rule = %decision.rule,
+        method = %req.method(),
+        path = %req.uri().path(),
+        "Blocked request by IP access rule"
+    );
+    Err(AppError::Forbidden(
+        "Access from this address is not allowed".to_string(),
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use axum::{
+        body::Body, http::StatusCode, middleware::from_fn_with_state, routing::get, Router,
+    };
+    use tower::ServiceExt;
+
+    use crate::config::AccessConfig;
+
+    #[tokio::test]
+    async fn test_blocked_requests_are_rejected_before_routing() {
+        let filter = Arc::new(IpFilter::with_geoip(
+            AccessConfig {
+                deny: vec!["192.0.2.0/24".parse().unwrap()],
+                ..AccessConfig::default()
+            },
+            None,
+        ));
+        let app = Router::new()
+            .route("/", get(|| async { "ok" }))
+            .layer(from_fn_with_state(filter.clone(), filter_ip));
+        let request = |peer: &str| {
+            let mut request = Request::builder().uri("/").body(Body::empty()).unwrap();
+            request
+                .extensions_mut()
+                .insert(ConnectInfo(SocketAddr::new(peer.parse().unwrap(), 4000)));
+            request
+        };
+
+        let response = app.clone().oneshot(request("192.0.2.10")).await.unwrap();
+        assert_eq!(response.status(), StatusCode::FORBIDDEN);
+        let response = app.clone().oneshot(request("198.51.100.1")).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        let response = app
+            .oneshot(Request::builder().uri("/").body(Body::empty()).unwrap())
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::FORBIDDEN);
+
+        assert_eq!(filter.stats()["deny:192.0.2.0/24"].blocked, 1);
+    }
+}
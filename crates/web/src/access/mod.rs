@@ -0,0 +1,360 @@
+//! IP-based access control.
+//!
+//! [`IpFilter`] checks the client address of every request against the
+//! allow and deny lists of an [`AccessConfig`] before routing or
+//! authentication run. Blocked requests are written to the audit log, and
+//! each decision is counted per rule.
+
+pub mod geoip;
+pub mod middleware;
+
+pub use geoip::{CountryTable, GeoIpLookup};
+pub use middleware::filter_ip;
+
+use std::collections::{BTreeMap, HashMap};
+use std::net::IpAddr;
+use std::sync::{Arc, Mutex};
+
+use axum::http::HeaderMap;
+use ipnet::IpNet;
+use serde::{Deserialize, Serialize};
+use squirrel_monitoring::metrics::{Metric, MetricCollector, MetricType};
+use tracing::warn;
+
+use crate::config::AccessConfig;
+
+/// Rule name of decisions no listed network or country matched
+pub const DEFAULT_RULE: &str = "default";
+/// Rule name of requests whose client address is unknown
+pub const UNKNOWN_ADDRESS_RULE: &str = "unknown_address";
+
+/// Outcome of checking a client address
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct AccessDecision {
+    /// Whether the request may proceed
+    pub allowed: bool,
+    /// Rule that decided, e.g. `deny:10.0.0.0/8` or `allow_country:DE`
+    pub rule: String,
+}
+
+impl AccessDecision {
+    fn allow(rule: impl Into<String>) -> Self {
+        Self {
+            allowed: true,
+            rule: rule.into(),
+        }
+    }
+
+    fn block(rule: impl Into<String>) -> Self {
+        Self {
+            allowed: false,
+            rule: rule.into(),
+        }
+    }
+}
+
+/// Number of requests a rule allowed and blocked
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RuleStats {
+    /// Requests the rule let through
+    pub allowed: u64,
+    /// Requests the rule rejected
+    pub blocked: u64,
+}
+
+/// The client address of a request from `peer`
+///
+/// When `peer` is one of `trusted_proxies`, the client is the last address
+/// in `X-Forwarded-For` that is not a trusted proxy itself.
+pub fn client_ip(
+    trusted_proxies: &[IpNet],
+    peer: Option<IpAddr>,
+    headers: &HeaderMap,
+) -> Option<IpAddr> {
+    let trusted = |ip: &IpAddr| trusted_proxies.iter().any(|net| net.contains(ip));
+    let peer = peer?;
+    if !trusted(&peer) {
+        return Some(peer);
+    }
+    let forwarded: Vec<IpAddr> = headers
+        .get_all("X-Forwarded-For")
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(|value| value.split(','))
+        .filter_map(|address| address.trim().parse().ok())
+        .collect();
+    Some(
+        forwarded
+            .iter()
+            .rev()
+            .find(|ip| !trusted(ip))
+            .or_else(|| forwarded.first())
+            .copied()
+            .unwrap_or(peer),
+    )
+}
+
+/// Checks client addresses against the configured access rules
+#[derive(Debug)]
+pub struct IpFilter {
+    config: AccessConfig,
+    geoip: Option<Arc<dyn GeoIpLookup>>,
+    metrics: Option<Arc<dyn MetricCollector>>,
+    stats: Mutex<HashMap<String, RuleStats>>,
+}
+
+impl IpFilter {
+    /// Create a filter, loading the configured GeoIP database
+    ///
+    /// Country rules are skipped with a warning if the database cannot be
+    /// loaded.
+    pub fn new(config: AccessConfig, metrics: Option<Arc<dyn MetricCollector>>) -> Self {
+        let geoip =
+            config
+                .geoip_database
+                .as_ref()
+                .and_then(|path| match CountryTable::open(path) {
+                    Ok(table) => Some(Arc::new(table) as Arc<dyn GeoIpLookup>),
+                    Err(e) => {
+                        warn!("Country access rules are disabled: {:#}", e);
+                        None
+                    }
+                });
+        let mut filter = Self::with_geoip(config, geoip);
+        filter.metrics = metrics;
+        filter
+    }
+
+    /// Create a filter resolving countries with `geoip`
+    pub fn with_geoip(config: AccessConfig, geoip: Option<Arc<dyn GeoIpLookup>>) -> Self {
+        let countries =
+            |codes: &[String]| codes.iter().map(|code| code.to_ascii_uppercase()).collect();
+        let config = AccessConfig {
+            allow_countries: countries(&config.allow_countries),
+            deny_countries: countries(&config.deny_countries),
+            ..config
+        };
+        Self {
+            config,
+            geoip,
+            metrics: None,
+            stats: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Whether any access rule is configured
+    pub fn is_enabled(&self) -> bool {
+        self.has_allowlist()
+            || !self.config.deny.is_empty()
+            || !self.config.deny_countries.is_empty()
+    }
+
+    fn has_allowlist(&self) -> bool {
+        !self.config.allow.is_empty() || !self.config.allow_countries.is_empty()
+    }
+
+    /// The client address of a request from `peer`, see [`client_ip`]
+    pub fn client_ip(&self, peer: Option<IpAddr>, headers: &HeaderMap) -> Option<IpAddr> {
+        client_ip(&self.config.trusted_proxies, peer, headers)
+    }
+
+    /// Decide whether a request from `client` may proceed
+    ///
+    /// Deny rules take precedence over allow rules. Without an allowlist,
+    /// addresses no rule matched are allowed.
+    pub fn decide(&self, client: Option<IpAddr>) -> AccessDecision {
+        if !self.is_enabled() {
+            return AccessDecision::allow(DEFAULT_RULE);
+        }
+        let Some(ip) = client else {
+            return AccessDecision::block(UNKNOWN_ADDRESS_RULE);
+        };
+        let ip = match ip {
+            IpAddr::V6(v6) => v6.to_ipv4_mapped().map_or(ip, IpAddr::V4),
+            IpAddr::V4(_) => ip,
+        };
+        let country = self.geoip.as_ref().and_then(|geoip| geoip.country(ip));
+
+        if let Some(net) = self.config.deny.iter().find(|net| net.contains(&ip)) {
+            return AccessDecision::block(format!("deny:{}", net));
+        }
+        if let Some(country) =
+            country.filter(|country| self.config.deny_countries.iter().any(|c| c == country))
+        {
+            return AccessDecision::block(format!("deny_country:{}", country));
+        }
+        if let Some(net) = self.config.allow.iter().find(|net| net.contains(&ip)) {
+            return AccessDecision::allow(format!("allow:{}", net));
+        }
+        if let Some(country) =
+            country.filter(|country| self.config.allow_countries.iter().any(|c| c == country))
+        {
+            return AccessDecision::allow(format!("allow_country:{}", country));
+        }
+        if self.has_allowlist() {
+            AccessDecision::block(DEFAULT_RULE)
+        } else {
+            AccessDecision::allow(DEFAULT_RULE)
+        }
+    }
+
+    /// Decide on a request from `client` and count the decision
+    pub fn check(&self, client: Option<IpAddr>) -> AccessDecision {
+        let decision = self.decide(client);
+        self.record(&decision);
+        decision
+    }
+
+    /// Requests allowed and blocked so far, by rule
+    pub fn stats(&self) -> BTreeMap<String, RuleStats> {
+        let stats = self
+            .stats
+            .lock()
+            .unwrap_or_else(std::sync::PoisonError::into_inner);
+        stats
+            .iter()
+            .map(|(rule, stats)| (rule.clone(), *stats))
+            .collect()
+    }
+
+    /// Count a decision against its rule and report it as a metric
+    fn record(&self, decision: &AccessDecision) {
+        {
+            let mut stats = self
+                .stats
+                .lock()
+                .unwrap_or_else(std::sync::PoisonError::into_inner);
+            let stats = stats.entry(decision.rule.clone()).or_default();
+            if decision.allowed {
+                stats.allowed += 1;
+            } else {
+                stats.blocked += 1;
+            }
+        }
+
+        if let Some(metrics) = &self.metrics {
+            let labels = HashMap::from([
+                ("rule".to_string(), decision.rule.clone()),
+                (
+                    "action".to_string(),
+                    if decision.allowed {
+                        "allowed"
+                    } else {
+                        "blocked"
+                    }
+                    .to_string(),
+                ),
+            ]);
+            let metric = Metric::new("ip_access_requests_total", 1.0, MetricType::Counter, labels);
+            let metrics = metrics.clone();
+            tokio::spawn(async move {
+                if let Err(e) = metrics.record_metric(metric).await {
+                    warn!("Failed to record access metric: {}", e);
+                }
+            });
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn filter(config: AccessConfig) -> IpFilter {
+        let table = CountryTable::parse("81.2.69.0/24,GB\n203.0.113.0/24,CN\n").unwrap();
+        IpFilter::with_geoip(config, Some(Arc::new(table)))
+    }
+
+    fn ip(address: &str) -> Option<IpAddr> {
+        Some(address.parse().unwrap())
+    }
+
+    #[test]
+    fn test_deny_rules_take_precedence_over_allowlist() {
+        let filter = filter(AccessConfig {
+            allow: vec!["10.0.0.0/8".parse().unwrap()],
+            deny: vec!["10.1.0.0/16".parse().unwrap()],
+            allow_countries: vec!["gb".to_string()],
+            deny_countries: vec!["CN".to_string()],
+            ..AccessConfig::default()
+        });
+
+        let decide = |address: &str| filter.check(ip(address));
+        assert_eq!(
+            decide("10.1.2.3"),
+            AccessDecision::block("deny:10.1.0.0/16")
+        );
+        assert_eq!(
+            decide("10.2.0.1"),
+            AccessDecision::allow("allow:10.0.0.0/8")
+        );
+        assert_eq!(
+            decide("::ffff:10.2.0.1"),
+            AccessDecision::allow("allow:10.0.0.0/8")
+        );
+        assert_eq!(
+            decide("81.2.69.10"),
+            AccessDecision::allow("allow_country:GB")
+        );
+        assert_eq!(
+            decide("203.0.113.7"),
+            AccessDecision::block("deny_country:CN")
+        );
+        assert_eq!(decide("192.0.2.1"), AccessDecision::block(DEFAULT_RULE));
+        assert_eq!(
+            filter.check(None),
+            AccessDecision::block(UNKNOWN_ADDRESS_RULE)
+        );
+
+        let stats = filter.stats();
+        assert_eq!(
+            stats["allow:10.0.0.0/8"],
+            RuleStats {
+                allowed: 2,
+                blocked: 0
+            }
+        );
+        assert_eq!(
+            stats[DEFAULT_RULE],
+            RuleStats {
+                allowed: 0,
+                blocked: 1
+            }
+        );
+    }
+
+    #[test]
+    fn test_denylist_only_allows_other_addresses() {
+        let filter = filter(AccessConfig {
+            deny: vec!["192.0.2.0/24".parse().unwrap()],
+            ..AccessConfig::default()
+        });
+        assert!(!filter.decide(ip("192.0.2.9")).allowed);
+        assert!(filter.decide(ip("198.51.100.1")).allowed);
+        assert!(!IpFilter::with_geoip(AccessConfig::default(), None).is_enabled());
+    }
+
+    #[test]
+    fn test_client_ip_from_trusted_proxies() {
+        let filter = filter(AccessConfig {
+            trusted_proxies: vec!["10.0.0.0/8".parse().unwrap()],
+            ..AccessConfig::default()
+        });
+        let mut headers = HeaderMap::new();
+        headers.insert(
+            "X-Forwarded-For",
+            "198.51.100.1, 203.0.113.5, 10.0.0.2".parse().unwrap(),
+        );
+
+        assert_eq!(
+            filter.client_ip(ip("10.0.0.1"), &headers),
+            ip("203.0.113.5")
+        );
+        assert_eq!(filter.client_ip(ip("192.0.2.1"), &headers), ip("192.0.2.1"));
+        assert_eq!(
+            filter.client_ip(ip("10.0.0.1"), &HeaderMap::new()),
+            ip("10.0.0.1")
+        );
+        assert_eq!(filter.client_ip(None, &headers), None);
+    }
+}
@@ -0,0 +1,44 @@
+//! Middleware enforcing degraded mode.
+
+use std::sync::Arc;
+
+use axum::{
+    extract::State,
+    http::{Method, Request, StatusCode},
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+
+use super::AdminControls;
+use crate::api::error::AppError;
+
+/// Paths still accepting changes in degraded mode, so admins can turn it
+/// off and users can sign in
+const EXEMPT_PREFIXES: &[&str] = &["/api/admin", "/api/auth"];
+
+/// Refuse requests that change state while the server is in degraded mode
+///
+/// Reads are served as usual; other requests get `503 Service Unavailable`
+/// with the reason the admin gave.
+pub async fn refuse_when_degraded<B>(
+    State(controls): State<Arc<AdminControls>>,
+    req: Request<B>,
+    next: Next<B>,
+) -> Response {
+    let read = matches!(*req.method(), Method::GET | Method::HEAD | Method::OPTIONS);
+    let exempt = EXEMPT_PREFIXES.iter().any(|prefix| req.uri().path().starts_with(prefix));
+    if read || exempt {
+        return next.run(req).await;
+    }
+    match controls.degraded() {
+        Some(degraded) => AppError::Custom(
+            StatusCode::SERVICE_UNAVAILABLE,
+            match degraded.reason {
+                Some(reason) => format!("Server is in degraded mode and not accepting changes: {}", reason),
+                None => "Server is in degraded mode and not accepting changes".to_string(),
+            },
+        )
+        .into_response(),
+        None => next.run(req).await,
+    }
+}
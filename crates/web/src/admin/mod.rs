@@ -0,0 +1,280 @@
+//! Runtime administration.
+//!
+//! Admins can put the server in degraded mode, where it refuses changes
+//! but keeps serving reads, turn off plugins whose commands then can not be
+//! submitted, and attach command policies to users. The flags
+//! live in the web database so that the `squirrel
+//! admin` CLI and every instance share them; each instance reads them again
+//! every [`AdminConfig::refresh_interval_secs`]. Every admin action, allowed
+//! or not, is written to the audit log.
+
+pub mod middleware;
+pub mod store;
+
+pub use middleware::refuse_when_degraded;
+pub use store::{
+    AdminFlags, AdminPolicy, AdminStore, AuditEntry, AuditOutcome, DegradedMode, DisabledPlugin, MemoryAdminStore,
+    SqlAdminStore,
+};
+
+use std::future::Future;
+use std::sync::{Arc, PoisonError, RwLock};
+use std::time::Duration;
+
+use chrono::Utc;
+use serde::{Deserialize, Serialize};
+use squirrel_commands::policy::{self, CommandPolicy, PolicySubject, PolicyTarget};
+use squirrel_commands::CommandError;
+use squirrel_app::supervisor::{RestartPolicy, Supervisor};
+use sqlx::SqlitePool;
+use tracing::{info, warn};
+use uuid::Uuid;
+
+use crate::api::error::AppError;
+use crate::auth::AuthService;
+use crate::config::AdminConfig;
+use crate::idempotency::{IdempotencyStore, SqlIdempotencyStore};
+
+/// Admin flags cached for the request path, and the audit log
+pub struct AdminControls {
+    store: Arc<dyn AdminStore>,
+    config: AdminConfig,
+    flags: RwLock<AdminFlags>,
+}
+
+impl AdminControls {
+    /// Create new AdminControls; flags are empty until the first refresh
+    pub fn new(store: Arc<dyn AdminStore>, config: AdminConfig) -> Self {
+        Self {
+            store,
+            config,
+            flags: RwLock::new(AdminFlags::default()),
+        }
+    }
+
+    /// Read the flags again from the store
+    pub async fn refresh(&self) -> Result<AdminFlags, AppError> {
+        let flags = self.store.flags().await?;
+        *self.flags.write().unwrap_or_else(PoisonError::into_inner) = flags.clone();
+        Ok(flags)
+    }
+
+    /// The flags as last read
+    pub fn flags(&self) -> AdminFlags {
+        self.flags.read().unwrap_or_else(PoisonError::into_inner).clone()
+    }
+
+    /// The degraded mode, if it is on
+    pub fn degraded(&self) -> Option<DegradedMode> {
+        self.flags.read().unwrap_or_else(PoisonError::into_inner).degraded.clone()
+    }
+
+    /// The plugin named `name`, if it is turned off
+    pub fn disabled_plugin(&self, name: &str) -> Option<DisabledPlugin> {
+        self.flags
+            .read()
+            .unwrap_or_else(PoisonError::into_inner)
+            .disabled_plugins
+            .iter()
+            .find(|plugin| plugin.name == name)
+            .cloned()
+    }
+
+    /// Turn degraded mode on or off
+    pub async fn set_degraded(&self, actor: &str, enabled: bool, reason: Option<String>) -> Result<AdminFlags, AppError> {
+        let degraded = enabled.then(|| DegradedMode {
+            reason,
+            enabled_by: actor.to_string(),
+            enabled_at: Utc::now(),
+        });
+        self.store.set_degraded(degraded.as_ref()).await?;
+        self.refresh().await
+    }
+
+    /// Turn a plugin off
+    pub async fn disable_plugin(&self, actor: &str, name: &str, reason: Option<String>) -> Result<DisabledPlugin, AppError> {
+        let plugin = DisabledPlugin {
+            name: name.to_string(),
+            reason,
+            disabled_by: actor.to_string(),
+            disabled_at: Utc::now(),
+        };
+        self.store.disable_plugin(&plugin).await?;
+        self.refresh().await?;
+        Ok(plugin)
+    }
+
+    /// Turn a plugin back on; returns false if it was not off
+    pub async fn enable_plugin(&self, name: &str) -> Result<bool, AppError> {
+        let enabled = self.store.enable_plugin(name).await?;
+        self.refresh().await?;
+        Ok(enabled)
+    }
+
+    /// Attach a command policy, giving it a new ID. Policies can only be
+    /// attached to users for now: the server does not authenticate API keys,
+    /// so an `api_key:` policy would never apply.
+    pub async fn add_policy(&self, actor: &str, mut policy: CommandPolicy) -> Result<AdminPolicy, AppError> {
+        if let PolicySubject::ApiKey(_) = policy.subject {
+            return Err(AppError::InvalidRequest(
+                "Policies can not be attached to API keys until the server authenticates them".to_string(),
+            ));
+        }
+        policy.id = Uuid::new_v4().to_string();
+        let policy = AdminPolicy {
+            policy,
+            created_by: actor.to_string(),
+            created_at: Utc::now(),
+        };
+        self.store.add_policy(&policy).await?;
+        self.refresh().await?;
+        Ok(policy)
+    }
+
+    /// Remove a command policy; returns false if there was none with the ID
+    pub async fn remove_policy(&self, id: &str) -> Result<bool, AppError> {
+        let removed = self.store.remove_policy(id).await?;
+        self.refresh().await?;
+        Ok(removed)
+    }
+
+    /// Refuse `target` if a policy attached to one of `subjects` denies it
+    pub fn check_policies(&self, subjects: &[PolicySubject], target: &PolicyTarget) -> Result<(), AppError> {
+        let flags = self.flags.read().unwrap_or_else(PoisonError::into_inner);
+        // Denials answer like the other refusals of a command, as plain
+        // Forbidden responses
+        policy::evaluate(flags.policies.iter().map(|policy| &policy.policy), subjects, target).map_err(|e| match e {
+            CommandError::AuthorizationError(message) => AppError::Forbidden(message),
+            other => AppError::from(other),
+        })
+    }
+
+    /// Write an admin action to the audit log
+    ///
+    /// The action is also logged with the `audit` tracing target, so it is
+    /// kept even if the database write fails.
+    pub async fn audit(&self, entry: AuditEntry) {
+        info!(
+            target: "audit",
+            actor = %entry.actor,
+            action = %entry.action,
+            target_id = entry.target.as_deref().unwrap_or_default(),
+            outcome = entry.outcome.as_str(),
+            detail = entry.detail.as_deref().unwrap_or_default(),
+            "Admin action"
+        );
+        if let Err(e) = self.store.record(&entry).await {
+            warn!("Failed to write admin action {} to the audit log: {}", entry.action, e);
+        }
+    }
+
+    /// Run an admin action for `actor`, writing it to the audit log whether
+    /// it was refused, succeeded or failed
+    ///
+    /// Actors without the admin role are refused with `403 Forbidden` and
+    /// `run` is never polled.
+    pub async fn audited<T, Fut>(
+        &self,
+        actor: &str,
+        is_admin: bool,
+        action: &str,
+        target: Option<&str>,
+        run: Fut,
+    ) -> Result<T, AppError>
+    where
+        Fut: Future<Output = Result<T, AppError>>,
+    {
+        if !is_admin {
+            self.audit(AuditEntry::new(actor, action, target, AuditOutcome::Denied, None)).await;
+            return Err(AppError::Forbidden("Only admins can use the admin API".to_string()));
+        }
+
+        let result = run.await;
+        let (outcome, detail) = match &result {
+            Ok(_) => (AuditOutcome::Succeeded, None),
+            Err(e) => (AuditOutcome::Failed, Some(e.to_string())),
+        };
+        self.audit(AuditEntry::new(actor, action, target, outcome, detail)).await;
+        result
+    }
+
+    /// The latest `limit` audit log entries, newest first
+    pub async fn audit_log(&self, limit: usize) -> Result<Vec<AuditEntry>, AppError> {
+        self.store.audit_log(limit).await
+    }
+
+    /// Minimum age of the unreferenced blobs garbage collection deletes
+    pub fn gc_min_age(&self) -> Duration {
+        Duration::from_secs(self.config.gc_min_age_secs)
+    }
+
+    /// Supervise the background task reading flags and signing keys changed
+    /// by other instances or the CLI
+    pub fn supervise_refresher(self: &Arc<Self>, supervisor: &Supervisor, auth: AuthService) {
+        let controls = self.clone();
+        supervisor.spawn("admin.refresh", RestartPolicy::default(), move |shutdown| {
+            let controls = controls.clone();
+            let auth = auth.clone();
+            async move {
+                let period = Duration::from_secs(controls.config.refresh_interval_secs.max(1));
+                while !shutdown.sleep(period).await {
+                    if let Err(e) = controls.refresh().await {
+                        warn!("Failed to refresh admin flags: {}", e);
+                    }
+                    if let Err(e) = auth.reload_signing_keys().await {
+                        warn!("Failed to reload signing keys: {}", e);
+                    }
+                }
+                Ok(())
+            }
+        });
+    }
+}
+
+/// What compacting the web database removed
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DatabaseGcReport {
+    /// Expired sessions and revoked refresh tokens, and signing keys no
+    /// longer verifying any token
+    pub auth_rows: u64,
+    /// Expired idempotency keys
+    pub idempotency_keys: u64,
+}
+
+/// Delete expired rows from the web database and reclaim their space
+pub async fn compact_database(pool: &SqlitePool, auth: &AuthService) -> Result<DatabaseGcReport, AppError> {
+    let auth_rows = auth.prune().await.map_err(|e| AppError::Internal(e.to_string()))?;
+    let idempotency_keys = SqlIdempotencyStore::new(pool.clone()).purge_expired(Utc::now()).await?;
+    sqlx::query("VACUUM").execute(pool).await?;
+    Ok(DatabaseGcReport { auth_rows, idempotency_keys })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_flags_are_cached_until_refreshed() {
+        let store = Arc::new(MemoryAdminStore::new());
+        let controls = AdminControls::new(store.clone(), AdminConfig::default());
+
+        let flags = controls.set_degraded("root", true, Some("failover".to_string())).await.unwrap();
+        assert_eq!(flags.degraded.unwrap().reason.as_deref(), Some("failover"));
+        controls.disable_plugin("root", "galaxy", None).await.unwrap();
+        assert_eq!(controls.disabled_plugin("galaxy").unwrap().disabled_by, "root");
+
+        // Another instance or the CLI changed the flags
+        store.set_degraded(None).await.unwrap();
+        store.enable_plugin("galaxy").await.unwrap();
+        assert!(controls.degraded().is_some());
+        controls.refresh().await.unwrap();
+        assert!(controls.degraded().is_none());
+        assert!(controls.disabled_plugin("galaxy").is_none());
+        assert!(!controls.enable_plugin("galaxy").await.unwrap());
+
+        controls
+            .audit(AuditEntry::new("root", "degraded.disable", None, AuditOutcome::Succeeded, None))
+            .await;
+        assert_eq!(controls.audit_log(10).await.unwrap()[0].action, "degraded.disable");
+    }
+}
@@ -0,0 +1,474 @@
+//! Admin state and audit log storage backends.
+
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use squirrel_commands::policy::CommandPolicy;
+use sqlx::sqlite::SqliteRow;
+use sqlx::{Row, SqlitePool};
+use tokio::sync::Mutex;
+use uuid::Uuid;
+
+use crate::api::error::AppError;
+use crate::db::{timestamp, InstrumentedPool};
+
+/// Setting key of the degraded mode
+const DEGRADED_MODE_KEY: &str = "degraded_mode";
+
+/// Why and since when the server refuses changes
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DegradedMode {
+    /// Reason shown to refused clients
+    pub reason: Option<String>,
+    /// Admin who turned it on
+    pub enabled_by: String,
+    /// When it was turned on
+    pub enabled_at: DateTime<Utc>,
+}
+
+/// A plugin an admin turned off
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DisabledPlugin {
+    /// Plugin name
+    pub name: String,
+    /// Why it was turned off
+    pub reason: Option<String>,
+    /// Admin who turned it off
+    pub disabled_by: String,
+    /// When it was turned off
+    pub disabled_at: DateTime<Utc>,
+}
+
+/// A command policy an admin attached to a user or API key
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct AdminPolicy {
+    /// The policy
+    #[serde(flatten)]
+    pub policy: CommandPolicy,
+    /// Admin who added it
+    pub created_by: String,
+    /// When it was added
+    pub created_at: DateTime<Utc>,
+}
+
+/// Switches admins flip at runtime
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct AdminFlags {
+    /// Set while the server is in degraded mode
+    pub degraded: Option<DegradedMode>,
+    /// Plugins turned off, by name
+    pub disabled_plugins: Vec<DisabledPlugin>,
+    /// Command policies, oldest first
+    #[serde(default)]
+    pub policies: Vec<AdminPolicy>,
+}
+
+/// How an audited admin action ended
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum AuditOutcome {
+    /// The action was carried out
+    Succeeded,
+    /// The actor is not an admin
+    Denied,
+    /// The action was allowed but failed
+    Failed,
+}
+
+impl AuditOutcome {
+    /// The outcome as stored, e.g. `succeeded`
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Succeeded => "succeeded",
+            Self::Denied => "denied",
+            Self::Failed => "failed",
+        }
+    }
+
+    fn parse(outcome: &str) -> Result<Self, AppError> {
+        match outcome {
+            "succeeded" => Ok(Self::Succeeded),
+            "denied" => Ok(Self::Denied),
+            "failed" => Ok(Self::Failed),
+            other => Err(AppError::Internal(format!("Invalid audit outcome '{}'", other))),
+        }
+    }
+}
+
+/// One admin action, as written to the audit log
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct AuditEntry {
+    /// Entry ID
+    pub id: String,
+    /// User or CLI operator who attempted the action
+    pub actor: String,
+    /// Action, e.g. `sessions.kick`
+    pub action: String,
+    /// What the action was applied to, e.g. a session ID
+    pub target: Option<String>,
+    /// How the action ended
+    pub outcome: AuditOutcome,
+    /// Error or extra context
+    pub detail: Option<String>,
+    /// When the action was attempted
+    pub created_at: DateTime<Utc>,
+}
+
+impl AuditEntry {
+    /// A new entry with a random ID, stamped now
+    pub fn new(actor: &str, action: &str, target: Option<&str>, outcome: AuditOutcome, detail: Option<String>) -> Self {
+        Self {
+            id: Uuid::new_v4().to_string(),
+            actor: actor.to_string(),
+            action: action.to_string(),
+            target: target.map(str::to_string),
+            outcome,
+            detail,
+            created_at: Utc::now(),
+        }
+    }
+}
+
+/// Storage for admin flags and the audit log
+#[async_trait]
+pub trait AdminStore: Send + Sync {
+    /// Read the current flags
+    async fn flags(&self) -> Result<AdminFlags, AppError>;
+
+    /// Turn degraded mode on, or off with `None`
+    async fn set_degraded(&self, degraded: Option<&DegradedMode>) -> Result<(), AppError>;
+
+    /// Turn a plugin off, replacing an earlier reason
+    async fn disable_plugin(&self, plugin: &DisabledPlugin) -> Result<(), AppError>;
+
+    /// Turn a plugin back on; returns false if it was not off
+    async fn enable_plugin(&self, name: &str) -> Result<bool, AppError>;
+
+    /// Add a command policy
+    async fn add_policy(&self, policy: &AdminPolicy) -> Result<(), AppError>;
+
+    /// Remove a command policy; returns false if there was none with the ID
+    async fn remove_policy(&self, id: &str) -> Result<bool, AppError>;
+
+    /// Append an entry to the audit log
+    async fn record(&self, entry: &AuditEntry) -> Result<(), AppError>;
+
+    /// The latest `limit` audit log entries, newest first
+    async fn audit_log(&self, limit: usize) -> Result<Vec<AuditEntry>, AppError>;
+}
+
+/// Reads an entry from an `admin_audit` row
+fn audit_from_row(row: &SqliteRow) -> Result<AuditEntry, AppError> {
+    Ok(AuditEntry {
+        id: row.try_get("id")?,
+        actor: row.try_get("actor")?,
+        action: row.try_get("action")?,
+        target: row.try_get("target")?,
+        outcome: AuditOutcome::parse(row.try_get("outcome")?)?,
+        detail: row.try_get("detail")?,
+        created_at: timestamp(row.try_get("created_at")?)?,
+    })
+}
+
+/// Reads a policy from a `command_policies` row
+fn policy_from_row(row: &SqliteRow) -> Result<AdminPolicy, AppError> {
+    let field = |name: &str| -> Result<String, AppError> { Ok(row.try_get(name)?) };
+    let invalid = |e: String| AppError::Internal(format!("Invalid command policy: {}", e));
+    Ok(AdminPolicy {
+        policy: CommandPolicy {
+            id: field("id")?,
+            subject: field("subject")?.parse().map_err(invalid)?,
+            effect: field("effect")?.parse().map_err(invalid)?,
+            scope: field("scope")?.parse().map_err(invalid)?,
+            pattern: field("pattern")?,
+            description: row.try_get("description")?,
+        },
+        created_by: field("created_by")?,
+        created_at: timestamp(row.try_get("created_at")?)?,
+    })
+}
+
+/// Admin store backed by the `admin_settings`, `disabled_plugins`,
+/// `command_policies` and `admin_audit` tables
+pub struct SqlAdminStore {
+    pool: InstrumentedPool,
+}
+
+impl SqlAdminStore {
+    /// Create a new SqlAdminStore
+    pub fn new(pool: SqlitePool) -> Self {
+        Self { pool: pool.into() }
+    }
+}
+
+#[async_trait]
+impl AdminStore for SqlAdminStore {
+    async fn flags(&self) -> Result<AdminFlags, AppError> {
+        let degraded = sqlx::query("SELECT value FROM admin_settings WHERE key = ?")
+            .bind(DEGRADED_MODE_KEY)
+            .fetch_optional(&self.pool)
+            .await?
+            .map(|row| -> Result<DegradedMode, AppError> {
+                let value: String = row.try_get("value")?;
+                serde_json::from_str(&value)
+                    .map_err(|e| AppError::Internal(format!("Invalid degraded mode setting: {}", e)))
+            })
+            .transpose()?;
+
+        let rows = sqlx::query("SELECT name, reason, disabled_by, disabled_at FROM disabled_plugins ORDER BY name")
+            .fetch_all(&self.pool)
+            .await?;
+        let disabled_plugins = rows
+            .iter()
+            .map(|row| -> Result<DisabledPlugin, AppError> {
+                Ok(DisabledPlugin {
+                    name: row.try_get("name")?,
+                    reason: row.try_get("reason")?,
+                    disabled_by: row.try_get("disabled_by")?,
+                    disabled_at: timestamp(row.try_get("disabled_at")?)?,
+                })
+            })
+            .collect::<Result<_, _>>()?;
+
+        let policies = sqlx::query("SELECT * FROM command_policies ORDER BY created_at, rowid")
+            .fetch_all(&self.pool)
+            .await?
+            .iter()
+            .map(policy_from_row)
+            .collect::<Result<_, _>>()?;
+
+        Ok(AdminFlags { degraded, disabled_plugins, policies })
+    }
+
+    async fn set_degraded(&self, degraded: Option<&DegradedMode>) -> Result<(), AppError> {
+        let Some(degraded) = degraded else {
+            sqlx::query("DELETE FROM admin_settings WHERE key = ?")
+                .bind(DEGRADED_MODE_KEY)
+                .execute(&self.pool)
+                .await?;
+            return Ok(());
+        };
+        let value = serde_json::to_string(degraded)
+            .map_err(|e| AppError::Internal(format!("Failed to store degraded mode: {}", e)))?;
+        sqlx::query(
+            "INSERT INTO admin_settings (key, value, updated_by, updated_at) VALUES (?, ?, ?, ?)
+             ON CONFLICT(key) DO UPDATE SET value = excluded.value, updated_by = excluded.updated_by,
+             updated_at = excluded.updated_at",
+        )
+        .bind(DEGRADED_MODE_KEY)
+        .bind(value)
+        .bind(&degraded.enabled_by)
+        .bind(degraded.enabled_at.timestamp_millis())
+        .execute(&self.pool)
+        .await?;
+        Ok(())
+    }
+
+    async fn disable_plugin(&self, plugin: &DisabledPlugin) -> Result<(), AppError> {
+        sqlx::query(
+            "INSERT INTO disabled_plugins (name, reason, disabled_by, disabled_at) VALUES (?, ?, ?, ?)
+             ON CONFLICT(name) DO UPDATE SET reason = excluded.reason, disabled_by = excluded.disabled_by,
+             disabled_at = excluded.disabled_at",
+        )
+        .bind(&plugin.name)
+        .bind(&plugin.reason)
+        .bind(&plugin.disabled_by)
+        .bind(plugin.disabled_at.timestamp_millis())
+        .execute(&self.pool)
+        .await?;
+        Ok(())
+    }
+
+    async fn enable_plugin(&self, name: &str) -> Result<bool, AppError> {
+        let deleted = sqlx::query("DELETE FROM disabled_plugins WHERE name = ?")
+            .bind(name)
+            .execute(&self.pool)
+            .await?
+            .rows_affected();
+        Ok(deleted > 0)
+    }
+
+    async fn add_policy(&self, policy: &AdminPolicy) -> Result<(), AppError> {
+        sqlx::query(
+            "INSERT INTO command_policies (id, subject, effect, scope, pattern, description, created_by, created_at)
+             VALUES (?, ?, ?, ?, ?, ?, ?, ?)",
+        )
+        .bind(&policy.policy.id)
+        .bind(policy.policy.subject.to_string())
+        .bind(policy.policy.effect.as_str())
+        .bind(policy.policy.scope.as_str())
+        .bind(&policy.policy.pattern)
+        .bind(&policy.policy.description)
+        .bind(&policy.created_by)
+        .bind(policy.created_at.timestamp_millis())
+        .execute(&self.pool)
+        .await?;
+        Ok(())
+    }
+
+    async fn remove_policy(&self, id: &str) -> Result<bool, AppError> {
+        let deleted = sqlx::query("DELETE FROM command_policies WHERE id = ?")
+            .bind(id)
+            .execute(&self.pool)
+            .await?
+            .rows_affected();
+        Ok(deleted > 0)
+    }
+
+    async fn record(&self, entry: &AuditEntry) -> Result<(), AppError> {
+        sqlx::query(
+            "INSERT INTO admin_audit (id, actor, action, target, outcome, detail, created_at)
+             VALUES (?, ?, ?, ?, ?, ?, ?)",
+        )
+        .bind(&entry.id)
+        .bind(&entry.actor)
+        .bind(&entry.action)
+        .bind(&entry.target)
+        .bind(entry.outcome.as_str())
+        .bind(&entry.detail)
+        .bind(entry.created_at.timestamp_millis())
+        .execute(&self.pool)
+        .await?;
+        Ok(())
+    }
+
+    async fn audit_log(&self, limit: usize) -> Result<Vec<AuditEntry>, AppError> {
+        sqlx::query("SELECT * FROM admin_audit ORDER BY created_at DESC, rowid DESC LIMIT ?")
+            .bind(i64::try_from(limit).unwrap_or(i64::MAX))
+            .fetch_all(&self.pool)
+            .await?
+            .iter()
+            .map(audit_from_row)
+            .collect()
+    }
+}
+
+/// In-process admin store for tests; nothing survives a restart
+#[derive(Default)]
+pub struct MemoryAdminStore {
+    flags: Mutex<AdminFlags>,
+    audit: Mutex<Vec<AuditEntry>>,
+}
+
+impl MemoryAdminStore {
+    /// Create a new MemoryAdminStore
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+#[async_trait]
+impl AdminStore for MemoryAdminStore {
+    async fn flags(&self) -> Result<AdminFlags, AppError> {
+        Ok(self.flags.lock().await.clone())
+    }
+
+    async fn set_degraded(&self, degraded: Option<&DegradedMode>) -> Result<(), AppError> {
+        self.flags.lock().await.degraded = degraded.cloned();
+        Ok(())
+    }
+
+    async fn disable_plugin(&self, plugin: &DisabledPlugin) -> Result<(), AppError> {
+        let mut flags = self.flags.lock().await;
+        flags.disabled_plugins.retain(|disabled| disabled.name != plugin.name);
+        flags.disabled_plugins.push(plugin.clone());
+        flags.disabled_plugins.sort_by(|a, b| a.name.cmp(&b.name));
+        Ok(())
+    }
+
+    async fn enable_plugin(&self, name: &str) -> Result<bool, AppError> {
+        let mut flags = self.flags.lock().await;
+        let before = flags.disabled_plugins.len();
+        flags.disabled_plugins.retain(|disabled| disabled.name != name);
+        Ok(flags.disabled_plugins.len() < before)
+    }
+
+    async fn add_policy(&self, policy: &AdminPolicy) -> Result<(), AppError> {
+        self.flags.lock().await.policies.push(policy.clone());
+        Ok(())
+    }
+
+    async fn remove_policy(&self, id: &str) -> Result<bool, AppError> {
+        let mut flags = self.flags.lock().await;
+        let before = flags.policies.len();
+        flags.policies.retain(|policy| policy.policy.id != id);
+        Ok(flags.policies.len() < before)
+    }
+
+    async fn record(&self, entry: &AuditEntry) -> Result<(), AppError> {
+        self.audit.lock().await.push(entry.clone());
+        Ok(())
+    }
+
+    async fn audit_log(&self, limit: usize) -> Result<Vec<AuditEntry>, AppError> {
+        Ok(self.audit.lock().await.iter().rev().take(limit).cloned().collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_helpers::migrated_pool;
+
+    #[tokio::test]
+    async fn test_sql_store_keeps_flags_and_audit_log() {
+        let pool = migrated_pool().await;
+        let store = SqlAdminStore::new(pool);
+        assert_eq!(store.flags().await.unwrap(), AdminFlags::default());
+
+        let degraded = DegradedMode {
+            reason: Some("database failover".to_string()),
+            enabled_by: "root".to_string(),
+            enabled_at: timestamp(Utc::now().timestamp_millis()).unwrap(),
+        };
+        store.set_degraded(Some(&degraded)).await.unwrap();
+        let plugin = DisabledPlugin {
+            name: "galaxy".to_string(),
+            reason: None,
+            disabled_by: "root".to_string(),
+            disabled_at: degraded.enabled_at,
+        };
+        store.disable_plugin(&plugin).await.unwrap();
+        store.disable_plugin(&plugin).await.unwrap();
+        let policy = AdminPolicy {
+            policy: CommandPolicy {
+                id: "p1".to_string(),
+                subject: "api_key:ci".parse().unwrap(),
+                effect: "deny".parse().unwrap(),
+                scope: "namespace".parse().unwrap(),
+                pattern: "gal*".to_string(),
+                description: Some("no workflows from CI".to_string()),
+            },
+            created_by: "root".to_string(),
+            created_at: degraded.enabled_at,
+        };
+        store.add_policy(&policy).await.unwrap();
+        let flags = store.flags().await.unwrap();
+        assert_eq!(flags.degraded, Some(degraded));
+        assert_eq!(flags.disabled_plugins, vec![plugin]);
+        assert_eq!(flags.policies, vec![policy]);
+
+        store.set_degraded(None).await.unwrap();
+        assert!(store.enable_plugin("galaxy").await.unwrap());
+        assert!(!store.enable_plugin("galaxy").await.unwrap());
+        assert!(store.remove_policy("p1").await.unwrap());
+        assert!(!store.remove_policy("p1").await.unwrap());
+        assert_eq!(store.flags().await.unwrap(), AdminFlags::default());
+
+        for action in ["degraded.enable", "plugins.disable"] {
+            store
+                .record(&AuditEntry::new("root", action, None, AuditOutcome::Succeeded, None))
+                .await
+                .unwrap();
+        }
+        store
+            .record(&AuditEntry::new("mallory", "keys.rotate", None, AuditOutcome::Denied, None))
+            .await
+            .unwrap();
+        let log = store.audit_log(2).await.unwrap();
+        assert_eq!(
+            log.iter().map(|entry| (entry.action.as_str(), entry.outcome)).collect::<Vec<_>>(),
+            vec![("keys.rotate", AuditOutcome::Denied), ("plugins.disable", AuditOutcome::Succeeded)]
+        );
+    }
+}
@@ -0,0 +1,243 @@
+//! Agent side of the protocol.
+//!
+//! An [`AgentClient`] connects to the server, advertises the commands in its
+//! registry and runs the tasks it is sent, reconnecting whenever the
+//! connection drops.
+
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::Duration;
+
+use futures::{SinkExt, StreamExt};
+use squirrel_commands::wire::{CommandInvocation, SchemaRegistry, WireContext};
+use squirrel_commands::workflow::StepRunner;
+use squirrel_commands::{CommandError, CommandRegistry};
+use squirrel_core::deadline::Deadline;
+use thiserror::Error;
+use tokio::sync::{mpsc, Semaphore};
+use tokio_tungstenite::{connect_async, tungstenite::Message};
+use tracing::{debug, info, warn};
+
+use super::protocol::{AgentCapabilities, AgentMessage, AgentResources, ServerMessage};
+
+/// Errors ending an agent session
+#[derive(Debug, Error)]
+pub enum AgentClientError {
+    /// The server refused the registration
+    #[error("Rejected by server: {0}")]
+    Rejected(String),
+
+    /// The connection failed or was closed unexpectedly
+    #[error("Connection error: {0}")]
+    Connection(String),
+
+    /// A message could not be encoded or decoded
+    #[error("Protocol error: {0}")]
+    Protocol(#[from] serde_json::Error),
+}
+
+impl From<tokio_tungstenite::tungstenite::Error> for AgentClientError {
+    fn from(error: tokio_tungstenite::tungstenite::Error) -> Self {
+        Self::Connection(error.to_string())
+    }
+}
+
+/// Configuration for a remote agent
+#[derive(Debug, Clone)]
+pub struct AgentClientConfig {
+    /// WebSocket URL of the server's agent endpoint
+    pub server_url: String,
+    /// Agent name shown by the server
+    pub name: String,
+    /// Shared registration token
+    pub token: Option<String>,
+    /// Labels used for affinity rules
+    pub labels: HashMap<String, String>,
+    /// Resources offered to the scheduler
+    pub resources: AgentResources,
+    /// Delay before reconnecting after the connection drops
+    pub reconnect_delay: Duration,
+}
+
+impl Default for AgentClientConfig {
+    fn default() -> Self {
+        Self {
+            server_url: "ws://127.0.0.1:3000/ws/agents".to_string(),
+            name: "agent".to_string(),
+            token: None,
+            labels: HashMap::new(),
+            resources: AgentResources::default(),
+            reconnect_delay: Duration::from_secs(5),
+        }
+    }
+}
+
+/// Remote agent that executes commands dispatched by the server
+pub struct AgentClient {
+    config: AgentClientConfig,
+    registry: CommandRegistry,
+    schemas: SchemaRegistry,
+}
+
+impl AgentClient {
+    /// Create a new AgentClient running commands from `registry`
+    pub fn new(config: AgentClientConfig, registry: CommandRegistry) -> Self {
+        Self {
+            config,
+            registry,
+            schemas: SchemaRegistry::default(),
+        }
+    }
+
+    /// Replace the schemas used to read tasks, e.g. to add extensions
+    pub fn with_schemas(mut self, schemas: SchemaRegistry) -> Self {
+        self.schemas = schemas;
+        self
+    }
+
+    /// Capabilities advertised to the server
+    pub fn capabilities(&self) -> AgentCapabilities {
+        AgentCapabilities {
+            commands: self.registry.list_commands().unwrap_or_default(),
+            tools: Vec::new(),
+            labels: self.config.labels.clone(),
+        }
+    }
+
+    /// Serve tasks until the process exits, reconnecting on failure
+    ///
+    /// Returns an error only if the server rejects the agent.
+    pub async fn run(&self) -> Result<(), AgentClientError> {
+        loop {
+            match self.session().await {
+                Ok(()) => info!("Connection to {} closed", self.config.server_url),
+                Err(e @ AgentClientError::Rejected(_)) => return Err(e),
+                Err(e) => warn!("Agent connection failed: {}", e),
+            }
+            tokio::time::sleep(self.config.reconnect_delay).await;
+        }
+    }
+
+    /// Runs a single connection until it closes
+    async fn session(&self) -> Result<(), AgentClientError> {
+        let (socket, _) = connect_async(self.config.server_url.as_str()).await?;
+        let (mut sender, mut receiver) = socket.split();
+
+        let register = AgentMessage::Register {
+            name: self.config.name.clone(),
+            token: self.config.token.clone(),
+            capabilities: self.capabilities(),
+            resources: self.config.resources.clone(),
+        };
+        sender.send(Message::Text(serde_json::to_string(&register)?)).await?;
+
+        let (agent_id, heartbeat_interval) = loop {
+            let frame = receiver
+                .next()
+                .await
+                .ok_or_else(|| AgentClientError::Connection("Closed during registration".to_string()))??;
+            let Message::Text(text) = frame else { continue };
+            match serde_json::from_str(&text)? {
+                ServerMessage::Registered { agent_id, heartbeat_interval_secs } => {
+                    break (agent_id, Duration::from_secs(heartbeat_interval_secs.max(1)));
+                }
+                ServerMessage::Rejected { reason } => return Err(AgentClientError::Rejected(reason)),
+                ServerMessage::Execute { .. } => {
+                    return Err(AgentClientError::Connection("Received a task before registration".to_string()))
+                }
+            }
+        };
+        info!("Registered with {} as {}", self.config.server_url, agent_id);
+
+        let max_tasks = self.config.resources.max_concurrent_tasks.max(1);
+        let (results_tx, mut results_rx) = mpsc::channel::<AgentMessage>(max_tasks);
+        let slots = Arc::new(Semaphore::new(max_tasks));
+        let mut heartbeat = tokio::time::interval(heartbeat_interval);
+
+        loop {
+            tokio::select! {
+                _ = heartbeat.tick() => {
+                    sender.send(Message::Text(serde_json::to_string(&AgentMessage::Heartbeat)?)).await?;
+                }
+                Some(result) = results_rx.recv() => {
+                    sender.send(Message::Text(serde_json::to_string(&result)?)).await?;
+                }
+                frame = receiver.next() => {
+                    let text = match frame {
+                        Some(Ok(Message::Text(text))) => text,
+                        Some(Ok(Message::Close(_))) | None => return Ok(()),
+                        Some(Ok(_)) => continue,
+                        Some(Err(e)) => return Err(e.into()),
+                    };
+                    match serde_json::from_str::<ServerMessage>(&text) {
+                        Ok(ServerMessage::Execute { task_id, kind, target, args, timeout_secs, invocation }) => {
+                            // Servers without the wire schema only send the plain fields
+                            let task = match invocation {
+                                Some(envelope) => self.schemas.open::<CommandInvocation>(envelope),
+                                None => Ok(CommandInvocation {
+                                    id: task_id.clone(),
+                                    kind,
+                                    target,
+                                    args,
+                                    timeout_secs,
+                                    context: WireContext::default(),
+                                }),
+                            };
+                            let registry = self.registry.clone();
+                            let results = results_tx.clone();
+                            let slots = slots.clone();
+                            tokio::spawn(async move {
+                                // The sender's deadline runs while the task waits for a slot
+                                let deadline = task
+                                    .as_ref()
+                                    .ok()
+                                    .and_then(|task| task.context.deadline_ms)
+                                    .map(Deadline::from_remaining_millis);
+                                let _permit = slots.acquire_owned().await;
+                                let result = match task {
+                                    Ok(task) => {
+                                        debug!(
+                                            "Running task {} for {:?} in {:?}",
+                                            task_id, task.context.user, task.context.workspace
+                                        );
+                                        execute(&registry, &task, deadline).await
+                                    }
+                                    Err(e) => Err(e),
+                                };
+                                let message = match result {
+                                    Ok(output) => AgentMessage::TaskResult { task_id, success: true, output: Some(output), error: None },
+                                    Err(e) => {
+                                        // Tagged with the task, so forwarded logs can be searched by run
+                                        warn!(run_id = %task_id, "Task {} failed: {}", task_id, e);
+                                        AgentMessage::TaskResult { task_id, success: false, output: None, error: Some(e.to_string()) }
+                                    }
+                                };
+                                let _ = results.send(message).await;
+                            });
+                        }
+                        Ok(other) => warn!("Ignoring unexpected server message: {:?}", other),
+                        Err(e) => warn!("Ignoring malformed server message: {}", e),
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// Runs a task through the registry, stopping it at its timeout or the
+/// sender's deadline, whichever is first
+async fn execute(
+    registry: &CommandRegistry,
+    task: &CommandInvocation,
+    deadline: Option<Deadline>,
+) -> Result<String, CommandError> {
+    let timeout = task.timeout_secs.map(|secs| Deadline::after(Duration::from_secs(secs)));
+    let run = registry.run(task.kind, &task.target, &task.args);
+    match timeout.into_iter().chain(deadline).min() {
+        Some(deadline) => deadline
+            .run(run)
+            .await
+            .unwrap_or_else(|_| Err(CommandError::DeadlineExceeded(format!("{} did not finish in time", task.target)))),
+        None => run.await,
+    }
+}
@@ -0,0 +1,130 @@
+//! WebSocket endpoint that remote agents connect to.
+
+use std::sync::Arc;
+use std::time::Duration;
+
+use axum::{
+    extract::{
+        ws::{Message, WebSocket},
+        State, WebSocketUpgrade,
+    },
+    response::IntoResponse,
+};
+use futures::{stream::SplitStream, SinkExt, StreamExt};
+use tokio::sync::mpsc;
+use tracing::{debug, error, info, warn};
+
+use super::protocol::{AgentMessage, ServerMessage};
+use super::scheduler::AgentScheduler;
+use crate::api::error::AppError;
+use crate::state::AppState;
+
+/// Maximum number of queued messages for an agent
+const MAX_QUEUE_SIZE: usize = 100;
+
+/// Time an agent has to register after connecting
+const REGISTRATION_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Agent WebSocket connection handler
+pub async fn agent_ws_handler(
+    ws: WebSocketUpgrade,
+    State(state): State<Arc<AppState>>,
+) -> Result<impl IntoResponse, AppError> {
+    let scheduler = state.get_agent_scheduler()?.clone();
+    Ok(ws.on_upgrade(move |socket| handle_agent_socket(socket, scheduler)))
+}
+
+/// Reads the next protocol message, skipping control frames
+async fn next_message(receiver: &mut SplitStream<WebSocket>) -> Option<AgentMessage> {
+    while let Some(frame) = receiver.next().await {
+        match frame {
+            Ok(Message::Text(text)) => match serde_json::from_str(&text) {
+                Ok(message) => return Some(message),
+                Err(e) => warn!("Ignoring malformed agent message: {}", e),
+            },
+            Ok(Message::Close(_)) => return None,
+            Ok(_) => {}
+            Err(e) => {
+                debug!("Agent connection error: {}", e);
+                return None;
+            }
+        }
+    }
+    None
+}
+
+/// Serializes a server message into a text frame
+fn frame(message: &ServerMessage) -> Message {
+    Message::Text(serde_json::to_string(message).unwrap_or_default())
+}
+
+/// Handle an agent connection
+async fn handle_agent_socket(socket: WebSocket, scheduler: Arc<AgentScheduler>) {
+    let (mut sender, mut receiver) = socket.split();
+
+    let registration = tokio::time::timeout(REGISTRATION_TIMEOUT, next_message(&mut receiver)).await;
+    let Ok(Some(AgentMessage::Register { name, token, capabilities, resources })) = registration else {
+        let reason = "Expected a register message".to_string();
+        let _ = sender.send(frame(&ServerMessage::Rejected { reason })).await;
+        return;
+    };
+
+    let (tx, mut rx) = mpsc::channel::<ServerMessage>(MAX_QUEUE_SIZE);
+    let agent_id = match scheduler
+        .register(&name, token.as_deref(), capabilities, resources, tx)
+        .await
+    {
+        Ok(id) => id,
+        Err(e) => {
+            warn!("Rejected agent {}: {}", name, e);
+            let _ = sender.send(frame(&ServerMessage::Rejected { reason: e.to_string() })).await;
+            return;
+        }
+    };
+
+    let registered = ServerMessage::Registered {
+        agent_id: agent_id.clone(),
+        heartbeat_interval_secs: scheduler.heartbeat_interval().as_secs(),
+    };
+    if sender.send(frame(&registered)).await.is_err() {
+        scheduler.disconnect(&agent_id).await;
+        return;
+    }
+
+    // Task for sending dispatched work to the agent
+    let send_task = tokio::spawn(async move {
+        while let Some(message) = rx.recv().await {
+            if let Err(e) = sender.send(frame(&message)).await {
+                error!("Error sending message to agent: {}", e);
+                break;
+            }
+        }
+    });
+
+    while let Some(message) = next_message(&mut receiver).await {
+        match message {
+            AgentMessage::Heartbeat => {
+                if scheduler.heartbeat(&agent_id).await.is_err() {
+                    info!("Closing connection of expired agent {}", agent_id);
+                    break;
+                }
+            }
+            AgentMessage::TaskResult { task_id, success, output, error } => {
+                let result = if success {
+                    Ok(output.unwrap_or_default())
+                } else {
+                    Err(error.unwrap_or_else(|| "Task failed".to_string()))
+                };
+                if let Err(e) = scheduler.complete(&agent_id, &task_id, result).await {
+                    warn!("Ignoring result from agent {}: {}", agent_id, e);
+                }
+            }
+            AgentMessage::Register { .. } => {
+                warn!("Agent {} sent a second register message", agent_id);
+            }
+        }
+    }
+
+    send_task.abort();
+    scheduler.disconnect(&agent_id).await;
+}
@@ -0,0 +1,17 @@
+//! Distributed worker agents.
+//!
+//! Remote machines run the `squirrel_agent` binary, which connects to the
+//! server's `/ws/agents` WebSocket endpoint, registers its capabilities and
+//! resources, sends heartbeats and executes the command and tool tasks the
+//! [`AgentScheduler`] dispatches to it. Tasks are submitted and inspected
+//! through `/api/agents`.
+
+pub mod client;
+pub mod connection;
+pub mod protocol;
+pub mod scheduler;
+
+pub use client::{AgentClient, AgentClientConfig};
+pub use connection::agent_ws_handler;
+pub use protocol::{AgentCapabilities, AgentMessage, AgentResources, ServerMessage};
+pub use scheduler::{Affinity, AgentInfo, AgentScheduler, AgentTask, AgentTaskStatus, TaskRequest};
@@ -0,0 +1,127 @@
+//! Wire protocol spoken between the server and remote agents.
+//!
+//! Messages are JSON text frames tagged with a `type` field.
+
+use std::collections::HashMap;
+
+use serde::{Deserialize, Serialize};
+use squirrel_commands::wire::Envelope;
+use squirrel_commands::workflow::StepKind;
+
+/// What an agent is able to execute
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct AgentCapabilities {
+    /// Command names the agent can run
+    #[serde(default)]
+    pub commands: Vec<String>,
+    /// MCP tool names the agent can run
+    #[serde(default)]
+    pub tools: Vec<String>,
+    /// Free-form labels used for affinity rules, e.g. `gpu=true`
+    #[serde(default)]
+    pub labels: HashMap<String, String>,
+}
+
+impl AgentCapabilities {
+    /// Whether the agent advertises the given command or tool
+    pub fn supports(&self, kind: StepKind, target: &str) -> bool {
+        let names = match kind {
+            StepKind::Command => &self.commands,
+            StepKind::Tool => &self.tools,
+        };
+        names.iter().any(|name| name == target)
+    }
+}
+
+/// Resources an agent offers to the scheduler
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct AgentResources {
+    /// Number of CPUs on the agent machine
+    pub cpus: usize,
+    /// Memory available on the agent machine in megabytes, if known
+    #[serde(default)]
+    pub memory_mb: Option<u64>,
+    /// Maximum number of tasks the agent runs at once
+    pub max_concurrent_tasks: usize,
+}
+
+impl Default for AgentResources {
+    fn default() -> Self {
+        let cpus = std::thread::available_parallelism().map_or(1, |n| n.get());
+        Self {
+            cpus,
+            memory_mb: None,
+            max_concurrent_tasks: cpus,
+        }
+    }
+}
+
+/// Messages sent from an agent to the server
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum AgentMessage {
+    /// First message on a connection, announcing the agent
+    Register {
+        /// Human-readable agent name
+        name: String,
+        /// Shared registration token
+        #[serde(default)]
+        token: Option<String>,
+        /// What the agent can execute
+        capabilities: AgentCapabilities,
+        /// Resources the agent offers
+        resources: AgentResources,
+    },
+    /// Periodic liveness signal
+    Heartbeat,
+    /// Outcome of a dispatched task
+    TaskResult {
+        /// Task ID
+        task_id: String,
+        /// Whether the task succeeded
+        success: bool,
+        /// Task output on success
+        #[serde(default)]
+        output: Option<String>,
+        /// Error message on failure
+        #[serde(default)]
+        error: Option<String>,
+    },
+}
+
+/// Messages sent from the server to an agent
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum ServerMessage {
+    /// Registration was accepted
+    Registered {
+        /// ID assigned to the agent
+        agent_id: String,
+        /// How often the agent must send heartbeats
+        heartbeat_interval_secs: u64,
+    },
+    /// Registration was refused and the connection will be closed
+    Rejected {
+        /// Why the agent was refused
+        reason: String,
+    },
+    /// Run a task
+    Execute {
+        /// Task ID to report the result under
+        task_id: String,
+        /// Whether the target is a command or a tool
+        kind: StepKind,
+        /// Command or tool name
+        target: String,
+        /// Arguments
+        args: Vec<String>,
+        /// Maximum run time in seconds
+        #[serde(default)]
+        timeout_secs: Option<u64>,
+        /// The same task as a versioned `squirrel.command_invocation`,
+        /// including the context it runs in; agents that understand it
+        /// prefer it over the fields above
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        invocation: Option<Envelope>,
+    },
+}
@@ -0,0 +1,670 @@
+//! Scheduler that assigns queued tasks to connected agents.
+//!
+//! Tasks wait in a FIFO queue until an agent that advertises the task's
+//! command or tool, satisfies its affinity rules and has a free slot is
+//! available. Among eligible agents the one matching the most preferred
+//! labels wins, then the least loaded one. When an agent disconnects or
+//! misses its heartbeats, its running tasks are put back at the front of the
+//! queue until they have used up their attempts.
+
+use std::collections::{HashMap, VecDeque};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use squirrel_commands::wire::{CommandInvocation, SchemaRegistry, WireContext};
+use squirrel_app::supervisor::{RestartPolicy, Supervisor};
+use squirrel_commands::workflow::StepKind;
+use squirrel_core::expr::{self, Expression};
+use tokio::sync::{mpsc, Mutex};
+use tracing::{debug, info, warn};
+use uuid::Uuid;
+
+use super::protocol::{AgentCapabilities, AgentResources, ServerMessage};
+use crate::api::error::AppError;
+use crate::config::AgentConfig;
+
+/// Placement rules for a task
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Affinity {
+    /// Labels an agent must carry to run the task
+    #[serde(default)]
+    pub required_labels: HashMap<String, String>,
+    /// Labels that make an agent preferred over others
+    #[serde(default)]
+    pub preferred_labels: HashMap<String, String>,
+    /// Run the task only on this agent
+    #[serde(default)]
+    pub agent_id: Option<String>,
+    /// Condition the agent must meet, over its `agent_id`, `labels`,
+    /// `commands` and `tools`, e.g. `labels.region in ["eu", "us"]`
+    #[serde(default)]
+    pub condition: Option<Expression>,
+}
+
+impl Affinity {
+    /// Whether the agent may run a task with these rules
+    fn allows(&self, agent_id: &str, capabilities: &AgentCapabilities) -> bool {
+        self.agent_id.as_deref().is_none_or(|pinned| pinned == agent_id)
+            && self
+                .required_labels
+                .iter()
+                .all(|(key, value)| capabilities.labels.get(key) == Some(value))
+            && self.condition.as_ref().is_none_or(|condition| {
+                let input = expr::input([
+                    ("agent_id", serde_json::Value::from(agent_id)),
+                    ("labels", serde_json::json!(capabilities.labels)),
+                    ("commands", serde_json::json!(capabilities.commands)),
+                    ("tools", serde_json::json!(capabilities.tools)),
+                ]);
+                condition.test(&input).unwrap_or_else(|e| {
+                    debug!("Affinity condition failed for agent {}: {}", agent_id, e);
+                    false
+                })
+            })
+    }
+
+    /// Number of preferred labels the agent carries
+    fn score(&self, capabilities: &AgentCapabilities) -> usize {
+        self.preferred_labels
+            .iter()
+            .filter(|(key, value)| capabilities.labels.get(*key) == Some(*value))
+            .count()
+    }
+}
+
+/// A task to run on a remote agent
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TaskRequest {
+    /// Whether the target is a command or a tool
+    pub kind: StepKind,
+    /// Command or tool name
+    pub target: String,
+    /// Arguments
+    pub args: Vec<String>,
+    /// Placement rules
+    pub affinity: Affinity,
+    /// Maximum run time in seconds, enforced by the agent
+    pub timeout_secs: Option<u64>,
+    /// User, workspace and extensions sent along with the task
+    #[serde(default)]
+    pub context: WireContext,
+}
+
+/// State of a remote task
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum AgentTaskStatus {
+    /// Waiting for a suitable agent
+    Queued,
+    /// Dispatched to an agent
+    Running,
+    /// Finished successfully
+    Succeeded,
+    /// Failed on the agent or ran out of attempts
+    Failed,
+}
+
+/// A task tracked by the scheduler
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AgentTask {
+    /// Task ID
+    pub id: String,
+    /// The submitted request
+    #[serde(flatten)]
+    pub request: TaskRequest,
+    /// Current state
+    pub status: AgentTaskStatus,
+    /// Agent the task is or was last dispatched to
+    pub agent_id: Option<String>,
+    /// Number of times the task has been dispatched
+    pub attempts: u32,
+    /// Output on success
+    pub output: Option<String>,
+    /// Error message on failure
+    pub error: Option<String>,
+    /// Submission timestamp
+    pub created_at: DateTime<Utc>,
+    /// Timestamp of the latest dispatch
+    pub started_at: Option<DateTime<Utc>>,
+    /// Completion timestamp
+    pub finished_at: Option<DateTime<Utc>>,
+}
+
+/// Public view of a connected agent
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AgentInfo {
+    /// Agent ID
+    pub id: String,
+    /// Agent name
+    pub name: String,
+    /// What the agent can execute
+    pub capabilities: AgentCapabilities,
+    /// Resources the agent offers
+    pub resources: AgentResources,
+    /// IDs of the tasks dispatched to the agent
+    pub running_tasks: Vec<String>,
+    /// Registration timestamp
+    pub connected_at: DateTime<Utc>,
+    /// Timestamp of the latest heartbeat
+    pub last_heartbeat: DateTime<Utc>,
+}
+
+/// A connected agent
+struct AgentEntry {
+    info: AgentInfo,
+    sender: mpsc::Sender<ServerMessage>,
+    last_seen: Instant,
+}
+
+impl AgentEntry {
+    fn has_capacity(&self) -> bool {
+        self.info.running_tasks.len() < self.info.resources.max_concurrent_tasks.max(1)
+    }
+
+    fn load(&self) -> f64 {
+        self.info.running_tasks.len() as f64 / self.info.resources.max_concurrent_tasks.max(1) as f64
+    }
+}
+
+/// Mutable scheduler state
+#[derive(Default)]
+struct SchedulerState {
+    agents: HashMap<String, AgentEntry>,
+    tasks: HashMap<String, AgentTask>,
+    queue: VecDeque<String>,
+    schemas: SchemaRegistry,
+}
+
+impl SchedulerState {
+    /// Picks the best agent for a task, if any can take it now
+    fn select_agent(&self, request: &TaskRequest) -> Option<String> {
+        self.agents
+            .iter()
+            .filter(|(id, agent)| {
+                agent.has_capacity()
+                    && agent.info.capabilities.supports(request.kind, &request.target)
+                    && request.affinity.allows(id, &agent.info.capabilities)
+            })
+            .max_by(|(a_id, a), (b_id, b)| {
+                let a_score = request.affinity.score(&a.info.capabilities);
+                let b_score = request.affinity.score(&b.info.capabilities);
+                a_score
+                    .cmp(&b_score)
+                    .then_with(|| b.load().total_cmp(&a.load()))
+                    .then_with(|| b_id.cmp(a_id))
+            })
+            .map(|(id, _)| id.clone())
+    }
+
+    /// Sends every queued task that can be placed to an agent
+    fn dispatch(&mut self) {
+        let mut waiting = VecDeque::new();
+
+        while let Some(task_id) = self.queue.pop_front() {
+            let Some(task) = self.tasks.get(&task_id) else {
+                continue;
+            };
+            let Some(agent_id) = self.select_agent(&task.request) else {
+                waiting.push_back(task_id);
+                continue;
+            };
+
+            let invocation = CommandInvocation {
+                id: task_id.clone(),
+                kind: task.request.kind,
+                target: task.request.target.clone(),
+                args: task.request.args.clone(),
+                timeout_secs: task.request.timeout_secs,
+                context: task.request.context.clone(),
+            };
+            let message = ServerMessage::Execute {
+                task_id: task_id.clone(),
+                kind: task.request.kind,
+                target: task.request.target.clone(),
+                args: task.request.args.clone(),
+                timeout_secs: task.request.timeout_secs,
+                invocation: self
+                    .schemas
+                    .seal(&invocation)
+                    .map_err(|e| warn!("Cannot encode task {}: {}", task_id, e))
+                    .ok(),
+            };
+            let agent = self.agents.get_mut(&agent_id).expect("selected agent exists");
+            if let Err(e) = agent.sender.try_send(message) {
+                debug!("Could not dispatch task {} to agent {}: {}", task_id, agent_id, e);
+                waiting.push_back(task_id);
+                continue;
+            }
+            agent.info.running_tasks.push(task_id.clone());
+
+            let task = self.tasks.get_mut(&task_id).expect("queued task exists");
+            task.status = AgentTaskStatus::Running;
+            task.agent_id = Some(agent_id.clone());
+            task.attempts += 1;
+            task.started_at = Some(Utc::now());
+            debug!("Dispatched task {} to agent {}", task_id, agent_id);
+        }
+
+        self.queue = waiting;
+    }
+
+    /// Removes an agent, re-queueing or failing its running tasks
+    fn remove_agent(&mut self, agent_id: &str, max_attempts: u32) -> bool {
+        let Some(agent) = self.agents.remove(agent_id) else {
+            return false;
+        };
+
+        for task_id in agent.info.running_tasks.into_iter().rev() {
+            let Some(task) = self.tasks.get_mut(&task_id) else {
+                continue;
+            };
+            if task.status != AgentTaskStatus::Running {
+                continue;
+            }
+            if task.attempts < max_attempts {
+                task.status = AgentTaskStatus::Queued;
+                task.agent_id = None;
+                self.queue.push_front(task_id);
+            } else {
+                task.status = AgentTaskStatus::Failed;
+                task.error = Some(format!(
+                    "Agent {} was lost after {} attempt(s)",
+                    agent_id, task.attempts
+                ));
+                task.finished_at = Some(Utc::now());
+            }
+        }
+
+        true
+    }
+}
+
+/// Assigns tasks to remote agents and tracks their liveness
+pub struct AgentScheduler {
+    config: AgentConfig,
+    state: Mutex<SchedulerState>,
+}
+
+impl AgentScheduler {
+    /// Create a new AgentScheduler
+    pub fn new(config: AgentConfig) -> Self {
+        Self {
+            config,
+            state: Mutex::new(SchedulerState::default()),
+        }
+    }
+
+    /// Interval at which agents must send heartbeats
+    pub fn heartbeat_interval(&self) -> Duration {
+        Duration::from_secs(self.config.heartbeat_interval_secs.max(1))
+    }
+
+    /// Check the registration token an agent presents
+    pub fn authorize(&self, token: Option<&str>) -> Result<(), AppError> {
+        match &self.config.token {
+            Some(expected) if token != Some(expected.as_str()) => {
+                Err(AppError::Unauthorized("Invalid agent token".to_string()))
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// Register a connected agent and return its ID
+    ///
+    /// Queued tasks are dispatched to the new agent through `sender`.
+    pub async fn register(
+        &self,
+        name: &str,
+        token: Option<&str>,
+        capabilities: AgentCapabilities,
+        resources: AgentResources,
+        sender: mpsc::Sender<ServerMessage>,
+    ) -> Result<String, AppError> {
+        self.authorize(token)?;
+
+        let id = Uuid::new_v4().to_string();
+        let now = Utc::now();
+        let entry = AgentEntry {
+            info: AgentInfo {
+                id: id.clone(),
+                name: name.to_string(),
+                capabilities,
+                resources,
+                running_tasks: Vec::new(),
+                connected_at: now,
+                last_heartbeat: now,
+            },
+            sender,
+            last_seen: Instant::now(),
+        };
+
+        let mut state = self.state.lock().await;
+        state.agents.insert(id.clone(), entry);
+        info!("Agent {} registered as {}", name, id);
+        state.dispatch();
+
+        Ok(id)
+    }
+
+    /// Record a heartbeat from an agent
+    pub async fn heartbeat(&self, agent_id: &str) -> Result<(), AppError> {
+        let mut state = self.state.lock().await;
+        let agent = state
+            .agents
+            .get_mut(agent_id)
+            .ok_or_else(|| AppError::NotFound(format!("Agent {} not found", agent_id)))?;
+        agent.last_seen = Instant::now();
+        agent.info.last_heartbeat = Utc::now();
+        Ok(())
+    }
+
+    /// Record the result of a task reported by an agent
+    pub async fn complete(
+        &self,
+        agent_id: &str,
+        task_id: &str,
+        result: Result<String, String>,
+    ) -> Result<(), AppError> {
+        let mut state = self.state.lock().await;
+
+        let task = state
+            .tasks
+            .get_mut(task_id)
+            .filter(|task| task.status == AgentTaskStatus::Running && task.agent_id.as_deref() == Some(agent_id))
+            .ok_or_else(|| AppError::NotFound(format!("Task {} is not running on agent {}", task_id, agent_id)))?;
+        match result {
+            Ok(output) => {
+                task.status = AgentTaskStatus::Succeeded;
+                task.output = Some(output);
+            }
+            Err(error) => {
+                task.status = AgentTaskStatus::Failed;
+                task.error = Some(error);
+            }
+        }
+        task.finished_at = Some(Utc::now());
+
+        if let Some(agent) = state.agents.get_mut(agent_id) {
+            agent.info.running_tasks.retain(|id| id != task_id);
+        }
+        state.dispatch();
+
+        Ok(())
+    }
+
+    /// Remove a disconnected agent, re-queueing its running tasks
+    pub async fn disconnect(&self, agent_id: &str) {
+        let mut state = self.state.lock().await;
+        if state.remove_agent(agent_id, self.config.max_attempts) {
+            info!("Agent {} disconnected", agent_id);
+            state.dispatch();
+        }
+    }
+
+    /// Remove agents that have missed their heartbeats and return their IDs
+    pub async fn reap_expired(&self) -> Vec<String> {
+        let timeout = Duration::from_secs(self.config.heartbeat_timeout_secs);
+        let mut state = self.state.lock().await;
+
+        let expired: Vec<String> = state
+            .agents
+            .iter()
+            .filter(|(_, agent)| agent.last_seen.elapsed() > timeout)
+            .map(|(id, _)| id.clone())
+            .collect();
+        for agent_id in &expired {
+            warn!("Agent {} missed its heartbeats; re-queueing its tasks", agent_id);
+            state.remove_agent(agent_id, self.config.max_attempts);
+        }
+        if !expired.is_empty() {
+            state.dispatch();
+        }
+
+        expired
+    }
+
+    /// Supervise a background task that periodically removes lost agents
+    pub fn supervise_monitor(self: &Arc<Self>, supervisor: &Supervisor) {
+        let scheduler = self.clone();
+        supervisor.spawn("agents.monitor", RestartPolicy::default(), move |shutdown| {
+            let scheduler = scheduler.clone();
+            async move {
+                while !shutdown.sleep(scheduler.heartbeat_interval()).await {
+                    scheduler.reap_expired().await;
+                }
+                Ok(())
+            }
+        });
+    }
+
+    /// Queue a task and return its ID
+    pub async fn submit(&self, request: TaskRequest) -> Result<String, AppError> {
+        if request.target.is_empty() {
+            return Err(AppError::InvalidRequest("Task target must not be empty".to_string()));
+        }
+        if let Some(condition) = &request.affinity.condition {
+            Expression::condition(condition.source())
+                .map_err(|e| AppError::InvalidRequest(format!("Invalid affinity condition: {e}")))?;
+        }
+
+        let id = Uuid::new_v4().to_string();
+        let task = AgentTask {
+            id: id.clone(),
+            request,
+            status: AgentTaskStatus::Queued,
+            agent_id: None,
+            attempts: 0,
+            output: None,
+            error: None,
+            created_at: Utc::now(),
+            started_at: None,
+            finished_at: None,
+        };
+
+        let mut state = self.state.lock().await;
+        state.tasks.insert(id.clone(), task);
+        state.queue.push_back(id.clone());
+        state.dispatch();
+
+        Ok(id)
+    }
+
+    /// Get a task by ID
+    pub async fn task(&self, task_id: &str) -> Result<AgentTask, AppError> {
+        self.state
+            .lock()
+            .await
+            .tasks
+            .get(task_id)
+            .cloned()
+            .ok_or_else(|| AppError::NotFound(format!("Task {} not found", task_id)))
+    }
+
+    /// Tasks of a trace: the task with that ID and those submitted with it
+    /// as their request ID, oldest first
+    pub async fn traced_tasks(&self, trace_id: &str) -> Vec<AgentTask> {
+        let state = self.state.lock().await;
+        let mut tasks: Vec<AgentTask> = state
+            .tasks
+            .values()
+            .filter(|task| task.id == trace_id || task.request.context.request_id.as_deref() == Some(trace_id))
+            .cloned()
+            .collect();
+        tasks.sort_by_key(|task| task.created_at);
+        tasks
+    }
+
+    /// List connected agents
+    pub async fn agents(&self) -> Vec<AgentInfo> {
+        let state = self.state.lock().await;
+        let mut agents: Vec<AgentInfo> = state.agents.values().map(|agent| agent.info.clone()).collect();
+        agents.sort_by_key(|agent| agent.connected_at);
+        agents
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn capabilities(commands: &[&str], labels: &[(&str, &str)]) -> AgentCapabilities {
+        AgentCapabilities {
+            commands: commands.iter().map(|c| c.to_string()).collect(),
+            tools: Vec::new(),
+            labels: labels.iter().map(|(k, v)| (k.to_string(), v.to_string())).collect(),
+        }
+    }
+
+    fn resources(slots: usize) -> AgentResources {
+        AgentResources {
+            cpus: slots,
+            memory_mb: None,
+            max_concurrent_tasks: slots,
+        }
+    }
+
+    fn request(target: &str, affinity: Affinity) -> TaskRequest {
+        TaskRequest {
+            kind: StepKind::Command,
+            target: target.to_string(),
+            args: Vec::new(),
+            affinity,
+            timeout_secs: None,
+            context: WireContext::default(),
+        }
+    }
+
+    fn dispatched(rx: &mut mpsc::Receiver<ServerMessage>) -> Option<String> {
+        match rx.try_recv() {
+            Ok(ServerMessage::Execute { task_id, .. }) => Some(task_id),
+            _ => None,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_tasks_follow_capabilities_and_affinity() {
+        let scheduler = AgentScheduler::new(AgentConfig::default());
+        let (cpu_tx, mut cpu_rx) = mpsc::channel(8);
+        let (gpu_tx, mut gpu_rx) = mpsc::channel(8);
+        scheduler
+            .register("cpu", None, capabilities(&["echo"], &[]), resources(2), cpu_tx)
+            .await
+            .unwrap();
+        let gpu = scheduler
+            .register("gpu", None, capabilities(&["echo", "align"], &[("gpu", "true")]), resources(1), gpu_tx)
+            .await
+            .unwrap();
+
+        let align = scheduler.submit(request("align", Affinity::default())).await.unwrap();
+        assert_eq!(dispatched(&mut gpu_rx), Some(align.clone()));
+        assert_eq!(scheduler.task(&align).await.unwrap().agent_id, Some(gpu.clone()));
+
+        // The GPU agent is full, so a task requiring it waits in the queue
+        let affinity = Affinity {
+            required_labels: HashMap::from([("gpu".to_string(), "true".to_string())]),
+            ..Affinity::default()
+        };
+        let pinned = scheduler.submit(request("echo", affinity)).await.unwrap();
+        assert_eq!(scheduler.task(&pinned).await.unwrap().status, AgentTaskStatus::Queued);
+        assert_eq!(dispatched(&mut cpu_rx), None);
+
+        // A condition no agent meets keeps the task queued, one that is not a
+        // condition is rejected
+        let condition = |source: &str| Affinity {
+            condition: Some(Expression::parse(source).unwrap()),
+            ..Affinity::default()
+        };
+        let eu = scheduler.submit(request("echo", condition(r#"labels.region == "eu""#))).await.unwrap();
+        assert_eq!(scheduler.task(&eu).await.unwrap().status, AgentTaskStatus::Queued);
+        assert!(scheduler.submit(request("echo", condition("len(tools)"))).await.is_err());
+
+        let unknown = scheduler.submit(request("missing", Affinity::default())).await.unwrap();
+        assert_eq!(scheduler.task(&unknown).await.unwrap().status, AgentTaskStatus::Queued);
+
+        scheduler.complete(&gpu, &align, Ok("done".to_string())).await.unwrap();
+        assert_eq!(scheduler.task(&align).await.unwrap().status, AgentTaskStatus::Succeeded);
+        assert_eq!(dispatched(&mut gpu_rx), Some(pinned));
+    }
+
+    #[tokio::test]
+    async fn test_lost_agent_tasks_are_requeued() {
+        let config = AgentConfig {
+            max_attempts: 2,
+            ..AgentConfig::default()
+        };
+        let scheduler = AgentScheduler::new(config);
+        let (first_tx, mut first_rx) = mpsc::channel(8);
+        let first = scheduler
+            .register("first", None, capabilities(&["echo"], &[]), resources(1), first_tx)
+            .await
+            .unwrap();
+
+        let task = scheduler.submit(request("echo", Affinity::default())).await.unwrap();
+        assert_eq!(dispatched(&mut first_rx), Some(task.clone()));
+
+        scheduler.disconnect(&first).await;
+        assert_eq!(scheduler.task(&task).await.unwrap().status, AgentTaskStatus::Queued);
+
+        let (second_tx, mut second_rx) = mpsc::channel(8);
+        let second = scheduler
+            .register("second", None, capabilities(&["echo"], &[]), resources(1), second_tx)
+            .await
+            .unwrap();
+        assert_eq!(dispatched(&mut second_rx), Some(task.clone()));
+        assert_eq!(scheduler.task(&task).await.unwrap().attempts, 2);
+
+        scheduler.disconnect(&second).await;
+        let failed = scheduler.task(&task).await.unwrap();
+        assert_eq!(failed.status, AgentTaskStatus::Failed);
+        assert!(scheduler.agents().await.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_dispatch_sends_versioned_invocation() {
+        let scheduler = AgentScheduler::new(AgentConfig::default());
+        let (tx, mut rx) = mpsc::channel(8);
+        scheduler
+            .register("agent", None, capabilities(&["echo"], &[]), resources(1), tx)
+            .await
+            .unwrap();
+
+        let mut task = request("echo", Affinity::default());
+        task.args = vec!["hello".to_string()];
+        task.context.user = Some("alice".to_string());
+        let id = scheduler.submit(task).await.unwrap();
+
+        let message = rx.try_recv().unwrap();
+        let json = serde_json::to_value(&message).unwrap();
+        let ServerMessage::Execute { invocation: Some(envelope), .. } = message else {
+            panic!("expected an execute message with an invocation");
+        };
+        let invocation: CommandInvocation = SchemaRegistry::default().open(envelope).unwrap();
+        assert_eq!(invocation.id, id);
+        assert_eq!(invocation.args, vec!["hello".to_string()]);
+        assert_eq!(invocation.context.user.as_deref(), Some("alice"));
+
+        // Agents predating the envelope still find the plain fields
+        assert_eq!(json["target"], "echo");
+        assert_eq!(json["args"][0], "hello");
+    }
+
+    #[tokio::test]
+    async fn test_registration_requires_token() {
+        let config = AgentConfig {
+            token: Some("secret".to_string()),
+            ..AgentConfig::default()
+        };
+        let scheduler = AgentScheduler::new(config);
+        let (tx, _rx) = mpsc::channel(1);
+        let result = scheduler
+            .register("agent", Some("wrong"), AgentCapabilities::default(), resources(1), tx.clone())
+            .await;
+        assert!(matches!(result, Err(AppError::Unauthorized(_))));
+        assert!(scheduler
+            .register("agent", Some("secret"), AgentCapabilities::default(), resources(1), tx)
+            .await
+            .is_ok());
+    }
+}
@@ -0,0 +1,163 @@
+//! Admin API data models.
+//!
+//! This module contains the requests and responses of the `/api/admin`
+//! endpoints, which manage sessions, plugins, degraded mode, command
+//! policies, garbage collection, database maintenance, blob integrity checks
+//! and signing keys, and report slow queries.
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use squirrel_commands::policy::{PolicyEffect, PolicyScope, PolicySubject};
+use squirrel_core::blob::GcReport;
+use uuid::Uuid;
+
+use crate::admin::{AdminFlags, DatabaseGcReport};
+use crate::auth::signing_keys::SigningKeyInfo;
+use crate::config::Config;
+use crate::db::ReplicaStatus;
+use crate::integrity::VerificationReport;
+use crate::maintenance::MaintenanceReport;
+use crate::read_cache::CacheStats;
+
+/// Default number of audit log entries returned
+pub const DEFAULT_AUDIT_LIMIT: usize = 100;
+
+/// Filter of the session list
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct SessionsQuery {
+    /// Only list the sessions of this user
+    pub user_id: Option<Uuid>,
+}
+
+/// Turn degraded mode on or off
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DegradedModeRequest {
+    /// Whether the server refuses changes
+    pub enabled: bool,
+    /// Reason shown to refused clients
+    #[serde(default)]
+    pub reason: Option<String>,
+}
+
+/// Why a plugin is turned off
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct DisablePluginRequest {
+    /// Reason shown when its commands are refused
+    #[serde(default)]
+    pub reason: Option<String>,
+}
+
+/// Filter of the command policy list
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct PoliciesQuery {
+    /// Only list the policies of this subject, e.g. `user:<id>`
+    pub subject: Option<PolicySubject>,
+}
+
+/// Attach a command policy to a user or API key
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AddPolicyRequest {
+    /// User the policy applies to, written `user:<id>`; `api_key:<id>`
+    /// subjects are refused until the server authenticates API keys
+    pub subject: PolicySubject,
+    /// Whether matching targets are allowed or denied
+    pub effect: PolicyEffect,
+    /// What the pattern is matched against; `command` if not given
+    #[serde(default)]
+    pub scope: PolicyScope,
+    /// Glob pattern of command names, namespaces or tool IDs
+    pub pattern: String,
+    /// Why the policy exists
+    #[serde(default)]
+    pub description: Option<String>,
+}
+
+/// Options of a garbage collection
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize)]
+pub struct GcRequest {
+    /// Only report the blobs that would be deleted, leaving the database alone
+    #[serde(default)]
+    pub dry_run: bool,
+}
+
+/// What a garbage collection removed
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct GcResponse {
+    /// Rows deleted from the web database; not set for a dry run
+    pub database: Option<DatabaseGcReport>,
+    /// Blobs deleted from the blob store, if one is configured
+    pub blobs: Option<GcReport>,
+}
+
+/// The configuration and state the server runs with
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RuntimeResponse {
+    /// Active configuration
+    pub config: Config,
+    /// Degraded mode, disabled plugins and command policies
+    pub flags: AdminFlags,
+    /// Stored access token signing keys, without their secrets
+    pub signing_keys: Vec<SigningKeyInfo>,
+    /// Whether this instance is the leader
+    pub leader: bool,
+    /// Hits, misses and evictions of the read caches
+    pub caches: Vec<CacheStats>,
+    /// Health and lag of the read replicas of the web database
+    #[serde(default)]
+    pub replicas: Vec<ReplicaStatus>,
+}
+
+/// Options of a maintenance run started by an admin
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct MaintenanceRequest {
+    /// Only report what would be deleted
+    pub dry_run: bool,
+}
+
+/// Schedule and latest run of the database maintenance
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MaintenanceStatus {
+    /// When runs start, as a cron expression in UTC
+    pub schedule: String,
+    /// When the next scheduled run starts, if runs are scheduled
+    pub next_run: Option<DateTime<Utc>>,
+    /// Report of the latest run on this instance
+    pub last_run: Option<MaintenanceReport>,
+}
+
+/// Options of an integrity check started by an admin
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct IntegrityRequest {
+    /// Number of blobs to hash; every blob if not set
+    pub sample: Option<usize>,
+}
+
+/// Schedule and latest run of the blob integrity checks
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct IntegrityStatus {
+    /// When checks start, as a cron expression in UTC
+    pub schedule: String,
+    /// When the next scheduled check starts, if checks are scheduled
+    pub next_run: Option<DateTime<Utc>>,
+    /// Report of the latest check on this instance
+    pub last_run: Option<VerificationReport>,
+}
+
+/// Number of slow queries listed when the request does not say
+pub const DEFAULT_SLOW_QUERY_LIMIT: usize = 50;
+
+/// Size of the slow query page
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct SlowQueriesQuery {
+    /// Number of statements, newest first
+    pub limit: Option<usize>,
+}
+
+/// Size of the audit log page
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct AuditQuery {
+    /// Number of entries, newest first
+    pub limit: Option<usize>,
+}
@@ -0,0 +1,61 @@
+//! Agent API data models.
+//!
+//! This module contains all data models related to the remote agent API functionality.
+
+use serde::{Deserialize, Serialize};
+use squirrel_commands::wire::WireContext;
+use squirrel_commands::workflow::StepKind;
+
+use crate::agents::{Affinity, AgentInfo, TaskRequest};
+
+/// Request to run a task on a remote agent
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SubmitAgentTaskRequest {
+    /// Whether the target is a command or a tool
+    #[serde(default = "default_kind")]
+    pub kind: StepKind,
+    /// Command or tool name
+    pub target: String,
+    /// Arguments
+    #[serde(default)]
+    pub args: Vec<String>,
+    /// Placement rules
+    #[serde(default)]
+    pub affinity: Affinity,
+    /// Maximum run time in seconds
+    #[serde(default)]
+    pub timeout_secs: Option<u64>,
+}
+
+fn default_kind() -> StepKind {
+    StepKind::Command
+}
+
+impl From<SubmitAgentTaskRequest> for TaskRequest {
+    fn from(request: SubmitAgentTaskRequest) -> Self {
+        Self {
+            kind: request.kind,
+            target: request.target,
+            args: request.args,
+            affinity: request.affinity,
+            timeout_secs: request.timeout_secs,
+            context: WireContext::default(),
+        }
+    }
+}
+
+/// Response for a submitted agent task
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SubmitAgentTaskResponse {
+    /// Task ID
+    pub id: String,
+    /// URL to check task status
+    pub status_url: String,
+}
+
+/// Response for listing connected agents
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AgentListResponse {
+    /// Connected agents, oldest connection first
+    pub agents: Vec<AgentInfo>,
+}
@@ -0,0 +1,47 @@
+//! Alert API data models.
+//!
+//! This module contains all data models related to the alert API functionality.
+
+use serde::{Deserialize, Serialize};
+use squirrel_monitoring::alerts::{AlertGroup, Silence, TrackedAlert};
+
+/// Firing alerts
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AlertListResponse {
+    /// Alerts, most recently fired first
+    pub alerts: Vec<TrackedAlert>,
+}
+
+/// Firing alerts grouped by the configured labels
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AlertGroupListResponse {
+    /// Groups, most severe first
+    pub groups: Vec<AlertGroup>,
+}
+
+/// Request to silence alerts
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CreateSilenceRequest {
+    /// Label matchers as `name=value` or `name!=value`; all must match
+    pub matchers: Vec<String>,
+    /// How long the silence lasts, in seconds
+    pub duration_secs: u64,
+    /// Why the alerts are silenced
+    #[serde(default)]
+    pub comment: Option<String>,
+}
+
+/// Query parameters for listing silences
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct SilenceListQuery {
+    /// Include silences that have ended
+    #[serde(default)]
+    pub include_expired: bool,
+}
+
+/// Silences
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SilenceListResponse {
+    /// Silences in creation order
+    pub silences: Vec<Silence>,
+}
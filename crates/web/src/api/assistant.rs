@@ -0,0 +1,89 @@
+//! Assistant API data models.
+//!
+//! This module contains all data models related to asking the assistant to
+//! plan and run tools and commands, and to the cost and audit trail of the
+//! calls it makes.
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use squirrel_mcp::ai::{Plan, TargetKind};
+use squirrel_monitoring::llm_costs::{BudgetStatus, LlmCostSummary};
+use uuid::Uuid;
+
+/// Request to the assistant
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AssistantRequest {
+    /// What the user wants, in their own words
+    pub message: String,
+    /// Conversation to continue; a new one is started otherwise
+    #[serde(default)]
+    pub conversation_id: Option<Uuid>,
+    /// Run the planned steps instead of only returning them
+    #[serde(default)]
+    pub execute: bool,
+}
+
+/// Answer of the assistant
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AssistantResponse {
+    /// Conversation the request was added to
+    pub conversation_id: Uuid,
+    /// What the assistant said
+    pub reply: String,
+    /// Steps the assistant proposes, and those it refused
+    pub plan: Plan,
+    /// Outcome of each step run, if `execute` was set
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub executed: Vec<ExecutedStep>,
+    /// Request ID the steps ran under, the ID of their trace
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub request_id: Option<String>,
+}
+
+/// Outcome of running a planned step
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ExecutedStep {
+    /// Tool capability or command
+    pub kind: TargetKind,
+    /// Command name, or `tool_id/capability`
+    pub target: String,
+    /// ID of the command started, for command steps
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub command_id: Option<String>,
+    /// Output of a tool step
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub output: Option<Value>,
+    /// Why the step failed; later steps are not run
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+/// Query parameters for language model costs and audit records
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct AssistantCallQueryParams {
+    /// Only calls at or after this time
+    #[serde(default)]
+    pub from: Option<DateTime<Utc>>,
+    /// Only calls before this time
+    #[serde(default)]
+    pub to: Option<DateTime<Utc>>,
+    /// Only this user; other users' calls need the admin role
+    #[serde(default)]
+    pub user: Option<String>,
+    /// Only this provider, for costs
+    #[serde(default)]
+    pub provider: Option<String>,
+    /// Only this conversation, for audit records
+    #[serde(default)]
+    pub conversation_id: Option<Uuid>,
+}
+
+/// Cost of language model calls per user and provider
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AssistantCostResponse {
+    /// Calls summed per user and provider
+    pub summaries: Vec<LlmCostSummary>,
+    /// The caller's spending against the budgets that apply to them
+    pub budgets: Vec<BudgetStatus>,
+}
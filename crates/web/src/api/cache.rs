@@ -0,0 +1,45 @@
+//! Result cache API data models.
+//!
+//! This module contains all data models related to inspecting and
+//! invalidating cached command results.
+
+use serde::{Deserialize, Serialize};
+use squirrel_commands::cache::CachedResult;
+
+/// Query parameters for listing cached results
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct CacheListQuery {
+    /// Only list results of this command
+    #[serde(default)]
+    pub command: Option<String>,
+}
+
+/// Cached results, newest first
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CacheListResponse {
+    /// Cached results
+    pub entries: Vec<CachedResult>,
+}
+
+/// Request to remove cached results
+///
+/// Exactly one selector must be given.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct InvalidateCacheRequest {
+    /// Remove the results of runs that read this file
+    #[serde(default)]
+    pub input: Option<String>,
+    /// Remove the results of this command
+    #[serde(default)]
+    pub command: Option<String>,
+    /// Remove all results
+    #[serde(default)]
+    pub all: bool,
+}
+
+/// Cached results that were removed
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct InvalidateCacheResponse {
+    /// Keys of the removed results
+    pub removed: Vec<String>,
+}
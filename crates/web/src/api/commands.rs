@@ -4,6 +4,7 @@
 
 use serde::{Deserialize, Serialize};
 use chrono::{DateTime, Utc};
+use squirrel_commands::palette::SearchHit;
 
 /// Command definition
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -105,6 +106,9 @@ pub struct CreateCommandRequest {
     pub command: String,
     /// Command parameters
     pub parameters: serde_json::Value,
+    /// Job whose MCP context the command executes in
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub job_id: Option<String>,
 }
 
 /// Response for a created command
@@ -146,6 +150,13 @@ pub struct CommandListResponse {
     pub commands: Vec<CommandDefinition>,
 }
 
+/// Command palette search response
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CommandSearchResponse {
+    /// Matching commands, best first
+    pub results: Vec<SearchHit>,
+}
+
 /// Command history response
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct CommandHistoryResponse {
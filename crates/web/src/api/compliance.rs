@@ -0,0 +1,18 @@
+//! Compliance API data models.
+//!
+//! This module contains the request body of erasing a user's data.
+
+use serde::{Deserialize, Serialize};
+
+use crate::compliance::ErasureMode;
+
+/// Request to erase a user's data
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct EraseUserRequest {
+    /// How the data is erased, anonymize by default
+    #[serde(default)]
+    pub mode: ErasureMode,
+    /// Only report what would be erased
+    #[serde(default)]
+    pub dry_run: bool,
+}
@@ -0,0 +1,29 @@
+//! Context API data models.
+//!
+//! This module contains all data models related to the context API functionality.
+
+use serde::{Deserialize, Serialize};
+use squirrel_mcp::Context;
+
+/// Query parameters for searching contexts
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ContextSearchQuery {
+    /// Search query, e.g. `params.dataset = GRCh38 and tool ~ bwa`
+    pub q: String,
+    /// Maximum number of contexts to return
+    #[serde(default = "default_search_limit")]
+    pub limit: usize,
+}
+
+fn default_search_limit() -> usize {
+    50
+}
+
+/// Contexts matching a search
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ContextSearchResponse {
+    /// Matching contexts, most recently updated first
+    pub contexts: Vec<Context>,
+    /// Number of matching contexts before the limit was applied
+    pub total: usize,
+}
@@ -1,12 +1,22 @@
 use axum::{
-    http::StatusCode,
+    http::{header, StatusCode},
     response::{IntoResponse, Response},
     Json,
 };
 use serde_json::json;
+use squirrel_commands::CommandError;
+use squirrel_core::deadline::DeadlineExceeded;
+use squirrel_core::error::{CodedError, ErrorCode};
+use squirrel_mcp::tool::ToolError;
 use uuid::Uuid;
 use chrono::Utc;
 
+use crate::db::resilience::unavailable_message;
+use crate::i18n;
+
+/// Media type of coded error responses (RFC 9457)
+pub const PROBLEM_JSON: &str = "application/problem+json";
+
 /// Application error type
 #[derive(Debug, thiserror::Error)]
 pub enum AppError {
@@ -45,6 +55,28 @@ pub enum AppError {
     /// Generic error with custom status code
     #[error("{0}")]
     Custom(StatusCode, String),
+
+    /// Error with a shared error code, answered as `application/problem+json`
+    #[error("{0}")]
+    Coded(#[from] CodedError),
+}
+
+impl From<CommandError> for AppError {
+    fn from(err: CommandError) -> Self {
+        AppError::Coded(CodedError::from_error(err))
+    }
+}
+
+impl From<ToolError> for AppError {
+    fn from(err: ToolError) -> Self {
+        AppError::Coded(CodedError::from_error(err))
+    }
+}
+
+impl From<DeadlineExceeded> for AppError {
+    fn from(err: DeadlineExceeded) -> Self {
+        AppError::Coded(CodedError::from_error(err))
+    }
 }
 
 impl From<anyhow::Error> for AppError {
@@ -53,6 +85,43 @@ impl From<anyhow::Error> for AppError {
     }
 }
 
+/// Answer a coded error as a problem details object
+///
+/// The `type` URI and `code` name the error code; server errors are logged
+/// with their cause chain, which is not sent to the client.
+fn problem_response(err: CodedError) -> Response {
+    let code = err.code();
+    let status = StatusCode::from_u16(code.http_status()).unwrap_or(StatusCode::INTERNAL_SERVER_ERROR);
+    let request_id = Uuid::new_v4().to_string();
+    if status.is_server_error() {
+        tracing::error!(request_id = %request_id, code = %code, causes = ?err.causes(), "{}", err);
+    }
+
+    let locale = i18n::request_locale();
+    let title = i18n::message(&format!("api-error-{}", code.as_str().replace('_', "-")), &[]);
+    let body = json!({
+        "type": format!("urn:squirrel:error:{}", code),
+        "title": title,
+        "status": status.as_u16(),
+        "detail": err.message(),
+        "code": code,
+        "hint": err.hint(),
+        "retryable": code.is_retryable(),
+        "requestId": request_id,
+        "timestamp": Utc::now().to_rfc3339()
+    });
+
+    (
+        status,
+        [
+            (header::CONTENT_TYPE, PROBLEM_JSON.to_string()),
+            (header::CONTENT_LANGUAGE, locale.to_string()),
+        ],
+        body.to_string(),
+    )
+        .into_response()
+}
+
 impl IntoResponse for AppError {
     fn into_response(self) -> Response {
         let (status, error_code, message) = match self {
@@ -91,6 +160,11 @@ impl IntoResponse for AppError {
                 "internal_error", 
                 msg
             ),
+            // The database being down or busy is not the request's fault
+            AppError::Database(err) if unavailable_message(&err).is_some() => {
+                let message = unavailable_message(&err).unwrap_or_default();
+                return problem_response(CodedError::new(ErrorCode::Unavailable, message));
+            }
             AppError::Database(err) => (
                 StatusCode::INTERNAL_SERVER_ERROR, 
                 "database_error", 
@@ -101,16 +175,22 @@ impl IntoResponse for AppError {
                 "custom_error", 
                 msg
             ),
+            AppError::Coded(err) => return problem_response(err),
         };
 
         // Generate a request ID for tracking
         let request_id = Uuid::new_v4().to_string();
         let timestamp = Utc::now().to_rfc3339();
 
+        // The code and message stay stable; the title is for people
+        let locale = i18n::request_locale();
+        let title = i18n::message(&format!("api-error-{}", error_code.replace('_', "-")), &[]);
+
         let body = Json(json!({
             "success": false,
             "error": {
                 "code": error_code,
+                "title": title,
                 "message": message,
                 "details": null
             },
@@ -121,6 +201,30 @@ impl IntoResponse for AppError {
             }
         }));
 
-        (status, body).into_response()
+        (status, [(header::CONTENT_LANGUAGE, locale.to_string())], body).into_response()
     }
-} 
\ No newline at end of file
+} 
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use axum::body::HttpBody;
+
+    #[tokio::test]
+    async fn test_coded_errors_are_problem_details() {
+        let response = AppError::from(CommandError::CommandNotFound("algn".to_string())).into_response();
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+        assert_eq!(response.headers()[header::CONTENT_TYPE], PROBLEM_JSON);
+
+        let body = response.into_body().data().await.unwrap().unwrap();
+        let body: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(body["type"], "urn:squirrel:error:not_found");
+        assert_eq!(body["code"], "not_found");
+        assert_eq!(body["status"], 404);
+        assert_eq!(body["detail"], "Command not found: algn");
+        assert_eq!(body["hint"], "Run `squirrel help` to list the available commands");
+
+        let response = AppError::from(ToolError::TooManyErrors("blast".to_string())).into_response();
+        assert_eq!(response.status(), StatusCode::SERVICE_UNAVAILABLE);
+    }
+}
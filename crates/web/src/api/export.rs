@@ -0,0 +1,28 @@
+//! Export API data models.
+//!
+//! This module contains the query parameters of CSV and Parquet extracts of
+//! jobs, command history, usage and experiments.
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+use crate::export::{ExportDataset, ExportFormat};
+
+/// Query parameters for an export
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ExportQueryParams {
+    /// Dataset to extract: jobs, commands, usage or experiments
+    pub dataset: ExportDataset,
+    /// Encoding of the output, csv by default
+    #[serde(default)]
+    pub format: ExportFormat,
+    /// Only rows from this time on
+    #[serde(default)]
+    pub from: Option<DateTime<Utc>>,
+    /// Only rows before this time
+    #[serde(default)]
+    pub to: Option<DateTime<Utc>>,
+    /// Only this user's rows; other users' rows need the admin role
+    #[serde(default)]
+    pub user: Option<String>,
+}
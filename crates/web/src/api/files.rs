@@ -0,0 +1,102 @@
+//! File transfer API data models.
+//!
+//! This module contains all data models related to resumable uploads and
+//! file downloads.
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use squirrel_core::blob::StorageUsage;
+
+/// Request to start a resumable upload
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CreateUploadRequest {
+    /// Virtual path the file is stored at, e.g. `workspace://datasets/reads.fastq`
+    pub path: String,
+    /// Total size of the file in bytes
+    pub size: u64,
+    /// Expected SHA-256 of the whole file, as hex; checked on completion
+    #[serde(default)]
+    pub sha256: Option<String>,
+    /// Replace an existing file at `path`
+    #[serde(default)]
+    pub overwrite: bool,
+}
+
+/// State of an upload
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum UploadState {
+    /// More bytes are expected
+    Pending,
+    /// All bytes were received, verified and stored
+    Completed,
+}
+
+/// Upload as returned by the API
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct UploadResponse {
+    /// Upload ID
+    pub id: String,
+    /// Virtual path the file is stored at
+    pub path: String,
+    /// Total size of the file in bytes
+    pub size: u64,
+    /// Number of bytes received; the next chunk must start here
+    pub offset: u64,
+    /// State of the upload
+    pub state: UploadState,
+    /// SHA-256 of the stored file, once completed
+    pub sha256: Option<String>,
+    /// When the upload expires unless it is completed
+    pub expires_at: DateTime<Utc>,
+}
+
+/// Query selecting a file
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FileQuery {
+    /// Virtual path of the file
+    pub path: String,
+}
+
+/// File metadata as returned by the API
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FileInfoResponse {
+    /// Virtual path of the file
+    pub path: String,
+    /// Size of the file in bytes
+    pub size: u64,
+    /// SHA-256 of the file contents, as hex
+    pub sha256: String,
+    /// Last modification time
+    pub modified_at: Option<DateTime<Utc>>,
+}
+
+/// Blob store usage as returned by the API
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct StorageUsageResponse {
+    /// Number of stored blobs
+    pub blobs: u64,
+    /// Bytes taken by the stored blobs
+    pub stored_bytes: u64,
+    /// Number of files referring to blobs
+    pub references: u64,
+    /// Bytes the files would take without deduplication
+    pub referenced_bytes: u64,
+    /// Bytes of blobs no file refers to, freed by `squirrel blob gc`
+    pub unreferenced_bytes: u64,
+    /// Bytes saved by storing identical content once
+    pub saved_bytes: u64,
+}
+
+impl From<StorageUsage> for StorageUsageResponse {
+    fn from(usage: StorageUsage) -> Self {
+        Self {
+            saved_bytes: usage.saved_bytes(),
+            blobs: usage.blobs,
+            stored_bytes: usage.stored_bytes,
+            references: usage.references,
+            referenced_bytes: usage.referenced_bytes,
+            unreferenced_bytes: usage.unreferenced_bytes,
+        }
+    }
+}
@@ -0,0 +1,62 @@
+//! Log aggregation API data models.
+//!
+//! This module contains all data models related to forwarding log events to
+//! the server and searching them.
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+use crate::logs::{LogEvent, LogLevel, NewLogEvent};
+
+/// A batch of forwarded log events
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LogBatch {
+    /// Events, in the order they were emitted
+    pub events: Vec<NewLogEvent>,
+}
+
+/// Outcome of forwarding a batch
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LogBatchResponse {
+    /// Number of events stored
+    pub accepted: usize,
+}
+
+/// Query parameters for searching log events
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct LogSearchParams {
+    /// Only events of this level or more severe
+    #[serde(default)]
+    pub level: Option<LogLevel>,
+    /// Only events of this component or its subcomponents
+    #[serde(default)]
+    pub component: Option<String>,
+    /// Only events from this machine or agent
+    #[serde(default)]
+    pub source: Option<String>,
+    /// Only events of this run
+    #[serde(default)]
+    pub run_id: Option<String>,
+    /// Only events emitted from this time on
+    #[serde(default)]
+    pub from: Option<DateTime<Utc>>,
+    /// Only events emitted before this time
+    #[serde(default)]
+    pub to: Option<DateTime<Utc>>,
+    /// Only events whose message contains this text
+    #[serde(default)]
+    pub q: Option<String>,
+    /// Only events forwarded by this user or agent; others' events need the admin role
+    #[serde(default)]
+    pub user: Option<String>,
+    /// Most events returned
+    #[serde(default)]
+    pub limit: Option<usize>,
+}
+
+/// Log events matching a search, most recent first
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LogSearchResponse {
+    /// Matching events
+    pub events: Vec<LogEvent>,
+}
@@ -9,6 +9,24 @@ use axum::Json;
 
 pub mod error;
 pub mod commands;
+pub mod workflows;
+pub mod agents;
+pub mod webhooks;
+pub mod plugins;
+pub mod alerts;
+pub mod assistant;
+pub mod contexts;
+pub mod tools;
+pub mod files;
+pub mod cache;
+pub mod usage;
+pub mod rollout;
+pub mod tags;
+pub mod admin;
+pub mod users;
+pub mod export;
+pub mod logs;
+pub mod compliance;
 
 /// API Response envelope for standardized responses
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -110,6 +128,12 @@ pub struct CreateJobRequest {
     pub name: String,
     /// Job parameters
     pub parameters: serde_json::Value,
+    /// Reproducibility manifest of the run, as written by `--capture-manifest`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub manifest: Option<serde_json::Value>,
+    /// Existing MCP context to run the job in; a new one is allocated otherwise
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub context_id: Option<String>,
 }
 
 /// Response model for a created job
@@ -121,6 +145,23 @@ pub struct CreateJobResponse {
     pub name: String,
     /// Job status
     pub status: JobState,
+    /// MCP context the job's commands execute in
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub context_id: Option<String>,
+}
+
+/// Request to record how a job ended
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FinishJobRequest {
+    /// Final state, `Completed` or `Failed`
+    pub status: JobState,
+}
+
+/// Query selecting how a job report is rendered
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct JobReportQuery {
+    /// `html` (the default) or `markdown`
+    pub format: Option<String>,
 }
 
 /// Job status information
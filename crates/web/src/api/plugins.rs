@@ -0,0 +1,23 @@
+//! Plugin API data models.
+//!
+//! This module contains all data models related to the plugin API functionality.
+
+use serde::{Deserialize, Serialize};
+use squirrel_commands::plugin_logs::LogLine;
+
+/// Query parameters for reading plugin logs
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct PluginLogsQuery {
+    /// Number of most recent lines to return; all buffered lines when omitted
+    #[serde(default)]
+    pub lines: Option<usize>,
+}
+
+/// Recent log output of a plugin
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PluginLogsResponse {
+    /// Plugin name
+    pub plugin: String,
+    /// Log lines, oldest first
+    pub lines: Vec<LogLine>,
+}
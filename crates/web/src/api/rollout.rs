@@ -0,0 +1,30 @@
+//! Configuration rollout API data models.
+//!
+//! This module contains the requests of the `/api/admin/config` endpoints,
+//! which stage, canary, promote and roll back server configurations.
+
+use serde::{Deserialize, Serialize};
+
+use crate::config::Config;
+
+/// A configuration to validate or propose
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ConfigCandidateRequest {
+    /// The whole configuration; settings left out keep their defaults
+    pub config: Config,
+}
+
+/// Share of clients the candidate serves
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+pub struct CanaryRequest {
+    /// Percent of clients, 0 to 100
+    pub percent: u8,
+}
+
+/// Options of a promotion
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize)]
+pub struct PromoteRequest {
+    /// Promote even if the canary has not passed the gates
+    #[serde(default)]
+    pub force: bool,
+}
@@ -0,0 +1,65 @@
+//! Tag API data models.
+//!
+//! This module contains all data models related to labeling jobs, command
+//! definitions, experiments and datasets with tags and finding them by tag.
+
+use serde::{Deserialize, Serialize};
+use squirrel_core::tags::{ResourceKind, Tag};
+
+use crate::tags::{TagUsage, TaggedResource};
+
+/// Tags in use, with the number of resources having each
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TagListResponse {
+    /// Tags, ordered by key and value
+    pub tags: Vec<TagUsage>,
+}
+
+/// Query parameters for finding resources by tag
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct FindTaggedQuery {
+    /// Only find resources of this kind
+    #[serde(default)]
+    pub kind: Option<ResourceKind>,
+    /// Comma-separated tags the resources must all have, e.g. `experiment=rnaseq,urgent`
+    #[serde(default)]
+    pub tag: Option<String>,
+}
+
+/// Resources matching a tag filter
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TaggedResourcesResponse {
+    /// Matching resources with their tags
+    pub resources: Vec<TaggedResource>,
+}
+
+/// Request to add tags to a resource
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AddTagsRequest {
+    /// Tags to add, e.g. `["experiment=rnaseq"]`
+    pub tags: Vec<Tag>,
+}
+
+/// Query parameters for removing tags from a resource
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct RemoveTagsQuery {
+    /// Comma-separated tags to remove; a key alone removes all its values
+    #[serde(default)]
+    pub tag: Option<String>,
+}
+
+/// Request to add and remove tags on every resource matching a filter
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct BulkTagRequest {
+    /// Only change resources of this kind
+    #[serde(default)]
+    pub kind: Option<ResourceKind>,
+    /// Tags the changed resources must all have; at least one is required
+    pub filter: Vec<Tag>,
+    /// Tags to add
+    #[serde(default)]
+    pub add: Vec<Tag>,
+    /// Tags to remove; a key alone removes all its values
+    #[serde(default)]
+    pub remove: Vec<Tag>,
+}
@@ -0,0 +1,31 @@
+//! Tool API data models.
+//!
+//! This module contains all data models related to the tool API functionality.
+
+use serde::{Deserialize, Serialize};
+use squirrel_mcp::tool::ExecutionRecord;
+
+/// Query parameters for a tool's execution history
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ToolHistoryQuery {
+    /// Maximum number of executions to return
+    #[serde(default = "default_history_limit")]
+    pub limit: usize,
+}
+
+fn default_history_limit() -> usize {
+    50
+}
+
+/// Recent executions of a tool
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ToolHistoryResponse {
+    /// Tool ID
+    pub tool_id: String,
+    /// Executions, most recent first
+    pub executions: Vec<ExecutionRecord>,
+    /// Most recent execution that did not succeed
+    pub last_error: Option<ExecutionRecord>,
+    /// Number of recorded executions before the limit was applied
+    pub total: usize,
+}
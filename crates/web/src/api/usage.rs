@@ -0,0 +1,55 @@
+//! Usage accounting API data models.
+//!
+//! This module contains all data models related to resource usage records
+//! and quotas.
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use squirrel_monitoring::accounting::{QuotaStatus, ResourceUsage, UsageRecord};
+
+/// Query parameters for usage records
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct UsageQueryParams {
+    /// Only periods ending after this time
+    #[serde(default)]
+    pub from: Option<DateTime<Utc>>,
+    /// Only periods starting before this time
+    #[serde(default)]
+    pub to: Option<DateTime<Utc>>,
+    /// Only this user; other users' usage needs the admin role
+    #[serde(default)]
+    pub user: Option<String>,
+    /// Only this workspace
+    #[serde(default)]
+    pub workspace: Option<String>,
+}
+
+/// Total usage of a user in a workspace
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct UsageTotal {
+    /// User
+    pub user: String,
+    /// Workspace
+    pub workspace: String,
+    /// Resources used
+    #[serde(flatten)]
+    pub usage: ResourceUsage,
+}
+
+/// Usage records with their totals
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct UsageResponse {
+    /// Records per period, oldest first
+    pub records: Vec<UsageRecord>,
+    /// Totals per user and workspace over the selected periods
+    pub totals: Vec<UsageTotal>,
+}
+
+/// Usage against the quotas that apply to a user in a workspace
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct QuotaStatusResponse {
+    /// Workspace the quotas were checked for
+    pub workspace: String,
+    /// Usage against each quota
+    pub quotas: Vec<QuotaStatus>,
+}
@@ -0,0 +1,119 @@
+//! User and team API data models.
+//!
+//! This module contains the requests of the `/api/users` and `/api/teams`
+//! endpoints, and of accepting invitations and resetting passwords under
+//! `/api/auth`.
+
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use crate::auth::models::Role;
+use crate::users::{Team, TeamMember, TeamRole};
+
+fn default_role() -> Role {
+    Role::User
+}
+
+fn default_team_role() -> TeamRole {
+    TeamRole::Member
+}
+
+/// Filter of the user list
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct UsersQuery {
+    /// Also list deactivated accounts
+    #[serde(default)]
+    pub include_deactivated: bool,
+}
+
+/// Create an account
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CreateUserRequest {
+    /// Username
+    pub username: String,
+    /// Email address
+    pub email: String,
+    /// Initial password
+    pub password: String,
+    /// Role, `User` unless given
+    #[serde(default = "default_role")]
+    pub role: Role,
+}
+
+/// Change the role of an account
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SetRoleRequest {
+    /// New role
+    pub role: Role,
+}
+
+/// Invite someone to create an account
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct InviteUserRequest {
+    /// Email address of the new account
+    pub email: String,
+    /// Role of the new account, `User` unless given
+    #[serde(default = "default_role")]
+    pub role: Role,
+    /// Team the new account joins as a member
+    #[serde(default)]
+    pub team_id: Option<Uuid>,
+}
+
+/// Accept an invitation
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AcceptInvitationRequest {
+    /// Invitation token
+    pub token: String,
+    /// Username of the new account
+    pub username: String,
+    /// Password of the new account
+    pub password: String,
+}
+
+/// Set a new password with a reset token
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ResetPasswordRequest {
+    /// Password reset token
+    pub token: String,
+    /// New password
+    pub password: String,
+}
+
+/// Create a team
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CreateTeamRequest {
+    /// Unique name
+    pub name: String,
+    /// What the team works on
+    #[serde(default)]
+    pub description: Option<String>,
+}
+
+/// Rename a team or change its description
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct UpdateTeamRequest {
+    /// New name
+    #[serde(default)]
+    pub name: Option<String>,
+    /// New description
+    #[serde(default)]
+    pub description: Option<String>,
+}
+
+/// Add a member to a team or change their role
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SetMemberRequest {
+    /// Role within the team, `member` unless given
+    #[serde(default = "default_team_role")]
+    pub role: TeamRole,
+}
+
+/// A team and its members
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TeamDetail {
+    /// The team
+    pub team: Team,
+    /// Its members, by the time they joined
+    pub members: Vec<TeamMember>,
+}
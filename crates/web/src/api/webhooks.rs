@@ -0,0 +1,109 @@
+//! Webhook API data models.
+//!
+//! This module contains all data models related to the Webhook API functionality.
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+use crate::handlers::webhooks::{Webhook, WebhookDelivery, WebhookEvent};
+
+/// Request to register a webhook
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CreateWebhookRequest {
+    /// URL deliveries are posted to
+    pub url: String,
+    /// Events to subscribe to, e.g. `command.failed`
+    pub events: Vec<WebhookEvent>,
+    /// Signing secret; generated when omitted
+    #[serde(default)]
+    pub secret: Option<String>,
+    /// Optional description
+    #[serde(default)]
+    pub description: Option<String>,
+    /// Template rendering the body of deliveries, which must produce JSON
+    #[serde(default)]
+    pub template: Option<String>,
+}
+
+/// Request to update a webhook; omitted fields are left unchanged
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct UpdateWebhookRequest {
+    /// New URL
+    #[serde(default)]
+    pub url: Option<String>,
+    /// New event subscriptions
+    #[serde(default)]
+    pub events: Option<Vec<WebhookEvent>>,
+    /// New signing secret
+    #[serde(default)]
+    pub secret: Option<String>,
+    /// New description
+    #[serde(default)]
+    pub description: Option<String>,
+    /// New payload template; empty to send the default payload
+    #[serde(default)]
+    pub template: Option<String>,
+    /// Enable or disable deliveries
+    #[serde(default)]
+    pub active: Option<bool>,
+}
+
+/// Webhook as returned by the API
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct WebhookResponse {
+    /// Webhook ID
+    pub id: String,
+    /// URL deliveries are posted to
+    pub url: String,
+    /// Subscribed events
+    pub events: Vec<WebhookEvent>,
+    /// Optional description
+    pub description: Option<String>,
+    /// Template rendering the body of deliveries
+    pub template: Option<String>,
+    /// Whether deliveries are sent
+    pub active: bool,
+    /// Created timestamp
+    pub created_at: DateTime<Utc>,
+    /// Updated timestamp
+    pub updated_at: DateTime<Utc>,
+}
+
+impl From<Webhook> for WebhookResponse {
+    fn from(webhook: Webhook) -> Self {
+        Self {
+            id: webhook.id,
+            url: webhook.url,
+            events: webhook.events,
+            description: webhook.description,
+            template: webhook.template,
+            active: webhook.active,
+            created_at: webhook.created_at,
+            updated_at: webhook.updated_at,
+        }
+    }
+}
+
+/// Response for a registered webhook, including its signing secret
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CreateWebhookResponse {
+    /// The registered webhook
+    #[serde(flatten)]
+    pub webhook: WebhookResponse,
+    /// Signing secret; only returned once
+    pub secret: String,
+}
+
+/// Response for listing webhooks
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct WebhookListResponse {
+    /// Webhooks, oldest first
+    pub webhooks: Vec<WebhookResponse>,
+}
+
+/// Response for listing webhook deliveries
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct WebhookDeliveryListResponse {
+    /// Deliveries, newest first
+    pub deliveries: Vec<WebhookDelivery>,
+}
@@ -0,0 +1,96 @@
+//! Workflow API data models.
+//!
+//! This module contains all data models related to the Workflow API functionality.
+
+use std::collections::HashMap;
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use squirrel_commands::workflow::{RunStatus, WorkflowRun};
+
+/// Request to start a workflow run
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CreateWorkflowRunRequest {
+    /// Workflow definition in YAML
+    pub definition: String,
+    /// Workflow inputs
+    #[serde(default)]
+    pub inputs: HashMap<String, String>,
+    /// Priority; under memory pressure, lower-priority runs are cancelled first
+    #[serde(default)]
+    pub priority: i32,
+}
+
+/// Response for a started workflow run
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CreateWorkflowRunResponse {
+    /// Run ID
+    pub id: String,
+    /// URL to check run status
+    pub status_url: String,
+}
+
+/// Request to validate a workflow definition
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ValidateWorkflowRequest {
+    /// Workflow definition in YAML
+    pub definition: String,
+}
+
+/// Response for a valid workflow definition
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ValidateWorkflowResponse {
+    /// Workflow name
+    pub name: String,
+    /// Step IDs in execution order
+    pub execution_order: Vec<String>,
+    /// Declared input names
+    pub inputs: Vec<String>,
+}
+
+/// Summary of a workflow run
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct WorkflowRunSummary {
+    /// Run ID
+    pub id: String,
+    /// Workflow name
+    pub workflow: String,
+    /// Run status
+    pub status: RunStatus,
+    /// Fraction of finished steps, between 0 and 1
+    pub progress: f32,
+    /// Created timestamp
+    pub created_at: DateTime<Utc>,
+    /// Finished timestamp
+    pub finished_at: Option<DateTime<Utc>>,
+}
+
+impl From<&WorkflowRun> for WorkflowRunSummary {
+    fn from(run: &WorkflowRun) -> Self {
+        Self {
+            id: run.id.clone(),
+            workflow: run.workflow.clone(),
+            status: run.status,
+            progress: run.progress(),
+            created_at: run.created_at,
+            finished_at: run.finished_at,
+        }
+    }
+}
+
+/// Response for listing workflow runs
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct WorkflowRunListResponse {
+    /// Workflow runs, newest first
+    pub runs: Vec<WorkflowRunSummary>,
+}
+
+/// Response for workflow run status
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct WorkflowRunResponse {
+    /// Fraction of finished steps, between 0 and 1
+    pub progress: f32,
+    /// Full run record including step states
+    #[serde(flatten)]
+    pub run: WorkflowRun,
+}
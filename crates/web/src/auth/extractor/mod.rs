@@ -18,6 +18,9 @@ pub struct AuthClaims {
     /// Roles assigned to the user
     #[serde(default)]
     pub roles: Vec<String>,
+    /// ID of the API key the request authenticated with, if it used one
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub api_key: Option<String>,
 }
 
 impl fmt::Display for AuthClaims {
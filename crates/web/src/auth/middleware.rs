@@ -8,9 +8,20 @@ use axum::{
 };
 use std::sync::Arc;
 
-use super::{AuthError, Role};
+use super::{extractor::AuthClaims, AuthError, Claims, Role};
 use crate::AppState;
 
+/// Claims for the handlers that take [`AuthClaims`], from verified `claims`
+fn auth_claims(state: &AppState, claims: &Claims) -> AuthClaims {
+    AuthClaims {
+        sub: claims.sub.to_string(),
+        iat: claims.exp - state.auth.config.jwt_expiration_minutes * 60,
+        exp: claims.exp,
+        roles: vec![claims.role.as_str().to_string()],
+        api_key: None,
+    }
+}
+
 /// Require authentication for a route
 pub async fn require_auth<B>(
     State(state): State<Arc<AppState>>,
@@ -25,6 +36,7 @@ pub async fn require_auth<B>(
         .ok_or(AuthError::InvalidCredentials)?;
 
     let claims = state.auth.verify_token(auth_header).await?;
+    req.extensions_mut().insert(auth_claims(&state, &claims));
     req.extensions_mut().insert(claims);
     
     Ok(next.run(req).await)
@@ -49,7 +61,48 @@ pub async fn require_admin<B>(
         return Err(AuthError::Unauthorized);
     }
 
+    req.extensions_mut().insert(auth_claims(&state, &claims));
     req.extensions_mut().insert(claims);
     
     Ok(next.run(req).await)
-} 
\ No newline at end of file
+} 
+#[cfg(test)]
+mod tests {
+    use axum::body::{Body, HttpBody};
+    use axum::http::{header, Request, StatusCode};
+    use tower::ServiceExt;
+    use uuid::Uuid;
+
+    use crate::auth::{AuthConfig, AuthService, Claims, Role};
+    use crate::config::Config;
+    use crate::create_app;
+    use crate::test_helpers::migrated_pool;
+
+    #[tokio::test]
+    async fn test_admin_routes_take_claims_from_the_bearer_token() {
+        let pool = migrated_pool().await;
+        let auth = AuthService::new(AuthConfig::default(), pool.clone());
+        let app = create_app(pool, Config::default()).await;
+        let token = |role| {
+            let exp = chrono::Utc::now().timestamp() + 600;
+            auth.sign(&Claims { sub: Uuid::new_v4(), role, exp }).unwrap()
+        };
+        let request = |token: Option<String>| {
+            let mut request = Request::builder().uri("/api/admin/policies");
+            if let Some(token) = token {
+                request = request.header(header::AUTHORIZATION, format!("Bearer {token}"));
+            }
+            request.body(Body::empty()).unwrap()
+        };
+
+        let response = app.clone().oneshot(request(Some(token(Role::Admin)))).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        let body = response.into_body().data().await.unwrap().unwrap();
+        let body: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(body["success"], true);
+        let response = app.clone().oneshot(request(Some(token(Role::User)))).await.unwrap();
+        assert_eq!(response.status(), StatusCode::FORBIDDEN);
+        let response = app.oneshot(request(None)).await.unwrap();
+        assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+    }
+}
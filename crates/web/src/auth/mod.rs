@@ -1,4 +1,8 @@
 //! Authentication and authorization for the web interface.
+//!
+//! Sessions are bearer tokens sent in the `Authorization` header, never
+//! cookies, so browsers do not attach them to cross-site requests and
+//! state-changing routes need no CSRF token.
 
 use axum::{
     async_trait,
@@ -7,11 +11,13 @@ use axum::{
     response::{IntoResponse, Response},
     Json,
 };
-use serde::{Deserialize, Serialize};
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
 use sqlx::SqlitePool;
 use uuid::Uuid;
-use chrono::{DateTime, Utc};
-use jsonwebtoken::{encode, decode, Header, Validation, EncodingKey, DecodingKey};
+use chrono::Utc;
+#[cfg(feature = "db")]
+use chrono::DateTime;
+use jsonwebtoken::{encode, decode, decode_header, Header, Validation, EncodingKey, DecodingKey};
 #[cfg(feature = "db")]
 use bcrypt;
 use std::sync::Arc;
@@ -21,14 +27,22 @@ pub mod models;
 pub mod routes;
 pub mod middleware;
 pub mod extractor;
+pub mod sessions;
+pub mod signing_keys;
+pub mod two_factor;
 
-use models::{User, Role, LoginRequest, RegisterRequest};
+use models::{User, Role, LoginRequest, RegisterRequest, TwoFactorEnrollment, TwoFactorStatus};
+use sessions::{AuthSession, SessionStore};
+use signing_keys::{SigningKeyInfo, SigningKeys};
+use two_factor::TwoFactorStore;
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct AuthConfig {
     pub jwt_secret: String,
     pub jwt_expiration_minutes: i64,
     pub refresh_token_expiration_days: i64,
+    #[serde(default)]
+    pub two_factor: TwoFactorConfig,
 }
 
 impl Default for AuthConfig {
@@ -37,10 +51,59 @@ impl Default for AuthConfig {
             jwt_secret: "your-secret-key".to_string(),
             jwt_expiration_minutes: 60,
             refresh_token_expiration_days: 30,
+            two_factor: TwoFactorConfig::default(),
         }
     }
 }
 
+/// Two-factor authentication policy
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct TwoFactorConfig {
+    /// Issuer shown by authenticator apps
+    pub issuer: String,
+    /// Roles that must use two-factor authentication to sign in
+    pub required_roles: Vec<Role>,
+    /// Seconds a login has to answer its two-factor challenge
+    pub challenge_ttl_seconds: i64,
+    /// Number of recovery codes issued at a time
+    pub recovery_codes: usize,
+}
+
+impl Default for TwoFactorConfig {
+    fn default() -> Self {
+        Self {
+            issuer: "Squirrel".to_string(),
+            required_roles: vec![Role::Admin],
+            challenge_ttl_seconds: 300,
+            recovery_codes: 10,
+        }
+    }
+}
+
+impl TwoFactorConfig {
+    /// Whether users with `role` must use two-factor authentication
+    pub fn is_required(&self, role: Role) -> bool {
+        self.required_roles.contains(&role)
+    }
+}
+
+/// Outcome of a correct password
+#[derive(Debug, Clone)]
+pub enum LoginOutcome {
+    /// Signed in with an access and a refresh token
+    Authenticated {
+        access_token: String,
+        refresh_token: String,
+    },
+    /// The login must be completed with a second factor
+    TwoFactorRequired {
+        challenge_token: String,
+        /// The user has to enroll first, as their role requires two factors
+        enrollment_required: bool,
+    },
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Claims {
     pub sub: Uuid,
@@ -48,24 +111,16 @@ pub struct Claims {
     pub exp: i64,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
-pub struct RefreshToken {
-    pub id: Uuid,
-    pub user_id: Uuid,
-    pub token: String,
-    pub expires_at: DateTime<Utc>,
-    pub created_at: DateTime<Utc>,
-}
-
 #[async_trait]
-impl<S> FromRequestParts<S> for Claims
-where
-    S: Send + Sync,
-{
+impl FromRequestParts<Arc<AppState>> for Claims {
     type Rejection = AuthError;
 
-    async fn from_request_parts(parts: &mut Parts, _state: &S) -> Result<Self, Self::Rejection> {
-        // This method is called when extracting Claims from a request
+    async fn from_request_parts(parts: &mut Parts, state: &Arc<AppState>) -> Result<Self, Self::Rejection> {
+        // Routes behind `require_auth` already carry verified claims
+        if let Some(claims) = parts.extensions.get::<Claims>() {
+            return Ok(claims.clone());
+        }
+
         // Get the authorization header
         let auth_header = parts
             .headers
@@ -78,10 +133,6 @@ where
             .strip_prefix("Bearer ")
             .ok_or(AuthError::InvalidToken)?;
 
-        // Extract app state to access auth service
-        let state = parts.extensions.get::<Arc<AppState>>()
-            .ok_or_else(|| AuthError::InternalError("AppState not found in request".to_string()))?;
-            
         // Verify token
         let claims = state.auth.verify_token(token).await?;
         
@@ -97,8 +148,20 @@ pub enum AuthError {
     InvalidToken,
     #[error("User not found")]
     UserNotFound,
+    #[error("Session not found")]
+    SessionNotFound,
+    #[error("Invalid two-factor code")]
+    InvalidTwoFactorCode,
+    #[error("Two-factor authentication required")]
+    TwoFactorRequired,
+    #[error("Two-factor authentication not enabled")]
+    TwoFactorNotEnabled,
+    #[error("Two-factor authentication already enabled")]
+    TwoFactorAlreadyEnabled,
     #[error("Invalid credentials")]
     InvalidCredentials,
+    #[error("Account deactivated")]
+    AccountDeactivated,
     #[error("Unauthorized")]
     Unauthorized,
     #[error("Database error: {0}")]
@@ -121,7 +184,13 @@ impl IntoResponse for AuthError {
             AuthError::MissingToken => (StatusCode::UNAUTHORIZED, "AUTH_MISSING_TOKEN", "Missing authentication token"),
             AuthError::InvalidToken => (StatusCode::UNAUTHORIZED, "AUTH_INVALID_TOKEN", "Invalid authentication token"),
             AuthError::UserNotFound => (StatusCode::NOT_FOUND, "AUTH_USER_NOT_FOUND", "User not found"),
+            AuthError::SessionNotFound => (StatusCode::NOT_FOUND, "AUTH_SESSION_NOT_FOUND", "Session not found"),
+            AuthError::InvalidTwoFactorCode => (StatusCode::UNAUTHORIZED, "AUTH_INVALID_2FA_CODE", "Invalid two-factor code"),
+            AuthError::TwoFactorRequired => (StatusCode::FORBIDDEN, "AUTH_2FA_REQUIRED", "Two-factor authentication required"),
+            AuthError::TwoFactorNotEnabled => (StatusCode::BAD_REQUEST, "AUTH_2FA_NOT_ENABLED", "Two-factor authentication not enabled"),
+            AuthError::TwoFactorAlreadyEnabled => (StatusCode::CONFLICT, "AUTH_2FA_ALREADY_ENABLED", "Two-factor authentication already enabled"),
             AuthError::InvalidCredentials => (StatusCode::UNAUTHORIZED, "AUTH_INVALID_CREDENTIALS", "Invalid credentials"),
+            AuthError::AccountDeactivated => (StatusCode::FORBIDDEN, "AUTH_ACCOUNT_DEACTIVATED", "Account deactivated"),
             AuthError::Unauthorized => (StatusCode::UNAUTHORIZED, "AUTH_UNAUTHORIZED", "Unauthorized"),
             AuthError::DatabaseError(_) => (StatusCode::INTERNAL_SERVER_ERROR, "AUTH_DATABASE_ERROR", "Database error"),
             AuthError::JwtError(_) => (StatusCode::INTERNAL_SERVER_ERROR, "AUTH_JWT_ERROR", "JWT error"),
@@ -154,139 +223,299 @@ impl IntoResponse for AuthError {
 pub struct AuthService {
     pub config: AuthConfig,
     pub pool: SqlitePool,
+    sessions: SessionStore,
+    two_factor: TwoFactorStore,
+    signing_keys: SigningKeys,
 }
 
 impl AuthService {
     pub fn new(config: AuthConfig, pool: SqlitePool) -> Self {
-        Self { config, pool }
+        let sessions = SessionStore::new(pool.clone());
+        let two_factor = TwoFactorStore::new(pool.clone());
+        let signing_keys = SigningKeys::new(pool.clone());
+        Self { config, pool, sessions, two_factor, signing_keys }
     }
     
     pub async fn verify_token(&self, token: &str) -> Result<Claims, AuthError> {
-        let token_data = decode::<Claims>(
+        let ttl = chrono::Duration::minutes(self.config.jwt_expiration_minutes);
+        self.verify_signed(token, ttl).await
+    }
+
+    /// Sign `claims` with the current signing key, or the configured secret
+    /// while no key is stored
+    pub fn sign<T: Serialize>(&self, claims: &T) -> Result<String, AuthError> {
+        let (header, secret) = match self.signing_keys.current() {
+            Some(key) => (Header { kid: Some(key.id), ..Header::default() }, key.secret),
+            None => (Header::default(), self.config.jwt_secret.clone()),
+        };
+        Ok(encode(&header, claims, &EncodingKey::from_secret(secret.as_ref()))?)
+    }
+
+    /// Verify a token signed with [`AuthService::sign`] that lives for at
+    /// most `ttl`, so keys retired longer ago are not accepted
+    pub async fn verify_signed<T: DeserializeOwned>(&self, token: &str, ttl: chrono::Duration) -> Result<T, AuthError> {
+        let secret = match decode_header(token)?.kid {
+            Some(kid) => {
+                let key = match self.signing_keys.verifying(&kid, ttl) {
+                    Some(key) => Some(key),
+                    // The key may have been rotated in by another instance
+                    None => {
+                        self.signing_keys.reload().await?;
+                        self.signing_keys.verifying(&kid, ttl)
+                    }
+                };
+                key.ok_or(AuthError::InvalidToken)?.secret
+            }
+            None if self.signing_keys.accepts_configured_secret(ttl) => self.config.jwt_secret.clone(),
+            None => return Err(AuthError::InvalidToken),
+        };
+        let token_data = decode::<T>(
             token,
-            &DecodingKey::from_secret(self.config.jwt_secret.as_ref()),
+            &DecodingKey::from_secret(secret.as_ref()),
             &Validation::default(),
         )?;
 
         Ok(token_data.claims)
     }
     
-    pub async fn generate_refresh_token(&self, user_id: Uuid) -> Result<String, AuthError> {
-        // In mock mode, just generate a token string
-        #[cfg(feature = "mock-db")]
-        {
-            Ok(format!("mock_refresh_token_{}", user_id))
+    /// Start a session for `user_id` on `device`, returning its refresh token
+    pub async fn generate_refresh_token(&self, user_id: Uuid, device: &str) -> Result<String, AuthError> {
+        let ttl = chrono::Duration::days(self.config.refresh_token_expiration_days);
+        let (_, token) = self.sessions.create(user_id, device, ttl).await?;
+        Ok(token)
+    }
+    
+    /// Exchange a refresh token for an access token and a new refresh token
+    ///
+    /// The presented refresh token can not be used again; presenting it a
+    /// second time revokes its session.
+    pub async fn refresh_access_token(&self, refresh_token: &str) -> Result<(Claims, String, String), AuthError> {
+        let ttl = chrono::Duration::days(self.config.refresh_token_expiration_days);
+        let (session, refresh_token) = self.sessions.rotate(refresh_token, ttl).await?;
+        let role = self.user_role(session.user_id).await?;
+        
+        // Sessions started before the role required two factors end here
+        if self.config.two_factor.is_required(role) && !self.two_factor.is_enabled(session.user_id).await? {
+            self.sessions.revoke(session.user_id, session.id).await?;
+            return Err(AuthError::TwoFactorRequired);
         }
-
-        #[cfg(feature = "db")]
-        {
-            let now = Utc::now();
-            let expiration = now + chrono::Duration::days(self.config.refresh_token_expiration_days);
-            let token = Uuid::new_v4().to_string();
-            let token_id = Uuid::new_v4();
-            
-            // Remove any existing refresh tokens for this user
-            sqlx::query!(
-                "DELETE FROM refresh_tokens WHERE user_id = ?",
-                user_id.to_string()
-            )
-            .execute(&self.pool)
-            .await?;
-            
-            // Store the refresh token
-            sqlx::query!(
-                "INSERT INTO refresh_tokens (id, user_id, token, expires_at, created_at) VALUES (?, ?, ?, ?, ?)",
-                token_id.to_string(),
-                user_id.to_string(),
-                token,
-                expiration,
-                now
-            )
-            .execute(&self.pool)
-            .await?;
-            
-            Ok(token)
+        
+        // Generate new access token
+        let access_token = self.generate_token(session.user_id, role).await?;
+        
+        // Create claims for response
+        let claims = Claims {
+            sub: session.user_id,
+            role,
+            exp: (Utc::now() + chrono::Duration::minutes(self.config.jwt_expiration_minutes)).timestamp(),
+        };
+        
+        Ok((claims, access_token, refresh_token))
+    }
+    
+    /// Active sessions of a user
+    pub async fn list_sessions(&self, user_id: Uuid) -> Result<Vec<AuthSession>, AuthError> {
+        self.sessions.list(user_id).await
+    }
+    
+    /// End one session of a user; returns whether it was active
+    pub async fn revoke_session(&self, user_id: Uuid, session_id: Uuid) -> Result<bool, AuthError> {
+        self.sessions.revoke(user_id, session_id).await
+    }
+    
+    /// End every session of a user; returns how many were active
+    ///
+    /// Access tokens already issued stay valid until they expire.
+    pub async fn logout_all(&self, user_id: Uuid) -> Result<u64, AuthError> {
+        self.sessions.revoke_all(user_id).await
+    }
+    
+    /// Active sessions of every user, or of one, most recently used first
+    pub async fn list_all_sessions(&self, user_id: Option<Uuid>) -> Result<Vec<AuthSession>, AuthError> {
+        match user_id {
+            Some(user_id) => self.sessions.list(user_id).await,
+            None => self.sessions.list_all().await,
         }
     }
     
-    pub async fn refresh_access_token(&self, refresh_token: &str) -> Result<(Claims, String), AuthError> {
-        #[cfg(feature = "mock-db")]
-        {
-            // In mock mode, only accept tokens with the correct format
-            if refresh_token.starts_with("mock_refresh_token_") {
-                let user_id = Uuid::parse_str("00000000-0000-0000-0000-000000000001").unwrap();
-                let role = Role::User;
-                
-                // Generate new access token
-                let access_token = self.generate_token(user_id, role).await?;
-                
-                // Create claims for response
-                let now = Utc::now();
-                let claims = Claims {
-                    sub: user_id,
-                    role,
-                    exp: (now + chrono::Duration::minutes(self.config.jwt_expiration_minutes)).timestamp(),
-                };
-                
-                Ok((claims, access_token))
-            } else {
-                Err(AuthError::InvalidToken)
+    /// End a session of any user; returns whether it was active
+    pub async fn kick_session(&self, session_id: Uuid) -> Result<bool, AuthError> {
+        self.sessions.kick(session_id).await
+    }
+    
+    /// Delete expired sessions and revoked refresh tokens, and signing keys
+    /// no longer verifying any token; returns how many rows were deleted
+    pub async fn prune(&self) -> Result<u64, AuthError> {
+        let ttl = chrono::Duration::minutes(self.config.jwt_expiration_minutes);
+        let sessions = self.sessions.prune().await?;
+        let keys = self.signing_keys.prune(Utc::now() - ttl).await?;
+        Ok(sessions + keys)
+    }
+    
+    /// Sign new access tokens with a new key, retiring the current one
+    ///
+    /// Tokens signed with the retired key stay valid until they expire.
+    pub async fn rotate_signing_key(&self) -> Result<SigningKeyInfo, AuthError> {
+        self.signing_keys.rotate().await
+    }
+    
+    /// The stored signing keys, without their secrets
+    pub fn signing_keys(&self) -> Vec<SigningKeyInfo> {
+        self.signing_keys.list()
+    }
+    
+    /// Pick up signing keys rotated by another instance or the CLI
+    pub async fn reload_signing_keys(&self) -> Result<(), AuthError> {
+        self.signing_keys.reload().await
+    }
+    
+    /// Finish a login whose password was correct
+    ///
+    /// Users with two-factor authentication enabled, or whose role requires
+    /// it, get a login challenge instead of tokens.
+    pub async fn start_login(&self, user_id: Uuid, role: Role, device: &str) -> Result<LoginOutcome, AuthError> {
+        let enabled = self.two_factor.is_enabled(user_id).await?;
+        if enabled || self.config.two_factor.is_required(role) {
+            let ttl = chrono::Duration::seconds(self.config.two_factor.challenge_ttl_seconds);
+            let challenge_token = self.two_factor.create_challenge(user_id, device, ttl).await?;
+            return Ok(LoginOutcome::TwoFactorRequired {
+                challenge_token,
+                enrollment_required: !enabled,
+            });
+        }
+        
+        Ok(LoginOutcome::Authenticated {
+            access_token: self.generate_token(user_id, role).await?,
+            refresh_token: self.generate_refresh_token(user_id, device).await?,
+        })
+    }
+    
+    /// Complete a login challenge with a TOTP or recovery code
+    ///
+    /// If the user was still enrolling, a correct code enables two-factor
+    /// authentication and the new recovery codes are returned as well.
+    pub async fn verify_two_factor(
+        &self,
+        challenge_token: &str,
+        code: &str,
+    ) -> Result<(Claims, String, String, Option<Vec<String>>), AuthError> {
+        let challenge = self.two_factor.challenge(challenge_token).await?;
+        let user_id = challenge.user_id;
+        
+        let verified = if self.two_factor.is_enabled(user_id).await? {
+            self.two_factor.verify(user_id, code).await.map(|valid| (valid, None))
+        } else {
+            self.two_factor
+                .confirm(user_id, code, self.config.two_factor.recovery_codes)
+                .await
+                .map(|codes| (true, Some(codes)))
+        };
+        let recovery_codes = match verified {
+            Ok((true, recovery_codes)) => recovery_codes,
+            Ok((false, _)) | Err(AuthError::InvalidTwoFactorCode) => {
+                self.two_factor.fail_challenge(challenge_token).await?;
+                return Err(AuthError::InvalidTwoFactorCode);
             }
+            Err(e) => return Err(e),
+        };
+        // A challenge completes one login, even if answered twice at once
+        if !self.two_factor.finish_challenge(challenge_token).await? {
+            return Err(AuthError::InvalidToken);
         }
-
+        
+        let role = self.user_role(user_id).await?;
+        let access_token = self.generate_token(user_id, role).await?;
+        let refresh_token = self.generate_refresh_token(user_id, &challenge.device).await?;
+        let claims = Claims {
+            sub: user_id,
+            role,
+            exp: (Utc::now() + chrono::Duration::minutes(self.config.jwt_expiration_minutes)).timestamp(),
+        };
+        
+        Ok((claims, access_token, refresh_token, recovery_codes))
+    }
+    
+    /// The user a pending login challenge belongs to
+    pub async fn challenge_user(&self, challenge_token: &str) -> Result<Uuid, AuthError> {
+        Ok(self.two_factor.challenge(challenge_token).await?.user_id)
+    }
+    
+    /// Start two-factor enrollment, returning the secret and its
+    /// provisioning URI
+    pub async fn enroll_two_factor(&self, user_id: Uuid) -> Result<TwoFactorEnrollment, AuthError> {
+        let user = self.get_user(user_id).await?;
+        let secret = self.two_factor.begin_enrollment(user_id).await?;
+        let otpauth_uri = two_factor::otpauth_uri(&self.config.two_factor.issuer, &user.username, &secret);
+        
+        Ok(TwoFactorEnrollment { secret, otpauth_uri })
+    }
+    
+    /// Enable two-factor authentication with the first code from the
+    /// authenticator app, returning recovery codes
+    pub async fn confirm_two_factor(&self, user_id: Uuid, code: &str) -> Result<Vec<String>, AuthError> {
+        self.two_factor
+            .confirm(user_id, code, self.config.two_factor.recovery_codes)
+            .await
+    }
+    
+    /// Replace a user's recovery codes after checking a TOTP code
+    pub async fn regenerate_recovery_codes(&self, user_id: Uuid, code: &str) -> Result<Vec<String>, AuthError> {
+        if !self.two_factor.is_enabled(user_id).await? {
+            return Err(AuthError::TwoFactorNotEnabled);
+        }
+        if !self.two_factor.verify_totp(user_id, code).await? {
+            return Err(AuthError::InvalidTwoFactorCode);
+        }
+        self.two_factor
+            .replace_recovery_codes(user_id, self.config.two_factor.recovery_codes)
+            .await
+    }
+    
+    /// Disable two-factor authentication after checking a code
+    ///
+    /// Fails if the user's role requires two factors.
+    pub async fn disable_two_factor(&self, user_id: Uuid, role: Role, code: &str) -> Result<(), AuthError> {
+        if self.config.two_factor.is_required(role) {
+            return Err(AuthError::TwoFactorRequired);
+        }
+        if !self.two_factor.verify(user_id, code).await? {
+            return Err(AuthError::InvalidTwoFactorCode);
+        }
+        self.two_factor.disable(user_id).await
+    }
+    
+    /// Whether a user has two-factor authentication enabled or required
+    pub async fn two_factor_status(&self, user_id: Uuid, role: Role) -> Result<TwoFactorStatus, AuthError> {
+        Ok(TwoFactorStatus {
+            enabled: self.two_factor.is_enabled(user_id).await?,
+            required: self.config.two_factor.is_required(role),
+            recovery_codes_remaining: self.two_factor.remaining_recovery_codes(user_id).await?,
+        })
+    }
+    
+    /// The current role of a user
+    async fn user_role(&self, user_id: Uuid) -> Result<Role, AuthError> {
         #[cfg(feature = "db")]
         {
-            // Find the refresh token
-            let token = sqlx::query_as!(
-                RefreshToken,
-                r#"
-                SELECT 
-                    id as "id: Uuid", 
-                    user_id as "user_id: Uuid", 
-                    token,
-                    expires_at as "expires_at: DateTime<Utc>",
-                    created_at as "created_at: DateTime<Utc>"
-                FROM refresh_tokens 
-                WHERE token = ?
-                "#,
-                refresh_token
-            )
-            .fetch_optional(&self.pool)
-            .await?
-            .ok_or(AuthError::InvalidToken)?;
-            
-            // Check if token has expired
-            let now = Utc::now();
-            if token.expires_at < now {
-                // Delete expired token
-                sqlx::query!(
-                    "DELETE FROM refresh_tokens WHERE id = ?",
-                    token.id.to_string()
-                )
-                .execute(&self.pool)
-                .await?;
-                
-                return Err(AuthError::InvalidToken);
-            }
-            
-            // Get user
-            let user = self.get_user(token.user_id).await?;
-            
-            // Generate new access token
-            let access_token = self.generate_token(user.id, user.role).await?;
-            
-            // Generate new refresh token
-            let new_refresh_token = self.generate_refresh_token(user.id).await?;
-            
-            // Create claims for response
-            let claims = Claims {
-                sub: user.id,
-                role: user.role,
-                exp: (now + chrono::Duration::minutes(self.config.jwt_expiration_minutes)).timestamp(),
-            };
-            
-            Ok((claims, access_token))
+            Ok(self.get_user(user_id).await?.role)
         }
+        
+        #[cfg(not(feature = "db"))]
+        {
+            let _ = user_id;
+            Ok(Role::User)
+        }
+    }
+
+    /// Whether an admin deactivated the account of `user_id`
+    #[cfg(feature = "db")]
+    async fn is_deactivated(&self, user_id: Uuid) -> Result<bool, AuthError> {
+        let deactivated_at: Option<Option<String>> =
+            sqlx::query_scalar("SELECT deactivated_at FROM users WHERE id = ?")
+                .bind(user_id.to_string())
+                .fetch_optional(&self.pool)
+                .await?;
+        Ok(matches!(deactivated_at, Some(Some(_))))
     }
     
     pub async fn generate_token(&self, user_id: Uuid, role: Role) -> Result<String, AuthError> {
@@ -299,13 +528,7 @@ impl AuthService {
             exp: expiration.timestamp(),
         };
 
-        let token = encode(
-            &Header::default(),
-            &claims,
-            &EncodingKey::from_secret(self.config.jwt_secret.as_ref()),
-        )?;
-
-        Ok(token)
+        self.sign(&claims)
     }
     
     pub async fn get_user(&self, user_id: Uuid) -> Result<User, AuthError> {
@@ -359,7 +582,9 @@ impl AuthService {
         }
     }
     
-    pub async fn login(&self, req: LoginRequest) -> Result<(User, String, String), AuthError> {
+    /// Check credentials and start a session on `device`, or a two-factor
+    /// challenge for it
+    pub async fn login(&self, req: LoginRequest, device: &str) -> Result<(User, LoginOutcome), AuthError> {
         #[cfg(feature = "mock-db")]
         {
             // In mock mode, only accept specific credentials
@@ -379,13 +604,9 @@ impl AuthService {
                     updated_at: Utc::now(),
                 };
                 
-                // Generate JWT token
-                let token = self.generate_token(user.id, Role::User).await?;
-                
-                // Generate refresh token
-                let refresh_token = self.generate_refresh_token(user.id).await?;
+                let outcome = self.start_login(user.id, Role::User, device).await?;
 
-                Ok((user, token, refresh_token))
+                Ok((user, outcome))
             } else {
                 Err(AuthError::InvalidCredentials)
             }
@@ -423,13 +644,13 @@ impl AuthService {
                 return Err(AuthError::InvalidCredentials);
             }
 
-            // Generate JWT token
-            let token = self.generate_token(user.id, user.role).await?;
-            
-            // Generate refresh token
-            let refresh_token = self.generate_refresh_token(user.id).await?;
+            if self.is_deactivated(user.id).await? {
+                return Err(AuthError::AccountDeactivated);
+            }
 
-            Ok((user, token, refresh_token))
+            let outcome = self.start_login(user.id, user.role, device).await?;
+
+            Ok((user, outcome))
         }
     }
     
@@ -657,110 +878,3 @@ pub async fn authenticate_user(
         Err(AuthError::InvalidCredentials)
     }
 }
-
-#[cfg(feature = "db")]
-pub async fn store_refresh_token(
-    pool: &SqlitePool,
-    user_id: &Uuid,
-    token: &str,
-    expires_at: &DateTime<Utc>,
-) -> Result<(), AuthError> {
-    sqlx::query!(
-        r#"
-        INSERT INTO refresh_tokens (token, user_id, expires_at)
-        VALUES (?, ?, ?)
-        "#,
-        token,
-        user_id.to_string(),
-        expires_at
-    )
-    .execute(pool)
-    .await
-    .map_err(|e| {
-        eprintln!("Failed to store refresh token: {:?}", e);
-        AuthError::Internal("Failed to store refresh token".to_string())
-    })?;
-
-    Ok(())
-}
-
-#[cfg(feature = "mock-db")]
-pub async fn store_refresh_token(
-    _pool: &SqlitePool,
-    _user_id: &Uuid,
-    _token: &str,
-    _expires_at: &DateTime<Utc>,
-) -> Result<(), AuthError> {
-    // Mock implementation - always succeeds
-    Ok(())
-}
-
-#[cfg(feature = "db")]
-pub async fn validate_refresh_token(
-    pool: &SqlitePool,
-    token: &str,
-) -> Result<Uuid, AuthError> {
-    let result = sqlx::query!(
-        r#"
-        SELECT user_id FROM refresh_tokens
-        WHERE token = ? AND expires_at > datetime('now')
-        "#,
-        token
-    )
-    .fetch_optional(pool)
-    .await
-    .map_err(|e| {
-        eprintln!("Failed to validate refresh token: {:?}", e);
-        AuthError::Internal("Failed to validate refresh token".to_string())
-    })?;
-
-    let user_id = result
-        .ok_or(AuthError::InvalidToken)?
-        .user_id;
-
-    let user_id = Uuid::parse_str(&user_id)
-        .map_err(|_| AuthError::Internal("Invalid user ID".to_string()))?;
-
-    Ok(user_id)
-}
-
-#[cfg(feature = "mock-db")]
-pub async fn validate_refresh_token(
-    _pool: &SqlitePool,
-    token: &str,
-) -> Result<Uuid, AuthError> {
-    // Mock implementation - only accept 'valid_refresh_token'
-    if token == "valid_refresh_token" {
-        Ok(Uuid::parse_str("00000000-0000-0000-0000-000000000001").unwrap())
-    } else {
-        Err(AuthError::InvalidToken)
-    }
-}
-
-#[cfg(feature = "db")]
-pub async fn revoke_refresh_token(
-    pool: &SqlitePool,
-    token: &str,
-) -> Result<(), AuthError> {
-    sqlx::query!(
-        "DELETE FROM refresh_tokens WHERE token = ?",
-        token
-    )
-    .execute(pool)
-    .await
-    .map_err(|e| {
-        eprintln!("Failed to revoke refresh token: {:?}", e);
-        AuthError::Internal("Failed to revoke refresh token".to_string())
-    })?;
-
-    Ok(())
-}
-
-#[cfg(feature = "mock-db")]
-pub async fn revoke_refresh_token(
-    _pool: &SqlitePool,
-    _token: &str,
-) -> Result<(), AuthError> {
-    // Mock implementation - always succeeds
-    Ok(())
-}
\ No newline at end of file
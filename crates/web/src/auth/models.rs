@@ -6,12 +6,34 @@ use serde::{Deserialize, Serialize};
 use sqlx::{Decode, Encode, Sqlite, Type};
 use uuid::Uuid;
 
-#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq)]
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
 pub enum Role {
     User,
     Admin,
 }
 
+impl Role {
+    /// The role as stored, e.g. `Admin`
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Role::User => "User",
+            Role::Admin => "Admin",
+        }
+    }
+}
+
+impl std::str::FromStr for Role {
+    type Err = String;
+
+    fn from_str(role: &str) -> Result<Self, Self::Err> {
+        match role.to_ascii_lowercase().as_str() {
+            "user" => Ok(Role::User),
+            "admin" => Ok(Role::Admin),
+            _ => Err(format!("Unknown role '{}', expected User or Admin", role)),
+        }
+    }
+}
+
 #[cfg(feature = "db")]
 impl Type<Sqlite> for Role {
     fn type_info() -> <Sqlite as sqlx::Database>::TypeInfo {
@@ -110,6 +132,32 @@ pub struct AuthResponse {
     pub role: Role,
     /// Refresh token for getting a new access token
     pub refresh_token: String,
+    /// Recovery codes, when this login completed two-factor enrollment
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub recovery_codes: Option<Vec<String>>,
+}
+
+/// Login response: tokens, or a challenge for the second factor
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum LoginResponse {
+    /// Signed in
+    Authenticated(AuthResponse),
+    /// A two-factor code is needed to finish signing in
+    TwoFactorRequired(TwoFactorChallenge),
+}
+
+/// Login challenge to answer at `POST /api/auth/2fa/verify`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TwoFactorChallenge {
+    /// Always true, so clients can tell this from an [`AuthResponse`]
+    pub two_factor_required: bool,
+    /// The user must enroll at `POST /api/auth/2fa/enroll` first
+    pub enrollment_required: bool,
+    /// Token identifying this login
+    pub challenge_token: String,
+    /// Challenge expiration in seconds
+    pub expires_in: i64,
 }
 
 /// User profile response
@@ -151,4 +199,62 @@ impl From<User> for UserProfile {
             created_at: Utc::now(),
         }
     }
-} 
\ No newline at end of file
+} 
+/// Response to ending every session of a user
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LogoutAllResponse {
+    /// Number of sessions that were ended
+    pub revoked_sessions: u64,
+}
+
+/// Request to start two-factor enrollment
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct TwoFactorEnrollRequest {
+    /// Login challenge, for users whose role requires enrolling before
+    /// they can sign in; others authenticate with their access token
+    #[serde(default)]
+    pub challenge_token: Option<String>,
+}
+
+/// A new TOTP secret for an authenticator app
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TwoFactorEnrollment {
+    /// Base32 secret, for entering by hand
+    pub secret: String,
+    /// `otpauth://` provisioning URI, for rendering as a QR code
+    pub otpauth_uri: String,
+}
+
+/// Request carrying a two-factor code
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TwoFactorCodeRequest {
+    /// TOTP code, or a recovery code where accepted
+    pub code: String,
+}
+
+/// Request to complete a login challenge
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TwoFactorVerifyRequest {
+    /// Token from the login response
+    pub challenge_token: String,
+    /// TOTP code or recovery code
+    pub code: String,
+}
+
+/// Newly issued recovery codes, shown once
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RecoveryCodesResponse {
+    /// Single-use recovery codes
+    pub recovery_codes: Vec<String>,
+}
+
+/// Two-factor state of a user
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TwoFactorStatus {
+    /// Two-factor authentication is enabled
+    pub enabled: bool,
+    /// The user's role requires two-factor authentication
+    pub required: bool,
+    /// Unused recovery codes
+    pub recovery_codes_remaining: u64,
+}
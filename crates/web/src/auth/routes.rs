@@ -1,26 +1,36 @@
 //! Authentication routes for user management.
 
 use axum::{
-    routing::{post, get},
+    routing::{delete, post, get},
     Router, 
-    extract::State,
+    extract::{Path, State},
+    http::{header, HeaderMap},
     Json,
-    response::IntoResponse,
 };
 use std::sync::Arc;
 use serde::Deserialize;
+use uuid::Uuid;
 
 use crate::{
     AppState,
     auth::{
-        models::{LoginRequest, RegisterRequest, UserProfile, AuthResponse},
-        AuthError, Claims,
+        models::{
+            LoginRequest, LoginResponse, LogoutAllResponse, RecoveryCodesResponse, RegisterRequest, Role,
+            TwoFactorChallenge, TwoFactorCodeRequest, TwoFactorEnrollRequest, TwoFactorEnrollment,
+            TwoFactorStatus, TwoFactorVerifyRequest, UserProfile, AuthResponse,
+        },
+        sessions::AuthSession,
+        AuthError, Claims, LoginOutcome,
     },
-    api::{api_success, ApiResponse},
+    api::{
+        api_success,
+        error::AppError,
+        users::{AcceptInvitationRequest, ResetPasswordRequest},
+        ApiResponse,
+    },
+    users::UserAccount,
 };
 
-#[cfg(feature = "mock-db")]
-use crate::auth::models::Role;
 
 /// Refresh token request
 #[derive(Debug, Deserialize)]
@@ -29,124 +39,116 @@ pub struct RefreshTokenRequest {
     pub refresh_token: String,
 }
 
-/// Simple handlers for route registration
-async fn register_stub() -> impl IntoResponse {
-    "Register endpoint"
-}
-
-async fn login_stub() -> impl IntoResponse {
-    "Login endpoint"
-}
-
-async fn get_profile_stub() -> impl IntoResponse {
-    "Profile endpoint"
+/// Authentication routes
+pub fn auth_routes() -> Router<Arc<AppState>> {
+    Router::new()
+        .route("/register", post(register))
+        .route("/login", post(login))
+        .route("/refresh", post(refresh))
+        .route("/profile", get(get_profile))
+        .route("/sessions", get(list_sessions))
+        .route("/sessions/:id", delete(revoke_session))
+        .route("/logout-all", post(logout_all))
+        .route("/2fa", get(two_factor_status))
+        .route("/2fa/enroll", post(enroll_two_factor))
+        .route("/2fa/confirm", post(confirm_two_factor))
+        .route("/2fa/verify", post(verify_two_factor))
+        .route("/2fa/recovery-codes", post(regenerate_recovery_codes))
+        .route("/2fa/disable", post(disable_two_factor))
+        .route("/invitations/accept", post(accept_invitation))
+        .route("/password-reset", post(reset_password))
 }
 
-/// Simple refresh handler for route registration
-async fn refresh_stub() -> impl IntoResponse {
-    "Refresh token endpoint"
+/// Device a session is started from, as named by the client's User-Agent
+fn device_name(headers: &HeaderMap) -> String {
+    headers
+        .get(header::USER_AGENT)
+        .and_then(|value| value.to_str().ok())
+        .map(str::trim)
+        .filter(|agent| !agent.is_empty())
+        .unwrap_or("unknown")
+        .to_string()
 }
 
-/// Authentication routes
-pub fn auth_routes() -> Router {
-    Router::new()
-        .route("/register", post(register_stub))
-        .route("/login", post(login_stub))
-        .route("/refresh", post(refresh_stub))
-        .route("/profile", get(get_profile_stub))
+/// Response to a login whose password was correct
+fn login_response(state: &AppState, user_id: Uuid, role: Role, outcome: LoginOutcome) -> LoginResponse {
+    match outcome {
+        LoginOutcome::Authenticated { access_token, refresh_token } => LoginResponse::Authenticated(AuthResponse {
+            token: access_token,
+            token_type: "Bearer".to_string(),
+            expires_in: state.auth.config.jwt_expiration_minutes * 60,
+            user_id,
+            role,
+            refresh_token,
+            recovery_codes: None,
+        }),
+        LoginOutcome::TwoFactorRequired { challenge_token, enrollment_required } => {
+            LoginResponse::TwoFactorRequired(TwoFactorChallenge {
+                two_factor_required: true,
+                enrollment_required,
+                challenge_token,
+                expires_in: state.auth.config.two_factor.challenge_ttl_seconds,
+            })
+        }
+    }
 }
 
 /// Register a new user
 #[cfg(feature = "db")]
 async fn register(
     State(state): State<Arc<AppState>>,
+    headers: HeaderMap,
     Json(req): Json<RegisterRequest>,
-) -> Result<Json<ApiResponse<AuthResponse>>, AuthError> {
+) -> Result<Json<ApiResponse<LoginResponse>>, AuthError> {
     let user = state.auth.register(req).await?;
-    let token = state.auth.generate_token(user.id, user.role).await?;
-    let refresh_token = state.auth.generate_refresh_token(user.id).await?;
+    let outcome = state.auth.start_login(user.id, user.role, &device_name(&headers)).await?;
     
-    let response = AuthResponse {
-        token,
-        token_type: "Bearer".to_string(),
-        expires_in: state.auth.config.jwt_expiration_minutes * 60,
-        user_id: user.id,
-        role: user.role,
-        refresh_token,
-    };
-    
-    Ok(api_success(response))
+    Ok(api_success(login_response(&state, user.id, user.role, outcome)))
 }
 
 /// Register a new user (mock implementation)
 #[cfg(feature = "mock-db")]
 async fn register(
     State(state): State<Arc<AppState>>,
+    headers: HeaderMap,
     Json(req): Json<RegisterRequest>,
-) -> Result<Json<ApiResponse<AuthResponse>>, AuthError> {
+) -> Result<Json<ApiResponse<LoginResponse>>, AuthError> {
     let user = state.auth.register(req).await?;
-    let token = state.auth.generate_token(user.id, Role::User).await?;
-    let refresh_token = state.auth.generate_refresh_token(user.id).await?;
+    let outcome = state.auth.start_login(user.id, Role::User, &device_name(&headers)).await?;
     
-    let response = AuthResponse {
-        token,
-        token_type: "Bearer".to_string(),
-        expires_in: state.auth.config.jwt_expiration_minutes * 60,
-        user_id: user.id,
-        role: Role::User,
-        refresh_token,
-    };
-    
-    Ok(api_success(response))
+    Ok(api_success(login_response(&state, user.id, Role::User, outcome)))
 }
 
 /// Login an existing user
 #[cfg(feature = "db")]
 async fn login(
     State(state): State<Arc<AppState>>,
+    headers: HeaderMap,
     Json(req): Json<LoginRequest>,
-) -> Result<Json<ApiResponse<AuthResponse>>, AuthError> {
-    let (user, token, refresh_token) = state.auth.login(req).await?;
-    
-    let response = AuthResponse {
-        token,
-        token_type: "Bearer".to_string(),
-        expires_in: state.auth.config.jwt_expiration_minutes * 60,
-        user_id: user.id,
-        role: user.role,
-        refresh_token,
-    };
+) -> Result<Json<ApiResponse<LoginResponse>>, AuthError> {
+    let (user, outcome) = state.auth.login(req, &device_name(&headers)).await?;
     
-    Ok(api_success(response))
+    Ok(api_success(login_response(&state, user.id, user.role, outcome)))
 }
 
 /// Login an existing user (mock implementation)
 #[cfg(feature = "mock-db")]
 async fn login(
     State(state): State<Arc<AppState>>,
+    headers: HeaderMap,
     Json(req): Json<LoginRequest>,
-) -> Result<Json<ApiResponse<AuthResponse>>, AuthError> {
-    let (user, token, refresh_token) = state.auth.login(req).await?;
+) -> Result<Json<ApiResponse<LoginResponse>>, AuthError> {
+    let (user, outcome) = state.auth.login(req, &device_name(&headers)).await?;
     
-    let response = AuthResponse {
-        token,
-        token_type: "Bearer".to_string(),
-        expires_in: state.auth.config.jwt_expiration_minutes * 60,
-        user_id: user.id,
-        role: Role::User,
-        refresh_token,
-    };
-    
-    Ok(api_success(response))
+    Ok(api_success(login_response(&state, user.id, Role::User, outcome)))
 }
 
-/// Refresh authentication token
+/// Refresh authentication token, rotating the refresh token
 async fn refresh(
     State(state): State<Arc<AppState>>,
     Json(req): Json<RefreshTokenRequest>,
 ) -> Result<Json<ApiResponse<AuthResponse>>, AuthError> {
-    let (claims, access_token) = state.auth.refresh_access_token(&req.refresh_token).await?;
-    let refresh_token = state.auth.generate_refresh_token(claims.sub).await?;
+    let (claims, access_token, refresh_token) = state.auth.refresh_access_token(&req.refresh_token).await?;
     
     let response = AuthResponse {
         token: access_token,
@@ -155,6 +157,7 @@ async fn refresh(
         user_id: claims.sub,
         role: claims.role,
         refresh_token,
+        recovery_codes: None,
     };
     
     Ok(api_success(response))
@@ -190,4 +193,142 @@ async fn get_profile(
     };
     
     Ok(api_success(profile))
-} 
\ No newline at end of file
+}
+
+/// List the authenticated user's active sessions
+async fn list_sessions(
+    State(state): State<Arc<AppState>>,
+    claims: Claims,
+) -> Result<Json<ApiResponse<Vec<AuthSession>>>, AuthError> {
+    let sessions = state.auth.list_sessions(claims.sub).await?;
+    
+    Ok(api_success(sessions))
+}
+
+/// End one of the authenticated user's sessions
+async fn revoke_session(
+    State(state): State<Arc<AppState>>,
+    claims: Claims,
+    Path(id): Path<Uuid>,
+) -> Result<Json<ApiResponse<()>>, AuthError> {
+    if !state.auth.revoke_session(claims.sub, id).await? {
+        return Err(AuthError::SessionNotFound);
+    }
+    
+    Ok(api_success(()))
+}
+
+/// End every session of the authenticated user
+async fn logout_all(
+    State(state): State<Arc<AppState>>,
+    claims: Claims,
+) -> Result<Json<ApiResponse<LogoutAllResponse>>, AuthError> {
+    let revoked_sessions = state.auth.logout_all(claims.sub).await?;
+    
+    Ok(api_success(LogoutAllResponse { revoked_sessions }))
+}
+
+/// Two-factor state of the authenticated user
+async fn two_factor_status(
+    State(state): State<Arc<AppState>>,
+    claims: Claims,
+) -> Result<Json<ApiResponse<TwoFactorStatus>>, AuthError> {
+    let status = state.auth.two_factor_status(claims.sub, claims.role).await?;
+    
+    Ok(api_success(status))
+}
+
+/// Start two-factor enrollment for the authenticated user, or for the
+/// user of a login challenge that requires enrolling
+async fn enroll_two_factor(
+    State(state): State<Arc<AppState>>,
+    claims: Option<Claims>,
+    req: Option<Json<TwoFactorEnrollRequest>>,
+) -> Result<Json<ApiResponse<TwoFactorEnrollment>>, AuthError> {
+    let challenge_token = req.and_then(|Json(req)| req.challenge_token);
+    let user_id = match (challenge_token, claims) {
+        (Some(challenge_token), _) => state.auth.challenge_user(&challenge_token).await?,
+        (None, Some(claims)) => claims.sub,
+        (None, None) => return Err(AuthError::MissingToken),
+    };
+    let enrollment = state.auth.enroll_two_factor(user_id).await?;
+    
+    Ok(api_success(enrollment))
+}
+
+/// Enable two-factor authentication for the authenticated user
+async fn confirm_two_factor(
+    State(state): State<Arc<AppState>>,
+    claims: Claims,
+    Json(req): Json<TwoFactorCodeRequest>,
+) -> Result<Json<ApiResponse<RecoveryCodesResponse>>, AuthError> {
+    let recovery_codes = state.auth.confirm_two_factor(claims.sub, &req.code).await?;
+    
+    Ok(api_success(RecoveryCodesResponse { recovery_codes }))
+}
+
+/// Complete a login challenge with a two-factor code
+async fn verify_two_factor(
+    State(state): State<Arc<AppState>>,
+    Json(req): Json<TwoFactorVerifyRequest>,
+) -> Result<Json<ApiResponse<AuthResponse>>, AuthError> {
+    let (claims, access_token, refresh_token, recovery_codes) =
+        state.auth.verify_two_factor(&req.challenge_token, &req.code).await?;
+    
+    let response = AuthResponse {
+        token: access_token,
+        token_type: "Bearer".to_string(),
+        expires_in: state.auth.config.jwt_expiration_minutes * 60,
+        user_id: claims.sub,
+        role: claims.role,
+        refresh_token,
+        recovery_codes,
+    };
+    
+    Ok(api_success(response))
+}
+
+/// Replace the authenticated user's recovery codes
+async fn regenerate_recovery_codes(
+    State(state): State<Arc<AppState>>,
+    claims: Claims,
+    Json(req): Json<TwoFactorCodeRequest>,
+) -> Result<Json<ApiResponse<RecoveryCodesResponse>>, AuthError> {
+    let recovery_codes = state.auth.regenerate_recovery_codes(claims.sub, &req.code).await?;
+    
+    Ok(api_success(RecoveryCodesResponse { recovery_codes }))
+}
+
+/// Disable two-factor authentication for the authenticated user
+async fn disable_two_factor(
+    State(state): State<Arc<AppState>>,
+    claims: Claims,
+    Json(req): Json<TwoFactorCodeRequest>,
+) -> Result<Json<ApiResponse<()>>, AuthError> {
+    state.auth.disable_two_factor(claims.sub, claims.role, &req.code).await?;
+    
+    Ok(api_success(()))
+}
+
+/// Create an account by accepting an invitation
+async fn accept_invitation(
+    State(state): State<Arc<AppState>>,
+    Json(req): Json<AcceptInvitationRequest>,
+) -> Result<Json<ApiResponse<UserAccount>>, AppError> {
+    let user = state
+        .get_users()?
+        .accept_invitation(&req.token, &req.username, &req.password)
+        .await?;
+    
+    Ok(api_success(user))
+}
+
+/// Set a new password with a reset token, ending the user's sessions
+async fn reset_password(
+    State(state): State<Arc<AppState>>,
+    Json(req): Json<ResetPasswordRequest>,
+) -> Result<Json<ApiResponse<()>>, AppError> {
+    state.get_users()?.reset_password(&req.token, &req.password).await?;
+    
+    Ok(api_success(()))
+}
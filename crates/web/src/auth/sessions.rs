@@ -0,0 +1,437 @@
+//! Refresh-token sessions.
+//!
+//! Every login starts a session for the signing-in device, identified by a
+//! refresh token. Refresh tokens are single use: each refresh rotates the
+//! session to a new token and adds the old one to the revocation list. A
+//! rotated token that is presented again was stolen or replayed, so the
+//! whole session is revoked. Only SHA-256 hashes of tokens are stored.
+
+use chrono::{DateTime, Duration, Utc};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use sqlx::sqlite::SqliteRow;
+use sqlx::{Row, SqlitePool};
+use uuid::Uuid;
+
+use crate::db::{timestamp, InstrumentedPool};
+
+use super::AuthError;
+
+/// Why a refresh token was revoked
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RevocationReason {
+    /// The token was exchanged for a new one
+    Rotated,
+    /// The session was ended by its user
+    Logout,
+    /// A rotated token was presented again
+    ReuseDetected,
+    /// The session was ended by an admin
+    Kicked,
+}
+
+impl RevocationReason {
+    fn as_str(self) -> &'static str {
+        match self {
+            Self::Rotated => "rotated",
+            Self::Logout => "logout",
+            Self::ReuseDetected => "reuse_detected",
+            Self::Kicked => "kicked",
+        }
+    }
+}
+
+/// A signed-in device of a user
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct AuthSession {
+    /// Session ID
+    pub id: Uuid,
+    /// User the session belongs to
+    pub user_id: Uuid,
+    /// Device or client the session was started from
+    pub device: String,
+    /// When the user signed in
+    pub created_at: DateTime<Utc>,
+    /// When the session's refresh token was last used
+    pub last_used_at: DateTime<Utc>,
+    /// When the current refresh token expires
+    pub expires_at: DateTime<Utc>,
+}
+
+/// Session storage backed by the `auth_sessions` and
+/// `revoked_refresh_tokens` tables
+#[derive(Debug, Clone)]
+pub struct SessionStore {
+    pool: InstrumentedPool,
+}
+
+impl SessionStore {
+    /// Create a new SessionStore
+    pub fn new(pool: SqlitePool) -> Self {
+        Self { pool: pool.into() }
+    }
+
+    /// Start a session for `user_id`, returning it and its refresh token
+    pub async fn create(
+        &self,
+        user_id: Uuid,
+        device: &str,
+        ttl: Duration,
+    ) -> Result<(AuthSession, String), AuthError> {
+        self.prune().await?;
+
+        let now = Utc::now();
+        let session = AuthSession {
+            id: Uuid::new_v4(),
+            user_id,
+            device: device.to_string(),
+            created_at: now,
+            last_used_at: now,
+            expires_at: now + ttl,
+        };
+        let token = new_token();
+        sqlx::query(
+            "INSERT INTO auth_sessions (id, user_id, device, token_hash, created_at, last_used_at, expires_at)
+             VALUES (?, ?, ?, ?, ?, ?, ?)",
+        )
+        .bind(session.id.to_string())
+        .bind(user_id.to_string())
+        .bind(&session.device)
+        .bind(hash_token(&token))
+        .bind(now.timestamp_millis())
+        .bind(now.timestamp_millis())
+        .bind(session.expires_at.timestamp_millis())
+        .execute(&self.pool)
+        .await?;
+
+        Ok((session, token))
+    }
+
+    /// Exchange a refresh token for a new one
+    ///
+    /// Fails with [`AuthError::InvalidToken`] if the token is unknown,
+    /// expired or revoked. Presenting a rotated token revokes its session.
+    pub async fn rotate(
+        &self,
+        token: &str,
+        ttl: Duration,
+    ) -> Result<(AuthSession, String), AuthError> {
+        let hash = hash_token(token);
+        let now = Utc::now();
+
+        let row = sqlx::query(
+            "SELECT id, user_id, device, created_at, last_used_at, expires_at FROM auth_sessions
+             WHERE token_hash = ? AND revoked_at IS NULL",
+        )
+        .bind(&hash)
+        .fetch_optional(&self.pool)
+        .await?;
+        let Some(row) = row else {
+            self.detect_reuse(&hash).await?;
+            return Err(AuthError::InvalidToken);
+        };
+        let mut session = session_from_row(&row)?;
+        if session.expires_at <= now {
+            return Err(AuthError::InvalidToken);
+        }
+
+        let new_token = new_token();
+        let expires_at = now + ttl;
+        let mut tx = self.pool.begin().await?;
+        // Matching on the old hash makes a concurrent second use lose
+        let updated = sqlx::query(
+            "UPDATE auth_sessions SET token_hash = ?, last_used_at = ?, expires_at = ?
+             WHERE id = ? AND token_hash = ? AND revoked_at IS NULL",
+        )
+        .bind(hash_token(&new_token))
+        .bind(now.timestamp_millis())
+        .bind(expires_at.timestamp_millis())
+        .bind(session.id.to_string())
+        .bind(&hash)
+        .execute(&mut *tx)
+        .await?;
+        if updated.rows_affected() == 0 {
+            tx.rollback().await?;
+            self.detect_reuse(&hash).await?;
+            return Err(AuthError::InvalidToken);
+        }
+        insert_revoked(
+            &mut tx,
+            &hash,
+            session.id,
+            RevocationReason::Rotated,
+            session.expires_at,
+        )
+        .await?;
+        tx.commit().await?;
+
+        session.last_used_at = now;
+        session.expires_at = expires_at;
+        Ok((session, new_token))
+    }
+
+    /// Active sessions of a user, most recently used first
+    pub async fn list(&self, user_id: Uuid) -> Result<Vec<AuthSession>, AuthError> {
+        let rows = sqlx::query(
+            "SELECT id, user_id, device, created_at, last_used_at, expires_at FROM auth_sessions
+             WHERE user_id = ? AND revoked_at IS NULL AND expires_at > ?
+             ORDER BY last_used_at DESC",
+        )
+        .bind(user_id.to_string())
+        .bind(Utc::now().timestamp_millis())
+        .fetch_all(&self.pool)
+        .await?;
+        rows.iter().map(session_from_row).collect()
+    }
+
+    /// Active sessions of every user, most recently used first
+    pub async fn list_all(&self) -> Result<Vec<AuthSession>, AuthError> {
+        let rows = sqlx::query(
+            "SELECT id, user_id, device, created_at, last_used_at, expires_at FROM auth_sessions
+             WHERE revoked_at IS NULL AND expires_at > ?
+             ORDER BY last_used_at DESC",
+        )
+        .bind(Utc::now().timestamp_millis())
+        .fetch_all(&self.pool)
+        .await?;
+        rows.iter().map(session_from_row).collect()
+    }
+
+    /// End one session of a user; returns whether it was active
+    pub async fn revoke(&self, user_id: Uuid, session_id: Uuid) -> Result<bool, AuthError> {
+        let revoked = self
+            .revoke_where(
+                "id = ? AND user_id = ?",
+                &[session_id.to_string(), user_id.to_string()],
+                RevocationReason::Logout,
+            )
+            .await?;
+        Ok(revoked > 0)
+    }
+
+    /// End a session of any user, as an admin; returns whether it was active
+    pub async fn kick(&self, session_id: Uuid) -> Result<bool, AuthError> {
+        let revoked = self
+            .revoke_where("id = ?", &[session_id.to_string()], RevocationReason::Kicked)
+            .await?;
+        Ok(revoked > 0)
+    }
+
+    /// End every session of a user; returns how many were active
+    pub async fn revoke_all(&self, user_id: Uuid) -> Result<u64, AuthError> {
+        self.revoke_where(
+            "user_id = ?",
+            &[user_id.to_string()],
+            RevocationReason::Logout,
+        )
+        .await
+    }
+
+    /// Whether a refresh token is on the revocation list
+    pub async fn is_revoked(&self, token: &str) -> Result<bool, AuthError> {
+        let row = sqlx::query("SELECT 1 FROM revoked_refresh_tokens WHERE token_hash = ?")
+            .bind(hash_token(token))
+            .fetch_optional(&self.pool)
+            .await?;
+        Ok(row.is_some())
+    }
+
+    /// Revoke the session of a rotated token that was presented again
+    async fn detect_reuse(&self, hash: &str) -> Result<(), AuthError> {
+        let row = sqlx::query(
+            "SELECT session_id FROM revoked_refresh_tokens WHERE token_hash = ? AND reason = ?",
+        )
+        .bind(hash)
+        .bind(RevocationReason::Rotated.as_str())
+        .fetch_optional(&self.pool)
+        .await?;
+        if let Some(row) = row {
+            let session_id: String = row.try_get("session_id")?;
+            tracing::warn!(session_id = %session_id, "Rotated refresh token reused; revoking session");
+            self.revoke_where("id = ?", &[session_id], RevocationReason::ReuseDetected)
+                .await?;
+        }
+        Ok(())
+    }
+
+    /// Revoke the active sessions matching `condition`, listing their
+    /// current tokens as revoked
+    async fn revoke_where(
+        &self,
+        condition: &str,
+        params: &[String],
+        reason: RevocationReason,
+    ) -> Result<u64, AuthError> {
+        let mut tx = self.pool.begin().await?;
+        let select = format!(
+            "SELECT id, token_hash, expires_at FROM auth_sessions WHERE revoked_at IS NULL AND {}",
+            condition
+        );
+        let mut query = sqlx::query(&select);
+        for param in params {
+            query = query.bind(param);
+        }
+        let rows = query.fetch_all(&mut *tx).await?;
+
+        let now = Utc::now().timestamp_millis();
+        for row in &rows {
+            let id: String = row.try_get("id")?;
+            let hash: String = row.try_get("token_hash")?;
+            let expires_at = timestamp(row.try_get("expires_at")?)?;
+            let session_id =
+                Uuid::parse_str(&id).map_err(|e| AuthError::Internal(e.to_string()))?;
+            sqlx::query("UPDATE auth_sessions SET revoked_at = ? WHERE id = ?")
+                .bind(now)
+                .bind(&id)
+                .execute(&mut *tx)
+                .await?;
+            insert_revoked(&mut tx, &hash, session_id, reason, expires_at).await?;
+        }
+        tx.commit().await?;
+
+        Ok(rows.len() as u64)
+    }
+
+    /// Delete revoked tokens and sessions past their expiry; returns how
+    /// many rows were deleted
+    pub async fn prune(&self) -> Result<u64, AuthError> {
+        let now = Utc::now().timestamp_millis();
+        let tokens = sqlx::query("DELETE FROM revoked_refresh_tokens WHERE expires_at <= ?")
+            .bind(now)
+            .execute(&self.pool)
+            .await?
+            .rows_affected();
+        let sessions = sqlx::query("DELETE FROM auth_sessions WHERE expires_at <= ?")
+            .bind(now)
+            .execute(&self.pool)
+            .await?
+            .rows_affected();
+        Ok(tokens + sessions)
+    }
+}
+
+/// Add a token to the revocation list
+async fn insert_revoked(
+    tx: &mut sqlx::Transaction<'_, sqlx::Sqlite>,
+    hash: &str,
+    session_id: Uuid,
+    reason: RevocationReason,
+    expires_at: DateTime<Utc>,
+) -> Result<(), AuthError> {
+    sqlx::query(
+        "INSERT OR IGNORE INTO revoked_refresh_tokens (token_hash, session_id, reason, revoked_at, expires_at)
+         VALUES (?, ?, ?, ?, ?)",
+    )
+    .bind(hash)
+    .bind(session_id.to_string())
+    .bind(reason.as_str())
+    .bind(Utc::now().timestamp_millis())
+    .bind(expires_at.timestamp_millis())
+    .execute(&mut **tx)
+    .await?;
+    Ok(())
+}
+
+/// A new random opaque token
+pub(super) fn new_token() -> String {
+    format!("{}{}", Uuid::new_v4().simple(), Uuid::new_v4().simple())
+}
+
+/// Hex SHA-256 of a token, as stored
+pub(super) fn hash_token(token: &str) -> String {
+    hex::encode(Sha256::digest(token.as_bytes()))
+}
+
+fn session_from_row(row: &SqliteRow) -> Result<AuthSession, AuthError> {
+    let uuid = |column: &str| -> Result<Uuid, AuthError> {
+        let value: String = row.try_get(column)?;
+        Uuid::parse_str(&value).map_err(|e| AuthError::Internal(e.to_string()))
+    };
+    Ok(AuthSession {
+        id: uuid("id")?,
+        user_id: uuid("user_id")?,
+        device: row.try_get("device")?,
+        created_at: timestamp(row.try_get("created_at")?)?,
+        last_used_at: timestamp(row.try_get("last_used_at")?)?,
+        expires_at: timestamp(row.try_get("expires_at")?)?,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_helpers::migrated_pool;
+
+    async fn store() -> SessionStore {
+        let pool = migrated_pool().await;
+        SessionStore::new(pool)
+    }
+
+    #[tokio::test]
+    async fn test_refresh_tokens_rotate_once_and_reuse_revokes_session() {
+        let store = store().await;
+        let user = Uuid::new_v4();
+        let ttl = Duration::days(1);
+        let (session, first) = store.create(user, "laptop", ttl).await.unwrap();
+
+        let (rotated, second) = store.rotate(&first, ttl).await.unwrap();
+        assert_eq!(rotated.id, session.id);
+        assert!(store.is_revoked(&first).await.unwrap());
+        assert!(!store.is_revoked(&second).await.unwrap());
+
+        // Replaying the first token revokes the session, so the second dies too
+        assert!(matches!(
+            store.rotate(&first, ttl).await,
+            Err(AuthError::InvalidToken)
+        ));
+        assert!(matches!(
+            store.rotate(&second, ttl).await,
+            Err(AuthError::InvalidToken)
+        ));
+        assert!(store.is_revoked(&second).await.unwrap());
+        assert!(store.list(user).await.unwrap().is_empty());
+        assert!(matches!(
+            store.rotate("unknown", ttl).await,
+            Err(AuthError::InvalidToken)
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_list_and_revoke_sessions() {
+        let store = store().await;
+        let user = Uuid::new_v4();
+        let ttl = Duration::days(1);
+        let (laptop, _) = store.create(user, "laptop", ttl).await.unwrap();
+        let (_, phone_token) = store.create(user, "phone", ttl).await.unwrap();
+        let (_, other_token) = store.create(Uuid::new_v4(), "laptop", ttl).await.unwrap();
+        tokio::time::sleep(std::time::Duration::from_millis(5)).await;
+        store.rotate(&phone_token, ttl).await.unwrap();
+
+        let devices: Vec<String> = store
+            .list(user)
+            .await
+            .unwrap()
+            .into_iter()
+            .map(|s| s.device)
+            .collect();
+        assert_eq!(devices, ["phone", "laptop"]);
+
+        assert!(!store.revoke(Uuid::new_v4(), laptop.id).await.unwrap());
+        assert!(store.revoke(user, laptop.id).await.unwrap());
+        assert_eq!(store.list(user).await.unwrap().len(), 1);
+
+        assert_eq!(store.revoke_all(user).await.unwrap(), 1);
+        assert!(store.list(user).await.unwrap().is_empty());
+        assert!(store.rotate(&other_token, ttl).await.is_ok());
+
+        let (_, expired) = store
+            .create(user, "tablet", Duration::milliseconds(-1))
+            .await
+            .unwrap();
+        assert!(matches!(
+            store.rotate(&expired, ttl).await,
+            Err(AuthError::InvalidToken)
+        ));
+    }
+}
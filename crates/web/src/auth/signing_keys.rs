@@ -0,0 +1,172 @@
+//! Rotating access token signing keys.
+//!
+//! Until the first rotation, access tokens are signed with the configured
+//! `jwt_secret`. Rotating adds a random key to the `auth_signing_keys`
+//! table that signs every new token, named by the token's `kid` header, and
+//! retires the previous key. Retired keys, the configured secret included,
+//! keep verifying the tokens they signed until those would have expired.
+
+use std::sync::{Arc, PoisonError, RwLock};
+
+use chrono::{DateTime, Duration, Utc};
+use serde::{Deserialize, Serialize};
+use sqlx::{Row, SqlitePool};
+use uuid::Uuid;
+
+use crate::db::{timestamp, InstrumentedPool};
+
+use super::AuthError;
+
+/// A stored signing key
+#[derive(Debug, Clone)]
+pub struct SigningKey {
+    /// Key ID, sent as the token's `kid`
+    pub id: String,
+    /// HMAC secret
+    pub secret: String,
+    /// When the key was added
+    pub created_at: DateTime<Utc>,
+    /// When a newer key replaced it
+    pub retired_at: Option<DateTime<Utc>>,
+}
+
+/// A signing key as listed, without its secret
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SigningKeyInfo {
+    /// Key ID
+    pub id: String,
+    /// When the key was added
+    pub created_at: DateTime<Utc>,
+    /// When a newer key replaced it
+    pub retired_at: Option<DateTime<Utc>>,
+}
+
+impl From<&SigningKey> for SigningKeyInfo {
+    fn from(key: &SigningKey) -> Self {
+        Self {
+            id: key.id.clone(),
+            created_at: key.created_at,
+            retired_at: key.retired_at,
+        }
+    }
+}
+
+/// Signing keys backed by the `auth_signing_keys` table, cached in memory
+///
+/// Keys rotated by another instance or the CLI are picked up by
+/// [`reload`](Self::reload).
+#[derive(Debug, Clone)]
+pub struct SigningKeys {
+    pool: InstrumentedPool,
+    keys: Arc<RwLock<Vec<SigningKey>>>,
+}
+
+impl SigningKeys {
+    /// Create new SigningKeys; nothing is cached until the first reload
+    pub fn new(pool: SqlitePool) -> Self {
+        Self {
+            pool: pool.into(),
+            keys: Arc::new(RwLock::new(Vec::new())),
+        }
+    }
+
+    /// Read the stored keys again
+    pub async fn reload(&self) -> Result<(), AuthError> {
+        let rows = sqlx::query(
+            "SELECT id, secret, created_at, retired_at FROM auth_signing_keys ORDER BY created_at",
+        )
+        .fetch_all(&self.pool)
+        .await?;
+        let mut keys = Vec::with_capacity(rows.len());
+        for row in &rows {
+            keys.push(SigningKey {
+                id: row.try_get("id")?,
+                secret: row.try_get("secret")?,
+                created_at: timestamp(row.try_get("created_at")?)?,
+                retired_at: row
+                    .try_get::<Option<i64>, _>("retired_at")?
+                    .map(timestamp)
+                    .transpose()?,
+            });
+        }
+        *self.keys.write().unwrap_or_else(PoisonError::into_inner) = keys;
+        Ok(())
+    }
+
+    /// Add a new key signing every token from now on, retiring the current one
+    pub async fn rotate(&self) -> Result<SigningKeyInfo, AuthError> {
+        let now = Utc::now();
+        let key = SigningKey {
+            id: Uuid::new_v4().simple().to_string(),
+            secret: format!("{}{}", Uuid::new_v4().simple(), Uuid::new_v4().simple()),
+            created_at: now,
+            retired_at: None,
+        };
+        let mut tx = self.pool.begin().await?;
+        sqlx::query("UPDATE auth_signing_keys SET retired_at = ? WHERE retired_at IS NULL")
+            .bind(now.timestamp_millis())
+            .execute(&mut *tx)
+            .await?;
+        sqlx::query("INSERT INTO auth_signing_keys (id, secret, created_at) VALUES (?, ?, ?)")
+            .bind(&key.id)
+            .bind(&key.secret)
+            .bind(now.timestamp_millis())
+            .execute(&mut *tx)
+            .await?;
+        tx.commit().await?;
+
+        self.reload().await?;
+        Ok(SigningKeyInfo::from(&key))
+    }
+
+    /// Delete keys retired before `retired_before`
+    pub async fn prune(&self, retired_before: DateTime<Utc>) -> Result<u64, AuthError> {
+        let pruned = sqlx::query("DELETE FROM auth_signing_keys WHERE retired_at <= ?")
+            .bind(retired_before.timestamp_millis())
+            .execute(&self.pool)
+            .await?
+            .rows_affected();
+        if pruned > 0 {
+            self.reload().await?;
+        }
+        Ok(pruned)
+    }
+
+    /// The key signing new tokens, if one was rotated in
+    pub fn current(&self) -> Option<SigningKey> {
+        self.keys
+            .read()
+            .unwrap_or_else(PoisonError::into_inner)
+            .iter()
+            .rev()
+            .find(|key| key.retired_at.is_none())
+            .cloned()
+    }
+
+    /// The key named `id`, if it still verifies tokens issued within `ttl`
+    pub fn verifying(&self, id: &str, ttl: Duration) -> Option<SigningKey> {
+        let now = Utc::now();
+        self.keys
+            .read()
+            .unwrap_or_else(PoisonError::into_inner)
+            .iter()
+            .find(|key| key.id == id)
+            .filter(|key| key.retired_at.is_none_or(|retired_at| retired_at + ttl > now))
+            .cloned()
+    }
+
+    /// Whether the configured secret still verifies tokens issued within
+    /// `ttl`: it retired when the first key was rotated in
+    pub fn accepts_configured_secret(&self, ttl: Duration) -> bool {
+        self.keys
+            .read()
+            .unwrap_or_else(PoisonError::into_inner)
+            .first()
+            .is_none_or(|first| first.created_at + ttl > Utc::now())
+    }
+
+    /// Every stored key, oldest first
+    pub fn list(&self) -> Vec<SigningKeyInfo> {
+        self.keys.read().unwrap_or_else(PoisonError::into_inner).iter().map(SigningKeyInfo::from).collect()
+    }
+}
@@ -0,0 +1,426 @@
+//! TOTP two-factor authentication.
+//!
+//! Users enroll by scanning an `otpauth://` URI into an authenticator app
+//! and confirming the first code, which also issues single-use recovery
+//! codes. While two-factor authentication is enabled, or required for the
+//! user's role, a correct password only yields a short-lived login
+//! challenge; the challenge is exchanged for tokens together with a code.
+//! Codes follow RFC 6238 (HMAC-SHA1, 6 digits, 30 second steps), and each
+//! time step is accepted once.
+
+use chrono::{Duration, Utc};
+use data_encoding::BASE32_NOPAD;
+use hmac::{Hmac, Mac};
+use sha1::Sha1;
+use sqlx::{Row, SqlitePool};
+use uuid::Uuid;
+
+use crate::db::InstrumentedPool;
+
+use super::sessions::{hash_token, new_token};
+use super::AuthError;
+
+/// Length of a TOTP time step in seconds
+pub const TOTP_STEP_SECONDS: i64 = 30;
+/// Digits in a TOTP code
+pub const TOTP_DIGITS: u32 = 6;
+/// Wrong codes a login challenge accepts before it is discarded
+pub const MAX_CHALLENGE_ATTEMPTS: i64 = 5;
+
+/// The TOTP code of `secret` for time step `step`
+pub fn totp_code(secret: &[u8], step: u64) -> u32 {
+    let mut mac = Hmac::<Sha1>::new_from_slice(secret).expect("HMAC accepts keys of any length");
+    mac.update(&step.to_be_bytes());
+    let hash = mac.finalize().into_bytes();
+    // Dynamic truncation, RFC 4226 section 5.3
+    let offset = (hash[hash.len() - 1] & 0x0f) as usize;
+    let binary = u32::from_be_bytes([
+        hash[offset] & 0x7f,
+        hash[offset + 1],
+        hash[offset + 2],
+        hash[offset + 3],
+    ]);
+    binary % 10u32.pow(TOTP_DIGITS)
+}
+
+/// Provisioning URI for authenticator apps, usually shown as a QR code
+pub fn otpauth_uri(issuer: &str, account: &str, secret: &str) -> String {
+    format!(
+        "otpauth://totp/{}:{}?secret={}&issuer={}&algorithm=SHA1&digits={}&period={}",
+        percent_encode(issuer),
+        percent_encode(account),
+        secret,
+        percent_encode(issuer),
+        TOTP_DIGITS,
+        TOTP_STEP_SECONDS
+    )
+}
+
+fn percent_encode(value: &str) -> String {
+    value
+        .bytes()
+        .map(|byte| match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' => {
+                (byte as char).to_string()
+            }
+            _ => format!("%{:02X}", byte),
+        })
+        .collect()
+}
+
+/// Recovery codes are compared without separators or case
+fn normalize_recovery_code(code: &str) -> String {
+    code.chars()
+        .filter(char::is_ascii_alphanumeric)
+        .map(|c| c.to_ascii_lowercase())
+        .collect()
+}
+
+/// A login waiting on its second factor
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LoginChallenge {
+    /// User who entered a correct password
+    pub user_id: Uuid,
+    /// Device the session will be started for
+    pub device: String,
+}
+
+/// Two-factor state backed by the `two_factor`, `two_factor_recovery_codes`
+/// and `two_factor_challenges` tables
+#[derive(Debug, Clone)]
+pub struct TwoFactorStore {
+    pool: InstrumentedPool,
+}
+
+impl TwoFactorStore {
+    /// Create a new TwoFactorStore
+    pub fn new(pool: SqlitePool) -> Self {
+        Self { pool: pool.into() }
+    }
+
+    /// Whether the user has confirmed two-factor enrollment
+    pub async fn is_enabled(&self, user_id: Uuid) -> Result<bool, AuthError> {
+        let row =
+            sqlx::query("SELECT 1 FROM two_factor WHERE user_id = ? AND enabled_at IS NOT NULL")
+                .bind(user_id.to_string())
+                .fetch_optional(&self.pool)
+                .await?;
+        Ok(row.is_some())
+    }
+
+    /// Generate a new secret for the user, replacing an unconfirmed one
+    ///
+    /// Returns the base32 secret. Fails if two-factor authentication is
+    /// already enabled.
+    pub async fn begin_enrollment(&self, user_id: Uuid) -> Result<String, AuthError> {
+        if self.is_enabled(user_id).await? {
+            return Err(AuthError::TwoFactorAlreadyEnabled);
+        }
+        let mut secret = Uuid::new_v4().into_bytes().to_vec();
+        secret.extend_from_slice(&Uuid::new_v4().into_bytes()[..4]);
+        let secret = BASE32_NOPAD.encode(&secret);
+        sqlx::query(
+            "INSERT OR REPLACE INTO two_factor (user_id, secret, last_used_step, created_at)
+             VALUES (?, ?, 0, ?)",
+        )
+        .bind(user_id.to_string())
+        .bind(&secret)
+        .bind(Utc::now().timestamp_millis())
+        .execute(&self.pool)
+        .await?;
+        Ok(secret)
+    }
+
+    /// Enable two-factor authentication if `code` matches the enrolled
+    /// secret, returning new recovery codes
+    pub async fn confirm(
+        &self,
+        user_id: Uuid,
+        code: &str,
+        recovery_codes: usize,
+    ) -> Result<Vec<String>, AuthError> {
+        if self.is_enabled(user_id).await? {
+            return Err(AuthError::TwoFactorAlreadyEnabled);
+        }
+        if !self.verify_totp(user_id, code).await? {
+            return Err(AuthError::InvalidTwoFactorCode);
+        }
+        sqlx::query("UPDATE two_factor SET enabled_at = ? WHERE user_id = ?")
+            .bind(Utc::now().timestamp_millis())
+            .bind(user_id.to_string())
+            .execute(&self.pool)
+            .await?;
+        self.replace_recovery_codes(user_id, recovery_codes).await
+    }
+
+    /// Check a code from the authenticator app or an unused recovery code
+    pub async fn verify(&self, user_id: Uuid, code: &str) -> Result<bool, AuthError> {
+        if !self.is_enabled(user_id).await? {
+            return Err(AuthError::TwoFactorNotEnabled);
+        }
+        if self.verify_totp(user_id, code).await? {
+            return Ok(true);
+        }
+        self.use_recovery_code(user_id, code).await
+    }
+
+    /// Check a TOTP code, allowing one step of clock drift either way
+    ///
+    /// A step that was already used, or precedes one that was, is rejected
+    /// so an intercepted code cannot be replayed.
+    pub async fn verify_totp(&self, user_id: Uuid, code: &str) -> Result<bool, AuthError> {
+        let row = sqlx::query("SELECT secret, last_used_step FROM two_factor WHERE user_id = ?")
+            .bind(user_id.to_string())
+            .fetch_optional(&self.pool)
+            .await?;
+        let Some(row) = row else {
+            return Err(AuthError::TwoFactorNotEnabled);
+        };
+        let secret: String = row.try_get("secret")?;
+        let secret = BASE32_NOPAD
+            .decode(secret.as_bytes())
+            .map_err(|e| AuthError::Internal(format!("Invalid two-factor secret: {}", e)))?;
+        let last_used_step: i64 = row.try_get("last_used_step")?;
+        let Ok(code) = code.trim().parse::<u32>() else {
+            return Ok(false);
+        };
+
+        let current = Utc::now().timestamp() / TOTP_STEP_SECONDS;
+        let Some(step) = (current - 1..=current + 1)
+            .filter(|step| *step > last_used_step)
+            .find(|step| totp_code(&secret, *step as u64) == code)
+        else {
+            return Ok(false);
+        };
+        let updated = sqlx::query(
+            "UPDATE two_factor SET last_used_step = ? WHERE user_id = ? AND last_used_step < ?",
+        )
+        .bind(step)
+        .bind(user_id.to_string())
+        .bind(step)
+        .execute(&self.pool)
+        .await?;
+        Ok(updated.rows_affected() == 1)
+    }
+
+    /// Replace the user's recovery codes with `count` new ones
+    pub async fn replace_recovery_codes(
+        &self,
+        user_id: Uuid,
+        count: usize,
+    ) -> Result<Vec<String>, AuthError> {
+        let codes: Vec<String> = (0..count)
+            .map(|_| {
+                let code = Uuid::new_v4().simple().to_string();
+                format!("{}-{}", &code[..5], &code[5..10])
+            })
+            .collect();
+
+        let mut tx = self.pool.begin().await?;
+        sqlx::query("DELETE FROM two_factor_recovery_codes WHERE user_id = ?")
+            .bind(user_id.to_string())
+            .execute(&mut *tx)
+            .await?;
+        for code in &codes {
+            sqlx::query("INSERT INTO two_factor_recovery_codes (code_hash, user_id) VALUES (?, ?)")
+                .bind(hash_token(&normalize_recovery_code(code)))
+                .bind(user_id.to_string())
+                .execute(&mut *tx)
+                .await?;
+        }
+        tx.commit().await?;
+
+        Ok(codes)
+    }
+
+    /// Number of recovery codes the user has not used
+    pub async fn remaining_recovery_codes(&self, user_id: Uuid) -> Result<u64, AuthError> {
+        let row = sqlx::query(
+            "SELECT COUNT(*) AS remaining FROM two_factor_recovery_codes WHERE user_id = ? AND used_at IS NULL",
+        )
+        .bind(user_id.to_string())
+        .fetch_one(&self.pool)
+        .await?;
+        let remaining: i64 = row.try_get("remaining")?;
+        Ok(remaining as u64)
+    }
+
+    /// Disable two-factor authentication and delete its recovery codes
+    pub async fn disable(&self, user_id: Uuid) -> Result<(), AuthError> {
+        let mut tx = self.pool.begin().await?;
+        for table in ["two_factor", "two_factor_recovery_codes"] {
+            sqlx::query(&format!("DELETE FROM {} WHERE user_id = ?", table))
+                .bind(user_id.to_string())
+                .execute(&mut *tx)
+                .await?;
+        }
+        tx.commit().await?;
+        Ok(())
+    }
+
+    /// Mark a recovery code as used; returns whether it was unused
+    async fn use_recovery_code(&self, user_id: Uuid, code: &str) -> Result<bool, AuthError> {
+        let updated = sqlx::query(
+            "UPDATE two_factor_recovery_codes SET used_at = ?
+             WHERE code_hash = ? AND user_id = ? AND used_at IS NULL",
+        )
+        .bind(Utc::now().timestamp_millis())
+        .bind(hash_token(&normalize_recovery_code(code)))
+        .bind(user_id.to_string())
+        .execute(&self.pool)
+        .await?;
+        Ok(updated.rows_affected() == 1)
+    }
+
+    /// Record a login waiting on its second factor, returning its token
+    pub async fn create_challenge(
+        &self,
+        user_id: Uuid,
+        device: &str,
+        ttl: Duration,
+    ) -> Result<String, AuthError> {
+        let now = Utc::now().timestamp_millis();
+        sqlx::query("DELETE FROM two_factor_challenges WHERE expires_at <= ?")
+            .bind(now)
+            .execute(&self.pool)
+            .await?;
+
+        let token = new_token();
+        sqlx::query(
+            "INSERT INTO two_factor_challenges (token_hash, user_id, device, expires_at)
+             VALUES (?, ?, ?, ?)",
+        )
+        .bind(hash_token(&token))
+        .bind(user_id.to_string())
+        .bind(device)
+        .bind((Utc::now() + ttl).timestamp_millis())
+        .execute(&self.pool)
+        .await?;
+        Ok(token)
+    }
+
+    /// The pending login a challenge token belongs to
+    ///
+    /// Fails with [`AuthError::InvalidToken`] if the challenge is unknown
+    /// or expired.
+    pub async fn challenge(&self, token: &str) -> Result<LoginChallenge, AuthError> {
+        let row = sqlx::query(
+            "SELECT user_id, device FROM two_factor_challenges WHERE token_hash = ? AND expires_at > ?",
+        )
+        .bind(hash_token(token))
+        .bind(Utc::now().timestamp_millis())
+        .fetch_optional(&self.pool)
+        .await?
+        .ok_or(AuthError::InvalidToken)?;
+        let user_id: String = row.try_get("user_id")?;
+        Ok(LoginChallenge {
+            user_id: Uuid::parse_str(&user_id).map_err(|e| AuthError::Internal(e.to_string()))?,
+            device: row.try_get("device")?,
+        })
+    }
+
+    /// Count a wrong code against a challenge, discarding it after
+    /// [`MAX_CHALLENGE_ATTEMPTS`]
+    pub async fn fail_challenge(&self, token: &str) -> Result<(), AuthError> {
+        let hash = hash_token(token);
+        sqlx::query(
+            "UPDATE two_factor_challenges SET attempts = attempts + 1 WHERE token_hash = ?",
+        )
+        .bind(&hash)
+        .execute(&self.pool)
+        .await?;
+        sqlx::query("DELETE FROM two_factor_challenges WHERE token_hash = ? AND attempts >= ?")
+            .bind(&hash)
+            .bind(MAX_CHALLENGE_ATTEMPTS)
+            .execute(&self.pool)
+            .await?;
+        Ok(())
+    }
+
+    /// Consume a challenge once its login completed; returns whether it
+    /// was still pending
+    pub async fn finish_challenge(&self, token: &str) -> Result<bool, AuthError> {
+        let deleted = sqlx::query("DELETE FROM two_factor_challenges WHERE token_hash = ?")
+            .bind(hash_token(token))
+            .execute(&self.pool)
+            .await?;
+        Ok(deleted.rows_affected() == 1)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_helpers::migrated_pool;
+
+    #[test]
+    fn test_totp_matches_rfc_6238_vectors() {
+        let secret = b"12345678901234567890";
+        // RFC 6238 appendix B, last six digits of the SHA1 codes
+        assert_eq!(totp_code(secret, 59 / 30), 287082);
+        assert_eq!(totp_code(secret, 1111111109 / 30), 81804);
+        assert_eq!(totp_code(secret, 1234567890 / 30), 5924);
+        assert_eq!(
+            otpauth_uri("Squirrel", "ada@example.com", "JBSWY3DP"),
+            "otpauth://totp/Squirrel:ada%40example.com?secret=JBSWY3DP&issuer=Squirrel&algorithm=SHA1&digits=6&period=30"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_enrollment_codes_and_recovery_codes() {
+        let pool = migrated_pool().await;
+        let store = TwoFactorStore::new(pool);
+        let user = Uuid::new_v4();
+
+        let secret = store.begin_enrollment(user).await.unwrap();
+        let secret = BASE32_NOPAD.decode(secret.as_bytes()).unwrap();
+        let code = |offset: i64| {
+            let step = Utc::now().timestamp() / TOTP_STEP_SECONDS + offset;
+            format!("{:06}", totp_code(&secret, step as u64))
+        };
+        assert!(!store.is_enabled(user).await.unwrap());
+        assert!(matches!(
+            store.confirm(user, "000000x", 3).await,
+            Err(AuthError::InvalidTwoFactorCode)
+        ));
+
+        let recovery = store.confirm(user, &code(-1), 3).await.unwrap();
+        assert_eq!(recovery.len(), 3);
+        assert!(store.is_enabled(user).await.unwrap());
+        assert!(matches!(
+            store.begin_enrollment(user).await,
+            Err(AuthError::TwoFactorAlreadyEnabled)
+        ));
+
+        // Each step is accepted once, and earlier steps are not accepted after it
+        assert!(store.verify(user, &code(1)).await.unwrap());
+        assert!(!store.verify(user, &code(1)).await.unwrap());
+        assert!(!store.verify(user, &code(0)).await.unwrap());
+
+        assert!(store
+            .verify(user, &recovery[0].to_uppercase())
+            .await
+            .unwrap());
+        assert!(!store.verify(user, &recovery[0]).await.unwrap());
+        assert_eq!(store.remaining_recovery_codes(user).await.unwrap(), 2);
+
+        let challenge = store
+            .create_challenge(user, "laptop", Duration::minutes(5))
+            .await
+            .unwrap();
+        assert_eq!(store.challenge(&challenge).await.unwrap().device, "laptop");
+        for _ in 0..MAX_CHALLENGE_ATTEMPTS {
+            store.fail_challenge(&challenge).await.unwrap();
+        }
+        assert!(matches!(
+            store.challenge(&challenge).await,
+            Err(AuthError::InvalidToken)
+        ));
+
+        store.disable(user).await.unwrap();
+        assert!(matches!(
+            store.verify(user, &recovery[1]).await,
+            Err(AuthError::TwoFactorNotEnabled)
+        ));
+    }
+}
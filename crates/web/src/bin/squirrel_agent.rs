@@ -0,0 +1,64 @@
+use anyhow::Result;
+use squirrel_commands::create_command_registry;
+use squirrel_web::agents::{AgentClient, AgentClientConfig};
+use squirrel_web::logs::{LogForwarder, LogForwarderConfig};
+use tracing_subscriber::filter::LevelFilter;
+use tracing_subscriber::prelude::*;
+
+/// Reads an environment variable, treating empty values as unset
+fn env(name: &str) -> Option<String> {
+    std::env::var(name).ok().filter(|value| !value.is_empty())
+}
+
+#[tokio::main]
+async fn main() -> Result<()> {
+    // Build the agent configuration from the environment
+    let mut config = AgentClientConfig::default();
+    if let Some(url) = env("SQUIRREL_SERVER_URL") {
+        config.server_url = url;
+    }
+    config.name = env("SQUIRREL_AGENT_NAME")
+        .or_else(|| env("HOSTNAME"))
+        .unwrap_or(config.name);
+    config.token = env("SQUIRREL_AGENT_TOKEN");
+    if let Some(labels) = env("SQUIRREL_AGENT_LABELS") {
+        // Labels are given as comma-separated key=value pairs
+        config.labels = labels
+            .split(',')
+            .filter_map(|pair| pair.split_once('='))
+            .map(|(key, value)| (key.trim().to_string(), value.trim().to_string()))
+            .collect();
+    }
+    if let Some(max_tasks) = env("SQUIRREL_AGENT_MAX_TASKS").and_then(|value| value.parse().ok()) {
+        config.resources.max_concurrent_tasks = max_tasks;
+    }
+    if let Some(memory_mb) = env("SQUIRREL_AGENT_MEMORY_MB").and_then(|value| value.parse().ok()) {
+        config.resources.memory_mb = Some(memory_mb);
+    }
+
+    // Initialize tracing, forwarding events to the server if asked to
+    let forwarder = env("SQUIRREL_LOG_SERVER").map(|server_url| {
+        LogForwarder::spawn(LogForwarderConfig {
+            server_url,
+            agent_token: config.token.clone(),
+            source: Some(config.name.clone()),
+            ..LogForwarderConfig::default()
+        })
+    });
+    tracing_subscriber::registry()
+        .with(LevelFilter::INFO)
+        .with(tracing_subscriber::fmt::layer())
+        .with(forwarder.map(|forwarder| forwarder.layer("agent")))
+        .init();
+
+    // Execute tasks with the built-in commands
+    let registry = create_command_registry()
+        .ok()
+        .and_then(|registry| registry.lock().ok().map(|registry| registry.clone()))
+        .unwrap_or_default();
+
+    tracing::info!("Starting agent {} for {}", config.name, config.server_url);
+    AgentClient::new(config, registry).run().await?;
+
+    Ok(())
+}
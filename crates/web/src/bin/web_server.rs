@@ -1,21 +1,35 @@
+use std::sync::{Arc, OnceLock};
+use std::time::Duration;
+
 use anyhow::Result;
+use squirrel_app::startup::{Stage, StageError, Startup};
+use squirrel_app::supervisor::Supervisor;
 use squirrel_web::{
     config::Config,
-    create_app, ServerConfig, auth::AuthConfig,
+    create_supervised_app, ServerConfig, auth::AuthConfig,
     CorsConfig, MockSessionConfig,
     setup_database,
 };
+use tokio::sync::Mutex;
+
+/// Count heap allocations for `/api/debug/profile`
+#[cfg(feature = "profiling")]
+#[global_allocator]
+static ALLOCATOR: squirrel_web::profiling::CountingAllocator = squirrel_web::profiling::CountingAllocator;
 
 #[tokio::main]
 async fn main() -> Result<()> {
     // Initialize tracing
     tracing_subscriber::fmt::init();
     
-    // Create server configuration
+    // Create server configuration; deployments set it through the environment
     let server_config = ServerConfig {
-        bind_address: "127.0.0.1".to_string(),
-        port: 3000,
-        database_url: "sqlite::memory:".to_string(),
+        bind_address: std::env::var("SQUIRREL_BIND_ADDRESS").unwrap_or_else(|_| "127.0.0.1".to_string()),
+        port: match std::env::var("SQUIRREL_PORT") {
+            Ok(port) => port.parse()?,
+            Err(_) => 3000,
+        },
+        database_url: std::env::var("DATABASE_URL").unwrap_or_else(|_| "sqlite::memory:".to_string()),
         mcp_config: MockSessionConfig::default(),
         cors_config: CorsConfig {
             allowed_origins: vec![],  // We'll use AllowOrigin::any() in the lib.rs
@@ -25,23 +39,90 @@ async fn main() -> Result<()> {
         auth_config: AuthConfig::default(),
     };
     
-    // Connect to the database and run migrations
-    let db = setup_database(&server_config.database_url)
-        .await
-        .expect("Failed to setup database");
+    // Start the subsystems in dependency order; the process is ready once
+    // every stage is, and stops if one cannot start
+    let config = Arc::new(OnceLock::new());
+    let pool = Arc::new(OnceLock::new());
+    // The router is not Sync, so it is handed out through a mutex
+    let router = Arc::new(Mutex::new(None));
+    let supervisor = Arc::new(Supervisor::new());
+    let startup = Startup::new();
+    let readiness = startup.readiness();
+    let startup = startup
+        .stage(Stage::new("config", {
+            let config = config.clone();
+            move || async move {
+                // Load the config file named by SQUIRREL_CONFIG, or use the defaults
+                let mut app_config = match std::env::var_os("SQUIRREL_CONFIG") {
+                    Some(path) => Config::load(std::path::Path::new(&path))?,
+                    None => Config::default(),
+                };
+                if let Some(dir) = std::env::var_os("SQUIRREL_TOOL_MANIFEST_DIR") {
+                    app_config.tool_manifest_dir = Some(dir.into());
+                }
+                let _ = config.set(app_config);
+                Ok::<_, StageError>(())
+            }
+        }))
+        .stage(
+            // Connect to the database and run migrations
+            Stage::new("database", {
+                let pool = pool.clone();
+                let database_url = server_config.database_url.clone();
+                move || async move {
+                    let _ = pool.set(setup_database(&database_url).await?);
+                    Ok::<_, StageError>(())
+                }
+            })
+            .with_timeout(Duration::from_secs(30))
+            .with_probe({
+                let pool = pool.clone();
+                move || {
+                    let pool = pool.get().cloned();
+                    async move {
+                        match pool {
+                            Some(pool) => sqlx::query("SELECT 1").execute(&pool).await.is_ok(),
+                            None => false,
+                        }
+                    }
+                }
+            }),
+        )
+        .stage(
+            // Create the app, supervising its background tasks
+            Stage::new("app", {
+                let (config, pool, router) = (config.clone(), pool.clone(), router.clone());
+                let (supervisor, readiness) = (supervisor.clone(), readiness.clone());
+                move || async move {
+                    let (Some(app_config), Some(db)) = (config.get().cloned(), pool.get().cloned()) else {
+                        return Err("config and database are not set up".into());
+                    };
+                    let app = create_supervised_app(db, app_config, supervisor, readiness).await;
+                    *router.lock().await = Some(app);
+                    Ok::<_, StageError>(())
+                }
+            })
+            .depends_on("config")
+            .depends_on("database"),
+        );
     
-    // Create a default config to pass to create_app
-    let app_config = Config::default();
-    
-    // Pass the config parameter to create_app
-    let app = create_app(db, app_config).await;
+    startup.run().await?;
+    let app = router.lock().await.take().expect("the app stage creates the app");
     
     // Start the server
-    let addr = std::net::SocketAddr::from(([127, 0, 0, 1], server_config.port));
+    let ip: std::net::IpAddr = server_config.bind_address.parse()?;
+    let addr = std::net::SocketAddr::from((ip, server_config.port));
     tracing::info!("Starting server on {}", addr);
     axum::Server::bind(&addr)
-        .serve(app.into_make_service())
+        .serve(app.into_make_service_with_connect_info::<std::net::SocketAddr>())
+        .with_graceful_shutdown(async {
+            let _ = tokio::signal::ctrl_c().await;
+            tracing::info!("Shutting down");
+        })
         .await?;
     
+    // Stop the background tasks, giving them time to finish what they started
+    supervisor.shutdown(Duration::from_secs(10)).await;
+    
     Ok(())
 } 
\ No newline at end of file
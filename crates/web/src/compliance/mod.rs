@@ -0,0 +1,955 @@
+//! Data protection requests: exporting and erasing everything a user left.
+//!
+//! A user's data is spread over the web database (the account, jobs,
+//! command history, queued jobs, tags, forwarded log events, admin audit
+//! entries, command policies, teams they created, team memberships,
+//! invitations, sessions with their refresh tokens and second factors), the
+//! usage ledger, the assistant's audit log, the MCP contexts of their
+//! conversations and jobs, the command result cache, the feature flag
+//! settings naming them, the upload records of the file service and their
+//! webhook subscriptions. A
+//! [`ComplianceService`] reads all of it into one archive, and erases it in
+//! one of two ways:
+//!
+//! - [`ErasureMode::Anonymize`] scrubs the account, so it can no longer sign
+//!   in and names nobody, and removes credentials, memberships, webhooks
+//!   and free-text assistant calls and conversations; jobs, commands, usage
+//!   and other activity records stay, linked to the anonymous account ID.
+//! - [`ErasureMode::Purge`] removes every record, the account last. Teams
+//!   and invitations the user created stay for their other members, with
+//!   the user's ID replaced.
+//!
+//! Every erasure ends with a verification: each store is searched again and
+//! the report says how many records are left that should be gone. Records
+//! the server cannot erase, such as feature flag settings in the
+//! configuration file, are reported as unverified.
+
+use std::sync::Arc;
+
+use chrono::Utc;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Map, Value};
+use sqlx::sqlite::SqliteRow;
+use sqlx::{Column, Row, SqlitePool, TypeInfo, ValueRef};
+use squirrel_commands::cache::ResultCache;
+use squirrel_mcp::ai::{AuditQuery, Assistant};
+use squirrel_mcp::context_manager::{Context, ContextManager};
+use squirrel_monitoring::accounting::{UsageLedger, UsageQuery};
+use uuid::Uuid;
+
+use crate::api::error::AppError;
+use crate::db::InstrumentedPool;
+use crate::flags::FeatureFlags;
+use crate::handlers::files::FileService;
+use crate::handlers::webhooks::WebhookService;
+
+/// Archive entry describing the export
+pub const MANIFEST_ENTRY: &str = "manifest.json";
+
+/// What erasing does to the records of a store
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Handling {
+    /// Removed in both modes
+    Delete,
+    /// Kept, linked to the anonymous account, when anonymizing; removed
+    /// when purging
+    Keep,
+    /// Kept when anonymizing; when purging, the records stay for the other
+    /// users they belong to, with the user's ID replaced by [`ERASED`]
+    Detach,
+    /// Cannot be erased by the server
+    Manual,
+}
+
+impl Handling {
+    /// The action taken on the records in `mode`
+    fn action(self, mode: ErasureMode) -> ErasureAction {
+        match (self, mode) {
+            (Handling::Delete, _) | (Handling::Keep, ErasureMode::Purge) => ErasureAction::Deleted,
+            (Handling::Keep | Handling::Detach, ErasureMode::Anonymize) => ErasureAction::Kept,
+            (Handling::Detach, ErasureMode::Purge) => ErasureAction::Anonymized,
+            (Handling::Manual, _) => ErasureAction::Unverified,
+        }
+    }
+}
+
+/// A table holding records of users
+struct UserTable {
+    /// Name of the store in reports and of its archive entry
+    store: &'static str,
+    /// Table name
+    table: &'static str,
+    /// Column holding the user ID
+    column: &'static str,
+    /// Prefix of the user ID in the column, e.g. `user:` for policy subjects
+    prefix: &'static str,
+    /// Columns exported, leaving out secrets such as token hashes
+    columns: &'static str,
+    /// What erasing does to the rows
+    handling: Handling,
+}
+
+impl UserTable {
+    /// The value of the column for `user_id`
+    fn key(&self, user_id: &str) -> String {
+        format!("{}{}", self.prefix, user_id)
+    }
+}
+
+/// Tables holding records of users, erased in this order before the account
+///
+/// Refresh tokens are the token hashes of `auth_sessions`.
+const USER_TABLES: &[UserTable] = &[
+    UserTable {
+        store: "jobs",
+        table: "jobs",
+        column: "user_id",
+        prefix: "",
+        columns: "id, repository_url, git_ref, config, status, progress, error, result_url, created_at, updated_at",
+        handling: Handling::Keep,
+    },
+    UserTable {
+        store: "command_executions",
+        table: "command_executions",
+        column: "user_id",
+        prefix: "",
+        columns: "id, command_name, parameters, status, progress, result, error, started_at, completed_at, created_at, updated_at",
+        handling: Handling::Keep,
+    },
+    UserTable {
+        store: "job_queue",
+        table: "job_queue",
+        column: "user_id",
+        prefix: "",
+        columns: "id, kind, payload, status, attempts, error, created_at, updated_at",
+        handling: Handling::Keep,
+    },
+    UserTable {
+        store: "tag_assignments",
+        table: "tag_assignments",
+        column: "user_id",
+        prefix: "",
+        columns: "tag_id, resource_kind, resource_id, created_at",
+        handling: Handling::Keep,
+    },
+    UserTable {
+        store: "log_events",
+        table: "log_events",
+        column: "submitted_by",
+        prefix: "",
+        columns: "id, timestamp, level, component, source, run_id, message, fields, received_at",
+        handling: Handling::Keep,
+    },
+    UserTable {
+        store: "admin_audit",
+        table: "admin_audit",
+        column: "actor",
+        prefix: "",
+        columns: "id, action, target, outcome, detail, created_at",
+        handling: Handling::Keep,
+    },
+    UserTable {
+        store: "command_policies",
+        table: "command_policies",
+        column: "subject",
+        prefix: "user:",
+        columns: "id, effect, scope, pattern, description, created_by, created_at",
+        handling: Handling::Delete,
+    },
+    UserTable {
+        store: "team_members",
+        table: "team_members",
+        column: "user_id",
+        prefix: "",
+        columns: "team_id, role, added_at",
+        handling: Handling::Delete,
+    },
+    UserTable {
+        store: "user_invitations",
+        table: "user_invitations",
+        column: "accepted_by",
+        prefix: "",
+        columns: "id, email, role, team_id, invited_by, created_at, expires_at, accepted_at",
+        handling: Handling::Delete,
+    },
+    UserTable {
+        store: "teams_created",
+        table: "teams",
+        column: "created_by",
+        prefix: "",
+        columns: "id, name, description, created_at, updated_at",
+        handling: Handling::Detach,
+    },
+    UserTable {
+        store: "invitations_sent",
+        table: "user_invitations",
+        column: "invited_by",
+        prefix: "",
+        columns: "id, email, role, team_id, created_at, expires_at, accepted_by, accepted_at",
+        handling: Handling::Detach,
+    },
+    UserTable {
+        store: "auth_sessions",
+        table: "auth_sessions",
+        column: "user_id",
+        prefix: "",
+        columns: "id, device, created_at, last_used_at, expires_at, revoked_at",
+        handling: Handling::Delete,
+    },
+    UserTable {
+        store: "idempotency_keys",
+        table: "idempotency_keys",
+        column: "user_id",
+        prefix: "",
+        columns: "scope, key, created_at, expires_at",
+        handling: Handling::Delete,
+    },
+    UserTable {
+        store: "two_factor",
+        table: "two_factor",
+        column: "user_id",
+        prefix: "",
+        columns: "created_at, enabled_at",
+        handling: Handling::Delete,
+    },
+    UserTable {
+        store: "two_factor_recovery_codes",
+        table: "two_factor_recovery_codes",
+        column: "user_id",
+        prefix: "",
+        columns: "used_at",
+        handling: Handling::Delete,
+    },
+    UserTable {
+        store: "two_factor_challenges",
+        table: "two_factor_challenges",
+        column: "user_id",
+        prefix: "",
+        columns: "device, expires_at",
+        handling: Handling::Delete,
+    },
+];
+
+/// What replaces the ID of a purged user in the records kept for others
+const ERASED: &str = "erased";
+
+/// Store name of the account in reports
+const ACCOUNT: &str = "account";
+
+/// Store name of the usage ledger in reports
+const USAGE: &str = "usage";
+
+/// Store name of the assistant's audit log in reports
+const ASSISTANT_CALLS: &str = "assistant_calls";
+
+/// Store name of the file service's upload records in reports
+const UPLOADS: &str = "uploads";
+
+/// Store name of the webhooks the user registered in reports
+const WEBHOOKS: &str = "webhooks";
+
+/// Store name of the assistant conversations kept as MCP contexts in reports
+const CONVERSATIONS: &str = "assistant_conversations";
+
+/// Store name of the MCP contexts of the user's jobs in reports
+const JOB_CONTEXTS: &str = "job_contexts";
+
+/// Store name of the cached results of the user's runs in reports
+const CACHED_RESULTS: &str = "cached_results";
+
+/// Store name of the feature flag settings admins changed in reports
+const FLAG_SETTINGS: &str = "flag_settings";
+
+/// Store name of the feature flag settings of the configuration in reports
+const CONFIGURED_FLAGS: &str = "configured_flags";
+
+/// How a user's data is erased
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ErasureMode {
+    /// Scrub the account and remove credentials, keeping activity records
+    /// linked to the anonymous account
+    #[default]
+    Anonymize,
+    /// Remove every record
+    Purge,
+}
+
+/// What an erasure does to the records of a store
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ErasureAction {
+    /// The records are removed
+    Deleted,
+    /// The records are scrubbed of what names the user
+    Anonymized,
+    /// The records stay, linked to the anonymous account
+    Kept,
+    /// The records cannot be erased by the server and must be removed by
+    /// hand, e.g. from the configuration file
+    Unverified,
+}
+
+/// What an erasure did to one store
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct StoreReport {
+    /// Table or store, e.g. `jobs` or `usage`
+    pub store: String,
+    /// What was done to its records
+    pub action: ErasureAction,
+    /// Records of the user found before the erasure
+    pub records: u64,
+    /// Records found afterwards that should be gone, or that are left to
+    /// remove by hand; `None` on dry runs and for kept records
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub remaining: Option<u64>,
+}
+
+/// Outcome of an erasure
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ErasureReport {
+    /// ID of the erased user
+    pub user_id: String,
+    /// How the data was erased
+    pub mode: ErasureMode,
+    /// Whether nothing was changed
+    pub dry_run: bool,
+    /// Username and email address the account now has, when anonymized
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub pseudonym: Option<String>,
+    /// What was done to every store
+    pub stores: Vec<StoreReport>,
+    /// Whether no store has records left that should be gone or that must
+    /// be removed by hand
+    pub verified: bool,
+}
+
+/// Exports and erases the data of users across the database, the usage
+/// ledger, the assistant's audit log, the MCP contexts, the result cache,
+/// the feature flags, the file service and the webhooks
+#[derive(Clone, Default)]
+pub struct ComplianceService {
+    pool: Option<InstrumentedPool>,
+    ledger: Option<Arc<UsageLedger>>,
+    assistant: Option<Arc<Assistant>>,
+    contexts: Option<Arc<ContextManager>>,
+    result_cache: Option<Arc<ResultCache>>,
+    flags: Option<Arc<FeatureFlags>>,
+    files: Option<Arc<FileService>>,
+    webhooks: Option<Arc<WebhookService>>,
+}
+
+impl ComplianceService {
+    /// Create a new ComplianceService with no stores
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Cover the account and the records in the database of `pool`
+    pub fn with_database(mut self, pool: SqlitePool) -> Self {
+        self.pool = Some(pool.into());
+        self
+    }
+
+    /// Cover the usage `ledger` recorded
+    pub fn with_usage_ledger(mut self, ledger: Arc<UsageLedger>) -> Self {
+        self.ledger = Some(ledger);
+        self
+    }
+
+    /// Cover the language model calls the audit log of `assistant` recorded
+    pub fn with_assistant(mut self, assistant: Arc<Assistant>) -> Self {
+        self.assistant = Some(assistant);
+        self
+    }
+
+    /// Cover the assistant conversations and job contexts kept in `contexts`
+    pub fn with_context_manager(mut self, contexts: Arc<ContextManager>) -> Self {
+        self.contexts = Some(contexts);
+        self
+    }
+
+    /// Cover the results of runs `result_cache` keeps
+    pub fn with_result_cache(mut self, result_cache: Arc<ResultCache>) -> Self {
+        self.result_cache = Some(result_cache);
+        self
+    }
+
+    /// Cover the settings of `flags` that name users
+    pub fn with_feature_flags(mut self, flags: Arc<FeatureFlags>) -> Self {
+        self.flags = Some(flags);
+        self
+    }
+
+    /// Cover the uploads `files` keeps records of
+    pub fn with_file_service(mut self, files: Arc<FileService>) -> Self {
+        self.files = Some(files);
+        self
+    }
+
+    /// Cover the webhooks registered with `webhooks`
+    pub fn with_webhooks(mut self, webhooks: Arc<WebhookService>) -> Self {
+        self.webhooks = Some(webhooks);
+        self
+    }
+
+    /// The database, which every export and erasure needs for the account
+    fn pool(&self) -> Result<&InstrumentedPool, AppError> {
+        self.pool
+            .as_ref()
+            .ok_or_else(|| AppError::Internal("Compliance database not configured".to_string()))
+    }
+
+    /// The account row of `user_id`, without the password hash
+    async fn account(&self, user_id: &str) -> Result<Value, AppError> {
+        let row = sqlx::query(
+            "SELECT id, username, email, role, created_at, updated_at, deactivated_at FROM users WHERE id = ?",
+        )
+        .bind(user_id)
+        .fetch_optional(self.pool()?)
+        .await?
+        .ok_or_else(|| AppError::NotFound(format!("No user {}", user_id)))?;
+        Ok(row_json(&row)?)
+    }
+
+    /// Every record of `user_id`, as named JSON documents
+    pub async fn collect(&self, user_id: &str) -> Result<Vec<(String, Value)>, AppError> {
+        let mut documents = vec![(ACCOUNT.to_string(), self.account(user_id).await?)];
+        for table in USER_TABLES {
+            let sql = format!("SELECT {} FROM {} WHERE {} = ?", table.columns, table.table, table.column);
+            let rows = sqlx::query(&sql).bind(table.key(user_id)).fetch_all(self.pool()?).await?;
+            let rows = rows.iter().map(row_json).collect::<Result<Vec<_>, _>>()?;
+            documents.push((table.store.to_string(), Value::Array(rows)));
+        }
+        for (store, records, _) in self.external_records(user_id).await? {
+            documents.push((store.to_string(), records));
+        }
+        Ok(documents)
+    }
+
+    /// A gzip-compressed tar archive of every record of `user_id`: one
+    /// `<store>.json` entry per store, after a manifest counting them
+    ///
+    /// Fails with [`AppError::NotFound`] when there is no such account.
+    pub async fn export(&self, user_id: &str) -> Result<Vec<u8>, AppError> {
+        let documents = self.collect(user_id).await?;
+        let exported_at = Utc::now();
+        let counts: Map<String, Value> = documents
+            .iter()
+            .map(|(store, document)| {
+                let records = document.as_array().map_or(1, Vec::len);
+                (store.clone(), Value::from(records))
+            })
+            .collect();
+        let manifest = json!({
+            "user_id": user_id,
+            "exported_at": exported_at,
+            "squirrel_version": env!("CARGO_PKG_VERSION"),
+            "records": counts,
+        });
+
+        let mut entries = vec![(MANIFEST_ENTRY.to_string(), serde_json::to_vec_pretty(&manifest).map_err(json_error)?)];
+        for (store, document) in &documents {
+            let data = serde_json::to_vec_pretty(document).map_err(json_error)?;
+            entries.push((format!("{}.json", store), data));
+        }
+        let mtime = u64::try_from(exported_at.timestamp()).unwrap_or_default();
+        let mut archive = Vec::new();
+        squirrel_commands::bundle::write_archive(&mut archive, &entries, mtime)
+            .map_err(|e| AppError::Internal(format!("Failed to write export archive: {}", e)))?;
+        Ok(archive)
+    }
+
+    /// Erase the records of `user_id` as `mode` says, or with `dry_run` only
+    /// report what would be erased
+    ///
+    /// The database changes in one transaction; the stores outside it
+    /// follow once it committed. Fails with [`AppError::NotFound`] when
+    /// there is no such account.
+    pub async fn erase(&self, user_id: &str, mode: ErasureMode, dry_run: bool) -> Result<ErasureReport, AppError> {
+        self.account(user_id).await?;
+        let pool = self.pool()?;
+        let pseudonym = (mode == ErasureMode::Anonymize).then(|| pseudonym(user_id));
+
+        let mut stores = Vec::new();
+        for table in USER_TABLES {
+            stores.push(StoreReport {
+                store: table.store.to_string(),
+                action: table.handling.action(mode),
+                records: count(pool, table.table, table.column, &table.key(user_id)).await?,
+                remaining: None,
+            });
+        }
+        stores.push(StoreReport {
+            store: ACCOUNT.to_string(),
+            action: match mode {
+                ErasureMode::Anonymize => ErasureAction::Anonymized,
+                ErasureMode::Purge => ErasureAction::Deleted,
+            },
+            records: 1,
+            remaining: None,
+        });
+        for (store, records, handling) in self.external_records(user_id).await? {
+            stores.push(StoreReport {
+                store: store.to_string(),
+                action: handling.action(mode),
+                records: records.as_array().map_or(0, Vec::len) as u64,
+                remaining: None,
+            });
+        }
+
+        if !dry_run {
+            let mut tx = pool.begin().await?;
+            for (table, report) in USER_TABLES.iter().zip(&stores) {
+                match report.action {
+                    ErasureAction::Deleted => {
+                        let sql = format!("DELETE FROM {} WHERE {} = ?", table.table, table.column);
+                        sqlx::query(&sql).bind(table.key(user_id)).execute(&mut *tx).await?;
+                    }
+                    ErasureAction::Anonymized => {
+                        let sql = format!("UPDATE {} SET {} = ? WHERE {} = ?", table.table, table.column, table.column);
+                        sqlx::query(&sql).bind(ERASED).bind(table.key(user_id)).execute(&mut *tx).await?;
+                    }
+                    ErasureAction::Kept | ErasureAction::Unverified => {}
+                }
+            }
+            match &pseudonym {
+                Some(pseudonym) => {
+                    let now = Utc::now();
+                    sqlx::query(
+                        "UPDATE users SET username = ?, email = ?, password_hash = '',
+                         deactivated_at = COALESCE(deactivated_at, ?), updated_at = ? WHERE id = ?",
+                    )
+                    .bind(pseudonym)
+                    .bind(format!("{}@erased.invalid", pseudonym))
+                    .bind(now)
+                    .bind(now)
+                    .bind(user_id)
+                    .execute(&mut *tx)
+                    .await?;
+                }
+                None => {
+                    sqlx::query("DELETE FROM users WHERE id = ?").bind(user_id).execute(&mut *tx).await?;
+                }
+            }
+            tx.commit().await?;
+
+            for report in &stores[USER_TABLES.len() + 1..] {
+                if report.action == ErasureAction::Deleted {
+                    self.remove_external(&report.store, user_id).await?;
+                }
+            }
+
+            self.verify(user_id, pseudonym.as_deref(), &mut stores).await?;
+        }
+        let verified = !dry_run
+            && stores
+                .iter()
+                .all(|report| report.action == ErasureAction::Kept || report.remaining == Some(0));
+
+        Ok(ErasureReport {
+            user_id: user_id.to_string(),
+            mode,
+            dry_run,
+            pseudonym,
+            stores,
+            verified,
+        })
+    }
+
+    /// Records of `user_id` outside the database: the store, its records as
+    /// a JSON array and what erasing does to them
+    async fn external_records(&self, user_id: &str) -> Result<Vec<(&'static str, Value, Handling)>, AppError> {
+        let mut records = Vec::new();
+        if let Some(ledger) = &self.ledger {
+            let query = UsageQuery {
+                user: Some(user_id.to_string()),
+                ..UsageQuery::default()
+            };
+            records.push((USAGE, to_json(ledger.records(&query))?, Handling::Keep));
+        }
+        if let Some(auditor) = self.assistant.as_ref().and_then(|assistant| assistant.auditor()) {
+            let query = AuditQuery {
+                user: Some(user_id.to_string()),
+                ..AuditQuery::default()
+            };
+            let calls = auditor.log().records(&query).map_err(|e| AppError::Internal(e.to_string()))?;
+            records.push((ASSISTANT_CALLS, to_json(calls)?, Handling::Delete));
+        }
+        if self.contexts.is_some() {
+            records.push((CONVERSATIONS, to_json(self.contexts_of("user", user_id).await?)?, Handling::Delete));
+            records.push((JOB_CONTEXTS, to_json(self.contexts_of("owner", user_id).await?)?, Handling::Keep));
+        }
+        if let Some(result_cache) = &self.result_cache {
+            let mut entries = result_cache.entries()?;
+            entries.retain(|entry| entry.owner.as_deref() == Some(user_id));
+            records.push((CACHED_RESULTS, to_json(entries)?, Handling::Delete));
+        }
+        if let Some(flags) = &self.flags {
+            records.push((FLAG_SETTINGS, to_json(flags.overrides_naming(user_id))?, Handling::Delete));
+            records.push((CONFIGURED_FLAGS, to_json(flags.configured_naming(user_id))?, Handling::Manual));
+        }
+        if let Some(files) = &self.files {
+            records.push((UPLOADS, to_json(files.uploads_of(user_id))?, Handling::Keep));
+        }
+        if let Some(webhooks) = &self.webhooks {
+            records.push((WEBHOOKS, to_json(webhooks.list(user_id)?)?, Handling::Delete));
+        }
+        Ok(records)
+    }
+
+    /// The MCP contexts whose metadata names `user_id` under `key`
+    async fn contexts_of(&self, key: &str, user_id: &str) -> Result<Vec<Context>, AppError> {
+        let Some(contexts) = &self.contexts else {
+            return Ok(Vec::new());
+        };
+        let value = user_id.replace('\\', "\\\\").replace('"', "\\\"");
+        let query = format!("metadata.{} = \"{}\"", key, value);
+        let mut found = contexts.search(&query).await.map_err(|e| AppError::Internal(e.to_string()))?;
+        found.retain(|context| {
+            context
+                .metadata
+                .as_ref()
+                .and_then(|metadata| metadata.get(key))
+                .and_then(Value::as_str)
+                == Some(user_id)
+        });
+        Ok(found)
+    }
+
+    /// Remove the records of `user_id` from `store`, a store outside the
+    /// database
+    async fn remove_external(&self, store: &str, user_id: &str) -> Result<(), AppError> {
+        match store {
+            USAGE => {
+                if let Some(ledger) = &self.ledger {
+                    ledger.remove_user(user_id).map_err(|e| AppError::Internal(e.to_string()))?;
+                }
+            }
+            ASSISTANT_CALLS => {
+                if let Some(auditor) = self.assistant.as_ref().and_then(|assistant| assistant.auditor()) {
+                    auditor.log().remove_user(user_id).map_err(|e| AppError::Internal(e.to_string()))?;
+                }
+            }
+            CONVERSATIONS | JOB_CONTEXTS => {
+                let key = if store == CONVERSATIONS { "user" } else { "owner" };
+                if let Some(contexts) = &self.contexts {
+                    for context in self.contexts_of(key, user_id).await? {
+                        contexts
+                            .delete_context(context.id)
+                            .await
+                            .map_err(|e| AppError::Internal(e.to_string()))?;
+                    }
+                }
+            }
+            CACHED_RESULTS => {
+                if let Some(result_cache) = &self.result_cache {
+                    result_cache.invalidate_where(|entry| entry.owner.as_deref() == Some(user_id))?;
+                }
+            }
+            FLAG_SETTINGS => {
+                if let Some(flags) = &self.flags {
+                    flags.forget_user(ERASED, user_id).await?;
+                }
+            }
+            UPLOADS => {
+                if let Some(files) = &self.files {
+                    files.discard_uploads_of(user_id).await;
+                }
+            }
+            WEBHOOKS => {
+                if let Some(webhooks) = &self.webhooks {
+                    webhooks.remove_owner(user_id)?;
+                }
+            }
+            _ => {}
+        }
+        Ok(())
+    }
+
+    /// Search every store again, recording what is left that should be gone
+    async fn verify(&self, user_id: &str, pseudonym: Option<&str>, stores: &mut [StoreReport]) -> Result<(), AppError> {
+        let pool = self.pool()?;
+        let external = self.external_records(user_id).await?;
+        for report in stores.iter_mut().filter(|report| report.action != ErasureAction::Kept) {
+            let remaining = if let Some(table) = USER_TABLES.iter().find(|table| table.store == report.store) {
+                count(pool, table.table, table.column, &table.key(user_id)).await?
+            } else if report.store == ACCOUNT {
+                // An anonymized account is left only under its pseudonym
+                sqlx::query_scalar::<_, i64>("SELECT COUNT(*) FROM users WHERE id = ? AND (?2 IS NULL OR username != ?2)")
+                    .bind(user_id)
+                    .bind(pseudonym)
+                    .fetch_one(pool)
+                    .await?
+                    .unsigned_abs()
+            } else {
+                external
+                    .iter()
+                    .find(|(store, _, _)| *store == report.store)
+                    .map_or(0, |(_, records, _)| records.as_array().map_or(0, Vec::len) as u64)
+            };
+            report.remaining = Some(remaining);
+        }
+        Ok(())
+    }
+}
+
+/// Records as a JSON array
+fn to_json<T: Serialize>(records: T) -> Result<Value, AppError> {
+    serde_json::to_value(records).map_err(json_error)
+}
+
+/// Report a record that could not be encoded
+fn json_error(error: serde_json::Error) -> AppError {
+    AppError::Internal(format!("Failed to encode records: {}", error))
+}
+
+/// Username and email local part of an anonymized account
+fn pseudonym(user_id: &str) -> String {
+    match Uuid::parse_str(user_id) {
+        Ok(id) => format!("erased-{}", id.simple()),
+        Err(_) => format!("erased-{}", Uuid::new_v4().simple()),
+    }
+}
+
+/// Number of rows of `table` whose `column` is `key`
+async fn count(pool: &InstrumentedPool, table: &str, column: &str, key: &str) -> Result<u64, AppError> {
+    let sql = format!("SELECT COUNT(*) FROM {} WHERE {} = ?", table, column);
+    let count: i64 = sqlx::query_scalar(&sql).bind(key).fetch_one(pool).await?;
+    Ok(count.unsigned_abs())
+}
+
+/// A row as a JSON object, by the types its values are stored as
+fn row_json(row: &SqliteRow) -> Result<Value, sqlx::Error> {
+    let mut object = Map::new();
+    for column in row.columns() {
+        let index = column.ordinal();
+        let raw = row.try_get_raw(index)?;
+        let value = if raw.is_null() {
+            Value::Null
+        } else {
+            let kind = raw.type_info().name().to_string();
+            match kind.as_str() {
+                "INTEGER" => Value::from(row.try_get::<i64, _>(index)?),
+                "REAL" => Value::from(row.try_get::<f64, _>(index)?),
+                _ => Value::from(row.try_get::<String, _>(index)?),
+            }
+        };
+        object.insert(column.name().to_string(), value);
+    }
+    Ok(Value::Object(object))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::FlagsConfig;
+    use crate::api::webhooks::CreateWebhookRequest;
+    use crate::flags::MemoryFlagStore;
+    use crate::handlers::webhooks::{HttpTransport, WebhookEvent};
+    use crate::test_helpers::migrated_pool;
+    use squirrel_commands::manifest::{ManifestOptions, RunManifest};
+    use squirrel_core::flags::FlagSetting;
+    use squirrel_mcp::ai::ConversationStore;
+    use squirrel_mcp::persistence::PersistenceConfig;
+    use squirrel_monitoring::accounting::{AccountingConfig, ResourceUsage};
+
+    #[tokio::test]
+    async fn test_export_then_erase_a_user() {
+        let pool = migrated_pool().await;
+        let now = Utc::now();
+        let (alice, bob) = (Uuid::new_v4().to_string(), Uuid::new_v4().to_string());
+        for (id, name) in [(&alice, "alice"), (&bob, "bob")] {
+            sqlx::query(
+                "INSERT INTO users (id, username, email, password_hash, role, created_at, updated_at)
+                 VALUES (?, ?, ?, 'hash', 'User', ?, ?)",
+            )
+            .bind(id)
+            .bind(name)
+            .bind(format!("{}@lab.org", name))
+            .bind(now)
+            .bind(now)
+            .execute(&pool)
+            .await
+            .unwrap();
+            sqlx::query(
+                "INSERT INTO jobs (id, user_id, repository_url, git_ref, config, status, progress, created_at, updated_at)
+                 VALUES (?, ?, 'https://example.org/repo', 'main', '{}', 'completed', 1.0, ?, ?)",
+            )
+            .bind(Uuid::new_v4().to_string())
+            .bind(id)
+            .bind(now)
+            .bind(now)
+            .execute(&pool)
+            .await
+            .unwrap();
+        }
+        sqlx::query(
+            "INSERT INTO auth_sessions (id, user_id, device, token_hash, created_at, last_used_at, expires_at)
+             VALUES ('s1', ?, 'laptop', 'secret-hash', 0, 0, 0)",
+        )
+        .bind(&alice)
+        .execute(&pool)
+        .await
+        .unwrap();
+        let ledger = Arc::new(UsageLedger::in_memory(AccountingConfig::default()));
+        ledger.record(&alice, "lab", ResourceUsage::execution()).unwrap();
+        let service = ComplianceService::new().with_database(pool.clone()).with_usage_ledger(ledger.clone());
+
+        let archive = service.export(&alice).await.unwrap();
+        let entries = squirrel_commands::bundle::read_archive(archive.as_slice()).unwrap();
+        assert_eq!(entries[0].0, MANIFEST_ENTRY);
+        let entry = |name: &str| -> Value {
+            let (_, data) = entries.iter().find(|(entry, _)| entry == name).unwrap();
+            serde_json::from_slice(data).unwrap()
+        };
+        assert_eq!(entry("account.json")["username"], "alice");
+        assert!(entry("account.json").get("password_hash").is_none());
+        assert_eq!(entry("jobs.json").as_array().unwrap().len(), 1);
+        assert!(entry("auth_sessions.json")[0].get("token_hash").is_none());
+        assert_eq!(entry(MANIFEST_ENTRY)["records"]["usage"], 1);
+
+        let dry_run = service.erase(&alice, ErasureMode::Anonymize, true).await.unwrap();
+        assert!(!dry_run.verified);
+        assert_eq!(count(&InstrumentedPool::from(pool.clone()), "auth_sessions", "user_id", &alice).await.unwrap(), 1);
+
+        let report = service.erase(&alice, ErasureMode::Anonymize, false).await.unwrap();
+        assert!(report.verified);
+        let store = |report: &ErasureReport, name: &str| report.stores.iter().find(|s| s.store == name).cloned().unwrap();
+        assert_eq!(store(&report, "auth_sessions").action, ErasureAction::Deleted);
+        assert_eq!(store(&report, "auth_sessions").remaining, Some(0));
+        assert_eq!(store(&report, "jobs").action, ErasureAction::Kept);
+        let account = service.account(&alice).await.unwrap();
+        assert_eq!(account["username"].as_str(), report.pseudonym.as_deref());
+        assert!(!account["deactivated_at"].is_null());
+        assert_eq!(ledger.records(&UsageQuery::default()).len(), 1);
+
+        let report = service.erase(&alice, ErasureMode::Purge, false).await.unwrap();
+        assert!(report.verified);
+        assert_eq!(store(&report, "jobs").records, 1);
+        assert!(matches!(service.export(&alice).await, Err(AppError::NotFound(_))));
+        assert!(ledger.records(&UsageQuery::default()).is_empty());
+        assert_eq!(service.collect(&bob).await.unwrap()[1].1.as_array().unwrap().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_purge_covers_policies_teams_flags_cache_contexts_and_webhooks() {
+        let pool = migrated_pool().await;
+        let dir = tempfile::tempdir().unwrap();
+        let now = Utc::now();
+        let (alice, bob) = (Uuid::new_v4().to_string(), Uuid::new_v4().to_string());
+        sqlx::query(
+            "INSERT INTO users (id, username, email, password_hash, role, created_at, updated_at)
+             VALUES (?, 'alice', 'alice@lab.org', 'hash', 'User', ?, ?)",
+        )
+        .bind(&alice)
+        .bind(now)
+        .bind(now)
+        .execute(&pool)
+        .await
+        .unwrap();
+        for subject in [format!("user:{}", alice), format!("user:{}", bob)] {
+            sqlx::query(
+                "INSERT INTO command_policies (id, subject, effect, scope, pattern, created_by, created_at)
+                 VALUES (?, ?, 'deny', 'command', 'deploy', 'root', 0)",
+            )
+            .bind(Uuid::new_v4().to_string())
+            .bind(subject)
+            .execute(&pool)
+            .await
+            .unwrap();
+        }
+        sqlx::query("INSERT INTO teams (id, name, created_by, created_at, updated_at) VALUES ('t1', 'lab', ?, 0, 0)")
+            .bind(&alice)
+            .execute(&pool)
+            .await
+            .unwrap();
+        sqlx::query(
+            "INSERT INTO user_invitations (id, email, role, team_id, invited_by, created_at, expires_at)
+             VALUES ('i1', 'carol@lab.org', 'User', 't1', ?, 0, 0)",
+        )
+        .bind(&alice)
+        .execute(&pool)
+        .await
+        .unwrap();
+
+        let mut flags_config = FlagsConfig::default();
+        flags_config.settings.set(
+            "wasm_plugins",
+            FlagSetting {
+                users: [alice.clone()].into(),
+                ..FlagSetting::off()
+            },
+        );
+        let flags = Arc::new(FeatureFlags::new(Arc::new(MemoryFlagStore::new()), flags_config));
+        let setting = FlagSetting {
+            users: [alice.clone(), bob.clone()].into(),
+            ..FlagSetting::off()
+        };
+        flags.set("root", "ai_assistant", setting).await.unwrap();
+        let result_cache = Arc::new(ResultCache::open(dir.path().join("cache")).unwrap());
+        for owner in [&alice, &bob] {
+            let manifest = RunManifest::capture("count", std::slice::from_ref(owner), &ManifestOptions::default()).unwrap();
+            ResultCache::open(dir.path().join("cache")).unwrap().with_owner(owner).store(&manifest, "4 bases").unwrap();
+        }
+        let contexts = Arc::new(
+            ContextManager::with_persistence(PersistenceConfig {
+                data_dir: dir.path().join("mcp"),
+                ..PersistenceConfig::default()
+            })
+            .await,
+        );
+        let conversations = ConversationStore::new(contexts.clone());
+        let conversation = conversations.start(&alice).await.unwrap();
+        conversations.start(&bob).await.unwrap();
+        let webhooks = Arc::new(WebhookService::new(Arc::new(HttpTransport::new(std::time::Duration::from_secs(1)))));
+        for owner in [&alice, &bob] {
+            let request = CreateWebhookRequest {
+                url: "https://hooks.lab.org/squirrel".to_string(),
+                events: vec![WebhookEvent::CommandFailed],
+                secret: Some("hook-secret".to_string()),
+                description: None,
+                template: None,
+            };
+            webhooks.create(owner, request).unwrap();
+        }
+
+        let service = ComplianceService::new()
+            .with_database(pool.clone())
+            .with_context_manager(contexts.clone())
+            .with_result_cache(result_cache.clone())
+            .with_feature_flags(flags.clone())
+            .with_webhooks(webhooks.clone());
+        let entries = squirrel_commands::bundle::read_archive(service.export(&alice).await.unwrap().as_slice()).unwrap();
+        let manifest: Value = serde_json::from_slice(&entries[0].1).unwrap();
+        let exported = ["command_policies", "teams_created", "invitations_sent", "cached_results", "assistant_conversations"];
+        for store in exported.into_iter().chain(["webhooks"]) {
+            assert_eq!(manifest["records"][store], 1, "{}", store);
+        }
+        let (_, exported) = entries.iter().find(|(name, _)| name == "webhooks.json").unwrap();
+        assert!(!String::from_utf8_lossy(exported).contains("hook-secret"));
+
+        let report = service.erase(&alice, ErasureMode::Purge, false).await.unwrap();
+        let store = |name: &str| report.stores.iter().find(|s| s.store == name).cloned().unwrap();
+        for name in ["command_policies", "cached_results", "flag_settings", "assistant_conversations", "webhooks"] {
+            assert_eq!((store(name).action, store(name).remaining), (ErasureAction::Deleted, Some(0)), "{}", name);
+        }
+        assert_eq!((store("teams_created").action, store("teams_created").remaining), (ErasureAction::Anonymized, Some(0)));
+        assert_eq!((store("invitations_sent").records, store("invitations_sent").remaining), (1, Some(0)));
+        // The configured flag setting is left to remove from the configuration
+        assert_eq!((store("configured_flags").action, store("configured_flags").remaining), (ErasureAction::Unverified, Some(1)));
+        assert!(!report.verified);
+
+        let created_by: String = sqlx::query_scalar("SELECT created_by FROM teams WHERE id = 't1'").fetch_one(&pool).await.unwrap();
+        assert_eq!(created_by, ERASED);
+        assert_eq!(count(&InstrumentedPool::from(pool.clone()), "command_policies", "subject", &format!("user:{}", bob)).await.unwrap(), 1);
+        let ai_assistant = flags.get("ai_assistant").unwrap().setting.unwrap();
+        assert_eq!(ai_assistant.users, [bob.clone()].into());
+        let owners: Vec<_> = result_cache.entries().unwrap().into_iter().map(|entry| entry.owner).collect();
+        assert_eq!(owners, [Some(bob.clone())]);
+        assert!(conversations.get(conversation, &alice).await.is_err());
+        assert_eq!(service.contexts_of("user", &bob).await.unwrap().len(), 1);
+        assert!(webhooks.list(&alice).unwrap().is_empty());
+        assert_eq!(webhooks.list(&bob).unwrap().len(), 1);
+    }
+}
@@ -1,12 +1,134 @@
+use std::path::{Path, PathBuf};
+
+use ipnet::IpNet;
 use serde::{Deserialize, Serialize};
 
+use squirrel_commands::cache::ResultCache;
+use squirrel_core::blob::BlobStore;
+use squirrel_core::encryption::{self, EncryptionError, MasterKey};
+use squirrel_core::flags::FlagSet;
+use squirrel_core::redaction::RedactionConfig;
+use squirrel_mcp::ai::AssistantConfig;
+use squirrel_mcp::tool::ExecutionHistory;
+use squirrel_mcp::SecurityLevel;
+use squirrel_monitoring::accounting::AccountingConfig;
+use squirrel_monitoring::alerts::notify::NotificationChannel;
+use squirrel_monitoring::alerts::LifecycleConfig;
+use squirrel_monitoring::metrics::MetricConfig;
+use squirrel_monitoring::reports::ReportConfig;
+use squirrel_monitoring::watchdog::WatchdogConfig;
+
+use crate::maintenance::CronSchedule;
+use crate::queue::OrphanPolicy;
+use crate::websocket::BackplaneConfig;
+
 /// Configuration for the web server
 #[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
 pub struct Config {
     /// Base URL for the API
     pub api_base_url: String,
     /// Timeout for API requests
     pub request_timeout_secs: u64,
+    /// MCP server commands are forwarded to
+    #[serde(default)]
+    pub mcp: McpClientConfig,
+    /// Remote agent settings
+    #[serde(default)]
+    pub agents: AgentConfig,
+    /// Leader election settings for multi-instance deployments
+    #[serde(default)]
+    pub leader_election: LeaderElectionConfig,
+    /// Leases and recovery of the persistent job queue
+    #[serde(default)]
+    pub job_queue: JobQueueConfig,
+    /// Lifetime of the idempotency keys of submissions
+    #[serde(default)]
+    pub idempotency: IdempotencyConfig,
+    /// Admin flags refresh and garbage collection
+    #[serde(default)]
+    pub admin: AdminConfig,
+    /// Feature flags gating experimental behavior
+    #[serde(default)]
+    pub flags: FlagsConfig,
+    /// In-memory caches of frequent reads
+    #[serde(default)]
+    pub read_cache: ReadCacheConfig,
+    /// Slow query log of the web database
+    #[serde(default)]
+    pub query_log: QueryLogConfig,
+    /// Read replicas of the web database
+    #[serde(default)]
+    pub replicas: ReplicaConfig,
+    /// Retries and circuit breaking for statements of the web database
+    #[serde(default)]
+    pub db_resilience: DbResilienceConfig,
+    /// Scheduled retention cleanup and compaction of the web database
+    #[serde(default)]
+    pub maintenance: MaintenanceConfig,
+    /// Invitations and password resets of user accounts
+    #[serde(default)]
+    pub users: UsersConfig,
+    /// Log events forwarded by agents, plugins and the CLI
+    #[serde(default)]
+    pub logs: LogsConfig,
+    /// Rules redacting secrets from logs, contexts and webhook payloads
+    #[serde(default)]
+    pub redaction: RedactionConfig,
+    /// Pub/sub backplane sharing WebSocket events between instances
+    #[serde(default)]
+    pub backplane: BackplaneConfig,
+    /// Alert grouping and the file alert state is shared through
+    #[serde(default)]
+    pub alerts: LifecycleConfig,
+    /// Daily and weekly report digests, sent by the leader
+    #[serde(default)]
+    pub reports: ReportsConfig,
+    /// StatsD and InfluxDB backends the server's metrics are exported to
+    /// every `interval` seconds
+    #[serde(default)]
+    pub metrics: MetricConfig,
+    /// Directory of tool manifests registered at startup
+    #[serde(default)]
+    pub tool_manifest_dir: Option<PathBuf>,
+    /// File tool execution history is persisted to, for `squirrel tool history`
+    #[serde(default)]
+    pub tool_history_path: Option<PathBuf>,
+    /// IP and country access rules, checked before any other handling
+    #[serde(default)]
+    pub access: AccessConfig,
+    /// Security headers added to responses
+    #[serde(default)]
+    pub security_headers: SecurityHeadersConfig,
+    /// File upload and download settings
+    #[serde(default)]
+    pub files: FilesConfig,
+    /// Scheduled integrity checks of the blob store
+    #[serde(default)]
+    pub blob_integrity: IntegrityConfig,
+    /// Directory of cached command results, shared with `squirrel --cache`
+    #[serde(default)]
+    pub result_cache_dir: Option<PathBuf>,
+    /// Usage accounting period, ledger file and quotas
+    #[serde(default)]
+    pub usage: AccountingConfig,
+    /// Memory budget guarding the server against being killed for memory
+    #[serde(default)]
+    pub memory_watchdog: WatchdogConfig,
+    /// Language model behind `/api/assistant`; `SQUIRREL_AI_ENDPOINT` and
+    /// `SQUIRREL_AI_MODEL` override the endpoint and model
+    #[serde(default)]
+    pub assistant: AssistantConfig,
+    /// Request rate limits per client address
+    #[serde(default)]
+    pub rate_limit: RateLimitConfig,
+    /// Gates a candidate configuration must pass before it is promoted
+    #[serde(default)]
+    pub rollout: RolloutConfig,
+    /// Locale of API messages when a request's `Accept-Language` matches
+    /// none with a catalog; English if unset
+    #[serde(default)]
+    pub locale: Option<String>,
 }
 
 impl Default for Config {
@@ -14,6 +136,727 @@ impl Default for Config {
         Self {
             api_base_url: "http://localhost:8000".to_string(),
             request_timeout_secs: 30,
+            mcp: McpClientConfig::default(),
+            agents: AgentConfig::default(),
+            leader_election: LeaderElectionConfig::default(),
+            job_queue: JobQueueConfig::default(),
+            idempotency: IdempotencyConfig::default(),
+            admin: AdminConfig::default(),
+            flags: FlagsConfig::default(),
+            read_cache: ReadCacheConfig::default(),
+            query_log: QueryLogConfig::default(),
+            replicas: ReplicaConfig::default(),
+            db_resilience: DbResilienceConfig::default(),
+            maintenance: MaintenanceConfig::default(),
+            users: UsersConfig::default(),
+            logs: LogsConfig::default(),
+            redaction: RedactionConfig::default(),
+            backplane: BackplaneConfig::default(),
+            alerts: LifecycleConfig {
+                state_path: LifecycleConfig::default_state_path(),
+                ..LifecycleConfig::default()
+            },
+            reports: ReportsConfig::default(),
+            metrics: MetricConfig::default(),
+            tool_manifest_dir: None,
+            tool_history_path: ExecutionHistory::default_path(),
+            access: AccessConfig::default(),
+            security_headers: SecurityHeadersConfig::default(),
+            files: FilesConfig::default(),
+            blob_integrity: IntegrityConfig::default(),
+            result_cache_dir: ResultCache::default_path(),
+            usage: AccountingConfig {
+                ledger_path: AccountingConfig::default_ledger_path(),
+                ..AccountingConfig::default()
+            },
+            memory_watchdog: WatchdogConfig::default(),
+            assistant: AssistantConfig::default(),
+            rate_limit: RateLimitConfig::default(),
+            rollout: RolloutConfig::default(),
+            locale: None,
+        }
+    }
+}
+
+impl Config {
+    /// Reads a configuration file: TOML if its extension is `.toml`, JSON
+    /// otherwise. Settings missing from the file keep their defaults.
+    ///
+    /// Secrets such as `mcp.client_secret` can be written as `enc:` values,
+    /// encrypted with `squirrel config encrypt`. They are decrypted with the
+    /// master key the CLI uses, see [`squirrel_core::encryption`].
+    pub fn load(path: &Path) -> anyhow::Result<Self> {
+        Self::load_with_key(path, || MasterKey::load(&encryption::default_key_file()?))
+    }
+
+    /// Reads a configuration file, decrypting its `enc:` values with the key
+    /// `load_key` returns; the key is only loaded if there are any
+    fn load_with_key<F>(path: &Path, load_key: F) -> anyhow::Result<Self>
+    where
+        F: FnOnce() -> Result<MasterKey, EncryptionError>,
+    {
+        let contents = std::fs::read_to_string(path)
+            .map_err(|e| anyhow::anyhow!("Cannot read {}: {}", path.display(), e))?;
+        let mut value: serde_json::Value = if path.extension().is_some_and(|ext| ext == "toml") {
+            toml::from_str(&contents).map_err(|e| anyhow::anyhow!("Invalid {}: {}", path.display(), e))?
+        } else {
+            serde_json::from_str(&contents).map_err(|e| anyhow::anyhow!("Invalid {}: {}", path.display(), e))?
+        };
+        encryption::decrypt_json(&mut value, load_key)
+            .map_err(|e| anyhow::anyhow!("Cannot decrypt {}: {}", path.display(), e))?;
+        serde_json::from_value(value).map_err(|e| anyhow::anyhow!("Invalid {}: {}", path.display(), e))
+    }
+}
+
+/// Connection to the MCP server that executes commands
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct McpClientConfig {
+    /// `ws://`, `wss://` or `tcp://` address of the server; without one,
+    /// commands go to a built-in mock
+    pub url: Option<String>,
+    /// Client ID presented to the server's security manager
+    pub client_id: String,
+    /// Client secret presented to the server's security manager; write it
+    /// as an `enc:` value so that it is not stored in plaintext
+    pub client_secret: String,
+    /// Security level requested for the session
+    pub security_level: SecurityLevel,
+    /// Roles requested for the session
+    pub roles: Vec<String>,
+    /// Time to wait for a response before giving up
+    pub request_timeout_secs: u64,
+    /// Delay before reconnecting after the connection drops, doubled for
+    /// every failed attempt up to five minutes
+    pub reconnect_delay_secs: u64,
+}
+
+impl Default for McpClientConfig {
+    fn default() -> Self {
+        Self {
+            url: None,
+            client_id: "squirrel-web".to_string(),
+            client_secret: String::new(),
+            security_level: SecurityLevel::default(),
+            roles: Vec::new(),
+            request_timeout_secs: 30,
+            reconnect_delay_secs: 5,
+        }
+    }
+}
+
+/// Configuration for remote worker agents
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct AgentConfig {
+    /// Shared token agents must present when registering, if set
+    pub token: Option<String>,
+    /// Interval at which agents are asked to send heartbeats
+    pub heartbeat_interval_secs: u64,
+    /// Time without a heartbeat after which an agent is considered lost
+    pub heartbeat_timeout_secs: u64,
+    /// Maximum number of times a task is dispatched before it fails
+    pub max_attempts: u32,
+}
+
+impl Default for AgentConfig {
+    fn default() -> Self {
+        Self {
+            token: None,
+            heartbeat_interval_secs: 10,
+            heartbeat_timeout_secs: 30,
+            max_attempts: 3,
+        }
+    }
+}
+
+/// Configuration for leader election between server instances
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct LeaderElectionConfig {
+    /// Whether instances compete for a database lease; when disabled the
+    /// instance always considers itself the leader
+    pub enabled: bool,
+    /// Name of the lease shared by all instances
+    pub lease_name: String,
+    /// How long a lease stays valid without being renewed
+    pub lease_duration_secs: u64,
+    /// How often the lease is acquired or renewed
+    pub renew_interval_secs: u64,
+}
+
+impl Default for LeaderElectionConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            lease_name: "squirrel-web".to_string(),
+            lease_duration_secs: 15,
+            renew_interval_secs: 5,
+        }
+    }
+}
+
+/// Configuration for the persistent job queue
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct JobQueueConfig {
+    /// Number of entries this instance runs at once
+    pub workers: usize,
+    /// How long a worker's lease on an entry stays valid without being
+    /// renewed; an entry whose lease expires is an orphan
+    pub lease_duration_secs: u64,
+    /// How often workers look for pending entries when the queue is empty
+    pub poll_interval_ms: u64,
+    /// How often the leader looks for orphans
+    pub reap_interval_secs: u64,
+    /// Whether orphans are queued again or failed
+    pub orphan_policy: OrphanPolicy,
+    /// Maximum number of times an entry is leased before it fails
+    pub max_attempts: u32,
+}
+
+impl Default for JobQueueConfig {
+    fn default() -> Self {
+        Self {
+            workers: 4,
+            lease_duration_secs: 30,
+            poll_interval_ms: 500,
+            reap_interval_secs: 10,
+            orphan_policy: OrphanPolicy::Requeue,
+            max_attempts: 3,
+        }
+    }
+}
+
+/// Configuration for the idempotency keys of submissions
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct IdempotencyConfig {
+    /// How long the response of a submission is returned for its key
+    pub ttl_secs: u64,
+    /// How long a key stays reserved by a submission that never finished,
+    /// e.g. because the server stopped while it ran
+    pub reservation_secs: u64,
+}
+
+impl Default for IdempotencyConfig {
+    fn default() -> Self {
+        Self {
+            ttl_secs: 24 * 60 * 60,
+            reservation_secs: 60,
+        }
+    }
+}
+
+/// Configuration for the admin API
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct AdminConfig {
+    /// How often flags and signing keys changed by other instances or the
+    /// CLI are read again
+    pub refresh_interval_secs: u64,
+    /// How old an unreferenced blob must be before garbage collection
+    /// deletes it, so that blobs of uploads in flight are kept
+    pub gc_min_age_secs: u64,
+}
+
+impl Default for AdminConfig {
+    fn default() -> Self {
+        Self {
+            refresh_interval_secs: 10,
+            gc_min_age_secs: 60 * 60,
+        }
+    }
+}
+
+/// Configuration for feature flags
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct FlagsConfig {
+    /// Settings of flags, by name; flags without one keep their default
+    pub settings: FlagSet,
+    /// Keep the settings admins change in the web database, shared with
+    /// other instances; otherwise they last until the server stops
+    pub persist: bool,
+    /// How often settings changed by other instances are read again
+    pub refresh_interval_secs: u64,
+}
+
+impl Default for FlagsConfig {
+    fn default() -> Self {
+        Self {
+            settings: FlagSet::new(),
+            persist: true,
+            refresh_interval_secs: 10,
+        }
+    }
+}
+
+/// Configuration for the in-memory caches of frequent reads, per entity
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ReadCacheConfig {
+    /// Users by ID, read by authentication and the user directory
+    pub users: CacheSettings,
+    /// Members of teams, read to check team roles
+    pub team_members: CacheSettings,
+    /// Pages of `/api/commands`
+    pub commands: CacheSettings,
+    /// The report of `/api/capabilities`
+    pub capabilities: CacheSettings,
+    /// How often cache hits, misses and evictions are reported as metrics
+    pub metrics_interval_secs: u64,
+}
+
+impl Default for ReadCacheConfig {
+    fn default() -> Self {
+        Self {
+            users: CacheSettings::new(1024, 60),
+            team_members: CacheSettings::new(256, 60),
+            commands: CacheSettings::new(64, 30),
+            capabilities: CacheSettings::new(1, 30),
+            metrics_interval_secs: 15,
+        }
+    }
+}
+
+/// Settings of one read cache
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct CacheSettings {
+    /// Whether reads are cached at all
+    pub enabled: bool,
+    /// Most entries kept; the least recently used one makes room
+    pub capacity: usize,
+    /// How long an entry is served before it is read again
+    pub ttl_secs: u64,
+}
+
+impl CacheSettings {
+    /// Keep up to `capacity` entries for `ttl_secs` each
+    pub fn new(capacity: usize, ttl_secs: u64) -> Self {
+        Self {
+            enabled: true,
+            capacity,
+            ttl_secs,
+        }
+    }
+
+    /// Cache nothing
+    pub fn disabled() -> Self {
+        Self {
+            enabled: false,
+            ..Self::default()
+        }
+    }
+}
+
+impl Default for CacheSettings {
+    fn default() -> Self {
+        Self::new(256, 60)
+    }
+}
+
+/// Configuration for the slow query log of the web database
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct QueryLogConfig {
+    /// Statements taking at least this long are logged as slow
+    pub slow_query_ms: u64,
+    /// Most slow statements kept for `/api/admin/slow-queries`
+    pub slow_query_capacity: usize,
+}
+
+impl Default for QueryLogConfig {
+    fn default() -> Self {
+        Self {
+            slow_query_ms: 250,
+            slow_query_capacity: 200,
         }
     }
-} 
\ No newline at end of file
+}
+
+/// Configuration for the read replicas of the web database
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ReplicaConfig {
+    /// URLs of the replicas; without any, reads go to the primary
+    pub urls: Vec<String>,
+    /// Replicas further behind the primary than this serve no reads; keep
+    /// it above `check_interval_ms`, which a caught up replica may lag by
+    pub max_staleness_ms: u64,
+    /// How often the heartbeat is written and the replicas are checked
+    pub check_interval_ms: u64,
+    /// Most connections to each replica
+    pub max_connections: u32,
+}
+
+impl Default for ReplicaConfig {
+    fn default() -> Self {
+        Self {
+            urls: Vec::new(),
+            max_staleness_ms: 5000,
+            check_interval_ms: 1000,
+            max_connections: 5,
+        }
+    }
+}
+
+/// Configuration for retrying statements of the web database and failing
+/// them at once while it cannot be reached
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct DbResilienceConfig {
+    /// Attempts of a statement failing before it ran, including the first
+    pub max_attempts: u32,
+    /// Delay before the first retry, doubled for every further one
+    pub retry_delay_ms: u64,
+    /// Upper bound for the delay between retries
+    pub max_retry_delay_ms: u64,
+    /// Statements in a row failing to reach the database before the
+    /// circuit opens
+    pub failure_threshold: u32,
+    /// How long statements fail at once after the circuit opened
+    pub open_secs: u64,
+}
+
+impl Default for DbResilienceConfig {
+    fn default() -> Self {
+        Self {
+            max_attempts: 3,
+            retry_delay_ms: 50,
+            max_retry_delay_ms: 1000,
+            failure_threshold: 5,
+            open_secs: 30,
+        }
+    }
+}
+
+/// Configuration for scheduled maintenance of the web database
+///
+/// Retention periods are in days; rows of a kind without one are kept.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct MaintenanceConfig {
+    /// Whether maintenance runs on the schedule
+    pub enabled: bool,
+    /// When maintenance runs, as a cron expression in UTC
+    pub schedule: CronSchedule,
+    /// Only report what scheduled runs would delete
+    pub dry_run: bool,
+    /// Update the query planner statistics with `ANALYZE`
+    pub analyze: bool,
+    /// Give freed space back with `VACUUM`
+    pub vacuum: bool,
+    /// How long finished jobs and job queue entries are kept
+    pub job_retention_days: Option<u32>,
+    /// How long finished command executions are kept
+    pub command_history_retention_days: Option<u32>,
+    /// How long finished webhook deliveries are kept
+    pub webhook_delivery_retention_days: Option<u32>,
+    /// How long forwarded log events are kept
+    pub log_retention_days: Option<u32>,
+}
+
+impl Default for MaintenanceConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            schedule: "0 3 * * *".parse().expect("Default maintenance schedule is valid"),
+            dry_run: false,
+            analyze: true,
+            vacuum: true,
+            job_retention_days: Some(90),
+            command_history_retention_days: Some(90),
+            webhook_delivery_retention_days: Some(30),
+            log_retention_days: Some(14),
+        }
+    }
+}
+
+/// Configuration for log events forwarded to `/api/logs`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct LogsConfig {
+    /// Most events accepted in one batch
+    pub max_batch: usize,
+    /// Longer messages are cut to this many bytes
+    pub max_message_bytes: usize,
+    /// Events kept in the mock database mode
+    pub memory_capacity: usize,
+}
+
+impl Default for LogsConfig {
+    fn default() -> Self {
+        Self {
+            max_batch: 1000,
+            max_message_bytes: 16 * 1024,
+            memory_capacity: 10_000,
+        }
+    }
+}
+
+/// Configuration for user accounts
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct UsersConfig {
+    /// How long an invitation can be accepted
+    pub invitation_ttl_hours: i64,
+    /// How long a password reset token can be used
+    pub password_reset_ttl_minutes: i64,
+    /// Shortest password accepted for new accounts and resets
+    pub min_password_length: usize,
+}
+
+impl Default for UsersConfig {
+    fn default() -> Self {
+        Self {
+            invitation_ttl_hours: 72,
+            password_reset_ttl_minutes: 60,
+            min_password_length: 8,
+        }
+    }
+}
+
+/// Configuration for file uploads and downloads
+///
+/// Files are addressed by virtual path: `workspace://` maps to
+/// `workspace_dir` and `data://` to `data_dir`, which is read-only. Uploaded
+/// files are kept in the blob store in `blob_dir` and hard linked into the
+/// workspace, so identical uploads take space once; the store should be on
+/// the same filesystem as the workspace.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct FilesConfig {
+    /// Directory of the `workspace://` root
+    pub workspace_dir: PathBuf,
+    /// Directory of the read-only `data://` root
+    pub data_dir: PathBuf,
+    /// Directory partial uploads are kept in until they complete
+    pub upload_dir: PathBuf,
+    /// Directory of the content-addressed blob store; without one, uploads
+    /// are stored as plain files
+    pub blob_dir: Option<PathBuf>,
+    /// Largest file that can be uploaded, in bytes
+    pub max_file_size: u64,
+    /// Time after which an unfinished upload is discarded
+    pub upload_expiry_secs: u64,
+}
+
+impl Default for FilesConfig {
+    fn default() -> Self {
+        Self {
+            workspace_dir: PathBuf::from("workspace"),
+            data_dir: PathBuf::from("data"),
+            upload_dir: std::env::temp_dir().join("squirrel").join("uploads"),
+            blob_dir: BlobStore::default_path(),
+            max_file_size: 64 * 1024 * 1024 * 1024,
+            upload_expiry_secs: 24 * 60 * 60,
+        }
+    }
+}
+
+/// Configuration for scheduled integrity checks of the blob store
+///
+/// A copy of the store directory in `backup_dir`, such as a nightly mirror
+/// or a snapshot, lets checks restore the blobs they find corrupt or missing.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct IntegrityConfig {
+    /// Whether checks run on the schedule
+    pub enabled: bool,
+    /// When checks run, as a cron expression in UTC
+    pub schedule: CronSchedule,
+    /// Blobs hashed again by a scheduled check, taking turns over the
+    /// store; without a sample size every blob is
+    pub sample_size: Option<usize>,
+    /// Directory of a backup of the blob store to restore blobs from
+    pub backup_dir: Option<PathBuf>,
+}
+
+impl Default for IntegrityConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            schedule: "30 4 * * *".parse().expect("Default integrity check schedule is valid"),
+            sample_size: Some(1000),
+            backup_dir: None,
+        }
+    }
+}
+
+/// Security headers added to every response
+///
+/// A header set to `None` is not sent. Handlers that set one of these
+/// headers themselves keep their value.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SecurityHeadersConfig {
+    /// Whether the headers are added at all
+    pub enabled: bool,
+    /// `Content-Security-Policy` value
+    pub content_security_policy: Option<String>,
+    /// `Strict-Transport-Security` value
+    pub strict_transport_security: Option<String>,
+    /// `X-Frame-Options` value
+    pub frame_options: Option<String>,
+    /// `Referrer-Policy` value
+    pub referrer_policy: Option<String>,
+    /// `X-Content-Type-Options` value
+    pub content_type_options: Option<String>,
+    /// Path prefixes whose responses get no security headers, such as
+    /// WebSocket and server-sent event endpoints
+    pub exempt_paths: Vec<String>,
+}
+
+impl Default for SecurityHeadersConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            content_security_policy: Some(
+                "default-src 'self'; object-src 'none'; base-uri 'self'; frame-ancestors 'none'".to_string(),
+            ),
+            strict_transport_security: Some("max-age=31536000; includeSubDomains".to_string()),
+            frame_options: Some("DENY".to_string()),
+            referrer_policy: Some("no-referrer".to_string()),
+            content_type_options: Some("nosniff".to_string()),
+            exempt_paths: vec!["/ws".to_string()],
+        }
+    }
+}
+
+/// Request rate limits per client address
+///
+/// Each client may make `burst` requests at once, refilled at
+/// `requests_per_minute`. Requests beyond that are refused with
+/// `429 Too Many Requests`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct RateLimitConfig {
+    /// Sustained requests per minute of a client; without a limit, requests
+    /// are not limited
+    pub requests_per_minute: Option<u32>,
+    /// Requests a client may make at once; defaults to `requests_per_minute`
+    pub burst: Option<u32>,
+    /// Path prefixes that are never limited, such as health checks
+    pub exempt_paths: Vec<String>,
+}
+
+impl Default for RateLimitConfig {
+    fn default() -> Self {
+        Self {
+            requests_per_minute: None,
+            burst: None,
+            exempt_paths: vec!["/health".to_string(), "/api/health".to_string()],
+        }
+    }
+}
+
+/// Gates of configuration rollouts through `/api/admin/config`
+///
+/// A canaried candidate is only promoted once it has served
+/// `min_canary_requests` and its share of server errors is at most
+/// `max_error_rate_increase` above that of the active configuration.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct RolloutConfig {
+    /// Requests the canary must serve before it can be promoted
+    pub min_canary_requests: u64,
+    /// Largest rise in the share of server errors, e.g. `0.01` for one
+    /// percentage point
+    pub max_error_rate_increase: f64,
+    /// Number of replaced configurations kept for rollback
+    pub history: usize,
+}
+
+impl Default for RolloutConfig {
+    fn default() -> Self {
+        Self {
+            min_canary_requests: 100,
+            max_error_rate_increase: 0.01,
+            history: 10,
+        }
+    }
+}
+
+/// Report digests and the channels they are delivered through
+///
+/// Recipients name a channel by its kind, `email`, `slack` or `webhook`, so
+/// at most one channel of each kind is used.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ReportsConfig {
+    /// Schedule, recipients and contents of the digests
+    #[serde(flatten)]
+    pub digests: ReportConfig,
+    /// Channels the recipients are reached through
+    pub channels: Vec<NotificationChannel>,
+}
+
+/// IP-based access control
+///
+/// Requests from denied networks or countries are rejected. When an
+/// allowlist is set, only requests from allowed networks or countries pass.
+/// Country rules need a GeoIP database.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct AccessConfig {
+    /// Networks requests are accepted from, e.g. `10.0.0.0/8`
+    pub allow: Vec<IpNet>,
+    /// Networks requests are rejected from
+    pub deny: Vec<IpNet>,
+    /// ISO country codes requests are accepted from
+    pub allow_countries: Vec<String>,
+    /// ISO country codes requests are rejected from
+    pub deny_countries: Vec<String>,
+    /// `network,country` CSV file resolving client countries
+    pub geoip_database: Option<PathBuf>,
+    /// Proxies whose `X-Forwarded-For` header names the client
+    pub trusted_proxies: Vec<IpNet>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_load_decrypts_secrets() {
+        let dir = tempfile::tempdir().unwrap();
+        let key = MasterKey::generate().unwrap();
+        let path = dir.path().join("server.toml");
+        std::fs::write(
+            &path,
+            format!(
+                "[mcp]\nurl = \"ws://mcp:8080\"\nclient_secret = \"{}\"\n",
+                key.encrypt("hunter2").unwrap()
+            ),
+        )
+        .unwrap();
+        let encoded = key.encode();
+
+        let config = Config::load_with_key(&path, || MasterKey::parse(&encoded)).unwrap();
+        assert_eq!(config.mcp.client_secret, "hunter2");
+        assert_eq!(config.mcp.url.as_deref(), Some("ws://mcp:8080"));
+
+        let other = MasterKey::generate().unwrap();
+        let error = Config::load_with_key(&path, || Ok(other)).unwrap_err();
+        assert!(error.to_string().contains("wrong master key"), "{error}");
+
+        // Plaintext configurations need no key
+        std::fs::write(&path, "[mcp]\nclient_id = \"web\"\n").unwrap();
+        let config = Config::load_with_key(&path, || panic!("no encrypted values")).unwrap();
+        assert_eq!(config.mcp.client_id, "web");
+    }
+
+    #[test]
+    fn test_redaction_metrics_have_their_own_interval() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("server.toml");
+        std::fs::write(&path, "[redaction]\nmetrics_interval_secs = 60\n").unwrap();
+
+        let config = Config::load(&path).unwrap();
+        assert_eq!(config.redaction.metrics_interval_secs, 60);
+        assert_eq!(config.read_cache.metrics_interval_secs, 15);
+        assert!(config.redaction.builtin_rules);
+    }
+}
@@ -0,0 +1,491 @@
+//! Database pool and query instrumentation.
+//!
+//! Stores run their statements through an [`InstrumentedPool`], which times
+//! every statement and counts the rows it returned or changed, and reports
+//! them to the installed [`QueryLog`]. The log records them as metrics
+//! labeled with a summary of the statement, such as `SELECT users`:
+//!
+//! - `db_queries_total`, counting statements by `query` and `outcome`
+//! - `db_query_duration_ms`, a histogram of their latency
+//! - `db_query_rows`, a histogram of the rows they returned or changed
+//!
+//! Statements slower than the configured threshold are logged and kept for
+//! `/api/admin/slow-queries`. Bound parameters are never recorded, and
+//! literals written into the statement are replaced with `?`. Statements run
+//! inside a transaction are not timed on their own.
+//!
+//! Statements failing before they ran are retried, and statements fail at
+//! once while the database cannot be reached, see [`resilience`].
+//!
+//! Deployments with read replicas spread reads over them through a
+//! [`ReplicatedPool`], see [`replicas`].
+
+pub mod replicas;
+pub mod resilience;
+
+pub use replicas::{ReadPreference, ReplicaStatus, ReplicatedPool};
+pub use resilience::{install_resilience, resilience, CircuitState, DatabaseUnavailable, DbResilience};
+
+use std::collections::{HashMap, VecDeque};
+use std::fmt;
+use std::ops::Deref;
+use std::pin::Pin;
+use std::sync::{Arc, Mutex, PoisonError, RwLock};
+use std::task::{Context, Poll};
+use std::time::{Duration, Instant};
+
+use chrono::{DateTime, TimeZone, Utc};
+use futures::future::BoxFuture;
+use futures::stream::{BoxStream, Stream};
+use serde::{Deserialize, Serialize};
+use sqlx::sqlite::{SqliteQueryResult, SqliteRow, SqliteStatement, SqliteTypeInfo};
+use sqlx::{Describe, Either, Execute, Executor, Sqlite};
+use squirrel_monitoring::metrics::{Metric, MetricCollector, MetricType};
+
+use crate::config::QueryLogConfig;
+use resilience::Replayable;
+
+pub use sqlx::SqlitePool;
+
+/// Database options
+#[derive(Debug, Clone)]
+pub struct DatabaseOptions {
+    /// Database URL
+    pub url: String,
+    /// Max connections in the pool
+    pub max_connections: u32,
+}
+
+impl Default for DatabaseOptions {
+    fn default() -> Self {
+        Self {
+            url: "sqlite::memory:".to_string(),
+            max_connections: 5,
+        }
+    }
+}
+
+/// A statement that took longer than the slow query threshold
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SlowQuery {
+    /// Summary of the statement, e.g. `SELECT users`
+    pub query: String,
+    /// The statement, with literals replaced with `?`
+    pub sql: String,
+    /// How long it took
+    pub duration_ms: u64,
+    /// Rows it returned or changed before it finished
+    pub rows: u64,
+    /// Whether it failed
+    pub failed: bool,
+    /// When it finished
+    pub finished_at: DateTime<Utc>,
+}
+
+/// Records statements as metrics and keeps the slow ones
+pub struct QueryLog {
+    config: QueryLogConfig,
+    collector: Option<Arc<dyn MetricCollector>>,
+    /// Slow statements, oldest first
+    slow: Mutex<VecDeque<SlowQuery>>,
+}
+
+impl QueryLog {
+    /// Create a new QueryLog, recording metrics in `collector` if given
+    pub fn new(config: QueryLogConfig, collector: Option<Arc<dyn MetricCollector>>) -> Self {
+        Self {
+            config,
+            collector,
+            slow: Mutex::new(VecDeque::new()),
+        }
+    }
+
+    /// Record a statement that ran for `elapsed` and returned or changed `rows`
+    pub fn record(&self, sql: &str, elapsed: Duration, rows: u64, failed: bool) {
+        let query = summary(sql);
+        if let Some(collector) = &self.collector {
+            // Statements finishing outside a runtime go unrecorded
+            if let Ok(runtime) = tokio::runtime::Handle::try_current() {
+                let collector = collector.clone();
+                let metrics = Self::metrics(&query, elapsed, rows, failed);
+                runtime.spawn(async move {
+                    for metric in metrics {
+                        if let Err(e) = collector.record_metric(metric).await {
+                            tracing::warn!("Failed to record query metric: {}", e);
+                        }
+                    }
+                });
+            }
+        }
+
+        let duration_ms = u64::try_from(elapsed.as_millis()).unwrap_or(u64::MAX);
+        if duration_ms < self.config.slow_query_ms || self.config.slow_query_capacity == 0 {
+            return;
+        }
+        let sql = redact(sql);
+        tracing::warn!(query = %query, duration_ms, rows, failed, "Slow query: {}", sql);
+        let mut slow = self.slow.lock().unwrap_or_else(PoisonError::into_inner);
+        while slow.len() >= self.config.slow_query_capacity {
+            slow.pop_front();
+        }
+        slow.push_back(SlowQuery {
+            query,
+            sql,
+            duration_ms,
+            rows,
+            failed,
+            finished_at: Utc::now(),
+        });
+    }
+
+    /// The metrics a statement is recorded as
+    fn metrics(query: &str, elapsed: Duration, rows: u64, failed: bool) -> Vec<Metric> {
+        let labels = HashMap::from([("query".to_string(), query.to_string())]);
+        let mut counted = labels.clone();
+        counted.insert("outcome".to_string(), if failed { "error" } else { "ok" }.to_string());
+        vec![
+            Metric::new("db_queries_total", 1.0, MetricType::Counter, counted),
+            Metric::new("db_query_duration_ms", elapsed.as_secs_f64() * 1000.0, MetricType::Histogram, labels.clone()),
+            Metric::new("db_query_rows", rows as f64, MetricType::Histogram, labels),
+        ]
+    }
+
+    /// The latest `limit` slow statements, newest first
+    pub fn slow_queries(&self, limit: usize) -> Vec<SlowQuery> {
+        self.slow
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .iter()
+            .rev()
+            .take(limit)
+            .cloned()
+            .collect()
+    }
+}
+
+/// The installed query log
+static QUERY_LOG: RwLock<Option<Arc<QueryLog>>> = RwLock::new(None);
+
+/// Report the statements of every instrumented pool to `log` from now on
+pub fn install_query_log(log: Arc<QueryLog>) {
+    *QUERY_LOG.write().unwrap_or_else(PoisonError::into_inner) = Some(log);
+}
+
+/// The installed query log, if any
+pub fn query_log() -> Option<Arc<QueryLog>> {
+    QUERY_LOG.read().unwrap_or_else(PoisonError::into_inner).clone()
+}
+
+/// Summary of a statement: its verb and the table it works on
+pub fn summary(sql: &str) -> String {
+    let words: Vec<&str> = sql.split_whitespace().collect();
+    let Some(verb) = words.first() else {
+        return String::new();
+    };
+    let verb = verb.to_ascii_uppercase();
+    let table = if verb == "UPDATE" {
+        words.get(1)
+    } else {
+        words
+            .iter()
+            .position(|word| word.eq_ignore_ascii_case("FROM") || word.eq_ignore_ascii_case("INTO"))
+            .and_then(|at| words.get(at + 1))
+    };
+    match table.map(|table| table.trim_matches(|c: char| !(c.is_alphanumeric() || c == '_'))) {
+        Some(table) if !table.is_empty() => format!("{} {}", verb, table),
+        _ => verb,
+    }
+}
+
+/// A statement on one line, with string and number literals replaced with `?`
+pub fn redact(sql: &str) -> String {
+    let mut redacted = String::with_capacity(sql.len());
+    let mut chars = sql.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '\'' => {
+                // A doubled quote escapes a quote inside the literal
+                while let Some(c) = chars.next() {
+                    if c == '\'' && chars.next_if_eq(&'\'').is_none() {
+                        break;
+                    }
+                }
+                redacted.push('?');
+            }
+            c if c.is_ascii_digit() && !redacted.ends_with(|p: char| p.is_alphanumeric() || p == '_') => {
+                while chars.next_if(|c| c.is_ascii_alphanumeric() || *c == '.').is_some() {}
+                redacted.push('?');
+            }
+            c if c.is_whitespace() => {
+                if !redacted.is_empty() && !redacted.ends_with(' ') {
+                    redacted.push(' ');
+                }
+            }
+            c => redacted.push(c),
+        }
+    }
+    redacted.trim_end().to_string()
+}
+
+/// Converts milliseconds stored in a column into a timestamp
+pub(crate) fn timestamp(millis: i64) -> Result<DateTime<Utc>, sqlx::Error> {
+    Utc.timestamp_millis_opt(millis)
+        .single()
+        .ok_or_else(|| sqlx::Error::Decode(format!("Invalid stored timestamp {}", millis).into()))
+}
+
+/// What a statement did
+#[derive(Debug, Default)]
+struct QueryOutcome {
+    /// Rows returned
+    returned: u64,
+    /// Rows changed, as SQLite reports them
+    affected: u64,
+    failed: bool,
+}
+
+impl QueryOutcome {
+    /// Rows `sql` returned, or changed if it returned none
+    ///
+    /// SQLite reports the changes of the last write for reads, so reads
+    /// only count the rows they return.
+    fn rows(&self, sql: &str) -> u64 {
+        let reads = matches!(summary(sql).split(' ').next(), Some("SELECT" | "WITH" | "PRAGMA"));
+        if self.returned > 0 || reads {
+            self.returned
+        } else {
+            self.affected
+        }
+    }
+}
+
+/// Times a statement until it finishes
+struct QueryTimer<'q> {
+    sql: &'q str,
+    started: Instant,
+}
+
+impl<'q> QueryTimer<'q> {
+    fn start(sql: &'q str) -> Self {
+        Self {
+            sql,
+            started: Instant::now(),
+        }
+    }
+
+    /// Report the statement and its `outcome` to the installed log
+    fn finish(self, outcome: &QueryOutcome) {
+        if let Some(log) = query_log() {
+            log.record(self.sql, self.started.elapsed(), outcome.rows(self.sql), outcome.failed);
+        }
+    }
+}
+
+/// Results of a statement, counted as they are read
+///
+/// The statement is reported once the results are read to the end, or when
+/// the stream is dropped before that.
+struct TimedStream<'e, 'q> {
+    inner: BoxStream<'e, Result<Either<SqliteQueryResult, SqliteRow>, sqlx::Error>>,
+    timer: Option<QueryTimer<'q>>,
+    outcome: QueryOutcome,
+}
+
+impl TimedStream<'_, '_> {
+    /// Report the statement, unless it was reported already
+    fn finish(&mut self) {
+        if let Some(timer) = self.timer.take() {
+            timer.finish(&self.outcome);
+        }
+    }
+}
+
+impl Stream for TimedStream<'_, '_> {
+    type Item = Result<Either<SqliteQueryResult, SqliteRow>, sqlx::Error>;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let this = self.get_mut();
+        let next = this.inner.as_mut().poll_next(cx);
+        match &next {
+            Poll::Ready(Some(Ok(Either::Left(result)))) => this.outcome.affected += result.rows_affected(),
+            Poll::Ready(Some(Ok(Either::Right(_)))) => this.outcome.returned += 1,
+            Poll::Ready(Some(Err(_))) => this.outcome.failed = true,
+            Poll::Ready(None) => this.finish(),
+            Poll::Pending => {}
+        }
+        next
+    }
+}
+
+impl Drop for TimedStream<'_, '_> {
+    fn drop(&mut self) {
+        self.finish();
+    }
+}
+
+/// A pool timing the statements run through it
+///
+/// `&InstrumentedPool` runs queries like `&SqlitePool` does; the pool itself,
+/// for transactions and connections, is reached through `Deref`.
+#[derive(Clone)]
+pub struct InstrumentedPool {
+    pool: SqlitePool,
+}
+
+impl InstrumentedPool {
+    /// Time the statements run through `pool`
+    pub fn new(pool: SqlitePool) -> Self {
+        Self { pool }
+    }
+}
+
+impl From<SqlitePool> for InstrumentedPool {
+    fn from(pool: SqlitePool) -> Self {
+        Self::new(pool)
+    }
+}
+
+impl Deref for InstrumentedPool {
+    type Target = SqlitePool;
+
+    fn deref(&self) -> &SqlitePool {
+        &self.pool
+    }
+}
+
+impl fmt::Debug for InstrumentedPool {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("InstrumentedPool").field("pool", &self.pool).finish()
+    }
+}
+
+impl<'p> Executor<'p> for &'p InstrumentedPool {
+    type Database = Sqlite;
+
+    fn fetch_many<'e, 'q: 'e, E>(
+        self,
+        query: E,
+    ) -> BoxStream<'e, Result<Either<SqliteQueryResult, SqliteRow>, sqlx::Error>>
+    where
+        'p: 'e,
+        E: 'q + Execute<'q, Sqlite>,
+    {
+        let timer = QueryTimer::start(query.sql());
+        let inner = match resilience() {
+            Some(resilience) => {
+                let statement = Replayable::take(query);
+                let pool = &self.pool;
+                resilience.stream(move || Executor::fetch_many(pool, statement.query()))
+            }
+            None => Executor::fetch_many(&self.pool, query),
+        };
+        Box::pin(TimedStream {
+            inner,
+            timer: Some(timer),
+            outcome: QueryOutcome::default(),
+        })
+    }
+
+    fn fetch_optional<'e, 'q: 'e, E>(self, query: E) -> BoxFuture<'e, Result<Option<SqliteRow>, sqlx::Error>>
+    where
+        'p: 'e,
+        E: 'q + Execute<'q, Sqlite>,
+    {
+        let timer = QueryTimer::start(query.sql());
+        let fetch: BoxFuture<'e, Result<Option<SqliteRow>, sqlx::Error>> = match resilience() {
+            Some(resilience) => {
+                let statement = Replayable::take(query);
+                let pool = &self.pool;
+                Box::pin(async move { resilience.run(|| Executor::fetch_optional(pool, statement.query())).await })
+            }
+            None => Executor::fetch_optional(&self.pool, query),
+        };
+        Box::pin(async move {
+            let row = fetch.await;
+            let outcome = match &row {
+                Ok(row) => QueryOutcome {
+                    returned: u64::from(row.is_some()),
+                    ..QueryOutcome::default()
+                },
+                Err(_) => QueryOutcome {
+                    failed: true,
+                    ..QueryOutcome::default()
+                },
+            };
+            timer.finish(&outcome);
+            row
+        })
+    }
+
+    fn prepare_with<'e, 'q: 'e>(
+        self,
+        sql: &'q str,
+        parameters: &'e [SqliteTypeInfo],
+    ) -> BoxFuture<'e, Result<SqliteStatement<'q>, sqlx::Error>>
+    where
+        'p: 'e,
+    {
+        Executor::prepare_with(&self.pool, sql, parameters)
+    }
+
+    fn describe<'e, 'q: 'e>(self, sql: &'q str) -> BoxFuture<'e, Result<Describe<Sqlite>, sqlx::Error>>
+    where
+        'p: 'e,
+    {
+        Executor::describe(&self.pool, sql)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use sqlx::sqlite::SqlitePoolOptions;
+
+    #[test]
+    fn test_statements_are_summarized_and_redacted() {
+        assert_eq!(summary("SELECT id FROM users WHERE id = ?"), "SELECT users");
+        assert_eq!(summary("insert into tags (name) values (?)"), "INSERT tags");
+        assert_eq!(summary("UPDATE sessions SET revoked = 1"), "UPDATE sessions");
+        assert_eq!(summary("PRAGMA wal_checkpoint(TRUNCATE)"), "PRAGMA");
+        assert_eq!(
+            redact("SELECT *\n  FROM users WHERE name = 'o''brien' AND age > 42 AND v2 = ?"),
+            "SELECT * FROM users WHERE name = ? AND age > ? AND v2 = ?"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_slow_statements_are_kept_newest_first() {
+        let config = QueryLogConfig {
+            slow_query_ms: 0,
+            slow_query_capacity: 1000,
+        };
+        install_query_log(Arc::new(QueryLog::new(config, None)));
+        let pool = SqlitePoolOptions::new()
+            .max_connections(1)
+            .connect("sqlite::memory:")
+            .await
+            .unwrap();
+        let pool = InstrumentedPool::new(pool);
+
+        sqlx::query("CREATE TABLE query_log_test (name TEXT)").execute(&pool).await.unwrap();
+        sqlx::query("INSERT INTO query_log_test VALUES ('secret'), (?)")
+            .bind("hidden")
+            .execute(&pool)
+            .await
+            .unwrap();
+        let rows = sqlx::query("SELECT name FROM query_log_test").fetch_all(&pool).await.unwrap();
+        assert_eq!(rows.len(), 2);
+
+        let slow: Vec<_> = query_log()
+            .unwrap()
+            .slow_queries(1000)
+            .into_iter()
+            .filter(|query| query.query.ends_with("query_log_test"))
+            .collect();
+        assert_eq!(
+            slow.iter().map(|query| (query.query.as_str(), query.rows)).collect::<Vec<_>>(),
+            vec![("SELECT query_log_test", 2), ("INSERT query_log_test", 2)]
+        );
+        assert_eq!(slow[1].sql, "INSERT INTO query_log_test VALUES (?), (?)");
+    }
+}
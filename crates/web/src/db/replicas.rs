@@ -0,0 +1,301 @@
+//! Read replicas of the web database.
+//!
+//! A [`ReplicatedPool`] sends writes to the primary database and spreads
+//! reads over its read replicas, such as the read-only nodes of a LiteFS
+//! cluster. Copying the data is the replication layer's job; the pool only
+//! decides where each statement goes.
+//!
+//! How far a replica is behind is measured with a heartbeat: every check,
+//! the instance writes the time to the `replication_heartbeat` table of the
+//! primary, then reads it back from each replica. A replica that cannot be
+//! queried, or whose heartbeat is further behind than the staleness bound,
+//! gets no reads until it catches up. With no replica fit to read, reads go
+//! to the primary. A caller that must see its own writes, or can accept
+//! older data, picks per query with a [`ReadPreference`].
+
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, PoisonError, RwLock};
+use std::time::Duration;
+
+use chrono::{DateTime, TimeZone, Utc};
+use serde::{Deserialize, Serialize};
+use sqlx::sqlite::SqlitePoolOptions;
+use sqlx::Row;
+use squirrel_app::supervisor::{RestartPolicy, Supervisor};
+use tracing::{debug, info, warn};
+
+use super::{InstrumentedPool, SqlitePool};
+use crate::api::error::AppError;
+use crate::config::ReplicaConfig;
+
+/// Where a read may be served from
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ReadPreference {
+    /// The primary, which has every committed write
+    Primary,
+    /// A replica within the pool's staleness bound
+    Replica,
+    /// A replica at most this far behind
+    Within(Duration),
+}
+
+/// State of a replica as of its latest check
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ReplicaStatus {
+    /// URL of the replica
+    pub url: String,
+    /// Whether the replica answered its latest check with a heartbeat
+    pub healthy: bool,
+    /// How far the replica's heartbeat was behind at its latest check
+    pub lag_ms: Option<u64>,
+    /// When the replica was last checked
+    pub checked_at: Option<DateTime<Utc>>,
+    /// Why the latest check failed
+    pub error: Option<String>,
+}
+
+/// A replica and what its latest check found
+struct Replica {
+    pool: InstrumentedPool,
+    status: RwLock<ReplicaStatus>,
+}
+
+impl Replica {
+    /// What the latest check found
+    fn status(&self) -> ReplicaStatus {
+        self.status.read().unwrap_or_else(PoisonError::into_inner).clone()
+    }
+
+    /// Whether the replica may serve reads at most `bound` behind
+    fn serves(&self, bound: Duration) -> bool {
+        let status = self.status.read().unwrap_or_else(PoisonError::into_inner);
+        let bound = u64::try_from(bound.as_millis()).unwrap_or(u64::MAX);
+        status.healthy && status.lag_ms.is_some_and(|lag| lag <= bound)
+    }
+}
+
+/// Writes to the primary database, reads from replicas close enough behind it
+pub struct ReplicatedPool {
+    primary: InstrumentedPool,
+    replicas: Vec<Replica>,
+    max_staleness: Duration,
+    /// Replica the next read starts looking from
+    next: AtomicUsize,
+}
+
+impl ReplicatedPool {
+    /// Create a new ReplicatedPool without replicas, reading from replicas
+    /// at most `max_staleness` behind once they are added
+    pub fn new(primary: SqlitePool, max_staleness: Duration) -> Self {
+        Self {
+            primary: primary.into(),
+            replicas: Vec::new(),
+            max_staleness,
+            next: AtomicUsize::new(0),
+        }
+    }
+
+    /// Read from the replica at `url` behind `pool` too, once a check found
+    /// it close enough behind
+    pub fn with_replica(mut self, url: impl Into<String>, pool: SqlitePool) -> Self {
+        self.replicas.push(Replica {
+            pool: pool.into(),
+            status: RwLock::new(ReplicaStatus {
+                url: url.into(),
+                healthy: false,
+                lag_ms: None,
+                checked_at: None,
+                error: None,
+            }),
+        });
+        self
+    }
+
+    /// Read from the replicas in `config`, connecting to them as they are
+    /// first used
+    pub fn connect(primary: SqlitePool, config: &ReplicaConfig) -> Result<Self, AppError> {
+        let mut pool = Self::new(primary, Duration::from_millis(config.max_staleness_ms));
+        for url in &config.urls {
+            let replica = SqlitePoolOptions::new()
+                .max_connections(config.max_connections)
+                .connect_lazy(url)
+                .map_err(|e| AppError::Internal(format!("Invalid replica URL {}: {}", url, e)))?;
+            pool = pool.with_replica(url.clone(), replica);
+        }
+        Ok(pool)
+    }
+
+    /// The primary, for writes and reads that must see them
+    pub fn primary(&self) -> &InstrumentedPool {
+        &self.primary
+    }
+
+    /// Where to read from by default: a replica within the staleness bound,
+    /// or the primary
+    pub fn reader(&self) -> &InstrumentedPool {
+        self.read(ReadPreference::Replica)
+    }
+
+    /// Where to read from as `preference` allows; replicas take turns, and
+    /// the primary serves the read when no replica may
+    pub fn read(&self, preference: ReadPreference) -> &InstrumentedPool {
+        let bound = match preference {
+            ReadPreference::Primary => return &self.primary,
+            ReadPreference::Replica => self.max_staleness,
+            ReadPreference::Within(bound) => bound,
+        };
+        let count = self.replicas.len();
+        let start = self.next.fetch_add(1, Ordering::Relaxed);
+        (0..count)
+            .map(|offset| &self.replicas[start.wrapping_add(offset) % count])
+            .find(|replica| replica.serves(bound))
+            .map_or(&self.primary, |replica| &replica.pool)
+    }
+
+    /// State of the replicas as of their latest check
+    pub fn replicas(&self) -> Vec<ReplicaStatus> {
+        self.replicas.iter().map(Replica::status).collect()
+    }
+
+    /// Write the heartbeat to the primary, then check how far behind each
+    /// replica is
+    pub async fn check(&self) {
+        let now = Utc::now();
+        let beat = sqlx::query(
+            "INSERT INTO replication_heartbeat (id, beat_at) VALUES (1, ?)
+             ON CONFLICT(id) DO UPDATE SET beat_at = MAX(beat_at, excluded.beat_at)",
+        )
+        .bind(now.timestamp_millis())
+        .execute(&self.primary)
+        .await;
+        // Another instance may be the one that can write
+        if let Err(e) = beat {
+            debug!("Failed to write the replication heartbeat: {}", e);
+        }
+
+        for replica in &self.replicas {
+            let checked = sqlx::query("SELECT beat_at FROM replication_heartbeat WHERE id = 1")
+                .fetch_optional(&replica.pool)
+                .await;
+            let (lag_ms, error) = match checked {
+                Ok(Some(row)) => match row.try_get::<i64, _>("beat_at") {
+                    Ok(millis) => {
+                        let beat_at = Utc.timestamp_millis_opt(millis).single().unwrap_or_default();
+                        let lag = (Utc::now() - beat_at).num_milliseconds().max(0);
+                        (Some(u64::try_from(lag).unwrap_or_default()), None)
+                    }
+                    Err(e) => (None, Some(e.to_string())),
+                },
+                Ok(None) => (None, Some("No heartbeat replicated yet".to_string())),
+                Err(e) => (None, Some(e.to_string())),
+            };
+
+            let was_serving = replica.serves(self.max_staleness);
+            let url = {
+                let mut status = replica.status.write().unwrap_or_else(PoisonError::into_inner);
+                status.healthy = error.is_none();
+                status.lag_ms = lag_ms;
+                status.checked_at = Some(Utc::now());
+                status.error = error.clone();
+                status.url.clone()
+            };
+            match (was_serving, replica.serves(self.max_staleness)) {
+                (true, false) => match (&error, lag_ms) {
+                    (Some(error), _) => warn!("Replica {} no longer serves reads: {}", url, error),
+                    (None, lag) => warn!("Replica {} no longer serves reads: {} ms behind", url, lag.unwrap_or_default()),
+                },
+                (false, true) => info!("Replica {} serves reads again", url),
+                _ => {}
+            }
+        }
+    }
+
+    /// Check the replicas every `interval` under `supervisor`
+    pub fn supervise_health_checks(self: &Arc<Self>, supervisor: &Supervisor, interval: Duration) {
+        if self.replicas.is_empty() {
+            return;
+        }
+        let pool = self.clone();
+        supervisor.spawn("db.replicas", RestartPolicy::default(), move |shutdown| {
+            let pool = pool.clone();
+            async move {
+                loop {
+                    pool.check().await;
+                    if shutdown.sleep(interval).await {
+                        return Ok(());
+                    }
+                }
+            }
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_helpers::migrated_pool;
+
+    /// A migrated database that answers `SELECT name FROM origin` with `name`
+    async fn database(name: &str) -> SqlitePool {
+        let pool = migrated_pool().await;
+        sqlx::query("CREATE TABLE origin (name TEXT)").execute(&pool).await.unwrap();
+        sqlx::query("INSERT INTO origin VALUES (?)").bind(name).execute(&pool).await.unwrap();
+        pool
+    }
+
+    async fn beat(pool: &SqlitePool, at: DateTime<Utc>) {
+        sqlx::query("INSERT OR REPLACE INTO replication_heartbeat (id, beat_at) VALUES (1, ?)")
+            .bind(at.timestamp_millis())
+            .execute(pool)
+            .await
+            .unwrap();
+    }
+
+    async fn origin(pool: &InstrumentedPool) -> String {
+        sqlx::query("SELECT name FROM origin").fetch_one(pool).await.unwrap().try_get("name").unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_reads_go_to_replicas_close_enough_behind() {
+        let primary = database("primary").await;
+        let fresh = database("fresh").await;
+        let stale = database("stale").await;
+        let pool = ReplicatedPool::new(primary.clone(), Duration::from_secs(5))
+            .with_replica("sqlite://fresh.db", fresh.clone())
+            .with_replica("sqlite://stale.db", stale.clone());
+
+        // Until a check found them close enough behind, replicas serve nothing
+        assert_eq!(origin(pool.reader()).await, "primary");
+
+        beat(&fresh, Utc::now()).await;
+        beat(&stale, Utc::now() - chrono::Duration::seconds(60)).await;
+        pool.check().await;
+        for _ in 0..3 {
+            assert_eq!(origin(pool.reader()).await, "fresh");
+        }
+        assert_eq!(origin(pool.read(ReadPreference::Primary)).await, "primary");
+        let mut lenient = Vec::new();
+        for _ in 0..2 {
+            lenient.push(origin(pool.read(ReadPreference::Within(Duration::from_secs(600)))).await);
+        }
+        lenient.sort();
+        assert_eq!(lenient, vec!["fresh", "stale"]);
+
+        let replicas = pool.replicas();
+        assert!(replicas[0].healthy && replicas[0].lag_ms.unwrap() < 5000);
+        assert!(replicas[1].healthy && replicas[1].lag_ms.unwrap() >= 60_000);
+        let beat_at: i64 = sqlx::query("SELECT beat_at FROM replication_heartbeat")
+            .fetch_one(&primary)
+            .await
+            .unwrap()
+            .try_get("beat_at")
+            .unwrap();
+        assert!(beat_at > 0);
+
+        // Reads fail over to the primary once the replica cannot be queried
+        fresh.close().await;
+        pool.check().await;
+        assert!(!pool.replicas()[0].healthy);
+        assert_eq!(origin(pool.reader()).await, "primary");
+    }
+}
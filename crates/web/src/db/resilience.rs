@@ -0,0 +1,370 @@
+//! Retries and circuit breaking for statements of the web database.
+//!
+//! Statements run through an [`InstrumentedPool`](super::InstrumentedPool)
+//! go through the installed [`DbResilience`]:
+//!
+//! - A statement failing before it ran, because no connection could be had
+//!   in time or the database was busy or locked, is run again with growing
+//!   delays, up to `db_resilience.max_attempts` attempts in all. A stream of
+//!   rows is only run again if it failed before its first row.
+//! - Once `db_resilience.failure_threshold` statements in a row failed
+//!   because the database could not be reached, the circuit opens: for
+//!   `db_resilience.open_secs`, statements fail at once with
+//!   [`DatabaseUnavailable`] rather than wait for a connection. The next
+//!   statement after that is a trial; if the database answers it, the
+//!   circuit closes again, otherwise it stays open for another period.
+//!
+//! API clients get `503 Service Unavailable` with the `unavailable` error
+//! code for these errors, see [`unavailable_message`].
+
+use std::future::Future;
+use std::io;
+use std::sync::{Arc, Mutex, PoisonError, RwLock};
+use std::time::{Duration, Instant};
+
+use futures::stream::{self, BoxStream, StreamExt};
+use serde::{Deserialize, Serialize};
+use sqlx::query::Query;
+use sqlx::sqlite::SqliteArguments;
+use sqlx::{Execute, Sqlite};
+use squirrel_core::retry::{Retry, RetryPolicy};
+use tracing::{info, warn};
+
+use crate::config::DbResilienceConfig;
+
+/// Name retried statements are reported under
+const RETRY_OPERATION: &str = "db.query";
+
+/// Error of statements refused while the circuit is open
+#[derive(Debug, Clone, thiserror::Error)]
+#[error("The database is unavailable; statements fail at once for another {}s", .retry_after.as_secs().max(1))]
+pub struct DatabaseUnavailable {
+    /// Time until the circuit lets a statement through again
+    pub retry_after: Duration,
+}
+
+/// State of the circuit
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum CircuitState {
+    /// Statements run
+    Closed,
+    /// Statements fail at once
+    Open,
+    /// One trial statement runs; the others fail at once
+    HalfOpen,
+}
+
+/// The circuit and the failures counted towards opening it
+struct Circuit {
+    state: CircuitState,
+    /// When the circuit opened, or let the trial statement through
+    since: Instant,
+    /// Statements in a row that could not reach the database
+    failures: u32,
+}
+
+/// Retries statements that failed before they ran and stops sending
+/// statements to a database that cannot be reached
+pub struct DbResilience {
+    config: DbResilienceConfig,
+    policy: RetryPolicy,
+    circuit: Mutex<Circuit>,
+}
+
+impl DbResilience {
+    /// Create a new DbResilience
+    pub fn new(config: DbResilienceConfig) -> Self {
+        let policy = RetryPolicy::exponential(
+            config.max_attempts.max(1),
+            Duration::from_millis(config.retry_delay_ms),
+            Duration::from_millis(config.max_retry_delay_ms),
+        )
+        .with_jitter();
+        Self {
+            config,
+            policy,
+            circuit: Mutex::new(Circuit {
+                state: CircuitState::Closed,
+                since: Instant::now(),
+                failures: 0,
+            }),
+        }
+    }
+
+    /// State of the circuit
+    pub fn state(&self) -> CircuitState {
+        self.circuit.lock().unwrap_or_else(PoisonError::into_inner).state
+    }
+
+    /// Let a statement through, or refuse it if the circuit is open
+    fn admit(&self) -> Result<(), sqlx::Error> {
+        let mut circuit = self.circuit.lock().unwrap_or_else(PoisonError::into_inner);
+        if circuit.state == CircuitState::Closed {
+            return Ok(());
+        }
+        // A trial that never finished, e.g. because it was cancelled, does
+        // not keep the circuit from trying again
+        let open_for = Duration::from_secs(self.config.open_secs);
+        let elapsed = circuit.since.elapsed();
+        if elapsed >= open_for {
+            circuit.state = CircuitState::HalfOpen;
+            circuit.since = Instant::now();
+            return Ok(());
+        }
+        Err(sqlx::Error::Io(io::Error::other(DatabaseUnavailable {
+            retry_after: open_for - elapsed,
+        })))
+    }
+
+    /// Count the outcome of a statement towards the circuit
+    fn record(&self, error: Option<&sqlx::Error>) {
+        let mut circuit = self.circuit.lock().unwrap_or_else(PoisonError::into_inner);
+        if !error.is_some_and(is_outage) {
+            // The database answered
+            if circuit.state != CircuitState::Closed {
+                info!("Database reachable again, closing the circuit");
+            }
+            circuit.state = CircuitState::Closed;
+            circuit.failures = 0;
+            return;
+        }
+        circuit.failures = circuit.failures.saturating_add(1);
+        let opens = match circuit.state {
+            CircuitState::Closed => circuit.failures >= self.config.failure_threshold.max(1),
+            CircuitState::HalfOpen => true,
+            CircuitState::Open => false,
+        };
+        if opens {
+            warn!(
+                "Database unreachable after {} failed statements, failing statements for {}s",
+                circuit.failures, self.config.open_secs
+            );
+            circuit.state = CircuitState::Open;
+            circuit.since = Instant::now();
+        }
+    }
+
+    /// Run the statement `attempt` runs, again while it fails before it ran
+    pub async fn run<T, F, Fut>(&self, mut attempt: F) -> Result<T, sqlx::Error>
+    where
+        F: FnMut() -> Fut,
+        Fut: Future<Output = Result<T, sqlx::Error>>,
+    {
+        self.admit()?;
+        // Only statements that are retried are reported to the retry observer
+        let mut retry: Option<Retry> = None;
+        loop {
+            let error = match attempt().await {
+                Ok(value) => {
+                    if let Some(retry) = retry {
+                        retry.succeeded();
+                    }
+                    self.record(None);
+                    return Ok(value);
+                }
+                Err(e) => e,
+            };
+            if is_transient(&error) {
+                let retry = retry.get_or_insert_with(|| self.policy.start(RETRY_OPERATION));
+                if let Some(delay) = retry.next_delay() {
+                    tokio::time::sleep(delay).await;
+                    continue;
+                }
+            } else if let Some(retry) = retry.take() {
+                retry.stopped();
+            }
+            self.record(Some(&error));
+            return Err(error);
+        }
+    }
+
+    /// Stream the results of the statement `attempt` runs, running it again
+    /// while it fails before its first result
+    pub fn stream<'e, T, F>(self: Arc<Self>, attempt: F) -> BoxStream<'e, Result<T, sqlx::Error>>
+    where
+        T: Send + 'e,
+        F: Fn() -> BoxStream<'e, Result<T, sqlx::Error>> + Send + 'e,
+    {
+        let started = async move {
+            self.run(move || {
+                let mut results = attempt();
+                async move {
+                    match results.next().await {
+                        Some(Err(e)) => Err(e),
+                        first => Ok((first, results)),
+                    }
+                }
+            })
+            .await
+        };
+        stream::once(started)
+            .flat_map(|started| match started {
+                Ok((first, rest)) => stream::iter(first).chain(rest).boxed(),
+                Err(e) => stream::iter(Some(Err(e))).boxed(),
+            })
+            .boxed()
+    }
+}
+
+/// A statement taken apart so that it can be run again
+pub(crate) struct Replayable<'q> {
+    sql: &'q str,
+    arguments: Option<SqliteArguments<'q>>,
+    persistent: bool,
+}
+
+impl<'q> Replayable<'q> {
+    /// Take the SQL and arguments of `query`
+    pub(crate) fn take<E: Execute<'q, Sqlite>>(mut query: E) -> Self {
+        Self {
+            sql: query.sql(),
+            arguments: query.take_arguments(),
+            persistent: query.persistent(),
+        }
+    }
+
+    /// A query running the statement once more
+    pub(crate) fn query(&self) -> Query<'q, Sqlite, SqliteArguments<'q>> {
+        sqlx::query_with(self.sql, self.arguments.clone().unwrap_or_default()).persistent(self.persistent)
+    }
+}
+
+/// Whether `error` means the statement did not run and may succeed if run
+/// again: no connection was free in time, or the database was busy or locked
+pub fn is_transient(error: &sqlx::Error) -> bool {
+    match error {
+        sqlx::Error::PoolTimedOut => true,
+        sqlx::Error::Database(error) => error
+            .code()
+            .and_then(|code| code.parse::<i32>().ok())
+            // Extended result codes keep the primary code in their low byte
+            .is_some_and(|code| matches!(code & 0xff, 5 | 6)),
+        _ => false,
+    }
+}
+
+/// Whether `error` means the database could not be reached
+fn is_outage(error: &sqlx::Error) -> bool {
+    matches!(
+        error,
+        sqlx::Error::PoolTimedOut | sqlx::Error::Io(_) | sqlx::Error::WorkerCrashed
+    )
+}
+
+/// The error `error` was made from if it was refused by an open circuit
+pub fn unavailable(error: &sqlx::Error) -> Option<&DatabaseUnavailable> {
+    match error {
+        sqlx::Error::Io(error) => error.get_ref()?.downcast_ref(),
+        _ => None,
+    }
+}
+
+/// What to tell an API client about `error` if the database was
+/// unavailable rather than the statement wrong
+pub fn unavailable_message(error: &sqlx::Error) -> Option<String> {
+    if let Some(unavailable) = unavailable(error) {
+        return Some(unavailable.to_string());
+    }
+    if is_transient(error) {
+        return Some("The database is busy; retry shortly".to_string());
+    }
+    is_outage(error).then(|| "The database cannot be reached; retry shortly".to_string())
+}
+
+/// The installed resilience
+static RESILIENCE: RwLock<Option<Arc<DbResilience>>> = RwLock::new(None);
+
+/// Run the statements of every instrumented pool through `resilience` from now on
+pub fn install_resilience(resilience: Arc<DbResilience>) {
+    *RESILIENCE.write().unwrap_or_else(PoisonError::into_inner) = Some(resilience);
+}
+
+/// The installed resilience, if any
+pub fn resilience() -> Option<Arc<DbResilience>> {
+    RESILIENCE.read().unwrap_or_else(PoisonError::into_inner).clone()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicU32, Ordering};
+
+    fn resilience(failure_threshold: u32, open_secs: u64) -> DbResilience {
+        DbResilience::new(DbResilienceConfig {
+            max_attempts: 3,
+            retry_delay_ms: 1,
+            max_retry_delay_ms: 1,
+            failure_threshold,
+            open_secs,
+        })
+    }
+
+    #[tokio::test]
+    async fn test_statements_are_retried_until_they_run() {
+        let resilience = resilience(5, 30);
+        let attempts = AtomicU32::new(0);
+        let value = resilience
+            .run(|| async {
+                if attempts.fetch_add(1, Ordering::SeqCst) < 2 {
+                    Err(sqlx::Error::PoolTimedOut)
+                } else {
+                    Ok(42)
+                }
+            })
+            .await
+            .unwrap();
+        assert_eq!((value, attempts.load(Ordering::SeqCst)), (42, 3));
+
+        // Errors of statements that ran are not retried
+        attempts.store(0, Ordering::SeqCst);
+        let missing = resilience
+            .run(|| async {
+                attempts.fetch_add(1, Ordering::SeqCst);
+                Err::<(), _>(sqlx::Error::RowNotFound)
+            })
+            .await;
+        assert!(matches!(missing, Err(sqlx::Error::RowNotFound)));
+        assert_eq!(attempts.load(Ordering::SeqCst), 1);
+        assert_eq!(resilience.state(), CircuitState::Closed);
+
+        let results: Vec<_> = Arc::new(resilience)
+            .stream(|| {
+                let first = attempts.fetch_add(1, Ordering::SeqCst) < 2;
+                let results = if first { vec![Err(sqlx::Error::PoolTimedOut)] } else { vec![Ok(1), Ok(2)] };
+                stream::iter(results).boxed()
+            })
+            .collect()
+            .await;
+        assert_eq!(results.into_iter().map(Result::unwrap).collect::<Vec<_>>(), vec![1, 2]);
+    }
+
+    #[tokio::test]
+    async fn test_circuit_opens_while_the_database_is_unreachable() {
+        let resilience = resilience(2, 0);
+        let down = || async { Err::<(), _>(sqlx::Error::PoolTimedOut) };
+        resilience.run(down).await.unwrap_err();
+        assert_eq!(resilience.state(), CircuitState::Closed);
+        resilience.run(down).await.unwrap_err();
+        assert_eq!(resilience.state(), CircuitState::Open);
+
+        // With no open period left, the next statement is a trial
+        assert!(resilience.run(down).await.is_err());
+        assert_eq!(resilience.state(), CircuitState::Open);
+        resilience.run(|| async { Ok(()) }).await.unwrap();
+        assert_eq!(resilience.state(), CircuitState::Closed);
+
+        let resilience = resilience_open();
+        let refused = resilience.run(|| async { Ok(()) }).await.unwrap_err();
+        assert!(unavailable(&refused).is_some());
+        assert!(unavailable_message(&refused).unwrap().contains("unavailable"));
+        assert!(unavailable_message(&sqlx::Error::RowNotFound).is_none());
+    }
+
+    /// A resilience whose circuit opened just now
+    fn resilience_open() -> DbResilience {
+        let resilience = resilience(1, 30);
+        resilience.record(Some(&sqlx::Error::PoolTimedOut));
+        resilience
+    }
+}
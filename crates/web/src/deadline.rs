@@ -0,0 +1,93 @@
+//! Request deadlines.
+//!
+//! [`bound_requests`] gives each request a [`Deadline`] of
+//! `request_timeout_secs` from the configuration serving it and handles the
+//! request under it. Everything the handler awaits, from database queries to
+//! MCP calls and tool executions, is cancelled when the deadline passes, and
+//! the client gets a `504` problem response. Code deeper down reads the
+//! deadline with [`Deadline::current`] to bound its own waits or hand the
+//! remaining budget on to a peer.
+
+use std::time::Duration;
+
+use axum::{
+    http::Request,
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use squirrel_core::deadline::Deadline;
+use tracing::warn;
+
+use crate::api::error::AppError;
+use crate::rollout::ServedConfig;
+
+/// Handle a request under the deadline of its configuration's request timeout
+///
+/// The timeout is that of the configuration the rollout layer chose for the
+/// request, so this layer must run inside it; requests it has not seen are
+/// not bounded.
+pub async fn bound_requests<B>(req: Request<B>, next: Next<B>) -> Response {
+    let Some(served) = req.extensions().get::<ServedConfig>() else {
+        return next.run(req).await;
+    };
+    let timeout = Duration::from_secs(served.config.request_timeout_secs);
+    let path = req.uri().path().to_string();
+    match Deadline::after(timeout).run(next.run(req)).await {
+        Ok(response) => response,
+        Err(err) => {
+            warn!("Request for {} cancelled after {:?}", path, timeout);
+            AppError::from(err).into_response()
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::sync::Arc;
+
+    use axum::{
+        body::{Body, HttpBody},
+        http::{header, StatusCode},
+        middleware::from_fn,
+        routing::get,
+        Router,
+    };
+    use tower::ServiceExt;
+
+    use crate::api::error::PROBLEM_JSON;
+    use crate::config::Config;
+    use crate::rollout::Arm;
+
+    #[tokio::test]
+    async fn test_requests_past_the_timeout_are_cancelled() {
+        let config = Config {
+            request_timeout_secs: 1,
+            ..Config::default()
+        };
+        let app = Router::new()
+            .route("/fast", get(|| async { Deadline::current().is_some().to_string() }))
+            .route("/slow", get(|| async { tokio::time::sleep(Duration::from_secs(30)).await }))
+            .layer(from_fn(bound_requests));
+        let request = |path: &str| {
+            let mut request = Request::builder().uri(path).body(Body::empty()).unwrap();
+            request.extensions_mut().insert(ServedConfig {
+                revision: 1,
+                arm: Arm::Stable,
+                config: Arc::new(config.clone()),
+                client: None,
+            });
+            request
+        };
+
+        let response = app.clone().oneshot(request("/fast")).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        let body = response.into_body().data().await.unwrap().unwrap();
+        assert_eq!(&body[..], b"true");
+
+        let response = app.oneshot(request("/slow")).await.unwrap();
+        assert_eq!(response.status(), StatusCode::GATEWAY_TIMEOUT);
+        assert_eq!(response.headers()[header::CONTENT_TYPE], PROBLEM_JSON);
+    }
+}
@@ -0,0 +1,387 @@
+//! Deployment artifacts for the web server.
+//!
+//! [`generate`] derives a container image and the manifests to run it from a
+//! server [`Config`]:
+//!
+//! - `Dockerfile` building `web_server` with the features of the profile,
+//! - `config.toml`, the configuration with every path moved under the
+//!   container's data volume,
+//! - `docker-compose.yml`, mounting the configuration and the volume,
+//! - `k8s/`, a ConfigMap, Deployment with health probes and Service.
+//!
+//! The server reads its settings from the environment variables the
+//! manifests set: `SQUIRREL_CONFIG`, `SQUIRREL_BIND_ADDRESS`, `SQUIRREL_PORT`
+//! and `DATABASE_URL`.
+
+use std::fmt;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+
+use crate::config::Config;
+use crate::websocket::backplane::BackplaneKind;
+
+/// Directory of the container's data volume
+pub const DATA_DIR: &str = "/var/lib/squirrel";
+/// Path the configuration is mounted at in the container
+pub const CONFIG_PATH: &str = "/etc/squirrel/config.toml";
+
+/// Database setup a deployment is generated for
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum DeploymentProfile {
+    /// Default build with an in-memory database; each replica has its own
+    MockDb,
+}
+
+impl fmt::Display for DeploymentProfile {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::MockDb => write!(f, "mock-db"),
+        }
+    }
+}
+
+impl FromStr for DeploymentProfile {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "mock-db" => Ok(Self::MockDb),
+            other => Err(format!("Unknown profile '{other}'; expected mock-db")),
+        }
+    }
+}
+
+/// Settings of a generated deployment that the server configuration does not hold
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DeploymentOptions {
+    /// Database setup
+    pub profile: DeploymentProfile,
+    /// Name of the application, used for services, labels and volumes
+    pub name: String,
+    /// Image the manifests run
+    pub image: String,
+    /// Kubernetes namespace; the current one without
+    pub namespace: Option<String>,
+    /// Port the server listens on in the container
+    pub port: u16,
+    /// Number of server replicas
+    pub replicas: u32,
+}
+
+impl Default for DeploymentOptions {
+    fn default() -> Self {
+        Self {
+            profile: DeploymentProfile::MockDb,
+            name: "squirrel".to_string(),
+            image: "squirrel-web:latest".to_string(),
+            namespace: None,
+            port: 3000,
+            replicas: 1,
+        }
+    }
+}
+
+/// A generated file
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct GeneratedFile {
+    /// Path relative to the output directory
+    pub path: PathBuf,
+    /// Contents
+    pub contents: String,
+}
+
+/// Generated files, and what to look out for when deploying them
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Deployment {
+    /// Files to write
+    pub files: Vec<GeneratedFile>,
+    /// Settings that will not work as expected in this deployment
+    pub warnings: Vec<String>,
+}
+
+impl Deployment {
+    /// The file generated at `path`, if any
+    pub fn file(&self, path: impl AsRef<Path>) -> Option<&str> {
+        self.files
+            .iter()
+            .find(|file| file.path == path.as_ref())
+            .map(|file| file.contents.as_str())
+    }
+
+    fn add(&mut self, path: &str, contents: String) {
+        self.files.push(GeneratedFile {
+            path: PathBuf::from(path),
+            contents,
+        });
+    }
+}
+
+/// Generates the image and manifests deploying `config`
+pub fn generate(config: &Config, options: &DeploymentOptions) -> anyhow::Result<Deployment> {
+    let mut deployment = Deployment {
+        warnings: warnings(config, options),
+        ..Deployment::default()
+    };
+    let config_toml = toml::to_string_pretty(&container_config(config))?;
+
+    deployment.add("Dockerfile", dockerfile(options));
+    deployment.add("config.toml", config_toml.clone());
+    deployment.add("docker-compose.yml", yaml(&compose(options))?);
+    deployment.add("k8s/configmap.yaml", yaml(&config_map(options, &config_toml))?);
+    deployment.add("k8s/deployment.yaml", yaml(&server_deployment(config, options))?);
+    deployment.add("k8s/service.yaml", yaml(&service(options))?);
+    Ok(deployment)
+}
+
+/// `config` with its files and directories moved under [`DATA_DIR`]
+///
+/// Paths on the machine generating the deployment mean nothing in the
+/// container, so each one is replaced by a fixed place on the data volume.
+pub fn container_config(config: &Config) -> Config {
+    let data = |name: &str| PathBuf::from(DATA_DIR).join(name);
+    let mut config = config.clone();
+    config.files.workspace_dir = data("workspace");
+    config.files.data_dir = data("data");
+    config.files.upload_dir = data("uploads");
+    config.files.blob_dir = config.files.blob_dir.as_ref().map(|_| data("blobs"));
+    config.result_cache_dir = config.result_cache_dir.as_ref().map(|_| data("cache/results"));
+    config.usage.ledger_path = config.usage.ledger_path.as_ref().map(|_| data("usage.json"));
+    config.alerts.state_path = config.alerts.state_path.as_ref().map(|_| data("alerts.json"));
+    config.tool_history_path = config.tool_history_path.as_ref().map(|_| data("tool-history.json"));
+    config.tool_manifest_dir = config.tool_manifest_dir.as_ref().map(|_| data("tools"));
+    config
+}
+
+/// Settings that will not work as expected with `options`
+fn warnings(config: &Config, options: &DeploymentOptions) -> Vec<String> {
+    let mut warnings = Vec::new();
+    if options.replicas > 1 {
+        if options.profile == DeploymentProfile::MockDb {
+            warnings.push("Each mock-db replica keeps its own in-memory database".to_string());
+        }
+        if !config.leader_election.enabled {
+            warnings.push("Every replica runs the leader-only tasks; enable leader_election".to_string());
+        }
+        if config.backplane.kind == BackplaneKind::None {
+            warnings.push("WebSocket events stay on the replica they occur on; configure a backplane".to_string());
+        }
+    }
+    warnings
+}
+
+/// Serializes a manifest
+fn yaml(value: &Value) -> anyhow::Result<String> {
+    Ok(serde_yaml::to_string(value)?)
+}
+
+/// Labels of the application's `component`
+fn labels(options: &DeploymentOptions, component: &str) -> Value {
+    json!({
+        "app.kubernetes.io/name": options.name,
+        "app.kubernetes.io/component": component,
+    })
+}
+
+/// Metadata of the object `name` of `component`
+fn metadata(options: &DeploymentOptions, name: &str, component: &str) -> Value {
+    let mut metadata = json!({ "name": name, "labels": labels(options, component) });
+    if let Some(namespace) = &options.namespace {
+        metadata["namespace"] = json!(namespace);
+    }
+    metadata
+}
+
+/// Image build for the profile's features
+fn dockerfile(options: &DeploymentOptions) -> String {
+    let features = match options.profile {
+        DeploymentProfile::MockDb => "",
+    };
+    format!(
+        r#"# Generated by `squirrel deploy generate --profile {profile}`; build from the workspace root
+FROM rust:1-bookworm AS build
+WORKDIR /src
+COPY . .
+RUN cargo build --release -p squirrel-web --bin web_server{features}
+
+FROM debian:bookworm-slim
+RUN apt-get update \
+    && apt-get install -y --no-install-recommends ca-certificates curl libssl3 \
+    && rm -rf /var/lib/apt/lists/*
+RUN useradd --system --create-home --home-dir {data} squirrel
+COPY --from=build /src/target/release/web_server /usr/local/bin/web_server
+ENV SQUIRREL_CONFIG={config} \
+    SQUIRREL_BIND_ADDRESS=0.0.0.0 \
+    SQUIRREL_PORT={port}
+USER squirrel
+WORKDIR {data}
+VOLUME {data}
+EXPOSE {port}
+CMD ["web_server"]
+"#,
+        profile = options.profile,
+        data = DATA_DIR,
+        config = CONFIG_PATH,
+        port = options.port,
+    )
+}
+
+/// Database URL of the server in Compose
+fn compose_database_url(options: &DeploymentOptions) -> String {
+    match options.profile {
+        DeploymentProfile::MockDb => "sqlite::memory:".to_string(),
+    }
+}
+
+/// Compose file running the server
+fn compose(options: &DeploymentOptions) -> Value {
+    let port = options.port;
+    let server = json!({
+        "image": options.image,
+        "build": { "context": "..", "dockerfile": "deploy/Dockerfile" },
+        "ports": [format!("{port}:{port}")],
+        "environment": {
+            "SQUIRREL_CONFIG": CONFIG_PATH,
+            "SQUIRREL_BIND_ADDRESS": "0.0.0.0",
+            "SQUIRREL_PORT": port.to_string(),
+            "DATABASE_URL": compose_database_url(options),
+        },
+        "volumes": [
+            format!("./config.toml:{CONFIG_PATH}:ro"),
+            format!("{}-data:{DATA_DIR}", options.name),
+        ],
+        "healthcheck": {
+            "test": ["CMD", "curl", "-fsS", format!("http://localhost:{port}/health")],
+            "interval": "10s",
+            "timeout": "3s",
+            "retries": 3,
+        },
+        "restart": "unless-stopped",
+    });
+    let volumes = json!({ format!("{}-data", options.name): {} });
+    let mut services = json!({});
+    services[&options.name] = server;
+    json!({ "services": services, "volumes": volumes })
+}
+
+/// ConfigMap holding the server configuration
+fn config_map(options: &DeploymentOptions, config_toml: &str) -> Value {
+    json!({
+        "apiVersion": "v1",
+        "kind": "ConfigMap",
+        "metadata": metadata(options, &format!("{}-config", options.name), "web"),
+        "data": { "config.toml": config_toml },
+    })
+}
+
+/// Deployment of the server with health probes
+fn server_deployment(config: &Config, options: &DeploymentOptions) -> Value {
+    let mut env = vec![
+        json!({ "name": "SQUIRREL_CONFIG", "value": CONFIG_PATH }),
+        json!({ "name": "SQUIRREL_BIND_ADDRESS", "value": "0.0.0.0" }),
+        json!({ "name": "SQUIRREL_PORT", "value": options.port.to_string() }),
+    ];
+    match options.profile {
+        DeploymentProfile::MockDb => env.push(json!({ "name": "DATABASE_URL", "value": "sqlite::memory:" })),
+    }
+
+    let probe = |path: &str, initial_delay_seconds: u32| {
+        json!({
+            "httpGet": { "path": path, "port": "http" },
+            "initialDelaySeconds": initial_delay_seconds,
+            "periodSeconds": 10,
+            "timeoutSeconds": 3,
+            "failureThreshold": 3,
+        })
+    };
+    let mut container = json!({
+        "name": "web",
+        "image": options.image,
+        "ports": [{ "name": "http", "containerPort": options.port }],
+        "env": env,
+        "readinessProbe": probe("/ready", 5),
+        "livenessProbe": probe("/health", 15),
+        "volumeMounts": [
+            { "name": "config", "mountPath": CONFIG_PATH, "subPath": "config.toml", "readOnly": true },
+            { "name": "data", "mountPath": DATA_DIR },
+        ],
+    });
+    // The watchdog sheds work before the process reaches its budget, so the
+    // budget is the most the container needs
+    if let Some(budget_mb) = config.memory_watchdog.budget_mb {
+        container["resources"] = json!({ "limits": { "memory": format!("{budget_mb}Mi") } });
+    }
+
+    json!({
+        "apiVersion": "apps/v1",
+        "kind": "Deployment",
+        "metadata": metadata(options, &options.name, "web"),
+        "spec": {
+            "replicas": options.replicas,
+            "selector": { "matchLabels": labels(options, "web") },
+            "template": {
+                "metadata": { "labels": labels(options, "web") },
+                "spec": {
+                    "containers": [container],
+                    "volumes": [
+                        { "name": "config", "configMap": { "name": format!("{}-config", options.name) } },
+                        { "name": "data", "emptyDir": {} },
+                    ],
+                },
+            },
+        },
+    })
+}
+
+/// Service in front of the server replicas
+fn service(options: &DeploymentOptions) -> Value {
+    json!({
+        "apiVersion": "v1",
+        "kind": "Service",
+        "metadata": metadata(options, &options.name, "web"),
+        "spec": {
+            "selector": labels(options, "web"),
+            "ports": [{ "name": "http", "port": 80, "targetPort": "http" }],
+        },
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn manifest(deployment: &Deployment, path: &str) -> Value {
+        serde_yaml::from_str(deployment.file(path).unwrap()).unwrap()
+    }
+
+    #[test]
+    fn test_mock_db_deployment() {
+        let mut config = Config::default();
+        config.memory_watchdog.budget_mb = Some(2048);
+        let deployment = generate(&config, &DeploymentOptions::default()).unwrap();
+        assert!(deployment.warnings.is_empty());
+        assert!(deployment.file("Dockerfile").unwrap().contains("--bin web_server\n"));
+
+        let server = manifest(&deployment, "k8s/deployment.yaml");
+        let container = &server["spec"]["template"]["spec"]["containers"][0];
+        assert_eq!(container["readinessProbe"]["httpGet"]["path"], "/ready");
+        assert_eq!(container["livenessProbe"]["httpGet"]["path"], "/health");
+        assert_eq!(container["resources"]["limits"]["memory"], "2048Mi");
+        assert_eq!(container["env"][3]["value"], "sqlite::memory:");
+
+        let compose = manifest(&deployment, "docker-compose.yml");
+        assert_eq!(compose["services"]["squirrel"]["ports"][0], "3000:3000");
+        assert_eq!(compose["services"]["squirrel"]["environment"]["DATABASE_URL"], "sqlite::memory:");
+        assert!("postgres".parse::<DeploymentProfile>().is_err());
+
+        // The mounted configuration is the container's view of the config
+        let config_map = manifest(&deployment, "k8s/configmap.yaml");
+        let mounted: Config = toml::from_str(config_map["data"]["config.toml"].as_str().unwrap()).unwrap();
+        assert_eq!(mounted.files.workspace_dir, Path::new("/var/lib/squirrel/workspace"));
+        assert_eq!(mounted.usage.ledger_path.as_deref(), Some(Path::new("/var/lib/squirrel/usage.json")));
+        assert_eq!(mounted.memory_watchdog.budget_mb, Some(2048));
+    }
+}
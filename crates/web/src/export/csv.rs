@@ -0,0 +1,61 @@
+//! CSV output of exports, as in RFC 4180.
+
+use chrono::SecondsFormat;
+
+use super::{Column, Encoder, Value};
+use crate::api::error::AppError;
+
+/// Writes a header row, then one line per row
+pub(super) struct CsvEncoder {
+    /// The header row, until it is written
+    header: Option<Vec<u8>>,
+}
+
+impl CsvEncoder {
+    pub(super) fn new(columns: &[Column]) -> Self {
+        let mut header = Vec::new();
+        write_line(&mut header, columns.iter().map(|column| column.name.to_string()));
+        Self { header: Some(header) }
+    }
+}
+
+impl Encoder for CsvEncoder {
+    fn push(&mut self, row: &[Value]) -> Result<Vec<u8>, AppError> {
+        let mut output = self.header.take().unwrap_or_default();
+        write_line(&mut output, row.iter().map(field));
+        Ok(output)
+    }
+
+    fn finish(self: Box<Self>) -> Result<Vec<u8>, AppError> {
+        // An export without rows is still a header
+        Ok(self.header.unwrap_or_default())
+    }
+}
+
+/// The text of a value; times in RFC 3339
+fn field(value: &Value) -> String {
+    match value {
+        Value::Null => String::new(),
+        Value::Text(text) => text.clone(),
+        Value::Integer(integer) => integer.to_string(),
+        Value::Real(real) => real.to_string(),
+        Value::Time(time) => time.to_rfc3339_opts(SecondsFormat::AutoSi, true),
+    }
+}
+
+/// Write fields as a line, quoting those with separators, quotes or line breaks
+fn write_line(output: &mut Vec<u8>, fields: impl Iterator<Item = String>) {
+    for (index, field) in fields.enumerate() {
+        if index > 0 {
+            output.push(b',');
+        }
+        if field.contains([',', '"', '\n', '\r']) {
+            output.push(b'"');
+            output.extend(field.replace('"', "\"\"").into_bytes());
+            output.push(b'"');
+        } else {
+            output.extend(field.into_bytes());
+        }
+    }
+    output.extend(b"\r\n");
+}
@@ -0,0 +1,606 @@
+//! Extracts of jobs, command history, usage and experiments for analysis.
+//!
+//! An export selects one dataset over a date range, optionally for one user,
+//! and encodes it as CSV or, with the `parquet-export` feature, as Parquet.
+//! Rows are read from the database as they are encoded and the output is
+//! handed on in chunks, so an export never holds the whole dataset in
+//! memory; Parquet output is written one row group at a time.
+//!
+//! Jobs and command executions are selected by when they were created,
+//! experiments by when a resource was tagged `experiment=<name>`, and usage
+//! by the accounting periods overlapping the range.
+
+mod csv;
+#[cfg(feature = "parquet-export")]
+mod parquet;
+
+use std::fmt;
+use std::str::FromStr;
+use std::sync::Arc;
+
+use axum::body::Bytes;
+use chrono::{DateTime, TimeZone, Utc};
+use futures::TryStreamExt;
+use serde::{Deserialize, Serialize};
+use sqlx::sqlite::SqliteRow;
+use sqlx::{Row, SqlitePool};
+use squirrel_monitoring::accounting::{UsageLedger, UsageQuery, UsageRecord};
+use tokio::sync::mpsc;
+use tokio_stream::wrappers::ReceiverStream;
+use tracing::warn;
+
+use crate::api::error::AppError;
+use crate::db::{InstrumentedPool, ReplicatedPool};
+
+/// Bytes of output gathered before they are handed on
+const CHUNK_BYTES: usize = 64 * 1024;
+
+/// Chunks encoded ahead of a slow reader
+const CHUNKS_AHEAD: usize = 4;
+
+/// Columns of the `jobs` dataset
+const JOB_COLUMNS: &[Column] = &[
+    Column::text("id"),
+    Column::text("user_id"),
+    Column::text("repository_url"),
+    Column::text("git_ref"),
+    Column::text("status"),
+    Column::real("progress"),
+    Column::text("error"),
+    Column::text("result_url"),
+    Column::time("created_at"),
+    Column::time("updated_at"),
+];
+
+/// Columns of the `commands` dataset
+const COMMAND_COLUMNS: &[Column] = &[
+    Column::text("id"),
+    Column::text("command_name"),
+    Column::text("user_id"),
+    Column::text("status"),
+    Column::real("progress"),
+    Column::text("parameters"),
+    Column::text("error"),
+    Column::time("started_at"),
+    Column::time("completed_at"),
+    Column::time("created_at"),
+];
+
+/// Columns of the `usage` dataset
+const USAGE_COLUMNS: &[Column] = &[
+    Column::text("user"),
+    Column::text("workspace"),
+    Column::time("period_start"),
+    Column::time("period_end"),
+    Column::integer("cpu_ms"),
+    Column::integer("memory_mb_seconds"),
+    Column::integer("storage_bytes"),
+    Column::integer("executions"),
+];
+
+/// Columns of the `experiments` dataset
+const EXPERIMENT_COLUMNS: &[Column] = &[
+    Column::text("experiment"),
+    Column::text("resource_kind"),
+    Column::text("resource_id"),
+    Column::text("user_id"),
+    Column::millis("tagged_at"),
+];
+
+/// What an export extracts
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ExportDataset {
+    /// Jobs with their status and outcome
+    Jobs,
+    /// Command executions
+    Commands,
+    /// Resource usage per accounting period
+    Usage,
+    /// Resources tagged `experiment=<name>`
+    Experiments,
+}
+
+impl ExportDataset {
+    /// The dataset's name, e.g. `jobs`
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Jobs => "jobs",
+            Self::Commands => "commands",
+            Self::Usage => "usage",
+            Self::Experiments => "experiments",
+        }
+    }
+
+    /// The columns of the dataset's rows
+    pub fn columns(self) -> &'static [Column] {
+        match self {
+            Self::Jobs => JOB_COLUMNS,
+            Self::Commands => COMMAND_COLUMNS,
+            Self::Usage => USAGE_COLUMNS,
+            Self::Experiments => EXPERIMENT_COLUMNS,
+        }
+    }
+
+    /// The query selecting the dataset's rows, binding the user, then the
+    /// start and end of the range; `None` for usage, which is not in the
+    /// database
+    fn query(self) -> Option<&'static str> {
+        match self {
+            Self::Jobs => Some(
+                "SELECT id, user_id, repository_url, git_ref, status, progress, error, result_url, created_at, updated_at
+                 FROM jobs
+                 WHERE (?1 IS NULL OR user_id = ?1) AND (?2 IS NULL OR created_at >= ?2) AND (?3 IS NULL OR created_at < ?3)
+                 ORDER BY created_at",
+            ),
+            Self::Commands => Some(
+                "SELECT id, command_name, user_id, status, progress, parameters, error, started_at, completed_at, created_at
+                 FROM command_executions
+                 WHERE (?1 IS NULL OR user_id = ?1) AND (?2 IS NULL OR created_at >= ?2) AND (?3 IS NULL OR created_at < ?3)
+                 ORDER BY created_at",
+            ),
+            Self::Experiments => Some(
+                "SELECT t.value, a.resource_kind, a.resource_id, a.user_id, a.created_at
+                 FROM tag_assignments a JOIN tags t ON t.id = a.tag_id
+                 WHERE t.key = 'experiment' AND t.value != ''
+                   AND (?1 IS NULL OR a.user_id = ?1) AND (?2 IS NULL OR a.created_at >= ?2) AND (?3 IS NULL OR a.created_at < ?3)
+                 ORDER BY a.created_at",
+            ),
+            Self::Usage => None,
+        }
+    }
+}
+
+impl FromStr for ExportDataset {
+    type Err = AppError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_lowercase().as_str() {
+            "jobs" | "job" => Ok(Self::Jobs),
+            "commands" | "command" => Ok(Self::Commands),
+            "usage" | "metrics" => Ok(Self::Usage),
+            "experiments" | "experiment" => Ok(Self::Experiments),
+            _ => Err(AppError::InvalidRequest(format!(
+                "Unknown dataset '{}'; expected jobs, commands, usage or experiments",
+                s
+            ))),
+        }
+    }
+}
+
+impl fmt::Display for ExportDataset {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// How an export is encoded
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ExportFormat {
+    /// Comma-separated values with a header row
+    #[default]
+    Csv,
+    /// Apache Parquet
+    Parquet,
+}
+
+impl ExportFormat {
+    /// The format's name, e.g. `csv`
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Csv => "csv",
+            Self::Parquet => "parquet",
+        }
+    }
+
+    /// The content type of the output
+    pub fn content_type(self) -> &'static str {
+        match self {
+            Self::Csv => "text/csv; charset=utf-8",
+            Self::Parquet => "application/vnd.apache.parquet",
+        }
+    }
+
+    /// Whether this build can write the format
+    pub fn available(self) -> bool {
+        match self {
+            Self::Csv => true,
+            Self::Parquet => cfg!(feature = "parquet-export"),
+        }
+    }
+}
+
+impl FromStr for ExportFormat {
+    type Err = AppError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_lowercase().as_str() {
+            "csv" => Ok(Self::Csv),
+            "parquet" => Ok(Self::Parquet),
+            _ => Err(AppError::InvalidRequest(format!("Unknown export format '{}'; expected csv or parquet", s))),
+        }
+    }
+}
+
+impl fmt::Display for ExportFormat {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// The rows an export selects
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ExportRequest {
+    /// Dataset to extract
+    pub dataset: ExportDataset,
+    /// Encoding of the output
+    pub format: ExportFormat,
+    /// Only this user's rows; every user's without one
+    pub user: Option<String>,
+    /// Only rows from this time on
+    pub from: Option<DateTime<Utc>>,
+    /// Only rows before this time
+    pub to: Option<DateTime<Utc>>,
+}
+
+impl ExportRequest {
+    /// Name of the file the output is saved as, e.g. `jobs.csv`
+    pub fn file_name(&self) -> String {
+        format!("{}.{}", self.dataset, self.format)
+    }
+}
+
+/// Type of the values of a column
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ColumnKind {
+    /// Text
+    Text,
+    /// 64-bit integers
+    Integer,
+    /// Floating point numbers
+    Real,
+    /// Times in UTC
+    Time,
+}
+
+/// A column of an export
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Column {
+    /// Name in the header or schema
+    pub name: &'static str,
+    /// Type of the values
+    pub kind: ColumnKind,
+    /// Whether a time is stored as milliseconds since the epoch rather
+    /// than as text
+    millis: bool,
+}
+
+impl Column {
+    const fn new(name: &'static str, kind: ColumnKind) -> Self {
+        Self { name, kind, millis: false }
+    }
+
+    const fn text(name: &'static str) -> Self {
+        Self::new(name, ColumnKind::Text)
+    }
+
+    const fn integer(name: &'static str) -> Self {
+        Self::new(name, ColumnKind::Integer)
+    }
+
+    const fn real(name: &'static str) -> Self {
+        Self::new(name, ColumnKind::Real)
+    }
+
+    const fn time(name: &'static str) -> Self {
+        Self::new(name, ColumnKind::Time)
+    }
+
+    const fn millis(name: &'static str) -> Self {
+        Self { millis: true, ..Self::time(name) }
+    }
+
+    /// The value of the column in `row`, at `index`
+    fn decode(&self, row: &SqliteRow, index: usize) -> Result<Value, sqlx::Error> {
+        let value = match self.kind {
+            ColumnKind::Text => row.try_get::<Option<String>, _>(index)?.map(Value::Text),
+            ColumnKind::Integer => row.try_get::<Option<i64>, _>(index)?.map(Value::Integer),
+            ColumnKind::Real => row.try_get::<Option<f64>, _>(index)?.map(Value::Real),
+            ColumnKind::Time if self.millis => row
+                .try_get::<Option<i64>, _>(index)?
+                .and_then(|millis| Utc.timestamp_millis_opt(millis).single())
+                .map(Value::Time),
+            ColumnKind::Time => row.try_get::<Option<DateTime<Utc>>, _>(index)?.map(Value::Time),
+        };
+        Ok(value.unwrap_or(Value::Null))
+    }
+}
+
+/// A value of an exported row
+#[derive(Debug, Clone, PartialEq)]
+pub enum Value {
+    /// No value
+    Null,
+    /// Text
+    Text(String),
+    /// An integer
+    Integer(i64),
+    /// A floating point number
+    Real(f64),
+    /// A time
+    Time(DateTime<Utc>),
+}
+
+/// Encodes rows as they arrive
+trait Encoder: Send {
+    /// Encode a row, returning the output ready to be handed on
+    fn push(&mut self, row: &[Value]) -> Result<Vec<u8>, AppError>;
+
+    /// The rest of the output, once every row was pushed
+    fn finish(self: Box<Self>) -> Result<Vec<u8>, AppError>;
+}
+
+/// An encoder of `format` for rows of `columns`
+fn encoder(format: ExportFormat, columns: &'static [Column]) -> Result<Box<dyn Encoder>, AppError> {
+    match format {
+        ExportFormat::Csv => Ok(Box::new(csv::CsvEncoder::new(columns))),
+        #[cfg(feature = "parquet-export")]
+        ExportFormat::Parquet => Ok(Box::new(parquet::ParquetEncoder::new(columns)?)),
+        #[cfg(not(feature = "parquet-export"))]
+        ExportFormat::Parquet => Err(parquet_unavailable()),
+    }
+}
+
+/// Why Parquet cannot be written by this build
+fn parquet_unavailable() -> AppError {
+    AppError::InvalidRequest("Parquet exports need the parquet-export feature; use csv".to_string())
+}
+
+/// Usage figures fit in an integer column
+fn integer(value: u64) -> Value {
+    Value::Integer(i64::try_from(value).unwrap_or(i64::MAX))
+}
+
+/// The row of a usage record
+fn usage_row(record: &UsageRecord) -> Vec<Value> {
+    vec![
+        Value::Text(record.user.clone()),
+        Value::Text(record.workspace.clone()),
+        Value::Time(record.period_start),
+        Value::Time(record.period_end),
+        integer(record.usage.cpu_ms),
+        integer(record.usage.memory_mb_seconds),
+        integer(record.usage.storage_bytes),
+        integer(record.usage.executions),
+    ]
+}
+
+/// Gathers encoded output and hands it on in chunks
+struct Output {
+    chunk: Vec<u8>,
+    sender: mpsc::Sender<Result<Bytes, AppError>>,
+}
+
+impl Output {
+    /// Add to the output; false once nobody reads it any more
+    async fn write(&mut self, bytes: Vec<u8>) -> bool {
+        self.chunk.extend(bytes);
+        if self.chunk.len() < CHUNK_BYTES {
+            return true;
+        }
+        self.flush().await
+    }
+
+    /// Hand on what was gathered
+    async fn flush(&mut self) -> bool {
+        if self.chunk.is_empty() {
+            return true;
+        }
+        let chunk = Bytes::from(std::mem::take(&mut self.chunk));
+        self.sender.send(Ok(chunk)).await.is_ok()
+    }
+}
+
+/// Streams extracts of the web database and the usage ledger
+#[derive(Clone, Default)]
+pub struct Exporter {
+    pool: Option<InstrumentedPool>,
+    replicas: Option<Arc<ReplicatedPool>>,
+    ledger: Option<Arc<UsageLedger>>,
+}
+
+impl Exporter {
+    /// Create a new Exporter with nothing to export from
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Export jobs, command executions and experiments from the database of `pool`
+    pub fn with_database(mut self, pool: SqlitePool) -> Self {
+        self.pool = Some(pool.into());
+        self
+    }
+
+    /// Read the database from the replicas of `replicas` where they are
+    /// close enough behind
+    pub fn with_replicas(mut self, replicas: Arc<ReplicatedPool>) -> Self {
+        self.pool = Some(replicas.primary().clone());
+        self.replicas = Some(replicas);
+        self
+    }
+
+    /// Export usage from `ledger`
+    pub fn with_usage_ledger(mut self, ledger: Arc<UsageLedger>) -> Self {
+        self.ledger = Some(ledger);
+        self
+    }
+
+    /// Start an export, streaming its output in chunks
+    ///
+    /// Requests that cannot be served fail here, before any output; a
+    /// failure while reading rows ends the stream with the error.
+    pub fn export(&self, request: ExportRequest) -> Result<ReceiverStream<Result<Bytes, AppError>>, AppError> {
+        if !request.format.available() {
+            return Err(parquet_unavailable());
+        }
+        if let (Some(from), Some(to)) = (request.from, request.to) {
+            if from > to {
+                return Err(AppError::InvalidRequest("The range ends before it starts".to_string()));
+            }
+        }
+        match request.dataset {
+            ExportDataset::Usage if self.ledger.is_none() => {
+                return Err(AppError::Internal("Usage ledger not configured".to_string()));
+            }
+            ExportDataset::Usage => {}
+            _ if self.pool.is_none() => return Err(AppError::Internal("Web database not configured".to_string())),
+            _ => {}
+        }
+        let encoder = encoder(request.format, request.dataset.columns())?;
+
+        let (sender, receiver) = mpsc::channel(CHUNKS_AHEAD);
+        let exporter = self.clone();
+        tokio::spawn(async move {
+            let mut output = Output { chunk: Vec::new(), sender };
+            match exporter.write(&request, encoder, &mut output).await {
+                Ok(()) => {
+                    output.flush().await;
+                }
+                Err(e) => {
+                    warn!("Export of {} failed: {}", request.dataset, e);
+                    let _ = output.sender.send(Err(e)).await;
+                }
+            }
+        });
+        Ok(ReceiverStream::new(receiver))
+    }
+
+    /// Encode the rows of `request` into `output`
+    async fn write(
+        &self,
+        request: &ExportRequest,
+        mut encoder: Box<dyn Encoder>,
+        output: &mut Output,
+    ) -> Result<(), AppError> {
+        let columns = request.dataset.columns();
+        let pool = match &self.replicas {
+            Some(replicas) => Some(replicas.reader()),
+            None => self.pool.as_ref(),
+        };
+        match (request.dataset.query(), pool) {
+            (Some(sql), Some(pool)) => {
+                // Experiments are tagged at times stored in milliseconds
+                let millis = request.dataset == ExportDataset::Experiments;
+                let query = sqlx::query(sql).bind(request.user.as_deref());
+                let query = if millis {
+                    query
+                        .bind(request.from.map(|from| from.timestamp_millis()))
+                        .bind(request.to.map(|to| to.timestamp_millis()))
+                } else {
+                    query.bind(request.from).bind(request.to)
+                };
+                let mut rows = query.fetch(pool);
+                while let Some(row) = rows.try_next().await? {
+                    let values = columns
+                        .iter()
+                        .enumerate()
+                        .map(|(index, column)| column.decode(&row, index))
+                        .collect::<Result<Vec<_>, _>>()?;
+                    if !output.write(encoder.push(&values)?).await {
+                        // The reader went away
+                        return Ok(());
+                    }
+                }
+            }
+            (Some(_), None) => return Err(AppError::Internal("Web database not configured".to_string())),
+            (None, _) => {
+                let query = UsageQuery {
+                    from: request.from,
+                    to: request.to,
+                    user: request.user.clone(),
+                    workspace: None,
+                };
+                let records = self.ledger.as_ref().map(|ledger| ledger.records(&query)).unwrap_or_default();
+                for record in &records {
+                    if !output.write(encoder.push(&usage_row(record))?).await {
+                        return Ok(());
+                    }
+                }
+            }
+        }
+        output.write(encoder.finish()?).await;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use futures::StreamExt;
+    use crate::test_helpers::migrated_pool;
+
+    async fn read(exporter: &Exporter, request: ExportRequest) -> String {
+        let chunks: Vec<_> = exporter.export(request).unwrap().collect().await;
+        let bytes: Vec<u8> = chunks.into_iter().flat_map(|chunk| chunk.unwrap().to_vec()).collect();
+        String::from_utf8(bytes).unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_exports_select_a_users_rows_in_a_range() {
+        let pool = migrated_pool().await;
+        let at = |day: u32| Utc.with_ymd_and_hms(2026, 10, day, 12, 0, 0).unwrap();
+        for user in ["alice", "bob"] {
+            sqlx::query(
+                "INSERT INTO users (id, username, email, password_hash, role, created_at, updated_at)
+                 VALUES (?, ?, ?, 'hash', 'User', ?, ?)",
+            )
+            .bind(user)
+            .bind(user)
+            .bind(format!("{}@lab.org", user))
+            .bind(at(1))
+            .bind(at(1))
+            .execute(&pool)
+            .await
+            .unwrap();
+        }
+        for (id, user, day, error) in [("c1", "alice", 1, None), ("c2", "alice", 5, Some("exit 1, \"oops\"")), ("c3", "bob", 5, None)] {
+            sqlx::query(
+                "INSERT INTO command_executions (id, command_name, user_id, parameters, status, error, created_at, updated_at)
+                 VALUES (?, 'align', ?, '{}', 'completed', ?, ?, ?)",
+            )
+            .bind(id)
+            .bind(user)
+            .bind(error)
+            .bind(at(day))
+            .bind(at(day))
+            .execute(&pool)
+            .await
+            .unwrap();
+        }
+        let exporter = Exporter::new().with_database(pool);
+        let request = ExportRequest {
+            dataset: ExportDataset::Commands,
+            format: ExportFormat::Csv,
+            user: Some("alice".to_string()),
+            from: Some(at(2)),
+            to: None,
+        };
+
+        let csv = read(&exporter, request.clone()).await;
+        let lines: Vec<&str> = csv.lines().collect();
+        assert_eq!(lines[0], "id,command_name,user_id,status,progress,parameters,error,started_at,completed_at,created_at");
+        assert_eq!(
+            lines[1..],
+            ["c2,align,alice,completed,0,{},\"exit 1, \"\"oops\"\"\",,,2026-10-05T12:00:00Z"]
+        );
+        assert_eq!(request.file_name(), "commands.csv");
+
+        let empty = ExportRequest { to: Some(at(3)), ..request.clone() };
+        assert_eq!(read(&exporter, empty).await.lines().count(), 1);
+        let usage = ExportRequest { dataset: ExportDataset::Usage, ..request.clone() };
+        assert!(exporter.export(usage).is_err());
+        let backwards = ExportRequest { from: Some(at(4)), to: Some(at(3)), ..request };
+        assert!(exporter.export(backwards).is_err());
+    }
+}
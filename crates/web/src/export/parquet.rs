@@ -0,0 +1,169 @@
+//! Parquet output of exports.
+//!
+//! Rows are gathered into row groups of [`ROW_GROUP_ROWS`]; each full group
+//! is written and handed on, so only one group is held at a time. The file
+//! footer follows the last group.
+
+use std::io::Write;
+use std::sync::{Arc, Mutex};
+
+use arrow_array::builder::{Float64Builder, Int64Builder, StringBuilder, TimestampMillisecondBuilder};
+use arrow_array::{ArrayRef, RecordBatch};
+use arrow_schema::{DataType, Field, Schema, SchemaRef, TimeUnit};
+use parquet::arrow::ArrowWriter;
+use parquet::basic::Compression;
+use parquet::file::properties::WriterProperties;
+
+use super::{Column, ColumnKind, Encoder, Value};
+use crate::api::error::AppError;
+
+/// Rows per row group
+const ROW_GROUP_ROWS: usize = 8192;
+
+/// Output the writer appends to and the encoder drains
+#[derive(Clone, Default)]
+struct SharedBuffer(Arc<Mutex<Vec<u8>>>);
+
+impl SharedBuffer {
+    /// What was written since the last call
+    fn take(&self) -> Vec<u8> {
+        self.0.lock().map(|mut buffer| std::mem::take(&mut *buffer)).unwrap_or_default()
+    }
+}
+
+impl Write for SharedBuffer {
+    fn write(&mut self, bytes: &[u8]) -> std::io::Result<usize> {
+        let mut buffer = self
+            .0
+            .lock()
+            .map_err(|_| std::io::Error::new(std::io::ErrorKind::Other, "Export buffer poisoned"))?;
+        buffer.extend_from_slice(bytes);
+        Ok(bytes.len())
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        Ok(())
+    }
+}
+
+/// Why Parquet could not be written
+fn parquet_error(e: impl std::fmt::Display) -> AppError {
+    AppError::Internal(format!("Failed to write Parquet: {}", e))
+}
+
+/// Writes rows as Parquet row groups
+pub(super) struct ParquetEncoder {
+    columns: &'static [Column],
+    schema: SchemaRef,
+    writer: ArrowWriter<SharedBuffer>,
+    buffer: SharedBuffer,
+    /// Rows of the group being gathered
+    rows: Vec<Vec<Value>>,
+}
+
+impl ParquetEncoder {
+    pub(super) fn new(columns: &'static [Column]) -> Result<Self, AppError> {
+        let fields: Vec<Field> = columns
+            .iter()
+            .map(|column| {
+                let data_type = match column.kind {
+                    ColumnKind::Text => DataType::Utf8,
+                    ColumnKind::Integer => DataType::Int64,
+                    ColumnKind::Real => DataType::Float64,
+                    ColumnKind::Time => DataType::Timestamp(TimeUnit::Millisecond, Some("UTC".into())),
+                };
+                Field::new(column.name, data_type, true)
+            })
+            .collect();
+        let schema: SchemaRef = Arc::new(Schema::new(fields));
+        let properties = WriterProperties::builder()
+            .set_compression(Compression::SNAPPY)
+            .set_max_row_group_size(ROW_GROUP_ROWS)
+            .build();
+        let buffer = SharedBuffer::default();
+        let writer = ArrowWriter::try_new(buffer.clone(), schema.clone(), Some(properties)).map_err(parquet_error)?;
+        Ok(Self {
+            columns,
+            schema,
+            writer,
+            buffer,
+            rows: Vec::with_capacity(ROW_GROUP_ROWS),
+        })
+    }
+
+    /// Write the gathered rows as a row group
+    fn write_group(&mut self) -> Result<(), AppError> {
+        if self.rows.is_empty() {
+            return Ok(());
+        }
+        let arrays: Vec<ArrayRef> = (0..self.columns.len()).map(|index| self.array(index)).collect();
+        let batch = RecordBatch::try_new(self.schema.clone(), arrays).map_err(parquet_error)?;
+        self.rows.clear();
+        self.writer.write(&batch).map_err(parquet_error)?;
+        self.writer.flush().map_err(parquet_error)
+    }
+
+    /// The values of the gathered rows in column `index`
+    fn array(&self, index: usize) -> ArrayRef {
+        let values = self.rows.iter().map(|row| row.get(index).unwrap_or(&Value::Null));
+        match self.columns[index].kind {
+            ColumnKind::Text => {
+                let mut builder = StringBuilder::new();
+                for value in values {
+                    builder.append_option(match value {
+                        Value::Text(text) => Some(text.as_str()),
+                        _ => None,
+                    });
+                }
+                Arc::new(builder.finish())
+            }
+            ColumnKind::Integer => {
+                let mut builder = Int64Builder::new();
+                for value in values {
+                    builder.append_option(match value {
+                        Value::Integer(integer) => Some(*integer),
+                        _ => None,
+                    });
+                }
+                Arc::new(builder.finish())
+            }
+            ColumnKind::Real => {
+                let mut builder = Float64Builder::new();
+                for value in values {
+                    builder.append_option(match value {
+                        Value::Real(real) => Some(*real),
+                        _ => None,
+                    });
+                }
+                Arc::new(builder.finish())
+            }
+            ColumnKind::Time => {
+                let mut builder = TimestampMillisecondBuilder::new().with_timezone("UTC");
+                for value in values {
+                    builder.append_option(match value {
+                        Value::Time(time) => Some(time.timestamp_millis()),
+                        _ => None,
+                    });
+                }
+                Arc::new(builder.finish())
+            }
+        }
+    }
+}
+
+impl Encoder for ParquetEncoder {
+    fn push(&mut self, row: &[Value]) -> Result<Vec<u8>, AppError> {
+        self.rows.push(row.to_vec());
+        if self.rows.len() >= ROW_GROUP_ROWS {
+            self.write_group()?;
+        }
+        Ok(self.buffer.take())
+    }
+
+    fn finish(mut self: Box<Self>) -> Result<Vec<u8>, AppError> {
+        self.write_group()?;
+        let buffer = self.buffer.clone();
+        self.writer.close().map_err(parquet_error)?;
+        Ok(buffer.take())
+    }
+}
@@ -0,0 +1,406 @@
+//! Feature flags admins change at runtime.
+//!
+//! The flags themselves are declared in [`squirrel_core::flags`], which is
+//! where every crate reads them. Their settings come from the `flags`
+//! section of the configuration; admins replace a setting through
+//! `/api/flags`, and reset it to the configured one. Settings admins
+//! changed are stored in the `feature_flags` table, shared with other
+//! instances that read them again every
+//! [`FlagsConfig::refresh_interval_secs`], unless `flags.persist` is off;
+//! [`MemoryFlagStore`] then keeps them until the server stops.
+
+use std::sync::{Arc, PoisonError, RwLock};
+use std::time::Duration;
+
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use squirrel_app::supervisor::{RestartPolicy, Supervisor};
+use squirrel_core::flags::{self, Flag, FlagSet, FlagSetting, FlagTarget};
+use sqlx::{Row, SqlitePool};
+use tokio::sync::Mutex;
+use tracing::warn;
+
+use crate::api::error::AppError;
+use crate::config::FlagsConfig;
+use crate::db::{timestamp, InstrumentedPool};
+
+/// A setting an admin gave a flag
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct StoredFlag {
+    /// Flag name
+    pub name: String,
+    /// Who the flag is on for
+    pub setting: FlagSetting,
+    /// Admin who changed it
+    pub updated_by: String,
+    /// When it was changed
+    pub updated_at: DateTime<Utc>,
+}
+
+/// Where the setting of a flag comes from
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum FlagSource {
+    /// No setting; the flag has its default
+    Default,
+    /// The configuration
+    Config,
+    /// An admin
+    Admin,
+}
+
+/// A flag and its setting, as listed by `/api/flags`
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct FlagState {
+    /// Flag name
+    pub name: String,
+    /// What it turns on
+    pub description: String,
+    /// Whether it is on without a setting
+    pub default_enabled: bool,
+    /// Where its setting comes from
+    pub source: FlagSource,
+    /// Who it is on for; not set if it has its default
+    pub setting: Option<FlagSetting>,
+    /// Admin who changed it last
+    pub updated_by: Option<String>,
+    /// When an admin changed it last
+    pub updated_at: Option<DateTime<Utc>>,
+}
+
+/// Storage for the flag settings admins changed
+#[async_trait]
+pub trait FlagStore: Send + Sync {
+    /// Every stored setting, by flag name
+    async fn list(&self) -> Result<Vec<StoredFlag>, AppError>;
+
+    /// Store a setting, replacing the flag's earlier one
+    async fn set(&self, flag: &StoredFlag) -> Result<(), AppError>;
+
+    /// Remove the setting of a flag; returns false if it had none
+    async fn remove(&self, name: &str) -> Result<bool, AppError>;
+}
+
+/// Flag store backed by the `feature_flags` table
+pub struct SqlFlagStore {
+    pool: InstrumentedPool,
+}
+
+impl SqlFlagStore {
+    /// Create a new SqlFlagStore
+    pub fn new(pool: SqlitePool) -> Self {
+        Self { pool: pool.into() }
+    }
+}
+
+#[async_trait]
+impl FlagStore for SqlFlagStore {
+    async fn list(&self) -> Result<Vec<StoredFlag>, AppError> {
+        sqlx::query("SELECT name, setting, updated_by, updated_at FROM feature_flags ORDER BY name")
+            .fetch_all(&self.pool)
+            .await?
+            .iter()
+            .map(|row| -> Result<StoredFlag, AppError> {
+                let setting: String = row.try_get("setting")?;
+                Ok(StoredFlag {
+                    name: row.try_get("name")?,
+                    setting: serde_json::from_str(&setting)
+                        .map_err(|e| AppError::Internal(format!("Invalid flag setting: {}", e)))?,
+                    updated_by: row.try_get("updated_by")?,
+                    updated_at: timestamp(row.try_get("updated_at")?)?,
+                })
+            })
+            .collect()
+    }
+
+    async fn set(&self, flag: &StoredFlag) -> Result<(), AppError> {
+        let setting = serde_json::to_string(&flag.setting)
+            .map_err(|e| AppError::Internal(format!("Failed to store flag setting: {}", e)))?;
+        sqlx::query(
+            "INSERT INTO feature_flags (name, setting, updated_by, updated_at) VALUES (?, ?, ?, ?)
+             ON CONFLICT(name) DO UPDATE SET setting = excluded.setting, updated_by = excluded.updated_by,
+             updated_at = excluded.updated_at",
+        )
+        .bind(&flag.name)
+        .bind(setting)
+        .bind(&flag.updated_by)
+        .bind(flag.updated_at.timestamp_millis())
+        .execute(&self.pool)
+        .await?;
+        Ok(())
+    }
+
+    async fn remove(&self, name: &str) -> Result<bool, AppError> {
+        let deleted = sqlx::query("DELETE FROM feature_flags WHERE name = ?")
+            .bind(name)
+            .execute(&self.pool)
+            .await?
+            .rows_affected();
+        Ok(deleted > 0)
+    }
+}
+
+/// In-process flag store, for the mock database mode and when
+/// `flags.persist` is off; nothing survives a restart
+#[derive(Default)]
+pub struct MemoryFlagStore {
+    flags: Mutex<Vec<StoredFlag>>,
+}
+
+impl MemoryFlagStore {
+    /// Create a new MemoryFlagStore
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+#[async_trait]
+impl FlagStore for MemoryFlagStore {
+    async fn list(&self) -> Result<Vec<StoredFlag>, AppError> {
+        Ok(self.flags.lock().await.clone())
+    }
+
+    async fn set(&self, flag: &StoredFlag) -> Result<(), AppError> {
+        let mut flags = self.flags.lock().await;
+        flags.retain(|stored| stored.name != flag.name);
+        flags.push(flag.clone());
+        flags.sort_by(|a, b| a.name.cmp(&b.name));
+        Ok(())
+    }
+
+    async fn remove(&self, name: &str) -> Result<bool, AppError> {
+        let mut flags = self.flags.lock().await;
+        let before = flags.len();
+        flags.retain(|stored| stored.name != name);
+        Ok(flags.len() < before)
+    }
+}
+
+/// Configured flag settings merged with the ones admins changed
+pub struct FeatureFlags {
+    store: Arc<dyn FlagStore>,
+    config: FlagsConfig,
+    overrides: RwLock<Vec<StoredFlag>>,
+    merged: RwLock<Arc<FlagSet>>,
+}
+
+impl FeatureFlags {
+    /// Create new FeatureFlags, installing the configured settings until
+    /// the first refresh reads the ones admins changed
+    pub fn new(store: Arc<dyn FlagStore>, config: FlagsConfig) -> Self {
+        let flags = Self {
+            store,
+            config,
+            overrides: RwLock::new(Vec::new()),
+            merged: RwLock::new(Arc::new(FlagSet::new())),
+        };
+        flags.install();
+        flags
+    }
+
+    /// Read the settings admins changed again, and install them
+    pub async fn refresh(&self) -> Result<(), AppError> {
+        let overrides = self.store.list().await?;
+        *self.overrides.write().unwrap_or_else(PoisonError::into_inner) = overrides;
+        self.install();
+        Ok(())
+    }
+
+    /// Install the configured settings, replaced by the ones admins changed
+    fn install(&self) {
+        let overrides: FlagSet = self
+            .overrides
+            .read()
+            .unwrap_or_else(PoisonError::into_inner)
+            .iter()
+            .map(|stored| (stored.name.clone(), stored.setting.clone()))
+            .collect();
+        let merged = self.config.settings.merged(&overrides);
+        *self.merged.write().unwrap_or_else(PoisonError::into_inner) = Arc::new(merged.clone());
+        flags::install(merged);
+    }
+
+    /// Whether `flag` is on for `target`
+    pub fn is_enabled(&self, flag: &Flag, target: &FlagTarget) -> bool {
+        self.merged.read().unwrap_or_else(PoisonError::into_inner).is_enabled(flag, target)
+    }
+
+    /// Every known flag and its setting
+    pub fn list(&self) -> Vec<FlagState> {
+        flags::KNOWN.iter().map(|flag| self.state(flag)).collect()
+    }
+
+    /// The known flag named `name` and its setting
+    pub fn get(&self, name: &str) -> Result<FlagState, AppError> {
+        Ok(self.state(&known(name)?))
+    }
+
+    /// Replace the setting of a flag
+    pub async fn set(&self, actor: &str, name: &str, setting: FlagSetting) -> Result<FlagState, AppError> {
+        let flag = known(name)?;
+        let stored = StoredFlag {
+            name: flag.name().to_string(),
+            setting,
+            updated_by: actor.to_string(),
+            updated_at: Utc::now(),
+        };
+        self.store.set(&stored).await?;
+        self.refresh().await?;
+        Ok(self.state(&flag))
+    }
+
+    /// Reset a flag to its configured setting; returns false if no admin
+    /// had changed it
+    pub async fn reset(&self, name: &str) -> Result<bool, AppError> {
+        let flag = known(name)?;
+        let removed = self.store.remove(flag.name()).await?;
+        self.refresh().await?;
+        Ok(removed)
+    }
+
+    /// Names of the flags whose admin setting names `user`
+    pub fn overrides_naming(&self, user: &str) -> Vec<String> {
+        self.overrides
+            .read()
+            .unwrap_or_else(PoisonError::into_inner)
+            .iter()
+            .filter(|stored| stored.setting.users.contains(user))
+            .map(|stored| stored.name.clone())
+            .collect()
+    }
+
+    /// Names of the flags whose configured setting names `user`, which only
+    /// a change of the configuration removes
+    pub fn configured_naming(&self, user: &str) -> Vec<String> {
+        self.config
+            .settings
+            .settings()
+            .filter(|(_, setting)| setting.users.contains(user))
+            .map(|(name, _)| name.to_string())
+            .collect()
+    }
+
+    /// Take `user` out of the admin settings naming them, returning how many
+    /// settings changed
+    pub async fn forget_user(&self, actor: &str, user: &str) -> Result<usize, AppError> {
+        let naming: Vec<StoredFlag> = self
+            .overrides
+            .read()
+            .unwrap_or_else(PoisonError::into_inner)
+            .iter()
+            .filter(|stored| stored.setting.users.contains(user))
+            .cloned()
+            .collect();
+        let changed = naming.len();
+        for mut stored in naming {
+            stored.setting.users.remove(user);
+            stored.updated_by = actor.to_string();
+            stored.updated_at = Utc::now();
+            self.store.set(&stored).await?;
+        }
+        self.refresh().await?;
+        Ok(changed)
+    }
+
+    /// The state of `flag`
+    fn state(&self, flag: &Flag) -> FlagState {
+        let overrides = self.overrides.read().unwrap_or_else(PoisonError::into_inner);
+        let stored = overrides.iter().find(|stored| stored.name == flag.name());
+        let (source, setting) = match (stored, self.config.settings.get(flag.name())) {
+            (Some(stored), _) => (FlagSource::Admin, Some(stored.setting.clone())),
+            (None, Some(setting)) => (FlagSource::Config, Some(setting.clone())),
+            (None, None) => (FlagSource::Default, None),
+        };
+        FlagState {
+            name: flag.name().to_string(),
+            description: flag.description().to_string(),
+            default_enabled: flag.default_enabled(),
+            source,
+            setting,
+            updated_by: stored.map(|stored| stored.updated_by.clone()),
+            updated_at: stored.map(|stored| stored.updated_at),
+        }
+    }
+
+    /// Supervise the background task reading settings changed by other
+    /// instances
+    pub fn supervise_refresher(self: &Arc<Self>, supervisor: &Supervisor) {
+        let flags = self.clone();
+        supervisor.spawn("flags.refresh", RestartPolicy::default(), move |shutdown| {
+            let flags = flags.clone();
+            async move {
+                let period = Duration::from_secs(flags.config.refresh_interval_secs.max(1));
+                while !shutdown.sleep(period).await {
+                    if let Err(e) = flags.refresh().await {
+                        warn!("Failed to refresh feature flags: {}", e);
+                    }
+                }
+                Ok(())
+            }
+        });
+    }
+}
+
+/// The known flag named `name`
+fn known(name: &str) -> Result<Flag, AppError> {
+    flags::known(name).ok_or_else(|| AppError::NotFound(format!("No feature flag {}", name)))
+}
+
+/// Refuse a request of `user` in `workspace` if `flag` is off for them
+///
+/// Reads the settings of `flags`, or the installed ones without it.
+pub fn require(flags: Option<&FeatureFlags>, flag: Flag, user: &str, workspace: &str) -> Result<(), AppError> {
+    let target = FlagTarget::user(user).in_workspace(workspace);
+    let enabled = match flags {
+        Some(flags) => flags.is_enabled(&flag, &target),
+        None => flag.is_enabled(&target),
+    };
+    if enabled {
+        Ok(())
+    } else {
+        Err(AppError::Forbidden(format!(
+            "This feature is turned off by the '{}' flag",
+            flag.name()
+        )))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_helpers::migrated_pool;
+
+    #[tokio::test]
+    async fn test_admin_settings_replace_configured_ones() {
+        let pool = migrated_pool().await;
+        let store = Arc::new(SqlFlagStore::new(pool));
+        let mut config = FlagsConfig::default();
+        config.settings.set("wasm_plugins", FlagSetting::on());
+        let feature_flags = FeatureFlags::new(store.clone(), config);
+        feature_flags.refresh().await.unwrap();
+
+        let wasm = feature_flags.get("wasm_plugins").unwrap();
+        assert_eq!((wasm.source, wasm.default_enabled), (FlagSource::Config, false));
+        assert_eq!(feature_flags.get("ai_assistant").unwrap().source, FlagSource::Default);
+        assert!(matches!(feature_flags.get("nope"), Err(AppError::NotFound(_))));
+
+        let setting = FlagSetting {
+            workspaces: ["lab".to_string()].into(),
+            ..FlagSetting::off()
+        };
+        let wasm = feature_flags.set("root", "wasm_plugins", setting.clone()).await.unwrap();
+        assert_eq!((wasm.source, wasm.updated_by.as_deref()), (FlagSource::Admin, Some("root")));
+        assert_eq!(store.list().await.unwrap()[0].setting, setting);
+        assert!(require(Some(&feature_flags), flags::WASM_PLUGINS, "alice", "lab").is_ok());
+        assert!(matches!(
+            require(Some(&feature_flags), flags::WASM_PLUGINS, "alice", "default"),
+            Err(AppError::Forbidden(_))
+        ));
+
+        assert!(feature_flags.reset("wasm_plugins").await.unwrap());
+        assert!(!feature_flags.reset("wasm_plugins").await.unwrap());
+        assert_eq!(feature_flags.get("wasm_plugins").unwrap().source, FlagSource::Config);
+        assert!(require(Some(&feature_flags), flags::WASM_PLUGINS, "alice", "default").is_ok());
+    }
+}
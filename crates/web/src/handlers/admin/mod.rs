@@ -0,0 +1,11 @@
+//! Admin module for handling runtime administration API endpoints
+//!
+//! This module contains the admin-only handlers that list and end sessions,
+//! turn plugins and degraded mode on and off, collect garbage, rotate the
+//! signing keys and show the runtime configuration. Every call is written
+//! to the admin audit log.
+
+mod routes;
+
+pub use routes::admin_routes;
+pub(crate) use routes::audited;
@@ -0,0 +1,701 @@
+use axum::{
+    Router,
+    routing::{delete, get, post},
+    extract::{Extension, Path, Query, State},
+    Json,
+};
+use squirrel_app::schedule::{ScheduleError, TaskRun, TaskStatus};
+use squirrel_commands::policy::CommandPolicy;
+use std::future::Future;
+use std::sync::Arc;
+use uuid::Uuid;
+use crate::state::AppState;
+use crate::admin::{self, AdminFlags, AdminPolicy, AuditEntry, DisabledPlugin};
+use crate::db::{self, SlowQuery};
+use crate::maintenance::MaintenanceReport;
+use crate::integrity::VerificationReport;
+use crate::locks::{LockStatus, DEFAULT_LOCK_TTL, GC_LOCK};
+use crate::auth::{AuthError, extractor::AuthClaims, sessions::AuthSession, signing_keys::SigningKeyInfo};
+use crate::handlers::rollout::rollout_routes;
+use crate::handlers::usage::is_admin;
+use crate::api::{
+    api_success,
+    admin::{
+        AddPolicyRequest, AuditQuery, DegradedModeRequest, DisablePluginRequest, GcRequest, GcResponse, PoliciesQuery,
+        IntegrityRequest, IntegrityStatus, MaintenanceRequest, MaintenanceStatus, RuntimeResponse, SessionsQuery, SlowQueriesQuery, DEFAULT_AUDIT_LIMIT,
+        DEFAULT_SLOW_QUERY_LIMIT,
+    },
+    error::AppError,
+    ApiResponse,
+};
+
+/// Admin routes, including the configuration rollout under `/config`
+pub fn admin_routes() -> Router<Arc<AppState>> {
+    Router::new()
+        .route("/sessions", get(list_sessions))
+        .route("/sessions/:id", delete(kick_session))
+        .route("/plugins", get(list_disabled_plugins))
+        .route("/plugins/:name/disable", post(disable_plugin))
+        .route("/plugins/:name/enable", post(enable_plugin))
+        .route("/policies", get(list_policies).post(add_policy))
+        .route("/policies/:id", delete(remove_policy))
+        .route("/degraded", get(get_degraded).put(set_degraded))
+        .route("/gc", post(collect_garbage))
+        .route("/maintenance", get(get_maintenance).post(run_maintenance))
+        .route("/integrity", get(get_integrity).post(run_integrity_check))
+        .route("/schedules", get(list_schedules))
+        .route("/schedules/:name/pause", post(pause_schedule))
+        .route("/schedules/:name/resume", post(resume_schedule))
+        .route("/schedules/:name/run", post(run_schedule))
+        .route("/locks", get(list_locks))
+        .route("/keys", get(list_signing_keys))
+        .route("/keys/rotate", post(rotate_signing_key))
+        .route("/runtime", get(get_runtime))
+        .route("/audit", get(get_audit_log))
+        .route("/slow-queries", get(get_slow_queries))
+        .nest("/config", rollout_routes())
+}
+
+/// Run an admin action for `user`, writing it to the audit log whether it
+/// was refused, succeeded or failed
+pub(crate) async fn audited<T, Fut>(
+    state: &AppState,
+    user: &AuthClaims,
+    action: &str,
+    target: Option<&str>,
+    run: Fut,
+) -> Result<T, AppError>
+where
+    Fut: Future<Output = Result<T, AppError>>,
+{
+    state
+        .get_admin()?
+        .audited(&user.sub, is_admin(user), action, target, run)
+        .await
+}
+
+/// Map an authentication error to the API error it is reported as
+fn auth_error(error: AuthError) -> AppError {
+    AppError::Internal(error.to_string())
+}
+
+/// Map a scheduler error to the API error it is reported as
+fn schedule_error(error: ScheduleError) -> AppError {
+    match error {
+        ScheduleError::NotFound(_) => AppError::NotFound(error.to_string()),
+        ScheduleError::InvalidName(_) => AppError::InvalidRequest(error.to_string()),
+        ScheduleError::Taken { .. } | ScheduleError::Running(_) | ScheduleError::Locked { .. } => {
+            AppError::Conflict(error.to_string())
+        }
+    }
+}
+
+/// List the active sessions of every user, or of one
+async fn list_sessions(
+    State(state): State<Arc<AppState>>,
+    Extension(user): Extension<AuthClaims>,
+    Query(query): Query<SessionsQuery>,
+) -> Result<Json<ApiResponse<Vec<AuthSession>>>, AppError> {
+    let target = query.user_id.map(|id| id.to_string());
+    let sessions = audited(&state, &user, "sessions.list", target.as_deref(), async {
+        state.auth.list_all_sessions(query.user_id).await.map_err(auth_error)
+    })
+    .await?;
+
+    Ok(api_success(sessions))
+}
+
+/// End a session of any user
+///
+/// Access tokens already issued to the session stay valid until they expire.
+async fn kick_session(
+    State(state): State<Arc<AppState>>,
+    Extension(user): Extension<AuthClaims>,
+    Path(id): Path<Uuid>,
+) -> Result<Json<ApiResponse<()>>, AppError> {
+    let target = id.to_string();
+    audited(&state, &user, "sessions.kick", Some(&target), async {
+        if state.auth.kick_session(id).await.map_err(auth_error)? {
+            Ok(())
+        } else {
+            Err(AppError::NotFound(format!("No active session {}", id)))
+        }
+    })
+    .await?;
+
+    Ok(api_success(()))
+}
+
+/// List the plugins turned off
+async fn list_disabled_plugins(
+    State(state): State<Arc<AppState>>,
+    Extension(user): Extension<AuthClaims>,
+) -> Result<Json<ApiResponse<Vec<DisabledPlugin>>>, AppError> {
+    let plugins = audited(&state, &user, "plugins.list", None, async {
+        Ok(state.get_admin()?.flags().disabled_plugins)
+    })
+    .await?;
+
+    Ok(api_success(plugins))
+}
+
+/// Turn a plugin off, refusing the commands it provides
+async fn disable_plugin(
+    State(state): State<Arc<AppState>>,
+    Extension(user): Extension<AuthClaims>,
+    Path(name): Path<String>,
+    request: Option<Json<DisablePluginRequest>>,
+) -> Result<Json<ApiResponse<DisabledPlugin>>, AppError> {
+    let Json(request) = request.unwrap_or_default();
+    let plugin = audited(&state, &user, "plugins.disable", Some(&name), async {
+        state.get_admin()?.disable_plugin(&user.sub, &name, request.reason).await
+    })
+    .await?;
+
+    Ok(api_success(plugin))
+}
+
+/// Turn a plugin back on
+async fn enable_plugin(
+    State(state): State<Arc<AppState>>,
+    Extension(user): Extension<AuthClaims>,
+    Path(name): Path<String>,
+) -> Result<Json<ApiResponse<()>>, AppError> {
+    audited(&state, &user, "plugins.enable", Some(&name), async {
+        if state.get_admin()?.enable_plugin(&name).await? {
+            Ok(())
+        } else {
+            Err(AppError::NotFound(format!("Plugin {} is not disabled", name)))
+        }
+    })
+    .await?;
+
+    Ok(api_success(()))
+}
+
+/// List the command policies, of every subject or of one
+async fn list_policies(
+    State(state): State<Arc<AppState>>,
+    Extension(user): Extension<AuthClaims>,
+    Query(query): Query<PoliciesQuery>,
+) -> Result<Json<ApiResponse<Vec<AdminPolicy>>>, AppError> {
+    let target = query.subject.as_ref().map(ToString::to_string);
+    let policies = audited(&state, &user, "policies.list", target.as_deref(), async {
+        let mut policies = state.get_admin()?.flags().policies;
+        if let Some(subject) = &query.subject {
+            policies.retain(|policy| &policy.policy.subject == subject);
+        }
+        Ok(policies)
+    })
+    .await?;
+
+    Ok(api_success(policies))
+}
+
+/// Attach a command policy to a user
+async fn add_policy(
+    State(state): State<Arc<AppState>>,
+    Extension(user): Extension<AuthClaims>,
+    Json(request): Json<AddPolicyRequest>,
+) -> Result<Json<ApiResponse<AdminPolicy>>, AppError> {
+    let target = request.subject.to_string();
+    let policy = audited(&state, &user, "policies.add", Some(&target), async {
+        if request.pattern.trim().is_empty() {
+            return Err(AppError::InvalidRequest("Policy pattern is empty".to_string()));
+        }
+        let policy = CommandPolicy {
+            id: String::new(),
+            subject: request.subject,
+            effect: request.effect,
+            scope: request.scope,
+            pattern: request.pattern,
+            description: request.description,
+        };
+        state.get_admin()?.add_policy(&user.sub, policy).await
+    })
+    .await?;
+
+    Ok(api_success(policy))
+}
+
+/// Remove a command policy
+async fn remove_policy(
+    State(state): State<Arc<AppState>>,
+    Extension(user): Extension<AuthClaims>,
+    Path(id): Path<String>,
+) -> Result<Json<ApiResponse<()>>, AppError> {
+    audited(&state, &user, "policies.remove", Some(&id), async {
+        if state.get_admin()?.remove_policy(&id).await? {
+            Ok(())
+        } else {
+            Err(AppError::NotFound(format!("No command policy {}", id)))
+        }
+    })
+    .await?;
+
+    Ok(api_success(()))
+}
+
+/// Get the degraded mode and disabled plugins
+async fn get_degraded(
+    State(state): State<Arc<AppState>>,
+    Extension(user): Extension<AuthClaims>,
+) -> Result<Json<ApiResponse<AdminFlags>>, AppError> {
+    let flags = audited(&state, &user, "degraded.get", None, async { Ok(state.get_admin()?.flags()) }).await?;
+
+    Ok(api_success(flags))
+}
+
+/// Turn degraded mode on or off
+async fn set_degraded(
+    State(state): State<Arc<AppState>>,
+    Extension(user): Extension<AuthClaims>,
+    Json(request): Json<DegradedModeRequest>,
+) -> Result<Json<ApiResponse<AdminFlags>>, AppError> {
+    let action = if request.enabled { "degraded.enable" } else { "degraded.disable" };
+    let flags = audited(&state, &user, action, None, async {
+        state
+            .get_admin()?
+            .set_degraded(&user.sub, request.enabled, request.reason)
+            .await
+    })
+    .await?;
+
+    Ok(api_success(flags))
+}
+
+/// Delete expired rows from the web database and unreferenced blobs
+async fn collect_garbage(
+    State(state): State<Arc<AppState>>,
+    Extension(user): Extension<AuthClaims>,
+    request: Option<Json<GcRequest>>,
+) -> Result<Json<ApiResponse<GcResponse>>, AppError> {
+    let Json(request) = request.unwrap_or_default();
+    let action = if request.dry_run { "gc.dry_run" } else { "gc.run" };
+    let response = audited(&state, &user, action, None, async {
+        let controls = state.get_admin()?;
+        let collect = async {
+            let database = if request.dry_run {
+                None
+            } else {
+                Some(admin::compact_database(&state.db, &state.auth).await?)
+            };
+            let blobs = match &state.file_service {
+                Some(files) => files.collect_garbage(controls.gc_min_age(), request.dry_run).await?,
+                None => None,
+            };
+            Ok::<_, AppError>(GcResponse { database, blobs })
+        };
+        // Only one instance collects at a time; dry runs delete nothing
+        match &state.locks {
+            Some(locks) if !request.dry_run => locks.with_lock(GC_LOCK, DEFAULT_LOCK_TTL, collect).await,
+            _ => collect.await,
+        }
+    })
+    .await?;
+
+    Ok(api_success(response))
+}
+
+/// List the access token signing keys, without their secrets
+async fn list_signing_keys(
+    State(state): State<Arc<AppState>>,
+    Extension(user): Extension<AuthClaims>,
+) -> Result<Json<ApiResponse<Vec<SigningKeyInfo>>>, AppError> {
+    let keys = audited(&state, &user, "keys.list", None, async { Ok(state.auth.signing_keys()) }).await?;
+
+    Ok(api_success(keys))
+}
+
+/// Sign new access tokens with a new key
+///
+/// Tokens signed with the previous key stay valid until they expire.
+async fn rotate_signing_key(
+    State(state): State<Arc<AppState>>,
+    Extension(user): Extension<AuthClaims>,
+) -> Result<Json<ApiResponse<SigningKeyInfo>>, AppError> {
+    let key = audited(&state, &user, "keys.rotate", None, async {
+        state.auth.rotate_signing_key().await.map_err(auth_error)
+    })
+    .await?;
+
+    Ok(api_success(key))
+}
+
+/// Get the active configuration, the admin flags and the leadership
+async fn get_runtime(
+    State(state): State<Arc<AppState>>,
+    Extension(user): Extension<AuthClaims>,
+) -> Result<Json<ApiResponse<RuntimeResponse>>, AppError> {
+    let runtime = audited(&state, &user, "runtime.get", None, async {
+        let config = match &state.config_rollout {
+            Some(rollout) => rollout.active().as_ref().clone(),
+            None => state.config.clone(),
+        };
+        Ok(RuntimeResponse {
+            config,
+            flags: state.get_admin()?.flags(),
+            signing_keys: state.auth.signing_keys(),
+            leader: state.leader.as_ref().is_none_or(|leader| leader.is_leader()),
+            caches: state.read_caches.as_ref().map(|caches| caches.metrics.stats()).unwrap_or_default(),
+            replicas: state.replicas.as_ref().map(|replicas| replicas.replicas()).unwrap_or_default(),
+        })
+    })
+    .await?;
+
+    Ok(api_success(runtime))
+}
+
+/// Get the latest admin actions, newest first
+async fn get_audit_log(
+    State(state): State<Arc<AppState>>,
+    Extension(user): Extension<AuthClaims>,
+    Query(query): Query<AuditQuery>,
+) -> Result<Json<ApiResponse<Vec<AuditEntry>>>, AppError> {
+    let limit = query.limit.unwrap_or(DEFAULT_AUDIT_LIMIT);
+    let entries = audited(&state, &user, "audit.list", None, async {
+        state.get_admin()?.audit_log(limit).await
+    })
+    .await?;
+
+    Ok(api_success(entries))
+}
+
+/// Get the maintenance schedule and the latest run
+async fn get_maintenance(
+    State(state): State<Arc<AppState>>,
+    Extension(user): Extension<AuthClaims>,
+) -> Result<Json<ApiResponse<MaintenanceStatus>>, AppError> {
+    let status = audited(&state, &user, "maintenance.get", None, async {
+        let maintenance = state.get_maintenance()?;
+        Ok(MaintenanceStatus {
+            schedule: maintenance.schedule().to_string(),
+            next_run: maintenance.next_run(),
+            last_run: maintenance.last_report(),
+        })
+    })
+    .await?;
+
+    Ok(api_success(status))
+}
+
+/// Run database maintenance now, or with `dry_run` report what it would delete
+async fn run_maintenance(
+    State(state): State<Arc<AppState>>,
+    Extension(user): Extension<AuthClaims>,
+    request: Option<Json<MaintenanceRequest>>,
+) -> Result<Json<ApiResponse<MaintenanceReport>>, AppError> {
+    let Json(request) = request.unwrap_or_default();
+    let action = if request.dry_run { "maintenance.dry_run" } else { "maintenance.run" };
+    let report = audited(&state, &user, action, None, async {
+        state.get_maintenance()?.run(request.dry_run).await
+    })
+    .await?;
+
+    Ok(api_success(report))
+}
+
+/// Get the blob integrity check schedule and the latest check
+async fn get_integrity(
+    State(state): State<Arc<AppState>>,
+    Extension(user): Extension<AuthClaims>,
+) -> Result<Json<ApiResponse<IntegrityStatus>>, AppError> {
+    let status = audited(&state, &user, "integrity.get", None, async {
+        let integrity = state.get_integrity()?;
+        Ok(IntegrityStatus {
+            schedule: integrity.schedule().to_string(),
+            next_run: integrity.next_run(),
+            last_run: integrity.last_report(),
+        })
+    })
+    .await?;
+
+    Ok(api_success(status))
+}
+
+/// Verify the checksums of stored blobs now, restoring damaged blobs from
+/// the backup
+async fn run_integrity_check(
+    State(state): State<Arc<AppState>>,
+    Extension(user): Extension<AuthClaims>,
+    request: Option<Json<IntegrityRequest>>,
+) -> Result<Json<ApiResponse<VerificationReport>>, AppError> {
+    let Json(request) = request.unwrap_or_default();
+    let report = audited(&state, &user, "integrity.run", None, async {
+        state.get_integrity()?.run(request.sample).await
+    })
+    .await?;
+
+    Ok(api_success(report))
+}
+
+/// List the periodic tasks of the host and plugins
+async fn list_schedules(
+    State(state): State<Arc<AppState>>,
+    Extension(user): Extension<AuthClaims>,
+) -> Result<Json<ApiResponse<Vec<TaskStatus>>>, AppError> {
+    let tasks = audited(&state, &user, "schedules.list", None, async {
+        Ok(state.get_scheduler()?.tasks())
+    })
+    .await?;
+
+    Ok(api_success(tasks))
+}
+
+/// Skip the scheduled runs of a task until it is resumed
+async fn pause_schedule(
+    State(state): State<Arc<AppState>>,
+    Extension(user): Extension<AuthClaims>,
+    Path(name): Path<String>,
+) -> Result<Json<ApiResponse<TaskStatus>>, AppError> {
+    let task = audited(&state, &user, "schedules.pause", Some(&name), async {
+        state.get_scheduler()?.pause(&name).map_err(schedule_error)
+    })
+    .await?;
+
+    Ok(api_success(task))
+}
+
+/// Run a paused task on its schedule again
+async fn resume_schedule(
+    State(state): State<Arc<AppState>>,
+    Extension(user): Extension<AuthClaims>,
+    Path(name): Path<String>,
+) -> Result<Json<ApiResponse<TaskStatus>>, AppError> {
+    let task = audited(&state, &user, "schedules.resume", Some(&name), async {
+        state.get_scheduler()?.resume(&name).map_err(schedule_error)
+    })
+    .await?;
+
+    Ok(api_success(task))
+}
+
+/// Run a task now, on this instance, and report how the run went
+///
+/// A run that fails is reported like a successful one, with its outcome.
+async fn run_schedule(
+    State(state): State<Arc<AppState>>,
+    Extension(user): Extension<AuthClaims>,
+    Path(name): Path<String>,
+) -> Result<Json<ApiResponse<TaskRun>>, AppError> {
+    let run = audited(&state, &user, "schedules.run", Some(&name), async {
+        state.get_scheduler()?.run_now(&name).await.map_err(schedule_error)
+    })
+    .await?;
+
+    Ok(api_success(run))
+}
+
+/// List the distributed locks and whether they are held
+async fn list_locks(
+    State(state): State<Arc<AppState>>,
+    Extension(user): Extension<AuthClaims>,
+) -> Result<Json<ApiResponse<Vec<LockStatus>>>, AppError> {
+    let locks = audited(&state, &user, "locks.list", None, async {
+        state.get_locks()?.locks().await
+    })
+    .await?;
+
+    Ok(api_success(locks))
+}
+
+/// Get the latest statements slower than the slow query threshold, newest first
+async fn get_slow_queries(
+    State(state): State<Arc<AppState>>,
+    Extension(user): Extension<AuthClaims>,
+    Query(query): Query<SlowQueriesQuery>,
+) -> Result<Json<ApiResponse<Vec<SlowQuery>>>, AppError> {
+    let limit = query.limit.unwrap_or(DEFAULT_SLOW_QUERY_LIMIT);
+    let queries = audited(&state, &user, "queries.slow", None, async {
+        Ok(db::query_log().map(|log| log.slow_queries(limit)).unwrap_or_default())
+    })
+    .await?;
+
+    Ok(api_success(queries))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::admin::{AdminControls, AuditOutcome, MemoryAdminStore};
+    use crate::config::AdminConfig;
+    use squirrel_app::schedule::{RunOutcome, TaskOptions, TaskScheduler, HOST_OWNER};
+    use squirrel_app::supervisor::Supervisor;
+    use crate::locks::{LockService, MemoryLockStore};
+    use crate::test_helpers::claims;
+
+    #[tokio::test]
+    async fn test_admin_actions_require_the_role_and_are_audited() {
+        let admin = Arc::new(AdminControls::new(Arc::new(MemoryAdminStore::new()), AdminConfig::default()));
+        let state = Arc::new(AppState {
+            admin: Some(admin.clone()),
+            ..AppState::default()
+        });
+
+        let refused = set_degraded(
+            State(state.clone()),
+            Extension(claims("mallory", &["User"])),
+            Json(DegradedModeRequest { enabled: true, reason: None }),
+        )
+        .await;
+        assert!(matches!(refused, Err(AppError::Forbidden(_))));
+        assert!(admin.degraded().is_none());
+
+        let Json(enabled) = set_degraded(
+            State(state.clone()),
+            Extension(claims("root", &["Admin"])),
+            Json(DegradedModeRequest { enabled: true, reason: Some("failover".to_string()) }),
+        )
+        .await
+        .unwrap();
+        let degraded = enabled.data.unwrap().degraded.unwrap();
+        assert_eq!(degraded.reason.as_deref(), Some("failover"));
+        assert_eq!(admin.degraded().unwrap().enabled_by, "root");
+
+        let missing = enable_plugin(
+            State(state.clone()),
+            Extension(claims("root", &["Admin"])),
+            Path("galaxy".to_string()),
+        )
+        .await;
+        assert!(matches!(missing, Err(AppError::NotFound(_))));
+
+        let log = admin.audit_log(10).await.unwrap();
+        assert_eq!(
+            log.iter()
+                .map(|entry| (entry.actor.as_str(), entry.action.as_str(), entry.outcome))
+                .collect::<Vec<_>>(),
+            vec![
+                ("root", "plugins.enable", AuditOutcome::Failed),
+                ("root", "degraded.enable", AuditOutcome::Succeeded),
+                ("mallory", "degraded.enable", AuditOutcome::Denied),
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_policies_deny_commands_to_their_subject() {
+        let admin = Arc::new(AdminControls::new(Arc::new(MemoryAdminStore::new()), AdminConfig::default()));
+        let state = Arc::new(AppState {
+            admin: Some(admin.clone()),
+            ..AppState::default()
+        });
+        let request = |subject: &str, effect: &str, pattern: &str| {
+            Json(serde_json::from_value::<AddPolicyRequest>(serde_json::json!({
+                "subject": subject,
+                "effect": effect,
+                "pattern": pattern,
+            })).unwrap())
+        };
+
+        let refused = add_policy(
+            State(state.clone()),
+            Extension(claims("mallory", &["User"])),
+            request("user:mallory", "allow", "*"),
+        )
+        .await;
+        assert!(matches!(refused, Err(AppError::Forbidden(_))));
+
+        // No request authenticates with an API key yet, so such a policy would never apply
+        let refused = add_policy(
+            State(state.clone()),
+            Extension(claims("root", &["Admin"])),
+            request("api_key:ci", "deny", "deploy"),
+        )
+        .await;
+        assert!(matches!(refused, Err(AppError::InvalidRequest(_))));
+
+        let ci = claims("ci", &["User"]);
+        assert!(state.check_command_policy(&ci, "deploy --target prod").is_ok());
+        let Json(added) = add_policy(
+            State(state.clone()),
+            Extension(claims("root", &["Admin"])),
+            request("user:ci", "deny", "deploy"),
+        )
+        .await
+        .unwrap();
+        let policy = added.data.unwrap();
+        assert_eq!(policy.created_by, "root");
+
+        assert!(matches!(state.check_command_policy(&ci, "deploy --target prod"), Err(AppError::Forbidden(_))));
+        assert!(state.check_command_policy(&claims("ada", &["User"]), "deploy").is_ok());
+        assert!(state.check_command_policy(&ci, "status").is_ok());
+
+        let Json(removed) =
+            remove_policy(State(state.clone()), Extension(claims("root", &["Admin"])), Path(policy.policy.id.clone()))
+                .await
+                .unwrap();
+        assert!(removed.success);
+        assert!(state.check_command_policy(&ci, "deploy").is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_schedules_are_paused_and_run_by_admins() {
+        let admin = Arc::new(AdminControls::new(Arc::new(MemoryAdminStore::new()), AdminConfig::default()));
+        let scheduler = Arc::new(TaskScheduler::new(Arc::new(Supervisor::new())));
+        scheduler
+            .register(HOST_OWNER, "db.maintenance", TaskOptions::new(std::time::Duration::from_secs(3600)), || async {
+                Ok(())
+            })
+            .unwrap();
+        let state = Arc::new(AppState {
+            admin: Some(admin.clone()),
+            scheduler: Some(scheduler.clone()),
+            ..AppState::default()
+        });
+        let name = || Path("db.maintenance".to_string());
+
+        let refused = run_schedule(State(state.clone()), Extension(claims("mallory", &["User"])), name()).await;
+        assert!(matches!(refused, Err(AppError::Forbidden(_))));
+
+        let Json(paused) = pause_schedule(State(state.clone()), Extension(claims("root", &["Admin"])), name())
+            .await
+            .unwrap();
+        assert!(paused.data.unwrap().paused);
+        let Json(run) = run_schedule(State(state.clone()), Extension(claims("root", &["Admin"])), name())
+            .await
+            .unwrap();
+        let run = run.data.unwrap();
+        assert!(run.manual && run.outcome == RunOutcome::Succeeded);
+
+        let Json(tasks) = list_schedules(State(state.clone()), Extension(claims("root", &["Admin"]))).await.unwrap();
+        let tasks = tasks.data.unwrap();
+        assert_eq!((tasks.len(), tasks[0].runs, tasks[0].paused), (1, 1, true));
+
+        let missing = resume_schedule(
+            State(state.clone()),
+            Extension(claims("root", &["Admin"])),
+            Path("reports.nightly".to_string()),
+        )
+        .await;
+        assert!(matches!(missing, Err(AppError::NotFound(_))));
+        let actions: Vec<String> = admin.audit_log(10).await.unwrap().into_iter().map(|entry| entry.action).collect();
+        assert_eq!(actions, vec!["schedules.resume", "schedules.list", "schedules.run", "schedules.pause", "schedules.run"]);
+    }
+
+    #[tokio::test]
+    async fn test_admins_see_which_locks_are_held() {
+        let admin = Arc::new(AdminControls::new(Arc::new(MemoryAdminStore::new()), AdminConfig::default()));
+        let locks = Arc::new(LockService::new(Arc::new(MemoryLockStore::new()), "a"));
+        let state = Arc::new(AppState {
+            admin: Some(admin),
+            locks: Some(locks.clone()),
+            ..AppState::default()
+        });
+        let held = locks.try_lock("db.maintenance", DEFAULT_LOCK_TTL).await.unwrap().unwrap();
+        let freed = locks.try_lock(GC_LOCK, DEFAULT_LOCK_TTL).await.unwrap().unwrap();
+        freed.release().await;
+
+        let refused = list_locks(State(state.clone()), Extension(claims("mallory", &["User"]))).await;
+        assert!(matches!(refused, Err(AppError::Forbidden(_))));
+        let Json(listed) = list_locks(State(state.clone()), Extension(claims("root", &["Admin"]))).await.unwrap();
+        let listed: Vec<(String, bool)> = listed
+            .data
+            .unwrap()
+            .into_iter()
+            .map(|status| (status.lock.name, status.held))
+            .collect();
+        assert_eq!(listed, vec![("db.maintenance".to_string(), true), ("gc".to_string(), false)]);
+        held.release().await;
+    }
+}
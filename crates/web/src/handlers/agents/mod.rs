@@ -0,0 +1,8 @@
+//! Agents module for handling remote agent API endpoints
+//!
+//! This module contains handlers for listing connected agents and running
+//! tasks on them.
+
+mod routes;
+
+pub use routes::agent_routes;
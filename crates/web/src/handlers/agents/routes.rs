@@ -0,0 +1,76 @@
+use axum::{
+    Router,
+    routing::{get, post},
+    extract::{Path, State},
+    http::HeaderMap,
+    Extension, Json,
+};
+use squirrel_commands::extensions::RequestId;
+use std::sync::Arc;
+use squirrel_core::flags;
+use crate::state::AppState;
+use crate::agents::{AgentTask, TaskRequest};
+use crate::auth::extractor::AuthClaims;
+use crate::handlers::usage::workspace;
+use crate::api::{
+    api_success,
+    agents::{AgentListResponse, SubmitAgentTaskRequest, SubmitAgentTaskResponse},
+    error::AppError,
+    ApiResponse,
+};
+
+/// Agent routes
+pub fn agent_routes() -> Router<Arc<AppState>> {
+    Router::new()
+        .route("/", get(list_agents))
+        .route("/tasks", post(submit_task))
+        .route("/tasks/:id", get(get_task))
+}
+
+/// List connected agents
+async fn list_agents(
+    State(state): State<Arc<AppState>>,
+) -> Result<Json<ApiResponse<AgentListResponse>>, AppError> {
+    let scheduler = state.get_agent_scheduler()?;
+    let response = AgentListResponse {
+        agents: scheduler.agents().await,
+    };
+
+    Ok(api_success(response))
+}
+
+/// Queue a task for a remote agent, on behalf of the caller and workspace,
+/// unless the `new_scheduler` flag is off for them
+async fn submit_task(
+    State(state): State<Arc<AppState>>,
+    Extension(user): Extension<AuthClaims>,
+    headers: HeaderMap,
+    Json(payload): Json<SubmitAgentTaskRequest>,
+) -> Result<Json<ApiResponse<SubmitAgentTaskResponse>>, AppError> {
+    let workspace = workspace(&headers);
+    state.check_flag(flags::NEW_SCHEDULER, &user.sub, &workspace)?;
+    let scheduler = state.get_agent_scheduler()?;
+    let mut request: TaskRequest = payload.into();
+    request.context.request_id = Some(RequestId::generate().to_string());
+    request.context.user = Some(user.sub);
+    request.context.workspace = Some(workspace);
+    let id = scheduler.submit(request).await?;
+
+    let response = SubmitAgentTaskResponse {
+        id: id.clone(),
+        status_url: format!("/api/agents/tasks/{}", id),
+    };
+
+    Ok(api_success(response))
+}
+
+/// Get agent task status
+async fn get_task(
+    State(state): State<Arc<AppState>>,
+    Path(id): Path<String>,
+) -> Result<Json<ApiResponse<AgentTask>>, AppError> {
+    let scheduler = state.get_agent_scheduler()?;
+    let task = scheduler.task(&id).await?;
+
+    Ok(api_success(task))
+}
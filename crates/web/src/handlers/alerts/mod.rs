@@ -0,0 +1,8 @@
+//! Alerts module for handling alert API endpoints
+//!
+//! This module contains handlers for listing firing alerts and their groups,
+//! acknowledging and resolving them, and managing silences.
+
+mod routes;
+
+pub use routes::alert_routes;
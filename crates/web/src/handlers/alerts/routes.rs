@@ -0,0 +1,157 @@
+use axum::{
+    Router,
+    routing::{get, post},
+    extract::{Path, Query, State, Extension},
+    Json,
+};
+use chrono::Duration;
+use std::sync::Arc;
+use squirrel_monitoring::alerts::{LabelMatcher, Silence, TrackedAlert};
+use uuid::Uuid;
+use crate::state::AppState;
+use crate::auth::extractor::AuthClaims;
+use crate::api::{
+    api_success,
+    alerts::{
+        AlertGroupListResponse, AlertListResponse, CreateSilenceRequest, SilenceListQuery,
+        SilenceListResponse,
+    },
+    error::AppError,
+    ApiResponse,
+};
+
+/// Alert routes
+pub fn alert_routes() -> Router<Arc<AppState>> {
+    Router::new()
+        .route("/", get(list_alerts))
+        .route("/groups", get(list_groups))
+        .route("/silences", get(list_silences).post(create_silence))
+        .route("/silences/:id", axum::routing::delete(expire_silence))
+        .route("/:fingerprint/ack", post(acknowledge_alert).delete(unacknowledge_alert))
+        .route("/:fingerprint/resolve", post(resolve_alert))
+}
+
+/// List firing alerts
+async fn list_alerts(
+    State(state): State<Arc<AppState>>,
+) -> Result<Json<ApiResponse<AlertListResponse>>, AppError> {
+    let lifecycle = state.get_alert_lifecycle()?;
+
+    Ok(api_success(AlertListResponse { alerts: lifecycle.alerts() }))
+}
+
+/// List firing alerts by group
+async fn list_groups(
+    State(state): State<Arc<AppState>>,
+) -> Result<Json<ApiResponse<AlertGroupListResponse>>, AppError> {
+    let lifecycle = state.get_alert_lifecycle()?;
+
+    Ok(api_success(AlertGroupListResponse { groups: lifecycle.groups() }))
+}
+
+/// Acknowledge an alert
+async fn acknowledge_alert(
+    State(state): State<Arc<AppState>>,
+    Extension(user): Extension<AuthClaims>,
+    Path(fingerprint): Path<String>,
+) -> Result<Json<ApiResponse<TrackedAlert>>, AppError> {
+    let lifecycle = state.get_alert_lifecycle()?;
+    find_alert(&state, &fingerprint)?;
+    let alert = lifecycle
+        .acknowledge(&fingerprint, &user.sub)
+        .map_err(|e| AppError::Internal(e.to_string()))?;
+
+    Ok(api_success(alert))
+}
+
+/// Withdraw the acknowledgment of an alert
+async fn unacknowledge_alert(
+    State(state): State<Arc<AppState>>,
+    Path(fingerprint): Path<String>,
+) -> Result<Json<ApiResponse<TrackedAlert>>, AppError> {
+    let lifecycle = state.get_alert_lifecycle()?;
+    find_alert(&state, &fingerprint)?;
+    let alert = lifecycle
+        .unacknowledge(&fingerprint)
+        .map_err(|e| AppError::Internal(e.to_string()))?;
+
+    Ok(api_success(alert))
+}
+
+/// Resolve an alert
+async fn resolve_alert(
+    State(state): State<Arc<AppState>>,
+    Path(fingerprint): Path<String>,
+) -> Result<Json<ApiResponse<TrackedAlert>>, AppError> {
+    let lifecycle = state.get_alert_lifecycle()?;
+    let alert = lifecycle
+        .resolve(&fingerprint)
+        .map_err(|e| AppError::Internal(e.to_string()))?
+        .ok_or_else(|| AppError::NotFound(format!("No firing alert with fingerprint {}", fingerprint)))?;
+
+    Ok(api_success(alert))
+}
+
+/// List silences
+async fn list_silences(
+    State(state): State<Arc<AppState>>,
+    Query(query): Query<SilenceListQuery>,
+) -> Result<Json<ApiResponse<SilenceListResponse>>, AppError> {
+    let lifecycle = state.get_alert_lifecycle()?;
+
+    Ok(api_success(SilenceListResponse { silences: lifecycle.silences(query.include_expired) }))
+}
+
+/// Silence alerts matching labels for a while
+async fn create_silence(
+    State(state): State<Arc<AppState>>,
+    Extension(user): Extension<AuthClaims>,
+    Json(payload): Json<CreateSilenceRequest>,
+) -> Result<Json<ApiResponse<Silence>>, AppError> {
+    let lifecycle = state.get_alert_lifecycle()?;
+    let matchers = payload
+        .matchers
+        .iter()
+        .map(|matcher| matcher.parse::<LabelMatcher>())
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|e| AppError::InvalidRequest(e.to_string()))?;
+    if matchers.is_empty() {
+        return Err(AppError::InvalidRequest("At least one matcher is required".to_string()));
+    }
+    let duration = i64::try_from(payload.duration_secs)
+        .ok()
+        .filter(|secs| *secs > 0)
+        .and_then(Duration::try_seconds)
+        .ok_or_else(|| AppError::InvalidRequest("duration_secs must be positive".to_string()))?;
+
+    let silence = Silence::new(matchers, duration, user.sub, payload.comment.unwrap_or_default());
+    let silence = lifecycle
+        .add_silence(silence)
+        .map_err(|e| AppError::Internal(e.to_string()))?;
+
+    Ok(api_success(silence))
+}
+
+/// End a silence now
+async fn expire_silence(
+    State(state): State<Arc<AppState>>,
+    Path(id): Path<Uuid>,
+) -> Result<Json<ApiResponse<Silence>>, AppError> {
+    let lifecycle = state.get_alert_lifecycle()?;
+    if !lifecycle.silences(true).iter().any(|silence| silence.id == id) {
+        return Err(AppError::NotFound(format!("Silence {} not found", id)));
+    }
+    let silence = lifecycle
+        .expire_silence(id)
+        .map_err(|e| AppError::Internal(e.to_string()))?;
+
+    Ok(api_success(silence))
+}
+
+/// The firing alert with `fingerprint`, or a not-found error
+fn find_alert(state: &AppState, fingerprint: &str) -> Result<TrackedAlert, AppError> {
+    state
+        .get_alert_lifecycle()?
+        .get(fingerprint)
+        .ok_or_else(|| AppError::NotFound(format!("No firing alert with fingerprint {}", fingerprint)))
+}
@@ -0,0 +1,72 @@
+//! Assistant module for handling assistant API endpoints
+//!
+//! This module contains handlers for asking the assistant to plan requests
+//! against the registered tools and commands, and for running the plans.
+
+mod routes;
+
+pub use routes::assistant_routes;
+
+use axum::http::StatusCode;
+use squirrel_mcp::ai::{AiError, ToolOption};
+
+use crate::api::error::AppError;
+use crate::state::AppState;
+
+impl From<AiError> for AppError {
+    fn from(err: AiError) -> Self {
+        match err {
+            AiError::ConversationNotFound(_) => AppError::NotFound(err.to_string()),
+            AiError::Model(_) => AppError::Custom(StatusCode::BAD_GATEWAY, err.to_string()),
+            AiError::NotConfigured | AiError::Storage(_) => AppError::Internal(err.to_string()),
+        }
+    }
+}
+
+/// The commands and tool capabilities the assistant can plan with
+///
+/// Which of them a user may use is decided when planning.
+pub async fn catalog(state: &AppState) -> Result<Vec<ToolOption>, AppError> {
+    let mut catalog = Vec::new();
+    if let Some(mcp_command) = &state.mcp_command {
+        let commands = mcp_command.list_available_commands().await?;
+        catalog.extend(commands.into_iter().map(|command| {
+            ToolOption::command(command.name, command.description, command.parameter_schema)
+        }));
+    }
+    if let Some(tool_manager) = &state.tool_manager {
+        for tool in tool_manager.get_all_tools().await {
+            catalog.extend(ToolOption::from_tool(&tool));
+        }
+    }
+    Ok(catalog)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Arc;
+    use squirrel_mcp::ai::TargetKind;
+    use squirrel_mcp::tool::ToolManager;
+    use uuid::Uuid;
+
+    use crate::mcp::MockMcpClient;
+
+    #[tokio::test]
+    async fn test_catalog_lists_commands_and_tools() {
+        let state = AppState {
+            mcp_command: Some(Arc::new(MockMcpClient::new("localhost".to_string(), 9000))),
+            tool_manager: Some(Arc::new(ToolManager::new())),
+            ..AppState::default()
+        };
+        let catalog = catalog(&state).await.unwrap();
+        let targets: Vec<&str> = catalog.iter().map(|option| option.target.as_str()).collect();
+        assert_eq!(targets, ["test-command", "another-command"]);
+        assert!(catalog.iter().all(|option| option.kind == TargetKind::Command));
+
+        let error = AppError::from(AiError::ConversationNotFound(Uuid::new_v4()));
+        assert!(matches!(error, AppError::NotFound(_)));
+        let error = AppError::from(AiError::Model("timed out".to_string()));
+        assert!(matches!(error, AppError::Custom(StatusCode::BAD_GATEWAY, _)));
+    }
+}
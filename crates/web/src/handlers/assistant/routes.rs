@@ -0,0 +1,205 @@
+use axum::{
+    Router,
+    routing::{get, post},
+    extract::{Path, Query, State, Extension},
+    http::HeaderMap,
+    Json,
+};
+use std::sync::Arc;
+use squirrel_commands::extensions::RequestId;
+use squirrel_core::flags;
+use squirrel_mcp::ai::{AuditQuery, AuditRecord, Caller, Conversation, PlannedStep, TargetKind};
+use squirrel_monitoring::accounting::ResourceUsage;
+use squirrel_monitoring::llm_costs::LlmCostQuery;
+use uuid::Uuid;
+use crate::state::AppState;
+use crate::auth::extractor::AuthClaims;
+use crate::handlers::usage::{enforce_quotas, is_admin, record_usage, workspace};
+use crate::api::{
+    api_success,
+    assistant::{AssistantCallQueryParams, AssistantCostResponse, AssistantRequest, AssistantResponse, ExecutedStep},
+    error::AppError,
+    ApiResponse,
+};
+
+/// Assistant routes
+pub fn assistant_routes() -> Router<Arc<AppState>> {
+    Router::new()
+        .route("/", post(ask))
+        .route("/conversations/:id", get(get_conversation))
+        .route("/costs", get(get_costs))
+        .route("/audit", get(get_audit))
+}
+
+/// Plan a request with the tools and commands the user may use, and run the
+/// plan if asked to, unless the `ai_assistant` flag is off for the caller
+async fn ask(
+    State(state): State<Arc<AppState>>,
+    Extension(user): Extension<AuthClaims>,
+    headers: HeaderMap,
+    Json(request): Json<AssistantRequest>,
+) -> Result<Json<ApiResponse<AssistantResponse>>, AppError> {
+    let workspace = workspace(&headers);
+    state.check_flag(flags::AI_ASSISTANT, &user.sub, &workspace)?;
+    if request.message.trim().is_empty() {
+        return Err(AppError::InvalidRequest("Message is empty".to_string()));
+    }
+    let assistant = state.get_assistant()?;
+    let caller = Caller::new(user.sub.clone(), user.roles.clone());
+    let catalog = super::catalog(&state).await?;
+    let answer = assistant
+        .ask(&caller, request.conversation_id, &request.message, &catalog)
+        .await?;
+
+    let (executed, request_id) = if request.execute {
+        let request_id = RequestId::generate().to_string();
+        (execute(&state, &user, &workspace, &answer.plan.steps, &request_id).await?, Some(request_id))
+    } else {
+        (Vec::new(), None)
+    };
+
+    Ok(api_success(AssistantResponse {
+        conversation_id: answer.conversation_id,
+        reply: answer.reply,
+        plan: answer.plan,
+        executed,
+        request_id,
+    }))
+}
+
+/// Run planned steps in order, stopping at the first that fails
+///
+/// Each step counts as a submission: it is refused under memory pressure or
+/// once a quota is used up, and charged to the user's usage. A step a
+/// command policy denies fails like any other. Tools run under
+/// `request_id`, so their executions show in its trace.
+async fn execute(
+    state: &AppState,
+    user: &AuthClaims,
+    workspace: &str,
+    steps: &[PlannedStep],
+    request_id: &str,
+) -> Result<Vec<ExecutedStep>, AppError> {
+    let mut executed = Vec::with_capacity(steps.len());
+    for step in steps {
+        state.check_memory_pressure()?;
+        enforce_quotas(state, &user.sub, workspace)?;
+
+        let mut outcome = ExecutedStep {
+            kind: step.kind,
+            target: step.target.clone(),
+            command_id: None,
+            output: None,
+            error: None,
+        };
+        match (step.kind, step.tool_capability()) {
+            (TargetKind::Tool, Some((tool_id, capability))) => {
+                let tool_manager = state.get_tool_manager()?;
+                if let Err(e) = state.check_tool_policy(user, tool_id) {
+                    outcome.error = Some(e.to_string());
+                } else {
+                    match tool_manager
+                        .execute_tool(tool_id, capability, step.arguments.clone(), Some(request_id.to_string()))
+                        .await {
+                        Ok(result) => {
+                            outcome.output = result.output;
+                            outcome.error = result.error_message;
+                        }
+                        Err(e) => outcome.error = Some(e.to_string()),
+                    }
+                }
+            }
+            (TargetKind::Tool, None) => {
+                outcome.error = Some(format!("Invalid tool target '{}'", step.target));
+            }
+            (TargetKind::Command, _) => {
+                let command_service = state.get_command_service()?;
+                if let Err(e) = state.check_command_policy(user, &step.target) {
+                    outcome.error = Some(e.to_string());
+                } else {
+                    match command_service.create_command(&user.sub, &step.target, &step.arguments, None).await {
+                        Ok(id) => outcome.command_id = Some(id),
+                        Err(e) => outcome.error = Some(e.to_string()),
+                    }
+                }
+            }
+        }
+        record_usage(state, &user.sub, workspace, ResourceUsage::execution());
+
+        let failed = outcome.error.is_some();
+        executed.push(outcome);
+        if failed {
+            break;
+        }
+    }
+    Ok(executed)
+}
+
+/// Get one of the user's conversations with the assistant
+async fn get_conversation(
+    State(state): State<Arc<AppState>>,
+    Extension(user): Extension<AuthClaims>,
+    Path(id): Path<Uuid>,
+) -> Result<Json<ApiResponse<Conversation>>, AppError> {
+    let conversation = state.get_assistant()?.conversations().get(id, &user.sub).await?;
+    Ok(api_success(conversation))
+}
+
+/// The user whose calls are asked for; `None` for every user
+///
+/// Users see their own calls; admins see everyone's unless they pick a user.
+fn call_subject(user: &AuthClaims, requested: Option<String>) -> Result<Option<String>, AppError> {
+    match requested {
+        Some(name) if name != user.sub && !is_admin(user) => {
+            Err(AppError::Forbidden("Only admins can see other users' assistant calls".to_string()))
+        }
+        Some(name) => Ok(Some(name)),
+        None if is_admin(user) => Ok(None),
+        None => Ok(Some(user.sub.clone())),
+    }
+}
+
+/// Get the cost of language model calls per user and provider, and the
+/// user's spending against their budgets
+async fn get_costs(
+    State(state): State<Arc<AppState>>,
+    Extension(user): Extension<AuthClaims>,
+    Query(params): Query<AssistantCallQueryParams>,
+) -> Result<Json<ApiResponse<AssistantCostResponse>>, AppError> {
+    let assistant = state.get_assistant()?;
+    let auditor = assistant
+        .auditor()
+        .ok_or_else(|| AppError::Internal("Assistant audit not enabled".to_string()))?;
+    let query = LlmCostQuery {
+        from: params.from,
+        to: params.to,
+        user: call_subject(&user, params.user)?,
+        provider: params.provider,
+    };
+
+    let costs = auditor.costs();
+    let budgets = costs.budget_status(&user.sub, assistant.provider());
+    Ok(api_success(AssistantCostResponse {
+        summaries: costs.report(&query),
+        budgets,
+    }))
+}
+
+/// Get the redacted audit records of language model calls
+async fn get_audit(
+    State(state): State<Arc<AppState>>,
+    Extension(user): Extension<AuthClaims>,
+    Query(params): Query<AssistantCallQueryParams>,
+) -> Result<Json<ApiResponse<Vec<AuditRecord>>>, AppError> {
+    let assistant = state.get_assistant()?;
+    let auditor = assistant
+        .auditor()
+        .ok_or_else(|| AppError::Internal("Assistant audit not enabled".to_string()))?;
+    let query = AuditQuery {
+        from: params.from,
+        to: params.to,
+        user: call_subject(&user, params.user)?,
+        conversation_id: params.conversation_id,
+    };
+    Ok(api_success(auditor.log().records(&query)?))
+}
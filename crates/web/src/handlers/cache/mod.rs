@@ -0,0 +1,8 @@
+//! Cache module for handling result cache API endpoints
+//!
+//! This module contains handlers for inspecting and invalidating the cached
+//! results of command runs.
+
+mod routes;
+
+pub use routes::cache_routes;
@@ -0,0 +1,195 @@
+use axum::{
+    Router,
+    routing::{get, post},
+    extract::{Path, Query, State, Extension},
+    Json,
+};
+use std::sync::Arc;
+use squirrel_commands::cache::CachedResult;
+use crate::state::AppState;
+use crate::auth::extractor::AuthClaims;
+use crate::handlers::usage::is_admin;
+use crate::api::{
+    api_success,
+    cache::{CacheListQuery, CacheListResponse, InvalidateCacheRequest, InvalidateCacheResponse},
+    error::AppError,
+    ApiResponse,
+};
+
+/// Result cache routes
+pub fn cache_routes() -> Router<Arc<AppState>> {
+    Router::new()
+        .route("/", get(list_entries))
+        .route("/invalidate", post(invalidate_entries))
+        .route("/:key", get(get_entry).delete(delete_entry))
+}
+
+/// Whether `user` may see `entry`, which holds the arguments and output of
+/// its owner's run
+fn can_read(user: &AuthClaims, entry: &CachedResult) -> bool {
+    is_admin(user) || entry.owner.as_deref() == Some(user.sub.as_str())
+}
+
+/// List cached results, newest first
+///
+/// Admins see all results, other users only their own.
+async fn list_entries(
+    State(state): State<Arc<AppState>>,
+    Extension(user): Extension<AuthClaims>,
+    Query(query): Query<CacheListQuery>,
+) -> Result<Json<ApiResponse<CacheListResponse>>, AppError> {
+    let result_cache = state.get_result_cache()?;
+    let mut entries = result_cache.entries()?;
+    entries.retain(|entry| can_read(&user, entry));
+    if let Some(command) = &query.command {
+        entries.retain(|entry| &entry.command == command);
+    }
+
+    Ok(api_success(CacheListResponse { entries }))
+}
+
+/// Get a cached result and what it was computed from
+async fn get_entry(
+    State(state): State<Arc<AppState>>,
+    Extension(user): Extension<AuthClaims>,
+    Path(key): Path<String>,
+) -> Result<Json<ApiResponse<CachedResult>>, AppError> {
+    let result_cache = state.get_result_cache()?;
+    let entry = result_cache
+        .get(&key)?
+        .filter(|entry| can_read(&user, entry))
+        .ok_or_else(|| AppError::NotFound(format!("Cached result {} not found", key)))?;
+
+    Ok(api_success(entry))
+}
+
+/// Remove a cached result
+///
+/// The cache is shared by all users, so only admins may remove results.
+async fn delete_entry(
+    State(state): State<Arc<AppState>>,
+    Extension(user): Extension<AuthClaims>,
+    Path(key): Path<String>,
+) -> Result<Json<ApiResponse<()>>, AppError> {
+    if !is_admin(&user) {
+        return Err(AppError::Forbidden("Only admins may remove cached results".to_string()));
+    }
+    let result_cache = state.get_result_cache()?;
+    if !result_cache.remove(&key)? {
+        return Err(AppError::NotFound(format!("Cached result {} not found", key)));
+    }
+
+    Ok(api_success(()))
+}
+
+/// Remove the cached results that read a file, belong to a command, or all of them
+///
+/// Only admins may invalidate results.
+async fn invalidate_entries(
+    State(state): State<Arc<AppState>>,
+    Extension(user): Extension<AuthClaims>,
+    Json(payload): Json<InvalidateCacheRequest>,
+) -> Result<Json<ApiResponse<InvalidateCacheResponse>>, AppError> {
+    if !is_admin(&user) {
+        return Err(AppError::Forbidden("Only admins may invalidate cached results".to_string()));
+    }
+    let result_cache = state.get_result_cache()?;
+    let removed = match (&payload.input, &payload.command, payload.all) {
+        (Some(input), None, false) => {
+            let path = state.get_file_service()?.resolve(input)?;
+            result_cache.invalidate_input(&path)
+        }
+        (None, Some(command), false) => result_cache.invalidate_command(command),
+        (None, None, true) => result_cache.clear(),
+        _ => {
+            return Err(AppError::InvalidRequest(
+                "Pass exactly one of input, command or all".to_string(),
+            ))
+        }
+    }?;
+
+    Ok(api_success(InvalidateCacheResponse { removed }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use squirrel_commands::cache::ResultCache;
+    use squirrel_commands::manifest::{ManifestOptions, RunManifest};
+    use crate::test_helpers::claims;
+
+    /// State whose cache holds one result of `count` for each of `owners`
+    fn state_with_results(dir: &std::path::Path, owners: &[&str]) -> (Arc<AppState>, Vec<String>) {
+        let keys = owners
+            .iter()
+            .map(|owner| {
+                let cache = ResultCache::open(dir).unwrap().with_owner(*owner);
+                let manifest = RunManifest::capture("count", &[owner.to_string()], &ManifestOptions::default()).unwrap();
+                cache.store(&manifest, "4 bases").unwrap().key
+            })
+            .collect();
+        let state = Arc::new(AppState {
+            result_cache: Some(Arc::new(ResultCache::open(dir).unwrap())),
+            ..AppState::default()
+        });
+        (state, keys)
+    }
+
+    #[tokio::test]
+    async fn test_users_only_read_their_own_results() {
+        let dir = tempfile::tempdir().unwrap();
+        let (state, keys) = state_with_results(dir.path(), &["ada", "grace"]);
+
+        let Json(response) = list_entries(
+            State(state.clone()),
+            Extension(claims("ada", &["User"])),
+            Query(CacheListQuery::default()),
+        )
+        .await
+        .unwrap();
+        let entries = response.data.unwrap().entries;
+        assert_eq!(entries.iter().map(|entry| entry.key.as_str()).collect::<Vec<_>>(), vec![keys[0].as_str()]);
+
+        let hidden = get_entry(State(state.clone()), Extension(claims("ada", &["User"])), Path(keys[1].clone())).await;
+        assert!(matches!(hidden, Err(AppError::NotFound(_))));
+        let Json(response) = get_entry(State(state.clone()), Extension(claims("ada", &["User"])), Path(keys[0].clone()))
+            .await
+            .unwrap();
+        assert_eq!(response.data.unwrap().owner.as_deref(), Some("ada"));
+
+        let Json(response) = list_entries(
+            State(state),
+            Extension(claims("root", &["Admin"])),
+            Query(CacheListQuery::default()),
+        )
+        .await
+        .unwrap();
+        assert_eq!(response.data.unwrap().entries.len(), 2);
+    }
+
+    #[tokio::test]
+    async fn test_only_admins_remove_results() {
+        let dir = tempfile::tempdir().unwrap();
+        let (state, keys) = state_with_results(dir.path(), &["ada"]);
+
+        let refused = delete_entry(State(state.clone()), Extension(claims("ada", &["User"])), Path(keys[0].clone())).await;
+        assert!(matches!(refused, Err(AppError::Forbidden(_))));
+        let refused = invalidate_entries(
+            State(state.clone()),
+            Extension(claims("ada", &["User"])),
+            Json(InvalidateCacheRequest { all: true, ..InvalidateCacheRequest::default() }),
+        )
+        .await;
+        assert!(matches!(refused, Err(AppError::Forbidden(_))));
+        assert_eq!(state.get_result_cache().unwrap().entries().unwrap().len(), 1);
+
+        let Json(response) = invalidate_entries(
+            State(state.clone()),
+            Extension(claims("root", &["Admin"])),
+            Json(InvalidateCacheRequest { all: true, ..InvalidateCacheRequest::default() }),
+        )
+        .await
+        .unwrap();
+        assert_eq!(response.data.unwrap().removed, keys);
+    }
+}
@@ -0,0 +1,108 @@
+//! Capability discovery handler for the API.
+//!
+//! Reports which optional subsystems this server was built with and has
+//! configured, and which commands and tools clients can call.
+
+use std::sync::Arc;
+use axum::{extract::State, Json};
+use squirrel_commands::capabilities::{common_subsystems, CapabilityReport, Subsystem, ToolInfo};
+use squirrel_commands::plugin_logs;
+
+use crate::api::{api_success, error::AppError, ApiResponse};
+use crate::websocket::BackplaneKind;
+use crate::AppState;
+
+/// Get the capabilities of this server
+pub async fn get_capabilities(
+    State(state): State<Arc<AppState>>,
+) -> Result<Json<ApiResponse<CapabilityReport>>, AppError> {
+    let report = match &state.read_caches {
+        Some(caches) => caches.capabilities.get_or_load((), || capability_report(&state)).await?,
+        None => capability_report(&state).await?,
+    };
+    Ok(api_success(report))
+}
+
+/// Build the capability report for `state`
+async fn capability_report(state: &AppState) -> Result<CapabilityReport, AppError> {
+    let database = Subsystem::compiled("database", true)
+        .with_detail(if cfg!(feature = "db") { "sqlite" } else { "mock-db" });
+    let backplane = state.config.backplane.kind;
+    let plugins = plugin_logs::global().plugins();
+    let subsystems = vec![
+        database,
+        Subsystem::feature(
+            "redis-backplane",
+            cfg!(feature = "redis-backplane"),
+            backplane == BackplaneKind::Redis,
+        ),
+        Subsystem::feature(
+            "nats-backplane",
+            cfg!(feature = "nats-backplane"),
+            backplane == BackplaneKind::Nats,
+        ),
+        Subsystem::feature(
+            "parquet-export",
+            cfg!(feature = "parquet-export"),
+            state.exporter.is_some(),
+        ),
+        Subsystem::compiled("leader-election", state.config.leader_election.enabled),
+        Subsystem::compiled("mcp", state.mcp_command.is_some()),
+        Subsystem::compiled("contexts", state.context_manager.is_some()),
+        Subsystem::compiled("plugins", true)
+            .with_detail(format!("{} plugins reporting logs", plugins.len())),
+    ];
+
+    let commands = match &state.mcp_command {
+        Some(client) => client
+            .list_available_commands()
+            .await
+            .map_err(|e| AppError::Internal(e.to_string()))?
+            .into_iter()
+            .map(|command| command.name)
+            .collect(),
+        None => Vec::new(),
+    };
+
+    let tools = match &state.tool_manager {
+        Some(manager) => manager
+            .get_all_tools()
+            .await
+            .into_iter()
+            .map(|tool| ToolInfo {
+                id: tool.id,
+                name: tool.name,
+                version: tool.version,
+                capabilities: tool.capabilities.into_iter().map(|c| c.name).collect(),
+            })
+            .collect(),
+        None => Vec::new(),
+    };
+
+    Ok(CapabilityReport::new("squirrel-web", squirrel_core::build_info::version())
+        .with_subsystems(subsystems)
+        .with_subsystems(common_subsystems())
+        .with_commands(commands)
+        .with_tools(tools))
+}
+
+#[cfg(all(test, feature = "mock-db"))]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_report_lists_subsystems_and_commands() {
+        let state = AppState::default();
+
+        let report = capability_report(&state).await.expect("Failed to build report");
+
+        assert_eq!(report.component, "squirrel-web");
+        assert!(report.subsystem("database").is_some_and(|s| s.available()));
+        assert!(report.supports("mcp"));
+        assert!(!report.supports("contexts"));
+        assert!(!report.supports("redis-backplane"));
+        assert!(!report.supports("wasm"));
+        assert!(!report.commands.is_empty());
+        assert!(report.tools.is_empty());
+    }
+}
@@ -7,7 +7,7 @@ pub mod service;
 // Re-export the service, conditionally re-export DbCommandService
 pub use service::CommandService;
 #[cfg(feature = "db")]
-pub use service::DbCommandService;
+pub use service::{CommandJobHandler, DbCommandService, COMMAND_JOB};
 pub use service::MockCommandService;
 
 mod routes;
@@ -2,15 +2,18 @@ use axum::{
     Router,
     routing::{get, post},
     extract::{Path, Query, State, Extension},
+    http::HeaderMap,
     Json,
 };
 use std::sync::Arc;
 use crate::state::AppState;
 use crate::auth::extractor::AuthClaims;
+use crate::handlers::usage::{enforce_quotas, record_usage, workspace};
+use crate::idempotency::idempotency_key;
 use crate::api::{
     api_success,
     commands::{
-        CommandListResponse, CommandHistoryResponse, CommandStatusResponse, 
+        CommandListResponse, CommandHistoryResponse, CommandSearchResponse, CommandStatusResponse,
         CreateCommandRequest, CreateCommandResponse, CommandStatus
     },
     error::AppError,
@@ -19,6 +22,8 @@ use crate::api::{
 use serde::{Deserialize, Serialize};
 use chrono::{DateTime, Utc};
 use std::str::FromStr;
+use squirrel_commands::palette::{self, DEFAULT_LIMIT};
+use squirrel_monitoring::accounting::ResourceUsage;
 
 /// Command routes
 pub fn command_routes() -> Router<Arc<AppState>> {
@@ -28,27 +33,64 @@ pub fn command_routes() -> Router<Arc<AppState>> {
         .route("/:id", get(get_command_status))
         .route("/:id/cancel", post(cancel_command))
         .route("/history", get(get_command_history))
+        .route("/search", get(search_commands))
 }
 
-/// Create a new command
+/// Create a new command, unless the user or workspace used up a quota
+///
+/// A command submitted for a job executes in the job's MCP context and is
+/// recorded there as a step. A repeated submission with the same
+/// `Idempotency-Key` returns the original command instead of a new one.
 async fn create_command(
     State(state): State<Arc<AppState>>,
     Extension(user): Extension<AuthClaims>,
+    headers: HeaderMap,
     Json(payload): Json<CreateCommandRequest>,
 ) -> Result<Json<ApiResponse<CreateCommandResponse>>, AppError> {
+    let key = idempotency_key(&headers)?;
+    let response = state
+        .get_idempotency()?
+        .run(&user.sub, "commands", key, &payload, || submit_command(&state, &user, &headers, &payload))
+        .await?;
+
+    Ok(api_success(response))
+}
+
+/// Create and record a new command
+async fn submit_command(
+    state: &AppState,
+    user: &AuthClaims,
+    headers: &HeaderMap,
+    payload: &CreateCommandRequest,
+) -> Result<CreateCommandResponse, AppError> {
     let command_service = state.get_command_service()?;
+    let workspace = workspace(headers);
+    state.check_memory_pressure()?;
+    state.check_plugin_enabled(&payload.command)?;
+    state.check_command_policy(user, &payload.command)?;
+    enforce_quotas(state, &user.sub, &workspace)?;
+    let context_id = match &payload.job_id {
+        Some(job_id) => Some(state.get_job_contexts()?.context_for(job_id, &user.sub).await?.to_string()),
+        None => None,
+    };
     let id = command_service.create_command(
         &user.sub,
         &payload.command,
         &payload.parameters,
+        context_id.as_deref(),
     ).await?;
+    record_usage(state, &user.sub, &workspace, ResourceUsage::execution());
+    if let Some(job_id) = &payload.job_id {
+        state
+            .get_job_contexts()?
+            .record_step(job_id, &payload.command, &payload.parameters, &id)
+            .await?;
+    }
 
-    let response = CreateCommandResponse {
+    Ok(CreateCommandResponse {
         id: id.clone(),
         status_url: format!("/api/commands/{}/status", id),
-    };
-
-    Ok(api_success(response))
+    })
 }
 
 /// List available commands
@@ -62,11 +104,12 @@ async fn list_commands(
     let page = params.page.unwrap_or(1);
     let limit = params.limit.unwrap_or(20);
     
-    let (commands, _total_items, _total_pages) = command_service.get_available_commands(
-        &user.sub,
-        page,
-        limit,
-    ).await?;
+    // The list is the same for every user, so pages are cached by position
+    let load = || command_service.get_available_commands(&user.sub, page, limit);
+    let (commands, _total_items, _total_pages) = match &state.read_caches {
+        Some(caches) => caches.commands.get_or_load((page, limit), load).await?,
+        None => load().await?,
+    };
     
     let response = CommandListResponse {
         commands,
@@ -163,6 +206,39 @@ async fn get_command_history(
     Ok(api_success(response))
 }
 
+/// Number of the user's recent executions that rank palette searches
+const SEARCH_HISTORY_LIMIT: u32 = 500;
+
+/// Search the commands fuzzily for the command palette
+///
+/// Commands match by name, subcommand, tags, namespace or plugin, and rank
+/// by match quality and how often they were used, counting both the
+/// recorded command history and the user's recent executions.
+async fn search_commands(
+    State(state): State<Arc<AppState>>,
+    Extension(user): Extension<AuthClaims>,
+    Query(params): Query<CommandSearchParams>,
+) -> Result<Json<ApiResponse<CommandSearchResponse>>, AppError> {
+    let registry = state.get_command_registry()?;
+    let mut entries = registry.index().map_err(|e| AppError::Internal(e.to_string()))?;
+
+    if let Some(command_service) = &state.command_service {
+        let (executions, _total_items, _total_pages) = command_service
+            .get_command_history(&user.sub, 1, SEARCH_HISTORY_LIMIT, None, None)
+            .await?;
+        for execution in executions {
+            let keys = palette::usage_keys(&execution.command_name, &[]);
+            for entry in entries.iter_mut().filter(|entry| keys.contains(&entry.name)) {
+                entry.uses += 1;
+            }
+        }
+    }
+
+    let query = params.q.unwrap_or_default();
+    let results = palette::rank(entries, &query, params.limit.unwrap_or(DEFAULT_LIMIT));
+    Ok(api_success(CommandSearchResponse { results }))
+}
+
 /// Helper function to format elapsed time
 fn format_elapsed(created_at: DateTime<Utc>) -> String {
     let elapsed = Utc::now().signed_duration_since(created_at);
@@ -193,6 +269,15 @@ pub struct CommandHistoryParams {
     pub command: Option<String>,
 }
 
+/// Command palette search parameters
+#[derive(Debug, Deserialize)]
+pub struct CommandSearchParams {
+    /// Characters to find in the commands; empty lists the most used
+    pub q: Option<String>,
+    /// Maximum number of results
+    pub limit: Option<usize>,
+}
+
 /// Pagination information
 #[derive(Debug, Serialize)]
 pub struct PaginationInfo {
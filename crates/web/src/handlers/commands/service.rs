@@ -16,16 +16,25 @@ use crate::api::commands::{
     CommandStatus,
 };
 use crate::mcp::{McpCommandClient, McpError};
+#[cfg(feature = "db")]
+use crate::db::InstrumentedPool;
+#[cfg(feature = "db")]
+use crate::queue::{JobHandler, JobQueue, QueueEntry};
+
+/// Kind of the job queue entries executing commands
+#[cfg(feature = "db")]
+pub const COMMAND_JOB: &str = "command";
 
 /// Command service trait
 #[async_trait]
 pub trait CommandService: Send + Sync + 'static {
-    /// Create and execute a new command
+    /// Create and execute a new command, within the given MCP context if any
     async fn create_command(
         &self,
         user_id: &str,
         command: &str,
         parameters: &serde_json::Value,
+        context_id: Option<&str>,
     ) -> Result<String, AppError>;
     
     /// Get available commands
@@ -62,17 +71,21 @@ pub trait CommandService: Send + Sync + 'static {
 }
 
 /// Database implementation of the command service
+///
+/// Commands are queued in the job queue and executed by a
+/// [`CommandJobHandler`], so they survive a restart of the server.
 #[cfg(feature = "db")]
 pub struct DbCommandService {
-    db: SqlitePool,
+    db: InstrumentedPool,
     mcp_client: Arc<dyn McpCommandClient>,
+    queue: Arc<JobQueue>,
 }
 
 #[cfg(feature = "db")]
 impl DbCommandService {
     /// Create a new DbCommandService
-    pub fn new(db: SqlitePool, mcp_client: Arc<dyn McpCommandClient>) -> Self {
-        Self { db, mcp_client }
+    pub fn new(db: SqlitePool, mcp_client: Arc<dyn McpCommandClient>, queue: Arc<JobQueue>) -> Self {
+        Self { db: db.into(), mcp_client, queue }
     }
 }
 
@@ -84,16 +97,20 @@ impl CommandService for DbCommandService {
         user_id: &str,
         command: &str,
         parameters: &serde_json::Value,
+        context_id: Option<&str>,
     ) -> Result<String, AppError> {
-        let command_id = Uuid::new_v4().to_string();
+        let entry = QueueEntry::new(
+            COMMAND_JOB,
+            user_id,
+            serde_json::json!({
+                "command": command,
+                "parameters": parameters,
+                "context_id": context_id,
+            }),
+        );
+        let command_id = entry.id.clone();
         let now = Utc::now();
         
-        // Execute command via MCP
-        let mcp_command_id = match self.mcp_client.execute_command(command, parameters).await {
-            Ok(id) => id,
-            Err(err) => return Err(AppError::from(err)),
-        };
-        
         // Store command in database
         sqlx::query!(
             r#"
@@ -106,7 +123,7 @@ impl CommandService for DbCommandService {
             command,
             user_id,
             parameters.to_string(),
-            CommandStatus::Running.as_str(),
+            CommandStatus::Queued.as_str(),
             0.0,
             now,
             now
@@ -115,6 +132,12 @@ impl CommandService for DbCommandService {
         .await
         .map_err(|e| AppError::Database(format!("Failed to store command execution: {}", e)))?;
         
+        // Queue the command for the next free worker
+        if let Err(e) = self.queue.enqueue(&entry).await {
+            update_execution(&self.db, &command_id, CommandStatus::Failed, None, Some(&e.to_string())).await?;
+            return Err(e);
+        }
+        
         Ok(command_id)
     }
     
@@ -372,6 +395,11 @@ impl CommandService for DbCommandService {
             return Err(AppError::NotFound(format!("Command execution {} not found", command_id)));
         }
         
+        // A command still queued never reached MCP
+        if self.queue.cancel(command_id).await? {
+            return update_execution(&self.db, command_id, CommandStatus::Cancelled, None, None).await;
+        }
+        
         // Cancel the command in MCP
         match self.mcp_client.cancel_command(command_id).await {
             Ok(_) => {
@@ -393,6 +421,129 @@ impl CommandService for DbCommandService {
     }
 }
 
+/// Update the status of a command execution, recording its completion once
+/// it reached a final status
+#[cfg(feature = "db")]
+async fn update_execution(
+    db: &InstrumentedPool,
+    command_id: &str,
+    status: CommandStatus,
+    result: Option<&serde_json::Value>,
+    error: Option<&str>,
+) -> Result<(), AppError> {
+    let now = Utc::now();
+    let finished = matches!(status, CommandStatus::Completed | CommandStatus::Failed | CommandStatus::Cancelled);
+    sqlx::query(
+        "UPDATE command_executions
+         SET status = ?, result = COALESCE(?, result), error = COALESCE(?, error),
+             started_at = COALESCE(started_at, ?), completed_at = ?, updated_at = ?
+         WHERE id = ?",
+    )
+    .bind(status.as_str())
+    .bind(result.map(ToString::to_string))
+    .bind(error)
+    .bind((status != CommandStatus::Queued).then_some(now))
+    .bind(finished.then_some(now))
+    .bind(now)
+    .bind(command_id)
+    .execute(db)
+    .await?;
+    Ok(())
+}
+
+/// Executes queued commands through MCP
+///
+/// The handler waits for the command to finish, keeping its queue entry
+/// leased meanwhile, so a command whose server restarts mid-execution is
+/// executed again or failed per the job queue's orphan policy.
+#[cfg(feature = "db")]
+pub struct CommandJobHandler {
+    db: InstrumentedPool,
+    mcp_client: Arc<dyn McpCommandClient>,
+    poll_interval: std::time::Duration,
+}
+
+#[cfg(feature = "db")]
+impl CommandJobHandler {
+    /// Create a new CommandJobHandler
+    pub fn new(db: SqlitePool, mcp_client: Arc<dyn McpCommandClient>) -> Self {
+        Self {
+            db: db.into(),
+            mcp_client,
+            poll_interval: std::time::Duration::from_secs(1),
+        }
+    }
+
+    async fn execute(&self, entry: &QueueEntry) -> Result<(), String> {
+        let command = entry.payload["command"].as_str().unwrap_or_default();
+        let parameters = &entry.payload["parameters"];
+        let context_id = entry.payload["context_id"].as_str();
+
+        let mcp_command_id = self
+            .mcp_client
+            .execute_command(command, parameters, context_id)
+            .await
+            .map_err(|e| AppError::from(e).to_string())?;
+        update_execution(&self.db, &entry.id, CommandStatus::Running, None, None)
+            .await
+            .map_err(|e| e.to_string())?;
+
+        loop {
+            tokio::time::sleep(self.poll_interval).await;
+            let status = match self.mcp_client.get_command_status(&mcp_command_id).await {
+                Ok(status) => status,
+                Err(e) => {
+                    tracing::warn!("Failed to get status of command {}: {:?}", entry.id, e);
+                    continue;
+                }
+            };
+            match status.status {
+                CommandStatus::Queued | CommandStatus::Running => continue,
+                CommandStatus::Completed | CommandStatus::Cancelled => {
+                    update_execution(&self.db, &entry.id, status.status, status.result.as_ref(), None)
+                        .await
+                        .map_err(|e| e.to_string())?;
+                    return Ok(());
+                }
+                CommandStatus::Failed => {
+                    let error = status.error.unwrap_or_else(|| "Command failed".to_string());
+                    update_execution(&self.db, &entry.id, CommandStatus::Failed, None, Some(&error))
+                        .await
+                        .map_err(|e| e.to_string())?;
+                    return Err(error);
+                }
+            }
+        }
+    }
+}
+
+#[cfg(feature = "db")]
+#[async_trait]
+impl JobHandler for CommandJobHandler {
+    async fn run(&self, entry: &QueueEntry) -> Result<(), String> {
+        let outcome = self.execute(entry).await;
+        if let Err(error) = &outcome {
+            if let Err(e) = update_execution(&self.db, &entry.id, CommandStatus::Failed, None, Some(error)).await {
+                tracing::warn!("Failed to record failure of command {}: {}", entry.id, e);
+            }
+        }
+        outcome
+    }
+
+    async fn abandoned(&self, entry: &QueueEntry) {
+        let error = entry.error.as_deref().unwrap_or("Worker was lost");
+        if let Err(e) = update_execution(&self.db, &entry.id, CommandStatus::Failed, None, Some(error)).await {
+            tracing::warn!("Failed to record failure of command {}: {}", entry.id, e);
+        }
+    }
+
+    async fn cancelled(&self, entry: &QueueEntry, reason: &str) {
+        if let Err(e) = update_execution(&self.db, &entry.id, CommandStatus::Cancelled, None, Some(reason)).await {
+            tracing::warn!("Failed to record cancellation of command {}: {}", entry.id, e);
+        }
+    }
+}
+
 /// Mock implementation of the command service for testing
 pub struct MockCommandService {
     mcp_client: Arc<dyn McpCommandClient>,
@@ -412,9 +563,10 @@ impl CommandService for MockCommandService {
         _user_id: &str,
         command: &str,
         parameters: &serde_json::Value,
+        context_id: Option<&str>,
     ) -> Result<String, AppError> {
         // Execute command via MCP but don't store in DB
-        match self.mcp_client.execute_command(command, parameters).await {
+        match self.mcp_client.execute_command(command, parameters, context_id).await {
             Ok(id) => Ok(id),
             Err(err) => Err(AppError::from(err)),
         }
@@ -556,6 +708,10 @@ impl From<McpError> for AppError {
                 format!("MCP timeout: {}", msg)
             ),
             McpError::Internal(msg) => AppError::Internal(format!("MCP internal error: {}", msg)),
+            McpError::Unavailable(msg) => AppError::Custom(
+                axum::http::StatusCode::SERVICE_UNAVAILABLE,
+                format!("MCP server unavailable: {}", msg)
+            ),
         }
     }
 } 
\ No newline at end of file
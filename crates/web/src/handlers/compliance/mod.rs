@@ -0,0 +1,8 @@
+//! Compliance module for handling data protection API endpoints
+//!
+//! This module contains the handlers exporting everything recorded about a
+//! user and erasing it.
+
+mod routes;
+
+pub use routes::compliance_routes;
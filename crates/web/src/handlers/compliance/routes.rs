@@ -0,0 +1,103 @@
+use axum::{
+    Router,
+    routing::{get, post},
+    extract::{Path, State, Extension},
+    http::{header, HeaderValue},
+    response::{IntoResponse, Response},
+    Json,
+};
+use std::sync::Arc;
+use uuid::Uuid;
+use crate::state::AppState;
+use crate::auth::extractor::AuthClaims;
+use crate::handlers::usage::is_admin;
+use crate::compliance::{ComplianceService, ErasureReport};
+use crate::api::{
+    api_success,
+    compliance::EraseUserRequest,
+    error::AppError,
+    ApiResponse,
+};
+
+/// Compliance routes
+pub fn compliance_routes() -> Router<Arc<AppState>> {
+    Router::new()
+        .route("/users/:id/export", get(export_user))
+        .route("/users/:id/erase", post(erase_user))
+}
+
+/// The compliance service over every store the server has
+fn compliance_service(state: &AppState) -> ComplianceService {
+    let mut service = ComplianceService::new().with_database(state.db.clone());
+    if let Some(ledger) = &state.usage_ledger {
+        service = service.with_usage_ledger(ledger.clone());
+    }
+    if let Some(assistant) = &state.assistant {
+        service = service.with_assistant(assistant.clone());
+    }
+    if let Some(contexts) = &state.context_manager {
+        service = service.with_context_manager(contexts.clone());
+    }
+    if let Some(result_cache) = &state.result_cache {
+        service = service.with_result_cache(result_cache.clone());
+    }
+    if let Some(flags) = &state.flags {
+        service = service.with_feature_flags(flags.clone());
+    }
+    if let Some(files) = &state.file_service {
+        service = service.with_file_service(files.clone());
+    }
+    if let Some(webhooks) = &state.webhook_service {
+        service = service.with_webhooks(webhooks.clone());
+    }
+    service
+}
+
+/// Download everything recorded about a user as a `.tar.gz` archive
+///
+/// Users export their own data; admins export anyone's.
+async fn export_user(
+    State(state): State<Arc<AppState>>,
+    Extension(user): Extension<AuthClaims>,
+    Path(id): Path<Uuid>,
+) -> Result<Response, AppError> {
+    let target = id.to_string();
+    let allowed = is_admin(&user) || user.sub == target;
+    let archive = state
+        .get_admin()?
+        .audited(&user.sub, allowed, "compliance.export", Some(&target), async {
+            compliance_service(&state).export(&target).await
+        })
+        .await?;
+
+    let mut response = archive.into_response();
+    let headers = response.headers_mut();
+    headers.insert(header::CONTENT_TYPE, HeaderValue::from_static("application/gzip"));
+    if let Ok(disposition) = HeaderValue::from_str(&format!("attachment; filename=\"squirrel-user-{}.tar.gz\"", id)) {
+        headers.insert(header::CONTENT_DISPOSITION, disposition);
+    }
+    Ok(response)
+}
+
+/// Anonymize or purge a user's data, reporting what is left in every store
+async fn erase_user(
+    State(state): State<Arc<AppState>>,
+    Extension(user): Extension<AuthClaims>,
+    Path(id): Path<Uuid>,
+    Json(request): Json<EraseUserRequest>,
+) -> Result<Json<ApiResponse<ErasureReport>>, AppError> {
+    let target = id.to_string();
+    let report = state
+        .get_admin()?
+        .audited(&user.sub, is_admin(&user), "compliance.erase", Some(&target), async {
+            compliance_service(&state).erase(&target, request.mode, request.dry_run).await
+        })
+        .await?;
+    if !report.dry_run {
+        if let Some(users) = &state.users {
+            users.forget_user(id);
+        }
+    }
+
+    Ok(api_success(report))
+}
@@ -0,0 +1,8 @@
+//! Contexts module for handling context API endpoints
+//!
+//! This module contains handlers for searching the contexts held by the
+//! MCP context manager.
+
+mod routes;
+
+pub use routes::context_routes;
@@ -0,0 +1,107 @@
+use axum::{
+    Router,
+    routing::get,
+    extract::{Extension, Query, State},
+    Json,
+};
+use serde_json::Value;
+use squirrel_mcp::context_manager::Context;
+use std::sync::Arc;
+use crate::state::AppState;
+use crate::auth::extractor::AuthClaims;
+use crate::handlers::usage::is_admin;
+use crate::api::{
+    api_success,
+    contexts::{ContextSearchQuery, ContextSearchResponse},
+    error::AppError,
+    ApiResponse,
+};
+
+/// Metadata keys naming the user a context belongs to: jobs record their
+/// `owner`, assistant conversations their `user`
+const OWNER_KEYS: &[&str] = &["owner", "user"];
+
+/// Context routes
+pub fn context_routes() -> Router<Arc<AppState>> {
+    Router::new()
+        .route("/search", get(search_contexts))
+}
+
+/// Whether `context` belongs to `user`
+fn owned_by(context: &Context, user: &str) -> bool {
+    let Some(metadata) = &context.metadata else {
+        return false;
+    };
+    OWNER_KEYS
+        .iter()
+        .any(|key| metadata.get(key).and_then(Value::as_str) == Some(user))
+}
+
+/// Search the caller's contexts by field values and full text; admins
+/// search every context
+async fn search_contexts(
+    State(state): State<Arc<AppState>>,
+    Extension(user): Extension<AuthClaims>,
+    Query(query): Query<ContextSearchQuery>,
+) -> Result<Json<ApiResponse<ContextSearchResponse>>, AppError> {
+    let context_manager = state.get_context_manager()?;
+    let mut contexts = context_manager
+        .search(&query.q)
+        .await
+        .map_err(|e| AppError::InvalidRequest(e.to_string()))?;
+    if !is_admin(&user) {
+        contexts.retain(|context| owned_by(context, &user.sub));
+    }
+    let total = contexts.len();
+    contexts.truncate(query.limit);
+
+    Ok(api_success(ContextSearchResponse { contexts, total }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::Utc;
+    use serde_json::json;
+    use squirrel_mcp::context_manager::ContextManager;
+    use uuid::Uuid;
+    use crate::test_helpers::claims;
+
+    fn context(metadata: Value) -> Context {
+        let now = Utc::now();
+        Context {
+            id: Uuid::new_v4(),
+            name: "genome notes".to_string(),
+            data: json!({}),
+            metadata: Some(metadata),
+            parent_id: None,
+            created_at: now,
+            updated_at: now,
+            expires_at: None,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_users_only_find_their_own_contexts() {
+        let contexts = ContextManager::new().await;
+        for metadata in [json!({ "owner": "ada" }), json!({ "user": "ada" }), json!({ "owner": "grace" }), json!({})] {
+            contexts.create_context(context(metadata)).await.unwrap();
+        }
+        let state = Arc::new(AppState {
+            context_manager: Some(Arc::new(contexts)),
+            ..AppState::default()
+        });
+        let search = |user| {
+            let query = ContextSearchQuery { q: "genome".to_string(), limit: 50 };
+            search_contexts(State(state.clone()), Extension(user), Query(query))
+        };
+
+        let Json(response) = search(claims("ada", &["User"])).await.unwrap();
+        let found = response.data.unwrap();
+        assert_eq!(found.total, 2);
+        assert!(found.contexts.iter().all(|context| owned_by(context, "ada")));
+
+        let Json(response) = search(claims("root", &["Admin"])).await.unwrap();
+        assert_eq!(response.data.unwrap().total, 4);
+    }
+}
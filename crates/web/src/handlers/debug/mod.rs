@@ -0,0 +1,8 @@
+//! Debug module for handling profiling API endpoints
+//!
+//! This module contains the handler returning memory and task profiles of
+//! the running server.
+
+mod routes;
+
+pub use routes::debug_routes;
@@ -0,0 +1,53 @@
+use axum::{
+    Router,
+    routing::get,
+    extract::Extension,
+    Json,
+};
+use std::sync::Arc;
+use crate::state::AppState;
+use crate::auth::extractor::AuthClaims;
+use crate::handlers::usage::is_admin;
+use crate::profiling::{self, ProfileSnapshot};
+use crate::api::{
+    api_success,
+    error::AppError,
+    ApiResponse,
+};
+
+/// Debug routes
+pub fn debug_routes() -> Router<Arc<AppState>> {
+    Router::new()
+        .route("/profile", get(get_profile))
+}
+
+/// Get the resident memory, Tokio runtime usage and, in builds with the
+/// `profiling` feature, heap allocation counts of the server
+///
+/// Only admins may profile the server. Profiles are not audited, so they
+/// can be taken at short intervals.
+async fn get_profile(
+    Extension(user): Extension<AuthClaims>,
+) -> Result<Json<ApiResponse<ProfileSnapshot>>, AppError> {
+    if !is_admin(&user) {
+        return Err(AppError::Forbidden("Only admins may profile the server".to_string()));
+    }
+
+    Ok(api_success(profiling::snapshot()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_helpers::claims;
+
+    #[tokio::test]
+    async fn test_only_admins_profile_the_server() {
+        let refused = get_profile(Extension(claims("mallory", &["User"]))).await;
+        assert!(matches!(refused, Err(AppError::Forbidden(_))));
+
+        let Json(response) = get_profile(Extension(claims("root", &["Admin"]))).await.unwrap();
+        let profile = response.data.unwrap();
+        assert!(profile.runtime.is_some());
+    }
+}
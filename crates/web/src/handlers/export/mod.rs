@@ -0,0 +1,8 @@
+//! Export module for handling data export API endpoints
+//!
+//! This module contains the handler streaming CSV and Parquet extracts of
+//! jobs, command history, usage and experiments for analysis.
+
+mod routes;
+
+pub use routes::export;
@@ -0,0 +1,52 @@
+use axum::{
+    body::StreamBody,
+    extract::{Query, State, Extension},
+    http::{header, HeaderValue},
+    response::{IntoResponse, Response},
+};
+use std::sync::Arc;
+use crate::state::AppState;
+use crate::auth::extractor::AuthClaims;
+use crate::handlers::usage::is_admin;
+use crate::api::{
+    export::ExportQueryParams,
+    error::AppError,
+};
+use crate::export::ExportRequest;
+
+/// Stream an extract of a dataset over a date range
+///
+/// Users export their own rows; admins export everyone's unless they pick
+/// a user.
+pub async fn export(
+    State(state): State<Arc<AppState>>,
+    Extension(user): Extension<AuthClaims>,
+    Query(params): Query<ExportQueryParams>,
+) -> Result<Response, AppError> {
+    let exporter = state.get_exporter()?;
+    let subject = match params.user {
+        Some(name) if name != user.sub && !is_admin(&user) => {
+            return Err(AppError::Forbidden("Only admins can export other users' data".to_string()));
+        }
+        Some(name) => Some(name),
+        None if is_admin(&user) => None,
+        None => Some(user.sub.clone()),
+    };
+    let request = ExportRequest {
+        dataset: params.dataset,
+        format: params.format,
+        user: subject,
+        from: params.from,
+        to: params.to,
+    };
+    let file_name = request.file_name();
+    let stream = exporter.export(request)?;
+
+    let mut response = StreamBody::new(stream).into_response();
+    let headers = response.headers_mut();
+    headers.insert(header::CONTENT_TYPE, HeaderValue::from_static(params.format.content_type()));
+    if let Ok(disposition) = HeaderValue::from_str(&format!("attachment; filename=\"{}\"", file_name)) {
+        headers.insert(header::CONTENT_DISPOSITION, disposition);
+    }
+    Ok(response)
+}
@@ -0,0 +1,12 @@
+//! Files module for handling file transfer API endpoints
+//!
+//! This module contains handlers for resumable uploads and streamed
+//! downloads of files in the workspace.
+
+pub mod service;
+
+pub use service::{create_file_service, FileService};
+
+mod routes;
+
+pub use routes::file_routes;
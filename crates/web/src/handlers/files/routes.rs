@@ -0,0 +1,196 @@
+use axum::{
+    Router,
+    routing::{get, post},
+    body::StreamBody,
+    extract::{BodyStream, Path, Query, State, Extension},
+    http::{header, HeaderMap, HeaderValue, StatusCode},
+    response::{IntoResponse, Response},
+    Json,
+};
+use std::io::SeekFrom;
+use std::sync::Arc;
+use tokio::io::{AsyncReadExt, AsyncSeekExt};
+use tokio_util::io::ReaderStream;
+use squirrel_monitoring::accounting::ResourceUsage;
+use crate::state::AppState;
+use crate::auth::extractor::AuthClaims;
+use crate::handlers::usage::{enforce_quotas, is_admin, record_usage, workspace};
+use crate::api::{
+    api_success,
+    files::{CreateUploadRequest, FileInfoResponse, FileQuery, StorageUsageResponse, UploadResponse, UploadState},
+    error::AppError,
+    ApiResponse,
+};
+use super::service::{ByteRange, Caller, ContentRange};
+
+/// Header carrying the SHA-256 of a single upload chunk
+const CHUNK_SHA256_HEADER: &str = "x-chunk-sha256";
+
+/// File routes
+pub fn file_routes() -> Router<Arc<AppState>> {
+    Router::new()
+        .route("/uploads", post(create_upload))
+        .route("/uploads/:id", get(get_upload).put(upload_chunk).delete(cancel_upload))
+        .route("/download", get(download_file))
+        .route("/info", get(get_file_info))
+        .route("/usage", get(get_storage_usage))
+}
+
+/// The caller files are read and written for
+fn caller(user: &AuthClaims) -> Caller<'_> {
+    Caller {
+        user: &user.sub,
+        admin: is_admin(user),
+    }
+}
+
+/// Start a resumable upload, unless the user or workspace used up a quota
+async fn create_upload(
+    State(state): State<Arc<AppState>>,
+    Extension(user): Extension<AuthClaims>,
+    headers: HeaderMap,
+    Json(payload): Json<CreateUploadRequest>,
+) -> Result<Json<ApiResponse<UploadResponse>>, AppError> {
+    let file_service = state.get_file_service()?;
+    let workspace = workspace(&headers);
+    enforce_quotas(&state, &user.sub, &workspace)?;
+    let upload = file_service.create(caller(&user), payload).await?;
+    charge_completed(&state, &user.sub, &workspace, &upload);
+
+    Ok(api_success(upload))
+}
+
+/// Get the state and offset of an upload
+async fn get_upload(
+    State(state): State<Arc<AppState>>,
+    Extension(user): Extension<AuthClaims>,
+    Path(id): Path<String>,
+) -> Result<Json<ApiResponse<UploadResponse>>, AppError> {
+    let file_service = state.get_file_service()?;
+    let upload = file_service.status(&user.sub, &id).await?;
+
+    Ok(api_success(upload))
+}
+
+/// Append a chunk described by its `Content-Range` header to an upload
+async fn upload_chunk(
+    State(state): State<Arc<AppState>>,
+    Extension(user): Extension<AuthClaims>,
+    Path(id): Path<String>,
+    headers: HeaderMap,
+    body: BodyStream,
+) -> Result<Json<ApiResponse<UploadResponse>>, AppError> {
+    let file_service = state.get_file_service()?;
+    let range = headers
+        .get(header::CONTENT_RANGE)
+        .and_then(|value| value.to_str().ok())
+        .ok_or_else(|| AppError::InvalidRequest("Missing Content-Range header".to_string()))?;
+    let range = ContentRange::parse(range)?;
+    let chunk_sha256 = headers
+        .get(CHUNK_SHA256_HEADER)
+        .map(|value| value.to_str().map_err(|_| AppError::InvalidRequest("Invalid X-Chunk-Sha256 header".to_string())))
+        .transpose()?;
+
+    let upload = file_service.append(&user.sub, &id, range, chunk_sha256, body).await?;
+    charge_completed(&state, &user.sub, &workspace(&headers), &upload);
+
+    Ok(api_success(upload))
+}
+
+/// Charge the bytes of a completed upload to the user's storage usage
+fn charge_completed(state: &AppState, user: &str, workspace: &str, upload: &UploadResponse) {
+    if upload.state == UploadState::Completed {
+        record_usage(state, user, workspace, ResourceUsage::storage(upload.size));
+    }
+}
+
+/// Cancel an upload and discard its bytes
+async fn cancel_upload(
+    State(state): State<Arc<AppState>>,
+    Extension(user): Extension<AuthClaims>,
+    Path(id): Path<String>,
+) -> Result<Json<ApiResponse<()>>, AppError> {
+    let file_service = state.get_file_service()?;
+    file_service.cancel(&user.sub, &id).await?;
+
+    Ok(api_success(()))
+}
+
+/// Get the size and checksum of a file the caller may read
+async fn get_file_info(
+    State(state): State<Arc<AppState>>,
+    Extension(user): Extension<AuthClaims>,
+    Query(query): Query<FileQuery>,
+) -> Result<Json<ApiResponse<FileInfoResponse>>, AppError> {
+    let file_service = state.get_file_service()?;
+    let info = file_service.info(caller(&user), &query.path).await?;
+
+    Ok(api_success(info))
+}
+
+/// Get the space used by the blob store
+async fn get_storage_usage(
+    State(state): State<Arc<AppState>>,
+    Extension(_user): Extension<AuthClaims>,
+) -> Result<Json<ApiResponse<StorageUsageResponse>>, AppError> {
+    let file_service = state.get_file_service()?;
+    let usage = file_service.usage().await?;
+
+    Ok(api_success(usage.into()))
+}
+
+/// Stream a file the caller may read, or the part of it selected by a
+/// `Range` header
+async fn download_file(
+    State(state): State<Arc<AppState>>,
+    Extension(user): Extension<AuthClaims>,
+    Query(query): Query<FileQuery>,
+    headers: HeaderMap,
+) -> Result<Response, AppError> {
+    let file_service = state.get_file_service()?;
+    let (mut file, size) = file_service.open_file(caller(&user), &query.path).await?;
+
+    let range = headers
+        .get(header::RANGE)
+        .and_then(|value| value.to_str().ok())
+        .map(ByteRange::parse)
+        .transpose()?;
+    let (status, start, end) = match range {
+        None => (StatusCode::OK, 0, size.saturating_sub(1)),
+        Some(range) => match range.resolve(size) {
+            Some((start, end)) => (StatusCode::PARTIAL_CONTENT, start, end),
+            None => {
+                let content_range = format!("bytes */{}", size);
+                return Ok((
+                    StatusCode::RANGE_NOT_SATISFIABLE,
+                    [(header::CONTENT_RANGE, content_range)],
+                ).into_response());
+            }
+        },
+    };
+    let length = if size == 0 { 0 } else { end - start + 1 };
+
+    file.seek(SeekFrom::Start(start))
+        .await
+        .map_err(|e| AppError::Internal(format!("Failed to read file: {}", e)))?;
+    let body = StreamBody::new(ReaderStream::new(file.take(length)));
+
+    let file_name = query.path.rsplit('/').next().unwrap_or_default().replace('"', "");
+    let mut response = (status, body).into_response();
+    let response_headers = response.headers_mut();
+    response_headers.insert(header::CONTENT_TYPE, HeaderValue::from_static("application/octet-stream"));
+    response_headers.insert(header::CONTENT_LENGTH, HeaderValue::from(length));
+    response_headers.insert(header::ACCEPT_RANGES, HeaderValue::from_static("bytes"));
+    if let Ok(disposition) = HeaderValue::from_str(&format!("attachment; filename=\"{}\"", file_name)) {
+        response_headers.insert(header::CONTENT_DISPOSITION, disposition);
+    }
+    if status == StatusCode::PARTIAL_CONTENT {
+        let content_range = format!("bytes {}-{}/{}", start, end, size);
+        response_headers.insert(
+            header::CONTENT_RANGE,
+            HeaderValue::from_str(&content_range).expect("content range is a valid header"),
+        );
+    }
+
+    Ok(response)
+}
@@ -0,0 +1,1019 @@
+//! File transfer service implementation
+//!
+//! Large files are uploaded in chunks. A client starts an upload with the
+//! target path, total size and optionally the SHA-256 of the file, then
+//! sends the bytes in one or more `PUT` requests carrying a
+//! `Content-Range: bytes <start>-<end>/<total>` header. Each chunk must start
+//! at the upload's current offset, so after a dropped connection the client
+//! asks for the offset and continues from there. Bytes of an interrupted
+//! chunk that reached the disk are kept, unless the chunk carried its own
+//! `X-Chunk-Sha256` checksum.
+//!
+//! Partial uploads live in the upload directory as `<id>.part`, next to a
+//! `<id>.json` record, so they survive a server restart. When the last byte
+//! arrives the file is hashed, checked against the expected checksum and
+//! moved into the blob store, which links it at its virtual path. Identical
+//! uploads therefore share one blob. Without a blob store the file is moved
+//! to its path directly. Cached command results that read the replaced file
+//! are invalidated.
+//!
+//! The user who uploads a file owns it. Only the owner and admins can
+//! download, inspect or replace an owned file. Files nobody uploaded, such
+//! as datasets and job outputs, can be read by every user but replaced only
+//! by admins. Owners are kept in `owners.json` in the upload directory.
+
+use std::collections::{HashMap, HashSet};
+use std::io::ErrorKind;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex, PoisonError, RwLock};
+
+use axum::body::Bytes;
+use axum::http::StatusCode;
+use chrono::{DateTime, Duration, Utc};
+use futures::{Stream, StreamExt};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use squirrel_commands::cache::ResultCache;
+use squirrel_core::blob::{self, BlobError, BlobStore, GcReport, StorageUsage};
+use squirrel_core::vfs::{Access, RootAccess, Vfs, VfsError, DATA_ROOT, WORKSPACE_ROOT};
+use squirrel_monitoring::metrics::{Metric, MetricCollector, MetricType};
+use tokio::fs::{self, File, OpenOptions};
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tracing::{debug, warn};
+use uuid::Uuid;
+
+use crate::api::error::AppError;
+use crate::api::files::{CreateUploadRequest, FileInfoResponse, UploadResponse, UploadState};
+use crate::config::FilesConfig;
+
+/// Size of the buffer files are hashed with
+const HASH_BUFFER_SIZE: usize = 1024 * 1024;
+
+/// File in the upload directory recording who owns each uploaded file
+const OWNERS_FILE: &str = "owners.json";
+
+/// The user a file is read or written for
+#[derive(Debug, Clone, Copy)]
+pub struct Caller<'a> {
+    /// User ID
+    pub user: &'a str,
+    /// Whether the user may access every user's files
+    pub admin: bool,
+}
+
+impl<'a> Caller<'a> {
+    /// A caller without admin rights
+    pub fn user(user: &'a str) -> Self {
+        Self { user, admin: false }
+    }
+}
+
+/// A `Content-Range` header of an upload chunk
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ContentRange {
+    /// Offset of the first byte
+    pub start: u64,
+    /// Offset of the last byte, inclusive
+    pub end: u64,
+    /// Total size of the file
+    pub total: u64,
+}
+
+impl ContentRange {
+    /// Parse `bytes <start>-<end>/<total>`
+    pub fn parse(value: &str) -> Result<Self, AppError> {
+        let invalid = || AppError::InvalidRequest(format!("Invalid Content-Range: {}", value));
+        let range = value.trim().strip_prefix("bytes ").ok_or_else(invalid)?;
+        let (span, total) = range.split_once('/').ok_or_else(invalid)?;
+        let (start, end) = span.split_once('-').ok_or_else(invalid)?;
+        let range = Self {
+            start: start.trim().parse().map_err(|_| invalid())?,
+            end: end.trim().parse().map_err(|_| invalid())?,
+            total: total.trim().parse().map_err(|_| invalid())?,
+        };
+        if range.start > range.end || range.end >= range.total {
+            return Err(invalid());
+        }
+        Ok(range)
+    }
+
+    /// Number of bytes in the range
+    pub fn len(&self) -> u64 {
+        self.end - self.start + 1
+    }
+}
+
+/// A `Range` header of a download
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ByteRange {
+    /// From an offset to the end of the file
+    From(u64),
+    /// Between two offsets, inclusive
+    Between(u64, u64),
+    /// The last bytes of the file
+    Suffix(u64),
+}
+
+impl ByteRange {
+    /// Parse `bytes=<start>-`, `bytes=<start>-<end>` or `bytes=-<length>`
+    ///
+    /// Requests for several ranges are rejected.
+    pub fn parse(value: &str) -> Result<Self, AppError> {
+        let invalid = || AppError::InvalidRequest(format!("Invalid or unsupported Range: {}", value));
+        let range = value.trim().strip_prefix("bytes=").ok_or_else(invalid)?;
+        if range.contains(',') {
+            return Err(invalid());
+        }
+        let (start, end) = range.split_once('-').ok_or_else(invalid)?;
+        let number = |s: &str| s.trim().parse::<u64>().map_err(|_| invalid());
+        match (start.trim().is_empty(), end.trim().is_empty()) {
+            (false, true) => Ok(Self::From(number(start)?)),
+            (false, false) => Ok(Self::Between(number(start)?, number(end)?)),
+            (true, false) => Ok(Self::Suffix(number(end)?)),
+            (true, true) => Err(invalid()),
+        }
+    }
+
+    /// The first and last offset of the range in a file of `size` bytes,
+    /// or `None` if the range cannot be satisfied
+    pub fn resolve(&self, size: u64) -> Option<(u64, u64)> {
+        let (start, end) = match *self {
+            Self::From(start) => (start, size.checked_sub(1)?),
+            Self::Between(start, end) => (start, end.min(size.checked_sub(1)?)),
+            Self::Suffix(0) => return None,
+            Self::Suffix(length) => (size.saturating_sub(length), size.checked_sub(1)?),
+        };
+        (start <= end).then_some((start, end))
+    }
+}
+
+/// A resumable upload
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Upload {
+    /// Upload ID
+    pub id: String,
+    /// User who started the upload
+    pub owner: String,
+    /// Virtual path the file is stored at
+    pub path: String,
+    /// Total size of the file in bytes
+    pub size: u64,
+    /// Expected SHA-256 of the file, as lowercase hex
+    pub sha256: Option<String>,
+    /// Whether an existing file at `path` is replaced
+    pub overwrite: bool,
+    /// Whether the upload was started by an admin, who may replace other
+    /// users' files
+    #[serde(default)]
+    pub admin: bool,
+    /// Created timestamp
+    pub created_at: DateTime<Utc>,
+    /// Time after which an unfinished upload is discarded
+    pub expires_at: DateTime<Utc>,
+    /// SHA-256 of the stored file, once completed
+    #[serde(default)]
+    pub completed_sha256: Option<String>,
+}
+
+impl Upload {
+    fn response(&self, offset: u64) -> UploadResponse {
+        UploadResponse {
+            id: self.id.clone(),
+            path: self.path.clone(),
+            size: self.size,
+            offset,
+            state: if self.completed_sha256.is_some() {
+                UploadState::Completed
+            } else {
+                UploadState::Pending
+            },
+            sha256: self.completed_sha256.clone(),
+            expires_at: self.expires_at,
+        }
+    }
+}
+
+/// Map a VFS error to the API error it is reported as
+pub fn vfs_error(error: VfsError) -> AppError {
+    match error {
+        VfsError::InvalidPath(_) | VfsError::UnknownRoot(_) => AppError::InvalidRequest(error.to_string()),
+        VfsError::Traversal(_) | VfsError::PermissionDenied { .. } => AppError::Forbidden(error.to_string()),
+        VfsError::Io { ref source, .. } if source.kind() == ErrorKind::NotFound => {
+            AppError::NotFound(error.to_string())
+        }
+        VfsError::Io { .. } => AppError::Internal(error.to_string()),
+    }
+}
+
+/// Map a blob store error to the API error it is reported as
+fn blob_error(error: BlobError) -> AppError {
+    match error {
+        BlobError::InvalidDigest(_) => AppError::InvalidRequest(error.to_string()),
+        _ => AppError::Internal(error.to_string()),
+    }
+}
+
+fn io_error(context: &str, error: std::io::Error) -> AppError {
+    AppError::Internal(format!("{}: {}", context, error))
+}
+
+/// SHA-256 of a file, as lowercase hex
+pub async fn hash_file(path: &Path) -> std::io::Result<String> {
+    let mut file = File::open(path).await?;
+    let mut hasher = Sha256::new();
+    let mut buffer = vec![0u8; HASH_BUFFER_SIZE];
+    loop {
+        let read = file.read(&mut buffer).await?;
+        if read == 0 {
+            break;
+        }
+        hasher.update(&buffer[..read]);
+    }
+    Ok(hex::encode(hasher.finalize()))
+}
+
+/// Normalize a hex SHA-256, rejecting anything else
+fn parse_sha256(value: &str) -> Result<String, AppError> {
+    blob::parse_digest(value).map_err(|_| AppError::InvalidRequest(format!("Invalid SHA-256 checksum: {}", value)))
+}
+
+/// Marks an upload as receiving a chunk until dropped
+struct ChunkGuard<'a> {
+    active: &'a Mutex<HashSet<String>>,
+    id: String,
+}
+
+impl Drop for ChunkGuard<'_> {
+    fn drop(&mut self) {
+        self.active.lock().unwrap_or_else(PoisonError::into_inner).remove(&self.id);
+    }
+}
+
+/// Service for resumable uploads and downloads
+pub struct FileService {
+    vfs: Vfs,
+    config: FilesConfig,
+    uploads: RwLock<HashMap<String, Upload>>,
+    active: Mutex<HashSet<String>>,
+    owners: tokio::sync::Mutex<HashMap<PathBuf, String>>,
+    blobs: Option<BlobStore>,
+    metrics: Option<Arc<dyn MetricCollector>>,
+    result_cache: Option<Arc<ResultCache>>,
+}
+
+impl FileService {
+    /// Create a service, picking up the unfinished uploads in the upload
+    /// directory
+    pub fn open(config: FilesConfig) -> std::io::Result<Self> {
+        let vfs = Vfs::new()
+            .with_root(WORKSPACE_ROOT, &config.workspace_dir, RootAccess::ReadWrite)
+            .with_root(DATA_ROOT, &config.data_dir, RootAccess::ReadOnly);
+        Self::with_vfs(config, vfs)
+    }
+
+    /// Create a service storing files in `vfs`
+    pub fn with_vfs(config: FilesConfig, vfs: Vfs) -> std::io::Result<Self> {
+        std::fs::create_dir_all(&config.upload_dir)?;
+        let blobs = config
+            .blob_dir
+            .as_ref()
+            .map(BlobStore::open)
+            .transpose()
+            .map_err(std::io::Error::other)?;
+        let owners = match std::fs::read(config.upload_dir.join(OWNERS_FILE)) {
+            Ok(data) => serde_json::from_slice(&data).map_err(std::io::Error::other)?,
+            Err(e) if e.kind() == ErrorKind::NotFound => HashMap::new(),
+            Err(e) => return Err(e),
+        };
+        let mut uploads = HashMap::new();
+        for entry in std::fs::read_dir(&config.upload_dir)? {
+            let path = entry?.path();
+            if path.extension().and_then(|e| e.to_str()) != Some("json")
+                || path.file_name().and_then(|name| name.to_str()) == Some(OWNERS_FILE)
+            {
+                continue;
+            }
+            match std::fs::read(&path).map(|data| serde_json::from_slice::<Upload>(&data)) {
+                Ok(Ok(upload)) => {
+                    uploads.insert(upload.id.clone(), upload);
+                }
+                Ok(Err(e)) => warn!("Ignoring unreadable upload record {:?}: {}", path, e),
+                Err(e) => warn!("Failed to read upload record {:?}: {}", path, e),
+            }
+        }
+        debug!("Resuming {} uploads from {:?}", uploads.len(), config.upload_dir);
+
+        Ok(Self {
+            vfs,
+            config,
+            uploads: RwLock::new(uploads),
+            active: Mutex::new(HashSet::new()),
+            owners: tokio::sync::Mutex::new(owners),
+            blobs,
+            metrics: None,
+            result_cache: None,
+        })
+    }
+
+    /// Report storage usage to `metrics` as uploads complete
+    pub fn with_metrics(mut self, metrics: Option<Arc<dyn MetricCollector>>) -> Self {
+        self.metrics = metrics;
+        self
+    }
+
+    /// Invalidate cached results that read a file when an upload replaces it
+    pub fn with_result_cache(mut self, result_cache: Option<Arc<ResultCache>>) -> Self {
+        self.result_cache = result_cache;
+        self
+    }
+
+    /// Host path of the file at virtual path `path`
+    pub fn resolve(&self, path: &str) -> Result<PathBuf, AppError> {
+        self.vfs.resolve(path, Access::Read).map_err(vfs_error)
+    }
+
+    fn part_path(&self, id: &str) -> PathBuf {
+        self.config.upload_dir.join(format!("{}.part", id))
+    }
+
+    fn record_path(&self, id: &str) -> PathBuf {
+        self.config.upload_dir.join(format!("{}.json", id))
+    }
+
+    /// Number of bytes received for an upload
+    async fn offset(&self, id: &str) -> Result<u64, AppError> {
+        match fs::metadata(self.part_path(id)).await {
+            Ok(metadata) => Ok(metadata.len()),
+            Err(e) if e.kind() == ErrorKind::NotFound => Ok(0),
+            Err(e) => Err(io_error("Failed to read upload", e)),
+        }
+    }
+
+    /// Refuse `caller` access to the file at `path`, stored at `host_path`,
+    /// unless they own it or are an admin; files nobody owns can be read by
+    /// everyone and replaced only by admins
+    async fn authorize(&self, caller: Caller<'_>, path: &str, host_path: &Path, access: Access) -> Result<(), AppError> {
+        if caller.admin {
+            return Ok(());
+        }
+        let allowed = match self.owners.lock().await.get(host_path) {
+            Some(owner) => owner == caller.user,
+            None => access == Access::Read || !fs::try_exists(host_path).await.unwrap_or(false),
+        };
+        if allowed {
+            return Ok(());
+        }
+        match access {
+            Access::Read => Err(AppError::NotFound(format!("File not found: {}", path))),
+            Access::Write => Err(AppError::Forbidden(format!("File belongs to another user: {}", path))),
+        }
+    }
+
+    /// Record `owner` as the owner of the file stored at `host_path`
+    async fn set_owner(&self, host_path: &Path, owner: &str) -> Result<(), AppError> {
+        let mut owners = self.owners.lock().await;
+        if owners.get(host_path).map(String::as_str) == Some(owner) {
+            return Ok(());
+        }
+        owners.insert(host_path.to_path_buf(), owner.to_string());
+        let data = serde_json::to_vec(&*owners).map_err(|e| AppError::Internal(e.to_string()))?;
+        fs::write(self.config.upload_dir.join(OWNERS_FILE), data)
+            .await
+            .map_err(|e| io_error("Failed to record file owner", e))
+    }
+
+    fn get(&self, owner: &str, id: &str) -> Result<Upload, AppError> {
+        self.uploads
+            .read()
+            .unwrap_or_else(PoisonError::into_inner)
+            .get(id)
+            .filter(|upload| upload.owner == owner)
+            .cloned()
+            .ok_or_else(|| AppError::NotFound(format!("Upload not found: {}", id)))
+    }
+
+    /// Remove the files and record of an upload
+    async fn discard(&self, id: &str) {
+        self.uploads.write().unwrap_or_else(PoisonError::into_inner).remove(id);
+        for path in [self.part_path(id), self.record_path(id)] {
+            if let Err(e) = fs::remove_file(&path).await {
+                if e.kind() != ErrorKind::NotFound {
+                    warn!("Failed to remove {:?}: {}", path, e);
+                }
+            }
+        }
+    }
+
+    /// Discard unfinished uploads that have expired
+    async fn purge_expired(&self) {
+        let now = Utc::now();
+        let expired: Vec<String> = self
+            .uploads
+            .read()
+            .unwrap_or_else(PoisonError::into_inner)
+            .values()
+            .filter(|upload| upload.completed_sha256.is_none() && upload.expires_at <= now)
+            .map(|upload| upload.id.clone())
+            .collect();
+        for id in expired {
+            debug!("Discarding expired upload {}", id);
+            self.discard(&id).await;
+        }
+    }
+
+    /// Start an upload
+    pub async fn create(&self, caller: Caller<'_>, request: CreateUploadRequest) -> Result<UploadResponse, AppError> {
+        self.purge_expired().await;
+
+        if request.size > self.config.max_file_size {
+            return Err(AppError::Custom(
+                StatusCode::PAYLOAD_TOO_LARGE,
+                format!("File size {} exceeds the limit of {} bytes", request.size, self.config.max_file_size),
+            ));
+        }
+        let sha256 = request.sha256.as_deref().map(parse_sha256).transpose()?;
+        let target = self.vfs.resolve(&request.path, Access::Write).map_err(vfs_error)?;
+        if !request.overwrite && fs::try_exists(&target).await.unwrap_or(false) {
+            return Err(AppError::Conflict(format!("File already exists: {}", request.path)));
+        }
+        self.authorize(caller, &request.path, &target, Access::Write).await?;
+
+        let now = Utc::now();
+        let upload = Upload {
+            id: Uuid::new_v4().to_string(),
+            owner: caller.user.to_string(),
+            path: request.path,
+            size: request.size,
+            sha256,
+            overwrite: request.overwrite,
+            admin: caller.admin,
+            created_at: now,
+            expires_at: now + Duration::seconds(self.config.upload_expiry_secs as i64),
+            completed_sha256: None,
+        };
+
+        File::create(self.part_path(&upload.id))
+            .await
+            .map_err(|e| io_error("Failed to create upload", e))?;
+        let record = serde_json::to_vec(&upload).map_err(|e| AppError::Internal(e.to_string()))?;
+        fs::write(self.record_path(&upload.id), record)
+            .await
+            .map_err(|e| io_error("Failed to record upload", e))?;
+        self.uploads.write().unwrap_or_else(PoisonError::into_inner).insert(upload.id.clone(), upload.clone());
+
+        if upload.size == 0 {
+            return self.complete(upload).await;
+        }
+        Ok(upload.response(0))
+    }
+
+    /// State of an upload
+    pub async fn status(&self, owner: &str, id: &str) -> Result<UploadResponse, AppError> {
+        let upload = self.get(owner, id)?;
+        let offset = if upload.completed_sha256.is_some() {
+            upload.size
+        } else {
+            self.offset(id).await?
+        };
+        Ok(upload.response(offset))
+    }
+
+    /// Cancel an unfinished upload
+    pub async fn cancel(&self, owner: &str, id: &str) -> Result<(), AppError> {
+        self.get(owner, id)?;
+        if self.active.lock().unwrap_or_else(PoisonError::into_inner).contains(id) {
+            return Err(AppError::Conflict(format!("Upload {} is receiving a chunk", id)));
+        }
+        self.discard(id).await;
+        Ok(())
+    }
+
+    /// The uploads `owner` started, oldest first
+    pub fn uploads_of(&self, owner: &str) -> Vec<Upload> {
+        let mut uploads: Vec<Upload> = self
+            .uploads
+            .read()
+            .unwrap_or_else(PoisonError::into_inner)
+            .values()
+            .filter(|upload| upload.owner == owner)
+            .cloned()
+            .collect();
+        uploads.sort_by_key(|upload| upload.created_at);
+        uploads
+    }
+
+    /// Discard the records and received data of the uploads `owner`
+    /// started, returning how many were discarded
+    ///
+    /// Files that completed uploads stored stay in the workspace.
+    pub async fn discard_uploads_of(&self, owner: &str) -> usize {
+        let uploads = self.uploads_of(owner);
+        for upload in &uploads {
+            self.discard(&upload.id).await;
+        }
+        uploads.len()
+    }
+
+    /// Append a chunk to an upload
+    ///
+    /// The chunk must start at the current offset and, if `chunk_sha256` is
+    /// given, hash to it. The upload completes when its last byte arrives.
+    pub async fn append<S, E>(
+        &self,
+        owner: &str,
+        id: &str,
+        range: ContentRange,
+        chunk_sha256: Option<&str>,
+        mut body: S,
+    ) -> Result<UploadResponse, AppError>
+    where
+        S: Stream<Item = Result<Bytes, E>> + Unpin,
+        E: std::fmt::Display,
+    {
+        let upload = self.get(owner, id)?;
+        if upload.completed_sha256.is_some() {
+            return Err(AppError::Conflict(format!("Upload {} is already completed", id)));
+        }
+        if range.total != upload.size {
+            return Err(AppError::InvalidRequest(format!(
+                "Content-Range total {} does not match the upload size {}",
+                range.total, upload.size
+            )));
+        }
+        let chunk_sha256 = chunk_sha256.map(parse_sha256).transpose()?;
+
+        if !self.active.lock().unwrap_or_else(PoisonError::into_inner).insert(id.to_string()) {
+            return Err(AppError::Conflict(format!("Upload {} is already receiving a chunk", id)));
+        }
+        let _guard = ChunkGuard {
+            active: &self.active,
+            id: id.to_string(),
+        };
+
+        let offset = self.offset(id).await?;
+        if range.start != offset {
+            return Err(AppError::Conflict(format!(
+                "Chunk starts at byte {} but the upload is at offset {}",
+                range.start, offset
+            )));
+        }
+
+        let part_path = self.part_path(id);
+        let mut file = OpenOptions::new()
+            .append(true)
+            .open(&part_path)
+            .await
+            .map_err(|e| io_error("Failed to open upload", e))?;
+        let mut hasher = Sha256::new();
+        let mut received = 0u64;
+        let mut failure = None;
+        while let Some(chunk) = body.next().await {
+            let chunk = match chunk {
+                Ok(chunk) => chunk,
+                Err(e) => {
+                    failure = Some(AppError::InvalidRequest(format!(
+                        "Upload interrupted after {} bytes: {}",
+                        received, e
+                    )));
+                    break;
+                }
+            };
+            received += chunk.len() as u64;
+            if received > range.len() {
+                failure = Some(AppError::InvalidRequest(format!(
+                    "Chunk is longer than its Content-Range of {} bytes",
+                    range.len()
+                )));
+                break;
+            }
+            hasher.update(&chunk);
+            if let Err(e) = file.write_all(&chunk).await {
+                failure = Some(io_error("Failed to write upload", e));
+                break;
+            }
+        }
+        file.flush().await.map_err(|e| io_error("Failed to write upload", e))?;
+
+        if failure.is_none() && received < range.len() {
+            failure = Some(AppError::InvalidRequest(format!(
+                "Chunk is shorter than its Content-Range: received {} of {} bytes",
+                received,
+                range.len()
+            )));
+        }
+        if failure.is_none() {
+            if let Some(expected) = &chunk_sha256 {
+                let actual = hex::encode(hasher.finalize());
+                if &actual != expected {
+                    failure = Some(AppError::Custom(
+                        StatusCode::UNPROCESSABLE_ENTITY,
+                        format!("Chunk checksum mismatch: expected {}, got {}", expected, actual),
+                    ));
+                }
+            }
+        }
+
+        if let Some(error) = failure {
+            // Keep what arrived of an interrupted chunk unless the chunk
+            // cannot be trusted, so the client can resume where it stopped
+            if received > range.len() || chunk_sha256.is_some() {
+                file.set_len(offset).await.map_err(|e| io_error("Failed to roll back upload", e))?;
+            }
+            return Err(error);
+        }
+        drop(file);
+
+        if range.end + 1 == upload.size {
+            return self.complete(upload).await;
+        }
+        Ok(upload.response(offset + received))
+    }
+
+    /// Verify a fully received upload and move it to its path
+    async fn complete(&self, mut upload: Upload) -> Result<UploadResponse, AppError> {
+        let part_path = self.part_path(&upload.id);
+        let sha256 = hash_file(&part_path)
+            .await
+            .map_err(|e| io_error("Failed to hash upload", e))?;
+        if let Some(expected) = &upload.sha256 {
+            if &sha256 != expected {
+                self.discard(&upload.id).await;
+                return Err(AppError::Custom(
+                    StatusCode::UNPROCESSABLE_ENTITY,
+                    format!("Checksum mismatch: expected {}, got {}; the upload was discarded", expected, sha256),
+                ));
+            }
+        }
+
+        let target = self.vfs.resolve(&upload.path, Access::Write).map_err(vfs_error)?;
+        if !upload.overwrite && fs::try_exists(&target).await.unwrap_or(false) {
+            return Err(AppError::Conflict(format!("File already exists: {}", upload.path)));
+        }
+        let caller = Caller {
+            user: &upload.owner,
+            admin: upload.admin,
+        };
+        self.authorize(caller, &upload.path, &target, Access::Write).await?;
+        if let Some(blobs) = &self.blobs {
+            let (blobs, name, digest, target) = (blobs.clone(), upload.path.clone(), sha256.clone(), target.clone());
+            tokio::task::spawn_blocking(move || {
+                blobs.add_file(&part_path, &digest)?;
+                blobs.link(&name, &digest, Some(&target))
+            })
+            .await
+            .map_err(|e| AppError::Internal(format!("Failed to store upload: {}", e)))?
+            .map_err(blob_error)?;
+            self.record_usage().await;
+        } else {
+            if let Some(parent) = target.parent() {
+                fs::create_dir_all(parent)
+                    .await
+                    .map_err(|e| io_error("Failed to create directory", e))?;
+            }
+            if fs::rename(&part_path, &target).await.is_err() {
+                // The upload directory may be on another filesystem
+                fs::copy(&part_path, &target)
+                    .await
+                    .map_err(|e| io_error("Failed to store upload", e))?;
+                fs::remove_file(&part_path)
+                    .await
+                    .map_err(|e| io_error("Failed to remove upload", e))?;
+            }
+        }
+        self.set_owner(&target, &upload.owner).await?;
+        if let Err(e) = fs::remove_file(self.record_path(&upload.id)).await {
+            warn!("Failed to remove upload record {}: {}", upload.id, e);
+        }
+        if let Some(result_cache) = &self.result_cache {
+            match result_cache.invalidate_input(&target) {
+                Ok(removed) if !removed.is_empty() => {
+                    debug!("Invalidated {} cached results that read {}", removed.len(), upload.path);
+                }
+                Ok(_) => {}
+                Err(e) => warn!("Failed to invalidate cached results that read {}: {}", upload.path, e),
+            }
+        }
+
+        debug!("Stored upload {} at {} ({} bytes)", upload.id, upload.path, upload.size);
+        upload.completed_sha256 = Some(sha256);
+        self.uploads.write().unwrap_or_else(PoisonError::into_inner).insert(upload.id.clone(), upload.clone());
+        Ok(upload.response(upload.size))
+    }
+
+    /// The blob store uploads are kept in, if one is configured
+    pub fn blob_store(&self) -> Option<&BlobStore> {
+        self.blobs.as_ref()
+    }
+
+    /// Space used by the blob store
+    pub async fn usage(&self) -> Result<StorageUsage, AppError> {
+        let blobs = self
+            .blobs
+            .clone()
+            .ok_or_else(|| AppError::NotFound("No blob store is configured".to_string()))?;
+        tokio::task::spawn_blocking(move || blobs.usage())
+            .await
+            .map_err(|e| AppError::Internal(e.to_string()))?
+            .map_err(blob_error)
+    }
+
+    /// Delete the blobs no file links to any more, if a blob store is
+    /// configured; see [`BlobStore::gc`]
+    pub async fn collect_garbage(&self, min_age: std::time::Duration, dry_run: bool) -> Result<Option<GcReport>, AppError> {
+        let Some(blobs) = self.blobs.clone() else {
+            return Ok(None);
+        };
+        let report = tokio::task::spawn_blocking(move || blobs.gc(min_age, dry_run))
+            .await
+            .map_err(|e| AppError::Internal(e.to_string()))?
+            .map_err(blob_error)?;
+        if !dry_run {
+            self.record_usage().await;
+        }
+        Ok(Some(report))
+    }
+
+    /// Record the storage usage gauges
+    async fn record_usage(&self) {
+        let Some(metrics) = &self.metrics else { return };
+        let usage = match self.usage().await {
+            Ok(usage) => usage,
+            Err(e) => {
+                warn!("Failed to measure blob store usage: {}", e);
+                return;
+            }
+        };
+        let gauges = [
+            ("blob_store_blobs", usage.blobs),
+            ("blob_store_stored_bytes", usage.stored_bytes),
+            ("blob_store_references", usage.references),
+            ("blob_store_referenced_bytes", usage.referenced_bytes),
+            ("blob_store_saved_bytes", usage.saved_bytes()),
+        ];
+        for (name, value) in gauges {
+            let metric = Metric::new(name, value as f64, MetricType::Gauge, HashMap::new());
+            if let Err(e) = metrics.record_metric(metric).await {
+                warn!("Failed to record storage metric: {}", e);
+            }
+        }
+    }
+
+    /// Open a file for download, returning it with its size
+    pub async fn open_file(&self, caller: Caller<'_>, path: &str) -> Result<(File, u64), AppError> {
+        let host_path = self.vfs.resolve(path, Access::Read).map_err(vfs_error)?;
+        self.authorize(caller, path, &host_path, Access::Read).await?;
+        let not_found = || AppError::NotFound(format!("File not found: {}", path));
+        let metadata = fs::metadata(&host_path).await.map_err(|_| not_found())?;
+        if !metadata.is_file() {
+            return Err(not_found());
+        }
+        let file = File::open(&host_path).await.map_err(|_| not_found())?;
+        Ok((file, metadata.len()))
+    }
+
+    /// Size, checksum and modification time of a file
+    pub async fn info(&self, caller: Caller<'_>, path: &str) -> Result<FileInfoResponse, AppError> {
+        let host_path = self.vfs.resolve(path, Access::Read).map_err(vfs_error)?;
+        self.authorize(caller, path, &host_path, Access::Read).await?;
+        let not_found = || AppError::NotFound(format!("File not found: {}", path));
+        let metadata = fs::metadata(&host_path).await.map_err(|_| not_found())?;
+        if !metadata.is_file() {
+            return Err(not_found());
+        }
+        let sha256 = hash_file(&host_path)
+            .await
+            .map_err(|e| io_error("Failed to hash file", e))?;
+        Ok(FileInfoResponse {
+            path: path.to_string(),
+            size: metadata.len(),
+            sha256,
+            modified_at: metadata.modified().ok().map(DateTime::<Utc>::from),
+        })
+    }
+}
+
+/// Create the file service, falling back to a temporary upload directory
+/// and plain file storage if the configured directories cannot be used
+pub fn create_file_service(
+    config: &FilesConfig,
+    metrics: Option<Arc<dyn MetricCollector>>,
+    result_cache: Option<Arc<ResultCache>>,
+) -> Arc<FileService> {
+    let service = FileService::open(config.clone()).unwrap_or_else(|e| {
+        warn!("Failed to open upload directory {:?} or blob store {:?}: {}", config.upload_dir, config.blob_dir, e);
+        let fallback = FilesConfig {
+            upload_dir: std::env::temp_dir().join(format!("squirrel-uploads-{}", Uuid::new_v4())),
+            blob_dir: None,
+            ..config.clone()
+        };
+        FileService::open(fallback).expect("temporary upload directory can be created")
+    });
+    Arc::new(service.with_metrics(metrics).with_result_cache(result_cache))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use futures::stream;
+
+    fn service(dir: &Path) -> FileService {
+        let config = FilesConfig {
+            workspace_dir: dir.join("workspace"),
+            data_dir: dir.join("data"),
+            upload_dir: dir.join("uploads"),
+            blob_dir: Some(dir.join("blobs")),
+            max_file_size: 1024,
+            upload_expiry_secs: 3600,
+        };
+        FileService::open(config).unwrap()
+    }
+
+    fn body(chunks: &[&'static [u8]]) -> impl Stream<Item = Result<Bytes, String>> + Unpin {
+        stream::iter(chunks.iter().map(|chunk| Ok(Bytes::from_static(chunk))).collect::<Vec<_>>())
+    }
+
+    fn request(path: &str, contents: &[u8]) -> CreateUploadRequest {
+        CreateUploadRequest {
+            path: path.to_string(),
+            size: contents.len() as u64,
+            sha256: Some(hex::encode(Sha256::digest(contents))),
+            overwrite: false,
+        }
+    }
+
+    fn alice() -> Caller<'static> {
+        Caller::user("alice")
+    }
+
+    fn range(start: u64, end: u64, total: u64) -> ContentRange {
+        ContentRange { start, end, total }
+    }
+
+    #[tokio::test]
+    async fn test_resumable_upload_survives_restart() {
+        let dir = tempfile::tempdir().unwrap();
+        let contents = b"hello, resumable world";
+        let files = service(dir.path());
+        let upload = files.create(alice(), request("workspace://in/hello.txt", contents)).await.unwrap();
+        assert_eq!(upload.state, UploadState::Pending);
+
+        let status = files
+            .append("alice", &upload.id, range(0, 9, 22), None, body(&[b"hello", b", res"]))
+            .await
+            .unwrap();
+        assert_eq!(status.offset, 10);
+
+        // A chunk that does not start at the offset is refused
+        let error = files
+            .append("alice", &upload.id, range(5, 21, 22), None, body(&[b"x"]))
+            .await
+            .unwrap_err();
+        assert!(matches!(error, AppError::Conflict(_)));
+        assert!(files.status("bob", &upload.id).await.is_err());
+
+        // The upload continues after a restart
+        let files = service(dir.path());
+        assert_eq!(files.status("alice", &upload.id).await.unwrap().offset, 10);
+        let done = files
+            .append("alice", &upload.id, range(10, 21, 22), None, body(&[b"umable world"]))
+            .await
+            .unwrap();
+        assert_eq!(done.state, UploadState::Completed);
+        assert_eq!(done.sha256.as_deref(), Some(hex::encode(Sha256::digest(contents)).as_str()));
+        assert_eq!(std::fs::read(dir.path().join("workspace/in/hello.txt")).unwrap(), contents);
+
+        let info = files.info(alice(), "workspace://in/hello.txt").await.unwrap();
+        assert_eq!(info.size, 22);
+        assert_eq!(Some(info.sha256), done.sha256);
+        assert!(matches!(
+            files.create(alice(), request("workspace://in/hello.txt", contents)).await,
+            Err(AppError::Conflict(_))
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_checksums_and_limits_are_enforced() {
+        let dir = tempfile::tempdir().unwrap();
+        let files = service(dir.path());
+
+        let upload = files.create(alice(), request("workspace://a.bin", b"abcd")).await.unwrap();
+        let error = files
+            .append("alice", &upload.id, range(0, 3, 4), Some(&"0".repeat(64)), body(&[b"abcd"]))
+            .await
+            .unwrap_err();
+        assert!(matches!(error, AppError::Custom(StatusCode::UNPROCESSABLE_ENTITY, _)));
+        assert_eq!(files.status("alice", &upload.id).await.unwrap().offset, 0);
+
+        let mut wrong = request("workspace://b.bin", b"abcd");
+        wrong.sha256 = Some(hex::encode(Sha256::digest(b"dcba")));
+        let upload = files.create(alice(), wrong).await.unwrap();
+        let error = files
+            .append("alice", &upload.id, range(0, 3, 4), None, body(&[b"abcd"]))
+            .await
+            .unwrap_err();
+        assert!(matches!(error, AppError::Custom(StatusCode::UNPROCESSABLE_ENTITY, _)));
+        assert!(files.status("alice", &upload.id).await.is_err());
+        assert!(!dir.path().join("workspace/b.bin").exists());
+
+        let mut large = request("workspace://large.bin", b"");
+        large.size = 4096;
+        assert!(matches!(
+            files.create(alice(), large).await,
+            Err(AppError::Custom(StatusCode::PAYLOAD_TOO_LARGE, _))
+        ));
+        assert!(matches!(
+            files.create(alice(), request("data://x.bin", b"x")).await,
+            Err(AppError::Forbidden(_))
+        ));
+        assert!(matches!(
+            files.create(alice(), request("workspace://../x.bin", b"x")).await,
+            Err(AppError::Forbidden(_))
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_identical_uploads_share_a_blob() {
+        let dir = tempfile::tempdir().unwrap();
+        let files = service(dir.path());
+        let contents = b"GATTACA";
+        for path in ["workspace://job1/reads.fa", "workspace://job2/reads.fa"] {
+            let upload = files.create(alice(), request(path, contents)).await.unwrap();
+            files
+                .append("alice", &upload.id, range(0, 6, 7), None, body(&[contents]))
+                .await
+                .unwrap();
+        }
+
+        assert_eq!(std::fs::read(dir.path().join("workspace/job2/reads.fa")).unwrap(), contents);
+        let usage = files.usage().await.unwrap();
+        assert_eq!((usage.blobs, usage.references), (1, 2));
+        assert_eq!(usage.saved_bytes(), 7);
+    }
+
+    #[tokio::test]
+    async fn test_replacing_a_file_invalidates_cached_results() {
+        use squirrel_commands::manifest::{ManifestOptions, RunManifest};
+
+        let dir = tempfile::tempdir().unwrap();
+        let result_cache = Arc::new(ResultCache::open(dir.path().join("cache")).unwrap());
+        let files = service(dir.path()).with_result_cache(Some(result_cache.clone()));
+        let upload = files.create(alice(), request("workspace://reads.fa", b"ACGT")).await.unwrap();
+        files.append("alice", &upload.id, range(0, 3, 4), None, body(&[b"ACGT"])).await.unwrap();
+
+        let options = ManifestOptions {
+            env_allowlist: Vec::new(),
+            inputs: vec![files.resolve("workspace://reads.fa").unwrap()],
+            ..ManifestOptions::default()
+        };
+        let manifest = RunManifest::capture("count", &[], &options).unwrap();
+        result_cache.store(&manifest, "4 bases").unwrap();
+
+        let replacement = CreateUploadRequest { overwrite: true, ..request("workspace://reads.fa", b"ACGTT") };
+        let upload = files.create(alice(), replacement).await.unwrap();
+        files.append("alice", &upload.id, range(0, 4, 5), None, body(&[b"ACGTT"])).await.unwrap();
+        assert!(result_cache.entries().unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_files_are_scoped_to_their_owner() {
+        let dir = tempfile::tempdir().unwrap();
+        let files = service(dir.path());
+        let upload = files.create(alice(), request("workspace://alice.txt", b"mine")).await.unwrap();
+        files.append("alice", &upload.id, range(0, 3, 4), None, body(&[b"mine"])).await.unwrap();
+        std::fs::write(dir.path().join("workspace/shared.txt"), b"shared").unwrap();
+
+        let bob = Caller::user("bob");
+        assert!(matches!(files.info(bob, "workspace://alice.txt").await, Err(AppError::NotFound(_))));
+        assert!(matches!(files.open_file(bob, "workspace://alice.txt").await, Err(AppError::NotFound(_))));
+        let replacement = CreateUploadRequest { overwrite: true, ..request("workspace://alice.txt", b"ours") };
+        assert!(matches!(files.create(bob, replacement.clone()).await, Err(AppError::Forbidden(_))));
+
+        // Files nobody uploaded are readable but only admins replace them
+        assert_eq!(files.info(bob, "workspace://shared.txt").await.unwrap().size, 6);
+        let shared = CreateUploadRequest { overwrite: true, ..request("workspace://shared.txt", b"x") };
+        assert!(matches!(files.create(bob, shared).await, Err(AppError::Forbidden(_))));
+
+        let admin = Caller { user: "root", admin: true };
+        assert_eq!(files.open_file(admin, "workspace://alice.txt").await.unwrap().1, 4);
+        assert!(files.create(admin, replacement).await.is_ok());
+
+        // Ownership survives a restart
+        let files = service(dir.path());
+        assert!(files.info(bob, "workspace://alice.txt").await.is_err());
+        assert!(files.info(alice(), "workspace://alice.txt").await.is_ok());
+    }
+
+    #[test]
+    fn test_range_headers() {
+        assert_eq!(ContentRange::parse("bytes 0-99/1000").unwrap(), range(0, 99, 1000));
+        assert!(ContentRange::parse("bytes 10-5/1000").is_err());
+        assert!(ContentRange::parse("bytes 0-1000/1000").is_err());
+        assert!(ContentRange::parse("0-99/1000").is_err());
+
+        assert_eq!(ByteRange::parse("bytes=100-").unwrap().resolve(1000), Some((100, 999)));
+        assert_eq!(ByteRange::parse("bytes=0-4999").unwrap().resolve(1000), Some((0, 999)));
+        assert_eq!(ByteRange::parse("bytes=-10").unwrap().resolve(1000), Some((990, 999)));
+        assert_eq!(ByteRange::parse("bytes=1000-").unwrap().resolve(1000), None);
+        assert!(ByteRange::parse("bytes=0-1,5-6").is_err());
+    }
+}
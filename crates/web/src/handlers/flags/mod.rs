@@ -0,0 +1,9 @@
+//! Feature flag module for handling the `/api/flags` endpoints
+//!
+//! This module contains the admin-only handlers that list feature flags,
+//! replace their settings and reset them to the configured ones. Every call
+//! is written to the admin audit log.
+
+mod routes;
+
+pub use routes::flag_routes;
@@ -0,0 +1,72 @@
+use axum::{
+    Router,
+    routing::get,
+    extract::{Extension, Path, State},
+    Json,
+};
+use squirrel_core::flags::FlagSetting;
+use std::sync::Arc;
+use crate::state::AppState;
+use crate::auth::extractor::AuthClaims;
+use crate::flags::FlagState;
+use crate::handlers::admin::audited;
+use crate::api::{api_success, error::AppError, ApiResponse};
+
+/// Feature flag routes
+pub fn flag_routes() -> Router<Arc<AppState>> {
+    Router::new()
+        .route("/", get(list_flags))
+        .route("/:name", get(get_flag).put(set_flag).delete(reset_flag))
+}
+
+/// List the feature flags and their settings
+async fn list_flags(
+    State(state): State<Arc<AppState>>,
+    Extension(user): Extension<AuthClaims>,
+) -> Result<Json<ApiResponse<Vec<FlagState>>>, AppError> {
+    let flags = audited(&state, &user, "flags.list", None, async { Ok(state.get_flags()?.list()) }).await?;
+
+    Ok(api_success(flags))
+}
+
+/// Get a feature flag and its setting
+async fn get_flag(
+    State(state): State<Arc<AppState>>,
+    Extension(user): Extension<AuthClaims>,
+    Path(name): Path<String>,
+) -> Result<Json<ApiResponse<FlagState>>, AppError> {
+    let flag = audited(&state, &user, "flags.get", Some(&name), async { state.get_flags()?.get(&name) }).await?;
+
+    Ok(api_success(flag))
+}
+
+/// Replace the setting of a feature flag
+async fn set_flag(
+    State(state): State<Arc<AppState>>,
+    Extension(user): Extension<AuthClaims>,
+    Path(name): Path<String>,
+    Json(setting): Json<FlagSetting>,
+) -> Result<Json<ApiResponse<FlagState>>, AppError> {
+    let flag = audited(&state, &user, "flags.set", Some(&name), async {
+        state.get_flags()?.set(&user.sub, &name, setting).await
+    })
+    .await?;
+
+    Ok(api_success(flag))
+}
+
+/// Reset a feature flag to its configured setting
+async fn reset_flag(
+    State(state): State<Arc<AppState>>,
+    Extension(user): Extension<AuthClaims>,
+    Path(name): Path<String>,
+) -> Result<Json<ApiResponse<FlagState>>, AppError> {
+    let flag = audited(&state, &user, "flags.reset", Some(&name), async {
+        let flags = state.get_flags()?;
+        flags.reset(&name).await?;
+        flags.get(&name)
+    })
+    .await?;
+
+    Ok(api_success(flag))
+}
@@ -2,16 +2,19 @@
 
 use std::sync::Arc;
 use axum::{
+    http::StatusCode,
     response::IntoResponse, 
     routing::get,
     Router,
     extract::State,
     Json,
-    Extension,
 };
 use serde_json::json;
 use serde::Serialize;
+use squirrel_app::supervisor::TaskState;
 
+use crate::db::{resilience, CircuitState};
+use crate::websocket::BackplaneStatus;
 use crate::AppState;
 
 /// Health response structure
@@ -52,7 +55,7 @@ pub async fn check_detailed(
     State(state): State<Arc<AppState>>,
 ) -> impl IntoResponse {
     // Check database connection
-    let db_status = "Connected";
+    let db_status = database_status();
 
     // Check MCP connection
     let mcp_status = match &state.mcp {
@@ -79,6 +82,15 @@ pub async fn check_detailed(
     Json(health)
 }
 
+/// Whether statements reach the database, as the circuit breaker sees it
+fn database_status() -> &'static str {
+    match resilience().map(|resilience| resilience.state()) {
+        None | Some(CircuitState::Closed) => "Connected",
+        Some(CircuitState::HalfOpen) => "Recovering",
+        Some(CircuitState::Open) => "Unavailable",
+    }
+}
+
 /// Get system uptime (mock implementation)
 fn get_system_uptime() -> String {
     // In a real implementation, this would get the actual system uptime
@@ -97,14 +109,14 @@ fn get_memory_usage() -> serde_json::Value {
 
 /// Get health status
 pub async fn get_health(
-    state: Extension<Arc<AppState>>,
+    State(state): State<Arc<AppState>>,
 ) -> impl IntoResponse {
     // Get system info for health check
     let uptime = get_system_uptime();
     let memory = get_memory_usage();
     
     // Check database connection
-    let db_status = "Connected";
+    let db_status = database_status();
     
     // Check MCP connection
     let mcp_status = match &state.mcp {
@@ -117,17 +129,56 @@ pub async fn get_health(
         }
     };
     
+    // Report which instance leads in multi-instance deployments
+    let leadership = state.leader.as_ref().map(|leader| leader.status());
+    
+    // Report the background tasks; one that failed for good degrades the instance
+    let tasks = state.supervisor.as_ref().map(|supervisor| supervisor.health()).unwrap_or_default();
+    // Events stop reaching other instances while the backplane is down
+    let backplane = state.ws_manager.backplane_status();
+    let status = if tasks.iter().any(|task| task.state == TaskState::Failed)
+        || backplane == Some(BackplaneStatus::Disconnected)
+    {
+        "Degraded"
+    } else {
+        "OK"
+    };
+    
     // Create health check response
     let health = json!({
-        "status": "OK",
+        "status": status,
         "version": env!("CARGO_PKG_VERSION"),
         "uptime": uptime,
         "memory": memory,
         "services": {
             "database": db_status,
-            "mcp": mcp_status
-        }
+            "mcp": mcp_status,
+            "websocket_backplane": backplane
+        },
+        "leadership": leadership,
+        "tasks": tasks
     });
     
     Json(health)
-} 
\ No newline at end of file
+}
+
+/// Readiness of the instance: 200 once every critical startup stage is ready
+/// and still passes its probe, 503 otherwise
+pub async fn get_ready(
+    State(state): State<Arc<AppState>>,
+) -> impl IntoResponse {
+    let (ready, stages) = match &state.readiness {
+        Some(readiness) => (readiness.is_ready().await, readiness.report()),
+        None => (true, Vec::new()),
+    };
+    let status = if ready {
+        StatusCode::OK
+    } else {
+        StatusCode::SERVICE_UNAVAILABLE
+    };
+    
+    (status, Json(json!({
+        "ready": ready,
+        "stages": stages
+    })))
+}
@@ -0,0 +1,342 @@
+//! MCP contexts bound to jobs.
+//!
+//! Each job runs within one MCP context: either a new context allocated when
+//! the job is created, or an existing one the job attaches to. Commands
+//! submitted for the job execute in that context, and each of them is
+//! appended to the context's `steps`, so state accumulates across the job.
+//! When the job finishes, the context is copied into a snapshot. A context
+//! the job allocated is then archived by deleting it, leaving the snapshot;
+//! an attached context stays in place for its other users.
+
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use squirrel_mcp::context_manager::{Context, ContextManager};
+use tokio::sync::RwLock;
+use uuid::Uuid;
+
+use crate::api::error::AppError;
+use crate::api::JobState;
+
+/// The context a job runs in
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct JobContext {
+    /// Job ID
+    pub job_id: String,
+    /// User who created the job
+    pub owner: String,
+    /// ID of the MCP context
+    pub context_id: Uuid,
+    /// Whether the job attached to an existing context instead of allocating one
+    pub attached: bool,
+    /// Number of commands run in the context for the job
+    pub steps: usize,
+    /// How the job ended, once it has
+    pub finished: Option<JobState>,
+    /// Snapshot taken when the job finished
+    pub snapshot_id: Option<Uuid>,
+    /// When the job finished
+    pub finished_at: Option<DateTime<Utc>>,
+}
+
+/// Binds jobs to MCP contexts
+pub struct JobContexts {
+    contexts: Arc<ContextManager>,
+    jobs: RwLock<HashMap<String, JobContext>>,
+}
+
+impl JobContexts {
+    /// Create job bindings over the given context manager
+    pub fn new(contexts: Arc<ContextManager>) -> Self {
+        Self {
+            contexts,
+            jobs: RwLock::new(HashMap::new()),
+        }
+    }
+
+    /// Give a new job a context: `existing` if given, otherwise a new one
+    pub async fn attach(
+        &self,
+        job_id: &str,
+        owner: &str,
+        name: &str,
+        existing: Option<&str>,
+    ) -> Result<JobContext, AppError> {
+        let (context_id, attached) = match existing {
+            Some(id) => {
+                let id = parse_context_id(id)?;
+                self.contexts
+                    .get_context(id)
+                    .await
+                    .map_err(|_| AppError::NotFound(format!("Context {} not found", id)))?;
+                (id, true)
+            }
+            None => {
+                let now = Utc::now();
+                let context = Context {
+                    id: Uuid::new_v4(),
+                    name: format!("job {}", name),
+                    data: json!({ "job_id": job_id, "steps": [] }),
+                    metadata: Some(json!({ "job_id": job_id, "owner": owner })),
+                    parent_id: None,
+                    created_at: now,
+                    updated_at: now,
+                    expires_at: None,
+                };
+                let id = self
+                    .contexts
+                    .create_context(context)
+                    .await
+                    .map_err(internal)?;
+                // Keep the context while the job runs, however long that is
+                self.contexts.pin_context(id).await.map_err(internal)?;
+                (id, false)
+            }
+        };
+
+        let binding = JobContext {
+            job_id: job_id.to_string(),
+            owner: owner.to_string(),
+            context_id,
+            attached,
+            steps: 0,
+            finished: None,
+            snapshot_id: None,
+            finished_at: None,
+        };
+        self.jobs
+            .write()
+            .await
+            .insert(job_id.to_string(), binding.clone());
+        Ok(binding)
+    }
+
+    /// The context of a job owned by `owner`
+    pub async fn get(&self, job_id: &str, owner: &str) -> Result<JobContext, AppError> {
+        self.jobs
+            .read()
+            .await
+            .get(job_id)
+            .filter(|binding| binding.owner == owner)
+            .cloned()
+            .ok_or_else(|| AppError::NotFound(format!("Job {} not found", job_id)))
+    }
+
+    /// The context new commands of a running job execute in
+    pub async fn context_for(&self, job_id: &str, owner: &str) -> Result<Uuid, AppError> {
+        let binding = self.get(job_id, owner).await?;
+        if binding.finished.is_some() {
+            return Err(AppError::Conflict(format!("Job {} has finished", job_id)));
+        }
+        Ok(binding.context_id)
+    }
+
+    /// Append a command submitted for the job to its context
+    pub async fn record_step(
+        &self,
+        job_id: &str,
+        command: &str,
+        parameters: &Value,
+        command_id: &str,
+    ) -> Result<(), AppError> {
+        let mut jobs = self.jobs.write().await;
+        let binding = jobs
+            .get_mut(job_id)
+            .ok_or_else(|| AppError::NotFound(format!("Job {} not found", job_id)))?;
+        let context = self
+            .contexts
+            .get_context(binding.context_id)
+            .await
+            .map_err(internal)?;
+
+        let mut data = match context.data {
+            Value::Object(data) => data,
+            other => {
+                let mut data = serde_json::Map::new();
+                data.insert("value".to_string(), other);
+                data
+            }
+        };
+        let step = json!({
+            "job_id": job_id,
+            "command": command,
+            "parameters": parameters,
+            "command_id": command_id,
+            "submitted_at": Utc::now(),
+        });
+        match data.get_mut("steps") {
+            Some(Value::Array(steps)) => steps.push(step),
+            _ => {
+                data.insert("steps".to_string(), Value::Array(vec![step]));
+            }
+        }
+        self.contexts
+            .update_context(binding.context_id, Value::Object(data), None)
+            .await
+            .map_err(internal)?;
+        binding.steps += 1;
+        Ok(())
+    }
+
+    /// Snapshot and archive the context of a job that ended in `state`
+    ///
+    /// Finishing a job twice returns the first result.
+    pub async fn finish(
+        &self,
+        job_id: &str,
+        owner: &str,
+        state: JobState,
+    ) -> Result<JobContext, AppError> {
+        let mut jobs = self.jobs.write().await;
+        let binding = jobs
+            .get_mut(job_id)
+            .filter(|binding| binding.owner == owner)
+            .ok_or_else(|| AppError::NotFound(format!("Job {} not found", job_id)))?;
+        if binding.finished.is_some() {
+            return Ok(binding.clone());
+        }
+
+        let context = self
+            .contexts
+            .get_context(binding.context_id)
+            .await
+            .map_err(internal)?;
+        let now = Utc::now();
+        let snapshot = Context {
+            id: Uuid::new_v4(),
+            name: format!("{} (snapshot)", context.name),
+            data: context.data,
+            metadata: Some(json!({
+                "job_id": job_id,
+                "owner": owner,
+                "state": state,
+                "snapshot_of": binding.context_id,
+                "archived_at": now,
+            })),
+            parent_id: None,
+            created_at: now,
+            updated_at: now,
+            expires_at: None,
+        };
+        let snapshot_id = self
+            .contexts
+            .create_context(snapshot)
+            .await
+            .map_err(internal)?;
+        if !binding.attached {
+            self.contexts
+                .delete_context(binding.context_id)
+                .await
+                .map_err(internal)?;
+        }
+
+        binding.finished = Some(state);
+        binding.snapshot_id = Some(snapshot_id);
+        binding.finished_at = Some(now);
+        Ok(binding.clone())
+    }
+}
+
+/// Parses a context ID given by a client
+fn parse_context_id(id: &str) -> Result<Uuid, AppError> {
+    Uuid::parse_str(id).map_err(|_| AppError::InvalidRequest(format!("Invalid context ID: {}", id)))
+}
+
+fn internal(error: squirrel_mcp::MCPError) -> AppError {
+    AppError::Internal(format!("Context error: {}", error))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use squirrel_mcp::persistence::PersistenceConfig;
+
+    /// A context manager persisting to `dir` instead of the working directory
+    async fn manager(dir: &tempfile::TempDir) -> Arc<ContextManager> {
+        Arc::new(
+            ContextManager::with_persistence(PersistenceConfig {
+                data_dir: dir.path().to_path_buf(),
+                ..PersistenceConfig::default()
+            })
+            .await,
+        )
+    }
+
+    #[tokio::test]
+    async fn test_steps_accumulate_until_the_job_finishes() {
+        let dir = tempfile::tempdir().unwrap();
+        let manager = manager(&dir).await;
+        let jobs = JobContexts::new(manager.clone());
+
+        let binding = jobs.attach("job-1", "alice", "align", None).await.unwrap();
+        assert!(!binding.attached);
+        assert_eq!(
+            jobs.context_for("job-1", "alice").await.unwrap(),
+            binding.context_id
+        );
+        assert!(jobs.context_for("job-1", "bob").await.is_err());
+
+        jobs.record_step("job-1", "index", &json!({ "ref": "GRCh38" }), "cmd-1")
+            .await
+            .unwrap();
+        jobs.record_step("job-1", "align", &json!({}), "cmd-2")
+            .await
+            .unwrap();
+        let context = manager.get_context(binding.context_id).await.unwrap();
+        assert_eq!(context.data["steps"][1]["command"], "align");
+
+        let finished = jobs
+            .finish("job-1", "alice", JobState::Completed)
+            .await
+            .unwrap();
+        assert_eq!(finished.steps, 2);
+        let snapshot = manager
+            .get_context(finished.snapshot_id.unwrap())
+            .await
+            .unwrap();
+        assert_eq!(snapshot.data["steps"][0]["command_id"], "cmd-1");
+        assert!(manager.get_context(binding.context_id).await.is_err());
+        assert!(matches!(
+            jobs.context_for("job-1", "alice").await,
+            Err(AppError::Conflict(_))
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_attached_context_outlives_the_job() {
+        let dir = tempfile::tempdir().unwrap();
+        let manager = manager(&dir).await;
+        let jobs = JobContexts::new(manager.clone());
+        let shared = jobs
+            .attach("job-1", "alice", "first", None)
+            .await
+            .unwrap()
+            .context_id;
+
+        let second = jobs
+            .attach("job-2", "alice", "second", Some(&shared.to_string()))
+            .await
+            .unwrap();
+        assert!(second.attached);
+        assert_eq!(second.context_id, shared);
+        jobs.record_step("job-2", "call", &json!({}), "cmd-3")
+            .await
+            .unwrap();
+        jobs.finish("job-2", "alice", JobState::Failed)
+            .await
+            .unwrap();
+        assert!(manager.get_context(shared).await.is_ok());
+
+        assert!(jobs
+            .attach("job-3", "alice", "third", Some("not-a-uuid"))
+            .await
+            .is_err());
+        assert!(jobs
+            .attach("job-3", "alice", "third", Some(&Uuid::new_v4().to_string()))
+            .await
+            .is_err());
+    }
+}
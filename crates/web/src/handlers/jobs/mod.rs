@@ -2,6 +2,7 @@
 
 use axum::{
     extract::{Path, State, Extension},
+    http::HeaderMap,
     Json,
     routing::{get, post},
     Router,
@@ -16,14 +17,22 @@ use chrono::{DateTime, Utc};
 use sqlx::sqlite::SqliteQueryResult;
 use serde::Deserialize;
 
+mod contexts;
+pub use contexts::{JobContext, JobContexts};
+
+mod report;
+pub use report::get_job_report;
+
 use crate::{
     api::{
-        CreateJobRequest, CreateJobResponse, JobStatus, JobState, error::AppError,
+        CreateJobRequest, CreateJobResponse, FinishJobRequest, JobStatus, JobState, error::AppError,
         api_success, api_success_with_pagination, ApiResponse
     },
     AppState,
     auth::Claims,
     auth::extractor::AuthClaims,
+    handlers::usage::{enforce_quotas, workspace},
+    idempotency::idempotency_key,
 };
 
 /// Job params for path extraction
@@ -61,16 +70,43 @@ pub fn job_routes() -> Router {
         .route("/:id/report", get(report_stub))
 }
 
-/// Create a new job
+/// Create a new job, bound to a new or existing MCP context
+///
+/// A repeated submission with the same `Idempotency-Key` returns the
+/// original job instead of a new one. Jobs are refused while the server is
+/// low on memory and once the user used up a quota in the workspace named by
+/// the `X-Squirrel-Workspace` header.
 pub async fn create_job(
-    State(_state): State<Arc<AppState>>,
-    Extension(_claims): Extension<AuthClaims>,
+    State(state): State<Arc<AppState>>,
+    Extension(claims): Extension<AuthClaims>,
+    headers: HeaderMap,
     Json(req): Json<CreateJobRequest>,
 ) -> Result<Json<ApiResponse<CreateJobResponse>>, AppError> {
+    let key = idempotency_key(&headers)?;
+    let workspace = workspace(&headers);
+    let response = state
+        .get_idempotency()?
+        .run(&claims.sub, "jobs", key, &req, || submit_job(&state, &claims, &workspace, &req))
+        .await?;
+    
+    Ok(api_success(response))
+}
+
+/// Create a new job and bind its MCP context
+async fn submit_job(
+    state: &AppState,
+    claims: &AuthClaims,
+    workspace: &str,
+    req: &CreateJobRequest,
+) -> Result<CreateJobResponse, AppError> {
+    state.check_memory_pressure()?;
+    enforce_quotas(state, &claims.sub, workspace)?;
+    
     #[cfg(feature = "db")]
-    if let Some(_) = _state.db {
-        let response = create_job_with_db(&_state, &_claims, &req).await?;
-        return Ok(api_success(response));
+    if let Some(_) = state.db {
+        let mut response = create_job_with_db(state, claims, req).await?;
+        bind_context(state, claims, req, &mut response).await?;
+        return Ok(response);
     }
     
     // If we get here, either there's no DB connection or the feature is disabled
@@ -81,13 +117,55 @@ pub async fn create_job(
     // In a real implementation, this would store the job in the database
     // TODO: Additional validation of job parameters would go here
     
-    let response = CreateJobResponse {
+    let mut response = CreateJobResponse {
         id: job_id,
         name: req.name.clone(),
         status: JobState::Queued,
+        context_id: None,
     };
+    bind_context(state, claims, req, &mut response).await?;
     
-    Ok(api_success(response))
+    Ok(response)
+}
+
+/// Allocates or attaches the MCP context of a new job, if contexts are configured
+async fn bind_context(
+    state: &AppState,
+    claims: &AuthClaims,
+    req: &CreateJobRequest,
+    response: &mut CreateJobResponse,
+) -> Result<(), AppError> {
+    if let Some(job_contexts) = &state.job_contexts {
+        let binding = job_contexts
+            .attach(&response.id, &claims.sub, &req.name, req.context_id.as_deref())
+            .await?;
+        response.context_id = Some(binding.context_id.to_string());
+    }
+    Ok(())
+}
+
+/// Get the MCP context a job runs in
+pub async fn get_job_context(
+    State(state): State<Arc<AppState>>,
+    Extension(claims): Extension<AuthClaims>,
+    Path(job_id): Path<String>,
+) -> Result<Json<ApiResponse<JobContext>>, AppError> {
+    let binding = state.get_job_contexts()?.get(&job_id, &claims.sub).await?;
+    Ok(api_success(binding))
+}
+
+/// Record how a job ended, snapshotting and archiving its MCP context
+pub async fn finish_job(
+    State(state): State<Arc<AppState>>,
+    Extension(claims): Extension<AuthClaims>,
+    Path(job_id): Path<String>,
+    Json(req): Json<FinishJobRequest>,
+) -> Result<Json<ApiResponse<JobContext>>, AppError> {
+    if !matches!(req.status, JobState::Completed | JobState::Failed) {
+        return Err(AppError::InvalidRequest("A job finishes as Completed or Failed".to_string()));
+    }
+    let binding = state.get_job_contexts()?.finish(&job_id, &claims.sub, req.status).await?;
+    Ok(api_success(binding))
 }
 
 /// Get job status
@@ -548,7 +626,7 @@ pub async fn cancel_job(
 /// Create a job
 #[cfg(feature = "db")]
 async fn create_job_with_db(
-    state: &Arc<AppState>,
+    state: &AppState,
     claims: &Claims,
     req: &CreateJobRequest,
 ) -> Result<CreateJobResponse, AppError> {
@@ -559,15 +637,16 @@ async fn create_job_with_db(
     sqlx::query!(
         r#"
         INSERT INTO jobs (
-            id, user_id, name, parameters,
+            id, user_id, name, parameters, manifest,
             status, progress, error, result_url,
             created_at, updated_at, started_at, completed_at
-        ) VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?)
+        ) VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?)
         "#,
         job_id,
         claims.sub.to_string(),
         req.name,
         serde_json::to_string(&req.parameters).unwrap(),
+        req.manifest.as_ref().map(|manifest| manifest.to_string()),
         "queued",
         0.0f32,
         Option::<String>::None,
@@ -585,6 +664,7 @@ async fn create_job_with_db(
         id: job_id,
         name: req.name.clone(),
         status: JobState::Queued,
+        context_id: None,
     };
     
     Ok(response)
@@ -620,4 +700,82 @@ pub async fn delete_job(
     Path(_params): Path<JobParams>,
 ) -> impl IntoResponse {
     // ... existing code ...
-} 
\ No newline at end of file
+} 
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::http::StatusCode;
+    use squirrel_monitoring::accounting::{AccountingConfig, Quota, QuotaScope, ResourceUsage, UsageLedger};
+    use squirrel_monitoring::watchdog::{MemoryWatchdog, WatchdogConfig};
+    use crate::config::{IdempotencyConfig, JobQueueConfig};
+    use crate::idempotency::{IdempotencyKeys, MemoryIdempotencyStore};
+    use crate::queue::{JobQueue, MemoryJobQueueStore};
+    use crate::handlers::usage::{record_usage, WORKSPACE_HEADER};
+    use crate::test_helpers::claims;
+
+    fn idempotency() -> Option<Arc<IdempotencyKeys>> {
+        Some(Arc::new(IdempotencyKeys::new(Arc::new(MemoryIdempotencyStore::new()), IdempotencyConfig::default())))
+    }
+
+    fn request() -> Json<CreateJobRequest> {
+        Json(CreateJobRequest {
+            name: "assembly".to_string(),
+            parameters: json!({}),
+            manifest: None,
+            context_id: None,
+        })
+    }
+
+    #[tokio::test]
+    async fn test_jobs_are_refused_under_memory_pressure() {
+        let pool = Arc::new(JobQueue::new(Arc::new(MemoryJobQueueStore::new()), JobQueueConfig::default()));
+        let config = WatchdogConfig { budget_mb: Some(100), ..WatchdogConfig::default() };
+        let watchdog = Arc::new(MemoryWatchdog::with_probe(config, pool, Arc::new(|| 90)));
+        let state = Arc::new(AppState {
+            idempotency: idempotency(),
+            memory_watchdog: Some(watchdog.clone()),
+            ..AppState::default()
+        });
+        let create = || {
+            create_job(State(state.clone()), Extension(claims("ada", &["User"])), HeaderMap::new(), request())
+        };
+
+        assert!(create().await.is_ok());
+        watchdog.check();
+        let refused = create().await;
+        assert!(matches!(refused, Err(AppError::Custom(StatusCode::SERVICE_UNAVAILABLE, _))));
+        watchdog.check_usage(50);
+        assert!(create().await.is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_jobs_are_refused_once_a_quota_is_used_up() {
+        let quota = Quota {
+            scope: QuotaScope::Workspace,
+            name: Some("lab".to_string()),
+            window_secs: 3600,
+            max_cpu_seconds: None,
+            max_memory_mb_seconds: None,
+            max_storage_bytes: None,
+            max_executions: Some(1),
+        };
+        let config = AccountingConfig { quotas: vec![quota], ..AccountingConfig::default() };
+        let state = Arc::new(AppState {
+            idempotency: idempotency(),
+            usage_ledger: Some(Arc::new(UsageLedger::in_memory(config))),
+            ..AppState::default()
+        });
+        let mut lab = HeaderMap::new();
+        lab.insert(WORKSPACE_HEADER, "lab".parse().unwrap());
+        let create = |headers: &HeaderMap| {
+            create_job(State(state.clone()), Extension(claims("ada", &["User"])), headers.clone(), request())
+        };
+
+        assert!(create(&lab).await.is_ok());
+        record_usage(&state, "ada", "lab", ResourceUsage::execution());
+        let refused = create(&lab).await;
+        assert!(matches!(refused, Err(AppError::Custom(StatusCode::TOO_MANY_REQUESTS, _))));
+        assert!(create(&HeaderMap::new()).await.is_ok());
+    }
+}
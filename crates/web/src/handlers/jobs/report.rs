@@ -0,0 +1,103 @@
+//! Rendered reports of jobs and workflow runs.
+//!
+//! `GET /api/jobs/:id/report` renders a [`RunReport`] of a workflow run or,
+//! failing that, of a job bound to an MCP context. Workflow runs are charted
+//! from the metrics recorded while they ran; jobs are read from their
+//! context, or from its snapshot once they have finished. Outputs naming
+//! workspace files link to the file download endpoint.
+
+use std::sync::Arc;
+
+use axum::{
+    extract::{Extension, Path, Query, State},
+    http::header,
+    response::{IntoResponse, Response},
+};
+use squirrel_commands::report::{ReportFormat, RunReport};
+
+use crate::{
+    api::{error::AppError, JobReportQuery},
+    auth::extractor::AuthClaims,
+    AppState,
+};
+
+/// Render a report of a workflow run or job as HTML or Markdown
+pub async fn get_job_report(
+    State(state): State<Arc<AppState>>,
+    Extension(claims): Extension<AuthClaims>,
+    Path(job_id): Path<String>,
+    Query(query): Query<JobReportQuery>,
+) -> Result<Response, AppError> {
+    let format = match query.format.as_deref() {
+        Some(format) => format
+            .parse::<ReportFormat>()
+            .map_err(|e| AppError::InvalidRequest(e.to_string()))?,
+        None => ReportFormat::default(),
+    };
+
+    let mut report = match workflow_report(&state, &claims.sub, &job_id).await {
+        Some(report) => report,
+        None => job_report(&state, &claims.sub, &job_id).await?,
+    };
+    for artifact in &mut report.artifacts {
+        if !artifact.href.contains("://") {
+            artifact.href = format!("/api/files/download?path={}", encode_query_value(&artifact.href));
+        }
+    }
+
+    Ok(([(header::CONTENT_TYPE, format.content_type())], report.render(format)).into_response())
+}
+
+/// Report of a workflow run started by the user, if there is one
+async fn workflow_report(state: &AppState, user_id: &str, run_id: &str) -> Option<RunReport> {
+    let service = state.workflow_service.as_ref()?;
+    let run = service.get_run(user_id, run_id).ok()?;
+    let charts = service.run_metrics(&run).await;
+    Some(
+        charts
+            .into_iter()
+            .fold(RunReport::from_workflow_run(&run), RunReport::with_chart),
+    )
+}
+
+/// Report of a job owned by the user, from its context or final snapshot
+async fn job_report(state: &AppState, user_id: &str, job_id: &str) -> Result<RunReport, AppError> {
+    let binding = state.get_job_contexts()?.get(job_id, user_id).await?;
+    let context_id = binding.snapshot_id.unwrap_or(binding.context_id);
+    let context = state
+        .get_context_manager()?
+        .get_context(context_id)
+        .await
+        .map_err(|_| AppError::NotFound(format!("Context of job {} not found", job_id)))?;
+
+    let finished = binding.finished.map(|state| format!("{:?}", state));
+    let mut report = RunReport::from_job_context(job_id, &context.name, finished.as_deref(), &context.data);
+    report.finished_at = binding.finished_at;
+    report.parameters.insert("context_id".to_string(), binding.context_id.to_string());
+    Ok(report)
+}
+
+/// Percent-encodes a value for a URL query string
+fn encode_query_value(value: &str) -> String {
+    let mut encoded = String::with_capacity(value.len());
+    for byte in value.bytes() {
+        match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'_' | b'.' | b'~' | b'/' => {
+                encoded.push(byte as char)
+            }
+            _ => encoded.push_str(&format!("%{:02X}", byte)),
+        }
+    }
+    encoded
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_query_values_are_percent_encoded() {
+        assert_eq!(encode_query_value("results/plot.png"), "results/plot.png");
+        assert_eq!(encode_query_value("a b&c=ü"), "a%20b%26c%3D%C3%BC");
+    }
+}
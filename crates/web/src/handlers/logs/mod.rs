@@ -0,0 +1,8 @@
+//! Logs module for handling log aggregation API endpoints
+//!
+//! This module contains handlers for receiving log events forwarded by
+//! agents, plugins and the CLI, and for searching them.
+
+mod routes;
+
+pub use routes::log_routes;
@@ -0,0 +1,89 @@
+use axum::{
+    Router,
+    routing::get,
+    extract::{Query, State, Extension},
+    http::HeaderMap,
+    Json,
+};
+use std::sync::Arc;
+use crate::state::AppState;
+use crate::auth::extractor::AuthClaims;
+use crate::handlers::usage::is_admin;
+use crate::logs::{forwarder::AGENT_TOKEN_HEADER, prepare_batch, LogQuery};
+use crate::api::{
+    api_success,
+    logs::{LogBatch, LogBatchResponse, LogSearchParams, LogSearchResponse},
+    error::AppError,
+    ApiResponse,
+};
+
+/// Who events forwarded with the agent registration token are stored as
+const AGENT_SUBMITTER: &str = "agent";
+
+/// Log aggregation routes
+pub fn log_routes() -> Router<Arc<AppState>> {
+    Router::new()
+        .route("/", get(search_logs).post(forward_logs))
+}
+
+/// Store a batch of forwarded log events
+///
+/// Users forward with their access token; agents may forward with their
+/// registration token instead.
+async fn forward_logs(
+    State(state): State<Arc<AppState>>,
+    user: Option<Extension<AuthClaims>>,
+    headers: HeaderMap,
+    Json(batch): Json<LogBatch>,
+) -> Result<Json<ApiResponse<LogBatchResponse>>, AppError> {
+    let store = state.get_log_store()?;
+    let submitted_by = match user {
+        Some(Extension(user)) => user.sub,
+        None => {
+            let token = headers
+                .get(AGENT_TOKEN_HEADER)
+                .and_then(|value| value.to_str().ok())
+                .ok_or_else(|| AppError::Unauthorized("Forwarding logs needs a user or agent token".to_string()))?;
+            state.get_agent_scheduler()?.authorize(Some(token))?;
+            AGENT_SUBMITTER.to_string()
+        }
+    };
+    let events = prepare_batch(&state.config.logs, state.redactor.as_deref(), batch.events)?;
+    let accepted = store.append(&submitted_by, events).await?;
+
+    Ok(api_success(LogBatchResponse { accepted }))
+}
+
+/// Search log events, most recent first
+///
+/// Users see the events they forwarded; admins see every event unless they
+/// pick a user.
+async fn search_logs(
+    State(state): State<Arc<AppState>>,
+    Extension(user): Extension<AuthClaims>,
+    Query(params): Query<LogSearchParams>,
+) -> Result<Json<ApiResponse<LogSearchResponse>>, AppError> {
+    let store = state.get_log_store()?;
+    let submitted_by = match params.user {
+        Some(name) if name != user.sub && !is_admin(&user) => {
+            return Err(AppError::Forbidden("Only admins can search other users' logs".to_string()));
+        }
+        Some(name) => Some(name),
+        None if is_admin(&user) => None,
+        None => Some(user.sub.clone()),
+    };
+    let query = LogQuery {
+        min_level: params.level,
+        component: params.component,
+        source: params.source,
+        run_id: params.run_id,
+        from: params.from,
+        to: params.to,
+        text: params.q,
+        submitted_by,
+        limit: params.limit,
+    };
+    let events = store.search(&query).await?;
+
+    Ok(api_success(LogSearchResponse { events }))
+}
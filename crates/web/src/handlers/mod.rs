@@ -4,4 +4,25 @@
 pub mod health;
 pub mod jobs;
 pub mod commands;
-pub mod auth; 
\ No newline at end of file
+pub mod workflows; 
+pub mod agents;
+pub mod webhooks;
+pub mod plugins;
+pub mod alerts;
+pub mod contexts;
+pub mod capabilities;
+pub mod tools;
+pub mod files;
+pub mod cache;
+pub mod usage;
+pub mod assistant;
+pub mod rollout;
+pub mod tags;
+pub mod admin;
+pub mod flags;
+pub mod users;
+pub mod export;
+pub mod logs;
+pub mod traces;
+pub mod compliance;
+pub mod debug;
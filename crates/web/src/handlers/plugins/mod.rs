@@ -0,0 +1,8 @@
+//! Plugins module for handling plugin API endpoints
+//!
+//! This module contains handlers for reading the log output captured for
+//! each plugin running in this process.
+
+mod routes;
+
+pub use routes::plugin_routes;
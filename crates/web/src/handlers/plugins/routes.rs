@@ -0,0 +1,33 @@
+use axum::{
+    Router,
+    routing::get,
+    extract::{Path, Query},
+    Json,
+};
+use std::sync::Arc;
+use squirrel_commands::plugin_logs;
+use crate::state::AppState;
+use crate::api::{
+    api_success,
+    plugins::{PluginLogsQuery, PluginLogsResponse},
+    error::AppError,
+    ApiResponse,
+};
+
+/// Plugin routes
+pub fn plugin_routes() -> Router<Arc<AppState>> {
+    Router::new()
+        .route("/:name/logs", get(get_plugin_logs))
+}
+
+/// Get the recent log output of a plugin
+async fn get_plugin_logs(
+    Path(name): Path<String>,
+    Query(query): Query<PluginLogsQuery>,
+) -> Result<Json<ApiResponse<PluginLogsResponse>>, AppError> {
+    let lines = plugin_logs::global()
+        .recent(&name, query.lines)
+        .ok_or_else(|| AppError::NotFound(format!("No logs for plugin {}", name)))?;
+
+    Ok(api_success(PluginLogsResponse { plugin: name, lines }))
+}
@@ -0,0 +1,8 @@
+//! Rollout module for handling configuration rollout API endpoints
+//!
+//! This module contains the admin-only handlers that validate a candidate
+//! configuration, canary it, and promote or roll it back.
+
+mod routes;
+
+pub use routes::rollout_routes;
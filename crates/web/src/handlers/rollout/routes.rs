@@ -0,0 +1,163 @@
+use axum::{
+    Router,
+    routing::{get, post},
+    extract::{State, Extension},
+    Json,
+};
+use std::sync::Arc;
+use crate::state::AppState;
+use crate::auth::extractor::AuthClaims;
+use crate::config::Config;
+use crate::handlers::usage::is_admin;
+use crate::rollout::{CandidateStatus, Revision, RolloutError, RolloutStatus, ValidationReport};
+use crate::api::{
+    api_success,
+    rollout::{CanaryRequest, ConfigCandidateRequest, PromoteRequest},
+    error::AppError,
+    ApiResponse,
+};
+
+/// Configuration rollout routes
+pub fn rollout_routes() -> Router<Arc<AppState>> {
+    Router::new()
+        .route("/", get(get_status))
+        .route("/active", get(get_active))
+        .route("/validate", post(validate_config))
+        .route("/candidate", post(propose_candidate).delete(discard_candidate))
+        .route("/candidate/canary", post(start_canary))
+        .route("/candidate/promote", post(promote_candidate))
+        .route("/rollback", post(rollback))
+}
+
+/// Refuse users without the admin role
+fn require_admin(user: &AuthClaims) -> Result<(), AppError> {
+    if is_admin(user) {
+        Ok(())
+    } else {
+        Err(AppError::Forbidden("Only admins can change the server configuration".to_string()))
+    }
+}
+
+/// Map a rollout error to the API error it is reported as
+fn rollout_error(error: RolloutError) -> AppError {
+    match error {
+        RolloutError::Invalid(_) | RolloutError::InvalidPercent(_) => AppError::InvalidRequest(error.to_string()),
+        RolloutError::NoCandidate | RolloutError::NoHistory => AppError::NotFound(error.to_string()),
+        RolloutError::CandidatePending(_) | RolloutError::GateFailed(_) => AppError::Conflict(error.to_string()),
+    }
+}
+
+/// Get the active revision, the candidate with its canary counts, and the history
+async fn get_status(
+    State(state): State<Arc<AppState>>,
+    Extension(user): Extension<AuthClaims>,
+) -> Result<Json<ApiResponse<RolloutStatus>>, AppError> {
+    require_admin(&user)?;
+    let rollout = state.get_config_rollout()?;
+
+    Ok(api_success(rollout.status()))
+}
+
+/// Get the active configuration
+async fn get_active(
+    State(state): State<Arc<AppState>>,
+    Extension(user): Extension<AuthClaims>,
+) -> Result<Json<ApiResponse<Config>>, AppError> {
+    require_admin(&user)?;
+    let rollout = state.get_config_rollout()?;
+
+    Ok(api_success(rollout.active().as_ref().clone()))
+}
+
+/// Validate a configuration without proposing it
+async fn validate_config(
+    State(state): State<Arc<AppState>>,
+    Extension(user): Extension<AuthClaims>,
+    Json(request): Json<ConfigCandidateRequest>,
+) -> Result<Json<ApiResponse<ValidationReport>>, AppError> {
+    require_admin(&user)?;
+    let rollout = state.get_config_rollout()?;
+
+    Ok(api_success(rollout.validate(&request.config)))
+}
+
+/// Propose a candidate configuration, validating it first
+async fn propose_candidate(
+    State(state): State<Arc<AppState>>,
+    Extension(user): Extension<AuthClaims>,
+    Json(request): Json<ConfigCandidateRequest>,
+) -> Result<Json<ApiResponse<CandidateStatus>>, AppError> {
+    require_admin(&user)?;
+    let rollout = state.get_config_rollout()?;
+    let candidate = rollout.propose(request.config, &user.sub).map_err(rollout_error)?;
+    tracing::info!(target: "audit", user = %user.sub, revision = candidate.revision, "Proposed configuration");
+
+    Ok(api_success(candidate))
+}
+
+/// Serve the candidate to a share of the clients
+async fn start_canary(
+    State(state): State<Arc<AppState>>,
+    Extension(user): Extension<AuthClaims>,
+    Json(request): Json<CanaryRequest>,
+) -> Result<Json<ApiResponse<CandidateStatus>>, AppError> {
+    require_admin(&user)?;
+    let rollout = state.get_config_rollout()?;
+    let candidate = rollout.start_canary(request.percent).map_err(rollout_error)?;
+    tracing::info!(
+        target: "audit",
+        user = %user.sub,
+        revision = candidate.revision,
+        percent = request.percent,
+        "Started configuration canary"
+    );
+
+    Ok(api_success(candidate))
+}
+
+/// Make the candidate the active configuration
+async fn promote_candidate(
+    State(state): State<Arc<AppState>>,
+    Extension(user): Extension<AuthClaims>,
+    request: Option<Json<PromoteRequest>>,
+) -> Result<Json<ApiResponse<Revision>>, AppError> {
+    require_admin(&user)?;
+    let rollout = state.get_config_rollout()?;
+    let Json(request) = request.unwrap_or_default();
+    let revision = rollout.promote(&user.sub, request.force).map_err(rollout_error)?;
+    tracing::info!(
+        target: "audit",
+        user = %user.sub,
+        revision = revision.revision,
+        forced = request.force,
+        "Promoted configuration"
+    );
+
+    Ok(api_success(revision))
+}
+
+/// Discard the candidate configuration
+async fn discard_candidate(
+    State(state): State<Arc<AppState>>,
+    Extension(user): Extension<AuthClaims>,
+) -> Result<Json<ApiResponse<CandidateStatus>>, AppError> {
+    require_admin(&user)?;
+    let rollout = state.get_config_rollout()?;
+    let candidate = rollout.discard().map_err(rollout_error)?;
+    tracing::info!(target: "audit", user = %user.sub, revision = candidate.revision, "Discarded configuration");
+
+    Ok(api_success(candidate))
+}
+
+/// Make the configuration replaced last active again
+async fn rollback(
+    State(state): State<Arc<AppState>>,
+    Extension(user): Extension<AuthClaims>,
+) -> Result<Json<ApiResponse<Revision>>, AppError> {
+    require_admin(&user)?;
+    let rollout = state.get_config_rollout()?;
+    let revision = rollout.rollback(&user.sub).map_err(rollout_error)?;
+    tracing::info!(target: "audit", user = %user.sub, revision = revision.revision, "Rolled back configuration");
+
+    Ok(api_success(revision))
+}
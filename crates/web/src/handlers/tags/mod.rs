@@ -0,0 +1,8 @@
+//! Tags module for handling tag API endpoints
+//!
+//! This module contains handlers for labeling jobs, command definitions,
+//! experiments and datasets, and for finding and bulk-changing them by tag.
+
+mod routes;
+
+pub use routes::tag_routes;
@@ -0,0 +1,111 @@
+use axum::{
+    Router,
+    routing::{get, post},
+    extract::{Path, Query, State, Extension},
+    Json,
+};
+use std::sync::Arc;
+use squirrel_core::tags::{parse_tags, ResourceKind, Tag};
+use crate::state::AppState;
+use crate::auth::extractor::AuthClaims;
+use crate::api::{
+    api_success,
+    tags::{
+        AddTagsRequest, BulkTagRequest, FindTaggedQuery, RemoveTagsQuery, TagListResponse,
+        TaggedResourcesResponse,
+    },
+    error::AppError,
+    ApiResponse,
+};
+use crate::tags::TaggedResource;
+
+/// Tag routes
+///
+/// Every route reads and changes the tags the caller assigned.
+pub fn tag_routes() -> Router<Arc<AppState>> {
+    Router::new()
+        .route("/", get(list_tags))
+        .route("/resources", get(find_resources))
+        .route("/bulk", post(bulk_change))
+        .route("/:kind/:id", get(get_tags).post(add_tags).delete(remove_tags))
+}
+
+/// Parse the comma-separated tags of a query parameter
+fn query_tags(tags: Option<&str>) -> Result<Vec<Tag>, AppError> {
+    Ok(parse_tags(tags)?)
+}
+
+/// List the caller's tags, with the number of resources having each
+async fn list_tags(
+    State(state): State<Arc<AppState>>,
+    Extension(user): Extension<AuthClaims>,
+) -> Result<Json<ApiResponse<TagListResponse>>, AppError> {
+    let tags = state.get_tag_store()?.usage(Some(&user.sub)).await?;
+    Ok(api_success(TagListResponse { tags }))
+}
+
+/// Find the resources having all the given tags
+async fn find_resources(
+    State(state): State<Arc<AppState>>,
+    Extension(user): Extension<AuthClaims>,
+    Query(query): Query<FindTaggedQuery>,
+) -> Result<Json<ApiResponse<TaggedResourcesResponse>>, AppError> {
+    let filters = query_tags(query.tag.as_deref())?;
+    let resources = state.get_tag_store()?.find(Some(&user.sub), query.kind, &filters).await?;
+    Ok(api_success(TaggedResourcesResponse { resources }))
+}
+
+/// Add and remove tags on every resource having all the filter tags
+async fn bulk_change(
+    State(state): State<Arc<AppState>>,
+    Extension(user): Extension<AuthClaims>,
+    Json(payload): Json<BulkTagRequest>,
+) -> Result<Json<ApiResponse<TaggedResourcesResponse>>, AppError> {
+    let resources = state
+        .get_tag_store()?
+        .bulk(&user.sub, payload.kind, &payload.filter, &payload.add, &payload.remove)
+        .await?;
+    Ok(api_success(TaggedResourcesResponse { resources }))
+}
+
+/// Get the tags of a resource
+async fn get_tags(
+    State(state): State<Arc<AppState>>,
+    Extension(user): Extension<AuthClaims>,
+    Path((kind, id)): Path<(String, String)>,
+) -> Result<Json<ApiResponse<TaggedResource>>, AppError> {
+    let kind: ResourceKind = kind.parse()?;
+    let tags = state.get_tag_store()?.tags_of(Some(&user.sub), kind, &id).await?;
+    Ok(api_success(TaggedResource { kind, id, tags }))
+}
+
+/// Add tags to a resource
+async fn add_tags(
+    State(state): State<Arc<AppState>>,
+    Extension(user): Extension<AuthClaims>,
+    Path((kind, id)): Path<(String, String)>,
+    Json(payload): Json<AddTagsRequest>,
+) -> Result<Json<ApiResponse<TaggedResource>>, AppError> {
+    let kind: ResourceKind = kind.parse()?;
+    if payload.tags.is_empty() {
+        return Err(AppError::InvalidRequest("No tags to add".to_string()));
+    }
+    let tags = state.get_tag_store()?.add(&user.sub, kind, &id, &payload.tags).await?;
+    Ok(api_success(TaggedResource { kind, id, tags }))
+}
+
+/// Remove tags from a resource
+async fn remove_tags(
+    State(state): State<Arc<AppState>>,
+    Extension(user): Extension<AuthClaims>,
+    Path((kind, id)): Path<(String, String)>,
+    Query(query): Query<RemoveTagsQuery>,
+) -> Result<Json<ApiResponse<TaggedResource>>, AppError> {
+    let kind: ResourceKind = kind.parse()?;
+    let filters = query_tags(query.tag.as_deref())?;
+    if filters.is_empty() {
+        return Err(AppError::InvalidRequest("No tags to remove; pass tag=".to_string()));
+    }
+    let tags = state.get_tag_store()?.remove(&user.sub, kind, &id, &filters).await?;
+    Ok(api_success(TaggedResource { kind, id, tags }))
+}
@@ -0,0 +1,8 @@
+//! Tools module for handling tool API endpoints
+//!
+//! This module contains handlers for inspecting the tools registered with
+//! the MCP tool manager.
+
+mod routes;
+
+pub use routes::tool_routes;
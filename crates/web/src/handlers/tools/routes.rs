@@ -0,0 +1,43 @@
+use axum::{
+    Router,
+    routing::get,
+    extract::{Path, Query, State},
+    Json,
+};
+use std::sync::Arc;
+use crate::state::AppState;
+use crate::api::{
+    api_success,
+    error::AppError,
+    tools::{ToolHistoryQuery, ToolHistoryResponse},
+    ApiResponse,
+};
+
+/// Tool routes
+pub fn tool_routes() -> Router<Arc<AppState>> {
+    Router::new()
+        .route("/:id/history", get(get_tool_history))
+}
+
+/// Get the recent executions of a tool and its last error
+async fn get_tool_history(
+    State(state): State<Arc<AppState>>,
+    Path(tool_id): Path<String>,
+    Query(query): Query<ToolHistoryQuery>,
+) -> Result<Json<ApiResponse<ToolHistoryResponse>>, AppError> {
+    let tool_manager = state.get_tool_manager()?;
+    let mut executions = tool_manager.get_tool_history(&tool_id).await;
+    if executions.is_empty() && tool_manager.get_tool(&tool_id).await.is_none() {
+        return Err(AppError::NotFound(format!("Tool {} not found", tool_id)));
+    }
+    let last_error = tool_manager.get_last_error(&tool_id).await;
+    let total = executions.len();
+    executions.truncate(query.limit);
+
+    Ok(api_success(ToolHistoryResponse {
+        tool_id,
+        executions,
+        last_error,
+        total,
+    }))
+}
@@ -0,0 +1,8 @@
+//! Traces module for handling request trace API endpoints
+//!
+//! This module contains the handler returning the cross-component timeline
+//! of a single request or command.
+
+mod routes;
+
+pub use routes::trace_routes;
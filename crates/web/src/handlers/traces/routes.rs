@@ -0,0 +1,45 @@
+use axum::{
+    Router,
+    routing::get,
+    extract::{Path, State, Extension},
+    Json,
+};
+use std::sync::Arc;
+use crate::state::AppState;
+use crate::auth::extractor::AuthClaims;
+use crate::handlers::usage::is_admin;
+use crate::traces::{Trace, TraceCollector};
+use crate::api::{
+    api_success,
+    error::AppError,
+    ApiResponse,
+};
+
+/// Trace routes
+pub fn trace_routes() -> Router<Arc<AppState>> {
+    Router::new()
+        .route("/:id", get(get_trace))
+}
+
+/// Get the timeline of a request or agent task: the log events forwarded
+/// for it, including MCP messages, its tool executions and its tasks
+///
+/// Admins see whole traces; users see the traces of tasks they submitted,
+/// and otherwise only the events they forwarded.
+async fn get_trace(
+    State(state): State<Arc<AppState>>,
+    Extension(user): Extension<AuthClaims>,
+    Path(id): Path<String>,
+) -> Result<Json<ApiResponse<Trace>>, AppError> {
+    let mut collector = TraceCollector::new().with_log_store(state.get_log_store()?.clone());
+    if let Some(scheduler) = &state.agent_scheduler {
+        collector = collector.with_agent_scheduler(scheduler.clone());
+    }
+    if let Some(tools) = &state.tool_manager {
+        collector = collector.with_tool_manager(tools.clone());
+    }
+    let viewer = (!is_admin(&user)).then_some(user.sub.as_str());
+    let trace = collector.collect(&id, viewer).await?;
+
+    Ok(api_success(trace))
+}
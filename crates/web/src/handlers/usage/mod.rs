@@ -0,0 +1,94 @@
+//! Usage module for handling resource accounting API endpoints
+//!
+//! This module contains handlers for querying resource usage and quotas, and
+//! the helpers other handlers use to charge usage and enforce quotas.
+
+mod routes;
+
+pub use routes::usage_routes;
+
+use axum::http::{HeaderMap, StatusCode};
+use squirrel_monitoring::accounting::{ResourceUsage, DEFAULT_WORKSPACE};
+use tracing::warn;
+
+use crate::api::error::AppError;
+use crate::auth::extractor::AuthClaims;
+use crate::state::AppState;
+
+/// Header naming the workspace a request works in
+pub const WORKSPACE_HEADER: &str = "x-squirrel-workspace";
+
+/// Workspace named by the request's `X-Squirrel-Workspace` header
+pub fn workspace(headers: &HeaderMap) -> String {
+    headers
+        .get(WORKSPACE_HEADER)
+        .and_then(|value| value.to_str().ok())
+        .map(str::trim)
+        .filter(|name| !name.is_empty())
+        .unwrap_or(DEFAULT_WORKSPACE)
+        .to_string()
+}
+
+/// Whether the user may see every user's usage
+pub fn is_admin(user: &AuthClaims) -> bool {
+    user.roles.iter().any(|role| role == "Admin")
+}
+
+/// Refuse new work from a user in a workspace that used up a quota
+pub fn enforce_quotas(state: &AppState, user: &str, workspace: &str) -> Result<(), AppError> {
+    let Some(ledger) = &state.usage_ledger else {
+        return Ok(());
+    };
+    match ledger.check_quotas(user, workspace) {
+        Some(status) => Err(AppError::Custom(
+            StatusCode::TOO_MANY_REQUESTS,
+            format!("Quota exceeded: {}", status),
+        )),
+        None => Ok(()),
+    }
+}
+
+/// Charge usage to a user in a workspace, logging failures
+pub fn record_usage(state: &AppState, user: &str, workspace: &str, usage: ResourceUsage) {
+    if let Some(ledger) = &state.usage_ledger {
+        if let Err(e) = ledger.record(user, workspace, usage) {
+            warn!("Failed to record usage of {} in {}: {}", user, workspace, e);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Arc;
+    use squirrel_monitoring::accounting::{AccountingConfig, Quota, QuotaScope, UsageLedger};
+
+    #[tokio::test]
+    async fn test_submissions_are_refused_once_a_quota_is_used_up() {
+        let quota = Quota {
+            scope: QuotaScope::Workspace,
+            name: Some("lab".to_string()),
+            window_secs: 3600,
+            max_cpu_seconds: None,
+            max_memory_mb_seconds: None,
+            max_storage_bytes: None,
+            max_executions: Some(1),
+        };
+        let config = AccountingConfig { quotas: vec![quota], ..AccountingConfig::default() };
+        let state = AppState {
+            usage_ledger: Some(Arc::new(UsageLedger::in_memory(config))),
+            ..AppState::default()
+        };
+
+        let mut headers = HeaderMap::new();
+        assert_eq!(workspace(&headers), DEFAULT_WORKSPACE);
+        headers.insert(WORKSPACE_HEADER, "lab".parse().unwrap());
+        assert_eq!(workspace(&headers), "lab");
+
+        enforce_quotas(&state, "alice", "lab").unwrap();
+        record_usage(&state, "alice", "lab", ResourceUsage::execution());
+        let result = enforce_quotas(&state, "bob", "lab");
+        assert!(matches!(result, Err(AppError::Custom(StatusCode::TOO_MANY_REQUESTS, _))));
+        enforce_quotas(&state, "bob", DEFAULT_WORKSPACE).unwrap();
+    }
+}
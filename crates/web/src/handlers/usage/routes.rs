@@ -0,0 +1,72 @@
+use axum::{
+    Router,
+    routing::get,
+    extract::{Query, State, Extension},
+    http::HeaderMap,
+    Json,
+};
+use std::sync::Arc;
+use squirrel_monitoring::accounting::UsageQuery;
+use crate::state::AppState;
+use crate::auth::extractor::AuthClaims;
+use super::is_admin;
+use crate::api::{
+    api_success,
+    usage::{QuotaStatusResponse, UsageQueryParams, UsageResponse, UsageTotal},
+    error::AppError,
+    ApiResponse,
+};
+
+/// Usage accounting routes
+pub fn usage_routes() -> Router<Arc<AppState>> {
+    Router::new()
+        .route("/", get(get_usage))
+        .route("/quotas", get(get_quotas))
+}
+
+/// Get usage records and totals for a date range
+///
+/// Users see their own usage; admins see everyone's unless they pick a user.
+async fn get_usage(
+    State(state): State<Arc<AppState>>,
+    Extension(user): Extension<AuthClaims>,
+    Query(params): Query<UsageQueryParams>,
+) -> Result<Json<ApiResponse<UsageResponse>>, AppError> {
+    let ledger = state.get_usage_ledger()?;
+    let subject = match params.user {
+        Some(name) if name != user.sub && !is_admin(&user) => {
+            return Err(AppError::Forbidden("Only admins can see other users' usage".to_string()));
+        }
+        Some(name) => Some(name),
+        None if is_admin(&user) => None,
+        None => Some(user.sub.clone()),
+    };
+    let query = UsageQuery {
+        from: params.from,
+        to: params.to,
+        user: subject,
+        workspace: params.workspace,
+    };
+
+    let records = ledger.records(&query);
+    let totals = ledger
+        .totals(&query)
+        .into_iter()
+        .map(|((user, workspace), usage)| UsageTotal { user, workspace, usage })
+        .collect();
+
+    Ok(api_success(UsageResponse { records, totals }))
+}
+
+/// Get the user's usage against the quotas of the request's workspace
+async fn get_quotas(
+    State(state): State<Arc<AppState>>,
+    Extension(user): Extension<AuthClaims>,
+    headers: HeaderMap,
+) -> Result<Json<ApiResponse<QuotaStatusResponse>>, AppError> {
+    let ledger = state.get_usage_ledger()?;
+    let workspace = super::workspace(&headers);
+    let quotas = ledger.quota_status(&user.sub, &workspace);
+
+    Ok(api_success(QuotaStatusResponse { workspace, quotas }))
+}
@@ -0,0 +1,10 @@
+//! Users module for handling user and team management API endpoints
+//!
+//! This module contains the handlers that create, invite, deactivate and
+//! reactivate accounts, assign roles and issue password reset tokens, which
+//! are admin-only, and those that manage teams, which team owners may use
+//! for their own team. Every change is written to the admin audit log.
+
+mod routes;
+
+pub use routes::{team_routes, user_routes};
@@ -0,0 +1,403 @@
+use axum::{
+    Router,
+    routing::{delete, get, post, put},
+    extract::{Extension, Path, Query, State},
+    Json,
+};
+use std::future::Future;
+use std::sync::Arc;
+use uuid::Uuid;
+use crate::state::AppState;
+use crate::admin::{AuditEntry, AuditOutcome};
+use crate::auth::extractor::AuthClaims;
+use crate::handlers::usage::is_admin;
+use crate::users::{Invitation, IssuedInvitation, PasswordReset, Team, TeamMember, TeamRole, UserAccount};
+use crate::api::{
+    api_success,
+    users::{
+        CreateTeamRequest, CreateUserRequest, InviteUserRequest, SetMemberRequest, SetRoleRequest, TeamDetail,
+        UpdateTeamRequest, UsersQuery,
+    },
+    error::AppError,
+    ApiResponse,
+};
+
+/// User management routes, all admin-only except reading one's own account
+pub fn user_routes() -> Router<Arc<AppState>> {
+    Router::new()
+        .route("/", get(list_users).post(create_user))
+        .route("/invitations", get(list_invitations).post(invite_user))
+        .route("/invitations/:id", delete(revoke_invitation))
+        .route("/:id", get(get_user))
+        .route("/:id/role", put(set_role))
+        .route("/:id/deactivate", post(deactivate_user))
+        .route("/:id/reactivate", post(reactivate_user))
+        .route("/:id/password-reset", post(issue_password_reset))
+}
+
+/// Team management routes
+pub fn team_routes() -> Router<Arc<AppState>> {
+    Router::new()
+        .route("/", get(list_teams).post(create_team))
+        .route("/:id", get(get_team).patch(update_team).delete(delete_team))
+        .route("/:id/members/:user_id", put(set_member).delete(remove_member))
+}
+
+/// Run a change for `user`, refusing it unless `allowed`, and write it to
+/// the audit log whether it was refused, succeeded or failed
+async fn audited<T, Fut>(
+    state: &AppState,
+    user: &AuthClaims,
+    allowed: bool,
+    action: &str,
+    target: Option<&str>,
+    run: Fut,
+) -> Result<T, AppError>
+where
+    Fut: Future<Output = Result<T, AppError>>,
+{
+    let controls = state.get_admin()?;
+    if !allowed {
+        controls.audit(AuditEntry::new(&user.sub, action, target, AuditOutcome::Denied, None)).await;
+        return Err(AppError::Forbidden(format!("Not allowed to {}", action)));
+    }
+    controls.audited(&user.sub, true, action, target, run).await
+}
+
+/// Whether `user` may change a team: admins and owners of the team may
+async fn manages_team(state: &AppState, user: &AuthClaims, team_id: Uuid) -> Result<bool, AppError> {
+    if is_admin(user) {
+        return Ok(true);
+    }
+    let Ok(user_id) = Uuid::parse_str(&user.sub) else {
+        return Ok(false);
+    };
+    Ok(state.get_users()?.team_role(team_id, user_id).await? == Some(TeamRole::Owner))
+}
+
+/// List the accounts
+async fn list_users(
+    State(state): State<Arc<AppState>>,
+    Extension(user): Extension<AuthClaims>,
+    Query(query): Query<UsersQuery>,
+) -> Result<Json<ApiResponse<Vec<UserAccount>>>, AppError> {
+    let users = audited(&state, &user, is_admin(&user), "users.list", None, async {
+        state.get_users()?.users(query.include_deactivated).await
+    })
+    .await?;
+
+    Ok(api_success(users))
+}
+
+/// Create an account
+async fn create_user(
+    State(state): State<Arc<AppState>>,
+    Extension(user): Extension<AuthClaims>,
+    Json(request): Json<CreateUserRequest>,
+) -> Result<Json<ApiResponse<UserAccount>>, AppError> {
+    let account = audited(&state, &user, is_admin(&user), "users.create", Some(&request.username), async {
+        state
+            .get_users()?
+            .create_user(&request.username, &request.email, &request.password, request.role)
+            .await
+    })
+    .await?;
+
+    Ok(api_success(account))
+}
+
+/// Get an account; users may read their own
+async fn get_user(
+    State(state): State<Arc<AppState>>,
+    Extension(user): Extension<AuthClaims>,
+    Path(id): Path<Uuid>,
+) -> Result<Json<ApiResponse<UserAccount>>, AppError> {
+    if !is_admin(&user) && user.sub != id.to_string() {
+        return Err(AppError::Forbidden("Users can only read their own account".to_string()));
+    }
+    let account = state.get_users()?.user(id).await?;
+
+    Ok(api_success(account))
+}
+
+/// Change the role of an account
+async fn set_role(
+    State(state): State<Arc<AppState>>,
+    Extension(user): Extension<AuthClaims>,
+    Path(id): Path<Uuid>,
+    Json(request): Json<SetRoleRequest>,
+) -> Result<Json<ApiResponse<UserAccount>>, AppError> {
+    let target = id.to_string();
+    let account = audited(&state, &user, is_admin(&user), "users.role", Some(&target), async {
+        state.get_users()?.set_role(id, request.role).await
+    })
+    .await?;
+
+    Ok(api_success(account))
+}
+
+/// Deactivate an account, ending its sessions
+async fn deactivate_user(
+    State(state): State<Arc<AppState>>,
+    Extension(user): Extension<AuthClaims>,
+    Path(id): Path<Uuid>,
+) -> Result<Json<ApiResponse<UserAccount>>, AppError> {
+    let target = id.to_string();
+    let account = audited(&state, &user, is_admin(&user), "users.deactivate", Some(&target), async {
+        if user.sub == target {
+            return Err(AppError::InvalidRequest("Admins can not deactivate themselves".to_string()));
+        }
+        state.get_users()?.deactivate(id).await
+    })
+    .await?;
+
+    Ok(api_success(account))
+}
+
+/// Let a deactivated account sign in again
+async fn reactivate_user(
+    State(state): State<Arc<AppState>>,
+    Extension(user): Extension<AuthClaims>,
+    Path(id): Path<Uuid>,
+) -> Result<Json<ApiResponse<UserAccount>>, AppError> {
+    let target = id.to_string();
+    let account = audited(&state, &user, is_admin(&user), "users.reactivate", Some(&target), async {
+        state.get_users()?.reactivate(id).await
+    })
+    .await?;
+
+    Ok(api_success(account))
+}
+
+/// Issue a password reset token, to be handed to the user
+async fn issue_password_reset(
+    State(state): State<Arc<AppState>>,
+    Extension(user): Extension<AuthClaims>,
+    Path(id): Path<Uuid>,
+) -> Result<Json<ApiResponse<PasswordReset>>, AppError> {
+    let target = id.to_string();
+    let reset = audited(&state, &user, is_admin(&user), "users.password_reset", Some(&target), async {
+        state.get_users()?.issue_password_reset(id).await
+    })
+    .await?;
+
+    Ok(api_success(reset))
+}
+
+/// List the invitations neither accepted nor expired
+async fn list_invitations(
+    State(state): State<Arc<AppState>>,
+    Extension(user): Extension<AuthClaims>,
+) -> Result<Json<ApiResponse<Vec<Invitation>>>, AppError> {
+    let invitations = audited(&state, &user, is_admin(&user), "invitations.list", None, async {
+        state.get_users()?.invitations().await
+    })
+    .await?;
+
+    Ok(api_success(invitations))
+}
+
+/// Invite someone to create an account, returning the token to hand them
+async fn invite_user(
+    State(state): State<Arc<AppState>>,
+    Extension(user): Extension<AuthClaims>,
+    Json(request): Json<InviteUserRequest>,
+) -> Result<Json<ApiResponse<IssuedInvitation>>, AppError> {
+    let issued = audited(&state, &user, is_admin(&user), "invitations.create", Some(&request.email), async {
+        state
+            .get_users()?
+            .invite(&user.sub, &request.email, request.role, request.team_id)
+            .await
+    })
+    .await?;
+
+    Ok(api_success(issued))
+}
+
+/// Revoke an invitation
+async fn revoke_invitation(
+    State(state): State<Arc<AppState>>,
+    Extension(user): Extension<AuthClaims>,
+    Path(id): Path<Uuid>,
+) -> Result<Json<ApiResponse<()>>, AppError> {
+    let target = id.to_string();
+    audited(&state, &user, is_admin(&user), "invitations.revoke", Some(&target), async {
+        state.get_users()?.revoke_invitation(id).await
+    })
+    .await?;
+
+    Ok(api_success(()))
+}
+
+/// List every team for admins, and their own teams for other users
+async fn list_teams(
+    State(state): State<Arc<AppState>>,
+    Extension(user): Extension<AuthClaims>,
+) -> Result<Json<ApiResponse<Vec<Team>>>, AppError> {
+    let users = state.get_users()?;
+    let teams = if is_admin(&user) {
+        users.teams(None).await?
+    } else {
+        match Uuid::parse_str(&user.sub) {
+            Ok(user_id) => users.teams(Some(user_id)).await?,
+            Err(_) => Vec::new(),
+        }
+    };
+
+    Ok(api_success(teams))
+}
+
+/// Create a team
+async fn create_team(
+    State(state): State<Arc<AppState>>,
+    Extension(user): Extension<AuthClaims>,
+    Json(request): Json<CreateTeamRequest>,
+) -> Result<Json<ApiResponse<Team>>, AppError> {
+    let team = audited(&state, &user, is_admin(&user), "teams.create", Some(&request.name), async {
+        state
+            .get_users()?
+            .create_team(&user.sub, &request.name, request.description)
+            .await
+    })
+    .await?;
+
+    Ok(api_success(team))
+}
+
+/// Get a team and its members; admins and members may
+async fn get_team(
+    State(state): State<Arc<AppState>>,
+    Extension(user): Extension<AuthClaims>,
+    Path(id): Path<Uuid>,
+) -> Result<Json<ApiResponse<TeamDetail>>, AppError> {
+    let users = state.get_users()?;
+    let team = users.team(id).await?;
+    let members = users.members(id).await?;
+    if !is_admin(&user) && !members.iter().any(|member| member.user_id.to_string() == user.sub) {
+        return Err(AppError::Forbidden("Only members can read a team".to_string()));
+    }
+
+    Ok(api_success(TeamDetail { team, members }))
+}
+
+/// Rename a team or change its description
+async fn update_team(
+    State(state): State<Arc<AppState>>,
+    Extension(user): Extension<AuthClaims>,
+    Path(id): Path<Uuid>,
+    Json(request): Json<UpdateTeamRequest>,
+) -> Result<Json<ApiResponse<Team>>, AppError> {
+    let target = id.to_string();
+    let allowed = manages_team(&state, &user, id).await?;
+    let team = audited(&state, &user, allowed, "teams.update", Some(&target), async {
+        state.get_users()?.update_team(id, request.name, request.description).await
+    })
+    .await?;
+
+    Ok(api_success(team))
+}
+
+/// Delete a team
+async fn delete_team(
+    State(state): State<Arc<AppState>>,
+    Extension(user): Extension<AuthClaims>,
+    Path(id): Path<Uuid>,
+) -> Result<Json<ApiResponse<()>>, AppError> {
+    let target = id.to_string();
+    audited(&state, &user, is_admin(&user), "teams.delete", Some(&target), async {
+        state.get_users()?.delete_team(id).await
+    })
+    .await?;
+
+    Ok(api_success(()))
+}
+
+/// Add a member to a team or change their role
+async fn set_member(
+    State(state): State<Arc<AppState>>,
+    Extension(user): Extension<AuthClaims>,
+    Path((id, user_id)): Path<(Uuid, Uuid)>,
+    request: Option<Json<SetMemberRequest>>,
+) -> Result<Json<ApiResponse<TeamMember>>, AppError> {
+    let role = request.map_or(TeamRole::Member, |Json(request)| request.role);
+    let target = format!("{}/{}", id, user_id);
+    let allowed = manages_team(&state, &user, id).await?;
+    let member = audited(&state, &user, allowed, "teams.set_member", Some(&target), async {
+        state.get_users()?.set_member(id, user_id, role).await
+    })
+    .await?;
+
+    Ok(api_success(member))
+}
+
+/// Remove a member from a team; members may leave on their own
+async fn remove_member(
+    State(state): State<Arc<AppState>>,
+    Extension(user): Extension<AuthClaims>,
+    Path((id, user_id)): Path<(Uuid, Uuid)>,
+) -> Result<Json<ApiResponse<()>>, AppError> {
+    let target = format!("{}/{}", id, user_id);
+    let allowed = user.sub == user_id.to_string() || manages_team(&state, &user, id).await?;
+    audited(&state, &user, allowed, "teams.remove_member", Some(&target), async {
+        state.get_users()?.remove_member(id, user_id).await
+    })
+    .await?;
+
+    Ok(api_success(()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::admin::{AdminControls, MemoryAdminStore};
+    use crate::auth::models::Role;
+    use crate::config::AdminConfig;
+    use crate::test_helpers::claims;
+
+    #[tokio::test]
+    async fn test_team_owners_manage_their_team_but_not_users() {
+        let admin = Arc::new(AdminControls::new(Arc::new(MemoryAdminStore::new()), AdminConfig::default()));
+        let state = Arc::new(AppState {
+            admin: Some(admin.clone()),
+            ..AppState::default()
+        });
+        let users = state.get_users().unwrap().clone();
+        let ada = users.create_user("ada", "ada@example.org", "correct horse", Role::User).await.unwrap();
+        let grace = users.create_user("grace", "grace@example.org", "correct horse", Role::User).await.unwrap();
+        let root = claims("root", &["Admin"]);
+        let owner = claims(&ada.id.to_string(), &["User"]);
+
+        let Json(team) = create_team(
+            State(state.clone()),
+            Extension(root),
+            Json(CreateTeamRequest { name: "genomics".to_string(), description: None }),
+        )
+        .await
+        .unwrap();
+        let team = team.data.unwrap();
+        users.set_member(team.id, ada.id, TeamRole::Owner).await.unwrap();
+
+        let Json(added) = set_member(State(state.clone()), Extension(owner.clone()), Path((team.id, grace.id)), None)
+            .await
+            .unwrap();
+        let member = added.data.unwrap();
+        assert_eq!((member.user_id, member.role), (grace.id, TeamRole::Member));
+        assert_eq!(users.team_role(team.id, grace.id).await.unwrap(), Some(TeamRole::Member));
+        let refused = deactivate_user(State(state.clone()), Extension(owner), Path(grace.id)).await;
+        assert!(matches!(refused, Err(AppError::Forbidden(_))));
+        let outsider = claims(&grace.id.to_string(), &["User"]);
+        let refused = set_member(State(state.clone()), Extension(outsider), Path((team.id, ada.id)), None).await;
+        assert!(matches!(refused, Err(AppError::Forbidden(_))));
+
+        let log = admin.audit_log(10).await.unwrap();
+        assert_eq!(
+            log.iter().map(|entry| (entry.action.as_str(), entry.outcome)).collect::<Vec<_>>(),
+            vec![
+                ("teams.set_member", AuditOutcome::Denied),
+                ("users.deactivate", AuditOutcome::Denied),
+                ("teams.set_member", AuditOutcome::Succeeded),
+                ("teams.create", AuditOutcome::Succeeded),
+            ]
+        );
+    }
+}
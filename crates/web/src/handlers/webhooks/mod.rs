@@ -0,0 +1,12 @@
+//! Webhooks module for handling webhook API endpoints
+//!
+//! This module contains handlers for managing webhook subscriptions and
+//! inspecting their deliveries.
+
+pub mod service;
+
+pub use service::{HttpTransport, Webhook, WebhookDelivery, WebhookEvent, WebhookService};
+
+mod routes;
+
+pub use routes::webhook_routes;
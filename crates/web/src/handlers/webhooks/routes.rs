@@ -0,0 +1,107 @@
+use axum::{
+    Router,
+    routing::get,
+    extract::{Path, State, Extension},
+    Json,
+};
+use std::sync::Arc;
+use crate::state::AppState;
+use crate::auth::extractor::AuthClaims;
+use crate::api::{
+    api_success,
+    webhooks::{
+        CreateWebhookRequest, CreateWebhookResponse, UpdateWebhookRequest,
+        WebhookDeliveryListResponse, WebhookListResponse, WebhookResponse,
+    },
+    error::AppError,
+    ApiResponse,
+};
+
+/// Webhook routes
+pub fn webhook_routes() -> Router<Arc<AppState>> {
+    Router::new()
+        .route("/", get(list_webhooks).post(create_webhook))
+        .route("/:id", get(get_webhook).put(update_webhook).delete(delete_webhook))
+        .route("/:id/deliveries", get(list_deliveries))
+}
+
+/// Register a webhook
+async fn create_webhook(
+    State(state): State<Arc<AppState>>,
+    Extension(user): Extension<AuthClaims>,
+    Json(payload): Json<CreateWebhookRequest>,
+) -> Result<Json<ApiResponse<CreateWebhookResponse>>, AppError> {
+    let webhook_service = state.get_webhook_service()?;
+    let webhook = webhook_service.create(&user.sub, payload)?;
+
+    let response = CreateWebhookResponse {
+        secret: webhook.secret.clone(),
+        webhook: webhook.into(),
+    };
+
+    Ok(api_success(response))
+}
+
+/// List webhooks
+async fn list_webhooks(
+    State(state): State<Arc<AppState>>,
+    Extension(user): Extension<AuthClaims>,
+) -> Result<Json<ApiResponse<WebhookListResponse>>, AppError> {
+    let webhook_service = state.get_webhook_service()?;
+    let webhooks = webhook_service.list(&user.sub)?;
+
+    let response = WebhookListResponse {
+        webhooks: webhooks.into_iter().map(WebhookResponse::from).collect(),
+    };
+
+    Ok(api_success(response))
+}
+
+/// Get a webhook
+async fn get_webhook(
+    State(state): State<Arc<AppState>>,
+    Extension(user): Extension<AuthClaims>,
+    Path(id): Path<String>,
+) -> Result<Json<ApiResponse<WebhookResponse>>, AppError> {
+    let webhook_service = state.get_webhook_service()?;
+    let webhook = webhook_service.get(&user.sub, &id)?;
+
+    Ok(api_success(webhook.into()))
+}
+
+/// Update a webhook
+async fn update_webhook(
+    State(state): State<Arc<AppState>>,
+    Extension(user): Extension<AuthClaims>,
+    Path(id): Path<String>,
+    Json(payload): Json<UpdateWebhookRequest>,
+) -> Result<Json<ApiResponse<WebhookResponse>>, AppError> {
+    let webhook_service = state.get_webhook_service()?;
+    let webhook = webhook_service.update(&user.sub, &id, payload)?;
+
+    Ok(api_success(webhook.into()))
+}
+
+/// Delete a webhook
+async fn delete_webhook(
+    State(state): State<Arc<AppState>>,
+    Extension(user): Extension<AuthClaims>,
+    Path(id): Path<String>,
+) -> Result<Json<ApiResponse<()>>, AppError> {
+    let webhook_service = state.get_webhook_service()?;
+    webhook_service.delete(&user.sub, &id)?;
+
+    Ok(api_success(()))
+}
+
+/// List deliveries of a webhook
+async fn list_deliveries(
+    State(state): State<Arc<AppState>>,
+    Extension(user): Extension<AuthClaims>,
+    Path(id): Path<String>,
+) -> Result<Json<ApiResponse<WebhookDeliveryListResponse>>, AppError> {
+    let webhook_service = state.get_webhook_service()?;
+    let deliveries = webhook_service.deliveries(&user.sub, &id)?;
+
+    Ok(api_success(WebhookDeliveryListResponse { deliveries }))
+}
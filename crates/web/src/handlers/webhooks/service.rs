@@ -0,0 +1,710 @@
+//! Webhook Service implementation
+//!
+//! This module contains the service layer for webhook subscriptions and
+//! deliveries. Each delivery is a JSON POST carrying the headers
+//! `X-Squirrel-Event`, `X-Squirrel-Delivery`, `X-Squirrel-Timestamp` and
+//! `X-Squirrel-Signature`. The signature is `sha256=` followed by the hex
+//! HMAC-SHA256 of `"{timestamp}.{body}"` keyed with the webhook secret, so
+//! receivers can verify both origin and freshness. Failed deliveries are
+//! retried with exponential backoff under a
+//! [`RetryPolicy`].
+//!
+//! The body is `{"id", "event", "created_at", "data"}` unless the webhook
+//! has a payload template. The template is rendered over that object and
+//! must produce JSON; a delivery whose template fails is not sent.
+
+use std::collections::HashMap;
+use std::fmt;
+use std::str::FromStr;
+use std::sync::{Arc, RwLock};
+use std::time::Duration;
+
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use hmac::{Hmac, Mac};
+use serde::{Deserialize, Serialize};
+use sha2::Sha256;
+use squirrel_core::redaction::Redactor;
+use squirrel_core::retry::RetryPolicy;
+use squirrel_core::template::TemplateEngine;
+use tracing::{debug, warn};
+use uuid::Uuid;
+
+use crate::api::error::AppError;
+use crate::api::webhooks::{CreateWebhookRequest, UpdateWebhookRequest};
+
+/// Maximum number of delivery records kept per webhook
+const MAX_DELIVERIES_PER_WEBHOOK: usize = 100;
+
+/// Lifecycle events clients can subscribe to
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum WebhookEvent {
+    /// A job finished
+    #[serde(rename = "job.completed")]
+    JobCompleted,
+    /// A command execution failed
+    #[serde(rename = "command.failed")]
+    CommandFailed,
+    /// A monitoring alert was raised
+    #[serde(rename = "alert.raised")]
+    AlertRaised,
+    /// A plugin was loaded
+    #[serde(rename = "plugin.loaded")]
+    PluginLoaded,
+}
+
+impl WebhookEvent {
+    /// Convert a WebhookEvent to a string
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::JobCompleted => "job.completed",
+            Self::CommandFailed => "command.failed",
+            Self::AlertRaised => "alert.raised",
+            Self::PluginLoaded => "plugin.loaded",
+        }
+    }
+}
+
+impl fmt::Display for WebhookEvent {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl FromStr for WebhookEvent {
+    type Err = AppError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "job.completed" => Ok(Self::JobCompleted),
+            "command.failed" => Ok(Self::CommandFailed),
+            "alert.raised" => Ok(Self::AlertRaised),
+            "plugin.loaded" => Ok(Self::PluginLoaded),
+            _ => Err(AppError::InvalidRequest(format!("Unknown webhook event: {}", s))),
+        }
+    }
+}
+
+/// A registered webhook
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Webhook {
+    /// Webhook ID
+    pub id: String,
+    /// User who registered the webhook
+    pub owner: String,
+    /// URL deliveries are posted to
+    pub url: String,
+    /// Events the webhook receives
+    pub events: Vec<WebhookEvent>,
+    /// Secret used to sign deliveries
+    #[serde(skip_serializing)]
+    pub secret: String,
+    /// Optional description
+    pub description: Option<String>,
+    /// Template rendering the body of deliveries
+    #[serde(default)]
+    pub template: Option<String>,
+    /// Whether deliveries are sent
+    pub active: bool,
+    /// Created timestamp
+    pub created_at: DateTime<Utc>,
+    /// Updated timestamp
+    pub updated_at: DateTime<Utc>,
+}
+
+/// State of a webhook delivery
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum DeliveryStatus {
+    /// Not yet delivered; attempts may be in progress
+    Pending,
+    /// The receiver answered with a 2xx status
+    Succeeded,
+    /// All attempts failed
+    Failed,
+}
+
+/// Record of a single event delivery
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct WebhookDelivery {
+    /// Delivery ID, also sent as `X-Squirrel-Delivery`
+    pub id: String,
+    /// Webhook ID
+    pub webhook_id: String,
+    /// Event type
+    pub event: WebhookEvent,
+    /// Event payload
+    pub payload: serde_json::Value,
+    /// Current state
+    pub status: DeliveryStatus,
+    /// Number of attempts made
+    pub attempts: u32,
+    /// HTTP status of the latest response
+    pub response_status: Option<u16>,
+    /// Error of the latest failed attempt
+    pub error: Option<String>,
+    /// Created timestamp
+    pub created_at: DateTime<Utc>,
+    /// Timestamp of the latest attempt
+    pub last_attempt_at: Option<DateTime<Utc>>,
+}
+
+/// HTTP transport used to post deliveries
+#[async_trait]
+pub trait WebhookTransport: Send + Sync {
+    /// POST `body` to `url` and return the response status code
+    async fn post(&self, url: &str, headers: &[(&str, String)], body: Vec<u8>) -> Result<u16, String>;
+}
+
+/// Transport posting deliveries with reqwest
+pub struct HttpTransport {
+    client: reqwest::Client,
+}
+
+impl HttpTransport {
+    /// Create a new HttpTransport with the given request timeout
+    pub fn new(timeout: Duration) -> Self {
+        let client = reqwest::Client::builder()
+            .timeout(timeout)
+            .build()
+            .unwrap_or_default();
+        Self { client }
+    }
+}
+
+#[async_trait]
+impl WebhookTransport for HttpTransport {
+    async fn post(&self, url: &str, headers: &[(&str, String)], body: Vec<u8>) -> Result<u16, String> {
+        let mut request = self
+            .client
+            .post(url)
+            .header(reqwest::header::CONTENT_TYPE, "application/json")
+            .body(body);
+        for (name, value) in headers {
+            request = request.header(*name, value);
+        }
+        let response = request.send().await.map_err(|e| e.to_string())?;
+        Ok(response.status().as_u16())
+    }
+}
+
+/// How failed deliveries are retried: five attempts, one second apart and
+/// doubling up to five minutes, with jitter
+const DEFAULT_RETRY_POLICY: RetryPolicy =
+    RetryPolicy::exponential(5, Duration::from_secs(1), Duration::from_secs(300)).with_jitter();
+
+/// Compute the `X-Squirrel-Signature` header value for a delivery
+pub fn sign(secret: &str, timestamp: i64, body: &[u8]) -> String {
+    let mut mac = Hmac::<Sha256>::new_from_slice(secret.as_bytes()).expect("HMAC accepts keys of any length");
+    mac.update(timestamp.to_string().as_bytes());
+    mac.update(b".");
+    mac.update(body);
+    format!("sha256={}", hex::encode(mac.finalize().into_bytes()))
+}
+
+/// Shared webhook storage
+type WebhookStore = Arc<RwLock<HashMap<String, Webhook>>>;
+
+/// Shared delivery storage, keyed by webhook ID
+type DeliveryStore = Arc<RwLock<HashMap<String, Vec<WebhookDelivery>>>>;
+
+fn poisoned() -> AppError {
+    AppError::Internal("Webhook store is poisoned".to_string())
+}
+
+fn validate_url(url: &str) -> Result<(), AppError> {
+    let parsed = reqwest::Url::parse(url).map_err(|e| AppError::InvalidRequest(format!("Invalid webhook URL: {}", e)))?;
+    if !matches!(parsed.scheme(), "http" | "https") {
+        return Err(AppError::InvalidRequest("Webhook URL must use http or https".to_string()));
+    }
+    Ok(())
+}
+
+fn validate_events(events: &[WebhookEvent]) -> Result<(), AppError> {
+    if events.is_empty() {
+        return Err(AppError::InvalidRequest("At least one event type is required".to_string()));
+    }
+    Ok(())
+}
+
+fn validate_template(template: &str) -> Result<(), AppError> {
+    TemplateEngine::check(template).map_err(|e| AppError::InvalidRequest(e.to_string()))
+}
+
+/// Render a payload template, which must produce JSON
+fn render_payload(templates: &TemplateEngine, template: &str, envelope: &serde_json::Value) -> Result<Vec<u8>, String> {
+    let body = templates.render_str(template, envelope).map_err(|e| e.to_string())?;
+    serde_json::from_str::<serde_json::Value>(&body)
+        .map_err(|e| format!("Payload template did not render JSON: {}", e))?;
+    Ok(body.into_bytes())
+}
+
+/// Service for managing webhooks and delivering events to them
+pub struct WebhookService {
+    webhooks: WebhookStore,
+    deliveries: DeliveryStore,
+    transport: Arc<dyn WebhookTransport>,
+    retry: RetryPolicy,
+    redactor: Option<Arc<Redactor>>,
+    templates: Arc<TemplateEngine>,
+}
+
+impl WebhookService {
+    /// Create a new WebhookService
+    pub fn new(transport: Arc<dyn WebhookTransport>) -> Self {
+        Self {
+            webhooks: Arc::new(RwLock::new(HashMap::new())),
+            deliveries: Arc::new(RwLock::new(HashMap::new())),
+            transport,
+            retry: DEFAULT_RETRY_POLICY,
+            redactor: None,
+            templates: Arc::new(TemplateEngine::new()),
+        }
+    }
+
+    /// Set the retry policy
+    pub fn with_retry_policy(mut self, retry: RetryPolicy) -> Self {
+        self.retry = retry;
+        self
+    }
+
+    /// Redact event payloads with `redactor` before they are delivered
+    pub fn with_redactor(mut self, redactor: Arc<Redactor>) -> Self {
+        self.redactor = Some(redactor);
+        self
+    }
+
+    /// Register a webhook for the user
+    ///
+    /// A secret is generated when none is given; it is only returned here.
+    pub fn create(&self, owner: &str, request: CreateWebhookRequest) -> Result<Webhook, AppError> {
+        validate_url(&request.url)?;
+        validate_events(&request.events)?;
+        if let Some(template) = &request.template {
+            validate_template(template)?;
+        }
+
+        let now = Utc::now();
+        let webhook = Webhook {
+            id: Uuid::new_v4().to_string(),
+            owner: owner.to_string(),
+            url: request.url,
+            events: request.events,
+            secret: request
+                .secret
+                .unwrap_or_else(|| format!("{}{}", Uuid::new_v4().simple(), Uuid::new_v4().simple())),
+            description: request.description,
+            template: request.template,
+            active: true,
+            created_at: now,
+            updated_at: now,
+        };
+
+        self.webhooks
+            .write()
+            .map_err(|_| poisoned())?
+            .insert(webhook.id.clone(), webhook.clone());
+        Ok(webhook)
+    }
+
+    /// List the user's webhooks, oldest first
+    pub fn list(&self, owner: &str) -> Result<Vec<Webhook>, AppError> {
+        let webhooks = self.webhooks.read().map_err(|_| poisoned())?;
+        let mut list: Vec<Webhook> = webhooks.values().filter(|w| w.owner == owner).cloned().collect();
+        list.sort_by_key(|webhook| webhook.created_at);
+        Ok(list)
+    }
+
+    /// Get one of the user's webhooks
+    pub fn get(&self, owner: &str, id: &str) -> Result<Webhook, AppError> {
+        let webhooks = self.webhooks.read().map_err(|_| poisoned())?;
+        webhooks
+            .get(id)
+            .filter(|webhook| webhook.owner == owner)
+            .cloned()
+            .ok_or_else(|| AppError::NotFound(format!("Webhook {} not found", id)))
+    }
+
+    /// Update one of the user's webhooks
+    pub fn update(&self, owner: &str, id: &str, request: UpdateWebhookRequest) -> Result<Webhook, AppError> {
+        if let Some(url) = &request.url {
+            validate_url(url)?;
+        }
+        if let Some(events) = &request.events {
+            validate_events(events)?;
+        }
+        if let Some(template) = request.template.as_deref().filter(|template| !template.is_empty()) {
+            validate_template(template)?;
+        }
+
+        let mut webhooks = self.webhooks.write().map_err(|_| poisoned())?;
+        let webhook = webhooks
+            .get_mut(id)
+            .filter(|webhook| webhook.owner == owner)
+            .ok_or_else(|| AppError::NotFound(format!("Webhook {} not found", id)))?;
+        if let Some(url) = request.url {
+            webhook.url = url;
+        }
+        if let Some(events) = request.events {
+            webhook.events = events;
+        }
+        if let Some(secret) = request.secret {
+            webhook.secret = secret;
+        }
+        if let Some(description) = request.description {
+            webhook.description = Some(description);
+        }
+        if let Some(template) = request.template {
+            webhook.template = Some(template).filter(|template| !template.is_empty());
+        }
+        if let Some(active) = request.active {
+            webhook.active = active;
+        }
+        webhook.updated_at = Utc::now();
+        Ok(webhook.clone())
+    }
+
+    /// Delete one of the user's webhooks and its delivery history
+    pub fn delete(&self, owner: &str, id: &str) -> Result<(), AppError> {
+        let mut webhooks = self.webhooks.write().map_err(|_| poisoned())?;
+        if webhooks.get(id).is_none_or(|webhook| webhook.owner != owner) {
+            return Err(AppError::NotFound(format!("Webhook {} not found", id)));
+        }
+        webhooks.remove(id);
+        self.deliveries.write().map_err(|_| poisoned())?.remove(id);
+        Ok(())
+    }
+
+    /// Delete every webhook of the user and their delivery history,
+    /// returning how many there were
+    pub fn remove_owner(&self, owner: &str) -> Result<usize, AppError> {
+        let mut webhooks = self.webhooks.write().map_err(|_| poisoned())?;
+        let mut deliveries = self.deliveries.write().map_err(|_| poisoned())?;
+        let owned: Vec<String> = webhooks.values().filter(|w| w.owner == owner).map(|w| w.id.clone()).collect();
+        for id in &owned {
+            webhooks.remove(id);
+            deliveries.remove(id);
+        }
+        Ok(owned.len())
+    }
+
+    /// List deliveries of one of the user's webhooks, newest first
+    pub fn deliveries(&self, owner: &str, id: &str) -> Result<Vec<WebhookDelivery>, AppError> {
+        self.get(owner, id)?;
+        let deliveries = self.deliveries.read().map_err(|_| poisoned())?;
+        let mut list = deliveries.get(id).cloned().unwrap_or_default();
+        list.reverse();
+        Ok(list)
+    }
+
+    /// Delete finished deliveries created before `before`, returning how many
+    /// there were; with `dry_run` they are only counted
+    pub fn prune_deliveries(&self, before: DateTime<Utc>, dry_run: bool) -> Result<u64, AppError> {
+        let mut deliveries = self.deliveries.write().map_err(|_| poisoned())?;
+        let expired = |delivery: &WebhookDelivery| {
+            delivery.status != DeliveryStatus::Pending && delivery.created_at < before
+        };
+        let mut pruned = 0;
+        for list in deliveries.values_mut() {
+            let count = list.iter().filter(|delivery| expired(delivery)).count();
+            if !dry_run {
+                list.retain(|delivery| !expired(delivery));
+            }
+            pruned += count as u64;
+        }
+        Ok(pruned)
+    }
+
+    /// Deliver an event to every active webhook subscribed to it
+    ///
+    /// The payload is redacted first, if a redactor is set. Deliveries run
+    /// in the background; returns the number started.
+    pub fn emit(&self, event: WebhookEvent, mut payload: serde_json::Value) -> usize {
+        let targets: Vec<Webhook> = match self.webhooks.read() {
+            Ok(webhooks) => webhooks
+                .values()
+                .filter(|webhook| webhook.active && webhook.events.contains(&event))
+                .cloned()
+                .collect(),
+            Err(_) => {
+                warn!("Webhook store is poisoned; dropping {} event", event);
+                return 0;
+            }
+        };
+        if let (false, Some(redactor)) = (targets.is_empty(), &self.redactor) {
+            redactor.redact_value(&mut payload);
+        }
+
+        for webhook in &targets {
+            let delivery = WebhookDelivery {
+                id: Uuid::new_v4().to_string(),
+                webhook_id: webhook.id.clone(),
+                event,
+                payload: payload.clone(),
+                status: DeliveryStatus::Pending,
+                attempts: 0,
+                response_status: None,
+                error: None,
+                created_at: Utc::now(),
+                last_attempt_at: None,
+            };
+            if let Ok(mut deliveries) = self.deliveries.write() {
+                let history = deliveries.entry(webhook.id.clone()).or_default();
+                history.push(delivery.clone());
+                if history.len() > MAX_DELIVERIES_PER_WEBHOOK {
+                    history.remove(0);
+                }
+            }
+
+            let deliveries = self.deliveries.clone();
+            let transport = self.transport.clone();
+            let retry = self.retry;
+            let templates = self.templates.clone();
+            let webhook = webhook.clone();
+            tokio::spawn(async move {
+                deliver(webhook, delivery, transport, deliveries, retry, templates).await;
+            });
+        }
+
+        targets.len()
+    }
+}
+
+/// Post a delivery until it succeeds or runs out of attempts
+async fn deliver(
+    webhook: Webhook,
+    mut delivery: WebhookDelivery,
+    transport: Arc<dyn WebhookTransport>,
+    deliveries: DeliveryStore,
+    retry: RetryPolicy,
+    templates: Arc<TemplateEngine>,
+) {
+    let envelope = serde_json::json!({
+        "id": delivery.id,
+        "event": delivery.event,
+        "created_at": delivery.created_at,
+        "data": delivery.payload,
+    });
+    let body = match &webhook.template {
+        Some(template) => match render_payload(&templates, template, &envelope) {
+            Ok(body) => body,
+            Err(e) => {
+                delivery.status = DeliveryStatus::Failed;
+                delivery.error = Some(e);
+                record(&deliveries, &delivery);
+                return;
+            }
+        },
+        None => serde_json::to_vec(&envelope).unwrap_or_default(),
+    };
+
+    let mut attempts = retry.start("webhook.deliver");
+    loop {
+        delivery.attempts += 1;
+        delivery.last_attempt_at = Some(Utc::now());
+
+        let timestamp = Utc::now().timestamp();
+        let headers = [
+            ("X-Squirrel-Event", delivery.event.to_string()),
+            ("X-Squirrel-Delivery", delivery.id.clone()),
+            ("X-Squirrel-Timestamp", timestamp.to_string()),
+            ("X-Squirrel-Signature", sign(&webhook.secret, timestamp, &body)),
+        ];
+        match transport.post(&webhook.url, &headers, body.clone()).await {
+            Ok(status) if (200..300).contains(&status) => {
+                delivery.response_status = Some(status);
+                delivery.error = None;
+                delivery.status = DeliveryStatus::Succeeded;
+            }
+            Ok(status) => {
+                delivery.response_status = Some(status);
+                delivery.error = Some(format!("Receiver responded with status {}", status));
+            }
+            Err(e) => {
+                delivery.response_status = None;
+                delivery.error = Some(e);
+            }
+        }
+
+        if delivery.status == DeliveryStatus::Succeeded {
+            record(&deliveries, &delivery);
+            attempts.succeeded();
+            break;
+        }
+        let delay = attempts.next_delay();
+        if delay.is_none() {
+            delivery.status = DeliveryStatus::Failed;
+        }
+        record(&deliveries, &delivery);
+        match delay {
+            Some(delay) => tokio::time::sleep(delay).await,
+            None => break,
+        }
+    }
+    debug!("Webhook delivery {} finished as {:?}", delivery.id, delivery.status);
+}
+
+/// Store the latest state of a delivery
+fn record(deliveries: &DeliveryStore, delivery: &WebhookDelivery) {
+    if let Ok(mut deliveries) = deliveries.write() {
+        if let Some(stored) = deliveries
+            .get_mut(&delivery.webhook_id)
+            .and_then(|history| history.iter_mut().find(|d| d.id == delivery.id))
+        {
+            *stored = delivery.clone();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Mutex;
+
+    /// A recorded request: URL, headers and body
+    type RecordedRequest = (String, HashMap<String, String>, Vec<u8>);
+
+    /// Transport that fails a number of times, then records successful posts
+    #[derive(Default)]
+    struct RecordingTransport {
+        failures_left: Mutex<u32>,
+        requests: Mutex<Vec<RecordedRequest>>,
+    }
+
+    #[async_trait]
+    impl WebhookTransport for RecordingTransport {
+        async fn post(&self, url: &str, headers: &[(&str, String)], body: Vec<u8>) -> Result<u16, String> {
+            let mut failures_left = self.failures_left.lock().unwrap();
+            if *failures_left > 0 {
+                *failures_left -= 1;
+                return Ok(503);
+            }
+            let headers = headers.iter().map(|(k, v)| (k.to_string(), v.clone())).collect();
+            self.requests.lock().unwrap().push((url.to_string(), headers, body));
+            Ok(204)
+        }
+    }
+
+    fn request(events: Vec<WebhookEvent>) -> CreateWebhookRequest {
+        CreateWebhookRequest {
+            url: "https://example.com/hook".to_string(),
+            events,
+            secret: Some("s3cret".to_string()),
+            description: None,
+            template: None,
+        }
+    }
+
+    fn fast_retries(max_attempts: u32) -> RetryPolicy {
+        RetryPolicy::exponential(max_attempts, Duration::from_millis(1), Duration::from_millis(5))
+    }
+
+    async fn wait_for(service: &WebhookService, owner: &str, id: &str) -> WebhookDelivery {
+        for _ in 0..100 {
+            let deliveries = service.deliveries(owner, id).unwrap();
+            if let Some(delivery) = deliveries.first().filter(|d| d.status != DeliveryStatus::Pending) {
+                return delivery.clone();
+            }
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+        panic!("delivery did not finish");
+    }
+
+    #[tokio::test]
+    async fn test_signed_delivery_with_retries() {
+        let transport = Arc::new(RecordingTransport::default());
+        *transport.failures_left.lock().unwrap() = 2;
+        let redactor = Redactor::new(&squirrel_core::redaction::RedactionConfig::default()).unwrap();
+        let service = WebhookService::new(transport.clone())
+            .with_retry_policy(fast_retries(3))
+            .with_redactor(Arc::new(redactor));
+
+        let webhook = service.create("alice", request(vec![WebhookEvent::CommandFailed])).unwrap();
+        assert_eq!(service.emit(WebhookEvent::JobCompleted, serde_json::json!({})), 0);
+        let payload = serde_json::json!({"command": "x", "env": {"API_KEY": "k1"}});
+        assert_eq!(service.emit(WebhookEvent::CommandFailed, payload), 1);
+
+        let delivery = wait_for(&service, "alice", &webhook.id).await;
+        assert_eq!(delivery.status, DeliveryStatus::Succeeded);
+        assert_eq!(delivery.attempts, 3);
+        assert_eq!(delivery.response_status, Some(204));
+
+        let requests = transport.requests.lock().unwrap();
+        let (_, headers, body) = &requests[0];
+        assert_eq!(headers["X-Squirrel-Event"], "command.failed");
+        let timestamp: i64 = headers["X-Squirrel-Timestamp"].parse().unwrap();
+        assert_eq!(headers["X-Squirrel-Signature"], sign("s3cret", timestamp, body));
+        let body: serde_json::Value = serde_json::from_slice(body).unwrap();
+        assert_eq!(body["data"]["env"]["API_KEY"], "[REDACTED]");
+    }
+
+    #[tokio::test]
+    async fn test_delivery_fails_after_max_attempts() {
+        let transport = Arc::new(RecordingTransport::default());
+        *transport.failures_left.lock().unwrap() = 10;
+        let service = WebhookService::new(transport).with_retry_policy(fast_retries(2));
+
+        let webhook = service.create("alice", request(vec![WebhookEvent::AlertRaised])).unwrap();
+        service.emit(WebhookEvent::AlertRaised, serde_json::json!({}));
+
+        let delivery = wait_for(&service, "alice", &webhook.id).await;
+        assert_eq!(delivery.status, DeliveryStatus::Failed);
+        assert_eq!(delivery.attempts, 2);
+        assert_eq!(delivery.response_status, Some(503));
+    }
+
+    #[tokio::test]
+    async fn test_payload_templates() {
+        let transport = Arc::new(RecordingTransport::default());
+        let service = WebhookService::new(transport.clone()).with_retry_policy(fast_retries(1));
+        let mut create = request(vec![WebhookEvent::JobCompleted]);
+        create.template = Some(r#"{"text": "Job {{data.job}} finished", "event": {{json event}}}"#.to_string());
+        let webhook = service.create("alice", create).unwrap();
+
+        service.emit(WebhookEvent::JobCompleted, serde_json::json!({"job": "j1"}));
+        let delivery = wait_for(&service, "alice", &webhook.id).await;
+        assert_eq!(delivery.status, DeliveryStatus::Succeeded);
+        let body = transport.requests.lock().unwrap()[0].2.clone();
+        let body: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(body, serde_json::json!({"text": "Job j1 finished", "event": "job.completed"}));
+
+        // A template referring to missing data fails the delivery without sending it
+        service.emit(WebhookEvent::JobCompleted, serde_json::json!({}));
+        let delivery = wait_for(&service, "alice", &webhook.id).await;
+        assert_eq!(delivery.status, DeliveryStatus::Failed);
+        assert_eq!(delivery.attempts, 0);
+        assert_eq!(transport.requests.lock().unwrap().len(), 1);
+
+        let update = UpdateWebhookRequest {
+            template: Some("{{#each data}}".to_string()),
+            ..UpdateWebhookRequest::default()
+        };
+        assert!(service.update("alice", &webhook.id, update).is_err());
+        let update = UpdateWebhookRequest {
+            template: Some(String::new()),
+            ..UpdateWebhookRequest::default()
+        };
+        assert!(service.update("alice", &webhook.id, update).unwrap().template.is_none());
+    }
+
+    #[test]
+    fn test_webhooks_are_scoped_and_validated() {
+        let service = WebhookService::new(Arc::new(RecordingTransport::default()));
+        let webhook = service.create("alice", request(vec![WebhookEvent::PluginLoaded])).unwrap();
+
+        assert!(matches!(service.get("bob", &webhook.id), Err(AppError::NotFound(_))));
+        assert!(matches!(service.delete("bob", &webhook.id), Err(AppError::NotFound(_))));
+        assert!(service.create("alice", request(Vec::new())).is_err());
+
+        let mut invalid = request(vec![WebhookEvent::PluginLoaded]);
+        invalid.url = "ftp://example.com".to_string();
+        assert!(service.create("alice", invalid).is_err());
+
+        let update = UpdateWebhookRequest {
+            active: Some(false),
+            ..UpdateWebhookRequest::default()
+        };
+        assert!(!service.update("alice", &webhook.id, update).unwrap().active);
+        assert_eq!(service.emit(WebhookEvent::PluginLoaded, serde_json::json!({})), 0);
+    }
+}
@@ -0,0 +1,12 @@
+//! Workflows module for handling workflow API endpoints
+//!
+//! This module contains handlers for submitting workflow runs and tracking
+//! their progress.
+
+pub mod service;
+
+pub use service::WorkflowService;
+
+mod routes;
+
+pub use routes::workflow_routes;
@@ -0,0 +1,116 @@
+use axum::{
+    Router,
+    routing::{get, post},
+    extract::{Path, State, Extension},
+    http::HeaderMap,
+    Json,
+};
+use std::sync::Arc;
+use squirrel_monitoring::accounting::ResourceUsage;
+use crate::state::AppState;
+use crate::auth::extractor::AuthClaims;
+use crate::handlers::usage::{enforce_quotas, record_usage, workspace};
+use crate::api::{
+    api_success,
+    workflows::{
+        CreateWorkflowRunRequest, CreateWorkflowRunResponse, ValidateWorkflowRequest,
+        ValidateWorkflowResponse, WorkflowRunListResponse, WorkflowRunResponse, WorkflowRunSummary,
+    },
+    error::AppError,
+    ApiResponse,
+};
+
+/// Workflow routes
+pub fn workflow_routes() -> Router<Arc<AppState>> {
+    Router::new()
+        .route("/", post(create_run))
+        .route("/", get(list_runs))
+        .route("/validate", post(validate_workflow))
+        .route("/:id", get(get_run))
+}
+
+/// Start a workflow run, unless the user or workspace used up a quota
+async fn create_run(
+    State(state): State<Arc<AppState>>,
+    Extension(user): Extension<AuthClaims>,
+    headers: HeaderMap,
+    Json(payload): Json<CreateWorkflowRunRequest>,
+) -> Result<Json<ApiResponse<CreateWorkflowRunResponse>>, AppError> {
+    let workflow_service = state.get_workflow_service()?;
+    let workspace = workspace(&headers);
+    state.check_memory_pressure()?;
+    enforce_quotas(&state, &user.sub, &workspace)?;
+    let id = workflow_service.start_run(
+        &user.sub,
+        &workspace,
+        payload.priority,
+        &payload.definition,
+        &payload.inputs,
+    )?;
+    record_usage(&state, &user.sub, &workspace, ResourceUsage::execution());
+
+    let response = CreateWorkflowRunResponse {
+        id: id.clone(),
+        status_url: format!("/api/workflows/{}", id),
+    };
+
+    Ok(api_success(response))
+}
+
+/// List workflow runs
+async fn list_runs(
+    State(state): State<Arc<AppState>>,
+    Extension(user): Extension<AuthClaims>,
+) -> Result<Json<ApiResponse<WorkflowRunListResponse>>, AppError> {
+    let workflow_service = state.get_workflow_service()?;
+    let runs = workflow_service.list_runs(&user.sub)?;
+
+    let response = WorkflowRunListResponse {
+        runs: runs.iter().map(WorkflowRunSummary::from).collect(),
+    };
+
+    Ok(api_success(response))
+}
+
+/// Get workflow run status
+async fn get_run(
+    State(state): State<Arc<AppState>>,
+    Extension(user): Extension<AuthClaims>,
+    Path(id): Path<String>,
+) -> Result<Json<ApiResponse<WorkflowRunResponse>>, AppError> {
+    let workflow_service = state.get_workflow_service()?;
+    let run = workflow_service.get_run(&user.sub, &id)?;
+
+    let response = WorkflowRunResponse {
+        progress: run.progress(),
+        run,
+    };
+
+    Ok(api_success(response))
+}
+
+/// Validate a workflow definition without running it
+async fn validate_workflow(
+    State(state): State<Arc<AppState>>,
+    Json(payload): Json<ValidateWorkflowRequest>,
+) -> Result<Json<ApiResponse<ValidateWorkflowResponse>>, AppError> {
+    let workflow_service = state.get_workflow_service()?;
+    let workflow = workflow_service.validate(&payload.definition)?;
+
+    let execution_order = workflow
+        .execution_order()
+        .map_err(|e| AppError::InvalidRequest(e.to_string()))?
+        .into_iter()
+        .map(String::from)
+        .collect();
+    let mut inputs: Vec<String> = workflow.inputs.keys().cloned().collect();
+    inputs.sort();
+
+    let response = ValidateWorkflowResponse {
+        name: workflow.name.clone(),
+        execution_order,
+        inputs,
+    };
+
+    Ok(api_success(response))
+}
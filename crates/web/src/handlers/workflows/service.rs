@@ -0,0 +1,408 @@
+//! Workflow Service implementation
+//!
+//! This module contains the service layer for running workflows and tracking
+//! their progress. Runs are executed in the background; every progress event
+//! updates the stored run, is broadcast on the `workflow` WebSocket channel
+//! and is recorded as a metric. The compute time of executed steps is charged
+//! to the user and workspace that started the run. Running workflows form the
+//! pool the memory watchdog cancels from, lowest priority first.
+
+use std::collections::{BTreeMap, HashMap};
+use std::sync::{Arc, RwLock};
+
+use squirrel_commands::report::MetricSeries;
+use squirrel_commands::workflow::{
+    CancelHandle, MemoryStepCache, RunStatus, StepRunner, WorkflowDefinition, WorkflowEngine, WorkflowEvent,
+    WorkflowObserver, WorkflowRun,
+};
+use squirrel_monitoring::accounting::{process_memory_mb, ResourceUsage, UsageLedger};
+use squirrel_monitoring::metrics::{Metric, MetricCollector, MetricType};
+use squirrel_monitoring::watchdog::{ExecutionPool, RunningExecution};
+use tracing::warn;
+
+use crate::api::error::AppError;
+use crate::websocket::{ChannelCategory, ConnectionManager};
+
+/// A run together with the user and workspace it was started for
+struct TrackedRun {
+    owner: String,
+    workspace: String,
+    priority: i32,
+    cancel: CancelHandle,
+    run: WorkflowRun,
+}
+
+/// Shared run storage
+type RunStore = Arc<RwLock<HashMap<String, TrackedRun>>>;
+
+/// Observer that keeps stored runs current and publishes progress
+struct RunTracker {
+    runs: RunStore,
+    ws_manager: ConnectionManager,
+    metrics: Option<Arc<dyn MetricCollector>>,
+    usage: Option<Arc<UsageLedger>>,
+}
+
+impl WorkflowObserver for RunTracker {
+    fn on_event(&self, run: &WorkflowRun, event: &WorkflowEvent) {
+        let mut account = None;
+        if let Ok(mut runs) = self.runs.write() {
+            if let Some(tracked) = runs.get_mut(&run.id) {
+                tracked.run = run.clone();
+                account = Some((tracked.owner.clone(), tracked.workspace.clone()));
+            }
+        }
+
+        // Steps run on the blocking pool, so their duration approximates the
+        // CPU time they used; memory is charged at the process's resident size
+        if let (Some(usage), Some((owner, workspace))) = (&self.usage, account) {
+            if let WorkflowEvent::StepCompleted { cached: false, duration_ms, .. } = event {
+                let (usage, duration_ms) = (usage.clone(), *duration_ms);
+                tokio::task::spawn_blocking(move || {
+                    let step = ResourceUsage::compute(duration_ms, process_memory_mb());
+                    if let Err(e) = usage.record(&owner, &workspace, step) {
+                        warn!("Failed to record workflow usage: {}", e);
+                    }
+                });
+            }
+        }
+
+        let data = serde_json::json!({
+            "run_id": run.id,
+            "workflow": run.workflow,
+            "status": run.status,
+            "progress": run.progress(),
+            "event": event,
+        });
+        let ws_manager = self.ws_manager.clone();
+        let channel = run.id.clone();
+        tokio::spawn(async move {
+            if let Err(e) = ws_manager
+                .broadcast_to_channel(ChannelCategory::Workflow, &channel, "workflow_progress", data)
+                .await
+            {
+                warn!("Failed to broadcast workflow progress: {}", e);
+            }
+        });
+
+        if let Some(metrics) = &self.metrics {
+            for metric in event_metrics(&run.workflow, event) {
+                let metrics = metrics.clone();
+                tokio::spawn(async move {
+                    if let Err(e) = metrics.record_metric(metric).await {
+                        warn!("Failed to record workflow metric: {}", e);
+                    }
+                });
+            }
+        }
+    }
+}
+
+/// Converts a progress event into metrics
+fn event_metrics(workflow: &str, event: &WorkflowEvent) -> Vec<Metric> {
+    let labels = |extra: &[(&str, &str)]| {
+        let mut labels = HashMap::from([("workflow".to_string(), workflow.to_string())]);
+        labels.extend(extra.iter().map(|(k, v)| (k.to_string(), v.to_string())));
+        labels
+    };
+
+    match event {
+        WorkflowEvent::StepCompleted { step, cached, duration_ms } => {
+            let outcome = if *cached { "cached" } else { "succeeded" };
+            let labels = labels(&[("step", step), ("outcome", outcome)]);
+            vec![
+                Metric::new("workflow_steps_total", 1.0, MetricType::Counter, labels.clone()),
+                Metric::new(
+                    "workflow_step_duration_ms",
+                    *duration_ms as f64,
+                    MetricType::Histogram,
+                    labels,
+                ),
+            ]
+        }
+        WorkflowEvent::StepFailed { step, .. } | WorkflowEvent::StepSkipped { step, .. } => {
+            let outcome = if matches!(event, WorkflowEvent::StepFailed { .. }) { "failed" } else { "skipped" };
+            let labels = labels(&[("step", step), ("outcome", outcome)]);
+            vec![Metric::new("workflow_steps_total", 1.0, MetricType::Counter, labels)]
+        }
+        WorkflowEvent::StepRetrying { step, .. } => {
+            let labels = labels(&[("step", step)]);
+            vec![Metric::new("workflow_step_retries_total", 1.0, MetricType::Counter, labels)]
+        }
+        WorkflowEvent::RunFinished { status, duration_ms } => {
+            let status = match status {
+                RunStatus::Succeeded => "succeeded",
+                RunStatus::Failed => "failed",
+                RunStatus::Cancelled => "cancelled",
+                RunStatus::Pending | RunStatus::Running => "unknown",
+            };
+            let labels = labels(&[("status", status)]);
+            vec![
+                Metric::new("workflow_runs_total", 1.0, MetricType::Counter, labels.clone()),
+                Metric::new(
+                    "workflow_run_duration_ms",
+                    *duration_ms as f64,
+                    MetricType::Histogram,
+                    labels,
+                ),
+            ]
+        }
+        WorkflowEvent::RunStarted { .. } | WorkflowEvent::StepStarted { .. } => Vec::new(),
+    }
+}
+
+/// Service for running workflows and tracking their progress
+pub struct WorkflowService {
+    engine: Arc<WorkflowEngine>,
+    runs: RunStore,
+    metrics: Option<Arc<dyn MetricCollector>>,
+}
+
+impl WorkflowService {
+    /// Create a new WorkflowService
+    pub fn new(
+        runner: Arc<dyn StepRunner>,
+        ws_manager: ConnectionManager,
+        metrics: Option<Arc<dyn MetricCollector>>,
+        usage: Option<Arc<UsageLedger>>,
+    ) -> Self {
+        let runs: RunStore = Arc::new(RwLock::new(HashMap::new()));
+        let tracker = Arc::new(RunTracker {
+            runs: runs.clone(),
+            ws_manager,
+            metrics: metrics.clone(),
+            usage,
+        });
+        let engine = WorkflowEngine::new(runner)
+            .with_cache(Arc::new(MemoryStepCache::new()))
+            .with_observer(tracker);
+
+        Self {
+            engine: Arc::new(engine),
+            runs,
+            metrics,
+        }
+    }
+
+    /// Parse and validate a workflow definition
+    pub fn validate(&self, definition: &str) -> Result<WorkflowDefinition, AppError> {
+        WorkflowDefinition::from_yaml(definition).map_err(|e| AppError::InvalidRequest(e.to_string()))
+    }
+
+    /// Start a workflow run for the user in a workspace in the background and
+    /// return its ID
+    ///
+    /// Under memory pressure, runs with a lower `priority` are cancelled first.
+    pub fn start_run(
+        &self,
+        user_id: &str,
+        workspace: &str,
+        priority: i32,
+        definition: &str,
+        inputs: &HashMap<String, String>,
+    ) -> Result<String, AppError> {
+        let workflow = self.validate(definition)?;
+        let run = WorkflowRun::new(&workflow, inputs).map_err(|e| AppError::InvalidRequest(e.to_string()))?;
+        let run_id = run.id.clone();
+        let cancel = CancelHandle::new();
+
+        self.runs
+            .write()
+            .map_err(|_| AppError::Internal("Workflow run store is poisoned".to_string()))?
+            .insert(
+                run_id.clone(),
+                TrackedRun {
+                    owner: user_id.to_string(),
+                    workspace: workspace.to_string(),
+                    priority,
+                    cancel: cancel.clone(),
+                    run: run.clone(),
+                },
+            );
+
+        let engine = self.engine.clone();
+        tokio::spawn(async move {
+            engine.execute_cancellable(&workflow, run, &cancel).await;
+        });
+
+        Ok(run_id)
+    }
+
+    /// Get a workflow run started by the user
+    pub fn get_run(&self, user_id: &str, run_id: &str) -> Result<WorkflowRun, AppError> {
+        let runs = self
+            .runs
+            .read()
+            .map_err(|_| AppError::Internal("Workflow run store is poisoned".to_string()))?;
+        runs.get(run_id)
+            .filter(|tracked| tracked.owner == user_id)
+            .map(|tracked| tracked.run.clone())
+            .ok_or_else(|| AppError::NotFound(format!("Workflow run {} not found", run_id)))
+    }
+
+    /// List workflow runs started by the user, newest first
+    pub fn list_runs(&self, user_id: &str) -> Result<Vec<WorkflowRun>, AppError> {
+        let runs = self
+            .runs
+            .read()
+            .map_err(|_| AppError::Internal("Workflow run store is poisoned".to_string()))?;
+        let mut list: Vec<WorkflowRun> = runs
+            .values()
+            .filter(|tracked| tracked.owner == user_id)
+            .map(|tracked| tracked.run.clone())
+            .collect();
+        list.sort_by_key(|run| std::cmp::Reverse(run.created_at));
+        Ok(list)
+    }
+
+    /// Series of the gauges and histograms recorded for the workflow of a run
+    /// while it ran, one per metric and step
+    pub async fn run_metrics(&self, run: &WorkflowRun) -> Vec<MetricSeries> {
+        let Some(metrics) = &self.metrics else {
+            return Vec::new();
+        };
+        let recorded = match metrics.collect_metrics().await {
+            Ok(recorded) => recorded,
+            Err(e) => {
+                warn!("Failed to read workflow metrics: {}", e);
+                return Vec::new();
+            }
+        };
+
+        let from = run.started_at.unwrap_or(run.created_at).timestamp();
+        let to = run.finished_at.map_or(i64::MAX, |finished| finished.timestamp());
+        let mut series: BTreeMap<String, Vec<(i64, f64)>> = BTreeMap::new();
+        for metric in recorded {
+            if metric.metric_type == MetricType::Counter
+                || metric.labels.get("workflow") != Some(&run.workflow)
+                || !(from..=to).contains(&metric.timestamp)
+            {
+                continue;
+            }
+            let name = match metric.labels.get("step") {
+                Some(step) => format!("{} ({})", metric.name, step),
+                None => metric.name.to_string(),
+            };
+            series.entry(name).or_default().push((metric.timestamp, metric.value));
+        }
+        series
+            .into_iter()
+            .map(|(name, samples)| MetricSeries::from_samples(name, samples))
+            .collect()
+    }
+}
+
+impl ExecutionPool for WorkflowService {
+    fn running(&self) -> Vec<RunningExecution> {
+        let Ok(runs) = self.runs.read() else {
+            return Vec::new();
+        };
+        runs.values()
+            .filter(|tracked| matches!(tracked.run.status, RunStatus::Pending | RunStatus::Running))
+            .filter(|tracked| tracked.cancel.reason().is_none())
+            .map(|tracked| RunningExecution {
+                id: tracked.run.id.clone(),
+                priority: tracked.priority,
+                started_at: tracked.run.started_at.unwrap_or(tracked.run.created_at),
+                description: format!("workflow '{}' of {}", tracked.run.workflow, tracked.owner),
+            })
+            .collect()
+    }
+
+    fn cancel(&self, id: &str, reason: &str) -> bool {
+        let Ok(runs) = self.runs.read() else {
+            return false;
+        };
+        let Some(tracked) = runs.get(id) else {
+            return false;
+        };
+        let finished = matches!(tracked.run.status, RunStatus::Succeeded | RunStatus::Failed | RunStatus::Cancelled);
+        if finished || tracked.cancel.reason().is_some() {
+            return false;
+        }
+        tracked.cancel.cancel(reason);
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use squirrel_commands::builtin::EchoCommand;
+    use squirrel_commands::CommandRegistry;
+    use std::time::Duration;
+
+    #[tokio::test]
+    async fn test_run_is_tracked_until_finished() {
+        let registry = CommandRegistry::new();
+        registry.register("echo", Arc::new(EchoCommand::new())).unwrap();
+        let service = WorkflowService::new(Arc::new(registry), crate::websocket::init(), None, None);
+
+        let definition = "name: hello\nsteps:\n  - {id: greet, command: echo, args: [hi]}\n";
+        let id = service.start_run("alice", "default", 0, definition, &HashMap::new()).unwrap();
+        assert!(service.get_run("bob", &id).is_err());
+
+        let mut run = service.get_run("alice", &id).unwrap();
+        for _ in 0..50 {
+            if run.status == RunStatus::Succeeded {
+                break;
+            }
+            tokio::time::sleep(Duration::from_millis(20)).await;
+            run = service.get_run("alice", &id).unwrap();
+        }
+        assert_eq!(run.status, RunStatus::Succeeded);
+        assert_eq!(run.step("greet").unwrap().output.as_deref(), Some("Echo: hi"));
+        assert_eq!(service.list_runs("alice").unwrap().len(), 1);
+    }
+
+    #[test]
+    fn test_invalid_definition_is_rejected() {
+        let service = WorkflowService::new(Arc::new(CommandRegistry::new()), crate::websocket::init(), None, None);
+        let result = service.start_run("alice", "default", 0, "name: broken\nsteps: []\n", &HashMap::new());
+        assert!(matches!(result, Err(AppError::InvalidRequest(_))));
+    }
+
+    /// Runner whose steps wait until they are aborted
+    struct StuckRunner;
+
+    #[async_trait::async_trait]
+    impl StepRunner for StuckRunner {
+        async fn run(
+            &self,
+            _kind: squirrel_commands::workflow::StepKind,
+            _target: &str,
+            _args: &[String],
+        ) -> squirrel_commands::CommandResult<String> {
+            std::future::pending().await
+        }
+    }
+
+    #[tokio::test]
+    async fn test_watchdog_cancels_lowest_priority_run() {
+        let service = WorkflowService::new(Arc::new(StuckRunner), crate::websocket::init(), None, None);
+        let definition = "name: stuck\nsteps:\n  - {id: wait, command: sleep}\n";
+        let batch = service.start_run("alice", "default", -5, definition, &HashMap::new()).unwrap();
+        let urgent = service.start_run("bob", "default", 5, definition, &HashMap::new()).unwrap();
+        tokio::time::sleep(Duration::from_millis(20)).await;
+
+        let mut running = service.running();
+        running.sort_by_key(|execution| execution.priority);
+        assert_eq!(running.len(), 2);
+        assert_eq!(running[0].id, batch);
+        assert!(service.cancel(&batch, "memory pressure"));
+        assert!(!service.cancel(&batch, "memory pressure"));
+
+        let mut run = service.get_run("alice", &batch).unwrap();
+        for _ in 0..50 {
+            if run.status == RunStatus::Cancelled {
+                break;
+            }
+            tokio::time::sleep(Duration::from_millis(20)).await;
+            run = service.get_run("alice", &batch).unwrap();
+        }
+        assert_eq!(run.status, RunStatus::Cancelled);
+        assert_eq!(run.step("wait").unwrap().skip_reason.as_deref(), Some("run cancelled: memory pressure"));
+        assert_eq!(service.running().len(), 1);
+        assert_eq!(service.get_run("bob", &urgent).unwrap().status, RunStatus::Running);
+    }
+}
@@ -0,0 +1,93 @@
+//! Localized API responses.
+//!
+//! [`negotiate_locale`] picks the locale of each request from its
+//! `Accept-Language` header among the locales with a catalog of Squirrel's
+//! messages, falling back to the configured `locale`. Handlers and error
+//! responses read it with [`request_locale`]; error bodies carry a
+//! localized `title` beside their stable `code`, and a `Content-Language`
+//! header naming the locale.
+
+use std::sync::Arc;
+
+use axum::{
+    extract::State,
+    http::{header, Request},
+    middleware::Next,
+    response::Response,
+};
+use squirrel_core::i18n::{self, localizer, Args, Locale, DEFAULT_DOMAIN};
+
+tokio::task_local! {
+    /// Locale negotiated for the request being handled
+    static REQUEST_LOCALE: Locale;
+}
+
+/// Negotiate the locale of a request and handle it in that locale
+pub async fn negotiate_locale<B>(
+    State(default): State<Arc<Locale>>,
+    req: Request<B>,
+    next: Next<B>,
+) -> Response {
+    let locale = req
+        .headers()
+        .get(header::ACCEPT_LANGUAGE)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|accept| i18n::negotiate(accept, &localizer().locales(DEFAULT_DOMAIN)).cloned())
+        .unwrap_or_else(|| default.as_ref().clone());
+    REQUEST_LOCALE.scope(locale, next.run(req)).await
+}
+
+/// Locale of the request being handled, or the process locale outside one
+pub fn request_locale() -> Locale {
+    REQUEST_LOCALE
+        .try_with(Clone::clone)
+        .unwrap_or_else(|_| localizer().locale())
+}
+
+/// Format one of Squirrel's messages in the locale of the request
+pub fn message(key: &str, args: &Args<'_>) -> String {
+    localizer().message(&request_locale(), DEFAULT_DOMAIN, key, args)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use axum::{
+        body::{Body, HttpBody}, http::StatusCode, middleware::from_fn_with_state, routing::get, Router,
+    };
+    use tower::ServiceExt;
+
+    use crate::api::error::AppError;
+
+    #[tokio::test]
+    async fn test_error_titles_follow_accept_language() {
+        let default: Locale = "de".parse().unwrap();
+        let app = Router::new()
+            .route("/", get(|| async { Err::<(), _>(AppError::NotFound("Job 1 not found".to_string())) }))
+            .layer(from_fn_with_state(Arc::new(default), negotiate_locale));
+        let request = |accept: Option<&str>| {
+            let mut request = Request::builder().uri("/");
+            if let Some(accept) = accept {
+                request = request.header(header::ACCEPT_LANGUAGE, accept);
+            }
+            request.body(Body::empty()).unwrap()
+        };
+        let title = |response: Response| async move {
+            let language = response.headers()[header::CONTENT_LANGUAGE].to_str().unwrap().to_string();
+            let body = response.into_body().data().await.unwrap().unwrap();
+            let body: serde_json::Value = serde_json::from_slice(&body).unwrap();
+            assert_eq!(body["error"]["code"], "not_found");
+            assert_eq!(body["error"]["message"], "Job 1 not found");
+            (language, body["error"]["title"].as_str().unwrap().to_string())
+        };
+
+        let response = app.clone().oneshot(request(Some("fr-CH, en;q=0.8"))).await.unwrap();
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+        assert_eq!(title(response).await, ("en".to_string(), "Not found".to_string()));
+        let response = app.clone().oneshot(request(Some("de-AT"))).await.unwrap();
+        assert_eq!(title(response).await, ("de".to_string(), "Nicht gefunden".to_string()));
+        let response = app.oneshot(request(None)).await.unwrap();
+        assert_eq!(title(response).await, ("de".to_string(), "Nicht gefunden".to_string()));
+    }
+}
@@ -0,0 +1,473 @@
+//! Idempotency keys guarding submissions against running twice.
+//!
+//! A client retrying `POST /api/commands` or `POST /api/jobs` sends the same
+//! `Idempotency-Key` header as the first attempt. The first submission with a
+//! key reserves it and, once it succeeds, stores its response; repeats
+//! within the key's lifetime get that response back instead of a new
+//! execution. Keys are scoped by user and route, and a repeat whose payload
+//! differs from the original is rejected. Keys are stored in the
+//! `idempotency_keys` table; [`MemoryIdempotencyStore`] keeps them in memory
+//! for the mock database mode.
+
+use std::collections::HashMap;
+use std::future::Future;
+use std::sync::Arc;
+use std::time::Duration;
+
+use async_trait::async_trait;
+use axum::http::{HeaderMap, StatusCode};
+use chrono::{DateTime, Utc};
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use sha2::{Digest, Sha256};
+use squirrel_app::supervisor::{RestartPolicy, Supervisor};
+use sqlx::{Row, SqlitePool};
+use tokio::sync::Mutex;
+use tracing::warn;
+
+use crate::api::error::AppError;
+use crate::config::IdempotencyConfig;
+use crate::db::InstrumentedPool;
+
+/// Header carrying the idempotency key of a submission
+pub const IDEMPOTENCY_KEY_HEADER: &str = "idempotency-key";
+
+/// Longest accepted idempotency key
+pub const MAX_KEY_LEN: usize = 255;
+
+/// What a submission found when reserving its key
+#[derive(Debug, Clone, PartialEq)]
+pub enum Reservation {
+    /// The key was free and is now reserved for the submission
+    Reserved,
+    /// A submission with the key succeeded with this response
+    Completed(serde_json::Value),
+    /// A submission with the key is still running
+    InProgress,
+    /// The key was used with a different payload
+    Mismatch,
+}
+
+/// Storage for idempotency keys
+#[async_trait]
+pub trait IdempotencyStore: Send + Sync {
+    /// Reserve `key` of a user in `scope` for a submission whose payload has
+    /// `fingerprint`, until `expires_at`
+    ///
+    /// An expired key is free again.
+    async fn reserve(
+        &self,
+        user_id: &str,
+        scope: &str,
+        key: &str,
+        fingerprint: &str,
+        expires_at: DateTime<Utc>,
+    ) -> Result<Reservation, AppError>;
+
+    /// Store the response of the submission holding a key, keeping it until `expires_at`
+    async fn complete(
+        &self,
+        user_id: &str,
+        scope: &str,
+        key: &str,
+        response: &serde_json::Value,
+        expires_at: DateTime<Utc>,
+    ) -> Result<(), AppError>;
+
+    /// Free a key whose submission failed, so that it can be retried
+    async fn release(&self, user_id: &str, scope: &str, key: &str) -> Result<(), AppError>;
+
+    /// Delete the keys that expired at or before `now`, returning how many
+    async fn purge_expired(&self, now: DateTime<Utc>) -> Result<u64, AppError>;
+}
+
+/// Idempotency key store backed by the `idempotency_keys` table
+pub struct SqlIdempotencyStore {
+    pool: InstrumentedPool,
+}
+
+impl SqlIdempotencyStore {
+    /// Create a new SqlIdempotencyStore
+    pub fn new(pool: SqlitePool) -> Self {
+        Self { pool: pool.into() }
+    }
+}
+
+#[async_trait]
+impl IdempotencyStore for SqlIdempotencyStore {
+    async fn reserve(
+        &self,
+        user_id: &str,
+        scope: &str,
+        key: &str,
+        fingerprint: &str,
+        expires_at: DateTime<Utc>,
+    ) -> Result<Reservation, AppError> {
+        let now = Utc::now().timestamp_millis();
+        let mut tx = self.pool.begin().await?;
+        sqlx::query("DELETE FROM idempotency_keys WHERE user_id = ? AND scope = ? AND key = ? AND expires_at <= ?")
+            .bind(user_id)
+            .bind(scope)
+            .bind(key)
+            .bind(now)
+            .execute(&mut *tx)
+            .await?;
+        let reserved = sqlx::query(
+            "INSERT INTO idempotency_keys (user_id, scope, key, fingerprint, created_at, expires_at)
+             VALUES (?, ?, ?, ?, ?, ?)
+             ON CONFLICT(user_id, scope, key) DO NOTHING",
+        )
+        .bind(user_id)
+        .bind(scope)
+        .bind(key)
+        .bind(fingerprint)
+        .bind(now)
+        .bind(expires_at.timestamp_millis())
+        .execute(&mut *tx)
+        .await?
+        .rows_affected();
+
+        let reservation = if reserved == 1 {
+            Reservation::Reserved
+        } else {
+            let row = sqlx::query("SELECT fingerprint, response FROM idempotency_keys WHERE user_id = ? AND scope = ? AND key = ?")
+                .bind(user_id)
+                .bind(scope)
+                .bind(key)
+                .fetch_one(&mut *tx)
+                .await?;
+            let stored: String = row.try_get("fingerprint")?;
+            let response: Option<String> = row.try_get("response")?;
+            match response {
+                _ if stored != fingerprint => Reservation::Mismatch,
+                Some(response) => Reservation::Completed(
+                    serde_json::from_str(&response)
+                        .map_err(|e| AppError::Internal(format!("Invalid stored response: {}", e)))?,
+                ),
+                None => Reservation::InProgress,
+            }
+        };
+        tx.commit().await?;
+        Ok(reservation)
+    }
+
+    async fn complete(
+        &self,
+        user_id: &str,
+        scope: &str,
+        key: &str,
+        response: &serde_json::Value,
+        expires_at: DateTime<Utc>,
+    ) -> Result<(), AppError> {
+        sqlx::query("UPDATE idempotency_keys SET response = ?, expires_at = ? WHERE user_id = ? AND scope = ? AND key = ?")
+            .bind(response.to_string())
+            .bind(expires_at.timestamp_millis())
+            .bind(user_id)
+            .bind(scope)
+            .bind(key)
+            .execute(&self.pool)
+            .await?;
+        Ok(())
+    }
+
+    async fn release(&self, user_id: &str, scope: &str, key: &str) -> Result<(), AppError> {
+        sqlx::query(
+            "DELETE FROM idempotency_keys WHERE user_id = ? AND scope = ? AND key = ? AND response IS NULL",
+        )
+        .bind(user_id)
+        .bind(scope)
+        .bind(key)
+        .execute(&self.pool)
+        .await?;
+        Ok(())
+    }
+
+    async fn purge_expired(&self, now: DateTime<Utc>) -> Result<u64, AppError> {
+        let purged = sqlx::query("DELETE FROM idempotency_keys WHERE expires_at <= ?")
+            .bind(now.timestamp_millis())
+            .execute(&self.pool)
+            .await?
+            .rows_affected();
+        Ok(purged)
+    }
+}
+
+/// A stored key
+struct StoredKey {
+    fingerprint: String,
+    response: Option<serde_json::Value>,
+    expires_at: DateTime<Utc>,
+}
+
+/// In-process idempotency key store for the mock database mode and tests
+#[derive(Default)]
+pub struct MemoryIdempotencyStore {
+    keys: Mutex<HashMap<(String, String, String), StoredKey>>,
+}
+
+impl MemoryIdempotencyStore {
+    /// Create a new MemoryIdempotencyStore
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+#[async_trait]
+impl IdempotencyStore for MemoryIdempotencyStore {
+    async fn reserve(
+        &self,
+        user_id: &str,
+        scope: &str,
+        key: &str,
+        fingerprint: &str,
+        expires_at: DateTime<Utc>,
+    ) -> Result<Reservation, AppError> {
+        let mut keys = self.keys.lock().await;
+        let id = (user_id.to_string(), scope.to_string(), key.to_string());
+        match keys.get(&id) {
+            Some(stored) if stored.expires_at > Utc::now() => Ok(match &stored.response {
+                _ if stored.fingerprint != fingerprint => Reservation::Mismatch,
+                Some(response) => Reservation::Completed(response.clone()),
+                None => Reservation::InProgress,
+            }),
+            _ => {
+                keys.insert(
+                    id,
+                    StoredKey {
+                        fingerprint: fingerprint.to_string(),
+                        response: None,
+                        expires_at,
+                    },
+                );
+                Ok(Reservation::Reserved)
+            }
+        }
+    }
+
+    async fn complete(
+        &self,
+        user_id: &str,
+        scope: &str,
+        key: &str,
+        response: &serde_json::Value,
+        expires_at: DateTime<Utc>,
+    ) -> Result<(), AppError> {
+        let id = (user_id.to_string(), scope.to_string(), key.to_string());
+        if let Some(stored) = self.keys.lock().await.get_mut(&id) {
+            stored.response = Some(response.clone());
+            stored.expires_at = expires_at;
+        }
+        Ok(())
+    }
+
+    async fn release(&self, user_id: &str, scope: &str, key: &str) -> Result<(), AppError> {
+        let mut keys = self.keys.lock().await;
+        let id = (user_id.to_string(), scope.to_string(), key.to_string());
+        if keys.get(&id).is_some_and(|stored| stored.response.is_none()) {
+            keys.remove(&id);
+        }
+        Ok(())
+    }
+
+    async fn purge_expired(&self, now: DateTime<Utc>) -> Result<u64, AppError> {
+        let mut keys = self.keys.lock().await;
+        let before = keys.len();
+        keys.retain(|_, stored| stored.expires_at > now);
+        Ok((before - keys.len()) as u64)
+    }
+}
+
+/// The idempotency key of a request, if it has one
+///
+/// Keys are up to [`MAX_KEY_LEN`] visible ASCII characters.
+pub fn idempotency_key(headers: &HeaderMap) -> Result<Option<&str>, AppError> {
+    let Some(value) = headers.get(IDEMPOTENCY_KEY_HEADER) else {
+        return Ok(None);
+    };
+    let key = value
+        .to_str()
+        .ok()
+        .map(str::trim)
+        .filter(|key| !key.is_empty() && key.len() <= MAX_KEY_LEN && key.chars().all(|c| c.is_ascii_graphic()))
+        .ok_or_else(|| {
+            AppError::InvalidRequest(format!(
+                "The Idempotency-Key header must have 1 to {} visible ASCII characters",
+                MAX_KEY_LEN
+            ))
+        })?;
+    Ok(Some(key))
+}
+
+/// Runs submissions at most once per idempotency key
+pub struct IdempotencyKeys {
+    store: Arc<dyn IdempotencyStore>,
+    config: IdempotencyConfig,
+}
+
+impl IdempotencyKeys {
+    /// Create new IdempotencyKeys
+    pub fn new(store: Arc<dyn IdempotencyStore>, config: IdempotencyConfig) -> Self {
+        Self { store, config }
+    }
+
+    /// Run `submit` unless a submission of the user in `scope` with the same
+    /// key already succeeded, in which case its response is returned
+    ///
+    /// Without a key, `submit` always runs. A key used with a different
+    /// payload fails with `422 Unprocessable Entity`, and one whose first
+    /// submission is still running with `409 Conflict`. A failed submission
+    /// frees its key for a retry.
+    pub async fn run<T, F, Fut>(
+        &self,
+        user_id: &str,
+        scope: &str,
+        key: Option<&str>,
+        payload: &impl Serialize,
+        submit: F,
+    ) -> Result<T, AppError>
+    where
+        T: Serialize + DeserializeOwned,
+        F: FnOnce() -> Fut,
+        Fut: Future<Output = Result<T, AppError>>,
+    {
+        let Some(key) = key else {
+            return submit().await;
+        };
+        let now = Utc::now();
+        // An abandoned reservation frees its key once the submission could
+        // no longer be running
+        let reserved_until = now + seconds(self.config.reservation_secs);
+        match self
+            .store
+            .reserve(user_id, scope, key, &fingerprint(payload)?, reserved_until)
+            .await?
+        {
+            Reservation::Reserved => {}
+            Reservation::Completed(response) => {
+                return serde_json::from_value(response)
+                    .map_err(|e| AppError::Internal(format!("Invalid stored response: {}", e)));
+            }
+            Reservation::InProgress => {
+                return Err(AppError::Conflict(format!(
+                    "A request with idempotency key '{}' is still in progress",
+                    key
+                )));
+            }
+            Reservation::Mismatch => {
+                return Err(AppError::Custom(
+                    StatusCode::UNPROCESSABLE_ENTITY,
+                    format!("Idempotency key '{}' was already used with a different payload", key),
+                ));
+            }
+        }
+
+        match submit().await {
+            Ok(response) => {
+                let stored = serde_json::to_value(&response)
+                    .map_err(|e| AppError::Internal(format!("Failed to store response: {}", e)))?;
+                let expires_at = Utc::now() + seconds(self.config.ttl_secs);
+                if let Err(e) = self.store.complete(user_id, scope, key, &stored, expires_at).await {
+                    warn!("Failed to store the response for idempotency key '{}': {}", key, e);
+                }
+                Ok(response)
+            }
+            Err(error) => {
+                if let Err(e) = self.store.release(user_id, scope, key).await {
+                    warn!("Failed to release idempotency key '{}': {}", key, e);
+                }
+                Err(error)
+            }
+        }
+    }
+
+    /// Supervise the background task deleting expired keys every hour
+    pub fn supervise_purger(self: &Arc<Self>, supervisor: &Supervisor) {
+        let keys = self.clone();
+        supervisor.spawn("idempotency.purge", RestartPolicy::default(), move |shutdown| {
+            let keys = keys.clone();
+            async move {
+                loop {
+                    if let Err(e) = keys.store.purge_expired(Utc::now()).await {
+                        warn!("Failed to purge expired idempotency keys: {}", e);
+                    }
+                    if shutdown.sleep(Duration::from_secs(3600)).await {
+                        return Ok(());
+                    }
+                }
+            }
+        });
+    }
+}
+
+/// Converts seconds into a chrono duration
+fn seconds(secs: u64) -> chrono::Duration {
+    chrono::Duration::seconds(i64::try_from(secs).unwrap_or(i64::MAX / 1000))
+}
+
+/// SHA-256 of the payload's JSON, with object keys in a fixed order
+fn fingerprint(payload: &impl Serialize) -> Result<String, AppError> {
+    let value = serde_json::to_value(payload)
+        .map_err(|e| AppError::InvalidRequest(format!("Invalid payload: {}", e)))?;
+    Ok(hex::encode(Sha256::digest(value.to_string().as_bytes())))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_helpers::migrated_pool;
+    use std::sync::atomic::{AtomicU32, Ordering};
+
+    #[tokio::test]
+    async fn test_repeated_key_returns_original_response() {
+        let pool = migrated_pool().await;
+        let keys = IdempotencyKeys::new(Arc::new(SqlIdempotencyStore::new(pool)), IdempotencyConfig::default());
+        let counter = AtomicU32::new(0);
+        let runs = &counter;
+        let submit = move || async move {
+            Ok::<_, AppError>(format!("execution-{}", runs.fetch_add(1, Ordering::SeqCst)))
+        };
+        let payload = serde_json::json!({"command": "echo", "parameters": {"text": "hi"}});
+
+        let first = keys.run("alice", "commands", Some("k1"), &payload, submit).await.unwrap();
+        let repeat = keys.run("alice", "commands", Some("k1"), &payload, submit).await.unwrap();
+        assert_eq!((first.as_str(), repeat.as_str()), ("execution-0", "execution-0"));
+
+        // Keys are scoped by user and route; no key always runs
+        assert_eq!(keys.run("bob", "commands", Some("k1"), &payload, submit).await.unwrap(), "execution-1");
+        assert_eq!(keys.run("alice", "jobs", Some("k1"), &payload, submit).await.unwrap(), "execution-2");
+        assert_eq!(keys.run("alice", "commands", None, &payload, submit).await.unwrap(), "execution-3");
+
+        let changed = serde_json::json!({"command": "echo", "parameters": {"text": "bye"}});
+        let result = keys.run("alice", "commands", Some("k1"), &changed, submit).await;
+        assert!(matches!(result, Err(AppError::Custom(StatusCode::UNPROCESSABLE_ENTITY, _))));
+
+        // A failed submission frees its key
+        let failed = keys
+            .run("alice", "commands", Some("k2"), &payload, || async {
+                Err::<String, _>(AppError::Internal("MCP unavailable".to_string()))
+            })
+            .await;
+        assert!(failed.is_err());
+        assert_eq!(keys.run("alice", "commands", Some("k2"), &payload, submit).await.unwrap(), "execution-4");
+        assert_eq!(counter.load(Ordering::SeqCst), 5);
+    }
+
+    #[tokio::test]
+    async fn test_running_and_expired_keys() {
+        let memory = MemoryIdempotencyStore::new();
+        let store = &memory;
+        let later = Utc::now() + chrono::Duration::minutes(1);
+        let reserve = move |fingerprint: &'static str, expires_at: DateTime<Utc>| {
+            store.reserve("alice", "jobs", "k", fingerprint, expires_at)
+        };
+
+        assert_eq!(reserve("a", later).await.unwrap(), Reservation::Reserved);
+        assert_eq!(reserve("a", later).await.unwrap(), Reservation::InProgress);
+        assert_eq!(reserve("b", later).await.unwrap(), Reservation::Mismatch);
+
+        let response = serde_json::json!({"id": "j1"});
+        store.complete("alice", "jobs", "k", &response, Utc::now()).await.unwrap();
+        assert_eq!(reserve("b", later).await.unwrap(), Reservation::Reserved);
+        assert_eq!(store.purge_expired(later).await.unwrap(), 1);
+    }
+}
@@ -0,0 +1,256 @@
+//! Scheduled integrity checks of the blob store.
+//!
+//! Every uploaded file is stored as a blob named by its SHA-256, which is
+//! its recorded checksum. On the cron schedule in `blob_integrity.schedule`,
+//! the leader runs the scheduled task `blobs.verify`: it hashes
+//! `blob_integrity.sample_size` blobs again, taking turns over the store, or
+//! every blob without a sample size, and checks that the blob of every file
+//! is stored. Corrupt blobs are quarantined. If `blob_integrity.backup_dir`
+//! holds a backup of the store, the blobs found corrupt or missing are
+//! restored from it and linked at their files again.
+//!
+//! Every blob still damaged after a run raises a critical alert from the
+//! source `blob_integrity`, labelled with its digest. A blob stays missing
+//! until it is restored, so every run sees it, and the alert is resolved by
+//! the first run after the restore.
+
+use std::collections::{BTreeSet, HashMap};
+use std::sync::{Arc, RwLock};
+use std::time::Instant;
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use squirrel_app::schedule::{CronSchedule, ScheduleError, TaskOptions, TaskScheduler, HOST_OWNER};
+use squirrel_core::blob::{BlobError, BlobStore, IntegrityReport, RestoreReport};
+use squirrel_monitoring::alerts::{Alert, AlertLifecycle, AlertSeverity, AlertType};
+use tokio::sync::Mutex;
+use tracing::{error, info, warn};
+
+use crate::api::error::AppError;
+use crate::config::IntegrityConfig;
+
+/// Name of the scheduled integrity check task
+pub const INTEGRITY_TASK: &str = "blobs.verify";
+
+/// Source of the alerts of damaged blobs
+pub const ALERT_SOURCE: &str = "blob_integrity";
+
+/// What an integrity check found and restored
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct VerificationReport {
+    /// When the check started
+    pub started_at: DateTime<Utc>,
+    /// How long it took
+    pub elapsed_ms: u64,
+    /// Blobs hashed, and those found corrupt or missing
+    pub integrity: IntegrityReport,
+    /// Blobs restored from the backup, if one is configured and blobs were
+    /// damaged
+    pub restore: Option<RestoreReport>,
+    /// Why the backup could not be restored from
+    pub restore_error: Option<String>,
+}
+
+impl VerificationReport {
+    /// Digests of the blobs still corrupt or missing after the check
+    pub fn damaged(&self) -> Vec<String> {
+        let restored: BTreeSet<&String> = self.restore.iter().flat_map(|restore| &restore.restored).collect();
+        let damaged: BTreeSet<&String> = self.integrity.corrupt.iter().chain(&self.integrity.missing).collect();
+        damaged.difference(&restored).map(|digest| (*digest).clone()).collect()
+    }
+}
+
+/// The alert raised while the blob with `digest` is damaged
+pub fn damaged_alert(digest: &str) -> Alert {
+    let message = format!("Blob {} is corrupt or missing and no intact copy was restored", digest);
+    let details = HashMap::from([("digest".to_string(), serde_json::json!(digest))]);
+    Alert::new(AlertType::Generic, AlertSeverity::Critical, ALERT_SOURCE.to_string(), message).with_details(details)
+}
+
+/// Checks the blob store on a schedule and restores damaged blobs
+pub struct BlobIntegrity {
+    blobs: BlobStore,
+    alerts: Option<Arc<AlertLifecycle>>,
+    config: IntegrityConfig,
+    /// Report of the latest check
+    last: RwLock<Option<VerificationReport>>,
+    /// Held by the check in progress, so checks never overlap
+    running: Mutex<()>,
+}
+
+impl BlobIntegrity {
+    /// Create a new BlobIntegrity for the store `blobs`
+    pub fn new(blobs: BlobStore, config: IntegrityConfig) -> Self {
+        Self {
+            blobs,
+            alerts: None,
+            config,
+            last: RwLock::new(None),
+            running: Mutex::new(()),
+        }
+    }
+
+    /// Raise alerts of damaged blobs through `alerts`
+    pub fn with_alerts(mut self, alerts: Arc<AlertLifecycle>) -> Self {
+        self.alerts = Some(alerts);
+        self
+    }
+
+    /// The schedule checks start on
+    pub fn schedule(&self) -> &CronSchedule {
+        &self.config.schedule
+    }
+
+    /// When the next scheduled check starts, if checks are scheduled
+    pub fn next_run(&self) -> Option<DateTime<Utc>> {
+        self.config
+            .enabled
+            .then(|| self.config.schedule.next_after(Utc::now()))
+            .flatten()
+    }
+
+    /// Report of the latest check
+    pub fn last_report(&self) -> Option<VerificationReport> {
+        self.last.read().ok().and_then(|last| last.clone())
+    }
+
+    /// Check the store now, hashing `sample` blobs or every blob, and
+    /// restore the damaged ones from the backup
+    pub async fn run(&self, sample: Option<usize>) -> Result<VerificationReport, AppError> {
+        let _running = self.running.lock().await;
+        let started = Instant::now();
+        let started_at = Utc::now();
+        let (blobs, backup_dir) = (self.blobs.clone(), self.config.backup_dir.clone());
+        let (integrity, restore) = tokio::task::spawn_blocking(move || {
+            let integrity = blobs.verify(sample)?;
+            let restore = match backup_dir {
+                Some(dir) if !integrity.is_intact() => Some(blobs.restore(&dir)),
+                _ => None,
+            };
+            Ok::<_, BlobError>((integrity, restore))
+        })
+        .await
+        .map_err(|e| AppError::Internal(format!("Integrity check failed: {}", e)))?
+        .map_err(|e| AppError::Internal(e.to_string()))?;
+
+        let (restore, restore_error) = match restore {
+            Some(Ok(restore)) => (Some(restore), None),
+            Some(Err(e)) => (None, Some(e.to_string())),
+            None => (None, None),
+        };
+        let report = VerificationReport {
+            started_at,
+            elapsed_ms: u64::try_from(started.elapsed().as_millis()).unwrap_or(u64::MAX),
+            integrity,
+            restore,
+            restore_error,
+        };
+        let damaged = report.damaged();
+        for digest in &damaged {
+            error!(digest = %digest, "Blob is corrupt or missing");
+        }
+        if let Some(e) = &report.restore_error {
+            warn!("Failed to restore blobs from the backup: {}", e);
+        }
+        info!(
+            checked_blobs = report.integrity.checked_blobs,
+            checked_bytes = report.integrity.checked_bytes,
+            corrupt = report.integrity.corrupt.len(),
+            missing = report.integrity.missing.len(),
+            restored = report.restore.as_ref().map_or(0, |restore| restore.restored.len()),
+            elapsed_ms = report.elapsed_ms,
+            "Blob integrity check finished"
+        );
+        self.update_alerts(&damaged);
+        if let Ok(mut last) = self.last.write() {
+            *last = Some(report.clone());
+        }
+        Ok(report)
+    }
+
+    /// Raise the alerts of the `damaged` blobs and resolve those of the
+    /// blobs that are intact again
+    fn update_alerts(&self, damaged: &[String]) {
+        let Some(alerts) = &self.alerts else { return };
+        for digest in damaged {
+            if let Err(e) = alerts.observe(&damaged_alert(digest)) {
+                warn!("Failed to record blob integrity alert: {}", e);
+            }
+        }
+        let recovered = alerts.alerts().into_iter().filter(|alert| {
+            alert.labels.get("source").map(String::as_str) == Some(ALERT_SOURCE)
+                && alert.labels.get("digest").is_some_and(|digest| !damaged.contains(digest))
+        });
+        for alert in recovered {
+            if let Err(e) = alerts.resolve(&alert.fingerprint) {
+                warn!("Failed to resolve blob integrity alert: {}", e);
+            }
+        }
+    }
+
+    /// Schedule checks on `scheduler`, if they are enabled
+    ///
+    /// The task is a singleton, run by the leader only, so instances sharing
+    /// the store do not check it at once.
+    pub fn schedule_on(self: &Arc<Self>, scheduler: &Arc<TaskScheduler>) -> Result<(), ScheduleError> {
+        if !self.config.enabled {
+            return Ok(());
+        }
+        let integrity = self.clone();
+        let options = TaskOptions::new(self.config.schedule.clone()).singleton();
+        scheduler.register(HOST_OWNER, INTEGRITY_TASK, options, move || {
+            let integrity = integrity.clone();
+            async move {
+                integrity.run(integrity.config.sample_size).await?;
+                Ok(())
+            }
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use squirrel_monitoring::alerts::LifecycleConfig;
+    use std::path::Path;
+
+    fn store(path: &Path, contents: &str) -> (BlobStore, String) {
+        let blobs = BlobStore::open(path).unwrap();
+        let source = path.join("upload");
+        std::fs::write(&source, contents).unwrap();
+        let digest = blobs.add(&source).unwrap();
+        (blobs, digest)
+    }
+
+    #[tokio::test]
+    async fn test_damaged_blobs_raise_alerts_until_restored() {
+        let dir = tempfile::tempdir().unwrap();
+        let (blobs, digest) = store(&dir.path().join("blobs"), "ACGT");
+        blobs.link("workspace://reads.fa", &digest, None).unwrap();
+        std::fs::remove_file(blobs.blob_path(&digest).unwrap()).unwrap();
+        let backup = BlobStore::open(dir.path().join("backup")).unwrap();
+        let alerts = Arc::new(AlertLifecycle::in_memory(LifecycleConfig::default()));
+        let config = IntegrityConfig {
+            backup_dir: Some(backup.root().to_path_buf()),
+            ..IntegrityConfig::default()
+        };
+        let integrity = BlobIntegrity::new(blobs.clone(), config).with_alerts(alerts.clone());
+
+        // The backup has no copy yet
+        let report = integrity.run(None).await.unwrap();
+        assert_eq!(report.integrity.missing, vec![digest.clone()]);
+        assert_eq!(report.restore.unwrap().unrecoverable, vec![digest.clone()]);
+        let raised = alerts.alerts();
+        assert_eq!(raised.len(), 1);
+        assert_eq!(raised[0].labels.get("digest"), Some(&digest));
+        assert_eq!(raised[0].severity, AlertSeverity::Critical);
+
+        store(backup.root(), "ACGT");
+        let report = integrity.run(Some(10)).await.unwrap();
+        assert_eq!(report.restore.as_ref().unwrap().restored, vec![digest.clone()]);
+        assert!(report.damaged().is_empty());
+        assert!(blobs.contains(&digest));
+        assert!(alerts.get(&raised[0].fingerprint).is_none());
+        assert_eq!(integrity.last_report(), Some(report));
+    }
+}
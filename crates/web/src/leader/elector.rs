@@ -0,0 +1,310 @@
+//! Lease-based leader elector.
+
+use std::collections::HashMap;
+use std::future::Future;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use squirrel_app::schedule::Leadership;
+use squirrel_monitoring::metrics::{Metric, MetricCollector, MetricType};
+use tokio::sync::{watch, Mutex};
+use tokio::task::JoinHandle;
+use tracing::{info, warn};
+use uuid::Uuid;
+
+use super::store::LeaseStore;
+use crate::config::LeaderElectionConfig;
+
+/// Leadership as seen by this instance
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct LeadershipStatus {
+    /// ID of this instance
+    pub instance_id: String,
+    /// Whether this instance is the leader
+    pub is_leader: bool,
+    /// ID of the current leader, if known
+    pub leader: Option<String>,
+    /// Lease term of the current leader
+    pub term: i64,
+    /// When leadership of this instance last changed
+    pub changed_at: DateTime<Utc>,
+}
+
+/// Competes for a lease so that exactly one instance acts as leader
+///
+/// The elector renews its lease every renew interval. If renewal fails for
+/// longer than the lease duration, the instance steps down on its own, so a
+/// partitioned leader never overlaps with its successor for longer than one
+/// renew interval.
+pub struct LeaderElector {
+    store: Arc<dyn LeaseStore>,
+    config: LeaderElectionConfig,
+    metrics: Option<Arc<dyn MetricCollector>>,
+    status: watch::Sender<LeadershipStatus>,
+    last_renewed: Mutex<Option<Instant>>,
+}
+
+impl LeaderElector {
+    /// Create a new LeaderElector with a random instance ID
+    pub fn new(
+        store: Arc<dyn LeaseStore>,
+        config: LeaderElectionConfig,
+        metrics: Option<Arc<dyn MetricCollector>>,
+    ) -> Self {
+        let (status, _) = watch::channel(LeadershipStatus {
+            instance_id: Uuid::new_v4().to_string(),
+            is_leader: false,
+            leader: None,
+            term: 0,
+            changed_at: Utc::now(),
+        });
+
+        Self {
+            store,
+            config,
+            metrics,
+            status,
+            last_renewed: Mutex::new(None),
+        }
+    }
+
+    /// ID of this instance
+    pub fn instance_id(&self) -> String {
+        self.status.borrow().instance_id.clone()
+    }
+
+    /// Whether this instance is currently the leader
+    pub fn is_leader(&self) -> bool {
+        self.status.borrow().is_leader
+    }
+
+    /// Current leadership status
+    pub fn status(&self) -> LeadershipStatus {
+        self.status.borrow().clone()
+    }
+
+    /// Subscribe to leadership changes
+    pub fn subscribe(&self) -> watch::Receiver<LeadershipStatus> {
+        self.status.subscribe()
+    }
+
+    fn lease_duration(&self) -> Duration {
+        Duration::from_secs(self.config.lease_duration_secs.max(1))
+    }
+
+    /// Run one acquire-or-renew round
+    pub async fn tick(&self) {
+        let instance_id = self.instance_id();
+        match self
+            .store
+            .try_acquire(&self.config.lease_name, &instance_id, self.lease_duration())
+            .await
+        {
+            Ok(lease) => {
+                let is_leader = lease.holder == instance_id;
+                if is_leader {
+                    *self.last_renewed.lock().await = Some(Instant::now());
+                }
+                self.update(is_leader, Some(lease.holder), lease.term);
+            }
+            Err(e) => {
+                warn!("Failed to renew leader lease: {}", e);
+                let expired = self
+                    .last_renewed
+                    .lock()
+                    .await
+                    .is_none_or(|renewed| renewed.elapsed() >= self.lease_duration());
+                if expired && self.is_leader() {
+                    let term = self.status.borrow().term;
+                    self.update(false, None, term);
+                }
+            }
+        }
+    }
+
+    /// Give up leadership, letting another instance take over immediately
+    pub async fn resign(&self) {
+        if !self.is_leader() {
+            return;
+        }
+        let instance_id = self.instance_id();
+        if let Err(e) = self.store.release(&self.config.lease_name, &instance_id).await {
+            warn!("Failed to release leader lease: {}", e);
+        }
+        *self.last_renewed.lock().await = None;
+        let term = self.status.borrow().term;
+        self.update(false, None, term);
+    }
+
+    /// Publish a new status, recording a metric when leadership changes
+    fn update(&self, is_leader: bool, leader: Option<String>, term: i64) {
+        let mut changed = false;
+        self.status.send_if_modified(|status| {
+            changed = status.is_leader != is_leader;
+            let modified = changed || status.leader != leader || status.term != term;
+            if changed {
+                status.changed_at = Utc::now();
+            }
+            status.is_leader = is_leader;
+            status.leader = leader;
+            status.term = term;
+            modified
+        });
+
+        if !changed {
+            return;
+        }
+        if is_leader {
+            info!("Instance {} became leader for term {}", self.instance_id(), term);
+        } else {
+            info!("Instance {} is no longer leader", self.instance_id());
+        }
+
+        if let Some(metrics) = &self.metrics {
+            let labels = HashMap::from([
+                ("instance".to_string(), self.instance_id()),
+                ("lease".to_string(), self.config.lease_name.clone()),
+            ]);
+            let mut transition_labels = labels.clone();
+            let transition = if is_leader { "acquired" } else { "lost" };
+            transition_labels.insert("transition".to_string(), transition.to_string());
+            let recorded = [
+                Metric::new("leader_transitions_total", 1.0, MetricType::Counter, transition_labels),
+                Metric::new(
+                    "leader_is_leader",
+                    if is_leader { 1.0 } else { 0.0 },
+                    MetricType::Gauge,
+                    labels,
+                ),
+            ];
+            for metric in recorded {
+                let metrics = metrics.clone();
+                tokio::spawn(async move {
+                    if let Err(e) = metrics.record_metric(metric).await {
+                        warn!("Failed to record leadership metric: {}", e);
+                    }
+                });
+            }
+        }
+    }
+
+    /// Spawn the background task that keeps competing for the lease
+    pub fn spawn(self: &Arc<Self>) -> JoinHandle<()> {
+        let elector = self.clone();
+        tokio::spawn(async move {
+            let period = Duration::from_secs(elector.config.renew_interval_secs.max(1));
+            let mut interval = tokio::time::interval(period);
+            loop {
+                interval.tick().await;
+                elector.tick().await;
+            }
+        })
+    }
+
+    /// Run a task only while this instance is the leader
+    ///
+    /// `task` is started every time leadership is gained and aborted as soon
+    /// as it is lost or a new term begins, so singleton background work such as periodic
+    /// evaluation never runs on two instances at once.
+    pub fn spawn_leader_task<F, Fut>(self: &Arc<Self>, name: &'static str, task: F) -> JoinHandle<()>
+    where
+        F: Fn() -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = ()> + Send + 'static,
+    {
+        let mut status = self.subscribe();
+        tokio::spawn(async move {
+            // The running task together with the term it was started in
+            let mut running: Option<(i64, JoinHandle<()>)> = None;
+            loop {
+                let (is_leader, term) = {
+                    let status = status.borrow_and_update();
+                    (status.is_leader, status.term)
+                };
+                let current_term = running.as_ref().map(|(term, _)| *term);
+                if current_term.is_some() && (!is_leader || current_term != Some(term)) {
+                    info!("Stopping leader task {}", name);
+                    if let Some((_, handle)) = running.take() {
+                        handle.abort();
+                    }
+                }
+                if is_leader && running.is_none() {
+                    info!("Starting leader task {} for term {}", name, term);
+                    running = Some((term, tokio::spawn(task())));
+                }
+                if status.changed().await.is_err() {
+                    break;
+                }
+            }
+            if let Some((_, handle)) = running {
+                handle.abort();
+            }
+        })
+    }
+}
+
+impl Leadership for LeaderElector {
+    fn is_leader(&self) -> bool {
+        LeaderElector::is_leader(self)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::leader::MemoryLeaseStore;
+    use std::sync::atomic::{AtomicU32, Ordering};
+
+    fn config(lease_duration_secs: u64) -> LeaderElectionConfig {
+        LeaderElectionConfig {
+            enabled: true,
+            lease_duration_secs,
+            ..LeaderElectionConfig::default()
+        }
+    }
+
+    #[tokio::test]
+    async fn test_only_one_instance_leads() {
+        let store: Arc<dyn LeaseStore> = Arc::new(MemoryLeaseStore::new());
+        let first = LeaderElector::new(store.clone(), config(30), None);
+        let second = LeaderElector::new(store, config(30), None);
+
+        first.tick().await;
+        second.tick().await;
+        assert!(first.is_leader());
+        assert!(!second.is_leader());
+        assert_eq!(second.status().leader, Some(first.instance_id()));
+
+        first.resign().await;
+        second.tick().await;
+        assert!(second.is_leader());
+        assert_eq!(second.status().term, 2);
+
+        first.tick().await;
+        assert!(!first.is_leader());
+    }
+
+    #[tokio::test]
+    async fn test_leader_task_follows_leadership() {
+        let elector = Arc::new(LeaderElector::new(Arc::new(MemoryLeaseStore::new()), config(30), None));
+        let starts = Arc::new(AtomicU32::new(0));
+        let counter = starts.clone();
+        let _handle = elector.spawn_leader_task("test", move || {
+            counter.fetch_add(1, Ordering::SeqCst);
+            std::future::pending::<()>()
+        });
+
+        tokio::task::yield_now().await;
+        assert_eq!(starts.load(Ordering::SeqCst), 0);
+
+        elector.tick().await;
+        tokio::time::sleep(Duration::from_millis(20)).await;
+        assert_eq!(starts.load(Ordering::SeqCst), 1);
+
+        elector.resign().await;
+        elector.tick().await;
+        tokio::time::sleep(Duration::from_millis(20)).await;
+        assert_eq!(starts.load(Ordering::SeqCst), 2);
+    }
+}
@@ -0,0 +1,13 @@
+//! Leader election for multi-instance deployments.
+//!
+//! Every instance serves HTTP, but work that must not run twice — such as
+//! periodic evaluation or scheduling loops — is started through
+//! [`LeaderElector::spawn_leader_task`] and only runs on the instance that
+//! holds the shared database lease. When the leader stops renewing its lease,
+//! another instance takes over once the lease expires.
+
+pub mod elector;
+pub mod store;
+
+pub use elector::{LeaderElector, LeadershipStatus};
+pub use store::{Lease, LeaseStore, MemoryLeaseStore, SqlLeaseStore};
@@ -0,0 +1,182 @@
+//! Lease storage backends.
+
+use std::collections::HashMap;
+use std::time::Duration;
+
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use sqlx::{Row, SqlitePool};
+use tokio::sync::Mutex;
+
+use crate::api::error::AppError;
+use crate::db::{timestamp, InstrumentedPool};
+
+/// A named lease held by one instance until it expires
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Lease {
+    /// Lease name
+    pub name: String,
+    /// Instance currently holding the lease
+    pub holder: String,
+    /// Incremented every time the lease changes hands
+    pub term: i64,
+    /// When the current holder acquired the lease
+    pub acquired_at: DateTime<Utc>,
+    /// When the lease expires unless renewed
+    pub expires_at: DateTime<Utc>,
+}
+
+/// Storage for leader leases
+#[async_trait]
+pub trait LeaseStore: Send + Sync {
+    /// Acquire the lease for `holder`, or renew it if `holder` already has it
+    ///
+    /// The lease is only taken over from another holder once it has expired,
+    /// and every acquisition of an expired lease starts a new term. Returns the lease as stored afterwards, which may belong to another
+    /// instance.
+    async fn try_acquire(&self, name: &str, holder: &str, ttl: Duration) -> Result<Lease, AppError>;
+
+    /// Give up the lease if `holder` has it
+    async fn release(&self, name: &str, holder: &str) -> Result<(), AppError>;
+}
+
+/// Converts a lease duration into a chrono duration
+fn lease_ttl(ttl: Duration) -> chrono::Duration {
+    chrono::Duration::from_std(ttl).unwrap_or_else(|_| chrono::Duration::seconds(15))
+}
+
+/// Lease store backed by the `leader_leases` table
+pub struct SqlLeaseStore {
+    pool: InstrumentedPool,
+}
+
+impl SqlLeaseStore {
+    /// Create a new SqlLeaseStore
+    pub fn new(pool: SqlitePool) -> Self {
+        Self { pool: pool.into() }
+    }
+}
+
+#[async_trait]
+impl LeaseStore for SqlLeaseStore {
+    async fn try_acquire(&self, name: &str, holder: &str, ttl: Duration) -> Result<Lease, AppError> {
+        let now = Utc::now();
+        let expires_at = now + lease_ttl(ttl);
+
+        // A single upsert keeps acquisition atomic across instances
+        sqlx::query(
+            "INSERT INTO leader_leases (name, holder, term, acquired_at, expires_at) VALUES (?, ?, 1, ?, ?)
+             ON CONFLICT(name) DO UPDATE SET
+                 term = CASE WHEN holder = excluded.holder AND expires_at > excluded.acquired_at
+                     THEN term ELSE term + 1 END,
+                 acquired_at = CASE WHEN holder = excluded.holder AND expires_at > excluded.acquired_at
+                     THEN acquired_at ELSE excluded.acquired_at END,
+                 holder = excluded.holder,
+                 expires_at = excluded.expires_at
+             WHERE holder = excluded.holder OR expires_at <= excluded.acquired_at",
+        )
+        .bind(name)
+        .bind(holder)
+        .bind(now.timestamp_millis())
+        .bind(expires_at.timestamp_millis())
+        .execute(&self.pool)
+        .await?;
+
+        let row = sqlx::query("SELECT holder, term, acquired_at, expires_at FROM leader_leases WHERE name = ?")
+            .bind(name)
+            .fetch_one(&self.pool)
+            .await?;
+        Ok(Lease {
+            name: name.to_string(),
+            holder: row.try_get("holder")?,
+            term: row.try_get("term")?,
+            acquired_at: timestamp(row.try_get("acquired_at")?)?,
+            expires_at: timestamp(row.try_get("expires_at")?)?,
+        })
+    }
+
+    async fn release(&self, name: &str, holder: &str) -> Result<(), AppError> {
+        sqlx::query("UPDATE leader_leases SET expires_at = 0 WHERE name = ? AND holder = ?")
+            .bind(name)
+            .bind(holder)
+            .execute(&self.pool)
+            .await?;
+        Ok(())
+    }
+}
+
+/// In-process lease store for single-instance deployments and tests
+#[derive(Default)]
+pub struct MemoryLeaseStore {
+    leases: Mutex<HashMap<String, Lease>>,
+}
+
+impl MemoryLeaseStore {
+    /// Create a new MemoryLeaseStore
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+#[async_trait]
+impl LeaseStore for MemoryLeaseStore {
+    async fn try_acquire(&self, name: &str, holder: &str, ttl: Duration) -> Result<Lease, AppError> {
+        let now = Utc::now();
+        let expires_at = now + lease_ttl(ttl);
+        let mut leases = self.leases.lock().await;
+
+        let lease = leases.entry(name.to_string()).or_insert_with(|| Lease {
+            name: name.to_string(),
+            holder: holder.to_string(),
+            term: 1,
+            acquired_at: now,
+            expires_at,
+        });
+        if lease.expires_at <= now {
+            lease.holder = holder.to_string();
+            lease.term += 1;
+            lease.acquired_at = now;
+            lease.expires_at = expires_at;
+        } else if lease.holder == holder {
+            lease.expires_at = expires_at;
+        }
+
+        Ok(lease.clone())
+    }
+
+    async fn release(&self, name: &str, holder: &str) -> Result<(), AppError> {
+        if let Some(lease) = self.leases.lock().await.get_mut(name) {
+            if lease.holder == holder {
+                lease.expires_at = DateTime::<Utc>::UNIX_EPOCH;
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_helpers::migrated_pool;
+
+    #[tokio::test]
+    async fn test_sql_lease_changes_hands_only_after_expiry() {
+        let pool = migrated_pool().await;
+        let store = SqlLeaseStore::new(pool);
+        let ttl = Duration::from_secs(30);
+
+        let lease = store.try_acquire("web", "a", ttl).await.unwrap();
+        assert_eq!((lease.holder.as_str(), lease.term), ("a", 1));
+
+        let lease = store.try_acquire("web", "b", ttl).await.unwrap();
+        assert_eq!(lease.holder, "a");
+
+        let renewed = store.try_acquire("web", "a", ttl).await.unwrap();
+        assert_eq!((renewed.term, renewed.acquired_at), (1, lease.acquired_at));
+
+        store.release("web", "a").await.unwrap();
+        let lease = store.try_acquire("web", "b", ttl).await.unwrap();
+        assert_eq!((lease.holder.as_str(), lease.term), ("b", 2));
+    }
+}
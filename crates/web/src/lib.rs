@@ -1,9 +1,10 @@
-use std::sync::Arc;
+use std::collections::HashMap;
+use std::sync::{Arc, PoisonError};
 use anyhow::Result;
 use axum::{Router, http::Method, routing::{get, post}};
 use tower_http::cors::{CorsLayer, Any};
 #[cfg(feature = "db")]
-use sqlx::{SqlitePool, migrate::MigrateDatabase, Sqlite};
+use sqlx::SqlitePool;
 #[cfg(feature = "mock-db")]
 use sqlx::{SqlitePool};
 use serde::{Deserialize, Serialize};
@@ -16,12 +17,87 @@ pub mod state;
 pub mod websocket;
 pub mod config;
 pub mod db;
+pub mod agents;
+pub mod leader;
+pub mod queue;
+pub mod access;
+pub mod security_headers;
+pub mod deployment;
+pub mod rate_limit;
+pub mod deadline;
+pub mod rollout;
+pub mod migrations;
+pub mod i18n;
+pub mod tags;
+pub mod idempotency;
+pub mod admin;
+pub mod flags;
+pub mod users;
+pub mod read_cache;
+pub mod maintenance;
+pub mod integrity;
+pub mod export;
+pub mod logs;
+pub mod traces;
+pub mod redaction;
+pub mod schedules;
+pub mod compliance;
+pub mod locks;
+pub mod loadtest;
+pub mod profiling;
+#[cfg(test)]
+mod test_helpers;
 
 use crate::state::AppState;
 use crate::config::Config;
 use crate::db::SqlitePool as DbPool;
 use auth::{AuthConfig, AuthService};
-use mcp::{McpCommandClient, MockMcpClient};
+use mcp::{McpCommandClient, MockMcpClient, RemoteMcpClient, UnavailableMcpClient};
+use handlers::workflows::WorkflowService;
+use handlers::webhooks::{HttpTransport, WebhookService};
+use handlers::files::create_file_service;
+use agents::AgentScheduler;
+use access::IpFilter;
+use security_headers::SecurityHeadersLayer;
+use rate_limit::RateLimiter;
+use rollout::ConfigRollout;
+use migrations::SqlMigrations;
+use squirrel_core::migration::Migrator;
+use squirrel_core::i18n::Locale;
+use squirrel_core::redaction::Redactor;
+use tags::{MemoryTagStore, SqlTagStore, TagStore};
+use idempotency::{IdempotencyKeys, MemoryIdempotencyStore, SqlIdempotencyStore};
+use admin::{AdminControls, MemoryAdminStore, SqlAdminStore};
+use flags::{FeatureFlags, FlagStore, MemoryFlagStore, SqlFlagStore};
+use users::{MemoryUserStore, SqlUserStore, UserDirectory};
+use read_cache::ReadCaches;
+use maintenance::DatabaseMaintenance;
+use integrity::BlobIntegrity;
+use locks::{LockService, SqlLockStore};
+use db::ReplicatedPool;
+use export::Exporter;
+use logs::{LogStore, MemoryLogStore, SqlLogStore};
+use redaction::{create_redactor, RedactionMetrics};
+use schedules::ScheduleObserver;
+use leader::{LeaderElector, LeaseStore, MemoryLeaseStore, SqlLeaseStore};
+use queue::{JobQueue, SqlJobQueueStore};
+use squirrel_commands::cache::ResultCache;
+use squirrel_app::startup::Readiness;
+use squirrel_app::schedule::TaskScheduler;
+use squirrel_app::supervisor::{RestartPolicy, Supervisor};
+use squirrel_commands::CommandRegistry;
+use squirrel_mcp::ai::{AiError, Assistant};
+use squirrel_mcp::context_manager::ContextManager;
+use squirrel_mcp::persistence::PersistenceConfig;
+use squirrel_mcp::tool::ToolManager;
+use squirrel_monitoring::accounting::UsageLedger;
+use squirrel_monitoring::alerts::notify::{NotificationConfig, NotificationManager};
+use squirrel_monitoring::alerts::{Alert, AlertLifecycle};
+use squirrel_monitoring::reports::{MonitoringReportSource, ReportService};
+use squirrel_monitoring::watchdog::{ExecutionPool, MemoryWatchdog, RunningExecution, WatchdogReport};
+use squirrel_monitoring::metrics::{
+    DefaultMetricCollector, Metric, MetricCollector, MetricType, MetricsManager, RetryMetrics,
+};
 
 pub use api::{CreateJobRequest, CreateJobResponse, JobStatus, JobState};
 pub use api::commands::{
@@ -96,6 +172,53 @@ impl Default for AppState {
             mcp_command.clone()
         )) as Arc<dyn handlers::commands::CommandService>;
         
+        // Open the usage ledger
+        let usage_ledger = create_usage_ledger(&config);
+        
+        // Create workflow service, running the built-in commands
+        let command_registry = create_command_registry();
+        let workflow_service = create_workflow_service(command_registry.clone(), &ws_manager, None, usage_ledger.clone());
+        
+        // Create remote agent scheduler
+        let agent_scheduler = Arc::new(AgentScheduler::new(config.agents.clone()));
+        
+        // Redact secrets from logs and webhook payloads
+        let redactor = create_redactor(&config.redaction);
+        
+        // Create webhook service
+        let webhook_service = create_webhook_service(&config, redactor.clone());
+        
+        // Open alert state
+        let alert_lifecycle = create_alert_lifecycle(&config);
+        
+        // Open the result cache, invalidated as uploads replace files
+        let result_cache = create_result_cache(&config);
+        
+        // Open the upload directory and blob store
+        let file_service = create_file_service(&config.files, None, result_cache.clone());
+        
+        // Stage configuration changes next to the active configuration
+        let config_rollout = Arc::new(ConfigRollout::new(config.clone()));
+        
+        // Keep idempotency keys of submissions in memory
+        let idempotency = Arc::new(IdempotencyKeys::new(
+            Arc::new(MemoryIdempotencyStore::new()),
+            config.idempotency.clone(),
+        ));
+        
+        // Keep admin flags and the audit log in memory
+        let admin = Arc::new(AdminControls::new(Arc::new(MemoryAdminStore::new()), config.admin.clone()));
+        
+        // Keep accounts, teams and invitations in memory
+        let users = Arc::new(UserDirectory::new(
+            Arc::new(MemoryUserStore::new()),
+            auth.clone(),
+            config.users.clone(),
+        ));
+        
+        // Keep the most recent forwarded log events in memory
+        let log_store: Arc<dyn LogStore> = Arc::new(MemoryLogStore::new(config.logs.memory_capacity));
+        
         Self {
             db: mock_db,
             config,
@@ -104,25 +227,315 @@ impl Default for AppState {
             ws_manager,
             auth,
             command_service: Some(command_service),
+            workflow_service: Some(workflow_service),
+            command_registry: Some(command_registry),
+            agent_scheduler: Some(agent_scheduler),
+            leader: None,
+            webhook_service: Some(webhook_service),
+            alert_lifecycle: Some(alert_lifecycle),
+            // Creating a context manager needs a running runtime
+            context_manager: None,
+            job_contexts: None,
+            tool_manager: Some(Arc::new(ToolManager::new())),
+            file_service: Some(file_service),
+            result_cache,
+            usage_ledger: Some(usage_ledger),
+            // The watchdog only runs in a served app
+            memory_watchdog: None,
+            assistant: None,
+            config_rollout: Some(config_rollout),
+            tag_store: Some(Arc::new(MemoryTagStore::new())),
+            idempotency: Some(idempotency),
+            admin: Some(admin),
+            // Requests are checked against the installed flag settings
+            flags: None,
+            users: Some(users),
+            supervisor: None,
+            // Tasks are only scheduled in a served app
+            scheduler: None,
+            readiness: None,
+            // Handlers read through to the services
+            read_caches: None,
+            // Maintenance needs a database
+            maintenance: None,
+            // Checks are only scheduled in a served app
+            integrity: None,
+            exporter: None,
+            log_store: Some(log_store),
+            redactor: Some(redactor),
+            // Locks are kept in the database
+            locks: None,
+            replicas: None,
+        }
+    }
+}
+
+/// Create the built-in command registry, ranking searches by the recorded history
+fn create_command_registry() -> Arc<CommandRegistry> {
+    let registry = squirrel_commands::create_command_registry()
+        .ok()
+        .and_then(|registry| registry.lock().ok().map(|registry| registry.clone()))
+        .unwrap_or_else(|| {
+            tracing::warn!("Failed to create command registry; workflows will have no commands");
+            CommandRegistry::new()
+        });
+    Arc::new(registry)
+}
+
+/// Create the workflow service, running steps through the command registry
+fn create_workflow_service(
+    registry: Arc<CommandRegistry>,
+    ws_manager: &websocket::ConnectionManager,
+    metrics: Option<Arc<dyn MetricCollector>>,
+    usage: Arc<UsageLedger>,
+) -> Arc<WorkflowService> {
+    Arc::new(WorkflowService::new(registry, ws_manager.clone(), metrics, Some(usage)))
+}
+
+/// Create the webhook service, posting deliveries over HTTP
+fn create_webhook_service(config: &Config, redactor: Arc<Redactor>) -> Arc<WebhookService> {
+    let transport = HttpTransport::new(std::time::Duration::from_secs(config.request_timeout_secs));
+    Arc::new(WebhookService::new(Arc::new(transport)).with_redactor(redactor))
+}
+
+/// Open the alert lifecycle state, falling back to memory if it cannot be read
+fn create_alert_lifecycle(config: &Config) -> Arc<AlertLifecycle> {
+    let lifecycle = AlertLifecycle::open(config.alerts.clone()).unwrap_or_else(|e| {
+        tracing::warn!("Failed to load alert state, keeping it in memory: {}", e);
+        AlertLifecycle::in_memory(config.alerts.clone())
+    });
+    Arc::new(lifecycle)
+}
+
+/// Open the usage ledger, falling back to memory if it cannot be read
+fn create_usage_ledger(config: &Config) -> Arc<UsageLedger> {
+    let ledger = UsageLedger::open(config.usage.clone()).unwrap_or_else(|e| {
+        tracing::warn!("Failed to load usage ledger, keeping it in memory: {}", e);
+        UsageLedger::in_memory(config.usage.clone())
+    });
+    Arc::new(ledger)
+}
+
+/// Executions of several pools, cancelled through the pool running them
+struct ExecutionPools(Vec<Arc<dyn ExecutionPool>>);
+
+impl ExecutionPool for ExecutionPools {
+    fn running(&self) -> Vec<RunningExecution> {
+        self.0.iter().flat_map(|pool| pool.running()).collect()
+    }
+
+    fn cancel(&self, id: &str, reason: &str) -> bool {
+        self.0.iter().any(|pool| pool.cancel(id, reason))
+    }
+}
+
+/// Start the memory watchdog if a budget is configured, raising its alerts
+/// through the alert lifecycle and reporting its state as metrics
+fn spawn_memory_watchdog(
+    config: &Config,
+    pool: Arc<dyn ExecutionPool>,
+    alerts: Arc<AlertLifecycle>,
+    metrics: Arc<dyn MetricCollector>,
+) -> Option<Arc<MemoryWatchdog>> {
+    config.memory_watchdog.budget_mb?;
+    let watchdog = Arc::new(MemoryWatchdog::new(config.memory_watchdog.clone(), pool));
+    let raised = std::sync::Mutex::new(None::<String>);
+    watchdog.spawn(move |report: &WatchdogReport| {
+        if let Some(alert) = &report.alert {
+            tracing::warn!("{}", alert.message);
+            match alerts.observe(alert) {
+                Ok(observation) => {
+                    *raised.lock().unwrap_or_else(PoisonError::into_inner) = Some(observation.fingerprint);
+                }
+                Err(e) => tracing::warn!("Failed to record memory alert: {}", e),
+            }
+        }
+        if report.recovered {
+            tracing::info!("Memory usage is back to {:.0}% of the budget; accepting executions", report.usage_percent);
+            if let Some(fingerprint) = raised.lock().unwrap_or_else(PoisonError::into_inner).take() {
+                if let Err(e) = alerts.resolve(&fingerprint) {
+                    tracing::warn!("Failed to resolve memory alert: {}", e);
+                }
+            }
+        }
+
+        let mut reported = vec![
+            Metric::new("memory_watchdog_rss_mb", report.rss_mb as f64, MetricType::Gauge, HashMap::new()),
+            Metric::new(
+                "memory_watchdog_accepting",
+                if report.accepting { 1.0 } else { 0.0 },
+                MetricType::Gauge,
+                HashMap::new(),
+            ),
+        ];
+        if !report.cancelled.is_empty() {
+            reported.push(Metric::new(
+                "memory_watchdog_cancellations_total",
+                report.cancelled.len() as f64,
+                MetricType::Counter,
+                HashMap::new(),
+            ));
+        }
+        let metrics = metrics.clone();
+        tokio::spawn(async move {
+            for metric in reported {
+                if let Err(e) = metrics.record_metric(metric).await {
+                    tracing::warn!("Failed to record memory watchdog metric: {}", e);
+                }
+            }
+        });
+    });
+    Some(watchdog)
+}
+
+/// Export the collected metrics to the configured backends, if any, every
+/// `metrics.interval` seconds until the supervisor shuts down
+async fn supervise_metric_exports(
+    config: &Config,
+    supervisor: &Supervisor,
+    metrics: Arc<dyn MetricCollector>,
+) -> Option<Arc<MetricsManager>> {
+    if !config.metrics.enabled || config.metrics.backends.is_empty() {
+        return None;
+    }
+    let manager = Arc::new(MetricsManager::from_config(&config.metrics));
+    if let Err(e) = manager.add_collector(metrics).await {
+        tracing::warn!("Failed to export metrics: {}", e);
+        return None;
+    }
+    let exports = manager.clone();
+    supervisor.spawn("metrics.export", RestartPolicy::default(), move |shutdown| {
+        let manager = exports.clone();
+        async move {
+            while !shutdown.sleep(manager.export_interval()).await {
+                if let Err(e) = manager.export().await {
+                    tracing::warn!("Failed to export metrics: {}", e);
+                }
+            }
+            Ok(())
+        }
+    });
+    Some(manager)
+}
+
+/// Send the configured report digests of the alerts and metrics, only while
+/// this instance is the leader
+fn spawn_reports(
+    config: &Config,
+    leader: &Arc<LeaderElector>,
+    alerts: Arc<AlertLifecycle>,
+    metrics: Arc<dyn MetricCollector>,
+) -> Option<tokio::task::JoinHandle<()>> {
+    if !config.reports.digests.enabled {
+        return None;
+    }
+    let channels = NotificationConfig {
+        channels: config.reports.channels.clone(),
+        rate_limit: 0,
+        templates: Vec::new(),
+        routing_rules: Vec::new(),
+    };
+    let delivery = match NotificationManager::new(channels) {
+        Ok(manager) => Arc::new(manager),
+        Err(e) => {
+            tracing::warn!("Failed to set up report channels, not sending reports: {}", e);
+            return None;
+        }
+    };
+    let source = MonitoringReportSource::new().with_alert_lifecycle(alerts).with_metric_collector(metrics);
+    let service = Arc::new(ReportService::new(config.reports.digests.clone(), Arc::new(source), delivery));
+    Some(leader.spawn_leader_task("reports", move || {
+        let service = service.clone();
+        async move { service.run().await }
+    }))
+}
+
+/// Create the feature flags, keeping the settings admins change in the web
+/// database unless `flags.persist` is off
+fn create_feature_flags(config: &Config, db: &DbPool) -> Arc<FeatureFlags> {
+    let store: Arc<dyn FlagStore> = if config.flags.persist {
+        Arc::new(SqlFlagStore::new(db.clone()))
+    } else {
+        Arc::new(MemoryFlagStore::new())
+    };
+    Arc::new(FeatureFlags::new(store, config.flags.clone()))
+}
+
+/// Open the command result cache, if a directory is configured and usable
+fn create_result_cache(config: &Config) -> Option<Arc<ResultCache>> {
+    let dir = config.result_cache_dir.as_ref()?;
+    match ResultCache::open(dir) {
+        Ok(cache) => Some(Arc::new(cache)),
+        Err(e) => {
+            tracing::warn!("Failed to open result cache {:?}: {}", dir, e);
+            None
+        }
+    }
+}
+
+/// Create the assistant, if a language model is configured, auditing its
+/// calls with `redactor` and raising budget alerts through the alert lifecycle
+fn create_assistant(
+    config: &Config,
+    context_manager: &Arc<ContextManager>,
+    redactor: Arc<Redactor>,
+    alerts: Arc<AlertLifecycle>,
+) -> Option<Arc<Assistant>> {
+    let assistant_config = config.assistant.clone().with_env_overrides();
+    let client = match assistant_config.client() {
+        Ok(client) => client,
+        Err(AiError::NotConfigured) => return None,
+        Err(e) => {
+            tracing::warn!("Assistant disabled: {}", e);
+            return None;
+        }
+    };
+    let auditor = match assistant_config.auditor(PersistenceConfig::default(), redactor) {
+        Ok(auditor) => auditor,
+        Err(e) => {
+            tracing::warn!("Assistant disabled: {}", e);
+            return None;
+        }
+    };
+    let mut assistant = Assistant::new(Arc::new(client), context_manager.clone(), &assistant_config);
+    if let Some(auditor) = auditor {
+        assistant = assistant.with_auditor(auditor.with_alerts(Arc::new(move |alert: &Alert| {
+            if let Err(e) = alerts.observe(alert) {
+                tracing::warn!("Failed to record language model budget alert: {}", e);
+            }
+        })));
+    }
+    Some(Arc::new(assistant))
+}
+
+/// Create the tool manager, registering the tools in the manifest directory
+async fn create_tool_manager(config: &Config) -> Arc<ToolManager> {
+    let mut builder = ToolManager::builder();
+    if let Some(path) = &config.tool_history_path {
+        builder = builder.history_path(path);
+    }
+    let tool_manager = Arc::new(builder.build());
+    if let Some(dir) = &config.tool_manifest_dir {
+        match tool_manager.load_from_directory(dir).await {
+            Ok(report) => {
+                for error in &report.errors {
+                    tracing::warn!("Skipped tool manifest {}", error);
+                }
+            }
+            Err(e) => tracing::warn!("Failed to load tool manifests: {}", e),
         }
     }
+    tool_manager
 }
 
 /// Initialize the database with migrations
 #[cfg(feature = "db")]
 pub async fn setup_database(database_url: &str) -> Result<DbPool> {
-    // Create database if it doesn't exist
-    if !Sqlite::database_exists(database_url).await.unwrap_or(false) {
-        Sqlite::create_database(database_url).await?;
-    }
-
-    // Connect to the database
-    let pool = DbPool::connect(database_url).await?;
+    // Connect to the database, creating it if it doesn't exist
+    let pool = migrations::connect(database_url).await?;
 
-    // Run migrations
-    sqlx::migrate!("./migrations")
-        .run(&pool)
-        .await?;
+    // Apply pending migrations, refusing to start if applied ones changed
+    Migrator::new(&SqlMigrations::new(pool.clone())).up(None).await?;
 
     Ok(pool)
 }
@@ -133,27 +546,67 @@ pub async fn setup_database(_database_url: &str) -> Result<DbPool> {
     let pool = DbPool::connect("sqlite::memory:").await?;
     
     // Run migrations even for in-memory database
-    sqlx::migrate!("./migrations")
-        .run(&pool)
-        .await?;
+    Migrator::new(&SqlMigrations::new(pool.clone())).up(None).await?;
     
     Ok(pool)
 }
 
+/// Connect to the configured MCP server, or use the mock client if there is none
+///
+/// A configured server that can not be connected to is never replaced by the
+/// mock: commands are refused with 503 until the server is restarted.
+fn create_mcp_command_client(config: &Config, ws_manager: &websocket::ConnectionManager) -> Arc<dyn McpCommandClient> {
+    if config.mcp.url.is_none() {
+        return Arc::new(MockMcpClient::new("localhost".to_string(), 8080));
+    }
+    match RemoteMcpClient::connect(config.mcp.clone(), Some(ws_manager.clone())) {
+        Ok(client) => Arc::new(client),
+        Err(e) => {
+            tracing::error!("Failed to connect to the configured MCP server, refusing commands: {}", e);
+            Arc::new(UnavailableMcpClient::new(e.to_string()))
+        }
+    }
+}
+
 /// Create the application router
 pub async fn create_app(db: DbPool, config: Config) -> Router {
-    // Initialize WebSocket manager
-    let ws_manager = websocket::init();
+    create_supervised_app(db, config, Arc::new(Supervisor::new()), Arc::new(Readiness::new())).await
+}
+
+/// Create the app, running its background tasks under `supervisor`
+///
+/// The server shuts the supervisor down after it stopped serving, which
+/// stops and joins the tasks. `/ready` reports `readiness`, the readiness of
+/// the stages the server started with.
+pub async fn create_supervised_app(
+    db: DbPool,
+    config: Config,
+    supervisor: Arc<Supervisor>,
+    readiness: Arc<Readiness>,
+) -> Router {
+    // Initialize WebSocket manager, sharing events with other instances if configured
+    let mut ws_manager = websocket::init();
+    match websocket::backplane::connect(&config.backplane).await {
+        Ok(Some(backplane)) => match ws_manager.clone().with_backplane(backplane).await {
+            Ok(manager) => ws_manager = manager,
+            Err(e) => tracing::warn!("Failed to subscribe to WebSocket backplane: {}", e),
+        },
+        Ok(None) => {}
+        Err(e) => tracing::warn!("Failed to connect WebSocket backplane: {}", e),
+    }
     
     // Create auth service
     let auth = AuthService::new(AuthConfig::default(), db.clone());
     
-    // Create MCP clients
-    let mcp_command = Arc::new(MockMcpClient::new(
-        "localhost".to_string(), 
-        8080
-    )) as Arc<dyn McpCommandClient>;
+    // Create MCP clients, forwarding commands to the configured server
+    let mcp_command = create_mcp_command_client(&config, &ws_manager);
 
+    // Keep queued work in the database so that it survives restarts
+    let job_queue = Arc::new(JobQueue::new(
+        Arc::new(SqlJobQueueStore::new(db.clone())),
+        config.job_queue.clone(),
+    ));
+    
     // Create command service based on feature
     #[cfg(feature = "mock-db")]
     let command_service = Arc::new(handlers::commands::MockCommandService::new(
@@ -161,10 +614,261 @@ pub async fn create_app(db: DbPool, config: Config) -> Router {
     )) as Arc<dyn handlers::commands::CommandService>;
     
     #[cfg(feature = "db")]
-    let command_service = Arc::new(handlers::commands::DbCommandService::new(
-        db.clone(),
-        mcp_command.clone(),
-    )) as Arc<dyn handlers::commands::CommandService>;
+    let command_service = {
+        job_queue.register(
+            handlers::commands::COMMAND_JOB,
+            Arc::new(handlers::commands::CommandJobHandler::new(db.clone(), mcp_command.clone())),
+        );
+        Arc::new(handlers::commands::DbCommandService::new(
+            db.clone(),
+            mcp_command.clone(),
+            job_queue.clone(),
+        )) as Arc<dyn handlers::commands::CommandService>
+    };
+    
+    // Create workflow service, reporting progress to monitoring
+    let metrics = DefaultMetricCollector::new();
+    if let Err(e) = metrics.initialize().await {
+        tracing::warn!("Failed to initialize workflow metrics: {}", e);
+    }
+    let metrics = Arc::new(metrics) as Arc<dyn MetricCollector>;
+    
+    // Count the attempts of retried calls: database, webhooks, MCP and exporters
+    RetryMetrics::install(metrics.clone());
+    
+    // Push the metrics to the configured StatsD and InfluxDB backends
+    supervise_metric_exports(&config, &supervisor, metrics.clone()).await;
+    
+    // Time the statements of the stores, keeping the slow ones
+    db::install_query_log(Arc::new(db::QueryLog::new(config.query_log.clone(), Some(metrics.clone()))));
+    
+    // Retry statements that failed before they ran, and fail them at once while the database is down
+    db::install_resilience(Arc::new(db::DbResilience::new(config.db_resilience.clone())));
+    
+    // Spread reads that tolerate some staleness over the read replicas, if any
+    let replicas = if config.replicas.urls.is_empty() {
+        None
+    } else {
+        match ReplicatedPool::connect(db.clone(), &config.replicas) {
+            Ok(replicas) => {
+                let replicas = Arc::new(replicas);
+                replicas.supervise_health_checks(
+                    &supervisor,
+                    std::time::Duration::from_millis(config.replicas.check_interval_ms),
+                );
+                Some(replicas)
+            }
+            Err(e) => {
+                tracing::warn!("Reading from the primary only: {}", e);
+                None
+            }
+        }
+    };
+    
+    // Open the usage ledger, charged by commands, workflow steps and uploads
+    let usage_ledger = create_usage_ledger(&config);
+    let command_registry = create_command_registry();
+    let workflow_service = create_workflow_service(
+        command_registry.clone(),
+        &ws_manager,
+        Some(metrics.clone()),
+        usage_ledger.clone(),
+    );
+    
+    // Check client addresses against the access rules before anything else
+    let ip_filter = Arc::new(IpFilter::new(config.access.clone(), Some(metrics.clone())));
+    
+    // Add security headers to every response outside the exempt paths
+    let security_headers = SecurityHeadersLayer::new(&config.security_headers);
+    
+    // Serve requests with the active or a canaried candidate configuration,
+    // limiting their rate by the configuration serving them
+    let config_rollout = Arc::new(ConfigRollout::new(config.clone()));
+    let rate_limiter = Arc::new(RateLimiter::new());
+    
+    // Locale of API messages for requests accepting none with a catalog
+    let default_locale = Arc::new(match config.locale.as_deref().map(str::parse::<Locale>) {
+        Some(Ok(locale)) => locale,
+        Some(Err(e)) => {
+            tracing::warn!("{}, using English for API messages", e);
+            Locale::english()
+        }
+        None => Locale::english(),
+    });
+    
+    // Compete for leadership; without election the instance always leads
+    let lease_store: Arc<dyn LeaseStore> = if config.leader_election.enabled {
+        Arc::new(SqlLeaseStore::new(db.clone()))
+    } else {
+        Arc::new(MemoryLeaseStore::new())
+    };
+    let leader = Arc::new(LeaderElector::new(
+        lease_store,
+        config.leader_election.clone(),
+        Some(metrics.clone()),
+    ));
+    leader.spawn();
+    
+    // Recover work orphaned by the last run before the workers start; a lone
+    // instance that just started holds no leases, so all running work is orphaned
+    match job_queue.recover(!config.leader_election.enabled).await {
+        Ok(recovered) if !recovered.is_empty() => {
+            tracing::info!("Recovered {} job queue entries left running", recovered.len());
+        }
+        Ok(_) => {}
+        Err(e) => tracing::warn!("Failed to recover job queue entries: {}", e),
+    }
+    job_queue.supervise_workers(&supervisor);
+    job_queue.spawn_reaper(&leader);
+    
+    // Create remote agent scheduler and start reaping lost agents
+    let agent_scheduler = Arc::new(AgentScheduler::new(config.agents.clone()));
+    agent_scheduler.supervise_monitor(&supervisor);
+    
+    // Redact secrets from logs, contexts and webhook payloads, reporting
+    // the matches of every rule as metrics
+    let redactor = create_redactor(&config.redaction);
+    Arc::new(RedactionMetrics::new(redactor.clone())).supervise_reporter(
+        &supervisor,
+        metrics.clone(),
+        std::time::Duration::from_secs(config.redaction.metrics_interval_secs),
+    );
+    
+    // Create webhook service
+    let webhook_service = create_webhook_service(&config, redactor.clone());
+    
+    // Open alert state
+    let alert_lifecycle = create_alert_lifecycle(&config);
+    
+    // Send report digests of the alerts and metrics, on the leader
+    spawn_reports(&config, &leader, alert_lifecycle.clone(), metrics.clone());
+    
+    // Keep operations that can start on any instance from running on two at once
+    let locks = Arc::new(LockService::new(Arc::new(SqlLockStore::new(db.clone())), leader.instance_id()));
+    
+    // Run the periodic tasks of the host and plugins, the singletons on the
+    // leader holding their lock, reporting their runs as metrics and their
+    // failures as alerts
+    let scheduler = Arc::new(
+        TaskScheduler::new(supervisor.clone())
+            .with_leadership(leader.clone())
+            .with_locks(locks.clone())
+            .with_observer(Arc::new(ScheduleObserver::new(
+                Some(metrics.clone()),
+                Some(alert_lifecycle.clone()),
+            ))),
+    );
+    
+    // Delete expired rows and compact the database on the schedule, on the leader
+    let maintenance = Arc::new(
+        DatabaseMaintenance::new(db.clone(), config.maintenance.clone())
+            .with_webhooks(webhook_service.clone())
+            .with_locks(locks.clone()),
+    );
+    if let Err(e) = maintenance.schedule_on(&scheduler) {
+        tracing::warn!("Failed to schedule database maintenance: {}", e);
+    }
+    
+    // Stream extracts of the database and the usage ledger, from a replica if one is close enough behind
+    let exporter = Exporter::new().with_database(db.clone()).with_usage_ledger(usage_ledger.clone());
+    let exporter = Arc::new(match &replicas {
+        Some(replicas) => exporter.with_replicas(replicas.clone()),
+        None => exporter,
+    });
+    
+    // Shed workflow runs and queued jobs, such as commands, before the process runs out of memory
+    let memory_watchdog = spawn_memory_watchdog(
+        &config,
+        Arc::new(ExecutionPools(vec![workflow_service.clone(), job_queue.clone()])),
+        alert_lifecycle.clone(),
+        metrics.clone(),
+    );
+    
+    // Create MCP context manager
+    let context_manager = Arc::new(
+        squirrel_mcp::context_manager::ContextManager::new()
+            .await
+            .with_redactor(redactor.clone()),
+    );
+    
+    // Create the assistant, if a language model is configured
+    let assistant = create_assistant(&config, &context_manager, redactor.clone(), alert_lifecycle.clone());
+    
+    // Create MCP tool manager with the configured tool manifests
+    let tool_manager = create_tool_manager(&config).await;
+    
+    // Open the result cache, invalidated as uploads replace files
+    let result_cache = create_result_cache(&config);
+    
+    // Open the upload directory and blob store, resuming unfinished uploads
+    let file_service = create_file_service(&config.files, Some(metrics.clone()), result_cache.clone());
+    
+    // Verify the checksums of stored blobs on the schedule, on the leader,
+    // raising alerts of damaged blobs and restoring them from the backup
+    let integrity = file_service.blob_store().map(|blobs| {
+        Arc::new(
+            BlobIntegrity::new(blobs.clone(), config.blob_integrity.clone()).with_alerts(alert_lifecycle.clone()),
+        )
+    });
+    if let Some(integrity) = &integrity {
+        if let Err(e) = integrity.schedule_on(&scheduler) {
+            tracing::warn!("Failed to schedule blob integrity checks: {}", e);
+        }
+    }
+    
+    // Keep tags in the web database
+    let tag_store: Arc<dyn TagStore> = Arc::new(SqlTagStore::new(db.clone()));
+    
+    // Keep forwarded log events in the web database
+    let log_store = SqlLogStore::new(db.clone());
+    let log_store: Arc<dyn LogStore> = Arc::new(match &replicas {
+        Some(replicas) => log_store.with_replicas(replicas.clone()),
+        None => log_store,
+    });
+    
+    // Keep idempotency keys of submissions in the web database
+    let idempotency = Arc::new(IdempotencyKeys::new(
+        Arc::new(SqlIdempotencyStore::new(db.clone())),
+        config.idempotency.clone(),
+    ));
+    idempotency.supervise_purger(&supervisor);
+    
+    // Read the admin flags and signing keys shared with other instances and
+    // the CLI, and keep reading them
+    let admin = Arc::new(AdminControls::new(Arc::new(SqlAdminStore::new(db.clone())), config.admin.clone()));
+    if let Err(e) = admin.refresh().await {
+        tracing::warn!("Failed to read admin flags: {}", e);
+    }
+    if let Err(e) = auth.reload_signing_keys().await {
+        tracing::warn!("Failed to read signing keys: {}", e);
+    }
+    admin.supervise_refresher(&supervisor, auth.clone());
+    
+    // Read the feature flag settings admins changed, and keep reading them
+    let flags = create_feature_flags(&config, &db);
+    if let Err(e) = flags.refresh().await {
+        tracing::warn!("Failed to read feature flags: {}", e);
+    }
+    flags.supervise_refresher(&supervisor);
+    
+    // Cache frequent reads, reporting hits and misses as metrics
+    let read_caches = Arc::new(ReadCaches::new(&config.read_cache));
+    read_caches.metrics.supervise_reporter(
+        &supervisor,
+        metrics.clone(),
+        std::time::Duration::from_secs(config.read_cache.metrics_interval_secs),
+    );
+    
+    // Keep accounts, teams and invitations in the web database
+    let users = Arc::new(
+        UserDirectory::new(
+            Arc::new(SqlUserStore::new(db.clone())),
+            auth.clone(),
+            config.users.clone(),
+        )
+        .with_caches(&config.read_cache),
+    );
+    users.register_caches(&read_caches.metrics);
     
     // Create app state
     let state = Arc::new(AppState {
@@ -175,6 +879,37 @@ pub async fn create_app(db: DbPool, config: Config) -> Router {
         ws_manager,
         auth,
         command_service: Some(command_service),
+        workflow_service: Some(workflow_service),
+        command_registry: Some(command_registry),
+        agent_scheduler: Some(agent_scheduler),
+        leader: Some(leader),
+        webhook_service: Some(webhook_service),
+        alert_lifecycle: Some(alert_lifecycle),
+        job_contexts: Some(Arc::new(handlers::jobs::JobContexts::new(context_manager.clone()))),
+        context_manager: Some(context_manager),
+        tool_manager: Some(tool_manager),
+        file_service: Some(file_service),
+        result_cache,
+        usage_ledger: Some(usage_ledger),
+        memory_watchdog,
+        assistant,
+        config_rollout: Some(config_rollout.clone()),
+        tag_store: Some(tag_store),
+        idempotency: Some(idempotency),
+        admin: Some(admin.clone()),
+        flags: Some(flags),
+        users: Some(users),
+        supervisor: Some(supervisor),
+        scheduler: Some(scheduler),
+        readiness: Some(readiness),
+        read_caches: Some(read_caches),
+        maintenance: Some(maintenance),
+        integrity,
+        exporter: Some(exporter),
+        log_store: Some(log_store),
+        redactor: Some(redactor),
+        locks: Some(locks),
+        replicas,
     });
 
     // Create WebSocket handler for commands
@@ -192,18 +927,167 @@ pub async fn create_app(db: DbPool, config: Config) -> Router {
     // Create the router with all routes
     
     
-    Router::new()
-        .route("/health", get(handlers::health::get_health))
-        .route("/api/health", get(handlers::health::get_health))
+    // Routes whose handlers take the caller's claims from the request
+    let authenticated = Router::new()
         .nest("/api/commands", handlers::commands::command_routes())
+        .nest("/api/workflows", handlers::workflows::workflow_routes())
+        .nest("/api/agents", handlers::agents::agent_routes())
+        .nest("/api/webhooks", handlers::webhooks::webhook_routes())
+        .nest("/api/alerts", handlers::alerts::alert_routes())
+        .nest("/api/files", handlers::files::file_routes())
+        .nest("/api/cache", handlers::cache::cache_routes())
+        .nest("/api/contexts", handlers::contexts::context_routes())
+        .nest("/api/usage", handlers::usage::usage_routes())
+        .nest("/api/assistant", handlers::assistant::assistant_routes())
+        .nest("/api/admin", handlers::admin::admin_routes())
+        .nest("/api/flags", handlers::flags::flag_routes())
+        .nest("/api/users", handlers::users::user_routes())
+        .nest("/api/teams", handlers::users::team_routes())
+        .nest("/api/tags", handlers::tags::tag_routes())
+        .route("/api/export", get(handlers::export::export))
+        .nest("/api/logs", handlers::logs::log_routes())
+        .nest("/api/traces", handlers::traces::trace_routes())
+        .nest("/api/compliance", handlers::compliance::compliance_routes())
+        .nest("/api/debug", handlers::debug::debug_routes())
         .route("/api/jobs", post(handlers::jobs::create_job))
         .route("/api/jobs", get(handlers::jobs::list_jobs))
         .route("/api/jobs/:id/status", get(handlers::jobs::get_job_status))
         .route("/api/jobs/:id/result", get(handlers::jobs::get_job_result))
         .route("/api/jobs/:id/cancel", post(handlers::jobs::cancel_job))
-        .route("/api/auth/login", post(handlers::auth::login))
-        .route("/api/auth/refresh", post(handlers::auth::refresh_token))
+        .route("/api/jobs/:id/context", get(handlers::jobs::get_job_context))
+        .route("/api/jobs/:id/finish", post(handlers::jobs::finish_job))
+        .route("/api/jobs/:id/report", get(handlers::jobs::get_job_report))
+        .route_layer(axum::middleware::from_fn_with_state(state.clone(), auth::middleware::require_auth));
+
+    Router::new()
+        .route("/health", get(handlers::health::get_health))
+        .route("/ready", get(handlers::health::get_ready))
+        .route("/api/health", get(handlers::health::get_health))
+        .route("/api/capabilities", get(handlers::capabilities::get_capabilities))
+        .nest("/api/plugins", handlers::plugins::plugin_routes())
+        .nest("/api/tools", handlers::tools::tool_routes())
+        .merge(authenticated)
+        .nest("/api/auth", auth::routes::auth_routes())
         .route("/ws", get(websocket::ws_handler))
+        .route("/ws/agents", get(agents::agent_ws_handler))
+        .layer(axum::middleware::from_fn_with_state(admin, admin::refuse_when_degraded))
+        .layer(axum::middleware::from_fn(deadline::bound_requests))
+        .layer(axum::middleware::from_fn_with_state(rate_limiter, rate_limit::limit_requests))
+        .layer(axum::middleware::from_fn_with_state(config_rollout, rollout::route_requests))
         .layer(CorsLayer::permissive())
+        .layer(security_headers)
+        .layer(axum::middleware::from_fn_with_state(ip_filter, access::filter_ip))
+        .layer(axum::middleware::from_fn_with_state(default_locale, i18n::negotiate_locale))
         .with_state(state)
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::http::StatusCode;
+    use crate::api::error::AppError;
+
+    #[tokio::test]
+    async fn test_unreachable_mcp_server_refuses_commands() {
+        let mut config = Config::default();
+        config.mcp.url = Some("http://mcp.invalid".to_string());
+        let client = create_mcp_command_client(&config, &websocket::init());
+
+        let error = client.execute_command("align", &serde_json::json!({}), None).await.unwrap_err();
+        let status = match AppError::from(error) {
+            AppError::Custom(status, _) => status,
+            other => panic!("unexpected error: {other:?}"),
+        };
+        assert_eq!(status, StatusCode::SERVICE_UNAVAILABLE);
+        assert!(client.list_available_commands().await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_metrics_are_exported_to_configured_backends() {
+        let server = tokio::net::UdpSocket::bind("127.0.0.1:0").await.unwrap();
+        let config: Config = toml::from_str(&format!(
+            "[metrics]\ninterval = 1\n[[metrics.backends]]\ntype = \"statsd\"\naddress = \"{}\"\n",
+            server.local_addr().unwrap()
+        ))
+        .unwrap();
+        let metrics = DefaultMetricCollector::new();
+        metrics.initialize().await.unwrap();
+        metrics
+            .record_metric(Metric::new("queue_depth", 2.0, MetricType::Gauge, HashMap::new()))
+            .await
+            .unwrap();
+        let supervisor = Supervisor::new();
+
+        let exports = supervise_metric_exports(&config, &supervisor, Arc::new(metrics)).await;
+        assert!(exports.is_some());
+        let mut buffer = [0u8; 2048];
+        let size = tokio::time::timeout(std::time::Duration::from_secs(5), server.recv(&mut buffer))
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(String::from_utf8_lossy(&buffer[..size]), "queue_depth:2|g");
+        assert!(supervise_metric_exports(&Config::default(), &supervisor, Arc::new(DefaultMetricCollector::new()))
+            .await
+            .is_none());
+    }
+
+    #[tokio::test]
+    async fn test_leader_sends_configured_reports() {
+        // Webhook channel receiving the reports
+        let (reports, mut received) = tokio::sync::mpsc::unbounded_channel::<serde_json::Value>();
+        let receiver = Router::new().route(
+            "/reports",
+            post(move |axum::Json(report): axum::Json<serde_json::Value>| async move {
+                let _ = reports.send(report);
+            }),
+        );
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}/reports", listener.local_addr().unwrap());
+        tokio::spawn(axum::Server::from_tcp(listener).unwrap().serve(receiver.into_make_service()));
+
+        let config: Config = toml::from_str(&format!(
+            r#"
+            [reports]
+            enabled = true
+            send_hour_utc = 0
+
+            [[reports.recipients]]
+            name = "ops"
+            channel = "webhook"
+            periods = ["daily"]
+
+            [[reports.channels]]
+            Webhook = {{ url = "{url}", method = "POST", headers = {{}} }}
+            "#
+        ))
+        .unwrap();
+        let alerts = Arc::new(AlertLifecycle::in_memory(config.alerts.clone()));
+        alerts
+            .observe(&Alert::new(
+                squirrel_monitoring::alerts::AlertType::Generic,
+                squirrel_monitoring::alerts::AlertSeverity::Critical,
+                "db".to_string(),
+                "database down".to_string(),
+            ))
+            .unwrap();
+        let metrics = DefaultMetricCollector::new();
+        metrics.initialize().await.unwrap();
+        let leader = Arc::new(LeaderElector::new(
+            Arc::new(MemoryLeaseStore::new()),
+            config.leader_election.clone(),
+            None,
+        ));
+
+        let _reports = spawn_reports(&config, &leader, alerts, Arc::new(metrics)).unwrap();
+        tokio::time::sleep(std::time::Duration::from_millis(100)).await;
+        assert!(received.try_recv().is_err(), "reports are only sent by the leader");
+
+        leader.tick().await;
+        let report = tokio::time::timeout(std::time::Duration::from_secs(5), received.recv())
+            .await
+            .unwrap()
+            .unwrap();
+        assert!(report["report"]["subject"].as_str().unwrap().starts_with("Daily Squirrel report"));
+        assert!(report["report"]["body"].as_str().unwrap().contains("Critical"), "{report}");
+    }
+}
@@ -0,0 +1,780 @@
+//! Load tests of the web API.
+//!
+//! A [`LoadTest`] drives a running instance with the requests its clients
+//! make: submitting jobs, polling their status and subscribing to their
+//! events over the WebSocket. Workers run side by side for a set time, each
+//! taking the next operation from the weighted [`RequestMix`], and every
+//! request's latency and outcome goes into the [`LoadTestReport`]. The
+//! report can be checked against [`Threshold`]s on latency percentiles,
+//! error rates and throughput, so a release that got slower is held back.
+
+use std::collections::{BTreeMap, VecDeque};
+use std::fmt;
+use std::str::FromStr;
+use std::sync::{Mutex, PoisonError};
+use std::time::{Duration, Instant};
+
+use futures::{SinkExt, StreamExt};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use thiserror::Error;
+use tokio_tungstenite::tungstenite::client::IntoClientRequest;
+use tokio_tungstenite::tungstenite::http::{header::AUTHORIZATION, HeaderValue};
+use tokio_tungstenite::{connect_async, tungstenite::Message};
+
+/// Jobs kept for polling and subscribing; older ones are forgotten
+const MAX_TRACKED_JOBS: usize = 1000;
+
+/// Distinct errors listed per operation
+const MAX_LISTED_ERRORS: usize = 5;
+
+/// ID of the subscribe commands, to tell their answers from events
+const SUBSCRIBE_ID: &str = "loadtest";
+
+/// Errors setting up a load test
+#[derive(Debug, Error)]
+pub enum LoadTestError {
+    /// The request mix could not be read or has no requests
+    #[error("Invalid request mix: {0}")]
+    InvalidMix(String),
+
+    /// A threshold could not be read
+    #[error("Invalid threshold: {0}")]
+    InvalidThreshold(String),
+
+    /// The configuration cannot drive a load test
+    #[error("Invalid load test: {0}")]
+    InvalidConfig(String),
+}
+
+/// A request a load test makes
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Operation {
+    /// Submit a job with `POST /api/jobs`
+    Submit,
+    /// Poll a submitted job with `GET /api/jobs/{id}/status`
+    Poll,
+    /// Subscribe to a submitted job's events over `/ws`
+    Subscribe,
+}
+
+impl Operation {
+    /// Every operation, in report order
+    pub const ALL: [Operation; 3] = [Operation::Submit, Operation::Poll, Operation::Subscribe];
+}
+
+impl fmt::Display for Operation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Submit => write!(f, "submit"),
+            Self::Poll => write!(f, "poll"),
+            Self::Subscribe => write!(f, "subscribe"),
+        }
+    }
+}
+
+impl FromStr for Operation {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "submit" => Ok(Self::Submit),
+            "poll" => Ok(Self::Poll),
+            "subscribe" => Ok(Self::Subscribe),
+            other => Err(format!("Unknown operation {}; use submit, poll or subscribe", other)),
+        }
+    }
+}
+
+/// How often each operation is made, relative to the others
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RequestMix {
+    /// Weight of job submissions
+    pub submit: u32,
+    /// Weight of status polls
+    pub poll: u32,
+    /// Weight of WebSocket subscriptions
+    pub subscribe: u32,
+}
+
+impl Default for RequestMix {
+    fn default() -> Self {
+        Self {
+            submit: 1,
+            poll: 4,
+            subscribe: 1,
+        }
+    }
+}
+
+impl RequestMix {
+    /// Weight of `operation`
+    pub fn weight(&self, operation: Operation) -> u32 {
+        match operation {
+            Operation::Submit => self.submit,
+            Operation::Poll => self.poll,
+            Operation::Subscribe => self.subscribe,
+        }
+    }
+
+    /// One round of the mix, each operation as often as its weight says,
+    /// interleaved so a worker does not make the same request in a row
+    fn schedule(&self) -> Vec<Operation> {
+        let mut left: Vec<(Operation, u32)> = Operation::ALL.iter().map(|op| (*op, self.weight(*op))).collect();
+        let mut schedule = Vec::new();
+        while left.iter().any(|(_, weight)| *weight > 0) {
+            for (operation, weight) in &mut left {
+                if *weight > 0 {
+                    schedule.push(*operation);
+                    *weight -= 1;
+                }
+            }
+        }
+        schedule
+    }
+}
+
+impl FromStr for RequestMix {
+    type Err = LoadTestError;
+
+    /// Read a mix like `submit=1,poll=4,subscribe=1`; operations left out
+    /// are not made
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut mix = Self {
+            submit: 0,
+            poll: 0,
+            subscribe: 0,
+        };
+        for part in s.split(',').map(str::trim).filter(|part| !part.is_empty()) {
+            let (name, weight) = part
+                .split_once('=')
+                .ok_or_else(|| LoadTestError::InvalidMix(format!("Expected operation=weight, got {}", part)))?;
+            let operation: Operation = name.trim().parse().map_err(LoadTestError::InvalidMix)?;
+            let weight: u32 = weight
+                .trim()
+                .parse()
+                .map_err(|_| LoadTestError::InvalidMix(format!("Invalid weight {} for {}", weight, operation)))?;
+            match operation {
+                Operation::Submit => mix.submit = weight,
+                Operation::Poll => mix.poll = weight,
+                Operation::Subscribe => mix.subscribe = weight,
+            }
+        }
+        if mix.schedule().is_empty() {
+            return Err(LoadTestError::InvalidMix("No operation has a weight".to_string()));
+        }
+        Ok(mix)
+    }
+}
+
+/// Configuration of a load test
+#[derive(Debug, Clone)]
+pub struct LoadTestConfig {
+    /// Base URL of the instance, e.g. `http://127.0.0.1:3000`
+    pub target: String,
+    /// Access token the requests are made with
+    pub token: Option<String>,
+    /// Workers making requests side by side
+    pub concurrency: usize,
+    /// How long the workers make requests
+    pub duration: Duration,
+    /// How often each operation is made
+    pub mix: RequestMix,
+    /// Name of the submitted jobs
+    pub job_name: String,
+    /// Parameters of the submitted jobs
+    pub job_parameters: Value,
+    /// Longest a request may take before it counts as failed
+    pub timeout: Duration,
+}
+
+impl Default for LoadTestConfig {
+    fn default() -> Self {
+        Self {
+            target: "http://127.0.0.1:3000".to_string(),
+            token: None,
+            concurrency: 10,
+            duration: Duration::from_secs(30),
+            mix: RequestMix::default(),
+            job_name: "loadtest".to_string(),
+            job_parameters: json!({}),
+            timeout: Duration::from_secs(10),
+        }
+    }
+}
+
+/// Latency and outcome of one request
+#[derive(Debug, Clone)]
+struct Sample {
+    operation: Operation,
+    latency: Duration,
+    error: Option<String>,
+}
+
+/// Drives an instance with a mix of requests
+pub struct LoadTest {
+    config: LoadTestConfig,
+    client: reqwest::Client,
+    /// Jobs submitted so far, newest last
+    jobs: Mutex<VecDeque<String>>,
+}
+
+impl LoadTest {
+    /// Create a new LoadTest
+    pub fn new(config: LoadTestConfig) -> Result<Self, LoadTestError> {
+        if config.concurrency == 0 {
+            return Err(LoadTestError::InvalidConfig("Concurrency must be positive".to_string()));
+        }
+        if config.mix.schedule().is_empty() {
+            return Err(LoadTestError::InvalidMix("No operation has a weight".to_string()));
+        }
+        if !config.target.starts_with("http://") && !config.target.starts_with("https://") {
+            return Err(LoadTestError::InvalidConfig(format!(
+                "Target {} is not an http:// or https:// URL",
+                config.target
+            )));
+        }
+        let client = reqwest::Client::builder()
+            .timeout(config.timeout)
+            .build()
+            .map_err(|e| LoadTestError::InvalidConfig(e.to_string()))?;
+        Ok(Self {
+            config,
+            client,
+            jobs: Mutex::new(VecDeque::new()),
+        })
+    }
+
+    /// Make requests until the duration is up, then report on them
+    pub async fn run(&self) -> LoadTestReport {
+        let schedule = self.config.mix.schedule();
+        let started = Instant::now();
+        let deadline = started + self.config.duration;
+        // Workers start at different points of the mix
+        let workers = (0..self.config.concurrency)
+            .map(|worker| self.worker(&schedule, worker * schedule.len() / self.config.concurrency, deadline));
+        let samples: Vec<Sample> = futures::future::join_all(workers).await.into_iter().flatten().collect();
+        LoadTestReport::new(started.elapsed(), self.config.concurrency, &samples)
+    }
+
+    /// Make the operations of `schedule` in turn from `offset` until `deadline`
+    async fn worker(&self, schedule: &[Operation], offset: usize, deadline: Instant) -> Vec<Sample> {
+        let mut samples = Vec::new();
+        let mut step = offset;
+        while Instant::now() < deadline {
+            let job = {
+                let jobs = self.jobs.lock().unwrap_or_else(PoisonError::into_inner);
+                (!jobs.is_empty()).then(|| jobs[step % jobs.len()].clone())
+            };
+            // Polling and subscribing need a job; submit one first
+            let operation = match (schedule[step % schedule.len()], job) {
+                (Operation::Poll, Some(job)) => (Operation::Poll, job),
+                (Operation::Subscribe, Some(job)) => (Operation::Subscribe, job),
+                (_, job) => (Operation::Submit, job.unwrap_or_default()),
+            };
+            step += 1;
+
+            let started = Instant::now();
+            let outcome = match &operation {
+                (Operation::Submit, _) => self.submit().await,
+                (Operation::Poll, job) => self.poll(job).await,
+                (Operation::Subscribe, job) => self.subscribe(job).await,
+            };
+            samples.push(Sample {
+                operation: operation.0,
+                latency: started.elapsed(),
+                error: outcome.err(),
+            });
+        }
+        samples
+    }
+
+    /// URL of `path` on the target
+    fn url(&self, path: &str) -> String {
+        format!("{}{}", self.config.target.trim_end_matches('/'), path)
+    }
+
+    /// Send `request` with the access token, failing on error statuses
+    async fn send(&self, mut request: reqwest::RequestBuilder) -> Result<reqwest::Response, String> {
+        if let Some(token) = &self.config.token {
+            request = request.bearer_auth(token);
+        }
+        let response = request.send().await.map_err(|e| e.to_string())?;
+        if !response.status().is_success() {
+            return Err(format!("HTTP {}", response.status()));
+        }
+        Ok(response)
+    }
+
+    /// Submit a job and keep its ID for polling and subscribing
+    async fn submit(&self) -> Result<(), String> {
+        let request = self.client.post(self.url("/api/jobs")).json(&json!({
+            "name": self.config.job_name,
+            "parameters": self.config.job_parameters,
+        }));
+        let body: Value = self.send(request).await?.json().await.map_err(|e| e.to_string())?;
+        let id = body["data"]["id"]
+            .as_str()
+            .ok_or_else(|| "No job ID in the response".to_string())?;
+
+        let mut jobs = self.jobs.lock().unwrap_or_else(PoisonError::into_inner);
+        jobs.push_back(id.to_string());
+        if jobs.len() > MAX_TRACKED_JOBS {
+            jobs.pop_front();
+        }
+        Ok(())
+    }
+
+    /// Read the status of `job`
+    async fn poll(&self, job: &str) -> Result<(), String> {
+        let request = self.client.get(self.url(&format!("/api/jobs/{}/status", job)));
+        self.send(request).await?.bytes().await.map_err(|e| e.to_string())?;
+        Ok(())
+    }
+
+    /// Open a WebSocket, subscribe to the events of `job` and close it again
+    async fn subscribe(&self, job: &str) -> Result<(), String> {
+        let url = self.url("/ws").replacen("http", "ws", 1);
+        let mut request = url.into_client_request().map_err(|e| e.to_string())?;
+        if let Some(token) = &self.config.token {
+            let value = HeaderValue::from_str(&format!("Bearer {}", token)).map_err(|e| e.to_string())?;
+            request.headers_mut().insert(AUTHORIZATION, value);
+        }
+        let command = json!({
+            "command": "subscribe",
+            "id": SUBSCRIBE_ID,
+            "params": { "category": "job", "channel": job },
+        });
+
+        let subscribed = async {
+            let (mut socket, _) = connect_async(request).await.map_err(|e| e.to_string())?;
+            socket
+                .send(Message::Text(command.to_string()))
+                .await
+                .map_err(|e| e.to_string())?;
+            // Skip events sent meanwhile until the answer to the command
+            let answer = loop {
+                let frame = socket
+                    .next()
+                    .await
+                    .ok_or_else(|| "Connection closed before the subscription was confirmed".to_string())?
+                    .map_err(|e| e.to_string())?;
+                let Message::Text(text) = frame else { continue };
+                let Ok(message) = serde_json::from_str::<Value>(&text) else { continue };
+                if message["id"] == SUBSCRIBE_ID {
+                    break message;
+                }
+            };
+            let _ = socket.close(None).await;
+            match answer["success"].as_bool() {
+                Some(true) => Ok(()),
+                _ => Err(answer["error"].as_str().unwrap_or("Subscription refused").to_string()),
+            }
+        };
+        tokio::time::timeout(self.config.timeout, subscribed)
+            .await
+            .unwrap_or_else(|_| Err("Timed out".to_string()))
+    }
+}
+
+/// A failure and how often it happened
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ErrorCount {
+    /// What failed
+    pub message: String,
+    /// Requests that failed this way
+    pub count: u64,
+}
+
+/// Latencies and outcomes of a set of requests
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct LatencyStats {
+    /// Requests made
+    pub requests: u64,
+    /// Requests that failed
+    pub errors: u64,
+    /// Share of the requests that failed, from 0 to 1
+    pub error_rate: f64,
+    /// Requests made per second
+    pub throughput: f64,
+    /// Median latency in milliseconds
+    pub p50_ms: f64,
+    /// 90th percentile latency in milliseconds
+    pub p90_ms: f64,
+    /// 95th percentile latency in milliseconds
+    pub p95_ms: f64,
+    /// 99th percentile latency in milliseconds
+    pub p99_ms: f64,
+    /// Highest latency in milliseconds
+    pub max_ms: f64,
+}
+
+impl LatencyStats {
+    /// Statistics of `samples`, made over `elapsed`
+    fn new<'a>(samples: impl Iterator<Item = &'a Sample>, elapsed: Duration) -> Self {
+        let mut latencies = Vec::new();
+        let mut errors = 0;
+        for sample in samples {
+            latencies.push(sample.latency.as_secs_f64() * 1000.0);
+            errors += u64::from(sample.error.is_some());
+        }
+        latencies.sort_by(f64::total_cmp);
+        // Nearest-rank percentile
+        let percentile = |p: f64| {
+            let rank = ((p / 100.0) * latencies.len() as f64).ceil() as usize;
+            latencies.get(rank.saturating_sub(1)).copied().unwrap_or_default()
+        };
+        let requests = latencies.len() as u64;
+        Self {
+            requests,
+            errors,
+            error_rate: if requests == 0 { 0.0 } else { errors as f64 / requests as f64 },
+            throughput: requests as f64 / elapsed.as_secs_f64().max(f64::EPSILON),
+            p50_ms: percentile(50.0),
+            p90_ms: percentile(90.0),
+            p95_ms: percentile(95.0),
+            p99_ms: percentile(99.0),
+            max_ms: latencies.last().copied().unwrap_or_default(),
+        }
+    }
+}
+
+/// Statistics of one operation
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct OperationReport {
+    /// The operation
+    pub operation: Operation,
+    /// Its latencies and outcomes
+    #[serde(flatten)]
+    pub stats: LatencyStats,
+    /// The most frequent ways it failed
+    pub failures: Vec<ErrorCount>,
+}
+
+/// What a load test measured
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct LoadTestReport {
+    /// How long requests were made, in milliseconds
+    pub duration_ms: u64,
+    /// Workers that made them
+    pub concurrency: usize,
+    /// Statistics of every request together
+    pub overall: LatencyStats,
+    /// Statistics of each operation of the mix that was made
+    pub operations: Vec<OperationReport>,
+}
+
+impl LoadTestReport {
+    /// Report on `samples`, made by `concurrency` workers over `elapsed`
+    fn new(elapsed: Duration, concurrency: usize, samples: &[Sample]) -> Self {
+        let operations = Operation::ALL
+            .iter()
+            .filter(|operation| samples.iter().any(|sample| sample.operation == **operation))
+            .map(|operation| {
+                let made = || samples.iter().filter(|sample| sample.operation == *operation);
+                let mut failures: BTreeMap<&str, u64> = BTreeMap::new();
+                for error in made().filter_map(|sample| sample.error.as_deref()) {
+                    *failures.entry(error).or_default() += 1;
+                }
+                let mut failures: Vec<ErrorCount> = failures
+                    .into_iter()
+                    .map(|(message, count)| ErrorCount {
+                        message: message.to_string(),
+                        count,
+                    })
+                    .collect();
+                failures.sort_by_key(|failure| std::cmp::Reverse(failure.count));
+                failures.truncate(MAX_LISTED_ERRORS);
+                OperationReport {
+                    operation: *operation,
+                    stats: LatencyStats::new(made(), elapsed),
+                    failures,
+                }
+            })
+            .collect();
+        Self {
+            duration_ms: u64::try_from(elapsed.as_millis()).unwrap_or(u64::MAX),
+            concurrency,
+            overall: LatencyStats::new(samples.iter(), elapsed),
+            operations,
+        }
+    }
+
+    /// Statistics of `operation`, or of every request
+    pub fn stats(&self, operation: Option<Operation>) -> Option<&LatencyStats> {
+        match operation {
+            None => Some(&self.overall),
+            Some(operation) => self
+                .operations
+                .iter()
+                .find(|report| report.operation == operation)
+                .map(|report| &report.stats),
+        }
+    }
+
+    /// The thresholds of `thresholds` this report fails, with why
+    pub fn check(&self, thresholds: &[Threshold]) -> Vec<String> {
+        thresholds
+            .iter()
+            .filter_map(|threshold| match self.stats(threshold.operation) {
+                None => Some(format!("{}: no {} requests were made", threshold, threshold.operation?)),
+                Some(stats) => {
+                    let value = threshold.metric.value(stats);
+                    let met = match threshold.comparison {
+                        Comparison::Below => value < threshold.limit,
+                        Comparison::Above => value > threshold.limit,
+                    };
+                    (!met).then(|| format!("{}: measured {}", threshold, threshold.metric.format(value)))
+                }
+            })
+            .collect()
+    }
+}
+
+/// A measure a threshold bounds
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Metric {
+    /// Median latency in milliseconds
+    P50,
+    /// 90th percentile latency in milliseconds
+    P90,
+    /// 95th percentile latency in milliseconds
+    P95,
+    /// 99th percentile latency in milliseconds
+    P99,
+    /// Highest latency in milliseconds
+    Max,
+    /// Share of failed requests
+    ErrorRate,
+    /// Requests per second
+    Rps,
+}
+
+impl Metric {
+    /// The measure in `stats`
+    pub fn value(self, stats: &LatencyStats) -> f64 {
+        match self {
+            Self::P50 => stats.p50_ms,
+            Self::P90 => stats.p90_ms,
+            Self::P95 => stats.p95_ms,
+            Self::P99 => stats.p99_ms,
+            Self::Max => stats.max_ms,
+            Self::ErrorRate => stats.error_rate,
+            Self::Rps => stats.throughput,
+        }
+    }
+
+    /// `value` with its unit
+    fn format(self, value: f64) -> String {
+        match self {
+            Self::ErrorRate => format!("{:.2}%", value * 100.0),
+            Self::Rps => format!("{:.1}/s", value),
+            _ => format!("{:.1} ms", value),
+        }
+    }
+}
+
+impl fmt::Display for Metric {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::P50 => write!(f, "p50"),
+            Self::P90 => write!(f, "p90"),
+            Self::P95 => write!(f, "p95"),
+            Self::P99 => write!(f, "p99"),
+            Self::Max => write!(f, "max"),
+            Self::ErrorRate => write!(f, "error_rate"),
+            Self::Rps => write!(f, "rps"),
+        }
+    }
+}
+
+impl FromStr for Metric {
+    type Err = LoadTestError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "p50" => Ok(Self::P50),
+            "p90" => Ok(Self::P90),
+            "p95" => Ok(Self::P95),
+            "p99" => Ok(Self::P99),
+            "max" => Ok(Self::Max),
+            "error_rate" => Ok(Self::ErrorRate),
+            "rps" => Ok(Self::Rps),
+            other => Err(LoadTestError::InvalidThreshold(format!(
+                "Unknown metric {}; use p50, p90, p95, p99, max, error_rate or rps",
+                other
+            ))),
+        }
+    }
+}
+
+/// Which side of the limit a measure must stay on
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Comparison {
+    /// The measure must be below the limit
+    Below,
+    /// The measure must be above the limit
+    Above,
+}
+
+/// A bound a load test's measure must stay within, such as `p99<500`
+///
+/// Written as `[operation.]metric<limit` or `[operation.]metric>limit`.
+/// Latencies are in milliseconds; error rates are a share from 0 to 1, or a
+/// percentage with `%`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Threshold {
+    /// Operation whose requests are measured; all requests if unset
+    pub operation: Option<Operation>,
+    /// What is measured
+    pub metric: Metric,
+    /// Which side of the limit it must stay on
+    pub comparison: Comparison,
+    /// The limit
+    pub limit: f64,
+}
+
+impl fmt::Display for Threshold {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if let Some(operation) = self.operation {
+            write!(f, "{}.", operation)?;
+        }
+        let comparison = match self.comparison {
+            Comparison::Below => '<',
+            Comparison::Above => '>',
+        };
+        write!(f, "{}{}{}", self.metric, comparison, self.limit)
+    }
+}
+
+impl FromStr for Threshold {
+    type Err = LoadTestError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid =
+            || LoadTestError::InvalidThreshold(format!("Expected e.g. p99<500 or submit.error_rate<1%, got {}", s));
+        let (index, comparison) = s
+            .char_indices()
+            .find_map(|(index, c)| match c {
+                '<' => Some((index, Comparison::Below)),
+                '>' => Some((index, Comparison::Above)),
+                _ => None,
+            })
+            .ok_or_else(invalid)?;
+        let (measure, limit) = (s[..index].trim(), s[index + 1..].trim());
+        let (operation, metric) = match measure.split_once('.') {
+            Some((operation, metric)) => (
+                Some(operation.parse().map_err(LoadTestError::InvalidThreshold)?),
+                metric,
+            ),
+            None => (None, measure),
+        };
+        let limit = match limit.strip_suffix('%') {
+            Some(percent) => percent.trim().parse::<f64>().map_err(|_| invalid())? / 100.0,
+            None => limit.parse().map_err(|_| invalid())?,
+        };
+        Ok(Self {
+            operation,
+            metric: metric.parse()?,
+            comparison,
+            limit,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::extract::ws::{Message as WsMessage, WebSocketUpgrade};
+    use axum::http::{HeaderMap, StatusCode};
+    use axum::response::IntoResponse;
+    use axum::routing::{get, post};
+    use axum::{Json, Router};
+
+    /// An instance answering the load test's requests
+    fn serve() -> String {
+        async fn submit(headers: HeaderMap) -> impl IntoResponse {
+            if headers.get("authorization").and_then(|value| value.to_str().ok()) != Some("Bearer secret") {
+                return StatusCode::UNAUTHORIZED.into_response();
+            }
+            Json(json!({ "success": true, "data": { "id": "job-1" } })).into_response()
+        }
+        async fn subscribe(ws: WebSocketUpgrade) -> impl IntoResponse {
+            ws.on_upgrade(|mut socket| async move {
+                let _ = socket.send(WsMessage::Text(json!({ "event": "connected" }).to_string())).await;
+                while let Some(Ok(WsMessage::Text(_))) = socket.recv().await {
+                    let answer = json!({ "success": true, "event": "subscribed", "data": {}, "id": SUBSCRIBE_ID });
+                    let _ = socket.send(WsMessage::Text(answer.to_string())).await;
+                }
+            })
+        }
+        let router = Router::new()
+            .route("/api/jobs", post(submit))
+            .route("/api/jobs/:id/status", get(|| async { Json(json!({ "success": true })) }))
+            .route("/ws", get(subscribe));
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let address = listener.local_addr().unwrap();
+        tokio::spawn(axum::Server::from_tcp(listener).unwrap().serve(router.into_make_service()));
+        format!("http://{}", address)
+    }
+
+    #[tokio::test]
+    async fn test_load_test_drives_the_request_mix() {
+        let config = LoadTestConfig {
+            target: serve(),
+            token: Some("secret".to_string()),
+            concurrency: 4,
+            duration: Duration::from_millis(300),
+            mix: "submit=1,poll=2,subscribe=1".parse().unwrap(),
+            ..LoadTestConfig::default()
+        };
+        let report = LoadTest::new(config.clone()).unwrap().run().await;
+
+        assert_eq!(report.concurrency, 4);
+        assert_eq!(report.operations.len(), 3);
+        for operation in &report.operations {
+            assert!(operation.stats.requests > 0, "{:?}", operation);
+            assert_eq!(operation.stats.errors, 0, "{:?}", operation.failures);
+            assert!(operation.stats.p50_ms <= operation.stats.p99_ms);
+            assert!(operation.stats.p99_ms <= operation.stats.max_ms);
+        }
+        let polls = report.stats(Some(Operation::Poll)).unwrap().requests;
+        let submits = report.stats(Some(Operation::Submit)).unwrap().requests;
+        assert!(polls > submits, "{} polls, {} submits", polls, submits);
+
+        let thresholds: Vec<Threshold> = ["error_rate<1%", "poll.p99<0.001", "rps>1"]
+            .iter()
+            .map(|threshold| threshold.parse().unwrap())
+            .collect();
+        let failed = report.check(&thresholds);
+        assert_eq!(failed.len(), 1, "{:?}", failed);
+        assert!(failed[0].starts_with("poll.p99<0.001: measured "), "{}", failed[0]);
+
+        // Without the token, every submission fails
+        let report = LoadTest::new(LoadTestConfig { token: None, ..config }).unwrap().run().await;
+        let submit = &report.operations[0];
+        assert_eq!(submit.operation, Operation::Submit);
+        assert_eq!(submit.stats.error_rate, 1.0);
+        assert_eq!(submit.failures[0].message, "HTTP 401 Unauthorized");
+        assert_eq!(report.check(&thresholds[..1]).len(), 1);
+    }
+
+    #[test]
+    fn test_mixes_and_thresholds_are_read() {
+        let mix: RequestMix = "submit=2, poll=1".parse().unwrap();
+        assert_eq!(mix.schedule(), vec![Operation::Submit, Operation::Poll, Operation::Submit]);
+        assert!("poll=0".parse::<RequestMix>().is_err());
+        assert!("fetch=1".parse::<RequestMix>().is_err());
+
+        let threshold: Threshold = "submit.error_rate<2.5%".parse().unwrap();
+        assert_eq!(threshold.operation, Some(Operation::Submit));
+        assert_eq!(threshold.metric, Metric::ErrorRate);
+        assert_eq!(threshold.limit, 0.025);
+        assert_eq!("rps>50".parse::<Threshold>().unwrap().comparison, Comparison::Above);
+        assert!("p99=500".parse::<Threshold>().is_err());
+        assert!("p42<500".parse::<Threshold>().is_err());
+    }
+}
@@ -0,0 +1,333 @@
+//! Distributed locks for multi-instance deployments.
+//!
+//! Leadership decides which instance runs scheduled work, but some
+//! operations can also be started on any instance, by an admin or by an
+//! instance starting up: migrations, garbage collection, database
+//! maintenance and scheduled singleton tasks run on demand. Each of them
+//! holds a named lock from the [`LockService`] while it runs, so two
+//! instances never run them at once.
+//!
+//! A lock is a lease in the shared database: it expires unless its holder
+//! renews it, and a [`LockGuard`] renews it in the background every third of
+//! its time to live. A holder that cannot renew in time, because it was
+//! partitioned or stalled, loses the lock; [`LockService::with_lock`] stops
+//! its work when that happens. Every acquisition gets a larger fencing
+//! token than the one before, so a store can refuse writes made under a
+//! lock that has changed hands since, see [`LockService::is_current`].
+
+pub mod store;
+
+pub use store::{LockRecord, LockStore, MemoryLockStore, SqlLockStore};
+
+use std::future::Future;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use async_trait::async_trait;
+use serde::{Deserialize, Serialize};
+use squirrel_app::schedule::{TaskLockGuard, TaskLocks};
+use squirrel_app::supervisor::TaskError;
+use tokio::sync::watch;
+use tokio::task::JoinHandle;
+use tracing::{debug, warn};
+use uuid::Uuid;
+
+use crate::api::error::AppError;
+
+/// Lock held while migrations run
+pub const MIGRATIONS_LOCK: &str = "migrations";
+
+/// Lock held while garbage is collected
+pub const GC_LOCK: &str = "gc";
+
+/// Prefix of the locks held by scheduled singleton tasks
+pub const SCHEDULE_LOCK_PREFIX: &str = "schedule.";
+
+/// Time to live of locks unless an operation asks for another
+pub const DEFAULT_LOCK_TTL: Duration = Duration::from_secs(30);
+
+/// How often a waiting holder tries to take a lock again
+const RETRY_INTERVAL: Duration = Duration::from_millis(250);
+
+/// A lock as seen by the admin API
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct LockStatus {
+    /// The lock as stored
+    #[serde(flatten)]
+    pub lock: LockRecord,
+    /// Whether it is held now
+    pub held: bool,
+}
+
+/// Takes named locks on behalf of this instance
+pub struct LockService {
+    store: Arc<dyn LockStore>,
+    instance_id: String,
+}
+
+impl LockService {
+    /// Create a new LockService taking locks in `store` for the instance
+    /// `instance_id`
+    pub fn new(store: Arc<dyn LockStore>, instance_id: impl Into<String>) -> Self {
+        Self {
+            store,
+            instance_id: instance_id.into(),
+        }
+    }
+
+    /// ID of the instance holders belong to
+    pub fn instance_id(&self) -> &str {
+        &self.instance_id
+    }
+
+    /// Take the lock `name` if it is free, renewing it every third of `ttl`
+    /// until the guard is released or dropped
+    ///
+    /// Returns `None` if someone else holds it, even another task of this
+    /// instance.
+    pub async fn try_lock(&self, name: &str, ttl: Duration) -> Result<Option<LockGuard>, AppError> {
+        let holder = format!("{}/{}", self.instance_id, Uuid::new_v4());
+        let lock = self.store.acquire(name, &holder, ttl).await?;
+        if lock.holder != holder {
+            return Ok(None);
+        }
+        debug!("Took lock {} with token {}", name, lock.token);
+        Ok(Some(LockGuard::hold(self.store.clone(), lock, ttl)))
+    }
+
+    /// Take the lock `name`, waiting up to `wait` for it to be freed
+    ///
+    /// Fails with a conflict if it is still held after `wait`.
+    pub async fn lock(&self, name: &str, ttl: Duration, wait: Duration) -> Result<LockGuard, AppError> {
+        let deadline = Instant::now() + wait;
+        loop {
+            if let Some(guard) = self.try_lock(name, ttl).await? {
+                return Ok(guard);
+            }
+            if Instant::now() >= deadline {
+                return Err(self.held(name).await);
+            }
+            tokio::time::sleep(RETRY_INTERVAL.min(deadline.saturating_duration_since(Instant::now()))).await;
+        }
+    }
+
+    /// Run `work` holding the lock `name`, stopping it if the lock is lost
+    ///
+    /// Fails with a conflict without running `work` if the lock is held.
+    pub async fn with_lock<T, Fut>(&self, name: &str, ttl: Duration, work: Fut) -> Result<T, AppError>
+    where
+        Fut: Future<Output = Result<T, AppError>>,
+    {
+        let Some(mut guard) = self.try_lock(name, ttl).await? else {
+            return Err(self.held(name).await);
+        };
+        let result = tokio::select! {
+            result = work => result,
+            () = guard.lost() => Err(AppError::Conflict(format!("Lost lock {} while running", name))),
+        };
+        guard.release().await;
+        result
+    }
+
+    /// Whether `token` is the fencing token of the lock `name` and the lock
+    /// is still held
+    pub async fn is_current(&self, name: &str, token: i64) -> Result<bool, AppError> {
+        let now = chrono::Utc::now();
+        Ok(self
+            .store
+            .get(name)
+            .await?
+            .is_some_and(|lock| lock.token == token && lock.is_held(now)))
+    }
+
+    /// Every lock ever taken, and whether it is held now
+    pub async fn locks(&self) -> Result<Vec<LockStatus>, AppError> {
+        let now = chrono::Utc::now();
+        Ok(self
+            .store
+            .list()
+            .await?
+            .into_iter()
+            .map(|lock| LockStatus {
+                held: lock.is_held(now),
+                lock,
+            })
+            .collect())
+    }
+
+    /// The conflict reported when the lock `name` is held
+    async fn held(&self, name: &str) -> AppError {
+        match self.store.get(name).await {
+            Ok(Some(lock)) => AppError::Conflict(format!(
+                "Lock {} is held by {} until {}",
+                name, lock.holder, lock.expires_at
+            )),
+            _ => AppError::Conflict(format!("Lock {} is held", name)),
+        }
+    }
+}
+
+#[async_trait]
+impl TaskLocks for LockService {
+    async fn try_lock(&self, task: &str) -> Result<Option<TaskLockGuard>, TaskError> {
+        let name = format!("{SCHEDULE_LOCK_PREFIX}{task}");
+        let guard = LockService::try_lock(self, &name, DEFAULT_LOCK_TTL).await?;
+        Ok(guard.map(|guard| Box::new(guard) as TaskLockGuard))
+    }
+}
+
+/// A held lock, renewed in the background until it is released or dropped
+pub struct LockGuard {
+    store: Arc<dyn LockStore>,
+    lock: LockRecord,
+    held: watch::Receiver<bool>,
+    renewer: JoinHandle<()>,
+    released: bool,
+}
+
+impl LockGuard {
+    /// Start renewing `lock`
+    fn hold(store: Arc<dyn LockStore>, lock: LockRecord, ttl: Duration) -> Self {
+        let (held_tx, held) = watch::channel(true);
+        let renewer = {
+            let store = store.clone();
+            let lock = lock.clone();
+            tokio::spawn(async move {
+                let period = (ttl / 3).max(Duration::from_millis(10));
+                let mut renewed = Instant::now();
+                loop {
+                    tokio::time::sleep(period).await;
+                    match store.renew(&lock.name, &lock.holder, lock.token, ttl).await {
+                        Ok(true) => renewed = Instant::now(),
+                        Ok(false) => {
+                            warn!("Lost lock {} with token {}", lock.name, lock.token);
+                            break;
+                        }
+                        Err(e) if renewed.elapsed() >= ttl => {
+                            warn!("Lost lock {} after failing to renew it: {}", lock.name, e);
+                            break;
+                        }
+                        Err(e) => warn!("Failed to renew lock {}: {}", lock.name, e),
+                    }
+                }
+                let _ = held_tx.send(false);
+            })
+        };
+        Self {
+            store,
+            lock,
+            held,
+            renewer,
+            released: false,
+        }
+    }
+
+    /// Name of the lock
+    pub fn name(&self) -> &str {
+        &self.lock.name
+    }
+
+    /// Fencing token of this acquisition
+    pub fn token(&self) -> i64 {
+        self.lock.token
+    }
+
+    /// Whether the lock is still held, as far as the last renewal knows
+    pub fn is_held(&self) -> bool {
+        *self.held.borrow()
+    }
+
+    /// Wait until the lock is lost
+    pub async fn lost(&mut self) {
+        let _ = self.held.wait_for(|held| !held).await;
+    }
+
+    /// Stop renewing the lock and free it
+    pub async fn release(mut self) {
+        self.renewer.abort();
+        self.released = true;
+        if let Err(e) = self.store.release(&self.lock.name, &self.lock.holder, self.lock.token).await {
+            warn!("Failed to release lock {}: {}", self.lock.name, e);
+        }
+    }
+}
+
+impl Drop for LockGuard {
+    /// Stop renewing the lock and free it in the background; a lock that
+    /// cannot be freed expires
+    fn drop(&mut self) {
+        if self.released || !self.is_held() {
+            return;
+        }
+        self.renewer.abort();
+        if let Ok(runtime) = tokio::runtime::Handle::try_current() {
+            let store = self.store.clone();
+            let lock = self.lock.clone();
+            runtime.spawn(async move {
+                if let Err(e) = store.release(&lock.name, &lock.holder, lock.token).await {
+                    warn!("Failed to release lock {}: {}", lock.name, e);
+                }
+            });
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn service(store: &Arc<MemoryLockStore>, instance: &str) -> LockService {
+        LockService::new(store.clone(), instance)
+    }
+
+    #[tokio::test]
+    async fn test_locks_exclude_other_instances_until_released() {
+        let store = Arc::new(MemoryLockStore::new());
+        let first = service(&store, "a");
+        let second = service(&store, "b");
+
+        let guard = first.try_lock(GC_LOCK, DEFAULT_LOCK_TTL).await.unwrap().unwrap();
+        assert_eq!(guard.token(), 1);
+        assert!(second.try_lock(GC_LOCK, DEFAULT_LOCK_TTL).await.unwrap().is_none());
+        assert!(first.try_lock(GC_LOCK, DEFAULT_LOCK_TTL).await.unwrap().is_none());
+        let held = second.with_lock(GC_LOCK, DEFAULT_LOCK_TTL, async { Ok(()) }).await;
+        assert!(matches!(held, Err(AppError::Conflict(message)) if message.contains("held by a/")));
+        assert!(first.is_current(GC_LOCK, 1).await.unwrap());
+
+        let locks = second.locks().await.unwrap();
+        assert_eq!(locks.len(), 1);
+        assert!(locks[0].held);
+
+        guard.release().await;
+        assert!(!first.is_current(GC_LOCK, 1).await.unwrap());
+        let ran = second
+            .with_lock(GC_LOCK, DEFAULT_LOCK_TTL, async { Ok("collected") })
+            .await
+            .unwrap();
+        assert_eq!(ran, "collected");
+
+        // Dropping a guard frees its lock too
+        let guard = second.lock(GC_LOCK, DEFAULT_LOCK_TTL, Duration::from_secs(1)).await.unwrap();
+        assert_eq!(guard.token(), 3);
+        drop(guard);
+        let guard = first.lock(GC_LOCK, DEFAULT_LOCK_TTL, Duration::from_secs(1)).await.unwrap();
+        assert_eq!(guard.token(), 4);
+    }
+
+    #[tokio::test]
+    async fn test_work_stops_when_its_lock_is_lost() {
+        let store = Arc::new(MemoryLockStore::new());
+        let locks = service(&store, "a");
+        let work = locks.with_lock("db.maintenance", Duration::from_millis(60), async {
+            // Another instance took over after the lease was freed by force
+            let lock = store.get("db.maintenance").await?.unwrap();
+            store.release(&lock.name, &lock.holder, lock.token).await?;
+            store.acquire("db.maintenance", "b/1", DEFAULT_LOCK_TTL).await?;
+            std::future::pending::<Result<(), AppError>>().await
+        });
+
+        let result = tokio::time::timeout(Duration::from_secs(5), work).await.unwrap();
+        assert!(matches!(result, Err(AppError::Conflict(message)) if message.contains("Lost lock")));
+        assert_eq!(store.get("db.maintenance").await.unwrap().unwrap().holder, "b/1");
+    }
+}
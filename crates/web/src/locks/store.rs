@@ -0,0 +1,308 @@
+//! Lock storage backends.
+
+use std::collections::BTreeMap;
+use std::time::Duration;
+
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use sqlx::sqlite::SqliteRow;
+use sqlx::{Row, SqlitePool};
+use tokio::sync::{Mutex, OnceCell};
+
+use crate::api::error::AppError;
+use crate::db::{timestamp, InstrumentedPool};
+
+/// A named lock, held by one holder until it expires
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct LockRecord {
+    /// Lock name
+    pub name: String,
+    /// Holder that took the lock last
+    pub holder: String,
+    /// Fencing token, incremented every time the lock is taken
+    pub token: i64,
+    /// When the holder took the lock
+    pub acquired_at: DateTime<Utc>,
+    /// When the lock expires unless renewed
+    pub expires_at: DateTime<Utc>,
+}
+
+impl LockRecord {
+    /// Whether the lock is held at `now`
+    pub fn is_held(&self, now: DateTime<Utc>) -> bool {
+        self.expires_at > now
+    }
+}
+
+/// Storage for distributed locks
+#[async_trait]
+pub trait LockStore: Send + Sync {
+    /// Take the lock `name` for `holder` until `ttl` from now, if it is free
+    /// or expired
+    ///
+    /// Every acquisition gets a larger token than the one before. Returns
+    /// the lock as stored afterwards, which may belong to another holder.
+    async fn acquire(&self, name: &str, holder: &str, ttl: Duration) -> Result<LockRecord, AppError>;
+
+    /// Extend the lock until `ttl` from now if `holder` still holds it under
+    /// `token`, returning whether it does
+    async fn renew(&self, name: &str, holder: &str, token: i64, ttl: Duration) -> Result<bool, AppError>;
+
+    /// Free the lock if `holder` holds it under `token`
+    async fn release(&self, name: &str, holder: &str, token: i64) -> Result<(), AppError>;
+
+    /// The lock `name`, held or not, if it was ever taken
+    async fn get(&self, name: &str) -> Result<Option<LockRecord>, AppError>;
+
+    /// Every lock ever taken, by name
+    async fn list(&self) -> Result<Vec<LockRecord>, AppError>;
+}
+
+/// Converts a lock duration into a chrono duration
+fn lock_ttl(ttl: Duration) -> chrono::Duration {
+    chrono::Duration::from_std(ttl).unwrap_or_else(|_| chrono::Duration::seconds(30))
+}
+
+/// Lock store backed by the `distributed_locks` table
+///
+/// The table is created on first use rather than by a migration, since
+/// migrations themselves run under a lock.
+pub struct SqlLockStore {
+    pool: InstrumentedPool,
+    created: OnceCell<()>,
+}
+
+impl SqlLockStore {
+    /// Create a new SqlLockStore
+    pub fn new(pool: SqlitePool) -> Self {
+        Self {
+            pool: pool.into(),
+            created: OnceCell::new(),
+        }
+    }
+
+    /// Create the table unless it exists
+    async fn ensure_table(&self) -> Result<(), AppError> {
+        self.created
+            .get_or_try_init(|| async {
+                sqlx::query(
+                    "CREATE TABLE IF NOT EXISTS distributed_locks (
+                         name TEXT PRIMARY KEY NOT NULL,
+                         holder TEXT NOT NULL,
+                         token INTEGER NOT NULL,
+                         acquired_at INTEGER NOT NULL,
+                         expires_at INTEGER NOT NULL
+                     )",
+                )
+                .execute(&self.pool)
+                .await?;
+                Ok::<_, AppError>(())
+            })
+            .await?;
+        Ok(())
+    }
+
+    /// Read a lock from its row
+    fn record(row: &SqliteRow) -> Result<LockRecord, AppError> {
+        Ok(LockRecord {
+            name: row.try_get("name")?,
+            holder: row.try_get("holder")?,
+            token: row.try_get("token")?,
+            acquired_at: timestamp(row.try_get("acquired_at")?)?,
+            expires_at: timestamp(row.try_get("expires_at")?)?,
+        })
+    }
+}
+
+#[async_trait]
+impl LockStore for SqlLockStore {
+    async fn acquire(&self, name: &str, holder: &str, ttl: Duration) -> Result<LockRecord, AppError> {
+        self.ensure_table().await?;
+        let now = Utc::now();
+        let expires_at = now + lock_ttl(ttl);
+
+        // A single upsert keeps acquisition atomic across instances
+        sqlx::query(
+            "INSERT INTO distributed_locks (name, holder, token, acquired_at, expires_at) VALUES (?, ?, 1, ?, ?)
+             ON CONFLICT(name) DO UPDATE SET
+                 holder = excluded.holder,
+                 token = token + 1,
+                 acquired_at = excluded.acquired_at,
+                 expires_at = excluded.expires_at
+             WHERE expires_at <= excluded.acquired_at",
+        )
+        .bind(name)
+        .bind(holder)
+        .bind(now.timestamp_millis())
+        .bind(expires_at.timestamp_millis())
+        .execute(&self.pool)
+        .await?;
+
+        let row = sqlx::query("SELECT * FROM distributed_locks WHERE name = ?")
+            .bind(name)
+            .fetch_one(&self.pool)
+            .await?;
+        Self::record(&row)
+    }
+
+    async fn renew(&self, name: &str, holder: &str, token: i64, ttl: Duration) -> Result<bool, AppError> {
+        self.ensure_table().await?;
+        let now = Utc::now();
+        let renewed = sqlx::query(
+            "UPDATE distributed_locks SET expires_at = ?
+             WHERE name = ? AND holder = ? AND token = ? AND expires_at > ?",
+        )
+        .bind((now + lock_ttl(ttl)).timestamp_millis())
+        .bind(name)
+        .bind(holder)
+        .bind(token)
+        .bind(now.timestamp_millis())
+        .execute(&self.pool)
+        .await?
+        .rows_affected();
+        Ok(renewed > 0)
+    }
+
+    async fn release(&self, name: &str, holder: &str, token: i64) -> Result<(), AppError> {
+        self.ensure_table().await?;
+        sqlx::query("UPDATE distributed_locks SET expires_at = 0 WHERE name = ? AND holder = ? AND token = ?")
+            .bind(name)
+            .bind(holder)
+            .bind(token)
+            .execute(&self.pool)
+            .await?;
+        Ok(())
+    }
+
+    async fn get(&self, name: &str) -> Result<Option<LockRecord>, AppError> {
+        self.ensure_table().await?;
+        sqlx::query("SELECT * FROM distributed_locks WHERE name = ?")
+            .bind(name)
+            .fetch_optional(&self.pool)
+            .await?
+            .as_ref()
+            .map(Self::record)
+            .transpose()
+    }
+
+    async fn list(&self) -> Result<Vec<LockRecord>, AppError> {
+        self.ensure_table().await?;
+        sqlx::query("SELECT * FROM distributed_locks ORDER BY name")
+            .fetch_all(&self.pool)
+            .await?
+            .iter()
+            .map(Self::record)
+            .collect()
+    }
+}
+
+/// In-process lock store for single-instance deployments and tests
+#[derive(Default)]
+pub struct MemoryLockStore {
+    locks: Mutex<BTreeMap<String, LockRecord>>,
+}
+
+impl MemoryLockStore {
+    /// Create a new MemoryLockStore
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+#[async_trait]
+impl LockStore for MemoryLockStore {
+    async fn acquire(&self, name: &str, holder: &str, ttl: Duration) -> Result<LockRecord, AppError> {
+        let now = Utc::now();
+        let expires_at = now + lock_ttl(ttl);
+        let mut locks = self.locks.lock().await;
+
+        match locks.get_mut(name) {
+            Some(lock) if lock.is_held(now) => {}
+            Some(lock) => {
+                lock.holder = holder.to_string();
+                lock.token += 1;
+                lock.acquired_at = now;
+                lock.expires_at = expires_at;
+            }
+            None => {
+                locks.insert(
+                    name.to_string(),
+                    LockRecord {
+                        name: name.to_string(),
+                        holder: holder.to_string(),
+                        token: 1,
+                        acquired_at: now,
+                        expires_at,
+                    },
+                );
+            }
+        }
+        Ok(locks[name].clone())
+    }
+
+    async fn renew(&self, name: &str, holder: &str, token: i64, ttl: Duration) -> Result<bool, AppError> {
+        let now = Utc::now();
+        let mut locks = self.locks.lock().await;
+        match locks.get_mut(name) {
+            Some(lock) if lock.holder == holder && lock.token == token && lock.is_held(now) => {
+                lock.expires_at = now + lock_ttl(ttl);
+                Ok(true)
+            }
+            _ => Ok(false),
+        }
+    }
+
+    async fn release(&self, name: &str, holder: &str, token: i64) -> Result<(), AppError> {
+        if let Some(lock) = self.locks.lock().await.get_mut(name) {
+            if lock.holder == holder && lock.token == token {
+                lock.expires_at = DateTime::<Utc>::UNIX_EPOCH;
+            }
+        }
+        Ok(())
+    }
+
+    async fn get(&self, name: &str) -> Result<Option<LockRecord>, AppError> {
+        Ok(self.locks.lock().await.get(name).cloned())
+    }
+
+    async fn list(&self) -> Result<Vec<LockRecord>, AppError> {
+        Ok(self.locks.lock().await.values().cloned().collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use sqlx::sqlite::SqlitePoolOptions;
+
+    #[tokio::test]
+    async fn test_sql_lock_tokens_grow_with_every_acquisition() {
+        let pool = SqlitePoolOptions::new()
+            .max_connections(1)
+            .connect("sqlite::memory:")
+            .await
+            .unwrap();
+        let store = SqlLockStore::new(pool);
+        let ttl = Duration::from_secs(30);
+
+        let lock = store.acquire("gc", "a", ttl).await.unwrap();
+        assert_eq!((lock.holder.as_str(), lock.token), ("a", 1));
+        let lock = store.acquire("gc", "b", ttl).await.unwrap();
+        assert_eq!((lock.holder.as_str(), lock.token), ("a", 1));
+        assert!(store.renew("gc", "a", 1, ttl).await.unwrap());
+        assert!(!store.renew("gc", "b", 1, ttl).await.unwrap());
+
+        store.release("gc", "a", 1).await.unwrap();
+        assert!(!store.renew("gc", "a", 1, ttl).await.unwrap());
+        let lock = store.acquire("gc", "b", ttl).await.unwrap();
+        assert_eq!((lock.holder.as_str(), lock.token), ("b", 2));
+
+        // A stale holder cannot release its successor's lock
+        store.release("gc", "a", 1).await.unwrap();
+        let locks = store.list().await.unwrap();
+        assert_eq!(locks.len(), 1);
+        assert!(locks[0].is_held(Utc::now()));
+        assert_eq!(store.get("gc").await.unwrap().unwrap().holder, "b");
+    }
+}
@@ -0,0 +1,273 @@
+//! Client side of log aggregation.
+//!
+//! A [`LogForwarder`] queues events and posts them to the server's
+//! `/api/logs` in batches, every `batch_size` events and at least every
+//! `flush_interval`. Forwarding never blocks the code that logs: when the
+//! queue is full, events are dropped and counted. A failed batch is dropped
+//! too, so an unreachable server costs nothing but the lost events.
+//!
+//! [`ForwardingLayer`] forwards `tracing` events, as the agent logs them;
+//! clients using the `log` crate call [`LogForwarder::forward`] themselves.
+
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+use serde_json::{Map, Value};
+use tokio::sync::{mpsc, oneshot};
+use tracing::field::{Field, Visit};
+use tracing::{debug, Event, Subscriber};
+use tracing_subscriber::layer::{Context, Layer};
+
+use super::{LogLevel, NewLogEvent};
+use crate::api::logs::LogBatch;
+
+/// Header carrying the agent registration token, for agents without a user
+pub const AGENT_TOKEN_HEADER: &str = "x-squirrel-agent-token";
+
+/// Targets whose events are never forwarded, so posting a batch does not
+/// log more events to forward
+const IGNORED_TARGETS: [&str; 4] = ["squirrel_web::logs", "reqwest", "hyper", "h2"];
+
+/// Configuration of a log forwarder
+#[derive(Debug, Clone)]
+pub struct LogForwarderConfig {
+    /// Base URL of the server, e.g. `http://127.0.0.1:3000`
+    pub server_url: String,
+    /// Access token of the forwarding user
+    pub token: Option<String>,
+    /// Registration token, for agents forwarding without a user
+    pub agent_token: Option<String>,
+    /// Machine or agent the events come from
+    pub source: Option<String>,
+    /// Least severe level forwarded
+    pub min_level: LogLevel,
+    /// Events posted at once
+    pub batch_size: usize,
+    /// Longest time an event waits before it is posted
+    pub flush_interval: Duration,
+    /// Events queued before new ones are dropped
+    pub queue_capacity: usize,
+}
+
+impl Default for LogForwarderConfig {
+    fn default() -> Self {
+        Self {
+            server_url: "http://127.0.0.1:3000".to_string(),
+            token: None,
+            agent_token: None,
+            source: None,
+            min_level: LogLevel::Info,
+            batch_size: 100,
+            flush_interval: Duration::from_secs(2),
+            queue_capacity: 10_000,
+        }
+    }
+}
+
+/// What the forwarding task is asked to do
+enum Message {
+    /// Queue an event
+    Event(NewLogEvent),
+    /// Post the queued events, then acknowledge
+    Flush(oneshot::Sender<()>),
+}
+
+/// Queues log events and posts them to the server in batches
+#[derive(Clone)]
+pub struct LogForwarder {
+    sender: mpsc::Sender<Message>,
+    min_level: LogLevel,
+    source: Option<String>,
+    dropped: Arc<AtomicU64>,
+}
+
+impl LogForwarder {
+    /// Start forwarding with `config`; needs a Tokio runtime
+    pub fn spawn(config: LogForwarderConfig) -> Self {
+        let (sender, receiver) = mpsc::channel(config.queue_capacity.max(1));
+        let forwarder = Self {
+            sender,
+            min_level: config.min_level,
+            source: config.source.clone(),
+            dropped: Arc::new(AtomicU64::new(0)),
+        };
+        tokio::spawn(run(config, receiver));
+        forwarder
+    }
+
+    /// Queue `event`, unless it is below the forwarded level or the queue is full
+    pub fn forward(&self, mut event: NewLogEvent) {
+        if event.level < self.min_level {
+            return;
+        }
+        if event.source.is_none() {
+            event.source = self.source.clone();
+        }
+        if self.sender.try_send(Message::Event(event)).is_err() {
+            self.dropped.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+
+    /// Number of events dropped because the queue was full
+    pub fn dropped(&self) -> u64 {
+        self.dropped.load(Ordering::Relaxed)
+    }
+
+    /// Post the queued events, waiting at most `timeout`; false if they
+    /// were not posted in time
+    pub async fn flush(&self, timeout: Duration) -> bool {
+        let (ack, done) = oneshot::channel();
+        if self.sender.send(Message::Flush(ack)).await.is_err() {
+            return false;
+        }
+        matches!(tokio::time::timeout(timeout, done).await, Ok(Ok(())))
+    }
+
+    /// A `tracing` layer forwarding events as coming from `component`
+    pub fn layer(&self, component: impl Into<String>) -> ForwardingLayer {
+        ForwardingLayer {
+            forwarder: self.clone(),
+            component: component.into(),
+        }
+    }
+}
+
+/// Queue events until a batch is full or the interval passes, then post them
+async fn run(config: LogForwarderConfig, mut receiver: mpsc::Receiver<Message>) {
+    let client = reqwest::Client::new();
+    let url = format!("{}/api/logs", config.server_url.trim_end_matches('/'));
+    let mut batch = Vec::new();
+    let mut ticker = tokio::time::interval(config.flush_interval);
+    ticker.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+    loop {
+        tokio::select! {
+            message = receiver.recv() => match message {
+                Some(Message::Event(event)) => {
+                    batch.push(event);
+                    if batch.len() >= config.batch_size {
+                        post(&client, &url, &config, &mut batch).await;
+                    }
+                }
+                Some(Message::Flush(ack)) => {
+                    post(&client, &url, &config, &mut batch).await;
+                    let _ = ack.send(());
+                }
+                None => {
+                    post(&client, &url, &config, &mut batch).await;
+                    break;
+                }
+            },
+            _ = ticker.tick() => post(&client, &url, &config, &mut batch).await,
+        }
+    }
+}
+
+/// Post the queued events, dropping them if the server does not take them
+async fn post(client: &reqwest::Client, url: &str, config: &LogForwarderConfig, batch: &mut Vec<NewLogEvent>) {
+    if batch.is_empty() {
+        return;
+    }
+    let body = LogBatch {
+        events: std::mem::take(batch),
+    };
+    let count = body.events.len();
+    let mut request = client.post(url).json(&body);
+    if let Some(token) = &config.token {
+        request = request.bearer_auth(token);
+    }
+    if let Some(token) = &config.agent_token {
+        request = request.header(AGENT_TOKEN_HEADER, token);
+    }
+    match request.send().await {
+        Ok(response) if response.status().is_success() => {}
+        Ok(response) => debug!("Server refused {} log events: {}", count, response.status()),
+        Err(e) => debug!("Failed to forward {} log events: {}", count, e),
+    }
+}
+
+/// Forwards `tracing` events through a [`LogForwarder`]
+///
+/// The `message` field becomes the message and a `run_id` field the run;
+/// other fields, and the target, are kept as structured fields.
+pub struct ForwardingLayer {
+    forwarder: LogForwarder,
+    component: String,
+}
+
+/// Gathers the fields of a `tracing` event
+#[derive(Default)]
+struct FieldVisitor {
+    message: String,
+    run_id: Option<String>,
+    fields: Map<String, Value>,
+}
+
+impl FieldVisitor {
+    fn record(&mut self, field: &Field, value: Value) {
+        match (field.name(), value) {
+            ("message", Value::String(message)) => self.message = message,
+            ("run_id", Value::String(run)) => self.run_id = Some(run),
+            (name, value) => {
+                self.fields.insert(name.to_string(), value);
+            }
+        }
+    }
+}
+
+impl Visit for FieldVisitor {
+    fn record_str(&mut self, field: &Field, value: &str) {
+        self.record(field, Value::from(value));
+    }
+
+    fn record_i64(&mut self, field: &Field, value: i64) {
+        self.record(field, Value::from(value));
+    }
+
+    fn record_u64(&mut self, field: &Field, value: u64) {
+        self.record(field, Value::from(value));
+    }
+
+    fn record_f64(&mut self, field: &Field, value: f64) {
+        self.record(field, Value::from(value));
+    }
+
+    fn record_bool(&mut self, field: &Field, value: bool) {
+        self.record(field, Value::from(value));
+    }
+
+    fn record_debug(&mut self, field: &Field, value: &dyn std::fmt::Debug) {
+        self.record(field, Value::from(format!("{:?}", value)));
+    }
+}
+
+/// The level of a `tracing` event
+fn level(level: &tracing::Level) -> LogLevel {
+    match *level {
+        tracing::Level::TRACE => LogLevel::Trace,
+        tracing::Level::DEBUG => LogLevel::Debug,
+        tracing::Level::INFO => LogLevel::Info,
+        tracing::Level::WARN => LogLevel::Warn,
+        tracing::Level::ERROR => LogLevel::Error,
+    }
+}
+
+impl<S: Subscriber> Layer<S> for ForwardingLayer {
+    fn on_event(&self, event: &Event<'_>, _ctx: Context<'_, S>) {
+        let metadata = event.metadata();
+        let level = level(metadata.level());
+        if level < self.forwarder.min_level
+            || IGNORED_TARGETS.iter().any(|target| metadata.target().starts_with(target))
+        {
+            return;
+        }
+        let mut visitor = FieldVisitor::default();
+        event.record(&mut visitor);
+        visitor.fields.insert("target".to_string(), Value::from(metadata.target()));
+        self.forwarder.forward(NewLogEvent {
+            run_id: visitor.run_id,
+            fields: visitor.fields,
+            ..NewLogEvent::new(level, self.component.clone(), visitor.message)
+        });
+    }
+}
@@ -0,0 +1,502 @@
+//! Aggregated log events of agents, plugins and the CLI.
+//!
+//! Clients forward structured events in batches to `POST /api/logs`, most
+//! conveniently through a [`LogForwarder`]. Each event names the component
+//! that emitted it, such as `agent`, `cli` or `plugin:blast`, and may carry
+//! the run it belongs to, so a distributed run can be followed across
+//! machines. Events are searchable by level, component, run, time and text.
+//!
+//! [`SqlLogStore`] keeps events in the `log_events` table, from which
+//! database maintenance deletes them after `maintenance.log_retention_days`.
+//! [`MemoryLogStore`] keeps the most recent ones in memory for the mock
+//! database mode.
+
+pub mod forwarder;
+
+pub use forwarder::{ForwardingLayer, LogForwarder, LogForwarderConfig};
+
+use std::collections::VecDeque;
+use std::fmt;
+use std::str::FromStr;
+use std::sync::Arc;
+
+use async_trait::async_trait;
+use chrono::{DateTime, TimeZone, Utc};
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+use sqlx::{Row, SqlitePool};
+use squirrel_core::redaction::Redactor;
+use tokio::sync::Mutex;
+
+use crate::api::error::AppError;
+use crate::config::LogsConfig;
+use crate::db::{InstrumentedPool, ReplicatedPool};
+
+/// Events returned by a search unless asked otherwise
+pub const DEFAULT_SEARCH_LIMIT: usize = 100;
+
+/// Most events a search returns
+pub const MAX_SEARCH_LIMIT: usize = 1000;
+
+/// Severity of a log event, from least to most severe
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum LogLevel {
+    /// Tracing detail
+    Trace,
+    /// Debugging detail
+    Debug,
+    /// Normal operation
+    Info,
+    /// Something unexpected that was handled
+    #[serde(alias = "warning")]
+    Warn,
+    /// A failure
+    Error,
+}
+
+impl LogLevel {
+    /// Every level, from least to most severe
+    pub const ALL: [Self; 5] = [Self::Trace, Self::Debug, Self::Info, Self::Warn, Self::Error];
+
+    /// The level's name, e.g. `warn`
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Trace => "trace",
+            Self::Debug => "debug",
+            Self::Info => "info",
+            Self::Warn => "warn",
+            Self::Error => "error",
+        }
+    }
+
+    /// Rank of the level as stored, from 0 for trace to 4 for error
+    fn rank(self) -> i64 {
+        self as i64
+    }
+
+    /// The level of a stored rank
+    fn from_rank(rank: i64) -> Self {
+        usize::try_from(rank)
+            .ok()
+            .and_then(|rank| Self::ALL.get(rank).copied())
+            .unwrap_or(Self::Info)
+    }
+}
+
+impl FromStr for LogLevel {
+    type Err = AppError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_lowercase().as_str() {
+            "trace" => Ok(Self::Trace),
+            "debug" => Ok(Self::Debug),
+            "info" => Ok(Self::Info),
+            "warn" | "warning" => Ok(Self::Warn),
+            "error" => Ok(Self::Error),
+            _ => Err(AppError::InvalidRequest(format!(
+                "Unknown log level '{}'; expected trace, debug, info, warn or error",
+                s
+            ))),
+        }
+    }
+}
+
+impl fmt::Display for LogLevel {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// A log event as a client forwards it
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct NewLogEvent {
+    /// When the event was emitted; when it arrives if not given
+    #[serde(default = "Utc::now")]
+    pub timestamp: DateTime<Utc>,
+    /// Severity
+    pub level: LogLevel,
+    /// Component that emitted it, e.g. `agent` or `plugin:blast`
+    pub component: String,
+    /// Machine or agent it came from
+    #[serde(default)]
+    pub source: Option<String>,
+    /// Job, workflow run or command execution it belongs to
+    #[serde(default)]
+    pub run_id: Option<String>,
+    /// The message
+    pub message: String,
+    /// Further structured fields
+    #[serde(default)]
+    pub fields: Map<String, Value>,
+}
+
+impl NewLogEvent {
+    /// Create an event emitted now, without a source, run or fields
+    pub fn new(level: LogLevel, component: impl Into<String>, message: impl Into<String>) -> Self {
+        Self {
+            timestamp: Utc::now(),
+            level,
+            component: component.into(),
+            source: None,
+            run_id: None,
+            message: message.into(),
+            fields: Map::new(),
+        }
+    }
+}
+
+/// A stored log event
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct LogEvent {
+    /// ID of the event, increasing in the order events arrived
+    pub id: i64,
+    /// The event as forwarded
+    #[serde(flatten)]
+    pub event: NewLogEvent,
+    /// User or agent that forwarded it
+    pub submitted_by: String,
+    /// When it arrived
+    pub received_at: DateTime<Utc>,
+}
+
+/// Which log events a search returns
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct LogQuery {
+    /// Only events of this level or more severe
+    pub min_level: Option<LogLevel>,
+    /// Only events of this component or its subcomponents, e.g. `plugin`
+    /// matches `plugin:blast`
+    pub component: Option<String>,
+    /// Only events from this source
+    pub source: Option<String>,
+    /// Only events of this run
+    pub run_id: Option<String>,
+    /// Only events emitted from this time on
+    pub from: Option<DateTime<Utc>>,
+    /// Only events emitted before this time
+    pub to: Option<DateTime<Utc>>,
+    /// Only events whose message contains this text, ignoring case
+    pub text: Option<String>,
+    /// Only events forwarded by this user or agent
+    pub submitted_by: Option<String>,
+    /// Most events returned; [`DEFAULT_SEARCH_LIMIT`] if not given
+    pub limit: Option<usize>,
+}
+
+impl LogQuery {
+    /// Number of events returned
+    fn limit(&self) -> usize {
+        self.limit.unwrap_or(DEFAULT_SEARCH_LIMIT).clamp(1, MAX_SEARCH_LIMIT)
+    }
+
+    /// Whether `event` is selected
+    fn matches(&self, event: &LogEvent) -> bool {
+        let new = &event.event;
+        self.min_level.is_none_or(|level| new.level >= level)
+            && self.component.as_deref().is_none_or(|component| {
+                new.component == component
+                    || new
+                        .component
+                        .strip_prefix(component)
+                        .is_some_and(|rest| rest.starts_with(':'))
+            })
+            && self.source.as_deref().is_none_or(|source| new.source.as_deref() == Some(source))
+            && self.run_id.as_deref().is_none_or(|run| new.run_id.as_deref() == Some(run))
+            && self.from.is_none_or(|from| new.timestamp >= from)
+            && self.to.is_none_or(|to| new.timestamp < to)
+            && self
+                .text
+                .as_deref()
+                .is_none_or(|text| new.message.to_lowercase().contains(&text.to_lowercase()))
+            && self.submitted_by.as_deref().is_none_or(|user| event.submitted_by == user)
+    }
+}
+
+/// Check a forwarded batch against `config`, redacting messages and fields
+/// with `redactor` and shortening long messages
+pub fn prepare_batch(
+    config: &LogsConfig,
+    redactor: Option<&Redactor>,
+    mut events: Vec<NewLogEvent>,
+) -> Result<Vec<NewLogEvent>, AppError> {
+    if events.len() > config.max_batch {
+        return Err(AppError::InvalidRequest(format!(
+            "A batch holds at most {} events, not {}",
+            config.max_batch,
+            events.len()
+        )));
+    }
+    for event in &mut events {
+        if event.component.trim().is_empty() {
+            return Err(AppError::InvalidRequest("Every event needs a component".to_string()));
+        }
+        if let Some(redactor) = redactor {
+            event.message = redactor.redact_text(&event.message);
+            let mut fields = Value::Object(std::mem::take(&mut event.fields));
+            redactor.redact_value(&mut fields);
+            if let Value::Object(fields) = fields {
+                event.fields = fields;
+            }
+        }
+        if event.message.len() > config.max_message_bytes {
+            let mut end = config.max_message_bytes;
+            while !event.message.is_char_boundary(end) {
+                end -= 1;
+            }
+            event.message.truncate(end);
+        }
+    }
+    Ok(events)
+}
+
+/// Storage for log events
+#[async_trait]
+pub trait LogStore: Send + Sync {
+    /// Store a batch forwarded by `submitted_by`, returning how many were stored
+    async fn append(&self, submitted_by: &str, events: Vec<NewLogEvent>) -> Result<usize, AppError>;
+
+    /// The events matching `query`, most recent first
+    async fn search(&self, query: &LogQuery) -> Result<Vec<LogEvent>, AppError>;
+}
+
+/// A time stored in milliseconds since the epoch
+fn from_millis(millis: i64) -> DateTime<Utc> {
+    Utc.timestamp_millis_opt(millis).single().unwrap_or_default()
+}
+
+/// Log store backed by the `log_events` table
+pub struct SqlLogStore {
+    pool: InstrumentedPool,
+    replicas: Option<Arc<ReplicatedPool>>,
+}
+
+impl SqlLogStore {
+    /// Create a new SqlLogStore
+    pub fn new(pool: SqlitePool) -> Self {
+        Self {
+            pool: pool.into(),
+            replicas: None,
+        }
+    }
+
+    /// Search the replicas of `replicas` where they are close enough behind
+    pub fn with_replicas(mut self, replicas: Arc<ReplicatedPool>) -> Self {
+        self.replicas = Some(replicas);
+        self
+    }
+}
+
+#[async_trait]
+impl LogStore for SqlLogStore {
+    async fn append(&self, submitted_by: &str, events: Vec<NewLogEvent>) -> Result<usize, AppError> {
+        let received_at = Utc::now().timestamp_millis();
+        let mut tx = self.pool.begin().await?;
+        for event in &events {
+            let fields = serde_json::to_string(&event.fields)
+                .map_err(|e| AppError::InvalidRequest(format!("Invalid log fields: {}", e)))?;
+            sqlx::query(
+                "INSERT INTO log_events (timestamp, level, component, source, run_id, message, fields, submitted_by, received_at)
+                 VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?)",
+            )
+            .bind(event.timestamp.timestamp_millis())
+            .bind(event.level.rank())
+            .bind(&event.component)
+            .bind(&event.source)
+            .bind(&event.run_id)
+            .bind(&event.message)
+            .bind(fields)
+            .bind(submitted_by)
+            .bind(received_at)
+            .execute(&mut *tx)
+            .await?;
+        }
+        tx.commit().await?;
+        Ok(events.len())
+    }
+
+    async fn search(&self, query: &LogQuery) -> Result<Vec<LogEvent>, AppError> {
+        let limit = i64::try_from(query.limit()).unwrap_or(i64::MAX);
+        let rows = sqlx::query(
+            "SELECT id, timestamp, level, component, source, run_id, message, fields, submitted_by, received_at
+             FROM log_events
+             WHERE (?1 IS NULL OR level >= ?1)
+               AND (?2 IS NULL OR component = ?2 OR substr(component, 1, length(?2) + 1) = ?2 || ':')
+               AND (?3 IS NULL OR source = ?3)
+               AND (?4 IS NULL OR run_id = ?4)
+               AND (?5 IS NULL OR timestamp >= ?5)
+               AND (?6 IS NULL OR timestamp < ?6)
+               AND (?7 IS NULL OR instr(lower(message), lower(?7)) > 0)
+               AND (?8 IS NULL OR submitted_by = ?8)
+             ORDER BY timestamp DESC, id DESC
+             LIMIT ?9",
+        )
+        .bind(query.min_level.map(LogLevel::rank))
+        .bind(&query.component)
+        .bind(&query.source)
+        .bind(&query.run_id)
+        .bind(query.from.map(|from| from.timestamp_millis()))
+        .bind(query.to.map(|to| to.timestamp_millis()))
+        .bind(&query.text)
+        .bind(&query.submitted_by)
+        .bind(limit)
+        .fetch_all(self.replicas.as_ref().map_or(&self.pool, |replicas| replicas.reader()))
+        .await?;
+
+        rows.iter()
+            .map(|row| {
+                let fields: String = row.try_get("fields")?;
+                Ok(LogEvent {
+                    id: row.try_get("id")?,
+                    event: NewLogEvent {
+                        timestamp: from_millis(row.try_get("timestamp")?),
+                        level: LogLevel::from_rank(row.try_get("level")?),
+                        component: row.try_get("component")?,
+                        source: row.try_get("source")?,
+                        run_id: row.try_get("run_id")?,
+                        message: row.try_get("message")?,
+                        fields: serde_json::from_str(&fields).unwrap_or_default(),
+                    },
+                    submitted_by: row.try_get("submitted_by")?,
+                    received_at: from_millis(row.try_get("received_at")?),
+                })
+            })
+            .collect()
+    }
+}
+
+/// Log store keeping the most recent events in memory
+pub struct MemoryLogStore {
+    capacity: usize,
+    events: Mutex<(i64, VecDeque<LogEvent>)>,
+}
+
+impl MemoryLogStore {
+    /// Create a new MemoryLogStore keeping at most `capacity` events
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            capacity: capacity.max(1),
+            events: Mutex::new((0, VecDeque::new())),
+        }
+    }
+}
+
+impl Default for MemoryLogStore {
+    fn default() -> Self {
+        Self::new(LogsConfig::default().memory_capacity)
+    }
+}
+
+#[async_trait]
+impl LogStore for MemoryLogStore {
+    async fn append(&self, submitted_by: &str, events: Vec<NewLogEvent>) -> Result<usize, AppError> {
+        let received_at = Utc::now();
+        let count = events.len();
+        let mut guard = self.events.lock().await;
+        let (last_id, stored) = &mut *guard;
+        for event in events {
+            *last_id += 1;
+            if stored.len() == self.capacity {
+                stored.pop_front();
+            }
+            stored.push_back(LogEvent {
+                id: *last_id,
+                event,
+                submitted_by: submitted_by.to_string(),
+                received_at,
+            });
+        }
+        Ok(count)
+    }
+
+    async fn search(&self, query: &LogQuery) -> Result<Vec<LogEvent>, AppError> {
+        let guard = self.events.lock().await;
+        let mut events: Vec<LogEvent> = guard.1.iter().filter(|event| query.matches(event)).cloned().collect();
+        events.sort_by_key(|event| std::cmp::Reverse((event.event.timestamp, event.id)));
+        events.truncate(query.limit());
+        Ok(events)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::Duration;
+    use crate::test_helpers::migrated_pool;
+
+    fn batch() -> Vec<NewLogEvent> {
+        let start = Utc.with_ymd_and_hms(2026, 10, 17, 9, 0, 0).unwrap();
+        let event = |minutes: i64, level: LogLevel, component: &str, message: &str| NewLogEvent {
+            timestamp: start + Duration::minutes(minutes),
+            run_id: Some("run-1".to_string()),
+            ..NewLogEvent::new(level, component, message)
+        };
+        vec![
+            event(0, LogLevel::Info, "agent", "Task started"),
+            event(1, LogLevel::Warn, "plugin:blast", "Database index is stale"),
+            event(2, LogLevel::Error, "plugin:blast", "BLAST exited with status 2"),
+            event(3, LogLevel::Debug, "cli", "Polling"),
+        ]
+    }
+
+    async fn check_search(store: &dyn LogStore) {
+        assert_eq!(store.append("agent:lab-1", batch()).await.unwrap(), 4);
+        let search = |query: LogQuery| async move {
+            store
+                .search(&query)
+                .await
+                .unwrap()
+                .into_iter()
+                .map(|event| event.event.message)
+                .collect::<Vec<_>>()
+        };
+
+        let plugin = LogQuery {
+            component: Some("plugin".to_string()),
+            ..LogQuery::default()
+        };
+        assert_eq!(search(plugin).await, ["BLAST exited with status 2", "Database index is stale"]);
+        let warnings = LogQuery {
+            min_level: Some(LogLevel::Warn),
+            text: Some("blast".to_string()),
+            ..LogQuery::default()
+        };
+        assert_eq!(search(warnings).await, ["BLAST exited with status 2"]);
+        let window = LogQuery {
+            from: Some(Utc.with_ymd_and_hms(2026, 10, 17, 9, 1, 0).unwrap()),
+            to: Some(Utc.with_ymd_and_hms(2026, 10, 17, 9, 3, 0).unwrap()),
+            limit: Some(1),
+            ..LogQuery::default()
+        };
+        assert_eq!(search(window).await, ["BLAST exited with status 2"]);
+        let other = LogQuery {
+            submitted_by: Some("alice".to_string()),
+            ..LogQuery::default()
+        };
+        assert!(search(other).await.is_empty());
+        assert!(search(LogQuery { component: Some("plug".to_string()), ..LogQuery::default() }).await.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_stores_search_by_level_component_time_and_text() {
+        let pool = migrated_pool().await;
+        check_search(&SqlLogStore::new(pool)).await;
+        check_search(&MemoryLogStore::default()).await;
+
+        let config = LogsConfig {
+            max_batch: 2,
+            max_message_bytes: 5,
+            ..LogsConfig::default()
+        };
+        assert!(prepare_batch(&config, None, batch()).is_err());
+        let long = vec![NewLogEvent::new(LogLevel::Info, "cli", "héllo world")];
+        assert_eq!(prepare_batch(&config, None, long).unwrap()[0].message, "héll");
+
+        let redactor = Redactor::new(&squirrel_core::redaction::RedactionConfig::default()).unwrap();
+        let mut secret = NewLogEvent::new(LogLevel::Warn, "agent", "Retrying with Bearer abc123");
+        secret.fields.insert("token".to_string(), Value::from("abc123"));
+        let prepared = prepare_batch(&LogsConfig::default(), Some(&redactor), vec![secret]).unwrap();
+        assert_eq!(prepared[0].message, "Retrying with Bearer [REDACTED]");
+        assert_eq!(prepared[0].fields["token"], "[REDACTED]");
+    }
+}
@@ -0,0 +1,313 @@
+//! Scheduled maintenance of the web database.
+//!
+//! On the cron schedule in `maintenance.schedule`, the leader runs the
+//! scheduled task `db.maintenance`: it deletes rows older than their
+//! retention period, then runs `ANALYZE` so the query
+//! planner has fresh statistics, then runs `VACUUM` to give the freed space
+//! back. Finished jobs, finished job queue entries, command history and
+//! finished webhook deliveries each have their own retention period, as do
+//! forwarded log events; without one they are kept. A dry run only counts what would be deleted, and
+//! neither analyzes nor vacuums.
+//!
+//! A run started by an admin can happen on any instance; with distributed
+//! locks, every run holds the lock `db.maintenance`, so instances sharing
+//! the database never maintain it at once.
+//!
+//! The web database is SQLite; there is no other backend to maintain.
+
+pub use squirrel_app::schedule::{CronError, CronSchedule};
+
+use std::sync::{Arc, RwLock};
+use std::time::Instant;
+
+use chrono::{DateTime, Duration, Utc};
+use serde::{Deserialize, Serialize};
+use squirrel_app::schedule::{ScheduleError, TaskOptions, TaskScheduler, HOST_OWNER};
+use sqlx::{Row, SqlitePool};
+use tokio::sync::Mutex;
+use tracing::info;
+
+use crate::api::error::AppError;
+use crate::config::MaintenanceConfig;
+use crate::db::InstrumentedPool;
+use crate::handlers::webhooks::WebhookService;
+use crate::locks::{LockService, DEFAULT_LOCK_TTL};
+
+/// Name of the scheduled maintenance task
+pub const MAINTENANCE_TASK: &str = "db.maintenance";
+
+/// What a maintenance run deleted, or would delete in a dry run
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct MaintenanceReport {
+    /// Whether rows were only counted
+    pub dry_run: bool,
+    /// When the run started
+    pub started_at: DateTime<Utc>,
+    /// How long it took
+    pub elapsed_ms: u64,
+    /// Finished jobs
+    pub jobs: u64,
+    /// Finished job queue entries
+    pub queue_entries: u64,
+    /// Finished command executions
+    pub command_executions: u64,
+    /// Finished webhook deliveries
+    pub webhook_deliveries: u64,
+    /// Forwarded log events
+    pub log_events: u64,
+    /// Whether `ANALYZE` ran
+    pub analyzed: bool,
+    /// Whether `VACUUM` ran
+    pub vacuumed: bool,
+}
+
+/// Jobs that will not change any more
+const FINISHED_JOBS: &str = "status IN ('completed', 'failed', 'cancelled')";
+
+/// Job queue entries that will not run again
+const FINISHED_QUEUE_ENTRIES: &str = "status IN ('completed', 'failed')";
+
+/// Command executions that will not change any more
+const FINISHED_COMMANDS: &str = "status IN ('completed', 'failed', 'cancelled')";
+
+/// The oldest rows of a table that are kept
+enum Cutoff {
+    /// A timestamp stored as text
+    Time(DateTime<Utc>),
+    /// A timestamp stored in milliseconds since the epoch
+    Millis(i64),
+}
+
+/// Deletes expired rows and compacts the web database on a schedule
+pub struct DatabaseMaintenance {
+    pool: InstrumentedPool,
+    webhooks: Option<Arc<WebhookService>>,
+    locks: Option<Arc<LockService>>,
+    config: MaintenanceConfig,
+    /// Report of the latest run
+    last: RwLock<Option<MaintenanceReport>>,
+    /// Held by the run in progress, so runs never overlap
+    running: Mutex<()>,
+}
+
+impl DatabaseMaintenance {
+    /// Create a new DatabaseMaintenance for the database of `pool`
+    pub fn new(pool: SqlitePool, config: MaintenanceConfig) -> Self {
+        Self {
+            pool: pool.into(),
+            webhooks: None,
+            locks: None,
+            config,
+            last: RwLock::new(None),
+            running: Mutex::new(()),
+        }
+    }
+
+    /// Delete expired deliveries of `webhooks` too
+    pub fn with_webhooks(mut self, webhooks: Arc<WebhookService>) -> Self {
+        self.webhooks = Some(webhooks);
+        self
+    }
+
+    /// Hold the lock `db.maintenance` from `locks` during runs
+    pub fn with_locks(mut self, locks: Arc<LockService>) -> Self {
+        self.locks = Some(locks);
+        self
+    }
+
+    /// The schedule runs start on
+    pub fn schedule(&self) -> &CronSchedule {
+        &self.config.schedule
+    }
+
+    /// When the next scheduled run starts, if runs are scheduled
+    pub fn next_run(&self) -> Option<DateTime<Utc>> {
+        self.config
+            .enabled
+            .then(|| self.config.schedule.next_after(Utc::now()))
+            .flatten()
+    }
+
+    /// Report of the latest run
+    pub fn last_report(&self) -> Option<MaintenanceReport> {
+        self.last.read().ok().and_then(|last| last.clone())
+    }
+
+    /// Delete, or with `dry_run` count, the rows of `table` matching
+    /// `condition`, if any, whose `column` is before `cutoff`
+    async fn expire(
+        &self,
+        table: &str,
+        condition: Option<&str>,
+        column: &str,
+        cutoff: Cutoff,
+        dry_run: bool,
+    ) -> Result<u64, AppError> {
+        let verb = if dry_run { "SELECT COUNT(*) FROM" } else { "DELETE FROM" };
+        let sql = match condition {
+            Some(condition) => format!("{} {} WHERE {} AND {} < ?", verb, table, condition, column),
+            None => format!("{} {} WHERE {} < ?", verb, table, column),
+        };
+        let query = sqlx::query(&sql);
+        let query = match cutoff {
+            Cutoff::Time(time) => query.bind(time),
+            Cutoff::Millis(millis) => query.bind(millis),
+        };
+        if dry_run {
+            let count: i64 = query.fetch_one(&self.pool).await?.try_get(0)?;
+            Ok(u64::try_from(count).unwrap_or_default())
+        } else {
+            Ok(query.execute(&self.pool).await?.rows_affected())
+        }
+    }
+
+    /// Run maintenance now; with `dry_run`, only count what would be deleted
+    ///
+    /// Fails with a conflict if another instance is maintaining the database.
+    pub async fn run(&self, dry_run: bool) -> Result<MaintenanceReport, AppError> {
+        let _running = self.running.lock().await;
+        match &self.locks {
+            Some(locks) => locks.with_lock(MAINTENANCE_TASK, DEFAULT_LOCK_TTL, self.maintain(dry_run)).await,
+            None => self.maintain(dry_run).await,
+        }
+    }
+
+    /// Run maintenance under the locks `run` took
+    async fn maintain(&self, dry_run: bool) -> Result<MaintenanceReport, AppError> {
+        let started = Instant::now();
+        let now = Utc::now();
+        let cutoff = |days: u32| now - Duration::days(i64::from(days));
+        let mut report = MaintenanceReport {
+            dry_run,
+            started_at: now,
+            ..MaintenanceReport::default()
+        };
+
+        if let Some(days) = self.config.job_retention_days {
+            report.jobs = self
+                .expire("jobs", Some(FINISHED_JOBS), "updated_at", Cutoff::Time(cutoff(days)), dry_run)
+                .await?;
+            report.queue_entries = self
+                .expire(
+                    "job_queue",
+                    Some(FINISHED_QUEUE_ENTRIES),
+                    "updated_at",
+                    Cutoff::Millis(cutoff(days).timestamp_millis()),
+                    dry_run,
+                )
+                .await?;
+        }
+        if let Some(days) = self.config.command_history_retention_days {
+            report.command_executions = self
+                .expire("command_executions", Some(FINISHED_COMMANDS), "updated_at", Cutoff::Time(cutoff(days)), dry_run)
+                .await?;
+        }
+        if let (Some(days), Some(webhooks)) = (self.config.webhook_delivery_retention_days, &self.webhooks) {
+            report.webhook_deliveries = webhooks.prune_deliveries(cutoff(days), dry_run)?;
+        }
+        if let Some(days) = self.config.log_retention_days {
+            report.log_events = self
+                .expire(
+                    "log_events",
+                    None,
+                    "received_at",
+                    Cutoff::Millis(cutoff(days).timestamp_millis()),
+                    dry_run,
+                )
+                .await?;
+        }
+
+        if !dry_run {
+            if self.config.analyze {
+                sqlx::query("ANALYZE").execute(&self.pool).await?;
+                report.analyzed = true;
+            }
+            if self.config.vacuum {
+                sqlx::query("VACUUM").execute(&self.pool).await?;
+                report.vacuumed = true;
+            }
+        }
+
+        report.elapsed_ms = u64::try_from(started.elapsed().as_millis()).unwrap_or(u64::MAX);
+        info!(
+            dry_run,
+            jobs = report.jobs,
+            queue_entries = report.queue_entries,
+            command_executions = report.command_executions,
+            webhook_deliveries = report.webhook_deliveries,
+            log_events = report.log_events,
+            elapsed_ms = report.elapsed_ms,
+            "Database maintenance finished"
+        );
+        if let Ok(mut last) = self.last.write() {
+            *last = Some(report.clone());
+        }
+        Ok(report)
+    }
+
+    /// Schedule maintenance runs on `scheduler`, if they are enabled
+    ///
+    /// The task is a singleton, run by the leader only, so instances sharing
+    /// the database do not maintain it at once.
+    pub fn schedule_on(self: &Arc<Self>, scheduler: &Arc<TaskScheduler>) -> Result<(), ScheduleError> {
+        if !self.config.enabled {
+            return Ok(());
+        }
+        let maintenance = self.clone();
+        let options = TaskOptions::new(self.config.schedule.clone()).singleton();
+        scheduler.register(HOST_OWNER, MAINTENANCE_TASK, options, move || {
+            let maintenance = maintenance.clone();
+            async move {
+                maintenance.run(maintenance.config.dry_run).await?;
+                Ok(())
+            }
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_helpers::migrated_pool;
+
+    async fn queue_entry(pool: &SqlitePool, id: &str, status: &str, age: Duration) {
+        let updated_at = (Utc::now() - age).timestamp_millis();
+        sqlx::query(
+            "INSERT INTO job_queue (id, kind, user_id, payload, status, created_at, updated_at)
+             VALUES (?, 'command', 'alice', '{}', ?, ?, ?)",
+        )
+        .bind(id)
+        .bind(status)
+        .bind(updated_at)
+        .bind(updated_at)
+        .execute(pool)
+        .await
+        .unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_dry_runs_count_what_runs_delete() {
+        let pool = migrated_pool().await;
+        queue_entry(&pool, "old", "completed", Duration::days(40)).await;
+        queue_entry(&pool, "stuck", "pending", Duration::days(40)).await;
+        queue_entry(&pool, "recent", "failed", Duration::days(1)).await;
+        let config = MaintenanceConfig {
+            job_retention_days: Some(30),
+            ..MaintenanceConfig::default()
+        };
+        let maintenance = DatabaseMaintenance::new(pool.clone(), config);
+
+        let dry_run = maintenance.run(true).await.unwrap();
+        assert_eq!((dry_run.queue_entries, dry_run.jobs, dry_run.vacuumed), (1, 0, false));
+        let report = maintenance.run(false).await.unwrap();
+        assert_eq!(report.queue_entries, 1);
+        assert!(report.analyzed && report.vacuumed);
+        assert_eq!(maintenance.last_report(), Some(report));
+
+        let left: Vec<String> = sqlx::query_scalar("SELECT id FROM job_queue ORDER BY id")
+            .fetch_all(&pool)
+            .await
+            .unwrap();
+        assert_eq!(left, vec!["recent".to_string(), "stuck".to_string()]);
+    }
+}
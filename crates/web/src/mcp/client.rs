@@ -0,0 +1,595 @@
+//! Client for a remote MCP server.
+//!
+//! Every frame is an [`MCPMessage`] encoded as JSON: a WebSocket text frame
+//! for `ws://` and `wss://` addresses, one line for `tcp://` addresses.
+//!
+//! 1. After connecting, the client sends a `Setup` message with its protocol
+//!    version and [`Credentials`]. The server authenticates them with its
+//!    `SecurityManager` and answers with a `Response` carrying `session_id`,
+//!    or an `Error` carrying `message`.
+//! 2. Requests are `Command` messages with a `method`, `params` and the
+//!    `session_id`. The server answers each with a `Response` of the same
+//!    ID holding `result`, or an `Error` holding `code` and `message`.
+//!    Commands run as MCP tools: `tools/call` starts one, within the MCP
+//!    context named by `context_id` if given, and returns its `command_id`;
+//!    `tools/list` lists them. `deadline_ms` tells the server how many
+//!    milliseconds the client still waits for the answer.
+//! 3. The server reports progress as `Event` messages with `event` set to
+//!    `command_status` and the [`CommandStatusResponse`] in `status`. The
+//!    client keeps the latest status of each command and forwards it to
+//!    WebSocket subscribers of the command's channel.
+//!
+//! The client reconnects whenever the connection drops, waiting longer after
+//! each failed attempt; requests made while it is down wait for the next
+//! connection until they time out.
+
+use std::collections::HashMap;
+use std::pin::Pin;
+use std::sync::{Arc, RwLock};
+use std::time::Duration;
+
+use async_trait::async_trait;
+use chrono::Utc;
+use futures::{Sink, SinkExt, Stream, StreamExt};
+use serde::Deserialize;
+use serde_json::{json, Value};
+use squirrel_core::deadline::Deadline;
+use squirrel_core::retry::{retry_with_policy, RetryPolicy, RetryResultExt};
+use squirrel_mcp::types::{MCPMessage, MessageId, MessageType};
+use squirrel_mcp::Credentials;
+use tokio::net::TcpStream;
+use tokio::sync::{mpsc, oneshot};
+use tokio::task::JoinHandle;
+use tokio_tungstenite::{connect_async, tungstenite::Message};
+use tokio_util::codec::{Framed, LinesCodec};
+use tracing::{debug, info, warn};
+use uuid::Uuid;
+
+use super::{McpCommandClient, McpError};
+use crate::api::commands::{CommandDefinition, CommandStatus, CommandStatusResponse};
+use crate::config::McpClientConfig;
+use crate::websocket::{ChannelCategory, ConnectionManager};
+
+/// Protocol version sent in the handshake
+const PROTOCOL_VERSION: (u32, u32) = (1, 0);
+
+/// Outgoing frames
+type FrameSink = Pin<Box<dyn Sink<String, Error = McpError> + Send>>;
+
+/// Incoming frames
+type FrameStream = Pin<Box<dyn Stream<Item = Result<String, McpError>> + Send>>;
+
+/// Reply to a request: the `result` of the response
+type Reply = oneshot::Sender<Result<Value, McpError>>;
+
+/// Latest status of each command, by command ID
+type StatusCache = Arc<RwLock<HashMap<String, CommandStatusResponse>>>;
+
+/// A request waiting to be sent
+struct PendingRequest {
+    message: MCPMessage,
+    reply: Reply,
+}
+
+/// A tool as listed by `tools/list`
+#[derive(Debug, Deserialize)]
+struct ToolDescriptor {
+    name: String,
+    #[serde(default)]
+    description: String,
+    #[serde(default, alias = "inputSchema")]
+    input_schema: Value,
+}
+
+/// MCP client connected to a remote server
+pub struct RemoteMcpClient {
+    requests: mpsc::Sender<PendingRequest>,
+    statuses: StatusCache,
+    timeout: Duration,
+    connection: JoinHandle<()>,
+}
+
+impl RemoteMcpClient {
+    /// Start connecting to the server in `config`
+    ///
+    /// Status updates are broadcast through `ws_manager` when one is given.
+    pub fn connect(config: McpClientConfig, ws_manager: Option<ConnectionManager>) -> Result<Self, McpError> {
+        let url = config
+            .url
+            .clone()
+            .ok_or_else(|| McpError::ConnectionError("No MCP server URL configured".to_string()))?;
+        if !["ws://", "wss://", "tcp://"].iter().any(|scheme| url.starts_with(scheme)) {
+            return Err(McpError::ConnectionError(format!(
+                "Unsupported MCP server address '{}'; use ws://, wss:// or tcp://",
+                url
+            )));
+        }
+
+        let (requests, receiver) = mpsc::channel(64);
+        let statuses = StatusCache::default();
+        let timeout = Duration::from_secs(config.request_timeout_secs.max(1));
+        let connection = tokio::spawn(run(url, config, receiver, statuses.clone(), ws_manager));
+        Ok(Self {
+            requests,
+            statuses,
+            timeout,
+            connection,
+        })
+    }
+
+    /// Send a request and wait for its result
+    ///
+    /// Waits at most the request timeout, or until the deadline of the
+    /// request being handled if that is earlier.
+    async fn request(&self, method: &str, params: Value) -> Result<Value, McpError> {
+        let deadline = Deadline::current()
+            .map_or_else(|| Deadline::after(self.timeout), |deadline| deadline.within(self.timeout));
+        let message = MCPMessage {
+            id: MessageId(Uuid::new_v4().to_string()),
+            message_type: MessageType::Command,
+            payload: json!({ "method": method, "params": params, "deadline_ms": deadline.remaining_millis() }),
+        };
+        let (reply, response) = oneshot::channel();
+        self.requests
+            .send(PendingRequest { message, reply })
+            .await
+            .map_err(|_| McpError::ConnectionError("MCP client is shut down".to_string()))?;
+        match deadline.run(response).await {
+            Ok(Ok(result)) => result,
+            Ok(Err(_)) => Err(McpError::ConnectionError(format!("Connection lost during {}", method))),
+            Err(_) => Err(McpError::Timeout(format!("No response to {} before the deadline", method))),
+        }
+    }
+
+    /// The last status reported for a command
+    fn cached_status(&self, command_id: &str) -> Option<CommandStatusResponse> {
+        self.statuses.read().ok()?.get(command_id).cloned()
+    }
+}
+
+impl Drop for RemoteMcpClient {
+    fn drop(&mut self) {
+        self.connection.abort();
+    }
+}
+
+#[async_trait]
+impl McpCommandClient for RemoteMcpClient {
+    async fn send_message(&self, message: &str) -> Result<String, McpError> {
+        match self.request("message", json!({ "text": message })).await? {
+            Value::String(text) => Ok(text),
+            other => Ok(other.to_string()),
+        }
+    }
+
+    async fn execute_command(
+        &self,
+        command: &str,
+        parameters: &serde_json::Value,
+        context_id: Option<&str>,
+    ) -> Result<String, McpError> {
+        let mut params = json!({ "name": command, "arguments": parameters });
+        if let Some(context_id) = context_id {
+            params["context_id"] = Value::String(context_id.to_string());
+        }
+        let result = self.request("tools/call", params).await?;
+        let command_id = result
+            .get("command_id")
+            .and_then(Value::as_str)
+            .ok_or_else(|| McpError::InvalidResponse("tools/call returned no command_id".to_string()))?
+            .to_string();
+
+        if let Ok(mut statuses) = self.statuses.write() {
+            statuses.entry(command_id.clone()).or_insert_with(|| CommandStatusResponse {
+                id: command_id.clone(),
+                command: command.to_string(),
+                status: CommandStatus::Queued,
+                progress: 0.0,
+                result: None,
+                error: None,
+                started_at: None,
+                completed_at: None,
+                elapsed: "00:00:00".to_string(),
+            });
+        }
+        Ok(command_id)
+    }
+
+    async fn get_command_status(
+        &self,
+        command_id: &str,
+    ) -> Result<CommandStatusResponse, McpError> {
+        let cached = self.cached_status(command_id);
+        if let Some(status) = cached.as_ref().filter(|status| is_finished(status.status)) {
+            return Ok(status.clone());
+        }
+        match self.request("commands/status", json!({ "command_id": command_id })).await {
+            Ok(result) => {
+                let status: CommandStatusResponse = serde_json::from_value(result)
+                    .map_err(|e| McpError::InvalidResponse(format!("Invalid command status: {}", e)))?;
+                update_status(&self.statuses, &status);
+                Ok(status)
+            }
+            // Fall back to the last reported status while the server is unreachable
+            Err(McpError::ConnectionError(_) | McpError::Timeout(_)) if cached.is_some() => {
+                Ok(cached.expect("checked above"))
+            }
+            Err(e) => Err(e),
+        }
+    }
+
+    async fn cancel_command(
+        &self,
+        command_id: &str,
+    ) -> Result<(), McpError> {
+        self.request("commands/cancel", json!({ "command_id": command_id })).await?;
+        Ok(())
+    }
+
+    async fn list_available_commands(
+        &self,
+    ) -> Result<Vec<CommandDefinition>, McpError> {
+        let result = self.request("tools/list", json!({})).await?;
+        let tools: Vec<ToolDescriptor> = serde_json::from_value(result.get("tools").cloned().unwrap_or(Value::Null))
+            .map_err(|e| McpError::InvalidResponse(format!("Invalid tool list: {}", e)))?;
+        let now = Utc::now();
+        Ok(tools
+            .into_iter()
+            .map(|tool| CommandDefinition {
+                id: tool.name.clone(),
+                name: tool.name,
+                description: tool.description,
+                parameter_schema: tool.input_schema,
+                created_at: now,
+                updated_at: now,
+            })
+            .collect())
+    }
+}
+
+/// Whether a command has stopped for good
+fn is_finished(status: CommandStatus) -> bool {
+    matches!(status, CommandStatus::Completed | CommandStatus::Failed | CommandStatus::Cancelled)
+}
+
+/// Stores a status unless a finished one is already known
+fn update_status(statuses: &StatusCache, status: &CommandStatusResponse) {
+    if let Ok(mut statuses) = statuses.write() {
+        let finished = statuses.get(&status.id).is_some_and(|known| is_finished(known.status));
+        if !finished {
+            statuses.insert(status.id.clone(), status.clone());
+        }
+    }
+}
+
+/// Longest wait between reconnection attempts
+const MAX_RECONNECT_DELAY: Duration = Duration::from_secs(300);
+
+/// How connecting is retried: `reconnect_delay_secs` apart, doubling up to
+/// [`MAX_RECONNECT_DELAY`], until the server is back
+fn reconnect_policy(config: &McpClientConfig) -> RetryPolicy {
+    let delay = Duration::from_secs(config.reconnect_delay_secs);
+    RetryPolicy::exponential(u32::MAX, delay, delay.max(MAX_RECONNECT_DELAY)).with_jitter()
+}
+
+/// Keeps a connection to the server open until the client is dropped
+async fn run(
+    url: String,
+    config: McpClientConfig,
+    mut requests: mpsc::Receiver<PendingRequest>,
+    statuses: StatusCache,
+    ws_manager: Option<ConnectionManager>,
+) {
+    let credentials = Credentials {
+        client_id: config.client_id.clone(),
+        client_secret: config.client_secret.clone(),
+        security_level: config.security_level,
+        requested_roles: (!config.roles.is_empty()).then(|| config.roles.clone()),
+    };
+    let policy = reconnect_policy(&config);
+    loop {
+        let connection = retry_with_policy("mcp.connect", &policy, || async {
+            let connection = establish(&url, &credentials).await;
+            if let Err(e) = &connection {
+                warn!("MCP connection to {} failed: {}", url, e);
+            }
+            connection.transient()
+        })
+        .await;
+        let Ok((sink, stream, session_id)) = connection else {
+            continue;
+        };
+        info!("Connected to MCP server {}", url);
+        match session(sink, stream, &session_id, &mut requests, &statuses, ws_manager.as_ref()).await {
+            Ok(()) => return,
+            Err(e) => warn!("MCP connection to {} failed: {}", url, e),
+        }
+        tokio::time::sleep(policy.backoff_delay(1)).await;
+    }
+}
+
+/// Opens and authenticates a connection, returning it with the session ID
+async fn establish(url: &str, credentials: &Credentials) -> Result<(FrameSink, FrameStream, String), McpError> {
+    let (mut sink, mut stream) = open(url).await?;
+    let session_id = handshake(&mut sink, &mut stream, credentials).await?;
+    Ok((sink, stream, session_id))
+}
+
+/// Serves a single connection; returns `Ok` once the client is dropped
+async fn session(
+    mut sink: FrameSink,
+    mut stream: FrameStream,
+    session_id: &str,
+    requests: &mut mpsc::Receiver<PendingRequest>,
+    statuses: &StatusCache,
+    ws_manager: Option<&ConnectionManager>,
+) -> Result<(), McpError> {
+    // Replies of requests sent on this connection; dropping them when the
+    // connection fails tells the callers
+    let mut pending: HashMap<String, Reply> = HashMap::new();
+    loop {
+        tokio::select! {
+            request = requests.recv() => {
+                let Some(PendingRequest { mut message, reply }) = request else {
+                    return Ok(());
+                };
+                if reply.is_closed() {
+                    continue;
+                }
+                message.payload["session_id"] = Value::String(session_id.to_string());
+                sink.send(encode(&message)?).await?;
+                pending.insert(message.id.0, reply);
+            }
+            frame = stream.next() => {
+                let text = frame.unwrap_or_else(|| Err(McpError::ConnectionError("Server closed the connection".to_string())))?;
+                match serde_json::from_str::<MCPMessage>(&text) {
+                    Ok(message) => dispatch(message, &mut pending, statuses, ws_manager),
+                    Err(e) => warn!("Ignoring malformed MCP message: {}", e),
+                }
+            }
+        }
+    }
+}
+
+/// Opens the transport named by the URL scheme
+async fn open(url: &str) -> Result<(FrameSink, FrameStream), McpError> {
+    if let Some(address) = url.strip_prefix("tcp://") {
+        let socket = TcpStream::connect(address)
+            .await
+            .map_err(|e| McpError::ConnectionError(format!("Cannot connect to {}: {}", address, e)))?;
+        let (sink, stream) = Framed::new(socket, LinesCodec::new()).split();
+        let sink = sink.sink_map_err(|e| McpError::ConnectionError(e.to_string()));
+        let stream = stream.map(|line| line.map_err(|e| McpError::ConnectionError(e.to_string())));
+        return Ok((Box::pin(sink), Box::pin(stream)));
+    }
+
+    let (socket, _) = connect_async(url)
+        .await
+        .map_err(|e| McpError::ConnectionError(format!("Cannot connect to {}: {}", url, e)))?;
+    let (sink, stream) = socket.split();
+    let sink = sink
+        .sink_map_err(|e| McpError::ConnectionError(e.to_string()))
+        .with(|text: String| futures::future::ok::<_, McpError>(Message::Text(text)));
+    let stream = stream.filter_map(|frame| {
+        futures::future::ready(match frame {
+            Ok(Message::Text(text)) => Some(Ok(text)),
+            Ok(Message::Close(_)) => Some(Err(McpError::ConnectionError("Server closed the connection".to_string()))),
+            Ok(_) => None,
+            Err(e) => Some(Err(McpError::ConnectionError(e.to_string()))),
+        })
+    });
+    Ok((Box::pin(sink), Box::pin(stream)))
+}
+
+/// Authenticates the connection, returning the session ID
+async fn handshake(
+    sink: &mut FrameSink,
+    stream: &mut FrameStream,
+    credentials: &Credentials,
+) -> Result<String, McpError> {
+    let setup = MCPMessage {
+        id: MessageId(Uuid::new_v4().to_string()),
+        message_type: MessageType::Setup,
+        payload: json!({
+            "protocol_version": { "major": PROTOCOL_VERSION.0, "minor": PROTOCOL_VERSION.1 },
+            "credentials": credentials,
+        }),
+    };
+    sink.send(encode(&setup)?).await?;
+
+    while let Some(frame) = stream.next().await {
+        let message: MCPMessage = serde_json::from_str(&frame?)
+            .map_err(|e| McpError::InvalidResponse(format!("Invalid handshake response: {}", e)))?;
+        if message.id != setup.id {
+            continue;
+        }
+        return match message.message_type {
+            MessageType::Response => message
+                .payload
+                .get("session_id")
+                .and_then(Value::as_str)
+                .map(str::to_string)
+                .ok_or_else(|| McpError::InvalidResponse("Handshake returned no session_id".to_string())),
+            _ => Err(McpError::ConnectionError(format!(
+                "Authentication failed: {}",
+                error_message(&message.payload)
+            ))),
+        };
+    }
+    Err(McpError::ConnectionError("Server closed the connection during the handshake".to_string()))
+}
+
+/// Routes a message from the server
+fn dispatch(
+    message: MCPMessage,
+    pending: &mut HashMap<String, Reply>,
+    statuses: &StatusCache,
+    ws_manager: Option<&ConnectionManager>,
+) {
+    match message.message_type {
+        MessageType::Response | MessageType::Error => {
+            let Some(reply) = pending.remove(&message.id.0) else {
+                debug!("Ignoring response to unknown request {}", message.id.0);
+                return;
+            };
+            let result = if message.message_type == MessageType::Response {
+                Ok(message.payload.get("result").cloned().unwrap_or(Value::Null))
+            } else {
+                Err(response_error(&message.payload))
+            };
+            let _ = reply.send(result);
+        }
+        MessageType::Event if message.payload.get("event").and_then(Value::as_str) == Some("command_status") => {
+            let status = message.payload.get("status").cloned().unwrap_or(Value::Null);
+            let status: CommandStatusResponse = match serde_json::from_value(status) {
+                Ok(status) => status,
+                Err(e) => return warn!("Ignoring invalid command status: {}", e),
+            };
+            update_status(statuses, &status);
+            if let Some(ws_manager) = ws_manager.cloned() {
+                tokio::spawn(async move {
+                    let data = serde_json::to_value(&status).unwrap_or_default();
+                    if let Err(e) = ws_manager
+                        .broadcast_to_channel(ChannelCategory::Command, &status.id, "command_status", data)
+                        .await
+                    {
+                        warn!("Failed to broadcast command status: {}", e);
+                    }
+                });
+            }
+        }
+        other => debug!("Ignoring MCP {} message", other),
+    }
+}
+
+/// Error carried by an `Error` response
+fn response_error(payload: &Value) -> McpError {
+    let message = error_message(payload);
+    match payload.get("code").and_then(Value::as_str) {
+        Some("command_not_found") => McpError::CommandNotFound(message),
+        Some("timeout") => McpError::Timeout(message),
+        _ => McpError::CommandError(message),
+    }
+}
+
+/// The `message` of an error payload
+fn error_message(payload: &Value) -> String {
+    payload
+        .get("message")
+        .and_then(Value::as_str)
+        .unwrap_or("unknown error")
+        .to_string()
+}
+
+/// Encodes a message as one frame
+fn encode(message: &MCPMessage) -> Result<String, McpError> {
+    serde_json::to_string(message).map_err(|e| McpError::Internal(format!("Cannot encode MCP message: {}", e)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::net::TcpListener;
+
+    /// Minimal MCP server accepting the secret `s3cret`
+    async fn serve(listener: TcpListener) {
+        let (socket, _) = listener.accept().await.unwrap();
+        let mut framed = Framed::new(socket, LinesCodec::new());
+        while let Some(Ok(line)) = framed.next().await {
+            let request: MCPMessage = serde_json::from_str(&line).unwrap();
+            let payload = &request.payload;
+            let (message_type, reply) = match request.message_type {
+                MessageType::Setup if payload["credentials"]["client_secret"] == "s3cret" => {
+                    (MessageType::Response, json!({ "session_id": "session-1" }))
+                }
+                MessageType::Setup => (MessageType::Error, json!({ "message": "invalid credentials" })),
+                _ => {
+                    assert_eq!(payload["session_id"], "session-1");
+                    match payload["method"].as_str().unwrap() {
+                        "tools/call" if payload["params"]["name"] == "align" => {
+                            (MessageType::Response, json!({ "result": { "command_id": "cmd-1" } }))
+                        }
+                        "tools/call" => (
+                            MessageType::Error,
+                            json!({ "code": "command_not_found", "message": payload["params"]["name"] }),
+                        ),
+                        "tools/list" => (
+                            MessageType::Response,
+                            json!({ "result": { "tools": [
+                                { "name": "align", "description": "Align reads", "inputSchema": { "type": "object" } }
+                            ] } }),
+                        ),
+                        method => panic!("unexpected method {method}"),
+                    }
+                }
+            };
+            let response = MCPMessage { id: request.id.clone(), message_type, payload: reply };
+            framed.send(serde_json::to_string(&response).unwrap()).await.unwrap();
+
+            if payload["method"] == "tools/call" && message_type == MessageType::Response {
+                let event = MCPMessage {
+                    id: MessageId("event-1".to_string()),
+                    message_type: MessageType::Event,
+                    payload: json!({ "event": "command_status", "status": {
+                        "id": "cmd-1", "command": "align", "status": "Completed", "progress": 1.0,
+                        "result": { "reads": 10 }, "error": null, "started_at": null,
+                        "completed_at": null, "elapsed": "00:00:01"
+                    } }),
+                };
+                framed.send(serde_json::to_string(&event).unwrap()).await.unwrap();
+            }
+        }
+    }
+
+    async fn client(secret: &str) -> RemoteMcpClient {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address = listener.local_addr().unwrap();
+        tokio::spawn(serve(listener));
+        let config = McpClientConfig {
+            url: Some(format!("tcp://{}", address)),
+            client_secret: secret.to_string(),
+            request_timeout_secs: 1,
+            reconnect_delay_secs: 60,
+            ..McpClientConfig::default()
+        };
+        RemoteMcpClient::connect(config, None).unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_commands_run_as_tools_and_stream_status() {
+        let client = client("s3cret").await;
+
+        let tools = client.list_available_commands().await.unwrap();
+        assert_eq!(tools.len(), 1);
+        assert_eq!(tools[0].name, "align");
+        assert_eq!(tools[0].parameter_schema, json!({ "type": "object" }));
+
+        let id = client.execute_command("align", &json!({ "min_quality": 20 }), None).await.unwrap();
+        assert_eq!(id, "cmd-1");
+        assert!(matches!(
+            client.execute_command("missing", &json!({}), None).await,
+            Err(McpError::CommandNotFound(_))
+        ));
+
+        // The completion event follows the tools/call response
+        for _ in 0..100 {
+            if client.cached_status("cmd-1").is_some_and(|status| is_finished(status.status)) {
+                break;
+            }
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+        let status = client.get_command_status("cmd-1").await.unwrap();
+        assert_eq!(status.status, CommandStatus::Completed);
+        assert_eq!(status.result, Some(json!({ "reads": 10 })));
+    }
+
+    #[tokio::test]
+    async fn test_rejected_credentials_fail_requests() {
+        let client = client("wrong").await;
+        assert!(matches!(
+            client.list_available_commands().await,
+            Err(McpError::Timeout(_) | McpError::ConnectionError(_))
+        ));
+        assert!(RemoteMcpClient::connect(McpClientConfig::default(), None).is_err());
+    }
+}
@@ -1,3 +1,9 @@
+//! Clients forwarding commands to the MCP server.
+//!
+//! [`RemoteMcpClient`] talks to a real server; [`MockMcpClient`] stands in
+//! for one when no server is configured. [`UnavailableMcpClient`] takes the
+//! place of a configured server the web server could not connect to.
+
 use anyhow::Result;
 use std::fmt;
 use async_trait::async_trait;
@@ -6,6 +12,9 @@ use crate::api::commands::{
     CommandStatusResponse,
 };
 
+mod client;
+pub use client::RemoteMcpClient;
+
 /// MCP connection errors
 #[derive(Debug)]
 pub enum McpError {
@@ -21,6 +30,8 @@ pub enum McpError {
     Timeout(String),
     /// Internal error
     Internal(String),
+    /// The configured MCP server is unavailable
+    Unavailable(String),
 }
 
 impl fmt::Display for McpError {
@@ -32,6 +43,7 @@ impl fmt::Display for McpError {
             McpError::CommandNotFound(msg) => write!(f, "Command not found: {}", msg),
             McpError::Timeout(msg) => write!(f, "MCP timeout: {}", msg),
             McpError::Internal(msg) => write!(f, "MCP internal error: {}", msg),
+            McpError::Unavailable(msg) => write!(f, "MCP server unavailable: {}", msg),
         }
     }
 }
@@ -42,11 +54,12 @@ pub trait McpCommandClient: Send + Sync + 'static {
     /// Send a message to the MCP
     async fn send_message(&self, message: &str) -> Result<String, McpError>;
     
-    /// Execute a command via MCP
+    /// Execute a command via MCP, within the given MCP context if any
     async fn execute_command(
         &self,
         command: &str,
         parameters: &serde_json::Value,
+        context_id: Option<&str>,
     ) -> Result<String, McpError>;
     
     /// Get command status
@@ -91,11 +104,13 @@ impl McpCommandClient for MockMcpClient {
         &self,
         command: &str,
         parameters: &serde_json::Value,
+        context_id: Option<&str>,
     ) -> Result<String, McpError> {
         tracing::info!(
-            "Mock MCP client executing command: {} with parameters: {}", 
+            "Mock MCP client executing command: {} with parameters: {} in context {:?}", 
             command, 
-            parameters
+            parameters,
+            context_id
         );
         
         // Generate a fake command ID
@@ -183,4 +198,50 @@ impl McpCommandClient for MockMcpClient {
         
         Ok(commands)
     }
-} 
\ No newline at end of file
+} 
+
+/// Client for a configured MCP server the web server could not connect to
+///
+/// Every call fails with [`McpError::Unavailable`], so that commands are
+/// refused instead of pretending to run.
+pub struct UnavailableMcpClient {
+    reason: String,
+}
+
+impl UnavailableMcpClient {
+    pub fn new(reason: impl Into<String>) -> Self {
+        Self { reason: reason.into() }
+    }
+
+    fn error(&self) -> McpError {
+        McpError::Unavailable(self.reason.clone())
+    }
+}
+
+#[async_trait]
+impl McpCommandClient for UnavailableMcpClient {
+    async fn send_message(&self, _message: &str) -> Result<String, McpError> {
+        Err(self.error())
+    }
+
+    async fn execute_command(
+        &self,
+        _command: &str,
+        _parameters: &serde_json::Value,
+        _context_id: Option<&str>,
+    ) -> Result<String, McpError> {
+        Err(self.error())
+    }
+
+    async fn get_command_status(&self, _command_id: &str) -> Result<CommandStatusResponse, McpError> {
+        Err(self.error())
+    }
+
+    async fn cancel_command(&self, _command_id: &str) -> Result<(), McpError> {
+        Err(self.error())
+    }
+
+    async fn list_available_commands(&self) -> Result<Vec<CommandDefinition>, McpError> {
+        Err(self.error())
+    }
+}
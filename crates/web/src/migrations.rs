@@ -0,0 +1,491 @@
+//! Migrations of the web database.
+//!
+//! [`SqlMigrations`] exposes the SQL migrations in `migrations/` to the
+//! [`squirrel_core::migration`] framework. They are tracked in the
+//! `_sqlx_migrations` table with the checksums SQLx computes, and each one
+//! runs in a transaction together with its record. Migrations with a
+//! `.down.sql` file can be reverted; the older plain `.sql` ones cannot.
+//!
+//! Instances sharing the database take turns: migrations are applied and
+//! reverted under the [`MIGRATIONS_LOCK`] distributed lock.
+//!
+//! [`schema_drift`] compares the schema of a database with the one its
+//! applied migrations give a fresh database, to catch tables, columns,
+//! indexes, triggers and views changed by hand.
+
+use std::any::Any;
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt::{self, Write as _};
+use std::sync::Arc;
+use std::time::Duration;
+
+use async_trait::async_trait;
+use chrono::NaiveDateTime;
+use serde::{Deserialize, Serialize};
+use sqlx::migrate::{Migrate, MigrateDatabase, Migration, Migrator};
+use sqlx::sqlite::SqlitePoolOptions;
+use sqlx::Sqlite;
+use squirrel_core::migration::{AppliedMigration, Direction, MigrationError, MigrationInfo, MigrationStore};
+use squirrel_core::retry::{retry_with_policy, RetryPolicy, RetryResultExt};
+use uuid::Uuid;
+
+use crate::db::SqlitePool;
+use crate::locks::{LockService, SqlLockStore, DEFAULT_LOCK_TTL, MIGRATIONS_LOCK};
+
+/// The migrations in `migrations/`
+pub static MIGRATOR: Migrator = sqlx::migrate!("./migrations");
+
+/// How connecting to the database is retried while it is unreachable
+const CONNECT_RETRY_POLICY: RetryPolicy =
+    RetryPolicy::exponential(5, Duration::from_millis(200), Duration::from_secs(5)).with_jitter();
+
+/// How long an instance waits for another one to finish migrating
+const MIGRATIONS_LOCK_WAIT: Duration = Duration::from_secs(300);
+
+/// Tables the server creates itself rather than through migrations
+const UNMIGRATED_TABLES: &[&str] = &["_sqlx_migrations", "distributed_locks"];
+
+/// Connect to the database at `url`, creating it if it does not exist
+pub async fn connect(url: &str) -> anyhow::Result<SqlitePool> {
+    if !Sqlite::database_exists(url).await.unwrap_or(false) {
+        Sqlite::create_database(url).await?;
+    }
+    let pool = retry_with_policy("db.connect", &CONNECT_RETRY_POLICY, || async {
+        SqlitePool::connect(url)
+            .await
+            .transient_if(|e| matches!(e, sqlx::Error::Io(_) | sqlx::Error::PoolTimedOut))
+    })
+    .await?;
+    Ok(pool)
+}
+
+/// Maps a database error to a migration store error
+fn store_error(error: impl std::fmt::Display) -> MigrationError {
+    MigrationError::Store(error.to_string())
+}
+
+/// The web database's migrations
+#[derive(Debug, Clone)]
+pub struct SqlMigrations {
+    pool: SqlitePool,
+}
+
+impl SqlMigrations {
+    /// Migrations of the database behind `pool`
+    pub fn new(pool: SqlitePool) -> Self {
+        Self { pool }
+    }
+
+    /// The SQL migration running `version` in `direction`
+    fn find(version: i64, direction: Direction) -> Result<&'static Migration, MigrationError> {
+        MIGRATOR
+            .iter()
+            .find(|migration| {
+                migration.version == version
+                    && match direction {
+                        Direction::Up => !migration.migration_type.is_down_migration(),
+                        Direction::Down => migration.migration_type.is_down_migration(),
+                    }
+            })
+            .ok_or(MigrationError::UnknownVersion(version))
+    }
+}
+
+#[async_trait]
+impl MigrationStore for SqlMigrations {
+    fn name(&self) -> &str {
+        "web"
+    }
+
+    fn migrations(&self) -> Vec<MigrationInfo> {
+        let reversible: Vec<i64> = MIGRATOR
+            .iter()
+            .filter(|migration| migration.migration_type.is_down_migration())
+            .map(|migration| migration.version)
+            .collect();
+        MIGRATOR
+            .iter()
+            .filter(|migration| !migration.migration_type.is_down_migration())
+            .map(|migration| MigrationInfo {
+                version: migration.version,
+                name: migration.description.to_string(),
+                checksum: hex::encode(&migration.checksum),
+                reversible: reversible.contains(&migration.version),
+            })
+            .collect()
+    }
+
+    async fn applied(&self) -> Result<Vec<AppliedMigration>, MigrationError> {
+        let mut conn = self.pool.acquire().await.map_err(store_error)?;
+        conn.ensure_migrations_table().await.map_err(store_error)?;
+        if let Some(version) = conn.dirty_version().await.map_err(store_error)? {
+            return Err(MigrationError::Store(format!(
+                "Migration {version} failed partway; repair the database before migrating"
+            )));
+        }
+        let rows: Vec<(i64, String, NaiveDateTime, Vec<u8>)> = sqlx::query_as(
+            "SELECT version, description, installed_on, checksum FROM _sqlx_migrations WHERE success ORDER BY version",
+        )
+        .fetch_all(&mut *conn)
+        .await
+        .map_err(store_error)?;
+        // One row per version; keyed to be safe against duplicates
+        let applied: BTreeMap<i64, AppliedMigration> = rows
+            .into_iter()
+            .map(|(version, name, installed_on, checksum)| {
+                let migration = AppliedMigration {
+                    version,
+                    name,
+                    checksum: hex::encode(checksum),
+                    applied_at: installed_on.and_utc(),
+                };
+                (version, migration)
+            })
+            .collect();
+        Ok(applied.into_values().collect())
+    }
+
+    async fn run(&self, version: i64, direction: Direction) -> Result<(), MigrationError> {
+        let migration = Self::find(version, direction)?;
+        let mut conn = self.pool.acquire().await.map_err(store_error)?;
+        conn.ensure_migrations_table().await.map_err(store_error)?;
+        let result = match direction {
+            Direction::Up => conn.apply(migration).await,
+            Direction::Down => conn.revert(migration).await,
+        };
+        result.map(|_| ()).map_err(|e| MigrationError::Failed {
+            version,
+            name: migration.description.to_string(),
+            message: e.to_string(),
+        })
+    }
+
+    async fn lock(&self) -> Result<Option<Box<dyn Any + Send>>, MigrationError> {
+        let locks = LockService::new(Arc::new(SqlLockStore::new(self.pool.clone())), Uuid::new_v4().to_string());
+        let guard = locks
+            .lock(MIGRATIONS_LOCK, DEFAULT_LOCK_TTL, MIGRATIONS_LOCK_WAIT)
+            .await
+            .map_err(store_error)?;
+        Ok(Some(Box::new(guard)))
+    }
+}
+
+/// Kind of a schema object
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SchemaObject {
+    /// A table
+    Table,
+    /// A column of a table
+    Column,
+    /// An index on a table
+    Index,
+    /// A trigger on a table
+    Trigger,
+    /// A view
+    View,
+}
+
+impl fmt::Display for SchemaObject {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Table => write!(f, "table"),
+            Self::Column => write!(f, "column"),
+            Self::Index => write!(f, "index"),
+            Self::Trigger => write!(f, "trigger"),
+            Self::View => write!(f, "view"),
+        }
+    }
+}
+
+/// How a database differs from its migrations
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum DriftKind {
+    /// The migrations create the object, but the database lacks it
+    Missing,
+    /// The database has the object, but the migrations do not create it
+    Extra,
+    /// The database defines the object differently
+    Changed,
+}
+
+impl fmt::Display for DriftKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Missing => write!(f, "missing"),
+            Self::Extra => write!(f, "extra"),
+            Self::Changed => write!(f, "changed"),
+        }
+    }
+}
+
+/// A schema object that differs from what the migrations create
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SchemaDrift {
+    /// How it differs
+    pub kind: DriftKind,
+    /// Kind of the object
+    pub object: SchemaObject,
+    /// Table the object belongs to; the object itself for tables and views
+    pub table: String,
+    /// Name of the object
+    pub name: String,
+    /// Definition the migrations give it
+    pub expected: Option<String>,
+    /// Definition the database has
+    pub actual: Option<String>,
+}
+
+impl fmt::Display for SchemaDrift {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self.object {
+            SchemaObject::Column => format!("{}.{}", self.table, self.name),
+            SchemaObject::Index | SchemaObject::Trigger => format!("{} on {}", self.name, self.table),
+            SchemaObject::Table | SchemaObject::View => self.name.clone(),
+        };
+        match (self.kind, &self.expected, &self.actual) {
+            (DriftKind::Changed, expected, actual) => write!(
+                f,
+                "changed {} {}: {}, expected {}",
+                self.object,
+                name,
+                actual.as_deref().unwrap_or_default(),
+                expected.as_deref().unwrap_or_default()
+            ),
+            (DriftKind::Missing, Some(definition), _) | (DriftKind::Extra, _, Some(definition)) => match self.object {
+                // Index definitions carry their own parentheses
+                SchemaObject::Index => write!(f, "{} {} {} {}", self.kind, self.object, name, definition),
+                _ => write!(f, "{} {} {} ({})", self.kind, self.object, name, definition),
+            },
+            _ => write!(f, "{} {} {}", self.kind, self.object, name),
+        }
+    }
+}
+
+/// Definitions of the schema objects of a database, by table, kind and name
+type Schema = BTreeMap<(String, SchemaObject, String), String>;
+
+/// Read the schema of the database behind `pool`, leaving out the tables
+/// the server creates itself
+async fn read_schema(pool: &SqlitePool) -> Result<Schema, sqlx::Error> {
+    let mut schema = Schema::new();
+    let objects: Vec<(String, String, String, Option<String>)> = sqlx::query_as(
+        "SELECT type, name, tbl_name, sql FROM sqlite_master WHERE name NOT LIKE 'sqlite_%' ORDER BY name",
+    )
+    .fetch_all(pool)
+    .await?;
+    for (kind, name, table, sql) in objects {
+        if UNMIGRATED_TABLES.contains(&table.as_str()) {
+            continue;
+        }
+        // Indexes are read with their table, which also covers the ones
+        // SQLite creates for constraints
+        let object = match kind.as_str() {
+            "table" => SchemaObject::Table,
+            "trigger" => SchemaObject::Trigger,
+            "view" => SchemaObject::View,
+            _ => continue,
+        };
+        // Triggers and views are compared by their statement, as written
+        let definition = match object {
+            SchemaObject::Table => String::new(),
+            _ => sql.unwrap_or_default().split_whitespace().collect::<Vec<_>>().join(" "),
+        };
+        schema.insert((table.clone(), object, name), definition);
+        if object != SchemaObject::Table {
+            continue;
+        }
+
+        let columns: Vec<(String, String, bool, Option<String>, i64)> =
+            sqlx::query_as("SELECT name, type, \"notnull\", dflt_value, pk FROM pragma_table_info(?) ORDER BY cid")
+                .bind(&table)
+                .fetch_all(pool)
+                .await?;
+        for (column, column_type, not_null, default, primary_key) in columns {
+            let mut definition = column_type;
+            if not_null {
+                definition.push_str(" NOT NULL");
+            }
+            if let Some(default) = default {
+                let _ = write!(definition, " DEFAULT {default}");
+            }
+            if primary_key > 0 {
+                definition.push_str(" PRIMARY KEY");
+            }
+            schema.insert((table.clone(), SchemaObject::Column, column), definition.trim().to_string());
+        }
+
+        let indexes: Vec<(String, bool, bool)> =
+            sqlx::query_as("SELECT name, \"unique\", partial FROM pragma_index_list(?)")
+                .bind(&table)
+                .fetch_all(pool)
+                .await?;
+        for (index, unique, partial) in indexes {
+            let columns: Vec<(Option<String>,)> =
+                sqlx::query_as("SELECT name FROM pragma_index_info(?) ORDER BY seqno")
+                    .bind(&index)
+                    .fetch_all(pool)
+                    .await?;
+            let columns: Vec<String> = columns
+                .into_iter()
+                .map(|(column,)| column.unwrap_or_else(|| "<expression>".to_string()))
+                .collect();
+            let definition = format!(
+                "{}({}){}",
+                if unique { "UNIQUE " } else { "" },
+                columns.join(", "),
+                if partial { " WHERE ..." } else { "" }
+            );
+            schema.insert((table.clone(), SchemaObject::Index, index), definition);
+        }
+    }
+    Ok(schema)
+}
+
+/// The objects that differ between `expected` and `actual`; those of a
+/// table missing on either side are left out, the table standing for them
+fn compare(expected: &Schema, actual: &Schema) -> Vec<SchemaDrift> {
+    let tables = |schema: &Schema| -> BTreeSet<String> {
+        schema
+            .keys()
+            .filter(|(_, object, _)| *object == SchemaObject::Table)
+            .map(|(table, _, _)| table.clone())
+            .collect()
+    };
+    let shared: BTreeSet<String> = tables(expected).intersection(&tables(actual)).cloned().collect();
+    let reported = |(table, object, _): &(String, SchemaObject, String)| {
+        matches!(object, SchemaObject::Table | SchemaObject::View) || shared.contains(table)
+    };
+    let definition = |definition: &String| Some(definition.clone()).filter(|definition| !definition.is_empty());
+    let drift = |kind, (table, object, name): &(String, SchemaObject, String), expected, actual| SchemaDrift {
+        kind,
+        object: *object,
+        table: table.clone(),
+        name: name.clone(),
+        expected,
+        actual,
+    };
+
+    let mut drifts = Vec::new();
+    for (key, wanted) in expected.iter().filter(|(key, _)| reported(key)) {
+        match actual.get(key) {
+            None => drifts.push(drift(DriftKind::Missing, key, definition(wanted), None)),
+            Some(found) if found != wanted => {
+                drifts.push(drift(DriftKind::Changed, key, definition(wanted), definition(found)));
+            }
+            Some(_) => {}
+        }
+    }
+    for (key, found) in actual.iter().filter(|(key, _)| reported(key) && !expected.contains_key(key)) {
+        drifts.push(drift(DriftKind::Extra, key, None, definition(found)));
+    }
+    drifts.sort_by(|a, b| (&a.table, a.object, &a.name).cmp(&(&b.table, b.object, &b.name)));
+    drifts
+}
+
+/// How the schema of the database behind `pool` differs from the one its
+/// applied migrations give a fresh database
+///
+/// Pending migrations are left out: a database behind its migrations has
+/// not drifted. Compare the migrations themselves with
+/// [`squirrel_core::migration::Migrator::status`].
+pub async fn schema_drift(pool: &SqlitePool) -> Result<Vec<SchemaDrift>, MigrationError> {
+    let applied: BTreeSet<i64> = SqlMigrations::new(pool.clone())
+        .applied()
+        .await?
+        .into_iter()
+        .map(|migration| migration.version)
+        .collect();
+
+    // One connection, as each in-memory connection is its own database
+    let canonical = SqlitePoolOptions::new()
+        .max_connections(1)
+        .connect("sqlite::memory:")
+        .await
+        .map_err(store_error)?;
+    {
+        let mut conn = canonical.acquire().await.map_err(store_error)?;
+        conn.ensure_migrations_table().await.map_err(store_error)?;
+        let migrations = MIGRATOR
+            .iter()
+            .filter(|migration| !migration.migration_type.is_down_migration() && applied.contains(&migration.version));
+        for migration in migrations {
+            conn.apply(migration).await.map_err(|e| MigrationError::Failed {
+                version: migration.version,
+                name: migration.description.to_string(),
+                message: e.to_string(),
+            })?;
+        }
+    }
+
+    let expected = read_schema(&canonical).await.map_err(store_error)?;
+    canonical.close().await;
+    let actual = read_schema(pool).await.map_err(store_error)?;
+    Ok(compare(&expected, &actual))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use squirrel_core::migration::{MigrationState, Migrator as DataMigrator};
+    use sqlx::sqlite::SqlitePoolOptions;
+    use crate::test_helpers::migrated_pool;
+
+    #[tokio::test]
+    async fn test_web_database_migrations() {
+        // One connection, as each in-memory connection is its own database
+        let pool = SqlitePoolOptions::new()
+            .max_connections(1)
+            .connect("sqlite::memory:")
+            .await
+            .unwrap();
+        let store = SqlMigrations::new(pool.clone());
+        let migrator = DataMigrator::new(&store);
+        let total = store.migrations().len();
+
+        assert_eq!(migrator.up(None).await.unwrap().len(), total);
+        let status = migrator.status().await.unwrap();
+        assert!(status.iter().all(|migration| migration.state == MigrationState::Applied));
+
+        // The records are the ones SQLx itself keeps
+        MIGRATOR.run(&pool).await.unwrap();
+
+        let reverted = migrator.down(None).await.unwrap();
+        assert_eq!(reverted[0].version, status.last().unwrap().version);
+        assert_eq!(migrator.status().await.unwrap().last().unwrap().state, MigrationState::Pending);
+        assert!(matches!(
+            migrator.down(Some(0)).await,
+            Err(MigrationError::Irreversible { .. })
+        ));
+        migrator.up(None).await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_schema_changed_by_hand_is_reported() {
+        let pool = migrated_pool().await;
+        assert_eq!(schema_drift(&pool).await.unwrap(), Vec::new());
+
+        for statement in [
+            "ALTER TABLE job_queue ADD COLUMN note TEXT NOT NULL DEFAULT ''",
+            "DROP INDEX idx_job_queue_status",
+            "CREATE INDEX idx_job_queue_status ON job_queue(status)",
+            "CREATE INDEX idx_auth_sessions_extra ON auth_sessions(id)",
+            "DROP TABLE replication_heartbeat",
+            "CREATE TABLE scratch (id INTEGER)",
+        ] {
+            sqlx::query(statement).execute(&pool).await.unwrap();
+        }
+        let drift: Vec<String> = schema_drift(&pool).await.unwrap().iter().map(ToString::to_string).collect();
+        assert_eq!(
+            drift,
+            vec![
+                "extra index idx_auth_sessions_extra on auth_sessions (id)".to_string(),
+                "extra column job_queue.note (TEXT NOT NULL DEFAULT '')".to_string(),
+                "changed index idx_job_queue_status on job_queue: (status), expected (status, created_at)".to_string(),
+                "missing table replication_heartbeat".to_string(),
+                "extra table scratch".to_string(),
+            ]
+        );
+    }
+}
@@ -0,0 +1,254 @@
+//! Memory and task profiling of a running server.
+//!
+//! A [`ProfileSnapshot`] tells where a long-running server's memory goes:
+//! its resident size, what the Tokio runtime is running, and, in builds with
+//! the `profiling` feature, what the heap holds. Those builds install the
+//! [`CountingAllocator`] as the global allocator of the server binary. It
+//! counts every allocation, reallocation and free and the bytes each one
+//! moves, like heaptrack does, at the cost of a few atomic additions per
+//! allocation. Comparing snapshots taken a while apart shows whether the
+//! heap keeps growing and whether tasks pile up.
+//!
+//! Admins read snapshots at `GET /api/debug/profile`, and `squirrel profile`
+//! takes them at intervals with [`fetch_profile`].
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use squirrel_monitoring::accounting::process_memory_mb;
+
+#[cfg(feature = "profiling")]
+pub use allocator::CountingAllocator;
+
+/// What the heap holds, as counted by the [`CountingAllocator`]
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct AllocationStats {
+    /// Allocations made since the server started
+    pub allocations: u64,
+    /// Allocations freed since the server started
+    pub deallocations: u64,
+    /// Allocations grown or shrunk in place or moved
+    pub reallocations: u64,
+    /// Allocations not freed yet
+    pub live_allocations: u64,
+    /// Bytes allocated since the server started
+    pub allocated_bytes: u64,
+    /// Bytes freed since the server started
+    pub freed_bytes: u64,
+    /// Bytes allocated and not freed yet
+    pub live_bytes: u64,
+    /// Most bytes allocated at once
+    pub peak_bytes: u64,
+}
+
+/// What the Tokio runtime is running
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RuntimeStats {
+    /// Worker threads of the runtime
+    pub workers: usize,
+    /// Tasks spawned and not finished yet
+    pub alive_tasks: usize,
+    /// Tasks waiting in the runtime's global queue
+    pub global_queue_depth: usize,
+}
+
+/// Memory and task usage of the server at one point in time
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ProfileSnapshot {
+    /// When the snapshot was taken
+    pub taken_at: DateTime<Utc>,
+    /// Resident memory of the process in megabytes
+    pub resident_mb: u64,
+    /// What the heap holds; unset unless the server was built with the
+    /// `profiling` feature
+    pub allocation: Option<AllocationStats>,
+    /// What the Tokio runtime is running; unset outside a runtime
+    pub runtime: Option<RuntimeStats>,
+}
+
+/// Take a snapshot of this process
+pub fn snapshot() -> ProfileSnapshot {
+    let runtime = tokio::runtime::Handle::try_current().ok().map(|handle| {
+        let metrics = handle.metrics();
+        RuntimeStats {
+            workers: metrics.num_workers(),
+            alive_tasks: metrics.num_alive_tasks(),
+            global_queue_depth: metrics.global_queue_depth(),
+        }
+    });
+    ProfileSnapshot {
+        taken_at: Utc::now(),
+        resident_mb: process_memory_mb(),
+        allocation: allocation_stats(),
+        runtime,
+    }
+}
+
+/// What the heap holds, if the [`CountingAllocator`] is the global allocator
+pub fn allocation_stats() -> Option<AllocationStats> {
+    #[cfg(feature = "profiling")]
+    {
+        allocator::stats()
+    }
+    #[cfg(not(feature = "profiling"))]
+    {
+        None
+    }
+}
+
+/// Take a snapshot of the server at `target`, such as
+/// `http://127.0.0.1:3000`, as the admin whose access token is `token`
+pub async fn fetch_profile(target: &str, token: Option<&str>) -> Result<ProfileSnapshot, String> {
+    let url = format!("{}/api/debug/profile", target.trim_end_matches('/'));
+    let mut request = reqwest::Client::new().get(&url);
+    if let Some(token) = token {
+        request = request.bearer_auth(token);
+    }
+    let response = request.send().await.map_err(|e| e.to_string())?;
+    let status = response.status();
+    let body: serde_json::Value = response.json().await.map_err(|e| format!("Invalid response from {}: {}", url, e))?;
+    if !status.is_success() {
+        let message = body["error"]["message"].as_str().unwrap_or_default();
+        return Err(format!("{} answered {}: {}", url, status, message));
+    }
+    serde_json::from_value(body["data"].clone()).map_err(|e| format!("Invalid profile from {}: {}", url, e))
+}
+
+#[cfg(feature = "profiling")]
+mod allocator {
+    use std::alloc::{GlobalAlloc, Layout, System};
+    use std::sync::atomic::{AtomicU64, Ordering};
+
+    use super::AllocationStats;
+
+    static ALLOCATIONS: AtomicU64 = AtomicU64::new(0);
+    static DEALLOCATIONS: AtomicU64 = AtomicU64::new(0);
+    static REALLOCATIONS: AtomicU64 = AtomicU64::new(0);
+    static ALLOCATED_BYTES: AtomicU64 = AtomicU64::new(0);
+    static FREED_BYTES: AtomicU64 = AtomicU64::new(0);
+    static PEAK_BYTES: AtomicU64 = AtomicU64::new(0);
+
+    /// The system allocator, counting what passes through it
+    ///
+    /// Install it in a binary with
+    /// `#[global_allocator] static ALLOCATOR: CountingAllocator = CountingAllocator;`.
+    #[derive(Debug, Clone, Copy, Default)]
+    pub struct CountingAllocator;
+
+    impl CountingAllocator {
+        /// Count `size` bytes allocated, raising the peak if need be
+        fn allocated(size: usize) {
+            let size = size as u64;
+            let allocated = ALLOCATED_BYTES.fetch_add(size, Ordering::Relaxed) + size;
+            let live = allocated.saturating_sub(FREED_BYTES.load(Ordering::Relaxed));
+            PEAK_BYTES.fetch_max(live, Ordering::Relaxed);
+        }
+
+        /// Count `size` bytes freed
+        fn freed(size: usize) {
+            FREED_BYTES.fetch_add(size as u64, Ordering::Relaxed);
+        }
+    }
+
+    // SAFETY: every call is passed on to the system allocator unchanged;
+    // the counters only observe it
+    unsafe impl GlobalAlloc for CountingAllocator {
+        unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+            let ptr = System.alloc(layout);
+            if !ptr.is_null() {
+                ALLOCATIONS.fetch_add(1, Ordering::Relaxed);
+                Self::allocated(layout.size());
+            }
+            ptr
+        }
+
+        unsafe fn alloc_zeroed(&self, layout: Layout) -> *mut u8 {
+            let ptr = System.alloc_zeroed(layout);
+            if !ptr.is_null() {
+                ALLOCATIONS.fetch_add(1, Ordering::Relaxed);
+                Self::allocated(layout.size());
+            }
+            ptr
+        }
+
+        unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+            System.dealloc(ptr, layout);
+            DEALLOCATIONS.fetch_add(1, Ordering::Relaxed);
+            Self::freed(layout.size());
+        }
+
+        unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+            let new_ptr = System.realloc(ptr, layout, new_size);
+            if !new_ptr.is_null() {
+                REALLOCATIONS.fetch_add(1, Ordering::Relaxed);
+                Self::freed(layout.size());
+                Self::allocated(new_size);
+            }
+            new_ptr
+        }
+    }
+
+    /// The counts so far, unless the allocator was never used
+    pub(super) fn stats() -> Option<AllocationStats> {
+        let allocations = ALLOCATIONS.load(Ordering::Relaxed);
+        if allocations == 0 {
+            return None;
+        }
+        let deallocations = DEALLOCATIONS.load(Ordering::Relaxed);
+        let allocated_bytes = ALLOCATED_BYTES.load(Ordering::Relaxed);
+        let freed_bytes = FREED_BYTES.load(Ordering::Relaxed);
+        Some(AllocationStats {
+            allocations,
+            deallocations,
+            reallocations: REALLOCATIONS.load(Ordering::Relaxed),
+            live_allocations: allocations.saturating_sub(deallocations),
+            allocated_bytes,
+            freed_bytes,
+            live_bytes: allocated_bytes.saturating_sub(freed_bytes),
+            peak_bytes: PEAK_BYTES.load(Ordering::Relaxed),
+        })
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use super::*;
+
+        #[test]
+        fn test_allocations_are_counted() {
+            let layout = Layout::from_size_align(4096, 8).unwrap();
+            let before = ALLOCATED_BYTES.load(Ordering::Relaxed);
+            unsafe {
+                let ptr = CountingAllocator.alloc(layout);
+                assert!(!ptr.is_null());
+                let ptr = CountingAllocator.realloc(ptr, layout, 8192);
+                CountingAllocator.dealloc(ptr, Layout::from_size_align(8192, 8).unwrap());
+            }
+            let stats = stats().unwrap();
+            assert!(stats.allocations >= 1 && stats.reallocations >= 1 && stats.deallocations >= 1);
+            assert!(ALLOCATED_BYTES.load(Ordering::Relaxed) >= before + 4096 + 8192);
+            assert!(stats.peak_bytes >= 8192);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+    async fn test_snapshots_report_the_runtime() {
+        let (stop, stopped) = tokio::sync::oneshot::channel::<()>();
+        let waiting = tokio::spawn(stopped);
+
+        let profile = snapshot();
+        let runtime = profile.runtime.unwrap();
+        assert_eq!(runtime.workers, 2);
+        assert!(runtime.alive_tasks >= 1);
+        assert!(profile.resident_mb > 0);
+        // The test binary uses the system allocator
+        #[cfg(not(feature = "profiling"))]
+        assert_eq!(profile.allocation, None);
+
+        stop.send(()).unwrap();
+        waiting.await.unwrap().unwrap();
+    }
+}
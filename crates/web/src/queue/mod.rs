@@ -0,0 +1,13 @@
+//! Persistent job queue surviving restarts.
+//!
+//! Queued work is written to the database before it runs. A worker leases an
+//! entry and renews the lease while the entry runs; if the worker dies, the
+//! lease expires and the entry becomes an orphan. Orphans are recovered when
+//! the server starts and by the leader afterwards, and are queued again or
+//! failed per the configured [`OrphanPolicy`].
+
+pub mod store;
+pub mod worker;
+
+pub use store::{JobQueueStore, MemoryJobQueueStore, OrphanPolicy, QueueEntry, QueueStatus, SqlJobQueueStore};
+pub use worker::{JobHandler, JobQueue};
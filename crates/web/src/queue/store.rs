@@ -0,0 +1,495 @@
+//! Job queue storage backends.
+
+use std::time::Duration;
+
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use sqlx::sqlite::SqliteRow;
+use sqlx::{Row, SqlitePool};
+use tokio::sync::Mutex;
+use uuid::Uuid;
+
+use crate::api::error::AppError;
+use crate::db::{timestamp, InstrumentedPool};
+
+/// State of a queue entry
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum QueueStatus {
+    /// Waiting for a worker
+    Pending,
+    /// Leased by a worker
+    Running,
+    /// Ran to the end
+    Completed,
+    /// Failed, or gave up after its worker was lost
+    Failed,
+}
+
+impl QueueStatus {
+    /// The status as stored, e.g. `pending`
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Pending => "pending",
+            Self::Running => "running",
+            Self::Completed => "completed",
+            Self::Failed => "failed",
+        }
+    }
+
+    fn parse(status: &str) -> Result<Self, AppError> {
+        match status {
+            "pending" => Ok(Self::Pending),
+            "running" => Ok(Self::Running),
+            "completed" => Ok(Self::Completed),
+            "failed" => Ok(Self::Failed),
+            other => Err(AppError::Internal(format!("Invalid queue entry status '{}'", other))),
+        }
+    }
+}
+
+/// What happens to a running entry whose worker stopped renewing its lease
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum OrphanPolicy {
+    /// Queue the entry again until it has used up its attempts, then fail it
+    #[default]
+    Requeue,
+    /// Fail the entry right away
+    Fail,
+}
+
+/// A unit of queued work
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct QueueEntry {
+    /// Entry ID
+    pub id: String,
+    /// Kind of work, naming the handler that runs it
+    pub kind: String,
+    /// User the work was queued for
+    pub user_id: String,
+    /// Handler-specific description of the work
+    pub payload: serde_json::Value,
+    /// Current state
+    pub status: QueueStatus,
+    /// Number of times the entry was leased
+    pub attempts: u32,
+    /// Worker holding or last holding the lease
+    pub worker: Option<String>,
+    /// When the lease expires unless renewed
+    pub lease_expires_at: Option<DateTime<Utc>>,
+    /// Why the entry failed or was re-queued
+    pub error: Option<String>,
+    /// When the entry was queued
+    pub created_at: DateTime<Utc>,
+    /// When the entry last changed
+    pub updated_at: DateTime<Utc>,
+}
+
+impl QueueEntry {
+    /// A new pending entry with a random ID
+    pub fn new(kind: &str, user_id: &str, payload: serde_json::Value) -> Self {
+        let now = Utc::now();
+        Self {
+            id: Uuid::new_v4().to_string(),
+            kind: kind.to_string(),
+            user_id: user_id.to_string(),
+            payload,
+            status: QueueStatus::Pending,
+            attempts: 0,
+            worker: None,
+            lease_expires_at: None,
+            error: None,
+            created_at: now,
+            updated_at: now,
+        }
+    }
+
+    /// Re-queues the entry if `policy` and its attempts allow, failing it otherwise
+    fn orphan(&mut self, policy: OrphanPolicy, max_attempts: u32, now: DateTime<Utc>) {
+        let worker = self.worker.as_deref().unwrap_or("unknown");
+        if policy == OrphanPolicy::Requeue && self.attempts < max_attempts {
+            self.status = QueueStatus::Pending;
+            self.error = Some(format!("Worker {} was lost; queued again", worker));
+        } else {
+            self.status = QueueStatus::Failed;
+            self.error = Some(format!("Worker {} was lost after {} attempt(s)", worker, self.attempts));
+        }
+        self.lease_expires_at = None;
+        self.updated_at = now;
+    }
+}
+
+/// Storage for queued work
+#[async_trait]
+pub trait JobQueueStore: Send + Sync {
+    /// Add a pending entry
+    async fn enqueue(&self, entry: &QueueEntry) -> Result<(), AppError>;
+
+    /// Lease the pending entry enqueued first to `worker` for `ttl`, if there
+    /// is one
+    async fn lease(&self, worker: &str, ttl: Duration) -> Result<Option<QueueEntry>, AppError>;
+
+    /// Extend the lease `worker` holds on an entry
+    ///
+    /// Returns false once the lease was lost, e.g. because the entry was
+    /// recovered as an orphan in the meantime.
+    async fn renew(&self, id: &str, worker: &str, ttl: Duration) -> Result<bool, AppError>;
+
+    /// Complete an entry leased by `worker`, or fail it with `error`
+    ///
+    /// Returns false if `worker` no longer holds the lease.
+    async fn finish(&self, id: &str, worker: &str, error: Option<&str>) -> Result<bool, AppError>;
+
+    /// Fail a pending entry before any worker leases it
+    async fn cancel(&self, id: &str, reason: &str) -> Result<bool, AppError>;
+
+    /// Recover running entries whose lease expired at or before `expired_before`
+    ///
+    /// Each orphan is re-queued or failed per `policy`; an entry leased
+    /// `max_attempts` times is always failed. Returns the recovered entries
+    /// as stored afterwards.
+    async fn reap(
+        &self,
+        expired_before: DateTime<Utc>,
+        policy: OrphanPolicy,
+        max_attempts: u32,
+    ) -> Result<Vec<QueueEntry>, AppError>;
+
+    /// Get an entry
+    async fn get(&self, id: &str) -> Result<Option<QueueEntry>, AppError>;
+}
+
+/// Converts a lease duration into a chrono duration
+fn lease_ttl(ttl: Duration) -> chrono::Duration {
+    chrono::Duration::from_std(ttl).unwrap_or_else(|_| chrono::Duration::seconds(30))
+}
+
+/// Reads an entry from a `job_queue` row
+fn entry_from_row(row: &SqliteRow) -> Result<QueueEntry, AppError> {
+    let payload: String = row.try_get("payload")?;
+    let attempts: i64 = row.try_get("attempts")?;
+    let lease_expires_at: Option<i64> = row.try_get("lease_expires_at")?;
+    Ok(QueueEntry {
+        id: row.try_get("id")?,
+        kind: row.try_get("kind")?,
+        user_id: row.try_get("user_id")?,
+        payload: serde_json::from_str(&payload)
+            .map_err(|e| AppError::Internal(format!("Invalid queue entry payload: {}", e)))?,
+        status: QueueStatus::parse(row.try_get("status")?)?,
+        attempts: u32::try_from(attempts).unwrap_or(0),
+        worker: row.try_get("worker")?,
+        lease_expires_at: lease_expires_at.map(timestamp).transpose()?,
+        error: row.try_get("error")?,
+        created_at: timestamp(row.try_get("created_at")?)?,
+        updated_at: timestamp(row.try_get("updated_at")?)?,
+    })
+}
+
+/// Job queue store backed by the `job_queue` table
+pub struct SqlJobQueueStore {
+    pool: InstrumentedPool,
+}
+
+impl SqlJobQueueStore {
+    /// Create a new SqlJobQueueStore
+    pub fn new(pool: SqlitePool) -> Self {
+        Self { pool: pool.into() }
+    }
+}
+
+#[async_trait]
+impl JobQueueStore for SqlJobQueueStore {
+    async fn enqueue(&self, entry: &QueueEntry) -> Result<(), AppError> {
+        sqlx::query(
+            "INSERT INTO job_queue (id, kind, user_id, payload, status, attempts, created_at, updated_at)
+             VALUES (?, ?, ?, ?, ?, 0, ?, ?)",
+        )
+        .bind(&entry.id)
+        .bind(&entry.kind)
+        .bind(&entry.user_id)
+        .bind(entry.payload.to_string())
+        .bind(QueueStatus::Pending.as_str())
+        .bind(entry.created_at.timestamp_millis())
+        .bind(entry.updated_at.timestamp_millis())
+        .execute(&self.pool)
+        .await?;
+        Ok(())
+    }
+
+    async fn lease(&self, worker: &str, ttl: Duration) -> Result<Option<QueueEntry>, AppError> {
+        loop {
+            // Rows get increasing rowids as they are inserted, which keeps
+            // entries enqueued within the same millisecond in order
+            let Some(id) = sqlx::query_scalar::<_, String>(
+                "SELECT id FROM job_queue WHERE status = 'pending' ORDER BY rowid LIMIT 1",
+            )
+            .fetch_optional(&self.pool)
+            .await?
+            else {
+                return Ok(None);
+            };
+
+            // Another instance may lease the same entry first; the status
+            // check lets only one of them have it
+            let now = Utc::now();
+            let leased = sqlx::query(
+                "UPDATE job_queue SET status = 'running', worker = ?, attempts = attempts + 1,
+                     lease_expires_at = ?, error = NULL, updated_at = ?
+                 WHERE id = ? AND status = 'pending'",
+            )
+            .bind(worker)
+            .bind((now + lease_ttl(ttl)).timestamp_millis())
+            .bind(now.timestamp_millis())
+            .bind(&id)
+            .execute(&self.pool)
+            .await?
+            .rows_affected();
+            if leased == 1 {
+                return self.get(&id).await;
+            }
+        }
+    }
+
+    async fn renew(&self, id: &str, worker: &str, ttl: Duration) -> Result<bool, AppError> {
+        let now = Utc::now();
+        let renewed = sqlx::query(
+            "UPDATE job_queue SET lease_expires_at = ?, updated_at = ?
+             WHERE id = ? AND worker = ? AND status = 'running'",
+        )
+        .bind((now + lease_ttl(ttl)).timestamp_millis())
+        .bind(now.timestamp_millis())
+        .bind(id)
+        .bind(worker)
+        .execute(&self.pool)
+        .await?
+        .rows_affected();
+        Ok(renewed == 1)
+    }
+
+    async fn finish(&self, id: &str, worker: &str, error: Option<&str>) -> Result<bool, AppError> {
+        let status = if error.is_some() { QueueStatus::Failed } else { QueueStatus::Completed };
+        let finished = sqlx::query(
+            "UPDATE job_queue SET status = ?, error = ?, lease_expires_at = NULL, updated_at = ?
+             WHERE id = ? AND worker = ? AND status = 'running'",
+        )
+        .bind(status.as_str())
+        .bind(error)
+        .bind(Utc::now().timestamp_millis())
+        .bind(id)
+        .bind(worker)
+        .execute(&self.pool)
+        .await?
+        .rows_affected();
+        Ok(finished == 1)
+    }
+
+    async fn cancel(&self, id: &str, reason: &str) -> Result<bool, AppError> {
+        let cancelled = sqlx::query(
+            "UPDATE job_queue SET status = 'failed', error = ?, updated_at = ? WHERE id = ? AND status = 'pending'",
+        )
+        .bind(reason)
+        .bind(Utc::now().timestamp_millis())
+        .bind(id)
+        .execute(&self.pool)
+        .await?
+        .rows_affected();
+        Ok(cancelled == 1)
+    }
+
+    async fn reap(
+        &self,
+        expired_before: DateTime<Utc>,
+        policy: OrphanPolicy,
+        max_attempts: u32,
+    ) -> Result<Vec<QueueEntry>, AppError> {
+        let mut tx = self.pool.begin().await?;
+        let rows = sqlx::query("SELECT * FROM job_queue WHERE status = 'running' AND lease_expires_at <= ?")
+            .bind(expired_before.timestamp_millis())
+            .fetch_all(&mut *tx)
+            .await?;
+
+        let now = Utc::now();
+        let mut recovered = Vec::with_capacity(rows.len());
+        for row in &rows {
+            let mut entry = entry_from_row(row)?;
+            entry.orphan(policy, max_attempts, now);
+            sqlx::query(
+                "UPDATE job_queue SET status = ?, error = ?, lease_expires_at = NULL, updated_at = ?
+                 WHERE id = ? AND status = 'running'",
+            )
+            .bind(entry.status.as_str())
+            .bind(&entry.error)
+            .bind(now.timestamp_millis())
+            .bind(&entry.id)
+            .execute(&mut *tx)
+            .await?;
+            recovered.push(entry);
+        }
+        tx.commit().await?;
+        Ok(recovered)
+    }
+
+    async fn get(&self, id: &str) -> Result<Option<QueueEntry>, AppError> {
+        sqlx::query("SELECT * FROM job_queue WHERE id = ?")
+            .bind(id)
+            .fetch_optional(&self.pool)
+            .await?
+            .as_ref()
+            .map(entry_from_row)
+            .transpose()
+    }
+}
+
+/// In-process job queue store for tests; entries do not survive a restart
+#[derive(Default)]
+pub struct MemoryJobQueueStore {
+    entries: Mutex<Vec<QueueEntry>>,
+}
+
+impl MemoryJobQueueStore {
+    /// Create a new MemoryJobQueueStore
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+#[async_trait]
+impl JobQueueStore for MemoryJobQueueStore {
+    async fn enqueue(&self, entry: &QueueEntry) -> Result<(), AppError> {
+        let mut entry = entry.clone();
+        entry.status = QueueStatus::Pending;
+        entry.attempts = 0;
+        self.entries.lock().await.push(entry);
+        Ok(())
+    }
+
+    async fn lease(&self, worker: &str, ttl: Duration) -> Result<Option<QueueEntry>, AppError> {
+        let now = Utc::now();
+        let mut entries = self.entries.lock().await;
+        let Some(entry) = entries.iter_mut().find(|entry| entry.status == QueueStatus::Pending) else {
+            return Ok(None);
+        };
+        entry.status = QueueStatus::Running;
+        entry.worker = Some(worker.to_string());
+        entry.attempts += 1;
+        entry.lease_expires_at = Some(now + lease_ttl(ttl));
+        entry.error = None;
+        entry.updated_at = now;
+        Ok(Some(entry.clone()))
+    }
+
+    async fn renew(&self, id: &str, worker: &str, ttl: Duration) -> Result<bool, AppError> {
+        let now = Utc::now();
+        let mut entries = self.entries.lock().await;
+        let Some(entry) = entries.iter_mut().find(|entry| {
+            entry.id == id && entry.status == QueueStatus::Running && entry.worker.as_deref() == Some(worker)
+        }) else {
+            return Ok(false);
+        };
+        entry.lease_expires_at = Some(now + lease_ttl(ttl));
+        entry.updated_at = now;
+        Ok(true)
+    }
+
+    async fn finish(&self, id: &str, worker: &str, error: Option<&str>) -> Result<bool, AppError> {
+        let mut entries = self.entries.lock().await;
+        let Some(entry) = entries.iter_mut().find(|entry| {
+            entry.id == id && entry.status == QueueStatus::Running && entry.worker.as_deref() == Some(worker)
+        }) else {
+            return Ok(false);
+        };
+        entry.status = if error.is_some() { QueueStatus::Failed } else { QueueStatus::Completed };
+        entry.error = error.map(str::to_string);
+        entry.lease_expires_at = None;
+        entry.updated_at = Utc::now();
+        Ok(true)
+    }
+
+    async fn cancel(&self, id: &str, reason: &str) -> Result<bool, AppError> {
+        let mut entries = self.entries.lock().await;
+        let Some(entry) = entries
+            .iter_mut()
+            .find(|entry| entry.id == id && entry.status == QueueStatus::Pending)
+        else {
+            return Ok(false);
+        };
+        entry.status = QueueStatus::Failed;
+        entry.error = Some(reason.to_string());
+        entry.updated_at = Utc::now();
+        Ok(true)
+    }
+
+    async fn reap(
+        &self,
+        expired_before: DateTime<Utc>,
+        policy: OrphanPolicy,
+        max_attempts: u32,
+    ) -> Result<Vec<QueueEntry>, AppError> {
+        let now = Utc::now();
+        let mut entries = self.entries.lock().await;
+        Ok(entries
+            .iter_mut()
+            .filter(|entry| {
+                entry.status == QueueStatus::Running
+                    && entry.lease_expires_at.is_some_and(|expires| expires <= expired_before)
+            })
+            .map(|entry| {
+                entry.orphan(policy, max_attempts, now);
+                entry.clone()
+            })
+            .collect())
+    }
+
+    async fn get(&self, id: &str) -> Result<Option<QueueEntry>, AppError> {
+        Ok(self.entries.lock().await.iter().find(|entry| entry.id == id).cloned())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_helpers::migrated_pool;
+
+    #[tokio::test]
+    async fn test_sql_queue_recovers_orphans_per_policy() {
+        let pool = migrated_pool().await;
+        let store = SqlJobQueueStore::new(pool);
+        let ttl = Duration::from_secs(30);
+
+        let first = QueueEntry::new("command", "alice", serde_json::json!({"command": "echo"}));
+        store.enqueue(&first).await.unwrap();
+        // Entries enqueued within the same millisecond are leased in order
+        let mut second = QueueEntry::new("command", "alice", serde_json::json!({"command": "ls"}));
+        second.created_at = first.created_at;
+        store.enqueue(&second).await.unwrap();
+
+        let leased = store.lease("a", ttl).await.unwrap().unwrap();
+        assert_eq!((leased.id.as_str(), leased.status, leased.attempts), (first.id.as_str(), QueueStatus::Running, 1));
+        assert!(store.renew(&first.id, "a", ttl).await.unwrap());
+        assert!(!store.renew(&first.id, "b", ttl).await.unwrap());
+
+        // Leases still valid are left alone
+        assert!(store.reap(Utc::now(), OrphanPolicy::Requeue, 2).await.unwrap().is_empty());
+
+        // Worker a died: its entry is queued again and leased by b
+        let far = Utc::now() + chrono::Duration::minutes(1);
+        let recovered = store.reap(far, OrphanPolicy::Requeue, 2).await.unwrap();
+        assert_eq!(recovered.len(), 1);
+        assert_eq!(store.get(&first.id).await.unwrap().unwrap().status, QueueStatus::Pending);
+        assert!(!store.finish(&first.id, "a", None).await.unwrap());
+        let leased = store.lease("b", ttl).await.unwrap().unwrap();
+        assert_eq!((leased.id.as_str(), leased.attempts), (first.id.as_str(), 2));
+
+        // Out of attempts, the next orphan fails
+        let recovered = store.reap(far, OrphanPolicy::Requeue, 2).await.unwrap();
+        assert_eq!(recovered[0].status, QueueStatus::Failed);
+
+        let leased = store.lease("b", ttl).await.unwrap().unwrap();
+        assert_eq!(leased.id, second.id);
+        let recovered = store.reap(far, OrphanPolicy::Fail, 2).await.unwrap();
+        assert_eq!((recovered[0].status, recovered[0].attempts), (QueueStatus::Failed, 1));
+        assert!(store.lease("b", ttl).await.unwrap().is_none());
+    }
+}
@@ -0,0 +1,386 @@
+//! Workers leasing and running queued entries.
+
+use std::collections::HashMap;
+use std::sync::{Arc, RwLock};
+use std::time::Duration;
+
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use squirrel_app::supervisor::{RestartPolicy, Supervisor};
+use squirrel_monitoring::watchdog::{ExecutionPool, RunningExecution};
+use tokio::sync::watch;
+use tokio::task::JoinHandle;
+use tracing::{info, warn};
+use uuid::Uuid;
+
+use super::store::{JobQueueStore, QueueEntry, QueueStatus};
+use crate::api::error::AppError;
+use crate::config::JobQueueConfig;
+use crate::leader::LeaderElector;
+
+/// Runs the entries of one kind
+#[async_trait]
+pub trait JobHandler: Send + Sync {
+    /// Run an entry to the end; an error fails it
+    ///
+    /// An entry re-queued after its worker was lost runs again, so handlers
+    /// should tolerate running the same entry more than once.
+    async fn run(&self, entry: &QueueEntry) -> Result<(), String>;
+
+    /// Called when an orphaned entry is failed instead of re-queued
+    async fn abandoned(&self, _entry: &QueueEntry) {}
+
+    /// Called when a running entry was cancelled and failed with `reason`
+    async fn cancelled(&self, _entry: &QueueEntry, _reason: &str) {}
+}
+
+/// Priority of running entries under memory pressure, the default priority
+/// of workflow runs
+const ENTRY_PRIORITY: i32 = 0;
+
+/// An entry a worker of this instance is running
+struct RunningEntry {
+    execution: RunningExecution,
+    cancel: watch::Sender<Option<String>>,
+}
+
+/// Persistent queue of work, run by the workers of every instance
+///
+/// Entries are leased for the configured lease duration and the lease is
+/// renewed while the entry runs, so an entry is only recovered once the
+/// worker running it stopped renewing. The entries running on this instance
+/// form a pool the memory watchdog cancels from.
+pub struct JobQueue {
+    store: Arc<dyn JobQueueStore>,
+    config: JobQueueConfig,
+    instance_id: String,
+    handlers: RwLock<HashMap<String, Arc<dyn JobHandler>>>,
+    running: RwLock<HashMap<String, RunningEntry>>,
+}
+
+impl JobQueue {
+    /// Create a new JobQueue with a random instance ID
+    pub fn new(store: Arc<dyn JobQueueStore>, config: JobQueueConfig) -> Self {
+        Self {
+            store,
+            config,
+            instance_id: Uuid::new_v4().to_string(),
+            handlers: RwLock::new(HashMap::new()),
+            running: RwLock::new(HashMap::new()),
+        }
+    }
+
+    /// Register the handler running entries of `kind`
+    pub fn register(&self, kind: &str, handler: Arc<dyn JobHandler>) {
+        if let Ok(mut handlers) = self.handlers.write() {
+            handlers.insert(kind.to_string(), handler);
+        }
+    }
+
+    fn handler(&self, kind: &str) -> Option<Arc<dyn JobHandler>> {
+        self.handlers.read().ok()?.get(kind).cloned()
+    }
+
+    fn lease_duration(&self) -> Duration {
+        Duration::from_secs(self.config.lease_duration_secs.max(1))
+    }
+
+    /// Queue an entry to run on the next free worker
+    pub async fn enqueue(&self, entry: &QueueEntry) -> Result<(), AppError> {
+        self.store.enqueue(entry).await
+    }
+
+    /// Get an entry
+    pub async fn get(&self, id: &str) -> Result<Option<QueueEntry>, AppError> {
+        self.store.get(id).await
+    }
+
+    /// Drop an entry no worker has leased yet; returns false if it already runs
+    pub async fn cancel(&self, id: &str) -> Result<bool, AppError> {
+        self.store.cancel(id, "Cancelled before it ran").await
+    }
+
+    /// Recover orphaned entries, re-queuing or failing them per policy
+    ///
+    /// With `every_running`, every running entry is an orphan, as when the
+    /// only instance has just started and cannot have leased any yet;
+    /// otherwise only entries whose lease has expired are.
+    pub async fn recover(&self, every_running: bool) -> Result<Vec<QueueEntry>, AppError> {
+        let expired_before = if every_running { DateTime::<Utc>::MAX_UTC } else { Utc::now() };
+        let recovered = self
+            .store
+            .reap(expired_before, self.config.orphan_policy, self.config.max_attempts)
+            .await?;
+        for entry in &recovered {
+            warn!(
+                "Recovered orphaned {} entry {}: {}",
+                entry.kind,
+                entry.id,
+                entry.error.as_deref().unwrap_or_default()
+            );
+            if entry.status == QueueStatus::Failed {
+                if let Some(handler) = self.handler(&entry.kind) {
+                    handler.abandoned(entry).await;
+                }
+            }
+        }
+        Ok(recovered)
+    }
+
+    /// Lease the next pending entry to `worker` and run it
+    ///
+    /// Returns false if the queue was empty.
+    pub async fn run_next(&self, worker: &str) -> Result<bool, AppError> {
+        let ttl = self.lease_duration();
+        let Some(entry) = self.store.lease(worker, ttl).await? else {
+            return Ok(false);
+        };
+        let Some(handler) = self.handler(&entry.kind) else {
+            let error = format!("No handler for {} entries", entry.kind);
+            self.store.finish(&entry.id, worker, Some(&error)).await?;
+            return Ok(true);
+        };
+
+        let (cancel, mut cancellation) = watch::channel(None::<String>);
+        self.track(&entry, cancel);
+        let mut cancelled = None;
+        let outcome = {
+            let run = handler.run(&entry);
+            let cancellation = async {
+                cancellation.wait_for(Option::is_some).await.ok().and_then(|reason| reason.clone())
+            };
+            tokio::pin!(run, cancellation);
+            let mut renewals = tokio::time::interval(ttl / 3);
+            renewals.tick().await;
+            loop {
+                tokio::select! {
+                    outcome = &mut run => break Some(outcome),
+                    reason = &mut cancellation => {
+                        let reason = reason.unwrap_or_default();
+                        warn!("Cancelled {} entry {}: {}", entry.kind, entry.id, reason);
+                        cancelled = Some(reason.clone());
+                        break Some(Err(reason));
+                    }
+                    _ = renewals.tick() => match self.store.renew(&entry.id, worker, ttl).await {
+                        Ok(true) => {}
+                        Ok(false) => {
+                            warn!("Lost the lease on {} entry {}; stopping it", entry.kind, entry.id);
+                            break None;
+                        }
+                        Err(e) => warn!("Failed to renew the lease on entry {}: {}", entry.id, e),
+                    },
+                }
+            }
+        };
+        if let Ok(mut running) = self.running.write() {
+            running.remove(&entry.id);
+        }
+        let Some(outcome) = outcome else {
+            return Ok(true);
+        };
+
+        if !self.store.finish(&entry.id, worker, outcome.as_ref().err().map(String::as_str)).await? {
+            warn!("Lost the lease on {} entry {} before it finished", entry.kind, entry.id);
+        }
+        if let Some(reason) = cancelled {
+            handler.cancelled(&entry, &reason).await;
+        }
+        Ok(true)
+    }
+
+    /// Add an entry a worker started to the running entries
+    fn track(&self, entry: &QueueEntry, cancel: watch::Sender<Option<String>>) {
+        let execution = RunningExecution {
+            id: entry.id.clone(),
+            priority: ENTRY_PRIORITY,
+            started_at: Utc::now(),
+            description: format!("{} job of {}", entry.kind, entry.user_id),
+        };
+        if let Ok(mut running) = self.running.write() {
+            running.insert(entry.id.clone(), RunningEntry { execution, cancel });
+        }
+    }
+
+    /// Supervise the configured number of workers
+    pub fn supervise_workers(self: &Arc<Self>, supervisor: &Supervisor) {
+        let poll_interval = Duration::from_millis(self.config.poll_interval_ms.max(10));
+        for n in 0..self.config.workers.max(1) {
+            let queue = self.clone();
+            let worker = format!("{}-{}", self.instance_id, n);
+            supervisor.spawn(format!("queue.worker-{}", n), RestartPolicy::default(), move |shutdown| {
+                let queue = queue.clone();
+                let worker = worker.clone();
+                async move {
+                    while !shutdown.is_requested() {
+                        match queue.run_next(&worker).await {
+                            Ok(true) => continue,
+                            Ok(false) => {}
+                            Err(e) => warn!("Job queue worker {} failed: {}", worker, e),
+                        }
+                        shutdown.sleep(poll_interval).await;
+                    }
+                    Ok(())
+                }
+            });
+        }
+    }
+
+    /// Look for orphans periodically while this instance is the leader
+    pub fn spawn_reaper(self: &Arc<Self>, leader: &Arc<LeaderElector>) -> JoinHandle<()> {
+        let queue = self.clone();
+        leader.spawn_leader_task("job-queue-reaper", move || {
+            let queue = queue.clone();
+            async move {
+                let period = Duration::from_secs(queue.config.reap_interval_secs.max(1));
+                let mut interval = tokio::time::interval(period);
+                loop {
+                    interval.tick().await;
+                    match queue.recover(false).await {
+                        Ok(recovered) if !recovered.is_empty() => {
+                            info!("Recovered {} orphaned job queue entries", recovered.len());
+                        }
+                        Ok(_) => {}
+                        Err(e) => warn!("Failed to recover orphaned job queue entries: {}", e),
+                    }
+                }
+            }
+        })
+    }
+}
+
+impl ExecutionPool for JobQueue {
+    fn running(&self) -> Vec<RunningExecution> {
+        let Ok(running) = self.running.read() else {
+            return Vec::new();
+        };
+        running
+            .values()
+            .filter(|entry| entry.cancel.borrow().is_none())
+            .map(|entry| entry.execution.clone())
+            .collect()
+    }
+
+    fn cancel(&self, id: &str, reason: &str) -> bool {
+        let Ok(running) = self.running.read() else {
+            return false;
+        };
+        running.get(id).is_some_and(|entry| {
+            entry.cancel.send_if_modified(|current| {
+                if current.is_some() {
+                    return false;
+                }
+                *current = Some(reason.to_string());
+                true
+            })
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::queue::{MemoryJobQueueStore, OrphanPolicy};
+    use std::sync::Mutex;
+
+    /// Handler recording the entries it ran, abandoned and saw cancelled
+    #[derive(Default)]
+    struct Recorder {
+        ran: Mutex<Vec<String>>,
+        abandoned: Mutex<Vec<String>>,
+        cancelled: Mutex<Vec<String>>,
+        started: tokio::sync::Notify,
+    }
+
+    #[async_trait]
+    impl JobHandler for Recorder {
+        async fn run(&self, entry: &QueueEntry) -> Result<(), String> {
+            self.ran.lock().unwrap().push(entry.id.clone());
+            self.started.notify_one();
+            if entry.payload["block"] == true {
+                std::future::pending::<()>().await;
+            }
+            match entry.payload["fail"].as_bool() {
+                Some(true) => Err("asked to fail".to_string()),
+                _ => Ok(()),
+            }
+        }
+
+        async fn abandoned(&self, entry: &QueueEntry) {
+            self.abandoned.lock().unwrap().push(entry.id.clone());
+        }
+
+        async fn cancelled(&self, entry: &QueueEntry, reason: &str) {
+            self.cancelled.lock().unwrap().push(format!("{}: {}", entry.id, reason));
+        }
+    }
+
+    #[tokio::test]
+    async fn test_entries_left_running_are_recovered_on_startup() {
+        let store = Arc::new(MemoryJobQueueStore::new());
+        let config = JobQueueConfig {
+            max_attempts: 2,
+            orphan_policy: OrphanPolicy::Requeue,
+            ..JobQueueConfig::default()
+        };
+
+        // A previous instance leased both entries and died
+        let retried = QueueEntry::new("test", "alice", serde_json::json!({}));
+        let failing = QueueEntry::new("test", "alice", serde_json::json!({"fail": true}));
+        store.enqueue(&retried).await.unwrap();
+        store.enqueue(&failing).await.unwrap();
+        let ttl = Duration::from_secs(30);
+        store.lease("dead", ttl).await.unwrap();
+        store.lease("dead", ttl).await.unwrap();
+        assert!(store.reap(Utc::now(), OrphanPolicy::Requeue, 1).await.unwrap().is_empty());
+
+        let queue = JobQueue::new(store.clone(), config);
+        let recorder = Arc::new(Recorder::default());
+        queue.register("test", recorder.clone());
+        assert_eq!(queue.recover(true).await.unwrap().len(), 2);
+
+        assert!(queue.run_next("w").await.unwrap());
+        assert!(queue.run_next("w").await.unwrap());
+        assert!(!queue.run_next("w").await.unwrap());
+        assert_eq!(*recorder.ran.lock().unwrap(), vec![retried.id.clone(), failing.id.clone()]);
+        assert_eq!(queue.get(&retried.id).await.unwrap().unwrap().status, QueueStatus::Completed);
+        let failed = queue.get(&failing.id).await.unwrap().unwrap();
+        assert_eq!((failed.status, failed.error.as_deref()), (QueueStatus::Failed, Some("asked to fail")));
+
+        // Out of attempts, an orphan is failed and its handler told
+        let last = QueueEntry::new("test", "alice", serde_json::json!({}));
+        queue.enqueue(&last).await.unwrap();
+        store.lease("dead", ttl).await.unwrap();
+        store.reap(DateTime::<Utc>::MAX_UTC, OrphanPolicy::Requeue, 2).await.unwrap();
+        store.lease("dead", ttl).await.unwrap();
+        let recovered = queue.recover(true).await.unwrap();
+        assert_eq!(recovered[0].status, QueueStatus::Failed);
+        assert_eq!(*recorder.abandoned.lock().unwrap(), vec![last.id.clone()]);
+        assert!(!queue.cancel(&last.id).await.unwrap());
+    }
+
+    #[tokio::test]
+    async fn test_running_entries_are_cancelled_through_the_pool() {
+        let queue = Arc::new(JobQueue::new(Arc::new(MemoryJobQueueStore::new()), JobQueueConfig::default()));
+        let recorder = Arc::new(Recorder::default());
+        queue.register("test", recorder.clone());
+        let entry = QueueEntry::new("test", "alice", serde_json::json!({"block": true}));
+        queue.enqueue(&entry).await.unwrap();
+
+        let worker = tokio::spawn({
+            let queue = queue.clone();
+            async move { queue.run_next("w").await }
+        });
+        recorder.started.notified().await;
+        let running = queue.running();
+        assert_eq!(running.len(), 1);
+        assert_eq!((running[0].id.as_str(), running[0].description.as_str()), (entry.id.as_str(), "test job of alice"));
+
+        assert!(ExecutionPool::cancel(queue.as_ref(), &entry.id, "memory pressure"));
+        assert!(!ExecutionPool::cancel(queue.as_ref(), &entry.id, "again"));
+        assert!(worker.await.unwrap().unwrap());
+        assert!(queue.running().is_empty());
+        let cancelled = queue.get(&entry.id).await.unwrap().unwrap();
+        assert_eq!((cancelled.status, cancelled.error.as_deref()), (QueueStatus::Failed, Some("memory pressure")));
+        assert_eq!(*recorder.cancelled.lock().unwrap(), vec![format!("{}: memory pressure", entry.id)]);
+    }
+}
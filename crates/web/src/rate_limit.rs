@@ -0,0 +1,152 @@
+//! Request rate limits.
+//!
+//! [`RateLimiter`] keeps a token bucket per client address. The limits come
+//! from the [`RateLimitConfig`] of the configuration serving each request,
+//! which [`crate::rollout`] picks, so a canaried configuration can try new
+//! limits on part of the traffic before they apply to all of it.
+
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use axum::{
+    extract::State,
+    http::{header, HeaderValue, Request},
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+
+use crate::api::error::AppError;
+use crate::config::RateLimitConfig;
+use crate::rollout::ServedConfig;
+
+/// Buckets kept before idle ones are dropped
+const MAX_BUCKETS: usize = 10_000;
+
+/// Requests left to a client, as of `updated`
+#[derive(Debug, Clone, Copy)]
+struct Bucket {
+    tokens: f64,
+    updated: Instant,
+}
+
+/// Token buckets of the clients seen recently
+#[derive(Debug, Default)]
+pub struct RateLimiter {
+    buckets: Mutex<HashMap<String, Bucket>>,
+}
+
+impl RateLimiter {
+    /// Create a limiter without any buckets
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Take one request of `client` out of its bucket at `now`
+    ///
+    /// Returns how long the client has to wait if its bucket is empty.
+    pub fn check(&self, config: &RateLimitConfig, client: &str, now: Instant) -> Result<(), Duration> {
+        let Some(per_minute) = config.requests_per_minute.filter(|rate| *rate > 0) else {
+            return Ok(());
+        };
+        let capacity = f64::from(config.burst.unwrap_or(per_minute).max(1));
+        let per_second = f64::from(per_minute) / 60.0;
+
+        let mut buckets = self
+            .buckets
+            .lock()
+            .unwrap_or_else(std::sync::PoisonError::into_inner);
+        if buckets.len() >= MAX_BUCKETS {
+            // A bucket idle long enough to refill completely is the same as none
+            let refill = Duration::from_secs_f64(capacity / per_second);
+            buckets.retain(|_, bucket| now.saturating_duration_since(bucket.updated) < refill);
+        }
+        let bucket = buckets.entry(client.to_string()).or_insert(Bucket {
+            tokens: capacity,
+            updated: now,
+        });
+        let elapsed = now.saturating_duration_since(bucket.updated).as_secs_f64();
+        bucket.tokens = (bucket.tokens + elapsed * per_second).min(capacity);
+        bucket.updated = now;
+        if bucket.tokens >= 1.0 {
+            bucket.tokens -= 1.0;
+            Ok(())
+        } else {
+            Err(Duration::from_secs_f64((1.0 - bucket.tokens) / per_second))
+        }
+    }
+}
+
+/// Whether requests for `path` are never limited
+fn is_exempt(config: &RateLimitConfig, path: &str) -> bool {
+    config.exempt_paths.iter().any(|prefix| {
+        let prefix = prefix.trim_end_matches('/');
+        path == prefix || path.strip_prefix(prefix).is_some_and(|rest| rest.starts_with('/'))
+    })
+}
+
+/// Refuse requests of clients that exceeded the rate limit
+///
+/// The limit is that of the configuration the rollout layer chose for the
+/// request, so this layer must run inside it; requests it has not seen are
+/// not limited. Refused requests get a `Retry-After` header.
+pub async fn limit_requests<B>(
+    State(limiter): State<Arc<RateLimiter>>,
+    req: Request<B>,
+    next: Next<B>,
+) -> Response {
+    let Some(served) = req.extensions().get::<ServedConfig>() else {
+        return next.run(req).await;
+    };
+    let config = &served.config.rate_limit;
+    if is_exempt(config, req.uri().path()) {
+        return next.run(req).await;
+    }
+    let client = served
+        .client
+        .map_or_else(|| "unknown".to_string(), |ip| ip.to_string());
+    match limiter.check(config, &client, Instant::now()) {
+        Ok(()) => next.run(req).await,
+        Err(wait) => {
+            let secs = (wait.as_secs_f64().ceil() as u64).max(1);
+            let mut response = AppError::RateLimitExceeded(format!(
+                "Too many requests; try again in {secs} seconds"
+            ))
+            .into_response();
+            response
+                .headers_mut()
+                .insert(header::RETRY_AFTER, HeaderValue::from(secs));
+            response
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_buckets_refill_at_the_configured_rate() {
+        let config = RateLimitConfig {
+            requests_per_minute: Some(60),
+            burst: Some(2),
+            ..RateLimitConfig::default()
+        };
+        let limiter = RateLimiter::new();
+        let start = Instant::now();
+
+        assert!(limiter.check(&config, "10.0.0.1", start).is_ok());
+        assert!(limiter.check(&config, "10.0.0.1", start).is_ok());
+        let wait = limiter.check(&config, "10.0.0.1", start).unwrap_err();
+        assert_eq!(wait, Duration::from_secs(1));
+        // Other clients have their own bucket
+        assert!(limiter.check(&config, "10.0.0.2", start).is_ok());
+
+        assert!(limiter.check(&config, "10.0.0.1", start + Duration::from_secs(1)).is_ok());
+        assert!(limiter.check(&config, "10.0.0.1", start + Duration::from_secs(1)).is_err());
+
+        assert!(limiter.check(&RateLimitConfig::default(), "10.0.0.1", start).is_ok());
+        assert!(is_exempt(&config, "/health"));
+        assert!(!is_exempt(&config, "/healthz"));
+    }
+}
@@ -0,0 +1,395 @@
+//! In-memory caches of frequent reads.
+//!
+//! A [`ReadCache`] keeps the most recently used values of one kind of entity
+//! for a while, so repeated reads skip the database or MCP. Each entry lives
+//! for the entity's `ttl_secs`; once the cache holds `capacity` entries, the
+//! least recently used one makes room. Writes going through this instance
+//! invalidate the entries they change; changes made by other instances show
+//! once the entry expires, so the TTL bounds how stale a read can be.
+//!
+//! Every cache counts its hits, misses and evictions. [`CacheMetrics`]
+//! reports them to the metric collector as `read_cache_hits_total`,
+//! `read_cache_misses_total` and `read_cache_evictions_total`, labeled with
+//! the cache name, and to `/api/admin/runtime`.
+
+use std::collections::{BTreeMap, HashMap};
+use std::future::Future;
+use std::hash::Hash;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex, PoisonError};
+use std::time::{Duration, Instant};
+
+use serde::{Deserialize, Serialize};
+use squirrel_app::supervisor::{RestartPolicy, Supervisor};
+use squirrel_commands::capabilities::CapabilityReport;
+use squirrel_monitoring::metrics::{Metric, MetricCollector, MetricType};
+use tracing::warn;
+
+use crate::api::commands::CommandDefinition;
+use crate::config::{CacheSettings, ReadCacheConfig};
+
+/// Hits, misses and evictions of a cache
+#[derive(Debug, Default)]
+struct CacheCounters {
+    hits: AtomicU64,
+    misses: AtomicU64,
+    evictions: AtomicU64,
+}
+
+impl CacheCounters {
+    /// The counters of the cache named `name`, as they are now
+    fn stats(&self, name: &str) -> CacheStats {
+        CacheStats {
+            name: name.to_string(),
+            hits: self.hits.load(Ordering::Relaxed),
+            misses: self.misses.load(Ordering::Relaxed),
+            evictions: self.evictions.load(Ordering::Relaxed),
+        }
+    }
+}
+
+/// Counters of a cache, as reported by `/api/admin/runtime`
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CacheStats {
+    /// Cache name, e.g. `users`
+    pub name: String,
+    /// Reads answered from the cache
+    pub hits: u64,
+    /// Reads that had to load the value
+    pub misses: u64,
+    /// Entries dropped to make room
+    pub evictions: u64,
+}
+
+/// A cached value
+struct Entry<V> {
+    value: V,
+    expires_at: Instant,
+    /// Position in the recency order
+    tick: u64,
+}
+
+/// Entries and their recency order
+struct Lru<K, V> {
+    entries: HashMap<K, Entry<V>>,
+    /// Keys by the tick they were last used at, oldest first
+    order: BTreeMap<u64, K>,
+    next_tick: u64,
+}
+
+impl<K: Clone + Eq + Hash, V> Lru<K, V> {
+    fn remove(&mut self, key: &K) -> Option<Entry<V>> {
+        let entry = self.entries.remove(key)?;
+        self.order.remove(&entry.tick);
+        Some(entry)
+    }
+
+    /// Mark `key` as just used
+    fn touch(&mut self, key: &K) {
+        let tick = self.next_tick;
+        self.next_tick += 1;
+        if let Some(entry) = self.entries.get_mut(key) {
+            self.order.remove(&entry.tick);
+            entry.tick = tick;
+            self.order.insert(tick, key.clone());
+        }
+    }
+}
+
+/// Least recently used values of one kind of entity, expiring after a TTL
+pub struct ReadCache<K, V> {
+    name: &'static str,
+    settings: CacheSettings,
+    lru: Mutex<Lru<K, V>>,
+    counters: Arc<CacheCounters>,
+}
+
+impl<K: Clone + Eq + Hash, V: Clone> ReadCache<K, V> {
+    /// Create a cache named `name`; it keeps nothing if `settings` turn it off
+    pub fn new(name: &'static str, settings: CacheSettings) -> Self {
+        Self {
+            name,
+            settings,
+            lru: Mutex::new(Lru {
+                entries: HashMap::new(),
+                order: BTreeMap::new(),
+                next_tick: 0,
+            }),
+            counters: Arc::new(CacheCounters::default()),
+        }
+    }
+
+    /// A cache keeping nothing
+    pub fn disabled(name: &'static str) -> Self {
+        Self::new(name, CacheSettings::disabled())
+    }
+
+    /// Name of the cache
+    pub fn name(&self) -> &'static str {
+        self.name
+    }
+
+    /// Whether the cache keeps anything
+    fn enabled(&self) -> bool {
+        self.settings.enabled && self.settings.capacity > 0
+    }
+
+    /// The value of `key`, if it is cached and has not expired
+    pub fn get(&self, key: &K) -> Option<V> {
+        if !self.enabled() {
+            return None;
+        }
+        let mut lru = self.lru.lock().unwrap_or_else(PoisonError::into_inner);
+        let fresh = lru.entries.get(key).map(|entry| entry.expires_at > Instant::now());
+        let value = match fresh {
+            Some(true) => {
+                lru.touch(key);
+                lru.entries.get(key).map(|entry| entry.value.clone())
+            }
+            Some(false) => {
+                lru.remove(key);
+                None
+            }
+            None => None,
+        };
+        let counter = if value.is_some() { &self.counters.hits } else { &self.counters.misses };
+        counter.fetch_add(1, Ordering::Relaxed);
+        value
+    }
+
+    /// Cache `value` under `key`, evicting the least recently used entry if
+    /// the cache is full
+    pub fn insert(&self, key: K, value: V) {
+        if !self.enabled() {
+            return;
+        }
+        let mut lru = self.lru.lock().unwrap_or_else(PoisonError::into_inner);
+        lru.remove(&key);
+        while lru.entries.len() >= self.settings.capacity {
+            let Some((_, oldest)) = lru.order.pop_first() else {
+                break;
+            };
+            lru.entries.remove(&oldest);
+            self.counters.evictions.fetch_add(1, Ordering::Relaxed);
+        }
+        let tick = lru.next_tick;
+        lru.next_tick += 1;
+        lru.order.insert(tick, key.clone());
+        lru.entries.insert(
+            key,
+            Entry {
+                value,
+                expires_at: Instant::now() + Duration::from_secs(self.settings.ttl_secs),
+                tick,
+            },
+        );
+    }
+
+    /// The value of `key`, loaded with `load` and cached if it is not cached
+    ///
+    /// Errors are not cached.
+    pub async fn get_or_load<E, Fut>(&self, key: K, load: impl FnOnce() -> Fut) -> Result<V, E>
+    where
+        Fut: Future<Output = Result<V, E>>,
+    {
+        if let Some(value) = self.get(&key) {
+            return Ok(value);
+        }
+        let value = load().await?;
+        self.insert(key, value.clone());
+        Ok(value)
+    }
+
+    /// Drop the entry of `key`, after a write changed it
+    pub fn invalidate(&self, key: &K) {
+        self.lru.lock().unwrap_or_else(PoisonError::into_inner).remove(key);
+    }
+
+    /// Drop every entry
+    pub fn clear(&self) {
+        let mut lru = self.lru.lock().unwrap_or_else(PoisonError::into_inner);
+        lru.entries.clear();
+        lru.order.clear();
+    }
+
+    /// Number of cached entries, including expired ones not yet dropped
+    pub fn len(&self) -> usize {
+        self.lru.lock().unwrap_or_else(PoisonError::into_inner).entries.len()
+    }
+
+    /// Whether nothing is cached
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Counters of the cache
+    pub fn stats(&self) -> CacheStats {
+        self.counters.stats(self.name)
+    }
+}
+
+/// A page of the command list: the definitions, total items and total pages
+pub type CommandPage = (Vec<CommandDefinition>, u64, u32);
+
+/// Caches of reads served by the API handlers, and the metrics of every
+/// read cache
+pub struct ReadCaches {
+    /// Pages of the command list, by page and page size
+    pub commands: ReadCache<(u32, u32), CommandPage>,
+    /// The capability report
+    pub capabilities: ReadCache<(), CapabilityReport>,
+    /// Counters of these caches and the ones registered with them
+    pub metrics: Arc<CacheMetrics>,
+}
+
+impl ReadCaches {
+    /// Create the caches with the settings of `config`
+    pub fn new(config: &ReadCacheConfig) -> Self {
+        let commands = ReadCache::new("commands", config.commands.clone());
+        let capabilities = ReadCache::new("capabilities", config.capabilities.clone());
+        let metrics = Arc::new(CacheMetrics::new());
+        metrics.register(&commands);
+        metrics.register(&capabilities);
+        Self {
+            commands,
+            capabilities,
+            metrics,
+        }
+    }
+}
+
+/// Reports the counters of caches to a metric collector
+pub struct CacheMetrics {
+    /// Counters of the registered caches, by cache name
+    caches: Mutex<Vec<(&'static str, Arc<CacheCounters>)>>,
+    /// Counters as last reported, by cache name
+    reported: Mutex<HashMap<&'static str, CacheStats>>,
+}
+
+impl CacheMetrics {
+    /// Create new CacheMetrics reporting no caches yet
+    pub fn new() -> Self {
+        Self {
+            caches: Mutex::new(Vec::new()),
+            reported: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Report the counters of `cache` too
+    pub fn register<K, V>(&self, cache: &ReadCache<K, V>) {
+        self.caches.lock().unwrap_or_else(PoisonError::into_inner).push((cache.name, cache.counters.clone()));
+    }
+
+    /// Counters of every registered cache
+    pub fn stats(&self) -> Vec<CacheStats> {
+        self.caches
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .iter()
+            .map(|(name, counters)| counters.stats(name))
+            .collect()
+    }
+
+    /// What the counters grew by since they were last reported
+    fn deltas(&self) -> Vec<Metric> {
+        let mut reported = self.reported.lock().unwrap_or_else(PoisonError::into_inner);
+        let mut metrics = Vec::new();
+        for (name, counters) in self.caches.lock().unwrap_or_else(PoisonError::into_inner).iter() {
+            let now = counters.stats(name);
+            let last = reported.insert(name, now.clone()).unwrap_or_default();
+            let labels = HashMap::from([("cache".to_string(), name.to_string())]);
+            for (metric, now, before) in [
+                ("read_cache_hits_total", now.hits, last.hits),
+                ("read_cache_misses_total", now.misses, last.misses),
+                ("read_cache_evictions_total", now.evictions, last.evictions),
+            ] {
+                if now > before {
+                    metrics.push(Metric::new(metric, (now - before) as f64, MetricType::Counter, labels.clone()));
+                }
+            }
+        }
+        metrics
+    }
+
+    /// Supervise the background task reporting the counters to `collector`
+    /// every `interval`
+    pub fn supervise_reporter(
+        self: &Arc<Self>,
+        supervisor: &Supervisor,
+        collector: Arc<dyn MetricCollector>,
+        interval: Duration,
+    ) {
+        let metrics = self.clone();
+        supervisor.spawn("cache.metrics", RestartPolicy::default(), move |shutdown| {
+            let metrics = metrics.clone();
+            let collector = collector.clone();
+            async move {
+                while !shutdown.sleep(interval.max(Duration::from_secs(1))).await {
+                    for metric in metrics.deltas() {
+                        if let Err(e) = collector.record_metric(metric).await {
+                            warn!("Failed to record cache metric: {}", e);
+                        }
+                    }
+                }
+                Ok(())
+            }
+        });
+    }
+}
+
+impl Default for CacheMetrics {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn settings(capacity: usize, ttl_secs: u64) -> CacheSettings {
+        CacheSettings { enabled: true, capacity, ttl_secs }
+    }
+
+    #[tokio::test]
+    async fn test_least_recently_used_entries_make_room() {
+        let cache = ReadCache::new("users", settings(2, 60));
+        let metrics = CacheMetrics::new();
+        metrics.register(&cache);
+
+        cache.insert("ada", 1);
+        cache.insert("grace", 2);
+        assert_eq!(cache.get(&"ada"), Some(1));
+        cache.insert("linus", 3);
+        assert_eq!(cache.get(&"grace"), None);
+        assert_eq!(cache.get(&"ada"), Some(1));
+
+        let loaded: Result<_, ()> = cache.get_or_load("grace", || async { Ok(4) }).await;
+        assert_eq!(loaded, Ok(4));
+        assert_eq!(cache.get(&"grace"), Some(4));
+        cache.invalidate(&"grace");
+        assert_eq!(cache.get(&"grace"), None);
+        let failed: Result<i32, &str> = cache.get_or_load("grace", || async { Err("gone") }).await;
+        assert_eq!(failed, Err("gone"));
+        assert_eq!(cache.get(&"grace"), None);
+
+        let stats = cache.stats();
+        assert_eq!((stats.hits, stats.misses, stats.evictions), (3, 5, 2));
+        let deltas = metrics.deltas();
+        assert_eq!(deltas.len(), 3);
+        assert!(metrics.deltas().is_empty());
+    }
+
+    #[test]
+    fn test_entries_expire_and_disabled_caches_keep_nothing() {
+        let cache = ReadCache::new("commands", settings(8, 0));
+        cache.insert(1, "page");
+        assert_eq!(cache.get(&1), None);
+        assert!(cache.is_empty());
+
+        let disabled = ReadCache::disabled("capabilities");
+        disabled.insert((), "report");
+        assert_eq!(disabled.get(&()), None);
+        assert_eq!(disabled.stats().misses, 0);
+    }
+}
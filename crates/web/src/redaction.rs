@@ -0,0 +1,118 @@
+//! Redaction of what the server logs, persists and sends.
+//!
+//! The server builds one [`Redactor`] from the `redaction` configuration and
+//! applies it to forwarded log events before they are stored, to the data of
+//! MCP contexts (job contexts and assistant conversations) before they are
+//! persisted, and to webhook payloads before they are delivered.
+//! [`RedactionMetrics`] reports the matches of every rule to the metric
+//! collector as `redactions_total`, labelled by `rule`.
+
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex, PoisonError};
+use std::time::Duration;
+
+use squirrel_app::supervisor::{RestartPolicy, Supervisor};
+use squirrel_core::redaction::{RedactionConfig, Redactor};
+use squirrel_monitoring::metrics::{Metric, MetricCollector, MetricType};
+use tracing::warn;
+
+/// The redactor of `config`, or one with just the built-in rules if a
+/// configured rule is invalid
+pub fn create_redactor(config: &RedactionConfig) -> Arc<Redactor> {
+    let redactor = Redactor::new(config).unwrap_or_else(|e| {
+        warn!("{}; redacting with the built-in rules only", e);
+        Redactor::new(&RedactionConfig::default()).unwrap_or_default()
+    });
+    Arc::new(redactor)
+}
+
+/// Reports the matches of a redactor's rules to a metric collector
+pub struct RedactionMetrics {
+    /// Redactor whose rules are reported
+    redactor: Arc<Redactor>,
+    /// Matches as last reported, by rule
+    reported: Mutex<HashMap<String, u64>>,
+}
+
+impl RedactionMetrics {
+    /// Create new RedactionMetrics for `redactor`
+    pub fn new(redactor: Arc<Redactor>) -> Self {
+        Self {
+            redactor,
+            reported: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// What the match counts grew by since they were last reported
+    fn deltas(&self) -> Vec<Metric> {
+        let mut reported = self.reported.lock().unwrap_or_else(PoisonError::into_inner);
+        let mut metrics = Vec::new();
+        for stats in self.redactor.stats() {
+            let before = reported.insert(stats.rule.clone(), stats.matches).unwrap_or_default();
+            if stats.matches > before {
+                let labels = HashMap::from([("rule".to_string(), stats.rule)]);
+                metrics.push(Metric::new(
+                    "redactions_total",
+                    (stats.matches - before) as f64,
+                    MetricType::Counter,
+                    labels,
+                ));
+            }
+        }
+        metrics
+    }
+
+    /// Supervise the background task reporting the matches to `collector`
+    /// every `interval`
+    pub fn supervise_reporter(
+        self: &Arc<Self>,
+        supervisor: &Supervisor,
+        collector: Arc<dyn MetricCollector>,
+        interval: Duration,
+    ) {
+        let metrics = self.clone();
+        supervisor.spawn("redaction.metrics", RestartPolicy::default(), move |shutdown| {
+            let metrics = metrics.clone();
+            let collector = collector.clone();
+            async move {
+                while !shutdown.sleep(interval.max(Duration::from_secs(1))).await {
+                    for metric in metrics.deltas() {
+                        if let Err(e) = collector.record_metric(metric).await {
+                            warn!("Failed to record redaction metric: {}", e);
+                        }
+                    }
+                }
+                Ok(())
+            }
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use squirrel_core::redaction::RedactionRule;
+
+    #[test]
+    fn test_reports_new_matches_per_rule() {
+        let invalid = RedactionConfig {
+            rules: vec![RedactionRule::regex("(")],
+            ..RedactionConfig::default()
+        };
+        let redactor = create_redactor(&invalid);
+        assert!(!redactor.is_empty());
+        let metrics = RedactionMetrics::new(redactor.clone());
+        assert!(metrics.deltas().is_empty());
+
+        assert_eq!(
+            redactor.redact_text("Authorization: Bearer abc and bearer def"),
+            "Authorization: Bearer [REDACTED] and bearer [REDACTED]"
+        );
+        let deltas = metrics.deltas();
+        assert_eq!(deltas.len(), 1);
+        assert_eq!(deltas[0].name, "redactions_total");
+        assert_eq!(deltas[0].value, 2.0);
+        assert_eq!(deltas[0].labels["rule"], "builtin:bearer");
+        assert!(metrics.deltas().is_empty());
+    }
+}
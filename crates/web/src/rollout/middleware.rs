@@ -0,0 +1,94 @@
+//! Middleware choosing the configuration that serves each request.
+
+use std::net::SocketAddr;
+use std::sync::Arc;
+
+use axum::{
+    extract::{ConnectInfo, State},
+    http::{HeaderValue, Request},
+    middleware::Next,
+    response::Response,
+};
+
+use super::ConfigRollout;
+use crate::access::client_ip;
+
+/// Header naming the configuration revision that served a response
+pub const REVISION_HEADER: &str = "x-config-revision";
+
+/// Serve the request with the active or the candidate configuration
+///
+/// The choice is added to the request as a [`super::ServedConfig`]
+/// extension for the layers and handlers inside this one, and the response
+/// is counted towards the rollout's promotion gates.
+pub async fn route_requests<B>(
+    State(rollout): State<Arc<ConfigRollout>>,
+    mut req: Request<B>,
+    next: Next<B>,
+) -> Response {
+    let peer = req
+        .extensions()
+        .get::<ConnectInfo<SocketAddr>>()
+        .map(|ConnectInfo(addr)| addr.ip());
+    let client = client_ip(&rollout.active().access.trusted_proxies, peer, req.headers());
+    let served = rollout.serve(client);
+    req.extensions_mut().insert(served.clone());
+
+    let mut response = next.run(req).await;
+    rollout.record(&served, response.status());
+    response
+        .headers_mut()
+        .insert(REVISION_HEADER, HeaderValue::from(served.revision));
+    response
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use axum::{
+        body::Body, http::StatusCode, middleware::from_fn_with_state, routing::get, Router,
+    };
+    use tower::ServiceExt;
+
+    use crate::config::{Config, RateLimitConfig};
+    use crate::rate_limit::{limit_requests, RateLimiter};
+
+    #[tokio::test]
+    async fn test_canary_serves_new_rate_limits() {
+        let rollout = Arc::new(ConfigRollout::new(Config::default()));
+        let app = Router::new()
+            .route("/", get(|| async { "ok" }))
+            .layer(from_fn_with_state(Arc::new(RateLimiter::new()), limit_requests))
+            .layer(from_fn_with_state(rollout.clone(), route_requests));
+        let request = || {
+            let mut request = Request::builder().uri("/").body(Body::empty()).unwrap();
+            request
+                .extensions_mut()
+                .insert(ConnectInfo(SocketAddr::from(([192, 0, 2, 1], 4000))));
+            request
+        };
+
+        let candidate = Config {
+            rate_limit: RateLimitConfig {
+                requests_per_minute: Some(1),
+                ..RateLimitConfig::default()
+            },
+            ..Config::default()
+        };
+        rollout.propose(candidate, "admin").unwrap();
+        let response = app.clone().oneshot(request()).await.unwrap();
+        assert_eq!(response.headers()[REVISION_HEADER], "1");
+
+        rollout.start_canary(100).unwrap();
+        let response = app.clone().oneshot(request()).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(response.headers()[REVISION_HEADER], "2");
+        let response = app.oneshot(request()).await.unwrap();
+        assert_eq!(response.status(), StatusCode::TOO_MANY_REQUESTS);
+        assert_eq!(response.headers()["retry-after"], "60");
+
+        let canary = rollout.status().candidate.unwrap().canary;
+        assert_eq!((canary.requests, canary.rate_limited), (2, 1));
+    }
+}
@@ -0,0 +1,616 @@
+//! Blue/green configuration rollouts.
+//!
+//! [`ConfigRollout`] holds the active configuration and at most one
+//! candidate next to it. A candidate goes through these stages:
+//!
+//! 1. It is proposed and validated against the active configuration. A
+//!    candidate with errors is refused.
+//! 2. Optionally, it serves a canary share of the requests. Clients are
+//!    assigned by address, so each one keeps seeing the same configuration.
+//!    Server errors and rate-limited requests are counted for both sides.
+//! 3. It is promoted, replacing the active configuration in one step, or
+//!    discarded. A canaried candidate is only promoted once it passes the
+//!    gates of [`RolloutConfig`], unless the promotion is forced.
+//!
+//! Replaced configurations are kept, so a promotion can be rolled back.
+//!
+//! Only the settings [`LIVE_SECTIONS`] names act on requests as soon as they
+//! are served. The other sections are set up once at startup, so validation
+//! reports them as needing a restart.
+
+pub mod middleware;
+
+pub use middleware::route_requests;
+
+use std::collections::hash_map::DefaultHasher;
+use std::collections::VecDeque;
+use std::hash::{Hash, Hasher};
+use std::net::IpAddr;
+use std::sync::{Arc, Mutex};
+
+use axum::http::{HeaderValue, StatusCode};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+use crate::config::{Config, RolloutConfig};
+
+/// Sections of the configuration that take effect without a restart
+pub const LIVE_SECTIONS: &[&str] = &["rate_limit", "rollout"];
+
+/// Side of a rollout a request was served by
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Arm {
+    /// The active configuration
+    Stable,
+    /// The candidate configuration
+    Canary,
+}
+
+/// Configuration serving a request, added to the request's extensions
+#[derive(Debug, Clone)]
+pub struct ServedConfig {
+    /// Revision of the configuration
+    pub revision: u64,
+    /// Side of the rollout
+    pub arm: Arm,
+    /// The configuration
+    pub config: Arc<Config>,
+    /// Client address of the request, if known
+    pub client: Option<IpAddr>,
+}
+
+/// Rollout error
+#[derive(Debug, thiserror::Error)]
+pub enum RolloutError {
+    /// The candidate failed validation
+    #[error("Candidate configuration is invalid: {}", .0.join("; "))]
+    Invalid(Vec<String>),
+
+    /// A candidate is already being rolled out
+    #[error("Candidate revision {0} is pending; promote or discard it first")]
+    CandidatePending(u64),
+
+    /// There is no candidate
+    #[error("No candidate configuration")]
+    NoCandidate,
+
+    /// The canary share is out of range
+    #[error("Canary percent must be between 0 and 100, got {0}")]
+    InvalidPercent(u8),
+
+    /// The canary has not passed the promotion gates
+    #[error("Promotion gate failed: {0}")]
+    GateFailed(String),
+
+    /// No earlier configuration is kept
+    #[error("No earlier configuration to roll back to")]
+    NoHistory,
+}
+
+/// Requests served by one side of a rollout
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ArmStats {
+    /// Requests served
+    pub requests: u64,
+    /// Responses with a 5xx status
+    pub server_errors: u64,
+    /// Requests refused by the rate limit
+    pub rate_limited: u64,
+}
+
+impl ArmStats {
+    /// Share of the requests that failed with a server error
+    pub fn error_rate(&self) -> f64 {
+        if self.requests == 0 {
+            0.0
+        } else {
+            self.server_errors as f64 / self.requests as f64
+        }
+    }
+
+    fn record(&mut self, status: StatusCode) {
+        self.requests += 1;
+        if status.is_server_error() {
+            self.server_errors += 1;
+        }
+        if status == StatusCode::TOO_MANY_REQUESTS {
+            self.rate_limited += 1;
+        }
+    }
+}
+
+/// Outcome of validating a candidate
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ValidationReport {
+    /// Problems that keep the candidate from being used
+    pub errors: Vec<String>,
+    /// Changed sections that only take effect after a restart
+    pub restart_required: Vec<String>,
+}
+
+impl ValidationReport {
+    /// Whether the candidate can be rolled out
+    pub fn is_valid(&self) -> bool {
+        self.errors.is_empty()
+    }
+}
+
+/// When and by whom a configuration was applied
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Revision {
+    /// Revision number, counting up from 1 at startup
+    pub revision: u64,
+    /// When the configuration became active
+    pub applied_at: DateTime<Utc>,
+    /// User who applied it
+    pub applied_by: String,
+}
+
+/// State of the candidate
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct CandidateStatus {
+    /// Revision number the candidate gets when promoted
+    pub revision: u64,
+    /// User who proposed it
+    pub submitted_by: String,
+    /// When it was proposed
+    pub submitted_at: DateTime<Utc>,
+    /// Outcome of validating it
+    pub validation: ValidationReport,
+    /// Share of clients it serves, in percent
+    pub canary_percent: u8,
+    /// Requests served by the active configuration since the canary started
+    pub stable: ArmStats,
+    /// Requests served by the candidate
+    pub canary: ArmStats,
+    /// Why promotion would be refused now, if it would
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub blocked_by: Option<String>,
+}
+
+/// State of a rollout
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RolloutStatus {
+    /// The active configuration
+    pub active: Revision,
+    /// The candidate, if one is pending
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub candidate: Option<CandidateStatus>,
+    /// Earlier configurations that can be rolled back to, latest first
+    pub history: Vec<Revision>,
+}
+
+/// A configuration that is or was active
+#[derive(Debug, Clone)]
+struct Applied {
+    revision: Revision,
+    config: Arc<Config>,
+}
+
+/// A configuration being rolled out
+#[derive(Debug, Clone)]
+struct Candidate {
+    config: Arc<Config>,
+    status: CandidateStatus,
+}
+
+#[derive(Debug)]
+struct Inner {
+    active: Applied,
+    candidate: Option<Candidate>,
+    history: VecDeque<Applied>,
+    next_revision: u64,
+}
+
+/// The active configuration and the candidate rolled out next to it
+#[derive(Debug)]
+pub struct ConfigRollout {
+    inner: Mutex<Inner>,
+}
+
+impl ConfigRollout {
+    /// Start with `config` active as revision 1
+    pub fn new(config: Config) -> Self {
+        let active = Applied {
+            revision: Revision {
+                revision: 1,
+                applied_at: Utc::now(),
+                applied_by: "startup".to_string(),
+            },
+            config: Arc::new(config),
+        };
+        Self {
+            inner: Mutex::new(Inner {
+                active,
+                candidate: None,
+                history: VecDeque::new(),
+                next_revision: 2,
+            }),
+        }
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, Inner> {
+        self.inner
+            .lock()
+            .unwrap_or_else(std::sync::PoisonError::into_inner)
+    }
+
+    /// The active configuration
+    pub fn active(&self) -> Arc<Config> {
+        self.lock().active.config.clone()
+    }
+
+    /// The active configuration, the candidate and the history
+    pub fn status(&self) -> RolloutStatus {
+        let inner = self.lock();
+        RolloutStatus {
+            active: inner.active.revision.clone(),
+            candidate: inner.candidate.as_ref().map(|candidate| {
+                let mut status = candidate.status.clone();
+                status.blocked_by = gate(&inner.active.config.rollout, &status).err();
+                status
+            }),
+            history: inner.history.iter().map(|applied| applied.revision.clone()).collect(),
+        }
+    }
+
+    /// Validate `config` against the active configuration
+    pub fn validate(&self, config: &Config) -> ValidationReport {
+        validate(&self.active(), config)
+    }
+
+    /// Propose `config` as the candidate, serving no requests yet
+    ///
+    /// # Errors
+    /// Returns an error if a candidate is pending or `config` is invalid
+    pub fn propose(&self, config: Config, user: &str) -> Result<CandidateStatus, RolloutError> {
+        let mut inner = self.lock();
+        if let Some(candidate) = &inner.candidate {
+            return Err(RolloutError::CandidatePending(candidate.status.revision));
+        }
+        let validation = validate(&inner.active.config, &config);
+        if !validation.is_valid() {
+            return Err(RolloutError::Invalid(validation.errors));
+        }
+        let status = CandidateStatus {
+            revision: inner.next_revision,
+            submitted_by: user.to_string(),
+            submitted_at: Utc::now(),
+            validation,
+            canary_percent: 0,
+            stable: ArmStats::default(),
+            canary: ArmStats::default(),
+            blocked_by: None,
+        };
+        inner.next_revision += 1;
+        inner.candidate = Some(Candidate {
+            config: Arc::new(config),
+            status: status.clone(),
+        });
+        Ok(status)
+    }
+
+    /// Serve the candidate to `percent` of the clients, counting afresh
+    ///
+    /// # Errors
+    /// Returns an error without a candidate or if `percent` is above 100
+    pub fn start_canary(&self, percent: u8) -> Result<CandidateStatus, RolloutError> {
+        if percent > 100 {
+            return Err(RolloutError::InvalidPercent(percent));
+        }
+        let mut inner = self.lock();
+        let candidate = inner.candidate.as_mut().ok_or(RolloutError::NoCandidate)?;
+        candidate.status.canary_percent = percent;
+        candidate.status.stable = ArmStats::default();
+        candidate.status.canary = ArmStats::default();
+        Ok(candidate.status.clone())
+    }
+
+    /// Make the candidate active, keeping the replaced configuration
+    ///
+    /// A canaried candidate must pass the promotion gates unless `force` is
+    /// set.
+    ///
+    /// # Errors
+    /// Returns an error without a candidate or if a gate fails
+    pub fn promote(&self, user: &str, force: bool) -> Result<Revision, RolloutError> {
+        let mut inner = self.lock();
+        let candidate = inner.candidate.as_ref().ok_or(RolloutError::NoCandidate)?;
+        if !force {
+            gate(&inner.active.config.rollout, &candidate.status).map_err(RolloutError::GateFailed)?;
+        }
+        let Some(candidate) = inner.candidate.take() else {
+            return Err(RolloutError::NoCandidate);
+        };
+        let applied = Applied {
+            revision: Revision {
+                revision: candidate.status.revision,
+                applied_at: Utc::now(),
+                applied_by: user.to_string(),
+            },
+            config: candidate.config,
+        };
+        let replaced = std::mem::replace(&mut inner.active, applied);
+        inner.history.push_front(replaced);
+        let keep = inner.active.config.rollout.history;
+        inner.history.truncate(keep);
+        Ok(inner.active.revision.clone())
+    }
+
+    /// Discard the candidate
+    ///
+    /// # Errors
+    /// Returns an error without a candidate
+    pub fn discard(&self) -> Result<CandidateStatus, RolloutError> {
+        let mut inner = self.lock();
+        let candidate = inner.candidate.take().ok_or(RolloutError::NoCandidate)?;
+        Ok(candidate.status)
+    }
+
+    /// Make the configuration replaced last active again
+    ///
+    /// # Errors
+    /// Returns an error while a candidate is pending or without history
+    pub fn rollback(&self, user: &str) -> Result<Revision, RolloutError> {
+        let mut inner = self.lock();
+        if let Some(candidate) = &inner.candidate {
+            return Err(RolloutError::CandidatePending(candidate.status.revision));
+        }
+        let mut previous = inner.history.pop_front().ok_or(RolloutError::NoHistory)?;
+        previous.revision.applied_at = Utc::now();
+        previous.revision.applied_by = user.to_string();
+        inner.active = previous;
+        Ok(inner.active.revision.clone())
+    }
+
+    /// The configuration serving a request from `client`
+    pub fn serve(&self, client: Option<IpAddr>) -> ServedConfig {
+        let inner = self.lock();
+        let canary = inner
+            .candidate
+            .as_ref()
+            .filter(|candidate| share(client) < candidate.status.canary_percent);
+        match canary {
+            Some(candidate) => ServedConfig {
+                revision: candidate.status.revision,
+                arm: Arm::Canary,
+                config: candidate.config.clone(),
+                client,
+            },
+            None => ServedConfig {
+                revision: inner.active.revision.revision,
+                arm: Arm::Stable,
+                config: inner.active.config.clone(),
+                client,
+            },
+        }
+    }
+
+    /// Count the response to a request `served` by a configuration
+    ///
+    /// Requests only count while a canary runs, and only against the
+    /// revisions still being compared.
+    pub fn record(&self, served: &ServedConfig, status: StatusCode) {
+        let mut inner = self.lock();
+        let active = inner.active.revision.revision;
+        let Some(candidate) = inner.candidate.as_mut() else {
+            return;
+        };
+        if candidate.status.canary_percent == 0 {
+            return;
+        }
+        match served.arm {
+            Arm::Canary if served.revision == candidate.status.revision => candidate.status.canary.record(status),
+            Arm::Stable if served.revision == active => candidate.status.stable.record(status),
+            _ => {}
+        }
+    }
+}
+
+/// Which of 100 shares `client` falls in
+fn share(client: Option<IpAddr>) -> u8 {
+    let mut hasher = DefaultHasher::new();
+    client.hash(&mut hasher);
+    (hasher.finish() % 100) as u8
+}
+
+/// Checks a canaried candidate against the promotion gates
+fn gate(gates: &RolloutConfig, status: &CandidateStatus) -> Result<(), String> {
+    if status.canary_percent == 0 {
+        return Ok(());
+    }
+    if status.canary.requests < gates.min_canary_requests {
+        return Err(format!(
+            "the canary served {} of the {} requests required",
+            status.canary.requests, gates.min_canary_requests
+        ));
+    }
+    let increase = status.canary.error_rate() - status.stable.error_rate();
+    if increase > gates.max_error_rate_increase {
+        return Err(format!(
+            "the canary's server error rate is {:.2}% against {:.2}% for the active configuration",
+            status.canary.error_rate() * 100.0,
+            status.stable.error_rate() * 100.0
+        ));
+    }
+    Ok(())
+}
+
+/// Validate `candidate`, and find the changes from `active` that need a restart
+pub fn validate(active: &Config, candidate: &Config) -> ValidationReport {
+    let mut errors = Vec::new();
+    if candidate.request_timeout_secs == 0 {
+        errors.push("request_timeout_secs must be positive".to_string());
+    }
+    if candidate.rate_limit.requests_per_minute == Some(0) {
+        errors.push("rate_limit.requests_per_minute must be positive".to_string());
+    }
+    if candidate.rate_limit.burst == Some(0) {
+        errors.push("rate_limit.burst must be positive".to_string());
+    }
+    if !(0.0..=1.0).contains(&candidate.rollout.max_error_rate_increase) {
+        errors.push("rollout.max_error_rate_increase must be between 0 and 1".to_string());
+    }
+    let watchdog = &candidate.memory_watchdog;
+    if watchdog.budget_mb.is_some() {
+        if watchdog.budget_mb == Some(0) {
+            errors.push("memory_watchdog.budget_mb must be positive".to_string());
+        }
+        if !(0.0 < watchdog.resume_percent
+            && watchdog.resume_percent < watchdog.pause_percent
+            && watchdog.pause_percent <= watchdog.cancel_percent
+            && watchdog.cancel_percent <= 100.0)
+        {
+            errors.push(
+                "memory_watchdog percents must satisfy 0 < resume < pause <= cancel <= 100".to_string(),
+            );
+        }
+    }
+    for (i, quota) in candidate.usage.quotas.iter().enumerate() {
+        if quota.window_secs == 0 {
+            errors.push(format!("usage.quotas[{i}].window_secs must be positive"));
+        }
+    }
+    let headers = &candidate.security_headers;
+    for (name, value) in [
+        ("content_security_policy", &headers.content_security_policy),
+        ("strict_transport_security", &headers.strict_transport_security),
+        ("frame_options", &headers.frame_options),
+        ("referrer_policy", &headers.referrer_policy),
+        ("content_type_options", &headers.content_type_options),
+    ] {
+        if value.as_deref().is_some_and(|value| HeaderValue::from_str(value).is_err()) {
+            errors.push(format!("security_headers.{name} is not a valid header value"));
+        }
+    }
+    let replicas = &candidate.replicas;
+    if !replicas.urls.is_empty() {
+        if replicas.check_interval_ms == 0 || replicas.max_connections == 0 {
+            errors.push("replicas.check_interval_ms and replicas.max_connections must be positive".to_string());
+        }
+        if replicas.max_staleness_ms <= replicas.check_interval_ms {
+            errors.push("replicas.max_staleness_ms must exceed replicas.check_interval_ms".to_string());
+        }
+    }
+    if candidate.db_resilience.max_attempts == 0 || candidate.db_resilience.failure_threshold == 0 {
+        errors.push("db_resilience.max_attempts and db_resilience.failure_threshold must be positive".to_string());
+    }
+    if candidate.blob_integrity.sample_size == Some(0) {
+        errors.push("blob_integrity.sample_size must be positive".to_string());
+    }
+    if let Some(path) = &candidate.access.geoip_database {
+        if !path.is_file() {
+            errors.push(format!("access.geoip_database {} does not exist", path.display()));
+        }
+    }
+
+    ValidationReport {
+        errors,
+        restart_required: changed_sections(active, candidate)
+            .into_iter()
+            .filter(|section| !LIVE_SECTIONS.contains(&section.as_str()))
+            .collect(),
+    }
+}
+
+/// Top-level sections that differ between `a` and `b`
+fn changed_sections(a: &Config, b: &Config) -> Vec<String> {
+    let (Ok(serde_json::Value::Object(a)), Ok(serde_json::Value::Object(b))) =
+        (serde_json::to_value(a), serde_json::to_value(b))
+    else {
+        return Vec::new();
+    };
+    let mut changed: Vec<String> = a
+        .keys()
+        .chain(b.keys())
+        .filter(|key| a.get(*key) != b.get(*key))
+        .cloned()
+        .collect();
+    changed.sort();
+    changed.dedup();
+    changed
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::RateLimitConfig;
+
+    fn limited(requests_per_minute: u32) -> Config {
+        Config {
+            rate_limit: RateLimitConfig {
+                requests_per_minute: Some(requests_per_minute),
+                ..RateLimitConfig::default()
+            },
+            ..Config::default()
+        }
+    }
+
+    #[test]
+    fn test_validation() {
+        let active = Config::default();
+        let mut candidate = limited(0);
+        candidate.request_timeout_secs = 0;
+        candidate.security_headers.frame_options = Some("DENY\n".to_string());
+        let report = validate(&active, &candidate);
+        assert_eq!(report.errors.len(), 3, "{:?}", report.errors);
+
+        let mut candidate = limited(600);
+        candidate.leader_election.enabled = true;
+        let report = validate(&active, &candidate);
+        assert!(report.is_valid());
+        assert_eq!(report.restart_required, ["leader_election"]);
+    }
+
+    #[test]
+    fn test_canary_gates_promotion_and_rollback() {
+        let rollout = ConfigRollout::new(Config {
+            rollout: RolloutConfig {
+                min_canary_requests: 10,
+                ..RolloutConfig::default()
+            },
+            ..Config::default()
+        });
+        assert!(matches!(rollout.propose(limited(0), "admin"), Err(RolloutError::Invalid(_))));
+        let candidate = rollout.propose(limited(600), "admin").unwrap();
+        assert_eq!(candidate.revision, 2);
+        assert!(matches!(rollout.propose(limited(60), "admin"), Err(RolloutError::CandidatePending(2))));
+
+        // Before the canary starts, every client is served the active configuration
+        let client: Option<IpAddr> = Some("192.0.2.1".parse().unwrap());
+        assert_eq!(rollout.serve(client).arm, Arm::Stable);
+        rollout.start_canary(100).unwrap();
+        let served = rollout.serve(client);
+        assert_eq!((served.arm, served.revision), (Arm::Canary, 2));
+        assert_eq!(served.config.rate_limit.requests_per_minute, Some(600));
+
+        for _ in 0..10 {
+            rollout.record(&served, StatusCode::INTERNAL_SERVER_ERROR);
+        }
+        let status = rollout.status().candidate.unwrap();
+        assert_eq!(status.canary.server_errors, 10);
+        assert!(status.blocked_by.is_some());
+        assert!(matches!(rollout.promote("admin", false), Err(RolloutError::GateFailed(_))));
+
+        // Restarting the canary counts afresh
+        rollout.start_canary(100).unwrap();
+        for _ in 0..10 {
+            rollout.record(&served, StatusCode::OK);
+        }
+        let revision = rollout.promote("admin", false).unwrap();
+        assert_eq!((revision.revision, revision.applied_by.as_str()), (2, "admin"));
+        assert_eq!(rollout.active().rate_limit.requests_per_minute, Some(600));
+        assert_eq!(rollout.serve(client).arm, Arm::Stable);
+
+        let revision = rollout.rollback("admin").unwrap();
+        assert_eq!(revision.revision, 1);
+        assert_eq!(rollout.active().rate_limit.requests_per_minute, None);
+        assert!(matches!(rollout.rollback("admin"), Err(RolloutError::NoHistory)));
+
+        rollout.propose(limited(60), "admin").unwrap();
+        assert_eq!(rollout.discard().unwrap().revision, 3);
+        assert!(matches!(rollout.promote("admin", true), Err(RolloutError::NoCandidate)));
+        assert!(matches!(rollout.start_canary(101), Err(RolloutError::InvalidPercent(101))));
+    }
+}
@@ -0,0 +1,146 @@
+//! Metrics and alerts of scheduled tasks.
+//!
+//! [`ScheduleObserver`] reports every run of a task the host or a plugin
+//! scheduled as metrics:
+//!
+//! - `scheduled_task_runs_total`, a counter by `task`, `owner` and `outcome`
+//!   (`succeeded`, `failed` or `timed_out`),
+//! - `scheduled_task_duration_ms`, a histogram of run durations by `task` and
+//!   `owner`,
+//!
+//! and raises an alert from the source `scheduler` once a task failed as
+//! often in a row as its `alert_after` option allows. The alert is resolved
+//! when the task succeeds again.
+
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use async_trait::async_trait;
+use squirrel_app::schedule::{TaskObserver, TaskRun, TaskStatus};
+use squirrel_monitoring::alerts::lifecycle::{alert_labels, fingerprint};
+use squirrel_monitoring::alerts::{Alert, AlertLifecycle, AlertSeverity, AlertType};
+use squirrel_monitoring::metrics::{Metric, MetricCollector, MetricType};
+use tracing::warn;
+
+/// Source of the alerts of failing tasks
+pub const ALERT_SOURCE: &str = "scheduler";
+
+/// Reports runs of scheduled tasks as metrics and failing tasks as alerts
+pub struct ScheduleObserver {
+    metrics: Option<Arc<dyn MetricCollector>>,
+    alerts: Option<Arc<AlertLifecycle>>,
+}
+
+impl ScheduleObserver {
+    /// Create a new ScheduleObserver, recording to `metrics` and raising
+    /// alerts through `alerts`, if given
+    pub fn new(metrics: Option<Arc<dyn MetricCollector>>, alerts: Option<Arc<AlertLifecycle>>) -> Self {
+        Self { metrics, alerts }
+    }
+}
+
+/// The alert raised while `task` is failing
+///
+/// Its labels, and so its fingerprint, only depend on the task's name and
+/// owner.
+pub fn failing_alert(task: &TaskStatus) -> Alert {
+    let error = task
+        .last_run
+        .as_ref()
+        .and_then(|run| run.error.as_deref())
+        .unwrap_or("unknown error");
+    let message = format!(
+        "Scheduled task {} failed {} times in a row: {}",
+        task.name, task.consecutive_failures, error
+    );
+    let details = HashMap::from([
+        ("task".to_string(), serde_json::json!(task.name)),
+        ("owner".to_string(), serde_json::json!(task.owner)),
+    ]);
+    Alert::new(AlertType::Generic, AlertSeverity::Error, ALERT_SOURCE.to_string(), message).with_details(details)
+}
+
+#[async_trait]
+impl TaskObserver for ScheduleObserver {
+    async fn finished(&self, task: &TaskStatus, run: &TaskRun) {
+        let Some(collector) = &self.metrics else {
+            return;
+        };
+        let labels = HashMap::from([
+            ("task".to_string(), task.name.clone()),
+            ("owner".to_string(), task.owner.clone()),
+        ]);
+        let mut counted = labels.clone();
+        counted.insert("outcome".to_string(), run.outcome.name().to_string());
+        for metric in [
+            Metric::new("scheduled_task_runs_total", 1.0, MetricType::Counter, counted),
+            Metric::new("scheduled_task_duration_ms", run.elapsed_ms as f64, MetricType::Histogram, labels),
+        ] {
+            if let Err(e) = collector.record_metric(metric).await {
+                warn!("Failed to record scheduled task metric: {}", e);
+            }
+        }
+    }
+
+    async fn failing(&self, task: &TaskStatus) {
+        warn!(task = %task.name, "Scheduled task failed {} times in a row", task.consecutive_failures);
+        if let Some(alerts) = &self.alerts {
+            if let Err(e) = alerts.observe(&failing_alert(task)) {
+                warn!("Failed to record scheduled task alert: {}", e);
+            }
+        }
+    }
+
+    async fn recovered(&self, task: &TaskStatus) {
+        if let Some(alerts) = &self.alerts {
+            let fingerprint = fingerprint(&alert_labels(&failing_alert(task)));
+            if let Err(e) = alerts.resolve(&fingerprint) {
+                warn!("Failed to resolve scheduled task alert: {}", e);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use squirrel_app::schedule::{TaskOptions, TaskScheduler, HOST_OWNER};
+    use squirrel_app::supervisor::{Supervisor, TaskError};
+    use squirrel_monitoring::alerts::LifecycleConfig;
+    use std::sync::atomic::{AtomicBool, Ordering};
+    use std::time::Duration;
+
+    #[tokio::test]
+    async fn test_failing_tasks_raise_and_resolve_alerts() {
+        let alerts = Arc::new(AlertLifecycle::in_memory(LifecycleConfig::default()));
+        let observer = Arc::new(ScheduleObserver::new(None, Some(alerts.clone())));
+        let scheduler = Arc::new(TaskScheduler::new(Arc::new(Supervisor::new())).with_observer(observer));
+        let broken = Arc::new(AtomicBool::new(true));
+        let state = broken.clone();
+        let options = TaskOptions::new(Duration::from_secs(3600)).with_alert_after(2);
+        scheduler
+            .register(HOST_OWNER, "reports.nightly", options, move || {
+                let broken = state.load(Ordering::SeqCst);
+                async move {
+                    if broken {
+                        Err::<(), TaskError>("disk full".into())
+                    } else {
+                        Ok(())
+                    }
+                }
+            })
+            .unwrap();
+
+        scheduler.run_now("reports.nightly").await.unwrap();
+        assert!(alerts.alerts().is_empty());
+        scheduler.run_now("reports.nightly").await.unwrap();
+        let raised = alerts.alerts();
+        assert_eq!(raised.len(), 1);
+        assert!(raised[0].message.contains("failed 2 times in a row: disk full"), "{}", raised[0].message);
+        assert_eq!(raised[0].labels.get("task").map(String::as_str), Some("reports.nightly"));
+
+        broken.store(false, Ordering::SeqCst);
+        scheduler.run_now("reports.nightly").await.unwrap();
+        assert!(alerts.get(&raised[0].fingerprint).is_none());
+    }
+}
@@ -0,0 +1,226 @@
+//! Security response headers.
+//!
+//! [`SecurityHeadersLayer`] adds `Content-Security-Policy`,
+//! `Strict-Transport-Security`, `X-Frame-Options`, `Referrer-Policy` and
+//! `X-Content-Type-Options` to every response that does not already set
+//! them. WebSocket upgrades, event streams and the configured exempt paths
+//! are left alone, as browsers apply some of these headers to the stream
+//! itself.
+
+use std::sync::Arc;
+use std::task::{Context, Poll};
+
+use axum::http::{header, HeaderName, HeaderValue, Request, Response};
+use futures::future::BoxFuture;
+use tower::{Layer, Service};
+
+use crate::config::SecurityHeadersConfig;
+
+/// Layer adding security headers to responses
+#[derive(Debug, Clone)]
+pub struct SecurityHeadersLayer {
+    policy: Arc<HeaderPolicy>,
+}
+
+/// Headers to add and the paths to leave alone
+#[derive(Debug)]
+struct HeaderPolicy {
+    headers: Vec<(HeaderName, HeaderValue)>,
+    exempt_paths: Vec<String>,
+}
+
+impl HeaderPolicy {
+    /// Whether responses to requests for `path` get no security headers
+    fn is_exempt(&self, path: &str) -> bool {
+        self.exempt_paths.iter().any(|prefix| {
+            let prefix = prefix.trim_end_matches('/');
+            path == prefix
+                || path
+                    .strip_prefix(prefix)
+                    .is_some_and(|rest| rest.starts_with('/'))
+        })
+    }
+}
+
+impl SecurityHeadersLayer {
+    /// Create a layer adding the headers configured in `config`
+    ///
+    /// Headers whose configured value is not a valid header value are
+    /// skipped with a warning.
+    pub fn new(config: &SecurityHeadersConfig) -> Self {
+        let configured = [
+            (
+                header::CONTENT_SECURITY_POLICY,
+                &config.content_security_policy,
+            ),
+            (
+                header::STRICT_TRANSPORT_SECURITY,
+                &config.strict_transport_security,
+            ),
+            (header::X_FRAME_OPTIONS, &config.frame_options),
+            (header::REFERRER_POLICY, &config.referrer_policy),
+            (header::X_CONTENT_TYPE_OPTIONS, &config.content_type_options),
+        ];
+        let headers = if config.enabled {
+            configured
+                .into_iter()
+                .filter_map(|(name, value)| {
+                    let value = value.as_deref()?;
+                    match HeaderValue::from_str(value) {
+                        Ok(value) => Some((name, value)),
+                        Err(_) => {
+                            tracing::warn!("Ignoring invalid {} header value: {:?}", name, value);
+                            None
+                        }
+                    }
+                })
+                .collect()
+        } else {
+            Vec::new()
+        };
+
+        Self {
+            policy: Arc::new(HeaderPolicy {
+                headers,
+                exempt_paths: config.exempt_paths.clone(),
+            }),
+        }
+    }
+}
+
+impl<S> Layer<S> for SecurityHeadersLayer {
+    type Service = SecurityHeaders<S>;
+
+    fn layer(&self, inner: S) -> Self::Service {
+        SecurityHeaders {
+            inner,
+            policy: self.policy.clone(),
+        }
+    }
+}
+
+/// Service adding security headers to the responses of `S`
+#[derive(Debug, Clone)]
+pub struct SecurityHeaders<S> {
+    inner: S,
+    policy: Arc<HeaderPolicy>,
+}
+
+impl<S, ReqBody, ResBody> Service<Request<ReqBody>> for SecurityHeaders<S>
+where
+    S: Service<Request<ReqBody>, Response = Response<ResBody>>,
+    S::Future: Send + 'static,
+{
+    type Response = S::Response;
+    type Error = S::Error;
+    type Future = BoxFuture<'static, Result<Self::Response, Self::Error>>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, req: Request<ReqBody>) -> Self::Future {
+        let skip = self.policy.headers.is_empty()
+            || self.policy.is_exempt(req.uri().path())
+            || req
+                .headers()
+                .get(header::UPGRADE)
+                .is_some_and(|upgrade| upgrade.as_bytes().eq_ignore_ascii_case(b"websocket"));
+        let policy = self.policy.clone();
+        let response = self.inner.call(req);
+
+        Box::pin(async move {
+            let mut response = response.await?;
+            let event_stream =
+                response
+                    .headers()
+                    .get(header::CONTENT_TYPE)
+                    .is_some_and(|content_type| {
+                        content_type.as_bytes().starts_with(b"text/event-stream")
+                    });
+            if !skip && !event_stream {
+                let headers = response.headers_mut();
+                for (name, value) in &policy.headers {
+                    if !headers.contains_key(name) {
+                        headers.insert(name.clone(), value.clone());
+                    }
+                }
+            }
+            Ok(response)
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use axum::{body::Body, routing::get, Router};
+    use tower::ServiceExt;
+
+    async fn get_headers(app: &Router, path: &str) -> axum::http::HeaderMap {
+        let request = Request::builder().uri(path).body(Body::empty()).unwrap();
+        app.clone()
+            .oneshot(request)
+            .await
+            .unwrap()
+            .headers()
+            .clone()
+    }
+
+    #[tokio::test]
+    async fn test_headers_added_except_for_exempt_routes_and_streams() {
+        let config = SecurityHeadersConfig {
+            frame_options: Some("SAMEORIGIN".to_string()),
+            strict_transport_security: None,
+            ..SecurityHeadersConfig::default()
+        };
+        let app = Router::new()
+            .route("/api/health", get(|| async { "ok" }))
+            .route("/ws", get(|| async { "socket" }))
+            .route("/wsx", get(|| async { "not a socket" }))
+            .route(
+                "/events",
+                get(|| async { ([(header::CONTENT_TYPE, "text/event-stream")], "data: 1\n\n") }),
+            )
+            .route(
+                "/embed",
+                get(|| async { ([(header::X_FRAME_OPTIONS, "ALLOWALL")], "embedded") }),
+            )
+            .layer(SecurityHeadersLayer::new(&config));
+
+        let headers = get_headers(&app, "/api/health").await;
+        assert_eq!(headers[header::X_FRAME_OPTIONS], "SAMEORIGIN");
+        assert_eq!(headers[header::X_CONTENT_TYPE_OPTIONS], "nosniff");
+        assert!(headers[header::CONTENT_SECURITY_POLICY]
+            .to_str()
+            .unwrap()
+            .contains("default-src 'self'"));
+        assert!(!headers.contains_key(header::STRICT_TRANSPORT_SECURITY));
+
+        assert!(!get_headers(&app, "/ws")
+            .await
+            .contains_key(header::CONTENT_SECURITY_POLICY));
+        assert!(get_headers(&app, "/wsx")
+            .await
+            .contains_key(header::CONTENT_SECURITY_POLICY));
+        assert!(!get_headers(&app, "/events")
+            .await
+            .contains_key(header::CONTENT_SECURITY_POLICY));
+        assert_eq!(
+            get_headers(&app, "/embed").await[header::X_FRAME_OPTIONS],
+            "ALLOWALL"
+        );
+
+        let disabled = SecurityHeadersConfig {
+            enabled: false,
+            ..SecurityHeadersConfig::default()
+        };
+        let app = Router::new()
+            .route("/", get(|| async { "ok" }))
+            .layer(SecurityHeadersLayer::new(&disabled));
+        assert!(!get_headers(&app, "/")
+            .await
+            .contains_key(header::X_FRAME_OPTIONS));
+    }
+}
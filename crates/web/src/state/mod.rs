@@ -8,8 +8,42 @@ use async_trait::async_trait;
 use crate::config::Config;
 use crate::websocket::ConnectionManager;
 use crate::auth::AuthService;
+use crate::handlers::jobs::JobContexts;
 use crate::mcp::McpCommandClient;
 use crate::handlers::commands::CommandService;
+use crate::handlers::workflows::WorkflowService;
+use crate::agents::AgentScheduler;
+use crate::leader::LeaderElector;
+use crate::handlers::webhooks::WebhookService;
+use crate::handlers::files::FileService;
+use crate::rollout::ConfigRollout;
+use crate::tags::TagStore;
+use crate::idempotency::IdempotencyKeys;
+use crate::admin::AdminControls;
+use crate::flags::{self, FeatureFlags};
+use crate::users::UserDirectory;
+use crate::read_cache::ReadCaches;
+use crate::maintenance::DatabaseMaintenance;
+use crate::integrity::BlobIntegrity;
+use crate::export::Exporter;
+use crate::logs::LogStore;
+use crate::locks::LockService;
+use crate::db::ReplicatedPool;
+use crate::auth::extractor::AuthClaims;
+use squirrel_app::startup::Readiness;
+use squirrel_app::schedule::TaskScheduler;
+use squirrel_app::supervisor::Supervisor;
+use squirrel_commands::cache::ResultCache;
+use squirrel_core::flags::Flag;
+use squirrel_commands::policy::{PolicySubject, PolicyTarget};
+use squirrel_commands::CommandRegistry;
+use squirrel_monitoring::accounting::UsageLedger;
+use squirrel_monitoring::alerts::AlertLifecycle;
+use squirrel_monitoring::watchdog::MemoryWatchdog;
+use squirrel_core::redaction::Redactor;
+use squirrel_mcp::ai::Assistant;
+use squirrel_mcp::context_manager::ContextManager;
+use squirrel_mcp::tool::ToolManager;
 use crate::api::error::AppError;
 
 /// Machine Context Protocol client trait (legacy)
@@ -52,6 +86,68 @@ pub struct AppState {
     pub auth: AuthService,
     /// Command service
     pub command_service: Option<Arc<dyn CommandService>>,
+    /// Workflow service
+    pub workflow_service: Option<Arc<WorkflowService>>,
+    /// Registry of the commands workflows run, searched by the command palette
+    pub command_registry: Option<Arc<CommandRegistry>>,
+    /// Remote agent scheduler
+    pub agent_scheduler: Option<Arc<AgentScheduler>>,
+    /// Leader elector
+    pub leader: Option<Arc<LeaderElector>>,
+    /// Webhook service
+    pub webhook_service: Option<Arc<WebhookService>>,
+    /// Alert deduplication, silences and acknowledgments
+    pub alert_lifecycle: Option<Arc<AlertLifecycle>>,
+    /// MCP context manager
+    pub context_manager: Option<Arc<ContextManager>>,
+    /// MCP contexts the commands of each job execute in
+    pub job_contexts: Option<Arc<JobContexts>>,
+    /// MCP tool manager
+    pub tool_manager: Option<Arc<ToolManager>>,
+    /// Resumable uploads and downloads
+    pub file_service: Option<Arc<FileService>>,
+    /// Cached command results
+    pub result_cache: Option<Arc<ResultCache>>,
+    /// Resource usage per user and workspace, and quotas
+    pub usage_ledger: Option<Arc<UsageLedger>>,
+    /// Memory watchdog pausing and cancelling background executions
+    pub memory_watchdog: Option<Arc<MemoryWatchdog>>,
+    /// Assistant planning requests with the registered tools and commands
+    pub assistant: Option<Arc<Assistant>>,
+    /// Active configuration and the candidate rolled out next to it
+    pub config_rollout: Option<Arc<ConfigRollout>>,
+    /// Tags of jobs, commands, experiments and datasets
+    pub tag_store: Option<Arc<dyn TagStore>>,
+    /// Idempotency keys of command and job submissions
+    pub idempotency: Option<Arc<IdempotencyKeys>>,
+    /// Degraded mode, disabled plugins and the admin audit log
+    pub admin: Option<Arc<AdminControls>>,
+    /// Feature flag settings admins change at runtime
+    pub flags: Option<Arc<FeatureFlags>>,
+    /// User accounts, teams and invitations
+    pub users: Option<Arc<UserDirectory>>,
+    /// Supervisor of the background tasks, reported by `/health`
+    pub supervisor: Option<Arc<Supervisor>>,
+    /// Periodic tasks of the host and plugins, controlled by admins
+    pub scheduler: Option<Arc<TaskScheduler>>,
+    /// Readiness of the startup stages, reported by `/ready`
+    pub readiness: Option<Arc<Readiness>>,
+    /// Caches of the command list and the capability report
+    pub read_caches: Option<Arc<ReadCaches>>,
+    /// Scheduled retention cleanup and compaction of the web database
+    pub maintenance: Option<Arc<DatabaseMaintenance>>,
+    /// Scheduled checksum verification and restore of the blob store
+    pub integrity: Option<Arc<BlobIntegrity>>,
+    /// CSV and Parquet extracts for analysis
+    pub exporter: Option<Arc<Exporter>>,
+    /// Log events forwarded by agents, plugins and the CLI
+    pub log_store: Option<Arc<dyn LogStore>>,
+    /// Redacts forwarded log events before they are stored
+    pub redactor: Option<Arc<Redactor>>,
+    /// Distributed locks of operations no two instances may run at once
+    pub locks: Option<Arc<LockService>>,
+    /// Read replicas of the web database, if any are configured
+    pub replicas: Option<Arc<ReplicatedPool>>,
 }
 
 impl AppState {
@@ -60,4 +156,221 @@ impl AppState {
         self.command_service.as_ref()
             .ok_or_else(|| AppError::Internal("Command service not configured".to_string()))
     }
-} 
\ No newline at end of file
+    
+    /// Get the workflow service
+    pub fn get_workflow_service(&self) -> Result<&Arc<WorkflowService>, AppError> {
+        self.workflow_service.as_ref()
+            .ok_or_else(|| AppError::Internal("Workflow service not configured".to_string()))
+    }
+    
+    /// Get the command registry
+    pub fn get_command_registry(&self) -> Result<&Arc<CommandRegistry>, AppError> {
+        self.command_registry.as_ref()
+            .ok_or_else(|| AppError::Internal("Command registry not configured".to_string()))
+    }
+    
+    /// Get the remote agent scheduler
+    pub fn get_agent_scheduler(&self) -> Result<&Arc<AgentScheduler>, AppError> {
+        self.agent_scheduler.as_ref()
+            .ok_or_else(|| AppError::Internal("Agent scheduler not configured".to_string()))
+    }
+    
+    /// Get the webhook service
+    pub fn get_webhook_service(&self) -> Result<&Arc<WebhookService>, AppError> {
+        self.webhook_service.as_ref()
+            .ok_or_else(|| AppError::Internal("Webhook service not configured".to_string()))
+    }
+    
+    /// Get the alert lifecycle tracker
+    pub fn get_alert_lifecycle(&self) -> Result<&Arc<AlertLifecycle>, AppError> {
+        self.alert_lifecycle.as_ref()
+            .ok_or_else(|| AppError::Internal("Alert lifecycle not configured".to_string()))
+    }
+    
+    /// Get the MCP context manager
+    pub fn get_context_manager(&self) -> Result<&Arc<ContextManager>, AppError> {
+        self.context_manager.as_ref()
+            .ok_or_else(|| AppError::Internal("Context manager not configured".to_string()))
+    }
+    
+    /// Get the assistant
+    pub fn get_assistant(&self) -> Result<&Arc<Assistant>, AppError> {
+        self.assistant.as_ref()
+            .ok_or_else(|| AppError::Internal("Assistant not configured".to_string()))
+    }
+    
+    /// Get the configuration rollout
+    pub fn get_config_rollout(&self) -> Result<&Arc<ConfigRollout>, AppError> {
+        self.config_rollout.as_ref()
+            .ok_or_else(|| AppError::Internal("Config rollout not configured".to_string()))
+    }
+    
+    /// Get the tag store
+    pub fn get_tag_store(&self) -> Result<&Arc<dyn TagStore>, AppError> {
+        self.tag_store.as_ref()
+            .ok_or_else(|| AppError::Internal("Tag store not configured".to_string()))
+    }
+    
+    /// Get the idempotency keys of submissions
+    pub fn get_idempotency(&self) -> Result<&Arc<IdempotencyKeys>, AppError> {
+        self.idempotency.as_ref()
+            .ok_or_else(|| AppError::Internal("Idempotency keys not configured".to_string()))
+    }
+    
+    /// Get the admin controls
+    pub fn get_admin(&self) -> Result<&Arc<AdminControls>, AppError> {
+        self.admin.as_ref()
+            .ok_or_else(|| AppError::Internal("Admin controls not configured".to_string()))
+    }
+    
+    /// Get the user directory
+    pub fn get_users(&self) -> Result<&Arc<UserDirectory>, AppError> {
+        self.users.as_ref()
+            .ok_or_else(|| AppError::Internal("User directory not configured".to_string()))
+    }
+    
+    /// Get the MCP tool manager
+    pub fn get_tool_manager(&self) -> Result<&Arc<ToolManager>, AppError> {
+        self.tool_manager.as_ref()
+            .ok_or_else(|| AppError::Internal("Tool manager not configured".to_string()))
+    }
+    
+    /// Get the MCP contexts bound to jobs
+    pub fn get_job_contexts(&self) -> Result<&Arc<JobContexts>, AppError> {
+        self.job_contexts
+            .as_ref()
+            .ok_or_else(|| AppError::Internal("Job contexts not configured".to_string()))
+    }
+
+    /// Get the file service
+    pub fn get_file_service(&self) -> Result<&Arc<FileService>, AppError> {
+        self.file_service.as_ref()
+            .ok_or_else(|| AppError::Internal("File service not configured".to_string()))
+    }
+    
+    /// Get the command result cache
+    pub fn get_result_cache(&self) -> Result<&Arc<ResultCache>, AppError> {
+        self.result_cache.as_ref()
+            .ok_or_else(|| AppError::Internal("Result cache not configured".to_string()))
+    }
+    
+    /// Get the usage ledger
+    pub fn get_usage_ledger(&self) -> Result<&Arc<UsageLedger>, AppError> {
+        self.usage_ledger.as_ref()
+            .ok_or_else(|| AppError::Internal("Usage ledger not configured".to_string()))
+    }
+    
+    /// Get the database maintenance
+    pub fn get_maintenance(&self) -> Result<&Arc<DatabaseMaintenance>, AppError> {
+        self.maintenance.as_ref()
+            .ok_or_else(|| AppError::Internal("Database maintenance not configured".to_string()))
+    }
+    
+    /// Get the integrity checks of the blob store
+    pub fn get_integrity(&self) -> Result<&Arc<BlobIntegrity>, AppError> {
+        self.integrity.as_ref()
+            .ok_or_else(|| AppError::NotFound("No blob store is configured".to_string()))
+    }
+    
+    /// Get the scheduler of periodic tasks
+    pub fn get_scheduler(&self) -> Result<&Arc<TaskScheduler>, AppError> {
+        self.scheduler.as_ref()
+            .ok_or_else(|| AppError::Internal("Task scheduler not configured".to_string()))
+    }
+    
+    /// Get the distributed locks
+    pub fn get_locks(&self) -> Result<&Arc<LockService>, AppError> {
+        self.locks.as_ref()
+            .ok_or_else(|| AppError::Internal("Distributed locks not configured".to_string()))
+    }
+    
+    /// Get the exporter
+    pub fn get_exporter(&self) -> Result<&Arc<Exporter>, AppError> {
+        self.exporter.as_ref()
+            .ok_or_else(|| AppError::Internal("Exports not configured".to_string()))
+    }
+    
+    /// Get the log store
+    pub fn get_log_store(&self) -> Result<&Arc<dyn LogStore>, AppError> {
+        self.log_store.as_ref()
+            .ok_or_else(|| AppError::Internal("Log store not configured".to_string()))
+    }
+    
+    /// Get the feature flags
+    pub fn get_flags(&self) -> Result<&Arc<FeatureFlags>, AppError> {
+        self.flags.as_ref()
+            .ok_or_else(|| AppError::Internal("Feature flags not configured".to_string()))
+    }
+    
+    /// Refuse a request of `user` in `workspace` if `flag` is off for them
+    pub fn check_flag(&self, flag: Flag, user: &str, workspace: &str) -> Result<(), AppError> {
+        flags::require(self.flags.as_deref(), flag, user, workspace)
+    }
+    
+    /// Refuse new background executions while the memory watchdog has paused them
+    pub fn check_memory_pressure(&self) -> Result<(), AppError> {
+        match &self.memory_watchdog {
+            Some(watchdog) if !watchdog.accepting() => Err(AppError::Custom(
+                axum::http::StatusCode::SERVICE_UNAVAILABLE,
+                "Server is low on memory and not accepting new executions; try again later".to_string(),
+            )),
+            _ => Ok(()),
+        }
+    }
+    
+    /// Refuse a command provided by a plugin an admin turned off
+    pub fn check_plugin_enabled(&self, command: &str) -> Result<(), AppError> {
+        let (Some(admin), Some(registry)) = (&self.admin, &self.command_registry) else {
+            return Ok(());
+        };
+        let name = command.split_whitespace().next().unwrap_or(command);
+        let Some(plugin) = registry
+            .get_command(name)
+            .ok()
+            .and_then(|command| command.plugin().map(str::to_string))
+        else {
+            return Ok(());
+        };
+        match admin.disabled_plugin(&plugin) {
+            Some(disabled) => Err(AppError::Forbidden(match disabled.reason {
+                Some(reason) => format!("Plugin {} is disabled: {}", plugin, reason),
+                None => format!("Plugin {} is disabled", plugin),
+            })),
+            None => Ok(()),
+        }
+    }
+    
+    /// Refuse a command a policy attached to the user or their API key denies
+    ///
+    /// `command` is the submitted command line; its first word names the
+    /// command and the second, if any, a subcommand.
+    pub fn check_command_policy(&self, user: &AuthClaims, command: &str) -> Result<(), AppError> {
+        let Some(admin) = &self.admin else {
+            return Ok(());
+        };
+        let mut words = command.split_whitespace().map(str::to_string);
+        let name = words.next().unwrap_or_default();
+        let plugin = self
+            .command_registry
+            .as_ref()
+            .and_then(|registry| registry.get_command(&name).ok())
+            .and_then(|command| command.plugin().map(str::to_string));
+        let target = PolicyTarget::command(&name, &words.collect::<Vec<_>>(), plugin.as_deref());
+        admin.check_policies(&policy_subjects(user), &target)
+    }
+    
+    /// Refuse an MCP tool a policy attached to the user or their API key denies
+    pub fn check_tool_policy(&self, user: &AuthClaims, tool_id: &str) -> Result<(), AppError> {
+        match &self.admin {
+            Some(admin) => admin.check_policies(&policy_subjects(user), &PolicyTarget::tool(tool_id)),
+            None => Ok(()),
+        }
+    }
+}
+
+/// The subjects whose command policies apply to a request
+fn policy_subjects(user: &AuthClaims) -> Vec<PolicySubject> {
+    let mut subjects = vec![PolicySubject::User(user.sub.clone())];
+    subjects.extend(user.api_key.clone().map(PolicySubject::ApiKey));
+    subjects
+}
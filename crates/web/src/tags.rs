@@ -0,0 +1,377 @@
+//! Tags of jobs, command definitions, experiments and datasets.
+//!
+//! Each user labels resources with their own tags: assignments record who
+//! made them, and the user-facing API only reads and changes the caller's.
+//! Each distinct tag is stored once in the `tags` table and assigned to
+//! resources in `tag_assignments`.
+//! [`MemoryTagStore`] keeps them in memory for the mock database mode.
+
+use std::collections::{BTreeMap, BTreeSet};
+
+use async_trait::async_trait;
+use chrono::Utc;
+use serde::{Deserialize, Serialize};
+use sqlx::{Row, SqlitePool};
+use squirrel_core::tags::{matches_all, ResourceKind, Tag, TagError};
+use tokio::sync::Mutex;
+
+use crate::api::error::AppError;
+use crate::db::InstrumentedPool;
+
+impl From<TagError> for AppError {
+    fn from(error: TagError) -> Self {
+        AppError::InvalidRequest(error.to_string())
+    }
+}
+
+/// A resource and its tags
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TaggedResource {
+    /// Kind of resource
+    pub kind: ResourceKind,
+    /// ID of the resource
+    pub id: String,
+    /// Tags of the resource
+    pub tags: Vec<Tag>,
+}
+
+/// A tag and how many resources have it
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TagUsage {
+    /// The tag
+    pub tag: Tag,
+    /// Number of resources with the tag
+    pub resources: u64,
+}
+
+/// Storage for tags
+///
+/// Reads take an optional owner: `None` covers the assignments of every
+/// user, for administration.
+#[async_trait]
+pub trait TagStore: Send + Sync {
+    /// Assign tags to a resource, returning all its tags afterwards
+    async fn add(&self, owner: &str, kind: ResourceKind, id: &str, tags: &[Tag]) -> Result<Vec<Tag>, AppError>;
+
+    /// Remove the tags matching `filters` from a resource, returning its remaining tags
+    ///
+    /// A filter without a value removes every value of its key.
+    async fn remove(&self, owner: &str, kind: ResourceKind, id: &str, filters: &[Tag]) -> Result<Vec<Tag>, AppError>;
+
+    /// Tags of a resource
+    async fn tags_of(&self, owner: Option<&str>, kind: ResourceKind, id: &str) -> Result<Vec<Tag>, AppError>;
+
+    /// Resources of a kind, or of every kind, matching all `filters`
+    async fn find(
+        &self,
+        owner: Option<&str>,
+        kind: Option<ResourceKind>,
+        filters: &[Tag],
+    ) -> Result<Vec<TaggedResource>, AppError>;
+
+    /// Every tag in use, with the number of resources having it
+    async fn usage(&self, owner: Option<&str>) -> Result<Vec<TagUsage>, AppError>;
+
+    /// Add and remove tags on every resource matching `filters`
+    ///
+    /// Returns the changed resources with their new tags. At least one
+    /// filter is required, so a bulk change never covers everything.
+    async fn bulk(
+        &self,
+        owner: &str,
+        kind: Option<ResourceKind>,
+        filters: &[Tag],
+        add: &[Tag],
+        remove: &[Tag],
+    ) -> Result<Vec<TaggedResource>, AppError> {
+        if filters.is_empty() {
+            return Err(AppError::InvalidRequest("A bulk change needs at least one tag filter".to_string()));
+        }
+        let mut changed = Vec::new();
+        for resource in self.find(Some(owner), kind, filters).await? {
+            if !remove.is_empty() {
+                self.remove(owner, resource.kind, &resource.id, remove).await?;
+            }
+            let tags = if add.is_empty() {
+                self.tags_of(Some(owner), resource.kind, &resource.id).await?
+            } else {
+                self.add(owner, resource.kind, &resource.id, add).await?
+            };
+            changed.push(TaggedResource { tags, ..resource });
+        }
+        Ok(changed)
+    }
+}
+
+/// A tag's value as stored; tags without a value store an empty one
+fn stored_value(tag: &Tag) -> &str {
+    tag.value().unwrap_or("")
+}
+
+/// Rebuilds a stored tag
+fn stored_tag(key: &str, value: &str) -> Result<Tag, AppError> {
+    Ok(Tag::new(key, (!value.is_empty()).then_some(value))?)
+}
+
+/// Tag store backed by the `tags` and `tag_assignments` tables
+pub struct SqlTagStore {
+    pool: InstrumentedPool,
+}
+
+impl SqlTagStore {
+    /// Create a new SqlTagStore
+    pub fn new(pool: SqlitePool) -> Self {
+        Self { pool: pool.into() }
+    }
+}
+
+#[async_trait]
+impl TagStore for SqlTagStore {
+    async fn add(&self, owner: &str, kind: ResourceKind, id: &str, tags: &[Tag]) -> Result<Vec<Tag>, AppError> {
+        let mut tx = self.pool.begin().await?;
+        for tag in tags {
+            sqlx::query("INSERT INTO tags (key, value) VALUES (?, ?) ON CONFLICT(key, value) DO NOTHING")
+                .bind(tag.key())
+                .bind(stored_value(tag))
+                .execute(&mut *tx)
+                .await?;
+            sqlx::query(
+                "INSERT INTO tag_assignments (tag_id, resource_kind, resource_id, user_id, created_at)
+                 SELECT id, ?, ?, ?, ? FROM tags WHERE key = ? AND value = ?
+                 ON CONFLICT DO NOTHING",
+            )
+            .bind(kind.as_str())
+            .bind(id)
+            .bind(owner)
+            .bind(Utc::now().timestamp_millis())
+            .bind(tag.key())
+            .bind(stored_value(tag))
+            .execute(&mut *tx)
+            .await?;
+        }
+        tx.commit().await?;
+        self.tags_of(Some(owner), kind, id).await
+    }
+
+    async fn remove(&self, owner: &str, kind: ResourceKind, id: &str, filters: &[Tag]) -> Result<Vec<Tag>, AppError> {
+        let mut tx = self.pool.begin().await?;
+        for filter in filters {
+            sqlx::query(
+                "DELETE FROM tag_assignments
+                 WHERE resource_kind = ? AND resource_id = ? AND user_id = ?
+                   AND tag_id IN (SELECT id FROM tags WHERE key = ? AND (? IS NULL OR value = ?))",
+            )
+            .bind(kind.as_str())
+            .bind(id)
+            .bind(owner)
+            .bind(filter.key())
+            .bind(filter.value())
+            .bind(filter.value())
+            .execute(&mut *tx)
+            .await?;
+        }
+        tx.commit().await?;
+        self.tags_of(Some(owner), kind, id).await
+    }
+
+    async fn tags_of(&self, owner: Option<&str>, kind: ResourceKind, id: &str) -> Result<Vec<Tag>, AppError> {
+        let rows = sqlx::query(
+            "SELECT DISTINCT t.key, t.value FROM tag_assignments a JOIN tags t ON t.id = a.tag_id
+             WHERE a.resource_kind = ? AND a.resource_id = ? AND (? IS NULL OR a.user_id = ?)
+             ORDER BY t.key, t.value",
+        )
+        .bind(kind.as_str())
+        .bind(id)
+        .bind(owner)
+        .bind(owner)
+        .fetch_all(&self.pool)
+        .await?;
+        rows.iter()
+            .map(|row| stored_tag(row.try_get("key")?, row.try_get("value")?))
+            .collect()
+    }
+
+    async fn find(
+        &self,
+        owner: Option<&str>,
+        kind: Option<ResourceKind>,
+        filters: &[Tag],
+    ) -> Result<Vec<TaggedResource>, AppError> {
+        let rows = sqlx::query(
+            "SELECT DISTINCT a.resource_kind, a.resource_id, t.key, t.value
+             FROM tag_assignments a JOIN tags t ON t.id = a.tag_id
+             WHERE (? IS NULL OR a.resource_kind = ?) AND (? IS NULL OR a.user_id = ?)",
+        )
+        .bind(kind.map(ResourceKind::as_str))
+        .bind(kind.map(ResourceKind::as_str))
+        .bind(owner)
+        .bind(owner)
+        .fetch_all(&self.pool)
+        .await?;
+
+        let mut resources: BTreeMap<(ResourceKind, String), BTreeSet<Tag>> = BTreeMap::new();
+        for row in &rows {
+            let kind: ResourceKind = row.try_get::<String, _>("resource_kind")?.parse()?;
+            let tag = stored_tag(row.try_get("key")?, row.try_get("value")?)?;
+            resources.entry((kind, row.try_get("resource_id")?)).or_default().insert(tag);
+        }
+        Ok(matching(resources, filters))
+    }
+
+    async fn usage(&self, owner: Option<&str>) -> Result<Vec<TagUsage>, AppError> {
+        let rows = sqlx::query(
+            "SELECT t.key, t.value, COUNT(DISTINCT a.resource_kind || ':' || a.resource_id) AS resources
+             FROM tag_assignments a JOIN tags t ON t.id = a.tag_id
+             WHERE ? IS NULL OR a.user_id = ?
+             GROUP BY t.key, t.value ORDER BY t.key, t.value",
+        )
+        .bind(owner)
+        .bind(owner)
+        .fetch_all(&self.pool)
+        .await?;
+        rows.iter()
+            .map(|row| {
+                Ok(TagUsage {
+                    tag: stored_tag(row.try_get("key")?, row.try_get("value")?)?,
+                    resources: row.try_get::<i64, _>("resources")?.try_into().unwrap_or(0),
+                })
+            })
+            .collect()
+    }
+}
+
+/// The resources whose tags match all `filters`, in order
+fn matching(resources: BTreeMap<(ResourceKind, String), BTreeSet<Tag>>, filters: &[Tag]) -> Vec<TaggedResource> {
+    resources
+        .into_iter()
+        .map(|((kind, id), tags)| TaggedResource { kind, id, tags: tags.into_iter().collect() })
+        .filter(|resource| matches_all(filters, &resource.tags))
+        .collect()
+}
+
+/// A tag assigned to a resource by a user
+type Assignment = (ResourceKind, String, String, Tag);
+
+/// In-process tag store for the mock database mode and tests
+#[derive(Default)]
+pub struct MemoryTagStore {
+    assignments: Mutex<BTreeSet<Assignment>>,
+}
+
+impl MemoryTagStore {
+    /// Create a new MemoryTagStore
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Tags of a resource among `assignments`
+    fn collect(assignments: &BTreeSet<Assignment>, owner: Option<&str>, kind: ResourceKind, id: &str) -> Vec<Tag> {
+        let tags: BTreeSet<Tag> = assignments
+            .iter()
+            .filter(|(k, i, user, _)| *k == kind && i == id && owner.is_none_or(|owner| user == owner))
+            .map(|(_, _, _, tag)| tag.clone())
+            .collect();
+        tags.into_iter().collect()
+    }
+}
+
+#[async_trait]
+impl TagStore for MemoryTagStore {
+    async fn add(&self, owner: &str, kind: ResourceKind, id: &str, tags: &[Tag]) -> Result<Vec<Tag>, AppError> {
+        let mut assignments = self.assignments.lock().await;
+        for tag in tags {
+            assignments.insert((kind, id.to_string(), owner.to_string(), tag.clone()));
+        }
+        Ok(Self::collect(&assignments, Some(owner), kind, id))
+    }
+
+    async fn remove(&self, owner: &str, kind: ResourceKind, id: &str, filters: &[Tag]) -> Result<Vec<Tag>, AppError> {
+        let mut assignments = self.assignments.lock().await;
+        assignments.retain(|(k, i, user, tag)| {
+            !(*k == kind && i == id && user == owner && filters.iter().any(|filter| filter.matches(tag)))
+        });
+        Ok(Self::collect(&assignments, Some(owner), kind, id))
+    }
+
+    async fn tags_of(&self, owner: Option<&str>, kind: ResourceKind, id: &str) -> Result<Vec<Tag>, AppError> {
+        Ok(Self::collect(&*self.assignments.lock().await, owner, kind, id))
+    }
+
+    async fn find(
+        &self,
+        owner: Option<&str>,
+        kind: Option<ResourceKind>,
+        filters: &[Tag],
+    ) -> Result<Vec<TaggedResource>, AppError> {
+        let mut resources: BTreeMap<(ResourceKind, String), BTreeSet<Tag>> = BTreeMap::new();
+        for (k, id, user, tag) in self.assignments.lock().await.iter() {
+            if kind.is_none_or(|kind| kind == *k) && owner.is_none_or(|owner| user == owner) {
+                resources.entry((*k, id.clone())).or_default().insert(tag.clone());
+            }
+        }
+        Ok(matching(resources, filters))
+    }
+
+    async fn usage(&self, owner: Option<&str>) -> Result<Vec<TagUsage>, AppError> {
+        let mut counts: BTreeMap<Tag, BTreeSet<(ResourceKind, String)>> = BTreeMap::new();
+        for (kind, id, user, tag) in self.assignments.lock().await.iter() {
+            if owner.is_none_or(|owner| user == owner) {
+                counts.entry(tag.clone()).or_default().insert((*kind, id.clone()));
+            }
+        }
+        Ok(counts
+            .into_iter()
+            .map(|(tag, resources)| TagUsage { tag, resources: resources.len() as u64 })
+            .collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use squirrel_core::tags::parse_tags;
+    use crate::test_helpers::migrated_pool;
+
+    async fn exercise(store: &dyn TagStore) {
+        let tags = |spec: &str| parse_tags([spec]).unwrap();
+        store.add("alice", ResourceKind::Job, "j1", &tags("experiment=rnaseq,urgent")).await.unwrap();
+        store.add("alice", ResourceKind::Job, "j2", &tags("experiment=chipseq")).await.unwrap();
+        store.add("alice", ResourceKind::Dataset, "reads.fastq", &tags("experiment=rnaseq")).await.unwrap();
+        store.add("bob", ResourceKind::Job, "j1", &tags("reviewed")).await.unwrap();
+
+        let found = store.find(Some("alice"), None, &tags("experiment=rnaseq")).await.unwrap();
+        let ids: Vec<&str> = found.iter().map(|resource| resource.id.as_str()).collect();
+        assert_eq!(ids, ["j1", "reads.fastq"]);
+        assert_eq!(found[0].tags, tags("experiment=rnaseq,urgent"));
+        let jobs = store.find(Some("alice"), Some(ResourceKind::Job), &tags("experiment")).await.unwrap();
+        assert_eq!(jobs.len(), 2);
+        assert_eq!(store.tags_of(None, ResourceKind::Job, "j1").await.unwrap().len(), 3);
+
+        let usage = store.usage(Some("alice")).await.unwrap();
+        let rnaseq = usage.iter().find(|usage| usage.tag == tags("experiment=rnaseq")[0]).unwrap();
+        assert_eq!(rnaseq.resources, 2);
+        assert!(usage.iter().all(|usage| usage.tag.key() != "reviewed"));
+
+        let remaining = store.remove("alice", ResourceKind::Job, "j1", &tags("experiment")).await.unwrap();
+        assert_eq!(remaining, tags("urgent"));
+
+        let changed = store
+            .bulk("alice", None, &tags("experiment=rnaseq"), &tags("archived"), &tags("experiment"))
+            .await
+            .unwrap();
+        assert_eq!(changed.len(), 1);
+        assert_eq!(changed[0].tags, tags("archived"));
+        assert!(store.bulk("alice", None, &[], &tags("archived"), &[]).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_memory_tag_store() {
+        exercise(&MemoryTagStore::new()).await;
+    }
+
+    #[tokio::test]
+    async fn test_sql_tag_store() {
+        let pool = migrated_pool().await;
+        exercise(&SqlTagStore::new(pool)).await;
+    }
+}
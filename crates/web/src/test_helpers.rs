@@ -0,0 +1,31 @@
+//! Helpers shared by the unit tests of the crate.
+
+use sqlx::sqlite::SqlitePoolOptions;
+
+use crate::auth::extractor::AuthClaims;
+use crate::db::SqlitePool;
+
+/// Claims of a signed-in user `sub` with `roles`, which never expire
+pub(crate) fn claims(sub: &str, roles: &[&str]) -> AuthClaims {
+    AuthClaims {
+        sub: sub.to_string(),
+        iat: 0,
+        exp: i64::MAX,
+        roles: roles.iter().map(|role| role.to_string()).collect(),
+        api_key: None,
+    }
+}
+
+/// An in-memory web database with every migration applied
+///
+/// The pool has one connection, as each in-memory connection is its own
+/// database.
+pub(crate) async fn migrated_pool() -> SqlitePool {
+    let pool = SqlitePoolOptions::new()
+        .max_connections(1)
+        .connect("sqlite::memory:")
+        .await
+        .unwrap();
+    sqlx::migrate!("./migrations").run(&pool).await.unwrap();
+    pool
+}
@@ -0,0 +1,341 @@
+//! Request traces.
+//!
+//! A trace is everything that happened for one request or command, across
+//! the components it went through. Its ID is the request ID propagated in
+//! the command context, or the ID of the agent task running the command;
+//! agents, plugins and the CLI forward log events with it as their run, and
+//! the MCP tool manager records tool executions under it. A
+//! [`TraceCollector`] gathers these into one timeline, in the order they
+//! happened.
+
+use std::collections::BTreeSet;
+use std::sync::Arc;
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+use squirrel_mcp::tool::{ExecutionRecord, ToolManager};
+
+use crate::agents::{AgentScheduler, AgentTask, AgentTaskStatus};
+use crate::api::error::AppError;
+use crate::logs::{LogEvent, LogLevel, LogQuery, LogStore, MAX_SEARCH_LIMIT};
+
+/// Component of log events forwarded by the MCP server, whose messages
+/// appear as [`TraceEntryKind::Mcp`] entries
+pub const MCP_COMPONENT: &str = "mcp";
+
+/// What a timeline entry records
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum TraceEntryKind {
+    /// A log event forwarded by an agent, plugin or the CLI
+    Log,
+    /// A message the MCP server logged
+    Mcp,
+    /// An MCP tool execution
+    Tool,
+    /// A change of state of an agent task
+    Task,
+}
+
+/// One entry of a trace's timeline
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TraceEntry {
+    /// When it happened
+    pub timestamp: DateTime<Utc>,
+    /// What it records
+    pub kind: TraceEntryKind,
+    /// Component it happened in, e.g. `agent`, `plugin:blast` or `tool:fetch`
+    pub component: String,
+    /// Severity, `error` for failed executions and tasks
+    pub level: LogLevel,
+    /// What happened
+    pub message: String,
+    /// Machine or agent it happened on
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub source: Option<String>,
+    /// Further structured fields
+    #[serde(default, skip_serializing_if = "Map::is_empty")]
+    pub fields: Map<String, Value>,
+}
+
+/// The timeline of a request or command
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Trace {
+    /// ID the trace was looked up by
+    pub id: String,
+    /// Agent tasks and requests the trace spans, the ID first
+    pub runs: Vec<String>,
+    /// Entries, oldest first
+    pub entries: Vec<TraceEntry>,
+}
+
+/// Gathers traces from forwarded logs, agent tasks and tool executions
+#[derive(Clone, Default)]
+pub struct TraceCollector {
+    logs: Option<Arc<dyn LogStore>>,
+    scheduler: Option<Arc<AgentScheduler>>,
+    tools: Option<Arc<ToolManager>>,
+}
+
+impl TraceCollector {
+    /// Create a new TraceCollector with nothing to gather from
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Gather the log events forwarded to `store`
+    pub fn with_log_store(mut self, store: Arc<dyn LogStore>) -> Self {
+        self.logs = Some(store);
+        self
+    }
+
+    /// Gather the tasks of `scheduler`
+    pub fn with_agent_scheduler(mut self, scheduler: Arc<AgentScheduler>) -> Self {
+        self.scheduler = Some(scheduler);
+        self
+    }
+
+    /// Gather the tool executions `tools` recorded
+    pub fn with_tool_manager(mut self, tools: Arc<ToolManager>) -> Self {
+        self.tools = Some(tools);
+        self
+    }
+
+    /// The timeline of trace `id`
+    ///
+    /// The trace spans the tasks with that ID or request ID, so it takes in
+    /// the events agents logged for those tasks. With `viewer` set, it is
+    /// limited to what that user may see: everything when they submitted one
+    /// of its tasks, otherwise only the log events they forwarded.
+    ///
+    /// Fails with [`AppError::NotFound`] when nothing is recorded for `id`.
+    pub async fn collect(&self, id: &str, viewer: Option<&str>) -> Result<Trace, AppError> {
+        let tasks = match &self.scheduler {
+            Some(scheduler) => scheduler.traced_tasks(id).await,
+            None => Vec::new(),
+        };
+        let mut runs = vec![id.to_string()];
+        let mut seen: BTreeSet<String> = runs.iter().cloned().collect();
+        for task in &tasks {
+            for run in std::iter::once(&task.id).chain(task.request.context.request_id.as_ref()) {
+                if seen.insert(run.clone()) {
+                    runs.push(run.clone());
+                }
+            }
+        }
+        let owner = viewer.is_none_or(|viewer| {
+            tasks
+                .iter()
+                .any(|task| task.request.context.user.as_deref() == Some(viewer))
+        });
+
+        let mut entries = Vec::new();
+        if owner {
+            entries.extend(tasks.iter().flat_map(task_entries));
+        }
+        for run in &runs {
+            if let Some(store) = &self.logs {
+                let query = LogQuery {
+                    run_id: Some(run.clone()),
+                    submitted_by: if owner { None } else { viewer.map(str::to_string) },
+                    limit: Some(MAX_SEARCH_LIMIT),
+                    ..LogQuery::default()
+                };
+                entries.extend(store.search(&query).await?.into_iter().map(log_entry));
+            }
+            if let (true, Some(tools)) = (owner, &self.tools) {
+                let executions = tools.get_request_executions(run).await;
+                entries.extend(executions.into_iter().map(|(tool_id, record)| tool_entry(tool_id, record)));
+            }
+        }
+        if entries.is_empty() {
+            return Err(AppError::NotFound(format!("Trace {} not found", id)));
+        }
+        entries.sort_by_key(|entry| entry.timestamp);
+
+        Ok(Trace {
+            id: id.to_string(),
+            runs,
+            entries,
+        })
+    }
+}
+
+/// Entry of a forwarded log event
+fn log_entry(event: LogEvent) -> TraceEntry {
+    let LogEvent {
+        id,
+        event,
+        submitted_by,
+        ..
+    } = event;
+    let mcp = event.component == MCP_COMPONENT
+        || event
+            .component
+            .strip_prefix(MCP_COMPONENT)
+            .is_some_and(|rest| rest.starts_with(':'));
+    let mut fields = event.fields;
+    fields.insert("log_id".to_string(), Value::from(id));
+    fields.insert("submitted_by".to_string(), Value::from(submitted_by));
+    if let Some(run) = event.run_id {
+        fields.insert("run_id".to_string(), Value::from(run));
+    }
+    TraceEntry {
+        timestamp: event.timestamp,
+        kind: if mcp { TraceEntryKind::Mcp } else { TraceEntryKind::Log },
+        component: event.component,
+        level: event.level,
+        message: event.message,
+        source: event.source,
+        fields,
+    }
+}
+
+/// Entry of a tool execution, at the time it started
+fn tool_entry(tool_id: String, record: ExecutionRecord) -> TraceEntry {
+    let level = if record.is_error() {
+        LogLevel::Error
+    } else {
+        LogLevel::Info
+    };
+    let mut message = format!(
+        "{} v{} {:?} in {} ms",
+        record.capability, record.capability_version, record.status, record.duration_ms
+    );
+    if let Some(error) = &record.error_message {
+        message.push_str(&format!(": {}", error));
+    }
+    let mut fields = Map::new();
+    fields.insert("request_id".to_string(), Value::from(record.request_id));
+    fields.insert("capability".to_string(), Value::from(record.capability));
+    fields.insert("duration_ms".to_string(), Value::from(record.duration_ms));
+    TraceEntry {
+        timestamp: record.started_at,
+        kind: TraceEntryKind::Tool,
+        component: format!("tool:{}", tool_id),
+        level,
+        message,
+        source: None,
+        fields,
+    }
+}
+
+/// Entries of an agent task: its submission, latest dispatch and completion
+fn task_entries(task: &AgentTask) -> Vec<TraceEntry> {
+    let entry = |timestamp: DateTime<Utc>, level, message: String| {
+        let mut fields = Map::new();
+        fields.insert("task_id".to_string(), Value::from(task.id.clone()));
+        fields.insert("target".to_string(), Value::from(task.request.target.clone()));
+        TraceEntry {
+            timestamp,
+            kind: TraceEntryKind::Task,
+            component: "scheduler".to_string(),
+            level,
+            message,
+            source: task.agent_id.clone(),
+            fields,
+        }
+    };
+    let info = LogLevel::Info;
+    let mut entries = vec![entry(task.created_at, info, format!("Task {} submitted", task.id))];
+    if let Some(started_at) = task.started_at {
+        let agent = task.agent_id.as_deref().unwrap_or("an agent");
+        entries.push(entry(
+            started_at,
+            info,
+            format!("Task {} dispatched to {} (attempt {})", task.id, agent, task.attempts),
+        ));
+    }
+    if let Some(finished_at) = task.finished_at {
+        entries.push(match task.status {
+            AgentTaskStatus::Failed => entry(
+                finished_at,
+                LogLevel::Error,
+                format!("Task {} failed: {}", task.id, task.error.as_deref().unwrap_or("unknown error")),
+            ),
+            _ => entry(finished_at, info, format!("Task {} succeeded", task.id)),
+        });
+    }
+    entries
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::agents::{Affinity, TaskRequest};
+    use crate::config::AgentConfig;
+    use crate::logs::{MemoryLogStore, NewLogEvent};
+    use chrono::{Duration, TimeZone};
+    use squirrel_commands::wire::WireContext;
+    use squirrel_commands::workflow::StepKind;
+
+    #[tokio::test]
+    async fn test_trace_spans_tasks_of_a_request() {
+        let scheduler = Arc::new(AgentScheduler::new(AgentConfig::default()));
+        let task_id = scheduler
+            .submit(TaskRequest {
+                kind: StepKind::Command,
+                target: "blast".to_string(),
+                args: Vec::new(),
+                affinity: Affinity::default(),
+                timeout_secs: None,
+                context: WireContext {
+                    request_id: Some("req-1".to_string()),
+                    user: Some("alice".to_string()),
+                    ..WireContext::default()
+                },
+            })
+            .await
+            .unwrap();
+
+        let start = Utc.with_ymd_and_hms(2026, 10, 17, 9, 0, 0).unwrap();
+        let event = |minutes: i64, component: &str, run: &str, message: &str| NewLogEvent {
+            timestamp: start + Duration::minutes(minutes),
+            run_id: Some(run.to_string()),
+            ..NewLogEvent::new(LogLevel::Info, component, message)
+        };
+        let store = Arc::new(MemoryLogStore::new(100));
+        store
+            .append(
+                "agent",
+                vec![
+                    event(2, "plugin:blast", &task_id, "Searching nt"),
+                    event(1, "agent", &task_id, "Task started"),
+                ],
+            )
+            .await
+            .unwrap();
+        store
+            .append("alice", vec![event(0, "mcp", "req-1", "tools/call blast")])
+            .await
+            .unwrap();
+        let collector = TraceCollector::new()
+            .with_log_store(store)
+            .with_agent_scheduler(scheduler);
+
+        let trace = collector.collect("req-1", None).await.unwrap();
+        assert_eq!(trace.runs, ["req-1".to_string(), task_id.clone()]);
+        let logged: Vec<_> = trace
+            .entries
+            .iter()
+            .filter(|entry| entry.kind != TraceEntryKind::Task)
+            .map(|entry| (entry.kind, entry.message.as_str()))
+            .collect();
+        assert_eq!(
+            logged,
+            [
+                (TraceEntryKind::Mcp, "tools/call blast"),
+                (TraceEntryKind::Log, "Task started"),
+                (TraceEntryKind::Log, "Searching nt"),
+            ]
+        );
+        assert!(trace.entries.iter().any(|entry| entry.kind == TraceEntryKind::Task));
+
+        let by_task = collector.collect(&task_id, Some("alice")).await.unwrap();
+        assert_eq!(by_task.entries.len(), trace.entries.len());
+        let other = collector.collect("req-1", Some("bob")).await;
+        assert!(matches!(other, Err(AppError::NotFound(_))));
+        assert!(matches!(collector.collect("req-2", None).await, Err(AppError::NotFound(_))));
+    }
+}
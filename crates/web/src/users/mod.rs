@@ -0,0 +1,588 @@
+//! User accounts, teams and invitations.
+//!
+//! Admins create accounts directly or invite users by email address; the
+//! invitee accepts with the invitation token, choosing a username and
+//! password. The server sends no email, so invitation and password reset
+//! tokens are handed to the user by whoever issued them. Both tokens are
+//! signed with the access token signing keys and expire; a reset token is
+//! bound to the password it replaces, so it works once. Deactivated accounts
+//! can not sign in and their sessions end.
+
+pub mod store;
+
+pub use store::{Invitation, MemoryUserStore, SqlUserStore, Team, TeamMember, TeamRole, UserAccount, UserStore};
+
+use std::sync::Arc;
+
+use chrono::{DateTime, Duration, Utc};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use uuid::Uuid;
+
+use crate::api::error::AppError;
+use crate::auth::models::Role;
+use crate::auth::{AuthError, AuthService};
+use crate::config::{ReadCacheConfig, UsersConfig};
+use crate::read_cache::{CacheMetrics, ReadCache};
+
+/// What a signed user token can be used for
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+enum TokenPurpose {
+    /// Accepting the invitation named by `sub`
+    Invitation,
+    /// Resetting the password of the user named by `sub`
+    PasswordReset,
+}
+
+/// Claims of invitation and password reset tokens
+#[derive(Debug, Serialize, Deserialize)]
+struct UserTokenClaims {
+    sub: Uuid,
+    purpose: TokenPurpose,
+    /// Fingerprint of the password a reset token replaces
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pwd: Option<String>,
+    exp: i64,
+}
+
+/// An invitation and the token accepting it
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct IssuedInvitation {
+    /// The stored invitation
+    pub invitation: Invitation,
+    /// Token to hand to the invitee
+    pub token: String,
+}
+
+/// A password reset token
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PasswordReset {
+    /// User whose password the token resets
+    pub user_id: Uuid,
+    /// Token to hand to the user
+    pub token: String,
+    /// When the token stops working
+    pub expires_at: DateTime<Utc>,
+}
+
+/// Fingerprint of a password hash, so reset tokens do not carry the hash
+fn password_fingerprint(password_hash: &str) -> String {
+    hex::encode(&Sha256::digest(password_hash.as_bytes())[..8])
+}
+
+/// Map an authentication error to the API error it is reported as
+fn auth_error(error: AuthError) -> AppError {
+    AppError::Internal(error.to_string())
+}
+
+/// Service managing user accounts, teams and invitations
+pub struct UserDirectory {
+    store: Arc<dyn UserStore>,
+    auth: AuthService,
+    config: UsersConfig,
+    /// Accounts by ID
+    users: ReadCache<Uuid, UserAccount>,
+    /// Members of teams by team ID
+    members: ReadCache<Uuid, Vec<TeamMember>>,
+}
+
+impl UserDirectory {
+    /// Create a new UserDirectory, signing tokens with the keys of `auth`
+    pub fn new(store: Arc<dyn UserStore>, auth: AuthService, config: UsersConfig) -> Self {
+        Self {
+            store,
+            auth,
+            config,
+            users: ReadCache::disabled("users"),
+            members: ReadCache::disabled("team_members"),
+        }
+    }
+
+    /// Cache accounts and team members as `config` says
+    pub fn with_caches(mut self, config: &ReadCacheConfig) -> Self {
+        self.users = ReadCache::new("users", config.users.clone());
+        self.members = ReadCache::new("team_members", config.team_members.clone());
+        self
+    }
+
+    /// Report the hits and misses of the caches to `metrics`
+    pub fn register_caches(&self, metrics: &CacheMetrics) {
+        metrics.register(&self.users);
+        metrics.register(&self.members);
+    }
+
+    /// Members of a team, from the cache if they are cached
+    async fn cached_members(&self, team_id: Uuid) -> Result<Vec<TeamMember>, AppError> {
+        self.members
+            .get_or_load(team_id, || self.store.members(team_id))
+            .await
+    }
+
+    fn check_username(username: &str) -> Result<(), AppError> {
+        let valid = !username.is_empty()
+            && username.len() <= 64
+            && username.chars().all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.'));
+        if valid {
+            Ok(())
+        } else {
+            Err(AppError::InvalidRequest(
+                "Usernames have 1 to 64 letters, digits, '-', '_' or '.'".to_string(),
+            ))
+        }
+    }
+
+    fn check_email(email: &str) -> Result<(), AppError> {
+        match email.split_once('@') {
+            Some((local, domain)) if !local.is_empty() && !domain.is_empty() && !email.contains(char::is_whitespace) => {
+                Ok(())
+            }
+            _ => Err(AppError::InvalidRequest(format!("Invalid email address '{}'", email))),
+        }
+    }
+
+    fn hash_password(&self, password: &str) -> Result<String, AppError> {
+        if password.chars().count() < self.config.min_password_length {
+            return Err(AppError::InvalidRequest(format!(
+                "Passwords have at least {} characters",
+                self.config.min_password_length
+            )));
+        }
+        bcrypt::hash(password, bcrypt::DEFAULT_COST)
+            .map_err(|e| AppError::Internal(format!("Failed to hash password: {}", e)))
+    }
+
+    /// Verify a token issued for `purpose` that lives for at most `ttl`
+    async fn verify_token(&self, token: &str, purpose: TokenPurpose, ttl: Duration) -> Result<UserTokenClaims, AppError> {
+        let invalid = || AppError::Unauthorized("Invalid or expired token".to_string());
+        let claims: UserTokenClaims = self.auth.verify_signed(token, ttl).await.map_err(|_| invalid())?;
+        if claims.purpose != purpose {
+            return Err(invalid());
+        }
+        Ok(claims)
+    }
+
+    /// Create an account
+    pub async fn create_user(&self, username: &str, email: &str, password: &str, role: Role) -> Result<UserAccount, AppError> {
+        Self::check_username(username)?;
+        Self::check_email(email)?;
+        let password_hash = self.hash_password(password)?;
+        let now = Utc::now();
+        let user = UserAccount {
+            id: Uuid::new_v4(),
+            username: username.to_string(),
+            email: email.to_string(),
+            role,
+            created_at: now,
+            updated_at: now,
+            deactivated_at: None,
+        };
+        self.store.create_user(&user, &password_hash).await?;
+        Ok(user)
+    }
+
+    /// The account with `id`
+    pub async fn user(&self, id: Uuid) -> Result<UserAccount, AppError> {
+        self.users
+            .get_or_load(id, || async {
+                self.store
+                    .user(id)
+                    .await?
+                    .ok_or_else(|| AppError::NotFound(format!("No user {}", id)))
+            })
+            .await
+    }
+
+    /// The account with `name` as ID, username or email address
+    pub async fn find_user(&self, name: &str) -> Result<UserAccount, AppError> {
+        if let Ok(id) = Uuid::parse_str(name) {
+            return self.user(id).await;
+        }
+        self.store
+            .user_by_name(name)
+            .await?
+            .ok_or_else(|| AppError::NotFound(format!("No user {}", name)))
+    }
+
+    /// All accounts by username, leaving out deactivated ones unless asked
+    pub async fn users(&self, include_deactivated: bool) -> Result<Vec<UserAccount>, AppError> {
+        self.store.users(include_deactivated).await
+    }
+
+    /// Change the role of an account
+    ///
+    /// Access tokens already issued keep the old role until they expire.
+    pub async fn set_role(&self, id: Uuid, role: Role) -> Result<UserAccount, AppError> {
+        let updated = self.store.set_role(id, role).await?;
+        self.users.invalidate(&id);
+        if !updated {
+            return Err(AppError::NotFound(format!("No user {}", id)));
+        }
+        self.user(id).await
+    }
+
+    /// Deactivate an account, ending its sessions
+    pub async fn deactivate(&self, id: Uuid) -> Result<UserAccount, AppError> {
+        let updated = self.store.set_deactivated(id, Some(Utc::now())).await?;
+        self.users.invalidate(&id);
+        if !updated {
+            return Err(AppError::NotFound(format!("No user {}", id)));
+        }
+        self.auth.logout_all(id).await.map_err(auth_error)?;
+        self.user(id).await
+    }
+
+    /// Let a deactivated account sign in again
+    pub async fn reactivate(&self, id: Uuid) -> Result<UserAccount, AppError> {
+        let updated = self.store.set_deactivated(id, None).await?;
+        self.users.invalidate(&id);
+        if !updated {
+            return Err(AppError::NotFound(format!("No user {}", id)));
+        }
+        self.user(id).await
+    }
+
+    /// Invite `email` to create an account with `role`, joining `team_id`
+    pub async fn invite(
+        &self,
+        inviter: &str,
+        email: &str,
+        role: Role,
+        team_id: Option<Uuid>,
+    ) -> Result<IssuedInvitation, AppError> {
+        Self::check_email(email)?;
+        if self.store.user_by_name(email).await?.is_some() {
+            return Err(AppError::Conflict(format!("{} already has an account", email)));
+        }
+        if let Some(team_id) = team_id {
+            self.team(team_id).await?;
+        }
+        let now = Utc::now();
+        let invitation = Invitation {
+            id: Uuid::new_v4(),
+            email: email.to_string(),
+            role,
+            team_id,
+            invited_by: inviter.to_string(),
+            created_at: now,
+            expires_at: now + Duration::hours(self.config.invitation_ttl_hours),
+            accepted_by: None,
+            accepted_at: None,
+        };
+        let token = self
+            .auth
+            .sign(&UserTokenClaims {
+                sub: invitation.id,
+                purpose: TokenPurpose::Invitation,
+                pwd: None,
+                exp: invitation.expires_at.timestamp(),
+            })
+            .map_err(auth_error)?;
+        self.store.create_invitation(&invitation).await?;
+        Ok(IssuedInvitation { invitation, token })
+    }
+
+    /// Invitations neither accepted nor expired, newest first
+    pub async fn invitations(&self) -> Result<Vec<Invitation>, AppError> {
+        self.store.pending_invitations(Utc::now()).await
+    }
+
+    /// Revoke an invitation, so its token no longer works
+    pub async fn revoke_invitation(&self, id: Uuid) -> Result<(), AppError> {
+        if self.store.delete_invitation(id).await? {
+            Ok(())
+        } else {
+            Err(AppError::NotFound(format!("No invitation {}", id)))
+        }
+    }
+
+    /// Accept an invitation, creating its account with `username` and `password`
+    pub async fn accept_invitation(&self, token: &str, username: &str, password: &str) -> Result<UserAccount, AppError> {
+        let ttl = Duration::hours(self.config.invitation_ttl_hours);
+        let claims = self.verify_token(token, TokenPurpose::Invitation, ttl).await?;
+        let invitation = self
+            .store
+            .invitation(claims.sub)
+            .await?
+            .ok_or_else(|| AppError::Unauthorized("The invitation was revoked".to_string()))?;
+        if invitation.accepted_at.is_some() {
+            return Err(AppError::Conflict("The invitation was already accepted".to_string()));
+        }
+        if invitation.expires_at <= Utc::now() {
+            return Err(AppError::Unauthorized("Invalid or expired token".to_string()));
+        }
+
+        Self::check_username(username)?;
+        let password_hash = self.hash_password(password)?;
+        let now = Utc::now();
+        let user = UserAccount {
+            id: Uuid::new_v4(),
+            username: username.to_string(),
+            email: invitation.email.clone(),
+            role: invitation.role,
+            created_at: now,
+            updated_at: now,
+            deactivated_at: None,
+        };
+        let membership = invitation.team_id.map(|team_id| TeamMember {
+            team_id,
+            user_id: user.id,
+            username: None,
+            role: TeamRole::Member,
+            added_at: now,
+        });
+        if !self
+            .store
+            .accept_invitation(invitation.id, &user, &password_hash, membership.as_ref())
+            .await?
+        {
+            return Err(AppError::Conflict("The invitation was already accepted".to_string()));
+        }
+        if let Some(team_id) = invitation.team_id {
+            self.members.invalidate(&team_id);
+        }
+        Ok(user)
+    }
+
+    /// Issue a token resetting the password of an active account
+    pub async fn issue_password_reset(&self, id: Uuid) -> Result<PasswordReset, AppError> {
+        let user = self.user(id).await?;
+        if !user.is_active() {
+            return Err(AppError::InvalidRequest(format!("User {} is deactivated", user.username)));
+        }
+        let password_hash = self
+            .store
+            .password_hash(id)
+            .await?
+            .ok_or_else(|| AppError::NotFound(format!("No user {}", id)))?;
+        let expires_at = Utc::now() + Duration::minutes(self.config.password_reset_ttl_minutes);
+        let token = self
+            .auth
+            .sign(&UserTokenClaims {
+                sub: id,
+                purpose: TokenPurpose::PasswordReset,
+                pwd: Some(password_fingerprint(&password_hash)),
+                exp: expires_at.timestamp(),
+            })
+            .map_err(auth_error)?;
+        Ok(PasswordReset { user_id: id, token, expires_at })
+    }
+
+    /// Set a new password with a reset token, ending the user's sessions
+    pub async fn reset_password(&self, token: &str, password: &str) -> Result<UserAccount, AppError> {
+        let ttl = Duration::minutes(self.config.password_reset_ttl_minutes);
+        let claims = self.verify_token(token, TokenPurpose::PasswordReset, ttl).await?;
+        let user = self.user(claims.sub).await?;
+        let current = self.store.password_hash(user.id).await?.map(|hash| password_fingerprint(&hash));
+        if !user.is_active() || current.is_none() || current != claims.pwd {
+            return Err(AppError::Unauthorized("Invalid or expired token".to_string()));
+        }
+
+        let password_hash = self.hash_password(password)?;
+        self.store.set_password_hash(user.id, &password_hash).await?;
+        self.users.invalidate(&user.id);
+        self.auth.logout_all(user.id).await.map_err(auth_error)?;
+        self.user(user.id).await
+    }
+
+    /// Create a team
+    pub async fn create_team(&self, actor: &str, name: &str, description: Option<String>) -> Result<Team, AppError> {
+        if name.trim().is_empty() {
+            return Err(AppError::InvalidRequest("Team names can not be empty".to_string()));
+        }
+        let now = Utc::now();
+        let team = Team {
+            id: Uuid::new_v4(),
+            name: name.trim().to_string(),
+            description,
+            created_by: actor.to_string(),
+            created_at: now,
+            updated_at: now,
+        };
+        self.store.create_team(&team).await?;
+        Ok(team)
+    }
+
+    /// The team with `id`
+    pub async fn team(&self, id: Uuid) -> Result<Team, AppError> {
+        self.store
+            .team(id)
+            .await?
+            .ok_or_else(|| AppError::NotFound(format!("No team {}", id)))
+    }
+
+    /// The team with `name` as ID or name
+    pub async fn find_team(&self, name: &str) -> Result<Team, AppError> {
+        if let Ok(id) = Uuid::parse_str(name) {
+            return self.team(id).await;
+        }
+        self.store
+            .teams(None)
+            .await?
+            .into_iter()
+            .find(|team| team.name == name)
+            .ok_or_else(|| AppError::NotFound(format!("No team {}", name)))
+    }
+
+    /// All teams by name, or those `member` belongs to
+    pub async fn teams(&self, member: Option<Uuid>) -> Result<Vec<Team>, AppError> {
+        self.store.teams(member).await
+    }
+
+    /// Rename a team or change its description
+    pub async fn update_team(&self, id: Uuid, name: Option<String>, description: Option<String>) -> Result<Team, AppError> {
+        let mut team = self.team(id).await?;
+        if let Some(name) = name {
+            if name.trim().is_empty() {
+                return Err(AppError::InvalidRequest("Team names can not be empty".to_string()));
+            }
+            team.name = name.trim().to_string();
+        }
+        if description.is_some() {
+            team.description = description;
+        }
+        team.updated_at = Utc::now();
+        if !self.store.update_team(&team).await? {
+            return Err(AppError::NotFound(format!("No team {}", id)));
+        }
+        Ok(team)
+    }
+
+    /// Delete a team and its memberships
+    pub async fn delete_team(&self, id: Uuid) -> Result<(), AppError> {
+        let deleted = self.store.delete_team(id).await?;
+        self.members.invalidate(&id);
+        if deleted {
+            Ok(())
+        } else {
+            Err(AppError::NotFound(format!("No team {}", id)))
+        }
+    }
+
+    /// Members of a team, by the time they joined
+    pub async fn members(&self, team_id: Uuid) -> Result<Vec<TeamMember>, AppError> {
+        self.team(team_id).await?;
+        self.cached_members(team_id).await
+    }
+
+    /// Role of `user_id` in a team, if they are a member
+    pub async fn team_role(&self, team_id: Uuid, user_id: Uuid) -> Result<Option<TeamRole>, AppError> {
+        Ok(self
+            .cached_members(team_id)
+            .await?
+            .into_iter()
+            .find(|member| member.user_id == user_id)
+            .map(|member| member.role))
+    }
+
+    /// Add an active user to a team, or change their role in it
+    pub async fn set_member(&self, team_id: Uuid, user_id: Uuid, role: TeamRole) -> Result<TeamMember, AppError> {
+        self.team(team_id).await?;
+        let user = self.user(user_id).await?;
+        if !user.is_active() {
+            return Err(AppError::InvalidRequest(format!("User {} is deactivated", user.username)));
+        }
+        self.store
+            .set_member(&TeamMember {
+                team_id,
+                user_id,
+                username: None,
+                role,
+                added_at: Utc::now(),
+            })
+            .await?;
+        self.members.invalidate(&team_id);
+        self.store
+            .members(team_id)
+            .await?
+            .into_iter()
+            .find(|member| member.user_id == user_id)
+            .ok_or_else(|| AppError::Internal(format!("Member {} of team {} vanished", user_id, team_id)))
+    }
+
+    /// Remove a user from a team
+    pub async fn remove_member(&self, team_id: Uuid, user_id: Uuid) -> Result<(), AppError> {
+        let removed = self.store.remove_member(team_id, user_id).await?;
+        self.members.invalidate(&team_id);
+        if removed {
+            Ok(())
+        } else {
+            Err(AppError::NotFound(format!("User {} is not a member of team {}", user_id, team_id)))
+        }
+    }
+
+    /// Drop what the caches hold of a user whose records were changed
+    /// behind the directory, such as by an erasure
+    pub fn forget_user(&self, id: Uuid) {
+        self.users.invalidate(&id);
+        self.members.clear();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::auth::AuthConfig;
+    use crate::test_helpers::migrated_pool;
+
+    async fn directory() -> UserDirectory {
+        let pool = migrated_pool().await;
+        let auth = AuthService::new(AuthConfig::default(), pool);
+        UserDirectory::new(Arc::new(MemoryUserStore::new()), auth, UsersConfig::default())
+            .with_caches(&ReadCacheConfig::default())
+    }
+
+    #[tokio::test]
+    async fn test_invitations_and_password_resets_use_signed_single_use_tokens() {
+        let directory = directory().await;
+        let team = directory.create_team("root", "genomics", None).await.unwrap();
+        assert!(matches!(
+            directory.invite("root", "not-an-email", Role::User, None).await,
+            Err(AppError::InvalidRequest(_))
+        ));
+
+        let issued = directory
+            .invite("root", "grace@example.org", Role::User, Some(team.id))
+            .await
+            .unwrap();
+        assert!(matches!(
+            directory.accept_invitation(&issued.token, "grace", "short").await,
+            Err(AppError::InvalidRequest(_))
+        ));
+        let grace = directory
+            .accept_invitation(&issued.token, "grace", "correct horse")
+            .await
+            .unwrap();
+        assert_eq!(grace.email, "grace@example.org");
+        assert_eq!(directory.team_role(team.id, grace.id).await.unwrap(), Some(TeamRole::Member));
+        assert!(matches!(
+            directory.accept_invitation(&issued.token, "mallory", "correct horse").await,
+            Err(AppError::Conflict(_))
+        ));
+        assert!(directory.invitations().await.unwrap().is_empty());
+
+        // A reset token can not stand in for an invitation, and works once
+        let reset = directory.issue_password_reset(grace.id).await.unwrap();
+        assert!(matches!(
+            directory.accept_invitation(&reset.token, "mallory", "correct horse").await,
+            Err(AppError::Unauthorized(_))
+        ));
+        directory.reset_password(&reset.token, "battery staple").await.unwrap();
+        assert!(matches!(
+            directory.reset_password(&reset.token, "battery staple").await,
+            Err(AppError::Unauthorized(_))
+        ));
+
+        directory.deactivate(grace.id).await.unwrap();
+        assert!(directory.users(false).await.unwrap().is_empty());
+        assert!(directory.issue_password_reset(grace.id).await.is_err());
+        assert!(directory.set_member(team.id, grace.id, TeamRole::Owner).await.is_err());
+        assert!(directory.reactivate(grace.id).await.unwrap().is_active());
+
+        // Writes show in the cached reads at once
+        directory.set_member(team.id, grace.id, TeamRole::Owner).await.unwrap();
+        assert_eq!(directory.team_role(team.id, grace.id).await.unwrap(), Some(TeamRole::Owner));
+        assert_eq!(directory.set_role(grace.id, Role::Admin).await.unwrap().role, Role::Admin);
+        assert_eq!(directory.user(grace.id).await.unwrap().role, Role::Admin);
+    }
+}
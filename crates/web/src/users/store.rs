@@ -0,0 +1,920 @@
+//! User, team and invitation storage backends.
+
+use std::collections::BTreeMap;
+use std::str::FromStr;
+
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use sqlx::sqlite::SqliteRow;
+use sqlx::{Row, SqlitePool};
+use tokio::sync::Mutex;
+use uuid::Uuid;
+
+use crate::api::error::AppError;
+use crate::auth::models::Role;
+use crate::db::{timestamp, InstrumentedPool};
+
+/// A user account, without its password hash
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct UserAccount {
+    /// User ID
+    pub id: Uuid,
+    /// Username
+    pub username: String,
+    /// Email address
+    pub email: String,
+    /// Role of the user
+    pub role: Role,
+    /// Account creation time
+    pub created_at: DateTime<Utc>,
+    /// Last update time
+    pub updated_at: DateTime<Utc>,
+    /// When an admin deactivated the account, if they did
+    pub deactivated_at: Option<DateTime<Utc>>,
+}
+
+impl UserAccount {
+    /// Whether the user can sign in
+    pub fn is_active(&self) -> bool {
+        self.deactivated_at.is_none()
+    }
+}
+
+/// Role of a user within a team
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum TeamRole {
+    /// Manages the members of the team
+    Owner,
+    /// Belongs to the team
+    Member,
+}
+
+impl TeamRole {
+    /// The role as stored, e.g. `owner`
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Owner => "owner",
+            Self::Member => "member",
+        }
+    }
+}
+
+impl FromStr for TeamRole {
+    type Err = String;
+
+    fn from_str(role: &str) -> Result<Self, Self::Err> {
+        match role.to_ascii_lowercase().as_str() {
+            "owner" => Ok(Self::Owner),
+            "member" => Ok(Self::Member),
+            _ => Err(format!("Unknown team role '{}', expected owner or member", role)),
+        }
+    }
+}
+
+/// A team of users
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Team {
+    /// Team ID
+    pub id: Uuid,
+    /// Unique name
+    pub name: String,
+    /// What the team works on
+    pub description: Option<String>,
+    /// User who created the team
+    pub created_by: String,
+    /// Creation time
+    pub created_at: DateTime<Utc>,
+    /// Last update time
+    pub updated_at: DateTime<Utc>,
+}
+
+/// Membership of a user in a team
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TeamMember {
+    /// Team ID
+    pub team_id: Uuid,
+    /// User ID
+    pub user_id: Uuid,
+    /// Username of the member; filled in when read, ignored when written
+    #[serde(default)]
+    pub username: Option<String>,
+    /// Role within the team
+    pub role: TeamRole,
+    /// When the user joined the team
+    pub added_at: DateTime<Utc>,
+}
+
+/// An invitation to create an account
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Invitation {
+    /// Invitation ID, named by the invitation token
+    pub id: Uuid,
+    /// Email address of the new account
+    pub email: String,
+    /// Role of the new account
+    pub role: Role,
+    /// Team the new account joins as a member
+    pub team_id: Option<Uuid>,
+    /// User who sent the invitation
+    pub invited_by: String,
+    /// Creation time
+    pub created_at: DateTime<Utc>,
+    /// After this the invitation can no longer be accepted
+    pub expires_at: DateTime<Utc>,
+    /// Account created by accepting the invitation
+    pub accepted_by: Option<Uuid>,
+    /// When the invitation was accepted
+    pub accepted_at: Option<DateTime<Utc>>,
+}
+
+/// Storage for user accounts, teams and invitations
+#[async_trait]
+pub trait UserStore: Send + Sync {
+    /// Create an account; fails with a conflict if the username or email is taken
+    async fn create_user(&self, user: &UserAccount, password_hash: &str) -> Result<(), AppError>;
+
+    /// The account with `id`
+    async fn user(&self, id: Uuid) -> Result<Option<UserAccount>, AppError>;
+
+    /// The account with `name` as username or email address
+    async fn user_by_name(&self, name: &str) -> Result<Option<UserAccount>, AppError>;
+
+    /// All accounts by username, leaving out deactivated ones unless asked
+    async fn users(&self, include_deactivated: bool) -> Result<Vec<UserAccount>, AppError>;
+
+    /// Change the role of an account; returns false if it does not exist
+    async fn set_role(&self, id: Uuid, role: Role) -> Result<bool, AppError>;
+
+    /// Deactivate an account, or reactivate it with `None`; returns false if
+    /// it does not exist
+    async fn set_deactivated(&self, id: Uuid, deactivated_at: Option<DateTime<Utc>>) -> Result<bool, AppError>;
+
+    /// Password hash of an account
+    async fn password_hash(&self, id: Uuid) -> Result<Option<String>, AppError>;
+
+    /// Replace the password hash of an account; returns false if it does not exist
+    async fn set_password_hash(&self, id: Uuid, password_hash: &str) -> Result<bool, AppError>;
+
+    /// Create a team; fails with a conflict if the name is taken
+    async fn create_team(&self, team: &Team) -> Result<(), AppError>;
+
+    /// The team with `id`
+    async fn team(&self, id: Uuid) -> Result<Option<Team>, AppError>;
+
+    /// All teams by name, or those `member` belongs to
+    async fn teams(&self, member: Option<Uuid>) -> Result<Vec<Team>, AppError>;
+
+    /// Replace the name and description of a team; returns false if it does not exist
+    async fn update_team(&self, team: &Team) -> Result<bool, AppError>;
+
+    /// Delete a team and its memberships; returns false if it does not exist
+    async fn delete_team(&self, id: Uuid) -> Result<bool, AppError>;
+
+    /// Members of a team, by the time they joined
+    async fn members(&self, team_id: Uuid) -> Result<Vec<TeamMember>, AppError>;
+
+    /// Add a member to a team, or change the role of one
+    async fn set_member(&self, member: &TeamMember) -> Result<(), AppError>;
+
+    /// Remove a member from a team; returns false if they were not one
+    async fn remove_member(&self, team_id: Uuid, user_id: Uuid) -> Result<bool, AppError>;
+
+    /// Store a new invitation
+    async fn create_invitation(&self, invitation: &Invitation) -> Result<(), AppError>;
+
+    /// The invitation with `id`
+    async fn invitation(&self, id: Uuid) -> Result<Option<Invitation>, AppError>;
+
+    /// Invitations neither accepted nor expired at `now`, newest first
+    async fn pending_invitations(&self, now: DateTime<Utc>) -> Result<Vec<Invitation>, AppError>;
+
+    /// Delete an invitation; returns false if it does not exist
+    async fn delete_invitation(&self, id: Uuid) -> Result<bool, AppError>;
+
+    /// Create the account of an invitation, add it to the invitation's team
+    /// and mark the invitation accepted, all at once
+    ///
+    /// Returns false, creating nothing, if the invitation was accepted before.
+    async fn accept_invitation(
+        &self,
+        invitation_id: Uuid,
+        user: &UserAccount,
+        password_hash: &str,
+        membership: Option<&TeamMember>,
+    ) -> Result<bool, AppError>;
+}
+
+/// Parses a stored ID
+fn uuid(id: &str) -> Result<Uuid, AppError> {
+    Uuid::parse_str(id).map_err(|e| AppError::Internal(format!("Invalid ID '{}': {}", id, e)))
+}
+
+/// Maps a unique constraint violation to a conflict
+fn conflict(error: sqlx::Error, message: impl FnOnce() -> String) -> AppError {
+    match &error {
+        sqlx::Error::Database(e) if e.is_unique_violation() => AppError::Conflict(message()),
+        _ => AppError::Database(error),
+    }
+}
+
+/// Reads an account from a `users` row
+fn user_from_row(row: &SqliteRow) -> Result<UserAccount, AppError> {
+    Ok(UserAccount {
+        id: uuid(row.try_get("id")?)?,
+        username: row.try_get("username")?,
+        email: row.try_get("email")?,
+        role: Role::from_str(row.try_get("role")?).map_err(AppError::Internal)?,
+        created_at: row.try_get("created_at")?,
+        updated_at: row.try_get("updated_at")?,
+        deactivated_at: row.try_get("deactivated_at")?,
+    })
+}
+
+/// Reads a team from a `teams` row
+fn team_from_row(row: &SqliteRow) -> Result<Team, AppError> {
+    Ok(Team {
+        id: uuid(row.try_get("id")?)?,
+        name: row.try_get("name")?,
+        description: row.try_get("description")?,
+        created_by: row.try_get("created_by")?,
+        created_at: timestamp(row.try_get("created_at")?)?,
+        updated_at: timestamp(row.try_get("updated_at")?)?,
+    })
+}
+
+/// Reads a member from a `team_members` row joined with its username
+fn member_from_row(row: &SqliteRow) -> Result<TeamMember, AppError> {
+    Ok(TeamMember {
+        team_id: uuid(row.try_get("team_id")?)?,
+        user_id: uuid(row.try_get("user_id")?)?,
+        username: row.try_get("username")?,
+        role: TeamRole::from_str(row.try_get("role")?).map_err(AppError::Internal)?,
+        added_at: timestamp(row.try_get("added_at")?)?,
+    })
+}
+
+/// Reads an invitation from a `user_invitations` row
+fn invitation_from_row(row: &SqliteRow) -> Result<Invitation, AppError> {
+    Ok(Invitation {
+        id: uuid(row.try_get("id")?)?,
+        email: row.try_get("email")?,
+        role: Role::from_str(row.try_get("role")?).map_err(AppError::Internal)?,
+        team_id: row.try_get::<Option<&str>, _>("team_id")?.map(uuid).transpose()?,
+        invited_by: row.try_get("invited_by")?,
+        created_at: timestamp(row.try_get("created_at")?)?,
+        expires_at: timestamp(row.try_get("expires_at")?)?,
+        accepted_by: row.try_get::<Option<&str>, _>("accepted_by")?.map(uuid).transpose()?,
+        accepted_at: row.try_get::<Option<i64>, _>("accepted_at")?.map(timestamp).transpose()?,
+    })
+}
+
+const USER_COLUMNS: &str = "id, username, email, role, created_at, updated_at, deactivated_at";
+
+/// User store backed by the `users`, `teams`, `team_members` and
+/// `user_invitations` tables
+pub struct SqlUserStore {
+    pool: InstrumentedPool,
+}
+
+impl SqlUserStore {
+    /// Create a new SqlUserStore
+    pub fn new(pool: SqlitePool) -> Self {
+        Self { pool: pool.into() }
+    }
+}
+
+#[async_trait]
+impl UserStore for SqlUserStore {
+    async fn create_user(&self, user: &UserAccount, password_hash: &str) -> Result<(), AppError> {
+        let mut conn = self.pool.acquire().await?;
+        insert_user(&mut conn, user, password_hash).await
+    }
+
+    async fn user(&self, id: Uuid) -> Result<Option<UserAccount>, AppError> {
+        sqlx::query(&format!("SELECT {} FROM users WHERE id = ?", USER_COLUMNS))
+            .bind(id.to_string())
+            .fetch_optional(&self.pool)
+            .await?
+            .as_ref()
+            .map(user_from_row)
+            .transpose()
+    }
+
+    async fn user_by_name(&self, name: &str) -> Result<Option<UserAccount>, AppError> {
+        sqlx::query(&format!("SELECT {} FROM users WHERE username = ? OR email = ?", USER_COLUMNS))
+            .bind(name)
+            .bind(name)
+            .fetch_optional(&self.pool)
+            .await?
+            .as_ref()
+            .map(user_from_row)
+            .transpose()
+    }
+
+    async fn users(&self, include_deactivated: bool) -> Result<Vec<UserAccount>, AppError> {
+        sqlx::query(&format!(
+            "SELECT {} FROM users WHERE ? OR deactivated_at IS NULL ORDER BY username",
+            USER_COLUMNS
+        ))
+        .bind(include_deactivated)
+        .fetch_all(&self.pool)
+        .await?
+        .iter()
+        .map(user_from_row)
+        .collect()
+    }
+
+    async fn set_role(&self, id: Uuid, role: Role) -> Result<bool, AppError> {
+        let updated = sqlx::query("UPDATE users SET role = ?, updated_at = ? WHERE id = ?")
+            .bind(role.as_str())
+            .bind(Utc::now())
+            .bind(id.to_string())
+            .execute(&self.pool)
+            .await?
+            .rows_affected();
+        Ok(updated > 0)
+    }
+
+    async fn set_deactivated(&self, id: Uuid, deactivated_at: Option<DateTime<Utc>>) -> Result<bool, AppError> {
+        let updated = sqlx::query("UPDATE users SET deactivated_at = ?, updated_at = ? WHERE id = ?")
+            .bind(deactivated_at)
+            .bind(Utc::now())
+            .bind(id.to_string())
+            .execute(&self.pool)
+            .await?
+            .rows_affected();
+        Ok(updated > 0)
+    }
+
+    async fn password_hash(&self, id: Uuid) -> Result<Option<String>, AppError> {
+        Ok(sqlx::query_scalar("SELECT password_hash FROM users WHERE id = ?")
+            .bind(id.to_string())
+            .fetch_optional(&self.pool)
+            .await?)
+    }
+
+    async fn set_password_hash(&self, id: Uuid, password_hash: &str) -> Result<bool, AppError> {
+        let updated = sqlx::query("UPDATE users SET password_hash = ?, updated_at = ? WHERE id = ?")
+            .bind(password_hash)
+            .bind(Utc::now())
+            .bind(id.to_string())
+            .execute(&self.pool)
+            .await?
+            .rows_affected();
+        Ok(updated > 0)
+    }
+
+    async fn create_team(&self, team: &Team) -> Result<(), AppError> {
+        sqlx::query(
+            "INSERT INTO teams (id, name, description, created_by, created_at, updated_at) VALUES (?, ?, ?, ?, ?, ?)",
+        )
+        .bind(team.id.to_string())
+        .bind(&team.name)
+        .bind(&team.description)
+        .bind(&team.created_by)
+        .bind(team.created_at.timestamp_millis())
+        .bind(team.updated_at.timestamp_millis())
+        .execute(&self.pool)
+        .await
+        .map_err(|e| conflict(e, || format!("Team {} already exists", team.name)))?;
+        Ok(())
+    }
+
+    async fn team(&self, id: Uuid) -> Result<Option<Team>, AppError> {
+        sqlx::query("SELECT * FROM teams WHERE id = ?")
+            .bind(id.to_string())
+            .fetch_optional(&self.pool)
+            .await?
+            .as_ref()
+            .map(team_from_row)
+            .transpose()
+    }
+
+    async fn teams(&self, member: Option<Uuid>) -> Result<Vec<Team>, AppError> {
+        let rows = match member {
+            Some(user_id) => {
+                sqlx::query(
+                    "SELECT teams.* FROM teams JOIN team_members ON team_members.team_id = teams.id
+                     WHERE team_members.user_id = ? ORDER BY teams.name",
+                )
+                .bind(user_id.to_string())
+                .fetch_all(&self.pool)
+                .await?
+            }
+            None => sqlx::query("SELECT * FROM teams ORDER BY name").fetch_all(&self.pool).await?,
+        };
+        rows.iter().map(team_from_row).collect()
+    }
+
+    async fn update_team(&self, team: &Team) -> Result<bool, AppError> {
+        let updated = sqlx::query("UPDATE teams SET name = ?, description = ?, updated_at = ? WHERE id = ?")
+            .bind(&team.name)
+            .bind(&team.description)
+            .bind(team.updated_at.timestamp_millis())
+            .bind(team.id.to_string())
+            .execute(&self.pool)
+            .await
+            .map_err(|e| conflict(e, || format!("Team {} already exists", team.name)))?
+            .rows_affected();
+        Ok(updated > 0)
+    }
+
+    async fn delete_team(&self, id: Uuid) -> Result<bool, AppError> {
+        let mut tx = self.pool.begin().await?;
+        sqlx::query("DELETE FROM team_members WHERE team_id = ?")
+            .bind(id.to_string())
+            .execute(&mut *tx)
+            .await?;
+        sqlx::query("UPDATE user_invitations SET team_id = NULL WHERE team_id = ?")
+            .bind(id.to_string())
+            .execute(&mut *tx)
+            .await?;
+        let deleted = sqlx::query("DELETE FROM teams WHERE id = ?")
+            .bind(id.to_string())
+            .execute(&mut *tx)
+            .await?
+            .rows_affected();
+        tx.commit().await?;
+        Ok(deleted > 0)
+    }
+
+    async fn members(&self, team_id: Uuid) -> Result<Vec<TeamMember>, AppError> {
+        sqlx::query(
+            "SELECT team_members.*, users.username FROM team_members
+             LEFT JOIN users ON users.id = team_members.user_id
+             WHERE team_members.team_id = ? ORDER BY team_members.added_at, team_members.user_id",
+        )
+        .bind(team_id.to_string())
+        .fetch_all(&self.pool)
+        .await?
+        .iter()
+        .map(member_from_row)
+        .collect()
+    }
+
+    async fn set_member(&self, member: &TeamMember) -> Result<(), AppError> {
+        let mut conn = self.pool.acquire().await?;
+        upsert_member(&mut conn, member).await
+    }
+
+    async fn remove_member(&self, team_id: Uuid, user_id: Uuid) -> Result<bool, AppError> {
+        let deleted = sqlx::query("DELETE FROM team_members WHERE team_id = ? AND user_id = ?")
+            .bind(team_id.to_string())
+            .bind(user_id.to_string())
+            .execute(&self.pool)
+            .await?
+            .rows_affected();
+        Ok(deleted > 0)
+    }
+
+    async fn create_invitation(&self, invitation: &Invitation) -> Result<(), AppError> {
+        sqlx::query(
+            "INSERT INTO user_invitations (id, email, role, team_id, invited_by, created_at, expires_at)
+             VALUES (?, ?, ?, ?, ?, ?, ?)",
+        )
+        .bind(invitation.id.to_string())
+        .bind(&invitation.email)
+        .bind(invitation.role.as_str())
+        .bind(invitation.team_id.map(|id| id.to_string()))
+        .bind(&invitation.invited_by)
+        .bind(invitation.created_at.timestamp_millis())
+        .bind(invitation.expires_at.timestamp_millis())
+        .execute(&self.pool)
+        .await?;
+        Ok(())
+    }
+
+    async fn invitation(&self, id: Uuid) -> Result<Option<Invitation>, AppError> {
+        sqlx::query("SELECT * FROM user_invitations WHERE id = ?")
+            .bind(id.to_string())
+            .fetch_optional(&self.pool)
+            .await?
+            .as_ref()
+            .map(invitation_from_row)
+            .transpose()
+    }
+
+    async fn pending_invitations(&self, now: DateTime<Utc>) -> Result<Vec<Invitation>, AppError> {
+        sqlx::query(
+            "SELECT * FROM user_invitations WHERE accepted_at IS NULL AND expires_at > ?
+             ORDER BY created_at DESC, rowid DESC",
+        )
+        .bind(now.timestamp_millis())
+        .fetch_all(&self.pool)
+        .await?
+        .iter()
+        .map(invitation_from_row)
+        .collect()
+    }
+
+    async fn delete_invitation(&self, id: Uuid) -> Result<bool, AppError> {
+        let deleted = sqlx::query("DELETE FROM user_invitations WHERE id = ?")
+            .bind(id.to_string())
+            .execute(&self.pool)
+            .await?
+            .rows_affected();
+        Ok(deleted > 0)
+    }
+
+    async fn accept_invitation(
+        &self,
+        invitation_id: Uuid,
+        user: &UserAccount,
+        password_hash: &str,
+        membership: Option<&TeamMember>,
+    ) -> Result<bool, AppError> {
+        let mut tx = self.pool.begin().await?;
+        let accepted = sqlx::query(
+            "UPDATE user_invitations SET accepted_by = ?, accepted_at = ? WHERE id = ? AND accepted_at IS NULL",
+        )
+        .bind(user.id.to_string())
+        .bind(user.created_at.timestamp_millis())
+        .bind(invitation_id.to_string())
+        .execute(&mut *tx)
+        .await?
+        .rows_affected();
+        if accepted == 0 {
+            return Ok(false);
+        }
+        insert_user(&mut tx, user, password_hash).await?;
+        if let Some(member) = membership {
+            upsert_member(&mut tx, member).await?;
+        }
+        tx.commit().await?;
+        Ok(true)
+    }
+}
+
+/// Inserts an account, reporting a taken username or email as a conflict
+async fn insert_user(conn: &mut sqlx::SqliteConnection, user: &UserAccount, password_hash: &str) -> Result<(), AppError> {
+    sqlx::query(
+        "INSERT INTO users (id, username, email, password_hash, role, created_at, updated_at, deactivated_at)
+         VALUES (?, ?, ?, ?, ?, ?, ?, ?)",
+    )
+    .bind(user.id.to_string())
+    .bind(&user.username)
+    .bind(&user.email)
+    .bind(password_hash)
+    .bind(user.role.as_str())
+    .bind(user.created_at)
+    .bind(user.updated_at)
+    .bind(user.deactivated_at)
+    .execute(conn)
+    .await
+    .map_err(|e| conflict(e, || "Username or email already in use".to_string()))?;
+    Ok(())
+}
+
+/// Adds a member, or changes the role of one
+async fn upsert_member(conn: &mut sqlx::SqliteConnection, member: &TeamMember) -> Result<(), AppError> {
+    sqlx::query(
+        "INSERT INTO team_members (team_id, user_id, role, added_at) VALUES (?, ?, ?, ?)
+         ON CONFLICT(team_id, user_id) DO UPDATE SET role = excluded.role",
+    )
+    .bind(member.team_id.to_string())
+    .bind(member.user_id.to_string())
+    .bind(member.role.as_str())
+    .bind(member.added_at.timestamp_millis())
+    .execute(conn)
+    .await?;
+    Ok(())
+}
+
+/// In-process user store for the mock database mode and tests; nothing
+/// survives a restart
+#[derive(Default)]
+pub struct MemoryUserStore {
+    state: Mutex<MemoryState>,
+}
+
+#[derive(Default)]
+struct MemoryState {
+    users: BTreeMap<Uuid, (UserAccount, String)>,
+    teams: BTreeMap<Uuid, Team>,
+    members: Vec<TeamMember>,
+    invitations: Vec<Invitation>,
+}
+
+impl MemoryState {
+    fn insert_user(&mut self, user: &UserAccount, password_hash: &str) -> Result<(), AppError> {
+        let taken = self.users.values().any(|(existing, _)| {
+            existing.id == user.id || existing.username == user.username || existing.email == user.email
+        });
+        if taken {
+            return Err(AppError::Conflict("Username or email already in use".to_string()));
+        }
+        self.users.insert(user.id, (user.clone(), password_hash.to_string()));
+        Ok(())
+    }
+
+    fn upsert_member(&mut self, member: &TeamMember) {
+        match self
+            .members
+            .iter_mut()
+            .find(|existing| existing.team_id == member.team_id && existing.user_id == member.user_id)
+        {
+            Some(existing) => existing.role = member.role,
+            None => self.members.push(TeamMember { username: None, ..member.clone() }),
+        }
+    }
+}
+
+impl MemoryUserStore {
+    /// Create a new MemoryUserStore
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+#[async_trait]
+impl UserStore for MemoryUserStore {
+    async fn create_user(&self, user: &UserAccount, password_hash: &str) -> Result<(), AppError> {
+        self.state.lock().await.insert_user(user, password_hash)
+    }
+
+    async fn user(&self, id: Uuid) -> Result<Option<UserAccount>, AppError> {
+        Ok(self.state.lock().await.users.get(&id).map(|(user, _)| user.clone()))
+    }
+
+    async fn user_by_name(&self, name: &str) -> Result<Option<UserAccount>, AppError> {
+        Ok(self
+            .state
+            .lock()
+            .await
+            .users
+            .values()
+            .map(|(user, _)| user)
+            .find(|user| user.username == name || user.email == name)
+            .cloned())
+    }
+
+    async fn users(&self, include_deactivated: bool) -> Result<Vec<UserAccount>, AppError> {
+        let mut users: Vec<UserAccount> = self
+            .state
+            .lock()
+            .await
+            .users
+            .values()
+            .map(|(user, _)| user)
+            .filter(|user| include_deactivated || user.is_active())
+            .cloned()
+            .collect();
+        users.sort_by(|a, b| a.username.cmp(&b.username));
+        Ok(users)
+    }
+
+    async fn set_role(&self, id: Uuid, role: Role) -> Result<bool, AppError> {
+        Ok(match self.state.lock().await.users.get_mut(&id) {
+            Some((user, _)) => {
+                user.role = role;
+                user.updated_at = Utc::now();
+                true
+            }
+            None => false,
+        })
+    }
+
+    async fn set_deactivated(&self, id: Uuid, deactivated_at: Option<DateTime<Utc>>) -> Result<bool, AppError> {
+        Ok(match self.state.lock().await.users.get_mut(&id) {
+            Some((user, _)) => {
+                user.deactivated_at = deactivated_at;
+                user.updated_at = Utc::now();
+                true
+            }
+            None => false,
+        })
+    }
+
+    async fn password_hash(&self, id: Uuid) -> Result<Option<String>, AppError> {
+        Ok(self.state.lock().await.users.get(&id).map(|(_, hash)| hash.clone()))
+    }
+
+    async fn set_password_hash(&self, id: Uuid, password_hash: &str) -> Result<bool, AppError> {
+        Ok(match self.state.lock().await.users.get_mut(&id) {
+            Some((user, hash)) => {
+                *hash = password_hash.to_string();
+                user.updated_at = Utc::now();
+                true
+            }
+            None => false,
+        })
+    }
+
+    async fn create_team(&self, team: &Team) -> Result<(), AppError> {
+        let mut state = self.state.lock().await;
+        if state.teams.values().any(|existing| existing.name == team.name) {
+            return Err(AppError::Conflict(format!("Team {} already exists", team.name)));
+        }
+        state.teams.insert(team.id, team.clone());
+        Ok(())
+    }
+
+    async fn team(&self, id: Uuid) -> Result<Option<Team>, AppError> {
+        Ok(self.state.lock().await.teams.get(&id).cloned())
+    }
+
+    async fn teams(&self, member: Option<Uuid>) -> Result<Vec<Team>, AppError> {
+        let state = self.state.lock().await;
+        let mut teams: Vec<Team> = state
+            .teams
+            .values()
+            .filter(|team| {
+                member.is_none_or(|user_id| {
+                    state.members.iter().any(|m| m.team_id == team.id && m.user_id == user_id)
+                })
+            })
+            .cloned()
+            .collect();
+        teams.sort_by(|a, b| a.name.cmp(&b.name));
+        Ok(teams)
+    }
+
+    async fn update_team(&self, team: &Team) -> Result<bool, AppError> {
+        let mut state = self.state.lock().await;
+        if state.teams.values().any(|existing| existing.id != team.id && existing.name == team.name) {
+            return Err(AppError::Conflict(format!("Team {} already exists", team.name)));
+        }
+        Ok(match state.teams.get_mut(&team.id) {
+            Some(existing) => {
+                existing.name = team.name.clone();
+                existing.description = team.description.clone();
+                existing.updated_at = team.updated_at;
+                true
+            }
+            None => false,
+        })
+    }
+
+    async fn delete_team(&self, id: Uuid) -> Result<bool, AppError> {
+        let mut state = self.state.lock().await;
+        state.members.retain(|member| member.team_id != id);
+        for invitation in state.invitations.iter_mut().filter(|invitation| invitation.team_id == Some(id)) {
+            invitation.team_id = None;
+        }
+        Ok(state.teams.remove(&id).is_some())
+    }
+
+    async fn members(&self, team_id: Uuid) -> Result<Vec<TeamMember>, AppError> {
+        let state = self.state.lock().await;
+        Ok(state
+            .members
+            .iter()
+            .filter(|member| member.team_id == team_id)
+            .map(|member| TeamMember {
+                username: state.users.get(&member.user_id).map(|(user, _)| user.username.clone()),
+                ..member.clone()
+            })
+            .collect())
+    }
+
+    async fn set_member(&self, member: &TeamMember) -> Result<(), AppError> {
+        self.state.lock().await.upsert_member(member);
+        Ok(())
+    }
+
+    async fn remove_member(&self, team_id: Uuid, user_id: Uuid) -> Result<bool, AppError> {
+        let mut state = self.state.lock().await;
+        let before = state.members.len();
+        state.members.retain(|member| member.team_id != team_id || member.user_id != user_id);
+        Ok(state.members.len() < before)
+    }
+
+    async fn create_invitation(&self, invitation: &Invitation) -> Result<(), AppError> {
+        self.state.lock().await.invitations.push(invitation.clone());
+        Ok(())
+    }
+
+    async fn invitation(&self, id: Uuid) -> Result<Option<Invitation>, AppError> {
+        Ok(self.state.lock().await.invitations.iter().find(|invitation| invitation.id == id).cloned())
+    }
+
+    async fn pending_invitations(&self, now: DateTime<Utc>) -> Result<Vec<Invitation>, AppError> {
+        Ok(self
+            .state
+            .lock()
+            .await
+            .invitations
+            .iter()
+            .rev()
+            .filter(|invitation| invitation.accepted_at.is_none() && invitation.expires_at > now)
+            .cloned()
+            .collect())
+    }
+
+    async fn delete_invitation(&self, id: Uuid) -> Result<bool, AppError> {
+        let mut state = self.state.lock().await;
+        let before = state.invitations.len();
+        state.invitations.retain(|invitation| invitation.id != id);
+        Ok(state.invitations.len() < before)
+    }
+
+    async fn accept_invitation(
+        &self,
+        invitation_id: Uuid,
+        user: &UserAccount,
+        password_hash: &str,
+        membership: Option<&TeamMember>,
+    ) -> Result<bool, AppError> {
+        let mut state = self.state.lock().await;
+        let pending = state
+            .invitations
+            .iter()
+            .any(|invitation| invitation.id == invitation_id && invitation.accepted_at.is_none());
+        if !pending {
+            return Ok(false);
+        }
+        state.insert_user(user, password_hash)?;
+        if let Some(member) = membership {
+            state.upsert_member(member);
+        }
+        if let Some(invitation) = state.invitations.iter_mut().find(|invitation| invitation.id == invitation_id) {
+            invitation.accepted_by = Some(user.id);
+            invitation.accepted_at = Some(user.created_at);
+        }
+        Ok(true)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_helpers::migrated_pool;
+
+    fn account(username: &str) -> UserAccount {
+        let now = timestamp(Utc::now().timestamp_millis()).unwrap();
+        UserAccount {
+            id: Uuid::new_v4(),
+            username: username.to_string(),
+            email: format!("{}@example.org", username),
+            role: Role::User,
+            created_at: now,
+            updated_at: now,
+            deactivated_at: None,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_sql_store_keeps_users_teams_and_invitations() {
+        let pool = migrated_pool().await;
+        let store = SqlUserStore::new(pool);
+
+        let ada = account("ada");
+        store.create_user(&ada, "hash").await.unwrap();
+        assert!(matches!(store.create_user(&account("ada"), "hash").await, Err(AppError::Conflict(_))));
+        assert_eq!(store.user_by_name("ada@example.org").await.unwrap().unwrap().id, ada.id);
+        assert!(store.set_role(ada.id, Role::Admin).await.unwrap());
+        assert!(store.set_deactivated(ada.id, Some(Utc::now())).await.unwrap());
+        assert!(store.users(false).await.unwrap().is_empty());
+        let stored = store.user(ada.id).await.unwrap().unwrap();
+        assert_eq!((stored.role, stored.is_active()), (Role::Admin, false));
+
+        let team = Team {
+            id: Uuid::new_v4(),
+            name: "genomics".to_string(),
+            description: None,
+            created_by: ada.id.to_string(),
+            created_at: ada.created_at,
+            updated_at: ada.created_at,
+        };
+        store.create_team(&team).await.unwrap();
+        assert!(matches!(store.create_team(&Team { id: Uuid::new_v4(), ..team.clone() }).await, Err(AppError::Conflict(_))));
+
+        let invitation = Invitation {
+            id: Uuid::new_v4(),
+            email: "grace@example.org".to_string(),
+            role: Role::User,
+            team_id: Some(team.id),
+            invited_by: ada.id.to_string(),
+            created_at: ada.created_at,
+            expires_at: ada.created_at + chrono::Duration::hours(1),
+            accepted_by: None,
+            accepted_at: None,
+        };
+        store.create_invitation(&invitation).await.unwrap();
+        assert_eq!(store.pending_invitations(Utc::now()).await.unwrap(), vec![invitation.clone()]);
+
+        let grace = UserAccount { email: invitation.email.clone(), ..account("grace") };
+        let membership = TeamMember {
+            team_id: team.id,
+            user_id: grace.id,
+            username: None,
+            role: TeamRole::Member,
+            added_at: grace.created_at,
+        };
+        assert!(store.accept_invitation(invitation.id, &grace, "hash", Some(&membership)).await.unwrap());
+        assert!(!store.accept_invitation(invitation.id, &account("eve"), "hash", None).await.unwrap());
+        assert!(store.user_by_name("eve").await.unwrap().is_none());
+        assert!(store.pending_invitations(Utc::now()).await.unwrap().is_empty());
+
+        let members = store.members(team.id).await.unwrap();
+        assert_eq!(members[0].username.as_deref(), Some("grace"));
+        assert_eq!(store.teams(Some(grace.id)).await.unwrap(), vec![team.clone()]);
+        assert!(store.teams(Some(ada.id)).await.unwrap().is_empty());
+
+        assert!(store.delete_team(team.id).await.unwrap());
+        assert!(store.members(team.id).await.unwrap().is_empty());
+        assert_eq!(store.invitation(invitation.id).await.unwrap().unwrap().team_id, None);
+    }
+}
@@ -0,0 +1,48 @@
+//! In-process backplane, connecting managers within one process.
+
+use async_trait::async_trait;
+use futures::stream::{BoxStream, StreamExt};
+use tokio::sync::broadcast;
+use tokio_stream::wrappers::BroadcastStream;
+
+use super::{Backplane, BackplaneMessage};
+use crate::websocket::error::WebSocketError;
+
+/// Capacity of the in-process message buffer
+const CHANNEL_SIZE: usize = 1000;
+
+/// Backplane that fans out over a broadcast channel
+///
+/// Useful for tests and for running several managers in one process.
+#[derive(Debug, Clone)]
+pub struct MemoryBackplane {
+    tx: broadcast::Sender<BackplaneMessage>,
+}
+
+impl Default for MemoryBackplane {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl MemoryBackplane {
+    /// Create a new MemoryBackplane
+    pub fn new() -> Self {
+        let (tx, _) = broadcast::channel(CHANNEL_SIZE);
+        Self { tx }
+    }
+}
+
+#[async_trait]
+impl Backplane for MemoryBackplane {
+    async fn publish(&self, message: &BackplaneMessage) -> Result<(), WebSocketError> {
+        // Having no subscribers is not an error
+        let _ = self.tx.send(message.clone());
+        Ok(())
+    }
+
+    async fn subscribe(&self) -> Result<BoxStream<'static, BackplaneMessage>, WebSocketError> {
+        let stream = BroadcastStream::new(self.tx.subscribe()).filter_map(|message| async move { message.ok() });
+        Ok(stream.boxed())
+    }
+}
@@ -0,0 +1,150 @@
+//! Cross-instance fan-out for WebSocket events.
+//!
+//! A [`ConnectionManager`](super::ConnectionManager) only knows the clients
+//! connected to its own process. When a backplane is attached, every channel
+//! broadcast and user message is also published to a shared pub/sub topic,
+//! and messages published by other instances are delivered to the local
+//! clients. Redis and NATS implementations are available behind the
+//! `redis-backplane` and `nats-backplane` features.
+
+use std::fmt::Debug;
+use std::sync::Arc;
+
+use async_trait::async_trait;
+use futures::stream::BoxStream;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use super::error::WebSocketError;
+use super::models::{ChannelCategory, WebSocketResponse};
+
+mod memory;
+#[cfg(feature = "nats-backplane")]
+mod nats;
+#[cfg(feature = "redis-backplane")]
+mod redis;
+
+pub use memory::MemoryBackplane;
+#[cfg(feature = "nats-backplane")]
+pub use nats::NatsBackplane;
+#[cfg(feature = "redis-backplane")]
+pub use redis::RedisBackplane;
+
+/// A WebSocket delivery to replay on other instances
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum BackplaneDelivery {
+    /// An event for every subscriber of a channel
+    Channel {
+        /// Channel category
+        category: ChannelCategory,
+        /// Channel name
+        channel: String,
+        /// Event name
+        event: String,
+        /// Event payload
+        data: Value,
+    },
+    /// A message for every connection of a user
+    User {
+        /// Target user ID
+        user_id: String,
+        /// Message to send
+        message: WebSocketResponse,
+    },
+}
+
+/// Envelope carried over the backplane
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BackplaneMessage {
+    /// ID of the instance that published the message
+    pub origin: String,
+    /// What to deliver
+    pub delivery: BackplaneDelivery,
+}
+
+/// Pub/sub transport shared by all server instances
+#[async_trait]
+pub trait Backplane: Debug + Send + Sync {
+    /// Publish a message to every instance, including this one
+    async fn publish(&self, message: &BackplaneMessage) -> Result<(), WebSocketError>;
+
+    /// Stream of messages published by any instance
+    async fn subscribe(&self) -> Result<BoxStream<'static, BackplaneMessage>, WebSocketError>;
+}
+
+/// Whether an instance currently receives messages from its backplane
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum BackplaneStatus {
+    /// The subscription is live
+    Connected,
+    /// The subscription ended and is being re-established
+    Disconnected,
+}
+
+/// Which backplane to use
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum BackplaneKind {
+    /// Events stay within the process
+    #[default]
+    None,
+    /// Redis pub/sub
+    Redis,
+    /// NATS core pub/sub
+    Nats,
+}
+
+/// Configuration for the WebSocket backplane
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct BackplaneConfig {
+    /// Backplane implementation
+    pub kind: BackplaneKind,
+    /// Server URL, e.g. `redis://127.0.0.1/` or `nats://127.0.0.1:4222`
+    pub url: String,
+    /// Redis channel or NATS subject the instances share
+    pub topic: String,
+}
+
+impl Default for BackplaneConfig {
+    fn default() -> Self {
+        Self {
+            kind: BackplaneKind::None,
+            url: String::new(),
+            topic: "squirrel.websocket".to_string(),
+        }
+    }
+}
+
+/// Connect the configured backplane, if any
+///
+/// Returns an error when the backplane is configured but unreachable or was
+/// not compiled in.
+pub async fn connect(config: &BackplaneConfig) -> Result<Option<Arc<dyn Backplane>>, WebSocketError> {
+    match config.kind {
+        BackplaneKind::None => Ok(None),
+        #[cfg(feature = "redis-backplane")]
+        BackplaneKind::Redis => Ok(Some(Arc::new(RedisBackplane::connect(&config.url, &config.topic).await?))),
+        #[cfg(feature = "nats-backplane")]
+        BackplaneKind::Nats => Ok(Some(Arc::new(NatsBackplane::connect(&config.url, &config.topic).await?))),
+        #[allow(unreachable_patterns)]
+        kind => Err(WebSocketError::Backplane(format!(
+            "{:?} backplane support is not enabled in this build",
+            kind
+        ))),
+    }
+}
+
+/// Decodes a payload received from a backplane, dropping malformed messages
+#[cfg(any(feature = "redis-backplane", feature = "nats-backplane"))]
+fn decode(payload: &[u8]) -> Option<BackplaneMessage> {
+    match serde_json::from_slice(payload) {
+        Ok(message) => Some(message),
+        Err(e) => {
+            tracing::warn!("Ignoring malformed backplane message: {}", e);
+            None
+        }
+    }
+}
@@ -0,0 +1,50 @@
+//! NATS pub/sub backplane.
+
+use async_trait::async_trait;
+use futures::stream::{BoxStream, StreamExt};
+
+use super::{decode, Backplane, BackplaneMessage};
+use crate::websocket::error::WebSocketError;
+
+/// Backplane publishing to a NATS subject
+#[derive(Debug)]
+pub struct NatsBackplane {
+    client: async_nats::Client,
+    subject: String,
+}
+
+impl NatsBackplane {
+    /// Connect to NATS at `url`, using `subject` for all messages
+    pub async fn connect(url: &str, subject: &str) -> Result<Self, WebSocketError> {
+        let client = async_nats::connect(url)
+            .await
+            .map_err(|e| WebSocketError::Backplane(e.to_string()))?;
+
+        Ok(Self {
+            client,
+            subject: subject.to_string(),
+        })
+    }
+}
+
+#[async_trait]
+impl Backplane for NatsBackplane {
+    async fn publish(&self, message: &BackplaneMessage) -> Result<(), WebSocketError> {
+        let payload = serde_json::to_vec(message)?;
+        self.client
+            .publish(self.subject.clone(), payload.into())
+            .await
+            .map_err(|e| WebSocketError::Backplane(e.to_string()))
+    }
+
+    async fn subscribe(&self) -> Result<BoxStream<'static, BackplaneMessage>, WebSocketError> {
+        let subscriber = self
+            .client
+            .subscribe(self.subject.clone())
+            .await
+            .map_err(|e| WebSocketError::Backplane(e.to_string()))?;
+
+        let stream = subscriber.filter_map(|message| async move { decode(&message.payload) });
+        Ok(stream.boxed())
+    }
+}
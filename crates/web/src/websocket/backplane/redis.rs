@@ -0,0 +1,60 @@
+//! Redis pub/sub backplane.
+
+use async_trait::async_trait;
+use futures::stream::{BoxStream, StreamExt};
+use redis::AsyncCommands;
+
+use super::{decode, Backplane, BackplaneMessage};
+use crate::websocket::error::WebSocketError;
+
+fn backplane_error(error: redis::RedisError) -> WebSocketError {
+    WebSocketError::Backplane(error.to_string())
+}
+
+/// Backplane publishing to a Redis channel
+#[derive(Debug)]
+pub struct RedisBackplane {
+    client: redis::Client,
+    publisher: redis::aio::MultiplexedConnection,
+    channel: String,
+}
+
+impl RedisBackplane {
+    /// Connect to Redis at `url`, using `channel` for all messages
+    pub async fn connect(url: &str, channel: &str) -> Result<Self, WebSocketError> {
+        let client = redis::Client::open(url).map_err(backplane_error)?;
+        let publisher = client
+            .get_multiplexed_tokio_connection()
+            .await
+            .map_err(backplane_error)?;
+
+        Ok(Self {
+            client,
+            publisher,
+            channel: channel.to_string(),
+        })
+    }
+}
+
+#[async_trait]
+impl Backplane for RedisBackplane {
+    async fn publish(&self, message: &BackplaneMessage) -> Result<(), WebSocketError> {
+        let payload = serde_json::to_vec(message)?;
+        let mut publisher = self.publisher.clone();
+        publisher
+            .publish::<_, _, ()>(&self.channel, payload)
+            .await
+            .map_err(backplane_error)
+    }
+
+    async fn subscribe(&self) -> Result<BoxStream<'static, BackplaneMessage>, WebSocketError> {
+        let connection = self.client.get_async_connection().await.map_err(backplane_error)?;
+        let mut pubsub = connection.into_pubsub();
+        pubsub.subscribe(&self.channel).await.map_err(backplane_error)?;
+
+        let stream = pubsub
+            .into_on_message()
+            .filter_map(|message| async move { decode(message.get_payload_bytes()) });
+        Ok(stream.boxed())
+    }
+}
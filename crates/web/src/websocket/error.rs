@@ -41,6 +41,10 @@ pub enum WebSocketError {
     #[error("Internal error: {0}")]
     Internal(String),
     
+    /// Error publishing to or receiving from the cross-instance backplane
+    #[error("Backplane error: {0}")]
+    Backplane(String),
+    
     /// JSON serialization/deserialization error
     #[error("JSON error: {0}")]
     JsonError(#[from] serde_json::Error),
@@ -59,6 +63,7 @@ impl Clone for WebSocketError {
             Self::SendError(s) => Self::SendError(s.clone()),
             Self::UnsupportedMessageType(s) => Self::UnsupportedMessageType(s.clone()),
             Self::Internal(s) => Self::Internal(s.clone()),
+            Self::Backplane(s) => Self::Backplane(s.clone()),
             Self::JsonError(e) => Self::JsonError(serde_json::Error::io(
                 std::io::Error::new(std::io::ErrorKind::Other, e.to_string())
             )),
@@ -79,6 +84,7 @@ impl WebSocketError {
             Self::SendError(_) => "SEND_ERROR",
             Self::UnsupportedMessageType(_) => "UNSUPPORTED_MESSAGE_TYPE",
             Self::Internal(_) => "INTERNAL_ERROR",
+            Self::Backplane(_) => "BACKPLANE_ERROR",
             Self::JsonError(_) => "JSON_ERROR",
         }
     }
@@ -8,7 +8,8 @@ use async_trait::async_trait;
 use serde_json::json;
 use tracing::error;
 
-use crate::api::commands::{CommandStatusEvent, CommandListUpdateEvent};
+use crate::api::commands::{CommandStatus, CommandStatusEvent, CommandListUpdateEvent};
+use crate::handlers::webhooks::WebhookEvent;
 use crate::state::AppState;
 use crate::websocket::{
     WebSocketHandler,
@@ -176,6 +177,18 @@ pub async fn broadcast_command_status_update(
         WebSocketError::SendError(format!("Failed to broadcast: {}", e))
     })?;
 
+    // Notify webhook subscribers of failures
+    if event.status == CommandStatus::Failed {
+        if let Some(webhooks) = &app_state.webhook_service {
+            webhooks.emit(WebhookEvent::CommandFailed, json!({
+                "command_id": event.id,
+                "command": event.command,
+                "error": event.error,
+                "timestamp": event.timestamp,
+            }));
+        }
+    }
+
     Ok(())
 }
 
@@ -1,14 +1,18 @@
 //! WebSocket connection manager for handling client connections and broadcasting messages.
 
 use std::collections::{HashMap, HashSet};
+use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::Arc;
+use std::time::Duration;
 use tokio::sync::{broadcast, mpsc, RwLock};
 use tokio_stream::Stream;
+use futures::StreamExt;
 use uuid::Uuid;
 use serde_json::Value;
 use tracing::{debug, error, info, warn};
 use std::str::FromStr;
 
+use super::backplane::{Backplane, BackplaneDelivery, BackplaneMessage, BackplaneStatus};
 use super::error::WebSocketError;
 use super::models::{ChannelCategory, Subscription, WebSocketEvent, WebSocketResponse};
 
@@ -18,6 +22,12 @@ const BROADCAST_CHANNEL_SIZE: usize = 1000;
 /// Maximum number of subscribers allowed per channel
 const MAX_SUBSCRIBERS_PER_CHANNEL: usize = 10000;
 
+/// Delay before the first attempt to resubscribe to a lost backplane
+const BACKPLANE_RETRY_INITIAL: Duration = Duration::from_millis(500);
+
+/// Longest delay between attempts to resubscribe to a lost backplane
+const BACKPLANE_RETRY_MAX: Duration = Duration::from_secs(30);
+
 /// Connection ID
 pub type ConnectionId = String;
 
@@ -57,6 +67,15 @@ pub struct ConnectionManager {
     
     /// Broadcast channel for all events
     event_tx: broadcast::Sender<WebSocketEvent>,
+    
+    /// ID of this instance, used to skip its own backplane messages
+    instance_id: String,
+    
+    /// Shared pub/sub transport reaching other instances
+    backplane: Option<Arc<dyn Backplane>>,
+    
+    /// Whether the backplane subscription is live
+    backplane_connected: Arc<AtomicBool>,
 }
 
 impl Default for ConnectionManager {
@@ -75,6 +94,92 @@ impl ConnectionManager {
             channel_subscribers: Arc::new(RwLock::new(HashMap::new())),
             user_connections: Arc::new(RwLock::new(HashMap::new())),
             event_tx,
+            instance_id: Uuid::new_v4().to_string(),
+            backplane: None,
+            backplane_connected: Arc::new(AtomicBool::new(false)),
+        }
+    }
+    
+    /// Attach a backplane so events reach clients connected to other instances
+    ///
+    /// Channel broadcasts and user messages are published to the backplane,
+    /// and messages published by other instances are delivered to this
+    /// instance's connections. When the subscription ends, for example
+    /// because the connection to Redis or NATS dropped, it is re-established
+    /// with exponential backoff; [`backplane_status`](Self::backplane_status)
+    /// reports it as disconnected meanwhile.
+    pub async fn with_backplane(mut self, backplane: Arc<dyn Backplane>) -> Result<Self, WebSocketError> {
+        let mut messages = backplane.subscribe().await?;
+        self.backplane = Some(backplane.clone());
+        self.backplane_connected.store(true, Ordering::SeqCst);
+        
+        let manager = self.clone();
+        tokio::spawn(async move {
+            let mut delay = BACKPLANE_RETRY_INITIAL;
+            loop {
+                while let Some(message) = messages.next().await {
+                    delay = BACKPLANE_RETRY_INITIAL;
+                    manager.deliver_remote(message).await;
+                }
+                manager.backplane_connected.store(false, Ordering::SeqCst);
+                warn!("WebSocket backplane subscription ended, resubscribing");
+                messages = loop {
+                    tokio::time::sleep(delay).await;
+                    delay = (delay * 2).min(BACKPLANE_RETRY_MAX);
+                    match backplane.subscribe().await {
+                        Ok(messages) => break messages,
+                        Err(e) => warn!("Failed to resubscribe to WebSocket backplane: {}", e),
+                    }
+                };
+                manager.backplane_connected.store(true, Ordering::SeqCst);
+                info!("Resubscribed to WebSocket backplane");
+            }
+        });
+        
+        Ok(self)
+    }
+    
+    /// State of the backplane subscription, if a backplane is attached
+    pub fn backplane_status(&self) -> Option<BackplaneStatus> {
+        self.backplane.as_ref()?;
+        Some(if self.backplane_connected.load(Ordering::SeqCst) {
+            BackplaneStatus::Connected
+        } else {
+            BackplaneStatus::Disconnected
+        })
+    }
+    
+    /// Deliver a message published by another instance to local connections
+    async fn deliver_remote(&self, message: BackplaneMessage) {
+        if message.origin == self.instance_id {
+            return;
+        }
+        let result = match message.delivery {
+            BackplaneDelivery::Channel { category, channel, event, data } => self
+                .deliver_to_channel(category, &channel, &event, data)
+                .await
+                .map(|_| ()),
+            // Users without connections on this instance are expected
+            BackplaneDelivery::User { user_id, message } => {
+                let _ = self.deliver_to_user(&user_id, message).await;
+                Ok(())
+            }
+        };
+        if let Err(e) = result {
+            warn!("Failed to deliver backplane message: {}", e);
+        }
+    }
+    
+    /// Publish a delivery to other instances, if a backplane is attached
+    async fn publish(&self, delivery: BackplaneDelivery) {
+        if let Some(backplane) = &self.backplane {
+            let message = BackplaneMessage {
+                origin: self.instance_id.clone(),
+                delivery,
+            };
+            if let Err(e) = backplane.publish(&message).await {
+                error!("Failed to publish to backplane: {}", e);
+            }
         }
     }
     
@@ -257,10 +362,34 @@ impl ConnectionManager {
     }
     
     /// Send a message to a specific user (all connections)
+    ///
+    /// With a backplane attached, the message also reaches the user's
+    /// connections on other instances, so having none locally is not an error.
     pub async fn send_to_user(
         &self,
         user_id: &str,
         message: WebSocketResponse,
+    ) -> Result<(), WebSocketError> {
+        if self.backplane.is_none() {
+            return self.deliver_to_user(user_id, message).await;
+        }
+        
+        self.publish(BackplaneDelivery::User {
+            user_id: user_id.to_string(),
+            message: message.clone(),
+        })
+        .await;
+        match self.deliver_to_user(user_id, message).await {
+            Err(WebSocketError::JsonError(e)) => Err(WebSocketError::JsonError(e)),
+            _ => Ok(()),
+        }
+    }
+    
+    /// Send a message to the user's connections on this instance
+    async fn deliver_to_user(
+        &self,
+        user_id: &str,
+        message: WebSocketResponse,
     ) -> Result<(), WebSocketError> {
         let user_conns = self.user_connections.read().await;
         
@@ -292,12 +421,33 @@ impl ConnectionManager {
     }
     
     /// Broadcast an event to a specific channel
+    ///
+    /// Returns the number of local subscribers the event was sent to; with a
+    /// backplane attached, subscribers on other instances receive it as well.
     pub async fn broadcast_to_channel(
         &self,
         category: ChannelCategory,
         channel: &str,
         event: &str,
         data: Value,
+    ) -> Result<usize, WebSocketError> {
+        self.publish(BackplaneDelivery::Channel {
+            category: category.clone(),
+            channel: channel.to_string(),
+            event: event.to_string(),
+            data: data.clone(),
+        })
+        .await;
+        self.deliver_to_channel(category, channel, event, data).await
+    }
+    
+    /// Send an event to the channel's subscribers on this instance
+    async fn deliver_to_channel(
+        &self,
+        category: ChannelCategory,
+        channel: &str,
+        event: &str,
+        data: Value,
     ) -> Result<usize, WebSocketError> {
         let channel_id = format!("{}:{}", category.as_str(), channel);
         
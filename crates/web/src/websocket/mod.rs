@@ -3,6 +3,7 @@
 //! This module handles WebSocket connections, message processing, and broadcasting
 //! to support real-time features in the Squirrel platform.
 
+pub mod backplane;
 pub mod error;
 mod handler;
 mod manager;
@@ -13,6 +14,7 @@ pub mod commands;
 #[cfg(test)]
 mod tests;
 
+pub use backplane::{Backplane, BackplaneConfig, BackplaneKind, BackplaneStatus, MemoryBackplane};
 pub use error::WebSocketError;
 pub use handler::ws_handler;
 pub use manager::ConnectionManager;
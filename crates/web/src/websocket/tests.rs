@@ -14,7 +14,10 @@ mod tests {
     use futures::StreamExt;
     
     use crate::websocket::{
+        Backplane,
+        BackplaneStatus,
         ConnectionManager,
+        backplane::BackplaneMessage,
         models::{ChannelCategory, WebSocketResponse, WebSocketEvent, WebSocketCommand},
         error::WebSocketError,
     };
@@ -365,4 +368,132 @@ mod tests {
             panic!("Expected data to be an object");
         }
     }
-} 
\ No newline at end of file
+    
+    /// Test that a shared backplane fans events out across managers
+    #[tokio::test]
+    async fn test_backplane_reaches_other_instances() {
+        let backplane = std::sync::Arc::new(crate::websocket::MemoryBackplane::new());
+        let first = ConnectionManager::new()
+            .with_backplane(backplane.clone())
+            .await
+            .expect("Failed to attach backplane");
+        let second = ConnectionManager::new()
+            .with_backplane(backplane)
+            .await
+            .expect("Failed to attach backplane");
+        
+        // A client connected to the second instance
+        let (tx, mut rx) = mpsc::channel::<Result<String, WebSocketError>>(10);
+        let connection_id = second
+            .register_connection(Some("remote_user".to_string()), vec![], tx)
+            .await;
+        second
+            .subscribe(&connection_id, ChannelCategory::Job, "shared-job")
+            .await
+            .expect("Failed to subscribe");
+        
+        // Broadcasting on the first instance reaches no local subscribers
+        let sent = first
+            .broadcast_to_channel(ChannelCategory::Job, "shared-job", "job-progress", json!({"progress": 50}))
+            .await
+            .expect("Failed to broadcast");
+        assert_eq!(sent, 0);
+        
+        let message = tokio::time::timeout(Duration::from_secs(1), rx.recv())
+            .await
+            .expect("Timed out waiting for backplane message")
+            .expect("Channel closed")
+            .expect("Error message received");
+        let response: WebSocketResponse = serde_json::from_str(&message).unwrap();
+        assert_eq!(response.event, "job-progress");
+        
+        // User messages without local connections succeed and are relayed
+        let notice = WebSocketResponse {
+            success: true,
+            event: "notice".to_string(),
+            data: json!({}),
+            error: None,
+            id: None,
+        };
+        first.send_to_user("remote_user", notice).await.expect("Failed to send to user");
+        let message = tokio::time::timeout(Duration::from_secs(1), rx.recv())
+            .await
+            .expect("Timed out waiting for user message")
+            .expect("Channel closed")
+            .expect("Error message received");
+        assert!(message.contains("notice"));
+    }
+    
+    /// Backplane whose subscriptions can be cut, like a dropped connection
+    #[derive(Debug, Default)]
+    struct FlakyBackplane {
+        subscribers: std::sync::Mutex<Vec<mpsc::UnboundedSender<BackplaneMessage>>>,
+    }
+    
+    impl FlakyBackplane {
+        fn disconnect(&self) {
+            self.subscribers.lock().unwrap().clear();
+        }
+    }
+    
+    #[async_trait::async_trait]
+    impl Backplane for FlakyBackplane {
+        async fn publish(&self, message: &BackplaneMessage) -> Result<(), WebSocketError> {
+            self.subscribers.lock().unwrap().retain(|tx| tx.send(message.clone()).is_ok());
+            Ok(())
+        }
+        
+        async fn subscribe(
+            &self,
+        ) -> Result<futures::stream::BoxStream<'static, BackplaneMessage>, WebSocketError> {
+            let (tx, rx) = mpsc::unbounded_channel();
+            self.subscribers.lock().unwrap().push(tx);
+            Ok(tokio_stream::wrappers::UnboundedReceiverStream::new(rx).boxed())
+        }
+    }
+    
+    /// Test that a manager resubscribes after its backplane subscription ends
+    #[tokio::test]
+    async fn test_backplane_resubscribes_after_disconnect() {
+        let backplane = std::sync::Arc::new(FlakyBackplane::default());
+        let first = ConnectionManager::new()
+            .with_backplane(backplane.clone())
+            .await
+            .expect("Failed to attach backplane");
+        let second = ConnectionManager::new()
+            .with_backplane(backplane.clone())
+            .await
+            .expect("Failed to attach backplane");
+        assert_eq!(second.backplane_status(), Some(BackplaneStatus::Connected));
+        assert_eq!(ConnectionManager::new().backplane_status(), None);
+        
+        let (tx, mut rx) = mpsc::channel::<Result<String, WebSocketError>>(10);
+        let connection_id = second
+            .register_connection(Some("remote_user".to_string()), vec![], tx)
+            .await;
+        second
+            .subscribe(&connection_id, ChannelCategory::Job, "shared-job")
+            .await
+            .expect("Failed to subscribe");
+        
+        backplane.disconnect();
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        assert_eq!(second.backplane_status(), Some(BackplaneStatus::Disconnected));
+        
+        // Both instances resubscribe after the first backoff
+        tokio::time::sleep(Duration::from_secs(1)).await;
+        assert_eq!(first.backplane_status(), Some(BackplaneStatus::Connected));
+        assert_eq!(second.backplane_status(), Some(BackplaneStatus::Connected));
+        first
+            .broadcast_to_channel(ChannelCategory::Job, "shared-job", "job-progress", json!({"progress": 75}))
+            .await
+            .expect("Failed to broadcast");
+        let message = tokio::time::timeout(Duration::from_secs(1), rx.recv())
+            .await
+            .expect("Timed out waiting for backplane message")
+            .expect("Channel closed")
+            .expect("Error message received");
+        let response: WebSocketResponse = serde_json::from_str(&message).unwrap();
+        assert_eq!(response.event, "job-progress");
+    }
+}
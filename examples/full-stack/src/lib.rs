@@ -0,0 +1,17 @@
+//! End-to-end example wiring every Squirrel subsystem together
+//!
+//! Runs the web server, the MCP server, monitoring and the plugin system in
+//! one process, with a sample plugin and sample tools. See
+//! [`stack::FullStack`] for how the pieces connect.
+
+/// Sample tool plugin and its bridge into the MCP tool manager
+pub mod plugin;
+pub use plugin::{PluginToolExecutor, TextToolsPlugin};
+
+/// Built-in sample tools
+pub mod tools;
+pub use tools::SystemToolExecutor;
+
+/// Boots and checks the full stack
+pub mod stack;
+pub use stack::{FullStack, SmokeReport, StackConfig};
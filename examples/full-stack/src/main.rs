@@ -0,0 +1,64 @@
+//! Runs every Squirrel subsystem in one process
+//!
+//! ```text
+//! cargo run -p squirrel-full-stack -- [--web-port 3000] [--mcp-port 8778] [--dashboard-port 8765] [--smoke]
+//! ```
+//!
+//! With `--smoke` the stack checks itself and exits; otherwise it runs until
+//! interrupted.
+
+use clap::Parser;
+use squirrel_full_stack::{FullStack, StackConfig};
+
+/// Command-line options
+#[derive(Debug, Parser)]
+#[command(
+    name = "full-stack",
+    about = "Run the web server, MCP server, monitoring and plugins together"
+)]
+struct Args {
+    /// Interface the servers bind to
+    #[arg(long, default_value = "127.0.0.1")]
+    host: String,
+    /// Web server port; 0 picks a free port
+    #[arg(long, default_value_t = 3000)]
+    web_port: u16,
+    /// MCP server port; 0 picks a free port
+    #[arg(long, default_value_t = 8778)]
+    mcp_port: u16,
+    /// Monitoring dashboard port; 0 picks a free port
+    #[arg(long, default_value_t = 8765)]
+    dashboard_port: u16,
+    /// Check every subsystem once and exit
+    #[arg(long)]
+    smoke: bool,
+}
+
+#[tokio::main]
+async fn main() -> anyhow::Result<()> {
+    tracing_subscriber::fmt::init();
+    let args = Args::parse();
+
+    let stack = FullStack::start(StackConfig {
+        host: args.host,
+        web_port: args.web_port,
+        mcp_port: args.mcp_port,
+        dashboard_port: args.dashboard_port,
+        ..StackConfig::default()
+    })
+    .await?;
+    println!("Web server:           http://{}", stack.web_addr());
+    println!("MCP server:           tcp://{}", stack.mcp_addr());
+    println!("Monitoring dashboard: http://{}", stack.dashboard_addr());
+
+    if args.smoke {
+        let report = stack.smoke_check().await;
+        stack.shutdown().await?;
+        println!("{}", report?);
+        return Ok(());
+    }
+
+    println!("Press Ctrl-C to stop");
+    tokio::signal::ctrl_c().await?;
+    stack.shutdown().await
+}
@@ -0,0 +1,228 @@
+//! Sample tool plugin and its bridge into the MCP tool manager
+//!
+//! [`TextToolsPlugin`] is an in-process plugin managed by the application
+//! plugin system. [`PluginToolExecutor`] exposes its tools through the MCP
+//! [`ToolManager`](squirrel_mcp::tool::ToolManager), refusing calls while the
+//! plugin is not active.
+
+use std::any::Any;
+use std::sync::Arc;
+
+use async_trait::async_trait;
+use futures::future::BoxFuture;
+use serde_json::{json, Value};
+use squirrel_app::error::{CoreError, Result};
+use squirrel_app::plugin::{
+    Plugin, PluginManager, PluginMetadata, PluginState, PluginStatus, ToolPlugin,
+};
+use squirrel_mcp::tool::{
+    Capability, ExecutionStatus, Parameter, ParameterType, Tool, ToolContext, ToolError,
+    ToolExecutionResult, ToolExecutor,
+};
+use tokio::sync::RwLock;
+use uuid::Uuid;
+
+/// Tool ID the plugin's tools are registered under
+pub const TEXT_TOOL_ID: &str = "text";
+
+/// Plugin providing text processing tools
+#[derive(Debug, Clone)]
+pub struct TextToolsPlugin {
+    /// Plugin metadata
+    metadata: PluginMetadata,
+    /// Persisted plugin state, shared between clones
+    state: Arc<RwLock<Option<PluginState>>>,
+}
+
+impl TextToolsPlugin {
+    /// Tools provided by the plugin
+    const TOOLS: [&'static str; 2] = ["word_count", "reverse"];
+
+    /// Creates the plugin
+    #[must_use]
+    pub fn new() -> Self {
+        Self {
+            metadata: PluginMetadata {
+                id: Uuid::new_v4(),
+                name: "text-tools".to_string(),
+                version: env!("CARGO_PKG_VERSION").to_string(),
+                description: "Sample plugin with text processing tools".to_string(),
+                author: "DataScienceBioLab".to_string(),
+                dependencies: Vec::new(),
+                capabilities: vec!["tool".to_string()],
+            },
+            state: Arc::new(RwLock::new(None)),
+        }
+    }
+
+    /// MCP tool definition of the plugin's tools
+    #[must_use]
+    pub fn tool_definition(&self) -> Tool {
+        let text_capability = |name: &str, description: &str| Capability {
+            name: name.to_string(),
+            description: description.to_string(),
+            parameters: vec![Parameter {
+                name: "text".to_string(),
+                description: "Input text".to_string(),
+                parameter_type: ParameterType::String,
+                required: true,
+            }],
+            return_type: None,
+            version: 1,
+            deprecation: None,
+        };
+        Tool::builder()
+            .id(TEXT_TOOL_ID)
+            .name("Text tools")
+            .version(self.metadata.version.clone())
+            .description(self.metadata.description.clone())
+            .capability(text_capability("word_count", "Counts the words in a text"))
+            .capability(text_capability("reverse", "Reverses a text"))
+            .build()
+    }
+}
+
+impl Default for TextToolsPlugin {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Plugin for TextToolsPlugin {
+    fn metadata(&self) -> &PluginMetadata {
+        &self.metadata
+    }
+
+    fn initialize(&self) -> BoxFuture<'_, Result<()>> {
+        Box::pin(async move {
+            tracing::info!("Text tools plugin initialized");
+            Ok(())
+        })
+    }
+
+    fn shutdown(&self) -> BoxFuture<'_, Result<()>> {
+        Box::pin(async move {
+            tracing::info!("Text tools plugin shut down");
+            Ok(())
+        })
+    }
+
+    fn get_state(&self) -> BoxFuture<'_, Result<Option<PluginState>>> {
+        Box::pin(async move { Ok(self.state.read().await.clone()) })
+    }
+
+    fn set_state(&self, state: PluginState) -> BoxFuture<'_, Result<()>> {
+        Box::pin(async move {
+            *self.state.write().await = Some(state);
+            Ok(())
+        })
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn clone_box(&self) -> Box<dyn Plugin> {
+        Box::new(self.clone())
+    }
+}
+
+#[async_trait]
+impl ToolPlugin for TextToolsPlugin {
+    async fn execute_tool(&self, tool: &str, args: Value) -> Result<Value> {
+        let text = args
+            .get("text")
+            .and_then(Value::as_str)
+            .ok_or_else(|| CoreError::Plugin("Missing string parameter 'text'".to_string()))?;
+        match tool {
+            "word_count" => Ok(json!({ "words": text.split_whitespace().count() })),
+            "reverse" => Ok(json!({ "text": text.chars().rev().collect::<String>() })),
+            _ => Err(CoreError::Plugin(format!("Unknown tool: {tool}"))),
+        }
+    }
+
+    fn get_tool_config(&self, tool: &str) -> Option<Value> {
+        Self::TOOLS
+            .contains(&tool)
+            .then(|| json!({ "parameters": ["text"] }))
+    }
+
+    fn list_tools(&self) -> Vec<String> {
+        Self::TOOLS.iter().map(ToString::to_string).collect()
+    }
+}
+
+/// Executes MCP tool calls with a tool plugin
+#[derive(Debug)]
+pub struct PluginToolExecutor {
+    /// MCP tool ID
+    tool_id: String,
+    /// The plugin executing the tools
+    plugin: Arc<dyn ToolPlugin>,
+    /// Plugin manager tracking the plugin's lifecycle
+    manager: Arc<PluginManager>,
+}
+
+impl PluginToolExecutor {
+    /// Creates an executor for `plugin`, which must be registered with `manager`
+    #[must_use]
+    pub fn new(
+        tool_id: impl Into<String>,
+        plugin: Arc<dyn ToolPlugin>,
+        manager: Arc<PluginManager>,
+    ) -> Self {
+        Self {
+            tool_id: tool_id.into(),
+            plugin,
+            manager,
+        }
+    }
+}
+
+#[async_trait]
+impl ToolExecutor for PluginToolExecutor {
+    async fn execute(
+        &self,
+        context: ToolContext,
+    ) -> std::result::Result<ToolExecutionResult, ToolError> {
+        let plugin_id = self.plugin.metadata().id;
+        let status = self.manager.get_plugin_status(plugin_id).await;
+        if status != Some(PluginStatus::Active) {
+            return Err(ToolError::ExecutionError(format!(
+                "Plugin {} is not active ({status:?})",
+                self.plugin.metadata().name
+            )));
+        }
+        if !self.plugin.list_tools().contains(&context.capability) {
+            return Err(ToolError::CapabilityNotFound(
+                self.tool_id.clone(),
+                context.capability,
+            ));
+        }
+
+        let args = Value::Object(context.parameters.into_iter().collect());
+        let (status, output, error_message) =
+            match self.plugin.execute_tool(&context.capability, args).await {
+                Ok(output) => (ExecutionStatus::Success, Some(output), None),
+                Err(e) => (ExecutionStatus::Failure, None, Some(e.to_string())),
+            };
+        Ok(ToolExecutionResult {
+            tool_id: context.tool_id,
+            capability: context.capability,
+            request_id: context.request_id,
+            status,
+            output,
+            error_message,
+            execution_time_ms: 0,
+            timestamp: chrono::Utc::now(),
+        })
+    }
+
+    fn get_tool_id(&self) -> String {
+        self.tool_id.clone()
+    }
+
+    fn get_capabilities(&self) -> Vec<String> {
+        self.plugin.list_tools()
+    }
+}
@@ -0,0 +1,523 @@
+//! Boots and checks the full stack
+//!
+//! [`FullStack::start`] brings up, in order:
+//!
+//! 1. monitoring: a metric collector and the dashboard server
+//! 2. the plugin system with the sample [`TextToolsPlugin`]
+//! 3. the MCP tool manager with the built-in and plugin tools
+//! 4. the MCP server, serving `tools/list`, `tools/call` and the built-in
+//!    commands over TCP
+//! 5. the web server
+//!
+//! [`FullStack::smoke_check`] then exercises each of them from the outside.
+
+use std::collections::HashMap;
+use std::fmt;
+use std::net::{SocketAddr, TcpListener};
+use std::sync::Arc;
+use std::time::Duration;
+
+use anyhow::{anyhow, bail, Context as _};
+use serde_json::{json, Value};
+use squirrel_app::plugin::{Plugin, PluginManager};
+use squirrel_cli::mcp::{MCPError, MCPMessage, MCPMessageType, MCPServer};
+use squirrel_commands::{create_command_registry, CommandRegistry};
+use squirrel_mcp::tool::{versioning::parse_version, ToolManager};
+use squirrel_monitoring::dashboard::{server::start_server, Manager};
+use squirrel_monitoring::metrics::{DefaultMetricCollector, Metric, MetricCollector, MetricType};
+use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+use tokio::net::TcpStream;
+use tokio::runtime::Handle;
+use tokio::sync::oneshot;
+use tokio::task::JoinHandle;
+
+use crate::plugin::{PluginToolExecutor, TextToolsPlugin, TEXT_TOOL_ID};
+use crate::tools::SystemToolExecutor;
+
+/// Metric recorded for every tool call served over MCP
+pub const TOOL_EXECUTION_METRIC: &str = "tool_execution_time_ms";
+
+/// How long the smoke check waits for a server to accept connections
+const STARTUP_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Addresses and settings of the stack; port 0 picks a free port
+#[derive(Debug, Clone)]
+pub struct StackConfig {
+    /// Interface all servers bind to
+    pub host: String,
+    /// Web server port
+    pub web_port: u16,
+    /// MCP server port
+    pub mcp_port: u16,
+    /// Monitoring dashboard port
+    pub dashboard_port: u16,
+    /// Web application settings
+    pub web: squirrel_web::config::Config,
+}
+
+impl Default for StackConfig {
+    fn default() -> Self {
+        Self {
+            host: "127.0.0.1".to_string(),
+            web_port: 3000,
+            mcp_port: 8778,
+            dashboard_port: 8765,
+            web: squirrel_web::config::Config::default(),
+        }
+    }
+}
+
+/// Resolves `host:port`, replacing port 0 with a currently free port
+fn resolve_addr(host: &str, port: u16) -> anyhow::Result<SocketAddr> {
+    let addr: SocketAddr = format!("{host}:{port}")
+        .parse()
+        .with_context(|| format!("Invalid address {host}:{port}"))?;
+    if port != 0 {
+        return Ok(addr);
+    }
+    Ok(TcpListener::bind(addr)?.local_addr()?)
+}
+
+/// All subsystems running in this process
+pub struct FullStack {
+    /// Web server address
+    web_addr: SocketAddr,
+    /// MCP server address
+    mcp_addr: SocketAddr,
+    /// Monitoring dashboard address
+    dashboard_addr: SocketAddr,
+    /// Metrics shared by the tools and the dashboard
+    metrics: Arc<dyn MetricCollector>,
+    /// Plugin system
+    plugins: Arc<PluginManager>,
+    /// MCP tools
+    tools: Arc<ToolManager>,
+    /// MCP server, running on its own threads
+    mcp_server: MCPServer,
+    /// Stops the web server
+    web_shutdown: Option<oneshot::Sender<()>>,
+    /// Web server task
+    web_task: JoinHandle<()>,
+    /// Dashboard server task
+    dashboard_task: JoinHandle<()>,
+}
+
+impl fmt::Debug for FullStack {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("FullStack")
+            .field("web_addr", &self.web_addr)
+            .field("mcp_addr", &self.mcp_addr)
+            .field("dashboard_addr", &self.dashboard_addr)
+            .finish_non_exhaustive()
+    }
+}
+
+impl FullStack {
+    /// Starts every subsystem
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if a subsystem fails to start or a port cannot be
+    /// bound.
+    pub async fn start(config: StackConfig) -> anyhow::Result<Self> {
+        let web_addr = resolve_addr(&config.host, config.web_port)?;
+        let mcp_addr = resolve_addr(&config.host, config.mcp_port)?;
+        let dashboard_addr = resolve_addr(&config.host, config.dashboard_port)?;
+
+        // Monitoring
+        let collector = DefaultMetricCollector::new();
+        collector.initialize().await?;
+        let metrics = Arc::new(collector) as Arc<dyn MetricCollector>;
+        let dashboard = Manager::default();
+        *dashboard.metric_collector.write().await = Box::new(Arc::clone(&metrics));
+        let dashboard_task = tokio::spawn(async move {
+            if let Err(e) = start_server(dashboard_addr, Arc::new(dashboard)).await {
+                tracing::error!("Dashboard server failed: {}", e);
+            }
+        });
+        tracing::info!("Monitoring dashboard on http://{}", dashboard_addr);
+
+        // Plugins
+        let plugins = Arc::new(PluginManager::new());
+        let plugin = TextToolsPlugin::new();
+        let plugin_id = plugin.metadata().id;
+        plugins.register_plugin(Box::new(plugin.clone())).await?;
+        plugins.load_plugin(plugin_id).await?;
+        tracing::info!("Loaded plugin {}", plugin.metadata().name);
+
+        // Tools
+        let tools = Arc::new(ToolManager::new());
+        tools
+            .register_tool(SystemToolExecutor::tool_definition(), SystemToolExecutor)
+            .await?;
+        let executor =
+            PluginToolExecutor::new(TEXT_TOOL_ID, Arc::new(plugin.clone()), Arc::clone(&plugins));
+        tools
+            .register_tool(plugin.tool_definition(), executor)
+            .await?;
+
+        // MCP server
+        let registry = create_command_registry()
+            .map_err(|e| anyhow!("Failed to create command registry: {e}"))?
+            .lock()
+            .map_err(|_| anyhow!("Command registry lock poisoned"))?
+            .clone();
+        let mcp_server =
+            start_mcp_server(mcp_addr, registry, Arc::clone(&tools), Arc::clone(&metrics))?;
+        tracing::info!("MCP server on tcp://{}", mcp_addr);
+
+        // Web server
+        let db = squirrel_web::setup_database("sqlite::memory:").await?;
+        let app = squirrel_web::create_app(db, config.web).await;
+        let server = axum::Server::try_bind(&web_addr)?
+            .serve(app.into_make_service_with_connect_info::<std::net::SocketAddr>());
+        let (web_shutdown, shutdown) = oneshot::channel::<()>();
+        let web_task = tokio::spawn(async move {
+            let server = server.with_graceful_shutdown(async {
+                shutdown.await.ok();
+            });
+            if let Err(e) = server.await {
+                tracing::error!("Web server failed: {}", e);
+            }
+        });
+        tracing::info!("Web server on http://{}", web_addr);
+
+        Ok(Self {
+            web_addr,
+            mcp_addr,
+            dashboard_addr,
+            metrics,
+            plugins,
+            tools,
+            mcp_server,
+            web_shutdown: Some(web_shutdown),
+            web_task,
+            dashboard_task,
+        })
+    }
+
+    /// Web server address
+    #[must_use]
+    pub const fn web_addr(&self) -> SocketAddr {
+        self.web_addr
+    }
+
+    /// MCP server address
+    #[must_use]
+    pub const fn mcp_addr(&self) -> SocketAddr {
+        self.mcp_addr
+    }
+
+    /// Monitoring dashboard address
+    #[must_use]
+    pub const fn dashboard_addr(&self) -> SocketAddr {
+        self.dashboard_addr
+    }
+
+    /// MCP tools served by the stack
+    #[must_use]
+    pub fn tools(&self) -> &Arc<ToolManager> {
+        &self.tools
+    }
+
+    /// Calls every subsystem through its public interface
+    ///
+    /// # Errors
+    ///
+    /// Returns an error describing the first check that failed.
+    pub async fn smoke_check(&self) -> anyhow::Result<SmokeReport> {
+        let mut report = SmokeReport::default();
+        let http = reqwest::Client::new();
+
+        let health = http_get(&http, &format!("http://{}/health", self.web_addr)).await?;
+        report.pass(format!("web: GET /health -> {health}"));
+
+        let layouts = http_get(
+            &http,
+            &format!("http://{}/api/layouts", self.dashboard_addr),
+        )
+        .await?;
+        report.pass(format!("monitoring: GET /api/layouts -> {layouts}"));
+
+        let mut mcp = McpClient::connect(self.mcp_addr).await?;
+        let listed = mcp.request("tools/list", json!({})).await?;
+        let tool_count = listed["tools"].as_array().map_or(0, Vec::len);
+        if tool_count < 2 {
+            bail!("mcp: expected the system and plugin tools, got {listed}");
+        }
+        report.pass(format!("mcp: tools/list -> {tool_count} tools"));
+
+        let echoed = mcp
+            .request(
+                "tools/call",
+                json!({"tool": "system", "capability": "echo", "arguments": {"ping": true}}),
+            )
+            .await?;
+        if echoed["output"]["ping"] != json!(true) {
+            bail!("mcp: system/echo returned {echoed}");
+        }
+        report.pass("mcp: tools/call system/echo".to_string());
+
+        let counted = mcp
+            .request(
+                "tools/call",
+                json!({"tool": TEXT_TOOL_ID, "capability": "word_count", "arguments": {"text": "the quick brown fox"}}),
+            )
+            .await?;
+        if counted["output"]["words"] != json!(4) {
+            bail!("plugins: text/word_count returned {counted}");
+        }
+        report.pass(
+            "plugins: tools/call text/word_count served by the text-tools plugin".to_string(),
+        );
+
+        let version = mcp.request("version", json!({"args": []})).await?;
+        report.pass(format!(
+            "commands: version -> {}",
+            version["output"].as_str().unwrap_or_default().trim()
+        ));
+
+        let recorded = self
+            .metrics
+            .collect_metrics()
+            .await?
+            .iter()
+            .filter(|metric| metric.name == TOOL_EXECUTION_METRIC)
+            .count();
+        if recorded < 2 {
+            bail!("monitoring: expected tool execution metrics, found {recorded}");
+        }
+        report.pass(format!(
+            "monitoring: {recorded} {TOOL_EXECUTION_METRIC} samples recorded"
+        ));
+
+        Ok(report)
+    }
+
+    /// Stops every subsystem
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the plugins fail to shut down.
+    pub async fn shutdown(mut self) -> anyhow::Result<()> {
+        self.mcp_server.stop();
+        if let Some(shutdown) = self.web_shutdown.take() {
+            shutdown.send(()).ok();
+        }
+        (&mut self.web_task).await.ok();
+        self.dashboard_task.abort();
+        self.plugins.shutdown().await?;
+        self.metrics.stop().await?;
+        Ok(())
+    }
+}
+
+/// Starts the MCP server, serving tools next to the registry's commands
+fn start_mcp_server(
+    addr: SocketAddr,
+    registry: CommandRegistry,
+    tools: Arc<ToolManager>,
+    metrics: Arc<dyn MetricCollector>,
+) -> anyhow::Result<MCPServer> {
+    // The server handles clients on plain threads, so handlers enter the
+    // runtime to reach the async tool manager
+    let runtime = Handle::current();
+    let mut server = MCPServer::new(addr.ip().to_string(), addr.port(), registry);
+
+    let list_runtime = runtime.clone();
+    let list_tools = Arc::clone(&tools);
+    server.register_handler("tools/list", move |message| {
+        let tools = list_runtime.block_on(list_tools.get_all_tools());
+        Ok(MCPMessage::new_response(
+            message.id.clone(),
+            message.command.clone(),
+            Some(json!({ "tools": tools })),
+        ))
+    });
+
+    server.register_handler("tools/call", move |message| {
+        let payload = message.payload.clone().unwrap_or_default();
+        let field = |name: &str| {
+            payload
+                .get(name)
+                .and_then(Value::as_str)
+                .ok_or_else(|| MCPError::ProtocolError(format!("Missing string field '{name}'")))
+        };
+        let (tool, capability) = (field("tool")?, field("capability")?);
+        let arguments = payload
+            .get("arguments")
+            .cloned()
+            .unwrap_or_else(|| json!({}));
+        // A top-level version plays the role of the version header
+        let version = payload
+            .get("version")
+            .map(parse_version)
+            .transpose()
+            .map_err(|e| MCPError::ProtocolError(e.to_string()))?;
+
+        let result = runtime
+            .block_on(async {
+                let result = tools
+                    .execute_tool_version(
+                        tool,
+                        capability,
+                        version,
+                        arguments,
+                        Some(message.id.clone()),
+                    )
+                    .await?;
+                let labels = HashMap::from([
+                    ("tool".to_string(), result.tool_id.clone()),
+                    ("capability".to_string(), result.capability.clone()),
+                    ("status".to_string(), format!("{:?}", result.status)),
+                ]);
+                let metric = Metric::new(
+                    TOOL_EXECUTION_METRIC,
+                    result.execution_time_ms as f64,
+                    MetricType::Gauge,
+                    labels,
+                );
+                if let Err(e) = metrics.record_metric(metric).await {
+                    tracing::warn!("Failed to record tool metric: {}", e);
+                }
+                Ok::<_, squirrel_mcp::tool::ToolError>(result)
+            })
+            .map_err(|e| MCPError::CommandError(e.to_string()))?;
+        if let Some(error) = &result.error_message {
+            return Err(MCPError::CommandError(error.clone()));
+        }
+        Ok(MCPMessage::new_response(
+            message.id.clone(),
+            message.command.clone(),
+            Some(serde_json::to_value(&result)?),
+        ))
+    });
+
+    server.start()?;
+    Ok(server)
+}
+
+/// GETs `url`, retrying while the server is still starting
+async fn http_get(client: &reqwest::Client, url: &str) -> anyhow::Result<reqwest::StatusCode> {
+    let deadline = tokio::time::Instant::now() + STARTUP_TIMEOUT;
+    loop {
+        match client.get(url).send().await {
+            Ok(response) if response.status().is_success() => return Ok(response.status()),
+            Ok(response) => bail!("GET {url} returned {}", response.status()),
+            Err(e) if e.is_connect() && tokio::time::Instant::now() < deadline => {
+                tokio::time::sleep(Duration::from_millis(100)).await;
+            }
+            Err(e) => return Err(e).with_context(|| format!("GET {url}")),
+        }
+    }
+}
+
+/// Line-delimited JSON client of the MCP server
+struct McpClient {
+    /// Reads responses
+    reader: BufReader<tokio::net::tcp::OwnedReadHalf>,
+    /// Writes requests
+    writer: tokio::net::tcp::OwnedWriteHalf,
+    /// Last request ID
+    next_id: u64,
+}
+
+impl McpClient {
+    /// Connects, retrying while the server is still starting
+    async fn connect(addr: SocketAddr) -> anyhow::Result<Self> {
+        let deadline = tokio::time::Instant::now() + STARTUP_TIMEOUT;
+        let stream = loop {
+            match TcpStream::connect(addr).await {
+                Ok(stream) => break stream,
+                Err(_) if tokio::time::Instant::now() < deadline => {
+                    tokio::time::sleep(Duration::from_millis(100)).await;
+                }
+                Err(e) => {
+                    return Err(e).with_context(|| format!("Connecting to MCP server {addr}"))
+                }
+            }
+        };
+        let (reader, writer) = stream.into_split();
+        Ok(Self {
+            reader: BufReader::new(reader),
+            writer,
+            next_id: 0,
+        })
+    }
+
+    /// Sends a request and returns the payload of its response
+    async fn request(&mut self, command: &str, payload: Value) -> anyhow::Result<Value> {
+        self.next_id += 1;
+        let request =
+            MCPMessage::new_request(self.next_id.to_string(), command.to_string(), Some(payload));
+        self.writer
+            .write_all(format!("{}\n", request.to_json()?).as_bytes())
+            .await?;
+
+        let mut line = String::new();
+        if self.reader.read_line(&mut line).await? == 0 {
+            bail!("MCP server closed the connection");
+        }
+        let response = MCPMessage::from_json(&line)?;
+        match response.message_type {
+            MCPMessageType::Response => Ok(response.payload.unwrap_or_default()),
+            _ => bail!(
+                "{command} failed: {}",
+                response
+                    .error
+                    .unwrap_or_else(|| "unknown error".to_string())
+            ),
+        }
+    }
+}
+
+/// Checks passed by [`FullStack::smoke_check`]
+#[derive(Debug, Clone, Default)]
+pub struct SmokeReport {
+    /// Description of each passed check
+    pub checks: Vec<String>,
+}
+
+impl SmokeReport {
+    /// Records a passed check
+    fn pass(&mut self, check: String) {
+        tracing::info!("ok: {}", check);
+        self.checks.push(check);
+    }
+}
+
+impl fmt::Display for SmokeReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for check in &self.checks {
+            writeln!(f, "ok  {check}")?;
+        }
+        write!(f, "{} checks passed", self.checks.len())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use squirrel_monitoring::alerts::LifecycleConfig;
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_full_stack_smoke_check() {
+        let config = StackConfig {
+            web_port: 0,
+            mcp_port: 0,
+            dashboard_port: 0,
+            web: squirrel_web::config::Config {
+                alerts: LifecycleConfig::default(),
+                ..squirrel_web::config::Config::default()
+            },
+            ..StackConfig::default()
+        };
+        let stack = FullStack::start(config).await.unwrap();
+
+        let report = stack.smoke_check().await.unwrap();
+        assert_eq!(report.checks.len(), 7);
+        assert_eq!(stack.tools().get_all_tools().await.len(), 2);
+
+        stack.shutdown().await.unwrap();
+    }
+}
@@ -0,0 +1,74 @@
+//! Built-in sample tools
+//!
+//! Tools implemented directly against the MCP [`ToolExecutor`] trait, next
+//! to the ones the sample plugin contributes.
+
+use async_trait::async_trait;
+use serde_json::{json, Value};
+use squirrel_mcp::tool::{
+    Capability, ExecutionStatus, Tool, ToolContext, ToolError, ToolExecutionResult, ToolExecutor,
+};
+
+/// Tool ID of the built-in tools
+pub const SYSTEM_TOOL_ID: &str = "system";
+
+/// Executor of the `echo` and `time` capabilities
+#[derive(Debug, Default, Clone, Copy)]
+pub struct SystemToolExecutor;
+
+impl SystemToolExecutor {
+    /// MCP tool definition of the built-in tools
+    #[must_use]
+    pub fn tool_definition() -> Tool {
+        let capability = |name: &str, description: &str| Capability {
+            name: name.to_string(),
+            description: description.to_string(),
+            parameters: Vec::new(),
+            return_type: None,
+            version: 1,
+            deprecation: None,
+        };
+        Tool::builder()
+            .id(SYSTEM_TOOL_ID)
+            .name("System tools")
+            .version(env!("CARGO_PKG_VERSION"))
+            .description("Built-in sample tools")
+            .capability(capability("echo", "Returns its parameters"))
+            .capability(capability("time", "Returns the current UTC time"))
+            .build()
+    }
+}
+
+#[async_trait]
+impl ToolExecutor for SystemToolExecutor {
+    async fn execute(&self, context: ToolContext) -> Result<ToolExecutionResult, ToolError> {
+        let output = match context.capability.as_str() {
+            "echo" => Value::Object(context.parameters.into_iter().collect()),
+            "time" => json!({ "utc": chrono::Utc::now().to_rfc3339() }),
+            _ => {
+                return Err(ToolError::CapabilityNotFound(
+                    context.tool_id,
+                    context.capability,
+                ))
+            }
+        };
+        Ok(ToolExecutionResult {
+            tool_id: context.tool_id,
+            capability: context.capability,
+            request_id: context.request_id,
+            status: ExecutionStatus::Success,
+            output: Some(output),
+            error_message: None,
+            execution_time_ms: 0,
+            timestamp: chrono::Utc::now(),
+        })
+    }
+
+    fn get_tool_id(&self) -> String {
+        SYSTEM_TOOL_ID.to_string()
+    }
+
+    fn get_capabilities(&self) -> Vec<String> {
+        vec!["echo".to_string(), "time".to_string()]
+    }
+}